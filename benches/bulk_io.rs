@@ -0,0 +1,73 @@
+//! Bulk file I/O: std backend vs io_uring.
+//!
+//! Writes and reads back batches of small files, the shape of extracting a
+//! large source tree. Run with the io_uring backend enabled to compare:
+//!
+//! ```bash
+//! cargo bench --bench bulk_io --features io-uring
+//! ```
+//!
+//! Without the feature (or off Linux) both series use `std::fs`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use embeddenator::bulk_io::{read_files, BulkFileWriter};
+use std::path::PathBuf;
+
+const FILE_COUNTS: [usize; 2] = [1_000, 10_000];
+const FILE_SIZE: usize = 4 * 1024;
+
+fn payload(i: usize) -> Vec<u8> {
+    (0..FILE_SIZE).map(|j| (i.wrapping_mul(31) ^ j) as u8).collect()
+}
+
+fn bench_bulk_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bulk_write");
+    group.sample_size(10);
+    for &count in &FILE_COUNTS {
+        group.throughput(Throughput::Bytes((count * FILE_SIZE) as u64));
+        let dir = tempfile::tempdir().expect("tempdir");
+        let paths: Vec<PathBuf> = (0..count).map(|i| dir.path().join(format!("f{i:06}.bin"))).collect();
+        let data: Vec<Vec<u8>> = (0..count).map(payload).collect();
+
+        for (name, make) in [
+            ("std", BulkFileWriter::std as fn() -> BulkFileWriter),
+            ("auto", BulkFileWriter::new as fn() -> BulkFileWriter),
+        ] {
+            let label = format!("{name}:{:?}", make().backend());
+            group.bench_with_input(BenchmarkId::new(label, count), &count, |b, _| {
+                b.iter(|| {
+                    let mut writer = make();
+                    for (path, bytes) in paths.iter().zip(&data) {
+                        writer.write_file(path.clone(), bytes.clone()).unwrap();
+                    }
+                    writer.finish().unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_bulk_read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bulk_read");
+    group.sample_size(10);
+    for &count in &FILE_COUNTS {
+        group.throughput(Throughput::Bytes((count * FILE_SIZE) as u64));
+        let dir = tempfile::tempdir().expect("tempdir");
+        let paths: Vec<PathBuf> = (0..count).map(|i| dir.path().join(format!("f{i:06}.bin"))).collect();
+        for (i, path) in paths.iter().enumerate() {
+            std::fs::write(path, payload(i)).unwrap();
+        }
+
+        group.bench_with_input(BenchmarkId::new("std", count), &count, |b, _| {
+            b.iter(|| paths.iter().map(|p| std::fs::read(p).unwrap()).collect::<Vec<_>>())
+        });
+        group.bench_with_input(BenchmarkId::new("bulk", count), &count, |b, _| {
+            b.iter(|| read_files(&paths).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_bulk_write, bench_bulk_read);
+criterion_main!(benches);
@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use embeddenator::{BitslicedTritVec, CarrySaveBundle, PackedTritVec, ReversibleVSAConfig, SparseVec, DIM};
+use embeddenator::{BitslicedTritVec, CarrySaveBundle, Codebook, PackedTritVec, ReversibleVSAConfig, SparseVec, DIM};
 
 fn bench_sparsevec_ops(c: &mut Criterion) {
     let mut group = c.benchmark_group("sparsevec_ops");
@@ -388,6 +388,26 @@ fn bench_carry_save_bundle(c: &mut Criterion) {
     }
 }
 
+fn bench_codebook_projection(c: &mut Criterion) {
+    let mut codebook = Codebook::new(DIM);
+    codebook.initialize_standard_basis();
+    let inputs: Vec<Vec<u8>> = (0..32u8)
+        .map(|i| (0..4096u32).map(|j| (j as u8).wrapping_mul(31) ^ i).collect())
+        .collect();
+
+    let mut group = c.benchmark_group("codebook_projection_32x4k");
+    group.bench_function("project_each", |bencher| {
+        bencher.iter(|| {
+            let results: Vec<_> = black_box(&inputs).iter().map(|d| codebook.project(d)).collect();
+            black_box(results)
+        })
+    });
+    group.bench_function("project_batch", |bencher| {
+        bencher.iter(|| black_box(codebook.project_batch(black_box(&inputs))))
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_sparsevec_ops,
@@ -395,6 +415,7 @@ criterion_group!(
     bench_reversible_encode_decode,
     bench_packed_path,
     bench_bitsliced_vs_packed,
-    bench_carry_save_bundle
+    bench_carry_save_bundle,
+    bench_codebook_projection
 );
 criterion_main!(benches);
@@ -0,0 +1,134 @@
+/* C interface to embeddenator. Generated by cbindgen from src/interop/ffi.rs; do not edit. */
+
+#ifndef EMBEDDENATOR_H
+#define EMBEDDENATOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Result of a fallible call.
+typedef enum EmbStatus {
+  EMB_STATUS_OK = 0,
+  // A required pointer argument was null.
+  EMB_STATUS_NULL_ARGUMENT = 1,
+  // A string argument was not valid UTF-8.
+  EMB_STATUS_INVALID_UTF8 = 2,
+  // The named file, engram or manifest does not exist.
+  EMB_STATUS_NOT_FOUND = 3,
+  // Malformed input, or data that failed verification.
+  EMB_STATUS_INVALID_DATA = 4,
+  // Any other I/O failure.
+  EMB_STATUS_IO = 5,
+  // The library panicked; the handle involved should be freed.
+  EMB_STATUS_PANIC = 6,
+} EmbStatus;
+
+// An engram and its manifest.
+typedef struct EmbEngram EmbEngram;
+
+// A sparse ternary vector.
+typedef struct EmbVec EmbVec;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Library version as a static NUL-terminated string.
+const char *emb_version(void);
+
+// Message for the last failed call on this thread, or null if none.
+//
+// The string stays valid until the next failing call on the same thread.
+const char *emb_last_error(void);
+
+// New empty engram. Never null.
+struct EmbEngram *emb_engram_new(void);
+
+// Release an engram. Null is ignored.
+void emb_engram_free(struct EmbEngram *engram);
+
+// Load an engram and manifest from disk into a new handle stored in `*out`.
+enum EmbStatus emb_engram_load(const char *engram_path,
+                               const char *manifest_path,
+                               struct EmbEngram **out);
+
+// Write the engram and manifest to disk.
+enum EmbStatus emb_engram_save(const struct EmbEngram *engram,
+                               const char *engram_path,
+                               const char *manifest_path);
+
+// Ingest every file under `dir`, stored relative to it.
+enum EmbStatus emb_engram_ingest_dir(struct EmbEngram *engram, const char *dir);
+
+// Ingest `len` bytes at `data` as the file `logical_path`.
+enum EmbStatus emb_engram_ingest_bytes(struct EmbEngram *engram,
+                                       const char *logical_path,
+                                       const uint8_t *data,
+                                       size_t len);
+
+// Reconstruct every file under `out_dir`.
+enum EmbStatus emb_engram_extract(const struct EmbEngram *engram, const char *out_dir);
+
+// Number of files in the manifest; 0 for a null handle.
+size_t emb_engram_file_count(const struct EmbEngram *engram);
+
+// Number of chunk vectors in the codebook; 0 for a null handle.
+size_t emb_engram_chunk_count(const struct EmbEngram *engram);
+
+// Reconstruct one file into a new buffer stored in `*out_data`/`*out_len`.
+//
+// Release the buffer with `emb_bytes_free`. A checksum mismatch is
+// reported as `InvalidData` and no buffer is returned.
+enum EmbStatus emb_engram_read_file(const struct EmbEngram *engram,
+                                    const char *path,
+                                    uint8_t **out_data,
+                                    size_t *out_len);
+
+// Release a buffer returned by `emb_engram_read_file`. Null is ignored.
+void emb_bytes_free(uint8_t *data, size_t len);
+
+// Find the chunks most similar to `data`.
+//
+// Writes up to `k` chunk ids and cosine scores, best first, into the
+// caller's `out_ids` and `out_scores` arrays (each at least `k` long) and
+// the number written into `*out_count`.
+enum EmbStatus emb_engram_query(const struct EmbEngram *engram,
+                                const uint8_t *data,
+                                size_t len,
+                                size_t k,
+                                uint64_t *out_ids,
+                                double *out_scores,
+                                size_t *out_count);
+
+// Copy of the engram's root (superposition) vector, or null for a null handle.
+struct EmbVec *emb_engram_root(const struct EmbEngram *engram);
+
+// Encode `len` bytes at `data` into a vector, or null on a bad argument.
+struct EmbVec *emb_vec_encode(const uint8_t *data, size_t len);
+
+// New random vector. Never null.
+struct EmbVec *emb_vec_random(void);
+
+// Release a vector. Null is ignored.
+void emb_vec_free(struct EmbVec *vec);
+
+// Superposition of `a` and `b` as a new vector, or null on a bad argument.
+struct EmbVec *emb_vec_bundle(const struct EmbVec *a, const struct EmbVec *b);
+
+// Binding of `a` and `b` as a new vector, or null on a bad argument.
+struct EmbVec *emb_vec_bind(const struct EmbVec *a, const struct EmbVec *b);
+
+// `a` cyclically shifted by `shift` as a new vector, or null on a bad argument.
+struct EmbVec *emb_vec_permute(const struct EmbVec *a, size_t shift);
+
+// Cosine similarity of `a` and `b`; NaN if either is null.
+double emb_vec_cosine(const struct EmbVec *a, const struct EmbVec *b);
+
+// Number of non-zero trits; 0 for a null handle.
+size_t emb_vec_nnz(const struct EmbVec *vec);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* EMBEDDENATOR_H */
@@ -16,6 +16,7 @@ enum CodecArg {
 	None,
 	Zstd,
 	Lz4,
+	ZstdDict,
 }
 
 impl From<CodecArg> for CompressionCodec {
@@ -24,6 +25,7 @@ impl From<CodecArg> for CompressionCodec {
 			CodecArg::None => CompressionCodec::None,
 			CodecArg::Zstd => CompressionCodec::Zstd,
 			CodecArg::Lz4 => CompressionCodec::Lz4,
+			CodecArg::ZstdDict => CompressionCodec::ZstdDict,
 		}
 	}
 }
@@ -220,6 +222,7 @@ fn main() -> io::Result<()> {
 	let opts = BinaryWriteOptions {
 		codec: args.engram_codec.into(),
 		level: args.engram_level,
+		..Default::default()
 	};
 	let wrapped = wrap_or_legacy(PayloadKind::EngramBincode, opts, &engram_bincode)?;
 
@@ -0,0 +1,293 @@
+//! `codebook`: managing a shared codebook.
+
+use super::{build_keyring, print_json, read_key_file, CompressionArg, KeyArgs};
+use crate::embrfs::EmbrFS;
+use crate::codebook::Codebook;
+use crate::envelope::{
+    BinaryWriteOptions, EncryptionCodec, EncryptionKey,
+};
+use crate::shared_codebook;
+use clap::{Args, Subcommand};
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Actions of `embeddenator codebook`.
+#[derive(Subcommand)]
+pub enum CodebookCommand {
+    /// Remove records that no engram references
+    Gc {
+        /// Shared codebook file
+        #[arg(short, long, value_name = "FILE")]
+        codebook: PathBuf,
+
+        /// Every engram still using the codebook; reference counts are rebuilt from
+        /// these and other registered engrams are forgotten (default: trust the counts)
+        #[arg(value_name = "ENGRAM")]
+        engrams: Vec<PathBuf>,
+
+        /// Report what would be removed without rewriting the codebook
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Compression for the rewritten codebook
+        #[arg(long, default_value = "none", value_enum)]
+        compression: CompressionArg,
+
+        /// Encrypt the rewritten codebook with this 32-byte hex key file
+        #[arg(long, value_name = "FILE")]
+        encrypt_key: Option<PathBuf>,
+
+        /// Key id recorded in the envelope header
+        #[arg(long, default_value_t = 1, value_name = "ID", requires = "encrypt_key")]
+        key_id: u32,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// Describe a codebook artifact (as written by `ingest --basis`)
+    Show {
+        /// Codebook file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Fail unless the codebook has this id
+        #[arg(long, value_name = "ID")]
+        pin: Option<String>,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// Compare two codebook artifacts
+    Diff {
+        /// Old codebook file
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// New codebook file
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// Group an engram's chunks by vector similarity
+    Cluster {
+        /// Engram whose chunks are clustered
+        #[arg(short, long, value_name = "FILE")]
+        engram: PathBuf,
+
+        /// Cosine a chunk needs with a cluster's representative to join it
+        #[arg(long, default_value_t = 0.9, value_name = "COSINE")]
+        threshold: f64,
+
+        /// Clusters to list, largest first (0 lists all)
+        #[arg(long, default_value_t = 10, value_name = "N")]
+        top: usize,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// Drop an engram's references before deleting it
+    Release {
+        /// Engram written with `ingest --codebook`
+        #[arg(short, long, value_name = "FILE")]
+        engram: PathBuf,
+
+        /// Compression for the rewritten codebook
+        #[arg(long, default_value = "none", value_enum)]
+        compression: CompressionArg,
+
+        /// Encrypt the rewritten codebook with this 32-byte hex key file
+        #[arg(long, value_name = "FILE")]
+        encrypt_key: Option<PathBuf>,
+
+        /// Key id recorded in the envelope header
+        #[arg(long, default_value_t = 1, value_name = "ID", requires = "encrypt_key")]
+        key_id: u32,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+}
+#[derive(Args)]
+pub struct CodebookArgs {
+    #[command(subcommand)]
+    pub action: CodebookCommand,
+}
+
+impl CodebookArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let CodebookArgs { action } = self;
+        let write_options = |compression: CompressionArg, encrypt_key: Option<&Path>, key_id: u32| {
+            let key = encrypt_key
+                .map(|path| EncryptionKey::from_hex(key_id, &read_key_file(path)?))
+                .transpose()?;
+            io::Result::Ok(BinaryWriteOptions {
+                codec: compression.into(),
+                encryption: if key.is_some() {
+                    EncryptionCodec::XChaCha20Poly1305
+                } else {
+                    EncryptionCodec::None
+                },
+                key,
+                ..Default::default()
+            })
+        };
+        match action {
+            CodebookCommand::Gc {
+                codebook,
+                engrams,
+                dry_run,
+                compression,
+                encrypt_key,
+                key_id,
+                keys: KeyArgs { keys },
+            } => {
+                let opts = write_options(compression, encrypt_key.as_deref(), key_id)?;
+                let live = (!engrams.is_empty()).then_some(&engrams[..]);
+                let report = shared_codebook::gc(&codebook, live, &build_keyring(&keys)?, opts, dry_run)?;
+                if json_output {
+                    print_json(&report)?;
+                } else {
+                    println!(
+                        "{}{} of {} records unreferenced; {} of {} engrams registered",
+                        if dry_run { "(dry run) " } else { "" },
+                        report.records_removed,
+                        report.records_before,
+                        report.engrams_after,
+                        report.engrams_before
+                    );
+                }
+            }
+            CodebookCommand::Show { file, pin, keys: KeyArgs { keys } } => {
+                let keyring = build_keyring(&keys)?;
+                let codebook = match &pin {
+                    Some(id) => Codebook::load_pinned(&file, &keyring, id)?,
+                    None => Codebook::load_with_keys(&file, &keyring)?,
+                };
+                let summary = serde_json::json!({
+                    "id": codebook.id(),
+                    "version": codebook.version,
+                    "dimensionality": codebook.dimensionality,
+                    "basis_vectors": codebook.basis_vectors.len(),
+                    "semantic_markers": codebook.semantic_markers.len(),
+                    "salted": codebook.salt.is_some(),
+                    "outliers": codebook.outliers,
+                });
+                if json_output {
+                    print_json(&summary)?;
+                } else {
+                    println!("Codebook {}", codebook.id());
+                    println!("  Version: {}", codebook.version);
+                    println!("  Dimensionality: {}", codebook.dimensionality);
+                    println!("  Basis vectors: {}", codebook.basis_vectors.len());
+                    println!("  Semantic markers: {}", codebook.semantic_markers.len());
+                    println!("  Salted: {}", if codebook.salt.is_some() { "yes" } else { "no" });
+                    println!(
+                        "  Outliers: {}-byte windows, {:?}",
+                        codebook.outliers.window, codebook.outliers.rules
+                    );
+                }
+            }
+            CodebookCommand::Diff { old, new, keys: KeyArgs { keys } } => {
+                let keyring = build_keyring(&keys)?;
+                let diff = Codebook::load_with_keys(&old, &keyring)?
+                    .diff(&Codebook::load_with_keys(&new, &keyring)?);
+                if json_output {
+                    print_json(&diff)?;
+                } else if diff.is_empty() {
+                    println!("Codebooks are identical ({})", diff.from);
+                } else {
+                    println!("{} -> {}", diff.from, diff.to);
+                    if let Some((a, b)) = diff.version {
+                        println!("  version: {} -> {}", a, b);
+                    }
+                    if let Some((a, b)) = diff.dimensionality {
+                        println!("  dimensionality: {} -> {}", a, b);
+                    }
+                    for (label, ids) in [
+                        ("added", &diff.basis_added),
+                        ("removed", &diff.basis_removed),
+                        ("changed", &diff.basis_changed),
+                        ("reweighted", &diff.basis_reweighted),
+                    ] {
+                        if !ids.is_empty() {
+                            println!("  basis {}: {:?}", label, ids);
+                        }
+                    }
+                    for (label, changed) in [
+                        ("semantic markers", diff.semantic_markers_changed),
+                        ("salt", diff.salt_changed),
+                        ("outlier rules", diff.outliers_changed),
+                    ] {
+                        if changed {
+                            println!("  {} changed", label);
+                        }
+                    }
+                }
+            }
+            CodebookCommand::Cluster {
+                engram,
+                threshold,
+                top,
+                keys: KeyArgs { keys },
+            } => {
+                let engram_data = EmbrFS::load_engram_with_keys(&engram, &build_keyring(&keys)?)?;
+                let mut clusters = Codebook::cluster(&engram_data.codebook, threshold);
+                let total = clusters.len();
+                let singletons = clusters.singletons();
+                let redundant = clusters.redundant_chunks();
+                let sizes = clusters.size_histogram();
+                if top > 0 {
+                    clusters.clusters.truncate(top);
+                }
+                if json_output {
+                    print_json(&serde_json::json!({
+                        "engram": engram,
+                        "threshold": threshold,
+                        "chunks": clusters.chunks,
+                        "clusters": total,
+                        "singletons": singletons,
+                        "redundant_chunks": redundant,
+                        "sizes": sizes,
+                        "largest": clusters.clusters,
+                    }))?;
+                } else {
+                    println!(
+                        "{} chunks in {} clusters at cosine >= {} ({} singletons, {} redundant)",
+                        clusters.chunks, total, threshold, singletons, redundant
+                    );
+                    for cluster in clusters.clusters.iter().filter(|c| c.size() > 1) {
+                        println!(
+                            "  chunk {}: {} chunks, min cosine {:.3}",
+                            cluster.representative,
+                            cluster.size(),
+                            cluster.min_similarity
+                        );
+                    }
+                }
+            }
+            CodebookCommand::Release {
+                engram,
+                compression,
+                encrypt_key,
+                key_id,
+                keys: KeyArgs { keys },
+            } => {
+                let opts = write_options(compression, encrypt_key.as_deref(), key_id)?;
+                shared_codebook::release(&engram, &build_keyring(&keys)?, opts)?;
+                if json_output {
+                    print_json(&serde_json::json!({ "released": engram }))?;
+                } else {
+                    println!("Released {}; run `codebook gc` to drop its unused records", engram.display());
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,487 @@
+//! CLI configuration file and named profiles.
+//!
+//! Settings are read from `$EMBEDDENATOR_CONFIG`, or else
+//! `$XDG_CONFIG_HOME/embeddenator/config.toml` (`~/.config/...` when
+//! `XDG_CONFIG_HOME` is unset). The file holds named profiles:
+//!
+//! ```toml
+//! default_profile = "team"
+//!
+//! [profiles.team]
+//! engram_compression = "zstd"
+//! engram_checksum = "blake3"
+//! encrypt_key = "~/.config/embeddenator/team.key"
+//! key_id = 2
+//! keys = ["1=~/.config/embeddenator/old.key", "2=~/.config/embeddenator/team.key"]
+//! chunk_size = 16384
+//!
+//! log_level = "info"
+//! log_format = "json"
+//!
+//! [profiles.team.vector_sync]
+//! backend = "qdrant"
+//! url = "http://qdrant.internal:6333"
+//! collection = "docs"
+//! ```
+//!
+//! The profile is picked by `--profile`, then `$EMBEDDENATOR_PROFILE`, then
+//! `default_profile`. Each setting can also be given as an environment
+//! variable named after it (`EMBEDDENATOR_ENGRAM_COMPRESSION`,
+//! `EMBEDDENATOR_VECTOR_SYNC_URL`, `EMBEDDENATOR_KEYS` as a comma-separated
+//! list). A flag on the command line wins over the environment, which wins
+//! over the profile.
+//!
+//! `chunk_size` and `similarity_chunks` only shape new engrams: ingest
+//! records them in the manifest's encoding, and readers follow the manifest
+//! rather than the profile. The vector dimension is fixed at
+//! [`DIM`](crate::vsa::DIM) and is not a setting.
+
+use super::codebooks::CodebookCommand;
+use super::ingest::IngestArgs;
+#[cfg(feature = "vector-sync")]
+use super::interop::SyncVectorsArgs;
+use super::{parse_key_arg, ChecksumArg, Commands, CompressionArg};
+use crate::logging::{LogConfig, LogFormat};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Settings a profile can hold, by TOML key. Dotted keys live in a table.
+pub const SETTINGS: &[&str] = &[
+    "engram_compression",
+    "engram_compression_level",
+    "engram_checksum",
+    "part_size",
+    "shard_chunks",
+    "sign_key",
+    "encrypt_key",
+    "key_id",
+    "keys",
+    "max_engram_bytes",
+    "max_file_size",
+    "max_chunks",
+    "chunk_size",
+    "similarity_chunks",
+    "log_level",
+    "log_format",
+    "vector_sync.backend",
+    "vector_sync.url",
+    "vector_sync.collection",
+    "vector_sync.token",
+];
+
+/// Defaults for CLI flags, from a profile with environment overrides.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// `ingest --engram-compression`: `none`, `zstd`, `lz4` or `zstd-dict`.
+    pub engram_compression: Option<String>,
+    pub engram_compression_level: Option<i32>,
+    /// `ingest --engram-checksum`: `none`, `blake3` or `xxh3`.
+    pub engram_checksum: Option<String>,
+    pub part_size: Option<u64>,
+    pub shard_chunks: Option<u64>,
+    pub sign_key: Option<PathBuf>,
+    pub encrypt_key: Option<PathBuf>,
+    pub key_id: Option<u32>,
+    /// Decryption keys as `ID=FILE`, used by every command taking `--key`.
+    pub keys: Vec<String>,
+    pub max_engram_bytes: Option<u64>,
+    pub max_file_size: Option<u64>,
+    pub max_chunks: Option<usize>,
+    /// `ingest --chunk-size`.
+    pub chunk_size: Option<usize>,
+    /// `ingest --similarity-chunks`.
+    pub similarity_chunks: Option<bool>,
+    /// Filter of the structured logs, e.g. `info` or `embeddenator=debug`;
+    /// `EMBEDDENATOR_LOG` wins over it. See [`crate::logging`].
+    pub log_level: Option<String>,
+    /// `text` or `json`.
+    pub log_format: Option<String>,
+    pub vector_sync: VectorSyncProfile,
+}
+
+/// Target of `sync-vectors`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VectorSyncProfile {
+    /// `qdrant` or `milvus`.
+    pub backend: Option<String>,
+    pub url: Option<String>,
+    pub collection: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    profiles: BTreeMap<String, toml::Table>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Path of the configuration file; `None` when no location can be derived.
+pub fn config_path(env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    if let Some(path) = env("EMBEDDENATOR_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = env("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("embeddenator").join("config.toml"))
+}
+
+/// Environment variable overriding `setting`.
+pub fn env_var_name(setting: &str) -> String {
+    format!("EMBEDDENATOR_{}", setting.replace('.', "_").to_uppercase())
+}
+
+impl Profile {
+    /// Load the profile selected by `name` (the `--profile` flag) from the
+    /// configuration file and apply environment overrides. `env` looks up
+    /// environment variables.
+    ///
+    /// A missing file at the default location is not an error; a file named
+    /// by `$EMBEDDENATOR_CONFIG` or a profile named explicitly must exist.
+    pub fn load(name: Option<&str>, env: impl Fn(&str) -> Option<String>) -> io::Result<Self> {
+        let path = config_path(&env);
+        let text = match &path {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(text) => Some(text),
+                Err(e)
+                    if e.kind() == io::ErrorKind::NotFound
+                        && env("EMBEDDENATOR_CONFIG").is_none() =>
+                {
+                    None
+                }
+                Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {e}", path.display()))),
+            },
+            None => None,
+        };
+        let source = path.as_deref().unwrap_or(Path::new("config.toml"));
+        Self::from_config(text.as_deref(), source, name, env)
+    }
+
+    /// Like [`Profile::load`], with the configuration file contents given
+    /// (`None` for no file). `source` names the file in error messages.
+    pub fn from_config(
+        text: Option<&str>,
+        source: &Path,
+        name: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> io::Result<Self> {
+        let file: ConfigFile = match text {
+            Some(text) => {
+                toml::from_str(text).map_err(|e| invalid(format!("{}: {e}", source.display())))?
+            }
+            None => ConfigFile::default(),
+        };
+        let explicit = name
+            .map(str::to_string)
+            .or_else(|| env("EMBEDDENATOR_PROFILE"));
+        let mut table = match explicit.or(file.default_profile) {
+            Some(name) => file.profiles.get(&name).cloned().ok_or_else(|| {
+                invalid(format!("no profile named {name:?} in {}", source.display()))
+            })?,
+            None => toml::Table::new(),
+        };
+        for setting in SETTINGS {
+            if let Some(value) = env(&env_var_name(setting)) {
+                set(&mut table, setting, env_value(setting, &value));
+            }
+        }
+        // The table mixes the file and the environment, so errors name the
+        // setting rather than the file.
+        Profile::deserialize(table).map_err(|e| {
+            invalid(format!("configuration: {}", e.to_string().trim_end().replace('\n', " ")))
+        })
+    }
+
+    /// How the structured logs of this run are filtered and printed.
+    pub fn log_config(&self) -> io::Result<LogConfig> {
+        let format = match &self.log_format {
+            Some(format) => format.parse().map_err(|e| invalid(format!("log_format: {e}")))?,
+            None => LogFormat::default(),
+        };
+        Ok(LogConfig {
+            level: self.log_level.clone(),
+            format,
+        })
+    }
+
+    /// Fill the flags of `command` that were not given on the command line.
+    /// `matches` are the matches of the whole command line.
+    pub fn apply(&self, command: &mut Commands, matches: &ArgMatches) -> io::Result<()> {
+        let Some((_, sub)) = matches.subcommand() else {
+            return Ok(());
+        };
+        let defaulted = |id: &str| sub.value_source(id) != Some(ValueSource::CommandLine);
+
+        if let Some(keys) = keys_mut(command) {
+            if keys.is_empty() {
+                *keys = self
+                    .keys
+                    .iter()
+                    .map(|k| {
+                        parse_key_arg(&expand_home(k)).map_err(|e| invalid(format!("keys: {e}")))
+                    })
+                    .collect::<io::Result<_>>()?;
+            }
+        }
+
+        match command {
+            Commands::Ingest(IngestArgs {
+                engram_compression,
+                engram_compression_level,
+                engram_checksum,
+                part_size,
+                shard_chunks,
+                sign_key,
+                encrypt_key,
+                key_id,
+                max_engram_bytes,
+                max_file_size,
+                max_chunks,
+                chunk_size,
+                similarity_chunks,
+                ..
+            }) => {
+                let compression = self
+                    .engram_compression
+                    .as_deref()
+                    .map(|v| parse_enum::<CompressionArg>("engram_compression", v))
+                    .transpose()?;
+                let checksum = self
+                    .engram_checksum
+                    .as_deref()
+                    .map(|v| parse_enum::<ChecksumArg>("engram_checksum", v))
+                    .transpose()?;
+                fill(
+                    engram_compression,
+                    compression,
+                    defaulted("engram_compression"),
+                );
+                fill(
+                    engram_compression_level,
+                    self.engram_compression_level.map(Some),
+                    defaulted("engram_compression_level"),
+                );
+                fill(engram_checksum, checksum, defaulted("engram_checksum"));
+                fill(part_size, self.part_size.map(Some), defaulted("part_size"));
+                fill(
+                    shard_chunks,
+                    self.shard_chunks.map(Some),
+                    defaulted("shard_chunks"),
+                );
+                fill(
+                    sign_key,
+                    self.sign_key.as_deref().map(expand_path).map(Some),
+                    defaulted("sign_key"),
+                );
+                fill(
+                    encrypt_key,
+                    self.encrypt_key.as_deref().map(expand_path).map(Some),
+                    defaulted("encrypt_key"),
+                );
+                fill(key_id, self.key_id, defaulted("key_id"));
+                fill(
+                    max_engram_bytes,
+                    self.max_engram_bytes.map(Some),
+                    defaulted("max_engram_bytes"),
+                );
+                fill(
+                    max_file_size,
+                    self.max_file_size.map(Some),
+                    defaulted("max_file_size"),
+                );
+                fill(
+                    max_chunks,
+                    self.max_chunks.map(Some),
+                    defaulted("max_chunks"),
+                );
+                fill(
+                    chunk_size,
+                    self.chunk_size.map(Some),
+                    defaulted("chunk_size"),
+                );
+                fill(
+                    similarity_chunks,
+                    self.similarity_chunks,
+                    defaulted("similarity_chunks"),
+                );
+            }
+            #[cfg(feature = "vector-sync")]
+            Commands::SyncVectors(SyncVectorsArgs {
+                backend,
+                url,
+                collection,
+                token,
+                ..
+            }) => {
+                let sync = &self.vector_sync;
+                let parsed = sync
+                    .backend
+                    .as_deref()
+                    .map(|v| parse_enum::<super::VectorBackendArg>("vector_sync.backend", v))
+                    .transpose()?;
+                fill(backend, parsed.map(Some), defaulted("backend"));
+                fill(url, sync.url.clone().map(Some), defaulted("url"));
+                fill(
+                    collection,
+                    sync.collection.clone().map(Some),
+                    defaulted("collection"),
+                );
+                fill(token, sync.token.clone().map(Some), defaulted("token"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn fill<T>(slot: &mut T, value: Option<T>, use_profile: bool) {
+    if let (Some(value), true) = (value, use_profile) {
+        *slot = value;
+    }
+}
+
+/// The `--key` list of `command`, if it takes one.
+fn keys_mut(command: &mut Commands) -> Option<&mut Vec<(u32, PathBuf)>> {
+    let args = match command {
+        Commands::Extract(args) => &mut args.keys,
+        Commands::Stats(args) => &mut args.keys,
+        Commands::Info(args) => &mut args.keys,
+        Commands::Capacity(args) => &mut args.keys,
+        Commands::Ls(args) => &mut args.keys,
+        Commands::Tree(args) => &mut args.keys,
+        Commands::Cat(args) => &mut args.keys,
+        Commands::Shell(args) => &mut args.keys,
+        Commands::Verify(args) => &mut args.keys,
+        Commands::Convert(args) => &mut args.keys,
+        Commands::Delta(args) => &mut args.keys,
+        Commands::ApplyDelta(args) => &mut args.keys,
+        Commands::Repair(args) => &mut args.keys,
+        // `rekey` documents its own --key: old and new keys alike.
+        Commands::Rekey(args) => return Some(&mut args.keys),
+        Commands::Export(args) => &mut args.keys,
+        Commands::Push(args) => &mut args.keys,
+        Commands::Pull(args) => &mut args.keys,
+        Commands::SyncServer(args) => &mut args.keys,
+        Commands::Codebook(args) => match &mut args.action {
+            CodebookCommand::Gc { keys, .. }
+            | CodebookCommand::Release { keys, .. }
+            | CodebookCommand::Show { keys, .. }
+            | CodebookCommand::Diff { keys, .. }
+            | CodebookCommand::Cluster { keys, .. } => keys,
+        },
+        #[cfg(feature = "fuse")]
+        Commands::Mount(args) => &mut args.keys,
+        #[cfg(feature = "http")]
+        Commands::Serve(args) => &mut args.keys,
+        #[cfg(feature = "nbd")]
+        Commands::NbdServe(args) => &mut args.keys,
+        #[cfg(feature = "sqlite")]
+        Commands::Sql(args) => &mut args.keys,
+        #[cfg(feature = "vector-sync")]
+        Commands::SyncVectors(args) => &mut args.keys,
+        _ => return None,
+    };
+    Some(&mut args.keys)
+}
+
+fn parse_enum<T: ValueEnum>(setting: &str, value: &str) -> io::Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        invalid(format!(
+            "{setting}: invalid value {value:?} (expected one of {})",
+            expected.join(", ")
+        ))
+    })
+}
+
+/// Insert `value` at the dotted `key` of `table`.
+fn set(table: &mut toml::Table, key: &str, value: toml::Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table
+                .entry(head.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(inner) = entry {
+                set(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Settings holding integers.
+const INTEGER_SETTINGS: &[&str] = &[
+    "engram_compression_level",
+    "part_size",
+    "shard_chunks",
+    "key_id",
+    "max_engram_bytes",
+    "max_file_size",
+    "max_chunks",
+    "chunk_size",
+];
+
+/// Settings holding booleans.
+const BOOLEAN_SETTINGS: &[&str] = &["similarity_chunks"];
+
+/// An environment value as TOML: `keys` is split on commas, integer and
+/// boolean settings are parsed when they can be (so bad input is reported
+/// against the setting), everything else is a string.
+fn env_value(setting: &str, value: &str) -> toml::Value {
+    if setting == "keys" {
+        return toml::Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(|k| toml::Value::String(k.to_string()))
+                .collect(),
+        );
+    }
+    if let (Ok(n), true) = (value.trim().parse::<i64>(), INTEGER_SETTINGS.contains(&setting)) {
+        return toml::Value::Integer(n);
+    }
+    match value.trim().parse::<bool>() {
+        Ok(b) if BOOLEAN_SETTINGS.contains(&setting) => toml::Value::Boolean(b),
+        _ => toml::Value::String(value.to_string()),
+    }
+}
+
+fn expand_path(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(expand_home(s)),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Replace a leading `~/` with `$HOME/`. Applies to the file part of
+/// `ID=FILE` key specs too.
+fn expand_home(s: &str) -> String {
+    let Ok(home) = std::env::var("HOME") else {
+        return s.to_string();
+    };
+    if let Some(rest) = s.strip_prefix("~/") {
+        return format!("{home}/{rest}");
+    }
+    match s.split_once("=~/") {
+        Some((id, rest)) => format!("{id}={home}/{rest}"),
+        None => s.to_string(),
+    }
+}
@@ -0,0 +1,203 @@
+//! `extract`: rebuilding files from an engram.
+
+use super::{build_keyring, print_json, read_key_file, EngramArgs, KeyArgs};
+use crate::embrfs::{
+    EmbrFS, Engram, FileEntry, Manifest,
+};
+use crate::codebook::ChunkCache;
+use crate::envelope::Keyring;
+use crate::safe_path::PathPolicy;
+use crate::path_norm::{Folding, PathCollision};
+use crate::namespace_keys;
+use crate::signing::{self, DetachedSignature, VerifyMode};
+use clap::Args;
+use std::env;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct ExtractArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Output directory where files will be reconstructed
+    #[arg(short, long, value_name = "DIR", help_heading = "Required")]
+    pub output_dir: PathBuf,
+
+    /// Only extract files from this namespace (prefix is stripped from output paths)
+    #[arg(long, value_name = "NAME")]
+    pub namespace: Option<String>,
+
+    /// Write manifest paths that are absolute or contain '..' where they lead,
+    /// even outside the output directory (only for engrams you trust)
+    #[arg(long)]
+    pub allow_unsafe_paths: bool,
+
+    /// Detached signature verification mode; enforce needs --trusted-key
+    #[arg(long, default_value = "off", value_enum)]
+    pub verify_signature: VerifyModeArg,
+
+    /// Detached signature file (default: `<engram>.sig`)
+    #[arg(long, value_name = "FILE")]
+    pub signature: Option<PathBuf>,
+
+    /// Trusted Ed25519 public key file; the signature must be made by this key
+    #[arg(long, value_name = "FILE")]
+    pub trusted_key: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+
+    /// Enable verbose output showing extraction progress
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl ExtractArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ExtractArgs {
+            paths: EngramArgs { engram, manifest },
+            output_dir,
+            namespace,
+            allow_unsafe_paths,
+            verify_signature,
+            signature,
+            trusted_key,
+            keys: KeyArgs { keys },
+            verbose,
+        } = self;
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - Holographic Extraction",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("======================================");
+        }
+
+        let mode: VerifyMode = verify_signature.into();
+        let keyring = build_keyring(&keys)?;
+        let (mut engram_data, manifest_data) = if mode != VerifyMode::Off {
+            let sig_path = signature.unwrap_or_else(|| signing::default_signature_path(&engram));
+            let sig = DetachedSignature::load(&sig_path)?;
+            let trusted = trusted_key.as_deref().map(read_key_file).transpose()?;
+            // Decoded from the verified bytes, not read again.
+            let (engram_bytes, manifest_bytes) =
+                signing::read_checked(&engram, &manifest, &sig, trusted.as_deref(), mode)?;
+            if verbose {
+                println!("Signature checked: {} (key {})", sig_path.display(), sig.public_key);
+            }
+            (
+                EmbrFS::engram_from_bytes(&engram_bytes, &keyring)?,
+                EmbrFS::manifest_from_bytes(&manifest_bytes, &keyring)?,
+            )
+        } else {
+            (
+                EmbrFS::load_engram_with_keys(&engram, &keyring)?,
+                EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+            )
+        };
+        open_key_groups(&mut engram_data, &manifest_data, &engram, &keyring, namespace.as_deref())?;
+        let config = manifest_data.encoding().vsa;
+
+        let paths = if allow_unsafe_paths { PathPolicy::AllowUnsafe } else { PathPolicy::Strict };
+
+        // Provenance is only worth collecting when it will be shown.
+        if !verbose && !json_output && paths == PathPolicy::Strict {
+            match namespace.as_deref() {
+                Some(ns) => EmbrFS::extract_namespace(&engram_data, &manifest_data, ns, &output_dir, false, &config)?,
+                None => {
+                    let cache = ChunkCache::default();
+                    EmbrFS::extract_with_cache(&engram_data, &manifest_data, &output_dir, false, &config, &cache)?
+                }
+            }
+            let folding = Folding::probe(&output_dir)?;
+            print_collisions(&manifest_data.collisions(namespace.as_deref(), folding));
+            return Ok(());
+        }
+        let cache = ChunkCache::default();
+        let report = EmbrFS::extract_with_path_policy(
+            &engram_data,
+            &manifest_data,
+            namespace.as_deref(),
+            &output_dir,
+            verbose,
+            &config,
+            Some(&cache),
+            paths,
+        )?;
+
+        if json_output {
+            let extracted: Vec<&FileEntry> = match namespace.as_deref() {
+                Some(ns) => manifest_data.files_in_namespace(ns).collect(),
+                None => manifest_data.files.iter().collect(),
+            };
+            print_json(&serde_json::json!({
+                "output_dir": output_dir,
+                "files": extracted.len(),
+                "bytes": extracted.iter().map(|f| f.size as u64).sum::<u64>(),
+                "provenance": report,
+            }))?;
+            return Ok(());
+        }
+        // In JSON the collisions are part of "provenance".
+        print_collisions(&report.collisions);
+        if verbose {
+            println!("\nExtraction complete!");
+            println!("  Output: {}", output_dir.display());
+            println!("  Chunks: {}", report.sources);
+            println!(
+                "  Confidence: {:.3} (lowest file {:.3})",
+                report.confidence, report.min_confidence
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum VerifyModeArg {
+    Off,
+    Warn,
+    Enforce,
+}
+
+impl From<VerifyModeArg> for VerifyMode {
+    fn from(v: VerifyModeArg) -> Self {
+        match v {
+            VerifyModeArg::Off => VerifyMode::Off,
+            VerifyModeArg::Warn => VerifyMode::Warn,
+            VerifyModeArg::Enforce => VerifyMode::Enforce,
+        }
+    }
+}
+
+/// Decrypt into `engram` the namespace key groups `keys` opens, failing if
+/// a file under `namespace` (or any file) is under a key that was not given.
+fn open_key_groups(
+    engram: &mut Engram,
+    manifest: &Manifest,
+    engram_path: &Path,
+    keys: &Keyring,
+    namespace: Option<&str>,
+) -> io::Result<()> {
+    let Some(key_manifest) = &manifest.namespace_keys else {
+        return Ok(());
+    };
+    let report = namespace_keys::open(engram, manifest, engram_path, keys)?;
+    match namespace {
+        Some(ns) => report.require(key_manifest, manifest.files_in_namespace(ns)),
+        None => report.require(key_manifest, &manifest.files),
+    }
+}
+
+/// One stderr line per group of extracted paths the output filesystem
+/// stored as a single file.
+fn print_collisions(collisions: &[PathCollision]) {
+    for collision in collisions {
+        let kept = collision.paths.last().map_or("", String::as_str);
+        eprintln!("collision: {} name one file here; it holds {}", collision.paths.join(", "), kept);
+    }
+}
@@ -0,0 +1,1019 @@
+//! `ingest` and `ingest-stream`: encoding files into an engram.
+
+use super::{build_keyring, load_semantic_encoder, parse_key_arg, parse_key_rule_arg, print_json, read_key_file, ChecksumArg, CompressionArg};
+use crate::embrfs::{
+    EmbrFS, IngestEstimate, IngestLimits,
+};
+use crate::codebook::{self, OutlierRule};
+use crate::semantic;
+use crate::chunk_vectors::{self, ChunkVectors};
+use crate::envelope::{
+    BinaryWriteOptions, EncryptionCodec, EncryptionKey,
+};
+use crate::ingest_filter::{CompressedContent, ExtensionFilter, FilterAction, SecretScanner, SizeLimit};
+use crate::ingest_queue::{IngestPipeline, Watermarks};
+use crate::reproducible::IngestClock;
+use crate::retention::RetentionPolicy;
+use crate::path_norm::{PathNormalization, UnicodeForm};
+use crate::role_schema::RoleValue;
+use crate::namespace_keys::{self, KeyRule};
+use crate::error::EmbrError;
+use crate::signing;
+use crate::vsa::{ChunkEncoding, DIM};
+#[cfg(feature = "kafka")]
+use crate::vsa::ReversibleVSAConfig;
+use crate::dimensional::DimensionalConfig;
+use clap::Args;
+use std::env;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::collections::HashMap;
+
+#[derive(Args)]
+pub struct IngestArgs {
+    /// Input path(s) to ingest (directory or file). Can be provided multiple times.
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        help_heading = "Required",
+        num_args = 1..,
+        action = clap::ArgAction::Append
+    )]
+    pub input: Vec<PathBuf>,
+
+    /// Named source root as NAME=PATH; files are stored under `NAME/`. Repeatable.
+    #[arg(long = "root", value_name = "NAME=PATH", value_parser = parse_root_arg)]
+    pub roots: Vec<(String, PathBuf)>,
+
+    /// Output engram file containing holographic encoding
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Optional compression for the output engram (default: none)
+    #[arg(long, default_value = "none", value_enum)]
+    pub engram_compression: CompressionArg,
+
+    /// Optional compression level (codec-dependent; used for zstd)
+    #[arg(long, value_name = "LEVEL")]
+    pub engram_compression_level: Option<i32>,
+
+    /// Record per-frame and whole-file checksums in the engram envelope
+    #[arg(long, default_value = "none", value_enum)]
+    pub engram_checksum: ChecksumArg,
+
+    /// Split the engram into `<engram>.partNNNN` files of at most this many bytes;
+    /// `<engram>` becomes a master index that other commands read transparently
+    #[arg(long, value_name = "BYTES")]
+    pub part_size: Option<u64>,
+
+    /// Shard the codebook into `<engram>.shardNNNN` files of this many chunk ids each,
+    /// that `cat` and `shell` load on demand
+    #[arg(long, value_name = "N", conflicts_with = "part_size")]
+    pub shard_chunks: Option<u64>,
+
+    /// Store the chunks in this shared codebook (created if missing) and write the
+    /// engram as references into it; see `embeddenator codebook --help`
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["part_size", "shard_chunks"])]
+    pub codebook: Option<PathBuf>,
+
+    /// Output manifest file containing file metadata and chunk mappings
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+
+    /// Sign the engram + manifest with this Ed25519 secret key file (writes `<engram>.sig`)
+    #[arg(long, value_name = "FILE")]
+    pub sign_key: Option<PathBuf>,
+
+    /// Encrypt the engram and manifest with this 32-byte hex key file (XChaCha20-Poly1305)
+    #[arg(long, value_name = "FILE")]
+    pub encrypt_key: Option<PathBuf>,
+
+    /// Key id recorded in the envelope header, used to pick the key after rotation
+    #[arg(long, default_value_t = 1, value_name = "ID", requires = "encrypt_key")]
+    pub key_id: u32,
+
+    /// Encrypt the chunks of files under PREFIX (a namespace or path prefix) with key ID,
+    /// stored in `<engram>.keyNNNN`. Repeatable; the longest matching prefix wins
+    #[arg(long = "key-rule", value_name = "PREFIX=ID", value_parser = parse_key_rule_arg)]
+    pub key_rules: Vec<KeyRule>,
+
+    /// Namespace key as ID=FILE (32-byte hex), for --key-rule. Repeatable.
+    #[arg(long = "key", value_name = "ID=FILE", value_parser = parse_key_arg)]
+    pub keys: Vec<(u32, PathBuf)>,
+
+    /// Abort if the estimated engram size would exceed this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_engram_bytes: Option<u64>,
+
+    /// Abort if any input file is larger than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_file_size: Option<u64>,
+
+    /// Abort if the engram would contain more than this many chunks
+    #[arg(long, value_name = "N")]
+    pub max_chunks: Option<usize>,
+
+    /// Read and encode directory inputs on N threads while the main thread
+    /// stores the chunks; the engram comes out the same as without
+    #[arg(long, value_name = "N")]
+    pub encode_threads: Option<usize>,
+
+    /// Bytes of encoded chunks that may wait for the main thread before the
+    /// encoding threads pause; they resume once it is down to three quarters
+    #[arg(long, default_value_t = 64 << 20, value_name = "BYTES", requires = "encode_threads")]
+    pub queue_bytes: u64,
+
+    /// Keep the engram and the waiting chunks within this many bytes together,
+    /// pausing the encoding threads sooner as the engram grows
+    #[arg(long, value_name = "BYTES", requires = "encode_threads")]
+    pub memory_budget: Option<u64>,
+
+    /// ONNX embedding model for semantic chunk signatures, written to
+    /// `<engram>.semantic` (requires --features onnx)
+    #[arg(long, value_name = "FILE")]
+    pub semantic_model: Option<PathBuf>,
+
+    /// tokenizer.json for --semantic-model; omit for byte-level models
+    #[arg(long, value_name = "FILE", requires = "semantic_model")]
+    pub semantic_tokenizer: Option<PathBuf>,
+
+    /// Also write every chunk's vector to `<engram>.vectors`, so
+    /// retrieval can run without loading the engram's corrections
+    #[arg(long)]
+    pub chunk_vectors: bool,
+
+    /// Encode chunks as similarity signatures, so near-duplicate content
+    /// scores high in queries and the root. Every chunk's bytes are then
+    /// stored as a correction, making the engram about as large as the input
+    #[arg(long)]
+    pub similarity_chunks: bool,
+
+    /// Bytes per chunk (default 4096). Recorded in the manifest, so extract
+    /// and every other reader cut files the same way
+    #[arg(long, value_name = "BYTES")]
+    pub chunk_size: Option<usize>,
+
+    /// Learn a basis of up to K vectors from the ingested chunks for
+    /// differential encoding, written to `<engram>.basis`
+    #[arg(long, value_name = "K")]
+    pub basis: Option<usize>,
+
+    /// Outlier rule recorded in the trained basis, deciding which data
+    /// its projections keep verbatim: entropy=BITS, residual=FRACTION or
+    /// similarity=COSINE. Repeatable; replaces the default entropy rule
+    #[arg(long = "outlier", value_name = "RULE", requires = "basis", value_parser = parse_outlier_arg)]
+    pub outliers: Vec<OutlierRule>,
+
+    /// Adaptive trit depth for differential encoding against the basis,
+    /// as BASE:MAX. Dimensions and coefficients that need more than BASE
+    /// trits are widened up to MAX, and the depths kept in the engram
+    #[arg(long, value_name = "BASE:MAX", requires = "basis", value_parser = parse_trit_depth_arg)]
+    pub trit_depth: Option<(u8, u8)>,
+
+    /// Store chunks as coefficients and residuals against the trained
+    /// basis where that is exact and smaller than the chunk vector, and
+    /// report the size against raw mode
+    #[arg(long, requires = "basis")]
+    pub differential: bool,
+
+    /// Bind a file or directory to a value under a role of the engram's
+    /// role schema, as PATH:ROLE=VALUE with PATH a logical path. Repeatable
+    #[arg(long = "role", value_name = "PATH:ROLE=VALUE", value_parser = parse_role_arg)]
+    pub roles: Vec<(String, String, String)>,
+
+    /// Bind every file's kind, owner, group and mode in the role schema
+    #[arg(long)]
+    pub metadata_roles: bool,
+
+    /// Seed of the role and value vectors when the engram has no role
+    /// schema yet; an existing schema keeps its own
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub schema_seed: u64,
+
+    /// Scan every file for secrets (credential patterns, high-entropy tokens,
+    /// private key files) and warn about, redact or reject what is found
+    #[arg(long, value_name = "ACTION", value_enum)]
+    pub scan_secrets: Option<SecretActionArg>,
+
+    /// Extra secret detector as NAME=REGEX for --scan-secrets; a capture group,
+    /// if any, marks the secret within the match. Repeatable
+    #[arg(long = "secret-pattern", value_name = "NAME=REGEX", requires = "scan_secrets", value_parser = parse_secret_pattern_arg)]
+    pub secret_patterns: Vec<(String, String)>,
+
+    /// Leave matching files out of the engram, listing each one skipped:
+    /// larger-than=BYTES (unlike --max-file-size, which aborts the ingest),
+    /// extension=EXT, or compressed for content that is already compressed
+    /// (gzip, zstd, zip, PNG, JPEG, ...) and would need a correction for
+    /// nearly every chunk. Repeatable
+    #[arg(long = "skip", value_name = "RULE", value_parser = parse_skip_arg)]
+    pub skip: Vec<SkipRule>,
+
+    /// Store this TOML retention policy in the manifest; see `embeddenator retention --help`
+    #[arg(long, value_name = "FILE")]
+    pub retention: Option<PathBuf>,
+
+    /// Store every logical path in this Unicode normalization form; recorded in
+    /// the manifest so later ingests into the engram do the same
+    #[arg(long, default_value = "as-is", value_name = "FORM", value_enum)]
+    pub normalize_paths: UnicodeFormArg,
+
+    /// Store every logical path in lowercase, so names that differ only in case
+    /// become one file; recorded in the manifest like --normalize-paths
+    #[arg(long)]
+    pub lowercase_paths: bool,
+
+    /// Write byte-identical output for identical input trees: deduplicated
+    /// chunk ids renumbered in path order, and ingest times taken from
+    /// SOURCE_DATE_EPOCH (left out if it is unset)
+    #[arg(long, conflicts_with_all = ["encrypt_key", "codebook"])]
+    pub deterministic: bool,
+
+    /// Estimate the engram size and check limits without encoding or writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Enable verbose output showing ingestion progress and statistics
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl IngestArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let IngestArgs {
+            input,
+            roots,
+            engram,
+            manifest,
+            engram_compression,
+            engram_compression_level,
+            engram_checksum,
+            part_size,
+            shard_chunks,
+            codebook,
+            sign_key,
+            encrypt_key,
+            key_id,
+            key_rules,
+            keys,
+            max_engram_bytes,
+            max_file_size,
+            max_chunks,
+            encode_threads,
+            queue_bytes,
+            memory_budget,
+            semantic_model,
+            semantic_tokenizer,
+            chunk_vectors,
+            similarity_chunks,
+            chunk_size,
+            basis,
+            outliers,
+            trit_depth,
+            differential,
+            roles,
+            metadata_roles,
+            schema_seed,
+            scan_secrets,
+            secret_patterns,
+            skip,
+            retention,
+            normalize_paths,
+            lowercase_paths,
+            deterministic,
+            dry_run,
+            verbose,
+        } = self;
+        // Progress output would corrupt the JSON document on stdout.
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - Holographic Ingestion",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("=====================================");
+        }
+
+        let mut fs = if deterministic {
+            EmbrFS::reproducible(IngestClock::from_env()?)
+        } else {
+            EmbrFS::new()
+        };
+        fs.limits = IngestLimits {
+            max_engram_bytes,
+            max_file_size,
+            max_chunks,
+            max_memory_bytes: None,
+        };
+        fs.pipeline = encode_threads.map(|threads| IngestPipeline {
+            watermarks: Watermarks::bounded(queue_bytes),
+            memory_budget,
+            ..IngestPipeline::new(threads)
+        });
+        if similarity_chunks {
+            let mut encoding = fs.manifest.encoding();
+            encoding.vsa.chunk_encoding = ChunkEncoding::similarity();
+            fs.manifest.encoding = Some(encoding);
+        }
+        if let Some((base, max)) = trit_depth {
+            let mut encoding = fs.manifest.encoding();
+            encoding.dimensional = DimensionalConfig::adaptive(DIM, base, max);
+            fs.manifest.encoding = Some(encoding);
+        }
+        if let Some(chunk_size) = chunk_size {
+            let mut encoding = fs.manifest.encoding();
+            encoding.chunk_size = chunk_size;
+            encoding.validate()?;
+            fs.manifest.encoding = Some(encoding);
+        }
+        let config = fs.vsa_config();
+
+        if dry_run {
+            let mut total = IngestEstimate::default();
+            for p in input.iter().chain(roots.iter().map(|(_, p)| p)) {
+                let est = fs.estimate_directory(p, &config)?;
+                total.files += est.files;
+                total.input_bytes += est.input_bytes;
+                total.chunks += est.chunks;
+                total.estimated_engram_bytes += est.estimated_engram_bytes;
+                total.violations.extend(est.violations);
+            }
+
+            if json_output {
+                print_json(&serde_json::json!({
+                    "dry_run": true,
+                    "files": total.files,
+                    "input_bytes": total.input_bytes,
+                    "chunks": total.chunks,
+                    "estimated_engram_bytes": total.estimated_engram_bytes,
+                    "violations": total.violations.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+                }))?;
+            } else {
+                println!("Dry run (nothing written)");
+                println!("  Files: {}", total.files);
+                println!("  Input bytes: {}", total.input_bytes);
+                println!("  Chunks: {}", total.chunks);
+                println!("  Estimated engram bytes: {}", total.estimated_engram_bytes);
+                for v in &total.violations {
+                    println!("  LIMIT: {}", v);
+                }
+            }
+
+            // Every violation was listed above; fail with the first so the
+            // exit code reports a quota error.
+            return match total.violations.into_iter().next() {
+                None => Ok(()),
+                Some(first) => Err(EmbrError::Quota(first).into()),
+            };
+        }
+
+        if let Some(model) = semantic_model.as_deref() {
+            fs.set_semantic_encoder(load_semantic_encoder(model, semantic_tokenizer.as_deref())?);
+        }
+        if let Some(action) = scan_secrets {
+            let mut scanner = SecretScanner::builtin();
+            for (name, pattern) in &secret_patterns {
+                scanner = scanner.with_pattern(name, pattern)?;
+            }
+            fs.filters.push(scanner, action.into());
+        }
+        for rule in skip {
+            match rule {
+                SkipRule::LargerThan(max) => fs.filters.push(SizeLimit::new(max), FilterAction::Skip),
+                SkipRule::Extension(ext) => fs.filters.push(ExtensionFilter::deny([ext]), FilterAction::Skip),
+                SkipRule::Compressed => fs.filters.push(CompressedContent::default(), FilterAction::Skip),
+            }
+        }
+        fs.manifest.retention = retention.map(RetentionPolicy::load).transpose()?;
+        fs.manifest.path_normalization = PathNormalization {
+            unicode: normalize_paths.into(),
+            lowercase: lowercase_paths,
+        };
+
+        // Backward-compatible behavior: a single directory input ingests with paths
+        // relative to that directory (no namespacing).
+        if input.len() == 1 && input[0].is_dir() && roots.is_empty() {
+            fs.ingest_directory(&input[0], verbose, &config)?;
+        } else {
+            let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+            // Ensure deterministic and collision-resistant namespacing for multiple directory roots.
+            let mut dir_prefix_counts: HashMap<String, usize> = HashMap::new();
+
+            for p in &input {
+                if !p.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Input path does not exist: {}", p.display()),
+                    ));
+                }
+
+                if p.is_dir() {
+                    let base = p
+                        .file_name()
+                        .and_then(|s| s.to_str())
+                        .filter(|s| !s.is_empty())
+                        .unwrap_or("input")
+                        .to_string();
+                    let count = dir_prefix_counts.entry(base.clone()).or_insert(0);
+                    *count += 1;
+                    let prefix = if *count == 1 {
+                        base
+                    } else {
+                        format!("{}_{}", base, count)
+                    };
+
+                    fs.ingest_directory_with_prefix(p, Some(&prefix), verbose, &config)?;
+                } else {
+                    let logical = logical_path_for_file_input(p, &cwd);
+                    fs.ingest_file(p, logical, verbose, &config)?;
+                }
+            }
+
+            for (name, path) in &roots {
+                if !path.is_dir() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Root is not a directory: {}", path.display()),
+                    ));
+                }
+                fs.ingest_root(name, path, verbose, &config)?;
+            }
+        }
+        if deterministic {
+            fs.canonicalize();
+        }
+        if !key_rules.is_empty() {
+            let opts = BinaryWriteOptions {
+                codec: engram_compression.into(),
+                level: engram_compression_level,
+                checksum: engram_checksum.into(),
+                ..Default::default()
+            };
+            namespace_keys::rekey(&mut fs.engram, &mut fs.manifest, &engram, key_rules, &build_keyring(&keys)?, opts)?;
+        }
+        if !roles.is_empty() || metadata_roles {
+            fs.role_schema(schema_seed);
+            for (path, role, value) in &roles {
+                fs.bind_role(path, role, RoleValue::Text(value.clone()))?;
+            }
+            if metadata_roles {
+                fs.bind_metadata_roles()?;
+            }
+        }
+        // Trained before the engram is saved, which keeps the depths
+        // adaptive precision raises and the differential chunks.
+        let basis = match basis {
+            Some(k) => {
+                let (mut codebook, report) = fs.train_basis(k);
+                if !outliers.is_empty() {
+                    codebook.outliers.rules = outliers;
+                }
+                let precision = trit_depth.map(|_| fs.adapt_precision(&codebook));
+                let differential = if differential { Some(fs.encode_differential(&codebook)?) } else { None };
+                Some((codebook, report, precision, differential))
+            }
+            None => None,
+        };
+        let sources: Vec<String> = input
+            .iter()
+            .map(|p| p.display().to_string())
+            .chain(roots.iter().map(|(name, p)| format!("{}={}", name, p.display())))
+            .collect();
+        fs.record_provenance(&sources)?;
+
+        let key = encrypt_key
+            .as_deref()
+            .map(|path| EncryptionKey::from_hex(key_id, &read_key_file(path)?))
+            .transpose()?;
+        let encryption = if key.is_some() {
+            EncryptionCodec::XChaCha20Poly1305
+        } else {
+            EncryptionCodec::None
+        };
+
+        let engram_opts = BinaryWriteOptions {
+            codec: engram_compression.into(),
+            level: engram_compression_level,
+            checksum: engram_checksum.into(),
+            encryption,
+            key,
+        };
+        if [part_size.is_some(), shard_chunks.is_some(), codebook.is_some()].iter().filter(|&&b| b).count() > 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--part-size, --shard-chunks and --codebook cannot be combined",
+            ));
+        }
+        let manifest_opts = BinaryWriteOptions {
+            encryption,
+            key,
+            ..Default::default()
+        };
+        let (mut parts, mut shards, mut shared) = (None, None, None);
+        if let Some(part_size) = part_size {
+            parts = Some(fs.save_engram_parts(&engram, part_size, engram_opts)?.parts.len());
+        } else if let Some(shard_chunks) = shard_chunks {
+            shards = Some(fs.save_engram_shards(&engram, shard_chunks, engram_opts)?.shards.len());
+        } else if let Some(codebook) = &codebook {
+            shared = Some(fs.save_engram_shared(&engram, codebook, engram_opts)?);
+        }
+        if parts.is_none() && shards.is_none() && shared.is_none() {
+            fs.save_transactional(&engram, &manifest, engram_opts, manifest_opts)?;
+        } else {
+            fs.save_manifest_with_options(&manifest, manifest_opts)?;
+        }
+
+        let semantic_path = match fs.semantic.as_ref() {
+            Some(signatures) => {
+                let path = semantic::default_signatures_path(&engram);
+                signatures.save(
+                    &path,
+                    BinaryWriteOptions {
+                        encryption,
+                        key,
+                        ..Default::default()
+                    },
+                )?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        let vectors_path = if chunk_vectors {
+            let path = chunk_vectors::default_vectors_path(&engram);
+            ChunkVectors::from_engram(&fs.engram).save(
+                &path,
+                BinaryWriteOptions {
+                    encryption,
+                    key,
+                    ..Default::default()
+                },
+            )?;
+            Some(path)
+        } else {
+            None
+        };
+
+        let basis = match basis {
+            Some((codebook, report, precision, differential)) => {
+                let path = codebook::default_basis_path(&engram);
+                codebook.save(
+                    &path,
+                    BinaryWriteOptions {
+                        encryption,
+                        key,
+                        ..Default::default()
+                    },
+                )?;
+                Some((path, report, precision, differential))
+            }
+            None => None,
+        };
+
+        let signature_path = if let Some(key_path) = sign_key.as_ref() {
+            let secret = read_key_file(key_path)?;
+            let sig = signing::sign_files(&engram, &manifest, &secret)?;
+            let sig_path = signing::default_signature_path(&engram);
+            sig.save(&sig_path)?;
+            Some(sig_path)
+        } else {
+            None
+        };
+
+        // In JSON the findings are part of "stats".
+        if !json_output {
+            for finding in &fs.filters.findings {
+                eprintln!("filter: {}", finding);
+            }
+        }
+
+        if json_output {
+            print_json(&serde_json::json!({
+                "engram": engram,
+                "engram_parts": parts,
+                "engram_shards": shards,
+                "codebook": codebook,
+                "shared_codebook": shared,
+                "manifest": manifest,
+                "signature": signature_path,
+                "semantic_signatures": semantic_path,
+                "chunk_vectors": vectors_path,
+                "basis": basis.as_ref().map(|(path, _, _, _)| path),
+                "basis_training": basis.as_ref().map(|(_, report, _, _)| report),
+                "precision": basis.as_ref().and_then(|(_, _, precision, _)| precision.as_ref()),
+                "differential": basis.as_ref().and_then(|(_, _, _, differential)| differential.as_ref()),
+                "role_schema": fs.engram.schema.as_ref().map(|schema| serde_json::json!({
+                    "seed": schema.seed,
+                    "roles": schema.roles().collect::<Vec<_>>(),
+                    "bound_paths": schema.paths().count(),
+                })),
+                "files": fs.manifest.files.len(),
+                "total_chunks": fs.manifest.total_chunks,
+                "stats": fs.ingest_stats(),
+                "encode_queue": fs.pipeline.map(|_| {
+                    let queue = fs.pipeline_stats();
+                    serde_json::json!({
+                        "chunks": queue.items,
+                        "peak_bytes": queue.peak_bytes,
+                        "pauses": queue.pauses,
+                    })
+                }),
+            }))?;
+        } else if verbose {
+            println!("\nIngestion complete!");
+            println!("  Engram: {}", engram.display());
+            if let Some(parts) = parts {
+                println!("  Engram parts: {}", parts);
+            }
+            if let Some(shards) = shards {
+                println!("  Codebook shards: {}", shards);
+            }
+            if let (Some(codebook), Some(shared)) = (&codebook, &shared) {
+                println!(
+                    "  Shared codebook: {} ({} new of {} records)",
+                    codebook.display(),
+                    shared.records_added,
+                    shared.records_total
+                );
+            }
+            println!("  Manifest: {}", manifest.display());
+            println!("  Files: {}", fs.manifest.files.len());
+            println!("  Total chunks: {}", fs.manifest.total_chunks);
+            if fs.pipeline.is_some() {
+                let queue = fs.pipeline_stats();
+                println!(
+                    "  Encode queue: peak {} bytes, paused {} times",
+                    queue.peak_bytes, queue.pauses
+                );
+            }
+            if let Some(sig_path) = signature_path {
+                println!("  Signature: {}", sig_path.display());
+            }
+            if let Some(path) = semantic_path {
+                println!("  Semantic signatures: {}", path.display());
+            }
+            if let Some(path) = vectors_path {
+                println!("  Chunk vectors: {}", path.display());
+            }
+            if let Some((path, report, precision, differential)) = &basis {
+                println!(
+                    "  Basis: {} ({} vectors from {} chunks, mean similarity {:.3})",
+                    path.display(),
+                    report.basis_size,
+                    report.samples,
+                    report.mean_similarity
+                );
+                if let Some(precision) = precision {
+                    println!(
+                        "  Adaptive precision: {} dimensions and {} coefficients widened over {} chunks, mean quality {:.3}",
+                        precision.expanded_dimensions,
+                        precision.expanded_coefficients,
+                        precision.chunks,
+                        precision.mean_quality
+                    );
+                }
+                if let Some(differential) = differential {
+                    println!(
+                        "  Differential: {} of {} chunks against the basis, {} bytes vs {} raw ({:.1}%), mean quality {:.3}",
+                        differential.differential,
+                        differential.chunks,
+                        differential.differential_bytes,
+                        differential.raw_bytes,
+                        differential.ratio() * 100.0,
+                        differential.mean_quality
+                    );
+                }
+            }
+            if let Some(schema) = &fs.engram.schema {
+                println!(
+                    "  Role schema: {} roles, {} paths bound (seed {})",
+                    schema.roles().count(),
+                    schema.paths().count(),
+                    schema.seed
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[derive(Args)]
+pub struct IngestStreamArgs {
+    /// Kafka bootstrap servers
+    #[arg(long, value_name = "HOST:PORT,...")]
+    pub brokers: String,
+
+    /// Topic to consume. Repeatable.
+    #[arg(long = "topic", value_name = "TOPIC", required = true)]
+    pub topics: Vec<String>,
+
+    /// Consumer group; committed offsets are tracked per group
+    #[arg(long, default_value = "embeddenator", value_name = "GROUP")]
+    pub group: String,
+
+    /// Message layout
+    #[arg(long, value_enum, default_value_t = StreamFormatArg::Files)]
+    pub format: StreamFormatArg,
+
+    /// Bucket to fetch objects from for s3-events. Repeatable.
+    #[arg(long = "bucket", value_name = "NAME")]
+    pub buckets: Vec<String>,
+
+    /// Extra librdkafka setting as KEY=VALUE (e.g. security.protocol=SASL_SSL). Repeatable.
+    #[arg(long = "kafka-option", value_name = "KEY=VALUE", value_parser = parse_kafka_option)]
+    pub kafka_options: Vec<(String, String)>,
+
+    /// Append log holding the checkpointed engram and manifest
+    #[arg(long, default_value = "root.edna", value_name = "FILE")]
+    pub checkpoint: PathBuf,
+
+    /// Checkpoint after this many events
+    #[arg(long, default_value_t = 1000)]
+    pub checkpoint_every: usize,
+
+    /// Checkpoint at least this often while events arrive
+    #[arg(long, default_value_t = 30, value_name = "SECS")]
+    pub checkpoint_secs: u64,
+
+    /// Messages requested per poll
+    #[arg(long, default_value_t = 100)]
+    pub batch_size: usize,
+
+    /// Stop once a poll returns no events
+    #[arg(long)]
+    pub exit_when_idle: bool,
+
+    /// Also write the final engram here on exit
+    #[arg(short, long, value_name = "FILE", requires = "manifest")]
+    pub engram: Option<PathBuf>,
+
+    /// Also write the final manifest here on exit
+    #[arg(short, long, value_name = "FILE", requires = "engram")]
+    pub manifest: Option<PathBuf>,
+
+    /// Print where streaming resumes from
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+#[cfg(feature = "kafka")]
+impl IngestStreamArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let IngestStreamArgs {
+            brokers,
+            topics,
+            group,
+            format,
+            buckets,
+            kafka_options,
+            checkpoint,
+            checkpoint_every,
+            checkpoint_secs,
+            batch_size,
+            exit_when_idle,
+            engram,
+            manifest,
+            verbose,
+        } = self;
+        use crate::kafka_source::{KafkaFormat, KafkaSource};
+        use crate::stream_ingest::{StreamIngestor, StreamSettings};
+        use std::sync::atomic::AtomicBool;
+        use std::time::Duration;
+
+        let verbose = verbose && !json_output;
+
+        let format = match format {
+            StreamFormatArg::Files => KafkaFormat::Files,
+            StreamFormatArg::S3Events => {
+                if buckets.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "--format s3-events needs at least one --bucket",
+                    ));
+                }
+                KafkaFormat::S3Events(s3_fetcher(&buckets)?)
+            }
+        };
+        let mut config = rdkafka::ClientConfig::new();
+        config.set("bootstrap.servers", &brokers).set("group.id", &group);
+        for (key, value) in &kafka_options {
+            config.set(key, value);
+        }
+        let topics: Vec<&str> = topics.iter().map(String::as_str).collect();
+        let mut source = KafkaSource::with_config(config, &topics, format)?;
+
+        let settings = StreamSettings {
+            checkpoint_every: checkpoint_every.max(1),
+            checkpoint_interval: Duration::from_secs(checkpoint_secs),
+            batch_size,
+            ..StreamSettings::default()
+        };
+        let mut ingestor = StreamIngestor::open(&checkpoint, ReversibleVSAConfig::default(), settings)?;
+        if verbose {
+            println!(
+                "Streaming from {} into {} ({} files already checkpointed)",
+                topics.join(","),
+                checkpoint.display(),
+                ingestor.fs().manifest.files.len()
+            );
+        }
+
+        let stats = ingestor.run(&mut source, &AtomicBool::new(false), exit_when_idle)?;
+        if let (Some(engram), Some(manifest)) = (engram, manifest) {
+            ingestor
+                .fs()
+                .save_transactional(&engram, &manifest, Default::default(), Default::default())?;
+        }
+        if json_output {
+            print_json(&serde_json::json!({
+                "ingested": stats.ingested,
+                "removed": stats.removed,
+                "skipped": stats.skipped,
+                "checkpoints": stats.checkpoints,
+            }))?;
+        } else {
+            println!(
+                "Stream ingest done: {} ingested, {} removed, {} skipped, {} checkpoints",
+                stats.ingested, stats.removed, stats.skipped, stats.checkpoints
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum SecretActionArg {
+    /// Store the content and report the finding
+    Warn,
+    /// Replace the detected bytes with `*` before storing
+    Redact,
+    /// Leave the file out of the engram
+    Skip,
+    /// Abort the ingest
+    Reject,
+}
+
+impl From<SecretActionArg> for FilterAction {
+    fn from(v: SecretActionArg) -> Self {
+        match v {
+            SecretActionArg::Warn => FilterAction::Warn,
+            SecretActionArg::Redact => FilterAction::Redact,
+            SecretActionArg::Skip => FilterAction::Skip,
+            SecretActionArg::Reject => FilterAction::Reject,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum UnicodeFormArg {
+    /// Keep paths as the source spells them
+    AsIs,
+    /// Composed form, as Linux and Windows tools usually write
+    Nfc,
+    /// Decomposed form, as HFS+ stores names
+    Nfd,
+}
+
+impl From<UnicodeFormArg> for UnicodeForm {
+    fn from(v: UnicodeFormArg) -> Self {
+        match v {
+            UnicodeFormArg::AsIs => UnicodeForm::AsIs,
+            UnicodeFormArg::Nfc => UnicodeForm::Nfc,
+            UnicodeFormArg::Nfd => UnicodeForm::Nfd,
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum StreamFormatArg {
+    /// Key is the logical path, value the file; a null value deletes
+    Files,
+    /// Values are S3 event notifications
+    S3Events,
+}
+
+/// Fetcher for `--bucket` objects named in S3 event notifications.
+#[cfg(all(feature = "kafka", feature = "s3"))]
+fn s3_fetcher(buckets: &[String]) -> io::Result<Box<dyn crate::stream_ingest::ObjectFetcher + Send>> {
+    let mut fetcher = crate::object_fetch::ObjectStoreFetcher::new()?;
+    for bucket in buckets {
+        fetcher = fetcher.with_s3_bucket(bucket)?;
+    }
+    Ok(Box::new(fetcher))
+}
+
+#[cfg(all(feature = "kafka", not(feature = "s3")))]
+fn s3_fetcher(_: &[String]) -> io::Result<Box<dyn crate::stream_ingest::ObjectFetcher + Send>> {
+    Err(io::Error::other("S3 object fetching not enabled (enable feature `s3`)"))
+}
+
+#[cfg(feature = "kafka")]
+fn parse_kafka_option(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", s)),
+    }
+}
+
+fn parse_root_arg(s: &str) -> Result<(String, PathBuf), String> {
+    let (name, path) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=PATH, got {:?}", s))?;
+    if name.is_empty() || path.is_empty() {
+        return Err(format!("expected NAME=PATH, got {:?}", s));
+    }
+    Ok((name.to_string(), PathBuf::from(path)))
+}
+
+fn parse_secret_pattern_arg(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, pattern)) if !name.is_empty() && !pattern.is_empty() => {
+            Ok((name.to_string(), pattern.to_string()))
+        }
+        _ => Err(format!("expected NAME=REGEX, got {:?}", s)),
+    }
+}
+
+fn parse_role_arg(s: &str) -> Result<(String, String, String), String> {
+    let parsed = s
+        .split_once('=')
+        .and_then(|(target, value)| target.rsplit_once(':').map(|(path, role)| (path, role, value)));
+    match parsed {
+        Some((path, role, value)) if !path.is_empty() && !role.is_empty() => {
+            Ok((path.to_string(), role.to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected PATH:ROLE=VALUE, got {:?}", s)),
+    }
+}
+
+fn parse_outlier_arg(s: &str) -> Result<OutlierRule, String> {
+    s.parse()
+}
+
+fn parse_trit_depth_arg(s: &str) -> Result<(u8, u8), String> {
+    let (base, max) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected BASE:MAX, got {:?}", s))?;
+    let depth = |v: &str| v.parse().map_err(|_| format!("invalid trit depth {:?}", v));
+    let (base, max) = (depth(base)?, depth(max)?);
+    if base == 0 || base > max {
+        return Err(format!("expected 0 < BASE <= MAX, got {:?}", s));
+    }
+    Ok((base, max))
+}
+
+/// A `--skip` rule of `ingest`.
+#[derive(Clone, Debug)]
+pub enum SkipRule {
+    LargerThan(u64),
+    Extension(String),
+    Compressed,
+}
+
+fn parse_skip_arg(s: &str) -> Result<SkipRule, String> {
+    match s.split_once('=') {
+        Some(("larger-than", bytes)) => bytes
+            .parse()
+            .map(SkipRule::LargerThan)
+            .map_err(|_| format!("invalid byte count {:?}", bytes)),
+        Some(("extension", ext)) if !ext.is_empty() => Ok(SkipRule::Extension(ext.to_string())),
+        None if s == "compressed" => Ok(SkipRule::Compressed),
+        _ => Err(format!(
+            "expected larger-than=BYTES, extension=EXT or compressed, got {:?}",
+            s
+        )),
+    }
+}
+
+fn path_to_forward_slash_string(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(s) => s.to_str().map(|v| v.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn logical_path_for_file_input(path: &Path, cwd: &Path) -> String {
+    if path.is_relative() {
+        return path_to_forward_slash_string(path);
+    }
+
+    if let Ok(rel) = path.strip_prefix(cwd) {
+        let s = path_to_forward_slash_string(rel);
+        if !s.is_empty() {
+            return s;
+        }
+    }
+
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("input.bin")
+        .to_string()
+}
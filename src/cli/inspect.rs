@@ -0,0 +1,912 @@
+//! Read-only commands that describe an engram: `stats`, `info`, `ls`, `cat`,
+//! `verify` and friends.
+
+use super::{build_keyring, load_access_stats, print_json, write_listing, EngramArgs, KeyArgs};
+use crate::embrfs::{
+    EmbrFS, EncodingConfig, FileEntry, Manifest,
+};
+use crate::append_log;
+use crate::codebook::ChunkCache;
+use crate::ingest_stats::{IngestStats, StatsBucket};
+use crate::capacity::{self, CapacityReport, MembershipVerdict};
+use crate::stream_monitor::{self, DriftAlert, StreamMonitor, WindowReport};
+use crate::info::{EngramInfo, StorageFormat};
+use crate::ingest_provenance::{IngestProvenance, ProvenanceIssue};
+use crate::lazy_envelope::Envelope;
+use crate::lazy_engram::{self, LazyEngram};
+use crate::listing::{self, PathFilter};
+use crate::shards::{self, ShardedEngram};
+use crate::error::EmbrError;
+use crate::bench::{self, BenchOptions, BenchReport};
+use crate::hybrid::HybridThresholds;
+use clap::Args;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Emit the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl StatsArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let StatsArgs {
+            paths: EngramArgs { engram, manifest },
+            json,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let engram_data = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let stats = IngestStats::compute(&engram_data, &manifest_data);
+
+        if json {
+            print_json(&stats)?;
+            return Ok(());
+        }
+
+        let print_bucket = |name: &str, b: &StatsBucket| {
+            println!(
+                "  {:<24} files {:>6}  chunks {:>8}  raw {:>12}  unique {:>12}  dedup {:>6.2}x  compression {:>6.3}x",
+                name, b.files, b.chunks, b.raw_bytes, b.unique_chunk_bytes, b.dedup_ratio, b.compression_ratio
+            );
+        };
+
+        println!("Totals:");
+        print_bucket("(all)", &stats.total);
+        println!("By extension:");
+        for (ext, b) in &stats.by_extension {
+            print_bucket(if ext.is_empty() { "(none)" } else { ext }, b);
+        }
+        println!("By directory:");
+        for (dir, b) in &stats.by_directory {
+            print_bucket(dir, b);
+        }
+        println!("Chunks per file:");
+        for bin in &stats.chunk_histogram {
+            println!("  {:>6}-{:<6} {:>8} files", bin.min_chunks, bin.max_chunks, bin.files);
+        }
+        let mut loaded = EmbrFS::new();
+        loaded.engram = engram_data;
+        loaded.manifest = manifest_data;
+        let memory = loaded.memory_usage();
+        println!("Memory when loaded (estimated):");
+        for (name, bytes) in [
+            ("codebook", memory.codebook_bytes),
+            ("root", memory.root_bytes),
+            ("corrections", memory.corrections_bytes),
+            ("manifest", memory.manifest_bytes),
+            ("(total)", memory.total()),
+        ] {
+            println!("  {name:<24} {bytes:>12} bytes");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Engram file to describe
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Manifest file, for dedup and compression ratios
+    #[arg(short, long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
+    /// Emit the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Select the root representation by this host's saved calibration
+    #[arg(long)]
+    pub calibrated: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl InfoArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let InfoArgs {
+            engram,
+            manifest,
+            json,
+            calibrated,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let storage = StorageFormat::detect(&engram)?;
+        let (engram_data, manifest_data) = if append_log::is_append_log(&engram)? {
+            let (engram_data, logged) = EmbrFS::load_append_log(&engram)?;
+            let manifest_data = match &manifest {
+                Some(m) => EmbrFS::load_manifest_with_keys(m, &keyring)?,
+                None => logged,
+            };
+            (engram_data, Some(manifest_data))
+        } else {
+            let manifest_data = match &manifest {
+                Some(m) => Some(EmbrFS::load_manifest_with_keys(m, &keyring)?),
+                None => None,
+            };
+            (EmbrFS::load_engram_with_keys(&engram, &keyring)?, manifest_data)
+        };
+        let mut info = EngramInfo::compute(&engram_data, manifest_data.as_ref()).with_storage(storage);
+        if calibrated {
+            let thresholds = HybridThresholds::saved()?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no saved calibration; run bench --calibrate first")
+            })?;
+            info = info.with_thresholds(&thresholds);
+        }
+
+        if json {
+            print_json(&info)?;
+            return Ok(());
+        }
+
+        if let Some(s) = &info.storage {
+            println!(
+                "Format:            {} v{}{}",
+                s.container,
+                s.version,
+                if s.sealed { " (sealed)" } else { "" }
+            );
+            println!(
+                "Storage:           {} bytes, compression {}, checksums {}, encryption {}",
+                s.file_bytes,
+                s.compression,
+                if s.checksummed { "on" } else { "off" },
+                if s.encrypted { "on" } else { "off" }
+            );
+        }
+        println!("Dimension:         {}", info.dimension);
+        println!(
+            "Root vector:       {} nnz, density {:.4} ({})",
+            info.root_nnz, info.root_density, info.root_representation
+        );
+        println!(
+            "Chunk vectors:     {} (mean {:.1} nnz, density {:.4})",
+            info.chunks, info.mean_chunk_nnz, info.mean_chunk_density
+        );
+        println!("Corrections:       {}", info.corrections);
+        println!("Codebook size:     {} bytes when loaded (estimated)", info.codebook_bytes);
+        if let Some(t) = &info.totals {
+            println!("Files:             {} ({} bytes)", t.files, t.raw_bytes);
+            println!("Dedup ratio:       {:.2}x", t.dedup_ratio);
+            println!("Compression ratio: {:.3}x", t.compression_ratio);
+        }
+        println!("SIMD:              {}", info.simd);
+        println!("Library version:   {}", info.library_version);
+        if let Some(p) = &info.provenance {
+            print_provenance(p, info.provenance_issues.as_deref());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct HeatmapArgs {
+    /// Access stats saved by --access-stats
+    #[arg(long, value_name = "FILE", help_heading = "Required")]
+    pub stats: PathBuf,
+
+    /// Manifest of the engram the accesses were counted on
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+
+    /// Files to show, busiest first
+    #[arg(long, default_value_t = 20, value_name = "N")]
+    pub top: usize,
+
+    /// Cells per file; a longer file folds several chunks into each cell
+    #[arg(long, default_value_t = 40, value_name = "N")]
+    pub width: usize,
+
+    /// Emit the rows as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl HeatmapArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let HeatmapArgs {
+            stats,
+            manifest,
+            top,
+            width,
+            json,
+        } = self;
+        let snapshot = load_access_stats(&stats)?.snapshot();
+        let manifest_data = EmbrFS::load_manifest(&manifest)?;
+        let mut rows = snapshot.heatmap(&manifest_data, width);
+        rows.truncate(top);
+        if json || json_output {
+            return print_json(&rows);
+        }
+        if rows.is_empty() {
+            println!("No accesses recorded in {}", stats.display());
+            return Ok(());
+        }
+        let max = rows.iter().flat_map(|row| row.cells.iter().copied()).max().unwrap_or(0);
+        let path_width = rows.iter().map(|row| row.path.chars().count()).max().unwrap_or(0);
+        for row in &rows {
+            println!(
+                "{:<path_width$}  |{:<width$}|  {} reads  {} extracts  {} queries  mean {:?}",
+                row.path,
+                heat_cells(&row.cells, max),
+                row.counts.reads,
+                row.counts.extracts,
+                row.counts.queries,
+                row.counts.mean_latency(),
+                width = width.max(1),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct CapacityArgs {
+    /// Engram file to analyze
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Measure at most this many chunks (default: all)
+    #[arg(long, value_name = "N")]
+    pub sample: Option<usize>,
+
+    /// Emit the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl CapacityArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let CapacityArgs {
+            engram,
+            sample,
+            json,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let engram_data = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let report = CapacityReport::analyze_sampled(&engram_data, sample.unwrap_or(usize::MAX));
+
+        if json {
+            print_json(&report)?;
+            return Ok(());
+        }
+
+        println!("Chunks bundled:     {}", report.members);
+        println!("Chunks measured:    {}", report.sampled);
+        println!("Root density:       {:.4}", report.root_density);
+        println!("Expected cosine:    {:.4}", report.expected_cosine);
+        println!("Observed cosine:    {:.4} (min {:.4})", report.observed_cosine, report.min_cosine);
+        println!("Noise floor:        {:.4} (threshold {:.4})", report.noise_floor, report.threshold);
+        println!("Below threshold:    {} of {}", report.below_threshold, report.sampled);
+        println!("Saturation:         {:.1}%", report.saturation.min(9.99) * 100.0);
+        println!("Remaining capacity: ~{} chunks", report.remaining);
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ContainsArgs {
+    /// Engram file to test against
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Manifest the engram was written with, for its encoding settings
+    #[arg(short, long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
+    /// File to look for
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Path the file would have been stored under (default: INPUT)
+    #[arg(long, value_name = "PATH")]
+    pub path: Option<String>,
+
+    /// Emit the result as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl ContainsArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ContainsArgs {
+            engram,
+            manifest,
+            input,
+            path,
+            json,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let engram_data = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let encoding = match &manifest {
+            Some(manifest) => EmbrFS::load_manifest_with_keys(manifest, &keyring)?.encoding(),
+            None => EncodingConfig::default(),
+        };
+        let data = std::fs::read(&input)?;
+        let logical_path = path.unwrap_or_else(|| input.to_string_lossy().replace('\\', "/"));
+        let result = engram_data.probably_contains_file(&data, &encoding.vsa, encoding.chunk_size, &logical_path);
+
+        if json {
+            print_json(&result)?;
+        } else {
+            let verdict = match result.verdict {
+                MembershipVerdict::Likely => "likely present",
+                MembershipVerdict::Unlikely => "unlikely",
+                MembershipVerdict::Saturated => "unknown (root saturated)",
+            };
+            println!("{}: {}", logical_path, verdict);
+            println!("Score:               {:.4} (threshold {:.4})", result.score, result.threshold);
+            println!("Expected for member: {:.4} ({} chunks bundled)", result.expected_score, result.members);
+            println!("False-positive rate: {:.2e}", result.false_positive_rate);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct MonitorArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Stream to read, '-' for stdin
+    #[arg(value_name = "INPUT", default_value = "-")]
+    pub input: PathBuf,
+
+    /// Flag windows whose similarity drops below this (default: the noise threshold)
+    #[arg(long, value_name = "COSINE")]
+    pub low: Option<f64>,
+
+    /// Flag windows whose similarity rises above this
+    #[arg(long, value_name = "COSINE")]
+    pub high: Option<f64>,
+
+    /// Chunks per window
+    #[arg(long, default_value_t = stream_monitor::DEFAULT_WINDOW_CHUNKS, value_name = "N")]
+    pub window_chunks: usize,
+
+    /// Windows between decays of the sketch (0: never forget)
+    #[arg(long, default_value_t = stream_monitor::DEFAULT_DECAY_EVERY, value_name = "N")]
+    pub decay_every: usize,
+
+    /// Print only flagged windows
+    #[arg(long)]
+    pub alerts_only: bool,
+
+    /// Emit one JSON object per window
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl MonitorArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let MonitorArgs {
+            paths: EngramArgs { engram, manifest },
+            input,
+            low,
+            high,
+            window_chunks,
+            decay_every,
+            alerts_only,
+            json,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let engram_data = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let mut monitor = StreamMonitor::for_engram(&engram_data, &manifest_data)
+            .with_thresholds(low.unwrap_or_else(capacity::retrieval_threshold), high)
+            .with_window_chunks(window_chunks)
+            .with_decay_every(decay_every);
+        let mut reader: Box<dyn Read> = if input.as_os_str() == "-" {
+            Box::new(io::stdin().lock())
+        } else {
+            Box::new(File::open(&input)?)
+        };
+
+        let mut out = io::stdout().lock();
+        let mut report_window = |report: &WindowReport| -> io::Result<()> {
+            if alerts_only && report.alert.is_none() {
+                return Ok(());
+            }
+            if json {
+                serde_json::to_writer(&mut out, report)?;
+                return writeln!(out);
+            }
+            let flag = match report.alert {
+                Some(DriftAlert::Below) => "  DRIFT (below)",
+                Some(DriftAlert::Above) => "  DRIFT (above)",
+                None => "",
+            };
+            writeln!(
+                out,
+                "window {:>6} @ {:>12}: similarity {:.4} (window {:.4}){}",
+                report.index, report.offset, report.similarity, report.window_similarity, flag
+            )?;
+            // Streams can be slow; show each window as it completes.
+            out.flush()
+        };
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            for report in monitor.push(&buf[..n]) {
+                report_window(&report)?;
+            }
+        }
+        if let Some(report) = monitor.finish() {
+            report_window(&report)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct LsArgs {
+    /// Manifest file with metadata and chunk mappings
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+
+    /// Only list paths matching any of these globs
+    #[arg(value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+
+    /// Emit the listing as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl LsArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let LsArgs {
+            manifest,
+            patterns,
+            json,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let filter = PathFilter::new(&patterns)?;
+        let entries = listing::list(&manifest_data, &filter);
+
+        if json {
+            print_json(&entries)?;
+            return Ok(());
+        }
+
+        write_listing(&mut io::stdout().lock(), &entries)
+    }
+}
+
+#[derive(Args)]
+pub struct TreeArgs {
+    /// Manifest file with metadata and chunk mappings
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+
+    /// Only include paths matching any of these globs
+    #[arg(value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+
+    /// Show at most this many directory levels
+    #[arg(short = 'L', long, value_name = "N")]
+    pub depth: Option<usize>,
+
+    /// Emit the tree as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl TreeArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let TreeArgs {
+            manifest,
+            patterns,
+            depth,
+            json,
+            keys: KeyArgs { keys },
+        } = self;
+        let json = json || json_output;
+        let keyring = build_keyring(&keys)?;
+        let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let filter = PathFilter::new(&patterns)?;
+        let root = listing::tree(&manifest_data, &filter);
+
+        if json {
+            print_json(&root)?;
+        } else {
+            print!("{}", root.render(depth));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct CatArgs {
+    /// Engram file to read from
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Manifest file (default: the one stored in an append log, else manifest.json)
+    #[arg(short, long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
+    /// Logical path of the file, as shown by `ls`
+    #[arg(value_name = "PATH")]
+    pub path: String,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl CatArgs {
+    pub(super) fn run(self) -> io::Result<()> {
+        let CatArgs {
+            engram,
+            manifest,
+            path,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let find = |manifest: &Manifest| -> io::Result<FileEntry> {
+            let wanted = path.trim_start_matches("./").trim_start_matches('/');
+            manifest.files.iter().find(|f| f.path == wanted).cloned().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("{path}: no such file in the manifest"))
+            })
+        };
+        let mut stdout = io::stdout().lock();
+
+        let result = if cfg!(feature = "mmap") && append_log::is_append_log(&engram)? {
+            let envelope = Envelope::open_mmap(&engram)?;
+            let manifest_data = match &manifest {
+                Some(m) => EmbrFS::load_manifest_with_keys(m, &keyring)?,
+                None => envelope.manifest()?,
+            };
+            let encoding = manifest_data.encoding();
+            envelope.write_file(&find(&manifest_data)?, &encoding.vsa, encoding.chunk_size, &mut stdout)
+        } else if shards::is_shard_index(&engram)? {
+            let sharded = ShardedEngram::open(&engram, &keyring)?;
+            let manifest = manifest.unwrap_or_else(|| PathBuf::from("manifest.json"));
+            let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+            let encoding = manifest_data.encoding();
+            sharded.write_file(&find(&manifest_data)?, &encoding.vsa, encoding.chunk_size, &mut stdout)
+        } else if lazy_engram::is_lazy_loadable(&engram)? {
+            let lazy = LazyEngram::open(&engram, &keyring)?;
+            let manifest = manifest.unwrap_or_else(|| PathBuf::from("manifest.json"));
+            let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+            let encoding = manifest_data.encoding();
+            lazy.write_file(&find(&manifest_data)?, &encoding.vsa, encoding.chunk_size, &mut stdout)
+        } else {
+            let (engram_data, manifest_data) = if append_log::is_append_log(&engram)? {
+                let (engram_data, logged) = EmbrFS::load_append_log(&engram)?;
+                match &manifest {
+                    Some(m) => (engram_data, EmbrFS::load_manifest_with_keys(m, &keyring)?),
+                    None => (engram_data, logged),
+                }
+            } else {
+                let manifest = manifest.unwrap_or_else(|| PathBuf::from("manifest.json"));
+                (
+                    EmbrFS::load_engram_with_keys(&engram, &keyring)?,
+                    EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+                )
+            };
+            let entry = find(&manifest_data)?;
+            let encoding = manifest_data.encoding();
+            EmbrFS::reconstruct_file(&engram_data, &entry, &encoding.vsa, encoding.chunk_size, |data| stdout.write_all(data)).and_then(
+                |mismatch| match mismatch {
+                    Some(mismatch) => Err(EmbrError::ManifestMismatch(vec![mismatch]).into()),
+                    None => Ok(()),
+                },
+            )
+        };
+
+        match result.and_then(|()| stdout.flush()) {
+            // The reader went away (e.g. `| head`); that is not a failure.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            other => other,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct ShellArgs {
+    /// Engram to open on start (or use `open` at the prompt)
+    #[arg(short, long, value_name = "FILE")]
+    pub engram: Option<PathBuf>,
+
+    /// Manifest file (default: the one stored in an append log, else manifest.json)
+    #[arg(short, long, value_name = "FILE", requires = "engram")]
+    pub manifest: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl ShellArgs {
+    pub(super) fn run(self) -> io::Result<()> {
+        let ShellArgs {
+            engram,
+            manifest,
+            keys: KeyArgs { keys },
+        } = self;
+        let mut session = super::shell::Session::new(build_keyring(&keys)?);
+        if let Some(engram) = engram {
+            session.open(&engram, manifest.as_deref())?;
+        }
+        super::shell::run(session)
+    }
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Comma-separated vector dimensions for the micro benchmarks
+    #[arg(long, value_delimiter = ',', value_name = "DIMS")]
+    pub dims: Vec<usize>,
+
+    /// Fraction of non-zero trits in micro benchmark operands
+    #[arg(long, default_value_t = 0.01)]
+    pub density: f64,
+
+    /// Minimum measured time per micro benchmark, in milliseconds
+    #[arg(long, value_name = "MS")]
+    pub min_time_ms: Option<u64>,
+
+    /// Size of the generated corpus for ingest/extract, in MiB
+    #[arg(long, value_name = "MIB")]
+    pub corpus_mib: Option<usize>,
+
+    /// Short run: one small dimension, brief timings and a small corpus
+    #[arg(long)]
+    pub quick: bool,
+
+    /// Skip the micro benchmarks
+    #[arg(long)]
+    pub no_micro: bool,
+
+    /// Skip the ingest/extract benchmarks
+    #[arg(long)]
+    pub no_macro: bool,
+
+    /// Write the JSON report here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Earlier JSON report to compare against
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// Allowed slowdown against --baseline, as a fraction
+    #[arg(long, default_value_t = 0.2)]
+    pub tolerance: f64,
+
+    /// Measure and save the hybrid representation thresholds for this CPU
+    #[arg(long, conflicts_with_all = ["dims", "no_micro", "no_macro", "baseline", "corpus_mib"])]
+    pub calibrate: bool,
+}
+
+impl BenchArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let BenchArgs {
+            dims,
+            density,
+            min_time_ms,
+            corpus_mib,
+            quick,
+            no_micro,
+            no_macro,
+            output,
+            baseline,
+            tolerance,
+            calibrate,
+        } = self;
+        let mut opts = if quick { BenchOptions::quick() } else { BenchOptions::default() };
+        if let Some(ms) = min_time_ms {
+            opts.min_time = std::time::Duration::from_millis(ms);
+        }
+        if calibrate {
+            let path = HybridThresholds::path().ok_or_else(|| {
+                io::Error::other("no calibration path; set EMBEDDENATOR_CALIBRATION or HOME")
+            })?;
+            let thresholds = bench::calibrate(opts.min_time);
+            thresholds.save(&path)?;
+            let json = serde_json::to_string_pretty(&thresholds)?;
+            if let Some(out) = &output {
+                std::fs::write(out, json.clone() + "\n")?;
+            }
+            if output.is_none() || json_output {
+                println!("{json}");
+            }
+            if !json_output {
+                eprintln!("Saved calibration to {}", path.display());
+            }
+            return Ok(());
+        }
+        if !dims.is_empty() {
+            opts.dims = dims;
+        }
+        opts.density = density;
+        if let Some(mib) = corpus_mib {
+            opts.corpus_bytes = mib << 20;
+        }
+        opts.micro = !no_micro;
+        opts.macro_ = !no_macro;
+
+        let report = bench::run(&opts)?;
+        let json = serde_json::to_string_pretty(&report)?;
+        if let Some(path) = &output {
+            std::fs::write(path, json.clone() + "\n")?;
+        }
+        if output.is_none() || json_output {
+            println!("{json}");
+        }
+
+        if let Some(path) = baseline {
+            let before: BenchReport = serde_json::from_slice(&std::fs::read(&path)?)?;
+            let regressions = report.regressions(&before, tolerance);
+            for r in &regressions {
+                eprintln!(
+                    "regression: {} {:.1}% slower ({:.3e} -> {:.3e})",
+                    r.name,
+                    r.slowdown * 100.0,
+                    r.baseline,
+                    r.current
+                );
+            }
+            if !regressions.is_empty() {
+                return Err(io::Error::other(format!(
+                    "{} benchmark(s) regressed by more than {:.0}% against {}",
+                    regressions.len(),
+                    tolerance * 100.0,
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl VerifyArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let VerifyArgs {
+            paths: EngramArgs { engram, manifest },
+            keys: KeyArgs { keys },
+            verbose,
+        } = self;
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - Verify",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("=========================");
+        }
+
+        let keyring = build_keyring(&keys)?;
+        let engram_data = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let config = manifest_data.encoding().vsa;
+
+        let report = EmbrFS::verify_with_cache(&engram_data, &manifest_data, &config, &ChunkCache::default())?;
+        if json_output {
+            print_json(&report)?;
+        } else {
+            for mismatch in &report.mismatches {
+                println!("MISMATCH {}", mismatch);
+            }
+            println!(
+                "Verified: {}  Mismatched: {}  Unchecked (no checksum): {}",
+                report.files_verified,
+                report.mismatches.len(),
+                report.files_unchecked
+            );
+        }
+
+        if report.is_ok() {
+            Ok(())
+        } else {
+            Err(EmbrError::ManifestMismatch(report.mismatches).into())
+        }
+    }
+}
+
+/// The provenance section of `info` in text form.
+fn print_provenance(p: &IngestProvenance, issues: Option<&[ProvenanceIssue]>) {
+    println!("Provenance:");
+    println!("  Tool:            {} {}", p.tool, p.tool_version);
+    println!("  Encoder:         {} ({})", p.encoder, p.encoder_hash);
+    println!("  Config:          {}", p.config_hash);
+    println!("  Chunking:        {} ({} bytes)", p.chunking.strategy, p.chunking.chunk_size);
+    println!("  Reproducible:    {}", if p.reproducible { "yes" } else { "no" });
+    if let Some(host) = &p.hostname {
+        println!("  Host:            {}", host);
+    }
+    if let Some(at) = p.recorded_at {
+        println!("  Recorded at:     {} (unix)", at);
+    }
+    for source in &p.sources {
+        println!("  Source:          {}", source);
+    }
+    println!("  Inputs:          {} files, {} bytes ({})", p.input_files, p.input_bytes, p.input_digest);
+    println!("  Engram digest:   {}", p.engram_digest);
+    match issues {
+        Some([]) => println!("  Check:           ok"),
+        Some(issues) => {
+            for issue in issues {
+                println!("  Check:           {}", issue);
+            }
+        }
+        None => println!("  Check:           not checked"),
+    }
+}
+
+/// Heatmap cells as shades, darkest for `max` accesses.
+fn heat_cells(cells: &[u64], max: u64) -> String {
+    const SHADES: [char; 5] = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+    let top = SHADES.len() as u64 - 1;
+    cells
+        .iter()
+        .map(|&heat| match heat {
+            0 => SHADES[0],
+            _ => SHADES[(1 + (heat * top - 1) / max.max(1)).min(top) as usize],
+        })
+        .collect()
+}
@@ -0,0 +1,521 @@
+//! Moving data between engrams and other tools: `export`, git archives and
+//! vector databases.
+
+use super::{build_keyring, print_json, EngramArgs, KeyArgs};
+#[cfg(feature = "vector-sync")]
+use super::VectorBackendArg;
+use crate::embrfs::EmbrFS;
+#[cfg(feature = "vector-sync")]
+use crate::delta::EngramDelta;
+use crate::dense_export::{self, DenseDtype};
+use crate::quantized_export::{self, QuantizedFormat};
+use crate::export;
+use crate::envelope::BinaryWriteOptions;
+use crate::vsa::ReversibleVSAConfig;
+use clap::Args;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Output directory
+    #[arg(short, long, default_value = "export", value_name = "DIR")]
+    pub output: PathBuf,
+
+    /// What to write
+    #[arg(long, value_enum, default_value_t = ExportFormatArg::Parquet)]
+    pub format: ExportFormatArg,
+
+    /// Element type for --format npy (faiss is always float32)
+    #[arg(long, value_enum, default_value_t = DenseDtypeArg::Float32)]
+    pub dtype: DenseDtypeArg,
+
+    /// Trit layout for --format quantized
+    #[arg(long, value_enum, default_value_t = PackingArg::Packed2)]
+    pub packing: PackingArg,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl ExportArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ExportArgs {
+            paths: EngramArgs { engram, manifest },
+            output,
+            format,
+            dtype,
+            packing,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let dense = match format {
+            ExportFormatArg::Parquet => {
+                let summary = export::to_parquet(
+                    &engram,
+                    &EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+                    &output,
+                )?;
+                if json_output {
+                    print_json(&serde_json::json!({
+                        "output": output,
+                        "files": summary.files,
+                        "chunks": summary.chunks,
+                    }))?;
+                } else {
+                    println!(
+                        "Exported {} files and {} chunk references to {}",
+                        summary.files,
+                        summary.chunks,
+                        output.display()
+                    );
+                }
+                return Ok(());
+            }
+            ExportFormatArg::Npy => dense_export::to_npy(&engram, dtype.into(), &output)?,
+            ExportFormatArg::Faiss => dense_export::to_faiss(&engram, &output)?,
+            ExportFormatArg::Quantized => {
+                let meta = quantized_export::export_engram(&engram, packing.into(), &output)?;
+                if json_output {
+                    print_json(&serde_json::json!({ "output": output, "metadata": meta }))?;
+                } else {
+                    println!(
+                        "Exported {} chunk signatures of dimension {} ({:?}, density {:.4}) to {}",
+                        meta.rows,
+                        meta.dim,
+                        meta.format,
+                        meta.density,
+                        output.display()
+                    );
+                }
+                return Ok(());
+            }
+        };
+        if json_output {
+            print_json(&serde_json::json!({ "output": output, "rows": dense.rows, "dim": dense.dim }))?;
+        } else {
+            println!(
+                "Exported {} chunk vectors of dimension {} to {}",
+                dense.rows,
+                dense.dim,
+                output.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ImportSignaturesArgs {
+    /// Directory holding signatures.json, signatures.npy and ids.npy
+    #[arg(short, long, value_name = "DIR")]
+    pub input: PathBuf,
+
+    /// Chunk vectors file to write
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+}
+
+impl ImportSignaturesArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ImportSignaturesArgs { input, output } = self;
+        let (meta, vectors) = quantized_export::import(&input)?;
+        vectors.save(&output, BinaryWriteOptions::default())?;
+        if json_output {
+            print_json(&serde_json::json!({ "output": output, "metadata": meta }))?;
+        } else {
+            println!(
+                "Imported {} chunk signatures of dimension {} ({:?}, density {:.4}) into {}",
+                meta.rows,
+                meta.dim,
+                meta.format,
+                meta.density,
+                output.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct IngestGitArgs {
+    /// Repository to read (any directory inside a work tree, or a bare repository)
+    #[arg(value_name = "REPO")]
+    pub repo: PathBuf,
+
+    /// Archive directory
+    #[arg(short, long, default_value = "git-archive", value_name = "DIR")]
+    pub output: PathBuf,
+
+    /// Commits to archive, as accepted by `git log`
+    #[arg(long, default_value = "HEAD", value_name = "RANGE", allow_hyphen_values = true)]
+    pub range: String,
+
+    /// Print each archived commit
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl IngestGitArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let IngestGitArgs {
+            repo,
+            output,
+            range,
+            verbose,
+        } = self;
+        let verbose = verbose && !json_output;
+        use crate::git_archive::{GitArchive, GitIngestOptions};
+        let options = GitIngestOptions { range: Some(range), verbose };
+        let summary = GitArchive::ingest(&repo, &output, &options, &ReversibleVSAConfig::default())?;
+        if json_output {
+            print_json(&serde_json::json!({
+                "output": output,
+                "commits_added": summary.commits_added,
+                "commits_present": summary.commits_present,
+                "blobs_encoded": summary.blobs_encoded,
+                "files_reused": summary.files_reused,
+                "chunks_added": summary.chunks_added,
+            }))?;
+        } else {
+            println!(
+                "Archived {} new commits to {} ({} already present): {} blobs encoded, {} files reused, {} chunks added",
+                summary.commits_added,
+                output.display(),
+                summary.commits_present,
+                summary.blobs_encoded,
+                summary.files_reused,
+                summary.chunks_added
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ExtractGitArgs {
+    /// Archive directory written by ingest-git
+    #[arg(value_name = "ARCHIVE")]
+    pub archive: PathBuf,
+
+    /// Commit to extract
+    #[arg(default_value = "HEAD", value_name = "REV")]
+    pub rev: String,
+
+    /// Extract the commit current at this time (or revision) instead of REV
+    #[arg(long, value_name = "WHEN", conflicts_with = "rev")]
+    pub as_of: Option<String>,
+
+    /// Output directory
+    #[arg(short, long, value_name = "DIR", required_unless_present = "list")]
+    pub output_dir: Option<PathBuf>,
+
+    /// List archived commits, newest first
+    #[arg(long)]
+    pub list: bool,
+}
+
+impl ExtractGitArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ExtractGitArgs {
+            archive,
+            rev,
+            as_of,
+            output_dir,
+            list,
+        } = self;
+        let archive = crate::git_archive::GitArchive::open(&archive)?;
+        if list && json_output {
+            let commits: Vec<serde_json::Value> = archive
+                .commits()
+                .iter()
+                .rev()
+                .map(|commit| {
+                    let refs: Vec<&str> = archive
+                        .refs()
+                        .iter()
+                        .filter(|(_, id)| **id == commit.id)
+                        .map(|(name, _)| name.as_str())
+                        .collect();
+                    serde_json::json!({ "id": commit.id, "summary": commit.summary, "refs": refs })
+                })
+                .collect();
+            return print_json(&commits);
+        }
+        if list {
+            for commit in archive.commits().iter().rev() {
+                let names: Vec<&str> = archive
+                    .refs()
+                    .iter()
+                    .filter(|(_, id)| **id == commit.id)
+                    .map(|(name, _)| name.trim_start_matches("refs/heads/").trim_start_matches("refs/tags/"))
+                    .collect();
+                let decoration = if names.is_empty() { String::new() } else { format!(" ({})", names.join(", ")) };
+                println!("{}{} {}", &commit.id[..12.min(commit.id.len())], decoration, commit.summary);
+            }
+            return Ok(());
+        }
+        let output_dir = output_dir.expect("clap requires --output-dir without --list");
+        let commit = match as_of {
+            Some(when) => archive.resolve_as_of(&when)?,
+            None => archive.resolve(&rev)?,
+        }
+        .id
+        .clone();
+        archive.extract(&commit, &output_dir, &ReversibleVSAConfig::default())?;
+        if json_output {
+            print_json(&serde_json::json!({ "commit": commit, "output_dir": output_dir }))?;
+        } else {
+            println!("Extracted {} to {}", commit, output_dir.display());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct QueryGitArgs {
+    /// Archive directory written by ingest-git
+    #[arg(value_name = "ARCHIVE")]
+    pub archive: PathBuf,
+
+    /// Query file to search for
+    #[arg(short, long, value_name = "FILE", help_heading = "Required")]
+    pub query: PathBuf,
+
+    /// Snapshot to search
+    #[arg(long, default_value = "HEAD", value_name = "WHEN")]
+    pub as_of: String,
+
+    /// Also search this earlier snapshot and show how the results changed
+    #[arg(long, value_name = "WHEN")]
+    pub diff_from: Option<String>,
+
+    /// Number of matches per snapshot
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+}
+
+impl QueryGitArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let QueryGitArgs {
+            archive,
+            query,
+            as_of,
+            diff_from,
+            k,
+        } = self;
+        let archive = crate::git_archive::GitArchive::open(&archive)?;
+        let data = std::fs::read(&query)?;
+        let config = ReversibleVSAConfig::default();
+        let to = archive.resolve_as_of(&as_of)?.id.clone();
+        let short = |id: &str| id[..12.min(id.len())].to_string();
+        let print_hits = |hits: &[crate::git_archive::SnapshotHit], mark: &str| {
+            for hit in hits {
+                println!("{}chunk {:>6}  cosine {:.4}  {}", mark, hit.chunk, hit.cosine, hit.paths.join(", "));
+            }
+        };
+        let Some(from) = diff_from else {
+            let hits = archive.query(&to, &data, k, &config)?;
+            if json_output {
+                return print_json(&serde_json::json!({ "commit": to, "hits": hits }));
+            }
+            println!("Top {} matches at {}:", hits.len(), short(&to));
+            print_hits(&hits, "  ");
+            return Ok(());
+        };
+        let from = archive.resolve_as_of(&from)?.id.clone();
+        let diff = archive.query_diff(&from, &to, &data, k, &config)?;
+        if json_output {
+            return print_json(&diff);
+        }
+        println!(
+            "Matches from {} to {}: {} added, {} removed, {} kept",
+            short(&diff.from),
+            short(&diff.to),
+            diff.added.len(),
+            diff.removed.len(),
+            diff.kept.len()
+        );
+        print_hits(&diff.added, "+ ");
+        print_hits(&diff.removed, "- ");
+        print_hits(&diff.kept, "  ");
+        Ok(())
+    }
+}
+
+#[cfg(feature = "vector-sync")]
+#[derive(Args)]
+pub struct SyncVectorsArgs {
+    /// Target database (required here or as `vector_sync.backend` in the profile)
+    #[arg(long, value_enum)]
+    pub backend: Option<VectorBackendArg>,
+
+    /// REST endpoint of the database (required here or as `vector_sync.url`)
+    #[arg(long, value_name = "URL")]
+    pub url: Option<String>,
+
+    /// Collection to write to (required here or as `vector_sync.collection`)
+    #[arg(long, value_name = "NAME")]
+    pub collection: Option<String>,
+
+    /// Qdrant API key or Milvus token
+    #[arg(long, value_name = "TOKEN")]
+    pub token: Option<String>,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Previously synced engram; only changes since it are sent
+    #[arg(long, value_name = "FILE", requires = "base_manifest")]
+    pub base_engram: Option<PathBuf>,
+
+    /// Manifest of the previously synced engram
+    #[arg(long, value_name = "FILE", requires = "base_engram")]
+    pub base_manifest: Option<PathBuf>,
+
+    /// Points per request
+    #[arg(long, default_value_t = crate::vector_sync::DEFAULT_BATCH_SIZE)]
+    pub batch_size: usize,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+#[cfg(feature = "vector-sync")]
+impl SyncVectorsArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let SyncVectorsArgs {
+            backend,
+            url,
+            collection,
+            token,
+            paths: EngramArgs { engram, manifest },
+            base_engram,
+            base_manifest,
+            batch_size,
+            keys: KeyArgs { keys },
+        } = self;
+        use crate::embrfs::{Engram, Manifest};
+        use crate::vector_sync::{MilvusSink, QdrantSink, SyncSummary, VectorSink, VectorSync};
+
+        fn run_sync<S: VectorSink>(
+            sink: S,
+            batch_size: usize,
+            base: Option<(Engram, Manifest)>,
+            engram: &Engram,
+            manifest: &Manifest,
+        ) -> io::Result<SyncSummary> {
+            let sync = VectorSync::new(sink).with_batch_size(batch_size);
+            match base {
+                Some((base_engram, base_manifest)) => {
+                    let delta = EngramDelta::between(&base_engram, &base_manifest, engram, manifest)?;
+                    sync.sync_delta(&base_manifest, &delta, engram, manifest)
+                }
+                None => sync.sync_all(engram, manifest),
+            }
+        }
+
+        let required = |flag: &str, setting: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{flag} is required (or set {setting} in the configuration profile)"),
+            )
+        };
+        let backend = backend.ok_or_else(|| required("--backend", "vector_sync.backend"))?;
+        let url = url.ok_or_else(|| required("--url", "vector_sync.url"))?;
+        let collection = collection.ok_or_else(|| required("--collection", "vector_sync.collection"))?;
+
+        let keyring = build_keyring(&keys)?;
+        let engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        let manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let base = match (base_engram, base_manifest) {
+            (Some(e), Some(m)) => Some((
+                EmbrFS::load_engram_with_keys(&e, &keyring)?,
+                EmbrFS::load_manifest_with_keys(&m, &keyring)?,
+            )),
+            _ => None,
+        };
+        let summary = match backend {
+            VectorBackendArg::Qdrant => {
+                let sink = QdrantSink::new(url.as_str(), collection.as_str());
+                let sink = match token {
+                    Some(t) => sink.with_api_key(t),
+                    None => sink,
+                };
+                run_sync(sink, batch_size, base, &engram, &manifest)?
+            }
+            VectorBackendArg::Milvus => {
+                let sink = MilvusSink::new(url.as_str(), collection.as_str());
+                let sink = match token {
+                    Some(t) => sink.with_token(t),
+                    None => sink,
+                };
+                run_sync(sink, batch_size, base, &engram, &manifest)?
+            }
+        };
+        if json_output {
+            print_json(&serde_json::json!({
+                "collection": collection,
+                "url": url,
+                "upserted": summary.upserted,
+                "deleted": summary.deleted,
+                "requests": summary.requests,
+            }))?;
+        } else {
+            println!(
+                "Synced {} to {}: {} points upserted, {} deleted in {} requests",
+                collection, url, summary.upserted, summary.deleted, summary.requests
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormatArg {
+    /// files.parquet and chunks.parquet metadata tables
+    Parquet,
+    /// Dense chunk vectors as vectors.npy plus ids.npy
+    Npy,
+    /// Dense chunk vectors as a FAISS IndexIDMap/IndexFlatIP file
+    Faiss,
+    /// Chunk vectors as int8 or packed 2-bit trits (signatures.npy, ids.npy)
+    /// with signatures.json metadata
+    Quantized,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum DenseDtypeArg {
+    Int8,
+    Float32,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum PackingArg {
+    /// One int8 per trit
+    Int8,
+    /// Four trits per byte
+    Packed2,
+}
+
+impl From<PackingArg> for QuantizedFormat {
+    fn from(v: PackingArg) -> Self {
+        match v {
+            PackingArg::Int8 => QuantizedFormat::Int8,
+            PackingArg::Packed2 => QuantizedFormat::Packed2,
+        }
+    }
+}
+
+impl From<DenseDtypeArg> for DenseDtype {
+    fn from(v: DenseDtypeArg) -> Self {
+        match v {
+            DenseDtypeArg::Int8 => DenseDtype::Int8,
+            DenseDtypeArg::Float32 => DenseDtype::Float32,
+        }
+    }
+}
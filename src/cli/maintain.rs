@@ -0,0 +1,943 @@
+//! Commands that rewrite an engram in place or alongside it: `convert`, `seal`,
+//! `repair`, `rekey`, `scrub`, `gc` and friends.
+
+use super::{build_keyring, parse_id_map_arg, parse_key_arg, parse_key_rule_arg, print_json, read_key_file, ChecksumArg, CompressionArg, EngramArgs, KeyArgs};
+use crate::embrfs::{
+    EmbrFS, Engram, Manifest,
+};
+use crate::append_log;
+use crate::convert::{self, ConvertOptions, TargetFormat};
+use crate::envelope::{
+    BinaryWriteOptions, EncryptionCodec, EncryptionKey,
+    EnvelopeHeader,
+};
+use crate::retention::{self, RetentionPolicy};
+use crate::manifest_schema::{self, Versioned};
+use crate::info::StorageFormat;
+use crate::namespace_keys::{self, KeyRule};
+use crate::replica;
+use crate::scrub;
+use crate::signing;
+use clap::{Args, Subcommand};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Actions of `embeddenator manifest`.
+#[derive(Subcommand)]
+pub enum ManifestCommand {
+    /// Rewrite a manifest in the current schema version
+    Upgrade {
+        /// Manifest file
+        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+        manifest: PathBuf,
+
+        /// Write the upgraded manifest here instead of over the original
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Report the migrations without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// Write a copy of a manifest in an older schema version
+    Downgrade {
+        /// Manifest file
+        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+        manifest: PathBuf,
+
+        /// Schema version to write
+        #[arg(long, value_name = "VERSION")]
+        to: u32,
+
+        /// Where to write the copy
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+}
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Engram file to convert
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Manifest file (default: the one stored in an append log, else manifest.json)
+    #[arg(short, long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
+    /// Where to write the converted engram (default: replace the input)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Storage format to write
+    #[arg(long, default_value = "envelope", value_enum)]
+    pub to: ConvertFormatArg,
+
+    /// Compression for the envelope format
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    /// Compression level (codec-dependent; used for zstd)
+    #[arg(long, value_name = "LEVEL")]
+    pub compression_level: Option<i32>,
+
+    /// Per-frame and whole-file checksums for the envelope format
+    #[arg(long, default_value = "none", value_enum)]
+    pub checksum: ChecksumArg,
+
+    /// Split the envelope into `<output>.partNNNN` files of at most this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub part_size: Option<u64>,
+
+    /// Shard the codebook into `<output>.shardNNNN` files of this many chunk ids each
+    #[arg(long, value_name = "N", conflicts_with = "part_size")]
+    pub shard_chunks: Option<u64>,
+
+    /// Encrypt the converted engram (and --manifest-out) with this 32-byte hex key file
+    #[arg(long, value_name = "FILE")]
+    pub encrypt_key: Option<PathBuf>,
+
+    /// Key id recorded in the envelope header
+    #[arg(long, default_value_t = 1, value_name = "ID", requires = "encrypt_key")]
+    pub key_id: u32,
+
+    /// Also write the manifest here; needed when converting an append log to
+    /// another format, since only append logs carry their own manifest
+    #[arg(long, value_name = "FILE")]
+    pub manifest_out: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl ConvertArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ConvertArgs {
+            engram,
+            manifest,
+            output,
+            to,
+            compression,
+            compression_level,
+            checksum,
+            part_size,
+            shard_chunks,
+            encrypt_key,
+            key_id,
+            manifest_out,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let source_is_log = append_log::is_append_log(&engram)?;
+        let target: TargetFormat = to.into();
+        if source_is_log && target != TargetFormat::AppendLog && manifest.is_none() && manifest_out.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the manifest is stored in the append log; pass --manifest-out to keep it",
+            ));
+        }
+
+        let mut fs = EmbrFS::new();
+        if source_is_log {
+            let (engram_data, logged) = EmbrFS::load_append_log(&engram)?;
+            fs.engram = engram_data;
+            fs.manifest = match &manifest {
+                Some(m) => EmbrFS::load_manifest_with_keys(m, &keyring)?,
+                None => logged,
+            };
+        } else {
+            let manifest = manifest.unwrap_or_else(|| PathBuf::from("manifest.json"));
+            fs.engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+            fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        }
+        let before = StorageFormat::detect(&engram)?;
+
+        let key = encrypt_key
+            .as_deref()
+            .map(|path| EncryptionKey::from_hex(key_id, &read_key_file(path)?))
+            .transpose()?;
+        let encryption = if key.is_some() {
+            EncryptionCodec::XChaCha20Poly1305
+        } else {
+            EncryptionCodec::None
+        };
+        let opts = ConvertOptions {
+            format: target,
+            write: BinaryWriteOptions {
+                codec: compression.into(),
+                level: compression_level,
+                checksum: checksum.into(),
+                encryption,
+                key,
+            },
+            part_size,
+            shard_chunks,
+        };
+        let dest = output.unwrap_or_else(|| engram.clone());
+        let report = convert::convert(&fs, &dest, &opts, &fs.vsa_config())?;
+
+        if let Some(path) = &manifest_out {
+            fs.save_manifest_with_options(
+                path,
+                BinaryWriteOptions {
+                    encryption,
+                    key,
+                    ..Default::default()
+                },
+            )?;
+        }
+        let stale_signature = signing::default_signature_path(&dest);
+        let stale_signature = stale_signature.exists().then_some(stale_signature);
+
+        if json_output {
+            print_json(&serde_json::json!({
+                "engram": dest,
+                "manifest": manifest_out,
+                "from": before,
+                "to": report.format,
+                "files_verified": report.files_verified,
+                "bytes_verified": report.bytes_verified,
+                "stale_signature": stale_signature,
+            }))?;
+        } else {
+            println!(
+                "Converted {} ({}) -> {} ({})",
+                engram.display(),
+                before.container,
+                dest.display(),
+                report.format.container
+            );
+            println!(
+                "Verified: {} files, {} bytes",
+                report.files_verified, report.bytes_verified
+            );
+            if let Some(path) = stale_signature {
+                eprintln!(
+                    "warning: {} signs the old engram; re-sign the converted one",
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct KeygenArgs {
+    /// Output path for the secret key (public key goes to `<path>.pub`)
+    #[arg(long, value_name = "FILE", help_heading = "Required")]
+    pub out: PathBuf,
+}
+
+impl KeygenArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let KeygenArgs { out } = self;
+        let (secret, public) = signing::generate_keypair()?;
+        std::fs::write(&out, format!("{}\n", secret))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&out, std::fs::Permissions::from_mode(0o600))?;
+        }
+        let mut pub_path = out.into_os_string();
+        pub_path.push(".pub");
+        let pub_path = PathBuf::from(pub_path);
+        std::fs::write(&pub_path, format!("{}\n", public))?;
+        if json_output {
+            print_json(&serde_json::json!({ "public_key": public, "public_key_file": pub_path }))?;
+        } else {
+            println!("Public key: {}", public);
+            println!("Wrote public key: {}", pub_path.display());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct SealArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Ed25519 secret key file to sign the seal with
+    #[arg(long, value_name = "FILE", required_unless_present = "check")]
+    pub sign_key: Option<PathBuf>,
+
+    /// Verify an existing seal instead of sealing
+    #[arg(long, conflicts_with = "sign_key")]
+    pub check: bool,
+
+    /// Public key file the seal must be signed with (with --check)
+    #[arg(long, value_name = "FILE", requires = "check")]
+    pub trusted_key: Option<PathBuf>,
+}
+
+impl SealArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let SealArgs {
+            paths: EngramArgs { engram, manifest },
+            sign_key,
+            check,
+            trusted_key,
+        } = self;
+        let seal = if check {
+            let trusted = trusted_key.as_deref().map(read_key_file).transpose()?;
+            crate::seal::verify_seal(&engram, &manifest, trusted.as_deref())?
+        } else {
+            let key_path = sign_key.expect("clap requires --sign-key without --check");
+            crate::seal::seal_files(&engram, &manifest, &read_key_file(&key_path)?)?
+        };
+        if json_output {
+            print_json(&seal)?;
+        } else {
+            println!(
+                "{} {} and {} (sealed at unix time {} by {})",
+                if check { "Seal intact:" } else { "Sealed" },
+                engram.display(),
+                manifest.display(),
+                seal.sealed_at,
+                seal.signature.public_key
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct RepairArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Replica of the same engram to take chunks from. Repeatable; tried in order.
+    #[arg(long = "peer", value_name = "FILE", required = true)]
+    pub peers: Vec<PathBuf>,
+
+    /// Write the repaired engram here instead of over the original
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Report what would be repaired without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Compression for the rewritten engram
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl RepairArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let RepairArgs {
+            paths: EngramArgs { engram, manifest },
+            peers,
+            output,
+            dry_run,
+            compression,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let mut fs = EmbrFS::new();
+        fs.engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let replicas = peers
+            .iter()
+            .map(|p| EmbrFS::load_engram_with_keys(p, &keyring))
+            .collect::<Result<Vec<_>, _>>()?;
+        let replica_refs: Vec<&Engram> = replicas.iter().collect();
+        let report = replica::repair(&mut fs.engram, &fs.manifest, &replica_refs, &fs.manifest.encoding().vsa)?;
+
+        let output = output.unwrap_or_else(|| engram.clone());
+        let written = !dry_run && !report.repaired.is_empty();
+        if written {
+            crate::seal::ensure_unsealed(&output)?;
+            let dir = match output.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            };
+            let staged = tempfile::NamedTempFile::new_in(dir)?;
+            fs.save_engram_with_options(
+                staged.path(),
+                BinaryWriteOptions {
+                    codec: compression.into(),
+                    ..Default::default()
+                },
+            )?;
+            staged.persist(&output).map_err(|e| e.error)?;
+        }
+        if json_output {
+            print_json(&serde_json::json!({
+                "engram": output,
+                "written": written,
+                "report": report,
+            }))?;
+        } else {
+            for chunk in &report.repaired {
+                println!("chunk {} <- {}", chunk.chunk_id, peers[chunk.peer].display());
+            }
+            for path in &report.unrepaired {
+                println!("UNREPAIRED {}", path);
+            }
+            println!(
+                "Checked: {}  Damaged: {}  Chunks repaired: {}  Unrepaired: {}{}",
+                report.files_checked,
+                report.damaged.len(),
+                report.repaired.len(),
+                report.unrepaired.len(),
+                if dry_run { " (dry run)" } else { "" }
+            );
+        }
+        if report.is_ok() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} file(s) could not be repaired", report.unrepaired.len()),
+            ))
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct RekeyArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Encrypt files under PREFIX with key ID. Repeatable; replaces the current rules
+    #[arg(long = "rule", value_name = "PREFIX=ID", value_parser = parse_key_rule_arg)]
+    pub rules: Vec<KeyRule>,
+
+    /// Move every rule using key FROM to key TO. Repeatable.
+    #[arg(long = "rotate", value_name = "FROM:TO", value_parser = parse_id_map_arg)]
+    pub rotate: Vec<(u32, u32)>,
+
+    /// Remove every rule, moving all chunks back into the engram
+    #[arg(long, conflicts_with_all = ["rules", "rotate"])]
+    pub clear: bool,
+
+    /// Compression for the rewritten groups and engram
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    /// Namespace key as ID=FILE (32-byte hex). Repeatable; old and new keys alike.
+    #[arg(long = "key", value_name = "ID=FILE", value_parser = parse_key_arg)]
+    pub keys: Vec<(u32, PathBuf)>,
+}
+
+impl RekeyArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let RekeyArgs {
+            paths: EngramArgs { engram, manifest },
+            rules,
+            rotate,
+            clear,
+            compression,
+            keys,
+        } = self;
+        if rules.is_empty() && rotate.is_empty() && !clear {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "nothing to do: pass --rule, --rotate or --clear",
+            ));
+        }
+        let storage = StorageFormat::detect(&engram)?;
+        if !matches!(storage.container.as_str(), "bincode" | "envelope") || storage.encrypted {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "rekey rewrites {} as a single unencrypted file, not {}{}; convert it first",
+                    engram.display(),
+                    storage.container,
+                    if storage.encrypted { " (encrypted)" } else { "" }
+                ),
+            ));
+        }
+        let mut header = Vec::new();
+        File::open(&manifest)?.take(16).read_to_end(&mut header)?;
+        if EnvelopeHeader::parse(&header).is_some_and(|h| h.encrypted) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("rekey rewrites {} unencrypted; decrypt it first", manifest.display()),
+            ));
+        }
+        let keyring = build_keyring(&keys)?;
+        let mut fs = EmbrFS::new();
+        fs.engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let mut new_rules = match (&fs.manifest.namespace_keys, rules.is_empty()) {
+            _ if clear => Vec::new(),
+            (Some(current), true) => current.rules.clone(),
+            _ => rules,
+        };
+        for (from, to) in &rotate {
+            for rule in new_rules.iter_mut().filter(|r| r.key_id == *from) {
+                rule.key_id = *to;
+            }
+        }
+        let opts = BinaryWriteOptions {
+            codec: compression.into(),
+            ..Default::default()
+        };
+        let report = namespace_keys::rekey_saved(&mut fs, &engram, &manifest, new_rules, &keyring, opts)?;
+        let stale_signature = signing::default_signature_path(&engram);
+        let stale_signature = stale_signature.exists().then_some(stale_signature);
+
+        if json_output {
+            print_json(&serde_json::json!({
+                "engram": engram,
+                "manifest": manifest,
+                "rules": fs.manifest.namespace_keys.as_ref().map(|k| &k.rules),
+                "report": report,
+                "stale_signature": stale_signature,
+            }))?;
+        } else {
+            for (key_id, chunks) in &report.written {
+                println!("key {}: wrote {} chunks", key_id, chunks);
+            }
+            for key_id in &report.unchanged {
+                println!("key {}: unchanged", key_id);
+            }
+            for key_id in &report.removed {
+                println!("key {}: removed", key_id);
+            }
+            if report.engram_changed {
+                println!("Rewrote {}", engram.display());
+            }
+            if let Some(path) = stale_signature {
+                eprintln!(
+                    "warning: {} signs the old manifest; re-sign the engram",
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ScrubArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Most chunks checked per second (unlimited if not given)
+    #[arg(long, value_name = "N")]
+    pub rate: Option<f64>,
+
+    /// Passes to run; 0 runs until stopped
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub passes: u64,
+
+    /// Seconds to wait between passes
+    #[arg(long, default_value_t = 3600, value_name = "SECS")]
+    pub interval: u64,
+
+    /// Write the repaired engram here instead of over the original
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Report damage without repairing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Write each pass's result here as JSON, for servers' health checks
+    #[arg(long, value_name = "FILE")]
+    pub status_file: Option<PathBuf>,
+
+    /// Compression for the rewritten engram
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl ScrubArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ScrubArgs {
+            paths: EngramArgs { engram, manifest },
+            rate,
+            passes,
+            interval,
+            output,
+            dry_run,
+            status_file,
+            compression,
+            keys: KeyArgs { keys },
+        } = self;
+        #[cfg(unix)]
+        // SAFETY: nice() only adjusts this process's scheduling priority.
+        unsafe {
+            libc::nice(10);
+        }
+        let keyring = build_keyring(&keys)?;
+        let output = output.unwrap_or_else(|| engram.clone());
+        let opts = scrub::ScrubOptions { rate, repair: !dry_run };
+        let mut pass = 0u64;
+        loop {
+            pass += 1;
+            let mut fs = EmbrFS::new();
+            fs.engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+            fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+            let report = scrub::scrub(&mut fs.engram, &fs.manifest, &fs.manifest.encoding().vsa, &opts)?;
+
+            let written = !dry_run && report.repairable() > 0;
+            if written {
+                crate::seal::ensure_unsealed(&output)?;
+                let dir = match output.parent() {
+                    Some(p) if !p.as_os_str().is_empty() => p,
+                    _ => Path::new("."),
+                };
+                let staged = tempfile::NamedTempFile::new_in(dir)?;
+                fs.save_engram_with_options(
+                    staged.path(),
+                    BinaryWriteOptions {
+                        codec: compression.into(),
+                        ..Default::default()
+                    },
+                )?;
+                staged.persist(&output).map_err(|e| e.error)?;
+            }
+            if let Some(path) = &status_file {
+                scrub::ScrubStatus::now(report.clone()).save(path)?;
+            }
+            if json_output {
+                print_json(&serde_json::json!({
+                    "pass": pass,
+                    "engram": output,
+                    "written": written,
+                    "report": report,
+                }))?;
+            } else {
+                for chunk in &report.damaged {
+                    match chunk.source {
+                        Some(source) => println!("chunk {} ({}) <- {:?}", chunk.chunk_id, chunk.path, source),
+                        None => println!("IRREPARABLE chunk {} ({})", chunk.chunk_id, chunk.path),
+                    }
+                }
+                println!(
+                    "Pass {}: checked {}  unchecked {}  damaged {}  repairable {}  irreparable {}{}",
+                    pass,
+                    report.chunks_checked,
+                    report.chunks_unchecked,
+                    report.damaged.len(),
+                    report.repairable(),
+                    report.irreparable(),
+                    if dry_run { " (dry run)" } else { "" }
+                );
+            }
+            if passes != 0 && pass >= passes {
+                return if report.is_ok() {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} chunk(s) left damaged", report.damaged.len() - if dry_run { 0 } else { report.repairable() }),
+                    ))
+                };
+            }
+            std::thread::sleep(std::time::Duration::from_secs(interval));
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct CompactArgs {
+    /// Append-log engram to compact in place
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+}
+
+impl CompactArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let CompactArgs { engram } = self;
+        let stats = EmbrFS::compact(&engram)?;
+        if json_output {
+            print_json(&serde_json::json!({
+                "engram": engram,
+                "chunks": stats.chunks,
+                "bytes_before": stats.bytes_before,
+                "bytes_after": stats.bytes_after,
+            }))?;
+        } else {
+            println!(
+                "Compacted {}: {} chunks, {} -> {} bytes ({} reclaimed)",
+                engram.display(),
+                stats.chunks,
+                stats.bytes_before,
+                stats.bytes_after,
+                stats.bytes_reclaimed()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct GcArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Remove the expired files and record the purge in the audit log
+    #[arg(long)]
+    pub apply_retention: bool,
+
+    /// Evaluate expiry as of this time (@UNIX, YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS]Z)
+    /// instead of now
+    #[arg(long, value_name = "WHEN")]
+    pub at: Option<String>,
+
+    /// Append the audit record here instead of `<manifest>.retention.jsonl`
+    #[arg(long, value_name = "FILE")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Compression for the rewritten engram
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl GcArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let GcArgs {
+            paths: EngramArgs { engram, manifest },
+            apply_retention,
+            at,
+            audit_log,
+            compression,
+            keys: KeyArgs { keys },
+        } = self;
+        let now = match at.as_deref() {
+            None => retention::unix_now(),
+            Some(spec) => crate::git_archive::parse_time(spec)?
+                .and_then(|t| u64::try_from(t).ok())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid time {:?}", spec))
+                })?,
+        };
+        let keyring = build_keyring(&keys)?;
+        let mut fs = EmbrFS::new();
+        fs.engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+        fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        if fs.manifest.retention.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has no retention policy; see `embeddenator retention`", manifest.display()),
+            ));
+        }
+
+        let audit = if apply_retention {
+            let audit = fs.apply_retention(now)?;
+            if !audit.purged.is_empty() {
+                let opts = BinaryWriteOptions {
+                    codec: compression.into(),
+                    ..Default::default()
+                };
+                fs.save_transactional(&engram, &manifest, opts, Default::default())?;
+            }
+            audit.append_to(audit_log.unwrap_or_else(|| retention::default_audit_path(&manifest)))?;
+            audit
+        } else {
+            fs.retention_preview(now)?
+        };
+
+        if json_output {
+            print_json(&audit)?;
+        } else {
+            let verb = if audit.applied { "Purged" } else { "Would purge" };
+            for file in &audit.purged {
+                println!("{} {} (expired @{}, rule {:?})", verb, file.path, file.expired_at, file.rule);
+            }
+            println!(
+                "{} {} file(s), {} bytes, {} chunk(s); {} file(s) kept",
+                verb,
+                audit.purged.len(),
+                audit.bytes_purged,
+                audit.chunks_removed,
+                audit.files_kept
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct RetentionArgs {
+    /// Manifest file
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+
+    /// Store the policy in this TOML file in the manifest, replacing any other
+    #[arg(long, value_name = "FILE", conflicts_with = "clear")]
+    pub set: Option<PathBuf>,
+
+    /// Remove the manifest's retention policy
+    #[arg(long)]
+    pub clear: bool,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl RetentionArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let RetentionArgs {
+            manifest,
+            set,
+            clear,
+            keys: KeyArgs { keys },
+        } = self;
+        let mut fs = EmbrFS::new();
+        fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &build_keyring(&keys)?)?;
+        if set.is_some() || clear {
+            fs.manifest.retention = set.map(RetentionPolicy::load).transpose()?;
+            fs.save_manifest(&manifest)?;
+        }
+
+        let policy = fs.manifest.retention.clone().unwrap_or_default();
+        let mut expiry = Vec::new();
+        for file in &fs.manifest.files {
+            if let Some(at) = policy.expires_at(file)? {
+                expiry.push((file.path.as_str(), at));
+            }
+        }
+        if json_output {
+            print_json(&serde_json::json!({
+                "manifest": manifest,
+                "retention": fs.manifest.retention,
+                "expiry": expiry
+                    .iter()
+                    .map(|(path, at)| serde_json::json!({"path": path, "expires_at": at}))
+                    .collect::<Vec<_>>(),
+            }))?;
+        } else if fs.manifest.retention.is_none() {
+            println!("{}: no retention policy", manifest.display());
+        } else {
+            for (name, class) in &policy.classes {
+                match class.ttl {
+                    Some(ttl) => println!("class {}: ttl {}s", name, ttl),
+                    None => println!("class {}: kept indefinitely", name),
+                }
+            }
+            for rule in &policy.rules {
+                match (&rule.class, rule.ttl) {
+                    (Some(class), _) => println!("rule {}: class {}", rule.path, class),
+                    (None, ttl) => println!("rule {}: ttl {}s", rule.path, ttl.unwrap_or_default()),
+                }
+            }
+            for (path, at) in &expiry {
+                println!("{} expires @{}", path, at);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ManifestArgs {
+    #[command(subcommand)]
+    pub action: ManifestCommand,
+}
+
+impl ManifestArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ManifestArgs { action } = self;
+        match action {
+            ManifestCommand::Upgrade {
+                manifest,
+                output,
+                dry_run,
+                keys: KeyArgs { keys },
+            } => {
+                let mut json = EmbrFS::load_manifest_json(&manifest, &build_keyring(&keys)?)?;
+                let from = manifest_schema::version_of(&json)?;
+                let applied = manifest_schema::upgrade(&mut json)?;
+                let target = output.unwrap_or_else(|| manifest.clone());
+                let written = !dry_run && (from != manifest_schema::MANIFEST_SCHEMA_VERSION || target != manifest);
+                if written {
+                    let mut fs = EmbrFS::new();
+                    fs.manifest = Manifest::from_json(json)?;
+                    fs.save_manifest(&target)?;
+                }
+                if json_output {
+                    print_json(&serde_json::json!({
+                        "manifest": manifest,
+                        "from": from,
+                        "to": manifest_schema::MANIFEST_SCHEMA_VERSION,
+                        "migrations": applied,
+                        "written": written.then_some(&target),
+                    }))?;
+                } else {
+                    if applied.is_empty() {
+                        println!("{}: already schema version {}", manifest.display(), from);
+                    } else {
+                        println!(
+                            "{}{}: schema version {} -> {}",
+                            if dry_run { "(dry run) " } else { "" },
+                            manifest.display(),
+                            from,
+                            manifest_schema::MANIFEST_SCHEMA_VERSION
+                        );
+                        for adds in &applied {
+                            println!("  + {}", adds);
+                        }
+                    }
+                    if written && target != manifest {
+                        println!("wrote {}", target.display());
+                    }
+                }
+                Ok(())
+            }
+            ManifestCommand::Downgrade {
+                manifest,
+                to,
+                output,
+                keys: KeyArgs { keys },
+            } => {
+                let loaded = EmbrFS::load_manifest_with_keys(&manifest, &build_keyring(&keys)?)?;
+                let mut json = serde_json::to_value(Versioned::new(&loaded))?;
+                manifest_schema::downgrade(&mut json, to)?;
+                std::fs::write(&output, serde_json::to_vec_pretty(&json)?)?;
+                if json_output {
+                    print_json(&serde_json::json!({"manifest": manifest, "to": to, "written": output}))?;
+                } else {
+                    println!("{}: wrote schema version {} to {}", manifest.display(), to, output.display());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ConvertFormatArg {
+    /// bincode in an envelope (bare bincode without compression, checksums or encryption)
+    Envelope,
+    /// rkyv archive (requires --features rkyv)
+    Rkyv,
+    /// append-only log carrying its own manifest
+    AppendLog,
+    /// protobuf in an envelope, stable across releases (requires --features proto)
+    Proto,
+}
+
+impl From<ConvertFormatArg> for TargetFormat {
+    fn from(v: ConvertFormatArg) -> Self {
+        match v {
+            ConvertFormatArg::Envelope => TargetFormat::Envelope,
+            ConvertFormatArg::Rkyv => TargetFormat::Rkyv,
+            ConvertFormatArg::AppendLog => TargetFormat::AppendLog,
+            ConvertFormatArg::Proto => TargetFormat::Proto,
+        }
+    }
+}
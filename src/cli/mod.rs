@@ -5,27 +5,132 @@
 //! - Extracting files from engrams
 //! - Querying similarity
 //! - Mounting engrams as FUSE filesystems (requires `fuse` feature)
+//!
+//! Flag defaults can come from a configuration file; see [`config`].
+//!
+//! Each subcommand's arguments (`<Name>Args`) and handler live in a private
+//! submodule by area: `ingest`, `extract`, `inspect`, `query`, `codebooks`,
+//! `versions`, `maintain`, `remote`, `interop`, `mount` and `serve`. Shared
+//! argument groups ([`KeyArgs`], [`EngramArgs`]) and helpers stay here.
+
+mod codebooks;
+pub mod config;
+mod extract;
+mod ingest;
+mod inspect;
+mod interop;
+mod maintain;
+#[cfg(feature = "fuse")]
+mod mount;
+mod query;
+mod remote;
+#[cfg(any(feature = "http", feature = "nbd", feature = "sqlite", feature = "grpc"))]
+mod serve;
+pub mod shell;
+mod versions;
 
 use crate::embrfs::{
-    DirectorySubEngramStore, EmbrFS, HierarchicalQueryBounds, load_hierarchical_manifest,
-    query_hierarchical_codebook_with_store,
-    save_hierarchical_manifest, save_sub_engrams_dir_with_options,
+    Engram, FileMatch, FileSignatures, Manifest, QuotaExceeded,
 };
-use crate::envelope::{BinaryWriteOptions, CompressionCodec};
-use crate::vsa::{SparseVec, ReversibleVSAConfig};
-use clap::{Parser, Subcommand};
+use crate::semantic::SemanticEncoder;
+use crate::envelope::{
+    ChecksumCodec, CompressionCodec, EncryptionKey, EnvelopeCorruption, Keyring,
+};
+use crate::access_stats::{AccessSnapshot, AccessStats};
+use crate::profile;
+use crate::logging;
+use crate::listing;
+use crate::namespace_keys::KeyRule;
+use crate::error::EmbrError;
+use crate::vsa::SparseVec;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use config::Profile;
 use std::env;
-use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, Write};
 use std::path::Path;
 use std::path::PathBuf;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(clap::ValueEnum, Clone, Copy, Debug)]
 pub enum CompressionArg {
     None,
     Zstd,
     Lz4,
+    /// zstd with a dictionary trained on the codebook (4 KiB frames)
+    ZstdDict,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ChecksumArg {
+    None,
+    Blake3,
+    Xxh3,
+}
+
+impl From<ChecksumArg> for ChecksumCodec {
+    fn from(v: ChecksumArg) -> Self {
+        match v {
+            ChecksumArg::None => ChecksumCodec::None,
+            ChecksumArg::Blake3 => ChecksumCodec::Blake3,
+            ChecksumArg::Xxh3 => ChecksumCodec::Xxh3,
+        }
+    }
+}
+
+#[cfg(feature = "vector-sync")]
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum VectorBackendArg {
+    Qdrant,
+    Milvus,
+}
+
+/// Semantic encoder for `--semantic-model`, with the default projection.
+#[cfg(feature = "onnx")]
+fn load_semantic_encoder(model: &Path, tokenizer: Option<&Path>) -> io::Result<SemanticEncoder> {
+    let embedder = crate::onnx_embed::OnnxEmbedder::load(model, tokenizer)?;
+    Ok(SemanticEncoder::new(Box::new(embedder), Default::default()))
+}
+
+#[cfg(not(feature = "onnx"))]
+fn load_semantic_encoder(_: &Path, _: Option<&Path>) -> io::Result<SemanticEncoder> {
+    Err(io::Error::other("ONNX semantic signatures not enabled (enable feature `onnx`)"))
+}
+
+fn read_key_file(path: &Path) -> io::Result<String> {
+    Ok(std::fs::read_to_string(path)?.trim().to_string())
+}
+
+fn parse_key_arg(s: &str) -> Result<(u32, PathBuf), String> {
+    let (id, path) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected ID=FILE, got {:?}", s))?;
+    let id = id.parse().map_err(|_| format!("invalid key id {:?}", id))?;
+    if path.is_empty() {
+        return Err(format!("expected ID=FILE, got {:?}", s));
+    }
+    Ok((id, PathBuf::from(path)))
+}
+
+fn parse_key_rule_arg(s: &str) -> Result<KeyRule, String> {
+    let (prefix, id) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected PREFIX=ID, got {:?}", s))?;
+    let id = id.parse().map_err(|_| format!("invalid key id {:?}", id))?;
+    Ok(KeyRule::new(prefix, id))
+}
+
+fn parse_id_map_arg(s: &str) -> Result<(u32, u32), String> {
+    let (from, to) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected FROM:TO, got {:?}", s))?;
+    let id = |v: &str| v.parse().map_err(|_| format!("invalid id {:?}", v));
+    Ok((id(from)?, id(to)?))
+}
+
+fn build_keyring(keys: &[(u32, PathBuf)]) -> io::Result<Keyring> {
+    keys.iter()
+        .map(|(id, path)| EncryptionKey::from_hex(*id, &read_key_file(path)?))
+        .collect()
 }
 
 impl From<CompressionArg> for CompressionCodec {
@@ -34,36 +139,55 @@ impl From<CompressionArg> for CompressionCodec {
             CompressionArg::None => CompressionCodec::None,
             CompressionArg::Zstd => CompressionCodec::Zstd,
             CompressionArg::Lz4 => CompressionCodec::Lz4,
+            CompressionArg::ZstdDict => CompressionCodec::ZstdDict,
         }
     }
 }
 
-fn path_to_forward_slash_string(path: &Path) -> String {
-    path.components()
-        .filter_map(|c| match c {
-            std::path::Component::Normal(s) => s.to_str().map(|v| v.to_string()),
-            _ => None,
-        })
-        .collect::<Vec<String>>()
-        .join("/")
+/// A file ranked by [`rank_files`], with the cosine of its best chunk among the
+/// chunk-level hits (if any of its chunks was one).
+#[derive(serde::Serialize)]
+struct RankedFile {
+    #[serde(flatten)]
+    hit: FileMatch,
+    best_chunk: Option<f64>,
 }
 
-fn logical_path_for_file_input(path: &Path, cwd: &Path) -> String {
-    if path.is_relative() {
-        return path_to_forward_slash_string(path);
-    }
-
-    if let Ok(rel) = path.strip_prefix(cwd) {
-        let s = path_to_forward_slash_string(rel);
-        if !s.is_empty() {
-            return s;
-        }
-    }
+/// Rank whole files against `base_query` by file signature similarity.
+fn rank_files(
+    engram: &Engram,
+    manifest: &Manifest,
+    namespace: Option<&str>,
+    base_query: &SparseVec,
+    chunk_hits: &HashMap<usize, (f64, i32)>,
+    k: usize,
+) -> Vec<RankedFile> {
+    let in_namespace: Option<HashSet<&str>> =
+        namespace.map(|ns| manifest.files_in_namespace(ns).map(|f| f.path.as_str()).collect());
+    let signatures = FileSignatures::build_filtered(engram, manifest, |f| {
+        in_namespace.as_ref().is_none_or(|paths| paths.contains(f.path.as_str()))
+    });
+    let hits = signatures.query_any_shift(base_query, &manifest.encoding().vsa, k);
+
+    let best_chunk: HashMap<&str, f64> = manifest
+        .files
+        .iter()
+        .filter_map(|f| {
+            let best = f
+                .chunks
+                .iter()
+                .filter_map(|id| chunk_hits.get(id).map(|&(cosine, _)| cosine))
+                .fold(None, |acc: Option<f64>, c| Some(acc.map_or(c, |a| a.max(c))))?;
+            Some((f.path.as_str(), best))
+        })
+        .collect();
 
-    path.file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("input.bin")
-        .to_string()
+    hits.into_iter()
+        .map(|hit| RankedFile {
+            best_chunk: best_chunk.get(hit.path.as_str()).copied(),
+            hit,
+        })
+        .collect()
 }
 
 #[derive(Parser)]
@@ -83,14 +207,196 @@ fn logical_path_for_file_input(path: &Path, cwd: &Path) -> String {
     Examples:\n\
       embeddenator ingest -i ./mydata -e data.engram -m data.json -v\n\
       embeddenator extract -e data.engram -m data.json -o ./restored -v\n\
-      embeddenator query -e data.engram -q ./testfile.txt -v"
+      embeddenator query -e data.engram -q ./testfile.txt -v\n\
+      embeddenator verify -e data.engram -m data.json --output-format json\n\n\
+    Exit codes:\n\
+      0  success\n\
+      1  other failure\n\
+      2  invalid arguments or configuration profile\n\
+      3  missing input (file, path or key file not found)\n\
+      4  corrupt data (bad envelope, checksum mismatch, missing chunk)\n\
+      5  ingest quota exceeded\n\
+      6  crypto failure (missing or wrong key, failed signature check)\n\
+      7  the engram is sealed and may not be changed\n\
+      8  ingest rejected content flagged by --scan-secrets"
 )]
 #[command(author = "Tyler Zervas <tz-dev@vectorweight.com>")]
 pub struct Cli {
+    /// Output format. `json` prints one JSON document per command on stdout,
+    /// and errors as JSON on stderr.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output_format: OutputFormat,
+
+    /// Profile from the configuration file supplying flag defaults
+    /// (default: $EMBEDDENATOR_PROFILE, then the file's `default_profile`)
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Time ingest, query and extract internals and write them to FILE as folded
+    /// stacks, for flamegraph.pl or inferno-flamegraph
+    #[arg(long, global = true, value_name = "FILE")]
+    pub flamegraph: Option<PathBuf>,
+
+    /// With --flamegraph, time only every N-th outermost scope, to keep the cost down
+    /// on large runs
+    #[arg(long, global = true, default_value_t = 1, value_name = "N")]
+    pub flamegraph_sample: u64,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Process exit codes. These are part of the CLI's interface: scripts branch
+/// on them, so values are never reused or renumbered.
+pub mod exit_code {
+    pub const SUCCESS: i32 = 0;
+    /// Any failure not covered below.
+    pub const FAILURE: i32 = 1;
+    /// Invalid arguments or configuration.
+    pub const USAGE: i32 = 2;
+    pub const NOT_FOUND: i32 = 3;
+    pub const CORRUPT: i32 = 4;
+    pub const QUOTA: i32 = 5;
+    pub const CRYPTO: i32 = 6;
+    /// The engram is sealed and may not be changed.
+    pub const IMMUTABLE: i32 = 7;
+    /// An ingest filter rejected a file.
+    pub const REJECTED: i32 = 8;
+}
+
+/// Exit code and short name (`corrupt`, `quota`, ...) for `err`.
+pub fn classify_error(err: &io::Error) -> (i32, &'static str) {
+    const CORRUPT: (i32, &str) = (exit_code::CORRUPT, "corrupt");
+    match EmbrError::find(err) {
+        Some(
+            EmbrError::EnvelopeCorruption(_)
+            | EmbrError::InvalidEnvelope(_)
+            | EmbrError::CorruptEngram(_)
+            | EmbrError::CorruptSubEngram(_)
+            | EmbrError::ManifestMismatch(_)
+            | EmbrError::MissingChunk { .. },
+        ) => return CORRUPT,
+        Some(EmbrError::Quota(_)) => return (exit_code::QUOTA, "quota"),
+        Some(EmbrError::MissingKey(_) | EmbrError::Crypto(_)) => return (exit_code::CRYPTO, "crypto"),
+        Some(EmbrError::Immutable(_)) => return (exit_code::IMMUTABLE, "immutable"),
+        Some(EmbrError::Rejected(_)) => return (exit_code::REJECTED, "rejected"),
+        _ => {}
+    }
+    // Envelope readers and ingest limits report through plain io::Error.
+    if let Some(inner) = err.get_ref() {
+        if inner.is::<EnvelopeCorruption>() {
+            return CORRUPT;
+        }
+        if inner.is::<QuotaExceeded>() {
+            return (exit_code::QUOTA, "quota");
+        }
+    }
+    match err.kind() {
+        io::ErrorKind::NotFound => (exit_code::NOT_FOUND, "not-found"),
+        io::ErrorKind::InvalidInput => (exit_code::USAGE, "usage"),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => CORRUPT,
+        _ => (exit_code::FAILURE, "failure"),
+    }
+}
+
+/// Run the command line and report any error on stderr (as JSON with
+/// `--output-format json`). Returns the process exit code.
+pub fn run_and_report() -> i32 {
+    let matches = Cli::command().get_matches();
+    let output = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli.output_format,
+        Err(e) => e.exit(),
+    };
+    let err = match parse_with_profile(&matches).and_then(|cli| run_logged(&matches, cli)) {
+        Ok(()) => return exit_code::SUCCESS,
+        // The reader went away (`| head`); nothing left to report to.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return exit_code::SUCCESS,
+        Err(e) => e,
+    };
+    let (code, kind) = classify_error(&err);
+    match output {
+        OutputFormat::Text => eprintln!("Error: {}", err),
+        OutputFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({ "error": { "code": code, "kind": kind, "message": err.to_string() } })
+        ),
+    }
+    code
+}
+
+/// Write `ls` output: size, chunks, kind and path per entry, then totals.
+fn write_listing<W: Write>(out: &mut W, entries: &[listing::ListEntry]) -> io::Result<()> {
+    for entry in entries {
+        writeln!(
+            out,
+            "{:>12} {:>8} {:<6} {}",
+            entry.size,
+            entry.chunks,
+            if entry.is_text { "text" } else { "binary" },
+            entry.path
+        )?;
+    }
+    let total: usize = entries.iter().map(|e| e.size).sum();
+    writeln!(out, "{} files, {} bytes", entries.len(), total)
+}
+
+/// Print `value` on stdout as pretty JSON.
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)
+}
+
+/// Access counts saved at `path` by earlier `--access-stats` runs; none if
+/// the file doesn't exist yet.
+fn load_access_stats(path: &Path) -> io::Result<AccessStats> {
+    let stats = AccessStats::new();
+    match std::fs::read(path) {
+        Ok(data) => {
+            let saved: AccessSnapshot = serde_json::from_slice(&data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+            stats.merge(&saved);
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    Ok(stats)
+}
+
+fn save_access_stats(path: &Path, stats: &AccessStats) -> io::Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(&stats.snapshot())?)
+}
+
+/// `--key ID=FILE` decryption keys, shared by every command that reads an
+/// encrypted engram.
+#[derive(Args, Clone, Debug, Default)]
+pub struct KeyArgs {
+    /// Decryption key as ID=FILE (32-byte hex). Repeatable to cover rotated keys.
+    #[arg(long = "key", value_name = "ID=FILE", value_parser = parse_key_arg)]
+    pub keys: Vec<(u32, PathBuf)>,
+}
+
+/// `-e/--engram` and `-m/--manifest` of commands that read an existing engram.
+#[derive(Args, Clone, Debug)]
+pub struct EngramArgs {
+    /// Engram file
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Manifest file with metadata and chunk mappings
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+}
+
+// Parsed once per run, so boxing the larger variants would buy nothing.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     /// Ingest files/directories into a holographic engram
@@ -105,40 +411,10 @@ pub enum Commands {
         • Reconstruction is bit-perfect for all file types\n\n\
         Example:\n\
           embeddenator ingest -i ./myproject -e project.engram -m project.json -v\n\
-          embeddenator ingest --input ~/Documents --engram docs.engram --verbose"
-    )]
-    Ingest {
-        /// Input path(s) to ingest (directory or file). Can be provided multiple times.
-        #[arg(
-            short,
-            long,
-            value_name = "PATH",
-            help_heading = "Required",
-            num_args = 1..,
-            action = clap::ArgAction::Append
-        )]
-        input: Vec<PathBuf>,
-
-        /// Output engram file containing holographic encoding
-        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
-        engram: PathBuf,
-
-        /// Optional compression for the output engram (default: none)
-        #[arg(long, default_value = "none", value_enum)]
-        engram_compression: CompressionArg,
-
-        /// Optional compression level (codec-dependent; used for zstd)
-        #[arg(long, value_name = "LEVEL")]
-        engram_compression_level: Option<i32>,
-
-        /// Output manifest file containing file metadata and chunk mappings
-        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
-        manifest: PathBuf,
-
-        /// Enable verbose output showing ingestion progress and statistics
-        #[arg(short, long)]
-        verbose: bool,
-    },
+          embeddenator ingest --input ~/Documents --engram docs.engram --verbose\n\
+          embeddenator ingest --root data=/srv/data --root cfg=/etc/app -e app.engram"
+    )]
+    Ingest(ingest::IngestArgs),
 
     /// Extract and reconstruct files from a holographic engram
     #[command(
@@ -151,27 +427,231 @@ pub enum Commands {
         • Reconstructs the directory structure\n\
         • Unbinds and decodes each chunk using VSA operations\n\
         • Writes bit-perfect copies of all original files\n\n\
+        Manifest paths that are absolute or climb out with '..' are refused before\n\
+        anything is written, unless --allow-unsafe-paths is given. Paths that the\n\
+        output filesystem cannot tell apart (by case or Unicode form) are reported\n\
+        on stderr; the last of each group is the one left on disk.\n\n\
         Example:\n\
           embeddenator extract -e project.engram -m project.json -o ./restored -v\n\
           embeddenator extract --engram backup.engram --output-dir ~/restored"
     )]
-    Extract {
-        /// Input engram file to extract from
-        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
-        engram: PathBuf,
+    Extract(extract::ExtractArgs),
+
+    /// Report deduplication and compression statistics for an engram
+    #[command(
+        long_about = "Report deduplication and compression statistics for an engram\n\n\
+        Summarizes raw bytes, unique chunk bytes, dedup ratio, compression ratio and a\n\
+        chunk-count histogram, broken down per file extension and per directory, plus\n\
+        the estimated memory the engram and manifest take once loaded.\n\n\
+        Example:\n\
+          embeddenator stats -e project.engram -m project.json\n\
+          embeddenator stats -e project.engram -m project.json --json"
+    )]
+    Stats(inspect::StatsArgs),
+
+    /// Summarize an engram: format, dimension, density and size
+    #[command(
+        visible_alias = "stat",
+        long_about = "Summarize an engram: format, dimension, density and size\n\n\
+        Reports the on-disk format and its version, vector dimension, the density of the\n\
+        root and chunk vectors, codebook size, and the SIMD features detected on this\n\
+        host. With a manifest (given by --manifest, or stored in an append log) the dedup\n\
+        and compression ratios are included too. The root's representation is the one\n\
+        the built-in thresholds select, or with --calibrated the ones `bench --calibrate`\n\
+        saved on this host.\n\n\
+        Example:\n\
+          embeddenator info -e project.engram\n\
+          embeddenator info -e project.engram -m project.json --json"
+    )]
+    Info(inspect::InfoArgs),
+
+    /// Show which files and chunks are accessed most, from saved access stats
+    #[command(
+        long_about = "Show which files and chunks are accessed most, from saved access stats\n\n\
+        `mount --access-stats FILE` counts every file read through the mount, and\n\
+        `query --access-stats FILE` every chunk a query returns, adding to the counts\n\
+        earlier runs saved in FILE. This lists the busiest files with their read,\n\
+        extract and query counts and mean latency, and a strip of cells across each\n\
+        file's chunks, darker where its chunks were accessed more.\n\n\
+        Example:\n\
+          embeddenator mount -e project.engram -m project.json /mnt/p --access-stats access.json\n\
+          embeddenator heatmap --stats access.json -m project.json --top 10"
+    )]
+    Heatmap(inspect::HeatmapArgs),
+
+    /// Report how saturated an engram's root bundle is
+    #[command(
+        long_about = "Report how saturated an engram's root bundle is\n\n\
+        Compares the cosine of stored chunks with the root against the value expected\n\
+        for an ideal bundle and against the noise floor, and estimates how many more\n\
+        chunks can be bundled before similarity against the root degrades.\n\n\
+        Example:\n\
+          embeddenator capacity -e project.engram\n\
+          embeddenator capacity -e project.engram --sample 1000 --json"
+    )]
+    Capacity(inspect::CapacityArgs),
 
-        /// Input manifest file with metadata and chunk mappings
-        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
-        manifest: PathBuf,
+    /// Check whether a file was probably ingested, from the root alone
+    #[command(
+        long_about = "Check whether a file was probably ingested, from the root alone\n\n\
+        Scores each chunk of the file against the engram's root bundle, like a Bloom\n\
+        filter: no codebook lookup or index is needed. The verdict is calibrated by how\n\
+        many chunks the root holds, with the chance that an unrelated file scores as\n\
+        high. Chunk vectors depend on the path the file was stored under, so give it\n\
+        with --path when it differs from INPUT.\n\n\
+        Example:\n\
+          embeddenator contains -e project.engram -m project.json src/main.rs\n\
+          embeddenator contains -e project.engram ./copy.txt --path docs/notes.txt --json"
+    )]
+    Contains(inspect::ContainsArgs),
 
-        /// Output directory where files will be reconstructed
-        #[arg(short, long, value_name = "DIR", help_heading = "Required")]
-        output_dir: PathBuf,
+    /// Watch a data stream for drift away from a baseline engram
+    #[command(
+        long_about = "Watch a data stream for drift away from a baseline engram\n\n\
+        Reads INPUT (or stdin for '-') in windows of chunks, keeps a decaying sketch of\n\
+        what it has seen and compares it with the engram's root after every window.\n\
+        Windows whose sketch similarity falls below --low, or rises above --high, are\n\
+        flagged. Close content only registers for engrams ingested with\n\
+        --similarity-chunks; otherwise just identical chunks do.\n\n\
+        Example:\n\
+          tail -f app.log | embeddenator monitor -e baseline.engram -m baseline.json -\n\
+          embeddenator monitor -e baseline.engram -m baseline.json feed.bin --low 0.2 --json"
+    )]
+    Monitor(inspect::MonitorArgs),
 
-        /// Enable verbose output showing extraction progress
-        #[arg(short, long)]
-        verbose: bool,
-    },
+    /// List the files stored in a manifest
+    #[command(
+        long_about = "List the files stored in a manifest\n\n\
+        Prints size, chunk count and text/binary flag for each file without mounting\n\
+        or extracting. Patterns are globs: one without '/' matches file names at any\n\
+        depth, one with '/' matches the whole path.\n\n\
+        Example:\n\
+          embeddenator ls -m project.json\n\
+          embeddenator ls -m project.json '*.rs' 'docs/**'\n\
+          embeddenator ls -m project.json --json"
+    )]
+    Ls(inspect::LsArgs),
+
+    /// Show the directory tree stored in a manifest
+    #[command(
+        long_about = "Show the directory tree stored in a manifest\n\n\
+        Renders the logical directory structure with file sizes and per-directory\n\
+        totals. Patterns filter files the same way as in `ls`; directories without\n\
+        matching files are left out.\n\n\
+        Example:\n\
+          embeddenator tree -m project.json\n\
+          embeddenator tree -m project.json --depth 2 '*.md'\n\
+          embeddenator tree -m project.json --json"
+    )]
+    Tree(inspect::TreeArgs),
+
+    /// Write one file from an engram to stdout
+    #[command(
+        long_about = "Write one file from an engram to stdout\n\n\
+        Reconstructs a single file, applying corrections and checking its recorded\n\
+        checksum. Append-log engrams are read lazily (with the `mmap` feature): only the\n\
+        chunks the file references are decoded, and the manifest stored in the log is\n\
+        used unless --manifest is given. Sharded engrams load only the shards holding\n\
+        those chunks. Other engrams are loaded in full.\n\n\
+        Example:\n\
+          embeddenator cat -e project.engram -m project.json src/main.rs\n\
+          embeddenator cat -e project.edna docs/README.md | less"
+    )]
+    Cat(inspect::CatArgs),
+
+    /// Explore an engram interactively
+    #[command(
+        long_about = "Explore an engram interactively
+
+        Opens a prompt with ls, tree, cat, query and diff commands over one engram, which
+        stays loaded (or memory-mapped, for append logs with the `mmap` feature, or
+        loaded shard by shard, for sharded engrams) between commands. Type help for the list. Built with the `shell` feature the prompt has
+        history and tab completion of commands and manifest paths; without it, commands
+        are read line by line from stdin, so sessions can be scripted.
+
+        Example:
+          embeddenator shell -e project.engram -m project.json
+          echo 'query fn main' | embeddenator shell -e project.edna"
+    )]
+    Shell(inspect::ShellArgs),
+
+    /// Run the built-in benchmarks and emit JSON results
+    #[command(
+        long_about = "Run the built-in benchmarks and emit JSON results\n\n\
+        Micro benchmarks time bind, bundle, dot and cosine on the sparse, packed,\n\
+        bitsliced and block-sparse representations at each dimension. Macro benchmarks\n\
+        measure ingest and extract throughput on a generated corpus. Inputs are seeded,\n\
+        so reports from different hosts or builds are comparable.\n\n\
+        With --baseline, results are compared with an earlier report and the command\n\
+        exits non-zero if any benchmark is slower by more than --tolerance.\n\n\
+        With --calibrate, the command instead measures where hybrid vectors should\n\
+        switch between sparse, bitsliced and block-sparse on this CPU, saves the\n\
+        thresholds to $EMBEDDENATOR_CALIBRATION (default\n\
+        $XDG_CACHE_HOME/embeddenator/calibration.json) and prints them. Nothing loads\n\
+        them unless asked to, such as info --calibrated.\n\n\
+        Example:\n\
+          embeddenator bench -o bench.json\n\
+          embeddenator bench --dims 4096,16384 --no-macro\n\
+          embeddenator bench --quick --baseline bench.json --tolerance 0.25\n\
+          embeddenator bench --calibrate"
+    )]
+    Bench(inspect::BenchArgs),
+
+    /// Verify reconstructed files against the checksums recorded at ingest
+    #[command(
+        long_about = "Verify reconstructed files against the checksums recorded at ingest\n\n\
+        Every file in the manifest is reconstructed in memory and its blake3 digest is\n\
+        compared with the one stored in the manifest. Nothing is written to disk.\n\
+        Mismatched files are reported together with the chunk ids that failed their\n\
+        per-chunk hash check; the command exits non-zero if any file mismatches.\n\n\
+        Example:\n\
+          embeddenator verify -e project.engram -m project.json"
+    )]
+    Verify(inspect::VerifyArgs),
+
+    /// Rewrite an engram in another storage format, verifying it first
+    #[command(
+        long_about = "Rewrite an engram in another storage format, verifying it first\n\n\
+        The engram is written to a temporary file beside the destination in the format\n\
+        chosen with --to (envelope, rkyv, append-log or proto), with the given compression,\n\
+        checksums, encryption and part or shard size. Every file in the manifest is\n\
+        then reconstructed from both the original and the new copy and compared byte\n\
+        for byte; only if all match is the destination replaced. Without --output the\n\
+        engram is converted in place. Leftover part and shard files are removed.\n\n\
+        Chunk vectors are stored sparse in every format; the root representation shown\n\
+        by `info` is chosen at load time. Changing VSA parameters requires re-ingesting.\n\
+        The proto format stays readable by later releases and by other languages.\n\
+        A detached signature of the old engram no longer matches and must be re-made.\n\n\
+        Example:\n\
+          embeddenator convert -e project.engram -m project.json --compression zstd --checksum blake3\n\
+          embeddenator convert -e project.engram -m project.json --to append-log -o project.edna\n\
+          embeddenator convert -e project.edna --to envelope -o project.engram --manifest-out project.json"
+    )]
+    Convert(maintain::ConvertArgs),
+
+    /// Maintain shared codebooks and inspect codebook artifacts
+    #[command(
+        long_about = "Maintain shared codebooks and inspect codebook artifacts\n\n\
+        `ingest --codebook FILE` stores chunk records in a shared codebook, keyed by digest,\n\
+        and writes the engram as references into it, so versions of a dataset that share\n\
+        most of their chunks store them once. The codebook counts the engrams using each\n\
+        record. Before deleting such an engram, release it; then collect garbage to drop\n\
+        records no engram uses. Given the engrams still in use, gc recounts from them\n\
+        instead of trusting the stored counts, and forgets every other engram.\n\n\
+        `ingest --basis K` writes a differential-encoding codebook to `<engram>.basis`.\n\
+        `show` prints its id, a digest of its content, and `--pin ID` fails on any other;\n\
+        `diff` lists the basis vectors and settings that differ between two of them.\n\
+        `cluster` groups an engram's chunks whose vectors are near-duplicates, showing\n\
+        how much near-dedup could save and which chunks stand for the most others.\n\n\
+        Example:\n\
+          embeddenator ingest -i ./v3 -e v3.engram -m v3.json --codebook datasets.codebook\n\
+          embeddenator codebook release -e v1.engram\n\
+          embeddenator codebook gc -c datasets.codebook\n\
+          embeddenator codebook gc -c datasets.codebook v2.engram v3.engram --dry-run\n\
+          embeddenator codebook diff v2.engram.basis v3.engram.basis\n\
+          embeddenator codebook cluster -e v3.engram --threshold 0.8"
+    )]
+    Codebook(codebooks::CodebookArgs),
 
     /// Query similarity between a file and engram contents
     #[command(
@@ -179,6 +659,9 @@ pub enum Commands {
         This command computes the similarity between a query file and the data encoded\n\
         in an engram using VSA cosine similarity. This enables holographic search and\n\
         content-based retrieval without full extraction.\n\n\
+        With a manifest, each file's chunk vectors are bundled into a file signature and\n\
+        the top-k files are listed by similarity, alongside their best matching chunk.\n\
+        The top codebook chunks are listed after them.\n\n\
         Similarity interpretation:\n\
         • >0.75: Strong match, likely contains similar content\n\
         • 0.3-0.75: Moderate similarity, some shared patterns\n\
@@ -187,31 +670,7 @@ pub enum Commands {
           embeddenator query -e archive.engram -q search.txt -v\n\
           embeddenator query --engram data.engram --query pattern.bin"
     )]
-    Query {
-        /// Engram file to query
-        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
-        engram: PathBuf,
-
-        /// Query file to search for
-        #[arg(short, long, value_name = "FILE", help_heading = "Required")]
-        query: PathBuf,
-
-        /// Optional hierarchical manifest (enables selective unfolding search)
-        #[arg(long, value_name = "FILE")]
-        hierarchical_manifest: Option<PathBuf>,
-
-        /// Directory containing bincode-serialized sub-engrams (used with --hierarchical-manifest)
-        #[arg(long, value_name = "DIR")]
-        sub_engrams_dir: Option<PathBuf>,
-
-        /// Top-k results to print for codebook/hierarchical search
-        #[arg(long, default_value_t = 10, value_name = "K")]
-        k: usize,
-
-        /// Enable verbose output showing similarity scores and details
-        #[arg(short, long)]
-        verbose: bool,
-    },
+    Query(query::QueryArgs),
 
     /// Query similarity using a literal text string (basic inference-to-vector)
     #[command(
@@ -219,31 +678,95 @@ pub enum Commands {
         This is a convenience wrapper that encodes the provided text as bytes into a VSA query vector\n\
         and runs the same retrieval path as `query`."
     )]
-    QueryText {
-        /// Engram file to query
-        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
-        engram: PathBuf,
+    QueryText(query::QueryTextArgs),
+
+    /// Find the ingested files most similar to a local file
+    #[command(
+        long_about = "Find the ingested files most similar to a local file\n\n\
+        The file is chunked and encoded with the encoding the manifest records, ranked\n\
+        against every file signature, and each of its chunks is looked up in the codebook.\n\
+        The files found either way are reranked by exact cosine, and for each one the\n\
+        regions that match are listed: a byte range of the local file next to the byte\n\
+        range of the ingested file it resembles.\n\n\
+        Example:\n\
+          embeddenator find-similar -e root.engram -m manifest.json notes/draft.md"
+    )]
+    FindSimilar(query::FindSimilarArgs),
 
-        /// Text to encode and search for
-        #[arg(long, value_name = "TEXT", help_heading = "Required")]
-        text: String,
+    /// Evaluate an algebra expression over the engram's vectors
+    #[command(
+        long_about = "Evaluate an algebra expression over the engram's vectors\n\n\
+        The expression composes bind, unbind, bundle, permute, cleanup and symbol over\n\
+        named vectors: root, chunk:<id> for each chunk, file:<path> for each file's\n\
+        signature, and role:<label> and record:<path> from the engram's role schema.\n\
+        Names with spaces go in double quotes, and symbol(NAME) is a seeded random\n\
+        vector, so role-filler queries need no stored roles. The result is ranked\n\
+        against every named vector.\n\n\
+        Example:\n\
+          embeddenator compute -e root.engram -m manifest.json 'cleanup(bundle(file:a.txt, file:b.txt))'"
+    )]
+    Compute(query::ComputeArgs),
 
-        /// Optional hierarchical manifest (enables selective unfolding search)
-        #[arg(long, value_name = "FILE")]
-        hierarchical_manifest: Option<PathBuf>,
+    /// Find files and directories bound to a value under a role
+    #[command(
+        long_about = "Find files and directories bound to a value under a role\n\n\
+        Searches the role schema ingest --role and --metadata-roles fill in. Each bound\n\
+        path's record is unbound with the role and compared with the value: --value TEXT,\n\
+        or --like PATH for whatever PATH is bound to under the role. Paths whose value\n\
+        scores like a member of their record are printed, best first.\n\n\
+        Example:\n\
+          embeddenator query-role -e root.engram --role author --value ada"
+    )]
+    QueryRole(query::QueryRoleArgs),
 
-        /// Directory containing bincode-serialized sub-engrams (used with --hierarchical-manifest)
-        #[arg(long, value_name = "DIR")]
-        sub_engrams_dir: Option<PathBuf>,
+    /// Query several engrams at once and rank the results together
+    #[command(
+        long_about = "Query several engrams at once and rank the results together\n\n\
+        Each --source is searched on its own, its hits are rescaled against the rest of\n\
+        that engram's results (--normalize), and the top-k across all sources are printed\n\
+        with the engram, chunk and files they came from. The engrams are not merged or\n\
+        modified. A source is ENGRAM or ENGRAM,MANIFEST; without a manifest its hits\n\
+        carry no file paths. An --aligned source is an engram encoded differently,\n\
+        searched through the alignment `align` learned for it.\n\n\
+        Example:\n\
+          embeddenator query-federated -q snippet.rs -s team-a.engram,team-a.json -s team-b.engram,team-b.json"
+    )]
+    QueryFederated(query::QueryFederatedArgs),
 
-        /// Top-k results to print for codebook/hierarchical search
-        #[arg(long, default_value_t = 10, value_name = "K")]
-        k: usize,
+    /// Learn a map from one engram's vector space into another's
+    #[command(
+        long_about = "Learn a map from one engram's vector space into another's\n\n\
+        Engrams encoded with different settings give the same bytes different vectors.\n\
+        Files both engrams hold (equal blake3 digests) are the anchors: their chunks\n\
+        are paired, and every signed dimension of the source is mapped to the --fanout\n\
+        target dimensions set in most nearly the same anchors, or to all of those it\n\
+        cannot tell apart. A few hundred anchor chunks map nearly every dimension.\n\
+        The alignment is written as JSON to -o (default <source>.align) with how well\n\
+        the projected anchors match, and query-federated --aligned searches the\n\
+        source in the target's space with it.\n\n\
+        Example:\n\
+          embeddenator align -t ours.engram,ours.json -s theirs.engram,theirs.json\n\
+          embeddenator query-federated -q snippet.rs -s ours.engram,ours.json \\\n\
+            --aligned theirs.engram,theirs.json,theirs.engram.align"
+    )]
+    Align(query::AlignArgs),
 
-        /// Enable verbose output showing similarity scores and details
-        #[arg(short, long)]
-        verbose: bool,
-    },
+    /// Measure retrieval quality against a labeled query set
+    #[command(
+        long_about = "Measure retrieval quality against a labeled query set\n\n\
+        Each line of --queries is a JSON object with the query, as \"text\" or as a\n\
+        \"file\" relative to the query set, and what it should find: \"relevant\" file\n\
+        paths or \"relevant_chunks\" ids. Every query is run through each stage of the\n\
+        retrieval stack (index candidates, cosine reranking, exhaustive search), and\n\
+        recall@k, precision@k and the mean reciprocal rank are printed per stage.\n\n\
+        Save a report with -o before changing the encoder or index, and pass it as\n\
+        --baseline afterwards to see each metric's change; the command fails if one\n\
+        dropped by more than --tolerance.\n\n\
+        Example:\n\
+          embeddenator eval -e docs.engram -m docs.json -q labeled.jsonl -o before.json\n\
+          embeddenator eval -e docs.engram -m docs.json -q labeled.jsonl --baseline before.json"
+    )]
+    Eval(query::EvalArgs),
 
     /// Build hierarchical retrieval artifacts (manifest + sub-engrams store)
     #[command(
@@ -251,47 +774,308 @@ pub enum Commands {
         This command produces a hierarchical manifest JSON and a directory of sub-engrams\n\
         suitable for store-backed selective unfolding (DirectorySubEngramStore)."
     )]
-    BundleHier {
-        /// Input engram file
-        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
-        engram: PathBuf,
+    BundleHier(query::BundleHierArgs),
+
+    /// Generate an Ed25519 keypair for signing engrams (requires --features signing)
+    #[command(
+        long_about = "Generate an Ed25519 keypair for signing engrams\n\n\
+        Writes the hex-encoded secret seed to the given path and the hex-encoded\n\
+        public key to `<path>.pub`.\n\n\
+        Example:\n\
+          embeddenator keygen --out signing.key\n\
+          embeddenator ingest -i ./data --sign-key signing.key"
+    )]
+    Keygen(maintain::KeygenArgs),
+
+    /// Seal an engram and manifest against further changes (requires --features signing)
+    #[command(
+        long_about = "Seal an engram and manifest against further changes\n\n\
+        Both files are rewritten, in one transaction, with a signed seal record in front\n\
+        of their unchanged contents; they still load as before. From then on ingest,\n\
+        convert, repair and every other command that would rewrite either file fail with\n\
+        exit code 7. With --check, the seal is verified instead: both files must be\n\
+        byte-for-byte as sealed, and with --trusted-key signed by that key.\n\n\
+        Sealing protects against changes made through embeddenator; use storage-level\n\
+        retention (object lock, chattr +i) against deletion. Split, sharded, rkyv,\n\
+        append-log and shared-codebook engrams can't be sealed.\n\n\
+        Example:\n\
+          embeddenator seal -e archive.engram -m archive.json --sign-key signing.key\n\
+          embeddenator seal -e archive.engram -m archive.json --check --trusted-key signing.key.pub"
+    )]
+    Seal(maintain::SealArgs),
+
+    /// Write a delta that turns a base engram into a newer one
+    #[command(
+        long_about = "Write a delta that turns a base engram into a newer one\n\n\
+        The delta names the base by content digest and stores only chunks that were\n\
+        added, changed or removed, plus the manifest entries that differ. Ship it\n\
+        alongside the base and rebuild the new engram with `apply-delta`.\n\n\
+        Example:\n\
+          embeddenator delta --base-engram v1.engram --base-manifest v1.json \\\n\
+            -e v2.engram -m v2.json -o v1-to-v2.delta"
+    )]
+    Delta(versions::DeltaArgs),
 
-        /// Input manifest file
-        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
-        manifest: PathBuf,
+    /// Rebuild an engram by applying one or more deltas to its base
+    #[command(
+        long_about = "Rebuild an engram by applying one or more deltas to its base\n\n\
+        Deltas are applied in the order given, each one checked against the digest of\n\
+        the engram it expects. The result is written as a regular engram and manifest.\n\n\
+        Example:\n\
+          embeddenator apply-delta --base-engram v1.engram --base-manifest v1.json \\\n\
+            --delta v1-to-v2.delta --delta v2-to-v3.delta -e v3.engram -m v3.json"
+    )]
+    ApplyDelta(versions::ApplyDeltaArgs),
+
+    /// Keep engram snapshots as generations that age into deltas and remote storage
+    #[command(
+        long_about = "Keep engram snapshots as generations that age into deltas and remote storage\n\n\
+        `commit` records an engram and manifest as the newest generation of a store\n\
+        directory. The newest --keep-full generations stay as full files; older ones are\n\
+        rewritten as deltas against the next newer generation, and with --remote, deltas\n\
+        older than the newest --keep-local generations move to the remote directory. The\n\
+        policy is remembered by the store. `checkout` rebuilds any generation, reading\n\
+        across tiers; `tier` reapplies the policy; `list` shows where each generation lives.\n\n\
+        Example:\n\
+          embeddenator generations commit --store gens -e root.engram -m manifest.json \\\n\
+            --keep-full 2 --keep-local 6 --remote /mnt/archive/gens\n\
+          embeddenator generations list --store gens\n\
+          embeddenator generations checkout --store gens --generation 3 --remote /mnt/archive/gens \\\n\
+            -e v3.engram -m v3.json"
+    )]
+    Generations(versions::GenerationsArgs),
+
+    /// Send an engram to a remote copy, transferring only changed chunks
+    #[command(
+        long_about = "Send an engram to a remote copy, transferring only changed chunks\n\n\
+        Like rsync, the two sides compare the digest of every chunk record and only the\n\
+        records the remote lacks or holds differently are sent, in batches of --batch,\n\
+        optionally compressed. The remote then holds exactly the local engram and manifest;\n\
+        its files are replaced atomically, so an interrupted push leaves the old copy.\n\n\
+        REMOTE is [USER@]HOST:PATH, reached with `ssh HOST embeddenator sync-server`\n\
+        (see --rsh and --remote-bin), or a local path.\n\n\
+        Examples:\n\
+          embeddenator push -e data.engram -m data.json backup01:/srv/engrams/data.engram\n\
+          embeddenator push backup01:/srv/root.engram --compression zstd --rsh \"ssh -p 2222\""
+    )]
+    Push(remote::PushArgs),
+
+    /// Update a local engram from a remote copy, fetching only changed chunks
+    #[command(
+        long_about = "Update a local engram from a remote copy, fetching only changed chunks\n\n\
+        The counterpart of push: chunk digests are compared and only the records that\n\
+        differ are fetched. The local engram and manifest are then replaced atomically with\n\
+        the remote's; a missing local engram is created.\n\n\
+        Example:\n\
+          embeddenator pull -e data.engram -m data.json build01:/srv/engrams/data.engram"
+    )]
+    Pull(remote::PullArgs),
+
+    /// Serve one push or pull over stdin and stdout (started by push and pull)
+    #[command(hide = true)]
+    SyncServer(remote::SyncServerArgs),
+
+    /// Repair damaged files of an engram from replicas
+    #[command(
+        long_about = "Repair damaged files of an engram from replicas\n\n\
+        Every file in the manifest is verified as with `verify`. For each one that fails,\n\
+        the replicas given with --peer are tried in order: the file's chunk records that\n\
+        differ from the replica's, found by comparing Merkle trees over the chunk digests,\n\
+        are replaced with the replica's and kept only if the file then verifies. Replicas\n\
+        may be damaged too, as long as not in the same places. The repaired engram is\n\
+        written in place unless --output is given; the command exits non-zero if any file\n\
+        could not be restored.\n\n\
+        Example:\n\
+          embeddenator repair -e project.engram -m project.json --peer /mnt/b/project.engram"
+    )]
+    Repair(maintain::RepairArgs),
+
+    /// Put namespaces under encryption keys of their own, or rotate those keys
+    #[command(
+        long_about = "Put namespaces under encryption keys of their own, or rotate those keys\n\n\
+        The chunks of files under a key rule's prefix are moved out of the engram into\n\
+        `<engram>.keyNNNN`, encrypted with that rule's key, and the manifest records the rules\n\
+        and groups. Extracting or mounting such a file needs its key; files under other keys\n\
+        or no rule read as before. --rule replaces the rules, --rotate moves the rules of one\n\
+        key id to another and --clear moves every chunk back into the engram. Only groups\n\
+        whose chunks or key change are written again, so rotating one namespace's key\n\
+        re-encrypts that namespace alone. --key must give every key whose group is read or\n\
+        written. The engram is rewritten only if its own chunks change.\n\n\
+        Example:\n\
+          embeddenator rekey -e root.engram -m manifest.json --rule hr=2 --key 2=hr.key\n\
+          embeddenator rekey -e root.engram -m manifest.json --rotate 2:3 --key 2=hr.key --key 3=hr-new.key"
+    )]
+    Rekey(maintain::RekeyArgs),
 
-        /// Output hierarchical manifest JSON
-        #[arg(long, default_value = "hier.json", value_name = "FILE")]
-        out_hierarchical_manifest: PathBuf,
+    /// Re-verify every chunk of an engram in the background and repair what it can
+    #[command(
+        long_about = "Re-verify every chunk of an engram in the background and repair what it can\n\n\
+        Each pass reconstructs every chunk the manifest references and checks it against the\n\
+        hash and parity trit in its correction record, at most --rate chunks per second and\n\
+        at reduced CPU priority. A damaged chunk is rebuilt when the engram still holds its\n\
+        bytes: a verbatim correction, a file that still matches its blake3 checksum, or an\n\
+        identical chunk elsewhere. Repaired engrams are written in place unless --output is\n\
+        given. Chunks that cannot be restored are logged and counted in the\n\
+        scrub_chunks_irreparable metric; `repair --peer` can restore them from a replica.\n\n\
+        With --passes 0 the scrubber runs until stopped, sleeping --interval seconds between\n\
+        passes. Otherwise it exits non-zero if the last pass left damaged chunks.\n\n\
+        With --status-file, each pass leaves its result there as JSON; `serve` and\n\
+        `grpc-serve` given the same file with --scrub-status report it on /healthz and /readyz.\n\n\
+        Example:\n\
+          embeddenator scrub -e project.engram -m project.json --rate 200 --passes 0"
+    )]
+    Scrub(maintain::ScrubArgs),
 
-        /// Output directory to write bincode sub-engrams
-        #[arg(long, default_value = "sub_engrams", value_name = "DIR")]
-        out_sub_engrams_dir: PathBuf,
+    /// Rewrite an append-log engram with live records only
+    #[command(
+        long_about = "Rewrite an append-log engram with live records only\n\n\
+        Every incremental save to an append log (`ingest-stream` checkpoints, library\n\
+        `save_append_log` calls) leaves the records it superseded in the file. This drops\n\
+        them, along with tombstones and old commits, and lays the remaining chunks out file\n\
+        by file in path order so that reading a file or directory touches one contiguous\n\
+        region. The compacted log replaces the original atomically.\n\n\
+        Example:\n\
+          embeddenator compact -e project.edna"
+    )]
+    Compact(maintain::CompactArgs),
 
-        /// Optional compression for the output `.subengram` blobs (default: none)
-        #[arg(long, default_value = "none", value_enum)]
-        sub_engram_compression: CompressionArg,
+    /// Purge files whose retention period has run out
+    #[command(
+        long_about = "Purge files whose retention period has run out
 
-        /// Optional compression level (codec-dependent; used for zstd)
-        #[arg(long, value_name = "LEVEL")]
-        sub_engram_compression_level: Option<i32>,
+        Evaluates the retention policy stored in the manifest (see `embeddenator retention`)
+        and lists the files it has expired. With --apply-retention those files are removed,
+        together with every chunk no remaining file references, the engram and manifest
+        are rewritten in one transaction, and a JSON audit record of what was purged is
+        appended to the audit log (default `<manifest>.retention.jsonl`). Without it
+        nothing is written.
 
-        /// Maximum sparsity per level bundle
-        #[arg(long, default_value_t = 500, value_name = "N")]
-        max_level_sparsity: usize,
+        Example:
+          embeddenator gc -e project.engram -m project.json --apply-retention"
+    )]
+    Gc(maintain::GcArgs),
 
-        /// Optional cap on chunk IDs per node (enables deterministic sharding when exceeded)
-        #[arg(long, value_name = "N")]
-        max_chunks_per_node: Option<usize>,
+    /// Show or change the retention policy of a manifest
+    #[command(
+        long_about = "Show or change the retention policy of a manifest
 
-        /// Embed sub-engrams in the manifest JSON (in addition to writing the directory)
-        #[arg(long, default_value_t = false)]
-        embed_sub_engrams: bool,
+        A retention policy gives paths and namespaces a time to live, directly or through
+        named retention classes, counted from each file's ingest time. It is a TOML file:
 
-        /// Enable verbose output
-        #[arg(short, long)]
-        verbose: bool,
-    },
+          [classes]
+          short = { ttl = \"30d\" }
+          legal-hold = {}          # no ttl: kept indefinitely
+
+          [[rule]]
+          path = \"scratch/**\"    # glob, as for `ls`; the first matching rule applies
+          ttl = \"7d\"             # seconds, or a number with s, m, h, d or w
+
+          [[rule]]
+          path = \"*.log\"
+          class = \"short\"
+
+        Without --set or --clear the current policy is printed with the expiry time of
+        every file it covers. `embeddenator gc --apply-retention` purges expired files.
+
+        Example:
+          embeddenator retention -m project.json --set retention.toml"
+    )]
+    Retention(maintain::RetentionArgs),
+
+    /// Migrate manifests between schema versions
+    #[command(
+        long_about = "Migrate manifests between schema versions
+
+        Manifests record the schema version they were written in. Every command reads
+        manifests of earlier versions by migrating them as they load; `manifest upgrade`
+        rewrites one in the current version for good. `manifest downgrade` writes a copy
+        without the fields added after an older version, for tools that only know it.
+        Both write plain JSON.
+
+        Example:
+          embeddenator manifest upgrade -m project.json
+          embeddenator manifest downgrade -m project.json --to 3 -o project-v3.json"
+    )]
+    Manifest(maintain::ManifestArgs),
+
+    /// Export metadata as Parquet tables or chunk vectors as dense matrices
+    #[command(
+        long_about = "Export metadata as Parquet tables or chunk vectors as dense matrices\n\n\
+        --format parquet (default, requires --features parquet) writes files.parquet\n\
+        (one row per manifest entry) and chunks.parquet (one row per chunk reference,\n\
+        with offsets, correction sizes and probe signatures), for exploring an engram\n\
+        with DuckDB, Polars and similar. No file contents are decoded.\n\n\
+        --format npy writes vectors.npy (one dense row per codebook vector, int8 trits\n\
+        or unit-length float32) and ids.npy (the chunk id of each row). --format faiss\n\
+        writes chunks.faiss, a flat inner-product index keyed by chunk id, for\n\
+        comparing retrieval against FAISS or reusing an existing serving stack.\n\n\
+        --format quantized keeps the vectors ternary: signatures.npy holds one int8 per\n\
+        trit, or with --packing packed2 (the default) four trits per byte, with the\n\
+        layout, dimension and density in signatures.json and the chunk ids in ids.npy.\n\
+        `embeddenator import-signatures` reads such a directory back.\n\n\
+        Examples:\n\
+          embeddenator export -e root.engram -m manifest.json -o engram-tables/\n\
+          embeddenator export -e root.engram -m manifest.json --format npy --dtype int8 -o vectors/\n\
+          embeddenator export -e root.engram -m manifest.json --format faiss -o index/\n\
+          embeddenator export -e root.engram -m manifest.json --format quantized -o signatures/"
+    )]
+    Export(interop::ExportArgs),
+
+    /// Check quantized chunk signatures and convert them back to chunk vectors
+    #[command(
+        long_about = "Check quantized chunk signatures and convert them back to chunk vectors\n\n\
+        Reads a directory in the layout `export --format quantized` writes, from this\n\
+        tool or from any pipeline that follows it, and refuses it unless signatures.json,\n\
+        the array headers and every trit agree: dimension, shape, trit codes, padding\n\
+        bits, ascending ids and the recorded density are all checked. The vectors are\n\
+        written as a chunk vectors file, like the one `ingest --chunk-vectors` writes.\n\n\
+        Example:\n\
+          embeddenator import-signatures -i signatures/ -o root.engram.vectors"
+    )]
+    ImportSignatures(interop::ImportSignaturesArgs),
+
+    /// Archive git history as one engram snapshot per commit
+    #[command(
+        long_about = "Archive git history as one engram snapshot per commit\n\n\
+        Walks the commits in --range oldest first and stores each commit's tree as its\n\
+        own manifest over one shared engram. Chunks already stored under the same path\n\
+        are referenced rather than re-encoded, so unchanged files cost nothing per commit.\n\
+        Running again on the same --output appends only new commits. Needs `git` on PATH.\n\n\
+        Examples:\n\
+          embeddenator ingest-git ~/src/project -o project-history\n\
+          embeddenator ingest-git ~/src/project -o project-history --range v1.0..main\n\
+          embeddenator ingest-git ~/src/project -o project-history --range=--all"
+    )]
+    IngestGit(interop::IngestGitArgs),
+
+    /// Extract one commit's tree from a git archive
+    #[command(
+        long_about = "Extract one commit's tree from a git archive\n\n\
+        REV is a branch or tag name recorded at ingest time, HEAD, or a commit id or\n\
+        unique prefix of at least 4 hex digits. --as-of extracts the tree as it was at a\n\
+        point in time instead: the latest commit made at or before it. Times are\n\
+        @<unix seconds>, YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS]Z, in UTC. With --list, prints\n\
+        the archived commits instead of extracting.\n\n\
+        Examples:\n\
+          embeddenator extract-git project-history v1.0 -o ./v1.0\n\
+          embeddenator extract-git project-history --as-of 2024-06-30 -o ./june\n\
+          embeddenator extract-git project-history --list"
+    )]
+    ExtractGit(interop::ExtractGitArgs),
+
+    /// Query one snapshot of a git archive, or compare two
+    #[command(
+        long_about = "Query one snapshot of a git archive, or compare two\n\n\
+        Searches only the chunks of the tree current at --as-of and prints the top-k\n\
+        matches with the files that use them. With --diff-from, runs the same query at a\n\
+        second point in time and shows which matches were added, removed or kept between\n\
+        it and --as-of. Both take a revision, @<unix seconds>, YYYY-MM-DD or\n\
+        YYYY-MM-DDTHH:MM[:SS]Z (UTC), as in extract-git --as-of.\n\n\
+        Examples:\n\
+          embeddenator query-git project-history -q snippet.rs --as-of v1.0\n\
+          embeddenator query-git project-history -q snippet.rs --diff-from 2024-01-01"
+    )]
+    QueryGit(interop::QueryGitArgs),
 
     /// Mount an engram as a FUSE filesystem (requires --features fuse)
     #[cfg(feature = "fuse")]
@@ -299,590 +1083,266 @@ pub enum Commands {
         long_about = "Mount an engram as a FUSE filesystem\n\n\
         This command mounts an engram at the specified mountpoint, making all files\n\
         accessible through the standard filesystem interface. Files are decoded\n\
-        on-demand from the holographic representation.\n\n\
+        on-demand from the holographic representation, on --read-threads worker\n\
+        threads so parallel readers don't queue behind one another; with --verbose,\n\
+        per-request latencies are printed on unmount.\n\n\
+        By default the command returns once the filesystem is mounted and keeps\n\
+        serving it from a background process; --foreground keeps it attached to the\n\
+        terminal instead. Either way SIGINT, SIGTERM or SIGHUP unmount cleanly.\n\n\
         Requirements:\n\
         • FUSE kernel module must be loaded (modprobe fuse)\n\
         • libfuse3-dev installed on the system\n\
         • Build with: cargo build --features fuse\n\n\
         To unmount:\n\
-          fusermount -u /path/to/mountpoint\n\n\
+          embeddenator unmount /path/to/mountpoint\n\n\
         Example:\n\
           embeddenator mount -e project.engram -m project.json /mnt/engram\n\
-          embeddenator mount --engram backup.engram --mountpoint ~/mnt --allow-other"
+          embeddenator mount -e backup.engram -m backup.json ~/mnt --allow-other -f\n\
+          embeddenator mount -e big.engram -m big.json /mnt/big --cache-mib 512\n\n\
+        Lookups of absent names (.git, node_modules probes during directory walks)\n\
+        are remembered for --negative-ttl seconds; --entry-ttl and --attr-ttl set how\n\
+        long the kernel caches names and attributes. Raise all three for large trees\n\
+        that are walked repeatedly.\n\n\
+        Files opened with O_DIRECT bypass the page cache, and their reads don't fill\n\
+        the chunk cache. --direct-io always applies this to every open, for engrams\n\
+        streamed through once; --direct-io never turns it off.\n\n\
+        With --access-stats FILE, reads are counted per file and chunk, the chunk cache\n\
+        keeps the most read chunks, and the counts are added to FILE on unmount; view\n\
+        them with `heatmap`.\n\n\
+        With --access-policy, each local uid only sees the namespaces (top-level\n\
+        directories) its principal may read; combine it with --allow-other.\n\n\
+        Example:\n\
+          embeddenator mount -e shared.engram -m shared.json /mnt/shared --allow-other --access-policy policy.toml\n\n\
+        Files ingested from a Unix filesystem keep their recorded owner and mode,\n\
+        enforced by the kernel; --uid-map and --gid-map translate the stored ids\n\
+        for this host. Encrypted engrams are decrypted chunk by chunk as files are\n\
+        read rather than at mount time; --cache-mib bounds the plaintext kept.\n\n\
+        Example:\n\
+          embeddenator mount -e home.engram -m home.json /mnt/home --key 1=home.key --allow-other --uid-map 1000:1001"
     )]
-    Mount {
-        /// Engram file to mount
-        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
-        engram: PathBuf,
+    Mount(mount::MountArgs),
 
-        /// Manifest file with metadata and chunk mappings
-        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
-        manifest: PathBuf,
+    /// Unmount an engram mounted with `mount` (requires --features fuse)
+    #[cfg(all(unix, feature = "fuse"))]
+    #[command(
+        long_about = "Unmount an engram mounted with `mount`\n\n\
+        Runs fusermount3 -u, fusermount -u or umount, whichever is available.\n\n\
+        Example:\n\
+          embeddenator unmount /mnt/engram"
+    )]
+    Unmount(mount::UnmountArgs),
 
-        /// Mountpoint directory (must exist and be empty)
-        #[arg(value_name = "MOUNTPOINT", help_heading = "Required")]
-        mountpoint: PathBuf,
+    /// Serve one engram read-only over HTTP (requires --features http)
+    #[cfg(feature = "http")]
+    #[command(
+        long_about = "Serve one engram read-only over HTTP\n\n\
+        Endpoints:\n\
+          GET  /files[?prefix=P]          list files as JSON\n\
+          GET  /files/<path>              reconstructed content (Range requests supported)\n\
+          GET  /search?q=TEXT[&k=N]       most similar chunks and the files using them\n\
+          POST /search[?k=N]              same, with the raw request body as the query\n\
+          GET  /chunks[?after=ID&limit=N] page through chunk ids\n\
+          GET  /chunks/<id>               chunk details and references\n\
+          GET  /healthz, /readyz          liveness and readiness probes\n\n\
+        With --access-policy, requests are authorized by their `Authorization: Bearer`\n\
+        token and only see files in the namespaces the token's principal may read.\n\n\
+        Example:\n\
+          embeddenator serve -e root.engram -m manifest.json --listen 127.0.0.1:8080\n\
+          embeddenator serve -e shared.engram -m shared.json --access-policy policy.toml\n\
+          embeddenator serve -e root.engram -m manifest.json --scrub-status scrub.json"
+    )]
+    Serve(serve::ServeArgs),
 
-        /// Allow other users to access the mount
-        #[arg(long)]
-        allow_other: bool,
+    /// Serve engram files as read-only network block devices (requires --features nbd)
+    #[cfg(feature = "nbd")]
+    #[command(
+        long_about = "Serve engram files as read-only network block devices\n\n\
+        Every file in the manifest is an NBD export named by its logical path; blocks are\n\
+        reconstructed on demand, so a VM can boot straight from an archived disk image.\n\
+        With a single file, or with --default-export, clients may omit the export name.\n\
+        Writes are refused.\n\n\
+        Examples:\n\
+          embeddenator nbd-serve -e disk.engram -m disk.json\n\
+          nbd-client -N vm.img 127.0.0.1 10809 /dev/nbd0 -readonly\n\
+          qemu-system-x86_64 -drive file=nbd://127.0.0.1:10809/vm.img,format=raw,readonly=on"
+    )]
+    NbdServe(serve::NbdServeArgs),
 
-        /// Run in foreground (don't daemonize)
-        #[arg(short, long)]
-        foreground: bool,
+    /// Run SQL against an engram's files and similarity search (requires --features sqlite)
+    #[cfg(feature = "sqlite")]
+    #[command(
+        long_about = "Run SQL against an engram's files and similarity search\n\n\
+        Registers the virtual tables embr_files(path, size, is_text, chunks, blake3) and\n\
+        embr_search(rank, chunk_id, cosine, path), called as embr_search(query [, k]),\n\
+        on a SQLite connection and runs one statement, printing tab-separated rows with\n\
+        a header. With --db the statement can join other tables in that database.\n\n\
+        Examples:\n\
+          embeddenator sql -e root.engram -m manifest.json \"SELECT path, size FROM embr_files ORDER BY size DESC LIMIT 5\"\n\
+          embeddenator sql --db owners.sqlite \"SELECT s.cosine, o.team FROM embr_search('fn main', 5) s JOIN owners o USING (path)\""
+    )]
+    Sql(serve::SqlArgs),
 
-        /// Enable verbose output
-        #[arg(short, long)]
-        verbose: bool,
-    },
-}
+    /// Serve engrams over gRPC (requires --features grpc)
+    #[cfg(feature = "grpc")]
+    #[command(
+        long_about = "Serve engrams over gRPC\n\n\
+        Starts the embeddenator.v1.Embeddenator service (proto/embeddenator_service.proto)\n\
+        with streaming ingest and extract, similarity queries, and engram management.\n\
+        Engrams live in memory; LoadEngram/SaveEngram paths resolve under --data-dir.\n\n\
+        With --access-policy, calls are authorized by the bearer token in their\n\
+        `authorization` metadata: reads and ingest are limited to the principal's\n\
+        namespaces, and managing engrams needs a `*` write grant.\n\n\
+        With --health-listen, GET /healthz and /readyz on that address report the load state of\n\
+        each engram and, with --scrub-status, the last scrub result; /readyz answers 503 while\n\
+        an engram is loading or failed to load.\n\n\
+        Example:\n\
+          embeddenator grpc-serve --listen 127.0.0.1:50051 --data-dir /srv/engrams\n\
+          embeddenator grpc-serve --data-dir /srv/engrams --access-policy policy.toml\n\
+          embeddenator grpc-serve --data-dir /srv/engrams --health-listen 0.0.0.0:8081"
+    )]
+    GrpcServe(serve::GrpcServeArgs),
 
-pub fn run() -> io::Result<()> {
-    let cli = Cli::parse();
+    /// Push chunk vectors into Qdrant or Milvus (requires --features vector-sync)
+    #[cfg(feature = "vector-sync")]
+    #[command(
+        long_about = "Push chunk vectors into Qdrant or Milvus\n\n\
+        Upserts one point per chunk, keyed by chunk id, with a sparse unit-length vector\n\
+        (inner product equals cosine) and a payload listing the files and byte ranges\n\
+        that use the chunk. The collection is created if missing.\n\n\
+        With --base-engram/--base-manifest only the difference from the base is sent:\n\
+        changed chunks, chunks of changed files, and deletions for removed chunks.\n\
+        Run it after each incremental ingest with the previous engram as the base.\n\n\
+        Examples:\n\
+          embeddenator sync-vectors --backend qdrant --url http://localhost:6333 --collection docs\n\
+          embeddenator sync-vectors --backend milvus --url http://localhost:19530 --collection docs \\\n\
+            --base-engram v1.engram --base-manifest v1.json -e v2.engram -m v2.json"
+    )]
+    SyncVectors(interop::SyncVectorsArgs),
 
-    match cli.command {
-        Commands::Ingest {
-            input,
-            engram,
-            manifest,
-            engram_compression,
-            engram_compression_level,
-            verbose,
-        } => {
-            if verbose {
-                println!(
-                    "Embeddenator v{} - Holographic Ingestion",
-                    env!("CARGO_PKG_VERSION")
-                );
-                println!("=====================================");
-            }
-
-            let mut fs = EmbrFS::new();
-            let config = ReversibleVSAConfig::default();
-
-            // Backward-compatible behavior: a single directory input ingests with paths
-            // relative to that directory (no namespacing).
-            if input.len() == 1 && input[0].is_dir() {
-                fs.ingest_directory(&input[0], verbose, &config)?;
-            } else {
-                let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-
-                // Ensure deterministic and collision-resistant namespacing for multiple directory roots.
-                let mut dir_prefix_counts: HashMap<String, usize> = HashMap::new();
-
-                for p in &input {
-                    if !p.exists() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::NotFound,
-                            format!("Input path does not exist: {}", p.display()),
-                        ));
-                    }
-
-                    if p.is_dir() {
-                        let base = p
-                            .file_name()
-                            .and_then(|s| s.to_str())
-                            .filter(|s| !s.is_empty())
-                            .unwrap_or("input")
-                            .to_string();
-                        let count = dir_prefix_counts.entry(base.clone()).or_insert(0);
-                        *count += 1;
-                        let prefix = if *count == 1 {
-                            base
-                        } else {
-                            format!("{}_{}", base, count)
-                        };
-
-                        fs.ingest_directory_with_prefix(p, Some(&prefix), verbose, &config)?;
-                    } else {
-                        let logical = logical_path_for_file_input(p, &cwd);
-                        fs.ingest_file(p, logical, verbose, &config)?;
-                    }
-                }
-            }
-
-            fs.save_engram_with_options(
-                &engram,
-                BinaryWriteOptions {
-                    codec: engram_compression.into(),
-                    level: engram_compression_level,
-                },
-            )?;
-            fs.save_manifest(&manifest)?;
-
-            if verbose {
-                println!("\nIngestion complete!");
-                println!("  Engram: {}", engram.display());
-                println!("  Manifest: {}", manifest.display());
-                println!("  Files: {}", fs.manifest.files.len());
-                println!("  Total chunks: {}", fs.manifest.total_chunks);
-            }
-
-            Ok(())
-        }
+    /// Ingest file events from a Kafka topic (requires --features kafka)
+    #[cfg(feature = "kafka")]
+    #[command(
+        long_about = "Ingest file events from a Kafka topic into a checkpointed engram\n\n\
+        Each event puts or deletes one file. State is checkpointed to an append log every\n\
+        --checkpoint-every events or --checkpoint-secs seconds, and consumer offsets are\n\
+        committed only after a checkpoint, so a crash replays events since the last one\n\
+        (at-least-once). Restarting with the same --checkpoint and --group resumes.\n\n\
+        With --format s3-events, messages are S3 event notifications (for example MinIO\n\
+        bucket notifications to Kafka) and objects are read from the --bucket stores,\n\
+        configured from AWS_* environment variables (requires --features s3). AWS S3\n\
+        only notifies SQS, SNS or EventBridge; bridge those events into a Kafka topic\n\
+        (for example with a Kafka Connect SQS source connector) to ingest them.\n\n\
+        Examples:\n\
+          embeddenator ingest-stream --brokers localhost:9092 --topic files --checkpoint root.edna\n\
+          embeddenator ingest-stream --brokers localhost:9092 --topic minio-events \\\n\
+            --format s3-events --bucket archive --exit-when-idle -e root.engram -m manifest.json"
+    )]
+    IngestStream(ingest::IngestStreamArgs),
+}
 
-        Commands::Extract {
-            engram,
-            manifest,
-            output_dir,
-            verbose,
-        } => {
-            if verbose {
-                println!(
-                    "Embeddenator v{} - Holographic Extraction",
-                    env!("CARGO_PKG_VERSION")
-                );
-                println!("======================================");
-            }
-
-            let engram_data = EmbrFS::load_engram(&engram)?;
-            let manifest_data = EmbrFS::load_manifest(&manifest)?;
-            let config = ReversibleVSAConfig::default();
-
-            EmbrFS::extract(&engram_data, &manifest_data, &output_dir, verbose, &config)?;
-
-            if verbose {
-                println!("\nExtraction complete!");
-                println!("  Output: {}", output_dir.display());
-            }
-
-            Ok(())
-        }
+pub fn run() -> io::Result<()> {
+    let matches = Cli::command().get_matches();
+    parse_with_profile(&matches).and_then(|cli| run_logged(&matches, cli))
+}
 
-        Commands::Query {
-            engram,
-            query,
-            hierarchical_manifest,
-            sub_engrams_dir,
-            k,
-            verbose,
-        } => {
-            if verbose {
-                println!(
-                    "Embeddenator v{} - Holographic Query",
-                    env!("CARGO_PKG_VERSION")
-                );
-                println!("=================================");
-            }
-
-            let engram_data = EmbrFS::load_engram(&engram)?;
-
-            let mut query_file = File::open(&query)?;
-            let mut query_data = Vec::new();
-            query_file.read_to_end(&mut query_data)?;
-
-            // Chunks are encoded with a path-hash bucket shift; when querying we don't know the
-            // original path, so sweep possible buckets (bounded by config.max_path_depth).
-            let config = ReversibleVSAConfig::default();
-            let base_query = SparseVec::encode_data(&query_data, &config, None);
-
-            // Build the codebook index once and reuse it across the sweep.
-            let codebook_index = engram_data.build_codebook_index();
-
-            let mut best_similarity = f64::MIN;
-            let mut best_shift = 0usize;
-            let mut best_top_cosine = f64::MIN;
-
-            // Merge matches across shifts; keep the best score per chunk.
-            let mut merged: HashMap<usize, (f64, i32)> = HashMap::new();
-
-            // Optionally merge hierarchical hits too.
-            let mut merged_hier: HashMap<(String, usize), (f64, i32)> = HashMap::new();
-
-            let hierarchical_loaded = if let (Some(hier_path), Some(_)) = (hierarchical_manifest.as_ref(), sub_engrams_dir.as_ref()) {
-                Some(load_hierarchical_manifest(hier_path)?)
-            } else {
-                None
-            };
-
-            // Increase per-bucket cutoff so global top-k merge is less likely to miss true winners.
-            let k_sweep = (k.saturating_mul(10)).max(100);
-            let candidate_k = (k_sweep.saturating_mul(10)).max(200);
-
-            for depth in 0..config.max_path_depth.max(1) {
-                let shift = depth * config.base_shift;
-                let query_vec = base_query.permute(shift);
-
-                let similarity = query_vec.cosine(&engram_data.root);
-                if similarity > best_similarity {
-                    best_similarity = similarity;
-                    best_shift = shift;
-                }
-
-                let matches = engram_data.query_codebook_with_index(
-                    &codebook_index,
-                    &query_vec,
-                    candidate_k,
-                    k_sweep,
-                );
-
-                if let Some(top) = matches.first() {
-                    if top.cosine > best_top_cosine {
-                        best_top_cosine = top.cosine;
-                        best_shift = shift;
-                        best_similarity = similarity;
-                    }
-                }
-
-                for m in matches {
-                    let entry = merged.entry(m.id).or_insert((m.cosine, m.approx_score));
-                    if m.cosine > entry.0 {
-                        *entry = (m.cosine, m.approx_score);
-                    }
-                }
-            }
-
-            // Hierarchical query can be expensive (sub-engram loads + per-node indexing).
-            // Run it once using the best shift from the sweep.
-            if let (Some(hierarchical), Some(sub_dir)) = (hierarchical_loaded.as_ref(), sub_engrams_dir.as_ref()) {
-                let store = DirectorySubEngramStore::new(sub_dir);
-                let bounds = HierarchicalQueryBounds {
-                    k,
-                    ..HierarchicalQueryBounds::default()
-                };
-                let query_vec = base_query.permute(best_shift);
-                let hier_hits = query_hierarchical_codebook_with_store(
-                    hierarchical,
-                    &store,
-                    &engram_data.codebook,
-                    &query_vec,
-                    &bounds,
-                );
-                for h in hier_hits {
-                    let key = (h.sub_engram_id, h.chunk_id);
-                    let entry = merged_hier.entry(key).or_insert((h.cosine, h.approx_score));
-                    if h.cosine > entry.0 {
-                        *entry = (h.cosine, h.approx_score);
-                    }
-                }
-            }
-
-            println!("Query file: {}", query.display());
-            if verbose {
-                println!(
-                    "Best bucket-shift: {} (buckets 0..{})",
-                    best_shift,
-                    config.max_path_depth.saturating_sub(1)
-                );
-            }
-            println!("Similarity to engram: {:.4}", best_similarity);
-
-            let mut top_matches: Vec<(usize, f64, i32)> = merged
-                .into_iter()
-                .map(|(id, (cosine, approx))| (id, cosine, approx))
-                .collect();
-            top_matches.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-            top_matches.truncate(k);
-
-            if !top_matches.is_empty() {
-                println!("Top codebook matches:");
-                for (id, cosine, approx) in top_matches {
-                    println!("  chunk {}  cosine {:.4}  approx_dot {}", id, cosine, approx);
-                }
-            } else if verbose {
-                println!("Top codebook matches: (none)");
-            }
-
-            let mut top_hier: Vec<(String, usize, f64, i32)> = merged_hier
-                .into_iter()
-                .map(|((sub_id, chunk_id), (cosine, approx))| (sub_id, chunk_id, cosine, approx))
-                .collect();
-            top_hier.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
-            top_hier.truncate(k);
-
-            if !top_hier.is_empty() {
-                println!("Top hierarchical matches:");
-                for (sub_id, chunk_id, cosine, approx) in top_hier {
-                    println!("  sub {}  chunk {}  cosine {:.4}  approx_dot {}", sub_id, chunk_id, cosine, approx);
-                }
-            } else if verbose && hierarchical_manifest.is_some() {
-                println!("Top hierarchical matches: (none)");
-            }
-
-            if best_similarity > 0.75 {
-                println!("Status: STRONG MATCH");
-            } else if best_similarity > 0.3 {
-                println!("Status: Partial match");
-            } else {
-                println!("Status: No significant match");
-            }
-
-            Ok(())
-        }
+/// Run `cli` as one [`logging::Operation`] named after its subcommand, so
+/// everything it logs shares a correlation id.
+fn run_logged(matches: &clap::ArgMatches, cli: Cli) -> io::Result<()> {
+    let op = logging::Operation::new(matches.subcommand_name().unwrap_or("embeddenator"));
+    let _op = op.enter();
+    let started = std::time::Instant::now();
+    logging::info("cli", format_args!("{} started", op.name()));
+    let result = run_cli(cli);
+    let secs = started.elapsed().as_secs_f64();
+    match &result {
+        Ok(()) => logging::info("cli", format_args!("{} finished in {:.3}s", op.name(), secs)),
+        Err(e) => logging::info("cli", format_args!("{} failed after {:.3}s: {}", op.name(), secs, e)),
+    }
+    result
+}
 
-        Commands::QueryText {
-            engram,
-            text,
-            hierarchical_manifest,
-            sub_engrams_dir,
-            k,
-            verbose,
-        } => {
-            if verbose {
-                println!(
-                    "Embeddenator v{} - Holographic Query (Text)",
-                    env!("CARGO_PKG_VERSION")
-                );
-                println!("========================================");
-            }
-
-            let engram_data = EmbrFS::load_engram(&engram)?;
-
-            let config = ReversibleVSAConfig::default();
-            let base_query = SparseVec::encode_data(text.as_bytes(), &config, None);
-
-            let codebook_index = engram_data.build_codebook_index();
-
-            let mut best_similarity = f64::MIN;
-            let mut best_shift = 0usize;
-            let mut best_top_cosine = f64::MIN;
-
-            let mut merged: HashMap<usize, (f64, i32)> = HashMap::new();
-            let mut merged_hier: HashMap<(String, usize), (f64, i32)> = HashMap::new();
-
-            let hierarchical_loaded = if let (Some(hier_path), Some(_)) = (hierarchical_manifest.as_ref(), sub_engrams_dir.as_ref()) {
-                Some(load_hierarchical_manifest(hier_path)?)
-            } else {
-                None
-            };
-
-            let k_sweep = (k.saturating_mul(10)).max(100);
-            let candidate_k = (k_sweep.saturating_mul(10)).max(200);
-
-            for depth in 0..config.max_path_depth.max(1) {
-                let shift = depth * config.base_shift;
-                let query_vec = base_query.permute(shift);
-
-                let similarity = query_vec.cosine(&engram_data.root);
-                if similarity > best_similarity {
-                    best_similarity = similarity;
-                    best_shift = shift;
-                }
-
-                let matches = engram_data.query_codebook_with_index(
-                    &codebook_index,
-                    &query_vec,
-                    candidate_k,
-                    k_sweep,
-                );
-
-                if let Some(top) = matches.first() {
-                    if top.cosine > best_top_cosine {
-                        best_top_cosine = top.cosine;
-                        best_shift = shift;
-                        best_similarity = similarity;
-                    }
-                }
-
-                for m in matches {
-                    let entry = merged.entry(m.id).or_insert((m.cosine, m.approx_score));
-                    if m.cosine > entry.0 {
-                        *entry = (m.cosine, m.approx_score);
-                    }
-                }
-            }
-
-            if let (Some(hierarchical), Some(sub_dir)) = (hierarchical_loaded.as_ref(), sub_engrams_dir.as_ref()) {
-                let store = DirectorySubEngramStore::new(sub_dir);
-                let bounds = HierarchicalQueryBounds {
-                    k,
-                    ..HierarchicalQueryBounds::default()
-                };
-                let query_vec = base_query.permute(best_shift);
-                let hier_hits = query_hierarchical_codebook_with_store(
-                    hierarchical,
-                    &store,
-                    &engram_data.codebook,
-                    &query_vec,
-                    &bounds,
-                );
-                for h in hier_hits {
-                    let key = (h.sub_engram_id, h.chunk_id);
-                    let entry = merged_hier.entry(key).or_insert((h.cosine, h.approx_score));
-                    if h.cosine > entry.0 {
-                        *entry = (h.cosine, h.approx_score);
-                    }
-                }
-            }
-
-            println!("Query text: {}", text);
-            if verbose {
-                println!(
-                    "Best bucket-shift: {} (buckets 0..{})",
-                    best_shift,
-                    config.max_path_depth.saturating_sub(1)
-                );
-            }
-            println!("Similarity to engram: {:.4}", best_similarity);
-
-            let mut top_matches: Vec<(usize, f64, i32)> = merged
-                .into_iter()
-                .map(|(id, (cosine, approx))| (id, cosine, approx))
-                .collect();
-            top_matches.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-            top_matches.truncate(k);
-
-            if !top_matches.is_empty() {
-                println!("Top codebook matches:");
-                for (id, cosine, approx) in top_matches {
-                    println!("  chunk {}  cosine {:.4}  approx_dot {}", id, cosine, approx);
-                }
-            } else if verbose {
-                println!("Top codebook matches: (none)");
-            }
-
-            let mut top_hier: Vec<(String, usize, f64, i32)> = merged_hier
-                .into_iter()
-                .map(|((sub_id, chunk_id), (cosine, approx))| (sub_id, chunk_id, cosine, approx))
-                .collect();
-            top_hier.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
-            top_hier.truncate(k);
-
-            if !top_hier.is_empty() {
-                println!("Top hierarchical matches:");
-                for (sub_id, chunk_id, cosine, approx) in top_hier {
-                    println!("  sub {}  chunk {}  cosine {:.4}  approx_dot {}", sub_id, chunk_id, cosine, approx);
-                }
-            } else if verbose && hierarchical_manifest.is_some() {
-                println!("Top hierarchical matches: (none)");
-            }
-
-            Ok(())
-        }
+/// Build the [`Cli`] from `matches`, filling flags not given on the command
+/// line from the environment and the selected profile.
+fn parse_with_profile(matches: &clap::ArgMatches) -> io::Result<Cli> {
+    let mut cli = Cli::from_arg_matches(matches).unwrap_or_else(|e| e.exit());
+    let profile = Profile::load(cli.profile.as_deref(), |name| env::var(name).ok())?;
+    logging::init_with(&profile.log_config()?);
+    profile.apply(&mut cli.command, matches)?;
+    Ok(cli)
+}
 
-        Commands::BundleHier {
-            engram,
-            manifest,
-            out_hierarchical_manifest,
-            out_sub_engrams_dir,
-            max_level_sparsity,
-            max_chunks_per_node,
-            embed_sub_engrams,
-            sub_engram_compression,
-            sub_engram_compression_level,
-            verbose,
-        } => {
-            if verbose {
-                println!(
-                    "Embeddenator v{} - Build Hierarchical Artifacts",
-                    env!("CARGO_PKG_VERSION")
-                );
-                println!("=============================================");
-            }
-
-            let engram_data = EmbrFS::load_engram(&engram)?;
-            let manifest_data = EmbrFS::load_manifest(&manifest)?;
-
-            let mut fs = EmbrFS::new();
-            fs.engram = engram_data;
-            fs.manifest = manifest_data;
-
-            let config = ReversibleVSAConfig::default();
-            let mut hierarchical = fs.bundle_hierarchically_with_options(
-                max_level_sparsity,
-                max_chunks_per_node,
-                verbose,
-                &config,
-            )?;
-
-            // Always write the sub-engrams directory for store-backed retrieval.
-            save_sub_engrams_dir_with_options(
-                &hierarchical.sub_engrams,
-                &out_sub_engrams_dir,
-                BinaryWriteOptions {
-                    codec: sub_engram_compression.into(),
-                    level: sub_engram_compression_level,
-                },
-            )?;
-
-            if !embed_sub_engrams {
-                hierarchical.sub_engrams.clear();
-            }
-
-            save_hierarchical_manifest(&hierarchical, &out_hierarchical_manifest)?;
-
-            if verbose {
-                println!("Wrote hierarchical manifest: {}", out_hierarchical_manifest.display());
-                println!("Wrote sub-engrams dir: {}", out_sub_engrams_dir.display());
-            }
-
-            Ok(())
-        }
+fn run_cli(mut cli: Cli) -> io::Result<()> {
+    if let Some(path) = cli.flamegraph.take() {
+        profile::enable(cli.flamegraph_sample);
+        let result = run_cli(cli);
+        profile::disable();
+        // A failed command still leaves a profile worth looking at.
+        std::fs::write(&path, profile::snapshot().folded())?;
+        return result;
+    }
+    let json_output = cli.output_format == OutputFormat::Json;
 
+    match cli.command {
+        Commands::Ingest(args) => args.run(json_output),
+        Commands::Extract(args) => args.run(json_output),
+        Commands::Stats(args) => args.run(json_output),
+        Commands::Info(args) => args.run(json_output),
+        Commands::Heatmap(args) => args.run(json_output),
+        Commands::Capacity(args) => args.run(json_output),
+        Commands::Contains(args) => args.run(json_output),
+        Commands::Monitor(args) => args.run(json_output),
+        Commands::Ls(args) => args.run(json_output),
+        Commands::Tree(args) => args.run(json_output),
+        Commands::Cat(args) => args.run(),
+        Commands::Shell(args) => args.run(),
+        Commands::Bench(args) => args.run(json_output),
+        Commands::Verify(args) => args.run(json_output),
+        Commands::Convert(args) => args.run(json_output),
+        Commands::Codebook(args) => args.run(json_output),
+        Commands::Query(args) => args.run(json_output),
+        Commands::QueryText(args) => args.run(json_output),
+        Commands::FindSimilar(args) => args.run(json_output),
+        Commands::Compute(args) => args.run(json_output),
+        Commands::QueryRole(args) => args.run(json_output),
+        Commands::QueryFederated(args) => args.run(json_output),
+        Commands::Align(args) => args.run(json_output),
+        Commands::Eval(args) => args.run(json_output),
+        Commands::BundleHier(args) => args.run(json_output),
+        Commands::Keygen(args) => args.run(json_output),
+        Commands::Seal(args) => args.run(json_output),
+        Commands::Delta(args) => args.run(json_output),
+        Commands::ApplyDelta(args) => args.run(json_output),
+        Commands::Generations(args) => args.run(json_output),
+        Commands::Push(args) => args.run(json_output),
+        Commands::Pull(args) => args.run(json_output),
+        Commands::SyncServer(args) => args.run(),
+        Commands::Repair(args) => args.run(json_output),
+        Commands::Rekey(args) => args.run(json_output),
+        Commands::Scrub(args) => args.run(json_output),
+        Commands::Compact(args) => args.run(json_output),
+        Commands::Gc(args) => args.run(json_output),
+        Commands::Retention(args) => args.run(json_output),
+        Commands::Manifest(args) => args.run(json_output),
+        Commands::Export(args) => args.run(json_output),
+        Commands::ImportSignatures(args) => args.run(json_output),
+        Commands::IngestGit(args) => args.run(json_output),
+        Commands::ExtractGit(args) => args.run(json_output),
+        Commands::QueryGit(args) => args.run(json_output),
         #[cfg(feature = "fuse")]
-        Commands::Mount {
-            engram,
-            manifest,
-            mountpoint,
-            allow_other,
-            foreground: _foreground,
-            verbose,
-        } => {
-            use crate::fuse_shim::{EngramFS, MountOptions, mount};
-            use crate::embrfs::DEFAULT_CHUNK_SIZE;
-            
-            if verbose {
-                println!(
-                    "Embeddenator v{} - FUSE Mount",
-                    env!("CARGO_PKG_VERSION")
-                );
-                println!("============================");
-            }
-
-            // Load engram and manifest
-            let engram_data = EmbrFS::load_engram(&engram)?;
-            let manifest_data = EmbrFS::load_manifest(&manifest)?;
-            let config = ReversibleVSAConfig::default();
-
-            if verbose {
-                println!("Loaded engram: {}", engram.display());
-                println!("Loaded manifest: {} files", manifest_data.files.len());
-            }
-
-            // Production-hardening: build a metadata-only filesystem and decode chunks on-demand
-            // during reads. This avoids preloading all file bytes into memory at mount time.
-            let fuse_fs = EngramFS::from_engram(
-                engram_data,
-                manifest_data,
-                config,
-                DEFAULT_CHUNK_SIZE,
-                true,
-            );
-
-            if verbose {
-                println!("Populated {} files into FUSE filesystem", fuse_fs.file_count());
-                println!("Total size: {} bytes", fuse_fs.total_size());
-                println!("Mounting at: {}", mountpoint.display());
-                println!();
-            }
-
-            // Verify mountpoint exists
-            if !mountpoint.exists() {
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("Mountpoint does not exist: {}", mountpoint.display())
-                ));
-            }
-
-            // Configure mount options
-            let options = MountOptions {
-                read_only: true,
-                allow_other,
-                allow_root: !allow_other,
-                fsname: format!("engram:{}", engram.display()),
-            };
-
-            // Mount the filesystem (blocks until unmounted)
-            println!("EngramFS mounted at {}", mountpoint.display());
-            println!("Use 'fusermount -u {}' to unmount", mountpoint.display());
-            
-            mount(fuse_fs, &mountpoint, options)?;
-
-            if verbose {
-                println!("\nUnmounted.");
-            }
-
-            Ok(())
-        }
+        Commands::Mount(args) => args.run(json_output),
+        #[cfg(all(unix, feature = "fuse"))]
+        Commands::Unmount(args) => args.run(),
+        #[cfg(feature = "http")]
+        Commands::Serve(args) => args.run(json_output),
+        #[cfg(feature = "nbd")]
+        Commands::NbdServe(args) => args.run(json_output),
+        #[cfg(feature = "sqlite")]
+        Commands::Sql(args) => args.run(json_output),
+        #[cfg(feature = "grpc")]
+        Commands::GrpcServe(args) => args.run(json_output),
+        #[cfg(feature = "vector-sync")]
+        Commands::SyncVectors(args) => args.run(json_output),
+        #[cfg(feature = "kafka")]
+        Commands::IngestStream(args) => args.run(json_output),
     }
-}
+}
\ No newline at end of file
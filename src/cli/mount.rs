@@ -0,0 +1,289 @@
+//! `mount` and `unmount`: an engram as a FUSE filesystem.
+
+use super::{build_keyring, load_access_stats, parse_id_map_arg, save_access_stats, EngramArgs, KeyArgs};
+use crate::codebook::ChunkCache;
+use crate::embrfs::EmbrFS;
+use crate::info::StorageFormat;
+use crate::lazy_engram::{self, LazyEngram};
+use crate::namespace_keys;
+use clap::Args;
+use std::env;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct MountArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Mountpoint directory (must exist and be empty)
+    #[arg(value_name = "MOUNTPOINT", help_heading = "Required")]
+    pub mountpoint: PathBuf,
+
+    /// Allow other users to access the mount
+    #[arg(long)]
+    pub allow_other: bool,
+
+    /// Run in foreground (don't daemonize)
+    #[arg(short, long)]
+    pub foreground: bool,
+
+    /// Maximum decoded chunks kept in the read cache (0 disables caching)
+    #[arg(long, default_value_t = crate::fuse_shim::DEFAULT_CACHE_ENTRIES, value_name = "N")]
+    pub cache_entries: usize,
+
+    /// Maximum size of the read cache in MiB (0 disables caching)
+    #[arg(long, default_value_t = crate::fuse_shim::DEFAULT_CACHE_BYTES >> 20, value_name = "MIB")]
+    pub cache_mib: usize,
+
+    /// Threads decoding reads in parallel (default: one per CPU; 0 serves reads on the request thread)
+    #[arg(long, value_name = "N")]
+    pub read_threads: Option<usize>,
+
+    /// Seconds the kernel may cache name lookups
+    #[arg(long, default_value_t = 1, value_name = "SECS")]
+    pub entry_ttl: u64,
+
+    /// Seconds the kernel may cache file attributes
+    #[arg(long, default_value_t = 1, value_name = "SECS")]
+    pub attr_ttl: u64,
+
+    /// Seconds lookups of absent names are remembered (0 disables)
+    #[arg(long, default_value_t = 1, value_name = "SECS")]
+    pub negative_ttl: u64,
+
+    /// Which opens bypass the kernel page cache
+    #[arg(long, value_enum, default_value = "on-request", value_name = "WHEN")]
+    pub direct_io: DirectIoArg,
+
+    /// TOML access policy limiting each uid to the namespaces it is granted
+    #[arg(long, value_name = "FILE")]
+    pub access_policy: Option<PathBuf>,
+
+    /// Report files stored as owned by uid FROM as owned by uid TO. Repeatable.
+    #[arg(long = "uid-map", value_name = "FROM:TO", value_parser = parse_id_map_arg)]
+    pub uid_map: Vec<(u32, u32)>,
+
+    /// Count file and chunk reads, keep the most read chunks cached, and add the
+    /// counts to FILE on unmount (see `heatmap`)
+    #[arg(long, value_name = "FILE")]
+    pub access_stats: Option<PathBuf>,
+
+    /// Report files stored with gid FROM as having gid TO. Repeatable.
+    #[arg(long = "gid-map", value_name = "FROM:TO", value_parser = parse_id_map_arg)]
+    pub gid_map: Vec<(u32, u32)>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl MountArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let MountArgs {
+            paths: EngramArgs { engram, manifest },
+            mountpoint,
+            allow_other,
+            foreground,
+            cache_entries,
+            cache_mib,
+            read_threads,
+            entry_ttl,
+            attr_ttl,
+            negative_ttl,
+            direct_io,
+            access_policy,
+            uid_map,
+            access_stats,
+            gid_map,
+            keys: KeyArgs { keys },
+            verbose,
+        } = self;
+        use crate::fuse_shim::{self, EngramFS, IdMap, MountOptions, UnmountReason};
+        use std::time::Duration;
+        use crate::reader::EngramReader;
+        use std::sync::Arc;
+
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - FUSE Mount",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("============================");
+        }
+
+        // Load engram and manifest
+        let keyring = build_keyring(&keys)?;
+        let manifest_data = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+        let config = manifest_data.encoding().vsa;
+        // The daemon changes directory to /.
+        let access_stats = access_stats.map(std::path::absolute).transpose()?;
+        let access = access_stats.as_deref().map(load_access_stats).transpose()?.map(Arc::new);
+        let mut cache = ChunkCache::with_limits(cache_entries, cache_mib.saturating_mul(1 << 20));
+        if let Some(access) = &access {
+            cache = cache.with_access_stats(Arc::clone(access));
+        }
+        let cache = Arc::new(cache);
+        if verbose {
+            println!("Loaded manifest: {} files", manifest_data.files.len());
+        }
+
+        // Production-hardening: build a metadata-only filesystem and decode chunks on-demand
+        // during reads. This avoids preloading all file bytes into memory at mount time.
+        // Encrypted engrams also stay encrypted on disk until a chunk is read,
+        // unless namespace key groups have to be decrypted into them.
+        let mut fuse_fs = if StorageFormat::detect(&engram)?.encrypted
+            && manifest_data.namespace_keys.is_none()
+            && lazy_engram::is_lazy_loadable(&engram)?
+        {
+            let lazy = LazyEngram::open(&engram, &keyring)?;
+            if verbose {
+                println!("Opened encrypted engram: {} ({} chunks, decrypted on access)", engram.display(), lazy.chunk_count());
+            }
+            EngramFS::from_lazy(lazy, &manifest_data, config, true).with_chunk_cache(cache)
+        } else {
+            let mut engram_data = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+            // Files under keys not given stay listed but fail to read.
+            let opened = namespace_keys::open(&mut engram_data, &manifest_data, &engram, &keyring)?;
+            if verbose {
+                println!("Loaded engram: {}", engram.display());
+                if !opened.locked.is_empty() {
+                    println!("Namespace keys not given: {:?}", opened.locked);
+                }
+            }
+            let reader = EngramReader::new(engram_data, manifest_data, config).with_chunk_cache(cache);
+            EngramFS::from_reader(&reader, true)
+        };
+        let ids = uid_map
+            .into_iter()
+            .fold(IdMap::default(), |ids, (from, to)| ids.uid(from, to));
+        let ids = gid_map.into_iter().fold(ids, |ids, (from, to)| ids.gid(from, to));
+        fuse_fs = fuse_fs.with_id_map(ids);
+        let read_threads = read_threads
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        fuse_fs = fuse_fs
+            .with_read_threads(read_threads)?
+            .with_ttls(Duration::from_secs(entry_ttl), Duration::from_secs(attr_ttl))
+            .with_negative_cache(Duration::from_secs(negative_ttl), fuse_shim::DEFAULT_NEGATIVE_ENTRIES)
+            .with_direct_io(direct_io.into());
+        let latencies = Arc::clone(fuse_fs.latencies());
+        if let Some(access) = &access {
+            fuse_fs = fuse_fs.with_access_stats(Arc::clone(access));
+        }
+        if let Some(path) = access_policy {
+            fuse_fs = fuse_fs.with_access_policy(crate::access::AccessPolicy::load(path)?);
+        }
+
+        if verbose {
+            println!("Populated {} files into FUSE filesystem", fuse_fs.file_count());
+            println!("Total size: {} bytes", fuse_fs.total_size());
+            println!("Mounting at: {}", mountpoint.display());
+            println!();
+        }
+
+        // Verify mountpoint exists
+        if !mountpoint.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Mountpoint does not exist: {}", mountpoint.display())
+            ));
+        }
+        // The daemon changes directory to /.
+        let mountpoint = mountpoint.canonicalize()?;
+
+        // Configure mount options
+        let options = MountOptions {
+            read_only: true,
+            allow_other,
+            allow_root: !allow_other,
+            fsname: format!("engram:{}", engram.display()),
+        };
+
+        let daemon = if foreground { None } else { Some(fuse_shim::daemonize()?) };
+        let session = match fuse_shim::spawn_mount(fuse_fs, &mountpoint, options) {
+            Ok(session) => session,
+            Err(e) => {
+                if let Some(daemon) = daemon {
+                    // The waiting parent reports the error.
+                    daemon.fail(&e);
+                    std::process::exit(1);
+                }
+                return Err(e);
+            }
+        };
+        println!("EngramFS mounted at {}", mountpoint.display());
+        println!("Use 'embeddenator unmount {}' to unmount", mountpoint.display());
+        if let Some(daemon) = daemon {
+            daemon.ready()?;
+        }
+
+        let reason = fuse_shim::wait_for_unmount(&mountpoint);
+        // Dropping the session unmounts, unless that already happened.
+        drop(session);
+        if let (Some(path), Some(access)) = (&access_stats, &access) {
+            save_access_stats(path, access)?;
+        }
+
+        if verbose {
+            match reason {
+                UnmountReason::Signal(sig) => println!("\nReceived signal {sig}; unmounted."),
+                UnmountReason::External => println!("\nUnmounted externally."),
+            }
+            for op in fuse_shim::FuseOp::ALL {
+                let stats = latencies.snapshot(op);
+                if stats.count > 0 {
+                    println!(
+                        "  {:<8} {:>8} requests  p50 {:?}  p99 {:?}  max {:?}",
+                        op.name(),
+                        stats.count,
+                        stats.quantile(0.5),
+                        stats.quantile(0.99),
+                        stats.max()
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[derive(Args)]
+pub struct UnmountArgs {
+    /// Mountpoint to unmount
+    #[arg(value_name = "MOUNTPOINT")]
+    pub mountpoint: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnmountArgs {
+    pub(super) fn run(self) -> io::Result<()> {
+        let UnmountArgs { mountpoint } = self;
+        crate::fuse_shim::unmount(&mountpoint)
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum DirectIoArg {
+    /// Files opened with O_DIRECT
+    OnRequest,
+    /// Every open
+    Always,
+    /// No open; O_DIRECT reads go through the page cache
+    Never,
+}
+
+impl From<DirectIoArg> for crate::fuse_shim::DirectIo {
+    fn from(v: DirectIoArg) -> Self {
+        match v {
+            DirectIoArg::OnRequest => Self::OnRequest,
+            DirectIoArg::Always => Self::Always,
+            DirectIoArg::Never => Self::Never,
+        }
+    }
+}
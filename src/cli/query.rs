@@ -0,0 +1,1334 @@
+//! Similarity search commands: `query`, `query-text`, `find-similar` and the
+//! retrieval evaluation tools.
+
+use super::{load_access_stats, load_semantic_encoder, print_json, rank_files, save_access_stats, ChecksumArg, CompressionArg, EngramArgs, RankedFile};
+use crate::embrfs::{
+    DirectorySubEngramStore, EmbrFS, Engram, HierarchicalQueryBounds, Manifest,
+    HierarchicalExplanation, NodeOutcome,
+    explain_hierarchical_query_with_store, load_hierarchical_manifest,
+    query_hierarchical_budgeted_with_store,
+    save_hierarchical_manifest, save_sub_engrams_dir_with_options,
+};
+use crate::retrieval::eval::{self, EvalOptions, EvalReport, QuerySet};
+use crate::retrieval::align::{self, AlignOptions, Alignment};
+use crate::retrieval::federation::{FederatedIndex, ScoreNormalization};
+use crate::retrieval::similar::{find_similar, SimilarOptions};
+use crate::semantic::{self, SemanticSignatures};
+use crate::envelope::BinaryWriteOptions;
+use crate::access_stats::AccessKind;
+use crate::vsa::{compute, SparseVec, ReversibleVSAConfig};
+use clap::Args;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::collections::HashMap;
+
+#[derive(Args)]
+pub struct QueryArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Query file to search for
+    #[arg(short, long, value_name = "FILE", help_heading = "Required")]
+    pub query: PathBuf,
+
+    /// Optional hierarchical manifest (enables selective unfolding search)
+    #[arg(long, value_name = "FILE")]
+    pub hierarchical_manifest: Option<PathBuf>,
+
+    /// Directory containing bincode-serialized sub-engrams (used with --hierarchical-manifest)
+    #[arg(long, value_name = "DIR")]
+    pub sub_engrams_dir: Option<PathBuf>,
+
+    /// Restrict codebook matches to this namespace (reads --manifest)
+    #[arg(long, value_name = "NAME")]
+    pub namespace: Option<String>,
+
+    /// Top-k results to print for file, codebook and hierarchical search
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+
+    /// Explain the hierarchical matches: the nodes that returned each
+    /// chunk and the path down to it, and why other nodes were skipped
+    #[arg(long, requires = "hierarchical_manifest")]
+    pub explain: bool,
+
+    /// Stop the hierarchical search after MS milliseconds and report the
+    /// best matches so far, flagged as truncated
+    #[arg(long, value_name = "MS", requires = "hierarchical_manifest")]
+    pub time_budget_ms: Option<u64>,
+
+    /// Stop the hierarchical search once N index candidates were scanned,
+    /// flagged as truncated
+    #[arg(long, value_name = "N", requires = "hierarchical_manifest")]
+    pub max_candidates: Option<usize>,
+
+    /// Count the returned chunks as queried in FILE, adding to earlier counts (see `heatmap`)
+    #[arg(long, value_name = "FILE")]
+    pub access_stats: Option<PathBuf>,
+
+    /// Enable verbose output showing similarity scores and details
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl QueryArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let QueryArgs {
+            paths: EngramArgs { engram, manifest },
+            query,
+            hierarchical_manifest,
+            sub_engrams_dir,
+            namespace,
+            k,
+            explain,
+            time_budget_ms,
+            max_candidates,
+            access_stats,
+            verbose,
+        } = self;
+        let started = std::time::Instant::now();
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - Holographic Query",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("=================================");
+        }
+
+        let engram_data = EmbrFS::load_engram(&engram)?;
+
+        let mut query_file = File::open(&query)?;
+        let mut query_data = Vec::new();
+        query_file.read_to_end(&mut query_data)?;
+
+        // The manifest resolves --namespace, names the files to rank and says
+        // how chunks were encoded; without one only chunk matches can be shown.
+        let manifest_data = if namespace.is_some() || manifest.exists() {
+            Some(EmbrFS::load_manifest(&manifest)?)
+        } else {
+            None
+        };
+
+        // Chunks are encoded with a path-hash bucket shift; when querying we don't know the
+        // original path, so sweep possible buckets (bounded by config.max_path_depth).
+        let config = manifest_data.as_ref().map_or_else(ReversibleVSAConfig::default, |m| m.encoding().vsa);
+        let base_query = SparseVec::encode_chunk(&query_data, &config, None);
+
+        // Build the codebook index once and reuse it across the sweep.
+        let codebook_index = engram_data.build_codebook_index();
+        let allowed = match (namespace.as_deref(), manifest_data.as_ref()) {
+            (Some(ns), Some(m)) => Some(m.chunk_ids_in_namespace(ns)),
+            _ => None,
+        };
+
+        let mut best_similarity = f64::MIN;
+        let mut best_shift = 0usize;
+        let mut best_top_cosine = f64::MIN;
+
+        // Merge matches across shifts; keep the best score per chunk.
+        let mut merged: HashMap<usize, (f64, i32)> = HashMap::new();
+
+        // Optionally merge hierarchical hits too.
+        let mut merged_hier: HashMap<(String, usize), (f64, i32)> = HashMap::new();
+
+        let hierarchical_loaded = if let (Some(hier_path), Some(_)) = (hierarchical_manifest.as_ref(), sub_engrams_dir.as_ref()) {
+            Some(load_hierarchical_manifest(hier_path)?)
+        } else {
+            None
+        };
+
+        // Increase per-bucket cutoff so global top-k merge is less likely to miss true winners.
+        let k_sweep = (k.saturating_mul(10)).max(100);
+        let candidate_k = (k_sweep.saturating_mul(10)).max(200);
+
+        for depth in 0..config.max_path_depth.max(1) {
+            let shift = depth * config.base_shift;
+            let query_vec = base_query.permute(shift);
+
+            let similarity = query_vec.cosine(&engram_data.root);
+            if similarity > best_similarity {
+                best_similarity = similarity;
+                best_shift = shift;
+            }
+
+            let matches = match allowed.as_ref() {
+                Some(allowed) => engram_data.query_codebook_with_index_filtered(
+                    &codebook_index,
+                    &query_vec,
+                    candidate_k,
+                    k_sweep,
+                    allowed,
+                ),
+                None => engram_data.query_codebook_with_index(
+                    &codebook_index,
+                    &query_vec,
+                    candidate_k,
+                    k_sweep,
+                ),
+            };
+
+            if let Some(top) = matches.first() {
+                if top.cosine > best_top_cosine {
+                    best_top_cosine = top.cosine;
+                    best_shift = shift;
+                    best_similarity = similarity;
+                }
+            }
+
+            for m in matches {
+                let entry = merged.entry(m.id).or_insert((m.cosine, m.approx_score));
+                if m.cosine > entry.0 {
+                    *entry = (m.cosine, m.approx_score);
+                }
+            }
+        }
+
+        // Hierarchical query can be expensive (sub-engram loads + per-node indexing).
+        // Run it once using the best shift from the sweep.
+        let mut explanation = None;
+        let mut truncated = None;
+        if let (Some(hierarchical), Some(sub_dir)) = (hierarchical_loaded.as_ref(), sub_engrams_dir.as_ref()) {
+            let store = DirectorySubEngramStore::new(sub_dir).with_digests(hierarchical);
+            let bounds = HierarchicalQueryBounds {
+                k,
+                time_budget: time_budget_ms.map(std::time::Duration::from_millis),
+                max_candidates,
+                ..HierarchicalQueryBounds::default()
+            };
+            let query_vec = base_query.permute(best_shift);
+            let codebook = &engram_data.codebook;
+            let hier_hits = if explain {
+                let explained =
+                    explain_hierarchical_query_with_store(hierarchical, &store, codebook, &query_vec, &bounds);
+                let hits = explained.results.iter().map(|r| r.hit.clone()).collect();
+                truncated = explained.truncated;
+                explanation = Some(explained);
+                hits
+            } else {
+                let found =
+                    query_hierarchical_budgeted_with_store(hierarchical, &store, codebook, &query_vec, &bounds);
+                truncated = found.truncated;
+                found.hits
+            };
+            for h in hier_hits {
+                if allowed.as_ref().is_some_and(|a| !a.contains(&h.chunk_id)) {
+                    continue;
+                }
+                let key = (h.sub_engram_id, h.chunk_id);
+                let entry = merged_hier.entry(key).or_insert((h.cosine, h.approx_score));
+                if h.cosine > entry.0 {
+                    *entry = (h.cosine, h.approx_score);
+                }
+            }
+        }
+
+        let files = manifest_data
+            .as_ref()
+            .map(|m| rank_files(&engram_data, m, namespace.as_deref(), &base_query, &merged, k));
+
+        let mut top_matches: Vec<(usize, f64, i32)> = merged
+            .into_iter()
+            .map(|(id, (cosine, approx))| (id, cosine, approx))
+            .collect();
+        top_matches.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        top_matches.truncate(k);
+        if let Some(path) = &access_stats {
+            let stats = load_access_stats(path)?;
+            let ids: Vec<usize> = top_matches.iter().map(|&(id, _, _)| id).collect();
+            stats.record_chunks(&ids, AccessKind::Query, started.elapsed());
+            save_access_stats(path, &stats)?;
+        }
+
+        let mut top_hier: Vec<(String, usize, f64, i32)> = merged_hier
+            .into_iter()
+            .map(|((sub_id, chunk_id), (cosine, approx))| (sub_id, chunk_id, cosine, approx))
+            .collect();
+        top_hier.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+        top_hier.truncate(k);
+
+        let best_score = files
+            .as_ref()
+            .and_then(|f| f.first())
+            .map_or(best_similarity, |f| f.hit.cosine);
+        let status = if best_score > 0.75 {
+            "STRONG MATCH"
+        } else if best_score > 0.3 {
+            "Partial match"
+        } else {
+            "No significant match"
+        };
+
+        if json_output {
+            return print_json(&serde_json::json!({
+                "query": query,
+                "similarity": best_similarity,
+                "best_shift": best_shift,
+                "files": files,
+                "chunks": chunk_hits_json(&top_matches),
+                "hierarchical": hierarchical_hits_json(&top_hier),
+                "truncated": truncated,
+                "explanation": explanation,
+                "status": status,
+            }));
+        }
+
+        println!("Query file: {}", query.display());
+        if verbose {
+            println!(
+                "Best bucket-shift: {} (buckets 0..{})",
+                best_shift,
+                config.max_path_depth.saturating_sub(1)
+            );
+        }
+        println!("Similarity to engram: {:.4}", best_similarity);
+        if let Some(files) = &files {
+            print_ranked_files(files);
+        }
+
+        if !top_matches.is_empty() {
+            println!("Top codebook matches:");
+            for (id, cosine, approx) in top_matches {
+                println!("  chunk {}  cosine {:.4}  approx_dot {}", id, cosine, approx);
+            }
+        } else if verbose {
+            println!("Top codebook matches: (none)");
+        }
+
+        if !top_hier.is_empty() {
+            println!("Top hierarchical matches:");
+            for (sub_id, chunk_id, cosine, approx) in top_hier {
+                println!("  sub {}  chunk {}  cosine {:.4}  approx_dot {}", sub_id, chunk_id, cosine, approx);
+            }
+        } else if verbose && hierarchical_manifest.is_some() {
+            println!("Top hierarchical matches: (none)");
+        }
+        if let Some(reason) = truncated {
+            println!("Hierarchical search stopped early ({}); matches are best-effort", reason);
+        }
+        if let Some(explanation) = &explanation {
+            print_explanation(explanation);
+        }
+
+        println!("Status: {}", status);
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct QueryTextArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Text to encode and search for
+    #[arg(long, value_name = "TEXT", help_heading = "Required")]
+    pub text: String,
+
+    /// Optional hierarchical manifest (enables selective unfolding search)
+    #[arg(long, value_name = "FILE")]
+    pub hierarchical_manifest: Option<PathBuf>,
+
+    /// Directory containing bincode-serialized sub-engrams (used with --hierarchical-manifest)
+    #[arg(long, value_name = "DIR")]
+    pub sub_engrams_dir: Option<PathBuf>,
+
+    /// Restrict codebook matches to this namespace (reads --manifest)
+    #[arg(long, value_name = "NAME")]
+    pub namespace: Option<String>,
+
+    /// Top-k results to print for file, codebook and hierarchical search
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+
+    /// Stop the hierarchical search after MS milliseconds and report the
+    /// best matches so far, flagged as truncated
+    #[arg(long, value_name = "MS", requires = "hierarchical_manifest")]
+    pub time_budget_ms: Option<u64>,
+
+    /// Stop the hierarchical search once N index candidates were scanned,
+    /// flagged as truncated
+    #[arg(long, value_name = "N", requires = "hierarchical_manifest")]
+    pub max_candidates: Option<usize>,
+
+    /// Also rank by semantic similarity with this ONNX model, against the
+    /// signatures in `<engram>.semantic` (requires --features onnx)
+    #[arg(long, value_name = "FILE")]
+    pub semantic_model: Option<PathBuf>,
+
+    /// tokenizer.json for --semantic-model; omit for byte-level models
+    #[arg(long, value_name = "FILE", requires = "semantic_model")]
+    pub semantic_tokenizer: Option<PathBuf>,
+
+    /// Weight of the semantic cosine in hybrid scores (0 = exact only, 1 = semantic only)
+    #[arg(long, default_value_t = semantic::DEFAULT_SEMANTIC_WEIGHT, value_name = "W")]
+    pub semantic_weight: f64,
+
+    /// Enable verbose output showing similarity scores and details
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl QueryTextArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let QueryTextArgs {
+            paths: EngramArgs { engram, manifest },
+            text,
+            hierarchical_manifest,
+            sub_engrams_dir,
+            namespace,
+            k,
+            time_budget_ms,
+            max_candidates,
+            semantic_model,
+            semantic_tokenizer,
+            semantic_weight,
+            verbose,
+        } = self;
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - Holographic Query (Text)",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("========================================");
+        }
+
+        let engram_data = EmbrFS::load_engram(&engram)?;
+
+        // The manifest resolves --namespace, names the files to rank and says
+        // how chunks were encoded; without one only chunk matches can be shown.
+        let manifest_data = if namespace.is_some() || manifest.exists() {
+            Some(EmbrFS::load_manifest(&manifest)?)
+        } else {
+            None
+        };
+
+        let config = manifest_data.as_ref().map_or_else(ReversibleVSAConfig::default, |m| m.encoding().vsa);
+        let base_query = SparseVec::encode_chunk(text.as_bytes(), &config, None);
+
+        let codebook_index = engram_data.build_codebook_index();
+        let allowed = match (namespace.as_deref(), manifest_data.as_ref()) {
+            (Some(ns), Some(m)) => Some(m.chunk_ids_in_namespace(ns)),
+            _ => None,
+        };
+
+        let mut best_similarity = f64::MIN;
+        let mut best_shift = 0usize;
+        let mut best_top_cosine = f64::MIN;
+
+        let mut merged: HashMap<usize, (f64, i32)> = HashMap::new();
+        let mut merged_hier: HashMap<(String, usize), (f64, i32)> = HashMap::new();
+
+        let hierarchical_loaded = if let (Some(hier_path), Some(_)) = (hierarchical_manifest.as_ref(), sub_engrams_dir.as_ref()) {
+            Some(load_hierarchical_manifest(hier_path)?)
+        } else {
+            None
+        };
+
+        let k_sweep = (k.saturating_mul(10)).max(100);
+        let candidate_k = (k_sweep.saturating_mul(10)).max(200);
+
+        for depth in 0..config.max_path_depth.max(1) {
+            let shift = depth * config.base_shift;
+            let query_vec = base_query.permute(shift);
+
+            let similarity = query_vec.cosine(&engram_data.root);
+            if similarity > best_similarity {
+                best_similarity = similarity;
+                best_shift = shift;
+            }
+
+            let matches = match allowed.as_ref() {
+                Some(allowed) => engram_data.query_codebook_with_index_filtered(
+                    &codebook_index,
+                    &query_vec,
+                    candidate_k,
+                    k_sweep,
+                    allowed,
+                ),
+                None => engram_data.query_codebook_with_index(
+                    &codebook_index,
+                    &query_vec,
+                    candidate_k,
+                    k_sweep,
+                ),
+            };
+
+            if let Some(top) = matches.first() {
+                if top.cosine > best_top_cosine {
+                    best_top_cosine = top.cosine;
+                    best_shift = shift;
+                    best_similarity = similarity;
+                }
+            }
+
+            for m in matches {
+                let entry = merged.entry(m.id).or_insert((m.cosine, m.approx_score));
+                if m.cosine > entry.0 {
+                    *entry = (m.cosine, m.approx_score);
+                }
+            }
+        }
+
+        let mut truncated = None;
+        if let (Some(hierarchical), Some(sub_dir)) = (hierarchical_loaded.as_ref(), sub_engrams_dir.as_ref()) {
+            let store = DirectorySubEngramStore::new(sub_dir).with_digests(hierarchical);
+            let bounds = HierarchicalQueryBounds {
+                k,
+                time_budget: time_budget_ms.map(std::time::Duration::from_millis),
+                max_candidates,
+                ..HierarchicalQueryBounds::default()
+            };
+            let query_vec = base_query.permute(best_shift);
+            let found = query_hierarchical_budgeted_with_store(
+                hierarchical,
+                &store,
+                &engram_data.codebook,
+                &query_vec,
+                &bounds,
+            );
+            truncated = found.truncated;
+            for h in found.hits {
+                if allowed.as_ref().is_some_and(|a| !a.contains(&h.chunk_id)) {
+                    continue;
+                }
+                let key = (h.sub_engram_id, h.chunk_id);
+                let entry = merged_hier.entry(key).or_insert((h.cosine, h.approx_score));
+                if h.cosine > entry.0 {
+                    *entry = (h.cosine, h.approx_score);
+                }
+            }
+        }
+
+        let files = manifest_data
+            .as_ref()
+            .map(|m| rank_files(&engram_data, m, namespace.as_deref(), &base_query, &merged, k));
+
+        let mut top_matches: Vec<(usize, f64, i32)> = merged
+            .into_iter()
+            .map(|(id, (cosine, approx))| (id, cosine, approx))
+            .collect();
+        top_matches.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        top_matches.truncate(k);
+
+        let hybrid_hits = match semantic_model.as_deref() {
+            Some(model) => {
+                let encoder = load_semantic_encoder(model, semantic_tokenizer.as_deref())?;
+                let signatures = SemanticSignatures::load(semantic::default_signatures_path(&engram))?;
+                if !encoder.matches(&signatures) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "semantic signatures were built with {}, not {}",
+                            signatures.model,
+                            encoder.model_id()
+                        ),
+                    ));
+                }
+                let exact_query = base_query.permute(best_shift);
+                let semantic_query = encoder.encode(text.as_bytes())?;
+                let mut hits = semantic::hybrid_query(
+                    &engram_data,
+                    &signatures,
+                    Some(&exact_query),
+                    Some(&semantic_query),
+                    k.saturating_mul(4),
+                    semantic_weight,
+                );
+                if let Some(allowed) = allowed.as_ref() {
+                    hits.retain(|h| allowed.contains(&h.id));
+                }
+                hits.truncate(k);
+                Some(hits)
+            }
+            None => None,
+        };
+
+        let mut top_hier: Vec<(String, usize, f64, i32)> = merged_hier
+            .into_iter()
+            .map(|((sub_id, chunk_id), (cosine, approx))| (sub_id, chunk_id, cosine, approx))
+            .collect();
+        top_hier.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+        top_hier.truncate(k);
+
+        if json_output {
+            let hybrid = hybrid_hits.as_ref().map(|hits| {
+                hits.iter()
+                    .map(|h| {
+                        serde_json::json!({ "id": h.id, "score": h.score, "exact": h.exact, "semantic": h.semantic })
+                    })
+                    .collect::<Vec<_>>()
+            });
+            return print_json(&serde_json::json!({
+                "query": text,
+                "similarity": best_similarity,
+                "best_shift": best_shift,
+                "files": files,
+                "chunks": chunk_hits_json(&top_matches),
+                "hybrid": hybrid,
+                "hierarchical": hierarchical_hits_json(&top_hier),
+                "truncated": truncated,
+            }));
+        }
+
+        println!("Query text: {}", text);
+        if verbose {
+            println!(
+                "Best bucket-shift: {} (buckets 0..{})",
+                best_shift,
+                config.max_path_depth.saturating_sub(1)
+            );
+        }
+        println!("Similarity to engram: {:.4}", best_similarity);
+        if let Some(files) = &files {
+            print_ranked_files(files);
+        }
+
+        if !top_matches.is_empty() {
+            println!("Top codebook matches:");
+            for (id, cosine, approx) in top_matches {
+                println!("  chunk {}  cosine {:.4}  approx_dot {}", id, cosine, approx);
+            }
+        } else if verbose {
+            println!("Top codebook matches: (none)");
+        }
+
+        if let Some(hits) = hybrid_hits {
+            println!("Top hybrid matches (semantic weight {:.2}):", semantic_weight.clamp(0.0, 1.0));
+            for h in hits {
+                println!(
+                    "  chunk {}  score {:.4}  exact {:.4}  semantic {:.4}",
+                    h.id, h.score, h.exact, h.semantic
+                );
+            }
+        }
+
+        if !top_hier.is_empty() {
+            println!("Top hierarchical matches:");
+            for (sub_id, chunk_id, cosine, approx) in top_hier {
+                println!("  sub {}  chunk {}  cosine {:.4}  approx_dot {}", sub_id, chunk_id, cosine, approx);
+            }
+        } else if verbose && hierarchical_manifest.is_some() {
+            println!("Top hierarchical matches: (none)");
+        }
+        if let Some(reason) = truncated {
+            println!("Hierarchical search stopped early ({}); matches are best-effort", reason);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct FindSimilarArgs {
+    /// Local file to compare against the engram
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Only consider files in this namespace
+    #[arg(long, value_name = "NAME")]
+    pub namespace: Option<String>,
+
+    /// Number of files to return
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+
+    /// Matching regions to list per file
+    #[arg(long, default_value_t = 5, value_name = "N")]
+    pub regions: usize,
+
+    /// Leave out regions scoring below this cosine
+    #[arg(long, default_value_t = 0.3, value_name = "COSINE")]
+    pub min_cosine: f64,
+}
+
+impl FindSimilarArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let FindSimilarArgs {
+            file,
+            paths: EngramArgs { engram, manifest },
+            namespace,
+            k,
+            regions,
+            min_cosine,
+        } = self;
+        let engram_data = EmbrFS::load_engram(&engram)?;
+        let manifest_data = EmbrFS::load_manifest(&manifest)?;
+        let data = std::fs::read(&file)?;
+        let opts = SimilarOptions {
+            k,
+            regions_per_file: regions,
+            min_region_cosine: min_cosine,
+            namespace,
+        };
+        let files = find_similar(&engram_data, &manifest_data, &data, &opts);
+
+        if json_output {
+            return print_json(&serde_json::json!({ "file": file, "files": files }));
+        }
+        println!("Similar to {}:", file.display());
+        if files.is_empty() {
+            println!("  (none)");
+        }
+        for (rank, f) in files.iter().enumerate() {
+            println!(
+                "  {:>3}. {}  score {:.4}  coverage {:.0}%",
+                rank + 1,
+                f.path,
+                f.score,
+                f.coverage * 100.0
+            );
+            for r in &f.regions {
+                println!(
+                    "         local {}..{}  ~  {}..{}  cosine {:.4}",
+                    r.query_offset,
+                    r.query_offset + r.query_len as u64,
+                    r.offset,
+                    r.offset + r.len as u64,
+                    r.cosine
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ComputeArgs {
+    /// Expression to evaluate
+    #[arg(value_name = "EXPR")]
+    pub expression: String,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Seed of the vectors symbol(NAME) returns; defaults to the seed of
+    /// the engram's role schema, or 0
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Number of named vectors to rank the result against
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+}
+
+impl ComputeArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ComputeArgs {
+            expression,
+            paths: EngramArgs { engram, manifest },
+            seed,
+            k,
+        } = self;
+        let engram_data = EmbrFS::load_engram(&engram)?;
+        let manifest_data = EmbrFS::load_manifest(&manifest)?;
+        let seed = seed.unwrap_or_else(|| engram_data.schema.as_ref().map_or(0, |schema| schema.seed));
+        let bindings = compute::bindings(&engram_data, &manifest_data, seed);
+        let result = compute::eval(&expression, &bindings)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let mut matches = bindings.matches(&result, f64::NEG_INFINITY);
+        matches.truncate(k);
+
+        if json_output {
+            let matches: Vec<_> = matches
+                .iter()
+                .map(|(name, cosine)| serde_json::json!({ "name": name, "cosine": cosine }))
+                .collect();
+            return print_json(&serde_json::json!({
+                "expression": expression,
+                "nonzero": result.pos.len() + result.neg.len(),
+                "matches": matches,
+            }));
+        }
+        println!("{}  ({} nonzero trits)", expression, result.pos.len() + result.neg.len());
+        for (name, cosine) in &matches {
+            println!("  {:.4}  {}", cosine, name);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct QueryRoleArgs {
+    /// Engram file holding the role schema
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Role to unbind
+    #[arg(long, value_name = "ROLE")]
+    pub role: String,
+
+    /// Value to look for
+    #[arg(long, value_name = "TEXT", required_unless_present = "like", conflicts_with = "like")]
+    pub value: Option<String>,
+
+    /// Look for the value this path is bound to under the role
+    #[arg(long, value_name = "PATH")]
+    pub like: Option<String>,
+
+    /// Number of paths to return
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+}
+
+impl QueryRoleArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let QueryRoleArgs {
+            engram,
+            role,
+            value,
+            like,
+            k,
+        } = self;
+        let engram_data = EmbrFS::load_engram(&engram)?;
+        let schema = engram_data
+            .schema
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the engram has no role schema"))?;
+        let Some(role_vec) = schema.role(&role) else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no role {:?} in the schema", role)));
+        };
+        let probe = match (&value, &like) {
+            (Some(text), _) => schema.symbol(text),
+            (None, Some(path)) => schema
+                .record(path)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("nothing is bound to {:?}", path)))?
+                .bind(&role_vec),
+            (None, None) => unreachable!("clap requires --value or --like"),
+        };
+        let hits = schema.query(&role, &probe, k);
+
+        if json_output {
+            return print_json(&serde_json::json!({
+                "role": role,
+                "value": value,
+                "like": like,
+                "hits": hits,
+            }));
+        }
+        if hits.is_empty() {
+            println!("No paths bound to a matching {}", role);
+        }
+        for hit in &hits {
+            println!("  {:.4}  {}", hit.cosine, hit.path);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct QueryFederatedArgs {
+    /// Engram to search, as ENGRAM or ENGRAM,MANIFEST (repeatable)
+    #[arg(short, long = "source", value_name = "ENGRAM[,MANIFEST]", required = true)]
+    pub sources: Vec<String>,
+
+    /// Query file to search for
+    #[arg(short, long, value_name = "FILE", help_heading = "Required")]
+    pub query: PathBuf,
+
+    /// Number of results across all sources
+    #[arg(long, default_value_t = 10, value_name = "K")]
+    pub k: usize,
+
+    /// How scores are made comparable across sources
+    #[arg(long, value_enum, default_value = "zscore", value_name = "HOW")]
+    pub normalize: NormalizationArg,
+
+    /// Engram encoded differently, searched through its alignment into the
+    /// other sources' space (repeatable)
+    #[arg(long, value_name = "ENGRAM,MANIFEST,ALIGNMENT")]
+    pub aligned: Vec<String>,
+}
+
+impl QueryFederatedArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let QueryFederatedArgs {
+            sources,
+            query,
+            k,
+            normalize,
+            aligned,
+        } = self;
+        let data = std::fs::read(&query)?;
+        let config = ReversibleVSAConfig::default();
+        let mut federation = FederatedIndex::new().with_normalization(normalize.into());
+        for source in &sources {
+            let (engram, manifest) = match source.split_once(',') {
+                Some((engram, manifest)) => (engram, Some(manifest)),
+                None => (source.as_str(), None),
+            };
+            let manifest = match manifest {
+                Some(path) => EmbrFS::load_manifest(path)?,
+                None => Manifest::default(),
+            };
+            let reader = crate::reader::EngramReader::new(EmbrFS::load_engram(engram)?, manifest, config.clone());
+            federation.add_engram(engram, std::sync::Arc::new(reader))?;
+        }
+        for source in &aligned {
+            let mut parts = source.splitn(3, ',');
+            let (Some(engram), Some(manifest), Some(alignment)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("expected ENGRAM,MANIFEST,ALIGNMENT, got {:?}", source),
+                ));
+            };
+            let reader = crate::reader::EngramReader::new(
+                EmbrFS::load_engram(engram)?,
+                EmbrFS::load_manifest(manifest)?,
+                config.clone(),
+            );
+            federation.add_aligned_engram(engram, std::sync::Arc::new(reader), &Alignment::load(alignment)?)?;
+        }
+        let hits = federation.query(&data, k);
+        if json_output {
+            return print_json(&hits);
+        }
+        for hit in &hits {
+            println!(
+                "{:>8.4}  cosine {:.4}  {} chunk {}  {}",
+                hit.score,
+                hit.cosine,
+                hit.source,
+                hit.chunk,
+                hit.paths.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct AlignArgs {
+    /// Engram whose space the source is mapped into, as ENGRAM,MANIFEST
+    #[arg(short, long, value_name = "ENGRAM,MANIFEST")]
+    pub target: String,
+
+    /// Engram to map, as ENGRAM,MANIFEST
+    #[arg(short, long, value_name = "ENGRAM,MANIFEST")]
+    pub source: String,
+
+    /// Where to write the alignment
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Target dimensions kept per source dimension; 1 learns a permutation
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub fanout: usize,
+
+    /// Anchors a dimension must be set in to be mapped
+    #[arg(long, default_value_t = 2, value_name = "N")]
+    pub min_support: usize,
+
+    /// Anchor chunks to learn from at most
+    #[arg(long, default_value_t = 512, value_name = "N")]
+    pub max_anchors: usize,
+}
+
+impl AlignArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let AlignArgs {
+            target,
+            source,
+            output,
+            fanout,
+            min_support,
+            max_anchors,
+        } = self;
+        let load = |pair: &str| -> io::Result<(Engram, Manifest)> {
+            let (engram, manifest) = pair.split_once(',').ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("expected ENGRAM,MANIFEST, got {:?}", pair))
+            })?;
+            Ok((EmbrFS::load_engram(engram)?, EmbrFS::load_manifest(manifest)?))
+        };
+        let (target_engram, target_manifest) = load(&target)?;
+        let (source_engram, source_manifest) = load(&source)?;
+        let options = AlignOptions {
+            fanout,
+            min_support,
+            max_anchors,
+        };
+        let alignment =
+            Alignment::between((&source_engram, &source_manifest), (&target_engram, &target_manifest), options)?;
+        let output = output.unwrap_or_else(|| {
+            align::default_alignment_path(source.split_once(',').map_or(source.as_str(), |(engram, _)| engram))
+        });
+        alignment.save(&output)?;
+
+        if json_output {
+            return print_json(&serde_json::json!({
+                "alignment": output,
+                "anchors": alignment.anchors,
+                "dimensions": alignment.dimensions(),
+                "coverage": alignment.coverage,
+                "ties": alignment.ties(),
+                "fit": alignment.fit,
+            }));
+        }
+        println!("Alignment: {}", output.display());
+        println!("  Anchors: {}", alignment.anchors);
+        println!(
+            "  Dimensions mapped: {} ({:.1}% of the signed dimensions the anchors set, {} tied)",
+            alignment.dimensions(),
+            alignment.coverage * 100.0,
+            alignment.ties()
+        );
+        println!("  Fit: {:.4} (1 when every anchor projects onto its target)", alignment.fit);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct EvalArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Labeled queries, one JSON object per line
+    #[arg(short, long, value_name = "FILE", help_heading = "Required")]
+    pub queries: PathBuf,
+
+    /// Comma-separated cutoffs for recall@k and precision@k
+    #[arg(short, long, value_delimiter = ',', default_values_t = eval::DEFAULT_CUTOFFS, value_name = "K")]
+    pub k: Vec<usize>,
+
+    /// Stage to evaluate (repeatable; default all)
+    #[arg(long = "stage", value_enum, value_name = "STAGE")]
+    pub stages: Vec<StageArg>,
+
+    /// Index candidates to rerank per query shift (default as `query`)
+    #[arg(long, value_name = "N")]
+    pub candidates: Option<usize>,
+
+    /// Write the JSON report here
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Earlier JSON report to compare against
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// Allowed drop of any metric against --baseline
+    #[arg(long, default_value_t = 0.01)]
+    pub tolerance: f64,
+
+    /// Also list each query's first relevant rank per stage
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl EvalArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let EvalArgs {
+            paths: EngramArgs { engram, manifest },
+            queries,
+            k,
+            stages,
+            candidates,
+            output,
+            baseline,
+            tolerance,
+            verbose,
+        } = self;
+        let set = QuerySet::load(&queries)?;
+        let mut options = EvalOptions {
+            cutoffs: k,
+            candidate_k: candidates,
+            ..EvalOptions::default()
+        };
+        if !stages.is_empty() {
+            options.stages = stages.into_iter().map(Into::into).collect();
+        }
+        let report = eval::evaluate(&EmbrFS::load_engram(&engram)?, &EmbrFS::load_manifest(&manifest)?, &set, &options)?;
+        if let Some(path) = &output {
+            std::fs::write(path, serde_json::to_string_pretty(&report)? + "\n")?;
+        }
+        let changes = match &baseline {
+            Some(path) => {
+                let before: EvalReport = serde_json::from_slice(&std::fs::read(path)?)?;
+                report.compare(&before)
+            }
+            None => Vec::new(),
+        };
+
+        if json_output {
+            print_json(&serde_json::json!({ "report": report, "changes": changes }))?;
+        } else {
+            print_eval_report(&report, verbose);
+            if !changes.is_empty() {
+                println!();
+                println!("Against {}:", baseline.as_ref().map_or_else(String::new, |p| p.display().to_string()));
+                for c in &changes {
+                    println!("  {:<28} {:>7.4} -> {:>7.4}  {:+.4}", c.name, c.baseline, c.current, c.delta);
+                }
+            }
+        }
+
+        let dropped = changes.iter().filter(|c| c.delta < -tolerance).count();
+        if dropped > 0 {
+            return Err(io::Error::other(format!(
+                "{} metric(s) dropped by more than {} against {}",
+                dropped,
+                tolerance,
+                baseline.as_ref().map_or_else(String::new, |p| p.display().to_string())
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct BundleHierArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Output hierarchical manifest JSON
+    #[arg(long, default_value = "hier.json", value_name = "FILE")]
+    pub out_hierarchical_manifest: PathBuf,
+
+    /// Output directory to write bincode sub-engrams
+    #[arg(long, default_value = "sub_engrams", value_name = "DIR")]
+    pub out_sub_engrams_dir: PathBuf,
+
+    /// Optional compression for the output `.subengram` blobs (default: none)
+    #[arg(long, default_value = "none", value_enum)]
+    pub sub_engram_compression: CompressionArg,
+
+    /// Optional compression level (codec-dependent; used for zstd)
+    #[arg(long, value_name = "LEVEL")]
+    pub sub_engram_compression_level: Option<i32>,
+
+    /// Record per-frame and whole-file checksums in each `.subengram` envelope
+    #[arg(long, default_value = "none", value_enum)]
+    pub sub_engram_checksum: ChecksumArg,
+
+    /// Maximum sparsity per level bundle
+    #[arg(long, default_value_t = 500, value_name = "N")]
+    pub max_level_sparsity: usize,
+
+    /// Optional cap on chunk IDs per node (enables deterministic sharding when exceeded)
+    #[arg(long, value_name = "N")]
+    pub max_chunks_per_node: Option<usize>,
+
+    /// Embed sub-engrams in the manifest JSON (in addition to writing the directory)
+    #[arg(long, default_value_t = false)]
+    pub embed_sub_engrams: bool,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl BundleHierArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let BundleHierArgs {
+            paths: EngramArgs { engram, manifest },
+            out_hierarchical_manifest,
+            out_sub_engrams_dir,
+            max_level_sparsity,
+            max_chunks_per_node,
+            embed_sub_engrams,
+            sub_engram_compression,
+            sub_engram_compression_level,
+            sub_engram_checksum,
+            verbose,
+        } = self;
+        let verbose = verbose && !json_output;
+        if verbose {
+            println!(
+                "Embeddenator v{} - Build Hierarchical Artifacts",
+                env!("CARGO_PKG_VERSION")
+            );
+            println!("=============================================");
+        }
+
+        let engram_data = EmbrFS::load_engram(&engram)?;
+        let manifest_data = EmbrFS::load_manifest(&manifest)?;
+
+        let mut fs = EmbrFS::new();
+        fs.engram = engram_data;
+        fs.manifest = manifest_data;
+
+        let config = fs.vsa_config();
+        let mut hierarchical = fs.bundle_hierarchically_with_options(
+            max_level_sparsity,
+            max_chunks_per_node,
+            verbose,
+            &config,
+        )?;
+
+        // Always write the sub-engrams directory for store-backed retrieval.
+        save_sub_engrams_dir_with_options(
+            &hierarchical.sub_engrams,
+            &out_sub_engrams_dir,
+            BinaryWriteOptions {
+                codec: sub_engram_compression.into(),
+                level: sub_engram_compression_level,
+                checksum: sub_engram_checksum.into(),
+                ..Default::default()
+            },
+        )?;
+
+        if !embed_sub_engrams {
+            hierarchical.sub_engrams.clear();
+        }
+
+        save_hierarchical_manifest(&hierarchical, &out_hierarchical_manifest)?;
+
+        if json_output {
+            print_json(&serde_json::json!({
+                "hierarchical_manifest": out_hierarchical_manifest,
+                "sub_engrams_dir": out_sub_engrams_dir,
+                "levels": hierarchical.levels.len(),
+            }))?;
+        } else if verbose {
+            println!("Wrote hierarchical manifest: {}", out_hierarchical_manifest.display());
+            println!("Wrote sub-engrams dir: {}", out_sub_engrams_dir.display());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum NormalizationArg {
+    /// Raw cosines
+    None,
+    /// Rescale each engram's hits to 0..1
+    MinMax,
+    /// Standard score within each engram's hits
+    Zscore,
+}
+
+impl From<NormalizationArg> for ScoreNormalization {
+    fn from(v: NormalizationArg) -> Self {
+        match v {
+            NormalizationArg::None => ScoreNormalization::None,
+            NormalizationArg::MinMax => ScoreNormalization::MinMax,
+            NormalizationArg::Zscore => ScoreNormalization::ZScore,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum StageArg {
+    /// Inverted-index candidates by approximate dot score
+    Index,
+    /// Candidates reranked by exact cosine, as `query` returns them
+    Reranked,
+    /// Exact cosine against every chunk
+    Exhaustive,
+}
+
+impl From<StageArg> for eval::Stage {
+    fn from(v: StageArg) -> Self {
+        match v {
+            StageArg::Index => eval::Stage::Index,
+            StageArg::Reranked => eval::Stage::Reranked,
+            StageArg::Exhaustive => eval::Stage::Exhaustive,
+        }
+    }
+}
+
+fn print_ranked_files(files: &[RankedFile]) {
+    if files.is_empty() {
+        println!("Top files: (none)");
+        return;
+    }
+    println!("Top files:");
+    println!("  {:>4}  {:>7}  {:>10}  {:>6}  path", "rank", "score", "best chunk", "chunks");
+    for (rank, f) in files.iter().enumerate() {
+        let chunk = f.best_chunk.map_or_else(|| "-".to_string(), |c| format!("{c:.4}"));
+        println!(
+            "  {:>4}  {:>7.4}  {:>10}  {:>6}  {}",
+            rank + 1,
+            f.hit.cosine,
+            chunk,
+            f.hit.chunks,
+            f.hit.path
+        );
+    }
+}
+
+/// `eval` in text form: one row of metrics per stage.
+fn print_eval_report(report: &EvalReport, verbose: bool) {
+    println!("{} queries", report.queries);
+    let Some(first) = report.stages.first() else {
+        return;
+    };
+    let mut header = format!("  {:<10}", "stage");
+    for c in &first.cutoffs {
+        header += &format!("  {:>9}  {:>8}", format!("recall@{}", c.k), format!("prec@{}", c.k));
+    }
+    println!("{header}  {:>7}  {:>9}", "mrr", "ms/query");
+    for stage in &report.stages {
+        let mut row = format!("  {:<10}", stage.stage.name());
+        for c in &stage.cutoffs {
+            row += &format!("  {:>9.4}  {:>8.4}", c.recall, c.precision);
+        }
+        println!("{row}  {:>7.4}  {:>9.2}", stage.mrr, stage.mean_millis);
+    }
+    if !verbose {
+        return;
+    }
+    for stage in &report.stages {
+        println!();
+        println!("{}:", stage.stage.name());
+        for q in &stage.queries {
+            let rank = q.first_relevant.map_or_else(|| "-".to_string(), |r| r.to_string());
+            println!("  {:<20} first relevant {:>4}  found {}/{}", q.id, rank, q.found, q.relevant);
+        }
+    }
+}
+
+/// Chunk-level hits as JSON values, for `--output-format json`.
+fn chunk_hits_json(hits: &[(usize, f64, i32)]) -> Vec<serde_json::Value> {
+    hits.iter()
+        .map(|&(id, cosine, approx)| serde_json::json!({ "id": id, "cosine": cosine, "approx_score": approx }))
+        .collect()
+}
+
+fn hierarchical_hits_json(hits: &[(String, usize, f64, i32)]) -> Vec<serde_json::Value> {
+    hits.iter()
+        .map(|(sub_id, chunk_id, cosine, approx)| {
+            serde_json::json!({ "sub_engram": sub_id, "chunk": chunk_id, "cosine": cosine, "approx_score": approx })
+        })
+        .collect()
+}
+
+/// `query --explain` in text form: each hierarchical result with its
+/// path and contributing nodes, then every node the search skipped.
+fn print_explanation(explanation: &HierarchicalExplanation) {
+    println!("Explanation:");
+    for r in &explanation.results {
+        println!("  chunk {}  via {}", r.hit.chunk_id, r.path.join(" > "));
+        for c in &r.contributions {
+            println!("    from {}  cosine {:.4}  approx_dot {}", c.sub_engram_id, c.cosine, c.approx_score);
+        }
+    }
+    let skipped: Vec<_> = explanation
+        .nodes
+        .iter()
+        .filter(|n| !matches!(n.outcome, NodeOutcome::Expanded { .. }))
+        .collect();
+    if !skipped.is_empty() {
+        println!("  Not searched:");
+    }
+    for n in skipped {
+        let why = match &n.outcome {
+            NodeOutcome::PrunedBySignature { cosine } => format!("pruned, signature cosine {:.4}", cosine),
+            NodeOutcome::OutsideBeam => "outside the beam".to_string(),
+            NodeOutcome::NotExpanded => "expansion budget exhausted".to_string(),
+            NodeOutcome::OverBudget => "time or candidate budget exhausted".to_string(),
+            NodeOutcome::BeyondMaxDepth => "below max depth".to_string(),
+            NodeOutcome::Unavailable => "could not be loaded".to_string(),
+            NodeOutcome::Expanded { .. } => unreachable!("filtered above"),
+        };
+        let score = n.score.map_or_else(String::new, |s| format!(" (score {:.4})", s));
+        println!("    {}{}: {}", n.sub_engram_id, score, why);
+    }
+}
@@ -0,0 +1,167 @@
+//! `push`, `pull` and `sync-server`: syncing an engram with a remote copy.
+
+use super::{build_keyring, parse_key_arg, print_json, CompressionArg, EngramArgs, KeyArgs};
+use crate::remote_sync::{self, RemoteSession, SyncOptions};
+use clap::Args;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct PushArgs {
+    /// Remote engram, as [USER@]HOST:PATH or a local path
+    #[arg(value_name = "REMOTE")]
+    pub remote: String,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+
+    /// Remote manifest path [default: manifest.json beside the remote engram]
+    #[arg(long, value_name = "PATH")]
+    pub remote_manifest: Option<String>,
+
+    /// Decryption key for the remote copy as ID=FILE, FILE being a path on
+    /// the remote side. Repeatable.
+    #[arg(long = "remote-key", value_name = "ID=FILE", value_parser = parse_key_arg)]
+    pub remote_keys: Vec<(u32, PathBuf)>,
+
+    /// Compression of chunk batches on the wire (none, zstd or lz4)
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    /// Chunk records per batch
+    #[arg(long, default_value_t = 256, value_name = "N")]
+    pub batch: usize,
+
+    /// Remote shell used to reach HOST
+    #[arg(long, default_value = "ssh", value_name = "COMMAND")]
+    pub rsh: String,
+
+    /// embeddenator executable on the remote host
+    #[arg(long, default_value = "embeddenator", value_name = "PATH")]
+    pub remote_bin: String,
+}
+
+impl PushArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let PushArgs {
+            remote,
+            paths: EngramArgs { engram, manifest },
+            keys: KeyArgs { keys },
+            remote_manifest,
+            remote_keys,
+            compression,
+            batch,
+            rsh,
+            remote_bin,
+        } = self;
+        let opts = SyncOptions { codec: compression.into(), batch_chunks: batch };
+        let keyring = build_keyring(&keys)?;
+        let session = RemoteSession::spawn(&remote, remote_manifest.as_deref(), Some(&rsh), &remote_bin, &remote_keys)?;
+        let report = session.push(&engram, &manifest, &keyring, &opts)?;
+        print_transfer(&remote, &report, "Sent", json_output)
+    }
+}
+
+#[derive(Args)]
+pub struct PullArgs {
+    /// Remote engram, as [USER@]HOST:PATH or a local path
+    #[arg(value_name = "REMOTE")]
+    pub remote: String,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+
+    /// Remote manifest path [default: manifest.json beside the remote engram]
+    #[arg(long, value_name = "PATH")]
+    pub remote_manifest: Option<String>,
+
+    /// Decryption key for the remote copy as ID=FILE, FILE being a path on
+    /// the remote side. Repeatable.
+    #[arg(long = "remote-key", value_name = "ID=FILE", value_parser = parse_key_arg)]
+    pub remote_keys: Vec<(u32, PathBuf)>,
+
+    /// Compression of chunk batches on the wire (none, zstd or lz4)
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    /// Chunk records per batch
+    #[arg(long, default_value_t = 256, value_name = "N")]
+    pub batch: usize,
+
+    /// Remote shell used to reach HOST
+    #[arg(long, default_value = "ssh", value_name = "COMMAND")]
+    pub rsh: String,
+
+    /// embeddenator executable on the remote host
+    #[arg(long, default_value = "embeddenator", value_name = "PATH")]
+    pub remote_bin: String,
+}
+
+impl PullArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let PullArgs {
+            remote,
+            paths: EngramArgs { engram, manifest },
+            keys: KeyArgs { keys },
+            remote_manifest,
+            remote_keys,
+            compression,
+            batch,
+            rsh,
+            remote_bin,
+        } = self;
+        let opts = SyncOptions { codec: compression.into(), batch_chunks: batch };
+        let keyring = build_keyring(&keys)?;
+        let session = RemoteSession::spawn(&remote, remote_manifest.as_deref(), Some(&rsh), &remote_bin, &remote_keys)?;
+        let report = session.pull(&engram, &manifest, &keyring, &opts)?;
+        print_transfer(&remote, &report, "Fetched", json_output)
+    }
+}
+
+#[derive(Args)]
+pub struct SyncServerArgs {
+    #[arg(value_name = "ENGRAM")]
+    pub engram: PathBuf,
+
+    #[arg(value_name = "MANIFEST")]
+    pub manifest: PathBuf,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl SyncServerArgs {
+    pub(super) fn run(self) -> io::Result<()> {
+        let SyncServerArgs {
+            engram,
+            manifest,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let stdin = io::stdin().lock();
+        let stdout = io::BufWriter::new(io::stdout().lock());
+        remote_sync::serve(stdin, stdout, &engram, &manifest, &keyring)
+    }
+}
+
+/// Summary line (or JSON) for `push` and `pull`.
+fn print_transfer(remote: &str, report: &remote_sync::TransferReport, verb: &str, json_output: bool) -> io::Result<()> {
+    if json_output {
+        return print_json(report);
+    }
+    if report.up_to_date {
+        println!("{} is up to date", remote);
+    } else {
+        println!(
+            "{} {} chunks, removed {}; {} bytes sent, {} received",
+            verb, report.chunks_transferred, report.chunks_removed, report.bytes_sent, report.bytes_received
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Read-only servers over an engram: `serve` (HTTP), `nbd-serve`, `sql` and
+//! `grpc-serve`.
+
+#[cfg(feature = "sqlite")]
+use super::print_json;
+#[cfg(any(feature = "http", feature = "nbd", feature = "sqlite"))]
+use super::{build_keyring, EngramArgs, KeyArgs};
+#[cfg(any(feature = "http", feature = "nbd", feature = "sqlite"))]
+use crate::embrfs::EmbrFS;
+use clap::Args;
+use std::io;
+#[cfg(any(feature = "http", feature = "nbd", feature = "grpc"))]
+use std::path::Path;
+#[cfg(any(feature = "http", feature = "sqlite", feature = "grpc"))]
+use std::path::PathBuf;
+
+#[cfg(feature = "http")]
+#[derive(Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080", value_name = "ADDR")]
+    pub listen: std::net::SocketAddr,
+
+    /// TOML access policy limiting each bearer token to the namespaces it is granted
+    #[arg(long, value_name = "FILE")]
+    pub access_policy: Option<PathBuf>,
+
+    /// Status file of `scrub --status-file`, reported by /healthz and /readyz
+    #[arg(long, value_name = "FILE")]
+    pub scrub_status: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+#[cfg(feature = "http")]
+impl ServeArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ServeArgs {
+            paths: EngramArgs { engram, manifest },
+            listen,
+            access_policy,
+            scrub_status,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let mut service = crate::http_api::HttpService::new(
+            EmbrFS::load_engram_with_keys(&engram, &keyring)?,
+            EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+        );
+        if let Some(path) = access_policy {
+            service = service.with_access_policy(crate::access::AccessPolicy::load(path)?);
+        }
+        if let Some(path) = scrub_status {
+            service.health().set_scrub_status_file(path);
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+        announce_listening(json_output, "HTTP", &listen.to_string(), &engram);
+        runtime.block_on(crate::http_api::serve(listen, service))
+    }
+}
+
+#[cfg(feature = "nbd")]
+#[derive(Args)]
+pub struct NbdServeArgs {
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:10809", value_name = "ADDR")]
+    pub listen: std::net::SocketAddr,
+
+    /// File served when a client asks for the empty export name
+    #[arg(long, value_name = "PATH")]
+    pub default_export: Option<String>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+#[cfg(feature = "nbd")]
+impl NbdServeArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let NbdServeArgs {
+            paths: EngramArgs { engram, manifest },
+            listen,
+            default_export,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let mut server = crate::nbd::NbdServer::new(
+            EmbrFS::load_engram_with_keys(&engram, &keyring)?,
+            EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+        );
+        if let Some(path) = default_export {
+            server = server.with_default_export(&path)?;
+        }
+        announce_listening(json_output, "NBD", &listen.to_string(), &engram);
+        crate::nbd::serve(listen, server)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(Args)]
+pub struct SqlArgs {
+    /// SQL statement to run
+    #[arg(value_name = "SQL")]
+    pub sql: String,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// SQLite database to open instead of an in-memory one
+    #[arg(long, value_name = "FILE")]
+    pub db: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqlArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let SqlArgs {
+            sql,
+            paths: EngramArgs { engram, manifest },
+            db,
+            keys: KeyArgs { keys },
+        } = self;
+        use rusqlite::types::ValueRef;
+        let sqlite_error = |e: rusqlite::Error| io::Error::other(format!("sqlite: {e}"));
+        let keyring = build_keyring(&keys)?;
+        let tables = crate::sqlite_vtab::EngramTables::new(
+            EmbrFS::load_engram_with_keys(&engram, &keyring)?,
+            EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+        );
+        let conn = match db {
+            Some(path) => rusqlite::Connection::open(path),
+            None => rusqlite::Connection::open_in_memory(),
+        }
+        .map_err(sqlite_error)?;
+        tables.register(&conn)?;
+
+        let mut stmt = conn.prepare(&sql).map_err(sqlite_error)?;
+        let columns = stmt.column_count();
+        let names: Vec<String> = stmt.column_names().iter().map(|n| n.to_string()).collect();
+        if columns > 0 && !json_output {
+            println!("{}", names.join("\t"));
+        }
+        let blob_literal = |b: &[u8]| format!("X'{}'", b.iter().map(|x| format!("{x:02X}")).collect::<String>());
+        let mut json_rows = Vec::new();
+        let mut rows = stmt.query([]).map_err(sqlite_error)?;
+        while let Some(row) = rows.next().map_err(sqlite_error)? {
+            if json_output {
+                let mut object = serde_json::Map::with_capacity(columns);
+                for (i, name) in names.iter().enumerate() {
+                    let value = match row.get_ref(i).map_err(sqlite_error)? {
+                        ValueRef::Null => serde_json::Value::Null,
+                        ValueRef::Integer(n) => n.into(),
+                        ValueRef::Real(x) => x.into(),
+                        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+                        ValueRef::Blob(b) => blob_literal(b).into(),
+                    };
+                    object.insert(name.clone(), value);
+                }
+                json_rows.push(serde_json::Value::Object(object));
+                continue;
+            }
+            let mut fields = Vec::with_capacity(columns);
+            for i in 0..columns {
+                fields.push(match row.get_ref(i).map_err(sqlite_error)? {
+                    ValueRef::Null => String::new(),
+                    ValueRef::Integer(n) => n.to_string(),
+                    ValueRef::Real(x) => x.to_string(),
+                    ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+                    ValueRef::Blob(b) => blob_literal(b),
+                });
+            }
+            println!("{}", fields.join("\t"));
+        }
+        if json_output {
+            print_json(&json_rows)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Args)]
+pub struct GrpcServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:50051", value_name = "ADDR")]
+    pub listen: std::net::SocketAddr,
+
+    /// Directory that load/save paths are resolved against
+    #[arg(long, default_value = ".", value_name = "DIR")]
+    pub data_dir: PathBuf,
+
+    /// TOML access policy limiting each bearer token to the namespaces it is granted
+    #[arg(long, value_name = "FILE")]
+    pub access_policy: Option<PathBuf>,
+
+    /// Address to serve /healthz and /readyz on
+    #[arg(long, value_name = "ADDR")]
+    pub health_listen: Option<std::net::SocketAddr>,
+
+    /// Status file of `scrub --status-file`, reported by the health probes
+    #[arg(long, value_name = "FILE")]
+    pub scrub_status: Option<PathBuf>,
+}
+
+#[cfg(feature = "grpc")]
+impl GrpcServeArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let GrpcServeArgs {
+            listen,
+            data_dir,
+            access_policy,
+            health_listen,
+            scrub_status,
+        } = self;
+        let mut service = crate::grpc::EngramService::new(&data_dir);
+        if let Some(path) = access_policy {
+            service = service.with_access_policy(crate::access::AccessPolicy::load(path)?);
+        }
+        if let Some(path) = scrub_status {
+            service.health().set_scrub_status_file(path);
+        }
+        if let Some(addr) = health_listen {
+            let listener = std::net::TcpListener::bind(addr)?;
+            announce_listening(json_output, "health", &listener.local_addr()?.to_string(), &data_dir);
+            crate::health::spawn(listener, service.health().clone())?;
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+        announce_listening(json_output, "gRPC", &listen.to_string(), &data_dir);
+        runtime.block_on(crate::grpc::serve(listen, service))
+    }
+}
+
+/// Tell the user a server is up: a line of text, or a one-line JSON event.
+#[cfg(any(feature = "http", feature = "nbd", feature = "grpc"))]
+fn announce_listening(json: bool, protocol: &str, listen: &str, source: &Path) {
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "event": "listening", "protocol": protocol.to_lowercase(), "listen": listen, "source": source })
+        );
+    } else {
+        println!("Serving {} over {} on {}", source.display(), protocol, listen);
+    }
+}
@@ -0,0 +1,569 @@
+//! Interactive shell over an open engram (`embeddenator shell`).
+//!
+//! A [`Session`] keeps the engram it opened between commands: append logs
+//! stay memory-mapped (with the `mmap` feature), sharded engrams keep their
+//! recently used shards and other engrams stay decoded, so `ls`, `cat`, `query` and `diff` don't pay the load cost each
+//! time. File signatures for `query` are built on first use and kept until
+//! the next `open`.
+//!
+//! With the `shell` feature the prompt has line editing, history and tab
+//! completion over commands and manifest paths; without it lines are read
+//! from stdin as they come, which also suits scripted sessions.
+
+use super::write_listing;
+use crate::append_log;
+use crate::codebook::ChunkCache;
+use crate::embrfs::{EmbrFS, Engram, FileEntry, FileSignatures, Manifest};
+use crate::envelope::Keyring;
+use crate::error::EmbrError;
+use crate::lazy_engram::{self, LazyEngram};
+use crate::lazy_envelope::Envelope;
+use crate::listing::{self, PathFilter};
+use crate::shards::{self, ShardedEngram};
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Commands understood by [`Session::execute`], with usage and summary.
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "open",
+        "open ENGRAM [MANIFEST]",
+        "open an engram (manifest defaults to the log's own, else manifest.json)",
+    ),
+    (
+        "ls",
+        "ls [PATTERN...]",
+        "list files, optionally filtered by glob patterns",
+    ),
+    (
+        "tree",
+        "tree [-L DEPTH] [PATTERN...]",
+        "show the directory tree",
+    ),
+    ("cat", "cat PATH", "write a file's contents"),
+    ("query", "query TEXT...", "rank files by similarity to TEXT"),
+    ("diff", "diff PATH PATH", "compare two files in the engram"),
+    ("help", "help", "show this list"),
+    ("exit", "exit", "leave the shell (also quit, Ctrl-D)"),
+];
+
+/// Commands whose arguments are manifest paths.
+const PATH_COMMANDS: &[&str] = &["ls", "tree", "cat", "diff"];
+
+/// Files shown by `query`.
+const QUERY_TOP_K: usize = 10;
+
+/// Whether the shell should keep reading commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Exit,
+}
+
+enum Store {
+    Loaded(Engram),
+    Mapped(Envelope),
+    Sharded(ShardedEngram),
+    Lazy(LazyEngram),
+}
+
+struct Opened {
+    engram: PathBuf,
+    manifest: Manifest,
+    store: Store,
+    signatures: Option<FileSignatures>,
+    /// Decoded chunks, so repeated `cat`s of a loaded engram don't decode
+    /// them again.
+    chunks: ChunkCache,
+}
+
+/// State of one shell session.
+pub struct Session {
+    keyring: Keyring,
+    config: ReversibleVSAConfig,
+    opened: Option<Opened>,
+}
+
+impl Session {
+    /// A session with nothing open; `keyring` decrypts engrams it opens.
+    pub fn new(keyring: Keyring) -> Self {
+        Session {
+            keyring,
+            config: ReversibleVSAConfig::default(),
+            opened: None,
+        }
+    }
+
+    /// Open `engram`, replacing whatever was open. Append logs carry their
+    /// own manifest; other engrams default to `manifest.json`.
+    pub fn open(&mut self, engram: &Path, manifest: Option<&Path>) -> io::Result<()> {
+        let load_manifest = |m: &Path| EmbrFS::load_manifest_with_keys(m, &self.keyring);
+        let (store, manifest) = if append_log::is_append_log(engram)? {
+            if cfg!(feature = "mmap") {
+                let envelope = Envelope::open_mmap(engram)?;
+                let manifest = match manifest {
+                    Some(m) => load_manifest(m)?,
+                    None => envelope.manifest()?,
+                };
+                (Store::Mapped(envelope), manifest)
+            } else {
+                let (engram, logged) = EmbrFS::load_append_log(engram)?;
+                let manifest = match manifest {
+                    Some(m) => load_manifest(m)?,
+                    None => logged,
+                };
+                (Store::Loaded(engram), manifest)
+            }
+        } else {
+            let manifest = load_manifest(manifest.unwrap_or(Path::new("manifest.json")))?;
+            if shards::is_shard_index(engram)? {
+                (Store::Sharded(ShardedEngram::open(engram, &self.keyring)?), manifest)
+            } else if lazy_engram::is_lazy_loadable(engram)? {
+                (Store::Lazy(LazyEngram::open(engram, &self.keyring)?), manifest)
+            } else {
+                (Store::Loaded(EmbrFS::load_engram_with_keys(engram, &self.keyring)?), manifest)
+            }
+        };
+        self.opened = Some(Opened {
+            engram: engram.to_path_buf(),
+            manifest,
+            store,
+            signatures: None,
+            chunks: ChunkCache::default(),
+        });
+        Ok(())
+    }
+
+    /// Prompt showing the open engram's file name.
+    pub fn prompt(&self) -> String {
+        match &self.opened {
+            Some(o) => format!(
+                "embeddenator:{}> ",
+                o.engram
+                    .file_name()
+                    .map(|n| n.to_string_lossy())
+                    .unwrap_or_default()
+            ),
+            None => "embeddenator> ".to_string(),
+        }
+    }
+
+    /// Paths in the open manifest, for completion.
+    pub fn paths(&self) -> Vec<String> {
+        self.opened
+            .as_ref()
+            .map(|o| o.manifest.files.iter().map(|f| f.path.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Run one command line, writing its output to `out`.
+    pub fn execute<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<Flow> {
+        let words = split_words(line)?;
+        let Some((command, args)) = words.split_first() else {
+            return Ok(Flow::Continue);
+        };
+        match command.as_str() {
+            "exit" | "quit" => return Ok(Flow::Exit),
+            "help" => {
+                for (_, usage, summary) in COMMANDS {
+                    writeln!(out, "  {usage:<30} {summary}")?;
+                }
+            }
+            "open" => match args {
+                [engram] => self.open(Path::new(engram), None)?,
+                [engram, manifest] => self.open(Path::new(engram), Some(Path::new(manifest)))?,
+                _ => return Err(usage("open")),
+            },
+            "ls" => {
+                let entries = listing::list(&self.opened()?.manifest, &PathFilter::new(args)?);
+                write_listing(out, &entries)?;
+            }
+            "tree" => {
+                let (depth, patterns) = match args {
+                    [flag, depth, rest @ ..] if flag == "-L" => {
+                        (Some(depth.parse().map_err(|_| usage("tree"))?), rest)
+                    }
+                    _ => (None, args),
+                };
+                let root = listing::tree(&self.opened()?.manifest, &PathFilter::new(patterns)?);
+                write!(out, "{}", root.render(depth))?;
+            }
+            "cat" => {
+                let [path] = args else {
+                    return Err(usage("cat"));
+                };
+                let opened = self.opened()?;
+                opened.write_file(opened.find(path)?, &self.config, out)?;
+            }
+            "query" => {
+                if args.is_empty() {
+                    return Err(usage("query"));
+                }
+                let query = SparseVec::encode_data(args.join(" ").as_bytes(), &self.config, None);
+                let opened = self.opened.as_mut().ok_or_else(nothing_open)?;
+                if opened.signatures.is_none() {
+                    opened.signatures = Some(opened.build_signatures()?);
+                }
+                let signatures = opened.signatures.as_ref().expect("built above");
+                for (rank, hit) in signatures
+                    .query_any_shift(&query, &self.config, QUERY_TOP_K)
+                    .iter()
+                    .enumerate()
+                {
+                    writeln!(
+                        out,
+                        "{:>3}. {:>8.4} {:>6} {}",
+                        rank + 1,
+                        hit.cosine,
+                        hit.chunks,
+                        hit.path
+                    )?;
+                }
+            }
+            "diff" => {
+                let [a, b] = args else {
+                    return Err(usage("diff"));
+                };
+                let opened = self.opened()?;
+                let mut left = Vec::new();
+                let mut right = Vec::new();
+                opened.write_file(opened.find(a)?, &self.config, &mut left)?;
+                opened.write_file(opened.find(b)?, &self.config, &mut right)?;
+                write_diff(out, opened.manifest.chunk_size(), a, &left, b, &right)?;
+            }
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown command {other:?}; try help"),
+                ))
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn opened(&self) -> io::Result<&Opened> {
+        self.opened.as_ref().ok_or_else(nothing_open)
+    }
+}
+
+impl Opened {
+    fn find(&self, path: &str) -> io::Result<&FileEntry> {
+        let wanted = path.trim_start_matches("./").trim_start_matches('/');
+        self.manifest
+            .files
+            .iter()
+            .find(|f| f.path == wanted)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{path}: no such file in the manifest"),
+                )
+            })
+    }
+
+    fn write_file<W: Write>(
+        &self,
+        entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let chunk_size = self.manifest.chunk_size();
+        match &self.store {
+            Store::Mapped(envelope) => envelope.write_file(entry, config, chunk_size, out),
+            Store::Sharded(sharded) => sharded.write_file(entry, config, chunk_size, out),
+            Store::Lazy(lazy) => lazy.write_file(entry, config, chunk_size, out),
+            Store::Loaded(engram) => {
+                let cache = Some(&self.chunks);
+                match EmbrFS::reconstruct_file_cached(engram, entry, config, chunk_size, cache, |data| {
+                    out.write_all(data)
+                })? {
+                    Some(mismatch) => Err(EmbrError::ManifestMismatch(vec![mismatch]).into()),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn build_signatures(&self) -> io::Result<FileSignatures> {
+        match &self.store {
+            Store::Loaded(engram) => Ok(FileSignatures::build(engram, &self.manifest)),
+            Store::Mapped(envelope) => {
+                FileSignatures::build_with(&self.manifest, |id| envelope.chunk(id))
+            }
+            Store::Sharded(sharded) => {
+                FileSignatures::build_with(&self.manifest, |id| sharded.chunk(id))
+            }
+            Store::Lazy(lazy) => FileSignatures::build_with(&self.manifest, |id| lazy.chunk(id)),
+        }
+    }
+}
+
+fn nothing_open() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "no engram open; use open ENGRAM [MANIFEST]",
+    )
+}
+
+fn usage(command: &str) -> io::Error {
+    let usage = COMMANDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .map_or(command, |c| c.1);
+    io::Error::new(io::ErrorKind::InvalidInput, format!("usage: {usage}"))
+}
+
+/// Split a command line into words. Double or single quotes group words
+/// with spaces; a backslash escapes the next character.
+pub fn split_words(line: &str) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                if let Some(next) = chars.next() {
+                    word.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (q, None) if q == '"' || q == '\'' => {
+                quote = Some(q);
+                word.get_or_insert_with(String::new);
+            }
+            (q, Some(open)) if q == open => quote = None,
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, _) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unterminated quote",
+        ));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Completions for the word ending at `pos` in `line`: command names for the
+/// first word, manifest `paths` after a path-taking command. Paths complete
+/// one directory level at a time, like a file-system shell. Returns the
+/// start of the word and the candidates.
+pub fn complete(line: &str, pos: usize, paths: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let word = &before[start..];
+    let first = before[..start].split_whitespace().next();
+
+    let candidates = match first {
+        None => COMMANDS
+            .iter()
+            .map(|(name, _, _)| *name)
+            .filter(|name| name.starts_with(word))
+            .map(|name| format!("{name} "))
+            .collect(),
+        Some(command) if PATH_COMMANDS.contains(&command) => {
+            let levels: BTreeSet<String> = paths
+                .iter()
+                .filter_map(|path| {
+                    let rest = path.strip_prefix(word)?;
+                    Some(match rest.find('/') {
+                        Some(slash) => format!("{word}{}", &rest[..=slash]),
+                        None => format!("{path} "),
+                    })
+                })
+                .collect();
+            levels.into_iter().collect()
+        }
+        Some(_) => Vec::new(),
+    };
+    (start, candidates)
+}
+
+fn write_diff<W: Write>(
+    out: &mut W,
+    chunk_size: usize,
+    a: &str,
+    left: &[u8],
+    b: &str,
+    right: &[u8],
+) -> io::Result<()> {
+    if left == right {
+        return writeln!(out, "identical ({} bytes)", left.len());
+    }
+    writeln!(out, "{a}: {} bytes", left.len())?;
+    writeln!(out, "{b}: {} bytes", right.len())?;
+    let first = left
+        .iter()
+        .zip(right)
+        .position(|(x, y)| x != y)
+        .unwrap_or(left.len().min(right.len()));
+    writeln!(out, "first difference at byte {first}")?;
+
+    let chunks = left.len().max(right.len()).div_ceil(chunk_size);
+    let differing = (0..chunks)
+        .filter(|i| {
+            let range = |data: &[u8]| {
+                let start = (i * chunk_size).min(data.len());
+                data[start..(start + chunk_size).min(data.len())].to_vec()
+            };
+            range(left) != range(right)
+        })
+        .count();
+    writeln!(out, "{differing} of {chunks} chunks differ")?;
+
+    // For text, show the first differing line of each side.
+    if let (Ok(l), Ok(r)) = (std::str::from_utf8(left), std::str::from_utf8(right)) {
+        let line = l[..first].matches('\n').count();
+        writeln!(out, "line {}:", line + 1)?;
+        writeln!(out, "- {}", l.lines().nth(line).unwrap_or(""))?;
+        writeln!(out, "+ {}", r.lines().nth(line).unwrap_or(""))?;
+    }
+    Ok(())
+}
+
+/// Read commands until `exit` or end of input. Errors from a command are
+/// reported and the session continues.
+pub fn run(mut session: Session) -> io::Result<()> {
+    let mut lines = LineReader::new()?;
+    while let Some(line) = lines.read(&session)? {
+        let mut out = io::stdout().lock();
+        let flow = session
+            .execute(&line, &mut out)
+            .and_then(|flow| out.flush().map(|()| flow));
+        drop(out);
+        match flow {
+            Ok(Flow::Exit) => break,
+            Ok(Flow::Continue) => {}
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+            Err(e) => eprintln!("error: {e}"),
+        }
+        lines.update(&session);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "shell")]
+struct LineReader {
+    editor: rustyline::Editor<helper::PathHelper, rustyline::history::DefaultHistory>,
+}
+
+#[cfg(feature = "shell")]
+impl LineReader {
+    fn new() -> io::Result<Self> {
+        let mut editor = rustyline::Editor::new().map_err(readline_error)?;
+        editor.set_helper(Some(helper::PathHelper::default()));
+        Ok(LineReader { editor })
+    }
+
+    fn read(&mut self, session: &Session) -> io::Result<Option<String>> {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.paths = session.paths();
+        }
+        match self.editor.readline(&session.prompt()) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    self.editor
+                        .add_history_entry(line.as_str())
+                        .map_err(readline_error)?;
+                }
+                Ok(Some(line))
+            }
+            Err(rustyline::error::ReadlineError::Eof) => Ok(None),
+            // Ctrl-C abandons the current line, as in a shell.
+            Err(rustyline::error::ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(e) => Err(readline_error(e)),
+        }
+    }
+
+    fn update(&mut self, session: &Session) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.paths = session.paths();
+        }
+    }
+}
+
+#[cfg(feature = "shell")]
+fn readline_error(e: rustyline::error::ReadlineError) -> io::Error {
+    match e {
+        rustyline::error::ReadlineError::Io(e) => e,
+        other => io::Error::other(other.to_string()),
+    }
+}
+
+#[cfg(feature = "shell")]
+mod helper {
+    use rustyline::completion::Completer;
+    use rustyline::highlight::Highlighter;
+    use rustyline::hint::Hinter;
+    use rustyline::validate::Validator;
+    use rustyline::{Context, Helper};
+
+    /// Completes commands and the paths of the open manifest.
+    #[derive(Default)]
+    pub(super) struct PathHelper {
+        pub(super) paths: Vec<String>,
+    }
+
+    impl Completer for PathHelper {
+        type Candidate = String;
+
+        fn complete(
+            &self,
+            line: &str,
+            pos: usize,
+            _: &Context<'_>,
+        ) -> rustyline::Result<(usize, Vec<String>)> {
+            Ok(super::complete(line, pos, &self.paths))
+        }
+    }
+
+    impl Hinter for PathHelper {
+        type Hint = String;
+    }
+
+    impl Highlighter for PathHelper {}
+
+    impl Validator for PathHelper {}
+
+    impl Helper for PathHelper {}
+}
+
+#[cfg(not(feature = "shell"))]
+struct LineReader {
+    stdin: io::StdinLock<'static>,
+    interactive: bool,
+}
+
+#[cfg(not(feature = "shell"))]
+impl LineReader {
+    fn new() -> io::Result<Self> {
+        use std::io::IsTerminal;
+
+        let stdin = io::stdin();
+        let interactive = stdin.is_terminal();
+        Ok(LineReader {
+            stdin: stdin.lock(),
+            interactive,
+        })
+    }
+
+    fn read(&mut self, session: &Session) -> io::Result<Option<String>> {
+        use std::io::BufRead;
+
+        if self.interactive {
+            print!("{}", session.prompt());
+            io::stdout().flush()?;
+        }
+        let mut line = String::new();
+        if self.stdin.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }
+
+    fn update(&mut self, _: &Session) {}
+}
@@ -0,0 +1,400 @@
+//! Engram history: `delta`, `apply-delta` and `generations`.
+
+use super::{build_keyring, print_json, CompressionArg, EngramArgs, KeyArgs};
+use crate::embrfs::EmbrFS;
+use crate::delta::{self, EngramDelta};
+use crate::generations::{DirectoryTier, GenerationLocation, GenerationPolicy, GenerationalStore, TierReport};
+use crate::envelope::BinaryWriteOptions;
+use clap::{Args, Subcommand};
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Actions of `embeddenator generations`.
+#[derive(Subcommand)]
+pub enum GenerationCommand {
+    /// Record an engram and manifest as the newest generation
+    Commit {
+        /// Store directory
+        #[arg(long, value_name = "DIR", help_heading = "Required")]
+        store: PathBuf,
+
+        #[command(flatten)]
+        paths: EngramArgs,
+
+        /// Newest generations kept as full files (default: the store's policy)
+        #[arg(long, value_name = "N")]
+        keep_full: Option<usize>,
+
+        /// Newest generations kept on this host (default: the store's policy)
+        #[arg(long, value_name = "N")]
+        keep_local: Option<usize>,
+
+        /// Directory receiving generations older than --keep-local
+        #[arg(long, value_name = "DIR")]
+        remote: Option<PathBuf>,
+
+        /// Optional compression for the files the store writes (default: none)
+        #[arg(long, default_value = "none", value_enum)]
+        compression: CompressionArg,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// List generations and where each one is stored
+    List {
+        /// Store directory
+        #[arg(long, value_name = "DIR", help_heading = "Required")]
+        store: PathBuf,
+    },
+
+    /// Rebuild one generation as a regular engram and manifest
+    Checkout {
+        /// Store directory
+        #[arg(long, value_name = "DIR", help_heading = "Required")]
+        store: PathBuf,
+
+        /// Generation to rebuild (default: the newest)
+        #[arg(long, value_name = "ID")]
+        generation: Option<u64>,
+
+        /// Directory holding generations pushed off this host
+        #[arg(long, value_name = "DIR")]
+        remote: Option<PathBuf>,
+
+        /// Output engram file
+        #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+        engram: PathBuf,
+
+        /// Output manifest file
+        #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+        manifest: PathBuf,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+
+    /// Compact and move generations until the store matches its policy
+    Tier {
+        /// Store directory
+        #[arg(long, value_name = "DIR", help_heading = "Required")]
+        store: PathBuf,
+
+        /// Newest generations kept as full files (default: the store's policy)
+        #[arg(long, value_name = "N")]
+        keep_full: Option<usize>,
+
+        /// Newest generations kept on this host (default: the store's policy)
+        #[arg(long, value_name = "N")]
+        keep_local: Option<usize>,
+
+        /// Directory receiving generations older than --keep-local
+        #[arg(long, value_name = "DIR")]
+        remote: Option<PathBuf>,
+
+        /// Optional compression for the deltas the store writes (default: none)
+        #[arg(long, default_value = "none", value_enum)]
+        compression: CompressionArg,
+
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+}
+#[derive(Args)]
+pub struct DeltaArgs {
+    /// Engram the delta is computed against
+    #[arg(long, value_name = "FILE", help_heading = "Required")]
+    pub base_engram: PathBuf,
+
+    /// Manifest of the base engram
+    #[arg(long, value_name = "FILE", help_heading = "Required")]
+    pub base_manifest: PathBuf,
+
+    #[command(flatten)]
+    pub paths: EngramArgs,
+
+    /// Output delta file
+    #[arg(short, long, default_value = "update.delta", value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// Optional compression for the delta envelope (default: none)
+    #[arg(long, default_value = "none", value_enum)]
+    pub compression: CompressionArg,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl DeltaArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let DeltaArgs {
+            base_engram,
+            base_manifest,
+            paths: EngramArgs { engram, manifest },
+            output,
+            compression,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let delta = EngramDelta::between(
+            &EmbrFS::load_engram_with_keys(&base_engram, &keyring)?,
+            &EmbrFS::load_manifest_with_keys(&base_manifest, &keyring)?,
+            &EmbrFS::load_engram_with_keys(&engram, &keyring)?,
+            &EmbrFS::load_manifest_with_keys(&manifest, &keyring)?,
+        )?;
+        delta.save(
+            &output,
+            BinaryWriteOptions {
+                codec: compression.into(),
+                ..Default::default()
+            },
+        )?;
+        if json_output {
+            print_json(&serde_json::json!({
+                "output": output,
+                "chunks_upserted": delta.upserted_chunks.len(),
+                "chunks_removed": delta.removed_chunks.len(),
+                "files_upserted": delta.manifest.upserted.len(),
+                "files_removed": delta.manifest.removed.len(),
+            }))?;
+        } else {
+            println!(
+                "Wrote {}: {} chunks upserted, {} removed, {} files upserted, {} removed",
+                output.display(),
+                delta.upserted_chunks.len(),
+                delta.removed_chunks.len(),
+                delta.manifest.upserted.len(),
+                delta.manifest.removed.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct ApplyDeltaArgs {
+    /// Engram the first delta was computed against
+    #[arg(long, value_name = "FILE", help_heading = "Required")]
+    pub base_engram: PathBuf,
+
+    /// Manifest of the base engram
+    #[arg(long, value_name = "FILE", help_heading = "Required")]
+    pub base_manifest: PathBuf,
+
+    /// Delta file to apply. Repeatable; applied in order.
+    #[arg(long = "delta", value_name = "FILE", required = true)]
+    pub deltas: Vec<PathBuf>,
+
+    /// Output engram file
+    #[arg(short, long, default_value = "root.engram", value_name = "FILE")]
+    pub engram: PathBuf,
+
+    /// Output manifest file
+    #[arg(short, long, default_value = "manifest.json", value_name = "FILE")]
+    pub manifest: PathBuf,
+
+    #[command(flatten)]
+    pub keys: KeyArgs,
+}
+
+impl ApplyDeltaArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let ApplyDeltaArgs {
+            base_engram,
+            base_manifest,
+            deltas,
+            engram,
+            manifest,
+            keys: KeyArgs { keys },
+        } = self;
+        let keyring = build_keyring(&keys)?;
+        let deltas = deltas
+            .iter()
+            .map(|p| EngramDelta::load_with_keys(p, &keyring))
+            .collect::<io::Result<Vec<_>>>()?;
+        let (engram_data, manifest_data) = delta::flatten(
+            &EmbrFS::load_engram_with_keys(&base_engram, &keyring)?,
+            &EmbrFS::load_manifest_with_keys(&base_manifest, &keyring)?,
+            &deltas,
+        )?;
+        let mut fs = EmbrFS::new();
+        fs.engram = engram_data;
+        fs.manifest = manifest_data;
+        fs.save_transactional(&engram, &manifest, Default::default(), Default::default())?;
+        if json_output {
+            print_json(&serde_json::json!({
+                "deltas": deltas.len(),
+                "files": fs.manifest.files.len(),
+                "chunks": fs.engram.codebook.len(),
+            }))?;
+        } else {
+            println!(
+                "Applied {} delta(s): {} files, {} chunks",
+                deltas.len(),
+                fs.manifest.files.len(),
+                fs.engram.codebook.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct GenerationsArgs {
+    #[command(subcommand)]
+    pub action: GenerationCommand,
+}
+
+impl GenerationsArgs {
+    pub(super) fn run(self, json_output: bool) -> io::Result<()> {
+        let GenerationsArgs { action } = self;
+        let open_store = |store: &Path,
+                          keep_full: Option<usize>,
+                          keep_local: Option<usize>,
+                          remote: Option<&Path>,
+                          keys: &[(u32, PathBuf)]| {
+            let mut store = GenerationalStore::open(store)?.with_keys(build_keyring(keys)?);
+            let policy = store.policy();
+            store = store.with_policy(GenerationPolicy {
+                keep_full: keep_full.unwrap_or(policy.keep_full),
+                keep_local: keep_local.unwrap_or(policy.keep_local),
+            });
+            if let Some(remote) = remote {
+                store = store.with_remote(Box::new(DirectoryTier::new(remote)?));
+            }
+            io::Result::Ok(store)
+        };
+        let print_tiering = |report: &TierReport| {
+            if !report.is_empty() {
+                println!(
+                    "Tiered: {} compacted to deltas, {} pushed to remote, {} bytes freed",
+                    report.compacted.len(),
+                    report.pushed.len(),
+                    report.bytes_freed
+                );
+            }
+        };
+        match action {
+            GenerationCommand::Commit {
+                store,
+                paths: EngramArgs { engram, manifest },
+                keep_full,
+                keep_local,
+                remote,
+                compression,
+                keys: KeyArgs { keys },
+            } => {
+                let keyring = build_keyring(&keys)?;
+                let mut fs = EmbrFS::new();
+                fs.engram = EmbrFS::load_engram_with_keys(&engram, &keyring)?;
+                fs.manifest = EmbrFS::load_manifest_with_keys(&manifest, &keyring)?;
+                let mut store = open_store(&store, keep_full, keep_local, remote.as_deref(), &keys)?
+                    .with_write_options(BinaryWriteOptions {
+                        codec: compression.into(),
+                        ..Default::default()
+                    });
+                let (record, report) = store.commit(&fs)?;
+                if json_output {
+                    print_json(&serde_json::json!({ "generation": record, "tiering": report }))?;
+                } else {
+                    println!(
+                        "Committed generation {}: {} files, {} chunks",
+                        record.id, record.files, record.chunks
+                    );
+                    print_tiering(&report);
+                }
+            }
+            GenerationCommand::List { store } => {
+                let store = GenerationalStore::open(&store)?;
+                if json_output {
+                    print_json(&serde_json::json!({
+                        "policy": store.policy(),
+                        "generations": store.generations(),
+                    }))?;
+                } else {
+                    let policy = store.policy();
+                    println!(
+                        "{} generation(s); keep {} full, {} local",
+                        store.generations().len(),
+                        policy.keep_full,
+                        policy.keep_local
+                    );
+                    for g in store.generations() {
+                        let form = match g.base {
+                            Some(base) => format!("delta vs {base}"),
+                            None => "full".to_string(),
+                        };
+                        let location = match g.location {
+                            GenerationLocation::Local => "local",
+                            GenerationLocation::Remote => "remote",
+                        };
+                        println!(
+                            "  {:>5}  {:<14} {:<6} {:>12} bytes  {} files, {} chunks",
+                            g.id, form, location, g.stored_bytes, g.files, g.chunks
+                        );
+                    }
+                }
+            }
+            GenerationCommand::Checkout {
+                store,
+                generation,
+                remote,
+                engram,
+                manifest,
+                keys: KeyArgs { keys },
+            } => {
+                let store = open_store(&store, None, None, remote.as_deref(), &keys)?;
+                let id = match generation {
+                    Some(id) => id,
+                    None => store
+                        .latest()
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the store has no generations"))?
+                        .id,
+                };
+                let (engram_data, manifest_data) = store.read(id)?;
+                let mut fs = EmbrFS::new();
+                fs.engram = engram_data;
+                fs.manifest = manifest_data;
+                fs.save_transactional(&engram, &manifest, Default::default(), Default::default())?;
+                if json_output {
+                    print_json(&serde_json::json!({
+                        "generation": id,
+                        "files": fs.manifest.files.len(),
+                        "chunks": fs.engram.codebook.len(),
+                    }))?;
+                } else {
+                    println!(
+                        "Checked out generation {}: {} files, {} chunks",
+                        id,
+                        fs.manifest.files.len(),
+                        fs.engram.codebook.len()
+                    );
+                }
+            }
+            GenerationCommand::Tier {
+                store,
+                keep_full,
+                keep_local,
+                remote,
+                compression,
+                keys: KeyArgs { keys },
+            } => {
+                let mut store = open_store(&store, keep_full, keep_local, remote.as_deref(), &keys)?
+                    .with_write_options(BinaryWriteOptions {
+                        codec: compression.into(),
+                        ..Default::default()
+                    });
+                let report = store.apply_policy()?;
+                if json_output {
+                    print_json(&report)?;
+                } else if report.is_empty() {
+                    println!("Nothing to tier");
+                } else {
+                    print_tiering(&report);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+//! Batched dot products between chunk vectors and a codebook basis.
+//!
+//! Projecting a batch onto a [`Codebook`](crate::codebook::Codebook) spends
+//! nearly all of its time on one dot product per (64-byte chunk, basis
+//! vector) pair. [`dot_matrix`] computes the whole matrix of them at once,
+//! on the CPU or, with the `cuda` feature, on an NVIDIA GPU. Differential
+//! encoding ([`EmbrFS::encode_differential`](crate::EmbrFS::encode_differential))
+//! projects every chunk vector onto its basis the same way. There is no
+//! wgpu backend yet; it is tracked in `docs/TODO.md`.
+//!
+//! The dots are exact integers on every backend and the cosines derived
+//! from them are computed afterwards on the CPU, so projections are
+//! bit-identical whichever backend ran. The CUDA path still checks its first
+//! batch against the CPU and is disabled for the rest of the process if the
+//! two disagree or the device fails, with a warning; the batch is then
+//! recomputed on the CPU.
+
+use crate::bitsliced::BitslicedTritVec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Smallest batch, in chunk-basis pairs, worth sending to a GPU.
+pub const MIN_GPU_PAIRS: usize = 1 << 16;
+
+/// Where [`dot_matrix`] runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionBackend {
+    /// A GPU when one is usable and the batch is large enough
+    /// ([`MIN_GPU_PAIRS`]), the CPU otherwise.
+    #[default]
+    Auto,
+    Cpu,
+    /// A GPU whenever one is usable, whatever the batch size; the CPU if not.
+    Cuda,
+}
+
+impl fmt::Display for ProjectionBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProjectionBackend::Auto => "auto",
+            ProjectionBackend::Cpu => "cpu",
+            ProjectionBackend::Cuda => "cuda",
+        })
+    }
+}
+
+/// Whether this build can offload projections and a CUDA device is usable.
+pub fn gpu_available() -> bool {
+    #[cfg(feature = "cuda")]
+    {
+        cuda::device().is_some()
+    }
+    #[cfg(not(feature = "cuda"))]
+    {
+        false
+    }
+}
+
+/// Dot product of every chunk with every basis vector, row-major by chunk:
+/// entry `c * basis.len() + b` is `chunks[c] · basis[b]`. All vectors must
+/// have the same length. Returns the matrix and the backend that computed
+/// it ([`ProjectionBackend::Cpu`] or [`ProjectionBackend::Cuda`]).
+pub fn dot_matrix(
+    basis: &[BitslicedTritVec],
+    chunks: &[BitslicedTritVec],
+    backend: ProjectionBackend,
+) -> (Vec<i32>, ProjectionBackend) {
+    let pairs = basis.len() * chunks.len();
+    let try_gpu = match backend {
+        ProjectionBackend::Cpu => false,
+        ProjectionBackend::Auto => pairs >= MIN_GPU_PAIRS,
+        ProjectionBackend::Cuda => pairs > 0,
+    };
+    #[cfg(feature = "cuda")]
+    if try_gpu {
+        if let Some(dots) = cuda::dot_matrix(basis, chunks) {
+            return (dots, ProjectionBackend::Cuda);
+        }
+    }
+    #[cfg(not(feature = "cuda"))]
+    let _ = try_gpu;
+    (cpu_dot_matrix(basis, chunks), ProjectionBackend::Cpu)
+}
+
+fn cpu_dot_matrix(basis: &[BitslicedTritVec], chunks: &[BitslicedTritVec]) -> Vec<i32> {
+    let row = |chunk: &BitslicedTritVec| basis.iter().map(|b| chunk.dot_dispatch(b)).collect::<Vec<_>>();
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        chunks.par_iter().flat_map_iter(row).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        chunks.iter().flat_map(row).collect()
+    }
+}
+
+#[cfg(feature = "cuda")]
+mod cuda {
+    use super::*;
+    use cudarc::driver::{CudaDevice, CudaFunction, LaunchAsync, LaunchConfig};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, OnceLock};
+
+    const MODULE: &str = "embeddenator_projection";
+    const KERNEL: &str = "ternary_dots";
+
+    /// One thread per (chunk, basis) pair; planes are `words` u64s each.
+    const SOURCE: &str = r#"
+extern "C" __global__ void ternary_dots(
+    const unsigned long long* basis_pos, const unsigned long long* basis_neg,
+    const unsigned long long* chunk_pos, const unsigned long long* chunk_neg,
+    int* out, unsigned int n_basis, unsigned int n_pairs, unsigned int words)
+{
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i >= n_pairs) return;
+    size_t b = (size_t)(i % n_basis) * words;
+    size_t c = (size_t)(i / n_basis) * words;
+    int dot = 0;
+    for (unsigned int w = 0; w < words; ++w) {
+        unsigned long long cp = chunk_pos[c + w], cn = chunk_neg[c + w];
+        unsigned long long bp = basis_pos[b + w], bn = basis_neg[b + w];
+        dot += __popcll(cp & bp) + __popcll(cn & bn) - __popcll(cp & bn) - __popcll(cn & bp);
+    }
+    out[i] = dot;
+}
+"#;
+
+    pub(super) struct Gpu {
+        device: Arc<CudaDevice>,
+        kernel: CudaFunction,
+    }
+
+    static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+    /// Set once a batch has matched the CPU; until then results are checked.
+    static VERIFIED: AtomicBool = AtomicBool::new(false);
+    static DISABLED: AtomicBool = AtomicBool::new(false);
+
+    fn init() -> Option<Gpu> {
+        // cudarc panics when the driver library is missing; look for it first.
+        // SAFETY: loading the CUDA driver runs no initialisation code of ours.
+        unsafe { libloading::Library::new(libloading::library_filename("cuda")) }.ok()?;
+        std::panic::catch_unwind(|| {
+            let device = CudaDevice::new(0).ok()?;
+            let ptx = cudarc::nvrtc::compile_ptx(SOURCE).ok()?;
+            device.load_ptx(ptx, MODULE, &[KERNEL]).ok()?;
+            let kernel = device.get_func(MODULE, KERNEL)?;
+            Some(Gpu { device, kernel })
+        })
+        .ok()
+        .flatten()
+    }
+
+    pub(super) fn device() -> Option<&'static Gpu> {
+        if DISABLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        GPU.get_or_init(init).as_ref()
+    }
+
+    fn disable(reason: &str) {
+        if !DISABLED.swap(true, Ordering::Relaxed) {
+            crate::logging::warn(&format!("CUDA projection disabled ({reason}); using the CPU"));
+        }
+    }
+
+    fn planes(vecs: &[BitslicedTritVec], words: usize) -> (Vec<u64>, Vec<u64>) {
+        let (mut pos, mut neg) = (Vec::with_capacity(vecs.len() * words), Vec::with_capacity(vecs.len() * words));
+        for v in vecs {
+            pos.extend_from_slice(v.pos_plane());
+            neg.extend_from_slice(v.neg_plane());
+        }
+        (pos, neg)
+    }
+
+    fn run(gpu: &Gpu, basis: &[BitslicedTritVec], chunks: &[BitslicedTritVec]) -> Result<Vec<i32>, String> {
+        let words = basis[0].pos_plane().len();
+        if basis.iter().chain(chunks).any(|v| v.pos_plane().len() != words) {
+            return Err("vectors of different lengths".into());
+        }
+        let pairs = u32::try_from(basis.len() * chunks.len()).map_err(|_| "batch too large".to_string())?;
+        let (bp, bn) = planes(basis, words);
+        let (cp, cn) = planes(chunks, words);
+        let dev = &gpu.device;
+        let err = |e: cudarc::driver::DriverError| e.to_string();
+        let (bp, bn) = (dev.htod_sync_copy(&bp).map_err(err)?, dev.htod_sync_copy(&bn).map_err(err)?);
+        let (cp, cn) = (dev.htod_sync_copy(&cp).map_err(err)?, dev.htod_sync_copy(&cn).map_err(err)?);
+        let mut out = dev.alloc_zeros::<i32>(pairs as usize).map_err(err)?;
+        let params = (&bp, &bn, &cp, &cn, &mut out, basis.len() as u32, pairs, words as u32);
+        // SAFETY: the kernel reads `words` u64s per vector from the four
+        // plane buffers, sized above, and writes one i32 per pair to `out`.
+        unsafe { gpu.kernel.clone().launch(LaunchConfig::for_num_elems(pairs), params) }.map_err(err)?;
+        dev.dtoh_sync_copy(&out).map_err(err)
+    }
+
+    /// The dot matrix from the GPU, or `None` if none is usable.
+    pub(super) fn dot_matrix(basis: &[BitslicedTritVec], chunks: &[BitslicedTritVec]) -> Option<Vec<i32>> {
+        let gpu = device()?;
+        if basis.is_empty() || chunks.is_empty() {
+            return Some(Vec::new());
+        }
+        let dots = match run(gpu, basis, chunks) {
+            Ok(dots) => dots,
+            Err(e) => {
+                disable(&e);
+                return None;
+            }
+        };
+        if !VERIFIED.load(Ordering::Relaxed) {
+            let sample = chunks.len().min(64);
+            if cpu_dot_matrix(basis, &chunks[..sample])[..] != dots[..sample * basis.len()] {
+                disable("results differ from the CPU");
+                return None;
+            }
+            VERIFIED.store(true, Ordering::Relaxed);
+        }
+        Some(dots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vsa::SparseVec;
+
+    #[test]
+    fn dot_matrix_matches_pairwise_dots() {
+        let len = 10_000;
+        let vec = |seed: u8| BitslicedTritVec::from_sparse(&SparseVec::from_bytes(&[seed; 64]), len);
+        let basis: Vec<_> = (0..5).map(vec).collect();
+        let chunks: Vec<_> = (10..17).map(vec).collect();
+        for backend in [ProjectionBackend::Cpu, ProjectionBackend::Auto, ProjectionBackend::Cuda] {
+            let (dots, used) = dot_matrix(&basis, &chunks, backend);
+            assert!(used != ProjectionBackend::Auto);
+            for (c, chunk) in chunks.iter().enumerate() {
+                for (b, plane) in basis.iter().enumerate() {
+                    assert_eq!(dots[c * basis.len() + b], chunk.dot(plane), "{backend}");
+                }
+            }
+        }
+    }
+}
@@ -19,10 +19,31 @@
 //! - Without the codebook, reconstruction is mathematically impossible
 //! - The engram alone is information-theoretically secure
 //! - Different codebooks = different "encryption keys"
+//!
+//! # Artifacts
+//!
+//! [`Codebook::save`] writes a codebook on its own, apart from any engram,
+//! so it can be shared and pinned: [`Codebook::id`] names its content and
+//! [`Codebook::load_pinned`] refuses any other. Words projected through a
+//! loaded codebook are bit-identical to the original's, [`WordMetadata`]
+//! included. [`Codebook::diff`] compares two versions.
 
+use crate::access_stats::{AccessStats, EVICTION_WINDOW};
+use crate::memory;
+use crate::metrics::metrics;
+use crate::signing::to_hex;
+use crate::dimensional::{DifferentialEncoder, DimensionalConfig, HyperVec};
+use crate::embrfs::bincode_io_error;
+use crate::envelope::{BinaryWriteOptions, ChecksumCodec, EnvelopeReader, EnvelopeWriter, Keyring, PayloadKind};
+use crate::bitsliced::BitslicedTritVec;
+use crate::batch_projection::{self, ProjectionBackend};
 use crate::vsa::{SparseVec, DIM};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 /// 64-bit balanced ternary encoding unit
 /// - 61 bits: data payload (39 trits worth of information)
@@ -233,8 +254,12 @@ pub struct SemanticOutlier {
     pub position: usize,
     /// Length of the outlier pattern
     pub length: usize,
-    /// Entropy score (higher = more unusual)
+    /// Shannon entropy of the window, in bits per byte
     pub entropy_score: f64,
+    /// [`OutlierDetector::name`] of the detector that flagged the window
+    pub detector: String,
+    /// That detector's score (higher = more unusual)
+    pub score: f64,
     /// The outlier pattern encoded as balanced ternary words
     pub encoded_pattern: Vec<BalancedTernaryWord>,
     /// Semantic vector for similarity matching
@@ -275,6 +300,9 @@ pub struct Codebook {
     
     /// Cryptographic salt for key derivation (optional)
     pub salt: Option<[u8; 32]>,
+    
+    /// How [`Codebook::project`] decides which windows are stored exactly
+    pub outliers: OutlierConfig,
 }
 
 /// Statistics tracked by the codebook
@@ -313,12 +341,13 @@ impl Codebook {
     /// Create a new empty codebook
     pub fn new(dimensionality: usize) -> Self {
         Codebook {
-            version: 1,
+            version: CODEBOOK_VERSION,
             dimensionality,
             basis_vectors: Vec::new(),
             semantic_markers: Vec::new(),
             statistics: CodebookStatistics::default(),
             salt: None,
+            outliers: OutlierConfig::default(),
         }
     }
 
@@ -411,32 +440,122 @@ impl Codebook {
     }
 
     /// Project data onto the codebook basis
-    /// Returns coefficients, residual, and detected outliers
+    /// Returns coefficients, residual, and outliers found by the detectors
+    /// of [`Codebook::outliers`]
     pub fn project(&self, data: &[u8]) -> ProjectionResult {
+        let detectors = self.outliers.detectors();
+        let detectors: Vec<&dyn OutlierDetector> = detectors.iter().map(|d| d.as_ref()).collect();
+        self.project_with(data, &detectors)
+    }
+
+    /// Project data onto the codebook basis, detecting outliers with
+    /// `detectors` instead of the configured ones
+    pub fn project_with(&self, data: &[u8], detectors: &[&dyn OutlierDetector]) -> ProjectionResult {
+        self.project_onto(&BasisPlanes::new(self), data, detectors, None)
+    }
+
+    /// Project many inputs at once, each as by [`Codebook::project`]
+    ///
+    /// The basis is converted to bitsliced form once for the whole batch,
+    /// and with the `parallel` feature inputs are projected across threads.
+    /// Results are in input order.
+    pub fn project_batch<D: AsRef<[u8]> + Sync>(&self, inputs: &[D]) -> Vec<ProjectionResult> {
+        self.project_batch_on(inputs, ProjectionBackend::Auto)
+    }
+
+    /// [`Codebook::project_batch`], with the chunk-basis dot products of
+    /// the whole batch computed in one pass on `backend`
+    ///
+    /// Results are identical on every backend; see [`crate::batch_projection`].
+    pub fn project_batch_on<D: AsRef<[u8]> + Sync>(
+        &self,
+        inputs: &[D],
+        backend: ProjectionBackend,
+    ) -> Vec<ProjectionResult> {
+        let planes = BasisPlanes::new(self);
+        let detectors = self.outliers.detectors();
+        let detectors: Vec<&dyn OutlierDetector> = detectors.iter().map(|d| d.as_ref()).collect();
+
+        // Without a basis there is nothing to multiply.
+        let chunks: Vec<&[u8]> = if planes.planes.is_empty() {
+            Vec::new()
+        } else {
+            inputs.iter().flat_map(|d| d.as_ref().chunks(PROJECTION_CHUNK)).collect()
+        };
+        let slice = |chunk: &&[u8]| {
+            let vector = SparseVec::from_bytes(chunk);
+            let norm = ((vector.pos.len() + vector.neg.len()) as f64).sqrt();
+            (norm, BitslicedTritVec::from_sparse(&vector, planes.len))
+        };
+        #[cfg(feature = "parallel")]
+        let sliced: Vec<(f64, BitslicedTritVec)> = {
+            use rayon::prelude::*;
+            chunks.par_iter().map(slice).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let sliced: Vec<(f64, BitslicedTritVec)> = chunks.iter().map(slice).collect();
+        let (norms, sliced): (Vec<f64>, Vec<BitslicedTritVec>) = sliced.into_iter().unzip();
+        let basis = planes.planes.len();
+        let (dots, _) = batch_projection::dot_matrix(&planes.planes, &sliced, backend);
+        drop(sliced);
+
+        // Each input's rows of the dot matrix.
+        let mut batch = Vec::with_capacity(inputs.len());
+        let mut first = 0;
+        for data in inputs {
+            let data = data.as_ref();
+            let dots = (basis > 0).then(|| {
+                let n = data.len().div_ceil(PROJECTION_CHUNK);
+                let rows = BatchDots { norms: &norms[first..first + n], dots: &dots[first * basis..(first + n) * basis] };
+                first += n;
+                rows
+            });
+            batch.push((data, dots));
+        }
+        let project = |(data, dots): &(&[u8], Option<BatchDots>)| {
+            self.project_onto(&planes, data, &detectors, dots.as_ref())
+        };
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            batch.par_iter().map(project).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            batch.iter().map(project).collect()
+        }
+    }
+
+    fn project_onto(
+        &self,
+        planes: &BasisPlanes,
+        data: &[u8],
+        detectors: &[&dyn OutlierDetector],
+        batch: Option<&BatchDots>,
+    ) -> ProjectionResult {
         let mut coefficients = HashMap::new();
         let mut residual = Vec::new();
         let mut outliers = Vec::new();
         
-        // 1. Analyze data for semantic outliers (entropy spikes)
-        let detected_outliers = self.detect_semantic_outliers(data);
+        // 1. Analyze data for semantic outliers
+        let detected_outliers = self.detect_semantic_outliers(data, detectors);
         outliers.extend(detected_outliers);
         
         // 2. Project data chunks onto basis vectors
-        let chunk_size = 64; // Process in 64-byte chunks
-        for (chunk_idx, chunk) in data.chunks(chunk_size).enumerate() {
-            let chunk_vec = SparseVec::from_bytes(chunk);
-            
+        for (chunk_idx, chunk) in data.chunks(PROJECTION_CHUNK).enumerate() {
             // Find best matching basis vectors
-            let mut best_matches: Vec<(u32, f64)> = self.basis_vectors
-                .iter()
-                .map(|basis| (basis.id, chunk_vec.cosine(&basis.vector)))
-                .filter(|(_, sim)| *sim > 0.3) // Threshold for relevance
-                .collect();
+            let mut best_matches: Vec<(u32, f64)> = match batch {
+                Some(batch) => planes.similarities_from_dots(batch.norms[chunk_idx], batch.row(chunk_idx, planes)),
+                None => planes.similarities(&SparseVec::from_bytes(chunk)),
+            }
+            .into_iter()
+            .filter(|(_, sim)| *sim > BASIS_RELEVANCE)
+            .collect();
             
             best_matches.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
             
             // Take top N matches
-            for (basis_id, similarity) in best_matches.iter().take(4) {
+            for (basis_id, similarity) in best_matches.iter().take(MAX_BASIS_MATCHES) {
                 // Encode coefficient as balanced ternary
                 let coef_value = (*similarity * 1000.0) as i64;
                 if let Some(word) = BalancedTernaryWord::new(coef_value, WordMetadata::Data) {
@@ -469,50 +588,56 @@ impl Codebook {
         }
     }
 
-    /// Detect semantic outliers (high entropy, rare patterns)
-    fn detect_semantic_outliers(&self, data: &[u8]) -> Vec<SemanticOutlier> {
+    /// Detect semantic outliers: windows flagged by any of `detectors`
+    fn detect_semantic_outliers(
+        &self,
+        data: &[u8],
+        detectors: &[&dyn OutlierDetector],
+    ) -> Vec<SemanticOutlier> {
         let mut outliers = Vec::new();
-        let window_size = 32;
+        let window_size = self.outliers.window;
         
-        if data.len() < window_size {
+        if detectors.is_empty() || window_size == 0 || data.len() < window_size {
             return outliers;
         }
         
         for i in 0..data.len() - window_size {
-            let window = &data[i..i + window_size];
-            let entropy = self.calculate_entropy(window);
+            // Overlapping outliers are deduplicated: skip windows within
+            // half a window of the last one kept
+            if outliers.last().is_some_and(|o: &SemanticOutlier| i - o.position < window_size / 2) {
+                continue;
+            }
+            let bytes = &data[i..i + window_size];
+            let window = OutlierWindow::new(i, bytes, self.calculate_entropy(bytes));
+            let Some((detector, score)) = detectors
+                .iter()
+                .find_map(|d| d.detect(self, &window).map(|score| (d.name(), score)))
+            else {
+                continue;
+            };
             
-            // High entropy windows are outliers (compressed/encrypted data)
-            if entropy > 7.5 {
-                let pattern_vec = SparseVec::from_bytes(window);
-                
-                // Encode the outlier pattern
-                let mut encoded_pattern = Vec::new();
-                for chunk in window.chunks(8) {
-                    let value = chunk.iter()
-                        .enumerate()
-                        .fold(0i64, |acc, (j, &b)| acc + ((b as i64) << (j * 8)));
-                    if let Some(word) = BalancedTernaryWord::new(value, WordMetadata::SemanticOutlier) {
-                        encoded_pattern.push(word);
-                    }
+            // Encode the outlier pattern
+            let mut encoded_pattern = Vec::new();
+            for chunk in window.bytes.chunks(8) {
+                let value = chunk.iter()
+                    .enumerate()
+                    .fold(0i64, |acc, (j, &b)| acc + ((b as i64) << (j * 8)));
+                if let Some(word) = BalancedTernaryWord::new(value, WordMetadata::SemanticOutlier) {
+                    encoded_pattern.push(word);
                 }
-                
-                outliers.push(SemanticOutlier {
-                    position: i,
-                    length: window_size,
-                    entropy_score: entropy,
-                    encoded_pattern,
-                    semantic_vec: pattern_vec,
-                });
-                
-                // Skip ahead to avoid overlapping outliers
-                // i += window_size / 2; // Can't mutate loop variable, handled by dedup later
             }
+            
+            outliers.push(SemanticOutlier {
+                position: i,
+                length: window_size,
+                entropy_score: window.entropy,
+                detector: detector.to_string(),
+                score,
+                encoded_pattern,
+                semantic_vec: window.vector().clone(),
+            });
         }
         
-        // Deduplicate overlapping outliers
-        outliers.dedup_by(|a, b| a.position.abs_diff(b.position) < window_size / 2);
-        
         outliers
     }
 
@@ -608,6 +733,713 @@ impl Codebook {
         result.truncate(expected_size);
         result
     }
+
+    /// Replace the basis with at most `k` vectors learned from `samples`,
+    /// so that data resembling the samples leaves small residuals.
+    ///
+    /// Runs spherical k-means over the ternary vectors: seeds are picked
+    /// farthest-first starting from the first sample (training is
+    /// deterministic), samples join the centroid with the highest cosine, and
+    /// each centroid keeps the sign of its members' per-dimension sum on the
+    /// dimensions where that sum is largest, as many as a member has on
+    /// average, so the basis stays as sparse as the data. A basis vector's
+    /// weight is the fraction of samples nearest to it; centroids left
+    /// without members are dropped. Empty samples are ignored.
+    pub fn train_basis(&mut self, samples: &[SparseVec], k: usize) -> BasisTrainingReport {
+        let samples: Vec<&SparseVec> = samples
+            .iter()
+            .filter(|v| !v.pos.is_empty() || !v.neg.is_empty())
+            .collect();
+        let k = k.min(samples.len());
+        let mut report = BasisTrainingReport {
+            samples: samples.len(),
+            ..Default::default()
+        };
+        self.basis_vectors.clear();
+        if k == 0 {
+            return report;
+        }
+
+        let mut centroids = vec![samples[0].clone()];
+        let mut closest: Vec<f64> = samples.iter().map(|s| s.cosine(&centroids[0])).collect();
+        while centroids.len() < k {
+            let mut next = 0;
+            for (i, &sim) in closest.iter().enumerate() {
+                if sim < closest[next] {
+                    next = i;
+                }
+            }
+            let seed = samples[next].clone();
+            for (sim, sample) in closest.iter_mut().zip(&samples) {
+                *sim = sim.max(sample.cosine(&seed));
+            }
+            centroids.push(seed);
+        }
+
+        let mut assignment = vec![usize::MAX; samples.len()];
+        let mut similarity = vec![0.0; samples.len()];
+        loop {
+            report.iterations += 1;
+            let mut changed = false;
+            for (i, sample) in samples.iter().enumerate() {
+                let (best, sim) = nearest_centroid(&centroids, sample);
+                similarity[i] = sim;
+                if assignment[i] != best {
+                    assignment[i] = best;
+                    changed = true;
+                }
+            }
+            if !changed {
+                report.converged = true;
+                break;
+            }
+            if report.iterations == MAX_BASIS_ITERATIONS {
+                break;
+            }
+            for (c, centroid) in centroids.iter_mut().enumerate() {
+                let members: Vec<&SparseVec> = samples
+                    .iter()
+                    .zip(&assignment)
+                    .filter(|&(_, &a)| a == c)
+                    .map(|(&s, _)| s)
+                    .collect();
+                if !members.is_empty() {
+                    *centroid = ternary_centroid(&members);
+                }
+            }
+        }
+
+        let mut counts = vec![0usize; centroids.len()];
+        for &a in &assignment {
+            counts[a] += 1;
+        }
+        for (centroid, count) in centroids.into_iter().zip(counts) {
+            if count == 0 {
+                continue;
+            }
+            self.basis_vectors.push(BasisVector {
+                id: self.basis_vectors.len() as u32,
+                vector: centroid,
+                label: None,
+                weight: count as f64 / samples.len() as f64,
+            });
+        }
+        report.basis_size = self.basis_vectors.len();
+        report.mean_similarity = similarity.iter().sum::<f64>() / samples.len() as f64;
+        report
+    }
+
+    /// A [`DifferentialEncoder`] whose basis is this codebook's basis
+    /// vectors, in order.
+    pub fn differential_encoder(&self, config: DimensionalConfig) -> DifferentialEncoder {
+        let mut encoder = DifferentialEncoder::new(config.clone());
+        for basis in &self.basis_vectors {
+            encoder.add_basis(HyperVec::from_sparse(config.clone(), &basis.vector));
+        }
+        encoder
+    }
+
+    /// Write the codebook as a standalone artifact: an envelope of kind
+    /// [`PayloadKind::Codebook`]. Checksums default to XXH3 when `opts`
+    /// does not pick a codec, so the file is always framed.
+    pub fn save<P: AsRef<Path>>(&self, path: P, mut opts: BinaryWriteOptions) -> io::Result<()> {
+        if opts.checksum == ChecksumCodec::None {
+            opts.checksum = ChecksumCodec::Xxh3;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = EnvelopeWriter::new(file, PayloadKind::Codebook, opts)?;
+        bincode::serialize_into(&mut writer, self).map_err(|e| bincode_io_error(*e))?;
+        writer.finish()?.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::load_with_keys(path, &Keyring::default())
+    }
+
+    /// Load a codebook artifact, decrypting it with `keys` if needed.
+    /// Codebooks written by a newer, unknown version are rejected.
+    pub fn load_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let path = path.as_ref();
+        if PayloadKind::sniff_file(path)? != Some(PayloadKind::Codebook) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a codebook envelope",
+            ));
+        }
+        let file = BufReader::new(File::open(path)?);
+        let mut reader = EnvelopeReader::with_keys(file, PayloadKind::Codebook, keys)?;
+        let codebook: Self = bincode::deserialize_from(&mut reader).map_err(|e| bincode_io_error(*e))?;
+        io::copy(&mut reader, &mut io::sink())?;
+        if codebook.version > CODEBOOK_VERSION {
+            return Err(io::Error::other(format!(
+                "unsupported codebook version {}",
+                codebook.version
+            )));
+        }
+        Ok(codebook)
+    }
+
+    /// Load a codebook artifact and check that its [`Codebook::id`] is
+    /// `expected`, so a pinned codebook can't be swapped for another.
+    pub fn load_pinned<P: AsRef<Path>>(path: P, keys: &Keyring, expected: &str) -> io::Result<Self> {
+        let codebook = Self::load_with_keys(path, keys)?;
+        let id = codebook.id();
+        if !id.eq_ignore_ascii_case(expected) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("codebook {} does not match the pinned {}", id, expected),
+            ));
+        }
+        Ok(codebook)
+    }
+
+    /// blake3 digest identifying the codebook's content: version,
+    /// dimensionality, basis, semantic markers, salt and outlier rules.
+    /// [`Codebook::statistics`] only tracks usage and is left out, so
+    /// encoding with a codebook never changes its identity.
+    pub fn digest(&self) -> [u8; 32] {
+        let bytes = bincode::serialize(&(
+            self.version,
+            self.dimensionality,
+            &self.basis_vectors,
+            &self.semantic_markers,
+            &self.salt,
+            &self.outliers,
+        ))
+        .expect("codebook fields serialize");
+        *blake3::hash(&bytes).as_bytes()
+    }
+
+    /// [`Codebook::digest`] as hex.
+    pub fn id(&self) -> String {
+        to_hex(&self.digest())
+    }
+
+    /// What changed from `self` to `other`. Basis vectors are matched by id.
+    pub fn diff(&self, other: &Codebook) -> CodebookDiff {
+        let before: BTreeMap<u32, &BasisVector> = self.basis_vectors.iter().map(|b| (b.id, b)).collect();
+        let after: BTreeMap<u32, &BasisVector> = other.basis_vectors.iter().map(|b| (b.id, b)).collect();
+        let same_vector = |a: &SparseVec, b: &SparseVec| a.pos == b.pos && a.neg == b.neg;
+        let mut diff = CodebookDiff {
+            from: self.id(),
+            to: other.id(),
+            version: (self.version != other.version).then_some((self.version, other.version)),
+            dimensionality: (self.dimensionality != other.dimensionality)
+                .then_some((self.dimensionality, other.dimensionality)),
+            basis_added: after.keys().filter(|id| !before.contains_key(id)).copied().collect(),
+            basis_removed: before.keys().filter(|id| !after.contains_key(id)).copied().collect(),
+            basis_changed: Vec::new(),
+            basis_reweighted: Vec::new(),
+            semantic_markers_changed: self.semantic_markers.len() != other.semantic_markers.len()
+                || self
+                    .semantic_markers
+                    .iter()
+                    .zip(&other.semantic_markers)
+                    .any(|(a, b)| !same_vector(a, b)),
+            salt_changed: self.salt != other.salt,
+            outliers_changed: self.outliers != other.outliers,
+        };
+        for (id, a) in &before {
+            let Some(b) = after.get(id) else { continue };
+            if !same_vector(&a.vector, &b.vector) || a.label != b.label {
+                diff.basis_changed.push(*id);
+            } else if a.weight != b.weight {
+                diff.basis_reweighted.push(*id);
+            }
+        }
+        diff
+    }
+
+    /// Group near-duplicate chunk vectors, such as an engram's codebook.
+    ///
+    /// Chunks are visited in ascending id order. Each joins the most similar
+    /// representative whose cosine with it is at least `threshold` (the
+    /// lowest id among equals), or else becomes the representative of a new
+    /// cluster, so the result is the same on every run. Representatives are
+    /// compared bitsliced, one word-parallel dot product each; chunks with no
+    /// nonzero trit have cosine 0 with everything.
+    ///
+    /// Representatives of the largest clusters make good
+    /// [`Codebook::train_basis`] samples: they stand for many chunks at once.
+    pub fn cluster(chunks: &HashMap<usize, SparseVec>, threshold: f64) -> ChunkClusters {
+        let mut ids: Vec<usize> = chunks.keys().copied().collect();
+        ids.sort_unstable();
+        let len = chunks
+            .values()
+            .flat_map(|v| v.pos.iter().chain(&v.neg))
+            .max()
+            .map_or(DIM, |&d| DIM.max(d + 1));
+
+        let mut clusters: Vec<ChunkCluster> = Vec::new();
+        let mut planes: Vec<(BitslicedTritVec, f64)> = Vec::new();
+        for id in ids {
+            let vector = &chunks[&id];
+            let norm = ((vector.pos.len() + vector.neg.len()) as f64).sqrt();
+            let sliced = BitslicedTritVec::from_sparse(vector, len);
+            let mut best: Option<(usize, f64)> = None;
+            for (i, (plane, plane_norm)) in planes.iter().enumerate() {
+                let sim = if norm > 0.0 && *plane_norm > 0.0 {
+                    sliced.dot_dispatch(plane) as f64 / (norm * plane_norm)
+                } else {
+                    0.0
+                };
+                if sim >= threshold && best.is_none_or(|(_, b)| sim > b) {
+                    best = Some((i, sim));
+                }
+            }
+            match best {
+                Some((i, sim)) => {
+                    let cluster = &mut clusters[i];
+                    cluster.members.push(id);
+                    cluster.min_similarity = cluster.min_similarity.min(sim);
+                }
+                None => {
+                    clusters.push(ChunkCluster {
+                        representative: id,
+                        members: vec![id],
+                        min_similarity: 1.0,
+                    });
+                    planes.push((sliced, norm));
+                }
+            }
+        }
+
+        clusters.sort_by(|a, b| {
+            b.members
+                .len()
+                .cmp(&a.members.len())
+                .then(a.representative.cmp(&b.representative))
+        });
+        ChunkClusters {
+            threshold,
+            chunks: chunks.len(),
+            clusters,
+        }
+    }
+}
+
+/// Layout version written by [`Codebook::new`] and accepted by
+/// [`Codebook::load`].
+pub const CODEBOOK_VERSION: u32 = 1;
+
+/// Differences between two codebooks, from [`Codebook::diff`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CodebookDiff {
+    /// [`Codebook::id`] of each side.
+    pub from: String,
+    pub to: String,
+    /// Old and new values, when they differ.
+    pub version: Option<(u32, u32)>,
+    pub dimensionality: Option<(usize, usize)>,
+    /// Basis ids only in the new codebook.
+    pub basis_added: Vec<u32>,
+    /// Basis ids only in the old codebook.
+    pub basis_removed: Vec<u32>,
+    /// Basis ids whose vector or label differ.
+    pub basis_changed: Vec<u32>,
+    /// Basis ids whose vector is unchanged but whose weight differs.
+    pub basis_reweighted: Vec<u32>,
+    pub semantic_markers_changed: bool,
+    pub salt_changed: bool,
+    pub outliers_changed: bool,
+}
+
+impl CodebookDiff {
+    /// Whether the codebooks have the same identity.
+    pub fn is_empty(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// Upper bound on assignment passes in [`Codebook::train_basis`].
+const MAX_BASIS_ITERATIONS: usize = 20;
+
+/// Most chunk vectors [`crate::EmbrFS::train_basis`] trains on; larger
+/// engrams are sampled evenly by chunk id.
+pub const MAX_BASIS_SAMPLES: usize = 2048;
+
+/// Outcome of [`Codebook::train_basis`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BasisTrainingReport {
+    /// Non-empty samples clustered.
+    pub samples: usize,
+    /// Basis vectors learned.
+    pub basis_size: usize,
+    /// Assignment passes run.
+    pub iterations: usize,
+    /// Whether assignments settled before the pass limit.
+    pub converged: bool,
+    /// Mean cosine between a sample and its nearest basis vector.
+    pub mean_similarity: f64,
+}
+
+/// `<engram>.basis`, where the CLI keeps a basis trained on an engram.
+pub fn default_basis_path<P: AsRef<Path>>(engram_path: P) -> PathBuf {
+    let mut s = engram_path.as_ref().as_os_str().to_os_string();
+    s.push(".basis");
+    PathBuf::from(s)
+}
+
+/// Chunks grouped by [`Codebook::cluster`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkClusters {
+    /// Cosine a chunk needed with a representative to join its cluster.
+    pub threshold: f64,
+    /// Chunks clustered.
+    pub chunks: usize,
+    /// Largest first; equal sizes by representative id.
+    pub clusters: Vec<ChunkCluster>,
+}
+
+/// One group of similar chunks.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkCluster {
+    /// First chunk of the cluster by id, which the others were compared to.
+    pub representative: usize,
+    /// Chunk ids in the cluster, ascending, the representative included.
+    pub members: Vec<usize>,
+    /// Lowest cosine of a member with the representative; 1 on its own.
+    pub min_similarity: f64,
+}
+
+impl ChunkCluster {
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+}
+
+impl ChunkClusters {
+    pub fn len(&self) -> usize {
+        self.clusters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clusters.is_empty()
+    }
+
+    /// Clusters of one chunk, which resemble no other.
+    pub fn singletons(&self) -> usize {
+        self.clusters.iter().filter(|c| c.size() == 1).count()
+    }
+
+    /// Chunks that aren't a representative: what near-dedup could drop.
+    pub fn redundant_chunks(&self) -> usize {
+        self.chunks - self.clusters.len()
+    }
+
+    /// Number of clusters of each size, by size.
+    pub fn size_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut sizes = BTreeMap::new();
+        for cluster in &self.clusters {
+            *sizes.entry(cluster.size()).or_default() += 1;
+        }
+        sizes
+    }
+
+    /// Representative ids, largest cluster first.
+    pub fn representatives(&self) -> impl Iterator<Item = usize> + '_ {
+        self.clusters.iter().map(|c| c.representative)
+    }
+}
+
+/// Index and cosine of the centroid closest to `sample`; ties go to the
+/// lowest index.
+fn nearest_centroid(centroids: &[SparseVec], sample: &SparseVec) -> (usize, f64) {
+    let mut best = (0, f64::NEG_INFINITY);
+    for (i, centroid) in centroids.iter().enumerate() {
+        let sim = sample.cosine(centroid);
+        if sim > best.1 {
+            best = (i, sim);
+        }
+    }
+    best
+}
+
+/// Ternary centroid of `members`: the sign of the per-dimension sum, kept on
+/// the dimensions with the largest sums (lowest index first among equals),
+/// as many as a member has on average.
+fn ternary_centroid(members: &[&SparseVec]) -> SparseVec {
+    let mut sums: HashMap<usize, i64> = HashMap::new();
+    let mut nnz = 0;
+    for member in members {
+        nnz += member.pos.len() + member.neg.len();
+        for &d in &member.pos {
+            *sums.entry(d).or_default() += 1;
+        }
+        for &d in &member.neg {
+            *sums.entry(d).or_default() -= 1;
+        }
+    }
+    let target = (nnz as f64 / members.len() as f64).round() as usize;
+    let mut dims: Vec<(usize, i64)> = sums.into_iter().filter(|&(_, s)| s != 0).collect();
+    dims.sort_unstable_by(|a, b| b.1.abs().cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+    dims.truncate(target);
+
+    let mut centroid = SparseVec::new();
+    for (d, sum) in dims {
+        if sum > 0 {
+            centroid.pos.push(d);
+        } else {
+            centroid.neg.push(d);
+        }
+    }
+    centroid.pos.sort_unstable();
+    centroid.neg.sort_unstable();
+    centroid
+}
+
+/// Bytes of input projected as one vector.
+const PROJECTION_CHUNK: usize = 64;
+/// Cosine above which a basis vector counts towards a projection.
+const BASIS_RELEVANCE: f64 = 0.3;
+/// Most basis vectors a projected chunk is expressed with.
+const MAX_BASIS_MATCHES: usize = 4;
+
+/// Basis vectors in bitsliced form, so projecting a chunk costs one
+/// word-parallel dot product per basis vector.
+struct BasisPlanes {
+    len: usize,
+    ids: Vec<u32>,
+    planes: Vec<BitslicedTritVec>,
+    norms: Vec<f64>,
+}
+
+impl BasisPlanes {
+    fn new(codebook: &Codebook) -> Self {
+        // Wide enough for chunk vectors too, so no overlap is cut off.
+        let len = codebook.dimensionality.max(DIM);
+        let basis = &codebook.basis_vectors;
+        BasisPlanes {
+            len,
+            ids: basis.iter().map(|b| b.id).collect(),
+            planes: basis.iter().map(|b| BitslicedTritVec::from_sparse(&b.vector, len)).collect(),
+            norms: basis.iter().map(|b| ((b.vector.pos.len() + b.vector.neg.len()) as f64).sqrt()).collect(),
+        }
+    }
+
+    /// Cosine of `vector` with each basis vector, in basis order; equal to
+    /// [`SparseVec::cosine`].
+    fn similarities(&self, vector: &SparseVec) -> Vec<(u32, f64)> {
+        if self.planes.is_empty() {
+            return Vec::new();
+        }
+        let norm = ((vector.pos.len() + vector.neg.len()) as f64).sqrt();
+        let sliced = BitslicedTritVec::from_sparse(vector, self.len);
+        let dots: Vec<i32> = self.planes.iter().map(|plane| sliced.dot_dispatch(plane)).collect();
+        self.similarities_from_dots(norm, &dots)
+    }
+
+    /// Cosines from precomputed dot products of a vector of norm `norm`
+    /// with each basis vector, in basis order.
+    fn similarities_from_dots(&self, norm: f64, dots: &[i32]) -> Vec<(u32, f64)> {
+        self.ids
+            .iter()
+            .zip(&self.norms)
+            .zip(dots)
+            .map(|((&id, &plane_norm), &dot)| {
+                let sim = if norm > 0.0 && plane_norm > 0.0 { dot as f64 / (norm * plane_norm) } else { 0.0 };
+                (id, sim)
+            })
+            .collect()
+    }
+}
+
+/// One input's share of a batch dot matrix: per chunk, its vector's norm
+/// and its dot product with each basis vector.
+struct BatchDots<'a> {
+    norms: &'a [f64],
+    dots: &'a [i32],
+}
+
+impl BatchDots<'_> {
+    fn row(&self, chunk_idx: usize, planes: &BasisPlanes) -> &[i32] {
+        let n = planes.planes.len();
+        &self.dots[chunk_idx * n..(chunk_idx + 1) * n]
+    }
+}
+
+/// A window of data examined by an [`OutlierDetector`].
+pub struct OutlierWindow<'a> {
+    /// Offset of the window in the projected data.
+    pub position: usize,
+    pub bytes: &'a [u8],
+    /// Shannon entropy of `bytes`, in bits per byte.
+    pub entropy: f64,
+    vector: std::cell::OnceCell<SparseVec>,
+}
+
+impl<'a> OutlierWindow<'a> {
+    pub fn new(position: usize, bytes: &'a [u8], entropy: f64) -> Self {
+        OutlierWindow {
+            position,
+            bytes,
+            entropy,
+            vector: std::cell::OnceCell::new(),
+        }
+    }
+
+    /// The window's vector ([`SparseVec::from_bytes`]), computed once on
+    /// first use and shared by the detectors.
+    pub fn vector(&self) -> &SparseVec {
+        self.vector.get_or_init(|| SparseVec::from_bytes(self.bytes))
+    }
+}
+
+/// Decides which windows of projected data are semantic outliers, kept
+/// verbatim rather than expressed through the basis.
+///
+/// [`Codebook::project`] runs the detectors of [`Codebook::outliers`];
+/// [`Codebook::project_with`] takes any others.
+pub trait OutlierDetector: Send + Sync {
+    /// Short name recorded in [`SemanticOutlier::detector`].
+    fn name(&self) -> &str;
+
+    /// Score of `window` if it is an outlier (higher = more unusual),
+    /// `None` otherwise.
+    fn detect(&self, codebook: &Codebook, window: &OutlierWindow<'_>) -> Option<f64>;
+}
+
+/// Flags windows whose entropy exceeds `threshold` bits per byte, such as
+/// compressed or encrypted data. A window of `n` bytes has at most
+/// `log2(n)` bits per byte, so the threshold must sit below that.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EntropyDetector {
+    pub threshold: f64,
+}
+
+impl OutlierDetector for EntropyDetector {
+    fn name(&self) -> &str {
+        "entropy"
+    }
+
+    fn detect(&self, _codebook: &Codebook, window: &OutlierWindow<'_>) -> Option<f64> {
+        (window.entropy > self.threshold).then_some(window.entropy)
+    }
+}
+
+/// Flags windows whose vector the basis leaves mostly unexplained: the
+/// score is one minus the squared cosines of the basis vectors a projection
+/// would use, and windows scoring above `threshold` are outliers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResidualDetector {
+    pub threshold: f64,
+}
+
+impl OutlierDetector for ResidualDetector {
+    fn name(&self) -> &str {
+        "residual"
+    }
+
+    fn detect(&self, codebook: &Codebook, window: &OutlierWindow<'_>) -> Option<f64> {
+        let mut similarities: Vec<f64> = codebook
+            .basis_vectors
+            .iter()
+            .map(|basis| window.vector().cosine(&basis.vector))
+            .filter(|&sim| sim > BASIS_RELEVANCE)
+            .collect();
+        similarities.sort_by(|a, b| b.total_cmp(a));
+        let explained: f64 = similarities.iter().take(MAX_BASIS_MATCHES).map(|s| s * s).sum();
+        let residual = (1.0 - explained).max(0.0);
+        (residual > self.threshold).then_some(residual)
+    }
+}
+
+/// Flags windows whose best cosine to any basis vector is below
+/// `min_similarity`; with an empty basis every window is an outlier. The
+/// score is one minus that cosine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BasisSimilarityDetector {
+    pub min_similarity: f64,
+}
+
+impl OutlierDetector for BasisSimilarityDetector {
+    fn name(&self) -> &str {
+        "similarity"
+    }
+
+    fn detect(&self, codebook: &Codebook, window: &OutlierWindow<'_>) -> Option<f64> {
+        let best = codebook
+            .basis_vectors
+            .iter()
+            .map(|basis| window.vector().cosine(&basis.vector))
+            .fold(0.0, f64::max);
+        (best < self.min_similarity).then_some(1.0 - best)
+    }
+}
+
+/// A built-in detector and its threshold, as stored in [`OutlierConfig`].
+///
+/// Parses from `entropy=BITS`, `residual=FRACTION` or `similarity=COSINE`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OutlierRule {
+    /// [`EntropyDetector`] with this threshold.
+    Entropy(f64),
+    /// [`ResidualDetector`] with this threshold.
+    Residual(f64),
+    /// [`BasisSimilarityDetector`] with this minimum similarity.
+    BasisSimilarity(f64),
+}
+
+impl OutlierRule {
+    pub fn detector(&self) -> Box<dyn OutlierDetector> {
+        match *self {
+            OutlierRule::Entropy(threshold) => Box::new(EntropyDetector { threshold }),
+            OutlierRule::Residual(threshold) => Box::new(ResidualDetector { threshold }),
+            OutlierRule::BasisSimilarity(min_similarity) => {
+                Box::new(BasisSimilarityDetector { min_similarity })
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for OutlierRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected DETECTOR=THRESHOLD, got {:?}", s))?;
+        let value: f64 = value
+            .parse()
+            .map_err(|_| format!("invalid threshold {:?}", value))?;
+        match name {
+            "entropy" => Ok(OutlierRule::Entropy(value)),
+            "residual" => Ok(OutlierRule::Residual(value)),
+            "similarity" => Ok(OutlierRule::BasisSimilarity(value)),
+            _ => Err(format!(
+                "unknown outlier detector {:?} (expected entropy, residual or similarity)",
+                name
+            )),
+        }
+    }
+}
+
+/// Which windows [`Codebook::project`] stores as semantic outliers.
+///
+/// Data is scanned in overlapping windows of `window` bytes; a window is an
+/// outlier if any rule flags it, and the first rule to do so is recorded.
+/// Without rules nothing is an outlier. The default flags 32-byte windows
+/// above 4.5 bits per byte, close to the 5-bit maximum for that size.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutlierConfig {
+    pub window: usize,
+    pub rules: Vec<OutlierRule>,
+}
+
+impl Default for OutlierConfig {
+    fn default() -> Self {
+        OutlierConfig {
+            window: 32,
+            rules: vec![OutlierRule::Entropy(4.5)],
+        }
+    }
+}
+
+impl OutlierConfig {
+    /// The detectors for `rules`, in order.
+    pub fn detectors(&self) -> Vec<Box<dyn OutlierDetector>> {
+        self.rules.iter().map(OutlierRule::detector).collect()
+    }
 }
 
 impl SparseVec {
@@ -644,6 +1476,210 @@ impl SparseVec {
     }
 }
 
+/// Default byte budget of a [`ChunkCache`].
+pub const DEFAULT_CHUNK_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Counters and occupancy of a [`ChunkCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunkCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    /// Bytes of cached chunk data.
+    pub bytes: usize,
+    pub max_bytes: usize,
+}
+
+/// LRU cache of decoded chunks, keyed by chunk id, under a byte budget.
+///
+/// Values are the final chunk bytes: decompressed, decrypted, VSA-decoded
+/// and corrected. A chunk id only ever decodes to one byte string within an
+/// engram, so one cache may serve extraction, verification and FUSE reads of
+/// the same engram at once; share it through an `Arc`. It must not be shared
+/// between different engrams.
+///
+/// Given [`AccessStats`] with [`ChunkCache::with_access_stats`], the cache
+/// evicts the least accessed of its [`EVICTION_WINDOW`] least recently used
+/// chunks instead of the least recently used one, so a scan through cold
+/// data doesn't push out chunks that are read all the time.
+///
+/// Hits, misses and evictions are also reported to [`crate::metrics`].
+pub struct ChunkCache {
+    state: Mutex<LruState>,
+    max_entries: usize,
+    max_bytes: usize,
+    access: Option<Arc<AccessStats>>,
+}
+
+#[derive(Default)]
+struct LruState {
+    /// Chunk id -> (bytes, tick of last use).
+    map: HashMap<u64, (Arc<[u8]>, u64)>,
+    /// Tick of last use -> chunk id, oldest first.
+    order: BTreeMap<u64, u64>,
+    tick: u64,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl ChunkCache {
+    /// A cache holding at most `max_bytes` of chunk data; 0 disables it.
+    pub fn new(max_bytes: usize) -> Self {
+        Self::with_limits(usize::MAX, max_bytes)
+    }
+
+    /// A cache bounded by both entry count and bytes; either at 0 disables it.
+    pub fn with_limits(max_entries: usize, max_bytes: usize) -> Self {
+        ChunkCache {
+            state: Mutex::new(LruState::default()),
+            max_entries,
+            max_bytes,
+            access: None,
+        }
+    }
+
+    /// Choose which chunk to evict by the accesses `access` recorded; see
+    /// the [type docs](Self). Keys must be the chunk ids `access` counts.
+    pub fn with_access_stats(mut self, access: Arc<AccessStats>) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruState> {
+        // The state is consistent between statements, so a panic while
+        // holding the lock leaves nothing half-updated.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Cached bytes of chunk `id`, marking it most recently used.
+    pub fn get(&self, id: u64) -> Option<Arc<[u8]>> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        state.tick += 1;
+        match state.map.get_mut(&id) {
+            Some((data, last_use)) => {
+                state.order.remove(last_use);
+                *last_use = state.tick;
+                state.order.insert(state.tick, id);
+                state.hits += 1;
+                metrics().inc_chunk_cache_hit();
+                Some(data.clone())
+            }
+            None => {
+                state.misses += 1;
+                metrics().inc_chunk_cache_miss();
+                None
+            }
+        }
+    }
+
+    /// Cache `data` as chunk `id`, evicting least recently used chunks to
+    /// stay within the limits. Chunks larger than the whole budget are not
+    /// cached.
+    pub fn insert(&self, id: u64, data: Arc<[u8]>) {
+        if self.max_entries == 0 || data.len() > self.max_bytes {
+            return;
+        }
+        let mut state = self.lock();
+        let state = &mut *state;
+        state.tick += 1;
+        if let Some((old, last_use)) = state.map.remove(&id) {
+            state.order.remove(&last_use);
+            state.bytes -= old.len();
+        }
+        state.bytes += data.len();
+        state.map.insert(id, (data, state.tick));
+        state.order.insert(state.tick, id);
+
+        while state.map.len() > self.max_entries || state.bytes > self.max_bytes {
+            let Some(last_use) = self.victim(&state.order, id) else {
+                break;
+            };
+            let victim = state.order.remove(&last_use).expect("victim is in the order");
+            if let Some((old, _)) = state.map.remove(&victim) {
+                state.bytes -= old.len();
+            }
+            state.evictions += 1;
+            metrics().inc_chunk_cache_eviction();
+        }
+    }
+
+    /// Last use of the chunk to evict next: the oldest, or with access
+    /// stats the least accessed of the oldest few. `inserted` is only
+    /// chosen when it is all that is left.
+    fn victim(&self, order: &BTreeMap<u64, u64>, inserted: u64) -> Option<u64> {
+        let Some(access) = &self.access else {
+            return order.keys().next().copied();
+        };
+        order
+            .iter()
+            .filter(|&(_, &id)| id != inserted)
+            .take(EVICTION_WINDOW)
+            .min_by_key(|&(&last_use, &id)| (access.heat(id), last_use))
+            .or_else(|| order.iter().next())
+            .map(|(&last_use, _)| last_use)
+    }
+
+    /// Cached bytes of chunk `id`, or the result of `decode` (cached if
+    /// `Some`). The lock is not held while decoding.
+    pub fn get_or_insert_with<F>(&self, id: u64, decode: F) -> Option<Arc<[u8]>>
+    where
+        F: FnOnce() -> Option<Vec<u8>>,
+    {
+        if let Some(data) = self.get(id) {
+            return Some(data);
+        }
+        let data: Arc<[u8]> = decode()?.into();
+        self.insert(id, data.clone());
+        Some(data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every cached chunk; counters are kept.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.map.clear();
+        state.order.clear();
+        state.bytes = 0;
+    }
+
+    pub fn stats(&self) -> ChunkCacheStats {
+        let state = self.lock();
+        ChunkCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            evictions: state.evictions,
+            entries: state.map.len(),
+            bytes: state.bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    /// Estimated heap bytes, including bookkeeping.
+    pub(crate) fn memory_bytes(&self) -> u64 {
+        let state = self.lock();
+        // B-tree nodes are at least half full.
+        let order_bytes = 2 * state.order.len() * std::mem::size_of::<(u64, u64)>();
+        memory::map_bytes(&state.map) + order_bytes as u64 + state.bytes as u64
+    }
+}
+
+impl Default for ChunkCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_CACHE_BYTES)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -691,6 +1727,50 @@ mod tests {
         assert!(!projection.coefficients.is_empty() || !projection.residual.is_empty());
     }
 
+    #[test]
+    fn test_chunk_cache_with_access_stats_keeps_accessed_chunks() {
+        use crate::access_stats::AccessKind;
+        use std::time::Duration;
+
+        let access = Arc::new(AccessStats::new());
+        let cache = ChunkCache::new(8).with_access_stats(Arc::clone(&access));
+        access.record_chunks(&[1], AccessKind::Read, Duration::ZERO);
+        cache.insert(1, vec![1; 4].into());
+        cache.insert(2, vec![2; 4].into());
+        cache.insert(3, vec![3; 4].into());
+        assert!(cache.get(1).is_some(), "1 is older but was read");
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(3).is_some());
+    }
+
+    #[test]
+    fn test_chunk_cache_evicts_least_recently_used() {
+        let cache = ChunkCache::new(10);
+        cache.insert(1, vec![1; 4].into());
+        cache.insert(2, vec![2; 4].into());
+        assert!(cache.get(1).is_some());
+        cache.insert(3, vec![3; 4].into());
+        assert!(cache.get(2).is_none(), "2 was least recently used");
+        assert_eq!(&cache.get(1).unwrap()[..], &[1; 4]);
+        assert!(cache.get(3).is_some());
+
+        cache.insert(4, vec![4; 11].into());
+        assert!(cache.get(4).is_none(), "larger than the budget");
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, 8, 1));
+        assert_eq!((stats.hits, stats.misses), (3, 2));
+
+        let mut decodes = 0;
+        for _ in 0..2 {
+            cache.get_or_insert_with(5, || {
+                decodes += 1;
+                Some(vec![5; 2])
+            });
+        }
+        assert_eq!(decodes, 1);
+        assert!(ChunkCache::new(0).get_or_insert_with(6, || Some(vec![6])).is_some());
+    }
+
     #[test]
     fn test_parity_computation() {
         let word = BalancedTernaryWord::new(12345, WordMetadata::Data).unwrap();
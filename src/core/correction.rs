@@ -57,6 +57,24 @@ pub enum CorrectionType {
     BlockReplace { offset: u64, original: Vec<u8> },
     /// Full data (for high-entropy regions)
     Verbatim(Vec<u8>),
+    /// Binary delta against the original bytes of chunk `base`, chunk
+    /// `base_len` bytes of the file at `base_path`. Only the engram holding
+    /// the base can apply it; see `Engram::apply_correction`.
+    Delta {
+        base: u64,
+        base_path: String,
+        base_len: u64,
+        ops: Vec<DeltaOp>,
+    },
+}
+
+/// One instruction of a [`CorrectionType::Delta`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeltaOp {
+    /// Copy `len` bytes of the base, starting at `offset`.
+    Copy { offset: u64, len: u64 },
+    /// Bytes the base does not have.
+    Insert(Vec<u8>),
 }
 
 /// A correction record for a data chunk
@@ -141,6 +159,10 @@ impl ChunkCorrection {
             }
             
             CorrectionType::Verbatim(data) => data.clone(),
+
+            // Needs the base chunk, which only the engram has: the result
+            // fails verification unless the approximation is already right.
+            CorrectionType::Delta { .. } => approximation.to_vec(),
         }
     }
 
@@ -149,6 +171,11 @@ impl ChunkCorrection {
         compute_hash(result) == self.hash
     }
 
+    /// Whether `data` matches both the recorded hash and parity trit.
+    pub fn verify_with_parity(&self, data: &[u8]) -> bool {
+        compute_data_parity(data) == self.parity && self.verify(data)
+    }
+
     /// Storage size of this correction
     pub fn storage_size(&self) -> usize {
         match &self.correction {
@@ -157,10 +184,108 @@ impl ChunkCorrection {
             CorrectionType::TritFlips(flips) => flips.len() * 10, // pos(8) + 2 trits(2)
             CorrectionType::BlockReplace { original, .. } => 8 + original.len(),
             CorrectionType::Verbatim(data) => data.len(),
+            CorrectionType::Delta { base_path, ops, .. } => 16 + base_path.len() + delta_size(ops),
+        }
+    }
+
+    /// A correction storing `original` as a delta against `base_bytes`, the
+    /// original bytes of chunk `base` of the file at `base_path`.
+    pub fn delta(chunk_id: u64, original: &[u8], base: u64, base_path: &str, base_bytes: &[u8]) -> Self {
+        ChunkCorrection {
+            chunk_id,
+            correction: CorrectionType::Delta {
+                base,
+                base_path: base_path.to_string(),
+                base_len: base_bytes.len() as u64,
+                ops: encode_delta(base_bytes, original),
+            },
+            hash: compute_hash(original),
+            parity: compute_data_parity(original),
         }
     }
 }
 
+/// Shortest run of bytes matched against the base by [`encode_delta`].
+const DELTA_MATCH: usize = 16;
+
+/// Encode `target` as copies from `base` and inserted bytes.
+///
+/// Every `DELTA_MATCH`-byte window of `base` is indexed; `target` is scanned
+/// for windows found there and each match is extended both ways, as in
+/// xdelta. Chunks are small, so the index holds every offset.
+pub fn encode_delta(base: &[u8], target: &[u8]) -> Vec<DeltaOp> {
+    let mut index: HashMap<&[u8], usize> = HashMap::new();
+    if base.len() >= DELTA_MATCH {
+        for offset in (0..=base.len() - DELTA_MATCH).rev() {
+            index.insert(&base[offset..offset + DELTA_MATCH], offset);
+        }
+    }
+
+    let mut ops = Vec::new();
+    let mut literal: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < target.len() {
+        let found = target
+            .get(i..i + DELTA_MATCH)
+            .and_then(|window| index.get(window).copied());
+        let Some(mut offset) = found else {
+            literal.push(target[i]);
+            i += 1;
+            continue;
+        };
+        let mut start = i;
+        while offset > 0 && !literal.is_empty() && base[offset - 1] == target[start - 1] {
+            offset -= 1;
+            start -= 1;
+            literal.pop();
+        }
+        let mut end = i + DELTA_MATCH;
+        while end < target.len() && offset + (end - start) < base.len() && base[offset + (end - start)] == target[end] {
+            end += 1;
+        }
+        if !literal.is_empty() {
+            ops.push(DeltaOp::Insert(std::mem::take(&mut literal)));
+        }
+        ops.push(DeltaOp::Copy {
+            offset: offset as u64,
+            len: (end - start) as u64,
+        });
+        i = end;
+    }
+    if !literal.is_empty() {
+        ops.push(DeltaOp::Insert(literal));
+    }
+    ops
+}
+
+/// Rebuild the target of [`encode_delta`] from `base`. `None` if a copy
+/// reaches past the end of `base`.
+pub fn apply_delta(base: &[u8], ops: &[DeltaOp]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(base.len());
+    for op in ops {
+        match op {
+            DeltaOp::Copy { offset, len } => {
+                let start = usize::try_from(*offset).ok()?;
+                let end = start.checked_add(usize::try_from(*len).ok()?)?;
+                out.extend_from_slice(base.get(start..end)?);
+            }
+            DeltaOp::Insert(bytes) => out.extend_from_slice(bytes),
+        }
+    }
+    Some(out)
+}
+
+/// Storage size of delta instructions: 16 bytes per copy, 8 plus the bytes
+/// per insert.
+fn delta_size(ops: &[DeltaOp]) -> usize {
+    ops.iter()
+        .map(|op| match op {
+            DeltaOp::Copy { .. } => 16,
+            DeltaOp::Insert(bytes) => 8 + bytes.len(),
+        })
+        .sum()
+}
+
 /// Compute verification hash (first 8 bytes of SHA256)
 fn compute_hash(data: &[u8]) -> [u8; 8] {
     let mut hasher = Sha256::new();
@@ -244,6 +369,7 @@ fn compute_correction(original: &[u8], approximation: &[u8]) -> CorrectionType {
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CorrectionStore {
     /// Corrections indexed by chunk ID
+    #[serde(serialize_with = "crate::reproducible::sorted_map")]
     corrections: HashMap<u64, ChunkCorrection>,
     
     /// Total storage used by corrections
@@ -268,17 +394,93 @@ impl CorrectionStore {
     /// Add a correction for a chunk
     pub fn add(&mut self, chunk_id: u64, original: &[u8], approximation: &[u8]) {
         let correction = ChunkCorrection::new(chunk_id, original, approximation);
-        
-        self.total_original_bytes += original.len() as u64;
-        
+        self.insert(correction, original.len());
+    }
+
+    /// Add a correction for a chunk, stored as a delta against `base_bytes`,
+    /// the original bytes of chunk `base` of the file at `base_path`, when
+    /// that is smaller than the correction of `approximation`.
+    pub fn add_with_base(
+        &mut self,
+        chunk_id: u64,
+        original: &[u8],
+        approximation: &[u8],
+        base: u64,
+        base_path: &str,
+        base_bytes: &[u8],
+    ) {
+        let plain = ChunkCorrection::new(chunk_id, original, approximation);
+        let correction = if plain.needs_correction() {
+            let delta = ChunkCorrection::delta(chunk_id, original, base, base_path, base_bytes);
+            if delta.storage_size() < plain.storage_size() {
+                delta
+            } else {
+                plain
+            }
+        } else {
+            plain
+        };
+        self.insert(correction, original.len());
+    }
+
+    /// Add `correction` for a chunk of `original_len` bytes to the records
+    /// and running totals.
+    pub(crate) fn insert(&mut self, correction: ChunkCorrection, original_len: usize) {
+        self.total_original_bytes += original_len as u64;
+
         if correction.needs_correction() {
             self.total_correction_bytes += correction.storage_size() as u64;
             self.corrected_chunks += 1;
         } else {
             self.perfect_chunks += 1;
         }
-        
-        self.corrections.insert(chunk_id, correction);
+
+        self.corrections.insert(correction.chunk_id, correction);
+    }
+
+    /// Remove the correction for a chunk of `original_len` bytes, undoing its
+    /// contribution to the running totals.
+    pub(crate) fn remove(&mut self, chunk_id: u64, original_len: usize) -> Option<ChunkCorrection> {
+        let correction = self.corrections.remove(&chunk_id)?;
+        self.total_original_bytes = self.total_original_bytes.saturating_sub(original_len as u64);
+        if correction.needs_correction() {
+            self.total_correction_bytes = self
+                .total_correction_bytes
+                .saturating_sub(correction.storage_size() as u64);
+            self.corrected_chunks = self.corrected_chunks.saturating_sub(1);
+        } else {
+            self.perfect_chunks = self.perfect_chunks.saturating_sub(1);
+        }
+        Some(correction)
+    }
+
+    /// Put `correction` in place of the chunk's record, or drop the record
+    /// if `None`, leaving the totals as they are. For records copied from a
+    /// replica of the same engram, whose totals already count them.
+    pub(crate) fn replace(&mut self, chunk_id: u64, correction: Option<ChunkCorrection>) -> Option<ChunkCorrection> {
+        match correction {
+            Some(correction) => self.corrections.insert(chunk_id, correction),
+            None => self.corrections.remove(&chunk_id),
+        }
+    }
+
+    /// Number of chunk records held, corrected or perfect.
+    pub(crate) fn len(&self) -> usize {
+        self.corrections.len()
+    }
+
+    /// Estimated heap bytes held by the store.
+    pub(crate) fn memory_bytes(&self) -> u64 {
+        let records: u64 = self.corrections.values().map(record_bytes).sum();
+        crate::memory::map_bytes(&self.corrections) + records
+    }
+
+    /// Estimated heap bytes the correction for `chunk_id` adds to the store,
+    /// counting its share of the table.
+    pub(crate) fn chunk_memory_bytes(&self, chunk_id: u64) -> u64 {
+        self.corrections.get(&chunk_id).map_or(0, |c| {
+            record_bytes(c) + crate::memory::slot_bytes::<u64, ChunkCorrection>()
+        })
     }
 
     /// Get correction for a chunk
@@ -329,6 +531,63 @@ impl CorrectionStore {
     pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
         bincode::deserialize(bytes).ok()
     }
+
+    /// Iterate over all stored corrections.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u64, &ChunkCorrection)> {
+        self.corrections.iter().map(|(&id, c)| (id, c))
+    }
+
+    /// Running totals, stored separately from per-chunk records by the append log.
+    pub(crate) fn totals(&self) -> CorrectionTotals {
+        CorrectionTotals {
+            total_correction_bytes: self.total_correction_bytes,
+            total_original_bytes: self.total_original_bytes,
+            perfect_chunks: self.perfect_chunks,
+            corrected_chunks: self.corrected_chunks,
+        }
+    }
+
+    /// Reassemble a store from individually persisted corrections.
+    pub(crate) fn from_parts(corrections: HashMap<u64, ChunkCorrection>, totals: CorrectionTotals) -> Self {
+        CorrectionStore {
+            corrections,
+            total_correction_bytes: totals.total_correction_bytes,
+            total_original_bytes: totals.total_original_bytes,
+            perfect_chunks: totals.perfect_chunks,
+            corrected_chunks: totals.corrected_chunks,
+        }
+    }
+}
+
+/// Heap bytes owned by one correction record.
+fn record_bytes(c: &ChunkCorrection) -> u64 {
+    match &c.correction {
+        CorrectionType::None => 0,
+        CorrectionType::BitFlips(flips) => crate::memory::vec_bytes(flips),
+        CorrectionType::TritFlips(flips) => crate::memory::vec_bytes(flips),
+        CorrectionType::BlockReplace { original, .. } => crate::memory::vec_bytes(original),
+        CorrectionType::Verbatim(data) => crate::memory::vec_bytes(data),
+        CorrectionType::Delta { base_path, ops, .. } => {
+            base_path.capacity() as u64
+                + crate::memory::vec_bytes(ops)
+                + ops
+                    .iter()
+                    .map(|op| match op {
+                        DeltaOp::Insert(bytes) => crate::memory::vec_bytes(bytes),
+                        DeltaOp::Copy { .. } => 0,
+                    })
+                    .sum::<u64>()
+        }
+    }
+}
+
+/// Aggregate counters of a [`CorrectionStore`], without the corrections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CorrectionTotals {
+    pub(crate) total_correction_bytes: u64,
+    pub(crate) total_original_bytes: u64,
+    pub(crate) perfect_chunks: u64,
+    pub(crate) corrected_chunks: u64,
 }
 
 /// Statistics about corrections
@@ -515,6 +774,29 @@ mod tests {
         assert_eq!(result.verified, 3);
     }
 
+    #[test]
+    fn test_delta_round_trip() {
+        let base: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+        let mut target = base.clone();
+        target.splice(1000..1000, b"inserted".iter().copied());
+        target.drain(3000..3100);
+        target[2000] ^= 0xFF;
+
+        let ops = encode_delta(&base, &target);
+        assert_eq!(apply_delta(&base, &ops).unwrap(), target);
+        assert!(delta_size(&ops) < 200, "{ops:?}");
+
+        // Unrelated bytes are inserted whole; copies past the base fail.
+        assert_eq!(encode_delta(b"", b"fresh"), vec![DeltaOp::Insert(b"fresh".to_vec())]);
+        assert!(apply_delta(b"short", &[DeltaOp::Copy { offset: 3, len: 4 }]).is_none());
+
+        let mut store = CorrectionStore::new();
+        store.add_with_base(7, &target, &[0; 4096], 3, "v1/blob.bin", &base);
+        let record = store.get(7).unwrap();
+        assert!(matches!(record.correction, CorrectionType::Delta { base: 3, .. }));
+        assert_eq!(store.stats().correction_bytes, record.storage_size() as u64);
+    }
+
     #[test]
     fn test_hash_stability() {
         // Ensure hash function is deterministic
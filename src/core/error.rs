@@ -0,0 +1,204 @@
+//! Crate-wide error type.
+//!
+//! [`EmbrError`] names the failures callers usually branch on: corrupt or
+//! malformed envelopes, engrams that fail validation on load, files that no
+//! longer match their manifest, missing chunks, dimension mismatches, ingest
+//! quotas, content rejected by an ingest filter, key or signature problems,
+//! writes to sealed engrams and manifest paths that would extract outside
+//! the output directory. Anything else is carried as
+//! [`EmbrError::Io`].
+//!
+//! The engram API ([`crate::EmbrFS`]) returns [`Result`]. Layers that plug
+//! into `std::io` (envelope readers and writers, append logs, vector stores)
+//! keep returning `io::Error`, with the `EmbrError` stored inside it.
+//! Converting either way is lossless: `io::Error::from(err)` wraps it with a
+//! matching [`io::ErrorKind`], and `EmbrError::from(io_err)` recovers the
+//! typed variant again.
+
+use crate::embrfs::{ChecksumMismatch, EngramCorruption, QuotaExceeded, SubEngramCorruption};
+use crate::envelope::EnvelopeCorruption;
+use crate::ingest_filter::Finding;
+use crate::job::JobProgress;
+use crate::safe_path::UnsafePath;
+use std::io;
+
+/// `Result` with [`EmbrError`] as the default error.
+pub type Result<T, E = EmbrError> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum EmbrError {
+    /// A checksum recorded in an envelope does not match the stored bytes.
+    #[error(transparent)]
+    EnvelopeCorruption(EnvelopeCorruption),
+
+    /// An envelope or container is malformed, truncated or of the wrong kind.
+    #[error("{0}")]
+    InvalidEnvelope(String),
+
+    /// A decoded engram is malformed, over its [`crate::LoadLimits`] or
+    /// breaks a vector invariant.
+    #[error(transparent)]
+    CorruptEngram(EngramCorruption),
+
+    /// A stored sub-engram is missing, malformed or does not match the
+    /// digest its hierarchical manifest records.
+    #[error(transparent)]
+    CorruptSubEngram(SubEngramCorruption),
+
+    /// Reconstructed files do not match the checksums in their manifest.
+    #[error("{} file(s) failed checksum verification: {}", .0.len(), join(.0))]
+    ManifestMismatch(Vec<ChecksumMismatch>),
+
+    /// A chunk referenced by the manifest is absent or fails its correction
+    /// check.
+    #[error("{path}: chunk {chunk_id} cannot be reconstructed")]
+    MissingChunk { path: String, chunk_id: usize },
+
+    /// Vectors do not have the dimension a store or index expects.
+    #[error("{context}: expected dimension {expected}, found {found}")]
+    DimensionMismatch {
+        context: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// Ingest would exceed an [`crate::IngestLimits`] bound.
+    #[error(transparent)]
+    Quota(QuotaExceeded),
+
+    /// An [`crate::ingest_filter`] rejected a file's content.
+    #[error("rejected by ingest filter: {0}")]
+    Rejected(Finding),
+
+    /// The key needed to decrypt is not available.
+    #[error("{0}")]
+    MissingKey(String),
+
+    /// Decryption, authentication or signature verification failed.
+    #[error("{0}")]
+    Crypto(String),
+
+    /// A write would change an engram sealed with [`crate::seal`].
+    #[error("{0}")]
+    Immutable(String),
+
+    /// The operation's [`crate::JobControl`] was cancelled; the progress
+    /// says what it finished first.
+    #[error("{0}")]
+    Cancelled(JobProgress),
+
+    /// A manifest path would be extracted outside the output directory;
+    /// see [`crate::safe_path`].
+    #[error(transparent)]
+    UnsafePath(UnsafePath),
+
+    #[error(transparent)]
+    Io(io::Error),
+}
+
+fn join(mismatches: &[ChecksumMismatch]) -> String {
+    mismatches
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl EmbrError {
+    /// The `io::ErrorKind` this error maps to when converted to `io::Error`.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            EmbrError::EnvelopeCorruption(_)
+            | EmbrError::InvalidEnvelope(_)
+            | EmbrError::CorruptEngram(_)
+            | EmbrError::CorruptSubEngram(_)
+            | EmbrError::ManifestMismatch(_)
+            | EmbrError::MissingChunk { .. }
+            | EmbrError::Crypto(_)
+            | EmbrError::UnsafePath(_) => io::ErrorKind::InvalidData,
+            EmbrError::DimensionMismatch { .. } => io::ErrorKind::InvalidInput,
+            EmbrError::MissingKey(_) => io::ErrorKind::PermissionDenied,
+            EmbrError::Immutable(_) => io::ErrorKind::ReadOnlyFilesystem,
+            EmbrError::Cancelled(_) => io::ErrorKind::Interrupted,
+            EmbrError::Quota(_) | EmbrError::Rejected(_) => io::ErrorKind::Other,
+            EmbrError::Io(e) => e.kind(),
+        }
+    }
+
+    /// The typed error inside `err`, if it carries one.
+    pub fn find(err: &io::Error) -> Option<&EmbrError> {
+        err.get_ref().and_then(|e| e.downcast_ref::<EmbrError>())
+    }
+}
+
+impl From<EmbrError> for io::Error {
+    fn from(err: EmbrError) -> Self {
+        match err {
+            EmbrError::Io(e) => e,
+            other => io::Error::new(other.kind(), other),
+        }
+    }
+}
+
+impl From<io::Error> for EmbrError {
+    fn from(err: io::Error) -> Self {
+        let Some(inner) = err.get_ref() else {
+            return EmbrError::Io(err);
+        };
+        if inner.is::<EmbrError>() {
+            let inner = err.into_inner().expect("checked above");
+            return *inner.downcast::<EmbrError>().expect("checked above");
+        }
+        // Envelope readers put their corruption reports in io::Error as is.
+        if let Some(corruption) = inner.downcast_ref::<EnvelopeCorruption>() {
+            return EmbrError::EnvelopeCorruption(corruption.clone());
+        }
+        if let Some(quota) = inner.downcast_ref::<QuotaExceeded>() {
+            return EmbrError::Quota(quota.clone());
+        }
+        EmbrError::Io(err)
+    }
+}
+
+impl From<EnvelopeCorruption> for EmbrError {
+    fn from(err: EnvelopeCorruption) -> Self {
+        EmbrError::EnvelopeCorruption(err)
+    }
+}
+
+impl From<EngramCorruption> for EmbrError {
+    fn from(err: EngramCorruption) -> Self {
+        EmbrError::CorruptEngram(err)
+    }
+}
+
+impl From<SubEngramCorruption> for EmbrError {
+    fn from(err: SubEngramCorruption) -> Self {
+        EmbrError::CorruptSubEngram(err)
+    }
+}
+
+impl From<QuotaExceeded> for EmbrError {
+    fn from(err: QuotaExceeded) -> Self {
+        EmbrError::Quota(err)
+    }
+}
+
+impl From<UnsafePath> for EmbrError {
+    fn from(err: UnsafePath) -> Self {
+        EmbrError::UnsafePath(err)
+    }
+}
+
+impl From<serde_json::Error> for EmbrError {
+    fn from(err: serde_json::Error) -> Self {
+        EmbrError::from(io::Error::from(err))
+    }
+}
+
+impl From<walkdir::Error> for EmbrError {
+    fn from(err: walkdir::Error) -> Self {
+        EmbrError::from(io::Error::from(err))
+    }
+}
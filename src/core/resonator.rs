@@ -5,6 +5,7 @@
 //! - Factorization of compound representations
 //! - Noise reduction through codebook projection
 
+use crate::logging;
 use crate::vsa::{SparseVec, ReversibleVSAConfig};
 use serde::{Deserialize, Serialize};
 
@@ -210,9 +211,10 @@ impl Resonator {
 
             final_delta = max_delta;
 
-            // Log progress if debug enabled
-            #[cfg(debug_assertions)]
-            println!("Iteration {}: delta = {:.6}", iterations, final_delta);
+            logging::debug(
+                "resonator",
+                format_args!("Iteration {}: delta = {:.6}", iterations, final_delta),
+            );
 
             // Check convergence - either max delta below threshold or all factors stable
             if final_delta < self.convergence_threshold || all_stable {
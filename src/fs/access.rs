@@ -0,0 +1,248 @@
+//! Per-namespace read and write grants for engrams shared between teams.
+//!
+//! A namespace is the first component of a logical path, the prefix
+//! [`EmbrFS::ingest_root`](crate::EmbrFS::ingest_root) puts in front of each
+//! root's files: `team-a/src/lib.rs` lies in `team-a`. Files at the top level
+//! of a manifest belong to no namespace and are only covered by `*`.
+//!
+//! An [`AccessPolicy`] names principals, each with the bearer tokens and
+//! local uids that identify it and the namespaces it may read and write. The
+//! gRPC and HTTP servers take the token from an `Authorization: Bearer`
+//! header; a FUSE mount maps the uid of the calling process. Callers without
+//! a token or mapped uid get the `[anonymous]` grants, or nothing if there
+//! are none.
+//!
+//! Policies are TOML files. Tokens are stored as the hex blake3 digest of the
+//! token, never in the clear:
+//!
+//! ```toml
+//! [anonymous]
+//! read = ["public"]
+//!
+//! [[principal]]
+//! name = "team-a"
+//! tokens = ["<blake3 hex of the token>"]
+//! uids = [1001]
+//! read = ["team-a", "shared"]
+//! write = ["team-a"]
+//!
+//! [[principal]]
+//! name = "ops"
+//! tokens = ["<blake3 hex of the token>"]
+//! write = ["*"]
+//! ```
+//!
+//! Write implies read. A `*` write grant also admits the gRPC engram
+//! management calls (create, load, save, drop).
+
+use crate::embrfs::Manifest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+/// Grant name covering every namespace and top-level file.
+pub const ALL_NAMESPACES: &str = "*";
+
+/// What a caller wants to do with a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Namespaces a caller may read and write.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grants {
+    #[serde(default)]
+    pub read: Vec<String>,
+    #[serde(default)]
+    pub write: Vec<String>,
+}
+
+impl Grants {
+    /// Read and write access to everything; what callers get when no policy
+    /// is configured.
+    pub fn all() -> Self {
+        Self {
+            read: vec![ALL_NAMESPACES.to_string()],
+            write: vec![ALL_NAMESPACES.to_string()],
+        }
+    }
+
+    /// Whether `access` to the logical path `path` is granted.
+    pub fn allows(&self, access: Access, path: &str) -> bool {
+        let namespace = namespace_of(path);
+        let covers = |list: &[String]| {
+            list.iter()
+                .any(|g| g == ALL_NAMESPACES || (!namespace.is_empty() && g == namespace))
+        };
+        covers(&self.write) || (access == Access::Read && covers(&self.read))
+    }
+
+    /// Whether every path may be written.
+    pub fn is_admin(&self) -> bool {
+        self.write.iter().any(|g| g == ALL_NAMESPACES)
+    }
+
+    /// `manifest` cut down to the files these grants may read.
+    pub fn readable(&self, manifest: &Manifest) -> Manifest {
+        let mut inodes = manifest.inodes.clone();
+        // A namespace's own directory is named without the trailing slash.
+        inodes.retain(|path| self.allows(Access::Read, path) || self.allows(Access::Read, &format!("{}/", path)));
+        Manifest {
+            files: manifest
+                .files
+                .iter()
+                .filter(|f| self.allows(Access::Read, &f.path))
+                .cloned()
+                .collect(),
+            total_chunks: manifest.total_chunks,
+            namespaces: manifest
+                .namespaces
+                .iter()
+                .filter(|(name, _)| self.allows(Access::Read, &format!("{}/", name)))
+                .map(|(name, root)| (name.clone(), root.clone()))
+                .collect(),
+            retention: manifest.retention.clone(),
+            encoding: manifest.encoding.clone(),
+            inodes,
+            path_normalization: manifest.path_normalization,
+            provenance: manifest.provenance.clone(),
+            namespace_keys: manifest.namespace_keys.clone(),
+        }
+    }
+}
+
+/// A caller named in an [`AccessPolicy`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Principal {
+    pub name: String,
+    /// Hex blake3 digests of the bearer tokens identifying this principal.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// Local uids mapped to this principal on a FUSE mount.
+    #[serde(default)]
+    pub uids: Vec<u32>,
+    #[serde(flatten)]
+    pub grants: Grants,
+}
+
+/// Who may read and write which namespaces; see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    /// Grants for callers that present no token or an unmapped uid.
+    #[serde(default)]
+    pub anonymous: Option<Grants>,
+    #[serde(default, rename = "principal")]
+    pub principals: Vec<Principal>,
+}
+
+impl AccessPolicy {
+    /// Load and validate a TOML policy file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::from_toml(&text)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse and validate a TOML policy.
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        let policy: Self = toml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Check that tokens are well-formed digests, that no token or uid names
+    /// two principals, and that grants name namespaces.
+    pub fn validate(&self) -> io::Result<()> {
+        let mut tokens = HashSet::new();
+        let mut uids = HashSet::new();
+        let grants = self.principals.iter().map(|p| (p.name.as_str(), &p.grants));
+        for (name, grants) in grants.chain(self.anonymous.iter().map(|g| ("[anonymous]", g))) {
+            for grant in grants.read.iter().chain(&grants.write) {
+                if grant.is_empty() || grant.contains('/') || grant == "." || grant == ".." {
+                    return Err(invalid(format!("{}: {:?} is not a namespace", name, grant)));
+                }
+            }
+        }
+        for p in &self.principals {
+            for token in &p.tokens {
+                if token.len() != 64 || !token.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(invalid(format!(
+                        "{}: tokens must be 64-digit blake3 hex digests",
+                        p.name
+                    )));
+                }
+                if !tokens.insert(token.to_ascii_lowercase()) {
+                    return Err(invalid(format!(
+                        "{}: token is already used by another principal",
+                        p.name
+                    )));
+                }
+            }
+            for &uid in &p.uids {
+                if !uids.insert(uid) {
+                    return Err(invalid(format!(
+                        "{}: uid {} is already mapped",
+                        p.name, uid
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Grants for a caller presenting `token`, or the anonymous grants
+    /// without one. `None` means the caller is not admitted: the token is
+    /// unknown, or there is no token and no anonymous access.
+    pub fn for_token(&self, token: Option<&str>) -> Option<&Grants> {
+        let Some(token) = token else {
+            return self.anonymous.as_ref();
+        };
+        let digest = token_digest(token);
+        self.principals
+            .iter()
+            .find(|p| p.tokens.iter().any(|t| t.eq_ignore_ascii_case(&digest)))
+            .map(|p| &p.grants)
+    }
+
+    /// Grants for local user `uid`, falling back to the anonymous grants.
+    pub fn for_uid(&self, uid: u32) -> Option<&Grants> {
+        self.principals
+            .iter()
+            .find(|p| p.uids.contains(&uid))
+            .map(|p| &p.grants)
+            .or(self.anonymous.as_ref())
+    }
+}
+
+/// Hex blake3 digest of a bearer token, as listed in a policy.
+pub fn token_digest(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
+}
+
+/// The namespace of a logical path: its first component, or `""` for a file
+/// at the top level. A leading `/` (as on a mount) is ignored.
+pub fn namespace_of(path: &str) -> &str {
+    let path = path.trim_start_matches('/');
+    match path.split_once('/') {
+        Some((namespace, _)) => namespace,
+        None => "",
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header value.
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|t| !t.is_empty())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
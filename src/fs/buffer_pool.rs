@@ -0,0 +1,215 @@
+//! Reusable byte buffers for the read paths.
+//!
+//! Reconstruction used to allocate a fresh `Vec` for every chunk it decoded
+//! and every file or range it assembled, and under heavy read load the
+//! allocator and the page faults of freshly mapped buffers showed up in
+//! profiles. A [`BufferPool`] keeps buffers that are done with and hands
+//! them out again.
+//!
+//! Buffers are kept in size classes of [`DEFAULT_CHUNK_SIZE`] doubled up to
+//! [`MAX_POOLED_BYTES`]: a request is served from the smallest class that
+//! fits it, so a chunk-sized buffer freed by verification serves the next
+//! FUSE read of one page, and a buffer a whole small file was extracted into
+//! serves the next one. Larger requests are allocated as usual and never
+//! pooled.
+//!
+//! FUSE reads, extraction and verification all draw on
+//! [`BufferPool::global`], so whichever of them runs warms the pool for the
+//! others.
+
+use crate::embrfs::DEFAULT_CHUNK_SIZE;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Size classes: `DEFAULT_CHUNK_SIZE << 0` through `DEFAULT_CHUNK_SIZE << 8`.
+const CLASSES: usize = 9;
+
+/// Capacity of the largest pooled buffer (1 MiB).
+pub const MAX_POOLED_BYTES: usize = DEFAULT_CHUNK_SIZE << (CLASSES - 1);
+
+/// Default number of idle buffers kept per size class.
+pub const DEFAULT_BUFFERS_PER_CLASS: usize = 32;
+
+static GLOBAL: OnceLock<BufferPool> = OnceLock::new();
+
+/// Counters and occupancy of a [`BufferPool`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Requests served from an idle buffer.
+    pub hits: u64,
+    /// Requests that allocated.
+    pub misses: u64,
+    /// Buffers returned and kept.
+    pub recycled: u64,
+    /// Buffers returned and dropped: too small, too large, or the class was full.
+    pub discarded: u64,
+    pub idle_buffers: usize,
+    /// Capacity of the idle buffers.
+    pub idle_bytes: usize,
+}
+
+/// Size-classed free lists of byte buffers; see the [module docs](self).
+#[derive(Debug)]
+pub struct BufferPool {
+    classes: Vec<Mutex<Vec<Vec<u8>>>>,
+    per_class: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    recycled: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl BufferPool {
+    /// A pool keeping at most `per_class` idle buffers of each size class.
+    pub fn new(per_class: usize) -> Self {
+        Self {
+            classes: (0..CLASSES).map(|_| Mutex::new(Vec::new())).collect(),
+            per_class,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+
+    /// The process-wide pool the read paths share, keeping
+    /// [`DEFAULT_BUFFERS_PER_CLASS`] buffers per class.
+    pub fn global() -> &'static BufferPool {
+        GLOBAL.get_or_init(|| BufferPool::new(DEFAULT_BUFFERS_PER_CLASS))
+    }
+
+    /// An empty buffer with room for at least `len` bytes. Hand it back
+    /// with [`BufferPool::give`] when done, or use [`BufferPool::lease`].
+    pub fn take(&self, len: usize) -> Vec<u8> {
+        let Some(class) = class_for_request(len) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Vec::with_capacity(len);
+        };
+        if let Some(buf) = self.free_list(class).pop() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return buf;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        Vec::with_capacity(class_size(class))
+    }
+
+    /// Return `buf` for reuse. Its contents are discarded.
+    pub fn give(&self, mut buf: Vec<u8>) {
+        let kept = class_for_capacity(buf.capacity()).is_some_and(|class| {
+            let mut list = self.free_list(class);
+            if list.len() >= self.per_class {
+                return false;
+            }
+            buf.clear();
+            list.push(buf);
+            true
+        });
+        let counter = if kept { &self.recycled } else { &self.discarded };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// [`BufferPool::take`], handing the buffer back when the lease drops.
+    pub fn lease(&self, len: usize) -> PooledBuffer<'_> {
+        PooledBuffer {
+            buf: self.take(len),
+            pool: self,
+        }
+    }
+
+    /// Lease `buf`, allocated elsewhere, so that it joins the pool once
+    /// dropped.
+    pub fn adopt(&self, buf: Vec<u8>) -> PooledBuffer<'_> {
+        PooledBuffer { buf, pool: self }
+    }
+
+    /// Drop every idle buffer.
+    pub fn clear(&self) {
+        for class in 0..CLASSES {
+            self.free_list(class).clear();
+        }
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        let mut stats = BufferPoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+            ..BufferPoolStats::default()
+        };
+        for class in 0..CLASSES {
+            let list = self.free_list(class);
+            stats.idle_buffers += list.len();
+            stats.idle_bytes += list.iter().map(Vec::capacity).sum::<usize>();
+        }
+        stats
+    }
+
+    fn free_list(&self, class: usize) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // A panic while holding the lock leaves at worst a list of empty
+        // buffers, which is still fine to use.
+        self.classes[class].lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUFFERS_PER_CLASS)
+    }
+}
+
+/// A buffer from [`BufferPool::lease`], returned to the pool on drop.
+#[derive(Debug)]
+pub struct PooledBuffer<'a> {
+    buf: Vec<u8>,
+    pool: &'a BufferPool,
+}
+
+impl PooledBuffer<'_> {
+    /// Keep the buffer instead of returning it to the pool.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        let buf = std::mem::take(&mut self.buf);
+        if buf.capacity() > 0 {
+            self.pool.give(buf);
+        }
+    }
+}
+
+fn class_size(class: usize) -> usize {
+    DEFAULT_CHUNK_SIZE << class
+}
+
+/// The smallest class whose buffers hold `len` bytes.
+fn class_for_request(len: usize) -> Option<usize> {
+    (0..CLASSES).find(|&class| class_size(class) >= len)
+}
+
+/// The largest class a buffer of `capacity` can serve every request of.
+/// Buffers grown past twice the largest class are not kept.
+fn class_for_capacity(capacity: usize) -> Option<usize> {
+    if capacity > 2 * MAX_POOLED_BYTES {
+        return None;
+    }
+    (0..CLASSES).rev().find(|&class| class_size(class) <= capacity)
+}
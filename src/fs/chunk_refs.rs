@@ -0,0 +1,195 @@
+//! Reference counts for the chunks of an engram used by several manifests.
+//!
+//! One engram's codebook can back more than one manifest: a full manifest
+//! and subsets of it, or the manifests left after files were removed from a
+//! copy. Dropping a chunk is only safe once none of them references it.
+//! [`ChunkRefs`] counts, per chunk id, the file entries referencing it in the
+//! manifests registered with it, and [`Engram::remove_unreferenced`] drops
+//! the chunks whose count reached zero.
+//!
+//! Counts are kept by the caller as manifests come and go, so they can fall
+//! out of step with the manifests actually in use. Removal therefore also
+//! takes the live manifests and refuses to run if one of them was never
+//! registered or still references a chunk the counts consider unused.
+
+use crate::embrfs::{EmbrFS, Engram, Manifest};
+use crate::error::{EmbrError, Result};
+use crate::signing::to_hex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+
+/// References to one chunk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ChunkRef {
+    /// File entries referencing the chunk, across registered manifests.
+    refs: u64,
+    /// Bytes of file data the chunk holds, kept after its last reference
+    /// goes so the engram's correction totals can be updated on removal.
+    len: usize,
+}
+
+/// Per-chunk reference counts across the manifests sharing an engram.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRefs {
+    chunks: BTreeMap<usize, ChunkRef>,
+    /// Digest of each registered manifest and how often it was registered.
+    manifests: BTreeMap<String, u64>,
+}
+
+/// Outcome of [`Engram::remove_unreferenced`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReclaimReport {
+    /// Chunk ids removed, ascending.
+    pub removed: Vec<usize>,
+    /// Estimated serialized bytes of their vectors and corrections.
+    pub reclaimed_bytes: u64,
+    /// Chunks left in the engram.
+    pub remaining: usize,
+}
+
+impl ChunkRefs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts for `manifests`, each registered once.
+    pub fn from_manifests<'a, I: IntoIterator<Item = &'a Manifest>>(manifests: I) -> Self {
+        let mut refs = Self::new();
+        for manifest in manifests {
+            refs.add_manifest(manifest);
+        }
+        refs
+    }
+
+    /// Register `manifest` and count one reference per file entry using
+    /// each of its chunks.
+    pub fn add_manifest(&mut self, manifest: &Manifest) {
+        *self.manifests.entry(manifest_digest(manifest)).or_default() += 1;
+        for file in &manifest.files {
+            for (idx, &id) in file.chunks.iter().enumerate() {
+                let entry = self.chunks.entry(id).or_default();
+                entry.refs += 1;
+                entry.len = EmbrFS::chunk_len(file, idx, manifest.chunk_size());
+            }
+        }
+    }
+
+    /// Unregister `manifest`, dropping its references. Returns `false`, and
+    /// changes nothing, if it isn't registered.
+    pub fn remove_manifest(&mut self, manifest: &Manifest) -> bool {
+        let digest = manifest_digest(manifest);
+        let Some(count) = self.manifests.get_mut(&digest) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.manifests.remove(&digest);
+        }
+        for file in &manifest.files {
+            for id in &file.chunks {
+                if let Some(entry) = self.chunks.get_mut(id) {
+                    entry.refs = entry.refs.saturating_sub(1);
+                }
+            }
+        }
+        true
+    }
+
+    /// Whether `manifest`, as it is now, is registered.
+    pub fn is_registered(&self, manifest: &Manifest) -> bool {
+        self.manifests.contains_key(&manifest_digest(manifest))
+    }
+
+    /// Registered manifests, counting repeats.
+    pub fn manifests(&self) -> u64 {
+        self.manifests.values().sum()
+    }
+
+    /// File entries referencing `chunk_id` across registered manifests.
+    pub fn refs(&self, chunk_id: usize) -> u64 {
+        self.chunks.get(&chunk_id).map_or(0, |c| c.refs)
+    }
+
+    /// Chunk ids of `engram` that no registered manifest references,
+    /// ascending.
+    pub fn unreferenced(&self, engram: &Engram) -> Vec<usize> {
+        engram
+            .chunk_record_ids()
+            .into_iter()
+            .map(|id| id as usize)
+            .filter(|&id| self.refs(id) == 0)
+            .collect()
+    }
+}
+
+impl Engram {
+    /// Drop the chunks that no manifest registered in `refs` references,
+    /// with their corrections, and take them out of the root: subtracted
+    /// from a counted root, otherwise rebuilt from the rest in id order.
+    ///
+    /// `live` is every manifest still used with this engram. Each must be
+    /// registered in `refs` as it is now, and none may reference a chunk
+    /// about to be removed; otherwise nothing is removed and an
+    /// `InvalidInput` error explains which check failed.
+    pub fn remove_unreferenced(
+        &mut self,
+        refs: &ChunkRefs,
+        live: &[&Manifest],
+    ) -> Result<ReclaimReport> {
+        if let Some(i) = live.iter().position(|m| !refs.is_registered(m)) {
+            return Err(invalid(format!(
+                "live manifest {} is not registered with the reference counts",
+                i
+            )));
+        }
+        let candidates = refs.unreferenced(self);
+        let used: HashSet<usize> = live
+            .iter()
+            .flat_map(|m| m.files.iter().flat_map(|f| f.chunks.iter().copied()))
+            .collect();
+        if let Some(id) = candidates.iter().find(|id| used.contains(id)) {
+            return Err(invalid(format!(
+                "reference counts are stale: chunk {} is unreferenced but a live manifest uses it",
+                id
+            )));
+        }
+
+        if let Some(manifest) = live.first() {
+            let ids: HashSet<u64> = candidates.iter().map(|&id| id as u64).collect();
+            self.detach_deltas(&ids, &manifest.encoding().vsa);
+        }
+
+        let mut report = ReclaimReport::default();
+        let mut removed = Vec::new();
+        for &id in &candidates {
+            report.reclaimed_bytes += match self.codebook.get(&id) {
+                Some(vec) => self.estimated_chunk_bytes(id, vec),
+                None => self
+                    .corrections
+                    .get(id as u64)
+                    .map_or(0, |c| 32 + c.storage_size() as u64),
+            };
+            removed.extend(self.codebook.remove(&id));
+            let len = refs.chunks.get(&id).map_or(0, |c| c.len);
+            self.corrections.remove(id as u64, len);
+        }
+        if !candidates.is_empty() {
+            let strategy = live.first().map(|m| m.encoding().vsa.root_strategy).unwrap_or_default();
+            self.unbundle_from_root(&removed, &strategy);
+        }
+        report.removed = candidates;
+        report.remaining = self.codebook.len();
+        Ok(report)
+    }
+}
+
+/// blake3 of the manifest's JSON form, identifying it as registered.
+fn manifest_digest(manifest: &Manifest) -> String {
+    let json = serde_json::to_vec(manifest).expect("manifests serialize to JSON");
+    to_hex(blake3::hash(&json).as_bytes())
+}
+
+fn invalid(msg: String) -> EmbrError {
+    io::Error::new(io::ErrorKind::InvalidInput, msg).into()
+}
@@ -0,0 +1,239 @@
+//! Chunks stored against a trained basis instead of as chunk vectors.
+//!
+//! Raw mode stores every chunk vector in full. Differential mode
+//! ([`EmbrFS::encode_differential`]) stores a chunk as its
+//! [`DifferentialEncoding`] against the basis of a trained [`Codebook`]
+//! instead: the coefficients of the basis vectors it resembles, and the
+//! residual they miss. Where chunks resemble the basis the residual is much
+//! smaller than the vector. The basis is saved with the chunks, so the
+//! engram decodes on its own.
+//!
+//! A chunk is stored differentially only if decoding gives back its vector
+//! exactly and takes fewer bytes than the vector; the others stay raw. So
+//! loading decodes the differential chunks back into the codebook, and
+//! their bytes are rebuilt as in raw mode: decoded, then corrected by the
+//! [`CorrectionStore`](crate::CorrectionStore) recorded at ingest.
+//!
+//! Saving checks every chunk against the vector in the codebook and saves
+//! chunks changed since they were encoded (by a repair or a replica sync,
+//! say) raw. Only bincode engram files keep the section; the other formats
+//! store every chunk vector in full.
+
+use crate::batch_projection::{self, ProjectionBackend};
+use crate::bitsliced::BitslicedTritVec;
+use crate::codebook::Codebook;
+use crate::dimensional::{DepthMap, DifferentialEncoder, DifferentialEncoding, DimensionalConfig, HyperVec};
+use crate::embrfs::{bincode_io_error, EmbrFS};
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+/// Chunks whose dot products with the basis one
+/// [`batch_projection::dot_matrix`] call computes, bounding the bitsliced
+/// copies held at once.
+const PROJECTION_BATCH: usize = 4096;
+
+/// The basis and the chunks encoded against it; see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DifferentialChunks {
+    pub config: DimensionalConfig,
+    pub basis: Vec<SparseVec>,
+    pub chunks: BTreeMap<usize, DifferentialChunk>,
+}
+
+/// One chunk's [`DifferentialEncoding`], without the per-vector config.
+/// Decoded at the depths of [`Engram::precision`](crate::Engram::precision).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DifferentialChunk {
+    /// Basis index and coefficient, in hundredths.
+    pub coefficients: Vec<(usize, i64)>,
+    /// Dimension and value.
+    pub residual: Vec<(usize, i64)>,
+}
+
+/// What [`EmbrFS::encode_differential`] stored, against raw mode.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DifferentialReport {
+    pub chunks: usize,
+    /// Chunks stored differentially; the rest stay raw.
+    pub differential: usize,
+    pub basis_vectors: usize,
+    /// Bytes of the chunk vectors in raw mode.
+    pub raw_bytes: u64,
+    /// Bytes of the same chunks in differential mode, basis included.
+    pub differential_bytes: u64,
+    /// Mean [`DifferentialEncoding::quality`] over every chunk.
+    pub mean_quality: f64,
+}
+
+impl DifferentialReport {
+    /// `differential_bytes / raw_bytes`: below 1 when differential mode
+    /// is smaller.
+    pub fn ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            1.0
+        } else {
+            self.differential_bytes as f64 / self.raw_bytes as f64
+        }
+    }
+}
+
+impl DifferentialChunks {
+    /// The encoder these chunks were encoded with, given the engram's
+    /// precision.
+    pub fn encoder(&self, precision: Option<&DepthMap>) -> DifferentialEncoder {
+        let mut encoder = DifferentialEncoder::new(self.config.clone()).with_depths(precision.cloned().unwrap_or_default());
+        for vector in &self.basis {
+            encoder.add_basis(HyperVec::from_sparse(self.config.clone(), vector));
+        }
+        encoder
+    }
+
+    /// The chunk vector `chunk` encodes.
+    pub fn decode(encoder: &DifferentialEncoder, chunk: &DifferentialChunk) -> SparseVec {
+        let config = &encoder.config;
+        let mut encoding = DifferentialEncoding {
+            coefficients: HyperVec::new(config.clone()),
+            residual: HyperVec::new(config.clone()),
+            expanded: DepthMap::default(),
+            quality: 1.0,
+        };
+        for &(idx, value) in &chunk.coefficients {
+            encoding.coefficients.set_at_depth(idx, value, encoder.depths.coefficient_depth(config, idx));
+        }
+        for &(dim, value) in &chunk.residual {
+            encoding.residual.set_at_depth(dim, value, encoder.depths.dimension_depth(config, dim));
+        }
+        to_sparse(&encoder.decode(&encoding))
+    }
+
+    /// Decode every chunk whose encoding is intact into `codebook`.
+    pub(crate) fn decode_into(&self, precision: Option<&DepthMap>, codebook: &mut HashMap<usize, SparseVec>) {
+        let encoder = self.encoder(precision);
+        for (&id, chunk) in &self.chunks {
+            codebook.insert(id, Self::decode(&encoder, chunk));
+        }
+    }
+
+    /// The chunks that still decode to their vector in `codebook`, as
+    /// saving keeps them.
+    pub(crate) fn current(&self, precision: Option<&DepthMap>, codebook: &HashMap<usize, SparseVec>) -> DifferentialChunks {
+        let encoder = self.encoder(precision);
+        let chunks = self
+            .chunks
+            .iter()
+            .filter(|(id, chunk)| codebook.get(id).is_some_and(|v| *v == Self::decode(&encoder, chunk)))
+            .map(|(&id, chunk)| (id, chunk.clone()))
+            .collect();
+        DifferentialChunks {
+            config: self.config.clone(),
+            basis: self.basis.clone(),
+            chunks,
+        }
+    }
+}
+
+impl From<&DifferentialEncoding> for DifferentialChunk {
+    fn from(encoding: &DifferentialEncoding) -> Self {
+        let pairs = |v: &HyperVec| v.dimensions.iter().map(|(&d, t)| (d, t.to_i64())).collect();
+        DifferentialChunk {
+            coefficients: pairs(&encoding.coefficients),
+            residual: pairs(&encoding.residual),
+        }
+    }
+}
+
+/// Signs of `v`; exact for the ±1 values chunk vectors hold.
+fn to_sparse(v: &HyperVec) -> SparseVec {
+    let mut out = SparseVec::new();
+    for (&dim, tryte) in &v.dimensions {
+        match tryte.to_i64().signum() {
+            1 => out.pos.push(dim),
+            -1 => out.neg.push(dim),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn size<T: Serialize + ?Sized>(value: &T) -> io::Result<u64> {
+    bincode::serialized_size(value).map_err(|e| bincode_io_error(*e))
+}
+
+impl EmbrFS {
+    /// Store the chunks differentially against the basis vectors of
+    /// `basis`, where that is exact and smaller; see the
+    /// [module docs](self). Depths adaptive precision raises are kept in
+    /// [`Engram::precision`](crate::Engram::precision). Replaces an earlier
+    /// differential encoding.
+    pub fn encode_differential(&mut self, basis: &Codebook) -> io::Result<DifferentialReport> {
+        if basis.basis_vectors.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the basis has no vectors; train it first"));
+        }
+        let mut encoder = self.differential_encoder(basis);
+        let vectors: Vec<SparseVec> = basis.basis_vectors.iter().map(|b| b.vector.clone()).collect();
+        let mut report = DifferentialReport {
+            basis_vectors: vectors.len(),
+            differential_bytes: size(&vectors)? + size(&encoder.config)?,
+            ..DifferentialReport::default()
+        };
+
+        // The cosines come from batched dot products over the dimensions a
+        // HyperVec keeps, so they equal those `encode` would compute.
+        let len = encoder.config.num_dimensions;
+        let norm = |v: &SparseVec| (v.pos.iter().chain(&v.neg).filter(|&&d| d < len).count() as f64).sqrt();
+        let planes: Vec<BitslicedTritVec> = vectors.iter().map(|v| BitslicedTritVec::from_sparse(v, len)).collect();
+        let plane_norms: Vec<f64> = vectors.iter().map(norm).collect();
+
+        let mut ids: Vec<usize> = self.engram.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        let mut chunks = BTreeMap::new();
+        let mut quality = 0.0;
+        for batch in ids.chunks(PROJECTION_BATCH) {
+            let sliced: Vec<BitslicedTritVec> = batch
+                .iter()
+                .map(|id| BitslicedTritVec::from_sparse(&self.engram.codebook[id], len))
+                .collect();
+            let (dots, _) = batch_projection::dot_matrix(&planes, &sliced, ProjectionBackend::Auto);
+            drop(sliced);
+            for (&id, row) in batch.iter().zip(dots.chunks(planes.len())) {
+                let vector = &self.engram.codebook[&id];
+                let chunk_norm = norm(vector);
+                let similarities: Vec<f64> = row
+                    .iter()
+                    .zip(&plane_norms)
+                    .map(|(&dot, &plane_norm)| {
+                        if chunk_norm > 0.0 && plane_norm > 0.0 { dot as f64 / (chunk_norm * plane_norm) } else { 0.0 }
+                    })
+                    .collect();
+                let data = HyperVec::from_sparse(encoder.config.clone(), vector);
+                let encoding = encoder.encode_with_similarities(&data, &similarities);
+                encoder.depths.merge(&encoding.expanded);
+                quality += encoding.quality;
+                report.chunks += 1;
+
+                let chunk = DifferentialChunk::from(&encoding);
+                let raw = size(&id)? + size(vector)?;
+                let encoded = size(&id)? + size(&chunk)?;
+                report.raw_bytes += raw;
+                if encoded < raw && DifferentialChunks::decode(&encoder, &chunk) == *vector {
+                    report.differential += 1;
+                    report.differential_bytes += encoded;
+                    chunks.insert(id, chunk);
+                } else {
+                    report.differential_bytes += raw;
+                }
+            }
+        }
+        report.mean_quality = if report.chunks == 0 { 1.0 } else { quality / report.chunks as f64 };
+
+        self.engram.precision = (!encoder.depths.is_empty()).then_some(encoder.depths);
+        self.engram.differential = (!chunks.is_empty()).then_some(DifferentialChunks {
+            config: encoder.config,
+            basis: vectors,
+            chunks,
+        });
+        Ok(report)
+    }
+}
@@ -20,40 +20,614 @@
 //! If encoding was perfect, correction is empty. If not, correction exactly
 //! compensates. Either way, reconstruction is guaranteed bit-perfect.
 
-use crate::vsa::{SparseVec, ReversibleVSAConfig, DIM};
+use crate::vsa::{ChunkEncoding, RootStrategy, SparseVec, SparseVecError, ReversibleVSAConfig, TreeBundle, DIM};
+use crate::bitsliced::{BitslicedTritVec, CountedBundle};
+use crate::dimensional::{DepthMap, DimensionalConfig, HyperVec, TritDepthConfig};
+use crate::differential::DifferentialChunks;
+use crate::role_schema::RoleSchema;
 use crate::resonator::Resonator;
-use crate::correction::{CorrectionStore, CorrectionStats};
+use crate::codebook::{BasisTrainingReport, ChunkCache, ChunkClusters, Codebook, MAX_BASIS_SAMPLES};
+use crate::append_log::{self, AppendLog, AppendStats, CompactStats, PendingRecord, RecordKind};
+use crate::buffer_pool::{BufferPool, PooledBuffer};
+use crate::ingest_provenance::IngestProvenance;
+use crate::namespace_keys::KeyManifest;
+use crate::bulk_io::{BulkFileWriter, BULK_FILE_LIMIT};
+use crate::correction::{self, ChunkCorrection, CorrectionStats, CorrectionStore, CorrectionTotals, CorrectionType};
 use crate::retrieval::{RerankedResult, TernaryInvertedIndex};
-use crate::envelope::{BinaryWriteOptions, PayloadKind, unwrap_auto, wrap_or_legacy};
+use crate::semantic::{SemanticEncoder, SemanticSignatures};
+use crate::envelope::{
+    BinaryWriteOptions, ChecksumCodec, CompressionCodec, DictionarySampler, EncryptionCodec, EnvelopeReader,
+    EnvelopeWriter, Keyring, PayloadKind, DEFAULT_DICT_SIZE, DICT_FRAME_SIZE, plain_header, unwrap_auto,
+    wrap_or_legacy,
+};
 use crate::metrics::metrics;
+use crate::multipart::{self, PartIndex, PartWriter};
+use crate::shards::{self, ShardIndex, ShardedEngram};
+use crate::shared_codebook::{self, SharedSaveReport};
+use crate::txn::{self, Transaction, TxnReport};
+use crate::seal;
+use crate::rkyv_engram::{self, RkyvEngram};
+use crate::wire;
+use crate::signing::{self, DetachedSignature, VerifyMode};
+use crate::ingest_stats::IngestStats;
+use crate::ingest_filter::{self, IngestFilters, Verdict};
+use crate::ingest_queue::{self, ChunkSource, IngestPipeline, IngestQueue, Piece, QueueStats, QueuedFile};
+use crate::reproducible::{self, IngestClock};
+use crate::job::{JobControl, JobProgress};
+use crate::retention::RetentionPolicy;
+use crate::inodes::InodeTable;
+use crate::manifest_schema::{self, Versioned};
+use crate::safe_path::{self, PathPolicy};
+use crate::path_norm::{self, Folding, PathCollision, PathNormalization};
+use crate::capacity::{CapacityMonitor, CapacityReport, MembershipResult};
+use crate::provenance::{ChunkProvenance, ExtractReport, FileProvenance};
+use crate::memory::{self, MemoryUsage};
+use crate::logging;
+use crate::profile;
+use crate::access_stats::{AccessKind, AccessSnapshot, AccessStats, EVICTION_WINDOW};
+use crate::error::{EmbrError, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
-
-#[cfg(feature = "metrics")]
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 /// Default chunk size for file encoding (4KB)
 pub const DEFAULT_CHUNK_SIZE: usize = 4096;
 
+/// Number of chunks encoded to calibrate a dry-run size estimate.
+const ESTIMATE_SAMPLE_CHUNKS: usize = 64;
+
+/// Bytes of a large file gathered before each write during extraction.
+const EXTRACT_STAGING_BYTES: usize = 64 * 1024;
+
+/// Resource limits enforced while ingesting.
+///
+/// `None` means unlimited. Limits are cumulative across every ingest call on
+/// the same `EmbrFS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IngestLimits {
+    /// Upper bound on the estimated serialized engram size, in bytes.
+    pub max_engram_bytes: Option<u64>,
+    /// Largest single input file accepted, in bytes.
+    pub max_file_size: Option<u64>,
+    /// Maximum number of chunks in the engram.
+    pub max_chunks: Option<usize>,
+    /// Upper bound on the estimated heap bytes the engram, manifest and
+    /// indices hold (see [`EmbrFS::held_memory_bytes`]). A file that would
+    /// take them over it is dropped again before the error is returned.
+    pub max_memory_bytes: Option<u64>,
+}
+
+/// Content-addressed chunk ids, for deduplicating repeated ingests.
+///
+/// Chunk encoding depends on the logical path, so a chunk is only shared
+/// when both its bytes and its path match an earlier one. Keys are
+/// BLAKE3 over the path and the chunk.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkIndex {
+    pub(crate) ids: HashMap<[u8; 32], usize>,
+}
+
+impl ChunkIndex {
+    fn key(logical_path: &str, chunk: &[u8]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(logical_path.len() as u64).to_le_bytes());
+        hasher.update(logical_path.as_bytes());
+        hasher.update(chunk);
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Number of distinct chunks indexed.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// Which ingest limit was hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaKind {
+    EngramBytes,
+    FileSize,
+    ChunkCount,
+    MemoryBytes,
+}
+
+/// Error raised when ingest would exceed an [`IngestLimits`] bound.
+///
+/// Returned as [`EmbrError::Quota`].
+/// After this error the `EmbrFS` may contain part of the offending file and
+/// should be discarded rather than saved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub kind: QuotaKind,
+    pub limit: u64,
+    pub attempted: u64,
+    pub path: String,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let what = match self.kind {
+            QuotaKind::EngramBytes => "engram size",
+            QuotaKind::FileSize => "file size",
+            QuotaKind::ChunkCount => "chunk count",
+            QuotaKind::MemoryBytes => "memory",
+        };
+        write!(
+            f,
+            "quota exceeded while ingesting {}: {} {} exceeds limit {}",
+            self.path, what, self.attempted, self.limit
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Bounds applied while decoding an engram from bytes that may not be
+/// trusted.
+///
+/// The defaults admit any engram this crate would plausibly write while
+/// keeping a crafted file (or a small compressed one that inflates without
+/// end) from exhausting memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadLimits {
+    /// Most decoded bytes the engram's bincode payload may occupy.
+    pub max_bytes: u64,
+    /// Most codebook vectors, and most correction records, accepted.
+    pub max_chunks: usize,
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 << 30,
+            max_chunks: 1 << 28,
+        }
+    }
+}
+
+/// Why a decoded engram was rejected. Returned as
+/// [`EmbrError::CorruptEngram`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngramCorruption {
+    /// The payload is not a well-formed engram encoding.
+    Malformed(String),
+    /// Decoding would read more than [`LoadLimits::max_bytes`].
+    TooLarge { limit: u64 },
+    /// More codebook vectors or correction records than
+    /// [`LoadLimits::max_chunks`].
+    TooManyChunks { count: usize, limit: usize },
+    /// The root (`chunk: None`) or a codebook vector breaks the
+    /// [`SparseVec`] invariants.
+    InvalidVector { chunk: Option<usize>, error: SparseVecError },
+    /// [`Engram::root_counts`] are malformed or do not produce the root.
+    InvalidRootCounts(String),
+}
+
+impl std::fmt::Display for EngramCorruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngramCorruption::Malformed(msg) => write!(f, "malformed engram: {}", msg),
+            EngramCorruption::TooLarge { limit } => {
+                write!(f, "engram exceeds the load limit of {} bytes", limit)
+            }
+            EngramCorruption::TooManyChunks { count, limit } => write!(
+                f,
+                "engram holds {} chunk records, more than the limit of {}",
+                count, limit
+            ),
+            EngramCorruption::InvalidVector { chunk: None, error } => {
+                write!(f, "invalid engram root vector: {}", error)
+            }
+            EngramCorruption::InvalidVector { chunk: Some(id), error } => {
+                write!(f, "invalid codebook vector for chunk {}: {}", id, error)
+            }
+            EngramCorruption::InvalidRootCounts(msg) => write!(f, "invalid engram root counts: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EngramCorruption {}
+
+/// Bincode-decode one value of at most `max_bytes` from `reader`, with the
+/// fixed-width integer encoding `bincode::serialize` writes.
+pub(crate) fn decode_bounded<T: serde::de::DeserializeOwned, R: Read>(
+    reader: R,
+    max_bytes: u64,
+) -> io::Result<T> {
+    use bincode::Options;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(max_bytes)
+        .deserialize_from(reader)
+        .map_err(|e| match *e {
+            bincode::ErrorKind::Io(e) => e,
+            bincode::ErrorKind::SizeLimit => {
+                EmbrError::CorruptEngram(EngramCorruption::TooLarge { limit: max_bytes }).into()
+            }
+            other => EmbrError::CorruptEngram(EngramCorruption::Malformed(other.to_string())).into(),
+        })
+}
+
+/// Read an [`PayloadKind::EngramProto`] envelope of at most `limits.max_bytes`.
+fn decode_proto_engram<R: Read>(reader: R, keys: &Keyring, limits: &LoadLimits) -> Result<Engram> {
+    let reader = EnvelopeReader::with_keys(reader, PayloadKind::EngramProto, keys)?;
+    let mut message = Vec::new();
+    reader.take(limits.max_bytes.saturating_add(1)).read_to_end(&mut message)?;
+    if message.len() as u64 > limits.max_bytes {
+        return Err(EmbrError::CorruptEngram(EngramCorruption::TooLarge { limit: limits.max_bytes }));
+    }
+    wire::decode_engram(&message).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData => EmbrError::CorruptEngram(EngramCorruption::Malformed(e.to_string())),
+        _ => e.into(),
+    })
+}
+
+/// Dry-run estimate of an ingest, produced without encoding every chunk.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IngestEstimate {
+    pub files: usize,
+    pub input_bytes: u64,
+    pub chunks: usize,
+    /// Extrapolated serialized engram size, calibrated on a sample of chunks.
+    pub estimated_engram_bytes: u64,
+    /// Limits that the full ingest would exceed.
+    pub violations: Vec<QuotaExceeded>,
+}
+
+/// Outcome of [`EmbrFS::adapt_precision`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PrecisionReport {
+    /// Chunks encoded against the basis.
+    pub chunks: usize,
+    /// Data dimensions stored deeper than the base depth, in all.
+    pub expanded_dimensions: usize,
+    /// Basis coefficients stored deeper than the base depth, in all.
+    pub expanded_coefficients: usize,
+    /// Mean [`DifferentialEncoding::quality`](crate::DifferentialEncoding::quality).
+    pub mean_quality: f64,
+}
+
 /// File entry in the manifest
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 pub struct FileEntry {
     pub path: String,
     pub is_text: bool,
     pub size: usize,
     pub chunks: Vec<usize>,
+    /// Hex-encoded blake3 digest of the original file bytes, recorded at ingest.
+    ///
+    /// Absent for manifests written before checksums were introduced; such
+    /// files are reconstructed without an end-to-end check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+    /// Unix seconds at ingest, where retention TTLs start; absent for files
+    /// ingested before it was recorded.
+    #[serde(default)]
+    pub ingested_at: Option<u64>,
+    /// The file's chunk vectors bundled with [`SparseVec::bundle_sum_many`],
+    /// as [`FileSignatures`] bundles them. Recorded at ingest when
+    /// [`EmbrFS::file_signatures`] is on, so signatures need not be rebuilt
+    /// from the codebook.
+    #[serde(default)]
+    pub signature: Option<SparseVec>,
+    /// Owner and permission bits of the source file, recorded when it was
+    /// ingested from a Unix filesystem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix: Option<UnixMeta>,
+}
+
+impl FileEntry {
+    /// Estimated heap bytes the entry owns.
+    pub(crate) fn memory_bytes(&self) -> u64 {
+        self.path.capacity() as u64
+            + memory::vec_bytes(&self.chunks)
+            + self.blake3.as_ref().map_or(0, |h| h.capacity() as u64)
+            + self.signature.as_ref().map_or(0, memory::sparse_vec_bytes)
+    }
+}
+
+/// POSIX ownership and mode of an ingested file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnixMeta {
+    pub uid: u32,
+    pub gid: u32,
+    /// Permission bits (`mode & 0o7777`).
+    pub mode: u32,
+}
+
+impl UnixMeta {
+    /// Ownership and mode of the file `metadata` describes; `None` off Unix.
+    pub fn of(metadata: &fs::Metadata) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Some(UnixMeta {
+                uid: metadata.uid(),
+                gid: metadata.gid(),
+                mode: metadata.mode() & 0o7777,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = metadata;
+            None
+        }
+    }
+}
+
+/// Bytes of one reconstructed chunk: shared with a [`ChunkCache`], or
+/// decoded for this read alone and handed to the [`BufferPool`] when dropped.
+enum ChunkData {
+    Shared(Arc<[u8]>),
+    Pooled(PooledBuffer<'static>),
+}
+
+impl std::ops::Deref for ChunkData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ChunkData::Shared(bytes) => bytes,
+            ChunkData::Pooled(buf) => buf,
+        }
+    }
+}
+
+/// A reconstructed file whose bytes do not match its recorded checksum.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecksumMismatch {
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+    /// Chunks that are missing or fail their correction-store hash check.
+    pub chunk_ids: Vec<usize>,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: expected blake3 {}, got {} (offending chunks: {:?})",
+            self.path, self.expected, self.actual, self.chunk_ids
+        )
+    }
+}
+
+/// Outcome of checking every file in a manifest against its recorded checksum.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// Files whose reconstruction matched the recorded checksum.
+    pub files_verified: usize,
+    /// Files without a recorded checksum (legacy manifests).
+    pub files_unchecked: usize,
+    pub mismatches: Vec<ChecksumMismatch>,
+}
+
+impl VerifyReport {
+    /// True when no file failed its checksum.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
 }
 
 /// Manifest describing filesystem structure
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Manifest {
     pub files: Vec<FileEntry>,
     pub total_chunks: usize,
+    /// Named source roots (namespace -> original source path).
+    ///
+    /// Files ingested under a namespace have logical paths `{namespace}/...`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub namespaces: BTreeMap<String, String>,
+    /// TTLs and retention classes for paths and namespaces; see
+    /// [`crate::retention`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
+    /// Encoding the chunks were written with. `None` (manifests written
+    /// before it was recorded) means [`EncodingConfig::default`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<EncodingConfig>,
+    /// Inode numbers mounts give each file and directory; filled in as
+    /// files are ingested (see [`crate::inodes`]). Empty in manifests
+    /// written before numbers were recorded.
+    #[serde(default, skip_serializing_if = "InodeTable::is_empty")]
+    pub inodes: InodeTable,
+    /// Normalization ingest applies to logical paths; see
+    /// [`crate::path_norm`]. The default keeps paths as given.
+    #[serde(default, skip_serializing_if = "PathNormalization::is_identity")]
+    pub path_normalization: PathNormalization,
+    /// Who ingested the engram, from what and with which settings; see
+    /// [`crate::ingest_provenance`]. `None` for manifests written before it
+    /// was recorded and for engrams not built by ingest alone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Box<IngestProvenance>>,
+    /// Rules putting namespaces under keys of their own, and the encrypted
+    /// chunk groups beside the engram; see [`crate::namespace_keys`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace_keys: Option<Box<KeyManifest>>,
+}
+
+/// How an engram's chunks are encoded. Decoding with any other settings
+/// yields the wrong bytes, so the manifest records it and every ingest and
+/// extract checks the config it is given against it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncodingConfig {
+    pub vsa: ReversibleVSAConfig,
+    pub dimensional: DimensionalConfig,
+    /// Bytes per chunk; every chunk of a file but the last is this long.
+    /// Manifests written before it was recorded used [`DEFAULT_CHUNK_SIZE`],
+    /// and it is left out when it is still that.
+    #[serde(default = "default_chunk_size", skip_serializing_if = "is_default_chunk_size")]
+    pub chunk_size: usize,
+}
+
+fn default_chunk_size() -> usize {
+    DEFAULT_CHUNK_SIZE
+}
+
+fn is_default_chunk_size(chunk_size: &usize) -> bool {
+    *chunk_size == DEFAULT_CHUNK_SIZE
+}
+
+impl Default for EncodingConfig {
+    fn default() -> Self {
+        Self {
+            vsa: ReversibleVSAConfig::default(),
+            dimensional: DimensionalConfig::default(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl EncodingConfig {
+    /// Check that chunks can be encoded this way: the dimension must be the
+    /// chunk vector dimension [`DIM`], and every size and depth nonzero.
+    pub fn validate(&self) -> Result<()> {
+        let (vsa, dimensional) = (&self.vsa, &self.dimensional);
+        if dimensional.num_dimensions != DIM {
+            return Err(EmbrError::DimensionMismatch {
+                context: "chunk encoding".to_string(),
+                expected: DIM,
+                found: dimensional.num_dimensions,
+            });
+        }
+        let depths_ok = match &dimensional.trit_depth {
+            TritDepthConfig::Uniform(depth) => *depth > 0,
+            TritDepthConfig::Variable(depths) => {
+                depths.len() == dimensional.num_dimensions && depths.iter().all(|&d| d > 0)
+            }
+            TritDepthConfig::Adaptive { base_depth, max_depth } => 0 < *base_depth && base_depth <= max_depth,
+        };
+        let problem = if self.chunk_size == 0 {
+            "chunk_size must be nonzero"
+        } else if vsa.block_size == 0 {
+            "block_size must be nonzero"
+        } else if vsa.max_path_depth == 0 {
+            "max_path_depth must be nonzero"
+        } else if matches!(vsa.root_strategy, RootStrategy::Tree { fan_in } if fan_in < 2) {
+            "a tree root needs a fan_in of at least 2"
+        } else if matches!(vsa.root_strategy, RootStrategy::Thinned { max_nonzero: 0 }) {
+            "a thinned root needs a nonzero max_nonzero"
+        } else if matches!(vsa.chunk_encoding, ChunkEncoding::Similarity { shingle: 0, .. }) {
+            "similarity signatures need a nonzero shingle"
+        } else if matches!(vsa.chunk_encoding, ChunkEncoding::Similarity { nonzero, .. } if nonzero == 0 || nonzero > DIM) {
+            "similarity signatures need between 1 and DIM nonzero trits"
+        } else if !depths_ok {
+            "trit depths must be nonzero, with one per dimension"
+        } else if !(dimensional.target_sparsity > 0.0 && dimensional.target_sparsity <= 1.0) {
+            "target_sparsity must be in (0, 1]"
+        } else {
+            return Ok(());
+        };
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid encoding config: {}", problem)).into())
+    }
+}
+
+impl Manifest {
+    /// Decode manifest JSON of any schema version, migrating it to the
+    /// current one first (see [`crate::manifest_schema`]).
+    ///
+    /// A recorded [`EncodingConfig`] this build cannot decode fails it.
+    pub fn from_json(mut json: serde_json::Value) -> Result<Manifest> {
+        manifest_schema::upgrade(&mut json)?;
+        let manifest: Manifest = serde_json::from_value(json)?;
+        manifest.encoding.as_ref().map_or(Ok(()), EncodingConfig::validate)?;
+        Ok(manifest)
+    }
+
+    /// [`Manifest::from_json`] over serialized JSON.
+    pub fn from_json_slice(json: &[u8]) -> Result<Manifest> {
+        Manifest::from_json(serde_json::from_slice(json)?)
+    }
+
+    /// The encoding this manifest's chunks were written with.
+    pub fn encoding(&self) -> EncodingConfig {
+        self.encoding.clone().unwrap_or_default()
+    }
+
+    /// Bytes per chunk of this manifest's files; see
+    /// [`EncodingConfig::chunk_size`].
+    pub fn chunk_size(&self) -> usize {
+        self.encoding.as_ref().map_or(DEFAULT_CHUNK_SIZE, |e| e.chunk_size)
+    }
+
+    /// Fail unless `config` is the VSA config the chunks were encoded with.
+    pub fn check_vsa_config(&self, config: &ReversibleVSAConfig) -> Result<()> {
+        let expected = self.encoding.as_ref().map_or_else(ReversibleVSAConfig::default, |e| e.vsa.clone());
+        if *config == expected {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("engram was encoded with {:?}, not {:?}", expected, config),
+        )
+        .into())
+    }
+
+    /// Number every file, and the directories above it, that has no inode
+    /// number yet, in manifest order.
+    pub fn assign_inodes(&mut self) {
+        for file in &self.files {
+            self.inodes.assign(&file.path);
+        }
+    }
+
+    /// Files (within `namespace`, if given) that a filesystem with
+    /// `folding` would store under one name; see [`crate::path_norm`].
+    pub fn collisions(&self, namespace: Option<&str>, folding: Folding) -> Vec<PathCollision> {
+        let files: Box<dyn Iterator<Item = &FileEntry>> = match namespace {
+            Some(ns) => Box::new(self.files_in_namespace(ns)),
+            None => Box::new(self.files.iter()),
+        };
+        path_norm::collisions(files.map(|f| f.path.as_str()), folding)
+    }
+
+    /// Files whose logical path lives under `namespace`.
+    pub fn files_in_namespace<'a>(&'a self, namespace: &'a str) -> impl Iterator<Item = &'a FileEntry> + 'a {
+        self.files
+            .iter()
+            .filter(move |f| namespace_relative_path(&f.path, namespace).is_some())
+    }
+
+    /// Estimated heap bytes of the file table and namespaces.
+    pub(crate) fn memory_bytes(&self) -> u64 {
+        let files: u64 = self.files.iter().map(FileEntry::memory_bytes).sum();
+        let namespaces: u64 = self
+            .namespaces
+            .iter()
+            .map(|(k, v)| (std::mem::size_of::<(String, String)>() + k.capacity() + v.capacity()) as u64)
+            .sum();
+        memory::vec_bytes(&self.files) + files + namespaces
+    }
+
+    /// Chunk IDs referenced by files under `namespace`.
+    pub fn chunk_ids_in_namespace(&self, namespace: &str) -> HashSet<usize> {
+        self.files_in_namespace(namespace)
+            .flat_map(|f| f.chunks.iter().copied())
+            .collect()
+    }
+}
+
+/// Path of `path` relative to `namespace`, or `None` if it lies outside it.
+fn namespace_relative_path<'a>(path: &'a str, namespace: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(namespace)?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix('/')
+    }
 }
 
 /// Hierarchical manifest for multi-level engrams
@@ -63,8 +637,103 @@ pub struct HierarchicalManifest {
     pub levels: Vec<ManifestLevel>,
     #[serde(default)]
     pub sub_engrams: HashMap<String, SubEngram>,
+    /// Digest of each sub-engram as stored, by id, checked by a
+    /// [`DirectorySubEngramStore::with_digests`]. Empty for manifests built
+    /// before digests were recorded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub digests: BTreeMap<String, SubEngramDigest>,
+}
+
+impl HierarchicalManifest {
+    /// Ids of the sub-engrams that may hold any of `chunk_ids`, sorted. Uses
+    /// the chunk lists of sub-engrams held in the manifest, and otherwise
+    /// the chunk ranges of their digests, which can over-include.
+    pub fn sub_engrams_holding(&self, chunk_ids: &[usize]) -> Vec<String> {
+        let wanted: HashSet<usize> = chunk_ids.iter().copied().collect();
+        let mut ids: BTreeSet<&String> = self
+            .sub_engrams
+            .iter()
+            .filter(|(_, sub)| sub.chunk_ids.iter().any(|id| wanted.contains(id)))
+            .map(|(id, _)| id)
+            .collect();
+        ids.extend(self.digests.iter().filter_map(|(id, digest)| {
+            let (lo, hi) = digest.chunk_range?;
+            let held = !self.sub_engrams.contains_key(id) && chunk_ids.iter().any(|c| (lo..=hi).contains(c));
+            held.then_some(id)
+        }));
+        ids.into_iter().cloned().collect()
+    }
+
+    /// Record the digest of every sub-engram, replacing any recorded before.
+    pub fn record_digests(&mut self) {
+        self.digests = self
+            .sub_engrams
+            .iter()
+            .map(|(id, sub)| (id.clone(), SubEngramDigest::of(sub)))
+            .collect();
+    }
+}
+
+/// What a [`HierarchicalManifest`] records about a stored sub-engram.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubEngramDigest {
+    /// Hex blake3 of the sub-engram's bincode encoding, before any envelope
+    /// compression or encryption.
+    pub blake3: String,
+    /// Lowest and highest chunk id the sub-engram holds, so corruption can
+    /// be traced to chunks without decoding it. `None` if it holds none.
+    pub chunk_range: Option<(usize, usize)>,
+}
+
+impl SubEngramDigest {
+    pub fn of(sub: &SubEngram) -> Self {
+        let encoded = bincode::serialize(sub).expect("serializing to memory cannot fail");
+        Self {
+            blake3: blake3::hash(&encoded).to_hex().to_string(),
+            chunk_range: sub.chunk_ids.iter().min().zip(sub.chunk_ids.iter().max()).map(|(&lo, &hi)| (lo, hi)),
+        }
+    }
+}
+
+/// A sub-engram a [`DirectorySubEngramStore`] could not load intact.
+/// Returned as [`EmbrError::CorruptSubEngram`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubEngramCorruption {
+    pub id: String,
+    /// Chunk ids the sub-engram covers, from its recorded digest.
+    pub chunk_range: Option<(usize, usize)>,
+    pub fault: SubEngramFault,
+}
+
+/// Why a sub-engram failed to load.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubEngramFault {
+    /// The manifest records it but the store has no file for it.
+    Missing,
+    /// The file is not a readable sub-engram envelope or encoding.
+    Malformed(String),
+    /// It decodes, but not to what the manifest recorded.
+    DigestMismatch { expected: String, found: String },
 }
 
+impl std::fmt::Display for SubEngramCorruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sub-engram {}", self.id)?;
+        if let Some((lo, hi)) = self.chunk_range {
+            write!(f, " (chunks {}..={})", lo, hi)?;
+        }
+        match &self.fault {
+            SubEngramFault::Missing => write!(f, " is missing"),
+            SubEngramFault::Malformed(msg) => write!(f, " is malformed: {}", msg),
+            SubEngramFault::DigestMismatch { expected, found } => {
+                write!(f, " has digest {}, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SubEngramCorruption {}
+
 /// Level in hierarchical manifest
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ManifestLevel {
@@ -77,6 +746,12 @@ pub struct ManifestLevel {
 pub struct ManifestItem {
     pub path: String,
     pub sub_engram_id: String,
+    /// File signatures under `path` rolled up with
+    /// [`SparseVec::bundle_sum_many`]: a file's own signature, or for a
+    /// directory the signatures of its children. Absent for manifests built
+    /// before directory signatures were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SparseVec>,
 }
 
 /// Sub-engram in hierarchical structure
@@ -110,6 +785,19 @@ pub struct HierarchicalQueryBounds {
     pub max_open_indices: usize,
     /// Maximum number of cached sub-engrams.
     pub max_open_engrams: usize,
+    /// Skip nodes whose [`ManifestItem::signature`] has a lower cosine with
+    /// the query, along with their subtrees. Nodes without a signature are
+    /// never skipped.
+    pub min_signature_cosine: Option<f64>,
+    /// Wall-clock budget, counted from the start of the query. Once spent,
+    /// no further node is expanded (though the best node always is) and the
+    /// best hits so far are returned, flagged as
+    /// [`QueryTruncation::TimeBudget`].
+    pub time_budget: Option<Duration>,
+    /// Budget of index candidates scanned over all expanded nodes; each
+    /// node scans at most what is left of it. Once spent, the best hits so
+    /// far are returned, flagged as [`QueryTruncation::CandidateBudget`].
+    pub max_candidates: Option<usize>,
 }
 
 impl Default for HierarchicalQueryBounds {
@@ -122,11 +810,44 @@ impl Default for HierarchicalQueryBounds {
             max_expansions: 128,
             max_open_indices: 16,
             max_open_engrams: 16,
+            min_signature_cosine: None,
+            time_budget: None,
+            max_candidates: None,
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// The budget of [`HierarchicalQueryBounds`] that stopped a query while
+/// promising nodes were still left to expand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryTruncation {
+    TimeBudget,
+    CandidateBudget,
+}
+
+impl std::fmt::Display for QueryTruncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QueryTruncation::TimeBudget => "time budget spent",
+            QueryTruncation::CandidateBudget => "candidate budget spent",
+        })
+    }
+}
+
+/// Hits of [`query_hierarchical_budgeted_with_store`] and how far the
+/// search got.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HierarchicalQueryResults {
+    pub hits: Vec<HierarchicalChunkHit>,
+    /// Set when a budget ran out first: `hits` are then the best among
+    /// the nodes expanded so far, not necessarily the best overall.
+    pub truncated: Option<QueryTruncation>,
+    pub nodes_expanded: usize,
+    pub candidates_scanned: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct HierarchicalChunkHit {
     pub sub_engram_id: String,
     pub chunk_id: usize,
@@ -134,6 +855,173 @@ pub struct HierarchicalChunkHit {
     pub cosine: f64,
 }
 
+/// What became of a node a hierarchical query considered.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "outcome")]
+pub enum NodeOutcome {
+    /// Loaded and searched; `hits` chunks came back from its index.
+    Expanded { hits: usize },
+    /// Skipped with its subtree: the cosine of its signature with the query
+    /// is below [`HierarchicalQueryBounds::min_signature_cosine`].
+    PrunedBySignature { cosine: f64 },
+    /// Ranked outside [`HierarchicalQueryBounds::beam_width`].
+    OutsideBeam,
+    /// Still in the beam when [`HierarchicalQueryBounds::max_expansions`]
+    /// ran out.
+    NotExpanded,
+    /// Still in the beam when the time or candidate budget ran out.
+    OverBudget,
+    /// A child of a node at [`HierarchicalQueryBounds::max_depth`].
+    BeyondMaxDepth,
+    /// The store could not load it.
+    Unavailable,
+}
+
+/// One node in a [`HierarchicalExplanation`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NodeTrace {
+    pub sub_engram_id: String,
+    /// The node whose children it was found among; `None` on level 0.
+    pub parent: Option<String>,
+    pub depth: usize,
+    /// Frontier score: cosine of the query with its signature or root.
+    /// `None` if it was never scored.
+    pub score: Option<f64>,
+    #[serde(flatten)]
+    pub outcome: NodeOutcome,
+}
+
+/// A node's hit on a chunk, in a [`ExplainedHit`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChunkContribution {
+    pub sub_engram_id: String,
+    pub approx_score: i32,
+    pub cosine: f64,
+}
+
+/// A hierarchical query result and how it was reached.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExplainedHit {
+    pub hit: HierarchicalChunkHit,
+    /// Every expanded node that returned this chunk, best first; the
+    /// first is the one `hit` reports.
+    pub contributions: Vec<ChunkContribution>,
+    /// Sub-engram ids from level 0 down to `hit.sub_engram_id`.
+    pub path: Vec<String>,
+}
+
+/// Results of [`explain_hierarchical_query_with_store`] with every node
+/// the search considered, in the order it first met them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HierarchicalExplanation {
+    pub results: Vec<ExplainedHit>,
+    pub nodes: Vec<NodeTrace>,
+    /// See [`HierarchicalQueryResults::truncated`].
+    pub truncated: Option<QueryTruncation>,
+}
+
+impl HierarchicalExplanation {
+    pub fn node(&self, sub_engram_id: &str) -> Option<&NodeTrace> {
+        self.nodes.iter().find(|n| n.sub_engram_id == sub_engram_id)
+    }
+}
+
+/// What a traced query records as it goes; see [`HierarchicalExplanation`].
+#[derive(Default)]
+struct QueryTrace {
+    nodes: Vec<NodeTrace>,
+    by_id: HashMap<String, usize>,
+    contributions: HashMap<usize, Vec<ChunkContribution>>,
+}
+
+impl QueryTrace {
+    fn set(&mut self, id: &str, parent: Option<&str>, depth: usize, score: Option<f64>, outcome: NodeOutcome) {
+        match self.by_id.get(id) {
+            Some(&i) => {
+                let node = &mut self.nodes[i];
+                node.score = score.or(node.score);
+                node.outcome = outcome;
+            }
+            None => {
+                self.by_id.insert(id.to_string(), self.nodes.len());
+                self.nodes.push(NodeTrace {
+                    sub_engram_id: id.to_string(),
+                    parent: parent.map(str::to_string),
+                    depth,
+                    score,
+                    outcome,
+                });
+            }
+        }
+    }
+
+    fn outcome(&mut self, id: &str, outcome: NodeOutcome) {
+        if let Some(&i) = self.by_id.get(id) {
+            self.nodes[i].outcome = outcome;
+        }
+    }
+
+    /// Record `candidates`, children of `parent`: pruned ones by their
+    /// signature cosine, scored ones as waiting in the frontier, and the
+    /// rest as failed to load.
+    fn considered(
+        &mut self,
+        candidates: &[String],
+        parent: Option<&str>,
+        depth: usize,
+        scored: &[FrontierItem],
+        pruned: impl Fn(&str) -> Option<f64>,
+    ) {
+        let scores: HashMap<&str, f64> = scored.iter().map(|f| (f.sub_engram_id.as_str(), f.score)).collect();
+        for id in candidates {
+            match (pruned(id), scores.get(id.as_str())) {
+                (Some(cosine), _) => self.set(id, parent, depth, None, NodeOutcome::PrunedBySignature { cosine }),
+                (None, Some(&score)) => self.set(id, parent, depth, Some(score), NodeOutcome::NotExpanded),
+                (None, None) => self.set(id, parent, depth, None, NodeOutcome::Unavailable),
+            }
+        }
+    }
+
+    fn path_to(&self, id: &str) -> Vec<String> {
+        let mut path = Vec::new();
+        let mut next = Some(id);
+        while let Some(id) = next {
+            // A manifest with a cycle would loop here otherwise.
+            if path.iter().any(|p| p == id) {
+                break;
+            }
+            path.push(id.to_string());
+            next = self.by_id.get(id).and_then(|&i| self.nodes[i].parent.as_deref());
+        }
+        path.reverse();
+        path
+    }
+
+    fn explain(mut self, found: HierarchicalQueryResults) -> HierarchicalExplanation {
+        let results = found
+            .hits
+            .into_iter()
+            .map(|hit| {
+                let mut contributions = self.contributions.remove(&hit.chunk_id).unwrap_or_default();
+                contributions.sort_by(|a, b| {
+                    let best = |c: &ChunkContribution| c.sub_engram_id == hit.sub_engram_id;
+                    best(b)
+                        .cmp(&best(a))
+                        .then_with(|| b.cosine.total_cmp(&a.cosine))
+                        .then_with(|| a.sub_engram_id.cmp(&b.sub_engram_id))
+                });
+                let path = self.path_to(&hit.sub_engram_id);
+                ExplainedHit { hit, contributions, path }
+            })
+            .collect();
+        HierarchicalExplanation {
+            results,
+            nodes: self.nodes,
+            truncated: found.truncated,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct FrontierItem {
     score: f64,
@@ -173,12 +1061,13 @@ impl RemappedInvertedIndex {
         vectors: &HashMap<usize, SparseVec>,
         candidate_k: usize,
         k: usize,
-    ) -> Vec<HierarchicalChunkHit> {
+    ) -> (Vec<HierarchicalChunkHit>, usize) {
         if k == 0 {
-            return Vec::new();
+            return (Vec::new(), 0);
         }
 
         let candidates = self.index.query_top_k(query, candidate_k);
+        let scanned = candidates.len();
         let mut out = Vec::with_capacity(candidates.len().min(k));
         for cand in candidates {
             let Some(&global_id) = self.local_to_global.get(cand.id) else {
@@ -197,14 +1086,16 @@ impl RemappedInvertedIndex {
         });
         out.truncate(k);
 
-        out.into_iter()
+        let hits = out
+            .into_iter()
             .map(|(chunk_id, approx_score, cosine)| HierarchicalChunkHit {
                 sub_engram_id: String::new(),
                 chunk_id,
                 approx_score,
                 cosine,
             })
-            .collect()
+            .collect();
+        (hits, scanned)
     }
 }
 
@@ -233,6 +1124,12 @@ impl<V> LruCache<V> {
     }
 
     fn insert(&mut self, key: String, value: V) -> usize {
+        self.insert_by_heat(key, value, |_| 0)
+    }
+
+    /// [`LruCache::insert`], evicting the entry of the least `heat` among
+    /// the [`EVICTION_WINDOW`] least recently used rather than the oldest.
+    fn insert_by_heat(&mut self, key: String, value: V, heat: impl Fn(&V) -> u64) -> usize {
         if self.cap == 0 {
             return 0;
         }
@@ -248,13 +1145,20 @@ impl<V> LruCache<V> {
 
         let mut evicted = 0usize;
         while self.map.len() > self.cap {
-            if let Some(evict) = self.order.first().cloned() {
-                self.order.remove(0);
-                self.map.remove(&evict);
-                evicted += 1;
-            } else {
+            // The new entry is last in `order`; it stays unless it is alone.
+            let newest = self.order.len() - 1;
+            let coldest = self.order[..newest]
+                .iter()
+                .enumerate()
+                .take(EVICTION_WINDOW)
+                .min_by_key(|&(i, k)| (self.map.get(k).map_or(0, &heat), i))
+                .map(|(i, _)| i);
+            let Some(pos) = coldest.or((!self.order.is_empty()).then_some(0)) else {
                 break;
-            }
+            };
+            let evict = self.order.remove(pos);
+            self.map.remove(&evict);
+            evicted += 1;
         }
 
         evicted
@@ -266,6 +1170,10 @@ impl<V> LruCache<V> {
             self.order.push(k);
         }
     }
+
+    fn contains(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
 }
 
 /// Storage/loader seam for hierarchical sub-engrams.
@@ -274,55 +1182,324 @@ impl<V> LruCache<V> {
 /// every sub-engram is materialized in memory.
 pub trait SubEngramStore {
     fn load(&self, id: &str) -> Option<SubEngram>;
-}
 
-fn escape_sub_engram_id(id: &str) -> String {
-    // Minimal reversible escaping for filenames.
-    // Note: not intended for untrusted input; IDs are internal.
-    id.replace('%', "%25").replace('/', "%2F")
+    /// Load several sub-engrams, in the order of `ids`. Hierarchical
+    /// queries batch the nodes they are about to visit through this, so
+    /// stores with slow reads can fetch them concurrently; the default
+    /// loads one at a time.
+    fn load_many(&self, ids: &[String]) -> Vec<Option<SubEngram>> {
+        ids.iter().map(|id| self.load(id)).collect()
+    }
+
+    /// Hint that `ids` will be loaded soon. Stores with a faster tier can
+    /// move them into it now; the default does nothing.
+    fn prefetch(&self, ids: &[String]) {
+        let _ = ids;
+    }
+
+    /// Check that the store can be reached, e.g. that its directory exists
+    /// or its endpoint answers, for health checks. The default succeeds.
+    fn probe(&self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
-/// Directory-backed store for sub-engrams.
+/// A bounded in-memory tier in front of a slower [`SubEngramStore`], e.g.
+/// a remote or disk-backed one. Loads are answered from memory when they
+/// can, and [`SubEngramStore::prefetch`] fills it ahead of use with one
+/// [`SubEngramStore::load_many`] on the store behind.
 ///
-/// Files are stored as bincode blobs under `${dir}/{escaped_id}.subengram`.
-pub struct DirectorySubEngramStore {
-    dir: PathBuf,
+/// When the tier is full the least recently used sub-engram makes room,
+/// or with [`TieredSubEngramStore::with_access_stats`] the one whose chunks
+/// were accessed least among the few least recently used.
+pub struct TieredSubEngramStore<S> {
+    backing: S,
+    memory: Mutex<LruCache<SubEngram>>,
+    access: Option<Arc<AccessStats>>,
 }
 
-impl DirectorySubEngramStore {
-    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+impl<S: SubEngramStore> TieredSubEngramStore<S> {
+    /// Keep up to `capacity` sub-engrams from `backing` in memory.
+    pub fn new(backing: S, capacity: usize) -> Self {
         Self {
-            dir: dir.as_ref().to_path_buf(),
+            backing,
+            memory: Mutex::new(LruCache::new(capacity)),
+            access: None,
         }
     }
 
-    fn path_for_id(&self, id: &str) -> PathBuf {
-        self.dir.join(format!("{}.subengram", escape_sub_engram_id(id)))
+    /// Keep the sub-engrams whose chunks `access` counts the most accesses
+    /// of in memory; see the [type docs](Self).
+    pub fn with_access_stats(mut self, access: Arc<AccessStats>) -> Self {
+        self.access = Some(access);
+        self
     }
-}
 
-impl SubEngramStore for DirectorySubEngramStore {
-    fn load(&self, id: &str) -> Option<SubEngram> {
-        let path = self.path_for_id(id);
-        let data = fs::read(path).ok()?;
-        let decoded = unwrap_auto(PayloadKind::SubEngramBincode, &data).ok()?;
-        bincode::deserialize(&decoded).ok()
+    pub fn backing(&self) -> &S {
+        &self.backing
     }
-}
 
-/// Save a hierarchical manifest as JSON.
-pub fn save_hierarchical_manifest<P: AsRef<Path>>(
-    hierarchical: &HierarchicalManifest,
-    path: P,
-) -> io::Result<()> {
-    let file = File::create(path)?;
+    /// Whether `id` is held in memory.
+    pub fn is_cached(&self, id: &str) -> bool {
+        self.memory().contains(id)
+    }
 
-    // Serialize deterministically: HashMap iteration order is not stable.
+    fn memory(&self) -> std::sync::MutexGuard<'_, LruCache<SubEngram>> {
+        self.memory.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn remember(&self, id: &str, sub: &SubEngram) {
+        let evicted = match &self.access {
+            Some(access) => self.memory().insert_by_heat(id.to_string(), sub.clone(), |sub| {
+                access.total_heat(sub.chunk_ids.iter().map(|&id| id as u64))
+            }),
+            None => self.memory().insert(id.to_string(), sub.clone()),
+        };
+        for _ in 0..evicted {
+            metrics().inc_sub_cache_eviction();
+        }
+    }
+}
+
+impl<S: SubEngramStore> SubEngramStore for TieredSubEngramStore<S> {
+    fn load(&self, id: &str) -> Option<SubEngram> {
+        if let Some(sub) = self.memory().get(id).cloned() {
+            metrics().inc_sub_cache_hit();
+            return Some(sub);
+        }
+        metrics().inc_sub_cache_miss();
+        let sub = self.backing.load(id)?;
+        self.remember(id, &sub);
+        Some(sub)
+    }
+
+    fn load_many(&self, ids: &[String]) -> Vec<Option<SubEngram>> {
+        let mut out: Vec<Option<SubEngram>> = {
+            let mut memory = self.memory();
+            ids.iter().map(|id| memory.get(id).cloned()).collect()
+        };
+        let missing: Vec<String> = ids
+            .iter()
+            .zip(&out)
+            .filter(|(_, sub)| sub.is_none())
+            .map(|(id, _)| id.clone())
+            .collect();
+        if missing.is_empty() {
+            return out;
+        }
+        let mut loaded = self.backing.load_many(&missing).into_iter();
+        for (id, slot) in ids.iter().zip(&mut out) {
+            if slot.is_none() {
+                *slot = loaded.next().flatten();
+                if let Some(sub) = slot {
+                    self.remember(id, sub);
+                }
+            }
+        }
+        out
+    }
+
+    fn prefetch(&self, ids: &[String]) {
+        let missing: Vec<String> = {
+            let memory = self.memory();
+            let mut seen = HashSet::new();
+            ids.iter()
+                .filter(|id| !memory.contains(id) && seen.insert(id.as_str()))
+                .take(memory.cap)
+                .cloned()
+                .collect()
+        };
+        for (id, loaded) in missing.iter().zip(self.backing.load_many(&missing)) {
+            if let Some(sub) = loaded {
+                self.remember(id, &sub);
+            }
+        }
+    }
+
+    fn probe(&self) -> io::Result<()> {
+        self.backing.probe()
+    }
+}
+
+fn escape_sub_engram_id(id: &str) -> String {
+    // Minimal reversible escaping for filenames.
+    // Note: not intended for untrusted input; IDs are internal.
+    id.replace('%', "%25").replace('/', "%2F")
+}
+
+/// Default number of threads [`DirectorySubEngramStore`] reads with.
+pub const DEFAULT_SUB_ENGRAM_LOAD_THREADS: usize = 8;
+
+/// Directory-backed store for sub-engrams.
+///
+/// Files are stored as bincode blobs under `${dir}/{escaped_id}.subengram`.
+/// Batches requested through [`SubEngramStore::load_many`] are read and
+/// decoded on up to [`DirectorySubEngramStore::with_threads`] threads.
+///
+/// Bytes are trusted as read unless digests are given with
+/// [`DirectorySubEngramStore::with_digests`]; then every load is checked,
+/// and [`DirectorySubEngramStore::verify_all`] checks the whole store up
+/// front.
+pub struct DirectorySubEngramStore {
+    dir: PathBuf,
+    threads: usize,
+    digests: BTreeMap<String, SubEngramDigest>,
+}
+
+impl DirectorySubEngramStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            threads: DEFAULT_SUB_ENGRAM_LOAD_THREADS,
+            digests: BTreeMap::new(),
+        }
+    }
+
+    /// Read batches on at most `threads` threads; 1 reads serially.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Check each sub-engram against the digest `hierarchical` records for
+    /// it as it is loaded. A sub-engram that fails is not returned by
+    /// [`SubEngramStore::load`], so queries skip it;
+    /// [`DirectorySubEngramStore::try_load`] says why.
+    pub fn with_digests(mut self, hierarchical: &HierarchicalManifest) -> Self {
+        self.digests = hierarchical.digests.clone();
+        self
+    }
+
+    /// Load `id`, failing with [`EmbrError::CorruptSubEngram`] if it is
+    /// missing, does not decode or does not match its recorded digest.
+    pub fn try_load(&self, id: &str) -> Result<SubEngram> {
+        let digest = self.digest_for(id);
+        match fs::read(self.path_for_id(id)) {
+            Ok(data) => decode_sub_engram(id, &data, digest),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(sub_engram_corruption(id, digest, SubEngramFault::Missing))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Load every sub-engram with a recorded digest and check it, returning
+    /// how many were checked or the first that fails.
+    pub fn verify_all(&self) -> Result<usize> {
+        for id in self.digests.keys() {
+            self.try_load(id)?;
+        }
+        Ok(self.digests.len())
+    }
+
+    pub(crate) fn path_for_id(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.subengram", escape_sub_engram_id(id)))
+    }
+
+    pub(crate) fn digest_for(&self, id: &str) -> Option<&SubEngramDigest> {
+        self.digests.get(id)
+    }
+}
+
+fn sub_engram_corruption(id: &str, digest: Option<&SubEngramDigest>, fault: SubEngramFault) -> EmbrError {
+    EmbrError::CorruptSubEngram(SubEngramCorruption {
+        id: id.to_string(),
+        chunk_range: digest.and_then(|d| d.chunk_range),
+        fault,
+    })
+}
+
+/// Decode the stored blob of sub-engram `id`, enveloped or legacy raw
+/// bincode, checking it against `digest` if there is one.
+pub(crate) fn decode_sub_engram(id: &str, data: &[u8], digest: Option<&SubEngramDigest>) -> Result<SubEngram> {
+    let malformed = |e: String| sub_engram_corruption(id, digest, SubEngramFault::Malformed(e));
+    let decoded = unwrap_auto(PayloadKind::SubEngramBincode, data).map_err(|e| malformed(e.to_string()))?;
+    let sub = bincode::deserialize(&decoded).map_err(|e| malformed(e.to_string()))?;
+    if let Some(digest) = digest {
+        let found = blake3::hash(&decoded).to_hex().to_string();
+        if found != digest.blake3 {
+            let expected = digest.blake3.clone();
+            return Err(sub_engram_corruption(id, Some(digest), SubEngramFault::DigestMismatch { expected, found }));
+        }
+    }
+    Ok(sub)
+}
+
+impl SubEngramStore for DirectorySubEngramStore {
+    fn load(&self, id: &str) -> Option<SubEngram> {
+        self.try_load(id).ok()
+    }
+
+    fn load_many(&self, ids: &[String]) -> Vec<Option<SubEngram>> {
+        let threads = self.threads.min(ids.len());
+        if threads <= 1 {
+            return ids.iter().map(|id| self.load(id)).collect();
+        }
+        // Thread t reads ids t, t + threads, ...; results are put back in order.
+        let mut out: Vec<Option<SubEngram>> = vec![None; ids.len()];
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    scope.spawn(move || {
+                        (t..ids.len())
+                            .step_by(threads)
+                            .map(|i| (i, self.load(&ids[i])))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                for (i, sub) in handle.join().expect("sub-engram loader panicked") {
+                    out[i] = sub;
+                }
+            }
+        });
+        out
+    }
+
+    fn probe(&self) -> io::Result<()> {
+        if std::fs::metadata(&self.dir)?.is_dir() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("{} is not a directory", self.dir.display())))
+        }
+    }
+}
+
+/// Unwrap I/O failures that bincode hit while streaming, so typed errors
+/// from the envelope layer (e.g. `EnvelopeCorruption`) stay downcastable.
+pub(crate) fn bincode_io_error(err: bincode::ErrorKind) -> io::Error {
+    match err {
+        bincode::ErrorKind::Io(e) => e,
+        other => io::Error::other(other),
+    }
+}
+
+/// Decode an append-log chunk record: the codebook vector and correction.
+pub(crate) fn decode_log_chunk(payload: &[u8]) -> io::Result<(Option<SparseVec>, Option<ChunkCorrection>)> {
+    bincode::deserialize(payload).map_err(|e| bincode_io_error(*e))
+}
+
+/// Decode an append-log meta record: the root vector and correction totals.
+pub(crate) fn decode_log_meta(payload: &[u8]) -> io::Result<(SparseVec, CorrectionTotals)> {
+    bincode::deserialize(payload).map_err(|e| bincode_io_error(*e))
+}
+
+/// Save a hierarchical manifest as JSON.
+pub fn save_hierarchical_manifest<P: AsRef<Path>>(
+    hierarchical: &HierarchicalManifest,
+    path: P,
+) -> Result<()> {
+    let file = File::create(path)?;
+
+    // Serialize deterministically: HashMap iteration order is not stable.
     #[derive(Serialize)]
-    struct StableHierarchicalManifest {
+    struct StableHierarchicalManifest<'a> {
         version: u32,
         levels: Vec<ManifestLevel>,
         sub_engrams: BTreeMap<String, SubEngram>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        digests: &'a BTreeMap<String, SubEngramDigest>,
     }
 
     let mut levels = hierarchical.levels.clone();
@@ -342,6 +1519,7 @@ pub fn save_hierarchical_manifest<P: AsRef<Path>>(
         version: hierarchical.version,
         levels,
         sub_engrams,
+        digests: &hierarchical.digests,
     };
 
     serde_json::to_writer_pretty(file, &stable)?;
@@ -349,7 +1527,7 @@ pub fn save_hierarchical_manifest<P: AsRef<Path>>(
 }
 
 /// Load a hierarchical manifest from JSON.
-pub fn load_hierarchical_manifest<P: AsRef<Path>>(path: P) -> io::Result<HierarchicalManifest> {
+pub fn load_hierarchical_manifest<P: AsRef<Path>>(path: P) -> Result<HierarchicalManifest> {
     let file = File::open(path)?;
     let manifest = serde_json::from_reader(file)?;
     Ok(manifest)
@@ -359,7 +1537,7 @@ pub fn load_hierarchical_manifest<P: AsRef<Path>>(path: P) -> io::Result<Hierarc
 pub fn save_sub_engrams_dir<P: AsRef<Path>>(
     sub_engrams: &HashMap<String, SubEngram>,
     dir: P,
-) -> io::Result<()> {
+) -> Result<()> {
     save_sub_engrams_dir_with_options(sub_engrams, dir, BinaryWriteOptions::default())
 }
 
@@ -368,7 +1546,7 @@ pub fn save_sub_engrams_dir_with_options<P: AsRef<Path>>(
     sub_engrams: &HashMap<String, SubEngram>,
     dir: P,
     opts: BinaryWriteOptions,
-) -> io::Result<()> {
+) -> Result<()> {
     let dir = dir.as_ref();
     fs::create_dir_all(dir)?;
 
@@ -419,10 +1597,74 @@ fn get_cached_sub_engram(
     Some(loaded)
 }
 
+/// Load the sub-engrams among `ids` that `cache` lacks with one
+/// [`SubEngramStore::load_many`] call. At most as many as the cache holds
+/// are loaded, so none is evicted before the caller gets to it.
+fn prefetch_sub_engrams(cache: &mut LruCache<SubEngram>, store: &impl SubEngramStore, ids: &[String]) {
+    let mut seen = HashSet::new();
+    let missing: Vec<String> = ids
+        .iter()
+        .filter(|id| !cache.contains(id) && seen.insert(id.as_str()))
+        .take(cache.cap)
+        .cloned()
+        .collect();
+    // A single load is left to `get_cached_sub_engram`.
+    if missing.len() < 2 {
+        return;
+    }
+    for (id, loaded) in missing.iter().zip(store.load_many(&missing)) {
+        metrics().inc_sub_cache_miss();
+        if let Some(sub) = loaded {
+            for _ in 0..cache.insert(id.clone(), sub) {
+                metrics().inc_sub_cache_eviction();
+            }
+        }
+    }
+}
+
+/// Score the nodes `ids` against `query` for the frontier: by signature where
+/// there is one, else by the root of the sub-engram, fetched in windows of
+/// `window`. Nodes that fail to load are dropped.
+fn score_frontier(
+    cache: &mut LruCache<SubEngram>,
+    store: &impl SubEngramStore,
+    signatures: &HashMap<&str, &SparseVec>,
+    query: &SparseVec,
+    ids: &[String],
+    depth: usize,
+    window: usize,
+) -> Vec<FrontierItem> {
+    let unsigned: Vec<String> = ids.iter().filter(|id| !signatures.contains_key(id.as_str())).cloned().collect();
+    let mut scored = Vec::with_capacity(ids.len());
+    let mut fetched = 0;
+    for id in ids {
+        let score = match signatures.get(id.as_str()) {
+            Some(signature) => query.cosine(signature),
+            None => {
+                if fetched % window == 0 {
+                    prefetch_sub_engrams(cache, store, &unsigned[fetched..(fetched + window).min(unsigned.len())]);
+                }
+                fetched += 1;
+                let Some(sub) = get_cached_sub_engram(cache, store, id) else {
+                    continue;
+                };
+                query.cosine(&sub.root)
+            }
+        };
+        scored.push(FrontierItem {
+            score,
+            sub_engram_id: id.clone(),
+            depth,
+        });
+    }
+    scored
+}
+
 /// Query a hierarchical manifest by selectively unfolding only promising sub-engrams.
 ///
-/// This performs a beam-limited traversal over `hierarchical.sub_engrams`.
-/// At each expanded node, it builds (and LRU-caches) an inverted index over the
+/// This performs a beam-limited traversal over `hierarchical.sub_engrams`,
+/// ranking nodes by their [`ManifestItem::signature`] where the manifest has
+/// one. At each expanded node, it builds (and LRU-caches) an inverted index over the
 /// node-local `chunk_ids` subset of `codebook`, then reranks by exact cosine.
 pub fn query_hierarchical_codebook(
     hierarchical: &HierarchicalManifest,
@@ -435,6 +1677,9 @@ pub fn query_hierarchical_codebook(
 }
 
 /// Store-backed variant of `query_hierarchical_codebook` that supports on-demand sub-engram loading.
+///
+/// With a time or candidate budget in `bounds` the hits may be cut short;
+/// [`query_hierarchical_budgeted_with_store`] says whether they were.
 pub fn query_hierarchical_codebook_with_store(
     hierarchical: &HierarchicalManifest,
     store: &impl SubEngramStore,
@@ -442,27 +1687,107 @@ pub fn query_hierarchical_codebook_with_store(
     query: &SparseVec,
     bounds: &HierarchicalQueryBounds,
 ) -> Vec<HierarchicalChunkHit> {
+    search_hierarchical_codebook(hierarchical, store, codebook, query, bounds, None).hits
+}
+
+/// [`query_hierarchical_codebook`] for interactive use: with
+/// [`HierarchicalQueryBounds::time_budget`] or
+/// [`HierarchicalQueryBounds::max_candidates`] set, it returns the best hits
+/// found once a budget runs out instead of finishing the search, and flags
+/// them as truncated.
+pub fn query_hierarchical_budgeted(
+    hierarchical: &HierarchicalManifest,
+    codebook: &HashMap<usize, SparseVec>,
+    query: &SparseVec,
+    bounds: &HierarchicalQueryBounds,
+) -> HierarchicalQueryResults {
+    let store = InMemorySubEngramStore::new(&hierarchical.sub_engrams);
+    query_hierarchical_budgeted_with_store(hierarchical, &store, codebook, query, bounds)
+}
+
+/// Store-backed variant of [`query_hierarchical_budgeted`].
+pub fn query_hierarchical_budgeted_with_store(
+    hierarchical: &HierarchicalManifest,
+    store: &impl SubEngramStore,
+    codebook: &HashMap<usize, SparseVec>,
+    query: &SparseVec,
+    bounds: &HierarchicalQueryBounds,
+) -> HierarchicalQueryResults {
+    search_hierarchical_codebook(hierarchical, store, codebook, query, bounds, None)
+}
+
+/// [`query_hierarchical_codebook`] that also explains its results: which
+/// nodes returned each chunk, with what cosine, the path down to the best
+/// of them, and what became of every node the search considered.
+pub fn explain_hierarchical_query(
+    hierarchical: &HierarchicalManifest,
+    codebook: &HashMap<usize, SparseVec>,
+    query: &SparseVec,
+    bounds: &HierarchicalQueryBounds,
+) -> HierarchicalExplanation {
+    let store = InMemorySubEngramStore::new(&hierarchical.sub_engrams);
+    explain_hierarchical_query_with_store(hierarchical, &store, codebook, query, bounds)
+}
+
+/// Store-backed variant of [`explain_hierarchical_query`]. The results are
+/// those [`query_hierarchical_codebook_with_store`] returns.
+pub fn explain_hierarchical_query_with_store(
+    hierarchical: &HierarchicalManifest,
+    store: &impl SubEngramStore,
+    codebook: &HashMap<usize, SparseVec>,
+    query: &SparseVec,
+    bounds: &HierarchicalQueryBounds,
+) -> HierarchicalExplanation {
+    let mut trace = QueryTrace::default();
+    let hits = search_hierarchical_codebook(hierarchical, store, codebook, query, bounds, Some(&mut trace));
+    trace.explain(hits)
+}
+
+fn search_hierarchical_codebook(
+    hierarchical: &HierarchicalManifest,
+    store: &impl SubEngramStore,
+    codebook: &HashMap<usize, SparseVec>,
+    query: &SparseVec,
+    bounds: &HierarchicalQueryBounds,
+    mut trace: Option<&mut QueryTrace>,
+) -> HierarchicalQueryResults {
+    let mut results = HierarchicalQueryResults::default();
     if bounds.k == 0 || hierarchical.levels.is_empty() {
-        return Vec::new();
+        return results;
     }
 
-    #[cfg(feature = "metrics")]
     let start = Instant::now();
 
     let mut sub_cache: LruCache<SubEngram> = LruCache::new(bounds.max_open_engrams);
     let mut index_cache: LruCache<RemappedInvertedIndex> = LruCache::new(bounds.max_open_indices);
 
+    // Sub-engrams are fetched a batch ahead of use (see `prefetch_sub_engrams`):
+    // level-0 nodes and each node's children in windows of the cache size,
+    // and the head of the frontier before it is expanded.
+    let window = bounds.max_open_engrams.max(1);
+
+    // Nodes are scored by their directory signature where the manifest has
+    // one, so pruned subtrees are never loaded, and by their root otherwise.
+    let signatures: HashMap<&str, &SparseVec> = hierarchical
+        .levels
+        .iter()
+        .flat_map(|level| &level.items)
+        .filter_map(|item| Some((item.sub_engram_id.as_str(), item.signature.as_ref()?)))
+        .collect();
+    // Cosine of a node's signature with the query, if that prunes it.
+    let pruned = |id: &str| {
+        let cosine = query.cosine(signatures.get(id)?);
+        bounds.min_signature_cosine.is_some_and(|min| cosine < min).then_some(cosine)
+    };
+    let kept = |id: &String| pruned(id).is_none();
+
     let mut frontier: Vec<FrontierItem> = Vec::new();
     if let Some(level0) = hierarchical.levels.first() {
-        for item in &level0.items {
-            let Some(sub) = get_cached_sub_engram(&mut sub_cache, store, &item.sub_engram_id) else {
-                continue;
-            };
-            frontier.push(FrontierItem {
-                score: query.cosine(&sub.root),
-                sub_engram_id: item.sub_engram_id.clone(),
-                depth: 0,
-            });
+        let ids: Vec<String> = level0.items.iter().map(|it| &it.sub_engram_id).filter(|id| kept(id)).cloned().collect();
+        frontier = score_frontier(&mut sub_cache, store, &signatures, query, &ids, 0, window);
+        if let Some(trace) = trace.as_deref_mut() {
+            let all: Vec<String> = level0.items.iter().map(|it| it.sub_engram_id.clone()).collect();
+            trace.considered(&all, None, 0, &frontier, pruned);
         }
     }
 
@@ -472,6 +1797,9 @@ pub fn query_hierarchical_codebook_with_store(
             .then_with(|| a.sub_engram_id.cmp(&b.sub_engram_id))
     });
     if frontier.len() > bounds.beam_width {
+        if let Some(trace) = trace.as_deref_mut() {
+            frontier[bounds.beam_width..].iter().for_each(|f| trace.outcome(&f.sub_engram_id, NodeOutcome::OutsideBeam));
+        }
         frontier.truncate(bounds.beam_width);
     }
 
@@ -481,9 +1809,36 @@ pub fn query_hierarchical_codebook_with_store(
     let mut best_by_chunk: HashMap<usize, HierarchicalChunkHit> = HashMap::new();
 
     while !frontier.is_empty() && expansions < bounds.max_expansions {
+        // Budgets are checked between nodes, so a node once started is
+        // always searched in full, and the time budget lets the best node
+        // through however little of it there is.
+        let remaining_candidates = bounds.max_candidates.map(|max| max.saturating_sub(results.candidates_scanned));
+        let over = if expansions > 0 && bounds.time_budget.is_some_and(|budget| start.elapsed() >= budget) {
+            Some(QueryTruncation::TimeBudget)
+        } else if remaining_candidates == Some(0) {
+            Some(QueryTruncation::CandidateBudget)
+        } else {
+            None
+        };
+        if let Some(reason) = over {
+            results.truncated = Some(reason);
+            if let Some(trace) = trace.as_deref_mut() {
+                frontier.iter().for_each(|f| trace.outcome(&f.sub_engram_id, NodeOutcome::OverBudget));
+            }
+            break;
+        }
+
+        if !sub_cache.contains(&frontier[0].sub_engram_id) {
+            let ahead = bounds.beam_width.min(bounds.max_expansions - expansions);
+            let ids: Vec<String> = frontier.iter().take(ahead).map(|f| f.sub_engram_id.clone()).collect();
+            prefetch_sub_engrams(&mut sub_cache, store, &ids);
+        }
         let node = frontier.remove(0);
 
         let Some(sub) = get_cached_sub_engram(&mut sub_cache, store, &node.sub_engram_id) else {
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.outcome(&node.sub_engram_id, NodeOutcome::Unavailable);
+            }
             continue;
         };
 
@@ -505,10 +1860,22 @@ pub fn query_hierarchical_codebook_with_store(
                 .expect("index cache insert")
         };
 
-        let mut local_hits = idx.query_top_k_reranked(query, codebook, bounds.candidate_k, bounds.k);
+        let candidate_k = remaining_candidates.map_or(bounds.candidate_k, |left| bounds.candidate_k.min(left));
+        let (mut local_hits, scanned) = idx.query_top_k_reranked(query, codebook, candidate_k, bounds.k);
+        results.candidates_scanned += scanned;
         for hit in &mut local_hits {
             hit.sub_engram_id = node.sub_engram_id.clone();
         }
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.outcome(&node.sub_engram_id, NodeOutcome::Expanded { hits: local_hits.len() });
+            for hit in &local_hits {
+                trace.contributions.entry(hit.chunk_id).or_default().push(ChunkContribution {
+                    sub_engram_id: hit.sub_engram_id.clone(),
+                    approx_score: hit.approx_score,
+                    cosine: hit.cosine,
+                });
+            }
+        }
 
         for hit in local_hits {
             match best_by_chunk.get(&hit.chunk_id) {
@@ -529,20 +1896,20 @@ pub fn query_hierarchical_codebook_with_store(
         }
 
         if node.depth >= bounds.max_depth {
+            if let Some(trace) = trace.as_deref_mut() {
+                for child in &sub.children {
+                    trace.set(child, Some(&node.sub_engram_id), node.depth + 1, None, NodeOutcome::BeyondMaxDepth);
+                }
+            }
             continue;
         }
 
-        let children = sub.children.clone();
-        for child_id in &children {
-            let Some(child) = get_cached_sub_engram(&mut sub_cache, store, child_id) else {
-                continue;
-            };
-            frontier.push(FrontierItem {
-                score: query.cosine(&child.root),
-                sub_engram_id: child_id.clone(),
-                depth: node.depth + 1,
-            });
+        let children: Vec<String> = sub.children.iter().filter(|id| kept(id)).cloned().collect();
+        let scored = score_frontier(&mut sub_cache, store, &signatures, query, &children, node.depth + 1, window);
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.considered(&sub.children, Some(&node.sub_engram_id), node.depth + 1, &scored, pruned);
         }
+        frontier.extend(scored);
 
         frontier.sort_by(|a, b| {
             b.score
@@ -550,6 +1917,9 @@ pub fn query_hierarchical_codebook_with_store(
                 .then_with(|| a.sub_engram_id.cmp(&b.sub_engram_id))
         });
         if frontier.len() > bounds.beam_width {
+            if let Some(trace) = trace.as_deref_mut() {
+                frontier[bounds.beam_width..].iter().for_each(|f| trace.outcome(&f.sub_engram_id, NodeOutcome::OutsideBeam));
+            }
             frontier.truncate(bounds.beam_width);
         }
     }
@@ -567,7 +1937,91 @@ pub fn query_hierarchical_codebook_with_store(
     #[cfg(feature = "metrics")]
     metrics().record_hier_query(start.elapsed());
 
-    out
+    results.hits = out;
+    results.nodes_expanded = expansions;
+    results
+}
+
+/// Subtrees on which two hierarchical manifests differ, from
+/// [`diff_hierarchical_manifests`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubtreeDiff {
+    /// Deepest paths whose signature differs, ascending.
+    pub changed: Vec<String>,
+    /// Paths only the newer manifest has, without their descendants.
+    pub added: Vec<String>,
+    /// Paths only the older manifest has, without their descendants.
+    pub removed: Vec<String>,
+    /// Nodes compared to find them.
+    pub nodes_compared: usize,
+}
+
+impl SubtreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compare two snapshots by their directory signatures, top down.
+///
+/// A node whose [`ManifestItem::signature`] is the same on both sides is
+/// taken as unchanged with its whole subtree, which is not visited. Nodes
+/// without a signature on either side are descended into, so manifests built
+/// before signatures were recorded are compared in full. Changes small
+/// enough to vanish in a bundle go unnoticed.
+pub fn diff_hierarchical_manifests(old: &HierarchicalManifest, new: &HierarchicalManifest) -> SubtreeDiff {
+    // Items by path, and each item's child paths (shard children have none).
+    fn index(h: &HierarchicalManifest) -> HashMap<&str, (&ManifestItem, Vec<&str>)> {
+        let paths: HashMap<&str, &str> = h
+            .levels
+            .iter()
+            .flat_map(|level| &level.items)
+            .map(|item| (item.sub_engram_id.as_str(), item.path.as_str()))
+            .collect();
+        h.levels
+            .iter()
+            .flat_map(|level| &level.items)
+            .map(|item| {
+                let children = h.sub_engrams.get(&item.sub_engram_id).map_or(Vec::new(), |sub| {
+                    sub.children.iter().filter_map(|c| paths.get(c.as_str()).copied()).collect()
+                });
+                (item.path.as_str(), (item, children))
+            })
+            .collect()
+    }
+    fn level0(h: &HierarchicalManifest) -> Vec<&str> {
+        h.levels.first().map_or(Vec::new(), |l| l.items.iter().map(|i| i.path.as_str()).collect())
+    }
+
+    let (old_index, new_index) = (index(old), index(new));
+    let mut diff = SubtreeDiff::default();
+    let mut pending: Vec<(Vec<&str>, Vec<&str>)> = vec![(level0(old), level0(new))];
+    while let Some((old_paths, new_paths)) = pending.pop() {
+        let paths: BTreeSet<&str> = old_paths.into_iter().chain(new_paths).collect();
+        for path in paths {
+            let (Some((a, a_children)), Some((b, b_children))) = (old_index.get(path), new_index.get(path)) else {
+                if old_index.contains_key(path) {
+                    diff.removed.push(path.to_string());
+                } else if new_index.contains_key(path) {
+                    diff.added.push(path.to_string());
+                }
+                continue;
+            };
+            diff.nodes_compared += 1;
+            if a.signature.is_some() && a.signature == b.signature {
+                continue;
+            }
+            if a_children.is_empty() && b_children.is_empty() {
+                diff.changed.push(path.to_string());
+            } else {
+                pending.push((a_children.clone(), b_children.clone()));
+            }
+        }
+    }
+    diff.changed.sort();
+    diff.added.sort();
+    diff.removed.sort();
+    diff
 }
 
 /// Unified manifest enum for backward compatibility
@@ -584,64 +2038,695 @@ impl From<Manifest> for UnifiedManifest {
 }
 
 /// Engram: holographic encoding of a filesystem with correction guarantee
-#[derive(Serialize, Deserialize)]
+#[derive(Deserialize)]
+#[serde(from = "StoredEngram")]
 pub struct Engram {
     pub root: SparseVec,
     pub codebook: HashMap<usize, SparseVec>,
     /// Correction store for 100% reconstruction guarantee
-    #[serde(default)]
     pub corrections: CorrectionStore,
+    /// Vote counts behind `root` once [`Engram::count_root`] was called, so
+    /// removed chunks are subtracted instead of re-bundling the codebook.
+    /// Bincode and protobuf engram files keep them; the other formats store
+    /// the root alone.
+    pub root_counts: Option<CountedBundle>,
+    /// Trit depths adaptive precision raised while encoding the chunks
+    /// differentially, so decoding uses the precision encoding did; see
+    /// [`EmbrFS::adapt_precision`]. Kept by the same formats as
+    /// `root_counts`.
+    pub precision: Option<DepthMap>,
+    /// Chunks also held in `codebook` that saving stores against a basis;
+    /// see [`EmbrFS::encode_differential`]. Kept by bincode engram files.
+    pub differential: Option<DifferentialChunks>,
+    /// Role labels and the values files and directories are bound to under
+    /// them; see [`crate::role_schema`]. Kept by bincode engram files.
+    pub schema: Option<RoleSchema>,
 }
 
-impl Engram {
-    /// Build a reusable inverted index over the codebook.
-    ///
-    /// This is useful when issuing multiple queries (e.g., shift-sweeps) and you
-    /// want to avoid rebuilding the index each time.
-    pub fn build_codebook_index(&self) -> TernaryInvertedIndex {
-        TernaryInvertedIndex::build_from_map(&self.codebook)
-    }
+/// An [`Engram`] as saved: the chunks in `differential` are missing from
+/// `codebook` until decoded into it.
+#[derive(Deserialize)]
+struct StoredEngram {
+    root: SparseVec,
+    codebook: HashMap<usize, SparseVec>,
+    #[serde(default)]
+    corrections: CorrectionStore,
+    #[serde(default, deserialize_with = "trailing_option")]
+    root_counts: Option<CountedBundle>,
+    #[serde(default, deserialize_with = "trailing_option")]
+    precision: Option<DepthMap>,
+    #[serde(default, deserialize_with = "trailing_option")]
+    differential: Option<DifferentialChunks>,
+    #[serde(default, deserialize_with = "trailing_option")]
+    schema: Option<RoleSchema>,
+}
 
-    /// Query the codebook using a pre-built inverted index.
-    pub fn query_codebook_with_index(
-        &self,
-        index: &TernaryInvertedIndex,
-        query: &SparseVec,
-        candidate_k: usize,
-        k: usize,
-    ) -> Vec<RerankedResult> {
-        if k == 0 || self.codebook.is_empty() {
-            return Vec::new();
+impl From<StoredEngram> for Engram {
+    fn from(stored: StoredEngram) -> Self {
+        let mut codebook = stored.codebook;
+        if let Some(differential) = &stored.differential {
+            differential.decode_into(stored.precision.as_ref(), &mut codebook);
+        }
+        Engram {
+            root: stored.root,
+            codebook,
+            corrections: stored.corrections,
+            root_counts: stored.root_counts,
+            precision: stored.precision,
+            differential: stored.differential,
+            schema: stored.schema,
         }
-        index.query_top_k_reranked(query, &self.codebook, candidate_k, k)
     }
+}
 
-    /// Query the engram's codebook for chunks most similar to `query`.
-    ///
-    /// This builds an inverted index over the codebook for sub-linear candidate
-    /// generation, then reranks those candidates using exact cosine similarity.
-    pub fn query_codebook(&self, query: &SparseVec, k: usize) -> Vec<RerankedResult> {
-        if k == 0 || self.codebook.is_empty() {
-            return Vec::new();
+impl Serialize for Engram {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct SortedCodebook<'a>(&'a HashMap<usize, SparseVec>);
+        impl Serialize for SortedCodebook<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                reproducible::sorted_map(self.0, serializer)
+            }
         }
 
-        // Simple heuristic: rerank a moderately-sized candidate set.
-        let candidate_k = (k.saturating_mul(10)).max(50);
-        let index = self.build_codebook_index();
-        self.query_codebook_with_index(&index, query, candidate_k, k)
+        // Chunks changed since they were encoded are saved raw.
+        let differential = self
+            .differential
+            .as_ref()
+            .map(|d| d.current(self.precision.as_ref(), &self.codebook))
+            .filter(|d| !d.chunks.is_empty());
+
+        // Bincode reads the trailing sections by position: each is written,
+        // even as `None`, whenever a later one is.
+        let trailing = if self.schema.is_some() {
+            4
+        } else if differential.is_some() {
+            3
+        } else if self.precision.is_some() {
+            2
+        } else {
+            self.root_counts.is_some() as usize
+        };
+        let mut state = serializer.serialize_struct("Engram", 3 + trailing)?;
+        state.serialize_field("root", &self.root)?;
+        match &differential {
+            Some(d) => {
+                let raw: BTreeMap<&usize, &SparseVec> =
+                    self.codebook.iter().filter(|(id, _)| !d.chunks.contains_key(id)).collect();
+                state.serialize_field("codebook", &raw)?;
+            }
+            None => state.serialize_field("codebook", &SortedCodebook(&self.codebook))?,
+        }
+        state.serialize_field("corrections", &self.corrections)?;
+        if trailing >= 1 {
+            state.serialize_field("root_counts", &self.root_counts)?;
+        } else {
+            state.skip_field("root_counts")?;
+        }
+        if trailing >= 2 {
+            state.serialize_field("precision", &self.precision)?;
+        } else {
+            state.skip_field("precision")?;
+        }
+        if trailing >= 3 {
+            state.serialize_field("differential", &differential)?;
+        } else {
+            state.skip_field("differential")?;
+        }
+        if trailing >= 4 {
+            state.serialize_field("schema", &self.schema)?;
+        } else {
+            state.skip_field("schema")?;
+        }
+        state.end()
     }
 }
 
-/// EmbrFS - Holographic Filesystem with Guaranteed Reconstruction
-///
-/// # 100% Reconstruction Guarantee
-///
-/// EmbrFS guarantees bit-perfect file reconstruction through a layered approach:
-///
-/// 1. **Encode**: Data chunks → SparseVec via reversible encoding
-/// 2. **Verify**: Immediately decode and compare to original
-/// 3. **Correct**: Store minimal correction if any difference exists
-/// 4. **Extract**: Decode + apply correction = exact original bytes
+/// Bundle `vec` into `root`, thinning the result back to `max_nonzero`
+/// trits when it grows past.
+fn thinned_bundle(root: &SparseVec, vec: &SparseVec, max_nonzero: usize) -> SparseVec {
+    let bundled = root.bundle(vec);
+    if bundled.pos.len() + bundled.neg.len() > max_nonzero {
+        bundled.thin(max_nonzero)
+    } else {
+        bundled
+    }
+}
+
+/// Engrams written before [`Engram::root_counts`] existed end where it would
+/// start, which bincode reports as an error. Counts are only a summary of
+/// the codebook, so ones that fail to decode are treated as absent too.
+fn trailing_option<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer).unwrap_or(None))
+}
+
+impl Engram {
+    /// Check the invariants every decoder and query here relies on: sorted,
+    /// in-range vector indices, and record counts within `limits`. Run on
+    /// every engram loaded from disk.
+    pub fn validate(&self, limits: &LoadLimits) -> Result<()> {
+        let corrections = self.corrections.len();
+        for count in [self.codebook.len(), corrections] {
+            if count > limits.max_chunks {
+                return Err(EmbrError::CorruptEngram(EngramCorruption::TooManyChunks {
+                    count,
+                    limit: limits.max_chunks,
+                }));
+            }
+        }
+        let invalid = |chunk, error| EmbrError::CorruptEngram(EngramCorruption::InvalidVector { chunk, error });
+        self.root.validate(DIM).map_err(|e| invalid(None, e))?;
+        for (&id, vec) in &self.codebook {
+            vec.validate(DIM).map_err(|e| invalid(Some(id), e))?;
+        }
+        if let Some(counts) = &self.root_counts {
+            let bad = |msg: String| EmbrError::CorruptEngram(EngramCorruption::InvalidRootCounts(msg));
+            if counts.len() != DIM {
+                return Err(bad(format!("counts cover {} trits, expected {}", counts.len(), DIM)));
+            }
+            counts.validate().map_err(bad)?;
+            let majority = counts.finalize().to_sparse();
+            if majority.pos != self.root.pos || majority.neg != self.root.neg {
+                return Err(bad("counts disagree with the root".into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep exact vote counts behind the root from now on (see
+    /// [`CountedBundle`]). The root becomes the majority vote of the
+    /// codebook, and removing chunks subtracts their votes instead of
+    /// re-bundling everything left.
+    pub fn count_root(&mut self) {
+        self.root_counts = Some(CountedBundle::new(DIM));
+        self.rebuild_root();
+    }
+
+    /// Take one chunk vector out of a counted root. Returns false, leaving
+    /// the root unchanged, if the root is not counted or `vec` is not part
+    /// of it.
+    pub fn subtract_from_root(&mut self, vec: &SparseVec) -> bool {
+        let Some(counts) = &mut self.root_counts else {
+            return false;
+        };
+        if !counts.subtract(&BitslicedTritVec::from_sparse(vec, DIM)) {
+            return false;
+        }
+        self.root = counts.finalize().to_sparse();
+        true
+    }
+
+    /// Add a newly stored chunk vector to the root. A counted root takes
+    /// its votes whatever the strategy; otherwise `strategy` decides, with
+    /// [`RootStrategy::Tree`] left to the caller (see [`TreeBundle`]).
+    pub(crate) fn bundle_into_root(&mut self, vec: &SparseVec, strategy: &RootStrategy) {
+        if self.root_counts.is_none() && *strategy == RootStrategy::CarrySave {
+            self.count_root();
+        }
+        match (&mut self.root_counts, strategy) {
+            (Some(counts), _) => {
+                counts.add(&BitslicedTritVec::from_sparse(vec, DIM));
+                self.root = counts.finalize().to_sparse();
+            }
+            (None, RootStrategy::Thinned { max_nonzero }) => self.root = thinned_bundle(&self.root, vec, *max_nonzero),
+            (None, _) => self.root = self.root.bundle(vec),
+        }
+    }
+
+    /// Take the vectors of chunks just dropped from the codebook out of the
+    /// root: their votes are subtracted when the root is counted, otherwise
+    /// the root is rebuilt from what the codebook still holds.
+    pub(crate) fn unbundle_from_root(&mut self, removed: &[SparseVec], strategy: &RootStrategy) {
+        if let Some(counts) = &mut self.root_counts {
+            let mut subtracted = true;
+            for vec in removed {
+                subtracted &= counts.subtract(&BitslicedTritVec::from_sparse(vec, DIM));
+            }
+            if subtracted {
+                self.root = counts.finalize().to_sparse();
+                return;
+            }
+        }
+        self.rebuild_root_with(strategy);
+    }
+
+    /// Whether `chunk` was probably bundled into the root, judged by its
+    /// cosine with the root alone, without the codebook or an index.
+    ///
+    /// `config` and `path` must be those the chunk was ingested with: in
+    /// reversible encoding the path shifts the chunk vector.
+    pub fn probably_contains(&self, chunk: &[u8], config: &ReversibleVSAConfig, path: Option<&str>) -> MembershipResult {
+        let vec = SparseVec::encode_chunk(chunk, config, path);
+        MembershipResult::from_score(vec.cosine(&self.root), self.codebook.len())
+    }
+
+    /// Whether `data` was probably ingested as `logical_path`: each
+    /// `chunk_size`-byte chunk is tested with [`Engram::probably_contains`]
+    /// and the results combined with [`MembershipResult::combine`]. Empty
+    /// data is never stored and scores 0.
+    pub fn probably_contains_file(
+        &self,
+        data: &[u8],
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        logical_path: &str,
+    ) -> MembershipResult {
+        let chunks: Vec<MembershipResult> = data
+            .chunks(chunk_size)
+            .map(|chunk| self.probably_contains(chunk, config, Some(logical_path)))
+            .collect();
+        MembershipResult::combine(&chunks).unwrap_or_else(|| MembershipResult::from_score(0.0, self.codebook.len()))
+    }
+
+    /// Ids with a codebook vector or a correction, in ascending order.
+    ///
+    /// Each id maps to one chunk record (see [`Engram::encode_chunk_record`]),
+    /// the unit that incremental formats store and compare.
+    pub(crate) fn chunk_record_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.codebook.keys().map(|&id| id as u64).collect();
+        ids.extend(
+            self.corrections
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| !self.codebook.contains_key(&(*id as usize))),
+        );
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Rebuild the root by bundling the codebook in id order, the order
+    /// ingest bundles chunks in, or recount it when it is counted.
+    pub(crate) fn rebuild_root(&mut self) {
+        self.rebuild_root_with(&RootStrategy::Pairwise);
+    }
+
+    /// Rebuild the root the way `strategy` builds it during ingest. A
+    /// counted root stays counted; [`RootStrategy::CarrySave`] starts
+    /// counting, and the other strategies stop.
+    pub(crate) fn rebuild_root_with(&mut self, strategy: &RootStrategy) {
+        let mut ids: Vec<usize> = self.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        match strategy {
+            RootStrategy::Pairwise => {}
+            RootStrategy::CarrySave => {
+                self.root_counts.get_or_insert_with(|| CountedBundle::new(DIM));
+            }
+            RootStrategy::Tree { fan_in } => {
+                self.root_counts = None;
+                let mut tree = TreeBundle::new(*fan_in);
+                for id in &ids {
+                    tree.push(&self.codebook[id]);
+                }
+                self.root = tree.root();
+                return;
+            }
+            RootStrategy::Thinned { max_nonzero } => {
+                self.root_counts = None;
+                self.root = ids
+                    .iter()
+                    .fold(SparseVec::new(), |root, id| thinned_bundle(&root, &self.codebook[id], *max_nonzero));
+                return;
+            }
+        }
+        if let Some(counts) = &mut self.root_counts {
+            *counts = CountedBundle::new(DIM);
+            for id in &ids {
+                counts.add(&BitslicedTritVec::from_sparse(&self.codebook[id], DIM));
+            }
+            self.root = counts.finalize().to_sparse();
+            return;
+        }
+        self.root = ids
+            .iter()
+            .fold(SparseVec::new(), |root, id| root.bundle(&self.codebook[id]));
+    }
+
+    /// Apply the correction of chunk `chunk_id` to its `decoded` bytes.
+    ///
+    /// A [`CorrectionType::Delta`] is applied to the original bytes of its
+    /// base chunk, themselves reconstructed this way. Bases always precede
+    /// the chunks built on them, so a base with a higher id is refused.
+    /// `None` if there is no correction or the result fails verification.
+    pub(crate) fn apply_correction(&self, chunk_id: u64, decoded: &[u8], config: &ReversibleVSAConfig) -> Option<Vec<u8>> {
+        let correction = self.corrections.get(chunk_id)?;
+        let result = match &correction.correction {
+            CorrectionType::Delta {
+                base,
+                base_path,
+                base_len,
+                ops,
+            } if *base < chunk_id => {
+                let base_bytes = self.chunk_bytes(*base, base_path, *base_len as usize, config)?;
+                correction::apply_delta(&base_bytes, ops)?
+            }
+            _ => correction.apply(decoded),
+        };
+        correction.verify(&result).then_some(result)
+    }
+
+    /// Original bytes of chunk `chunk_id`, `len` bytes of the file at `path`.
+    /// `None` if the chunk is absent from the codebook.
+    pub(crate) fn chunk_bytes(&self, chunk_id: u64, path: &str, len: usize, config: &ReversibleVSAConfig) -> Option<Vec<u8>> {
+        let decoded = self.codebook.get(&(chunk_id as usize))?.decode_data(config, Some(path), len);
+        Some(self.apply_correction(chunk_id, &decoded, config).unwrap_or(decoded))
+    }
+
+    /// Store chunks whose delta base is among `removed` verbatim, so they
+    /// outlive the base. Call before the base chunks are dropped.
+    pub(crate) fn detach_deltas(&mut self, removed: &HashSet<u64>, config: &ReversibleVSAConfig) {
+        let mut dependents: Vec<u64> = self
+            .corrections
+            .iter()
+            .filter(|(id, c)| {
+                !removed.contains(id) && matches!(&c.correction, CorrectionType::Delta { base, .. } if removed.contains(base))
+            })
+            .map(|(id, _)| id)
+            .collect();
+        dependents.sort_unstable();
+        // Resolve every dependent before any record changes.
+        let detached: Vec<(u64, Vec<u8>)> = dependents
+            .into_iter()
+            .filter_map(|id| Some((id, self.apply_correction(id, &[], config)?)))
+            .collect();
+        for (id, original) in detached {
+            let Some(old) = self.corrections.remove(id, original.len()) else {
+                continue;
+            };
+            self.corrections.insert(
+                ChunkCorrection {
+                    correction: CorrectionType::Verbatim(original.clone()),
+                    ..old
+                },
+                original.len(),
+            );
+        }
+    }
+
+    /// Serialize one chunk's vector and correction; decoded by [`decode_log_chunk`].
+    pub(crate) fn encode_chunk_record(&self, id: u64) -> io::Result<Vec<u8>> {
+        let entry = (self.codebook.get(&(id as usize)), self.corrections.get(id));
+        bincode::serialize(&entry).map_err(|e| bincode_io_error(*e))
+    }
+
+    /// Serialize the root and correction totals; decoded by [`decode_log_meta`].
+    pub(crate) fn encode_meta_record(&self) -> io::Result<Vec<u8>> {
+        bincode::serialize(&(&self.root, self.corrections.totals())).map_err(|e| bincode_io_error(*e))
+    }
+
+    /// Estimated heap bytes of the codebook, root and corrections.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            codebook_bytes: memory::vector_map_bytes(&self.codebook),
+            root_bytes: memory::sparse_vec_bytes(&self.root),
+            corrections_bytes: self.corrections.memory_bytes(),
+            ..Default::default()
+        }
+    }
+
+    /// Estimated heap bytes one codebook entry and its correction add.
+    pub(crate) fn chunk_memory_bytes(&self, chunk_id: usize, vec: &SparseVec) -> u64 {
+        memory::sparse_vec_bytes(vec)
+            + memory::slot_bytes::<usize, SparseVec>()
+            + self.corrections.chunk_memory_bytes(chunk_id as u64)
+    }
+
+    /// Estimated serialized size of one codebook entry plus its correction.
+    pub(crate) fn estimated_chunk_bytes(&self, chunk_id: usize, vec: &SparseVec) -> u64 {
+        // bincode: map key + two length-prefixed index vectors.
+        let vector_bytes = 8 + 16 + 8 * (vec.pos.len() + vec.neg.len()) as u64;
+        let correction_bytes = self
+            .corrections
+            .get(chunk_id as u64)
+            .map(|c| 32 + c.storage_size() as u64)
+            .unwrap_or(0);
+        vector_bytes + correction_bytes
+    }
+
+    /// Build a reusable inverted index over the codebook.
+    ///
+    /// This is useful when issuing multiple queries (e.g., shift-sweeps) and you
+    /// want to avoid rebuilding the index each time.
+    pub fn build_codebook_index(&self) -> TernaryInvertedIndex {
+        TernaryInvertedIndex::build_from_map(&self.codebook)
+    }
+
+    /// Query the codebook using a pre-built inverted index.
+    pub fn query_codebook_with_index(
+        &self,
+        index: &TernaryInvertedIndex,
+        query: &SparseVec,
+        candidate_k: usize,
+        k: usize,
+    ) -> Vec<RerankedResult> {
+        if k == 0 || self.codebook.is_empty() {
+            return Vec::new();
+        }
+        index.query_top_k_reranked(query, &self.codebook, candidate_k, k)
+    }
+
+    /// Like `query_codebook_with_index`, but only returns chunks in `allowed`.
+    ///
+    /// Used for namespace-scoped queries (see `Manifest::chunk_ids_in_namespace`).
+    pub fn query_codebook_with_index_filtered(
+        &self,
+        index: &TernaryInvertedIndex,
+        query: &SparseVec,
+        candidate_k: usize,
+        k: usize,
+        allowed: &HashSet<usize>,
+    ) -> Vec<RerankedResult> {
+        if k == 0 || allowed.is_empty() {
+            return Vec::new();
+        }
+        // Over-fetch so filtering still leaves up to `k` hits in the common case.
+        let widened = candidate_k.max(k).saturating_mul(4).min(self.codebook.len().max(1));
+        let mut hits = self.query_codebook_with_index(index, query, widened, widened);
+        hits.retain(|h| allowed.contains(&h.id));
+        hits.truncate(k);
+        hits
+    }
+
+    /// Query the engram's codebook for chunks most similar to `query`.
+    ///
+    /// This builds an inverted index over the codebook for sub-linear candidate
+    /// generation, then reranks those candidates using exact cosine similarity.
+    pub fn query_codebook(&self, query: &SparseVec, k: usize) -> Vec<RerankedResult> {
+        let _t = profile::scope("query_codebook");
+        if k == 0 || self.codebook.is_empty() {
+            return Vec::new();
+        }
+
+        // Simple heuristic: rerank a moderately-sized candidate set.
+        let candidate_k = (k.saturating_mul(10)).max(50);
+        let index = {
+            let _t = profile::scope("build_index");
+            self.build_codebook_index()
+        };
+        let hits = self.query_codebook_with_index(&index, query, candidate_k, k);
+        logging::debug(
+            "retrieval",
+            format_args!("query of {} chunks: {} hits for k={}", self.codebook.len(), hits.len(), k),
+        );
+        hits
+    }
+}
+
+/// A file ranked against a query by [`FileSignatures`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FileMatch {
+    pub path: String,
+    /// Cosine between the query and the file signature.
+    pub cosine: f64,
+    /// Approximate score from inverted-index accumulation (sparse dot proxy).
+    pub approx_score: i32,
+    /// Chunks bundled into the file signature.
+    pub chunks: usize,
+}
+
+/// File-level signatures: each file's chunk vectors bundled into one vector,
+/// with an inverted index over them for ranking whole files against a query.
+/// Signatures recorded in the manifest ([`FileEntry::signature`]) are used
+/// as they are.
+///
+/// Similarity against the root only says whether a query resembles *something*
+/// in the engram; ranking signatures says which files it resembles.
+pub struct FileSignatures {
+    paths: Vec<String>,
+    chunk_counts: Vec<usize>,
+    vectors: HashMap<usize, SparseVec>,
+    index: TernaryInvertedIndex,
+}
+
+impl FileSignatures {
+    /// Build signatures for every file in `manifest`.
+    pub fn build(engram: &Engram, manifest: &Manifest) -> Self {
+        Self::build_filtered(engram, manifest, |_| true)
+    }
+
+    /// Build signatures for the files of `manifest` accepted by `keep`.
+    ///
+    /// Files without any chunk in the codebook (such as empty files) are skipped.
+    pub fn build_filtered<F: Fn(&FileEntry) -> bool>(engram: &Engram, manifest: &Manifest, keep: F) -> Self {
+        let mut signatures = Self::empty();
+        for file in manifest.files.iter().filter(|f| keep(f)) {
+            if signatures.push_recorded(file) {
+                continue;
+            }
+            let chunks: Vec<&SparseVec> = file.chunks.iter().filter_map(|id| engram.codebook.get(id)).collect();
+            signatures.push(&file.path, &chunks);
+        }
+        signatures.finish()
+    }
+
+    /// Build signatures for every file in `manifest`, fetching chunk vectors
+    /// one file at a time from `chunk`. For codebooks that are not held in
+    /// memory, such as a mapped [`Envelope`](crate::lazy_envelope::Envelope).
+    /// Files with a recorded [`FileEntry::signature`] fetch nothing.
+    pub fn build_with<F>(manifest: &Manifest, mut chunk: F) -> io::Result<Self>
+    where
+        F: FnMut(usize) -> io::Result<Option<SparseVec>>,
+    {
+        let mut signatures = Self::empty();
+        for file in &manifest.files {
+            if signatures.push_recorded(file) {
+                continue;
+            }
+            let mut chunks = Vec::with_capacity(file.chunks.len());
+            for &id in &file.chunks {
+                chunks.extend(chunk(id)?);
+            }
+            signatures.push(&file.path, &chunks.iter().collect::<Vec<_>>());
+        }
+        Ok(signatures.finish())
+    }
+
+    fn empty() -> Self {
+        Self {
+            paths: Vec::new(),
+            chunk_counts: Vec::new(),
+            vectors: HashMap::new(),
+            index: TernaryInvertedIndex::new(),
+        }
+    }
+
+    /// Add a file's signature; files with no chunks are skipped.
+    fn push(&mut self, path: &str, chunks: &[&SparseVec]) {
+        if chunks.is_empty() {
+            return;
+        }
+        self.vectors.insert(self.paths.len(), SparseVec::bundle_sum_many(chunks.iter().copied()));
+        self.paths.push(path.to_string());
+        self.chunk_counts.push(chunks.len());
+    }
+
+    /// Add the [`FileEntry::signature`] recorded at ingest, if there is one.
+    fn push_recorded(&mut self, file: &FileEntry) -> bool {
+        let Some(signature) = &file.signature else {
+            return false;
+        };
+        if !file.chunks.is_empty() {
+            self.vectors.insert(self.paths.len(), signature.clone());
+            self.paths.push(file.path.clone());
+            self.chunk_counts.push(file.chunks.len());
+        }
+        true
+    }
+
+    fn finish(mut self) -> Self {
+        self.index = TernaryInvertedIndex::build_from_map(&self.vectors);
+        self
+    }
+
+    /// Number of files with a signature.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Paths and signatures, in manifest order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SparseVec)> {
+        self.paths.iter().enumerate().map(|(i, path)| (path.as_str(), &self.vectors[&i]))
+    }
+
+    /// The same files with every signature replaced by `f` of it, such as
+    /// a projection into another engram's space.
+    pub fn map<F: FnMut(&SparseVec) -> SparseVec>(&self, mut f: F) -> Self {
+        let mut mapped = Self::empty();
+        for (path, signature) in self.iter() {
+            mapped.vectors.insert(mapped.paths.len(), f(signature));
+            mapped.paths.push(path.to_string());
+        }
+        mapped.chunk_counts = self.chunk_counts.clone();
+        mapped.finish()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Top `k` files by cosine with `query`, reranking `candidate_k` candidates
+    /// from the inverted index.
+    pub fn query(&self, query: &SparseVec, candidate_k: usize, k: usize) -> Vec<FileMatch> {
+        if k == 0 || self.is_empty() {
+            return Vec::new();
+        }
+        self.index
+            .query_top_k_reranked(query, &self.vectors, candidate_k.max(k), k)
+            .into_iter()
+            .map(|hit| FileMatch {
+                path: self.paths[hit.id].clone(),
+                cosine: hit.cosine,
+                approx_score: hit.approx_score,
+                chunks: self.chunk_counts[hit.id],
+            })
+            .collect()
+    }
+
+    /// Like [`FileSignatures::query`] for a query encoded without a path.
+    ///
+    /// Chunks are encoded with a path-hash bucket shift that is unknown at query
+    /// time, so every bucket of `config` is tried and each file keeps its best
+    /// score.
+    pub fn query_any_shift(&self, base_query: &SparseVec, config: &ReversibleVSAConfig, k: usize) -> Vec<FileMatch> {
+        let candidate_k = k.saturating_mul(10).max(50);
+        let mut best: HashMap<String, FileMatch> = HashMap::new();
+        for depth in 0..config.max_path_depth.max(1) {
+            let query = base_query.permute(depth * config.base_shift);
+            for hit in self.query(&query, candidate_k, candidate_k) {
+                match best.get(&hit.path) {
+                    Some(prev) if prev.cosine >= hit.cosine => {}
+                    _ => {
+                        best.insert(hit.path.clone(), hit);
+                    }
+                }
+            }
+        }
+        let mut out: Vec<FileMatch> = best.into_values().collect();
+        out.sort_by(|a, b| {
+            b.cosine
+                .partial_cmp(&a.cosine)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        out.truncate(k);
+        out
+    }
+}
+
+/// EmbrFS - Holographic Filesystem with Guaranteed Reconstruction
+///
+/// # 100% Reconstruction Guarantee
+///
+/// EmbrFS guarantees bit-perfect file reconstruction through a layered approach:
+///
+/// 1. **Encode**: Data chunks → SparseVec via reversible encoding
+/// 2. **Verify**: Immediately decode and compare to original
+/// 3. **Correct**: Store minimal correction if any difference exists
+/// 4. **Extract**: Decode + apply correction = exact original bytes
 ///
 /// This guarantee holds regardless of:
 /// - Data content (binary, text, compressed, encrypted)
@@ -664,6 +2749,48 @@ pub struct EmbrFS {
     pub manifest: Manifest,
     pub engram: Engram,
     pub resonator: Option<Resonator>,
+    /// Limits checked during ingest (unlimited by default).
+    pub limits: IngestLimits,
+    /// Running estimate of the serialized engram size, for `limits`.
+    estimated_engram_bytes: u64,
+    /// Running estimate of the heap bytes held, for `limits`.
+    held_memory_bytes: u64,
+    /// Set by [`EmbrFS::in_memory`]: every save fails.
+    in_memory: bool,
+    /// Semantic signatures of ingested chunks, when a semantic encoder is set.
+    pub semantic: Option<SemanticSignatures>,
+    semantic_encoder: Option<SemanticEncoder>,
+    /// When set, chunks already in the engram (same bytes, same path) are
+    /// referenced instead of stored again. Off by default.
+    pub chunk_index: Option<ChunkIndex>,
+    /// Warns when the root bundle nears capacity during ingest; `None`
+    /// disables the check.
+    pub capacity_monitor: Option<CapacityMonitor>,
+    /// Filter chain run on every ingested file, which may flag, redact,
+    /// transform, skip or reject it (see [`crate::ingest_filter`]), with the
+    /// findings so far. Empty by default.
+    pub filters: IngestFilters,
+    /// Source of each ingested file's `ingested_at`.
+    pub clock: IngestClock,
+    /// Record a [`FileEntry::signature`] for every ingested file. Off by
+    /// default: a signature adds about as much to the manifest as a chunk
+    /// vector adds to the engram.
+    pub file_signatures: bool,
+    /// Pending sub-roots of a [`RootStrategy::Tree`] root, rebuilt from the
+    /// codebook whenever it falls out of step.
+    root_tree: Option<TreeBundle>,
+    /// Cancellation and read throttling for ingest. A cancelled ingest
+    /// keeps the files it finished and drops the one in progress.
+    pub control: JobControl,
+    /// Read and encode the files of ingested directories on worker threads
+    /// while this thread stores them; see [`crate::ingest_queue`]. `None`,
+    /// the default, ingests one chunk after another.
+    pub pipeline: Option<IngestPipeline>,
+    /// What the pipeline's queues went through so far.
+    pipeline_stats: QueueStats,
+    /// Accesses recorded by readers, mounts and extractions of this
+    /// engram; see [`EmbrFS::access_stats`].
+    access: Arc<AccessStats>,
 }
 
 impl Default for EmbrFS {
@@ -689,27 +2816,350 @@ impl EmbrFS {
     /// ```
     pub fn new() -> Self {
         EmbrFS {
-            manifest: Manifest {
-                files: Vec::new(),
-                total_chunks: 0,
-            },
+            manifest: Manifest::default(),
             engram: Engram {
                 root: SparseVec::new(),
                 codebook: HashMap::new(),
                 corrections: CorrectionStore::new(),
+                root_counts: None,
+                precision: None,
+                differential: None,
+                schema: None,
             },
             resonator: None,
+            limits: IngestLimits::default(),
+            estimated_engram_bytes: 0,
+            held_memory_bytes: 0,
+            in_memory: false,
+            semantic: None,
+            semantic_encoder: None,
+            chunk_index: None,
+            capacity_monitor: Some(CapacityMonitor::default()),
+            filters: IngestFilters::default(),
+            clock: IngestClock::default(),
+            file_signatures: false,
+            root_tree: None,
+            control: JobControl::default(),
+            pipeline: None,
+            pipeline_stats: QueueStats::default(),
+            access: Arc::new(AccessStats::new()),
+        }
+    }
+
+    /// Create an EmbrFS whose chunks are encoded with `vsa` and `dimensional`
+    /// instead of the defaults. The settings are stored in the manifest;
+    /// ingest and extract must then be given the same `vsa` config
+    /// ([`EmbrFS::vsa_config`]).
+    ///
+    /// Only the dimension [`DIM`] is accepted: chunk vectors have a fixed
+    /// dimension, so `dimensional.num_dimensions` configures nothing and
+    /// any other value fails with [`EmbrError::DimensionMismatch`]. The
+    /// other fields of `dimensional` are free to vary.
+    pub fn with_config(vsa: ReversibleVSAConfig, dimensional: DimensionalConfig) -> Result<Self> {
+        Self::with_encoding(EncodingConfig {
+            vsa,
+            dimensional,
+            ..EncodingConfig::default()
+        })
+    }
+
+    /// [`EmbrFS::with_config`] with the chunk size given as well.
+    pub fn with_encoding(encoding: EncodingConfig) -> Result<Self> {
+        encoding.validate()?;
+        let mut fs = Self::new();
+        fs.manifest.encoding = Some(encoding);
+        Ok(fs)
+    }
+
+    /// The VSA config this engram's chunks are encoded with.
+    pub fn vsa_config(&self) -> ReversibleVSAConfig {
+        self.manifest.encoding().vsa
+    }
+
+    /// Create an EmbrFS that is never written to disk, for pipelines that
+    /// stage data in an engram only while they work on it.
+    ///
+    /// Ingest, query and extract work as on any EmbrFS; every `save_*`
+    /// method fails with [`io::ErrorKind::Unsupported`] instead of creating
+    /// a file. Set [`IngestLimits::max_memory_bytes`] to bound what it
+    /// holds, and remove files to make room again.
+    pub fn in_memory() -> Self {
+        EmbrFS {
+            in_memory: true,
+            ..Self::new()
+        }
+    }
+
+    /// Whether this EmbrFS was created by [`EmbrFS::in_memory`].
+    pub fn is_in_memory(&self) -> bool {
+        self.in_memory
+    }
+
+    /// Estimated heap bytes of what this EmbrFS ingested and still holds:
+    /// chunk vectors, corrections, file entries, and the semantic
+    /// signatures and chunk index entries kept for them. This is the figure
+    /// [`IngestLimits::max_memory_bytes`] bounds; it is kept up to date as
+    /// files are ingested and removed, where [`EmbrFS::memory_usage`]
+    /// counts everything in one pass.
+    pub fn held_memory_bytes(&self) -> u64 {
+        self.held_memory_bytes
+    }
+
+    /// Chunks queued, peak queued bytes and pauses of the
+    /// [`EmbrFS::pipeline`] over every directory ingested with it.
+    pub fn pipeline_stats(&self) -> QueueStats {
+        self.pipeline_stats
+    }
+
+    /// Chunk and file accesses so far by readers built with
+    /// [`EngramReader::from_embrfs`](crate::reader::EngramReader::from_embrfs),
+    /// mounts of them, and extractions given [`EmbrFS::access_recorder`];
+    /// see [`crate::access_stats`].
+    pub fn access_stats(&self) -> AccessSnapshot {
+        self.access.snapshot()
+    }
+
+    /// The recorder behind [`EmbrFS::access_stats`], to share with anything
+    /// else reading this engram.
+    pub fn access_recorder(&self) -> &Arc<AccessStats> {
+        &self.access
+    }
+
+    fn ensure_persistent(&self, path: &Path) -> Result<()> {
+        if !self.in_memory {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("an in-memory engram is never saved, not even to {}", path.display()),
+        )
+        .into())
+    }
+
+    /// Fail with a memory quota error if ingest has taken the held bytes
+    /// over [`IngestLimits::max_memory_bytes`].
+    fn check_memory_limit(&self, logical_path: &str) -> Result<()> {
+        match self.limits.max_memory_bytes {
+            Some(max) if self.held_memory_bytes > max => Err(QuotaExceeded {
+                kind: QuotaKind::MemoryBytes,
+                limit: max,
+                attempted: self.held_memory_bytes,
+                path: logical_path.to_string(),
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create an EmbrFS that enforces `limits` during ingest.
+    pub fn with_limits(limits: IngestLimits) -> Self {
+        let mut fs = Self::new();
+        fs.limits = limits;
+        fs
+    }
+
+    /// Estimate the result of ingesting `dir` without modifying this EmbrFS.
+    ///
+    /// Walks the tree using metadata only, then encodes up to a small sample
+    /// of chunks (spread across files) to calibrate bytes-per-chunk. The
+    /// estimate is checked against `self.limits`, accounting for anything
+    /// already ingested.
+    pub fn estimate_directory<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        config: &ReversibleVSAConfig,
+    ) -> Result<IngestEstimate> {
+        let dir = dir.as_ref();
+        let chunk_size = self.manifest.chunk_size();
+        let mut files = Vec::new();
+        for entry in WalkDir::new(dir).follow_links(false) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                files.push((entry.path().to_path_buf(), entry.metadata().map_err(io::Error::other)?.len()));
+            }
+        }
+        files.sort();
+
+        let mut estimate = IngestEstimate {
+            files: files.len(),
+            ..IngestEstimate::default()
+        };
+        for (path, len) in &files {
+            estimate.input_bytes += len;
+            estimate.chunks += (*len as usize).div_ceil(chunk_size);
+            if let Some(max) = self.limits.max_file_size {
+                if *len > max {
+                    estimate.violations.push(QuotaExceeded {
+                        kind: QuotaKind::FileSize,
+                        limit: max,
+                        attempted: *len,
+                        path: path.display().to_string(),
+                    });
+                }
+            }
+        }
+
+        // Calibrate on the first chunk of evenly spaced files.
+        let stride = files.len().div_ceil(ESTIMATE_SAMPLE_CHUNKS).max(1);
+        let mut sample_bytes = 0u64;
+        let mut sample_count = 0u64;
+        let mut buf = vec![0u8; chunk_size];
+        for (path, len) in files.iter().step_by(stride) {
+            if *len == 0 {
+                continue;
+            }
+            let mut file = File::open(path)?;
+            let n = file.read(&mut buf)?;
+            let chunk = &buf[..n];
+            let vec = SparseVec::encode_chunk(chunk, config, None);
+            let decoded = vec.decode_data(config, None, n);
+            let correction = crate::correction::ChunkCorrection::new(0, chunk, &decoded);
+            let correction_bytes = if correction.needs_correction() {
+                32 + correction.storage_size() as u64
+            } else {
+                32
+            };
+            sample_bytes += 8 + 16 + 8 * (vec.pos.len() + vec.neg.len()) as u64 + correction_bytes;
+            sample_count += 1;
+        }
+        if sample_count > 0 {
+            estimate.estimated_engram_bytes =
+                (sample_bytes as f64 / sample_count as f64 * estimate.chunks as f64).ceil() as u64;
+        }
+
+        let total_chunks = self.manifest.total_chunks + estimate.chunks;
+        if let Some(max) = self.limits.max_chunks {
+            if total_chunks > max {
+                estimate.violations.push(QuotaExceeded {
+                    kind: QuotaKind::ChunkCount,
+                    limit: max as u64,
+                    attempted: total_chunks as u64,
+                    path: dir.display().to_string(),
+                });
+            }
+        }
+        let total_bytes = self.estimated_engram_bytes + estimate.estimated_engram_bytes;
+        if let Some(max) = self.limits.max_engram_bytes {
+            if total_bytes > max {
+                estimate.violations.push(QuotaExceeded {
+                    kind: QuotaKind::EngramBytes,
+                    limit: max,
+                    attempted: total_bytes,
+                    path: dir.display().to_string(),
+                });
+            }
+        }
+
+        Ok(estimate)
+    }
+
+    fn path_to_forward_slash_string(path: &Path) -> String {
+        path.components()
+            .filter_map(|c| match c {
+                std::path::Component::Normal(s) => s.to_str().map(|v| v.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+
+    /// Deduplication and compression statistics for the current engram,
+    /// with the findings of [`EmbrFS::filters`].
+    pub fn ingest_stats(&self) -> IngestStats {
+        IngestStats {
+            findings: self.filters.findings.clone(),
+            ..IngestStats::compute(&self.engram, &self.manifest)
+        }
+    }
+
+    /// Learn a basis of at most `k` vectors from the engram's chunk vectors
+    /// for differential encoding (see [`Codebook::train_basis`]). At most
+    /// [`MAX_BASIS_SAMPLES`] chunks are used, spread evenly over the chunk
+    /// ids.
+    pub fn train_basis(&self, k: usize) -> (Codebook, BasisTrainingReport) {
+        let mut ids: Vec<usize> = self.engram.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        let step = ids.len().div_ceil(MAX_BASIS_SAMPLES).max(1);
+        let samples: Vec<SparseVec> = ids
+            .iter()
+            .step_by(step)
+            .map(|id| self.engram.codebook[id].clone())
+            .collect();
+        let mut codebook = Codebook::new(DIM);
+        let report = codebook.train_basis(&samples, k);
+        (codebook, report)
+    }
+
+    /// A [`DifferentialEncoder`](crate::DifferentialEncoder) over `basis`
+    /// with the manifest's dimensional config, starting from the depths in
+    /// [`Engram::precision`] so it decodes what was encoded before.
+    pub fn differential_encoder(&self, basis: &Codebook) -> crate::DifferentialEncoder {
+        let config = self.manifest.encoding().dimensional;
+        basis
+            .differential_encoder(config)
+            .with_depths(self.engram.precision.clone().unwrap_or_default())
+    }
+
+    /// Run adaptive precision over every chunk: encode it differentially
+    /// against `basis` and keep the depths
+    /// [`TritDepthConfig::Adaptive`] had to raise in [`Engram::precision`].
+    /// Does nothing for other trit depth configs, which never expand.
+    pub fn adapt_precision(&mut self, basis: &Codebook) -> PrecisionReport {
+        let mut report = PrecisionReport::default();
+        let mut encoder = self.differential_encoder(basis);
+        if !matches!(encoder.config.trit_depth, TritDepthConfig::Adaptive { .. }) {
+            return report;
         }
+        let mut ids: Vec<usize> = self.engram.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        let mut quality = 0.0;
+        for id in ids {
+            let data = HyperVec::from_sparse(encoder.config.clone(), &self.engram.codebook[&id]);
+            let encoding = encoder.encode(&data);
+            encoder.depths.merge(&encoding.expanded);
+            quality += encoding.quality;
+            report.chunks += 1;
+        }
+        report.expanded_dimensions = encoder.depths.dimensions.len();
+        report.expanded_coefficients = encoder.depths.coefficients.len();
+        report.mean_quality = if report.chunks == 0 { 1.0 } else { quality / report.chunks as f64 };
+        self.engram.precision = (!encoder.depths.is_empty()).then_some(encoder.depths);
+        report
     }
 
-    fn path_to_forward_slash_string(path: &Path) -> String {
-        path.components()
-            .filter_map(|c| match c {
-                std::path::Component::Normal(s) => s.to_str().map(|v| v.to_string()),
-                _ => None,
-            })
-            .collect::<Vec<String>>()
-            .join("/")
+    /// Group this engram's chunks by similarity; see [`Codebook::cluster`].
+    pub fn cluster_chunks(&self, threshold: f64) -> ChunkClusters {
+        Codebook::cluster(&self.engram.codebook, threshold)
+    }
+
+    /// Estimated heap bytes of the engram, manifest and the optional
+    /// indices (dedup chunk index, semantic signatures, resonator).
+    ///
+    /// Call [`MemoryUsage::publish`] on the result to expose it as metrics.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = self.engram.memory_usage();
+        usage.manifest_bytes = self.manifest.memory_bytes();
+        usage.index_bytes = self.chunk_index.as_ref().map_or(0, |index| memory::map_bytes(&index.ids))
+            + self
+                .semantic
+                .as_ref()
+                .map_or(0, |s| memory::vector_map_bytes(&s.vectors))
+            + self.resonator.as_ref().map_or(0, |r| {
+                memory::vec_bytes(&r.codebook) + r.codebook.iter().map(memory::sparse_vec_bytes).sum::<u64>()
+            });
+        usage
+    }
+
+    /// Saturation of the root bundle over every stored chunk.
+    pub fn capacity_report(&self) -> CapacityReport {
+        CapacityReport::analyze(&self.engram)
+    }
+
+    /// Whether `data` was probably ingested as `logical_path`; see
+    /// [`Engram::probably_contains_file`].
+    pub fn probably_contains_file(&self, data: &[u8], logical_path: &str) -> MembershipResult {
+        self.engram
+            .probably_contains_file(data, &self.vsa_config(), self.manifest.chunk_size(), logical_path)
     }
 
     /// Set the resonator for enhanced pattern recovery during extraction
@@ -745,6 +3195,18 @@ impl EmbrFS {
         self.resonator = Some(resonator);
     }
 
+    /// Compute a semantic signature for every chunk ingested from now on.
+    ///
+    /// Signatures collect in [`EmbrFS::semantic`]. Existing signatures are
+    /// kept if they came from the same model and projection, and discarded
+    /// otherwise, so the set never mixes models.
+    pub fn set_semantic_encoder(&mut self, encoder: SemanticEncoder) {
+        if !self.semantic.as_ref().is_some_and(|s| encoder.matches(s)) {
+            self.semantic = Some(encoder.empty_signatures());
+        }
+        self.semantic_encoder = Some(encoder);
+    }
+
     /// Get correction statistics for this engram
     ///
     /// Returns statistics about how many chunks needed correction and the
@@ -768,10 +3230,51 @@ impl EmbrFS {
         dir: P,
         verbose: bool,
         config: &ReversibleVSAConfig,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
         self.ingest_directory_with_prefix(dir, None, verbose, config)
     }
 
+    /// Ingest a directory as a named root so several sources can share one engram.
+    ///
+    /// All files are stored under `{namespace}/...` and the namespace is
+    /// recorded in the manifest, enabling namespace-scoped extraction and
+    /// queries. Namespace names must be a single non-empty path component and
+    /// unique within the engram. The name is normalized like the paths
+    /// under it.
+    pub fn ingest_root<P: AsRef<Path>>(
+        &mut self,
+        namespace: &str,
+        dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        let namespace = self.manifest.path_normalization.apply(namespace.to_string());
+        let namespace = namespace.as_str();
+        if namespace.is_empty() || namespace.contains('/') || namespace == "." || namespace == ".." {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid namespace name: {:?}", namespace),
+            )
+            .into());
+        }
+        if self.manifest.namespaces.contains_key(namespace)
+            || self.manifest.files.iter().any(|f| namespace_relative_path(&f.path, namespace).is_some())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("namespace already present in engram: {}", namespace),
+            )
+            .into());
+        }
+
+        let dir = dir.as_ref();
+        self.ingest_directory_with_prefix(dir, Some(namespace), verbose, config)?;
+        self.manifest
+            .namespaces
+            .insert(namespace.to_string(), dir.display().to_string());
+        Ok(())
+    }
+
     /// Ingest a directory into the engram, optionally prefixing all logical paths.
     ///
     /// When `logical_prefix` is provided, all ingested file paths become:
@@ -782,11 +3285,9 @@ impl EmbrFS {
         logical_prefix: Option<&str>,
         verbose: bool,
         config: &ReversibleVSAConfig,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
         let dir = dir.as_ref();
-        if verbose {
-            println!("Ingesting directory: {}", dir.display());
-        }
+        logging::progress(verbose, "fs", format_args!("Ingesting directory: {}", dir.display()));
 
         let mut files_to_process = Vec::new();
         for entry in WalkDir::new(dir).follow_links(false) {
@@ -797,27 +3298,102 @@ impl EmbrFS {
         }
         files_to_process.sort();
 
-        for file_path in files_to_process {
-            let relative = file_path.strip_prefix(dir).unwrap_or(file_path.as_path());
-            let rel = Self::path_to_forward_slash_string(relative);
-            let logical_path = if let Some(prefix) = logical_prefix {
-                if prefix.is_empty() {
-                    rel
-                } else if rel.is_empty() {
-                    prefix.to_string()
+        let jobs: Vec<(PathBuf, String)> = files_to_process
+            .into_iter()
+            .map(|file_path| {
+                let relative = file_path.strip_prefix(dir).unwrap_or(file_path.as_path());
+                let rel = Self::path_to_forward_slash_string(relative);
+                let logical_path = if let Some(prefix) = logical_prefix {
+                    if prefix.is_empty() {
+                        rel
+                    } else if rel.is_empty() {
+                        prefix.to_string()
+                    } else {
+                        format!("{}/{}", prefix, rel)
+                    }
                 } else {
-                    format!("{}/{}", prefix, rel)
-                }
-            } else {
-                rel
-            };
+                    rel
+                };
+                (file_path, logical_path)
+            })
+            .collect();
+        if let Some(pipeline) = self.pipeline {
+            return self.ingest_pipelined(jobs, pipeline, verbose, config);
+        }
 
-            self.ingest_file(&file_path, logical_path, verbose, config)?;
+        let mut progress = JobProgress::default();
+        for (file_path, logical_path) in jobs {
+            self.control.checkpoint(progress)?;
+            let size = fs::metadata(&file_path).map_or(0, |m| m.len());
+            match self.ingest_file(&file_path, logical_path, verbose, config) {
+                Err(EmbrError::Cancelled(_)) => return Err(EmbrError::Cancelled(progress)),
+                result => result?,
+            }
+            progress.files += 1;
+            progress.bytes += size;
         }
 
         Ok(())
     }
 
+    /// Ingest the files of `jobs` under their logical paths, encoded on
+    /// the pipeline's threads and stored here in order.
+    fn ingest_pipelined(
+        &mut self,
+        jobs: Vec<(PathBuf, String)>,
+        pipeline: IngestPipeline,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        // Workers encode under the paths ingest will store.
+        let jobs: Vec<(PathBuf, String)> = jobs
+            .into_iter()
+            .map(|(path, logical)| (path, self.manifest.path_normalization.apply(logical)))
+            .collect();
+        let budget = pipeline.memory_budget.or(self.limits.max_memory_bytes);
+        let queue = IngestQueue::new(jobs.len(), pipeline.watermarks, budget);
+        queue.set_held(self.held_memory_bytes);
+        let result = std::thread::scope(|scope| {
+            ingest_queue::spawn_encoders(scope, &queue, &jobs, pipeline.threads, self.manifest.chunk_size(), config);
+            let result = self.bundle_queued(&queue, &jobs, verbose, config);
+            queue.close();
+            result
+        });
+        self.pipeline_stats.merge(&queue.stats());
+        result
+    }
+
+    /// Store the files of `jobs` as the workers queue them.
+    fn bundle_queued(
+        &mut self,
+        queue: &IngestQueue<Piece>,
+        jobs: &[(PathBuf, String)],
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        let mut progress = JobProgress::default();
+        for (i, (_, logical_path)) in jobs.iter().enumerate() {
+            self.control.checkpoint(progress)?;
+            let metadata = match queue.pop(i) {
+                Some(Piece::Opened(metadata)) => metadata,
+                Some(Piece::Failed(e)) => return Err(e.into()),
+                _ => return Err(io::Error::other("ingest queue closed early").into()),
+            };
+            let source = QueuedFile::new(queue, i);
+            let result = self.ingest_opened(source, &metadata, logical_path.clone(), verbose, config);
+            // A file skipped or failed part way leaves chunks behind.
+            queue.drop_file(i);
+            queue.set_held(self.held_memory_bytes);
+            match result {
+                Err(EmbrError::Cancelled(_)) => return Err(EmbrError::Cancelled(progress)),
+                result => result?,
+            }
+            progress.files += 1;
+            progress.bytes += metadata.len();
+        }
+        Ok(())
+    }
+
     /// Ingest a single file into the engram with guaranteed reconstruction
     ///
     /// This method encodes file data into sparse vectors and stores any
@@ -839,125 +3415,894 @@ impl EmbrFS {
     ///
     /// # Returns
     /// `io::Result<()>` indicating success or failure
+    ///
+    /// The file is read one chunk at a time and never held in memory whole,
+    /// so its size is bounded by the engram, not by RAM. The manifest records
+    /// the bytes actually read, even if the file changed size meanwhile.
     pub fn ingest_file<P: AsRef<Path>>(
         &mut self,
         file_path: P,
         logical_path: String,
         verbose: bool,
         config: &ReversibleVSAConfig,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
         let file_path = file_path.as_ref();
-        let file_len = fs::metadata(file_path)?.len() as usize;
+        let metadata = fs::metadata(file_path)?;
         let file = File::open(file_path)?;
-        let mut reader = BufReader::with_capacity(64 * 1024, file);
+        let reader = BufReader::with_capacity(64 * 1024, file);
+        self.ingest_opened(reader, &metadata, logical_path, verbose, config)
+    }
 
-        let chunk_size = DEFAULT_CHUNK_SIZE;
+    /// Ingest an opened file with the given `metadata`.
+    fn ingest_opened<S: ChunkSource>(
+        &mut self,
+        source: S,
+        metadata: &fs::Metadata,
+        logical_path: String,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        if self.ingest_reader(source, Some(metadata.len() as usize), logical_path, None, verbose, config)? {
+            if let Some(entry) = self.manifest.files.last_mut() {
+                entry.unix = UnixMeta::of(metadata);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ingest an in-memory file under `logical_path`.
+    ///
+    /// Behaves like [`EmbrFS::ingest_file`], including ingest limits, for
+    /// content that did not come from the local filesystem.
+    pub fn ingest_bytes(&mut self, data: &[u8], logical_path: String, config: &ReversibleVSAConfig) -> Result<()> {
+        self.ingest_reader(data, Some(data.len()), logical_path, None, false, config)?;
+        Ok(())
+    }
+
+    /// Ingest `data` under `logical_path` as a new version of the file at
+    /// `base_path`.
+    ///
+    /// Each chunk is encoded as usual, but its correction is stored as a
+    /// binary delta against the same chunk of the base version whenever that
+    /// is smaller, so a large file that changed slightly costs little more
+    /// than its changes. Extraction applies the deltas transparently, and
+    /// removing the base version stores its dependents verbatim first.
+    pub fn ingest_version(
+        &mut self,
+        data: &[u8],
+        logical_path: String,
+        base_path: &str,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        let Some(base) = self.manifest.files.iter().rev().find(|f| f.path == base_path).cloned() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no file at {} to diff against", base_path)).into());
+        };
+        self.ingest_reader(data, Some(data.len()), logical_path, Some(&base), false, config)?;
+        Ok(())
+    }
+
+    /// Ingest everything `reader` yields, up to end of stream, under
+    /// `logical_path`.
+    ///
+    /// For pipes, sockets and other sources whose length is not known up
+    /// front. Chunks are encoded as they fill, holding one chunk in memory;
+    /// short reads are gathered into whole chunks, so the result is the same
+    /// as ingesting the bytes from a file. Ingest limits are enforced as the
+    /// stream grows.
+    pub fn ingest_stream<R: Read>(
+        &mut self,
+        reader: R,
+        logical_path: String,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        self.ingest_reader(reader, None, logical_path, None, false, config)?;
+        Ok(())
+    }
+
+    /// Remove the file at `logical_path`, returning whether it existed.
+    ///
+    /// Chunks no other file references are dropped from the codebook,
+    /// corrections and semantic signatures. Their votes are subtracted from
+    /// a counted root ([`Engram::count_root`]); otherwise the root is rebuilt
+    /// from the remaining chunks in id order (the order ingest bundles them).
+    /// Chunk ids are never reused: `manifest.total_chunks` keeps counting.
+    /// The file's role bindings go with it.
+    pub fn remove_file(&mut self, logical_path: &str) -> bool {
+        let Some(pos) = self.manifest.files.iter().position(|f| f.path == logical_path) else {
+            return false;
+        };
+        let entry = self.manifest.files.remove(pos);
+        if let Some(schema) = &mut self.engram.schema {
+            schema.unbind(logical_path);
+        }
+        self.held_memory_bytes = self
+            .held_memory_bytes
+            .saturating_sub(std::mem::size_of::<FileEntry>() as u64 + entry.memory_bytes());
+        let still_used: HashSet<usize> = self
+            .manifest
+            .files
+            .iter()
+            .flat_map(|f| f.chunks.iter().copied())
+            .collect();
+
+        let chunk_size = self.manifest.chunk_size();
+        let unused: Vec<(usize, usize)> = entry
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| !still_used.contains(id))
+            .map(|(i, &id)| (id, Self::chunk_len(&entry, i, chunk_size)))
+            .collect();
+        self.discard_chunks(&unused);
+        true
+    }
+
+    /// Drop the `(id, len)` chunks from the codebook, corrections, semantic
+    /// signatures and chunk index, and take them out of the root.
+    pub(crate) fn discard_chunks(&mut self, chunks: &[(usize, usize)]) {
+        if chunks.is_empty() {
+            return;
+        }
+        let ids: HashSet<u64> = chunks.iter().map(|&(id, _)| id as u64).collect();
+        self.engram.detach_deltas(&ids, &self.vsa_config());
+        let mut removed = Vec::new();
+        for &(id, len) in chunks {
+            if let Some(vec) = self.engram.codebook.remove(&id) {
+                self.estimated_engram_bytes = self
+                    .estimated_engram_bytes
+                    .saturating_sub(self.engram.estimated_chunk_bytes(id, &vec));
+                self.held_memory_bytes = self
+                    .held_memory_bytes
+                    .saturating_sub(self.engram.chunk_memory_bytes(id, &vec));
+                removed.push(vec);
+            }
+            self.engram.corrections.remove(id as u64, len);
+            if let Some(semantic) = &mut self.semantic {
+                if let Some(v) = semantic.vectors.remove(&id) {
+                    self.held_memory_bytes = self.held_memory_bytes.saturating_sub(
+                        memory::sparse_vec_bytes(&v) + memory::slot_bytes::<usize, SparseVec>(),
+                    );
+                }
+            }
+        }
+        if let Some(index) = &mut self.chunk_index {
+            let codebook = &self.engram.codebook;
+            let before = index.ids.len();
+            index.ids.retain(|_, id| codebook.contains_key(id));
+            self.held_memory_bytes = self
+                .held_memory_bytes
+                .saturating_sub((before - index.ids.len()) as u64 * memory::slot_bytes::<[u8; 32], usize>());
+        }
+
+        self.root_tree = None;
+        self.engram.unbundle_from_root(&removed, &self.vsa_config().root_strategy);
+    }
+
+    /// Add newly stored chunk `vec` to the root, as the manifest's
+    /// [`RootStrategy`] builds it. Call before inserting it into the codebook.
+    fn bundle_into_root(&mut self, vec: &SparseVec) {
+        let _t = profile::scope("bundle_into_root");
+        let strategy = self.vsa_config().root_strategy;
+        let RootStrategy::Tree { fan_in } = strategy else {
+            return self.engram.bundle_into_root(vec, &strategy);
+        };
+        let codebook = &self.engram.codebook;
+        let tree = self.root_tree.get_or_insert_with(|| TreeBundle::new(fan_in));
+        if tree.len() != codebook.len() {
+            *tree = TreeBundle::new(fan_in);
+            let mut ids: Vec<&usize> = codebook.keys().collect();
+            ids.sort_unstable();
+            for id in ids {
+                tree.push(&codebook[id]);
+            }
+        }
+        tree.push(vec);
+        self.engram.root_counts = None;
+        self.engram.root = tree.root();
+    }
+
+    /// Rebuild the root from the codebook with the manifest's
+    /// [`RootStrategy`].
+    pub fn rebuild_root(&mut self) {
+        self.root_tree = None;
+        let strategy = self.vsa_config().root_strategy;
+        self.engram.rebuild_root_with(&strategy);
+    }
+
+    /// Ingest `reader` chunk by chunk. `size_hint` is the expected length,
+    /// used to fail fast on limits; the recorded size is what was read.
+    ///
+    /// With a `base` file, each chunk's correction may be stored as a delta
+    /// against the chunk at the same index of `base` (see
+    /// [`CorrectionStore::add_with_base`]).
+    ///
+    /// `logical_path` is normalized as the manifest's
+    /// [`PathNormalization`] says before anything else sees it. Returns
+    /// false if an ingest filter skipped the file.
+    fn ingest_reader<S: ChunkSource>(
+        &mut self,
+        mut source: S,
+        size_hint: Option<usize>,
+        logical_path: String,
+        base: Option<&FileEntry>,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+    ) -> Result<bool> {
+        let _t = profile::scope("ingest_file");
+        self.manifest.check_vsa_config(config)?;
+        let logical_path = self.manifest.path_normalization.apply(logical_path);
+        if let Some(file_len) = size_hint {
+            self.check_file_limits(file_len as u64, &logical_path)?;
+        }
+
+        let chunk_size = self.manifest.chunk_size();
         let mut chunks = Vec::new();
         let mut corrections_needed = 0usize;
 
         let mut buf = vec![0u8; chunk_size];
         let mut is_text: Option<bool> = None;
         let mut i = 0usize;
+        let mut hasher = blake3::Hasher::new();
+
+        // Filters see the original bytes; everything below sees them
+        // redacted and transformed.
+        let mut redactions = if self.filters.is_empty() {
+            Vec::new()
+        } else {
+            match self
+                .filters
+                .check_file(&logical_path, size_hint.map(|len| len as u64))?
+            {
+                Verdict::Store(ranges) => ranges,
+                Verdict::Skip => return Ok(false),
+            }
+        };
+        let mut offset = 0u64;
+        // Chunks stored so far as (id, len), dropped again if a filter
+        // rejects a later chunk.
+        let mut stored = Vec::new();
 
         loop {
-            let n = reader.read(&mut buf)?;
+            if self.control.is_cancelled() {
+                self.discard_chunks(&stored);
+                return Err(EmbrError::Cancelled(JobProgress::default()));
+            }
+            let n = source.read_chunk(&mut buf)?;
             if n == 0 {
                 break;
             }
+            self.control.throttle(n as u64);
+            if size_hint.is_none() {
+                self.check_file_limits(offset + n as u64, &logical_path)?;
+            }
+            if !self.filters.is_empty() {
+                match self.filters.check_chunk(&logical_path, offset, &buf[..n]) {
+                    Ok(Verdict::Store(ranges)) => redactions.extend(ranges),
+                    Ok(Verdict::Skip) => {
+                        self.discard_chunks(&stored);
+                        return Ok(false);
+                    }
+                    Err(e) => {
+                        self.discard_chunks(&stored);
+                        return Err(e);
+                    }
+                }
+                ingest_filter::redact(&mut buf[..n], offset, &redactions);
+                self.filters.transform_chunk(&logical_path, offset, &mut buf[..n]);
+            }
+            offset += n as u64;
             let chunk = &buf[..n];
+            hasher.update(chunk);
 
             if is_text.is_none() {
                 let t = is_text_file(chunk);
                 is_text = Some(t);
 
-                if verbose {
-                    println!(
-                        "Ingesting {}: {} bytes ({})",
-                        logical_path,
-                        file_len,
-                        if t { "text" } else { "binary" }
-                    );
+                let size = size_hint.map_or("streamed".to_string(), |len| format!("{} bytes", len));
+                logging::progress(
+                    verbose,
+                    "fs",
+                    format_args!("Ingesting {}: {} ({})", logical_path, size, if t { "text" } else { "binary" }),
+                );
+            }
+
+            let dedup_key = self.chunk_index.as_ref().map(|_| ChunkIndex::key(&logical_path, chunk));
+            if let (Some(index), Some(key)) = (&self.chunk_index, &dedup_key) {
+                if let Some(&existing) = index.ids.get(key) {
+                    chunks.push(existing);
+                    continue;
                 }
             }
 
             let chunk_id = self.manifest.total_chunks + i;
             
-            // Encode chunk to sparse vector
-            let chunk_vec = SparseVec::encode_data(chunk, config, Some(&logical_path));
-            
-            // Immediately verify: decode and compare
-            let decoded = chunk_vec.decode_data(config, Some(&logical_path), chunk.len());
+            let (chunk_vec, decoded) = match source.encoded(chunk) {
+                Some(encoded) => encoded,
+                None => {
+                    // Encode chunk to sparse vector
+                    let chunk_vec = {
+                        let _t = profile::scope("encode_chunk");
+                        SparseVec::encode_chunk(chunk, config, Some(&logical_path))
+                    };
+                    // Immediately verify: decode and compare
+                    let _t = profile::scope("verify_chunk");
+                    let decoded = chunk_vec.decode_data(config, Some(&logical_path), chunk.len());
+                    (chunk_vec, decoded)
+                }
+            };
+            let _t = profile::scope("store_chunk");
             
             // Store correction if needed (guarantees reconstruction)
-            self.engram.corrections.add(chunk_id as u64, chunk, &decoded);
+            let base_chunk = base.and_then(|b| {
+                let idx = chunks.len();
+                let base_id = *b.chunks.get(idx)?;
+                let len = Self::chunk_len(b, idx, chunk_size);
+                Some((base_id, b, self.engram.chunk_bytes(base_id as u64, &b.path, len, config)?))
+            });
+            match base_chunk {
+                Some((base_id, b, base_bytes)) => {
+                    self.engram
+                        .corrections
+                        .add_with_base(chunk_id as u64, chunk, &decoded, base_id as u64, &b.path, &base_bytes)
+                }
+                None => self.engram.corrections.add(chunk_id as u64, chunk, &decoded),
+            }
             
             if chunk != decoded.as_slice() {
                 corrections_needed += 1;
             }
 
-            self.engram.root = self.engram.root.bundle(&chunk_vec);
+            self.estimated_engram_bytes += self.engram.estimated_chunk_bytes(chunk_id, &chunk_vec);
+            if let Some(max) = self.limits.max_engram_bytes {
+                if self.estimated_engram_bytes > max {
+                    return Err(QuotaExceeded {
+                        kind: QuotaKind::EngramBytes,
+                        limit: max,
+                        attempted: self.estimated_engram_bytes,
+                        path: logical_path,
+                    }
+                    .into());
+                }
+            }
+
+            if let (Some(encoder), Some(semantic)) = (&self.semantic_encoder, &mut self.semantic) {
+                semantic.vectors.insert(chunk_id, encoder.encode(chunk)?);
+            }
+
+            self.bundle_into_root(&chunk_vec);
+            self.held_memory_bytes += self.engram.chunk_memory_bytes(chunk_id, &chunk_vec)
+                + self.semantic.as_ref().and_then(|s| s.vectors.get(&chunk_id)).map_or(0, |v| {
+                    memory::sparse_vec_bytes(v) + memory::slot_bytes::<usize, SparseVec>()
+                });
             self.engram.codebook.insert(chunk_id, chunk_vec);
             chunks.push(chunk_id);
+            stored.push((chunk_id, n));
+            if let (Some(index), Some(key)) = (&mut self.chunk_index, dedup_key) {
+                index.ids.insert(key, chunk_id);
+                self.held_memory_bytes += memory::slot_bytes::<[u8; 32], usize>();
+            }
+            if let Err(e) = self.check_memory_limit(&logical_path) {
+                self.discard_chunks(&stored);
+                return Err(e);
+            }
 
             i += 1;
         }
 
-        if verbose && corrections_needed > 0 {
-            println!(
-                "  → {} of {} chunks needed correction",
-                corrections_needed,
-                chunks.len()
+        if corrections_needed > 0 {
+            logging::progress(
+                verbose,
+                "fs",
+                format_args!("  → {} of {} chunks needed correction", corrections_needed, chunks.len()),
             );
         }
 
-        self.manifest.files.push(FileEntry {
+        let signature = if self.file_signatures {
+            Self::bundle_signature(&self.engram, &chunks)
+        } else {
+            None
+        };
+        // A manifest from before inodes were recorded gets its existing
+        // files numbered first, in the order its mounts used to.
+        if self.manifest.inodes.is_empty() {
+            self.manifest.assign_inodes();
+        }
+        let entry = FileEntry {
             path: logical_path,
             is_text: is_text.unwrap_or(true),
-            size: file_len,
+            size: offset as usize,
             chunks: chunks.clone(),
-        });
+            blake3: Some(hasher.finalize().to_hex().to_string()),
+            ingested_at: self.clock.stamp(),
+            signature,
+            unix: None,
+        };
+        let entry_bytes = std::mem::size_of::<FileEntry>() as u64 + entry.memory_bytes();
+        self.held_memory_bytes += entry_bytes;
+        if let Err(e) = self.check_memory_limit(&entry.path) {
+            self.held_memory_bytes -= entry_bytes;
+            self.discard_chunks(&stored);
+            return Err(e);
+        }
+        self.manifest.inodes.assign(&entry.path);
+        self.manifest.files.push(entry);
+
+        // Only newly stored chunks take fresh ids.
+        self.manifest.total_chunks += i;
+        if let Some(monitor) = &mut self.capacity_monitor {
+            monitor.observe(&self.engram);
+        }
+
+        Ok(true)
+    }
+
+    /// Fail if a file of `len` bytes is over the file size limit, or its
+    /// chunks would take the engram over the chunk limit.
+    fn check_file_limits(&self, len: u64, logical_path: &str) -> Result<()> {
+        if let Some(max) = self.limits.max_file_size {
+            if len > max {
+                return Err(QuotaExceeded {
+                    kind: QuotaKind::FileSize,
+                    limit: max,
+                    attempted: len,
+                    path: logical_path.to_string(),
+                }
+                .into());
+            }
+        }
+        if let Some(max) = self.limits.max_chunks {
+            let projected = self.manifest.total_chunks + (len as usize).div_ceil(self.manifest.chunk_size());
+            if projected > max {
+                return Err(QuotaExceeded {
+                    kind: QuotaKind::ChunkCount,
+                    limit: max as u64,
+                    attempted: projected as u64,
+                    path: logical_path.to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Save engram to file
+    pub fn save_engram<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.save_engram_with_options(path, BinaryWriteOptions::default())
+    }
+
+    /// Save engram to file, optionally compressed.
+    pub fn save_engram_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        opts: BinaryWriteOptions,
+    ) -> Result<()> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        let file = BufWriter::new(File::create(&path)?);
+        self.write_engram(file, opts)?.flush()?;
+        logging::debug(
+            "io",
+            format_args!("saved engram {} ({} chunks)", path.as_ref().display(), self.engram.codebook.len()),
+        );
+        Ok(())
+    }
+
+    /// Save the engram split into part files of at most `part_size` bytes,
+    /// plus a master index at `path`.
+    ///
+    /// [`EmbrFS::load_engram`] given `path` reads the parts transparently.
+    pub fn save_engram_parts<P: AsRef<Path>>(
+        &self,
+        path: P,
+        part_size: u64,
+        opts: BinaryWriteOptions,
+    ) -> Result<PartIndex> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        let parts = PartWriter::create(path, part_size)?;
+        Ok(self.write_engram(parts, opts)?.finish()?)
+    }
+
+    /// Save the engram as an index at `path` plus one shard file per
+    /// `shard_chunks` chunk ids (see [`crate::shards`]).
+    ///
+    /// [`EmbrFS::load_engram`] given `path` loads every shard;
+    /// [`ShardedEngram`] loads them on demand.
+    pub fn save_engram_shards<P: AsRef<Path>>(
+        &self,
+        path: P,
+        shard_chunks: u64,
+        opts: BinaryWriteOptions,
+    ) -> Result<ShardIndex> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        Ok(shards::save(&self.engram, path.as_ref(), shard_chunks, opts)?)
+    }
+
+    /// Save the engram at `path` as references into the shared codebook at
+    /// `codebook_path`, adding the chunk records it lacks (see
+    /// [`crate::shared_codebook`]).
+    pub fn save_engram_shared<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        path: P,
+        codebook_path: Q,
+        opts: BinaryWriteOptions,
+    ) -> Result<SharedSaveReport> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        Ok(shared_codebook::save(&self.engram, path.as_ref(), codebook_path.as_ref(), opts)?)
+    }
+
+    /// Save the engram as an rkyv archive that loads without deserializing
+    /// the codebook (see [`crate::rkyv_engram`]). Requires the `rkyv` feature.
+    pub fn save_engram_rkyv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        Ok(rkyv_engram::save(&self.engram, path)?)
+    }
+
+    /// Save the engram as an `embeddenator.v1.Engram` protobuf message in an
+    /// envelope. Unlike bincode, that layout stays readable across releases
+    /// (see [`crate::wire`]). Requires the `proto` feature.
+    pub fn save_engram_proto<P: AsRef<Path>>(&self, path: P, opts: BinaryWriteOptions) -> Result<()> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        let message = wire::encode_engram(&self.engram)?;
+        let mut file = BufWriter::new(File::create(path)?);
+        let plain = opts.codec == CompressionCodec::None
+            && opts.checksum == ChecksumCodec::None
+            && opts.encryption == EncryptionCodec::None;
+        // Unlike bincode, a bare message could not be told apart from other
+        // payloads, so even plain output carries a header.
+        if plain {
+            file.write_all(&plain_header(PayloadKind::EngramProto, message.len() as u64))?;
+            file.write_all(&message)?;
+            return Ok(file.flush()?);
+        }
+        let mut writer = if opts.codec == CompressionCodec::ZstdDict {
+            let mut sampler = DictionarySampler::new(DICT_FRAME_SIZE);
+            sampler.write_all(&message)?;
+            let dict = sampler.train(DEFAULT_DICT_SIZE)?;
+            EnvelopeWriter::with_dictionary(file, PayloadKind::EngramProto, opts, dict)?
+        } else {
+            EnvelopeWriter::new(file, PayloadKind::EngramProto, opts)?
+        };
+        writer.write_all(&message)?;
+        Ok(writer.finish()?.flush()?)
+    }
+
+    /// Serialize the engram through an envelope writer into `out`.
+    pub(crate) fn write_engram<W: Write>(&self, out: W, opts: BinaryWriteOptions) -> io::Result<W> {
+        // Serialize straight into the envelope so large engrams are never
+        // held in memory twice.
+        let mut writer = if opts.codec == CompressionCodec::ZstdDict {
+            // Sampling pass: train the dictionary on the serialized codebook
+            // without materializing it, then write for real.
+            let mut sampler = DictionarySampler::new(DICT_FRAME_SIZE);
+            bincode::serialize_into(&mut sampler, &self.engram).map_err(|e| bincode_io_error(*e))?;
+            let dict = sampler.train(DEFAULT_DICT_SIZE)?;
+            EnvelopeWriter::with_dictionary(out, PayloadKind::EngramBincode, opts, dict)?
+        } else {
+            EnvelopeWriter::new(out, PayloadKind::EngramBincode, opts)?
+        };
+        bincode::serialize_into(&mut writer, &self.engram).map_err(|e| bincode_io_error(*e))?;
+        writer.finish()
+    }
+
+    /// Load engram from file
+    pub fn load_engram<P: AsRef<Path>>(path: P) -> Result<Engram> {
+        Self::load_engram_with_keys(path, &Keyring::default())
+    }
+
+    /// Load an engram, decrypting it with `keys` if its envelope is encrypted.
+    ///
+    /// `path` may also be the master index of an engram split with
+    /// [`EmbrFS::save_engram_parts`], the index of one sharded with
+    /// [`EmbrFS::save_engram_shards`], a reference into a shared codebook
+    /// written by [`EmbrFS::save_engram_shared`], or an rkyv archive.
+    pub fn load_engram_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> Result<Engram> {
+        Self::load_engram_with_limits(path, keys, &LoadLimits::default())
+    }
+
+    /// Load an engram as [`EmbrFS::load_engram_with_keys`] does, decoding at
+    /// most `limits.max_bytes` and rejecting engrams that fail
+    /// [`Engram::validate`] with [`EmbrError::CorruptEngram`].
+    pub fn load_engram_with_limits<P: AsRef<Path>>(
+        path: P,
+        keys: &Keyring,
+        limits: &LoadLimits,
+    ) -> Result<Engram> {
+        let path = path.as_ref();
+        txn::recover_if_idle(path)?;
+        let engram = match PayloadKind::sniff_file(path)? {
+            Some(PayloadKind::EngramRkyv) => RkyvEngram::open(path)?.to_engram()?,
+            Some(PayloadKind::ShardIndex) => ShardedEngram::open(path, keys)?.to_engram()?,
+            Some(PayloadKind::CodebookRef) => shared_codebook::load(path, keys)?,
+            Some(PayloadKind::EngramProto) => decode_proto_engram(BufReader::new(File::open(path)?), keys, limits)?,
+            _ => {
+                let file = BufReader::new(multipart::open_spanning(path)?);
+                let mut reader = EnvelopeReader::with_keys(file, PayloadKind::EngramBincode, keys)?;
+                let engram = decode_bounded(&mut reader, limits.max_bytes)?;
+                // Drain to the end marker so trailing checksums are verified too.
+                io::copy(&mut reader, &mut io::sink())?;
+                engram
+            }
+        };
+        engram.validate(limits)?;
+        logging::debug(
+            "io",
+            format_args!("loaded engram {} ({} chunks)", path.display(), engram.codebook.len()),
+        );
+        Ok(engram)
+    }
+
+    /// Decode an engram from an in-memory envelope (or legacy raw bincode).
+    ///
+    /// For hosts without a filesystem, such as wasm32 in a browser. Split,
+    /// sharded and rkyv engrams are only readable through
+    /// [`EmbrFS::load_engram_with_keys`].
+    pub fn engram_from_bytes(data: &[u8], keys: &Keyring) -> Result<Engram> {
+        Self::engram_from_bytes_with_limits(data, keys, &LoadLimits::default())
+    }
 
-        self.manifest.total_chunks += chunks.len();
+    /// [`EmbrFS::engram_from_bytes`] with explicit [`LoadLimits`].
+    pub fn engram_from_bytes_with_limits(data: &[u8], keys: &Keyring, limits: &LoadLimits) -> Result<Engram> {
+        if PayloadKind::sniff(data) == Some(PayloadKind::EngramProto) {
+            let engram = decode_proto_engram(data, keys, limits)?;
+            engram.validate(limits)?;
+            return Ok(engram);
+        }
+        let mut reader = EnvelopeReader::with_keys(data, PayloadKind::EngramBincode, keys)?;
+        let engram: Engram = decode_bounded(&mut reader, limits.max_bytes)?;
+        io::copy(&mut reader, &mut io::sink())?;
+        engram.validate(limits)?;
+        Ok(engram)
+    }
+
+    /// Load an engram after checking its detached signature.
+    ///
+    /// The signature covers both the engram and the manifest at `manifest_path`.
+    /// With `VerifyMode::Enforce` a mismatch, or no `trusted_public_key`, is
+    /// returned as an error; with `VerifyMode::Warn` it is logged and loading
+    /// proceeds. The engram is decoded from the bytes that were checked, so
+    /// it must be a single file [`EmbrFS::engram_from_bytes`] reads.
+    pub fn load_engram_verified<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        manifest_path: Q,
+        sig: &DetachedSignature,
+        trusted_public_key: Option<&str>,
+        mode: VerifyMode,
+    ) -> Result<Engram> {
+        txn::recover_if_idle(path.as_ref())?;
+        let (engram, _) = signing::read_checked(&path, manifest_path, sig, trusted_public_key, mode)?;
+        Self::engram_from_bytes(&engram, &Keyring::default())
+    }
+
+    /// Save the engram and manifest to an append-only log at `path`.
+    ///
+    /// Only chunks whose encoding changed since the log's last commit are
+    /// written, chunks that disappeared get tombstones, and the manifest and
+    /// root are rewritten only when they differ. Repeated saves after small
+    /// ingests therefore cost O(delta) rather than O(engram).
+    pub fn save_append_log<P: AsRef<Path>>(&self, path: P) -> Result<AppendStats> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        let mut log = AppendLog::open(path)?;
+        let mut records = Vec::new();
+        let mut stats = AppendStats::default();
+
+        let ids = self.engram.chunk_record_ids();
+        let live: HashSet<u64> = ids.iter().copied().collect();
+
+        for id in ids {
+            let payload = self.engram.encode_chunk_record(id)?;
+            if log.chunk(id).map(|r| r.digest) != Some(append_log::payload_digest(&payload)) {
+                records.push(PendingRecord { kind: RecordKind::Chunk, id, payload });
+                stats.chunks_written += 1;
+            }
+        }
+        let mut removed: Vec<u64> = log.chunk_ids().filter(|id| !live.contains(id)).collect();
+        removed.sort_unstable();
+        for id in removed {
+            records.push(PendingRecord { kind: RecordKind::Tombstone, id, payload: Vec::new() });
+            stats.tombstones += 1;
+        }
+
+        let meta = self.engram.encode_meta_record()?;
+        if log.meta().map(|r| r.digest) != Some(append_log::payload_digest(&meta)) {
+            records.push(PendingRecord { kind: RecordKind::Meta, id: 0, payload: meta });
+            stats.meta_written = true;
+        }
+        let manifest = serde_json::to_vec(&Versioned::new(&self.manifest))?;
+        if log.manifest().map(|r| r.digest) != Some(append_log::payload_digest(&manifest)) {
+            records.push(PendingRecord { kind: RecordKind::Manifest, id: 0, payload: manifest });
+            stats.manifest_written = true;
+        }
+
+        stats.bytes_appended = log.append(records)?;
+        Ok(stats)
+    }
+
+    /// Load the engram and manifest from the latest commit of an append log.
+    ///
+    /// A torn tail from an interrupted save is truncated first, so this
+    /// returns the state of the last save that completed.
+    pub fn load_append_log<P: AsRef<Path>>(path: P) -> Result<(Engram, Manifest)> {
+        let log = AppendLog::open(path)?;
+        let missing = |what: &str| io::Error::new(io::ErrorKind::NotFound, format!("append log has no {}", what));
+
+        let meta = log.read(log.meta().ok_or_else(|| missing("engram metadata"))?)?;
+        let (root, totals) = decode_log_meta(&meta)?;
+        let manifest = Manifest::from_json_slice(&log.read(log.manifest().ok_or_else(|| missing("manifest"))?)?)?;
+
+        let mut codebook = HashMap::new();
+        let mut corrections = HashMap::new();
+        for id in log.chunk_ids().collect::<Vec<_>>() {
+            let payload = log.read(log.chunk(id).expect("id comes from the index"))?;
+            let (vec, correction) = decode_log_chunk(&payload)?;
+            if let Some(vec) = vec {
+                codebook.insert(id as usize, vec);
+            }
+            if let Some(correction) = correction {
+                corrections.insert(id, correction);
+            }
+        }
+
+        let engram = Engram {
+            root,
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, totals),
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        };
+        Ok((engram, manifest))
+    }
+
+    /// Rewrite the append log at `path` with live records only.
+    ///
+    /// Superseded chunk versions, tombstones and the commit chain of every
+    /// earlier save are dropped. Chunks are laid out in the order they are
+    /// read: files by path, so neighbours in a directory sit together, and
+    /// each file's chunks in sequence. The new log is written next to the
+    /// old one and renamed over it, so readers see either the old file or
+    /// the compacted one; handles opened before the rename keep reading the
+    /// old file.
+    pub fn compact<P: AsRef<Path>>(path: P) -> Result<CompactStats> {
+        let path = path.as_ref();
+        seal::ensure_unsealed(path)?;
+        if !append_log::is_append_log(path)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not an append log; only logs accumulate dead records", path.display()),
+            )
+            .into());
+        }
+        let log = AppendLog::open(path)?;
+        let manifest: Manifest = match log.manifest() {
+            Some(r) => Manifest::from_json_slice(&log.read(r)?)?,
+            None => Manifest::default(),
+        };
+        let mut files: Vec<&FileEntry> = manifest.files.iter().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let order: Vec<u64> = files.iter().flat_map(|f| f.chunks.iter().map(|&id| id as u64)).collect();
+
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let staged = tempfile::Builder::new().prefix(".compact").tempfile_in(dir)?.into_temp_path();
+        let compacted = log.compact_ordered_to(&staged, &order)?;
+        let stats = CompactStats {
+            chunks: compacted.chunk_ids().count(),
+            bytes_before: log.len(),
+            bytes_after: compacted.len(),
+        };
+        drop((log, compacted));
+        staged.persist(path).map_err(|e| e.error)?;
+        Ok(stats)
+    }
 
+    /// Save manifest to JSON file
+    pub fn save_manifest<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, &Versioned::new(&self.manifest))?;
         Ok(())
     }
 
-    /// Save engram to file
-    pub fn save_engram<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        self.save_engram_with_options(path, BinaryWriteOptions::default())
+    /// Save the manifest, wrapping the JSON in an envelope when `opts` asks
+    /// for compression, checksums or encryption.
+    ///
+    /// With default options this writes the same plain JSON as
+    /// [`EmbrFS::save_manifest`].
+    pub fn save_manifest_with_options<P: AsRef<Path>>(&self, path: P, opts: BinaryWriteOptions) -> Result<()> {
+        self.ensure_persistent(path.as_ref())?;
+        seal::ensure_unsealed(path.as_ref())?;
+        let file = BufWriter::new(File::create(path)?);
+        Ok(self.write_manifest(file, opts)?.flush()?)
     }
 
-    /// Save engram to file, optionally compressed.
-    pub fn save_engram_with_options<P: AsRef<Path>>(
+    /// Serialize the manifest, enveloped as `opts` asks, into `out`.
+    pub(crate) fn write_manifest<W: Write>(&self, mut out: W, opts: BinaryWriteOptions) -> io::Result<W> {
+        if opts.codec == CompressionCodec::ZstdDict {
+            let json = serde_json::to_vec_pretty(&Versioned::new(&self.manifest))?;
+            out.write_all(&wrap_or_legacy(PayloadKind::ManifestJson, opts, &json)?)?;
+            return Ok(out);
+        }
+        let mut writer = EnvelopeWriter::new(out, PayloadKind::ManifestJson, opts)?;
+        serde_json::to_writer_pretty(&mut writer, &Versioned::new(&self.manifest))?;
+        writer.finish()
+    }
+
+    /// Replace the engram at `engram_path` and the manifest at
+    /// `manifest_path` in one [`Transaction`]: after a crash both files hold
+    /// either their old or their new contents, never one of each or a torn
+    /// write (see [`crate::txn`]).
+    pub fn save_transactional<P: AsRef<Path>, Q: AsRef<Path>>(
         &self,
-        path: P,
-        opts: BinaryWriteOptions,
-    ) -> io::Result<()> {
-        let encoded = bincode::serialize(&self.engram).map_err(io::Error::other)?;
-        let maybe_wrapped = wrap_or_legacy(PayloadKind::EngramBincode, opts, &encoded)?;
-        fs::write(path, maybe_wrapped)?;
-        Ok(())
+        engram_path: P,
+        manifest_path: Q,
+        engram_opts: BinaryWriteOptions,
+        manifest_opts: BinaryWriteOptions,
+    ) -> Result<TxnReport> {
+        self.ensure_persistent(engram_path.as_ref())?;
+        let mut txn = Transaction::begin(&engram_path)?;
+        txn.stage(&engram_path, |out| self.write_engram(out, engram_opts).map(|_| ()))?;
+        txn.stage(&manifest_path, |out| self.write_manifest(out, manifest_opts).map(|_| ()))?;
+        Ok(txn.commit()?)
     }
 
-    /// Load engram from file
-    pub fn load_engram<P: AsRef<Path>>(path: P) -> io::Result<Engram> {
-        let data = fs::read(path)?;
-        let decoded = unwrap_auto(PayloadKind::EngramBincode, &data)?;
-        bincode::deserialize(&decoded).map_err(io::Error::other)
+    /// Load manifest from JSON file
+    pub fn load_manifest<P: AsRef<Path>>(path: P) -> Result<Manifest> {
+        Self::load_manifest_with_keys(path, &Keyring::default())
     }
 
-    /// Save manifest to JSON file
-    pub fn save_manifest<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        let file = File::create(path)?;
-        serde_json::to_writer_pretty(file, &self.manifest)?;
-        Ok(())
+    /// Load a manifest saved as plain JSON or inside an envelope, decrypting
+    /// it with `keys` if needed. Manifests of older schema versions are
+    /// migrated as they load; see [`crate::manifest_schema`].
+    ///
+    /// A recorded [`EncodingConfig`] this build cannot decode, such as one
+    /// with another vector dimension, fails the load.
+    pub fn load_manifest_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> Result<Manifest> {
+        Manifest::from_json(Self::load_manifest_json(path, keys)?)
     }
 
-    /// Load manifest from JSON file
-    pub fn load_manifest<P: AsRef<Path>>(path: P) -> io::Result<Manifest> {
-        let file = File::open(path)?;
-        let manifest = serde_json::from_reader(file)?;
-        Ok(manifest)
+    /// The JSON of a manifest saved as [`EmbrFS::load_manifest_with_keys`]
+    /// reads it, as written and without migrating it.
+    pub fn load_manifest_json<P: AsRef<Path>>(path: P, keys: &Keyring) -> Result<serde_json::Value> {
+        let file = BufReader::new(File::open(path)?);
+        let reader = EnvelopeReader::with_keys(file, PayloadKind::ManifestJson, keys)?;
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Decode a manifest from in-memory JSON or an envelope holding it.
+    pub fn manifest_from_bytes(data: &[u8], keys: &Keyring) -> Result<Manifest> {
+        let reader = EnvelopeReader::with_keys(data, PayloadKind::ManifestJson, keys)?;
+        Manifest::from_json(serde_json::from_reader(reader)?)
+    }
+
+    /// Reconstruct one file into memory. `config` and `chunk_size` are the
+    /// manifest's ([`Manifest::encoding`]).
+    ///
+    /// A checksum mismatch is returned as an `InvalidData` error.
+    pub fn reconstruct_bytes(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+    ) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(file_entry.size);
+        let mismatch = Self::reconstruct_file(engram, file_entry, config, chunk_size, |data| {
+            out.extend_from_slice(data);
+            Ok(())
+        })?;
+        match mismatch {
+            Some(mismatch) => Err(EmbrError::ManifestMismatch(vec![mismatch])),
+            None => Ok(out),
+        }
     }
 
     /// Extract files from engram to directory with guaranteed reconstruction
@@ -972,6 +4317,10 @@ impl EmbrFS {
     /// 2. Apply correction: `decoded_data + correction → original_data`
     /// 3. Verify: Hash matches stored hash (guaranteed by construction)
     ///
+    /// After each file is written, its blake3 digest is compared with the one
+    /// recorded at ingest. All files are still written, but if any mismatch the
+    /// call returns an `InvalidData` error naming the files and offending chunks.
+    ///
     /// # Arguments
     /// * `engram` - The engram containing encoded data and corrections
     /// * `manifest` - File metadata and chunk mappings
@@ -987,73 +4336,697 @@ impl EmbrFS {
         output_dir: P,
         verbose: bool,
         config: &ReversibleVSAConfig,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
+        manifest.check_vsa_config(config)?;
         let output_dir = output_dir.as_ref();
 
-        if verbose {
-            println!(
-                "Extracting {} files to {}",
-                manifest.files.len(),
-                output_dir.display()
-            );
-            let stats = engram.corrections.stats();
-            println!(
+        logging::progress(
+            verbose,
+            "fs",
+            format_args!("Extracting {} files to {}", manifest.files.len(), output_dir.display()),
+        );
+        let stats = engram.corrections.stats();
+        logging::progress(
+            verbose,
+            "fs",
+            format_args!(
                 "  Correction stats: {:.1}% perfect, {:.2}% overhead",
                 stats.perfect_ratio * 100.0,
                 stats.correction_ratio * 100.0
-            );
+            ),
+        );
+
+        Self::extract_files(engram, manifest.files.iter(), output_dir, None, verbose, config, manifest.chunk_size(), None, &JobControl::default(), None, PathPolicy::Strict, None)
+    }
+
+    /// [`EmbrFS::extract`] under `control`: writes are throttled to its rate
+    /// limit, and once it is cancelled extraction stops before the next
+    /// file with [`EmbrError::Cancelled`]. The files written by then are
+    /// complete.
+    pub fn extract_with_control<P: AsRef<Path>>(
+        engram: &Engram,
+        manifest: &Manifest,
+        output_dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+        control: &JobControl,
+    ) -> Result<()> {
+        manifest.check_vsa_config(config)?;
+        Self::extract_files(engram, manifest.files.iter(), output_dir.as_ref(), None, verbose, config, manifest.chunk_size(), None, control, None, PathPolicy::Strict, None)
+    }
+
+    /// [`EmbrFS::extract`], decoding chunks through `cache` so that chunks
+    /// repeated within or across files, or already decoded by another reader
+    /// sharing the cache, are decoded once.
+    pub fn extract_with_cache<P: AsRef<Path>>(
+        engram: &Engram,
+        manifest: &Manifest,
+        output_dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+        cache: &ChunkCache,
+    ) -> Result<()> {
+        manifest.check_vsa_config(config)?;
+        Self::extract_files(
+            engram,
+            manifest.files.iter(),
+            output_dir.as_ref(),
+            None,
+            verbose,
+            config,
+            manifest.chunk_size(),
+            Some(cache),
+            &JobControl::default(),
+            None,
+            PathPolicy::Strict,
+            None,
+        )
+    }
+
+    /// [`EmbrFS::extract`], counting each file written in `access` (see
+    /// [`crate::access_stats`]). Chunks are decoded through `cache` if given.
+    pub fn extract_with_access<P: AsRef<Path>>(
+        engram: &Engram,
+        manifest: &Manifest,
+        output_dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+        cache: Option<&ChunkCache>,
+        access: &AccessStats,
+    ) -> Result<()> {
+        manifest.check_vsa_config(config)?;
+        Self::extract_files(
+            engram,
+            manifest.files.iter(),
+            output_dir.as_ref(),
+            None,
+            verbose,
+            config,
+            manifest.chunk_size(),
+            cache,
+            &JobControl::default(),
+            None,
+            PathPolicy::Strict,
+            Some(access),
+        )
+    }
+
+    /// [`EmbrFS::extract`], also reporting where each chunk came from and
+    /// how far to trust it (see [`crate::provenance`]). With `namespace`,
+    /// only that namespace is extracted, as [`EmbrFS::extract_namespace`]
+    /// does. Chunks are decoded through `cache` if given.
+    pub fn extract_with_provenance<P: AsRef<Path>>(
+        engram: &Engram,
+        manifest: &Manifest,
+        namespace: Option<&str>,
+        output_dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+        cache: Option<&ChunkCache>,
+    ) -> Result<ExtractReport> {
+        Self::extract_with_path_policy(engram, manifest, namespace, output_dir, verbose, config, cache, PathPolicy::Strict)
+    }
+
+    /// [`EmbrFS::extract_with_provenance`], treating manifest paths that
+    /// would land outside `output_dir` as `paths` says (see
+    /// [`crate::safe_path`]). The other extract functions refuse them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn extract_with_path_policy<P: AsRef<Path>>(
+        engram: &Engram,
+        manifest: &Manifest,
+        namespace: Option<&str>,
+        output_dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+        cache: Option<&ChunkCache>,
+        paths: PathPolicy,
+    ) -> Result<ExtractReport> {
+        manifest.check_vsa_config(config)?;
+        let files: Box<dyn Iterator<Item = &FileEntry>> = match namespace {
+            Some(ns) => Box::new(manifest.files_in_namespace(ns)),
+            None => Box::new(manifest.files.iter()),
+        };
+        let mut provenance = Vec::new();
+        Self::extract_files(
+            engram,
+            files,
+            output_dir.as_ref(),
+            namespace,
+            verbose,
+            config,
+            manifest.chunk_size(),
+            cache,
+            &JobControl::default(),
+            Some(&mut provenance),
+            paths,
+            None,
+        )?;
+        let mut report = ExtractReport::new(provenance);
+        report.collisions = manifest.collisions(namespace, Folding::probe(output_dir.as_ref())?);
+        Ok(report)
+    }
+
+    /// Extract only the files of one namespace, with the namespace prefix
+    /// stripped from output paths.
+    pub fn extract_namespace<P: AsRef<Path>>(
+        engram: &Engram,
+        manifest: &Manifest,
+        namespace: &str,
+        output_dir: P,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+    ) -> Result<()> {
+        manifest.check_vsa_config(config)?;
+        if !manifest.namespaces.contains_key(namespace) && manifest.files_in_namespace(namespace).next().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("namespace not found in manifest: {}", namespace),
+            )
+            .into());
         }
+        Self::extract_files(
+            engram,
+            manifest.files_in_namespace(namespace),
+            output_dir.as_ref(),
+            Some(namespace),
+            verbose,
+            config,
+            manifest.chunk_size(),
+            None,
+            &JobControl::default(),
+            None,
+            PathPolicy::Strict,
+            None,
+        )
+    }
 
-        for file_entry in &manifest.files {
-            let file_path = output_dir.join(&file_entry.path);
+    /// Where each of `files` is written under `output_dir`, without
+    /// `strip_namespace`'s prefix. Fails on the first unsafe path under
+    /// [`PathPolicy::Strict`], before anything is written.
+    fn output_paths<'a>(
+        files: impl Iterator<Item = &'a FileEntry>,
+        output_dir: &Path,
+        strip_namespace: Option<&str>,
+        paths: PathPolicy,
+    ) -> Result<Vec<(&'a FileEntry, PathBuf)>> {
+        files
+            .map(|file_entry| {
+                let out_rel = match strip_namespace {
+                    Some(ns) => namespace_relative_path(&file_entry.path, ns).unwrap_or(&file_entry.path),
+                    None => &file_entry.path,
+                };
+                Ok((file_entry, safe_path::output_path(output_dir, out_rel, paths)?))
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn extract_files<'a>(
+        engram: &Engram,
+        files: impl Iterator<Item = &'a FileEntry>,
+        output_dir: &Path,
+        strip_namespace: Option<&str>,
+        verbose: bool,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        cache: Option<&ChunkCache>,
+        control: &JobControl,
+        mut provenance: Option<&mut Vec<FileProvenance>>,
+        paths: PathPolicy,
+        access: Option<&AccessStats>,
+    ) -> Result<()> {
+        let files = Self::output_paths(files, output_dir, strip_namespace, paths)?;
+        let mut mismatches = Vec::new();
+        let mut bulk = BulkFileWriter::new();
+        let mut progress = JobProgress::default();
+        for (file_entry, file_path) in files {
+            if control.is_cancelled() {
+                bulk.finish()?;
+                return Err(EmbrError::Cancelled(progress));
+            }
 
             if let Some(parent) = file_path.parent() {
                 fs::create_dir_all(parent)?;
             }
 
-            let file = File::create(&file_path)?;
-            let mut writer = BufWriter::with_capacity(64 * 1024, file);
-            let num_chunks = file_entry.chunks.len();
-            for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
-                if let Some(chunk_vec) = engram.codebook.get(&chunk_id) {
-                    // Calculate the actual chunk size
-                    // Last chunk may be smaller than DEFAULT_CHUNK_SIZE
-                    let chunk_size = if chunk_idx == num_chunks - 1 {
-                        // Last chunk: remaining bytes
-                        let remaining = file_entry.size - (chunk_idx * DEFAULT_CHUNK_SIZE);
-                        remaining.min(DEFAULT_CHUNK_SIZE)
-                    } else {
-                        DEFAULT_CHUNK_SIZE
-                    };
-                    
-                    // Decode the sparse vector to bytes
-                    // IMPORTANT: Use the same path as during encoding for correct shift calculation
-                    // Also use the same chunk_size as during ingest for correct correction matching
-                    let decoded = chunk_vec.decode_data(config, Some(&file_entry.path), chunk_size);
-                    
-                    // Apply correction to guarantee bit-perfect reconstruction
-                    let chunk_data = if let Some(corrected) = engram.corrections.apply(chunk_id as u64, &decoded) {
-                        corrected
-                    } else {
-                        // No correction found - use decoded directly
-                        // This can happen with legacy engrams or if correction store is empty
-                        decoded
-                    };
+            // Small files are batched so many writes share a syscall; large
+            // ones are streamed rather than held in memory.
+            let _t = profile::scope("extract_file");
+            let started = Instant::now();
+            let mut trace = provenance.is_some().then(Vec::new);
+            let mismatch = if file_entry.size <= BULK_FILE_LIMIT {
+                // The bulk writer hands the buffer back to the pool once written.
+                let mut data = BufferPool::global().take(file_entry.size);
+                let mismatch = Self::reconstruct_file_traced(engram, file_entry, config, chunk_size, cache, trace.as_mut(), |chunk| {
+                    data.extend_from_slice(chunk);
+                    Ok(())
+                })?;
+                bulk.write_file(file_path, data)?;
+                mismatch
+            } else {
+                let mut file = File::create(&file_path)?;
+                let mut staged = BufferPool::global().lease(EXTRACT_STAGING_BYTES);
+                let mismatch = Self::reconstruct_file_traced(engram, file_entry, config, chunk_size, cache, trace.as_mut(), |chunk| {
+                    if staged.len() + chunk.len() > EXTRACT_STAGING_BYTES {
+                        file.write_all(&staged)?;
+                        staged.clear();
+                    }
+                    staged.extend_from_slice(chunk);
+                    Ok(())
+                })?;
+                file.write_all(&staged)?;
+                mismatch
+            };
+            let file_provenance = trace.map(|chunks| FileProvenance::new(&file_entry.path, chunks));
+            if let Some(access) = access {
+                access.record_file(&file_entry.path, &file_entry.chunks, AccessKind::Extract, started.elapsed());
+            }
 
-                    writer.write_all(&chunk_data)?;
+            if let Some(mismatch) = mismatch {
+                mismatches.push(mismatch);
+            }
+            control.throttle(file_entry.size as u64);
+            progress.files += 1;
+            progress.bytes += file_entry.size as u64;
+
+            match file_provenance {
+                Some(file_provenance) => {
+                    logging::progress(
+                        verbose,
+                        "fs",
+                        format_args!(
+                            "Extracted: {} ({}; confidence {:.3})",
+                            file_entry.path, file_provenance.sources, file_provenance.confidence
+                        ),
+                    );
+                    if let Some(provenance) = provenance.as_deref_mut() {
+                        provenance.push(file_provenance);
+                    }
                 }
+                None => logging::progress(verbose, "fs", format_args!("Extracted: {}", file_entry.path)),
             }
+        }
+        bulk.finish()?;
 
-            writer.flush()?;
+        if !mismatches.is_empty() {
+            return Err(EmbrError::ManifestMismatch(mismatches));
+        }
+
+        Ok(())
+    }
+
+    /// Check every file in `manifest` against its recorded blake3 checksum
+    /// without writing anything to disk.
+    pub fn verify(
+        engram: &Engram,
+        manifest: &Manifest,
+        config: &ReversibleVSAConfig,
+    ) -> Result<VerifyReport> {
+        Self::verify_files(engram, manifest, config, None, &JobControl::default())
+    }
+
+    /// [`EmbrFS::verify`] under `control`: reads are throttled to its rate
+    /// limit, and once it is cancelled verification stops before the next
+    /// file with [`EmbrError::Cancelled`].
+    pub fn verify_with_control(
+        engram: &Engram,
+        manifest: &Manifest,
+        config: &ReversibleVSAConfig,
+        control: &JobControl,
+    ) -> Result<VerifyReport> {
+        Self::verify_files(engram, manifest, config, None, control)
+    }
+
+    /// [`EmbrFS::verify`], decoding chunks through `cache`; a later
+    /// extraction or mount sharing the cache reuses the decoded chunks.
+    pub fn verify_with_cache(
+        engram: &Engram,
+        manifest: &Manifest,
+        config: &ReversibleVSAConfig,
+        cache: &ChunkCache,
+    ) -> Result<VerifyReport> {
+        Self::verify_files(engram, manifest, config, Some(cache), &JobControl::default())
+    }
+
+    fn verify_files(
+        engram: &Engram,
+        manifest: &Manifest,
+        config: &ReversibleVSAConfig,
+        cache: Option<&ChunkCache>,
+        control: &JobControl,
+    ) -> Result<VerifyReport> {
+        manifest.check_vsa_config(config)?;
+        let mut report = VerifyReport::default();
+        let mut progress = JobProgress::default();
+        for file_entry in &manifest.files {
+            control.checkpoint(progress)?;
+            if file_entry.blake3.is_none() {
+                report.files_unchecked += 1;
+                continue;
+            }
+            match Self::reconstruct_file_cached(engram, file_entry, config, manifest.chunk_size(), cache, |_| Ok(()))? {
+                Some(mismatch) => report.mismatches.push(mismatch),
+                None => report.files_verified += 1,
+            }
+            control.throttle(file_entry.size as u64);
+            progress.files += 1;
+            progress.bytes += file_entry.size as u64;
+        }
+        Ok(report)
+    }
+
+    /// Size of chunk `chunk_idx` of a file split into `chunk_size`-byte
+    /// chunks; only the last chunk may be short.
+    pub(crate) fn chunk_len(file_entry: &FileEntry, chunk_idx: usize, chunk_size: usize) -> usize {
+        if chunk_idx + 1 == file_entry.chunks.len() {
+            let remaining = file_entry.size.saturating_sub(chunk_idx * chunk_size);
+            remaining.min(chunk_size)
+        } else {
+            chunk_size
+        }
+    }
+
+    /// Decode one chunk and apply its correction, going through `cache` if
+    /// given.
+    ///
+    /// Returns `None` if the chunk is absent from the codebook.
+    fn reconstruct_chunk(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        chunk_idx: usize,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        cache: Option<&ChunkCache>,
+    ) -> Option<ChunkData> {
+        let chunk_id = file_entry.chunks[chunk_idx];
+        let decode = || {
+            let _t = profile::scope("reconstruct_chunk");
+            let chunk_vec = engram.codebook.get(&chunk_id)?;
+            let len = Self::chunk_len(file_entry, chunk_idx, chunk_size);
+
+            // IMPORTANT: Use the same path and chunk size as during ingest so the
+            // shift calculation and correction matching line up.
+            let decoded = chunk_vec.decode_data(config, Some(&file_entry.path), len);
+
+            // No correction found (legacy engram or empty store) - use decoded directly.
+            match engram.apply_correction(chunk_id as u64, &decoded, config) {
+                Some(corrected) => {
+                    BufferPool::global().give(decoded);
+                    Some(corrected)
+                }
+                None => Some(decoded),
+            }
+        };
+        match cache {
+            Some(cache) => cache.get_or_insert_with(chunk_id as u64, decode).map(ChunkData::Shared),
+            // Nothing else holds on to the bytes, so they go to the buffer
+            // pool once the caller is done rather than into an `Arc`.
+            None => decode().map(|bytes| ChunkData::Pooled(BufferPool::global().adopt(bytes))),
+        }
+    }
+
+    /// Reconstruct a file chunk by chunk, feeding bytes to `sink`, and check
+    /// the result against the recorded checksum (if any).
+    pub(crate) fn reconstruct_file<F>(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        sink: F,
+    ) -> io::Result<Option<ChecksumMismatch>>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        Self::reconstruct_file_cached(engram, file_entry, config, chunk_size, None, sink)
+    }
+
+    /// [`EmbrFS::reconstruct_file`], decoding chunks through `cache`.
+    pub(crate) fn reconstruct_file_cached<F>(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        cache: Option<&ChunkCache>,
+        sink: F,
+    ) -> io::Result<Option<ChecksumMismatch>>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        Self::reconstruct_file_traced(engram, file_entry, config, chunk_size, cache, None, sink)
+    }
 
-            if verbose {
-                println!("Extracted: {}", file_entry.path);
+    /// [`EmbrFS::reconstruct_file_cached`], recording the provenance of
+    /// each chunk in `trace` if given.
+    fn reconstruct_file_traced<F>(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        cache: Option<&ChunkCache>,
+        mut trace: Option<&mut Vec<ChunkProvenance>>,
+        mut sink: F,
+    ) -> io::Result<Option<ChecksumMismatch>>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let mut hasher = blake3::Hasher::new();
+        let mut bad_chunks = Vec::new();
+
+        for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
+            let chunk_data = Self::reconstruct_chunk(engram, file_entry, chunk_idx, config, chunk_size, cache);
+            if let Some(trace) = trace.as_deref_mut() {
+                let len = Self::chunk_len(file_entry, chunk_idx, chunk_size);
+                trace.push(ChunkProvenance::of(engram, chunk_id, &file_entry.path, len, chunk_data.as_deref(), config));
+            }
+            let Some(chunk_data) = chunk_data else {
+                bad_chunks.push(chunk_id);
+                continue;
+            };
+            if let Some(correction) = engram.corrections.get(chunk_id as u64) {
+                if !correction.verify(&chunk_data) {
+                    bad_chunks.push(chunk_id);
+                }
             }
+            hasher.update(&chunk_data);
+            sink(&chunk_data)?;
         }
 
+        let Some(expected) = file_entry.blake3.as_ref() else {
+            return Ok(None);
+        };
+        let actual = hasher.finalize().to_hex().to_string();
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(None);
+        }
+
+        Ok(Some(ChecksumMismatch {
+            path: file_entry.path.clone(),
+            expected: expected.clone(),
+            actual,
+            chunk_ids: bad_chunks,
+        }))
+    }
+
+    /// Reconstruct bytes `start..end` of a file, decoding only the chunks
+    /// the range overlaps. `end` is clamped to the file size.
+    ///
+    /// There is no whole-file checksum to compare against, so each chunk is
+    /// checked against its correction instead; a missing or failing chunk is
+    /// an `InvalidData` error.
+    #[cfg(feature = "nbd")]
+    pub(crate) fn reconstruct_range<F>(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        start: usize,
+        end: usize,
+        sink: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        Self::reconstruct_range_cached(engram, file_entry, config, chunk_size, start, end, None, sink)
+    }
+
+    /// [`EmbrFS::reconstruct_range`], decoding chunks through `cache`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn reconstruct_range_cached<F>(
+        engram: &Engram,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        start: usize,
+        end: usize,
+        cache: Option<&ChunkCache>,
+        mut sink: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let end = end.min(file_entry.size);
+        if start >= end || file_entry.chunks.is_empty() {
+            return Ok(());
+        }
+        let first = start / chunk_size;
+        let last = ((end - 1) / chunk_size).min(file_entry.chunks.len() - 1);
+        for chunk_idx in first..=last {
+            let chunk_id = file_entry.chunks[chunk_idx];
+            let bad_chunk = || {
+                io::Error::from(EmbrError::MissingChunk {
+                    path: file_entry.path.clone(),
+                    chunk_id,
+                })
+            };
+            let chunk_data =
+                Self::reconstruct_chunk(engram, file_entry, chunk_idx, config, chunk_size, cache).ok_or_else(bad_chunk)?;
+            if let Some(correction) = engram.corrections.get(chunk_id as u64) {
+                if !correction.verify(&chunk_data) {
+                    return Err(bad_chunk());
+                }
+            }
+            let base = chunk_idx * chunk_size;
+            let lo = start.saturating_sub(base).min(chunk_data.len());
+            let hi = (end - base).min(chunk_data.len());
+            sink(&chunk_data[lo..hi])?;
+        }
         Ok(())
     }
 
+    /// The `k` chunks most similar to `data`, best first, with the paths of
+    /// the files that reference each one.
+    ///
+    /// Sweeps the path-depth shifts the same way the `query` command does,
+    /// keeping each chunk's best cosine.
+    #[cfg(any(feature = "grpc", feature = "http", feature = "ffi", feature = "sqlite"))]
+    pub(crate) fn similar_chunks(
+        &self,
+        data: &[u8],
+        k: usize,
+        config: &ReversibleVSAConfig,
+    ) -> Vec<(usize, f64, Vec<String>)> {
+        let index = self.engram.build_codebook_index();
+        Self::similar_chunks_with_index(&self.engram, &self.manifest, &index, data, k, config, None)
+    }
+
+    /// [`EmbrFS::similar_chunks`] over a prebuilt codebook index, optionally
+    /// limited to the chunks in `allowed`.
+    pub(crate) fn similar_chunks_with_index(
+        engram: &Engram,
+        manifest: &Manifest,
+        index: &TernaryInvertedIndex,
+        data: &[u8],
+        k: usize,
+        config: &ReversibleVSAConfig,
+        allowed: Option<&HashSet<usize>>,
+    ) -> Vec<(usize, f64, Vec<String>)> {
+        let base = SparseVec::encode_chunk(data, config, None);
+        let k_sweep = k.saturating_mul(10).max(100);
+        let candidate_k = k_sweep.saturating_mul(10).max(200);
+        let mut best: HashMap<usize, f64> = HashMap::new();
+        for depth in 0..config.max_path_depth.max(1) {
+            let query = base.permute(depth * config.base_shift);
+            let hits = match allowed {
+                Some(allowed) => engram.query_codebook_with_index_filtered(index, &query, candidate_k, k_sweep, allowed),
+                None => engram.query_codebook_with_index(index, &query, candidate_k, k_sweep),
+            };
+            for hit in hits {
+                let score = best.entry(hit.id).or_insert(f64::MIN);
+                *score = score.max(hit.cosine);
+            }
+        }
+        let mut top: Vec<(usize, f64)> = best.into_iter().collect();
+        top.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        top.truncate(k);
+
+        let mut paths = Self::chunk_paths(manifest, top.iter().map(|&(id, _)| id));
+        top.into_iter()
+            .map(|(id, cosine)| (id, cosine, paths.remove(&id).unwrap_or_default()))
+            .collect()
+    }
+
+    /// The paths of the files in `manifest` that reference each of `ids`.
+    pub(crate) fn chunk_paths(manifest: &Manifest, ids: impl Iterator<Item = usize>) -> HashMap<usize, Vec<String>> {
+        let mut paths: HashMap<usize, Vec<String>> = ids.map(|id| (id, Vec::new())).collect();
+        for file in &manifest.files {
+            for id in &file.chunks {
+                if let Some(list) = paths.get_mut(id) {
+                    if list.last() != Some(&file.path) {
+                        list.push(file.path.clone());
+                    }
+                }
+            }
+        }
+        paths
+    }
+
+    /// The signature of `file`: the recorded [`FileEntry::signature`], or
+    /// one bundled from the codebook for files ingested without. `None` if
+    /// one of its chunk vectors is missing.
+    pub fn file_signature(engram: &Engram, file: &FileEntry) -> Option<SparseVec> {
+        match &file.signature {
+            Some(signature) => Some(signature.clone()),
+            None => Self::bundle_signature(engram, &file.chunks),
+        }
+    }
+
+    fn bundle_signature(engram: &Engram, chunks: &[usize]) -> Option<SparseVec> {
+        let vectors = chunks.iter().map(|id| engram.codebook.get(id)).collect::<Option<Vec<_>>>()?;
+        Some(SparseVec::bundle_sum_many(vectors))
+    }
+
+    /// Signatures of the non-empty files in the manifest, shifted back by
+    /// their path shift so files at different paths compare, in manifest
+    /// order.
+    fn signatures_by_path(&self) -> Vec<(&str, SparseVec)> {
+        let config = self.vsa_config();
+        self.manifest
+            .files
+            .iter()
+            .filter(|f| !f.chunks.is_empty())
+            .filter_map(|f| {
+                let signature = Self::file_signature(&self.engram, f)?;
+                Some((f.path.as_str(), signature.inverse_permute(config.path_shift(&f.path))))
+            })
+            .collect()
+    }
+
+    /// The `k` files whose signatures are closest to that of the file at
+    /// `path`, wherever they are stored, best first, with their cosines.
+    /// Empty if there is no such file.
+    pub fn similar_files(&self, path: &str, k: usize) -> Vec<(String, f64)> {
+        let signatures = self.signatures_by_path();
+        let Some((_, query)) = signatures.iter().find(|(p, _)| *p == path) else {
+            return Vec::new();
+        };
+        let mut hits: Vec<(String, f64)> = signatures
+            .iter()
+            .filter(|(p, _)| *p != path)
+            .map(|(p, signature)| (p.to_string(), query.cosine(signature)))
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hits.truncate(k);
+        hits
+    }
+
+    /// Pairs of files, wherever they are stored, whose signatures have a
+    /// cosine of at least `min_cosine`, most similar first. Compares every
+    /// pair, so it is meant for manifests of moderate size.
+    pub fn near_duplicate_files(&self, min_cosine: f64) -> Vec<(String, String, f64)> {
+        let signatures = self.signatures_by_path();
+        let mut pairs = Vec::new();
+        for (i, (a, sig_a)) in signatures.iter().enumerate() {
+            for (b, sig_b) in &signatures[i + 1..] {
+                let cosine = sig_a.cosine(sig_b);
+                if cosine >= min_cosine {
+                    pairs.push((a.to_string(), b.to_string(), cosine));
+                }
+            }
+        }
+        pairs.sort_by(|x, y| y.2.total_cmp(&x.2).then_with(|| (&x.0, &x.1).cmp(&(&y.0, &y.1))));
+        pairs
+    }
+
     /// Extract files using resonator-enhanced pattern completion with guaranteed reconstruction
     ///
     /// Performs filesystem extraction with intelligent recovery capabilities powered by
@@ -1111,46 +5084,44 @@ impl EmbrFS {
         output_dir: P,
         verbose: bool,
         config: &ReversibleVSAConfig,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
         if self.resonator.is_none() {
             return Self::extract(&self.engram, &self.manifest, output_dir, verbose, config);
         }
 
+        self.manifest.check_vsa_config(config)?;
         let _resonator = self.resonator.as_ref().unwrap();
         let output_dir = output_dir.as_ref();
 
-        if verbose {
-            println!(
+        logging::progress(
+            verbose,
+            "fs",
+            format_args!(
                 "Extracting {} files with resonator enhancement to {}",
                 self.manifest.files.len(),
                 output_dir.display()
-            );
-            let stats = self.engram.corrections.stats();
-            println!(
+            ),
+        );
+        let stats = self.engram.corrections.stats();
+        logging::progress(
+            verbose,
+            "fs",
+            format_args!(
                 "  Correction stats: {:.1}% perfect, {:.2}% overhead",
                 stats.perfect_ratio * 100.0,
                 stats.correction_ratio * 100.0
-            );
-        }
-
-        for file_entry in &self.manifest.files {
-            let file_path = output_dir.join(&file_entry.path);
+            ),
+        );
 
+        for (file_entry, file_path) in Self::output_paths(self.manifest.files.iter(), output_dir, None, PathPolicy::Strict)? {
             if let Some(parent) = file_path.parent() {
                 fs::create_dir_all(parent)?;
             }
 
             let file = File::create(&file_path)?;
             let mut writer = BufWriter::with_capacity(64 * 1024, file);
-            let num_chunks = file_entry.chunks.len();
             for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
-                // Calculate the actual chunk size
-                let chunk_size = if chunk_idx == num_chunks - 1 {
-                    let remaining = file_entry.size - (chunk_idx * DEFAULT_CHUNK_SIZE);
-                    remaining.min(DEFAULT_CHUNK_SIZE)
-                } else {
-                    DEFAULT_CHUNK_SIZE
-                };
+                let chunk_size = Self::chunk_len(file_entry, chunk_idx, self.manifest.chunk_size());
                 
                 let chunk_data = if let Some(vector) = self.engram.codebook.get(&chunk_id) {
                     // Decode the SparseVec back to bytes using reversible encoding
@@ -1158,7 +5129,7 @@ impl EmbrFS {
                     let decoded = vector.decode_data(config, Some(&file_entry.path), chunk_size);
                     
                     // Apply correction to guarantee bit-perfect reconstruction
-                    if let Some(corrected) = self.engram.corrections.apply(chunk_id as u64, &decoded) {
+                    if let Some(corrected) = self.engram.apply_correction(chunk_id as u64, &decoded, config) {
                         corrected
                     } else {
                         decoded
@@ -1174,17 +5145,17 @@ impl EmbrFS {
                     let decoded = recovered_vec.decode_data(config, Some(&file_entry.path), chunk_size);
                     
                     // Apply correction if available (may not be if chunk was lost)
-                    if let Some(corrected) = self.engram.corrections.apply(chunk_id as u64, &decoded) {
+                    if let Some(corrected) = self.engram.apply_correction(chunk_id as u64, &decoded, config) {
                         corrected
                     } else {
                         // No correction available - best effort recovery
                         decoded
                     }
                 } else {
-                    return Err(io::Error::new(
-                        io::ErrorKind::NotFound, 
-                        format!("Missing chunk {} and no resonator available", chunk_id)
-                    ));
+                    return Err(EmbrError::MissingChunk {
+                        path: file_entry.path.clone(),
+                        chunk_id,
+                    });
                 };
 
                 writer.write_all(&chunk_data)?;
@@ -1192,9 +5163,7 @@ impl EmbrFS {
 
             writer.flush()?;
 
-            if verbose {
-                println!("Extracted with resonator: {}", file_entry.path);
-            }
+            logging::progress(verbose, "fs", format_args!("Extracted with resonator: {}", file_entry.path));
         }
 
         Ok(())
@@ -1211,6 +5180,7 @@ impl EmbrFS {
     /// 2. For each level, apply permutation based on path component hash
     /// 3. Bundle representations level-by-level with sparsity control
     /// 4. Create sub-engrams for intermediate nodes
+    /// 5. Roll file signatures up into a [`ManifestItem::signature`] per node
     ///
     /// # Why this matters
     /// - Enables scalable hierarchical storage beyond flat bundling limits
@@ -1241,7 +5211,7 @@ impl EmbrFS {
         max_level_sparsity: usize,
         verbose: bool,
         _config: &ReversibleVSAConfig,
-    ) -> io::Result<HierarchicalManifest> {
+    ) -> Result<HierarchicalManifest> {
         self.bundle_hierarchically_with_options(max_level_sparsity, None, verbose, _config)
     }
 
@@ -1256,7 +5226,7 @@ impl EmbrFS {
         max_chunks_per_node: Option<usize>,
         verbose: bool,
         _config: &ReversibleVSAConfig,
-    ) -> io::Result<HierarchicalManifest> {
+    ) -> Result<HierarchicalManifest> {
         let mut levels = Vec::new();
         let mut sub_engrams = HashMap::new();
 
@@ -1286,13 +5256,11 @@ impl EmbrFS {
         let max_level = level_prefixes.keys().max().unwrap_or(&0);
 
         for level in 0..=*max_level {
-            if verbose {
-                let item_count = level_prefixes
-                    .get(&level)
-                    .map(|comps| comps.values().map(|files| files.len()).sum::<usize>())
-                    .unwrap_or(0);
-                println!("Processing level {} with {} items", level, item_count);
-            }
+            let item_count = level_prefixes
+                .get(&level)
+                .map(|comps| comps.values().map(|files| files.len()).sum::<usize>())
+                .unwrap_or(0);
+            logging::progress(verbose, "fs", format_args!("Processing level {} with {} items", level, item_count));
 
             let mut level_bundle = SparseVec::new();
             let mut manifest_items = Vec::new();
@@ -1429,6 +5397,7 @@ impl EmbrFS {
                     manifest_items.push(ManifestItem {
                         path: prefix.clone(),
                         sub_engram_id: sub_id,
+                        signature: None,
                     });
                 }
             }
@@ -1447,11 +5416,44 @@ impl EmbrFS {
             });
         }
 
-        Ok(HierarchicalManifest {
+        // Roll signatures up from the deepest level: a node bundles the
+        // signature of the file at its path, if any, and those of its children.
+        let file_signatures: HashMap<&str, SparseVec> = self
+            .manifest
+            .files
+            .iter()
+            .filter(|f| !f.chunks.is_empty())
+            .filter_map(|f| Some((f.path.as_str(), Self::file_signature(&self.engram, f)?)))
+            .collect();
+        let mut rolled: HashMap<String, SparseVec> = HashMap::new();
+        for level in levels.iter_mut().rev() {
+            for item in &mut level.items {
+                let children = sub_engrams.get(&item.sub_engram_id).map_or(&[][..], |sub| &sub.children[..]);
+                let parts: Vec<&SparseVec> = file_signatures
+                    .get(item.path.as_str())
+                    .into_iter()
+                    .chain(children.iter().filter_map(|child| rolled.get(child)))
+                    .collect();
+                if parts.is_empty() {
+                    continue;
+                }
+                let mut signature = SparseVec::bundle_sum_many(parts);
+                if signature.pos.len() + signature.neg.len() > max_level_sparsity {
+                    signature = signature.thin(max_level_sparsity);
+                }
+                rolled.insert(item.sub_engram_id.clone(), signature.clone());
+                item.signature = Some(signature);
+            }
+        }
+
+        let mut hierarchical = HierarchicalManifest {
             version: 1,
             levels,
             sub_engrams,
-        })
+            digests: BTreeMap::new(),
+        };
+        hierarchical.record_digests();
+        Ok(hierarchical)
     }
 
     /// Extract files from hierarchical manifest with manifest-guided traversal
@@ -1497,21 +5499,21 @@ impl EmbrFS {
         output_dir: P,
         verbose: bool,
         config: &ReversibleVSAConfig,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
         let output_dir = output_dir.as_ref();
 
-        if verbose {
-            println!(
+        logging::progress(
+            verbose,
+            "fs",
+            format_args!(
                 "Extracting hierarchical manifest with {} levels to {}",
                 hierarchical.levels.len(),
                 output_dir.display()
-            );
-        }
+            ),
+        );
 
         // For each file in the original manifest, reconstruct it using hierarchical information
-        for file_entry in &self.manifest.files {
-            let file_path = output_dir.join(&file_entry.path);
-
+        for (file_entry, file_path) in Self::output_paths(self.manifest.files.iter(), output_dir, None, PathPolicy::Strict)? {
             if let Some(parent) = file_path.parent() {
                 fs::create_dir_all(parent)?;
             }
@@ -1520,22 +5522,15 @@ impl EmbrFS {
             let mut writer = BufWriter::with_capacity(64 * 1024, file);
 
             // Reconstruct each chunk using hierarchical information
-            let num_chunks = file_entry.chunks.len();
             for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
                 if let Some(chunk_vector) = self.engram.codebook.get(&chunk_id) {
-                    // Calculate the actual chunk size
-                    let chunk_size = if chunk_idx == num_chunks - 1 {
-                        let remaining = file_entry.size - (chunk_idx * DEFAULT_CHUNK_SIZE);
-                        remaining.min(DEFAULT_CHUNK_SIZE)
-                    } else {
-                        DEFAULT_CHUNK_SIZE
-                    };
+                    let chunk_size = Self::chunk_len(file_entry, chunk_idx, self.manifest.chunk_size());
                     
                     // Decode using hierarchical inverse transformations
                     let decoded = chunk_vector.decode_data(config, Some(&file_entry.path), chunk_size);
                     
                     // Apply correction if available
-                    let chunk_data = if let Some(corrected) = self.engram.corrections.apply(chunk_id as u64, &decoded) {
+                    let chunk_data = if let Some(corrected) = self.engram.apply_correction(chunk_id as u64, &decoded, config) {
                         corrected
                     } else {
                         decoded
@@ -1547,14 +5542,28 @@ impl EmbrFS {
 
             writer.flush()?;
 
-            if verbose {
-                println!("Extracted hierarchical: {}", file_entry.path);
-            }
+            logging::progress(verbose, "fs", format_args!("Extracted hierarchical: {}", file_entry.path));
         }
 
         Ok(())
     }
 }
+
+/// Read until `buf` is full or the reader is exhausted, so chunks only come
+/// up short at the end of a file, however the source splits its reads.
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
 pub fn is_text_file(data: &[u8]) -> bool {
     if data.is_empty() {
         return true;
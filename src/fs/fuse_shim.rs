@@ -47,10 +47,73 @@
 //! ls /mnt/engram
 //! cat /mnt/engram/some/file.txt
 //!
-//! # Unmount
-//! fusermount -u /mnt/engram
+//! # Unmount (or send the mount process SIGINT/SIGTERM)
+//! embeddenator unmount /mnt/engram
 //! ```
 //!
+//! # Access Control
+//!
+//! With [`EngramFS::with_access_policy`], each request is checked against
+//! the grants of the calling uid (see [`crate::access`]): entries in
+//! namespaces the caller may not read are left out of listings and look
+//! absent to lookups, and opening or reading them fails with `EACCES`. Other
+//! users only reach the mount at all if it was made with `allow_other`.
+//!
+//! # Ownership and Encryption
+//!
+//! Files ingested from a Unix filesystem carry their uid, gid and mode
+//! ([`UnixMeta`]); the mount serves those rather than making the mounting
+//! user own everything, translating ids through an [`IdMap`] for hosts whose
+//! ids differ from the source's. The kernel checks permissions against
+//! them (`default_permissions`). Files without the metadata belong to the
+//! mounting user with mode 0644.
+//!
+//! An encrypted engram can be mounted without decrypting it up front:
+//! [`EngramFS::from_lazy`] reads chunks from a [`LazyEngram`], which decrypts
+//! one envelope frame at a time, and only the decoded chunks in the bounded
+//! chunk cache stay in memory as plaintext.
+//!
+//! # Other Frontends
+//!
+//! The `lookup`, `getattr`, `readdir` and `readlink` handlers below go
+//! through [`crate::vfs::VirtualFs`], the same view the HTTP API resolves
+//! paths with; the FUSE layer adds only access checks, TTLs and errnos.
+//!
+//! # Concurrency
+//!
+//! fuser dispatches requests from a single session thread. Everything but
+//! reads is answered from lock-free in-memory tables there; reads, which may
+//! decode chunks, are handed to the worker threads set up by
+//! [`EngramFS::with_read_threads`] and answered from the worker, so one slow
+//! decode doesn't stall every reader behind it. [`EngramFS::latencies`]
+//! keeps a latency histogram per kind of request.
+//!
+//! # Direct I/O
+//!
+//! Files opened with `O_DIRECT` are answered with `FOPEN_DIRECT_IO`, so the
+//! kernel passes their reads straight through instead of filling the page
+//! cache, and chunks those reads decode are not added to the chunk cache
+//! either: one large sequential scan then evicts neither the hot pages nor
+//! the hot chunks other readers rely on. [`EngramFS::with_direct_io`] can
+//! apply this to every open or to none.
+//!
+//! # Access Statistics
+//!
+//! With [`EngramFS::with_access_stats`], every read of an engram-backed file
+//! is counted for the file and the chunks it covers (see
+//! [`crate::access_stats`]); a mount [built from a reader](EngramFS::from_reader)
+//! records into the reader's stats. Give the chunk cache the same recorder
+//! to have it keep the most read chunks.
+//!
+//! # Prefetch
+//!
+//! A job about to work through a file can ask the mount to decode the chunks
+//! it will need first, with ioctl [`PREFETCH_IOCTL`] on an open handle
+//! ([`request_prefetch`] issues it). The ioctl returns at once and the
+//! chunks are decoded into the chunk cache on a worker thread, so later
+//! reads of the range are cache hits. [`EngramReader::prefetch`] does the
+//! same for programs that read an engram without mounting it.
+//!
 //! # Feature Flag
 //!
 //! The FUSE integration requires the `fuse` feature to be enabled:
@@ -60,15 +123,26 @@
 //! embeddenator = { version = "0.2", features = ["fuse"] }
 //! ```
 
-use std::collections::VecDeque;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use arc_swap::ArcSwap;
 use rustc_hash::FxHashMap;
 
-use crate::embrfs::Engram;
+use crate::access::{Access, AccessPolicy};
+use crate::access_stats::{AccessKind, AccessStats};
+use crate::buffer_pool::BufferPool;
+use crate::codebook::ChunkCache;
+use crate::embrfs::{Engram, FileEntry, Manifest, UnixMeta};
+use crate::inodes::InodeTable;
+use crate::latency::{LatencyHistogram, LatencySnapshot};
+use crate::lazy_engram::LazyEngram;
+use crate::reader::EngramReader;
+use crate::memory::{self, MemoryUsage};
 use crate::vsa::ReversibleVSAConfig;
 
 #[cfg(feature = "fuse")]
@@ -77,12 +151,21 @@ use std::ffi::OsStr;
 #[cfg(feature = "fuse")]
 use std::path::Path;
 
+#[cfg(feature = "fuse")]
+use crate::vfs::VirtualFs;
+
 /// Inode number type (matches fuser's u64 inode convention)
 pub type Ino = u64;
 
 /// Root inode number (FUSE convention: inode 1 is root)
 pub const ROOT_INO: Ino = 1;
 
+/// Default maximum number of decoded chunks kept in the read cache.
+pub const DEFAULT_CACHE_ENTRIES: usize = 16_384;
+
+/// Default maximum bytes of decoded chunks kept in the read cache.
+pub const DEFAULT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
 /// File attributes for FUSE
 ///
 /// This mirrors fuser::FileAttr but is always available regardless
@@ -136,8 +219,8 @@ impl Default for FileAttr {
             kind: FileKind::RegularFile,
             perm: 0o644,
             nlink: 1,
-            uid: unsafe { libc::getuid() },
-            gid: unsafe { libc::getgid() },
+            uid: current_uid(),
+            gid: current_gid(),
             rdev: 0,
             blksize: 4096,
             flags: 0,
@@ -145,6 +228,27 @@ impl Default for FileAttr {
     }
 }
 
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(unix)]
+fn current_gid() -> u32 {
+    unsafe { libc::getgid() }
+}
+
+// No process credentials on wasm32 and other non-Unix targets.
+#[cfg(not(unix))]
+fn current_uid() -> u32 {
+    0
+}
+
+#[cfg(not(unix))]
+fn current_gid() -> u32 {
+    0
+}
+
 #[cfg(feature = "fuse")]
 impl From<FileAttr> for fuser::FileAttr {
     fn from(attr: FileAttr) -> Self {
@@ -215,6 +319,338 @@ struct BackedFile {
     path: String,
     chunks: Vec<usize>,
     size: usize,
+    /// Index into [`EngramFS::layers`] of the engram holding the chunks.
+    layer: usize,
+}
+
+/// An engram files are decoded from, with the configuration to decode it.
+#[derive(Clone)]
+struct BackingLayer {
+    engram: LayerEngram,
+    config: ReversibleVSAConfig,
+    /// Chunk size the engram was ingested with.
+    chunk_size: usize,
+}
+
+/// Where a layer's chunks come from.
+#[derive(Clone)]
+enum LayerEngram {
+    /// Fully decoded in memory.
+    Loaded(Arc<Engram>),
+    /// Read (and decrypted) from disk per chunk.
+    Lazy(Arc<LazyEngram>),
+}
+
+impl LayerEngram {
+    fn chunk_bytes(&self, chunk_id: usize, path: &str, len: usize, config: &ReversibleVSAConfig) -> Option<Vec<u8>> {
+        match self {
+            LayerEngram::Loaded(engram) => engram.chunk_bytes(chunk_id as u64, path, len, config),
+            LayerEngram::Lazy(engram) => engram.chunk_bytes(chunk_id, path, len, config).ok().flatten(),
+        }
+    }
+}
+
+/// Translation of stored owner ids to the ids a mount reports. Ids without
+/// a mapping are reported as stored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdMap {
+    pub uids: HashMap<u32, u32>,
+    pub gids: HashMap<u32, u32>,
+}
+
+impl IdMap {
+    /// Report stored uid `from` as `to`.
+    pub fn uid(mut self, from: u32, to: u32) -> Self {
+        self.uids.insert(from, to);
+        self
+    }
+
+    /// Report stored gid `from` as `to`.
+    pub fn gid(mut self, from: u32, to: u32) -> Self {
+        self.gids.insert(from, to);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uids.is_empty() && self.gids.is_empty()
+    }
+
+    /// `attr` with its owner translated.
+    pub fn apply(&self, mut attr: FileAttr) -> FileAttr {
+        attr.uid = self.uids.get(&attr.uid).copied().unwrap_or(attr.uid);
+        attr.gid = self.gids.get(&attr.gid).copied().unwrap_or(attr.gid);
+        attr
+    }
+}
+
+/// Bits of a chunk cache key below the layer index. Layer 0 keys are the
+/// plain chunk ids, so a cache shared with an [`EngramReader`] still lines
+/// up.
+const LAYER_KEY_SHIFT: u32 = 48;
+
+/// Which layer wins when layers of an overlay mount hold the same path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayerPrecedence {
+    /// Layers added later shadow earlier ones, so patches go on top of a
+    /// base.
+    #[default]
+    LaterShadows,
+    /// The first layer holding a path wins.
+    EarlierShadows,
+}
+
+/// Which opens bypass the kernel page cache; see the
+/// [module docs](self#direct-io).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DirectIo {
+    /// Opens with `O_DIRECT`.
+    #[default]
+    OnRequest,
+    /// Every open, for engrams that are streamed through once.
+    Always,
+    /// None; `O_DIRECT` opens are served through the page cache.
+    Never,
+}
+
+impl DirectIo {
+    /// Whether an open with `flags` gets direct I/O.
+    pub fn applies(self, flags: i32) -> bool {
+        match self {
+            DirectIo::OnRequest => O_DIRECT != 0 && flags & O_DIRECT != 0,
+            DirectIo::Always => true,
+            DirectIo::Never => false,
+        }
+    }
+}
+
+/// `O_DIRECT` where the platform has it.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+const O_DIRECT: i32 = libc::O_DIRECT;
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+const O_DIRECT: i32 = 0;
+
+/// File handle bit marking an open with direct I/O.
+#[cfg(feature = "fuse")]
+const FH_DIRECT: u64 = 1;
+
+/// `_IOW('E', 1, [u64; 2])`: warm the chunk cache for a byte range of the
+/// open file. The argument is the offset and length, native-endian. See
+/// [Prefetch](self#prefetch).
+pub const PREFETCH_IOCTL: u32 = (1 << 30) | (16 << 16) | ((b'E' as u32) << 8) | 1;
+
+/// Ask the mount `file` is on to prefetch `len` bytes of it from `offset`.
+#[cfg(target_os = "linux")]
+pub fn request_prefetch(file: &std::fs::File, offset: u64, len: u64) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let range = [offset, len];
+    // SAFETY: the kernel reads the 16 bytes PREFETCH_IOCTL encodes from `range`.
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), PREFETCH_IOCTL as _, range.as_ptr()) };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Most names [`NegativeCache`] remembers by default.
+pub const DEFAULT_NEGATIVE_ENTRIES: usize = 65_536;
+
+/// Names recently looked up and found absent, keyed by parent inode and
+/// name, so repeated probes (`.git`, `node_modules`) skip the directory
+/// scan. Entries expire after the TTL, and adding anything to the tree
+/// clears them all.
+#[derive(Debug)]
+struct NegativeCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<FxHashMap<(Ino, String), Instant>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl NegativeCache {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        NegativeCache {
+            ttl,
+            capacity,
+            entries: Mutex::new(FxHashMap::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, FxHashMap<(Ino, String), Instant>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether `name` under `parent` was found absent within the TTL.
+    fn contains(&self, parent: Ino, name: &str) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        let key = (parent, name.to_string());
+        let mut entries = self.entries();
+        match entries.get(&key) {
+            Some(at) if at.elapsed() < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Some(_) => {
+                entries.remove(&key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    fn insert(&self, parent: Ino, name: &str) {
+        if !self.enabled() {
+            return;
+        }
+        let mut entries = self.entries();
+        if entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            entries.retain(|_, at| at.elapsed() < ttl);
+            if entries.len() >= self.capacity {
+                entries.clear();
+            }
+        }
+        entries.insert((parent, name.to_string()), Instant::now());
+    }
+
+    fn clear(&self) {
+        self.entries().clear();
+    }
+
+    fn stats(&self) -> NegativeLookupStats {
+        NegativeLookupStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries().len(),
+        }
+    }
+}
+
+/// Counters of the negative-lookup cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NegativeLookupStats {
+    /// Lookups answered "absent" from the cache.
+    pub hits: u64,
+    /// Lookups that had to scan the directory.
+    pub misses: u64,
+    /// Names remembered.
+    pub entries: usize,
+}
+
+/// Kinds of request an [`EngramFS`] times.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FuseOp {
+    Lookup,
+    Getattr,
+    Open,
+    Read,
+    Readdir,
+    Access,
+}
+
+impl FuseOp {
+    pub const ALL: [FuseOp; 6] = [
+        FuseOp::Lookup,
+        FuseOp::Getattr,
+        FuseOp::Open,
+        FuseOp::Read,
+        FuseOp::Readdir,
+        FuseOp::Access,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FuseOp::Lookup => "lookup",
+            FuseOp::Getattr => "getattr",
+            FuseOp::Open => "open",
+            FuseOp::Read => "read",
+            FuseOp::Readdir => "readdir",
+            FuseOp::Access => "access",
+        }
+    }
+}
+
+/// Latency histogram per [`FuseOp`], from the request arriving to its
+/// reply (queueing for a worker included).
+#[derive(Debug, Default)]
+pub struct FuseLatencies {
+    ops: [LatencyHistogram; FuseOp::ALL.len()],
+}
+
+impl FuseLatencies {
+    pub fn record(&self, op: FuseOp, dur: Duration) {
+        self.ops[op as usize].record(dur);
+    }
+
+    pub fn snapshot(&self, op: FuseOp) -> LatencySnapshot {
+        self.ops[op as usize].snapshot()
+    }
+}
+
+/// Records the time from its creation to its drop as one `op`.
+#[cfg(feature = "fuse")]
+struct OpTimer {
+    latencies: Arc<FuseLatencies>,
+    op: FuseOp,
+    start: Instant,
+}
+
+#[cfg(feature = "fuse")]
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        self.latencies.record(self.op, self.start.elapsed());
+    }
+}
+
+#[cfg(feature = "fuse")]
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads running jobs from a shared queue. Dropping the pool lets them
+/// finish what is queued and exit.
+#[cfg(feature = "fuse")]
+struct WorkerPool {
+    jobs: std::sync::mpsc::Sender<Job>,
+}
+
+#[cfg(feature = "fuse")]
+impl WorkerPool {
+    fn new(threads: usize) -> std::io::Result<Self> {
+        let (jobs, queue) = std::sync::mpsc::channel::<Job>();
+        let queue = Arc::new(std::sync::Mutex::new(queue));
+        for i in 0..threads {
+            let queue = Arc::clone(&queue);
+            std::thread::Builder::new()
+                .name(format!("engramfs-read-{i}"))
+                .spawn(move || loop {
+                    // The guard drops at the end of the statement, before the job runs.
+                    let job = queue.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })?;
+        }
+        Ok(WorkerPool { jobs })
+    }
+
+    /// Queue `job`. If every worker is gone the job is dropped, and with
+    /// it any reply it holds, which fuser answers with `EIO`.
+    fn run(&self, job: impl FnOnce() + Send + 'static) {
+        let _ = self.jobs.send(Box::new(job));
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -231,70 +667,133 @@ struct FileRecord {
     attr: FileAttr,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-struct ChunkKey {
-    ino: Ino,
-    chunk_id: u64,
+/// What a read needs: the file records as of the read, the backing
+/// engrams and the chunk cache, all shared.
+struct DataPath {
+    files: Arc<FxHashMap<Ino, FileRecord>>,
+    layers: Arc<Vec<BackingLayer>>,
+    chunk_cache: Arc<ChunkCache>,
+    access: Option<Arc<AccessStats>>,
 }
 
-struct ChunkCache {
-    map: FxHashMap<ChunkKey, Vec<u8>>,
-    order: VecDeque<ChunkKey>,
-    total_bytes: usize,
-    max_entries: usize,
-    max_bytes: usize,
-}
-
-impl ChunkCache {
-    fn new(max_entries: usize, max_bytes: usize) -> Self {
-        Self {
-            map: FxHashMap::default(),
-            order: VecDeque::new(),
-            total_bytes: 0,
-            max_entries,
-            max_bytes,
+impl DataPath {
+    /// Bytes `offset..offset + size` of `ino`, in a buffer from the
+    /// [`BufferPool`]. Without `keep`, chunks missing from the chunk cache
+    /// are decoded but not added to it.
+    fn read(&self, ino: Ino, offset: u64, size: u32, keep: bool) -> Option<Vec<u8>> {
+        if size == 0 {
+            return Some(Vec::new());
         }
-    }
 
-    fn get(&mut self, key: ChunkKey) -> Option<&[u8]> {
-        if self.map.contains_key(&key) {
-            // touch
-            if let Some(pos) = self.order.iter().position(|k| *k == key) {
-                self.order.remove(pos);
+        let offset_usize = match usize::try_from(offset) {
+            Ok(v) => v,
+            Err(_) => return Some(Vec::new()),
+        };
+
+        let rec = self.files.get(&ino)?;
+
+        match &rec.storage {
+            FileStorage::Preloaded(data) => {
+                if offset_usize >= data.len() {
+                    return Some(Vec::new());
+                }
+                let end = std::cmp::min(offset_usize.saturating_add(size as usize), data.len());
+                let mut out = BufferPool::global().take(end - offset_usize);
+                out.extend_from_slice(&data[offset_usize..end]);
+                Some(out)
+            }
+            FileStorage::Backed(backed) => {
+                let max_len = backed.size;
+                if offset_usize >= max_len {
+                    return Some(Vec::new());
+                }
+                let end = std::cmp::min(offset_usize.saturating_add(size as usize), max_len);
+                let started = Instant::now();
+                let data = self.read_backed_range(backed, offset_usize, end, keep);
+                if let (Some(access), Some(chunk_size)) = (&self.access, NonZeroUsize::new(self.chunk_size(backed))) {
+                    let first = (offset_usize / chunk_size).min(backed.chunks.len());
+                    let last = end.div_ceil(chunk_size.get()).min(backed.chunks.len());
+                    access.record_file(&backed.path, &backed.chunks[first..last], AccessKind::Read, started.elapsed());
+                }
+                Some(data)
             }
-            self.order.push_back(key);
         }
-        self.map.get(&key).map(|v: &Vec<u8>| v.as_slice())
     }
 
-    fn insert(&mut self, key: ChunkKey, value: Vec<u8>) {
-        if self.max_entries == 0 || self.max_bytes == 0 {
-            return;
+    fn read_backed_range(&self, backed: &BackedFile, start: usize, end: usize, keep: bool) -> Vec<u8> {
+        if start >= end {
+            return Vec::new();
         }
 
-        let value_len = value.len();
-        if value_len > self.max_bytes {
-            // Don't cache single entries bigger than the entire cache.
-            return;
+        let chunk_size = self.chunk_size(backed);
+        if chunk_size == 0 {
+            return Vec::new();
         }
 
-        if let Some(existing) = self.map.remove(&key) {
-            self.total_bytes = self.total_bytes.saturating_sub(existing.len());
-            if let Some(pos) = self.order.iter().position(|k| *k == key) {
-                self.order.remove(pos);
-            }
+        let start_chunk = start / chunk_size;
+        let end_chunk = (end - 1) / chunk_size;
+        if start_chunk >= backed.chunks.len() {
+            return Vec::new();
         }
 
-        self.total_bytes += value_len;
-        self.map.insert(key, value);
-        self.order.push_back(key);
+        let mut out = BufferPool::global().take(end - start);
+        let last_chunk = end_chunk.min(backed.chunks.len().saturating_sub(1));
+
+        for chunk_index in start_chunk..=last_chunk {
+            let Some(chunk_bytes) = self.chunk(backed, chunk_index, keep) else {
+                continue;
+            };
 
-        while self.map.len() > self.max_entries || self.total_bytes > self.max_bytes {
-            let Some(evict) = self.order.pop_front() else { break };
-            if let Some(v) = self.map.remove(&evict) {
-                self.total_bytes = self.total_bytes.saturating_sub(v.len());
+            let (a, b) = slice_chunk_bounds(start, end, chunk_index, chunk_size);
+            if a < b && b <= chunk_bytes.len() {
+                out.extend_from_slice(&chunk_bytes[a..b]);
             }
         }
+
+        out
+    }
+
+    /// Chunk `chunk_index` of `backed`, from the cache or decoded; a decoded
+    /// chunk is cached only with `keep`.
+    fn chunk(&self, backed: &BackedFile, chunk_index: usize, keep: bool) -> Option<Arc<[u8]>> {
+        let BackingLayer { engram, config, chunk_size } = self.layers.get(backed.layer)?;
+        let chunk_id = *backed.chunks.get(chunk_index)?;
+        // Decode at the length ingest encoded, as extraction does.
+        let chunk_len = backed.size.saturating_sub(chunk_index * chunk_size).min(*chunk_size);
+        let key = ((backed.layer as u64) << LAYER_KEY_SHIFT) | chunk_id as u64;
+        let decode = || engram.chunk_bytes(chunk_id, &backed.path, chunk_len, config);
+        if keep {
+            self.chunk_cache.get_or_insert_with(key, decode)
+        } else {
+            self.chunk_cache.get(key).or_else(|| decode().map(Into::into))
+        }
+    }
+
+    /// Decode the chunks of bytes `offset..offset + len` of `ino` into the
+    /// chunk cache, returning how many the range covers (none for preloaded
+    /// files).
+    fn warm(&self, ino: Ino, offset: u64, len: u64) -> Option<usize> {
+        let FileStorage::Backed(backed) = &self.files.get(&ino)?.storage else {
+            return Some(0);
+        };
+        let chunk_size = self.chunk_size(backed);
+        if chunk_size == 0 || len == 0 {
+            return Some(0);
+        }
+        let start = usize::try_from(offset).unwrap_or(usize::MAX) / chunk_size;
+        let end = usize::try_from(offset.saturating_add(len).min(backed.size as u64))
+            .unwrap_or(usize::MAX)
+            .div_ceil(chunk_size)
+            .min(backed.chunks.len());
+        for chunk_index in start..end {
+            self.chunk(backed, chunk_index, true);
+        }
+        Some(end.saturating_sub(start))
+    }
+
+    /// Chunk size of the layer holding `backed`; 0 if there is none.
+    fn chunk_size(&self, backed: &BackedFile) -> usize {
+        self.layers.get(backed.layer).map_or(0, |layer| layer.chunk_size)
     }
 }
 
@@ -309,7 +808,7 @@ impl ChunkCache {
 /// Uses lock-free reads via `ArcSwap` for metadata (read-heavy, write-rare):
 /// - `inodes`, `inode_paths`, `path_inodes`, `directories`, `files`: Lock-free reads via atomic swap
 /// - `next_ino`: Lock-free increment via `AtomicU64`
-/// - `chunk_cache`: `RwLock` with read-then-write pattern for cache access
+/// - `chunk_cache`: [`ChunkCache`], locked only to look up or insert, never while decoding
 ///
 /// This eliminates read-lock contention in the hot path (FUSE operations).
 /// 
@@ -333,30 +832,49 @@ pub struct EngramFS {
     /// File records (ino -> backing/preloaded bytes + attrs) (lock-free reads)
     files: ArcSwap<FxHashMap<Ino, FileRecord>>,
 
-    /// Optional engram backing for on-demand decode.
-    engram: Option<Arc<Engram>>,
-
-    /// Decode config used for on-demand reads.
-    decode_config: Option<ReversibleVSAConfig>,
+    /// Engrams backing on-demand decode; more than one for an overlay
+    /// mount (see [`EngramFSBuilder::layer`]).
+    layers: Arc<Vec<BackingLayer>>,
 
-    /// Chunk size used for decode.
-    chunk_size: usize,
-
-    /// Small LRU chunk cache to avoid repeated decode on hot reads.
-    /// Uses RwLock because LRU cache mutates on read (access order).
-    chunk_cache: Arc<RwLock<ChunkCache>>,
+    /// LRU cache of decoded chunks, so hot reads skip the decode. May be
+    /// shared with other readers of the same engram.
+    chunk_cache: Arc<ChunkCache>,
     
     /// Next available inode number (lock-free increment)
     next_ino: AtomicU64,
     
     /// Read-only mode
     read_only: bool,
+
+    /// Per-uid namespace grants; everything is visible without one.
+    policy: Option<Arc<AccessPolicy>>,
+
+    /// Owner translation applied to attributes handed to the kernel.
+    ids: IdMap,
+
+    /// Time spent serving each kind of request.
+    latencies: Arc<FuseLatencies>,
+
+    /// Threads reads are handed to, so a slow decode doesn't hold up the
+    /// requests behind it; `None` serves reads on the session thread.
+    #[cfg(feature = "fuse")]
+    workers: Option<WorkerPool>,
     
     /// TTL for cached attributes
     attr_ttl: Duration,
     
     /// TTL for cached entries
     entry_ttl: Duration,
+
+    /// Recent lookups of absent names; its TTL is also how long the kernel
+    /// may cache the miss.
+    negative: NegativeCache,
+
+    /// Which opens bypass the page cache.
+    direct_io: DirectIo,
+
+    /// Where reads are counted, if anywhere.
+    access: Option<Arc<AccessStats>>,
 }
 
 impl EngramFS {
@@ -374,14 +892,20 @@ impl EngramFS {
             files: ArcSwap::from_pointee(FxHashMap::default()),
             next_ino: AtomicU64::new(2), // Start after root
             read_only,
+            policy: None,
+            ids: IdMap::default(),
+            latencies: Arc::new(FuseLatencies::default()),
+            #[cfg(feature = "fuse")]
+            workers: None,
             attr_ttl: Duration::from_secs(1),
             entry_ttl: Duration::from_secs(1),
+            negative: NegativeCache::new(Duration::from_secs(1), DEFAULT_NEGATIVE_ENTRIES),
+            direct_io: DirectIo::default(),
+            access: None,
 
-            engram: None,
-            decode_config: None,
-            chunk_size: 4096,
+            layers: Arc::new(Vec::new()),
             // Default: keep this small and bounded for production safety.
-            chunk_cache: Arc::new(RwLock::new(ChunkCache::new(16_384, 64 * 1024 * 1024))),
+            chunk_cache: Arc::new(ChunkCache::with_limits(DEFAULT_CACHE_ENTRIES, DEFAULT_CACHE_BYTES)),
         };
 
         // Initialize root directory
@@ -389,25 +913,155 @@ impl EngramFS {
         fs
     }
 
+    /// Bound the decoded chunk cache to `max_entries` chunks and `max_bytes`
+    /// bytes, dropping anything cached so far. Either limit at 0 disables
+    /// caching.
+    pub fn with_cache_limits(mut self, max_entries: usize, max_bytes: usize) -> Self {
+        self.chunk_cache = Arc::new(ChunkCache::with_limits(max_entries, max_bytes));
+        self
+    }
+
+    /// Use `cache` for decoded chunks, e.g. one also used to extract or
+    /// verify the same engram.
+    pub fn with_chunk_cache(mut self, cache: Arc<ChunkCache>) -> Self {
+        self.chunk_cache = cache;
+        self
+    }
+
+    /// Count reads in `access`; see [Access Statistics](self#access-statistics).
+    pub fn with_access_stats(mut self, access: Arc<AccessStats>) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    /// Only show each uid the namespaces `policy` grants it.
+    pub fn with_access_policy(mut self, policy: AccessPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Report stored owners through `ids`.
+    pub fn with_id_map(mut self, ids: IdMap) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Serve reads on `threads` worker threads rather than the session
+    /// thread, so parallel readers (`grep -r`, builds) are decoded side by
+    /// side; lookups and attributes, answered from memory, stay on the
+    /// session thread. 0 serves everything on the session thread.
+    #[cfg(feature = "fuse")]
+    pub fn with_read_threads(mut self, threads: usize) -> std::io::Result<Self> {
+        self.workers = if threads == 0 { None } else { Some(WorkerPool::new(threads)?) };
+        Ok(self)
+    }
+
+    /// Per-request latency histograms, shared with the mounted filesystem.
+    pub fn latencies(&self) -> &Arc<FuseLatencies> {
+        &self.latencies
+    }
+
+    #[cfg(feature = "fuse")]
+    fn time(&self, op: FuseOp) -> OpTimer {
+        OpTimer {
+            latencies: Arc::clone(&self.latencies),
+            op,
+            start: Instant::now(),
+        }
+    }
+
+    /// Attributes of `ino` as the mount reports them, owner translated.
+    pub fn mapped_attr(&self, ino: Ino) -> Option<FileAttr> {
+        self.get_attr(ino).map(|attr| self.ids.apply(attr))
+    }
+
+    /// Whether `uid` may see and read inode `ino`. The root directory is
+    /// always visible.
+    pub fn permits(&self, uid: u32, ino: Ino) -> bool {
+        let Some(policy) = &self.policy else {
+            return true;
+        };
+        if ino == ROOT_INO {
+            return true;
+        }
+        let Some(grants) = policy.for_uid(uid) else {
+            return false;
+        };
+        let Some(path) = self.inode_paths.load().get(&ino).cloned() else {
+            return false;
+        };
+        match self.get_attr(ino) {
+            Some(attr) if attr.kind == FileKind::Directory => grants.allows(Access::Read, &format!("{}/", path)),
+            Some(_) => grants.allows(Access::Read, &path),
+            None => false,
+        }
+    }
+
+    /// The decoded chunk cache, for its hit and miss counts.
+    pub fn chunk_cache(&self) -> &Arc<ChunkCache> {
+        &self.chunk_cache
+    }
+
     /// Construct an EngramFS backed by an engram+manifest.
     ///
     /// This is the production mount path: we populate directory structure and
     /// file metadata only, and decode chunk data on-demand during reads.
     pub fn from_engram(
         engram: Engram,
-        manifest: crate::embrfs::Manifest,
+        manifest: Manifest,
         decode_config: ReversibleVSAConfig,
         chunk_size: usize,
         read_only: bool,
     ) -> Self {
         let mut fs = Self::new(read_only);
-        fs.engram = Some(Arc::new(engram));
-        fs.decode_config = Some(decode_config);
-        fs.chunk_size = chunk_size;
+        Arc::make_mut(&mut fs.layers).push(BackingLayer {
+            engram: LayerEngram::Loaded(Arc::new(engram)),
+            config: decode_config,
+            chunk_size,
+        });
 
-        for file_entry in &manifest.files {
-            let _ = fs.add_backed_file(&file_entry.path, file_entry.chunks.clone(), file_entry.size);
-        }
+        fs.add_manifest(0, &manifest, true);
+
+        fs
+    }
+
+    /// Construct an EngramFS over a [`LazyEngram`], reading each chunk from
+    /// disk, and decrypting its frame if the envelope is encrypted, when it
+    /// is first read. Only the codebook offsets and the chunk cache are held
+    /// in memory.
+    pub fn from_lazy(
+        engram: LazyEngram,
+        manifest: &Manifest,
+        decode_config: ReversibleVSAConfig,
+        read_only: bool,
+    ) -> Self {
+        let mut fs = Self::new(read_only);
+        Arc::make_mut(&mut fs.layers).push(BackingLayer {
+            engram: LayerEngram::Lazy(Arc::new(engram)),
+            config: decode_config,
+            chunk_size: manifest.chunk_size(),
+        });
+
+        fs.add_manifest(0, manifest, true);
+
+        fs
+    }
+
+    /// Construct an EngramFS over a shared [`EngramReader`], decoding
+    /// through the reader's engram and chunk cache, so the mount and other
+    /// users of the reader hold one copy of the engram and share decoded
+    /// chunks. Reads are counted in the reader's access stats.
+    pub fn from_reader(reader: &EngramReader, read_only: bool) -> Self {
+        let mut fs = Self::new(read_only)
+            .with_chunk_cache(Arc::clone(reader.chunk_cache()))
+            .with_access_stats(Arc::clone(reader.access_stats()));
+        Arc::make_mut(&mut fs.layers).push(BackingLayer {
+            engram: LayerEngram::Loaded(Arc::clone(reader.shared_engram())),
+            config: reader.config().clone(),
+            chunk_size: reader.manifest().chunk_size(),
+        });
+
+        fs.add_manifest(0, reader.manifest(), true);
 
         fs
     }
@@ -452,6 +1106,15 @@ impl EngramFS {
         self.next_ino.fetch_add(1, Ordering::SeqCst)
     }
 
+    /// The number `inodes` records for `path` unless it is taken, else a
+    /// new one.
+    fn ino_for(&self, path: &str, inodes: &InodeTable) -> Ino {
+        match inodes.get(path) {
+            Some(ino) if !self.inodes.load().contains_key(&ino) => ino,
+            _ => self.alloc_ino(),
+        }
+    }
+
     /// Add a file to the filesystem
     ///
     /// # Arguments
@@ -472,7 +1135,7 @@ impl EngramFS {
 
         // Ensure parent directory exists
         let parent_path = parent_path(&path).ok_or("Invalid path")?;
-        let parent_ino = self.ensure_directory(&parent_path)?;
+        let parent_ino = self.ensure_directory(&parent_path, &InodeTable::default())?;
 
         // Create file
         let ino = self.alloc_ino();
@@ -529,13 +1192,58 @@ impl EngramFS {
             }
             new_map
         });
+        self.negative.clear();
 
         Ok(ino)
     }
 
     /// Add a file whose bytes are backed by an engram and decoded on-demand.
     pub fn add_backed_file(&self, path: &str, chunks: Vec<usize>, size: usize) -> Result<Ino, &'static str> {
-        let path = normalize_path(path);
+        self.add_layer_file(0, path, chunks, size, None, &InodeTable::default())
+    }
+
+    /// Add the files of `manifest`, backed by layer `layer`. With
+    /// `keep_inodes`, paths get the numbers the manifest records (see
+    /// [`crate::inodes`]) and new ones are allocated above them.
+    fn add_manifest(&self, layer: usize, manifest: &Manifest, keep_inodes: bool) {
+        let none = InodeTable::default();
+        let inodes = if keep_inodes {
+            self.next_ino.fetch_max(manifest.inodes.next(), Ordering::SeqCst);
+            &manifest.inodes
+        } else {
+            &none
+        };
+        for entry in &manifest.files {
+            let _ = self.add_entry(layer, entry, inodes);
+        }
+    }
+
+    /// Add a manifest entry backed by layer `layer`, with its recorded owner
+    /// and mode if any.
+    fn add_entry(&self, layer: usize, entry: &FileEntry, inodes: &InodeTable) -> Result<Ino, &'static str> {
+        self.add_layer_file(layer, &entry.path, entry.chunks.clone(), entry.size, entry.unix, inodes)
+    }
+
+    /// The overlay layer a file is decoded from; `None` for directories
+    /// and preloaded files.
+    pub fn layer_of(&self, ino: Ino) -> Option<usize> {
+        match &self.files.load().get(&ino)?.storage {
+            FileStorage::Backed(backed) => Some(backed.layer),
+            FileStorage::Preloaded(_) => None,
+        }
+    }
+
+    /// [`EngramFS::add_backed_file`] with chunks from layer `layer`.
+    fn add_layer_file(
+        &self,
+        layer: usize,
+        logical_path: &str,
+        chunks: Vec<usize>,
+        size: usize,
+        unix: Option<UnixMeta>,
+        inodes: &InodeTable,
+    ) -> Result<Ino, &'static str> {
+        let path = normalize_path(logical_path);
 
         // Lock-free existence check
         if self.path_inodes.load().contains_key(&path) {
@@ -543,12 +1251,12 @@ impl EngramFS {
         }
 
         let parent_path = parent_path(&path).ok_or("Invalid path")?;
-        let parent_ino = self.ensure_directory(&parent_path)?;
+        let parent_ino = self.ensure_directory(&parent_path, inodes)?;
 
-        let ino = self.alloc_ino();
+        let ino = self.ino_for(&path, inodes);
         let size_u64 = size as u64;
 
-        let attr = FileAttr {
+        let mut attr = FileAttr {
             ino,
             size: size_u64,
             blocks: size_u64.div_ceil(512),
@@ -557,6 +1265,11 @@ impl EngramFS {
             nlink: 1,
             ..Default::default()
         };
+        if let Some(unix) = unix {
+            attr.uid = unix.uid;
+            attr.gid = unix.gid;
+            attr.perm = (unix.mode & 0o7777) as u16;
+        }
 
         // Copy-on-write updates
         self.inodes.rcu(|map| {
@@ -579,7 +1292,12 @@ impl EngramFS {
             new_map.insert(
                 ino,
                 FileRecord {
-                    storage: FileStorage::Backed(BackedFile { path: path.clone(), chunks: chunks.clone(), size }),
+                    storage: FileStorage::Backed(BackedFile {
+                        path: logical_path.to_string(),
+                        chunks: chunks.clone(),
+                        size,
+                        layer,
+                    }),
                     attr: attr.clone(),
                 },
             );
@@ -598,12 +1316,13 @@ impl EngramFS {
             }
             new_map
         });
+        self.negative.clear();
 
         Ok(ino)
     }
 
     /// Ensure a directory exists, creating it if necessary
-    fn ensure_directory(&self, path: &str) -> Result<Ino, &'static str> {
+    fn ensure_directory(&self, path: &str, inodes: &InodeTable) -> Result<Ino, &'static str> {
         let path = normalize_path(path);
         
         // Root always exists
@@ -613,15 +1332,18 @@ impl EngramFS {
 
         // Lock-free existence check
         if let Some(&ino) = self.path_inodes.load().get(&path) {
-            return Ok(ino);
+            return match self.get_attr(ino) {
+                Some(attr) if attr.kind == FileKind::Directory => Ok(ino),
+                _ => Err("Parent is not a directory"),
+            };
         }
 
         // Create parent first (recursive)
         let parent_path = parent_path(&path).ok_or("Invalid path")?;
-        let parent_ino = self.ensure_directory(&parent_path)?;
+        let parent_ino = self.ensure_directory(&parent_path, inodes)?;
 
         // Create this directory
-        let ino = self.alloc_ino();
+        let ino = self.ino_for(&path, inodes);
         let attr = FileAttr {
             ino,
             size: 0,
@@ -662,136 +1384,66 @@ impl EngramFS {
                 entries.push(DirEntry {
                     ino,
                     name: dirname.to_string(),
-                    kind: FileKind::Directory,
-                });
-            }
-            new_map
-        });
-
-        // Update parent nlink
-        self.inodes.rcu(|map| {
-            let mut new_map = (**map).clone();
-            if let Some(parent_attr) = new_map.get_mut(&parent_ino) {
-                parent_attr.nlink += 1;
-            }
-            new_map
-        });
-
-        Ok(ino)
-    }
-
-    /// Lookup a path and return its inode (lock-free)
-    #[inline]
-    pub fn lookup_path(&self, path: &str) -> Option<Ino> {
-        let path = normalize_path(path);
-        self.path_inodes.load().get(&path).copied()
-    }
-
-    /// Get file attributes by inode (lock-free)
-    #[inline]
-    pub fn get_attr(&self, ino: Ino) -> Option<FileAttr> {
-        self.inodes.load().get(&ino).cloned()
-    }
-
-    /// Read file data (lock-free for metadata lookup)
-    #[inline]
-    pub fn read_data(&self, ino: Ino, offset: u64, size: u32) -> Option<Vec<u8>> {
-        if size == 0 {
-            return Some(Vec::new());
-        }
-
-        let offset_usize = match usize::try_from(offset) {
-            Ok(v) => v,
-            Err(_) => return Some(Vec::new()),
-        };
-
-        // Lock-free load of file records
-        let files = self.files.load();
-        let rec = files.get(&ino)?;
-
-        match &rec.storage {
-            FileStorage::Preloaded(data) => {
-                if offset_usize >= data.len() {
-                    return Some(Vec::new());
-                }
-                let end = std::cmp::min(offset_usize.saturating_add(size as usize), data.len());
-                Some(data[offset_usize..end].to_vec())
-            }
-            FileStorage::Backed(backed) => {
-                let max_len = backed.size;
-                if offset_usize >= max_len {
-                    return Some(Vec::new());
-                }
-                let end = std::cmp::min(offset_usize.saturating_add(size as usize), max_len);
-                Some(self.read_backed_range(ino, backed, offset_usize, end))
-            }
-        }
-    }
-
-    fn read_backed_range(&self, ino: Ino, backed: &BackedFile, start: usize, end: usize) -> Vec<u8> {
-        if start >= end {
-            return Vec::new();
-        }
+                    kind: FileKind::Directory,
+                });
+            }
+            new_map
+        });
+        self.negative.clear();
 
-        let Some(engram) = self.engram.as_ref() else {
-            return Vec::new();
-        };
-        let Some(cfg) = self.decode_config.as_ref() else {
-            return Vec::new();
-        };
+        // Update parent nlink
+        self.inodes.rcu(|map| {
+            let mut new_map = (**map).clone();
+            if let Some(parent_attr) = new_map.get_mut(&parent_ino) {
+                parent_attr.nlink += 1;
+            }
+            new_map
+        });
 
-        let chunk_size = self.chunk_size;
-        if chunk_size == 0 {
-            return Vec::new();
-        }
+        Ok(ino)
+    }
 
-        let start_chunk = start / chunk_size;
-        let end_chunk = (end - 1) / chunk_size;
-        if start_chunk >= backed.chunks.len() {
-            return Vec::new();
-        }
+    /// Lookup a path and return its inode (lock-free)
+    #[inline]
+    pub fn lookup_path(&self, path: &str) -> Option<Ino> {
+        let path = normalize_path(path);
+        self.path_inodes.load().get(&path).copied()
+    }
 
-        let mut out = Vec::with_capacity(end - start);
-        let last_chunk = end_chunk.min(backed.chunks.len().saturating_sub(1));
+    /// Get file attributes by inode (lock-free)
+    #[inline]
+    pub fn get_attr(&self, ino: Ino) -> Option<FileAttr> {
+        self.inodes.load().get(&ino).cloned()
+    }
 
-        for chunk_index in start_chunk..=last_chunk {
-            let chunk_id = backed.chunks[chunk_index] as u64;
-            let key = ChunkKey { ino, chunk_id };
-
-            // Try cache first.
-            if let Ok(mut cache) = self.chunk_cache.write() {
-                if let Some(bytes) = cache.get(key) {
-                    let (a, b) = slice_chunk_bounds(start, end, chunk_index, chunk_size);
-                    if a < b && b <= bytes.len() {
-                        out.extend_from_slice(&bytes[a..b]);
-                        continue;
-                    }
-                }
-            }
+    /// Read file data (lock-free for metadata lookup)
+    pub fn read_data(&self, ino: Ino, offset: u64, size: u32) -> Option<Vec<u8>> {
+        self.data_path().read(ino, offset, size, true)
+    }
 
-            // Decode chunk.
-            let Some(chunk_vec) = engram.codebook.get(&(chunk_id as usize)) else {
-                continue;
-            };
-            let decoded = chunk_vec.decode_data(cfg, Some(&backed.path), chunk_size);
-            let chunk_bytes = if let Some(corrected) = engram.corrections.apply(chunk_id, &decoded) {
-                corrected
-            } else {
-                decoded
-            };
+    /// Decode the chunks of bytes `offset..offset + len` of `ino` into the
+    /// chunk cache ahead of reading them, returning how many chunks that is;
+    /// `None` if `ino` is not a file. Applications on a mount ask for this
+    /// with [`PREFETCH_IOCTL`].
+    pub fn prefetch(&self, ino: Ino, offset: u64, len: u64) -> Option<usize> {
+        self.data_path().warm(ino, offset, len)
+    }
 
-            // Cache decoded chunk (best-effort).
-            if let Ok(mut cache) = self.chunk_cache.write() {
-                cache.insert(key, chunk_bytes.clone());
-            }
+    /// [`EngramFS::read_data`] as a direct-I/O open reads: cached chunks are
+    /// used, but chunks decoded for this read are not cached.
+    pub fn read_data_direct(&self, ino: Ino, offset: u64, size: u32) -> Option<Vec<u8>> {
+        self.data_path().read(ino, offset, size, false)
+    }
 
-            let (a, b) = slice_chunk_bounds(start, end, chunk_index, chunk_size);
-            if a < b && b <= chunk_bytes.len() {
-                out.extend_from_slice(&chunk_bytes[a..b]);
-            }
+    /// The state reads need, detached from `self` so they can be served on
+    /// another thread.
+    fn data_path(&self) -> DataPath {
+        DataPath {
+            files: self.files.load_full(),
+            layers: Arc::clone(&self.layers),
+            chunk_cache: Arc::clone(&self.chunk_cache),
+            access: self.access.clone(),
         }
-
-        out
     }
 
     /// Read directory contents (lock-free)
@@ -829,6 +1481,56 @@ impl EngramFS {
         self.files.load().values().map(|f| f.attr.size).sum()
     }
 
+    /// Estimated heap bytes of the backing engram, the inode tables and the
+    /// decoded data held in memory (chunk cache and preloaded files).
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        for layer in self.layers.iter() {
+            match &layer.engram {
+                LayerEngram::Loaded(engram) => {
+                    let engram = engram.memory_usage();
+                    usage.codebook_bytes += engram.codebook_bytes;
+                    usage.root_bytes += engram.root_bytes;
+                    usage.corrections_bytes += engram.corrections_bytes;
+                }
+                LayerEngram::Lazy(engram) => {
+                    usage.codebook_bytes += engram.index_bytes();
+                    usage.root_bytes += memory::sparse_vec_bytes(engram.root());
+                }
+            }
+        }
+
+        let paths = self.inode_paths.load();
+        let path_inodes = self.path_inodes.load();
+        let directories = self.directories.load();
+        let files = self.files.load();
+        let path_bytes: u64 = paths.values().map(|p| p.capacity() as u64).sum::<u64>()
+            + path_inodes.keys().map(|p| p.capacity() as u64).sum::<u64>();
+        let dir_bytes: u64 = directories
+            .values()
+            .map(|entries| memory::vec_bytes(entries) + entries.iter().map(|e| e.name.capacity() as u64).sum::<u64>())
+            .sum();
+        let mut preloaded = 0;
+        let mut backed = 0;
+        for record in files.values() {
+            match &record.storage {
+                FileStorage::Preloaded(data) => preloaded += memory::vec_bytes(data),
+                FileStorage::Backed(f) => backed += f.path.capacity() as u64 + memory::vec_bytes(&f.chunks),
+            }
+        }
+        usage.manifest_bytes = memory::map_bytes(&self.inodes.load())
+            + memory::map_bytes(&paths)
+            + memory::map_bytes(&path_inodes)
+            + memory::map_bytes(&directories)
+            + memory::map_bytes(&files)
+            + path_bytes
+            + dir_bytes
+            + backed;
+
+        usage.cache_bytes = self.chunk_cache.memory_bytes() + preloaded;
+        usage
+    }
+
     /// Check if filesystem is read-only
     pub fn is_read_only(&self) -> bool {
         self.read_only
@@ -843,6 +1545,55 @@ impl EngramFS {
     pub fn entry_ttl(&self) -> Duration {
         self.entry_ttl
     }
+
+    /// How long the kernel may cache names (`entry`) and attributes
+    /// (`attr`) before asking again (default: 1s each). Longer TTLs suit
+    /// read-only mounts walked repeatedly.
+    pub fn with_ttls(mut self, entry: Duration, attr: Duration) -> Self {
+        self.entry_ttl = entry;
+        self.attr_ttl = attr;
+        self
+    }
+
+    /// Remember absent names for `ttl`, here and in the kernel, holding at
+    /// most `max_entries` (defaults: 1s, [`DEFAULT_NEGATIVE_ENTRIES`]).
+    /// A zero `ttl` turns negative caching off.
+    pub fn with_negative_cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.negative = NegativeCache::new(ttl, max_entries);
+        self
+    }
+
+    /// Which opens bypass the kernel page cache (default:
+    /// [`DirectIo::OnRequest`]).
+    pub fn with_direct_io(mut self, direct_io: DirectIo) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    pub fn direct_io(&self) -> DirectIo {
+        self.direct_io
+    }
+
+    /// Get negative-lookup TTL
+    pub fn negative_ttl(&self) -> Duration {
+        self.negative.ttl
+    }
+
+    pub fn negative_lookup_stats(&self) -> NegativeLookupStats {
+        self.negative.stats()
+    }
+
+    /// [`EngramFS::lookup_entry`] through the negative-lookup cache.
+    pub fn lookup_name(&self, parent_ino: Ino, name: &str) -> Option<Ino> {
+        if self.negative.contains(parent_ino, name) {
+            return None;
+        }
+        let ino = self.lookup_entry(parent_ino, name);
+        if ino.is_none() {
+            self.negative.insert(parent_ino, name);
+        }
+        ino
+    }
 }
 
 // =============================================================================
@@ -855,10 +1606,14 @@ impl fuser::Filesystem for EngramFS {
     fn init(
         &mut self,
         _req: &fuser::Request<'_>,
-        _config: &mut fuser::KernelConfig,
+        config: &mut fuser::KernelConfig,
     ) -> Result<(), libc::c_int> {
         eprintln!("EngramFS initialized: {} files, {} bytes total",
             self.file_count(), self.total_size());
+        if self.workers.is_some() {
+            // Let the kernel keep more readahead in flight for the workers.
+            let _ = config.set_max_background(64);
+        }
         Ok(())
     }
 
@@ -870,12 +1625,13 @@ impl fuser::Filesystem for EngramFS {
     /// Look up a directory entry by name
     fn lookup(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         parent: u64,
         name: &OsStr,
         reply: fuser::ReplyEntry,
     ) {
-        match self.get_attr(parent) {
+        let _timer = self.time(FuseOp::Lookup);
+        match VirtualFs::getattr(self, parent) {
             Some(attr) if attr.kind == FileKind::Directory => {}
             Some(_) => {
                 reply.error(libc::ENOTDIR);
@@ -895,14 +1651,27 @@ impl fuser::Filesystem for EngramFS {
             }
         };
 
-        match self.lookup_entry(parent, name) {
-            Some(ino) => {
-                if let Some(attr) = self.get_attr(ino) {
-                    let fuser_attr: fuser::FileAttr = attr.into();
-                    reply.entry(&self.entry_ttl, &fuser_attr, 0);
-                } else {
-                    reply.error(libc::ENOENT);
-                }
+        let Some(ino) = VirtualFs::lookup(self, parent, name) else {
+            if self.negative.enabled() {
+                // Inode 0 lets the kernel cache the miss for the TTL.
+                let negative: fuser::FileAttr = FileAttr { ino: 0, ..Default::default() }.into();
+                reply.entry(&self.negative.ttl, &negative, 0);
+            } else {
+                reply.error(libc::ENOENT);
+            }
+            return;
+        };
+
+        // Hidden names are not cached as absent: other uids may see them.
+        if !self.permits(req.uid(), ino) {
+            reply.error(libc::ENOENT);
+            return;
+        }
+
+        match VirtualFs::getattr(self, ino) {
+            Some(attr) => {
+                let fuser_attr: fuser::FileAttr = self.ids.apply(attr).into();
+                reply.entry(&self.entry_ttl, &fuser_attr, 0);
             }
             None => {
                 reply.error(libc::ENOENT);
@@ -913,14 +1682,15 @@ impl fuser::Filesystem for EngramFS {
     /// Get file attributes
     fn getattr(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         ino: u64,
         _fh: Option<u64>,
         reply: fuser::ReplyAttr,
     ) {
-        match self.get_attr(ino) {
+        let _timer = self.time(FuseOp::Getattr);
+        match VirtualFs::getattr(self, ino).filter(|_| self.permits(req.uid(), ino)) {
             Some(attr) => {
-                let fuser_attr: fuser::FileAttr = attr.into();
+                let fuser_attr: fuser::FileAttr = self.ids.apply(attr).into();
                 reply.attr(&self.attr_ttl, &fuser_attr);
             }
             None => {
@@ -932,15 +1702,16 @@ impl fuser::Filesystem for EngramFS {
     /// Read data from a file
     fn read(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         size: u32,
         _flags: i32,
         _lock_owner: Option<u64>,
         reply: fuser::ReplyData,
     ) {
+        let timer = self.time(FuseOp::Read);
         if offset < 0 {
             reply.error(libc::EINVAL);
             return;
@@ -958,24 +1729,39 @@ impl fuser::Filesystem for EngramFS {
             }
         }
 
-        match self.read_data(ino, offset as u64, size) {
-            Some(data) => {
-                reply.data(&data);
-            }
-            None => {
-                reply.error(libc::ENOENT);
+        if !self.permits(req.uid(), ino) {
+            reply.error(libc::EACCES);
+            return;
+        }
+
+        let data_path = self.data_path();
+        let job = move || {
+            let _timer = timer;
+            match data_path.read(ino, offset as u64, size, fh & FH_DIRECT == 0) {
+                Some(data) => {
+                    reply.data(&data);
+                    BufferPool::global().give(data);
+                }
+                None => {
+                    reply.error(libc::ENOENT);
+                }
             }
+        };
+        match &self.workers {
+            Some(workers) => workers.run(job),
+            None => job(),
         }
     }
 
     /// Open a file
     fn open(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         ino: u64,
         flags: i32,
         reply: fuser::ReplyOpen,
     ) {
+        let _timer = self.time(FuseOp::Open);
         // Check if file exists and is a file.
         match self.get_attr(ino) {
             Some(attr) if attr.kind == FileKind::Directory => {
@@ -989,6 +1775,11 @@ impl fuser::Filesystem for EngramFS {
             }
         }
 
+        if !self.permits(req.uid(), ino) {
+            reply.error(libc::EACCES);
+            return;
+        }
+
         // Check for write flags on read-only filesystem
         if self.read_only {
             let write_flags = libc::O_WRONLY | libc::O_RDWR | libc::O_APPEND | libc::O_TRUNC;
@@ -998,8 +1789,12 @@ impl fuser::Filesystem for EngramFS {
             }
         }
 
-        // Return a dummy file handle (we're stateless)
-        reply.opened(0, 0);
+        // The handle carries no state beyond whether reads are direct.
+        if self.direct_io.applies(flags) {
+            reply.opened(FH_DIRECT, fuser::consts::FOPEN_DIRECT_IO);
+        } else {
+            reply.opened(0, 0);
+        }
     }
 
     /// Release an open file
@@ -1016,17 +1811,60 @@ impl fuser::Filesystem for EngramFS {
         reply.ok();
     }
 
+    /// Prefetch requests; see [Prefetch](self#prefetch)
+    fn ioctl(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _flags: u32,
+        cmd: u32,
+        in_data: &[u8],
+        _out_size: u32,
+        reply: fuser::ReplyIoctl,
+    ) {
+        if cmd != PREFETCH_IOCTL {
+            reply.error(libc::ENOTTY);
+            return;
+        }
+        let (Some(offset), Some(len)) = (
+            in_data.get(..8).and_then(|b| b.try_into().ok()).map(u64::from_ne_bytes),
+            in_data.get(8..16).and_then(|b| b.try_into().ok()).map(u64::from_ne_bytes),
+        ) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        if !self.permits(req.uid(), ino) {
+            reply.error(libc::EACCES);
+            return;
+        }
+        reply.ioctl(0, &[]);
+
+        let data_path = self.data_path();
+        let job = move || {
+            data_path.warm(ino, offset, len);
+        };
+        match &self.workers {
+            Some(workers) => workers.run(job),
+            None => job(),
+        }
+    }
+
     /// Open a directory
     fn opendir(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         ino: u64,
         _flags: i32,
         reply: fuser::ReplyOpen,
     ) {
         match self.get_attr(ino) {
             Some(attr) if attr.kind == FileKind::Directory => {
-                reply.opened(0, 0);
+                if self.permits(req.uid(), ino) {
+                    reply.opened(0, 0);
+                } else {
+                    reply.error(libc::EACCES);
+                }
             }
             Some(_) => {
                 reply.error(libc::ENOTDIR);
@@ -1040,12 +1878,13 @@ impl fuser::Filesystem for EngramFS {
     /// Read directory entries
     fn readdir(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         ino: u64,
         _fh: u64,
         offset: i64,
         mut reply: fuser::ReplyDirectory,
     ) {
+        let _timer = self.time(FuseOp::Readdir);
         if offset < 0 {
             reply.error(libc::EINVAL);
             return;
@@ -1059,9 +1898,11 @@ impl fuser::Filesystem for EngramFS {
         entries.push((parent_ino, fuser::FileType::Directory, "..".to_string()));
 
         // Add directory contents
-        if let Some(dir_entries) = self.read_dir(ino) {
+        if let Some(dir_entries) = VirtualFs::readdir(self, ino) {
             for entry in dir_entries {
-                entries.push((entry.ino, entry.kind.into(), entry.name));
+                if self.permits(req.uid(), entry.ino) {
+                    entries.push((entry.ino, entry.kind.into(), entry.name));
+                }
             }
         }
 
@@ -1114,17 +1955,23 @@ impl fuser::Filesystem for EngramFS {
     /// Check file access permissions
     fn access(
         &mut self,
-        _req: &fuser::Request<'_>,
+        req: &fuser::Request<'_>,
         ino: u64,
         mask: i32,
         reply: fuser::ReplyEmpty,
     ) {
+        let _timer = self.time(FuseOp::Access);
         // Check if file exists
         if self.get_attr(ino).is_none() {
             reply.error(libc::ENOENT);
             return;
         }
 
+        if !self.permits(req.uid(), ino) {
+            reply.error(libc::EACCES);
+            return;
+        }
+
         // Deny write access on read-only filesystem
         if self.read_only && (mask & libc::W_OK != 0) {
             reply.error(libc::EROFS);
@@ -1137,21 +1984,26 @@ impl fuser::Filesystem for EngramFS {
 
     /// Read symbolic link target
     fn readlink(&mut self, _req: &fuser::Request<'_>, ino: u64, reply: fuser::ReplyData) {
-        // We don't support symlinks yet
-        match self.get_attr(ino) {
-            Some(attr) if attr.kind == FileKind::Symlink => {
-                reply.error(libc::ENOSYS); // Not implemented
-            }
-            Some(_) => {
-                reply.error(libc::EINVAL); // Not a symlink
-            }
-            None => {
-                reply.error(libc::ENOENT);
-            }
+        match VirtualFs::readlink(self, ino) {
+            Ok(target) => reply.data(&target),
+            Err(err) => reply.error(errno(&err)),
         }
     }
 }
 
+/// Errno for a [`VirtualFs`] error.
+#[cfg(feature = "fuse")]
+fn errno(err: &std::io::Error) -> libc::c_int {
+    use std::io::ErrorKind;
+    match err.kind() {
+        ErrorKind::NotFound => libc::ENOENT,
+        ErrorKind::InvalidInput => libc::EINVAL,
+        ErrorKind::Unsupported => libc::ENOSYS,
+        ErrorKind::PermissionDenied => libc::EACCES,
+        _ => err.raw_os_error().unwrap_or(libc::EIO),
+    }
+}
+
 fn slice_chunk_bounds(start: usize, end: usize, chunk_index: usize, chunk_size: usize) -> (usize, usize) {
     let chunk_start = chunk_index * chunk_size;
     let chunk_end = chunk_start + chunk_size;
@@ -1288,6 +2140,182 @@ pub fn spawn_mount<P: AsRef<Path>>(
     fuser::spawn_mount2(fs, mountpoint.as_ref(), &mount_options)
 }
 
+// =============================================================================
+// MOUNT LIFECYCLE
+// =============================================================================
+
+/// Why [`wait_for_unmount`] returned.
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnmountReason {
+    /// SIGINT, SIGTERM or SIGHUP was received.
+    Signal(i32),
+    /// The mountpoint is no longer a mount (e.g. `fusermount -u` ran).
+    External,
+}
+
+#[cfg(unix)]
+static UNMOUNT_SIGNAL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+#[cfg(unix)]
+extern "C" fn record_unmount_signal(sig: libc::c_int) {
+    UNMOUNT_SIGNAL.store(sig, Ordering::SeqCst);
+}
+
+/// Whether `path` is the root of a mount, judged by its device differing
+/// from its parent's.
+#[cfg(unix)]
+pub fn is_mountpoint(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    let Some(parent) = path.parent() else {
+        return true;
+    };
+    match (std::fs::metadata(&path), std::fs::metadata(parent)) {
+        (Ok(m), Ok(p)) => m.dev() != p.dev(),
+        _ => false,
+    }
+}
+
+/// Block until SIGINT, SIGTERM or SIGHUP arrives, or until `mountpoint`
+/// stops being a mount.
+///
+/// Installs handlers for those signals, so the caller can unmount cleanly
+/// (by dropping its `BackgroundSession`) instead of being killed with the
+/// mount still in place.
+#[cfg(unix)]
+pub fn wait_for_unmount(mountpoint: &std::path::Path) -> UnmountReason {
+    UNMOUNT_SIGNAL.store(0, Ordering::SeqCst);
+    let handler = record_unmount_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for sig in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        // SAFETY: the handler only stores to an atomic.
+        unsafe {
+            libc::signal(sig, handler);
+        }
+    }
+    let mut was_mounted = is_mountpoint(mountpoint);
+    loop {
+        let sig = UNMOUNT_SIGNAL.load(Ordering::SeqCst);
+        if sig != 0 {
+            return UnmountReason::Signal(sig);
+        }
+        // Only report an external unmount after the mount was seen.
+        let mounted = is_mountpoint(mountpoint);
+        if was_mounted && !mounted {
+            return UnmountReason::External;
+        }
+        was_mounted |= mounted;
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/// Unmount a FUSE filesystem with `fusermount3 -u` or `fusermount -u`,
+/// falling back to `umount` (macOS and the BSDs have no fusermount).
+#[cfg(unix)]
+pub fn unmount(mountpoint: &std::path::Path) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let attempts: [(&str, &[&str]); 3] = [("fusermount3", &["-u"]), ("fusermount", &["-u"]), ("umount", &[])];
+    let mut last = None;
+    for (program, args) in attempts {
+        match Command::new(program).args(args).arg(mountpoint).stdin(Stdio::null()).output() {
+            Ok(out) if out.status.success() => return Ok(()),
+            Ok(out) => last = Some(String::from_utf8_lossy(&out.stderr).trim().to_string()),
+            // Not installed: try the next tool.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::other(format!(
+        "could not unmount {}: {}",
+        mountpoint.display(),
+        last.unwrap_or_else(|| "no fusermount or umount found".to_string())
+    )))
+}
+
+/// A forked background process waiting to report whether its mount
+/// succeeded; see [`daemonize`].
+#[cfg(unix)]
+pub struct Daemon {
+    status: std::fs::File,
+}
+
+#[cfg(unix)]
+impl Daemon {
+    /// Tell the waiting parent the mount is up (it exits 0), then detach
+    /// from the terminal by pointing stdio at `/dev/null`.
+    pub fn ready(self) -> std::io::Result<()> {
+        use std::io::Write;
+        use std::os::fd::AsRawFd;
+
+        (&self.status).write_all(b"\0")?;
+        let null = std::fs::OpenOptions::new().read(true).write(true).open("/dev/null")?;
+        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            // SAFETY: both descriptors are open.
+            if unsafe { libc::dup2(null.as_raw_fd(), fd) } < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell the waiting parent the mount failed; it prints `err` and exits 1.
+    pub fn fail(self, err: &dyn std::fmt::Display) {
+        use std::io::Write;
+
+        let _ = (&self.status).write_all(format!("{err}").as_bytes());
+    }
+}
+
+/// Fork into the background and start a new session.
+///
+/// Returns in the child only. The parent blocks until the child calls
+/// [`Daemon::ready`] or [`Daemon::fail`] (or exits), then exits itself, so
+/// a shell running `mount` sees whether mounting worked. Call this before
+/// starting any threads, and make paths absolute first: the child changes
+/// directory to `/`.
+#[cfg(unix)]
+pub fn daemonize() -> std::io::Result<Daemon> {
+    use std::io::Read;
+    use std::os::fd::FromRawFd;
+
+    let mut fds = [0 as libc::c_int; 2];
+    // SAFETY: `fds` has room for the two descriptors.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: both descriptors were just created and are owned here.
+    let (mut reader, writer) = unsafe { (std::fs::File::from_raw_fd(fds[0]), std::fs::File::from_raw_fd(fds[1])) };
+
+    // SAFETY: the caller guarantees no other threads are running.
+    match unsafe { libc::fork() } {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => {
+            drop(reader);
+            // SAFETY: plain syscalls in the single-threaded child.
+            unsafe {
+                libc::setsid();
+            }
+            std::env::set_current_dir("/")?;
+            Ok(Daemon { status: writer })
+        }
+        _ => {
+            drop(writer);
+            let mut status = Vec::new();
+            let _ = reader.read_to_end(&mut status);
+            match status.first() {
+                Some(0) => std::process::exit(0),
+                Some(_) => eprintln!("Error: {}", String::from_utf8_lossy(&status)),
+                None => eprintln!("Error: mount process exited before mounting"),
+            }
+            std::process::exit(1)
+        }
+    }
+}
+
 // =============================================================================
 // BUILDER PATTERN
 // =============================================================================
@@ -1308,6 +2336,8 @@ pub fn spawn_mount<P: AsRef<Path>>(
 /// ```
 pub struct EngramFSBuilder {
     fs: EngramFS,
+    manifests: Vec<Manifest>,
+    precedence: LayerPrecedence,
 }
 
 impl EngramFSBuilder {
@@ -1315,15 +2345,39 @@ impl EngramFSBuilder {
     pub fn new() -> Self {
         EngramFSBuilder {
             fs: EngramFS::new(true), // Read-only by default
+            manifests: Vec::new(),
+            precedence: LayerPrecedence::default(),
         }
     }
 
-    /// Add a file from decoded engram data
+    /// Add a file from decoded engram data. Files added this way shadow
+    /// every layer.
     pub fn add_file(self, path: &str, data: Vec<u8>) -> Self {
         let _ = self.fs.add_file(path, data);
         self
     }
 
+    /// Add the files of `manifest` as the next overlay layer, decoded on
+    /// demand from `engram` with `config`. Where layers hold the same path,
+    /// [`EngramFSBuilder::precedence`] decides which one is shown; a file
+    /// also hides any directory of the same path in the layers below it,
+    /// and the other way round.
+    pub fn layer(mut self, engram: Engram, manifest: Manifest, config: ReversibleVSAConfig) -> Self {
+        Arc::make_mut(&mut self.fs.layers).push(BackingLayer {
+            engram: LayerEngram::Loaded(Arc::new(engram)),
+            config,
+            chunk_size: manifest.chunk_size(),
+        });
+        self.manifests.push(manifest);
+        self
+    }
+
+    /// Which layer wins a path several hold (default: the later one).
+    pub fn precedence(mut self, precedence: LayerPrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
     /// Set read-only mode (default: true)
     pub fn read_only(mut self, read_only: bool) -> Self {
         self.fs.read_only = read_only;
@@ -1332,6 +2386,16 @@ impl EngramFSBuilder {
 
     /// Build the filesystem
     pub fn build(self) -> EngramFS {
+        // Layers go in from the winning end; a path already taken stays.
+        let order: Vec<usize> = match self.precedence {
+            LayerPrecedence::LaterShadows => (0..self.manifests.len()).rev().collect(),
+            LayerPrecedence::EarlierShadows => (0..self.manifests.len()).collect(),
+        };
+        // Only the winning layer's inode numbers can be kept; the others'
+        // would collide with them.
+        for (i, &layer) in order.iter().enumerate() {
+            self.fs.add_manifest(layer, &self.manifests[layer], i == 0);
+        }
         self.fs
     }
 }
@@ -1550,4 +2614,213 @@ mod tests {
             assert_eq!(file, fuser::FileType::RegularFile);
         }
     }
+
+    #[test]
+    fn test_cache_limits() {
+        use crate::embrfs::EmbrFS;
+
+        let config = ReversibleVSAConfig::default();
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 9) as u8).collect();
+        let mut embrfs = EmbrFS::new();
+        embrfs.ingest_bytes(&data, "a.bin".into(), &config).unwrap();
+        let fs = EngramFS::from_engram(embrfs.engram, embrfs.manifest, config, 4096, true).with_cache_limits(1, 1 << 20);
+
+        let ino = fs.lookup_path("/a.bin").unwrap();
+        assert_eq!(fs.read_data(ino, 0, 10_000).unwrap(), data);
+        assert_eq!(fs.chunk_cache().len(), 1);
+
+        let fs = fs.with_cache_limits(0, 0);
+        assert_eq!(fs.read_data(ino, 4000, 200).unwrap(), &data[4000..4200]);
+        assert!(fs.chunk_cache().is_empty());
+    }
+
+    #[test]
+    fn test_direct_reads_leave_the_chunk_cache_alone() {
+        use crate::embrfs::EmbrFS;
+
+        let config = ReversibleVSAConfig::default();
+        let data: Vec<u8> = (0..12_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+        let mut embrfs = EmbrFS::new();
+        embrfs.ingest_bytes(&data, "big.bin".into(), &config).unwrap();
+        let fs = EngramFS::from_engram(embrfs.engram, embrfs.manifest, config, 4096, true);
+        let ino = fs.lookup_path("/big.bin").unwrap();
+
+        assert_eq!(fs.read_data_direct(ino, 0, 12_000).unwrap(), data);
+        assert!(fs.chunk_cache().is_empty());
+        // Chunks already cached are still used.
+        assert_eq!(fs.read_data(ino, 4096, 10).unwrap(), &data[4096..4106]);
+        let hits = fs.chunk_cache().stats().hits;
+        assert_eq!(fs.read_data_direct(ino, 4096, 4096).unwrap(), &data[4096..8192]);
+        assert_eq!(fs.chunk_cache().stats().hits, hits + 1);
+        assert_eq!(fs.chunk_cache().len(), 1);
+
+        assert_eq!(fs.direct_io(), DirectIo::OnRequest);
+        assert!(!DirectIo::OnRequest.applies(libc::O_RDONLY));
+        assert!(DirectIo::Always.applies(libc::O_RDONLY));
+        #[cfg(target_os = "linux")]
+        {
+            assert!(DirectIo::OnRequest.applies(libc::O_RDONLY | libc::O_DIRECT));
+            assert!(!DirectIo::Never.applies(libc::O_DIRECT));
+        }
+    }
+
+    #[test]
+    fn test_prefetch_warms_the_chunk_cache() {
+        use crate::embrfs::EmbrFS;
+
+        let config = ReversibleVSAConfig::default();
+        let data: Vec<u8> = (0..12_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+        let mut embrfs = EmbrFS::new();
+        embrfs.ingest_bytes(&data, "big.bin".into(), &config).unwrap();
+        let fs = EngramFS::from_engram(embrfs.engram, embrfs.manifest, config, 4096, true);
+        let ino = fs.lookup_path("/big.bin").unwrap();
+
+        // Bytes 5000..9000 lie in the second and third chunks.
+        assert_eq!(fs.prefetch(ino, 5000, 4000), Some(2));
+        assert_eq!(fs.chunk_cache().len(), 2);
+        let misses = fs.chunk_cache().stats().misses;
+        assert_eq!(fs.read_data(ino, 4096, 7904).unwrap(), &data[4096..]);
+        assert_eq!(fs.chunk_cache().stats().misses, misses);
+
+        // Past the end there is nothing to fetch.
+        assert_eq!(fs.prefetch(ino, 20_000, 4096), Some(0));
+        assert_eq!(fs.prefetch(ino, 0, u64::MAX), Some(3));
+        assert_eq!(fs.prefetch(ROOT_INO + 1000, 0, 1), None);
+        assert_eq!(PREFETCH_IOCTL, 0x4010_4501);
+    }
+
+    #[test]
+    fn test_layered_mount() {
+        use crate::embrfs::EmbrFS;
+        let config = ReversibleVSAConfig::default();
+        let build = |files: &[(&str, &[u8])]| {
+            let mut embrfs = EmbrFS::new();
+            for (path, data) in files {
+                embrfs.ingest_bytes(data, path.to_string(), &config).unwrap();
+            }
+            embrfs
+        };
+        let read = |fs: &EngramFS, path: &str| {
+            let ino = fs.lookup_path(path).unwrap();
+            (fs.layer_of(ino).unwrap(), fs.read_data(ino, 0, 1 << 16).unwrap())
+        };
+
+        for (precedence, winner) in [(LayerPrecedence::LaterShadows, 1), (LayerPrecedence::EarlierShadows, 0)] {
+            let lower = build(&[("shared.txt", b"lower"), ("only_lower.txt", b"base")]);
+            let upper = build(&[("shared.txt", b"upper layer"), ("dir/only_upper.txt", b"new")]);
+            let fs = EngramFSBuilder::new()
+                .layer(lower.engram, lower.manifest, config.clone())
+                .layer(upper.engram, upper.manifest, config.clone())
+                .precedence(precedence)
+                .build();
+
+            let expected: &[u8] = if winner == 1 { b"upper layer" } else { b"lower" };
+            assert_eq!(read(&fs, "/shared.txt"), (winner, expected.to_vec()));
+            assert_eq!(read(&fs, "/only_lower.txt"), (0, b"base".to_vec()));
+            assert_eq!(read(&fs, "/dir/only_upper.txt"), (1, b"new".to_vec()));
+            assert_eq!(fs.file_count(), 3);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_recorded_owner_and_mode() {
+        use crate::embrfs::{EmbrFS, DEFAULT_CHUNK_SIZE};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.txt");
+        std::fs::write(&path, b"for owner only").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let config = ReversibleVSAConfig::default();
+        let mut embrfs = EmbrFS::new();
+        embrfs.ingest_file(&path, "secret.txt".into(), false, &config).unwrap();
+        embrfs.ingest_bytes(b"no metadata", "plain.txt".into(), &config).unwrap();
+        let unix = embrfs.manifest.files[0].unix.unwrap();
+        assert_eq!(unix.mode, 0o640);
+
+        let fs = EngramFS::from_engram(embrfs.engram, embrfs.manifest, config, DEFAULT_CHUNK_SIZE, true)
+            .with_id_map(IdMap::default().uid(unix.uid, 4242).gid(unix.gid, 4343));
+        let ino = fs.lookup_path("/secret.txt").unwrap();
+        let attr = fs.mapped_attr(ino).unwrap();
+        assert_eq!((attr.perm, attr.uid, attr.gid), (0o640, 4242, 4343));
+        assert_eq!(fs.get_attr(ino).unwrap().uid, unix.uid);
+        assert_eq!(fs.read_data(ino, 0, 100).unwrap(), b"for owner only");
+
+        let attr = fs.get_attr(fs.lookup_path("/plain.txt").unwrap()).unwrap();
+        assert_eq!((attr.perm, attr.uid), (0o644, current_uid()));
+    }
+
+    #[cfg(feature = "fuse")]
+    #[test]
+    fn test_worker_pool_runs_jobs_in_parallel() {
+        // Each job waits for all the others, so this only finishes if they
+        // run at the same time.
+        let pool = WorkerPool::new(4).unwrap();
+        let barrier = Arc::new(std::sync::Barrier::new(4));
+        let (done, finished) = std::sync::mpsc::channel();
+        for _ in 0..4 {
+            let (barrier, done) = (Arc::clone(&barrier), done.clone());
+            pool.run(move || {
+                barrier.wait();
+                done.send(()).unwrap();
+            });
+        }
+        for _ in 0..4 {
+            finished.recv_timeout(Duration::from_secs(10)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_negative_lookup_cache() {
+        let fs = EngramFS::new(true).with_negative_cache(Duration::from_secs(60), 2);
+        fs.add_file("/src/main.rs", b"fn main() {}".to_vec()).unwrap();
+        let src = fs.lookup_path("/src").unwrap();
+
+        assert_eq!(fs.lookup_name(src, ".git"), None);
+        assert_eq!(fs.lookup_name(src, ".git"), None);
+        assert!(fs.lookup_name(src, "main.rs").is_some());
+        assert_eq!(
+            fs.negative_lookup_stats(),
+            NegativeLookupStats { hits: 1, misses: 2, entries: 1 }
+        );
+
+        // Full: expired names go first, then everything.
+        fs.lookup_name(src, "node_modules");
+        fs.lookup_name(ROOT_INO, ".git");
+        assert_eq!(fs.negative_lookup_stats().entries, 1);
+
+        // Adding a name forgets that it was absent.
+        fs.lookup_name(src, "lib.rs");
+        fs.add_file("/src/lib.rs", Vec::new()).unwrap();
+        assert_eq!(fs.negative_lookup_stats().entries, 0);
+        assert!(fs.lookup_name(src, "lib.rs").is_some());
+
+        let fs = fs.with_negative_cache(Duration::ZERO, 2);
+        assert_eq!(fs.lookup_name(src, ".git"), None);
+        assert_eq!(fs.negative_lookup_stats(), NegativeLookupStats::default());
+
+        let fs = fs.with_ttls(Duration::from_secs(30), Duration::from_secs(10));
+        assert_eq!((fs.entry_ttl(), fs.attr_ttl()), (Duration::from_secs(30), Duration::from_secs(10)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unmount_helpers() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(is_mountpoint(std::path::Path::new("/")));
+        assert!(!is_mountpoint(dir.path()));
+        assert!(!is_mountpoint(&dir.path().join("missing")));
+        assert!(unmount(dir.path()).is_err());
+
+        std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(300));
+            // SAFETY: wait_for_unmount installed a handler before this fires.
+            unsafe {
+                libc::kill(libc::getpid(), libc::SIGHUP);
+            }
+        });
+        assert_eq!(wait_for_unmount(dir.path()), UnmountReason::Signal(libc::SIGHUP));
+    }
 }
@@ -0,0 +1,588 @@
+//! Content filters run on every file before it is stored in an engram.
+//!
+//! Once a chunk is bundled into an engram it stays recoverable from every
+//! copy of that engram, so secrets and personal data have to be caught at
+//! ingest, and so does anything else a deployment wants kept out. An
+//! [`IngestFilter`] looks at each file's logical path and then at each
+//! chunk, and reports [`Detection`]s as byte ranges of the file. Each filter
+//! is registered in [`IngestFilters`] (the chain on
+//! [`EmbrFS::filters`](crate::EmbrFS)) with the [`FilterAction`] taken on
+//! its detections:
+//!
+//! - **warn**: store the content unchanged and record a [`Finding`];
+//! - **redact**: overwrite the detected bytes with `*` before encoding, so
+//!   the file keeps its length and extracts with the secret masked;
+//! - **skip**: drop the file's chunks and leave it out of the engram, and
+//!   go on with the next file;
+//! - **reject**: drop the file's chunks and fail the ingest with
+//!   [`EmbrError::Rejected`].
+//!
+//! Every filter scans a chunk as it was read. Once all have, redactions are
+//! applied and then each filter may rewrite the chunk in place with
+//! [`IngestFilter::transform_chunk`], in the order the filters were pushed.
+//! Transforms keep the chunk's length: chunk `i` of a file always starts at
+//! byte `i` times the chunk size.
+//!
+//! Every detection is recorded as a [`Finding`] (never with the matched
+//! bytes) and reported in [`IngestStats::findings`](crate::IngestStats). A
+//! chunk detection starting inside a range the same filter already flagged
+//! from the path is not recorded again.
+//!
+//! Built-in filters:
+//!
+//! - [`SecretScanner::builtin`] detects common credential formats with
+//!   regular expressions, long high-entropy tokens in text, and private key
+//!   files by name. Chunks are scanned one at a time, so a secret straddling
+//!   a chunk boundary can be missed.
+//! - [`SizeLimit`] flags files larger than a cap.
+//! - [`ExtensionFilter`] flags files by extension.
+//! - [`CompressedContent`] flags files that are already compressed, which
+//!   encode poorly: nearly every chunk needs a full correction.
+
+use crate::embrfs::is_text_file;
+use crate::error::EmbrError;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+/// What happens to content an ingest filter detects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    #[default]
+    Warn,
+    Redact,
+    Skip,
+    Reject,
+}
+
+impl fmt::Display for FilterAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FilterAction::Warn => "warn",
+            FilterAction::Redact => "redact",
+            FilterAction::Skip => "skip",
+            FilterAction::Reject => "reject",
+        })
+    }
+}
+
+/// A range of a file flagged by a filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detection {
+    /// Name of the detector that fired, e.g. `aws-access-key`.
+    pub detector: String,
+    /// Byte range within the file.
+    pub range: Range<u64>,
+}
+
+/// The range of a whole file of length `len`; open-ended when the length is
+/// unknown.
+pub fn whole_file(len: Option<u64>) -> Range<u64> {
+    0..len.unwrap_or(u64::MAX)
+}
+
+/// A detection recorded during ingest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Logical path of the file.
+    pub path: String,
+    /// Byte offset of the detected content within the file.
+    pub offset: u64,
+    pub len: u64,
+    pub detector: String,
+    /// Action taken on the content.
+    pub action: FilterAction,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} at bytes {}..{} ({})",
+            self.path,
+            self.detector,
+            self.offset,
+            self.offset + self.len,
+            self.action
+        )
+    }
+}
+
+/// A check run on each file during ingest; see the [module docs](self).
+pub trait IngestFilter: Send + Sync {
+    /// Name of the filter, for diagnostics.
+    fn name(&self) -> &str;
+
+    /// Detections decided from the path alone, before any content is read.
+    /// `len` is `None` for streamed input of unknown length. A detection
+    /// covering [`whole_file`]`(len)` flags the whole file.
+    fn scan_file(&self, _path: &str, _len: Option<u64>) -> Vec<Detection> {
+        Vec::new()
+    }
+
+    /// Detections in the chunk `data`, which starts at byte `offset` of the
+    /// file. Ranges are file offsets.
+    fn scan_chunk(&self, path: &str, offset: u64, data: &[u8]) -> Vec<Detection>;
+
+    /// Rewrite the chunk before it is encoded, after redaction. The default
+    /// leaves it as it is.
+    fn transform_chunk(&self, _path: &str, _offset: u64, _data: &mut [u8]) {}
+}
+
+/// What the filters decided for a file or chunk.
+#[derive(Debug)]
+pub(crate) enum Verdict {
+    /// Store it, first overwriting these file ranges.
+    Store(Vec<Range<u64>>),
+    /// Leave the file out of the engram.
+    Skip,
+}
+
+/// The filters applied during ingest and the findings they produced.
+#[derive(Default)]
+pub struct IngestFilters {
+    filters: Vec<(Box<dyn IngestFilter>, FilterAction)>,
+    /// Findings so far, in ingest order.
+    pub findings: Vec<Finding>,
+    /// Ranges of the current file flagged from its path, by filter index.
+    flagged: Vec<(usize, Range<u64>)>,
+}
+
+impl IngestFilters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `filter` on every ingested file, taking `action` on what it finds.
+    pub fn push(&mut self, filter: impl IngestFilter + 'static, action: FilterAction) {
+        self.filters.push((Box::new(filter), action));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Record the file-level detections for `path` and decide whether to
+    /// read it.
+    pub(crate) fn check_file(&mut self, path: &str, len: Option<u64>) -> Result<Verdict, EmbrError> {
+        let detections: Vec<_> = self
+            .filters
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (filter, action))| {
+                filter
+                    .scan_file(path, len)
+                    .into_iter()
+                    .map(move |d| (i, d, *action))
+            })
+            .collect();
+        self.flagged = detections.iter().map(|(i, d, _)| (*i, d.range.clone())).collect();
+        self.record(path, detections)
+    }
+
+    /// Record the detections in one chunk and decide what to store.
+    pub(crate) fn check_chunk(&mut self, path: &str, offset: u64, data: &[u8]) -> Result<Verdict, EmbrError> {
+        let flagged = &self.flagged;
+        let detections: Vec<_> = self
+            .filters
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (filter, action))| {
+                filter
+                    .scan_chunk(path, offset, data)
+                    .into_iter()
+                    .filter(move |d| !flagged.iter().any(|(j, r)| *j == i && r.contains(&d.range.start)))
+                    .map(move |d| (i, d, *action))
+            })
+            .collect();
+        self.record(path, detections)
+    }
+
+    /// Run every filter's [`IngestFilter::transform_chunk`] on `data`, in
+    /// order.
+    pub(crate) fn transform_chunk(&self, path: &str, offset: u64, data: &mut [u8]) {
+        for (filter, _) in &self.filters {
+            filter.transform_chunk(path, offset, data);
+        }
+    }
+
+    /// Record every detection, then fail on the first rejected one, or skip
+    /// on any skipped one.
+    fn record(&mut self, path: &str, detections: Vec<(usize, Detection, FilterAction)>) -> Result<Verdict, EmbrError> {
+        let mut redact = Vec::new();
+        let mut rejected = None;
+        let mut skip = false;
+        for (_, detection, action) in detections {
+            let finding = Finding {
+                path: path.to_string(),
+                offset: detection.range.start,
+                len: detection.range.end.saturating_sub(detection.range.start),
+                detector: detection.detector,
+                action,
+            };
+            match action {
+                FilterAction::Warn => {}
+                FilterAction::Redact => redact.push(detection.range),
+                FilterAction::Skip => skip = true,
+                FilterAction::Reject => {
+                    rejected.get_or_insert_with(|| finding.clone());
+                }
+            }
+            self.findings.push(finding);
+        }
+        match rejected {
+            Some(finding) => Err(EmbrError::Rejected(finding)),
+            None if skip => Ok(Verdict::Skip),
+            None => Ok(Verdict::Store(redact)),
+        }
+    }
+}
+
+/// Overwrite the parts of `chunk` (starting at file offset `offset`) that
+/// fall in any of `ranges` with `*`.
+pub(crate) fn redact(chunk: &mut [u8], offset: u64, ranges: &[Range<u64>]) {
+    let end = offset + chunk.len() as u64;
+    for range in ranges {
+        let start = range.start.max(offset);
+        let stop = range.end.min(end);
+        if start < stop {
+            chunk[(start - offset) as usize..(stop - offset) as usize].fill(b'*');
+        }
+    }
+}
+
+/// Tokens shorter than this are never flagged for entropy.
+const DEFAULT_MIN_TOKEN_LEN: usize = 24;
+/// Shannon entropy, in bits per character, above which a token is flagged.
+/// Random base64 scores well above this, English words and identifiers well
+/// below.
+const DEFAULT_ENTROPY_BITS: f64 = 4.5;
+
+/// Regular-expression, entropy and file-name secret detectors.
+///
+/// When a pattern has a capture group, only the first group is reported, so
+/// `password = hunter2hunter2` redacts the value and keeps the key.
+pub struct SecretScanner {
+    patterns: Vec<(String, Regex)>,
+    /// Minimum token length and entropy threshold, or `None` to disable the
+    /// entropy detector.
+    entropy: Option<(usize, f64)>,
+    key_files: bool,
+}
+
+impl SecretScanner {
+    /// A scanner with no detectors.
+    pub fn empty() -> Self {
+        Self {
+            patterns: Vec::new(),
+            entropy: None,
+            key_files: false,
+        }
+    }
+
+    /// The built-in detectors: AWS access key ids, PEM private keys, GitHub
+    /// and Slack tokens, `key = value` assignments to secret-looking names,
+    /// high-entropy tokens in text, and SSH private key and `.env` files.
+    pub fn builtin() -> Self {
+        const PATTERNS: &[(&str, &str)] = &[
+            ("aws-access-key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+            ("private-key", r"-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY-----"),
+            ("github-token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+            ("slack-token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+            (
+                "secret-assignment",
+                r#"(?i)(?:api[_-]?key|secret|passw(?:or)?d|access[_-]?token)["']?\s*[:=]\s*["']?([^\s"',;]{8,})"#,
+            ),
+        ];
+        let mut scanner = Self::empty();
+        for (name, pattern) in PATTERNS {
+            scanner = scanner
+                .with_pattern(name, pattern)
+                .expect("built-in patterns compile");
+        }
+        scanner.entropy = Some((DEFAULT_MIN_TOKEN_LEN, DEFAULT_ENTROPY_BITS));
+        scanner.key_files = true;
+        scanner
+    }
+
+    /// Add a detector named `name` for the regular expression `pattern`.
+    pub fn with_pattern(mut self, name: &str, pattern: &str) -> io::Result<Self> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("secret pattern {}: {}", name, e),
+            )
+        })?;
+        self.patterns.push((name.to_string(), regex));
+        Ok(self)
+    }
+
+    /// Flag tokens of at least `min_len` characters, mixing letters and
+    /// digits, whose entropy exceeds `bits` per character; `None` disables
+    /// the entropy detector.
+    pub fn with_entropy(mut self, threshold: Option<(usize, f64)>) -> Self {
+        self.entropy = threshold;
+        self
+    }
+
+    fn entropy_hits(&self, data: &[u8]) -> Vec<Range<usize>> {
+        let Some((min_len, bits)) = self.entropy else {
+            return Vec::new();
+        };
+        if !is_text_file(data) {
+            return Vec::new();
+        }
+        let is_token = |b: &u8| b.is_ascii_alphanumeric() || b"+/=_-".contains(b);
+        let mut hits = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            if !is_token(&data[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < data.len() && is_token(&data[i]) {
+                i += 1;
+            }
+            let token = &data[start..i];
+            if token.len() >= min_len
+                && token.iter().any(u8::is_ascii_digit)
+                && token.iter().any(u8::is_ascii_alphabetic)
+                && shannon_entropy(token) > bits
+            {
+                hits.push(start..i);
+            }
+        }
+        hits
+    }
+}
+
+impl IngestFilter for SecretScanner {
+    fn name(&self) -> &str {
+        "secrets"
+    }
+
+    fn scan_file(&self, path: &str, len: Option<u64>) -> Vec<Detection> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let key_file = matches!(name, "id_rsa" | "id_dsa" | "id_ecdsa" | "id_ed25519");
+        let env_file = name == ".env" || name.starts_with(".env.");
+        if !self.key_files || !(key_file || env_file) {
+            return Vec::new();
+        }
+        vec![Detection {
+            detector: if key_file {
+                "ssh-private-key-file"
+            } else {
+                "env-file"
+            }
+            .to_string(),
+            range: whole_file(len),
+        }]
+    }
+
+    fn scan_chunk(&self, _path: &str, offset: u64, data: &[u8]) -> Vec<Detection> {
+        let at = |detector: &str, r: Range<usize>| Detection {
+            detector: detector.to_string(),
+            range: offset + r.start as u64..offset + r.end as u64,
+        };
+        let mut found = Vec::new();
+        for (name, regex) in &self.patterns {
+            for caps in regex.captures_iter(data) {
+                let m = caps
+                    .get(1)
+                    .or_else(|| caps.get(0))
+                    .expect("group 0 always matches");
+                found.push(at(name, m.range()));
+            }
+        }
+        for r in self.entropy_hits(data) {
+            // Tokens already reported by a pattern are not reported again.
+            let (start, end) = (offset + r.start as u64, offset + r.end as u64);
+            if !found
+                .iter()
+                .any(|d| d.range.start < end && start < d.range.end)
+            {
+                found.push(at("high-entropy", r));
+            }
+        }
+        found
+    }
+}
+
+/// Flags files larger than a cap, from the length when it is known up front
+/// and otherwise at the chunk that crosses it. Detections cover the whole
+/// file when flagged from the length, the crossing chunk otherwise; the cap
+/// is meant for warn, skip or reject.
+pub struct SizeLimit {
+    pub max_bytes: u64,
+}
+
+impl SizeLimit {
+    pub fn new(max_bytes: u64) -> Self {
+        Self { max_bytes }
+    }
+}
+
+impl IngestFilter for SizeLimit {
+    fn name(&self) -> &str {
+        "size-limit"
+    }
+
+    fn scan_file(&self, _path: &str, len: Option<u64>) -> Vec<Detection> {
+        match len {
+            Some(len) if len > self.max_bytes => vec![Detection {
+                detector: "size-limit".to_string(),
+                range: 0..len,
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn scan_chunk(&self, _path: &str, offset: u64, data: &[u8]) -> Vec<Detection> {
+        let end = offset + data.len() as u64;
+        if offset > self.max_bytes || end <= self.max_bytes {
+            return Vec::new();
+        }
+        vec![Detection {
+            detector: "size-limit".to_string(),
+            range: offset..end,
+        }]
+    }
+}
+
+/// Flags files by extension, compared without regard to case: those with
+/// one of a list, or those without.
+pub struct ExtensionFilter {
+    extensions: Vec<String>,
+    /// Flag the files whose extension is *not* listed.
+    allow_list: bool,
+}
+
+impl ExtensionFilter {
+    /// Flag files with any of `extensions` (given without the dot).
+    pub fn deny<S: AsRef<str>>(extensions: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            extensions: extensions.into_iter().map(|e| normalize_extension(e.as_ref())).collect(),
+            allow_list: false,
+        }
+    }
+
+    /// Flag files with none of `extensions`, including files without one.
+    pub fn allow<S: AsRef<str>>(extensions: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            allow_list: true,
+            ..Self::deny(extensions)
+        }
+    }
+}
+
+fn normalize_extension(ext: &str) -> String {
+    ext.trim_start_matches('.').to_ascii_lowercase()
+}
+
+impl IngestFilter for ExtensionFilter {
+    fn name(&self) -> &str {
+        "extensions"
+    }
+
+    fn scan_file(&self, path: &str, len: Option<u64>) -> Vec<Detection> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let ext = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => normalize_extension(ext),
+            _ => String::new(),
+        };
+        let listed = !ext.is_empty() && self.extensions.contains(&ext);
+        if listed == self.allow_list {
+            return Vec::new();
+        }
+        vec![Detection {
+            detector: "extension".to_string(),
+            range: whole_file(len),
+        }]
+    }
+
+    fn scan_chunk(&self, _path: &str, _offset: u64, _data: &[u8]) -> Vec<Detection> {
+        Vec::new()
+    }
+}
+
+/// Entropy, in bits per byte, above which a first chunk is taken to be
+/// compressed or encrypted.
+const COMPRESSED_ENTROPY_BITS: f64 = 7.5;
+
+/// Flags files whose first chunk starts with the signature of a compressed
+/// format (gzip, zstd, xz, bzip2, lz4, zip, 7z, PNG, JPEG) or is nearly
+/// random. The detection covers the first chunk.
+pub struct CompressedContent {
+    /// Also flag nearly random first chunks with no known signature.
+    pub by_entropy: bool,
+}
+
+impl Default for CompressedContent {
+    fn default() -> Self {
+        Self { by_entropy: true }
+    }
+}
+
+impl CompressedContent {
+    const SIGNATURES: &'static [(&'static str, &'static [u8])] = &[
+        ("gzip", b"\x1f\x8b"),
+        ("zstd", b"\x28\xb5\x2f\xfd"),
+        ("xz", b"\xfd7zXZ\x00"),
+        ("bzip2", b"BZh"),
+        ("lz4", b"\x04\x22\x4d\x18"),
+        ("zip", b"PK\x03\x04"),
+        ("7z", b"7z\xbc\xaf\x27\x1c"),
+        ("png", b"\x89PNG\r\n\x1a\n"),
+        ("jpeg", b"\xff\xd8\xff"),
+    ];
+
+    /// Format `data`, the start of a file, is compressed with, if any.
+    pub fn detect(&self, data: &[u8]) -> Option<&'static str> {
+        if let Some((format, _)) = Self::SIGNATURES.iter().find(|(_, magic)| data.starts_with(magic)) {
+            return Some(format);
+        }
+        // Short samples cannot reach a high entropy.
+        let random = self.by_entropy && data.len() >= 1024 && shannon_entropy(data) > COMPRESSED_ENTROPY_BITS;
+        random.then_some("high-entropy")
+    }
+}
+
+impl IngestFilter for CompressedContent {
+    fn name(&self) -> &str {
+        "compressed"
+    }
+
+    fn scan_chunk(&self, _path: &str, offset: u64, data: &[u8]) -> Vec<Detection> {
+        if offset != 0 {
+            return Vec::new();
+        }
+        self.detect(data)
+            .map(|format| Detection {
+                detector: format!("compressed-{}", format),
+                range: 0..data.len() as u64,
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte.
+fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let n = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / n;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,225 @@
+//! Who built an engram, from what, and how.
+//!
+//! Ingest records an [`IngestProvenance`] in the manifest: the tool and its
+//! version, digests of the encoder settings and of every other setting
+//! that changes the output, the chunking strategy, the host, and a digest
+//! over every input file. `embeddenator info` shows it, so an archive can be
+//! audited without the tree it came from.
+//!
+//! The digests make two claims checkable later. [`IngestProvenance::check`]
+//! recomputes them from an engram and manifest and reports what no longer
+//! matches, so edits made after ingest show up. And two ingests with equal
+//! recipes (the same tool version, settings and inputs) should have
+//! produced the same engram; [`IngestProvenance::reproduces`] says whether
+//! they did. Reproducible ingest ([`EmbrFS::reproducible`]) records no
+//! hostname and stamps its fixed [`IngestClock`](crate::IngestClock) time,
+//! so the provenance itself does not break byte-identical manifests.
+
+use crate::delta::engram_digest;
+use crate::embrfs::{EmbrFS, Engram, Manifest};
+use crate::vsa::ChunkEncoding;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+/// How input files were cut into chunks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunking {
+    /// `fixed`: every chunk is `chunk_size` bytes except a file's last.
+    pub strategy: String,
+    pub chunk_size: usize,
+}
+
+impl Chunking {
+    fn of(manifest: &Manifest) -> Self {
+        Chunking {
+            strategy: "fixed".to_string(),
+            chunk_size: manifest.chunk_size(),
+        }
+    }
+}
+
+/// Provenance of an ingest; see the [module docs](self). Digests are blake3,
+/// hex encoded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngestProvenance {
+    pub tool: String,
+    pub tool_version: String,
+    /// `reversible` or `similarity`.
+    pub encoder: String,
+    /// Digest of the recorded [`EncodingConfig`](crate::EncodingConfig).
+    pub encoder_hash: String,
+    /// Digest of every setting that shapes the output: the encoding, path
+    /// normalization and chunking, and whether ingest was reproducible.
+    pub config_hash: String,
+    pub chunking: Chunking,
+    pub reproducible: bool,
+    /// Host the ingest ran on; not recorded by reproducible ingest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Unix time of the ingest, as its clock stamps files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorded_at: Option<u64>,
+    /// Input paths as given to ingest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    pub input_files: usize,
+    pub input_bytes: u64,
+    /// Digest over the path, size and content digest of every input file.
+    pub input_digest: String,
+    /// [`engram_digest`] of the engram as ingest left it.
+    pub engram_digest: String,
+}
+
+/// A recorded digest that no longer matches; see [`IngestProvenance::check`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum ProvenanceIssue {
+    Encoder { recorded: String, found: String },
+    Config { recorded: String, found: String },
+    Inputs { recorded: String, found: String },
+    Engram { recorded: String, found: String },
+}
+
+impl fmt::Display for ProvenanceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (what, recorded, found) = match self {
+            ProvenanceIssue::Encoder { recorded, found } => ("encoder settings", recorded, found),
+            ProvenanceIssue::Config { recorded, found } => ("ingest settings", recorded, found),
+            ProvenanceIssue::Inputs { recorded, found } => ("input files", recorded, found),
+            ProvenanceIssue::Engram { recorded, found } => ("engram", recorded, found),
+        };
+        write!(f, "{} changed since ingest (recorded {}, now {})", what, short(recorded), short(found))
+    }
+}
+
+fn short(digest: &str) -> &str {
+    &digest[..digest.len().min(16)]
+}
+
+impl IngestProvenance {
+    /// Provenance of what `fs` holds now, ingested from `sources`.
+    pub fn record(fs: &EmbrFS, sources: &[String]) -> io::Result<Self> {
+        let reproducible = fs.chunk_index.is_some();
+        let manifest = &fs.manifest;
+        Ok(IngestProvenance {
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            encoder: match manifest.encoding().vsa.chunk_encoding {
+                ChunkEncoding::Reversible => "reversible",
+                ChunkEncoding::Similarity { .. } => "similarity",
+            }
+            .to_string(),
+            encoder_hash: encoder_hash(manifest)?,
+            config_hash: config_hash(manifest, reproducible)?,
+            chunking: Chunking::of(manifest),
+            reproducible,
+            hostname: if reproducible { None } else { hostname() },
+            recorded_at: fs.clock.stamp(),
+            sources: sources.to_vec(),
+            input_files: manifest.files.len(),
+            input_bytes: manifest.files.iter().map(|f| f.size as u64).sum(),
+            input_digest: input_digest(manifest),
+            engram_digest: hex(&engram_digest(&fs.engram)?),
+        })
+    }
+
+    /// Recompute the digests from `engram` and `manifest` and list those
+    /// that differ from the recorded ones. Empty if nothing changed.
+    pub fn check(&self, engram: &Engram, manifest: &Manifest) -> io::Result<Vec<ProvenanceIssue>> {
+        let mut issues = Vec::new();
+        let found = encoder_hash(manifest)?;
+        if found != self.encoder_hash {
+            issues.push(ProvenanceIssue::Encoder { recorded: self.encoder_hash.clone(), found });
+        }
+        let found = config_hash(manifest, self.reproducible)?;
+        if found != self.config_hash {
+            issues.push(ProvenanceIssue::Config { recorded: self.config_hash.clone(), found });
+        }
+        let found = input_digest(manifest);
+        if found != self.input_digest {
+            issues.push(ProvenanceIssue::Inputs { recorded: self.input_digest.clone(), found });
+        }
+        let found = hex(&engram_digest(engram)?);
+        if found != self.engram_digest {
+            issues.push(ProvenanceIssue::Engram { recorded: self.engram_digest.clone(), found });
+        }
+        Ok(issues)
+    }
+
+    /// Whether `self` and `other` ran the same tool version with the same
+    /// settings over the same inputs.
+    pub fn same_recipe(&self, other: &IngestProvenance) -> bool {
+        self.tool == other.tool
+            && self.tool_version == other.tool_version
+            && self.config_hash == other.config_hash
+            && self.chunking == other.chunking
+            && self.input_digest == other.input_digest
+    }
+
+    /// Whether `other` repeats this ingest and got the same engram. `false`
+    /// for differing recipes, which are not expected to agree.
+    pub fn reproduces(&self, other: &IngestProvenance) -> bool {
+        self.same_recipe(other) && self.engram_digest == other.engram_digest
+    }
+}
+
+impl EmbrFS {
+    /// Record the [`IngestProvenance`] of what this EmbrFS holds in its
+    /// manifest. Call once ingest is done (after [`EmbrFS::canonicalize`]
+    /// for reproducible ingest) and before saving.
+    pub fn record_provenance(&mut self, sources: &[String]) -> io::Result<()> {
+        self.manifest.provenance = Some(Box::new(IngestProvenance::record(self, sources)?));
+        Ok(())
+    }
+}
+
+fn encoder_hash(manifest: &Manifest) -> io::Result<String> {
+    Ok(blake3::hash(&serde_json::to_vec(&manifest.encoding())?).to_hex().to_string())
+}
+
+fn config_hash(manifest: &Manifest, reproducible: bool) -> io::Result<String> {
+    let settings = serde_json::json!({
+        "encoding": manifest.encoding(),
+        "path_normalization": manifest.path_normalization,
+        "chunking": Chunking::of(manifest),
+        "reproducible": reproducible,
+    });
+    Ok(blake3::hash(&serde_json::to_vec(&settings)?).to_hex().to_string())
+}
+
+/// Files are taken in path order so the digest does not depend on the
+/// order they were ingested in.
+fn input_digest(manifest: &Manifest) -> String {
+    let mut files: Vec<_> = manifest.files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut hasher = blake3::Hasher::new();
+    for file in files {
+        hasher.update(&(file.path.len() as u64).to_le_bytes());
+        hasher.update(file.path.as_bytes());
+        hasher.update(&(file.size as u64).to_le_bytes());
+        hasher.update(file.blake3.as_deref().unwrap_or("").as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+fn hex(digest: &[u8; 32]) -> String {
+    blake3::Hash::from(*digest).to_hex().to_string()
+}
+
+fn hostname() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        // SAFETY: `buf` is writable for the length passed.
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            if let Ok(name) = std::str::from_utf8(&buf[..end]) {
+                if !name.is_empty() {
+                    return Some(name.to_string());
+                }
+            }
+        }
+    }
+    std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")).ok()
+}
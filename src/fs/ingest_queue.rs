@@ -0,0 +1,468 @@
+//! Parallel directory ingest with a memory-bounded queue.
+//!
+//! Encoding a chunk (and decoding it again to find its correction) is the
+//! expensive part of ingest, and it depends only on the chunk's bytes and
+//! logical path. With an [`IngestPipeline`] set on an
+//! [`EmbrFS`](crate::EmbrFS), directory ingest reads and encodes files on
+//! worker threads while the calling thread, the bundler, stores the results
+//! in file order: it assigns chunk ids, deduplicates, runs the ingest
+//! filters and bundles into the root exactly as a serial ingest would, so
+//! the engram comes out the same. A chunk that a filter changed after a
+//! worker encoded it is simply encoded again.
+//!
+//! # Backpressure
+//!
+//! Encoded chunks wait in an [`IngestQueue`] until the bundler reaches them,
+//! and the queue is bounded by the bytes it holds. Once they reach the high
+//! [`Watermarks`] mark, workers stop encoding until the bundler has drained
+//! the queue to the low mark, so encoding never runs further ahead of the
+//! bundler than the marks allow, and a full queue does not flap between
+//! paused and running on every chunk.
+//!
+//! The queue also answers to the engram's own memory accounting
+//! ([`EmbrFS::held_memory_bytes`](crate::EmbrFS::held_memory_bytes)). Given
+//! a memory budget, the high mark drops so that the queue and what the
+//! engram holds stay within it together, down to a single chunk of
+//! lookahead once the engram alone fills the budget; the low mark keeps the
+//! same distance below it. The budget defaults to
+//! [`IngestLimits::max_memory_bytes`](crate::IngestLimits::max_memory_bytes).
+//!
+//! The worker encoding the file the bundler is on may always queue a chunk
+//! when none of that file's are waiting, so a queue filled by later files
+//! cannot stall ingest.
+
+use crate::logging;
+use crate::profile;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::Scope;
+
+/// Bytes of encoded chunks the queue may hold before workers pause (`high`)
+/// and may hold again before they resume (`low`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watermarks {
+    pub high: u64,
+    pub low: u64,
+}
+
+impl Watermarks {
+    /// Marks at `high` and `low` bytes; `low` is capped at `high`.
+    pub fn new(high: u64, low: u64) -> Self {
+        Self { high, low: low.min(high) }
+    }
+
+    /// Marks for a queue of at most `bytes`, resuming at three quarters.
+    pub fn bounded(bytes: u64) -> Self {
+        Self::new(bytes, bytes / 4 * 3)
+    }
+
+    /// The marks lowered so that `held` bytes held elsewhere and the queue
+    /// together stay within `budget`.
+    fn within(self, budget: Option<u64>, held: u64) -> Self {
+        let Some(budget) = budget else { return self };
+        let high = self.high.min(budget.saturating_sub(held));
+        Self {
+            high,
+            low: high.saturating_sub(self.high.saturating_sub(self.low)),
+        }
+    }
+}
+
+impl Default for Watermarks {
+    /// 64 MiB, resuming at 48 MiB.
+    fn default() -> Self {
+        Self::bounded(64 << 20)
+    }
+}
+
+/// How directory ingest runs in parallel; see the [module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IngestPipeline {
+    /// Encoding threads, besides the bundler.
+    pub threads: usize,
+    pub watermarks: Watermarks,
+    /// Bytes the engram and the queue may hold together;
+    /// [`IngestLimits::max_memory_bytes`](crate::IngestLimits::max_memory_bytes)
+    /// when unset.
+    pub memory_budget: Option<u64>,
+}
+
+impl IngestPipeline {
+    /// `threads` encoding threads with the default marks.
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            watermarks: Watermarks::default(),
+            memory_budget: None,
+        }
+    }
+}
+
+impl Default for IngestPipeline {
+    /// One encoding thread per available core.
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+/// What an [`IngestQueue`] went through.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Items that passed through.
+    pub items: u64,
+    /// Most bytes queued at once.
+    pub peak_bytes: u64,
+    /// Times the queue reached its high mark and paused the producers.
+    pub pauses: u64,
+}
+
+impl QueueStats {
+    /// Add the counts of `other`, as from a later run.
+    pub fn merge(&mut self, other: &QueueStats) {
+        self.items += other.items;
+        self.peak_bytes = self.peak_bytes.max(other.peak_bytes);
+        self.pauses += other.pauses;
+    }
+}
+
+struct State<T> {
+    /// Waiting items of each file, with their sizes.
+    files: Vec<VecDeque<(T, u64)>>,
+    finished: Vec<bool>,
+    /// Files whose items the consumer no longer wants.
+    dropped: Vec<bool>,
+    /// File the consumer is taking items from.
+    head: usize,
+    queued: u64,
+    held: u64,
+    paused: bool,
+    closed: bool,
+    stats: QueueStats,
+}
+
+/// Items of numbered files, produced on any thread and taken file by file
+/// in order, bounded by [`Watermarks`]; see the [module docs](self).
+pub struct IngestQueue<T> {
+    state: Mutex<State<T>>,
+    /// Signalled when producers may push again.
+    space: Condvar,
+    /// Signalled when the consumer may have something to take.
+    ready: Condvar,
+    watermarks: Watermarks,
+    budget: Option<u64>,
+}
+
+impl<T> IngestQueue<T> {
+    /// A queue for `files` files, holding memory within `budget` together
+    /// with what [`IngestQueue::set_held`] reports, if there is one.
+    pub fn new(files: usize, watermarks: Watermarks, budget: Option<u64>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                files: (0..files).map(|_| VecDeque::new()).collect(),
+                finished: vec![false; files],
+                dropped: vec![false; files],
+                head: 0,
+                queued: 0,
+                held: 0,
+                paused: false,
+                closed: false,
+                stats: QueueStats::default(),
+            }),
+            space: Condvar::new(),
+            ready: Condvar::new(),
+            watermarks,
+            budget,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn marks(&self, state: &State<T>) -> Watermarks {
+        self.watermarks.within(self.budget, state.held)
+    }
+
+    /// Resume the producers if the queue is down to its low mark.
+    fn maybe_resume(&self, state: &mut State<T>) {
+        if state.paused && state.queued <= self.marks(state).low {
+            state.paused = false;
+            self.space.notify_all();
+        }
+    }
+
+    /// Queue `item`, of `bytes` bytes, as the next of file `file`, waiting
+    /// while the queue is paused. Returns `false`, dropping the item, once
+    /// the queue is closed or the file dropped, as there is no point in
+    /// producing more for it.
+    pub fn push(&self, file: usize, item: T, bytes: u64) -> bool {
+        let mut state = self.lock();
+        // A paused queue still takes the file the consumer waits for when
+        // none of its items are queued.
+        let waits = |state: &State<T>| {
+            state.paused && !state.closed && !state.dropped[file] && (file != state.head || !state.files[file].is_empty())
+        };
+        while waits(&state) {
+            state = self.space.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if state.closed || state.dropped[file] {
+            return false;
+        }
+        state.files[file].push_back((item, bytes));
+        state.queued += bytes;
+        state.stats.items += 1;
+        state.stats.peak_bytes = state.stats.peak_bytes.max(state.queued);
+        if !state.paused && state.queued >= self.marks(&state).high {
+            state.paused = true;
+            state.stats.pauses += 1;
+        }
+        if file == state.head {
+            self.ready.notify_all();
+        }
+        true
+    }
+
+    /// Mark file `file` complete: nothing more will be pushed for it.
+    pub fn finish(&self, file: usize) {
+        let mut state = self.lock();
+        state.finished[file] = true;
+        if file == state.head {
+            self.ready.notify_all();
+        }
+    }
+
+    /// The next item of file `file`, waiting for it, or `None` once the
+    /// file is finished and drained or the queue is closed. Taking from a
+    /// file makes it the one whose producer is never held back.
+    pub fn pop(&self, file: usize) -> Option<T> {
+        let mut state = self.lock();
+        if state.head != file {
+            state.head = file;
+            self.space.notify_all();
+        }
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some((item, bytes)) = state.files[file].pop_front() {
+                state.queued -= bytes;
+                self.maybe_resume(&mut state);
+                if state.files[file].is_empty() {
+                    // The file's producer may be waiting for its turn.
+                    self.space.notify_all();
+                }
+                return Some(item);
+            }
+            if state.finished[file] {
+                return None;
+            }
+            state = self.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Drop what is queued for file `file` and anything pushed for it later,
+    /// for a consumer that stopped reading it early.
+    pub fn drop_file(&self, file: usize) {
+        let mut state = self.lock();
+        state.dropped[file] = true;
+        let freed: u64 = state.files[file].drain(..).map(|(_, bytes)| bytes).sum();
+        state.queued -= freed;
+        self.maybe_resume(&mut state);
+        // The file's producer may be waiting to push.
+        self.space.notify_all();
+    }
+
+    /// Report the bytes the consumer holds outside the queue, such as
+    /// [`EmbrFS::held_memory_bytes`](crate::EmbrFS::held_memory_bytes),
+    /// which lower the marks under a memory budget.
+    pub fn set_held(&self, bytes: u64) {
+        let mut state = self.lock();
+        state.held = bytes;
+        self.maybe_resume(&mut state);
+    }
+
+    /// Bytes queued right now.
+    pub fn queued_bytes(&self) -> u64 {
+        self.lock().queued
+    }
+
+    /// Drop everything queued and refuse further items, releasing every
+    /// waiting producer and consumer.
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        state.files.iter_mut().for_each(VecDeque::clear);
+        state.queued = 0;
+        self.space.notify_all();
+        self.ready.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.lock().stats
+    }
+}
+
+/// A chunk as a worker read and encoded it.
+pub(crate) struct EncodedChunk {
+    pub data: Vec<u8>,
+    pub vec: SparseVec,
+    pub decoded: Vec<u8>,
+}
+
+impl EncodedChunk {
+    fn bytes(&self) -> u64 {
+        (self.data.capacity() + self.decoded.capacity()) as u64 + crate::memory::sparse_vec_bytes(&self.vec)
+    }
+}
+
+/// What a worker queues for a file.
+pub(crate) enum Piece {
+    Opened(fs::Metadata),
+    Chunk(EncodedChunk),
+    Failed(io::Error),
+}
+
+/// Spawn `threads` threads in `scope` reading and encoding the files of
+/// `paths`, each under its logical path, queueing the pieces of file `i` as
+/// file `i`. Files are taken in order, so the file the consumer waits for
+/// is always being worked on or done. The threads stop once the queue is
+/// closed.
+pub(crate) fn spawn_encoders<'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    queue: &'scope IngestQueue<Piece>,
+    paths: &'scope [(PathBuf, String)],
+    threads: usize,
+    chunk_size: usize,
+    config: &'scope ReversibleVSAConfig,
+) {
+    let next = Arc::new(AtomicUsize::new(0));
+    let operation = logging::current();
+    for _ in 0..threads.clamp(1, paths.len().max(1)) {
+        let next = Arc::clone(&next);
+        let operation = operation.clone();
+        scope.spawn(move || {
+            // Log under the operation that started the ingest.
+            let _op = operation.as_ref().map(logging::Operation::enter);
+            while !queue.is_closed() {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some((path, logical)) = paths.get(i) else { break };
+                encode_file(queue, i, path, logical, chunk_size, config);
+                queue.finish(i);
+            }
+        });
+    }
+}
+
+/// Queue the pieces of one file, until it ends or the queue wants no more.
+fn encode_file(
+    queue: &IngestQueue<Piece>,
+    i: usize,
+    path: &Path,
+    logical: &str,
+    chunk_size: usize,
+    config: &ReversibleVSAConfig,
+) {
+    let _t = profile::scope("encode_file");
+    let opened = fs::metadata(path).and_then(|meta| Ok((meta, File::open(path)?)));
+    let mut reader = match opened {
+        Ok((meta, file)) => {
+            if !queue.push(i, Piece::Opened(meta), 0) {
+                return;
+            }
+            BufReader::with_capacity(64 * 1024, file)
+        }
+        Err(e) => {
+            queue.push(i, Piece::Failed(e), 0);
+            return;
+        }
+    };
+    loop {
+        let mut data = vec![0u8; chunk_size];
+        let n = match crate::embrfs::read_full(&mut reader, &mut data) {
+            Ok(0) => return,
+            Ok(n) => n,
+            Err(e) => {
+                queue.push(i, Piece::Failed(e), 0);
+                return;
+            }
+        };
+        data.truncate(n);
+        let vec = {
+            let _t = profile::scope("encode_chunk");
+            SparseVec::encode_chunk(&data, config, Some(logical))
+        };
+        let decoded = {
+            let _t = profile::scope("verify_chunk");
+            vec.decode_data(config, Some(logical), n)
+        };
+        let chunk = EncodedChunk { data, vec, decoded };
+        let bytes = chunk.bytes();
+        if !queue.push(i, Piece::Chunk(chunk), bytes) {
+            return;
+        }
+    }
+}
+
+/// Where ingest reads a file's chunks from.
+pub(crate) trait ChunkSource {
+    /// Fill `buf` as far as the file goes, returning the bytes read.
+    fn read_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// The vector and decoded bytes of the chunk just read, if they were
+    /// computed already for exactly `chunk`.
+    fn encoded(&mut self, _chunk: &[u8]) -> Option<(SparseVec, Vec<u8>)> {
+        None
+    }
+}
+
+impl<R: Read> ChunkSource for R {
+    fn read_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        crate::embrfs::read_full(self, buf)
+    }
+}
+
+/// File `file` of an [`IngestQueue`], read chunk by chunk as the worker
+/// queued it.
+pub(crate) struct QueuedFile<'a> {
+    queue: &'a IngestQueue<Piece>,
+    file: usize,
+    last: Option<EncodedChunk>,
+}
+
+impl<'a> QueuedFile<'a> {
+    pub fn new(queue: &'a IngestQueue<Piece>, file: usize) -> Self {
+        Self { queue, file, last: None }
+    }
+}
+
+impl ChunkSource for QueuedFile<'_> {
+    fn read_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.last = None;
+        match self.queue.pop(self.file) {
+            None => Ok(0),
+            Some(Piece::Chunk(chunk)) => {
+                // Workers read with the bundler's chunk size.
+                let n = chunk.data.len().min(buf.len());
+                buf[..n].copy_from_slice(&chunk.data[..n]);
+                self.last = Some(chunk);
+                Ok(n)
+            }
+            Some(Piece::Failed(e)) => Err(e),
+            Some(Piece::Opened(_)) => Err(io::Error::other("file opened twice in the ingest queue")),
+        }
+    }
+
+    fn encoded(&mut self, chunk: &[u8]) -> Option<(SparseVec, Vec<u8>)> {
+        let last = self.last.take()?;
+        (last.data == chunk).then_some((last.vec, last.decoded))
+    }
+}
@@ -0,0 +1,192 @@
+//! Deduplication and compression statistics for an ingested engram.
+//!
+//! Statistics are derived from the manifest and engram alone, so they can be
+//! computed right after ingest (`EmbrFS::ingest_stats`) or later from saved
+//! artifacts (`embeddenator stats`).
+//!
+//! - **Raw bytes**: sum of original file sizes.
+//! - **Unique chunk bytes**: bytes of distinct chunk contents, identified by
+//!   the content hash stored in the correction layer.
+//! - **Dedup ratio**: `raw_bytes / unique_chunk_bytes` (1.0 = no duplication).
+//! - **Compression ratio**: `raw_bytes / encoded_bytes`, where encoded bytes is
+//!   the estimated serialized size of the chunk vectors plus corrections.
+
+use crate::embrfs::{EmbrFS, Engram, FileEntry, Manifest};
+use crate::ingest_filter::Finding;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Aggregate counters for one group of files.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsBucket {
+    pub files: usize,
+    pub chunks: usize,
+    pub raw_bytes: u64,
+    pub unique_chunk_bytes: u64,
+    pub encoded_bytes: u64,
+    pub dedup_ratio: f64,
+    pub compression_ratio: f64,
+}
+
+/// Number of files whose chunk count falls in `[min_chunks, max_chunks]`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistogramBin {
+    pub min_chunks: usize,
+    pub max_chunks: usize,
+    pub files: usize,
+}
+
+/// Structured deduplication/compression report.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IngestStats {
+    pub total: StatsBucket,
+    /// Keyed by lowercase extension without the dot (`""` for none).
+    pub by_extension: BTreeMap<String, StatsBucket>,
+    /// Keyed by the file's parent directory (`"."` for top-level files).
+    pub by_directory: BTreeMap<String, StatsBucket>,
+    /// Files per chunk-count range, in power-of-two bins.
+    pub chunk_histogram: Vec<HistogramBin>,
+    /// What ingest filters detected; only known right after ingest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+}
+
+/// Accumulates a bucket while tracking which chunk contents were already seen.
+#[derive(Default)]
+struct BucketBuilder {
+    bucket: StatsBucket,
+    seen: HashSet<ChunkKey>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ChunkKey {
+    Content([u8; 8], usize),
+    Id(usize),
+}
+
+impl BucketBuilder {
+    fn add_file(&mut self, file: &FileEntry, chunks: &[(ChunkKey, u64, u64)]) {
+        self.bucket.files += 1;
+        self.bucket.raw_bytes += file.size as u64;
+        for &(key, len, encoded) in chunks {
+            self.bucket.chunks += 1;
+            // Every chunk is stored, duplicates included.
+            self.bucket.encoded_bytes += encoded;
+            if self.seen.insert(key) {
+                self.bucket.unique_chunk_bytes += len;
+            }
+        }
+    }
+
+    fn finish(mut self) -> StatsBucket {
+        let b = &mut self.bucket;
+        b.dedup_ratio = ratio(b.raw_bytes, b.unique_chunk_bytes);
+        b.compression_ratio = ratio(b.raw_bytes, b.encoded_bytes);
+        self.bucket
+    }
+}
+
+fn ratio(num: u64, den: u64) -> f64 {
+    if den == 0 {
+        1.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+pub(crate) fn extension_of(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext.to_ascii_lowercase(),
+        _ => String::new(),
+    }
+}
+
+fn directory_of(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((dir, _)) if !dir.is_empty() => dir.to_string(),
+        _ => ".".to_string(),
+    }
+}
+
+fn histogram_bin(chunks: usize) -> (usize, usize) {
+    if chunks == 0 {
+        return (0, 0);
+    }
+    let lo = 1usize << (usize::BITS - 1 - chunks.leading_zeros());
+    (lo, lo * 2 - 1)
+}
+
+impl IngestStats {
+    /// Compute statistics for `manifest` against `engram`.
+    pub fn compute(engram: &Engram, manifest: &Manifest) -> Self {
+        let mut total = BucketBuilder::default();
+        let mut by_ext: BTreeMap<String, BucketBuilder> = BTreeMap::new();
+        let mut by_dir: BTreeMap<String, BucketBuilder> = BTreeMap::new();
+        let mut histogram: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+
+        for file in &manifest.files {
+            let chunks: Vec<(ChunkKey, u64, u64)> = file
+                .chunks
+                .iter()
+                .enumerate()
+                .map(|(idx, &chunk_id)| {
+                    let len = EmbrFS::chunk_len(file, idx, manifest.chunk_size());
+                    let key = match engram.corrections.get(chunk_id as u64) {
+                        Some(c) => ChunkKey::Content(c.hash, len),
+                        None => ChunkKey::Id(chunk_id),
+                    };
+                    let encoded = engram
+                        .codebook
+                        .get(&chunk_id)
+                        .map(|v| engram.estimated_chunk_bytes(chunk_id, v))
+                        .unwrap_or(0);
+                    (key, len as u64, encoded)
+                })
+                .collect();
+
+            total.add_file(file, &chunks);
+            by_ext.entry(extension_of(&file.path)).or_default().add_file(file, &chunks);
+            by_dir.entry(directory_of(&file.path)).or_default().add_file(file, &chunks);
+            *histogram.entry(histogram_bin(file.chunks.len())).or_default() += 1;
+        }
+
+        IngestStats {
+            total: total.finish(),
+            by_extension: by_ext.into_iter().map(|(k, b)| (k, b.finish())).collect(),
+            by_directory: by_dir.into_iter().map(|(k, b)| (k, b.finish())).collect(),
+            chunk_histogram: histogram
+                .into_iter()
+                .map(|((min_chunks, max_chunks), files)| HistogramBin {
+                    min_chunks,
+                    max_chunks,
+                    files,
+                })
+                .collect(),
+            findings: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_grouping_helpers() {
+        assert_eq!(extension_of("a/b/c.TXT"), "txt");
+        assert_eq!(extension_of("a/.bashrc"), "");
+        assert_eq!(extension_of("Makefile"), "");
+        assert_eq!(directory_of("a/b/c.txt"), "a/b");
+        assert_eq!(directory_of("c.txt"), ".");
+    }
+
+    #[test]
+    fn histogram_bins_are_powers_of_two() {
+        assert_eq!(histogram_bin(0), (0, 0));
+        assert_eq!(histogram_bin(1), (1, 1));
+        assert_eq!(histogram_bin(3), (2, 3));
+        assert_eq!(histogram_bin(4), (4, 7));
+        assert_eq!(histogram_bin(1000), (512, 1023));
+    }
+}
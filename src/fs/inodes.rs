@@ -0,0 +1,101 @@
+//! Inode numbers that stay the same from one mount to the next.
+//!
+//! A mount numbers the files and directories it shows. Numbered in the order
+//! they are met, a path can get another number after an unrelated file is
+//! added, which breaks anything that remembers inode identity: an NFS
+//! re-export hands out stale file handles, and backup tools see every file
+//! as replaced. The manifest therefore carries an [`InodeTable`]: each path
+//! is numbered once, when ingest first writes it, and every mount of that
+//! manifest uses the recorded number.
+//!
+//! Numbers are never reused. A removed path keeps its entry, so the same
+//! path gets its old number back if it reappears and no other path ever
+//! gets it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// First number handed out; inode 1 is the root directory.
+pub const FIRST_INODE: u64 = 2;
+
+/// Inode numbers of files and directories by logical path; see the
+/// [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InodeTable {
+    pub(crate) next: u64,
+    pub(crate) paths: BTreeMap<String, u64>,
+}
+
+impl InodeTable {
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Number recorded for `path`. Leading and trailing slashes are ignored.
+    pub fn get(&self, path: &str) -> Option<u64> {
+        self.paths.get(path.trim_matches('/')).copied()
+    }
+
+    /// Number `path` and then any of its parent directories that have none
+    /// yet, outermost first (the order a mount creates them in), and return
+    /// the number of `path`.
+    pub fn assign(&mut self, path: &str) -> u64 {
+        let path = path.trim_matches('/');
+        if let Some(&ino) = self.paths.get(path) {
+            return ino;
+        }
+        for (end, _) in path.match_indices('/') {
+            self.number(&path[..end]);
+        }
+        self.number(path)
+    }
+
+    fn number(&mut self, path: &str) -> u64 {
+        if let Some(&ino) = self.paths.get(path) {
+            return ino;
+        }
+        let ino = self.next();
+        self.next = ino + 1;
+        self.paths.insert(path.to_string(), ino);
+        ino
+    }
+
+    /// The number the next new path will get; every recorded one is below
+    /// it, so a mount numbers paths missing from the table from here.
+    pub fn next(&self) -> u64 {
+        self.next.max(FIRST_INODE)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> + '_ {
+        self.paths.iter().map(|(path, &ino)| (path.as_str(), ino))
+    }
+
+    /// Table for the merge of manifests numbered by `a` and `b`, the same
+    /// whichever is given first: paths both number alike keep their number,
+    /// and the others are renumbered in path order above both tables.
+    pub fn merge(a: &InodeTable, b: &InodeTable) -> InodeTable {
+        let mut merged = InodeTable {
+            next: a.next().max(b.next()),
+            paths: a
+                .paths
+                .iter()
+                .filter(|&(path, ino)| b.paths.get(path) == Some(ino))
+                .map(|(path, &ino)| (path.clone(), ino))
+                .collect(),
+        };
+        let rest: BTreeSet<&String> = a.paths.keys().chain(b.paths.keys()).collect();
+        for path in rest {
+            merged.number(path);
+        }
+        merged
+    }
+
+    /// Drop the paths `keep` rejects. The numbers stay used.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.paths.retain(|path, _| keep(path));
+    }
+}
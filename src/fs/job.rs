@@ -0,0 +1,134 @@
+//! Cancellation and I/O throttling for long-running operations.
+//!
+//! Ingest ([`EmbrFS::control`](crate::EmbrFS::control)), extraction
+//! ([`EmbrFS::extract_with_control`](crate::EmbrFS::extract_with_control)),
+//! verification ([`EmbrFS::verify_with_control`](crate::EmbrFS::verify_with_control))
+//! and shared codebook collection
+//! ([`gc_with_control`](crate::shared_codebook::gc_with_control)) take a
+//! [`JobControl`]. Its [`CancellationToken`] is checked between files, and
+//! while a file is ingested, between chunks; a cancelled operation stops
+//! there with [`EmbrError::Cancelled`] reporting what it had finished. An
+//! optional rate limit caps the bytes per second the operation reads or
+//! writes, so heavy background jobs can share a host.
+
+use crate::error::{EmbrError, Result};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A flag that asks an operation to stop. Clones share the flag, so one can
+/// be handed to another thread and cancelled from there.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// How far an operation got before it was cancelled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct JobProgress {
+    /// Files (or, for codebook collection, engrams) fully processed.
+    pub files: usize,
+    /// Bytes of those files read or written.
+    pub bytes: u64,
+}
+
+impl std::fmt::Display for JobProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cancelled after {} file(s), {} bytes", self.files, self.bytes)
+    }
+}
+
+/// Caps throughput at a number of bytes per second, averaged over the
+/// operation: each call waits out the time the bytes before it were due
+/// to take. Shared by clones of the [`JobControl`] holding it.
+#[derive(Debug)]
+struct RateLimiter {
+    bytes_per_sec: u64,
+    next_free: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn consume(&self, bytes: u64) {
+        let now = Instant::now();
+        let start = {
+            let mut next_free = self.next_free.lock().unwrap_or_else(|e| e.into_inner());
+            let start = next_free.map_or(now, |t| t.max(now));
+            *next_free = Some(start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64));
+            start
+        };
+        let wait = start.saturating_duration_since(now);
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// Cancellation and throttling for one long-running operation. The
+/// default never cancels and does not throttle.
+#[derive(Clone, Debug, Default)]
+pub struct JobControl {
+    cancel: CancellationToken,
+    rate: Option<Arc<RateLimiter>>,
+}
+
+impl JobControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop when `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Read or write at most `bytes_per_sec` bytes per second; 0 removes
+    /// the limit.
+    pub fn with_rate_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.rate = (bytes_per_sec > 0).then(|| {
+            Arc::new(RateLimiter {
+                bytes_per_sec,
+                next_free: Mutex::new(None),
+            })
+        });
+        self
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Fail with [`EmbrError::Cancelled`] carrying `progress` if cancelled.
+    pub(crate) fn checkpoint(&self, progress: JobProgress) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(EmbrError::Cancelled(progress));
+        }
+        Ok(())
+    }
+
+    /// Account for `bytes` of I/O, sleeping as the rate limit requires.
+    pub(crate) fn throttle(&self, bytes: u64) {
+        if let Some(rate) = &self.rate {
+            rate.consume(bytes);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Browsing manifest contents without extracting: filtered listings and a
+//! directory tree, as used by `embeddenator ls` and `embeddenator tree`.
+//!
+//! Filters are glob patterns (`*`, `?`, `[...]`, `**`). As in `.gitignore`,
+//! a pattern without a `/` matches the file name at any depth (`*.rs`),
+//! while a pattern with one matches the whole logical path (`src/**/*.rs`).
+
+use crate::embrfs::{FileEntry, Manifest};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Glob patterns selecting manifest paths; an empty filter matches all.
+#[derive(Clone, Debug, Default)]
+pub struct PathFilter {
+    patterns: Vec<(Pattern, bool)>,
+}
+
+impl PathFilter {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> io::Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                let p = p.as_ref().trim_start_matches('/');
+                let pattern = Pattern::new(p).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid pattern {p:?}: {e}"),
+                    )
+                })?;
+                Ok((pattern, p.contains('/')))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(PathFilter { patterns })
+    }
+
+    /// Whether `path` matches any pattern.
+    pub fn matches(&self, path: &str) -> bool {
+        if self.patterns.is_empty() {
+            return true;
+        }
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.patterns.iter().any(|(pattern, whole_path)| {
+            pattern.matches_with(if *whole_path { path } else { name }, MATCH_OPTIONS)
+        })
+    }
+}
+
+/// One manifest entry as shown by `ls`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListEntry {
+    pub path: String,
+    pub size: usize,
+    pub chunks: usize,
+    pub is_text: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+}
+
+impl From<&FileEntry> for ListEntry {
+    fn from(file: &FileEntry) -> Self {
+        ListEntry {
+            path: file.path.clone(),
+            size: file.size,
+            chunks: file.chunks.len(),
+            is_text: file.is_text,
+            blake3: file.blake3.clone(),
+        }
+    }
+}
+
+/// Entries of `manifest` matching `filter`, sorted by path.
+pub fn list(manifest: &Manifest, filter: &PathFilter) -> Vec<ListEntry> {
+    let mut entries: Vec<ListEntry> = manifest
+        .files
+        .iter()
+        .filter(|f| filter.matches(&f.path))
+        .map(ListEntry::from)
+        .collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// A directory or file in the tree built by [`tree`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeNode {
+    pub name: String,
+    /// Total bytes of the files at or below this node.
+    pub size: u64,
+    /// Files at or below this node.
+    pub files: usize,
+    /// Chunks of the files at or below this node.
+    pub chunks: usize,
+    /// Only set for files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_text: Option<bool>,
+    /// Directories first, then files, each by name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    pub fn is_file(&self) -> bool {
+        self.is_text.is_some()
+    }
+
+    /// Render as indented lines, `tree(1)` style, down to `max_depth`
+    /// levels below this node (`None` for all). Directories cut off by the
+    /// depth limit still show their totals.
+    pub fn render(&self, max_depth: Option<usize>) -> String {
+        let mut out = format!(
+            "{} ({} files, {} bytes)\n",
+            self.name, self.files, self.size
+        );
+        self.render_children("", 1, max_depth, &mut out);
+        out
+    }
+
+    fn render_children(
+        &self,
+        prefix: &str,
+        depth: usize,
+        max_depth: Option<usize>,
+        out: &mut String,
+    ) {
+        if max_depth.is_some_and(|max| depth > max) {
+            return;
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            let branch = if last { "└── " } else { "├── " };
+            if child.is_file() {
+                out.push_str(&format!(
+                    "{prefix}{branch}{} ({} bytes)\n",
+                    child.name, child.size
+                ));
+            } else {
+                out.push_str(&format!(
+                    "{prefix}{branch}{}/ ({} files, {} bytes)\n",
+                    child.name, child.files, child.size
+                ));
+                let nested = format!("{prefix}{}", if last { "    " } else { "│   " });
+                child.render_children(&nested, depth + 1, max_depth, out);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct DirBuilder {
+    dirs: BTreeMap<String, DirBuilder>,
+    files: BTreeMap<String, (u64, usize, bool)>,
+}
+
+impl DirBuilder {
+    fn finish(self, name: String) -> TreeNode {
+        let mut node = TreeNode {
+            name,
+            ..Default::default()
+        };
+        for (name, dir) in self.dirs {
+            node.children.push(dir.finish(name));
+        }
+        for (name, (size, chunks, is_text)) in self.files {
+            node.children.push(TreeNode {
+                name,
+                size,
+                files: 1,
+                chunks,
+                is_text: Some(is_text),
+                children: Vec::new(),
+            });
+        }
+        for child in &node.children {
+            node.size += child.size;
+            node.files += child.files;
+            node.chunks += child.chunks;
+        }
+        node
+    }
+}
+
+/// Directory tree of the entries of `manifest` matching `filter`, rooted
+/// at a node named `.`. Directories with no matching files are omitted.
+pub fn tree(manifest: &Manifest, filter: &PathFilter) -> TreeNode {
+    let mut root = DirBuilder::default();
+    for file in manifest.files.iter().filter(|f| filter.matches(&f.path)) {
+        let mut parts: Vec<&str> = file.path.split('/').filter(|p| !p.is_empty()).collect();
+        let Some(name) = parts.pop() else {
+            continue;
+        };
+        let dir = parts.into_iter().fold(&mut root, |dir, part| {
+            dir.dirs.entry(part.to_string()).or_default()
+        });
+        dir.files.insert(
+            name.to_string(),
+            (file.size as u64, file.chunks.len(), file.is_text),
+        );
+    }
+    root.finish(".".to_string())
+}
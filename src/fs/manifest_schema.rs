@@ -0,0 +1,272 @@
+//! Versioned manifest JSON and migrations between versions.
+//!
+//! Manifests are written with a top-level `schema_version`, so a consumer
+//! can tell which fields to expect instead of finding out from a parse
+//! error. Manifests written before the field existed are dated by the fields
+//! they carry. [`upgrade`] brings manifest JSON of any earlier version up to
+//! [`MANIFEST_SCHEMA_VERSION`] one step at a time, and every manifest load
+//! ([`crate::EmbrFS::load_manifest`] and friends) goes through it, so all
+//! prior versions keep reading. A manifest newer than this build is refused
+//! rather than read with its new fields silently dropped.
+//!
+//! | Version | Adds |
+//! |---------|------|
+//! | 1 | `files` (`path`, `is_text`, `size`, `chunks`) and `total_chunks` |
+//! | 2 | per-file `blake3` checksums |
+//! | 3 | `namespaces` |
+//! | 4 | `retention` and per-file `ingested_at` |
+//! | 5 | `encoding` |
+//! | 6 | per-file `signature` |
+//! | 7 | per-file `unix` owner and mode |
+//! | 8 | `inodes` |
+//! | 9 | `schema_version` |
+//! | 10 | `path_normalization` |
+//! | 11 | `provenance` |
+//! | 12 | `namespace_keys` |
+//! | 13 | `encoding.chunk_size` |
+//!
+//! Every change so far only added optional fields, so each step's
+//! migration leaves the JSON as it is: a missing field already reads as
+//! "not recorded". In particular the step to 8 numbers no inodes; a mount
+//! numbers the files of an unnumbered manifest in the order it meets them,
+//! as it always has. A change that renames or restructures a field adds a
+//! step here that rewrites the old form.
+//!
+//! For tools that only know an older schema, [`downgrade`] removes the
+//! fields added since. It refuses when that would change how the manifest
+//! reads, which today means a non-default `encoding` (an older reader would
+//! decode the chunks with the default one), a recorded chunk size (an older
+//! reader would cut files into 4096-byte chunks) or `namespace_keys` (an
+//! older reader would not find the chunks kept under them).
+
+use crate::embrfs::{EncodingConfig, Manifest};
+use crate::error::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::io;
+
+/// Schema version this build writes.
+pub const MANIFEST_SCHEMA_VERSION: u32 = 13;
+
+/// One version of the schema and how to reach it from the one before.
+struct Step {
+    version: u32,
+    /// What the version adds, as `manifest upgrade` reports it.
+    adds: &'static str,
+    manifest_fields: &'static [&'static str],
+    file_fields: &'static [&'static str],
+    migrate: fn(&mut Map<String, Value>) -> Result<()>,
+}
+
+const STEPS: [Step; 12] = [
+    Step {
+        version: 2,
+        adds: "per-file checksums",
+        manifest_fields: &[],
+        file_fields: &["blake3"],
+        migrate: additive,
+    },
+    Step {
+        version: 3,
+        adds: "namespaces",
+        manifest_fields: &["namespaces"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 4,
+        adds: "retention policies and ingest times",
+        manifest_fields: &["retention"],
+        file_fields: &["ingested_at"],
+        migrate: additive,
+    },
+    Step {
+        version: 5,
+        adds: "recorded chunk encoding",
+        manifest_fields: &["encoding"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 6,
+        adds: "per-file signatures",
+        manifest_fields: &[],
+        file_fields: &["signature"],
+        migrate: additive,
+    },
+    Step {
+        version: 7,
+        adds: "Unix owners and modes",
+        manifest_fields: &[],
+        file_fields: &["unix"],
+        migrate: additive,
+    },
+    Step {
+        version: 8,
+        adds: "inode numbers",
+        manifest_fields: &["inodes"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 9,
+        adds: "schema version",
+        manifest_fields: &["schema_version"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 10,
+        adds: "path normalization policy",
+        manifest_fields: &["path_normalization"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 11,
+        adds: "ingest provenance",
+        manifest_fields: &["provenance"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 12,
+        adds: "namespace encryption keys",
+        manifest_fields: &["namespace_keys"],
+        file_fields: &[],
+        migrate: additive,
+    },
+    Step {
+        version: 13,
+        adds: "chunk size",
+        manifest_fields: &[],
+        file_fields: &[],
+        migrate: additive,
+    },
+];
+
+/// Migration of a version that only added optional fields.
+fn additive(_: &mut Map<String, Value>) -> Result<()> {
+    Ok(())
+}
+
+/// A manifest serialized with its `schema_version`, as manifests are
+/// written to disk.
+#[derive(Serialize)]
+pub struct Versioned<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    manifest: &'a Manifest,
+}
+
+impl<'a> Versioned<'a> {
+    pub fn new(manifest: &'a Manifest) -> Self {
+        Self {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            manifest,
+        }
+    }
+}
+
+/// Schema version of manifest JSON: its `schema_version`, or for a manifest
+/// written before that was recorded, the newest version whose fields it
+/// has.
+pub fn version_of(json: &Value) -> Result<u32> {
+    let manifest = object(json)?;
+    if let Some(version) = manifest.get("schema_version") {
+        return match version.as_u64().and_then(|v| u32::try_from(v).ok()) {
+            Some(v) if v > 0 => Ok(v),
+            _ => Err(invalid(format!("schema_version {} is not a version number", version))),
+        };
+    }
+    let files: Vec<&Map<String, Value>> = match manifest.get("files") {
+        Some(Value::Array(files)) => files.iter().filter_map(Value::as_object).collect(),
+        _ => Vec::new(),
+    };
+    let has = |step: &Step| {
+        step.manifest_fields.iter().any(|f| manifest.contains_key(*f))
+            || files.iter().any(|file| step.file_fields.iter().any(|f| file.contains_key(*f)))
+    };
+    Ok(STEPS.iter().rev().find(|step| has(step)).map_or(1, |step| step.version))
+}
+
+/// Migrate manifest JSON to [`MANIFEST_SCHEMA_VERSION`], returning what
+/// each applied step added, oldest first. Fails on JSON that is not a
+/// manifest object and on versions newer than this build.
+pub fn upgrade(json: &mut Value) -> Result<Vec<&'static str>> {
+    let from = version_of(json)?;
+    if from > MANIFEST_SCHEMA_VERSION {
+        return Err(invalid(format!(
+            "manifest schema version {} is newer than this build reads ({})",
+            from, MANIFEST_SCHEMA_VERSION
+        )));
+    }
+    let manifest = object_mut(json)?;
+    let mut applied = Vec::new();
+    for step in STEPS.iter().filter(|step| step.version > from) {
+        (step.migrate)(manifest)?;
+        applied.push(step.adds);
+    }
+    manifest.insert("schema_version".to_string(), MANIFEST_SCHEMA_VERSION.into());
+    Ok(applied)
+}
+
+/// Rewrite current manifest JSON as version `to`, dropping the fields added
+/// after it; see the [module docs](self).
+pub fn downgrade(json: &mut Value, to: u32) -> Result<()> {
+    if !(1..=MANIFEST_SCHEMA_VERSION).contains(&to) {
+        return Err(invalid(format!(
+            "no manifest schema version {}; versions run from 1 to {}",
+            to, MANIFEST_SCHEMA_VERSION
+        )));
+    }
+    upgrade(json)?;
+    let manifest = object_mut(json)?;
+    if to < 5 {
+        let default = serde_json::to_value(EncodingConfig::default())?;
+        if manifest.get("encoding").is_some_and(|e| !e.is_null() && *e != default) {
+            return Err(invalid(format!(
+                "the manifest records a non-default encoding, which schema version {} cannot express",
+                to
+            )));
+        }
+    }
+    // Only a chunk size other than the default is written.
+    if let Some(size) = manifest.get("encoding").and_then(|e| e.get("chunk_size")).filter(|_| to < 13) {
+        return Err(invalid(format!(
+            "the manifest records a chunk size of {}, which schema version {} cannot express",
+            size, to
+        )));
+    }
+    if to < 12 && manifest.get("namespace_keys").is_some_and(|k| !k.is_null()) {
+        return Err(invalid(format!(
+            "the manifest keeps chunks under namespace keys, which schema version {} cannot express",
+            to
+        )));
+    }
+    for step in STEPS.iter().filter(|step| step.version > to) {
+        for field in step.manifest_fields {
+            manifest.remove(*field);
+        }
+        if let Some(Value::Array(files)) = manifest.get_mut("files") {
+            for file in files.iter_mut().filter_map(Value::as_object_mut) {
+                for field in step.file_fields {
+                    file.remove(*field);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn object(json: &Value) -> Result<&Map<String, Value>> {
+    json.as_object().ok_or_else(|| invalid("a manifest is a JSON object".to_string()))
+}
+
+fn object_mut(json: &mut Value) -> Result<&mut Map<String, Value>> {
+    json.as_object_mut().ok_or_else(|| invalid("a manifest is a JSON object".to_string()))
+}
+
+fn invalid(msg: String) -> crate::error::EmbrError {
+    io::Error::new(io::ErrorKind::InvalidData, msg).into()
+}
@@ -0,0 +1,261 @@
+//! Merging two copies of an engram that were written to independently.
+//!
+//! Incremental ingest numbers new chunks from `manifest.total_chunks`, so
+//! two copies of one engram that each took new files hand out the same ids
+//! to different chunks. [`EmbrFS::merge_concurrent`] joins such copies with
+//! set semantics that don't depend on which copy is `a` and which is `b`:
+//!
+//! - Chunks: the union of both codebooks. An id holding the same record on
+//!   both sides is kept once. An id holding different records keeps the one
+//!   with the lower record digest; the other is renumbered past every id in
+//!   use, in digest order.
+//! - Files: the union of both manifests by path (add wins, so a removal on
+//!   one side is undone by the other). Where the copies disagree on a path's
+//!   content, the entry with the greater blake3 digest is kept and the rest
+//!   are returned as [`MergeConflict`]s; their chunks stay in the engram, so
+//!   a conflict can be resolved later by re-adding an entry from the report.
+//! - Namespaces: the union, keeping the lesser source root where the
+//!   copies differ. A retention policy set on either copy is kept; of two
+//!   different ones, the lesser in JSON order.
+//! - Role schemas: the union of both if their seeds agree, otherwise the
+//!   one with the lesser seed (see [`RoleSchema::merge`]).
+//! - Encoding: both copies must have been encoded the same way (see
+//!   [`crate::EncodingConfig`]); copies that were not cannot be merged.
+//! - Files are ordered by path and the root is rebuilt in id order, so
+//!   `merge_concurrent(a, b)` and `merge_concurrent(b, a)` are identical.
+
+use crate::correction::{ChunkCorrection, CorrectionStore, CorrectionTotals};
+use crate::embrfs::{EmbrFS, Engram, FileEntry, Manifest};
+use crate::inodes::InodeTable;
+use crate::role_schema::RoleSchema;
+use crate::error::Result;
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+/// A path whose content the merged copies disagreed on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub path: String,
+    /// Entry in the merged manifest.
+    pub kept: FileEntry,
+    /// Other versions, with chunk ids valid in the merged engram.
+    pub discarded: Vec<FileEntry>,
+}
+
+/// What [`EmbrFS::merge_concurrent`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// Ids holding the same record in both copies.
+    pub shared_chunks: usize,
+    /// Chunks given a new id because the other copy used theirs.
+    pub renumbered_chunks: usize,
+    /// Files in the merged manifest.
+    pub files: usize,
+    /// Paths whose content differed, by path.
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl EmbrFS {
+    /// Merge two independently updated copies of one engram; see
+    /// [`crate::merge`] for the rules. Only the engrams and manifests are
+    /// merged: the result has no chunk index or semantic signatures.
+    pub fn merge_concurrent(a: &EmbrFS, b: &EmbrFS) -> Result<(EmbrFS, MergeReport)> {
+        if a.manifest.encoding() != b.manifest.encoding() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the copies were encoded with different settings",
+            )
+            .into());
+        }
+        let mut report = MergeReport::default();
+        let digests_a = record_digests(&a.engram)?;
+        let digests_b = record_digests(&b.engram)?;
+        let mut ids: Vec<usize> = digests_a.keys().chain(digests_b.keys()).copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        // Records that lose their id, as (digest, old id, from b).
+        let mut displaced: Vec<([u8; 32], usize, bool)> = Vec::new();
+        for &id in &ids {
+            match (digests_a.get(&id), digests_b.get(&id)) {
+                (Some(x), Some(y)) if x == y => report.shared_chunks += 1,
+                (Some(x), Some(y)) if x < y => displaced.push((*y, id, true)),
+                (Some(x), Some(_)) => displaced.push((*x, id, false)),
+                _ => {}
+            }
+        }
+        displaced.sort_unstable_by_key(|&(digest, id, _)| (digest, id));
+
+        let mut next_id = [&a.manifest, &b.manifest]
+            .iter()
+            .map(|m| m.total_chunks)
+            .chain(ids.last().map(|&id| id + 1))
+            .max()
+            .unwrap_or(0);
+        let mut remap_a: HashMap<usize, usize> = HashMap::new();
+        let mut remap_b: HashMap<usize, usize> = HashMap::new();
+        for &(_, id, from_b) in &displaced {
+            if from_b { &mut remap_b } else { &mut remap_a }.insert(id, next_id);
+            next_id += 1;
+        }
+        report.renumbered_chunks = displaced.len();
+
+        let mut codebook: HashMap<usize, SparseVec> = HashMap::new();
+        let mut corrections: HashMap<u64, ChunkCorrection> = HashMap::new();
+        for (fs, remap) in [(a, &remap_a), (b, &remap_b)] {
+            for (&id, vec) in &fs.engram.codebook {
+                codebook.insert(*remap.get(&id).unwrap_or(&id), vec.clone());
+            }
+            for (id, correction) in fs.engram.corrections.iter() {
+                let new_id = *remap.get(&(id as usize)).unwrap_or(&(id as usize)) as u64;
+                let mut correction = correction.clone();
+                correction.chunk_id = new_id;
+                corrections.insert(new_id, correction);
+            }
+        }
+
+        let mut by_path: BTreeMap<String, Vec<FileEntry>> = BTreeMap::new();
+        for (fs, remap) in [(a, &remap_a), (b, &remap_b)] {
+            for file in &fs.manifest.files {
+                let mut file = file.clone();
+                for id in &mut file.chunks {
+                    *id = *remap.get(id).unwrap_or(id);
+                }
+                by_path.entry(file.path.clone()).or_default().push(file);
+            }
+        }
+        let mut files = Vec::with_capacity(by_path.len());
+        let mut chunk_lens: HashMap<usize, usize> = HashMap::new();
+        let chunk_size = a.manifest.chunk_size();
+        for (path, mut versions) in by_path {
+            for file in &versions {
+                for (idx, &id) in file.chunks.iter().enumerate() {
+                    chunk_lens.insert(id, EmbrFS::chunk_len(file, idx, chunk_size));
+                }
+            }
+            // Greatest content first, then the lowest chunk ids among copies
+            // of the same content.
+            versions.sort_by(|x, y| {
+                (&y.blake3, y.size, y.is_text)
+                    .cmp(&(&x.blake3, x.size, x.is_text))
+                    .then_with(|| x.chunks.cmp(&y.chunks))
+            });
+            let kept = versions.remove(0);
+            let mut discarded: Vec<FileEntry> = versions
+                .into_iter()
+                .filter(|v| !same_content(v, &kept))
+                .collect();
+            discarded.dedup();
+            if !discarded.is_empty() {
+                report.conflicts.push(MergeConflict {
+                    path,
+                    kept: kept.clone(),
+                    discarded,
+                });
+            }
+            files.push(kept);
+        }
+        report.files = files.len();
+
+        let mut namespaces = a.manifest.namespaces.clone();
+        for (name, root) in &b.manifest.namespaces {
+            let entry = namespaces
+                .entry(name.clone())
+                .or_insert_with(|| root.clone());
+            if root < entry {
+                *entry = root.clone();
+            }
+        }
+
+        let totals = totals_for(&corrections, &chunk_lens);
+        let mut merged = EmbrFS::new();
+        merged.engram = Engram {
+            root: SparseVec::new(),
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, totals),
+            root_counts: None,
+            // Depths are per vector dimension, not per chunk, so they survive
+            // renumbering; the deeper of the two decodes both.
+            precision: match (&a.engram.precision, &b.engram.precision) {
+                (Some(x), Some(y)) => {
+                    let mut depths = x.clone();
+                    depths.merge(y);
+                    Some(depths)
+                }
+                (x, y) => x.clone().or_else(|| y.clone()),
+            },
+            // The inputs may differ in basis; the merged chunks are saved raw.
+            differential: None,
+            schema: match (&a.engram.schema, &b.engram.schema) {
+                (Some(x), Some(y)) => Some(RoleSchema::merge(x, y)),
+                (x, y) => x.clone().or_else(|| y.clone()),
+            },
+        };
+        let retention = [&a.manifest.retention, &b.manifest.retention]
+            .into_iter()
+            .flatten()
+            .min_by_key(|policy| serde_json::to_string(policy).unwrap_or_default())
+            .cloned();
+        let mut inodes = InodeTable::merge(&a.manifest.inodes, &b.manifest.inodes);
+        for file in &files {
+            inodes.assign(&file.path);
+        }
+        merged.manifest = Manifest {
+            files,
+            total_chunks: next_id,
+            namespaces,
+            retention,
+            encoding: a.manifest.encoding.clone().or_else(|| b.manifest.encoding.clone()),
+            inodes,
+            path_normalization: a.manifest.path_normalization,
+            // Neither ingest produced the merged engram.
+            provenance: None,
+            // Chunks are renumbered, so neither side's key groups apply.
+            namespace_keys: None,
+        };
+        merged.rebuild_root();
+        Ok((merged, report))
+    }
+}
+
+/// blake3 of each chunk record, by id.
+fn record_digests(engram: &Engram) -> Result<HashMap<usize, [u8; 32]>> {
+    engram
+        .chunk_record_ids()
+        .into_iter()
+        .map(|id| {
+            let record = engram.encode_chunk_record(id)?;
+            Ok((id as usize, *blake3::hash(&record).as_bytes()))
+        })
+        .collect()
+}
+
+/// Whether two entries for a path hold the same bytes. Entries without a
+/// digest only match if they are identical.
+fn same_content(x: &FileEntry, y: &FileEntry) -> bool {
+    match (&x.blake3, &y.blake3) {
+        (Some(p), Some(q)) => p == q && x.size == y.size,
+        _ => x == y,
+    }
+}
+
+/// Correction totals recounted from the merged records; chunk sizes come
+/// from the files referencing them.
+fn totals_for(
+    corrections: &HashMap<u64, ChunkCorrection>,
+    chunk_lens: &HashMap<usize, usize>,
+) -> CorrectionTotals {
+    let mut totals = CorrectionTotals::default();
+    for (&id, correction) in corrections {
+        totals.total_original_bytes += chunk_lens.get(&(id as usize)).copied().unwrap_or(0) as u64;
+        if correction.needs_correction() {
+            totals.total_correction_bytes += correction.storage_size() as u64;
+            totals.corrected_chunks += 1;
+        } else {
+            totals.perfect_chunks += 1;
+        }
+    }
+    totals
+}
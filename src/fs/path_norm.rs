@@ -0,0 +1,127 @@
+//! Unicode and case normalization of logical paths.
+//!
+//! The same name can reach an engram in different forms: macOS hands out
+//! decomposed (NFD) names where Linux tools usually produce composed (NFC)
+//! ones, and `README.md` and `readme.md` are two files on Linux but one on
+//! a default macOS or Windows volume. A manifest can record a
+//! [`PathNormalization`] that ingest applies to every logical path before
+//! encoding it, so an engram built from several platforms names each file
+//! one way. The default leaves paths as they are.
+//!
+//! Extraction checks the other direction: [`Folding::probe`] finds out
+//! whether the output directory's filesystem ignores case or normalization,
+//! and [`collisions`] lists the manifest paths it would store as one file.
+//! The last of each group is what ends up on disk; extraction reports the
+//! groups rather than failing, since the bytes written are still right.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization};
+
+/// Unicode normalization form applied to logical paths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnicodeForm {
+    /// Keep paths as the source spelled them.
+    #[default]
+    AsIs,
+    /// Canonical composition, as Linux and Windows tools usually write.
+    Nfc,
+    /// Canonical decomposition, as HFS+ stores names.
+    Nfd,
+}
+
+/// Normalization ingest applies to logical paths; recorded in the manifest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathNormalization {
+    #[serde(default)]
+    pub unicode: UnicodeForm,
+    /// Lowercase paths, so names that differ only in case become one.
+    #[serde(default)]
+    pub lowercase: bool,
+}
+
+impl PathNormalization {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `path` in normal form; returned as is when already normal.
+    pub fn apply(&self, path: String) -> String {
+        let path = match self.unicode {
+            UnicodeForm::Nfc if !is_nfc(&path) => path.nfc().collect(),
+            UnicodeForm::Nfd if !is_nfd(&path) => path.nfd().collect(),
+            _ => path,
+        };
+        if self.lowercase && path.chars().any(char::is_uppercase) {
+            path.to_lowercase()
+        } else {
+            path
+        }
+    }
+}
+
+/// Which differences between names a filesystem ignores.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Folding {
+    pub case_insensitive: bool,
+    pub normalization_insensitive: bool,
+}
+
+impl Folding {
+    /// Folding under which a path collides wherever it might be extracted.
+    pub const PORTABLE: Folding = Folding {
+        case_insensitive: true,
+        normalization_insensitive: true,
+    };
+
+    /// Folding of the filesystem holding `dir`, found by creating a file in
+    /// it and looking it up again under other spellings.
+    pub fn probe(dir: &Path) -> io::Result<Folding> {
+        let probe = tempfile::Builder::new().prefix(".embeddenator-probe-\u{e9}").tempfile_in(dir)?;
+        let name = probe
+            .path()
+            .file_name()
+            .and_then(|n| n.to_str())
+            .expect("the probe name is UTF-8");
+        Ok(Folding {
+            case_insensitive: dir.join(name.to_uppercase()).exists(),
+            normalization_insensitive: dir.join(name.nfd().collect::<String>()).exists(),
+        })
+    }
+
+    /// The name a filesystem with this folding stores `path` under.
+    pub fn fold(&self, path: &str) -> String {
+        let path: String = if self.normalization_insensitive {
+            path.nfc().collect()
+        } else {
+            path.to_string()
+        };
+        if self.case_insensitive {
+            path.to_lowercase()
+        } else {
+            path
+        }
+    }
+}
+
+/// Manifest paths that one filesystem stores as a single file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathCollision {
+    /// The colliding paths, in manifest order; the last is written last.
+    pub paths: Vec<String>,
+}
+
+/// Groups of `paths` that collide under `folding`, ordered by first
+/// appearance. Repeated paths collide under any folding.
+pub fn collisions<'a>(paths: impl IntoIterator<Item = &'a str>, folding: Folding) -> Vec<PathCollision> {
+    let mut groups: BTreeMap<String, (usize, Vec<String>)> = BTreeMap::new();
+    for (i, path) in paths.into_iter().enumerate() {
+        groups.entry(folding.fold(path)).or_insert_with(|| (i, Vec::new())).1.push(path.to_string());
+    }
+    let mut found: Vec<(usize, Vec<String>)> = groups.into_values().filter(|(_, paths)| paths.len() > 1).collect();
+    found.sort_unstable_by_key(|(first, _)| *first);
+    found.into_iter().map(|(_, paths)| PathCollision { paths }).collect()
+}
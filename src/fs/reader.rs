@@ -0,0 +1,407 @@
+//! Shared read-only access to a loaded engram.
+//!
+//! [`EmbrFS`] bundles an engram with ingest state and is mutated through
+//! `&mut self`, so a server that keeps one behind a lock serializes readers
+//! behind every writer and rebuilds per-call state such as the codebook
+//! index. An [`EngramReader`] owns a finished engram and manifest instead.
+//! Every method takes `&self`, and the type is `Send + Sync`, so one
+//! `Arc<EngramReader>` serves any number of threads. The only interior
+//! mutation is in the two caches:
+//!
+//! - decoded chunks go through a [`ChunkCache`], whose mutex is held only to
+//!   look up or insert, never while decoding;
+//! - the codebook's inverted index is built once, by the first query, in a
+//!   `OnceLock`;
+//! - query answers go through a [`QueryCache`], so dashboards repeating the
+//!   same searches are answered without touching the index.
+//!
+//! Applications about to process a file in order can [`EngramReader::prefetch`]
+//! it first, so its chunks are decoded before they are asked for; a reader
+//! given the engram's [sub-engrams](EngramReader::with_sub_engrams) also
+//! hints the store to fetch the ones holding those chunks.
+//!
+//! Reads, extractions and query answers are counted in the reader's
+//! [`AccessStats`], which its chunk cache also evicts by; a reader built
+//! [from an `EmbrFS`](EngramReader::from_embrfs) records into that
+//! `EmbrFS`'s [`access_stats`](EmbrFS::access_stats).
+//!
+//! [`crate::EngramFS::from_reader`] mounts a reader, sharing its engram,
+//! chunk cache and access stats, and the HTTP API serves one. A reader is also a
+//! [`VirtualFs`](crate::vfs::VirtualFs): the inode tables behind it are
+//! built, like the index, on first use.
+
+use crate::access_stats::{AccessKind, AccessStats};
+use crate::codebook::ChunkCache;
+use crate::embrfs::{
+    ChecksumMismatch, EmbrFS, Engram, FileEntry, HierarchicalManifest, Manifest, SubEngramStore, VerifyReport,
+};
+use crate::error::{EmbrError, Result};
+use crate::fuse_shim::{EngramFS, Ino, DEFAULT_CACHE_BYTES, DEFAULT_CACHE_ENTRIES};
+use crate::retrieval::query_cache::{QueryCache, QueryKey};
+use crate::retrieval::{RerankedResult, TernaryInvertedIndex};
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+/// Thread-safe reader over one engram and manifest; see the
+/// [module docs](self).
+pub struct EngramReader {
+    engram: Arc<Engram>,
+    manifest: Manifest,
+    by_path: HashMap<String, usize>,
+    config: ReversibleVSAConfig,
+    cache: Arc<ChunkCache>,
+    access: Arc<AccessStats>,
+    index: OnceLock<TernaryInvertedIndex>,
+    queries: Arc<QueryCache>,
+    namespace: OnceLock<Namespace>,
+    sub_engrams: Option<SubEngramHints>,
+}
+
+/// Where [`EngramReader::prefetch`] sends sub-engram hints.
+struct SubEngramHints {
+    hierarchical: HierarchicalManifest,
+    store: Arc<dyn SubEngramStore + Send + Sync>,
+}
+
+/// What an [`EngramReader::prefetch`] warmed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrefetchReport {
+    /// Chunks of the range, now in the chunk cache.
+    pub chunks: usize,
+    /// Sub-engrams the store was asked to fetch.
+    pub sub_engrams: usize,
+}
+
+/// Read-only mount of a reader's engram, with the manifest entry behind
+/// each file inode.
+struct Namespace {
+    fs: EngramFS,
+    files: HashMap<Ino, usize>,
+}
+
+impl EngramReader {
+    /// Reader decoding with `config`, with a chunk cache of the same default
+    /// size as a mount's.
+    pub fn new(engram: Engram, manifest: Manifest, config: ReversibleVSAConfig) -> Self {
+        let by_path = manifest
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.path.clone(), i))
+            .collect();
+        let access = Arc::new(AccessStats::new());
+        Self {
+            engram: Arc::new(engram),
+            manifest,
+            by_path,
+            config,
+            cache: Arc::new(
+                ChunkCache::with_limits(DEFAULT_CACHE_ENTRIES, DEFAULT_CACHE_BYTES)
+                    .with_access_stats(Arc::clone(&access)),
+            ),
+            access,
+            index: OnceLock::new(),
+            queries: Arc::new(QueryCache::default()),
+            namespace: OnceLock::new(),
+            sub_engrams: None,
+        }
+    }
+
+    /// Reader over the engram and manifest of `fs`, recording accesses
+    /// into its [`EmbrFS::access_stats`]; its other state is dropped.
+    pub fn from_embrfs(fs: EmbrFS, config: ReversibleVSAConfig) -> Self {
+        let access = Arc::clone(fs.access_recorder());
+        Self::new(fs.engram, fs.manifest, config).with_access_stats(access)
+    }
+
+    /// Record accesses into `access`, e.g. one holding the counts of
+    /// earlier runs, and evict from a fresh default-sized chunk cache by
+    /// them. Call before [`EngramReader::with_chunk_cache`], which replaces
+    /// that cache.
+    pub fn with_access_stats(mut self, access: Arc<AccessStats>) -> Self {
+        self.cache = Arc::new(
+            ChunkCache::with_limits(DEFAULT_CACHE_ENTRIES, DEFAULT_CACHE_BYTES).with_access_stats(Arc::clone(&access)),
+        );
+        self.access = access;
+        self.namespace = OnceLock::new();
+        self
+    }
+
+    /// Decode chunks through `cache`, e.g. one shared with a mount of the
+    /// same engram.
+    pub fn with_chunk_cache(mut self, cache: Arc<ChunkCache>) -> Self {
+        self.cache = cache;
+        self.namespace = OnceLock::new();
+        self
+    }
+
+    /// Cache query answers in `cache` instead of the default one, e.g. to
+    /// size it differently or read its statistics.
+    pub fn with_query_cache(mut self, cache: Arc<QueryCache>) -> Self {
+        self.queries = cache;
+        self
+    }
+
+    /// Have [`EngramReader::prefetch`] also hint `store` to fetch the
+    /// sub-engrams of `hierarchical` holding the prefetched chunks, e.g. a
+    /// [`crate::TieredSubEngramStore`] in front of remote storage, so
+    /// hierarchical queries over them don't wait on the fetch.
+    pub fn with_sub_engrams(
+        mut self,
+        hierarchical: HierarchicalManifest,
+        store: Arc<dyn SubEngramStore + Send + Sync>,
+    ) -> Self {
+        self.sub_engrams = Some(SubEngramHints { hierarchical, store });
+        self
+    }
+
+    /// Decode with `config` from now on, dropping chunks and query answers
+    /// cached under the old one.
+    pub fn set_config(&mut self, config: ReversibleVSAConfig) {
+        self.config = config;
+        self.cache.clear();
+        self.queries.invalidate();
+        self.namespace = OnceLock::new();
+    }
+
+    pub fn engram(&self) -> &Engram {
+        &self.engram
+    }
+
+    pub(crate) fn shared_engram(&self) -> &Arc<Engram> {
+        &self.engram
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn config(&self) -> &ReversibleVSAConfig {
+        &self.config
+    }
+
+    pub fn chunk_cache(&self) -> &Arc<ChunkCache> {
+        &self.cache
+    }
+
+    pub fn query_cache(&self) -> &Arc<QueryCache> {
+        &self.queries
+    }
+
+    /// The store given to [`EngramReader::with_sub_engrams`], if any.
+    pub fn sub_engram_store(&self) -> Option<&Arc<dyn SubEngramStore + Send + Sync>> {
+        self.sub_engrams.as_ref().map(|hints| &hints.store)
+    }
+
+    /// Accesses counted so far; see [`crate::access_stats`].
+    pub fn access_stats(&self) -> &Arc<AccessStats> {
+        &self.access
+    }
+
+    /// Manifest entry for `path`.
+    pub fn file(&self, path: &str) -> Option<&FileEntry> {
+        self.by_path.get(path).map(|&i| &self.manifest.files[i])
+    }
+
+    /// Manifest entry behind file inode `ino` of [`EngramReader::namespace`].
+    pub fn file_at(&self, ino: Ino) -> Option<&FileEntry> {
+        self.namespace_tables().files.get(&ino).map(|&i| &self.manifest.files[i])
+    }
+
+    /// Read-only mount of this reader, sharing its engram and chunk cache;
+    /// built on first use.
+    pub fn namespace(&self) -> &EngramFS {
+        &self.namespace_tables().fs
+    }
+
+    fn namespace_tables(&self) -> &Namespace {
+        self.namespace.get_or_init(|| {
+            let fs = EngramFS::from_reader(self, true);
+            let files = self
+                .manifest
+                .files
+                .iter()
+                .enumerate()
+                .filter_map(|(i, f)| Some((fs.lookup_path(&f.path)?, i)))
+                .collect();
+            Namespace { fs, files }
+        })
+    }
+
+    /// The whole of `path`, checked against its recorded checksum.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let entry = self.file_or_not_found(path)?;
+        let mut out = Vec::with_capacity(entry.size);
+        let mismatch = self.stream_file(entry, |data| {
+            out.extend_from_slice(data);
+            Ok(())
+        })?;
+        match mismatch {
+            Some(mismatch) => Err(EmbrError::ManifestMismatch(vec![mismatch])),
+            None => Ok(out),
+        }
+    }
+
+    /// Bytes `start..end` of `path`, decoding only the chunks they overlap.
+    /// `end` is clamped to the file size; each chunk is checked against its
+    /// correction.
+    pub fn read_range(&self, path: &str, start: usize, end: usize) -> Result<Vec<u8>> {
+        let entry = self.file_or_not_found(path)?;
+        let mut out = Vec::with_capacity(end.min(entry.size).saturating_sub(start));
+        self.stream_range(entry, start, end, |data| {
+            out.extend_from_slice(data);
+            Ok(())
+        })?;
+        Ok(out)
+    }
+
+    /// Decode the chunks of bytes `range` of `path` into the chunk cache
+    /// ahead of reading them. `range` is clamped to the file. Blocks until
+    /// they are decoded, so call it from another thread to overlap with
+    /// processing; chunks already cached are not decoded again.
+    pub fn prefetch(&self, path: &str, range: Range<usize>) -> Result<PrefetchReport> {
+        let entry = self.file_or_not_found(path)?;
+        let (start, end) = (range.start.min(entry.size), range.end.min(entry.size));
+        if start >= end {
+            return Ok(PrefetchReport::default());
+        }
+        let chunk_size = self.manifest.chunk_size();
+        let chunk_ids = &entry.chunks[start / chunk_size..end.div_ceil(chunk_size).min(entry.chunks.len())];
+        let mut report = PrefetchReport {
+            chunks: chunk_ids.len(),
+            sub_engrams: 0,
+        };
+        if let Some(hints) = &self.sub_engrams {
+            let ids = hints.hierarchical.sub_engrams_holding(chunk_ids);
+            report.sub_engrams = ids.len();
+            hints.store.prefetch(&ids);
+        }
+        // Warming the cache is not an access of the file.
+        EmbrFS::reconstruct_range_cached(&self.engram, entry, &self.config, chunk_size, start, end, Some(&self.cache), |_| Ok(()))?;
+        Ok(report)
+    }
+
+    /// Feed `entry` to `sink` chunk by chunk and compare it with its
+    /// recorded checksum.
+    pub fn stream_file<F>(&self, entry: &FileEntry, sink: F) -> io::Result<Option<ChecksumMismatch>>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let started = Instant::now();
+        let mismatch = EmbrFS::reconstruct_file_cached(&self.engram, entry, &self.config, self.manifest.chunk_size(), Some(&self.cache), sink)?;
+        self.access.record_file(&entry.path, &entry.chunks, AccessKind::Read, started.elapsed());
+        Ok(mismatch)
+    }
+
+    /// Feed bytes `start..end` of `entry` to `sink`.
+    pub fn stream_range<F>(
+        &self,
+        entry: &FileEntry,
+        start: usize,
+        end: usize,
+        sink: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let started = Instant::now();
+        EmbrFS::reconstruct_range_cached(
+            &self.engram,
+            entry,
+            &self.config,
+            self.manifest.chunk_size(),
+            start,
+            end,
+            Some(&self.cache),
+            sink,
+        )?;
+        let end = end.min(entry.size);
+        if start < end {
+            let chunk_size = self.manifest.chunk_size();
+            let chunks = &entry.chunks[start / chunk_size..end.div_ceil(chunk_size).min(entry.chunks.len())];
+            self.access.record_file(&entry.path, chunks, AccessKind::Read, started.elapsed());
+        }
+        Ok(())
+    }
+
+    /// [`EmbrFS::extract`] of every file into `output_dir`.
+    pub fn extract<P: AsRef<Path>>(&self, output_dir: P, verbose: bool) -> Result<()> {
+        EmbrFS::extract_with_access(
+            &self.engram,
+            &self.manifest,
+            output_dir,
+            verbose,
+            &self.config,
+            Some(&self.cache),
+            &self.access,
+        )
+    }
+
+    /// [`EmbrFS::verify`] of every file.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        EmbrFS::verify_with_cache(&self.engram, &self.manifest, &self.config, &self.cache)
+    }
+
+    /// The codebook's inverted index, built on first use.
+    pub fn codebook_index(&self) -> &TernaryInvertedIndex {
+        self.index
+            .get_or_init(|| self.engram.build_codebook_index())
+    }
+
+    /// [`Engram::query_codebook`] through the shared index and query cache.
+    pub fn query_codebook(&self, query: &SparseVec, k: usize) -> Vec<RerankedResult> {
+        if k == 0 || self.engram.codebook.is_empty() {
+            return Vec::new();
+        }
+        let started = Instant::now();
+        let candidate_k = k.saturating_mul(10).max(50);
+        let results = self
+            .queries
+            .top_k_reranked(self.codebook_index(), query, &self.engram.codebook, candidate_k, k)
+            .to_vec();
+        let ids: Vec<usize> = results.iter().map(|r| r.id).collect();
+        self.access.record_chunks(&ids, AccessKind::Query, started.elapsed());
+        results
+    }
+
+    /// The `k` chunks most similar to `data`, best first, with the paths of
+    /// the files that reference each one; the search behind the servers'
+    /// query endpoints.
+    pub fn similar_chunks(&self, data: &[u8], k: usize) -> Vec<(usize, f64, Vec<String>)> {
+        let started = Instant::now();
+        let index = self.codebook_index();
+        let key = QueryKey::for_data(data).with_param(k as u64);
+        let hits = self
+            .queries
+            .chunks_with(index, key, || {
+                EmbrFS::similar_chunks_with_index(
+                    &self.engram,
+                    &self.manifest,
+                    index,
+                    data,
+                    k,
+                    &self.config,
+                    None,
+                )
+            })
+            .to_vec();
+        let ids: Vec<usize> = hits.iter().map(|(id, _, _)| *id).collect();
+        self.access.record_chunks(&ids, AccessKind::Query, started.elapsed());
+        hits
+    }
+
+    fn file_or_not_found(&self, path: &str) -> Result<&FileEntry> {
+        self.file(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file {:?} in engram", path),
+            )
+            .into()
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Reproducible ingest: identical input trees give byte-identical engrams.
+//!
+//! Plain ingest numbers chunks in the order files arrive, counting on from
+//! `manifest.total_chunks`, and stamps every file with the wall-clock time,
+//! so two runs over the same tree rarely agree byte for byte. An
+//! [`EmbrFS::reproducible`] instance instead:
+//!
+//! - deduplicates through a [`ChunkIndex`], so a chunk's id follows from its
+//!   content and path rather than from how often it was seen;
+//! - stamps files with a fixed [`IngestClock`] (for builds, the
+//!   `SOURCE_DATE_EPOCH` of the reproducible-builds convention);
+//! - and, through [`EmbrFS::canonicalize`] once ingest is done, orders the
+//!   manifest by path, renumbers chunks densely in that order and rebuilds
+//!   the root, which erases the order files were ingested in.
+//!
+//! Vector encoding is already a pure function of the chunk bytes and path,
+//! and engrams serialize their maps in key order, so nothing else varies.
+//! Encrypted output still differs between runs because every envelope draws
+//! a fresh nonce; attest the plaintext engram.
+
+use crate::correction::CorrectionStore;
+use crate::embrfs::{ChunkIndex, EmbrFS};
+use crate::inodes::InodeTable;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::env;
+use std::io;
+
+/// What ingest records as each file's [`FileEntry::ingested_at`].
+///
+/// [`FileEntry::ingested_at`]: crate::FileEntry::ingested_at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IngestClock {
+    /// The current time.
+    #[default]
+    Now,
+    /// The same Unix time for every file.
+    Fixed(u64),
+    /// No time at all; retention policies never expire such files.
+    Omit,
+}
+
+impl IngestClock {
+    /// `Fixed` at `SOURCE_DATE_EPOCH` when that is set, `Omit` otherwise.
+    pub fn from_env() -> io::Result<Self> {
+        match env::var("SOURCE_DATE_EPOCH") {
+            Ok(value) => value.trim().parse().map(IngestClock::Fixed).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("SOURCE_DATE_EPOCH is not a Unix time: {:?}", value),
+                )
+            }),
+            Err(_) => Ok(IngestClock::Omit),
+        }
+    }
+
+    pub(crate) fn stamp(self) -> Option<u64> {
+        match self {
+            IngestClock::Now => Some(crate::retention::unix_now()),
+            IngestClock::Fixed(at) => Some(at),
+            IngestClock::Omit => None,
+        }
+    }
+}
+
+impl EmbrFS {
+    /// An empty EmbrFS set up for reproducible ingest; see the
+    /// [module docs](self). Call [`EmbrFS::canonicalize`] before saving.
+    pub fn reproducible(clock: IngestClock) -> Self {
+        let mut fs = Self::new();
+        fs.chunk_index = Some(ChunkIndex::default());
+        fs.clock = clock;
+        fs
+    }
+
+    /// Put the engram in canonical form: files sorted by path (files with
+    /// the same path keep their order), chunk ids renumbered from 0 in the
+    /// order the sorted files reference them, and the root rebundled in that
+    /// order. Chunks no file references keep their relative order after the
+    /// rest. Inode numbers are likewise reassigned in path order, so mounts
+    /// made before canonicalizing see other numbers. Content and
+    /// reconstruction are unchanged.
+    pub fn canonicalize(&mut self) {
+        self.manifest.files.sort_by(|a, b| a.path.cmp(&b.path));
+        self.manifest.inodes = InodeTable::default();
+        self.manifest.assign_inodes();
+
+        let mut remap: HashMap<usize, usize> = HashMap::new();
+        let referenced = self
+            .manifest
+            .files
+            .iter()
+            .flat_map(|f| f.chunks.iter().copied());
+        let unreferenced = self
+            .engram
+            .chunk_record_ids()
+            .into_iter()
+            .map(|id| id as usize);
+        for id in referenced.chain(unreferenced) {
+            let next = remap.len();
+            remap.entry(id).or_insert(next);
+        }
+
+        for file in &mut self.manifest.files {
+            for id in &mut file.chunks {
+                *id = remap[id];
+            }
+        }
+        self.engram.codebook = std::mem::take(&mut self.engram.codebook)
+            .into_iter()
+            .map(|(id, vec)| (remap[&id], vec))
+            .collect();
+        let totals = self.engram.corrections.totals();
+        let corrections = self
+            .engram
+            .corrections
+            .iter()
+            .map(|(id, correction)| {
+                let id = remap[&(id as usize)] as u64;
+                let mut correction = correction.clone();
+                correction.chunk_id = id;
+                (id, correction)
+            })
+            .collect();
+        self.engram.corrections = CorrectionStore::from_parts(corrections, totals);
+        if let Some(semantic) = &mut self.semantic {
+            semantic.vectors = std::mem::take(&mut semantic.vectors)
+                .into_iter()
+                .filter_map(|(id, vec)| Some((*remap.get(&id)?, vec)))
+                .collect();
+        }
+        if let Some(index) = &mut self.chunk_index {
+            for id in index.ids.values_mut() {
+                *id = remap[id];
+            }
+        }
+        self.manifest.total_chunks = remap.len();
+        self.rebuild_root();
+    }
+}
+
+/// Serialize a `HashMap` in key order, so equal maps give equal bytes.
+pub(crate) fn sorted_map<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    serializer.collect_map(entries)
+}
@@ -0,0 +1,326 @@
+//! Retention rules and expiry of files in a manifest.
+//!
+//! A manifest can carry a [`RetentionPolicy`]: rules that give paths or
+//! namespaces a time to live, either directly or through a named retention
+//! class. Each file's clock starts at [`FileEntry::ingested_at`]. Rules are
+//! glob patterns over logical paths with the same syntax as `embeddenator ls`
+//! (`team-a/**` covers the `team-a` namespace, `*.log` any log file), and the
+//! first matching rule decides. Files no rule matches, files under a class
+//! without a TTL, and files from manifests written before ingest times were
+//! recorded are kept.
+//!
+//! [`EmbrFS::apply_retention`] removes the files that have expired along with
+//! the chunks no remaining file references, and returns a [`RetentionAudit`]
+//! listing every purged file by path, size and digest. `embeddenator gc
+//! --apply-retention` appends that record to an audit log next to the
+//! manifest.
+//!
+//! Policies are written in TOML. TTLs are seconds or a number with one of the
+//! units `s`, `m`, `h`, `d`, `w`:
+//!
+//! ```toml
+//! [classes]
+//! short = { ttl = "30d" }
+//! legal-hold = {}
+//!
+//! [[rule]]
+//! path = "legal/**"
+//! class = "legal-hold"
+//!
+//! [[rule]]
+//! path = "scratch/**"
+//! ttl = "7d"
+//!
+//! [[rule]]
+//! path = "*.log"
+//! class = "short"
+//! ```
+
+use crate::embrfs::{EmbrFS, FileEntry};
+use crate::listing::PathFilter;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A named retention period shared by several rules.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionClass {
+    /// Seconds a file is kept after ingest; `None` keeps it indefinitely.
+    #[serde(default, deserialize_with = "de_ttl")]
+    pub ttl: Option<u64>,
+}
+
+/// Retention for the paths matching `path`. Exactly one of `ttl` and
+/// `class` is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    /// Glob over logical paths.
+    pub path: String,
+    /// Seconds a matching file is kept after ingest.
+    #[serde(default, deserialize_with = "de_ttl")]
+    pub ttl: Option<u64>,
+    /// Retention class of matching files.
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+/// Retention rules stored in a manifest; see the [module docs](self).
+///
+/// No field is skipped when empty, so policies also round-trip through
+/// bincode (in [`crate::delta`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub classes: BTreeMap<String, RetentionClass>,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<RetentionRule>,
+}
+
+impl RetentionPolicy {
+    /// Load and validate a TOML policy file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::from_toml(&text)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse and validate a TOML policy.
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        let policy: Self = toml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Check that every rule has a valid pattern and exactly one of a TTL
+    /// and a known class.
+    pub fn validate(&self) -> io::Result<()> {
+        self.compile().map(|_| ())
+    }
+
+    /// When `file` expires, or `None` if it is kept indefinitely.
+    pub fn expires_at(&self, file: &FileEntry) -> io::Result<Option<u64>> {
+        Ok(self.compile()?.expiry(file).map(|(at, _)| at))
+    }
+
+    fn compile(&self) -> io::Result<Rules<'_>> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                let ttl = match (rule.ttl, &rule.class) {
+                    (Some(ttl), None) => Some(ttl),
+                    (None, Some(class)) => {
+                        self.classes
+                            .get(class)
+                            .ok_or_else(|| {
+                                invalid(format!(
+                                    "rule {:?}: unknown retention class {:?}",
+                                    rule.path, class
+                                ))
+                            })?
+                            .ttl
+                    }
+                    _ => {
+                        return Err(invalid(format!(
+                            "rule {:?}: set exactly one of ttl and class",
+                            rule.path
+                        )))
+                    }
+                };
+                Ok((PathFilter::new(&[&rule.path])?, rule, ttl))
+            })
+            .collect::<io::Result<_>>()
+            .map(Rules)
+    }
+}
+
+/// Validated rules with their patterns compiled and classes resolved.
+struct Rules<'a>(Vec<(PathFilter, &'a RetentionRule, Option<u64>)>);
+
+impl<'a> Rules<'a> {
+    /// Expiry time of `file` and the rule that sets it.
+    fn expiry(&self, file: &FileEntry) -> Option<(u64, &'a RetentionRule)> {
+        let (_, rule, ttl) = self
+            .0
+            .iter()
+            .find(|(filter, ..)| filter.matches(&file.path))?;
+        Some((file.ingested_at?.saturating_add((*ttl)?), *rule))
+    }
+}
+
+/// A file removed by [`EmbrFS::apply_retention`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgedFile {
+    pub path: String,
+    pub size: usize,
+    /// Hex blake3 digest of the file, when the manifest recorded one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+    pub ingested_at: u64,
+    pub expired_at: u64,
+    /// Pattern of the rule that expired the file.
+    pub rule: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+}
+
+/// What a retention pass purged, or would purge.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionAudit {
+    /// Unix seconds the policy was evaluated at.
+    pub evaluated_at: u64,
+    /// Whether the files were removed, rather than only listed.
+    pub applied: bool,
+    pub purged: Vec<PurgedFile>,
+    pub files_kept: usize,
+    /// Chunks dropped because only purged files referenced them.
+    pub chunks_removed: usize,
+    pub bytes_purged: u64,
+}
+
+impl RetentionAudit {
+    /// Append this record as one JSON line to the log at `path`.
+    pub fn append_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+        log.write_all(&line)?;
+        log.sync_all()
+    }
+}
+
+/// Conventional location of the retention audit log for a manifest.
+pub fn default_audit_path<P: AsRef<Path>>(manifest_path: P) -> PathBuf {
+    let mut s = manifest_path.as_ref().as_os_str().to_os_string();
+    s.push(".retention.jsonl");
+    PathBuf::from(s)
+}
+
+/// Current time in Unix seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl EmbrFS {
+    /// The files the manifest's retention policy expires at `now`, without
+    /// removing anything.
+    pub fn retention_preview(&self, now: u64) -> io::Result<RetentionAudit> {
+        let mut audit = RetentionAudit {
+            evaluated_at: now,
+            ..RetentionAudit::default()
+        };
+        let Some(policy) = &self.manifest.retention else {
+            audit.files_kept = self.manifest.files.len();
+            return Ok(audit);
+        };
+        let rules = policy.compile()?;
+        let mut kept_chunks = HashSet::new();
+        let mut purged_chunks = HashSet::new();
+        for file in &self.manifest.files {
+            match rules.expiry(file).filter(|&(at, _)| at <= now) {
+                Some((expired_at, rule)) => {
+                    purged_chunks.extend(file.chunks.iter().copied());
+                    audit.bytes_purged += file.size as u64;
+                    audit.purged.push(PurgedFile {
+                        path: file.path.clone(),
+                        size: file.size,
+                        blake3: file.blake3.clone(),
+                        ingested_at: file.ingested_at.unwrap_or_default(),
+                        expired_at,
+                        rule: rule.path.clone(),
+                        class: rule.class.clone(),
+                    });
+                }
+                None => {
+                    kept_chunks.extend(file.chunks.iter().copied());
+                    audit.files_kept += 1;
+                }
+            }
+        }
+        audit.chunks_removed = purged_chunks.difference(&kept_chunks).count();
+        Ok(audit)
+    }
+
+    /// Remove every file the manifest's retention policy expires at `now`,
+    /// and the chunks only those files referenced. Chunk ids are not reused.
+    pub fn apply_retention(&mut self, now: u64) -> io::Result<RetentionAudit> {
+        let mut audit = self.retention_preview(now)?;
+        if audit.purged.is_empty() {
+            audit.applied = true;
+            return Ok(audit);
+        }
+        let purged: HashSet<&str> = audit.purged.iter().map(|p| p.path.as_str()).collect();
+        let (removed, kept): (Vec<FileEntry>, Vec<FileEntry>) =
+            std::mem::take(&mut self.manifest.files)
+                .into_iter()
+                .partition(|f| purged.contains(f.path.as_str()));
+        self.manifest.files = kept;
+        let still_used: HashSet<usize> = self
+            .manifest
+            .files
+            .iter()
+            .flat_map(|f| f.chunks.iter().copied())
+            .collect();
+        let chunk_size = self.manifest.chunk_size();
+        let mut seen = HashSet::new();
+        let unused: Vec<(usize, usize)> = removed
+            .iter()
+            .flat_map(|f| {
+                f.chunks
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, &id)| (id, EmbrFS::chunk_len(f, i, chunk_size)))
+            })
+            .filter(|(id, _)| !still_used.contains(id) && seen.insert(*id))
+            .collect();
+        self.discard_chunks(&unused);
+        audit.applied = true;
+        Ok(audit)
+    }
+}
+
+/// A TTL given as seconds or as a string like `"90d"`.
+fn de_ttl<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ttl {
+        Secs(u64),
+        Text(String),
+    }
+    match Option::<Ttl>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Ttl::Secs(secs)) => Ok(Some(secs)),
+        Some(Ttl::Text(text)) => parse_ttl(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse `"45"`, `"90s"`, `"15m"`, `"12h"`, `"30d"` or `"2w"` into seconds.
+pub fn parse_ttl(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (digits, unit) = text.split_at(split);
+    let scale = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(format!("invalid ttl {:?}: unknown unit", text)),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+        .ok_or_else(|| format!("invalid ttl {:?}", text))
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
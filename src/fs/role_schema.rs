@@ -0,0 +1,248 @@
+//! Named roles that files and directories are bound to values under.
+//!
+//! A [`RoleSchema`] is a registry of role labels such as `author` or
+//! `licence`. A role's vector is the seeded bipolar role vector of
+//! [`Record`], derived from its label and the schema's seed, so the engram
+//! keeps only the labels and the seed and every load derives the same
+//! vectors. Values are text, whose vectors are the seeded symbols of
+//! [`ItemMemory::symbol`], or any vector, such as a file's signature.
+//!
+//! A path's bindings are bundled into one record vector, `Σ role ⊗ value`.
+//! [`RoleSchema::query`] unbinds a role from every record and ranks the
+//! paths whose value scores like a bundle member against the probe, which
+//! answers "files bound to `author` ≈ X". A binding on a directory is
+//! matched by the directory's path; its files keep their own bindings.
+//!
+//! [`EmbrFS::bind_role`] binds a path of the manifest and
+//! [`EmbrFS::bind_metadata_roles`] binds every file's `kind` and, for files
+//! ingested on Unix, its `owner`, `group` and `mode`. The schema is kept by
+//! bincode engram files.
+//!
+//! ```rust,ignore
+//! use embeddenator::role_schema::RoleValue;
+//!
+//! fs.role_schema(7).define("author");
+//! fs.bind_role("docs/guide.md", "author", RoleValue::Text("ada".into()))?;
+//! let schema = fs.engram.schema.as_ref().unwrap();
+//! let hits = schema.query("author", &schema.symbol("ada"), 10);
+//! assert_eq!(hits[0].path, "docs/guide.md");
+//! ```
+
+use crate::embrfs::EmbrFS;
+use crate::vsa::record::{bipolar, bundle_threshold, ItemMemory, Record};
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+/// Role labels and the values paths are bound to under them; see the
+/// [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoleSchema {
+    /// The seed role and symbol vectors derive from.
+    pub seed: u64,
+    /// Role labels, sorted.
+    roles: Vec<String>,
+    /// Each bound path's bindings, in the order bound.
+    bindings: BTreeMap<String, Vec<RoleBinding>>,
+}
+
+/// A value bound to a path under `role`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoleBinding {
+    pub role: String,
+    pub value: RoleValue,
+}
+
+/// What a role is bound to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RoleValue {
+    /// Text, standing for its seeded symbol.
+    Text(String),
+    Vector(SparseVec),
+}
+
+/// A path [`RoleSchema::query`] matched, with the cosine of its unbound
+/// value to the probe.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RoleHit {
+    pub path: String,
+    pub cosine: f64,
+}
+
+impl RoleSchema {
+    /// Empty schema whose vectors derive from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Register `label` and return its role vector.
+    pub fn define(&mut self, label: &str) -> SparseVec {
+        if let Err(i) = self.roles.binary_search_by(|r| r.as_str().cmp(label)) {
+            self.roles.insert(i, label.to_string());
+        }
+        self.role_vector(label)
+    }
+
+    /// Registered role labels, sorted.
+    pub fn roles(&self) -> impl Iterator<Item = &str> {
+        self.roles.iter().map(String::as_str)
+    }
+
+    /// The role vector of `label`; `None` if it is not registered.
+    pub fn role(&self, label: &str) -> Option<SparseVec> {
+        self.roles.binary_search_by(|r| r.as_str().cmp(label)).ok().map(|_| self.role_vector(label))
+    }
+
+    /// The seeded symbol of `text`, as [`RoleValue::Text`] binds it.
+    pub fn symbol(&self, text: &str) -> SparseVec {
+        ItemMemory::new(self.seed).symbol(text)
+    }
+
+    /// The vector `value` stands for.
+    pub fn value(&self, value: &RoleValue) -> SparseVec {
+        match value {
+            RoleValue::Text(text) => self.symbol(text),
+            RoleValue::Vector(vec) => vec.clone(),
+        }
+    }
+
+    /// Bind `value` to `path` under `role`, registering the role if it is
+    /// new. A path may hold several values under one role; binding the
+    /// same value twice keeps one.
+    pub fn bind(&mut self, path: &str, role: &str, value: RoleValue) {
+        self.define(role);
+        let bindings = self.bindings.entry(path.to_string()).or_default();
+        let binding = RoleBinding {
+            role: role.to_string(),
+            value,
+        };
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Drop every binding of `path`.
+    pub fn unbind(&mut self, path: &str) -> Vec<RoleBinding> {
+        self.bindings.remove(path).unwrap_or_default()
+    }
+
+    /// The bindings of `path`, in the order bound.
+    pub fn bindings(&self, path: &str) -> &[RoleBinding] {
+        self.bindings.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// Bound paths, sorted.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(String::as_str)
+    }
+
+    /// The record vector of `path`: its bindings bundled as [`Record`]
+    /// bundles fields. `None` if nothing is bound to it.
+    pub fn record(&self, path: &str) -> Option<SparseVec> {
+        let bindings = self.bindings.get(path).filter(|b| !b.is_empty())?;
+        let values: Vec<SparseVec> = bindings.iter().map(|b| self.value(&b.value)).collect();
+        Some(Record::encode(self.seed, bindings.iter().map(|b| b.role.as_str()).zip(&values)).vector)
+    }
+
+    /// Paths bound under `role` to a value like `probe`, best first, at
+    /// most `k`. A path matches when its unbound value scores above
+    /// [`bundle_threshold`] for the number of values in its record.
+    pub fn query(&self, role: &str, probe: &SparseVec, k: usize) -> Vec<RoleHit> {
+        let Some(role_vec) = self.role(role) else {
+            return Vec::new();
+        };
+        let mut hits: Vec<RoleHit> = self
+            .bindings
+            .iter()
+            .filter(|(_, bindings)| bindings.iter().any(|b| b.role == role))
+            .filter_map(|(path, bindings)| {
+                let cosine = self.record(path)?.bind(&role_vec).cosine(probe);
+                (cosine > bundle_threshold(bindings.len())).then(|| RoleHit {
+                    path: path.clone(),
+                    cosine,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.cosine.total_cmp(&a.cosine).then_with(|| a.path.cmp(&b.path)));
+        hits.truncate(k);
+        hits
+    }
+
+    /// Paths bound under `role` to `text`.
+    pub fn query_text(&self, role: &str, text: &str, k: usize) -> Vec<RoleHit> {
+        self.query(role, &self.symbol(text), k)
+    }
+
+    /// Both schemas in one: the union if their seeds agree, which keeps
+    /// every vector as it was; otherwise the one with the lesser seed, since
+    /// the other's roles mean nothing under it.
+    pub fn merge(a: &RoleSchema, b: &RoleSchema) -> RoleSchema {
+        if a.seed != b.seed {
+            return if a.seed < b.seed { a.clone() } else { b.clone() };
+        }
+        let mut merged = a.clone();
+        for role in &b.roles {
+            merged.define(role);
+        }
+        for (path, bindings) in &b.bindings {
+            for binding in bindings {
+                merged.bind(path, &binding.role, binding.value.clone());
+            }
+        }
+        merged
+    }
+
+    fn role_vector(&self, label: &str) -> SparseVec {
+        bipolar(self.seed, "role", label)
+    }
+}
+
+impl EmbrFS {
+    /// The engram's role schema, created with `seed` if it has none. An
+    /// existing schema keeps its own seed.
+    pub fn role_schema(&mut self, seed: u64) -> &mut RoleSchema {
+        self.engram.schema.get_or_insert_with(|| RoleSchema::new(seed))
+    }
+
+    /// Bind `value` to `path` under `role` in the engram's schema. `path` is
+    /// a file of the manifest or a directory holding one.
+    pub fn bind_role(&mut self, path: &str, role: &str, value: RoleValue) -> io::Result<()> {
+        let path = path.trim_end_matches('/');
+        let dir = format!("{}/", path);
+        if !self.manifest.files.iter().any(|f| f.path == path || f.path.starts_with(&dir)) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file or directory {:?} in the manifest", path),
+            ));
+        }
+        let schema = self.engram.schema.as_mut().ok_or_else(no_schema)?;
+        schema.bind(path, role, value);
+        Ok(())
+    }
+
+    /// Bind every file's metadata in the engram's schema: `kind` (`text`
+    /// or `binary`) and, where the manifest recorded them, `owner`
+    /// (`uid:N`), `group` (`gid:N`) and `mode` (octal). Returns the number
+    /// of files bound.
+    pub fn bind_metadata_roles(&mut self) -> io::Result<usize> {
+        let schema = self.engram.schema.as_mut().ok_or_else(no_schema)?;
+        for file in &self.manifest.files {
+            let kind = if file.is_text { "text" } else { "binary" };
+            schema.bind(&file.path, "kind", RoleValue::Text(kind.to_string()));
+            if let Some(unix) = file.unix {
+                schema.bind(&file.path, "owner", RoleValue::Text(format!("uid:{}", unix.uid)));
+                schema.bind(&file.path, "group", RoleValue::Text(format!("gid:{}", unix.gid)));
+                schema.bind(&file.path, "mode", RoleValue::Text(format!("{:o}", unix.mode)));
+            }
+        }
+        Ok(self.manifest.files.len())
+    }
+}
+
+fn no_schema() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "the engram has no role schema")
+}
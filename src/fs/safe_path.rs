@@ -0,0 +1,105 @@
+//! Where extraction writes each manifest path.
+//!
+//! Manifest paths come from whoever built the engram, so a crafted one
+//! could name `../../home/user/.bashrc` or `/etc/cron.d/job` and have
+//! extraction write outside the output directory. Extraction therefore
+//! resolves every path with [`relative_path`] before writing anything: `.`
+//! and empty components are dropped, and a path that is absolute, climbs
+//! with `..`, contains a NUL, or has a component the platform would read as
+//! more than one name (`a\..` or `C:` on Windows) fails the whole
+//! extraction with [`EmbrError::UnsafePath`](crate::EmbrError::UnsafePath).
+//!
+//! [`PathPolicy::AllowUnsafe`] (`extract --allow-unsafe-paths`) joins paths
+//! onto the output directory as they are, for trusted engrams that really
+//! do record paths like these.
+
+use crate::error::Result;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// How extraction treats manifest paths that would leave the output
+/// directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathPolicy {
+    /// Refuse to extract if any path is unsafe.
+    #[default]
+    Strict,
+    /// Write every path where joining it onto the output directory leads.
+    AllowUnsafe,
+}
+
+/// Why a manifest path is unsafe to extract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathProblem {
+    /// Starts at a root or drive instead of the output directory.
+    Absolute,
+    /// Has a `..` component.
+    ParentDir,
+    /// Names nothing once `.` and empty components are dropped.
+    Empty,
+    /// Contains a NUL byte.
+    Nul,
+    /// Has a component the platform splits into several, such as one
+    /// holding a `\` on Windows.
+    Separator,
+}
+
+/// A manifest path extraction refused to write; returned as
+/// [`EmbrError::UnsafePath`](crate::EmbrError::UnsafePath).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsafePath {
+    pub path: String,
+    pub problem: PathProblem,
+}
+
+impl fmt::Display for UnsafePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let why = match self.problem {
+            PathProblem::Absolute => "is absolute",
+            PathProblem::ParentDir => "climbs out with '..'",
+            PathProblem::Empty => "names no file",
+            PathProblem::Nul => "contains a NUL byte",
+            PathProblem::Separator => "has a component this platform reads as several",
+        };
+        write!(f, "unsafe path in manifest: {:?} {}", self.path, why)
+    }
+}
+
+impl std::error::Error for UnsafePath {}
+
+/// `logical` as a path relative to the output directory, made of plain
+/// names only.
+pub fn relative_path(logical: &str) -> std::result::Result<PathBuf, UnsafePath> {
+    let fail = |problem| UnsafePath {
+        path: logical.to_string(),
+        problem,
+    };
+    if logical.contains('\0') {
+        return Err(fail(PathProblem::Nul));
+    }
+    if logical.starts_with('/') {
+        return Err(fail(PathProblem::Absolute));
+    }
+    let mut out = PathBuf::new();
+    for name in logical.split('/').filter(|name| !name.is_empty() && *name != ".") {
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(part)), None) => out.push(part),
+            (Some(Component::ParentDir), None) => return Err(fail(PathProblem::ParentDir)),
+            (Some(Component::RootDir | Component::Prefix(_)), _) => return Err(fail(PathProblem::Absolute)),
+            _ => return Err(fail(PathProblem::Separator)),
+        }
+    }
+    if out.as_os_str().is_empty() {
+        return Err(fail(PathProblem::Empty));
+    }
+    Ok(out)
+}
+
+/// Where extraction into `output_dir` writes `logical` under `policy`.
+pub fn output_path(output_dir: &Path, logical: &str, policy: PathPolicy) -> Result<PathBuf> {
+    match policy {
+        PathPolicy::Strict => Ok(output_dir.join(relative_path(logical)?)),
+        PathPolicy::AllowUnsafe => Ok(output_dir.join(logical)),
+    }
+}
@@ -0,0 +1,273 @@
+//! Integrity scrubbing: re-verify every chunk of an engram and restore the
+//! damaged ones from what the engram itself still holds.
+//!
+//! [`EmbrFS::verify`] only notices damage when a whole file is read back.
+//! [`scrub`] checks chunk by chunk instead, comparing each reconstructed
+//! chunk against the hash and parity trit of its correction record, and
+//! paces itself to [`ScrubOptions::rate`] so a long-running
+//! `embeddenator scrub` stays out of the way of other readers.
+//!
+//! A chunk that fails is restored when one of the [`RepairSource`]s still
+//! yields bytes matching its record; its vector and correction are then
+//! rebuilt from those bytes as ingest would have built them. Chunks nothing
+//! restores are logged and counted in the `scrub_chunks_irreparable`
+//! metric; restoring those takes a replica (see [`crate::replica::repair`]).
+
+use crate::correction::{ChunkCorrection, CorrectionType};
+use crate::embrfs::{EmbrFS, Engram, FileEntry, Manifest};
+use crate::ternary::Trit;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How [`scrub`] runs.
+#[derive(Clone, Debug, Default)]
+pub struct ScrubOptions {
+    /// Most chunks checked per second; unlimited when `None`.
+    pub rate: Option<f64>,
+    /// Rewrite the chunks that can be restored. Otherwise they are only
+    /// reported.
+    pub repair: bool,
+}
+
+/// Where the bytes of a restored chunk came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepairSource {
+    /// The correction record holds the chunk verbatim.
+    Verbatim,
+    /// The file still reconstructs to its recorded blake3 checksum, so only
+    /// the correction record was damaged.
+    FileChecksum,
+    /// Another chunk with the same hash, parity and length verified.
+    Twin { chunk_id: usize },
+}
+
+/// A chunk that failed verification.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubbedChunk {
+    pub chunk_id: usize,
+    /// First file in the manifest that references the chunk.
+    pub path: String,
+    /// How it was (or, without [`ScrubOptions::repair`], could be)
+    /// restored; `None` if it could not.
+    pub source: Option<RepairSource>,
+}
+
+/// Outcome of [`scrub`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// Chunks compared against their correction record.
+    pub chunks_checked: usize,
+    /// Chunks without a correction record, which cannot be checked.
+    pub chunks_unchecked: usize,
+    /// Chunks that failed, in manifest order.
+    pub damaged: Vec<ScrubbedChunk>,
+    /// Whether the restorable chunks in `damaged` were rewritten.
+    pub repaired: bool,
+}
+
+impl ScrubReport {
+    /// Damaged chunks that could be restored.
+    pub fn repairable(&self) -> usize {
+        self.damaged.iter().filter(|c| c.source.is_some()).count()
+    }
+
+    /// Damaged chunks that could not be restored.
+    pub fn irreparable(&self) -> usize {
+        self.damaged.len() - self.repairable()
+    }
+
+    /// True when no chunk is left damaged.
+    pub fn is_ok(&self) -> bool {
+        self.damaged.is_empty() || (self.repaired && self.irreparable() == 0)
+    }
+}
+
+/// A [`ScrubReport`] and when its pass finished, as `embeddenator scrub
+/// --status-file` leaves it for servers to include in their health checks
+/// (see [`crate::health`]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubStatus {
+    /// Seconds since the Unix epoch.
+    pub finished_unix: u64,
+    pub report: ScrubReport,
+}
+
+impl ScrubStatus {
+    /// `report`, finished now.
+    pub fn now(report: ScrubReport) -> Self {
+        let finished_unix = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        Self { finished_unix, report }
+    }
+
+    /// Write as JSON to `path`, replacing it in one rename so a server
+    /// reading it concurrently never sees half a status.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let mut staged = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut staged, self)?;
+        staged.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = std::fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+enum Check {
+    Ok(Vec<u8>),
+    Failed,
+    Unchecked,
+}
+
+/// Verify every chunk `manifest` references and, with
+/// [`ScrubOptions::repair`], restore the damaged ones in place.
+///
+/// Shared chunks are checked once. Only the records of damaged chunks
+/// change; the root vector and correction totals are left as they are.
+pub fn scrub(
+    engram: &mut Engram,
+    manifest: &Manifest,
+    config: &ReversibleVSAConfig,
+    opts: &ScrubOptions,
+) -> io::Result<ScrubReport> {
+    manifest.check_vsa_config(config)?;
+    let chunk_size = manifest.chunk_size();
+    let started = Instant::now();
+    let mut report = ScrubReport { repaired: opts.repair, ..Default::default() };
+    let mut seen = HashSet::new();
+    let mut failed: Vec<(&FileEntry, usize)> = Vec::new();
+    // One verified chunk per (hash, parity, length), to restore identical
+    // chunks stored under other ids.
+    let mut verified: HashMap<([u8; 8], Trit, usize), usize> = HashMap::new();
+    let mut verified_bytes: HashMap<usize, Vec<u8>> = HashMap::new();
+
+    for file in &manifest.files {
+        for (idx, &chunk_id) in file.chunks.iter().enumerate() {
+            if !seen.insert(chunk_id) {
+                continue;
+            }
+            match check_chunk(engram, file, idx, config, chunk_size) {
+                Check::Unchecked => report.chunks_unchecked += 1,
+                Check::Ok(bytes) => {
+                    report.chunks_checked += 1;
+                    let record = engram.corrections.get(chunk_id as u64).expect("checked");
+                    let key = (record.hash, record.parity, bytes.len());
+                    if let Entry::Vacant(slot) = verified.entry(key) {
+                        slot.insert(chunk_id);
+                        verified_bytes.insert(chunk_id, bytes);
+                    }
+                }
+                Check::Failed => {
+                    report.chunks_checked += 1;
+                    failed.push((file, idx));
+                }
+            }
+            if let Some(rate) = opts.rate.filter(|r| *r > 0.0) {
+                let due = Duration::from_secs_f64((report.chunks_checked + report.chunks_unchecked) as f64 / rate);
+                if let Some(wait) = due.checked_sub(started.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        }
+    }
+
+    let mut file_ok: HashMap<&str, bool> = HashMap::new();
+    for (file, idx) in failed {
+        let chunk_id = file.chunks[idx];
+        let len = EmbrFS::chunk_len(file, idx, chunk_size);
+        let record = engram.corrections.get(chunk_id as u64).expect("checked").clone();
+
+        let mut found: Option<(Vec<u8>, RepairSource)> = None;
+        if let CorrectionType::Verbatim(data) = &record.correction {
+            if data.len() == len && record.verify_with_parity(data) {
+                found = Some((data.clone(), RepairSource::Verbatim));
+            }
+        }
+        if found.is_none() && file.blake3.is_some() {
+            let ok = match file_ok.get(file.path.as_str()) {
+                Some(&ok) => ok,
+                None => {
+                    let ok = EmbrFS::reconstruct_file(engram, file, config, chunk_size, |_| Ok(()))?.is_none();
+                    file_ok.insert(&file.path, ok);
+                    ok
+                }
+            };
+            if ok {
+                if let Some(vec) = engram.codebook.get(&chunk_id) {
+                    let decoded = vec.decode_data(config, Some(&file.path), len);
+                    found = Some((record.apply(&decoded), RepairSource::FileChecksum));
+                }
+            }
+        }
+        if found.is_none() {
+            let key = (record.hash, record.parity, len);
+            if let Some(&twin) = verified.get(&key) {
+                let bytes = verified_bytes[&twin].clone();
+                if record.verify_with_parity(&bytes) {
+                    found = Some((bytes, RepairSource::Twin { chunk_id: twin }));
+                }
+            }
+        }
+
+        let source = found.as_ref().map(|(_, source)| *source);
+        match found {
+            Some((bytes, _)) if opts.repair => rebuild(engram, file, chunk_id, &bytes, config),
+            Some(_) => {}
+            None => crate::logging::warn(&format!(
+                "scrub: chunk {} of {} is damaged and cannot be restored from this engram",
+                chunk_id, file.path
+            )),
+        }
+        report.damaged.push(ScrubbedChunk { chunk_id, path: file.path.clone(), source });
+    }
+
+    let repaired = if opts.repair { report.repairable() } else { 0 };
+    crate::metrics::metrics().record_scrub(
+        report.chunks_checked as u64,
+        repaired as u64,
+        report.irreparable() as u64,
+    );
+    Ok(report)
+}
+
+/// Reconstruct one chunk and compare it with its correction record.
+fn check_chunk(engram: &Engram, file: &FileEntry, idx: usize, config: &ReversibleVSAConfig, chunk_size: usize) -> Check {
+    let chunk_id = file.chunks[idx];
+    let Some(record) = engram.corrections.get(chunk_id as u64) else {
+        return Check::Unchecked;
+    };
+    // A missing vector is damage even when a verbatim record would still
+    // reconstruct the chunk.
+    let Some(vec) = engram.codebook.get(&chunk_id) else {
+        return Check::Failed;
+    };
+    let decoded = vec.decode_data(config, Some(&file.path), EmbrFS::chunk_len(file, idx, chunk_size));
+    let bytes = record.apply(&decoded);
+    if record.verify_with_parity(&bytes) {
+        Check::Ok(bytes)
+    } else {
+        Check::Failed
+    }
+}
+
+/// Re-encode a chunk from its original bytes, as ingest does.
+fn rebuild(engram: &mut Engram, file: &FileEntry, chunk_id: usize, bytes: &[u8], config: &ReversibleVSAConfig) {
+    let vec = SparseVec::encode_chunk(bytes, config, Some(&file.path));
+    let decoded = vec.decode_data(config, Some(&file.path), bytes.len());
+    engram.codebook.insert(chunk_id, vec);
+    engram
+        .corrections
+        .replace(chunk_id as u64, Some(ChunkCorrection::new(chunk_id as u64, bytes, &decoded)));
+}
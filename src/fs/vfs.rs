@@ -0,0 +1,98 @@
+//! The read-only filesystem view every file-serving frontend goes through.
+//!
+//! [`VirtualFs`] is the inode-level interface a protocol server needs:
+//! resolve a name in a directory, stat an inode, read a byte range, list a
+//! directory and read a link. [`EngramFS`] implements it over its inode
+//! tables, negative-lookup cache and chunk cache, and [`EngramReader`]
+//! implements it by building one of those over its own engram on first use.
+//! The FUSE mount answers `lookup`, `getattr`, `readdir` and `readlink`
+//! through the trait and the HTTP API resolves request paths through it, so
+//! a new frontend (9P, WinFsp) translates its protocol onto these five calls
+//! instead of growing its own path handling.
+//!
+//! Access control stays with the frontend: grants depend on who is asking,
+//! which only the protocol knows.
+
+use crate::fuse_shim::{DirEntry, EngramFS, FileAttr, FileKind, Ino, ROOT_INO};
+use crate::reader::EngramReader;
+use std::io;
+
+/// Read-only inode-level view of a filesystem; see the
+/// [module docs](self).
+pub trait VirtualFs: Send + Sync {
+    /// Inode of `name` in directory `parent`.
+    fn lookup(&self, parent: Ino, name: &str) -> Option<Ino>;
+
+    fn getattr(&self, ino: Ino) -> Option<FileAttr>;
+
+    /// Up to `size` bytes of `ino` from `offset`; empty past the end, `None`
+    /// if `ino` is not a file.
+    fn read(&self, ino: Ino, offset: u64, size: u32) -> Option<Vec<u8>>;
+
+    /// Entries of directory `ino`, without `.` and `..`.
+    fn readdir(&self, ino: Ino) -> Option<Vec<DirEntry>>;
+
+    /// Target of symlink `ino`. Fails with `NotFound` for unknown inodes and
+    /// `InvalidInput` for anything that isn't a link.
+    fn readlink(&self, ino: Ino) -> io::Result<Vec<u8>>;
+
+    /// Inode of `path`, looked up one component at a time from the root.
+    /// Leading, trailing and repeated slashes and `.` components are
+    /// ignored.
+    fn resolve(&self, path: &str) -> Option<Ino> {
+        path.split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+            .try_fold(ROOT_INO, |ino, name| self.lookup(ino, name))
+    }
+}
+
+impl VirtualFs for EngramFS {
+    fn lookup(&self, parent: Ino, name: &str) -> Option<Ino> {
+        self.lookup_name(parent, name)
+    }
+
+    fn getattr(&self, ino: Ino) -> Option<FileAttr> {
+        self.get_attr(ino)
+    }
+
+    fn read(&self, ino: Ino, offset: u64, size: u32) -> Option<Vec<u8>> {
+        self.read_data(ino, offset, size)
+    }
+
+    fn readdir(&self, ino: Ino) -> Option<Vec<DirEntry>> {
+        self.read_dir(ino)
+    }
+
+    fn readlink(&self, ino: Ino) -> io::Result<Vec<u8>> {
+        match self.get_attr(ino) {
+            Some(attr) if attr.kind == FileKind::Symlink => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "symlink targets are not stored in engrams",
+            )),
+            Some(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a symlink")),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+impl VirtualFs for EngramReader {
+    fn lookup(&self, parent: Ino, name: &str) -> Option<Ino> {
+        self.namespace().lookup(parent, name)
+    }
+
+    fn getattr(&self, ino: Ino) -> Option<FileAttr> {
+        self.namespace().getattr(ino)
+    }
+
+    fn read(&self, ino: Ino, offset: u64, size: u32) -> Option<Vec<u8>> {
+        self.namespace().read(ino, offset, size)
+    }
+
+    fn readdir(&self, ino: Ino) -> Option<Vec<DirEntry>> {
+        self.namespace().readdir(ino)
+    }
+
+    fn readlink(&self, ino: Ino) -> io::Result<Vec<u8>> {
+        self.namespace().readlink(ino)
+    }
+}
@@ -0,0 +1,372 @@
+//! C ABI for embedding engrams in C, C++ and other runtimes.
+//!
+//! Engrams and vectors are handed out as opaque heap handles
+//! ([`EmbEngram`], [`EmbVec`]) that the caller releases with the matching
+//! `*_free` function. Fallible calls return an [`EmbStatus`]; on failure,
+//! [`emb_last_error`] describes what went wrong on the calling thread.
+//! Panics are caught at the boundary and reported as
+//! [`EmbStatus::Panic`].
+//!
+//! Pointer contract for every function: handles must come from this library
+//! and not yet be freed, strings must be NUL-terminated UTF-8, and
+//! `data`/`len` pairs must describe readable memory (`data` may be null only
+//! when `len` is 0). Out-parameters must be valid for writes.
+//!
+//! The C header is `include/embeddenator.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/embeddenator.h src/interop/ffi.rs`.
+//! Build the shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+//!
+//! Only available with the `ffi` feature.
+
+#![allow(clippy::missing_safety_doc)]
+
+use crate::embrfs::EmbrFS;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Result of a fallible call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmbStatus {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullArgument = 1,
+    /// A string argument was not valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The named file, engram or manifest does not exist.
+    NotFound = 3,
+    /// Malformed input, or data that failed verification.
+    InvalidData = 4,
+    /// Any other I/O failure.
+    Io = 5,
+    /// The library panicked; the handle involved should be freed.
+    Panic = 6,
+}
+
+/// An engram and its manifest.
+pub struct EmbEngram {
+    fs: EmbrFS,
+    config: ReversibleVSAConfig,
+}
+
+/// A sparse ternary vector.
+pub struct EmbVec(SparseVec);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+type Failure = (EmbStatus, String);
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "\\0")).expect("interior NULs were escaped");
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn io_failure(err: impl Into<io::Error>) -> Failure {
+    let err = err.into();
+    let status = match err.kind() {
+        io::ErrorKind::NotFound => EmbStatus::NotFound,
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => EmbStatus::InvalidData,
+        _ => EmbStatus::Io,
+    };
+    (status, err.to_string())
+}
+
+/// Run `f`, recording any failure or panic for [`emb_last_error`].
+fn guard<F: FnOnce() -> Result<(), Failure>>(f: F) -> EmbStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => EmbStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error("panic inside embeddenator".into());
+            EmbStatus::Panic
+        }
+    }
+}
+
+/// Like [`guard`] for constructors: null on failure.
+fn guard_ptr<T, F: FnOnce() -> Result<T, Failure>>(f: F) -> *mut T {
+    let mut out = ptr::null_mut();
+    guard(|| {
+        out = Box::into_raw(Box::new(f()?));
+        Ok(())
+    });
+    out
+}
+
+unsafe fn arg_ref<'a, T>(p: *const T, name: &str) -> Result<&'a T, Failure> {
+    p.as_ref().ok_or_else(|| (EmbStatus::NullArgument, format!("`{}` is null", name)))
+}
+
+unsafe fn arg_mut<'a, T>(p: *mut T, name: &str) -> Result<&'a mut T, Failure> {
+    p.as_mut().ok_or_else(|| (EmbStatus::NullArgument, format!("`{}` is null", name)))
+}
+
+unsafe fn arg_str<'a>(p: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if p.is_null() {
+        return Err((EmbStatus::NullArgument, format!("`{}` is null", name)));
+    }
+    CStr::from_ptr(p)
+        .to_str()
+        .map_err(|_| (EmbStatus::InvalidUtf8, format!("`{}` is not valid UTF-8", name)))
+}
+
+unsafe fn arg_bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Failure> {
+    match (data.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err((EmbStatus::NullArgument, "`data` is null".into())),
+        (false, len) => Ok(std::slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Library version as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn emb_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Message for the last failed call on this thread, or null if none.
+///
+/// The string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn emb_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// New empty engram. Never null.
+#[no_mangle]
+pub extern "C" fn emb_engram_new() -> *mut EmbEngram {
+    Box::into_raw(Box::new(EmbEngram {
+        fs: EmbrFS::new(),
+        config: ReversibleVSAConfig::default(),
+    }))
+}
+
+/// Release an engram. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_free(engram: *mut EmbEngram) {
+    if !engram.is_null() {
+        drop(Box::from_raw(engram));
+    }
+}
+
+/// Load an engram and manifest from disk into a new handle stored in `*out`.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_load(
+    engram_path: *const c_char,
+    manifest_path: *const c_char,
+    out: *mut *mut EmbEngram,
+) -> EmbStatus {
+    guard(|| {
+        let out = arg_mut(out, "out")?;
+        let mut fs = EmbrFS::new();
+        fs.engram = EmbrFS::load_engram(arg_str(engram_path, "engram_path")?).map_err(io_failure)?;
+        fs.manifest = EmbrFS::load_manifest(arg_str(manifest_path, "manifest_path")?).map_err(io_failure)?;
+        *out = Box::into_raw(Box::new(EmbEngram {
+            fs,
+            config: ReversibleVSAConfig::default(),
+        }));
+        Ok(())
+    })
+}
+
+/// Write the engram and manifest to disk.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_save(
+    engram: *const EmbEngram,
+    engram_path: *const c_char,
+    manifest_path: *const c_char,
+) -> EmbStatus {
+    guard(|| {
+        let engram = arg_ref(engram, "engram")?;
+        engram.fs.save_engram(arg_str(engram_path, "engram_path")?).map_err(io_failure)?;
+        engram.fs.save_manifest(arg_str(manifest_path, "manifest_path")?).map_err(io_failure)
+    })
+}
+
+/// Ingest every file under `dir`, stored relative to it.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_ingest_dir(engram: *mut EmbEngram, dir: *const c_char) -> EmbStatus {
+    guard(|| {
+        let engram = arg_mut(engram, "engram")?;
+        let dir = arg_str(dir, "dir")?;
+        engram.fs.ingest_directory(dir, false, &engram.config).map_err(io_failure)
+    })
+}
+
+/// Ingest `len` bytes at `data` as the file `logical_path`.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_ingest_bytes(
+    engram: *mut EmbEngram,
+    logical_path: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> EmbStatus {
+    guard(|| {
+        let engram = arg_mut(engram, "engram")?;
+        let path = arg_str(logical_path, "logical_path")?.to_string();
+        let data = arg_bytes(data, len)?;
+        engram.fs.ingest_bytes(data, path, &engram.config).map_err(io_failure)
+    })
+}
+
+/// Reconstruct every file under `out_dir`.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_extract(engram: *const EmbEngram, out_dir: *const c_char) -> EmbStatus {
+    guard(|| {
+        let engram = arg_ref(engram, "engram")?;
+        let out_dir = arg_str(out_dir, "out_dir")?;
+        EmbrFS::extract(&engram.fs.engram, &engram.fs.manifest, out_dir, false, &engram.config).map_err(io_failure)
+    })
+}
+
+/// Number of files in the manifest; 0 for a null handle.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_file_count(engram: *const EmbEngram) -> usize {
+    engram.as_ref().map_or(0, |e| e.fs.manifest.files.len())
+}
+
+/// Number of chunk vectors in the codebook; 0 for a null handle.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_chunk_count(engram: *const EmbEngram) -> usize {
+    engram.as_ref().map_or(0, |e| e.fs.engram.codebook.len())
+}
+
+/// Reconstruct one file into a new buffer stored in `*out_data`/`*out_len`.
+///
+/// Release the buffer with `emb_bytes_free`. A checksum mismatch is
+/// reported as `InvalidData` and no buffer is returned.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_read_file(
+    engram: *const EmbEngram,
+    path: *const c_char,
+    out_data: *mut *mut u8,
+    out_len: *mut usize,
+) -> EmbStatus {
+    guard(|| {
+        let engram = arg_ref(engram, "engram")?;
+        let path = arg_str(path, "path")?;
+        let out_data = arg_mut(out_data, "out_data")?;
+        let out_len = arg_mut(out_len, "out_len")?;
+        let entry = engram
+            .fs
+            .manifest
+            .files
+            .iter()
+            .find(|f| f.path == path)
+            .ok_or_else(|| (EmbStatus::NotFound, format!("no file {:?} in engram", path)))?;
+        let buf = EmbrFS::reconstruct_bytes(&engram.fs.engram, entry, &engram.config).map_err(io_failure)?;
+        let buf = buf.into_boxed_slice();
+        *out_len = buf.len();
+        *out_data = Box::into_raw(buf).cast();
+        Ok(())
+    })
+}
+
+/// Release a buffer returned by `emb_engram_read_file`. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn emb_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Find the chunks most similar to `data`.
+///
+/// Writes up to `k` chunk ids and cosine scores, best first, into the
+/// caller's `out_ids` and `out_scores` arrays (each at least `k` long) and
+/// the number written into `*out_count`.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_query(
+    engram: *const EmbEngram,
+    data: *const u8,
+    len: usize,
+    k: usize,
+    out_ids: *mut u64,
+    out_scores: *mut f64,
+    out_count: *mut usize,
+) -> EmbStatus {
+    guard(|| {
+        let engram = arg_ref(engram, "engram")?;
+        let data = arg_bytes(data, len)?;
+        let out_count = arg_mut(out_count, "out_count")?;
+        if k > 0 && (out_ids.is_null() || out_scores.is_null()) {
+            return Err((EmbStatus::NullArgument, "`out_ids` and `out_scores` must not be null".into()));
+        }
+        let hits = engram.fs.similar_chunks(data, k, &engram.config);
+        for (i, (id, cosine, _)) in hits.iter().enumerate() {
+            *out_ids.add(i) = *id as u64;
+            *out_scores.add(i) = *cosine;
+        }
+        *out_count = hits.len();
+        Ok(())
+    })
+}
+
+/// Copy of the engram's root (superposition) vector, or null for a null handle.
+#[no_mangle]
+pub unsafe extern "C" fn emb_engram_root(engram: *const EmbEngram) -> *mut EmbVec {
+    guard_ptr(|| Ok(EmbVec(arg_ref(engram, "engram")?.fs.engram.root.clone())))
+}
+
+/// Encode `len` bytes at `data` into a vector, or null on a bad argument.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_encode(data: *const u8, len: usize) -> *mut EmbVec {
+    guard_ptr(|| Ok(EmbVec(SparseVec::encode_data(arg_bytes(data, len)?, &ReversibleVSAConfig::default(), None))))
+}
+
+/// New random vector. Never null.
+#[no_mangle]
+pub extern "C" fn emb_vec_random() -> *mut EmbVec {
+    Box::into_raw(Box::new(EmbVec(SparseVec::random())))
+}
+
+/// Release a vector. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_free(vec: *mut EmbVec) {
+    if !vec.is_null() {
+        drop(Box::from_raw(vec));
+    }
+}
+
+/// Superposition of `a` and `b` as a new vector, or null on a bad argument.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_bundle(a: *const EmbVec, b: *const EmbVec) -> *mut EmbVec {
+    guard_ptr(|| Ok(EmbVec(arg_ref(a, "a")?.0.bundle(&arg_ref(b, "b")?.0))))
+}
+
+/// Binding of `a` and `b` as a new vector, or null on a bad argument.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_bind(a: *const EmbVec, b: *const EmbVec) -> *mut EmbVec {
+    guard_ptr(|| Ok(EmbVec(arg_ref(a, "a")?.0.bind(&arg_ref(b, "b")?.0))))
+}
+
+/// `a` cyclically shifted by `shift` as a new vector, or null on a bad argument.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_permute(a: *const EmbVec, shift: usize) -> *mut EmbVec {
+    guard_ptr(|| Ok(EmbVec(arg_ref(a, "a")?.0.permute(shift))))
+}
+
+/// Cosine similarity of `a` and `b`; NaN if either is null.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_cosine(a: *const EmbVec, b: *const EmbVec) -> f64 {
+    match (a.as_ref(), b.as_ref()) {
+        (Some(a), Some(b)) => a.0.cosine(&b.0),
+        _ => f64::NAN,
+    }
+}
+
+/// Number of non-zero trits; 0 for a null handle.
+#[no_mangle]
+pub unsafe extern "C" fn emb_vec_nnz(vec: *const EmbVec) -> usize {
+    vec.as_ref().map_or(0, |v| v.0.pos.len() + v.0.neg.len())
+}
@@ -0,0 +1,597 @@
+//! Git history stored as engram snapshots.
+//!
+//! [`GitArchive::ingest`] walks a repository's commits oldest first and
+//! records each commit's tree as its own manifest over one shared engram.
+//! Chunk dedup ([`crate::embrfs::ChunkIndex`]) keeps history cheap: a file
+//! that is unchanged between commits costs nothing, and an edited file only
+//! stores the chunks that changed (chunks are shared per path, so a rename
+//! is stored again under the new path).
+//!
+//! An archive is a directory:
+//!
+//! ```text
+//! archive.json            commits (oldest first), refs, chunk id allocator
+//! root.engram             every chunk of every archived commit
+//! chunks.idx              dedup index, so later runs can append
+//! manifests/<commit>.json one manifest per commit
+//! ```
+//!
+//! Running ingest again on the same directory adds only commits that are not
+//! archived yet. The repository is read with the `git` executable (plumbing
+//! commands only), so it must be on `PATH`.
+//!
+//! Every archived commit is a point in time that can be read back:
+//! [`GitArchive::resolve_as_of`] finds the snapshot current at a timestamp,
+//! [`GitArchive::query`] searches only the chunks of one snapshot, and
+//! [`GitArchive::query_diff`] shows how a query's results changed between two.
+
+use crate::embrfs::{ChunkIndex, EmbrFS, Engram, FileEntry, Manifest};
+use crate::logging;
+use crate::retrieval::TernaryInvertedIndex;
+use crate::vsa::ReversibleVSAConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::OnceLock;
+
+/// Archive index file name within the archive directory.
+pub const ARCHIVE_INDEX: &str = "archive.json";
+const ENGRAM_FILE: &str = "root.engram";
+const CHUNK_INDEX_FILE: &str = "chunks.idx";
+const MANIFEST_DIR: &str = "manifests";
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Git file mode of a regular, non-executable file.
+const MODE_FILE: u32 = 0o100644;
+/// Git file mode of an executable file.
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+/// Git file mode of a symbolic link; the blob holds the link target.
+pub const MODE_SYMLINK: u32 = 0o120000;
+
+/// One archived commit.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitRecord {
+    pub id: String,
+    pub parents: Vec<String>,
+    pub tree: String,
+    pub author: String,
+    pub email: String,
+    /// Commit time, seconds since the Unix epoch.
+    pub time: i64,
+    pub summary: String,
+    /// Paths whose git mode is not a plain file (executables, symlinks).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modes: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ArchiveIndex {
+    version: u32,
+    /// Chunk id allocator, carried over between runs.
+    total_chunks: usize,
+    commits: Vec<CommitRecord>,
+    /// Ref name to commit id, for refs that point at archived commits.
+    refs: BTreeMap<String, String>,
+}
+
+/// What to ingest.
+#[derive(Clone, Debug, Default)]
+pub struct GitIngestOptions {
+    /// Revision range as understood by `git log` (`main`, `v1.0..v2.0`,
+    /// `--all`). Defaults to `HEAD`.
+    pub range: Option<String>,
+    pub verbose: bool,
+}
+
+/// Counters from one [`GitArchive::ingest`] run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GitIngestSummary {
+    /// Commits newly archived.
+    pub commits_added: usize,
+    /// Commits in the range that were already archived.
+    pub commits_present: usize,
+    /// Blobs read from git and encoded.
+    pub blobs_encoded: usize,
+    /// Files whose blob was already encoded under the same path this run.
+    pub files_reused: usize,
+    /// Chunks added to the engram.
+    pub chunks_added: usize,
+}
+
+/// A chunk matched by [`GitArchive::query`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHit {
+    pub chunk: usize,
+    pub cosine: f64,
+    /// Files of the snapshot that reference the chunk.
+    pub paths: Vec<String>,
+}
+
+/// How the results of one query changed between two snapshots.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryDiff {
+    /// Commit the comparison starts from.
+    pub from: String,
+    /// Commit it ends at.
+    pub to: String,
+    /// Hits at `to` that were not hits at `from`, best first.
+    pub added: Vec<SnapshotHit>,
+    /// Hits at `from` that are gone at `to`, best first.
+    pub removed: Vec<SnapshotHit>,
+    /// Hits at both, with their paths at `to`.
+    pub kept: Vec<SnapshotHit>,
+}
+
+/// An opened archive.
+pub struct GitArchive {
+    dir: PathBuf,
+    index: ArchiveIndex,
+    engram: Engram,
+    codebook_index: OnceLock<TernaryInvertedIndex>,
+}
+
+impl GitArchive {
+    /// Archive the commits of `repo` selected by `options` into `dir`,
+    /// appending to an existing archive there.
+    pub fn ingest(
+        repo: &Path,
+        dir: &Path,
+        options: &GitIngestOptions,
+        config: &ReversibleVSAConfig,
+    ) -> io::Result<GitIngestSummary> {
+        let mut index = if dir.join(ARCHIVE_INDEX).exists() {
+            load_index(dir)?
+        } else {
+            ArchiveIndex { version: ARCHIVE_VERSION, ..Default::default() }
+        };
+        let mut fs = EmbrFS::new();
+        if !index.commits.is_empty() {
+            fs.engram = EmbrFS::load_engram(dir.join(ENGRAM_FILE))?;
+            let idx = File::open(dir.join(CHUNK_INDEX_FILE))?;
+            fs.chunk_index = Some(bincode::deserialize_from(BufReader::new(idx)).map_err(io::Error::other)?);
+        } else {
+            fs.chunk_index = Some(ChunkIndex::default());
+        }
+        fs.manifest.total_chunks = index.total_chunks;
+        fs::create_dir_all(dir.join(MANIFEST_DIR))?;
+
+        let range = options.range.as_deref().unwrap_or("HEAD");
+        let archived: HashSet<String> = index.commits.iter().map(|c| c.id.clone()).collect();
+        let mut blobs = BlobReader::spawn(repo)?;
+        let mut encoded: HashMap<(String, String), FileEntry> = HashMap::new();
+        let mut summary = GitIngestSummary::default();
+
+        for mut commit in list_commits(repo, range)? {
+            if archived.contains(&commit.id) {
+                summary.commits_present += 1;
+                continue;
+            }
+            fs.manifest.files.clear();
+            for entry in list_tree(repo, &commit.id)? {
+                if entry.mode != MODE_FILE {
+                    commit.modes.insert(entry.path.clone(), entry.mode);
+                }
+                let key = (entry.path, entry.oid);
+                if let Some(file) = encoded.get(&key) {
+                    fs.manifest.files.push(file.clone());
+                    summary.files_reused += 1;
+                    continue;
+                }
+                let data = blobs.read(&key.1)?;
+                fs.ingest_bytes(&data, key.0.clone(), config)?;
+                summary.blobs_encoded += 1;
+                let file = fs.manifest.files.last().expect("file just ingested").clone();
+                encoded.insert(key, file);
+            }
+            fs.save_manifest(dir.join(MANIFEST_DIR).join(format!("{}.json", commit.id)))?;
+            logging::progress(
+                options.verbose,
+                "fs",
+                format_args!(
+                    "{} {} ({} files)",
+                    &commit.id[..commit.id.len().min(12)],
+                    commit.summary,
+                    fs.manifest.files.len()
+                ),
+            );
+            index.commits.push(commit);
+            summary.commits_added += 1;
+        }
+        blobs.finish()?;
+
+        summary.chunks_added = fs.manifest.total_chunks - index.total_chunks;
+        index.total_chunks = fs.manifest.total_chunks;
+        let archived: HashSet<&str> = index.commits.iter().map(|c| c.id.as_str()).collect();
+        index.refs = list_refs(repo)?
+            .into_iter()
+            .filter(|(_, id)| archived.contains(id.as_str()))
+            .collect();
+
+        // The index goes last: until it is replaced, the previous one only
+        // names manifests and chunks that are still present.
+        fs.save_engram(dir.join(ENGRAM_FILE))?;
+        let mut idx = BufWriter::new(File::create(dir.join(CHUNK_INDEX_FILE))?);
+        bincode::serialize_into(&mut idx, fs.chunk_index.as_ref().expect("dedup enabled above"))
+            .map_err(io::Error::other)?;
+        idx.flush()?;
+        let tmp = dir.join(format!("{ARCHIVE_INDEX}.tmp"));
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp)?), &index)?;
+        fs::rename(tmp, dir.join(ARCHIVE_INDEX))?;
+        Ok(summary)
+    }
+
+    /// Open the archive in `dir`.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let index = load_index(&dir)?;
+        let engram = EmbrFS::load_engram(dir.join(ENGRAM_FILE))?;
+        Ok(Self { dir, index, engram, codebook_index: OnceLock::new() })
+    }
+
+    /// Archived commits, oldest first (parents before children).
+    pub fn commits(&self) -> &[CommitRecord] {
+        &self.index.commits
+    }
+
+    /// Branch and tag names (`refs/heads/main`, `refs/tags/v1`, `HEAD`)
+    /// that pointed at archived commits when the archive was last updated.
+    pub fn refs(&self) -> &BTreeMap<String, String> {
+        &self.index.refs
+    }
+
+    /// The engram shared by every commit.
+    pub fn engram(&self) -> &Engram {
+        &self.engram
+    }
+
+    /// Find a commit by ref name (`main`, `v1.0`, `refs/heads/main`,
+    /// `HEAD`), full id, or an unambiguous id prefix of at least 4 digits.
+    pub fn resolve(&self, rev: &str) -> io::Result<&CommitRecord> {
+        let by_ref = [rev.to_string(), format!("refs/heads/{rev}"), format!("refs/tags/{rev}")]
+            .into_iter()
+            .find_map(|name| self.index.refs.get(&name));
+        let mut matches: Vec<&CommitRecord> = match by_ref {
+            Some(id) => self.index.commits.iter().filter(|c| &c.id == id).collect(),
+            None if rev.len() >= 4 && rev.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                let rev = rev.to_ascii_lowercase();
+                self.index.commits.iter().filter(|c| c.id.starts_with(&rev)).collect()
+            }
+            None => Vec::new(),
+        };
+        match matches.len() {
+            1 => Ok(matches.remove(0)),
+            0 => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no archived commit matches {rev:?}"),
+            )),
+            n => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{rev:?} is ambiguous ({n} archived commits)"),
+            )),
+        }
+    }
+
+    /// Find the commit current at `when`: a time given as `@<unix seconds>`,
+    /// `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM[:SS][Z]` (UTC) picks the latest
+    /// commit made at or before it, and anything else goes to
+    /// [`resolve`](Self::resolve). Commits with equal times are ordered as
+    /// archived, so the later one wins.
+    pub fn resolve_as_of(&self, when: &str) -> io::Result<&CommitRecord> {
+        let time = match parse_time(when) {
+            Ok(Some(time)) => time,
+            Ok(None) => return self.resolve(when),
+            // A ref such as `2024-q1` can look like a date.
+            Err(e) => return self.resolve(when).map_err(|_| e),
+        };
+        self.index
+            .commits
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.time <= time)
+            .max_by_key(|&(i, c)| (c.time, i))
+            .map(|(_, c)| c)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no archived commit at or before {when:?}")))
+    }
+
+    /// Manifest of the tree at `rev`.
+    pub fn manifest(&self, rev: &str) -> io::Result<Manifest> {
+        let commit = self.resolve(rev)?;
+        Ok(EmbrFS::load_manifest(self.dir.join(MANIFEST_DIR).join(format!("{}.json", commit.id)))?)
+    }
+
+    /// Write the tree at `rev` to `output_dir`. On Unix, executable bits
+    /// and symlinks are restored; elsewhere symlinks are written as files
+    /// holding their target.
+    pub fn extract<P: AsRef<Path>>(&self, rev: &str, output_dir: P, config: &ReversibleVSAConfig) -> io::Result<()> {
+        let output_dir = output_dir.as_ref();
+        let commit = self.resolve(rev)?;
+        let manifest = self.manifest(&commit.id)?;
+        EmbrFS::extract(&self.engram, &manifest, output_dir, false, config)?;
+        #[cfg(unix)]
+        for (path, &mode) in &commit.modes {
+            use crate::safe_path::{self, PathPolicy};
+            use std::os::unix::fs::PermissionsExt;
+            let file = safe_path::output_path(output_dir, path, PathPolicy::Strict)?;
+            match mode {
+                MODE_EXECUTABLE => fs::set_permissions(&file, fs::Permissions::from_mode(0o755))?,
+                MODE_SYMLINK => {
+                    let target = fs::read(&file)?;
+                    fs::remove_file(&file)?;
+                    let target = String::from_utf8(target)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: symlink target is not UTF-8")))?;
+                    std::os::unix::fs::symlink(target, &file)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The `k` chunks of the tree at `rev` most similar to `data`, best
+    /// first. Chunks that only later or earlier commits use are not
+    /// considered.
+    pub fn query(&self, rev: &str, data: &[u8], k: usize, config: &ReversibleVSAConfig) -> io::Result<Vec<SnapshotHit>> {
+        let manifest = self.manifest(rev)?;
+        let allowed: HashSet<usize> = manifest.files.iter().flat_map(|f| f.chunks.iter().copied()).collect();
+        let index = self.codebook_index.get_or_init(|| self.engram.build_codebook_index());
+        Ok(EmbrFS::similar_chunks_with_index(&self.engram, &manifest, index, data, k, config, Some(&allowed))
+            .into_iter()
+            .map(|(chunk, cosine, paths)| SnapshotHit { chunk, cosine, paths })
+            .collect())
+    }
+
+    /// Run the same [`query`](Self::query) at `from` and at `to` and compare
+    /// the top `k` hits of each by chunk.
+    pub fn query_diff(
+        &self,
+        from: &str,
+        to: &str,
+        data: &[u8],
+        k: usize,
+        config: &ReversibleVSAConfig,
+    ) -> io::Result<QueryDiff> {
+        let from = self.resolve(from)?.id.clone();
+        let to = self.resolve(to)?.id.clone();
+        let before = self.query(&from, data, k, config)?;
+        let after = self.query(&to, data, k, config)?;
+        let before_ids: HashSet<usize> = before.iter().map(|h| h.chunk).collect();
+        let after_ids: HashSet<usize> = after.iter().map(|h| h.chunk).collect();
+        let (kept, added) = after.into_iter().partition(|h| before_ids.contains(&h.chunk));
+        let removed = before.into_iter().filter(|h| !after_ids.contains(&h.chunk)).collect();
+        Ok(QueryDiff { from, to, added, removed, kept })
+    }
+}
+
+/// Unix seconds named by a time spec, or `None` if it is not one.
+pub(crate) fn parse_time(spec: &str) -> io::Result<Option<i64>> {
+    let bad = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid time {spec:?}"));
+    if let Some(secs) = spec.strip_prefix('@') {
+        return secs.parse().map(Some).map_err(|_| bad());
+    }
+    // Dates start with four digits and a dash, which no ref or id prefix
+    // worth resolving does.
+    let b = spec.as_bytes();
+    if b.len() < 5 || !b[..4].iter().all(u8::is_ascii_digit) || b[4] != b'-' {
+        return Ok(None);
+    }
+    let (date, time) = match spec.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.strip_suffix('Z').unwrap_or(time))),
+        None => (spec, None),
+    };
+    let field = |s: &str, max: i64| s.parse::<i64>().ok().filter(|v| (0..=max).contains(v)).ok_or_else(bad);
+    let mut parts = date.split('-');
+    let (Some(y), Some(m), Some(d), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(bad());
+    };
+    let (year, month, day) = (field(y, 9999)?, field(m, 12)?, field(d, 31)?);
+    if month == 0 || day == 0 {
+        return Err(bad());
+    }
+    let mut secs = days_from_civil(year, month, day) * 86_400;
+    if let Some(time) = time {
+        let parts: Vec<&str> = time.split(':').collect();
+        if !(2..=3).contains(&parts.len()) {
+            return Err(bad());
+        }
+        secs += field(parts[0], 23)? * 3600 + field(parts[1], 59)? * 60;
+        if let Some(s) = parts.get(2) {
+            secs += field(s, 60)?;
+        }
+    }
+    Ok(Some(secs))
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn load_index(dir: &Path) -> io::Result<ArchiveIndex> {
+    let index: ArchiveIndex = serde_json::from_reader(BufReader::new(File::open(dir.join(ARCHIVE_INDEX))?))?;
+    if index.version != ARCHIVE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported git archive version {}", index.version),
+        ));
+    }
+    Ok(index)
+}
+
+fn git_command(repo: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(repo).env("GIT_TERMINAL_PROMPT", "0");
+    cmd
+}
+
+fn spawn_error(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::NotFound {
+        io::Error::new(io::ErrorKind::NotFound, "git executable not found on PATH")
+    } else {
+        err
+    }
+}
+
+/// Run git and return its stdout, or its stderr as the error.
+fn git(repo: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = git_command(repo).args(args).output().map_err(spawn_error)?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn list_commits(repo: &Path, range: &str) -> io::Result<Vec<CommitRecord>> {
+    // Unit and record separators cannot appear in ids, names or subjects.
+    let out = git(
+        repo,
+        &[
+            "log",
+            "--reverse",
+            "--topo-order",
+            "--no-color",
+            "--format=%H%x1f%P%x1f%T%x1f%an%x1f%ae%x1f%ct%x1f%s%x1e",
+            range,
+            "--",
+        ],
+    )?;
+    let out = String::from_utf8_lossy(&out);
+    out.split('\u{1e}')
+        .map(|r| r.trim_start_matches('\n'))
+        .filter(|r| !r.is_empty())
+        .map(|record| {
+            let f: Vec<&str> = record.split('\u{1f}').collect();
+            let [id, parents, tree, author, email, time, summary] = f[..] else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected git log output"));
+            };
+            Ok(CommitRecord {
+                id: id.to_string(),
+                parents: parents.split_whitespace().map(str::to_string).collect(),
+                tree: tree.to_string(),
+                author: author.to_string(),
+                email: email.to_string(),
+                time: time.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad commit time"))?,
+                summary: summary.to_string(),
+                modes: BTreeMap::new(),
+            })
+        })
+        .collect()
+}
+
+struct TreeEntry {
+    mode: u32,
+    oid: String,
+    path: String,
+}
+
+/// Blobs of a commit's tree, recursively. Submodules are skipped.
+fn list_tree(repo: &Path, commit: &str) -> io::Result<Vec<TreeEntry>> {
+    let out = git(repo, &["ls-tree", "-r", "-z", "--full-tree", commit])?;
+    let mut entries = Vec::new();
+    for line in out.split(|&b| b == 0).filter(|l| !l.is_empty()) {
+        let bad = || io::Error::new(io::ErrorKind::InvalidData, "unexpected git ls-tree output");
+        let tab = line.iter().position(|&b| b == b'\t').ok_or_else(bad)?;
+        let meta = std::str::from_utf8(&line[..tab]).map_err(|_| bad())?;
+        let [mode, kind, oid] = meta.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(bad());
+        };
+        if kind != "blob" {
+            continue;
+        }
+        let Ok(path) = String::from_utf8(line[tab + 1..].to_vec()) else {
+            logging::warn(&format!(
+                "git archive: skipping non-UTF-8 path {:?} in {commit}",
+                String::from_utf8_lossy(&line[tab + 1..])
+            ));
+            continue;
+        };
+        entries.push(TreeEntry {
+            mode: u32::from_str_radix(mode, 8).map_err(|_| bad())?,
+            oid: oid.to_string(),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+/// Branches, tags (peeled to commits) and `HEAD`.
+fn list_refs(repo: &Path) -> io::Result<Vec<(String, String)>> {
+    let out = git(
+        repo,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(objectname)%00%(*objectname)",
+            "refs/heads",
+            "refs/tags",
+        ],
+    )?;
+    let mut refs: Vec<(String, String)> = String::from_utf8_lossy(&out)
+        .lines()
+        .filter_map(|line| {
+            let mut f = line.split('\0');
+            let (name, object, peeled) = (f.next()?, f.next()?, f.next().unwrap_or(""));
+            let target = if peeled.is_empty() { object } else { peeled };
+            Some((name.to_string(), target.to_string()))
+        })
+        .collect();
+    if let Ok(head) = git(repo, &["rev-parse", "--verify", "-q", "HEAD^{commit}"]) {
+        refs.push(("HEAD".into(), String::from_utf8_lossy(&head).trim().to_string()));
+    }
+    Ok(refs)
+}
+
+/// A long-running `git cat-file --batch`.
+struct BlobReader {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BlobReader {
+    fn spawn(repo: &Path) -> io::Result<Self> {
+        let mut child = git_command(repo)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(spawn_error)?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        Ok(Self { child, stdin, stdout })
+    }
+
+    fn read(&mut self, oid: &str) -> io::Result<Vec<u8>> {
+        writeln!(self.stdin, "{oid}")?;
+        self.stdin.flush()?;
+        let mut header = String::new();
+        self.stdout.read_line(&mut header)?;
+        // "<oid> blob <size>", or "<oid> missing".
+        let size = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [_, "blob", size] => size.parse::<usize>().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("git cat-file {oid}: {}", header.trim())))?;
+        let mut data = vec![0; size + 1];
+        self.stdout.read_exact(&mut data)?;
+        data.pop();
+        Ok(data)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        drop(self.stdin);
+        self.child.wait()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,513 @@
+//! gRPC service for driving engrams from other languages and hosts.
+//!
+//! [`EngramService`] implements the `embeddenator.v1.Embeddenator` service
+//! from `proto/embeddenator_service.proto`: named in-memory engrams that can
+//! be created, loaded from and saved to a data directory, fed by client-
+//! streamed ingest, read back through server-streamed extraction, and
+//! queried for similar chunks. Generated message, client and server types
+//! are in [`api`].
+//!
+//! File paths sent by clients (load/save targets and ingested logical paths)
+//! must be relative and may not contain `..`; load/save paths resolve under
+//! the service's data directory.
+//!
+//! With an [`AccessPolicy`], callers are identified by the bearer token in
+//! their `authorization` metadata (see [`crate::access`]). Listings, query
+//! hits and extraction only cover files the caller may read, ingest needs
+//! write access to each path, and creating, loading, saving or dropping an
+//! engram needs a `*` write grant.
+//!
+//! The service keeps its [`Health`] up to date as engrams are created,
+//! loaded and dropped; tonic has no HTTP routes of its own, so
+//! [`crate::health::spawn`] serves the probes on a separate port.
+//!
+//! With the `logging` feature each call is logged under a
+//! [`crate::logging::Operation`] named after its method, whose correlation
+//! id is the call's `x-request-id` metadata or a fresh one.
+//!
+//! Only available with the `grpc` feature.
+
+// `tonic::Status` is large, but it is the error type every handler returns.
+#![allow(clippy::result_large_err)]
+
+use crate::access::{self, Access, AccessPolicy, Grants};
+use crate::embrfs::{EmbrFS, FileEntry};
+use crate::health::Health;
+use crate::vsa::ReversibleVSAConfig;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Generated `embeddenator.v1` messages, client and server.
+#[allow(clippy::all)]
+pub mod api {
+    include!("grpc_gen.rs");
+}
+
+use api::embeddenator_server::{Embeddenator, EmbeddenatorServer};
+use api::ingest_request::Msg;
+use api::*;
+
+/// Bytes per streamed extract chunk when the request does not say.
+pub const DEFAULT_STREAM_CHUNK: usize = 1024 * 1024;
+
+/// Upper bound on the client-requested extract chunk size.
+pub const MAX_STREAM_CHUNK: usize = 4 * 1024 * 1024 - 1024;
+
+type Shared = Arc<RwLock<EmbrFS>>;
+
+/// In-memory engram registry served over gRPC.
+#[derive(Clone)]
+pub struct EngramService {
+    data_dir: PathBuf,
+    config: ReversibleVSAConfig,
+    engrams: Arc<RwLock<HashMap<String, Shared>>>,
+    policy: Option<Arc<AccessPolicy>>,
+    health: Health,
+}
+
+impl EngramService {
+    /// Service whose load/save paths resolve under `data_dir`.
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self {
+            data_dir: data_dir.as_ref().to_path_buf(),
+            config: ReversibleVSAConfig::default(),
+            engrams: Arc::default(),
+            policy: None,
+            health: Health::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: ReversibleVSAConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Authorize every call against `policy`.
+    pub fn with_access_policy(mut self, policy: AccessPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Load state of the served engrams, to register more with or serve
+    /// probes from.
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Wrap the service for `tonic::transport::Server::add_service`.
+    pub fn into_server(self) -> EmbeddenatorServer<Self> {
+        EmbeddenatorServer::new(self)
+    }
+
+    fn get(&self, name: &str) -> Result<Shared, Status> {
+        self.engrams
+            .read()
+            .expect("engram registry lock poisoned")
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("no engram named {:?}", name)))
+    }
+
+    fn insert(&self, name: String, fs: EmbrFS) {
+        self.health.engram_loaded(&name, fs.manifest.files.len(), fs.engram.codebook.len());
+        self.engrams
+            .write()
+            .expect("engram registry lock poisoned")
+            .insert(name, Arc::new(RwLock::new(fs)));
+    }
+
+    fn resolve(&self, rel: &str) -> Result<PathBuf, Status> {
+        Ok(self.data_dir.join(checked_relative(rel)?))
+    }
+
+    /// Grants of the caller sending `metadata`.
+    fn caller(&self, metadata: &tonic::metadata::MetadataMap) -> Result<Grants, Status> {
+        let Some(policy) = &self.policy else {
+            return Ok(Grants::all());
+        };
+        let token = match metadata.get("authorization") {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(access::bearer_token)
+                    .ok_or_else(|| Status::unauthenticated("expected a bearer token"))?,
+            ),
+            None => None,
+        };
+        policy
+            .for_token(token)
+            .cloned()
+            .ok_or_else(|| Status::unauthenticated("unknown or missing token"))
+    }
+
+    /// Fail unless the caller may manage engrams.
+    fn require_admin(&self, metadata: &tonic::metadata::MetadataMap) -> Result<(), Status> {
+        if self.caller(metadata)?.is_admin() {
+            Ok(())
+        } else {
+            Err(Status::permission_denied("managing engrams needs write access to every namespace"))
+        }
+    }
+}
+
+/// Serve `service` on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, service: EngramService) -> io::Result<()> {
+    server()
+        .add_service(service.into_server())
+        .serve(addr)
+        .await
+        .map_err(io::Error::other)
+}
+
+/// Serve `service` on an already bound listener (e.g. port 0 in tests).
+pub async fn serve_with_listener(listener: tokio::net::TcpListener, service: EngramService) -> io::Result<()> {
+    server()
+        .add_service(service.into_server())
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .map_err(io::Error::other)
+}
+
+#[cfg(feature = "logging")]
+fn server() -> tonic::transport::Server {
+    tonic::transport::Server::builder().trace_fn(|request| {
+        use crate::logging::Operation;
+        let method = request.uri().path().rsplit('/').next().unwrap_or("grpc").to_string();
+        let op = match request.headers().get("x-request-id").and_then(|v| v.to_str().ok()) {
+            Some(id) if !id.is_empty() && id.len() <= 128 => Operation::with_id(method, id),
+            _ => Operation::new(method),
+        };
+        op.span().clone()
+    })
+}
+
+#[cfg(not(feature = "logging"))]
+fn server() -> tonic::transport::Server {
+    tonic::transport::Server::builder()
+}
+
+fn checked_relative(rel: &str) -> Result<&Path, Status> {
+    let path = Path::new(rel);
+    if rel.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(Status::invalid_argument(format!(
+            "path {:?} must be relative without `..` components",
+            rel
+        )));
+    }
+    Ok(path)
+}
+
+fn status(err: impl Into<io::Error>) -> Status {
+    let err = err.into();
+    let msg = err.to_string();
+    match err.kind() {
+        io::ErrorKind::NotFound => Status::not_found(msg),
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => Status::invalid_argument(msg),
+        io::ErrorKind::AlreadyExists => Status::already_exists(msg),
+        io::ErrorKind::PermissionDenied => Status::permission_denied(msg),
+        _ => Status::internal(msg),
+    }
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    F: FnOnce() -> Result<T, Status> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+}
+
+fn info(name: &str, fs: &EmbrFS) -> EngramInfo {
+    EngramInfo {
+        name: name.to_string(),
+        files: fs.manifest.files.len() as u64,
+        chunks: fs.engram.codebook.len() as u64,
+        total_bytes: fs.manifest.files.iter().map(|f| f.size as u64).sum(),
+    }
+}
+
+fn file_info(f: &FileEntry) -> FileInfo {
+    FileInfo {
+        path: f.path.clone(),
+        size: f.size as u64,
+        is_text: f.is_text,
+        blake3: f.blake3.clone(),
+    }
+}
+
+#[tonic::async_trait]
+impl Embeddenator for EngramService {
+    async fn create_engram(&self, request: Request<EngramRef>) -> Result<Response<EngramInfo>, Status> {
+        self.require_admin(request.metadata())?;
+        let name = request.into_inner().name;
+        let mut engrams = self.engrams.write().expect("engram registry lock poisoned");
+        if engrams.contains_key(&name) {
+            return Err(Status::already_exists(format!("engram {:?} already exists", name)));
+        }
+        let fs = EmbrFS::new();
+        let reply = info(&name, &fs);
+        self.health.engram_loaded(&name, 0, 0);
+        engrams.insert(name, Arc::new(RwLock::new(fs)));
+        Ok(Response::new(reply))
+    }
+
+    async fn load_engram(&self, request: Request<EngramFiles>) -> Result<Response<EngramInfo>, Status> {
+        self.require_admin(request.metadata())?;
+        let req = request.into_inner();
+        let engram_path = self.resolve(&req.engram_path)?;
+        let manifest_path = self.resolve(&req.manifest_path)?;
+        // A reload keeps serving the engram it replaces until it succeeds.
+        let reloading = self.get(&req.name).is_ok();
+        if !reloading {
+            self.health.engram_loading(&req.name);
+        }
+        let fs = blocking(move || {
+            let mut fs = EmbrFS::new();
+            fs.engram = EmbrFS::load_engram(engram_path).map_err(status)?;
+            fs.manifest = EmbrFS::load_manifest(manifest_path).map_err(status)?;
+            Ok(fs)
+        })
+        .await
+        .inspect_err(|e| {
+            if !reloading {
+                self.health.engram_failed(&req.name, &e.message());
+            }
+        })?;
+        let reply = info(&req.name, &fs);
+        self.insert(req.name, fs);
+        Ok(Response::new(reply))
+    }
+
+    async fn save_engram(&self, request: Request<EngramFiles>) -> Result<Response<EngramInfo>, Status> {
+        self.require_admin(request.metadata())?;
+        let req = request.into_inner();
+        let shared = self.get(&req.name)?;
+        let engram_path = self.resolve(&req.engram_path)?;
+        let manifest_path = self.resolve(&req.manifest_path)?;
+        let reply = blocking(move || {
+            let fs = shared.read().expect("engram lock poisoned");
+            fs.save_engram(engram_path).map_err(status)?;
+            fs.save_manifest(manifest_path).map_err(status)?;
+            Ok(info(&req.name, &fs))
+        })
+        .await?;
+        Ok(Response::new(reply))
+    }
+
+    async fn drop_engram(&self, request: Request<EngramRef>) -> Result<Response<DropEngramResponse>, Status> {
+        self.require_admin(request.metadata())?;
+        let name = request.into_inner().name;
+        let dropped = self
+            .engrams
+            .write()
+            .expect("engram registry lock poisoned")
+            .remove(&name)
+            .is_some();
+        self.health.engram_dropped(&name);
+        Ok(Response::new(DropEngramResponse { dropped }))
+    }
+
+    async fn list_engrams(&self, _: Request<ListEngramsRequest>) -> Result<Response<ListEngramsResponse>, Status> {
+        let engrams = self.engrams.read().expect("engram registry lock poisoned");
+        let mut engrams: Vec<EngramInfo> = engrams
+            .iter()
+            .map(|(name, fs)| info(name, &fs.read().expect("engram lock poisoned")))
+            .collect();
+        engrams.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Response::new(ListEngramsResponse { engrams }))
+    }
+
+    async fn list_files(&self, request: Request<EngramRef>) -> Result<Response<ListFilesResponse>, Status> {
+        let grants = self.caller(request.metadata())?;
+        let shared = self.get(&request.into_inner().name)?;
+        let fs = shared.read().expect("engram lock poisoned");
+        let files = fs
+            .manifest
+            .files
+            .iter()
+            .filter(|f| grants.allows(Access::Read, &f.path))
+            .map(file_info)
+            .collect();
+        Ok(Response::new(ListFilesResponse { files }))
+    }
+
+    async fn ingest(&self, request: Request<Streaming<IngestRequest>>) -> Result<Response<IngestResponse>, Status> {
+        let grants = self.caller(request.metadata())?;
+        let mut stream = request.into_inner();
+        let name = match stream.message().await?.and_then(|m| m.msg) {
+            Some(Msg::Target(target)) => target.name,
+            _ => return Err(Status::invalid_argument("ingest must start with a target engram")),
+        };
+        let shared = self.get(&name)?;
+
+        let mut current: Option<(String, Vec<u8>)> = None;
+        let (mut files, mut bytes) = (0u64, 0u64);
+        while let Some(message) = stream.message().await? {
+            let chunk = match message.msg {
+                Some(Msg::Chunk(chunk)) => chunk,
+                _ => return Err(Status::invalid_argument("only file chunks may follow the target")),
+            };
+            let (path, data) = current.get_or_insert_with(|| (chunk.path.clone(), Vec::new()));
+            if *path != chunk.path || chunk.offset != data.len() as u64 {
+                return Err(Status::invalid_argument(format!(
+                    "chunk for {:?} at offset {} does not continue {:?} at offset {}",
+                    chunk.path,
+                    chunk.offset,
+                    path,
+                    data.len()
+                )));
+            }
+            data.extend_from_slice(&chunk.data);
+            if !chunk.last {
+                continue;
+            }
+
+            let (path, data) = current.take().expect("file in progress");
+            checked_relative(&path)?;
+            if !grants.allows(Access::Write, &path) {
+                return Err(Status::permission_denied(format!("no write access to {:?}", path)));
+            }
+            let shared = Arc::clone(&shared);
+            let config = self.config.clone();
+            let len = data.len() as u64;
+            blocking(move || {
+                let mut fs = shared.write().expect("engram lock poisoned");
+                if fs.manifest.files.iter().any(|f| f.path == path) {
+                    return Err(Status::already_exists(format!("{:?} is already in the engram", path)));
+                }
+                fs.ingest_bytes(&data, path, &config).map_err(status)
+            })
+            .await?;
+            files += 1;
+            bytes += len;
+        }
+        if let Some((path, _)) = current {
+            return Err(Status::invalid_argument(format!("stream ended before the last chunk of {:?}", path)));
+        }
+
+        let engram = info(&name, &shared.read().expect("engram lock poisoned"));
+        Ok(Response::new(IngestResponse {
+            engram: Some(engram),
+            files_ingested: files,
+            bytes_ingested: bytes,
+        }))
+    }
+
+    type ExtractStream = ReceiverStream<Result<FileChunk, Status>>;
+
+    async fn extract(&self, request: Request<ExtractRequest>) -> Result<Response<Self::ExtractStream>, Status> {
+        let grants = self.caller(request.metadata())?;
+        let req = request.into_inner();
+        let shared = self.get(&req.name)?;
+        let chunk_size = match req.chunk_size as usize {
+            0 => DEFAULT_STREAM_CHUNK,
+            n => n.min(MAX_STREAM_CHUNK),
+        };
+        let entries: Vec<FileEntry> = {
+            let fs = shared.read().expect("engram lock poisoned");
+            if req.paths.is_empty() {
+                grants.readable(&fs.manifest).files
+            } else {
+                req.paths
+                    .iter()
+                    .map(|p| {
+                        fs.manifest
+                            .files
+                            .iter()
+                            .find(|f| &f.path == p && grants.allows(Access::Read, p))
+                            .cloned()
+                            .ok_or_else(|| Status::not_found(format!("no file {:?} in engram", p)))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(4);
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || {
+            let fs = shared.read().expect("engram lock poisoned");
+            for entry in entries {
+                let mut pending = Vec::with_capacity(chunk_size.min(entry.size));
+                let mut offset = 0u64;
+                let send = |chunk: FileChunk| {
+                    tx.blocking_send(Ok(chunk))
+                        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+                };
+                let result = EmbrFS::reconstruct_file(&fs.engram, &entry, &config, fs.manifest.chunk_size(), |data| {
+                    pending.extend_from_slice(data);
+                    while pending.len() >= chunk_size {
+                        let rest = pending.split_off(chunk_size);
+                        let data = std::mem::replace(&mut pending, rest);
+                        offset += data.len() as u64;
+                        send(FileChunk {
+                            path: entry.path.clone(),
+                            offset: offset - data.len() as u64,
+                            data,
+                            last: false,
+                        })?;
+                    }
+                    Ok(())
+                });
+                let outcome = match result {
+                    Ok(None) => send(FileChunk {
+                        path: entry.path.clone(),
+                        offset,
+                        data: pending,
+                        last: true,
+                    }),
+                    Ok(Some(mismatch)) => {
+                        let _ = tx.blocking_send(Err(Status::data_loss(mismatch.to_string())));
+                        return;
+                    }
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = outcome {
+                    if err.kind() != io::ErrorKind::BrokenPipe {
+                        let _ = tx.blocking_send(Err(status(err)));
+                    }
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn query(&self, request: Request<QueryRequest>) -> Result<Response<QueryResponse>, Status> {
+        let grants = self.caller(request.metadata())?;
+        let req = request.into_inner();
+        let shared = self.get(&req.name)?;
+        let config = self.config.clone();
+        let k = if req.k == 0 { 10 } else { req.k as usize };
+        // Hits only in files the caller can't read are dropped, so restricted
+        // callers search deeper.
+        let everything = grants.allows(Access::Read, "");
+        let fetch = if everything { k } else { k.saturating_mul(4) };
+        let hits = blocking(move || {
+            let fs = shared.read().expect("engram lock poisoned");
+            Ok(fs
+                .similar_chunks(&req.data, fetch, &config)
+                .into_iter()
+                .filter_map(|(id, cosine, mut paths)| {
+                    paths.retain(|p| grants.allows(Access::Read, p));
+                    (everything || !paths.is_empty()).then_some(QueryHit {
+                        chunk_id: id as u64,
+                        cosine,
+                        paths,
+                    })
+                })
+                .take(k)
+                .collect())
+        })
+        .await?;
+        Ok(Response::new(QueryResponse { hits }))
+    }
+}
@@ -0,0 +1,590 @@
+//! Read-only HTTP API over a single engram.
+//!
+//! [`HttpService`] serves one loaded engram as an artifact store that can sit
+//! behind a reverse proxy:
+//!
+//! - `GET /files[?prefix=P]` lists manifest entries as JSON.
+//! - `GET /files/<path>` streams the reconstructed file, resolving `<path>`
+//!   through [`VirtualFs`] as a mount would. Single `bytes=` ranges are
+//!   honoured with `206 Partial Content` and decode only the chunks they
+//!   overlap; multi-range requests get the whole file.
+//! - `GET /search?q=TEXT[&k=N]` and `POST /search[?k=N]` (raw body) return
+//!   the most similar chunks and the files that reference them.
+//! - `GET /chunks[?after=ID&limit=N]` pages through chunk ids, and
+//!   `GET /chunks/<id>` describes one chunk and where it is used.
+//! - `GET /healthz` and `GET /readyz` answer liveness and readiness probes
+//!   from the service's [`Health`]; see [`crate::health`]. They need no
+//!   token.
+//!
+//! Each request runs as a [`logging::Operation`] whose correlation id is
+//! the request's `X-Request-Id`, or a fresh one, and is echoed back in the
+//! response's `X-Request-Id`; everything logged while serving it carries
+//! that id.
+//!
+//! With an [`AccessPolicy`] (see [`crate::access`]), each request is
+//! authorized by its `Authorization: Bearer` token: listings, search hits and
+//! chunk references only show files in namespaces the caller may read, and
+//! other files answer `404` as if absent. A missing token falls back to the
+//! policy's anonymous grants; an unknown one gets `401`.
+//!
+//! Errors are JSON objects of the form `{"error": "..."}`. Whole-file
+//! downloads are checked against the manifest's blake3 digest; if the check
+//! fails the final piece is withheld and the connection is aborted, so a
+//! client never sees a complete body with the wrong bytes.
+//!
+//! Only available with the `http` feature.
+
+use crate::access::{self, Access, AccessPolicy, Grants};
+use crate::embrfs::{EmbrFS, Engram, FileEntry, Manifest};
+use crate::health::Health;
+use crate::logging::{self, Operation};
+use crate::reader::EngramReader;
+use crate::vfs::VirtualFs;
+use crate::vsa::ReversibleVSAConfig;
+use axum::body::Body;
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Hits returned by `/search` when `k` is not given.
+pub const DEFAULT_SEARCH_K: usize = 10;
+
+/// Upper bound on `k` for `/search`.
+pub const MAX_SEARCH_K: usize = 1000;
+
+/// Chunk ids returned per `/chunks` page when `limit` is not given.
+pub const DEFAULT_CHUNK_PAGE: usize = 1000;
+
+/// Header carrying a request's correlation id, both ways.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Read-only HTTP front end for one engram.
+#[derive(Clone)]
+pub struct HttpService {
+    reader: Arc<EngramReader>,
+    policy: Option<Arc<AccessPolicy>>,
+    health: Health,
+}
+
+impl HttpService {
+    pub fn new(engram: Engram, manifest: Manifest) -> Self {
+        Self::from_reader(Arc::new(EngramReader::new(engram, manifest, ReversibleVSAConfig::default())))
+    }
+
+    /// Serve `reader`, which other threads (or a mount) may be using too.
+    ///
+    /// The service's [`Health`] starts out with the reader's engram loaded
+    /// and its chunk cache and sub-engram store registered.
+    pub fn from_reader(reader: Arc<EngramReader>) -> Self {
+        let health = Health::requiring_engram();
+        health.engram_loaded("default", reader.manifest().files.len(), reader.engram().codebook.len());
+        health.add_chunk_cache("chunks", Arc::clone(reader.chunk_cache()));
+        if let Some(store) = reader.sub_engram_store() {
+            health.add_store("sub-engrams", Arc::clone(store));
+        }
+        Self {
+            reader,
+            policy: None,
+            health,
+        }
+    }
+
+    /// What `/healthz` and `/readyz` report, to register more with, e.g. a
+    /// scrub status file.
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Authorize every request against `policy`.
+    pub fn with_access_policy(mut self, policy: AccessPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Decode with `config` instead of the default.
+    ///
+    /// # Panics
+    ///
+    /// If the service's reader is shared, i.e. it was built with
+    /// [`HttpService::from_reader`] or has been cloned; configure the reader
+    /// itself in that case.
+    pub fn with_config(mut self, config: ReversibleVSAConfig) -> Self {
+        Arc::get_mut(&mut self.reader)
+            .expect("HttpService::with_config needs an unshared reader")
+            .set_config(config);
+        self
+    }
+
+    /// Routes for this service, for mounting into a larger axum app.
+    pub fn into_router(self) -> Router {
+        Router::new()
+            .route("/files", get(list_files))
+            .route("/files/*path", get(get_file))
+            .route("/search", get(search_get).post(search_post))
+            .route("/chunks", get(list_chunks))
+            .route("/chunks/:id", get(get_chunk))
+            .route("/healthz", get(probe))
+            .route("/readyz", get(probe))
+            .layer(axum::middleware::from_fn(correlate))
+            .with_state(self)
+    }
+
+    /// Grants of the caller behind `headers`.
+    fn caller(&self, headers: &HeaderMap) -> Result<Grants, ApiError> {
+        let Some(policy) = &self.policy else {
+            return Ok(Grants::all());
+        };
+        let token = match headers.get(header::AUTHORIZATION) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(access::bearer_token)
+                    .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "expected a bearer token".into()))?,
+            ),
+            None => None,
+        };
+        policy
+            .for_token(token)
+            .cloned()
+            .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "unknown or missing token".into()))
+    }
+
+    /// Manifest entry of the file at `path`, resolved through the reader's
+    /// [`VirtualFs`] as a mount would.
+    fn entry(&self, path: &str, grants: &Grants) -> Result<&FileEntry, ApiError> {
+        self.reader
+            .resolve(path)
+            .and_then(|ino| self.reader.file_at(ino))
+            .filter(|entry| grants.allows(Access::Read, &entry.path))
+            .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("no file {:?} in engram", path)))
+    }
+
+    async fn search(&self, data: Vec<u8>, k: Option<usize>, grants: Grants) -> Result<Json<SearchResponse>, ApiError> {
+        let k = k.unwrap_or(DEFAULT_SEARCH_K).clamp(1, MAX_SEARCH_K);
+        let reader = Arc::clone(&self.reader);
+        // Over-fetch for restricted callers, since hits only in files they
+        // can't read are dropped.
+        let everything = grants.allows(Access::Read, "");
+        let fetch = if everything { k } else { k.saturating_mul(4).min(MAX_SEARCH_K) };
+        let op = logging::current();
+        let hits = tokio::task::spawn_blocking(move || {
+            let _op = op.as_ref().map(Operation::enter);
+            reader.similar_chunks(&data, fetch)
+        })
+        .await
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(Json(SearchResponse {
+            hits: hits
+                .into_iter()
+                .filter_map(|(chunk_id, cosine, mut paths)| {
+                    paths.retain(|p| grants.allows(Access::Read, p));
+                    (everything || !paths.is_empty()).then_some(SearchHit { chunk_id, cosine, paths })
+                })
+                .take(k)
+                .collect(),
+        }))
+    }
+}
+
+/// Serve `service` on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, service: HttpService) -> io::Result<()> {
+    serve_with_listener(tokio::net::TcpListener::bind(addr).await?, service).await
+}
+
+/// Serve `service` on an already bound listener (e.g. port 0 in tests).
+pub async fn serve_with_listener(listener: tokio::net::TcpListener, service: HttpService) -> io::Result<()> {
+    axum::serve(listener, service.into_router()).await
+}
+
+/// Serve each request as an [`Operation`] under its `X-Request-Id`, or a
+/// fresh id, and echo the id in the response.
+async fn correlate(request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let given = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128);
+    let op = match given {
+        Some(id) => Operation::with_id("http", id),
+        None => Operation::new("http"),
+    };
+    let (method, path) = (request.method().clone(), request.uri().path().to_string());
+    let started = Instant::now();
+    let mut response = logging::scope(op.clone(), async move {
+        let response = next.run(request).await;
+        logging::info(
+            "http",
+            format_args!(
+                "{} {} -> {} in {:.3}s",
+                method,
+                path,
+                response.status().as_u16(),
+                started.elapsed().as_secs_f64()
+            ),
+        );
+        response
+    })
+    .await;
+    if let Ok(id) = HeaderValue::from_str(op.id()) {
+        response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), id);
+    }
+    response
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response();
+        if self.0 == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+async fn probe(State(svc): State<HttpService>, uri: axum::http::Uri) -> Response {
+    let health = svc.health.clone();
+    let path = uri.path().to_string();
+    // Store probes may touch the filesystem.
+    match tokio::task::spawn_blocking(move || health.respond(&path)).await {
+        Ok(Some((code, report))) => {
+            let code = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (code, Json(report)).into_response()
+        }
+        Ok(None) => ApiError(StatusCode::NOT_FOUND, "no such probe".into()).into_response(),
+        Err(e) => ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct FileInfo<'a> {
+    path: &'a str,
+    size: usize,
+    is_text: bool,
+    chunks: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blake3: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct ListFilesParams {
+    prefix: Option<String>,
+}
+
+async fn list_files(State(svc): State<HttpService>, Query(params): Query<ListFilesParams>, headers: HeaderMap) -> Response {
+    let grants = match svc.caller(&headers) {
+        Ok(grants) => grants,
+        Err(err) => return err.into_response(),
+    };
+    let prefix = params.prefix.unwrap_or_default();
+    let files: Vec<FileInfo> = svc
+        .reader
+        .manifest()
+        .files
+        .iter()
+        .filter(|f| f.path.starts_with(&prefix) && grants.allows(Access::Read, &f.path))
+        .map(|f| FileInfo {
+            path: &f.path,
+            size: f.size,
+            is_text: f.is_text,
+            chunks: f.chunks.len(),
+            blake3: f.blake3.as_deref(),
+        })
+        .collect();
+    Json(files).into_response()
+}
+
+/// Outcome of matching a `Range` header against a file of `size` bytes.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+/// Parse a single `bytes=` range (RFC 9110 §14.1.2). Headers that are
+/// malformed, use another unit, or ask for several ranges are ignored.
+fn parse_range(header: &str, size: usize) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+    if first.is_empty() {
+        return match last.parse::<usize>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if size == 0 => ByteRange::Unsatisfiable,
+            Ok(n) => ByteRange::Partial(size.saturating_sub(n), size),
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(start) = first.parse::<usize>() else {
+        return ByteRange::Full;
+    };
+    let end = if last.is_empty() {
+        size
+    } else {
+        match last.parse::<usize>() {
+            Ok(last) if last >= start => last.saturating_add(1).min(size),
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= size {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(start, end)
+    }
+}
+
+async fn get_file(State(svc): State<HttpService>, UrlPath(path): UrlPath<String>, headers: HeaderMap) -> Response {
+    let entry = match svc.caller(&headers).and_then(|grants| svc.entry(&path, &grants).cloned()) {
+        Ok(entry) => entry,
+        Err(err) => return err.into_response(),
+    };
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(ByteRange::Full, |v| parse_range(v, entry.size));
+
+    let mut builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(
+            header::CONTENT_TYPE,
+            if entry.is_text { "text/plain; charset=utf-8" } else { "application/octet-stream" },
+        );
+    if let Some(etag) = entry.blake3.as_ref().and_then(|h| HeaderValue::from_str(&format!("\"{}\"", h)).ok()) {
+        builder = builder.header(header::ETAG, etag);
+    }
+
+    let (status, start, end) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, entry.size),
+        ByteRange::Partial(start, end) => {
+            builder = builder.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, entry.size));
+            (StatusCode::PARTIAL_CONTENT, start, end)
+        }
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", entry.size))
+                .body(Body::empty())
+                .expect("static headers are valid");
+        }
+    };
+    builder
+        .status(status)
+        .header(header::CONTENT_LENGTH, end - start)
+        .body(stream_file(&svc, entry, start, end, status == StatusCode::OK))
+        .expect("static headers are valid")
+}
+
+/// Reconstruct `start..end` of `entry` on the blocking pool and stream it.
+///
+/// With `whole_file`, the manifest checksum is verified and the last piece is
+/// held back until it passes.
+fn stream_file(svc: &HttpService, entry: FileEntry, start: usize, end: usize, whole_file: bool) -> Body {
+    let (tx, rx) = mpsc::channel::<io::Result<Vec<u8>>>(4);
+    let reader = Arc::clone(&svc.reader);
+    let op = logging::current();
+    tokio::task::spawn_blocking(move || {
+        let _op = op.as_ref().map(Operation::enter);
+        let send = |data: Vec<u8>| {
+            tx.blocking_send(Ok(data))
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+        };
+        let result = if whole_file {
+            let mut held: Option<Vec<u8>> = None;
+            reader.stream_file(&entry, |data| match held.replace(data.to_vec()) {
+                Some(previous) => send(previous),
+                None => Ok(()),
+            })
+            .and_then(|mismatch| match mismatch {
+                Some(mismatch) => Err(io::Error::new(io::ErrorKind::InvalidData, mismatch.to_string())),
+                None => held.map_or(Ok(()), send),
+            })
+        } else {
+            reader.stream_range(&entry, start, end, |data| send(data.to_vec()))
+        };
+        if let Err(err) = result {
+            if err.kind() != io::ErrorKind::BrokenPipe {
+                let _ = tx.blocking_send(Err(err));
+            }
+        }
+    });
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+#[derive(Serialize)]
+struct SearchHit {
+    chunk_id: usize,
+    cosine: f64,
+    paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    hits: Vec<SearchHit>,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: Option<String>,
+    k: Option<usize>,
+}
+
+async fn search_get(State(svc): State<HttpService>, Query(params): Query<SearchParams>, headers: HeaderMap) -> Response {
+    let grants = match svc.caller(&headers) {
+        Ok(grants) => grants,
+        Err(err) => return err.into_response(),
+    };
+    let Some(q) = params.q.filter(|q| !q.is_empty()) else {
+        return ApiError(StatusCode::BAD_REQUEST, "missing query parameter `q`".into()).into_response();
+    };
+    svc.search(q.into_bytes(), params.k, grants).await.into_response()
+}
+
+async fn search_post(
+    State(svc): State<HttpService>,
+    Query(params): Query<SearchParams>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    let grants = match svc.caller(&headers) {
+        Ok(grants) => grants,
+        Err(err) => return err.into_response(),
+    };
+    if body.is_empty() {
+        return ApiError(StatusCode::BAD_REQUEST, "request body is empty".into()).into_response();
+    }
+    svc.search(body.to_vec(), params.k, grants).await.into_response()
+}
+
+#[derive(Deserialize)]
+struct ChunkPageParams {
+    after: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ChunkPage {
+    total: usize,
+    ids: Vec<usize>,
+}
+
+async fn list_chunks(State(svc): State<HttpService>, Query(params): Query<ChunkPageParams>, headers: HeaderMap) -> Response {
+    let grants = match svc.caller(&headers) {
+        Ok(grants) => grants,
+        Err(err) => return err.into_response(),
+    };
+    let codebook = &svc.reader.engram().codebook;
+    // Restricted callers only see chunks used by files they can read.
+    let visible: Option<HashSet<usize>> = (!grants.allows(Access::Read, "")).then(|| {
+        grants
+            .readable(svc.reader.manifest())
+            .files
+            .iter()
+            .flat_map(|f| f.chunks.iter().copied())
+            .filter(|id| codebook.contains_key(id))
+            .collect()
+    });
+    let shown = |id: &usize| visible.as_ref().is_none_or(|v| v.contains(id));
+    let mut ids: Vec<usize> = codebook
+        .keys()
+        .copied()
+        .filter(|id| params.after.is_none_or(|after| *id > after) && shown(id))
+        .collect();
+    ids.sort_unstable();
+    ids.truncate(params.limit.unwrap_or(DEFAULT_CHUNK_PAGE));
+    Json(ChunkPage {
+        total: visible.as_ref().map_or(codebook.len(), HashSet::len),
+        ids,
+    })
+    .into_response()
+}
+
+#[derive(Serialize)]
+struct ChunkRef<'a> {
+    path: &'a str,
+    index: usize,
+    offset: usize,
+    len: usize,
+}
+
+#[derive(Serialize)]
+struct ChunkInfo<'a> {
+    id: usize,
+    nnz: usize,
+    has_correction: bool,
+    refs: Vec<ChunkRef<'a>>,
+}
+
+async fn get_chunk(State(svc): State<HttpService>, UrlPath(id): UrlPath<usize>, headers: HeaderMap) -> Response {
+    let grants = match svc.caller(&headers) {
+        Ok(grants) => grants,
+        Err(err) => return err.into_response(),
+    };
+    let not_found = || ApiError(StatusCode::NOT_FOUND, format!("no chunk {} in engram", id)).into_response();
+    let Some(vec) = svc.reader.engram().codebook.get(&id) else {
+        return not_found();
+    };
+    let mut refs = Vec::new();
+    let chunk_size = svc.reader.manifest().chunk_size();
+    for file in svc.reader.manifest().files.iter().filter(|f| grants.allows(Access::Read, &f.path)) {
+        for (index, _) in file.chunks.iter().enumerate().filter(|&(_, &c)| c == id) {
+            refs.push(ChunkRef {
+                path: &file.path,
+                index,
+                offset: index * chunk_size,
+                len: EmbrFS::chunk_len(file, index, chunk_size),
+            });
+        }
+    }
+    if refs.is_empty() && !grants.allows(Access::Read, "") {
+        return not_found();
+    }
+    Json(ChunkInfo {
+        id,
+        nnz: vec.pos.len() + vec.neg.len(),
+        has_correction: svc.reader.engram().corrections.get(id as u64).is_some(),
+        refs,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_forms() {
+        assert_eq!(parse_range("bytes=0-9", 100), ByteRange::Partial(0, 10));
+        assert_eq!(parse_range("bytes=90-", 100), ByteRange::Partial(90, 100));
+        assert_eq!(parse_range("bytes=-10", 100), ByteRange::Partial(90, 100));
+        assert_eq!(parse_range("bytes=-500", 100), ByteRange::Partial(0, 100));
+        assert_eq!(parse_range("bytes=50-5000", 100), ByteRange::Partial(50, 100));
+        assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), ByteRange::Full);
+        assert_eq!(parse_range("bytes=9-3", 100), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 100), ByteRange::Full);
+    }
+}
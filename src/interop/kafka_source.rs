@@ -0,0 +1,129 @@
+//! Kafka consumer as a streaming-ingest [`EventSource`].
+//!
+//! Messages are read with a consumer group and auto-commit disabled; offsets
+//! are committed only from [`EventSource::commit`], i.e. after the events are
+//! in a durable engram checkpoint. Two message layouts are understood (see
+//! [`KafkaFormat`]): file messages keyed by logical path, and S3 event
+//! notifications such as MinIO or Ceph bucket notifications publish to Kafka.
+//! AWS S3 cannot publish to Kafka; its notifications have to be bridged from
+//! SQS or EventBridge into a topic (see [`crate::stream_ingest`]).
+//!
+//! Broker connection failures are retried by librdkafka and surface as empty
+//! polls. A message that cannot be decoded stops the stream with an error
+//! rather than being skipped, so nothing is acknowledged past it.
+//!
+//! Only available with the `kafka` feature.
+
+use crate::stream_ingest::{parse_s3_notification, resolve_object_events, EventSource, FileEvent, ObjectFetcher};
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::Message;
+use std::io;
+use std::time::Duration;
+
+/// How message keys and values map to file events.
+pub enum KafkaFormat {
+    /// Key is the logical path (UTF-8), value the whole file. A null value
+    /// (a compaction tombstone) deletes the path.
+    Files,
+    /// Value is an S3 event notification; created objects are read with the
+    /// fetcher and keyed by object key.
+    S3Events(Box<dyn ObjectFetcher + Send>),
+}
+
+impl KafkaFormat {
+    /// File events carried by one message.
+    pub fn decode(&self, key: Option<&[u8]>, value: Option<&[u8]>) -> io::Result<Vec<FileEvent>> {
+        match self {
+            KafkaFormat::Files => {
+                let key = key.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "kafka: file message without a key"))?;
+                let path = std::str::from_utf8(key)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "kafka: message key is not a UTF-8 path"))?
+                    .to_string();
+                Ok(vec![match value {
+                    Some(data) => FileEvent::Put { path, data: data.to_vec() },
+                    None => FileEvent::Delete { path },
+                }])
+            }
+            KafkaFormat::S3Events(fetcher) => match value {
+                Some(message) => resolve_object_events(parse_s3_notification(message)?, fetcher.as_ref()),
+                None => Ok(Vec::new()),
+            },
+        }
+    }
+}
+
+fn kafka_error(err: KafkaError) -> io::Error {
+    io::Error::other(format!("kafka: {err}"))
+}
+
+/// A subscribed Kafka consumer.
+pub struct KafkaSource {
+    consumer: BaseConsumer,
+    format: KafkaFormat,
+}
+
+impl KafkaSource {
+    /// Join `group_id` on `brokers` and subscribe to `topics`. A group with
+    /// no committed offsets starts from the earliest message.
+    pub fn new(brokers: &str, group_id: &str, topics: &[&str], format: KafkaFormat) -> io::Result<Self> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers).set("group.id", group_id);
+        Self::with_config(config, topics, format)
+    }
+
+    /// Like [`KafkaSource::new`] with a caller-supplied librdkafka config
+    /// (for SASL, TLS and so on). Auto-commit is always turned off.
+    pub fn with_config(mut config: ClientConfig, topics: &[&str], format: KafkaFormat) -> io::Result<Self> {
+        if config.get("auto.offset.reset").is_none() {
+            config.set("auto.offset.reset", "earliest");
+        }
+        config.set("enable.auto.commit", "false");
+        let consumer: BaseConsumer = config.create().map_err(kafka_error)?;
+        consumer.subscribe(topics).map_err(kafka_error)?;
+        Ok(Self { consumer, format })
+    }
+}
+
+impl EventSource for KafkaSource {
+    fn poll(&mut self, max: usize, timeout: Duration) -> io::Result<Vec<FileEvent>> {
+        let mut events = Vec::new();
+        let mut messages = 0;
+        while messages < max {
+            // Only the first poll waits; after that, drain what is buffered.
+            let wait = if messages == 0 { timeout } else { Duration::ZERO };
+            let Some(message) = self.consumer.poll(wait) else {
+                break;
+            };
+            let message = match message {
+                Ok(message) => message,
+                // librdkafka reconnects on its own; report an empty poll.
+                Err(KafkaError::MessageConsumption(
+                    RDKafkaErrorCode::BrokerTransportFailure
+                    | RDKafkaErrorCode::AllBrokersDown
+                    | RDKafkaErrorCode::PartitionEOF,
+                )) => break,
+                Err(e) => return Err(kafka_error(e)),
+            };
+            let decoded = self.format.decode(message.key(), message.payload()).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("{}[{}]@{}: {e}", message.topic(), message.partition(), message.offset()),
+                )
+            })?;
+            events.extend(decoded);
+            messages += 1;
+        }
+        Ok(events)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        match self.consumer.commit_consumer_state(CommitMode::Sync) {
+            Ok(()) => Ok(()),
+            // Nothing consumed since the last commit.
+            Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => Ok(()),
+            Err(e) => Err(kafka_error(e)),
+        }
+    }
+}
@@ -7,9 +7,12 @@
 //! - algebraic VSA ops (bundle, bind, similarity)
 //! - a retrieval seam (candidate generation + optional rerank)
 
-use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use crate::hybrid::{HybridRepr, HybridThresholds, HybridTritVec};
+use crate::vsa::{ReversibleVSAConfig, SparseVec, DIM};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
+use std::io;
 
 /// Errors from kernel↔VSA interop helpers.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -104,17 +107,226 @@ impl VsaBackend for SparseVecBackend {
     }
 }
 
+/// Vectors sampled by [`HybridBackend::for_store`] to estimate density.
+const DENSITY_SAMPLE: usize = 256;
+
+/// Backend over [`HybridTritVec`], holding every vector it produces in one
+/// representation chosen up front from the dimension and expected density
+/// (see [`HybridThresholds::select`]).
+///
+/// Keeping one representation avoids converting on every operation:
+/// bitsliced ops for dense vectors, block-sparse ones at very large
+/// dimensions, and plain sparse merges otherwise. Build it with
+/// [`HybridBackend::for_store`] to take both numbers from a store, and read
+/// the store's `SparseVec`s through [`HybridBackend::view`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HybridBackend {
+    dim: usize,
+    repr: HybridRepr,
+}
+
+impl HybridBackend {
+    /// Select the representation for `dim`-dimensional vectors of about
+    /// `density` non-zero trits per dimension, by the built-in thresholds.
+    /// [`HybridBackend::with_repr`] takes the choice of calibrated ones,
+    /// `thresholds.select(dim, density)`.
+    pub fn new(dim: usize, density: f64) -> Self {
+        Self {
+            dim,
+            repr: HybridThresholds::BUILT_IN.select(dim, density),
+        }
+    }
+
+    /// Use `repr` regardless of density.
+    pub fn with_repr(dim: usize, repr: HybridRepr) -> Self {
+        Self { dim, repr }
+    }
+
+    /// Select from the dimension `store` declares ([`DIM`] if none) and the
+    /// mean density of its first few vectors.
+    pub fn for_store<S: ScannableVectorStore + ?Sized>(store: &S) -> io::Result<Self> {
+        let dim = store.declared_dim().unwrap_or(DIM);
+        let sample = store.scan(0, DENSITY_SAMPLE)?;
+        let nnz: usize = sample.iter().map(|(_, v)| v.pos.len() + v.neg.len()).sum();
+        let density = if sample.is_empty() {
+            0.0
+        } else {
+            nnz as f64 / (sample.len() * dim.max(1)) as f64
+        };
+        Ok(Self::new(dim, density))
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn repr(&self) -> HybridRepr {
+        self.repr
+    }
+
+    /// `vec` in this backend's representation.
+    pub fn adopt(&self, vec: SparseVec) -> HybridTritVec {
+        HybridTritVec::from_sparse_as(vec, self.dim, self.repr)
+    }
+
+    /// `store` seen as a store of this backend's vectors, converting each one
+    /// as it is fetched.
+    pub fn view<'a, S: VectorStore<SparseVec>>(&'a self, store: &'a S) -> HybridStoreView<'a, S> {
+        HybridStoreView { backend: self, store }
+    }
+
+    fn conform(&self, vec: HybridTritVec) -> HybridTritVec {
+        vec.into_repr(self.repr, self.dim)
+    }
+}
+
+impl VsaBackend for HybridBackend {
+    type Vector = HybridTritVec;
+
+    fn zero(&self) -> Self::Vector {
+        self.adopt(SparseVec::new())
+    }
+
+    fn bundle(&self, a: &Self::Vector, b: &Self::Vector) -> Self::Vector {
+        self.conform(a.bundle(b, self.dim))
+    }
+
+    fn bind(&self, a: &Self::Vector, b: &Self::Vector) -> Self::Vector {
+        self.conform(a.bind(b, self.dim))
+    }
+
+    fn cosine(&self, a: &Self::Vector, b: &Self::Vector) -> f64 {
+        a.cosine(b, self.dim)
+    }
+
+    fn encode_data(
+        &self,
+        data: &[u8],
+        config: &ReversibleVSAConfig,
+        path: Option<&str>,
+    ) -> Self::Vector {
+        self.adopt(SparseVec::encode_data(data, config, path))
+    }
+
+    fn decode_data(
+        &self,
+        vec: &Self::Vector,
+        config: &ReversibleVSAConfig,
+        path: Option<&str>,
+        expected_size: usize,
+    ) -> Vec<u8> {
+        vec.to_sparse().decode_data(config, path, expected_size)
+    }
+}
+
+/// A `SparseVec` store read through a [`HybridBackend`]; see
+/// [`HybridBackend::view`].
+pub struct HybridStoreView<'a, S> {
+    backend: &'a HybridBackend,
+    store: &'a S,
+}
+
+impl<S: VectorStore<SparseVec>> VectorStore<HybridTritVec> for HybridStoreView<'_, S> {
+    fn get(&self, id: usize) -> Option<Cow<'_, HybridTritVec>> {
+        let vec = self.store.get(id)?.into_owned();
+        Some(Cow::Owned(self.backend.adopt(vec)))
+    }
+
+    fn declared_dim(&self) -> Option<usize> {
+        Some(self.backend.dim)
+    }
+}
+
 /// Minimal vector store abstraction.
 ///
-/// This matches typical kernel/runtime needs: fetch vectors by ID.
-pub trait VectorStore<V> {
-    fn get(&self, id: usize) -> Option<&V>;
+/// This matches typical kernel/runtime needs: fetch vectors by ID. In-memory
+/// stores lend their vectors; persistent ones (`lmdb_store`, `rocksdb_store`)
+/// decode an owned copy per call.
+pub trait VectorStore<V: Clone> {
+    fn get(&self, id: usize) -> Option<Cow<'_, V>>;
+
+    /// Dimension the store was created for, if it records one.
+    fn declared_dim(&self) -> Option<usize> {
+        None
+    }
 }
 
 impl VectorStore<SparseVec> for HashMap<usize, SparseVec> {
-    fn get(&self, id: usize) -> Option<&SparseVec> {
-        self.get(&id)
+    fn get(&self, id: usize) -> Option<Cow<'_, SparseVec>> {
+        self.get(&id).map(Cow::Borrowed)
+    }
+}
+
+/// A [`VectorStore`] that can also be read in id order, a batch at a time.
+///
+/// This is what index builders stream over, so they never need the whole
+/// store in memory; see [`crate::block_prefilter`].
+pub trait ScannableVectorStore: VectorStore<SparseVec> {
+    /// Up to `limit` vectors with ids `>= start`, in ascending id order.
+    fn scan(&self, start: usize, limit: usize) -> io::Result<Vec<(usize, SparseVec)>>;
+}
+
+impl ScannableVectorStore for HashMap<usize, SparseVec> {
+    fn scan(&self, start: usize, limit: usize) -> io::Result<Vec<(usize, SparseVec)>> {
+        let mut ids: Vec<usize> = self.keys().copied().filter(|&id| id >= start).collect();
+        ids.sort_unstable();
+        Ok(ids.into_iter().take(limit).map(|id| (id, self[&id].clone())).collect())
+    }
+}
+
+/// Metadata key under which persistent stores record their dimension.
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+pub(crate) const DIM_KEY: &[u8] = b"dim";
+
+/// Big-endian key so byte order matches id order in sorted stores.
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+pub(crate) fn vector_key(id: usize) -> [u8; 8] {
+    (id as u64).to_be_bytes()
+}
+
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+pub(crate) fn vector_id(key: &[u8]) -> io::Result<usize> {
+    let raw: [u8; 8] = key
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "vector key is not 8 bytes"))?;
+    Ok(u64::from_be_bytes(raw) as usize)
+}
+
+/// Serialize `vec` after checking every index fits in `dim`.
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+pub(crate) fn encode_stored_vector(id: usize, vec: &SparseVec, dim: usize) -> io::Result<Vec<u8>> {
+    if let Some(&bad) = vec.pos.iter().chain(&vec.neg).find(|&&i| i >= dim) {
+        return Err(crate::error::EmbrError::DimensionMismatch {
+            context: format!("vector {} (index {})", id, bad),
+            expected: dim,
+            found: bad + 1,
+        }
+        .into());
+    }
+    bincode::serialize(vec).map_err(io::Error::other)
+}
+
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+pub(crate) fn decode_stored_vector(bytes: &[u8]) -> io::Result<SparseVec> {
+    bincode::deserialize(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Check the dimension recorded in a store against the one requested.
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+pub(crate) fn check_stored_dim(stored: &[u8], dim: usize) -> io::Result<()> {
+    let raw: [u8; 8] = stored
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed dimension record"))?;
+    let stored = u64::from_be_bytes(raw) as usize;
+    if stored != dim {
+        return Err(crate::error::EmbrError::DimensionMismatch {
+            context: "vector store".into(),
+            expected: dim,
+            found: stored,
+        }
+        .into());
     }
+    Ok(())
 }
 
 /// Candidate generation seam. Intended to wrap e.g. `TernaryInvertedIndex`.
@@ -150,7 +362,7 @@ where
         let vec = store
             .get(id)
             .ok_or(KernelInteropError::MissingVector { id })?;
-        scored.push((id, backend.cosine(query, vec)));
+        scored.push((id, backend.cosine(query, &vec)));
     }
 
     scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
@@ -0,0 +1,149 @@
+//! LMDB-backed [`VectorStore`] for chunk vectors.
+//!
+//! Vectors live in the `vectors` database keyed by big-endian id, so scans
+//! come back in id order; the `meta` database records the dimension the
+//! store was created for, and reopening with a different one is refused.
+//! Writes are transactional: a batch is either fully stored or not at all.
+//!
+//! Only available with the `lmdb` feature.
+
+use crate::kernel_interop::{
+    check_stored_dim, decode_stored_vector, encode_stored_vector, vector_id, vector_key, ScannableVectorStore, VectorStore,
+    DIM_KEY,
+};
+use crate::vsa::SparseVec;
+use heed::types::Bytes;
+use heed::{Database, Env, EnvOpenOptions};
+use std::borrow::Cow;
+use std::io;
+use std::ops::Bound;
+use std::path::Path;
+
+/// Memory map size used by [`LmdbVectorStore::open`] (the store's size cap).
+pub const DEFAULT_MAP_SIZE: usize = 1 << 30;
+
+/// Chunk vectors persisted in an LMDB environment directory.
+pub struct LmdbVectorStore {
+    env: Env,
+    vectors: Database<Bytes, Bytes>,
+    dim: usize,
+}
+
+fn lmdb_error(err: heed::Error) -> io::Error {
+    match err {
+        heed::Error::Io(err) => err,
+        other => io::Error::other(other),
+    }
+}
+
+impl LmdbVectorStore {
+    /// Open or create a store in the directory `path` for `dim`-dimensional
+    /// vectors.
+    pub fn open<P: AsRef<Path>>(path: P, dim: usize) -> io::Result<Self> {
+        Self::open_with_map_size(path, dim, DEFAULT_MAP_SIZE)
+    }
+
+    /// Like [`LmdbVectorStore::open`] with an explicit map size in bytes.
+    pub fn open_with_map_size<P: AsRef<Path>>(path: P, dim: usize, map_size: usize) -> io::Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        // SAFETY: heed refuses to open an environment that is already open in
+        // this process, and the store never hands out references into the map.
+        let env = unsafe { EnvOpenOptions::new().map_size(map_size).max_dbs(2).open(path.as_ref()) }
+            .map_err(lmdb_error)?;
+        let mut wtxn = env.write_txn().map_err(lmdb_error)?;
+        let vectors = env.create_database(&mut wtxn, Some("vectors")).map_err(lmdb_error)?;
+        let meta: Database<Bytes, Bytes> = env.create_database(&mut wtxn, Some("meta")).map_err(lmdb_error)?;
+        match meta.get(&wtxn, DIM_KEY).map_err(lmdb_error)? {
+            Some(stored) => check_stored_dim(stored, dim)?,
+            None => meta
+                .put(&mut wtxn, DIM_KEY, &(dim as u64).to_be_bytes())
+                .map_err(lmdb_error)?,
+        }
+        wtxn.commit().map_err(lmdb_error)?;
+        Ok(Self { env, vectors, dim })
+    }
+
+    /// Dimension recorded when the store was created.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Store `vec` under `id`, replacing any previous vector.
+    pub fn put(&self, id: usize, vec: &SparseVec) -> io::Result<()> {
+        self.put_batch([(id, vec)]).map(|_| ())
+    }
+
+    /// Store several vectors in one transaction, returning how many.
+    ///
+    /// Nothing is written if any vector has an index outside the store's
+    /// dimension.
+    pub fn put_batch<'a, I>(&self, items: I) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = (usize, &'a SparseVec)>,
+    {
+        let mut wtxn = self.env.write_txn().map_err(lmdb_error)?;
+        let mut count = 0;
+        for (id, vec) in items {
+            let value = encode_stored_vector(id, vec, self.dim)?;
+            self.vectors.put(&mut wtxn, &vector_key(id), &value).map_err(lmdb_error)?;
+            count += 1;
+        }
+        wtxn.commit().map_err(lmdb_error)?;
+        Ok(count)
+    }
+
+    pub fn get_vector(&self, id: usize) -> io::Result<Option<SparseVec>> {
+        let rtxn = self.env.read_txn().map_err(lmdb_error)?;
+        match self.vectors.get(&rtxn, &vector_key(id)).map_err(lmdb_error)? {
+            Some(bytes) => decode_stored_vector(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Delete the vector stored under `id`, returning whether there was one.
+    pub fn remove(&self, id: usize) -> io::Result<bool> {
+        let mut wtxn = self.env.write_txn().map_err(lmdb_error)?;
+        let removed = self.vectors.delete(&mut wtxn, &vector_key(id)).map_err(lmdb_error)?;
+        wtxn.commit().map_err(lmdb_error)?;
+        Ok(removed)
+    }
+
+    pub fn len(&self) -> io::Result<usize> {
+        let rtxn = self.env.read_txn().map_err(lmdb_error)?;
+        Ok(self.vectors.len(&rtxn).map_err(lmdb_error)? as usize)
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Up to `limit` vectors with ids `>= start`, in ascending id order.
+    pub fn scan(&self, start: usize, limit: usize) -> io::Result<Vec<(usize, SparseVec)>> {
+        let rtxn = self.env.read_txn().map_err(lmdb_error)?;
+        let start = vector_key(start);
+        let bounds: (Bound<&[u8]>, Bound<&[u8]>) = (Bound::Included(&start[..]), Bound::Unbounded);
+        let mut out = Vec::new();
+        for entry in self.vectors.range(&rtxn, &bounds).map_err(lmdb_error)?.take(limit) {
+            let (key, value) = entry.map_err(lmdb_error)?;
+            out.push((vector_id(key)?, decode_stored_vector(value)?));
+        }
+        Ok(out)
+    }
+}
+
+/// Read errors count as missing vectors, as with the in-memory store.
+impl VectorStore<SparseVec> for LmdbVectorStore {
+    fn get(&self, id: usize) -> Option<Cow<'_, SparseVec>> {
+        self.get_vector(id).ok().flatten().map(Cow::Owned)
+    }
+
+    fn declared_dim(&self) -> Option<usize> {
+        Some(self.dim)
+    }
+}
+
+impl ScannableVectorStore for LmdbVectorStore {
+    fn scan(&self, start: usize, limit: usize) -> io::Result<Vec<(usize, SparseVec)>> {
+        LmdbVectorStore::scan(self, start, limit)
+    }
+}
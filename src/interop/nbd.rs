@@ -0,0 +1,374 @@
+//! Read-only NBD (network block device) server over an engram.
+//!
+//! Every file in the manifest is an export named by its logical path, so an
+//! engram holding a disk image can be attached with `nbd-client`, `qemu-nbd`
+//! or directly as a QEMU drive (`nbd://host:10809/disk.img`) and booted
+//! without extracting it first. Reads reconstruct only the chunks they
+//! overlap, and each chunk is checked against its correction record; a chunk
+//! that fails the check is reported to the client as `EIO`.
+//!
+//! The server speaks the fixed-newstyle handshake with `NBD_OPT_GO`,
+//! `NBD_OPT_INFO`, `NBD_OPT_LIST` and the legacy `NBD_OPT_EXPORT_NAME`, and
+//! simple (not structured) replies. Writes and trims are refused with
+//! `EPERM`. Each connection runs on its own thread; the export is immutable,
+//! so clients may open several connections to the same export. A connection
+//! is one [`crate::logging::Operation`], so everything logged while serving
+//! it shares a correlation id.
+//!
+//! Only available with the `nbd` feature.
+
+use crate::embrfs::{EmbrFS, Engram, FileEntry, Manifest};
+use crate::vsa::ReversibleVSAConfig;
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// IANA-assigned NBD port.
+pub const DEFAULT_NBD_PORT: u16 = 10809;
+
+/// Largest read request served; larger ones get `EINVAL`.
+pub const MAX_READ_LEN: u32 = 32 << 20;
+
+const NBDMAGIC: u64 = 0x4e42_444d_4147_4943;
+const IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+const OPT_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const REQUEST_MAGIC: u32 = 0x2560_9513;
+const SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const FLAG_NO_ZEROES: u16 = 1 << 1;
+const FLAG_C_FIXED_NEWSTYLE: u32 = 1 << 0;
+const FLAG_C_NO_ZEROES: u32 = 1 << 1;
+
+const FLAG_HAS_FLAGS: u16 = 1 << 0;
+const FLAG_READ_ONLY: u16 = 1 << 1;
+const FLAG_SEND_FLUSH: u16 = 1 << 2;
+const FLAG_CAN_MULTI_CONN: u16 = 1 << 8;
+const TRANSMISSION_FLAGS: u16 = FLAG_HAS_FLAGS | FLAG_READ_ONLY | FLAG_SEND_FLUSH | FLAG_CAN_MULTI_CONN;
+
+const OPT_EXPORT_NAME: u32 = 1;
+const OPT_ABORT: u32 = 2;
+const OPT_LIST: u32 = 3;
+const OPT_INFO: u32 = 6;
+const OPT_GO: u32 = 7;
+
+const REP_ACK: u32 = 1;
+const REP_SERVER: u32 = 2;
+const REP_INFO: u32 = 3;
+const REP_ERR_UNSUP: u32 = (1 << 31) | 1;
+const REP_ERR_INVALID: u32 = (1 << 31) | 3;
+const REP_ERR_UNKNOWN: u32 = (1 << 31) | 6;
+
+const INFO_EXPORT: u16 = 0;
+const INFO_BLOCK_SIZE: u16 = 3;
+
+const CMD_READ: u16 = 0;
+const CMD_WRITE: u16 = 1;
+const CMD_DISC: u16 = 2;
+const CMD_FLUSH: u16 = 3;
+const CMD_TRIM: u16 = 4;
+const CMD_WRITE_ZEROES: u16 = 6;
+
+const EPERM: u32 = 1;
+const EIO: u32 = 5;
+const EINVAL: u32 = 22;
+
+/// Option payloads larger than this are refused without being buffered.
+const MAX_OPTION_LEN: u32 = 64 << 10;
+
+/// Serves the files of one engram as read-only block devices.
+#[derive(Clone)]
+pub struct NbdServer {
+    fs: Arc<EmbrFS>,
+    by_path: Arc<HashMap<String, usize>>,
+    default_export: Option<String>,
+    config: ReversibleVSAConfig,
+}
+
+impl NbdServer {
+    /// Export every file in `manifest`. If there is exactly one file it is
+    /// also the default export (the empty name).
+    pub fn new(engram: Engram, manifest: Manifest) -> Self {
+        let by_path = manifest
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.path.clone(), i))
+            .collect();
+        let default_export = match manifest.files.as_slice() {
+            [only] => Some(only.path.clone()),
+            _ => None,
+        };
+        let mut fs = EmbrFS::new();
+        fs.engram = engram;
+        fs.manifest = manifest;
+        Self {
+            fs: Arc::new(fs),
+            by_path: Arc::new(by_path),
+            default_export,
+            config: ReversibleVSAConfig::default(),
+        }
+    }
+
+    /// Serve `path` to clients that ask for the empty export name.
+    pub fn with_default_export(mut self, path: &str) -> io::Result<Self> {
+        if !self.by_path.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file {:?} in engram", path),
+            ));
+        }
+        self.default_export = Some(path.to_string());
+        Ok(self)
+    }
+
+    pub fn with_config(mut self, config: ReversibleVSAConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn export(&self, name: &str) -> Option<&FileEntry> {
+        let path = match name {
+            "" => self.default_export.as_deref()?,
+            name => name,
+        };
+        self.by_path.get(path).map(|&i| &self.fs.manifest.files[i])
+    }
+
+    /// Accept connections on `listener` until it fails, one thread each.
+    pub fn serve_listener(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                let op = crate::logging::Operation::new("nbd");
+                let _op = op.enter();
+                crate::logging::info("nbd", format_args!("connection from {}", peer));
+                if let Err(e) = server.handle(stream) {
+                    crate::logging::warn(&format!("nbd: connection {peer}: {e}"));
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Run the handshake and transmission phases for one client.
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        writer.write_all(&NBDMAGIC.to_be_bytes())?;
+        writer.write_all(&IHAVEOPT.to_be_bytes())?;
+        writer.write_all(&(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).to_be_bytes())?;
+        writer.flush()?;
+        let client_flags = read_u32(&mut reader)?;
+        if client_flags & FLAG_C_FIXED_NEWSTYLE == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "client does not support fixed newstyle negotiation",
+            ));
+        }
+        let no_zeroes = client_flags & FLAG_C_NO_ZEROES != 0;
+
+        let Some(entry) = self.negotiate(&mut reader, &mut writer, no_zeroes)? else {
+            return Ok(());
+        };
+        self.transmit(entry, &mut reader, &mut writer)
+    }
+
+    /// Option haggling. Returns the chosen export, or `None` if the client
+    /// aborted or asked for an export that does not exist.
+    fn negotiate<R: Read, W: Write>(&self, reader: &mut R, writer: &mut W, no_zeroes: bool) -> io::Result<Option<&FileEntry>> {
+        loop {
+            if read_u64(reader)? != IHAVEOPT {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "bad option magic"));
+            }
+            let option = read_u32(reader)?;
+            let len = read_u32(reader)?;
+            if len > MAX_OPTION_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "option payload too large"));
+            }
+            let mut data = vec![0; len as usize];
+            reader.read_exact(&mut data)?;
+
+            match option {
+                OPT_EXPORT_NAME => {
+                    // No way to report an error here: unknown names just close.
+                    let Some(entry) = self.export(&String::from_utf8_lossy(&data)) else {
+                        return Ok(None);
+                    };
+                    writer.write_all(&(entry.size as u64).to_be_bytes())?;
+                    writer.write_all(&TRANSMISSION_FLAGS.to_be_bytes())?;
+                    if !no_zeroes {
+                        writer.write_all(&[0; 124])?;
+                    }
+                    writer.flush()?;
+                    return Ok(Some(entry));
+                }
+                OPT_ABORT => {
+                    option_reply(writer, option, REP_ACK, &[])?;
+                    return Ok(None);
+                }
+                OPT_LIST if !data.is_empty() => {
+                    option_reply(writer, option, REP_ERR_INVALID, b"NBD_OPT_LIST takes no data")?;
+                }
+                OPT_LIST => {
+                    for file in &self.fs.manifest.files {
+                        let mut payload = (file.path.len() as u32).to_be_bytes().to_vec();
+                        payload.extend_from_slice(file.path.as_bytes());
+                        option_reply(writer, option, REP_SERVER, &payload)?;
+                    }
+                    option_reply(writer, option, REP_ACK, &[])?;
+                }
+                OPT_INFO | OPT_GO => {
+                    let Some((name, requests)) = parse_info_request(&data) else {
+                        option_reply(writer, option, REP_ERR_INVALID, b"malformed info request")?;
+                        continue;
+                    };
+                    let Some(entry) = self.export(&name) else {
+                        let message = format!("no export {:?}", name);
+                        option_reply(writer, option, REP_ERR_UNKNOWN, message.as_bytes())?;
+                        continue;
+                    };
+
+                    let mut info = INFO_EXPORT.to_be_bytes().to_vec();
+                    info.extend_from_slice(&(entry.size as u64).to_be_bytes());
+                    info.extend_from_slice(&TRANSMISSION_FLAGS.to_be_bytes());
+                    option_reply(writer, option, REP_INFO, &info)?;
+                    if requests.contains(&INFO_BLOCK_SIZE) {
+                        let mut info = INFO_BLOCK_SIZE.to_be_bytes().to_vec();
+                        info.extend_from_slice(&1u32.to_be_bytes());
+                        // The preferred size must be a power of two.
+                        let preferred = (self.fs.manifest.chunk_size().next_power_of_two() as u32).min(MAX_READ_LEN);
+                        info.extend_from_slice(&preferred.to_be_bytes());
+                        info.extend_from_slice(&MAX_READ_LEN.to_be_bytes());
+                        option_reply(writer, option, REP_INFO, &info)?;
+                    }
+                    option_reply(writer, option, REP_ACK, &[])?;
+                    if option == OPT_GO {
+                        return Ok(Some(entry));
+                    }
+                }
+                _ => option_reply(writer, option, REP_ERR_UNSUP, &[])?,
+            }
+        }
+    }
+
+    fn transmit<R: Read, W: Write>(&self, entry: &FileEntry, reader: &mut R, writer: &mut W) -> io::Result<()> {
+        let size = entry.size as u64;
+        loop {
+            let magic = match read_u32(reader) {
+                Ok(magic) => magic,
+                // Clients may hang up between requests instead of sending NBD_CMD_DISC.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if magic != REQUEST_MAGIC {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "bad request magic"));
+            }
+            let _flags = read_u16(reader)?;
+            let command = read_u16(reader)?;
+            let cookie = read_u64(reader)?;
+            let offset = read_u64(reader)?;
+            let len = read_u32(reader)?;
+
+            match command {
+                CMD_READ => {
+                    let end = offset.checked_add(len as u64).filter(|&end| end <= size);
+                    let (Some(end), true) = (end, len <= MAX_READ_LEN) else {
+                        simple_reply(writer, EINVAL, cookie, &[])?;
+                        continue;
+                    };
+                    let mut data = Vec::with_capacity(len as usize);
+                    let read = EmbrFS::reconstruct_range(
+                        &self.fs.engram,
+                        entry,
+                        &self.config,
+                        self.fs.manifest.chunk_size(),
+                        offset as usize,
+                        end as usize,
+                        |bytes| {
+                            data.extend_from_slice(bytes);
+                            Ok(())
+                        },
+                    );
+                    match read {
+                        Ok(()) => simple_reply(writer, 0, cookie, &data)?,
+                        Err(e) => {
+                            crate::logging::warn(&format!("nbd: read {}+{} of {}: {e}", offset, len, entry.path));
+                            simple_reply(writer, EIO, cookie, &[])?;
+                        }
+                    }
+                }
+                CMD_WRITE => {
+                    io::copy(&mut reader.take(len as u64), &mut io::sink())?;
+                    simple_reply(writer, EPERM, cookie, &[])?;
+                }
+                CMD_TRIM | CMD_WRITE_ZEROES => simple_reply(writer, EPERM, cookie, &[])?,
+                CMD_FLUSH => simple_reply(writer, 0, cookie, &[])?,
+                CMD_DISC => return Ok(()),
+                _ => simple_reply(writer, EINVAL, cookie, &[])?,
+            }
+        }
+    }
+}
+
+/// Serve `server` on `addr` until the process exits.
+pub fn serve(addr: SocketAddr, server: NbdServer) -> io::Result<()> {
+    server.serve_listener(TcpListener::bind(addr)?)
+}
+
+/// Export name and requested info types of an `NBD_OPT_INFO`/`NBD_OPT_GO`.
+fn parse_info_request(data: &[u8]) -> Option<(String, Vec<u16>)> {
+    let name_len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let name = data.get(4..4 + name_len)?;
+    let rest = &data[4 + name_len..];
+    let count = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+    let requests = rest.get(2..2 + 2 * count)?;
+    if rest.len() != 2 + 2 * count {
+        return None;
+    }
+    let requests = requests
+        .chunks_exact(2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .collect();
+    Some((String::from_utf8_lossy(name).into_owned(), requests))
+}
+
+fn option_reply<W: Write>(writer: &mut W, option: u32, reply: u32, data: &[u8]) -> io::Result<()> {
+    writer.write_all(&OPT_REPLY_MAGIC.to_be_bytes())?;
+    writer.write_all(&option.to_be_bytes())?;
+    writer.write_all(&reply.to_be_bytes())?;
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()
+}
+
+fn simple_reply<W: Write>(writer: &mut W, error: u32, cookie: u64, data: &[u8]) -> io::Result<()> {
+    writer.write_all(&SIMPLE_REPLY_MAGIC.to_be_bytes())?;
+    writer.write_all(&error.to_be_bytes())?;
+    writer.write_all(&cookie.to_be_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut b = [0; 2];
+    reader.read_exact(&mut b)?;
+    Ok(u16::from_be_bytes(b))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut b = [0; 4];
+    reader.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut b = [0; 8];
+    reader.read_exact(&mut b)?;
+    Ok(u64::from_be_bytes(b))
+}
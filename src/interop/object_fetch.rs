@@ -0,0 +1,68 @@
+//! [`ObjectFetcher`] over `object_store` (S3 and compatible stores).
+//!
+//! Each bucket named in notifications is mapped to a store. Requests run on
+//! a private single-threaded tokio runtime, so the fetcher can be used from
+//! the synchronous streaming-ingest loop.
+//!
+//! Only available with the `s3` feature.
+
+use crate::stream_ingest::ObjectFetcher;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+fn store_error(err: object_store::Error) -> io::Error {
+    match err {
+        object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, err.to_string()),
+        err => io::Error::other(format!("object store: {err}")),
+    }
+}
+
+/// Fetches objects from per-bucket stores.
+pub struct ObjectStoreFetcher {
+    stores: HashMap<String, Arc<dyn ObjectStore>>,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl ObjectStoreFetcher {
+    pub fn new() -> io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        Ok(Self {
+            stores: HashMap::new(),
+            runtime,
+        })
+    }
+
+    /// Serve `bucket` from `store`.
+    pub fn with_store(mut self, bucket: impl Into<String>, store: Arc<dyn ObjectStore>) -> Self {
+        self.stores.insert(bucket.into(), store);
+        self
+    }
+
+    /// Serve `bucket` from S3, configured from the standard `AWS_*`
+    /// environment variables (`AWS_ENDPOINT` selects an S3-compatible
+    /// endpoint such as MinIO).
+    pub fn with_s3_bucket(self, bucket: &str) -> io::Result<Self> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .map_err(store_error)?;
+        Ok(self.with_store(bucket, Arc::new(store)))
+    }
+}
+
+impl ObjectFetcher for ObjectStoreFetcher {
+    fn fetch(&self, bucket: &str, key: &str) -> io::Result<Vec<u8>> {
+        let store = self.stores.get(bucket).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("no object store configured for bucket {bucket:?}"))
+        })?;
+        let path = ObjectPath::from(key);
+        self.runtime.block_on(async {
+            let bytes = store.get(&path).await.map_err(store_error)?.bytes().await.map_err(store_error)?;
+            Ok(bytes.to_vec())
+        })
+    }
+}
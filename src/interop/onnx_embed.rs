@@ -0,0 +1,163 @@
+//! [`ChunkEmbedder`] backed by a local ONNX embedding model.
+//!
+//! Models run on tract (pure Rust, CPU). The model must take `int64` inputs
+//! of shape `[1, seq]`. Inputs named `attention_mask` get ones and
+//! `token_type_ids` zeros; every other input gets the token ids. The first
+//! output is either a pooled `[1, hidden]` embedding or per-token
+//! `[1, seq, hidden]` states, which are mean-pooled. Embeddings are
+//! L2-normalized.
+//!
+//! With a `tokenizer.json` (Hugging Face `tokenizers` format) chunks are
+//! tokenized as lossy UTF-8 text. Without one, token ids are the raw byte
+//! values, for byte-level models.
+//!
+//! Only available with the `onnx` feature.
+
+use crate::semantic::ChunkEmbedder;
+use std::io;
+use std::path::Path;
+use tokenizers::Tokenizer;
+use tract_onnx::prelude::*;
+
+/// Longest token sequence fed to the model by default.
+pub const DEFAULT_MAX_TOKENS: usize = 512;
+
+type Plan = TypedRunnableModel<TypedModel>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InputRole {
+    Ids,
+    Mask,
+    TokenTypes,
+}
+
+/// A loaded ONNX embedding model and optional tokenizer.
+pub struct OnnxEmbedder {
+    plan: Plan,
+    inputs: Vec<InputRole>,
+    tokenizer: Option<Tokenizer>,
+    max_tokens: usize,
+    model_id: String,
+}
+
+fn tract_error(err: TractError) -> io::Error {
+    io::Error::other(format!("onnx: {err:#}"))
+}
+
+fn file_id(path: &Path, bytes: &[u8]) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    format!("{name}@{}", &blake3::hash(bytes).to_hex()[..16])
+}
+
+impl OnnxEmbedder {
+    /// Load `model` and, for text models, its `tokenizer.json`.
+    pub fn load<P: AsRef<Path>>(model: P, tokenizer: Option<&Path>) -> io::Result<Self> {
+        let model = model.as_ref();
+        let bytes = std::fs::read(model)?;
+        let mut model_id = file_id(model, &bytes);
+
+        let mut graph = tract_onnx::onnx().model_for_read(&mut &bytes[..]).map_err(tract_error)?;
+        let seq = graph.symbol_table.sym("S");
+        let mut inputs = Vec::new();
+        for (i, outlet) in graph.input_outlets().map_err(tract_error)?.to_vec().into_iter().enumerate() {
+            inputs.push(match graph.node(outlet.node).name.as_str() {
+                "attention_mask" => InputRole::Mask,
+                "token_type_ids" => InputRole::TokenTypes,
+                _ => InputRole::Ids,
+            });
+            let fact = InferenceFact::dt_shape(i64::datum_type(), [1.to_dim(), seq.to_dim()]);
+            graph.set_input_fact(i, fact).map_err(tract_error)?;
+        }
+        let plan = graph
+            .into_optimized()
+            .and_then(|m| m.into_runnable())
+            .map_err(tract_error)?;
+
+        let tokenizer = match tokenizer {
+            Some(path) => {
+                let bytes = std::fs::read(path)?;
+                model_id = format!("{model_id}+{}", file_id(path, &bytes));
+                Some(Tokenizer::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            plan,
+            inputs,
+            tokenizer,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            model_id,
+        })
+    }
+
+    /// Truncate inputs to at most `max_tokens` tokens (at least 1).
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self
+    }
+
+    fn token_ids(&self, data: &[u8]) -> io::Result<Vec<i64>> {
+        let mut ids: Vec<i64> = match &self.tokenizer {
+            Some(tokenizer) => tokenizer
+                .encode(String::from_utf8_lossy(data).as_ref(), true)
+                .map_err(|e| io::Error::other(format!("tokenizer: {e}")))?
+                .get_ids()
+                .iter()
+                .map(|&id| id as i64)
+                .collect(),
+            None => data.iter().map(|&b| b as i64).collect(),
+        };
+        ids.truncate(self.max_tokens);
+        if ids.is_empty() {
+            ids.push(0);
+        }
+        Ok(ids)
+    }
+}
+
+impl ChunkEmbedder for OnnxEmbedder {
+    fn model_id(&self) -> String {
+        self.model_id.clone()
+    }
+
+    fn embed(&self, data: &[u8]) -> io::Result<Vec<f32>> {
+        let ids = self.token_ids(data)?;
+        let len = ids.len();
+        let input = |values: Vec<i64>| -> io::Result<TValue> {
+            let array = tract_ndarray::Array2::from_shape_vec((1, len), values).map_err(io::Error::other)?;
+            Ok(Tensor::from(array).into())
+        };
+        let mut inputs = TVec::new();
+        for role in &self.inputs {
+            inputs.push(match role {
+                InputRole::Ids => input(ids.clone())?,
+                InputRole::Mask => input(vec![1; len])?,
+                InputRole::TokenTypes => input(vec![0; len])?,
+            });
+        }
+
+        let outputs = self.plan.run(inputs).map_err(tract_error)?;
+        let output = outputs[0].to_array_view::<f32>().map_err(tract_error)?;
+        let mut embedding: Vec<f32> = match output.shape() {
+            [1, hidden] => output.iter().take(*hidden).copied().collect(),
+            [1, seq, _] if *seq > 0 => output
+                .index_axis(tract_ndarray::Axis(0), 0)
+                .mean_axis(tract_ndarray::Axis(0))
+                .expect("non-empty sequence")
+                .into_iter()
+                .collect(),
+            shape => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("onnx: unsupported output shape {shape:?}, expected [1, hidden] or [1, seq, hidden]"),
+                ))
+            }
+        };
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        Ok(embedding)
+    }
+}
@@ -0,0 +1,156 @@
+//! RocksDB-backed [`VectorStore`] for chunk vectors.
+//!
+//! Same layout as the LMDB store: a `vectors` column family keyed by
+//! big-endian id and a `meta` column family holding the store dimension.
+//! Suited to codebooks larger than a fixed memory map, at the cost of
+//! compaction work in the background. Batched puts go through one
+//! `WriteBatch` and are atomic.
+//!
+//! Only available with the `rocksdb` feature.
+
+use crate::kernel_interop::{
+    check_stored_dim, decode_stored_vector, encode_stored_vector, vector_id, vector_key, ScannableVectorStore, VectorStore,
+    DIM_KEY,
+};
+use crate::vsa::SparseVec;
+use rocksdb::{ColumnFamily, Direction, IteratorMode, Options, WriteBatch, DB};
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+
+const VECTORS_CF: &str = "vectors";
+const META_CF: &str = "meta";
+
+/// Chunk vectors persisted in a RocksDB database directory.
+pub struct RocksDbVectorStore {
+    db: DB,
+    dim: usize,
+}
+
+fn rocksdb_error(err: rocksdb::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+impl RocksDbVectorStore {
+    /// Open or create a store in the directory `path` for `dim`-dimensional
+    /// vectors.
+    pub fn open<P: AsRef<Path>>(path: P, dim: usize) -> io::Result<Self> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db = DB::open_cf(&opts, path, [VECTORS_CF, META_CF]).map_err(rocksdb_error)?;
+        let store = Self { db, dim };
+        let meta = store.cf(META_CF);
+        match store.db.get_cf(meta, DIM_KEY).map_err(rocksdb_error)? {
+            Some(stored) => check_stored_dim(&stored, dim)?,
+            None => store
+                .db
+                .put_cf(meta, DIM_KEY, (dim as u64).to_be_bytes())
+                .map_err(rocksdb_error)?,
+        }
+        Ok(store)
+    }
+
+    fn cf(&self, name: &str) -> &ColumnFamily {
+        self.db.cf_handle(name).expect("column families are created on open")
+    }
+
+    /// Dimension recorded when the store was created.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Store `vec` under `id`, replacing any previous vector.
+    pub fn put(&self, id: usize, vec: &SparseVec) -> io::Result<()> {
+        self.put_batch([(id, vec)]).map(|_| ())
+    }
+
+    /// Store several vectors in one atomic write, returning how many.
+    ///
+    /// Nothing is written if any vector has an index outside the store's
+    /// dimension.
+    pub fn put_batch<'a, I>(&self, items: I) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = (usize, &'a SparseVec)>,
+    {
+        let vectors = self.cf(VECTORS_CF);
+        let mut batch = WriteBatch::default();
+        let mut count = 0;
+        for (id, vec) in items {
+            batch.put_cf(vectors, vector_key(id), encode_stored_vector(id, vec, self.dim)?);
+            count += 1;
+        }
+        self.db.write(batch).map_err(rocksdb_error)?;
+        Ok(count)
+    }
+
+    pub fn get_vector(&self, id: usize) -> io::Result<Option<SparseVec>> {
+        match self.db.get_cf(self.cf(VECTORS_CF), vector_key(id)).map_err(rocksdb_error)? {
+            Some(bytes) => decode_stored_vector(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Delete the vector stored under `id`, returning whether there was one.
+    pub fn remove(&self, id: usize) -> io::Result<bool> {
+        let vectors = self.cf(VECTORS_CF);
+        let key = vector_key(id);
+        if self.db.get_cf(vectors, key).map_err(rocksdb_error)?.is_none() {
+            return Ok(false);
+        }
+        self.db.delete_cf(vectors, key).map_err(rocksdb_error)?;
+        Ok(true)
+    }
+
+    /// Number of stored vectors. RocksDB keeps no exact count, so this walks
+    /// every key.
+    pub fn len(&self) -> io::Result<usize> {
+        let mut count = 0;
+        for entry in self.db.iterator_cf(self.cf(VECTORS_CF), IteratorMode::Start) {
+            entry.map_err(rocksdb_error)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> {
+        match self.db.iterator_cf(self.cf(VECTORS_CF), IteratorMode::Start).next() {
+            Some(entry) => entry.map(|_| false).map_err(rocksdb_error),
+            None => Ok(true),
+        }
+    }
+
+    /// Up to `limit` vectors with ids `>= start`, in ascending id order.
+    pub fn scan(&self, start: usize, limit: usize) -> io::Result<Vec<(usize, SparseVec)>> {
+        let start = vector_key(start);
+        let mode = IteratorMode::From(&start, Direction::Forward);
+        let mut out = Vec::new();
+        for entry in self.db.iterator_cf(self.cf(VECTORS_CF), mode).take(limit) {
+            let (key, value) = entry.map_err(rocksdb_error)?;
+            out.push((vector_id(&key)?, decode_stored_vector(&value)?));
+        }
+        Ok(out)
+    }
+
+    /// Flush memtables to disk.
+    pub fn flush(&self) -> io::Result<()> {
+        self.db.flush_cf(self.cf(VECTORS_CF)).map_err(rocksdb_error)
+    }
+}
+
+/// Read errors count as missing vectors, as with the in-memory store.
+impl VectorStore<SparseVec> for RocksDbVectorStore {
+    fn get(&self, id: usize) -> Option<Cow<'_, SparseVec>> {
+        self.get_vector(id).ok().flatten().map(Cow::Owned)
+    }
+
+    fn declared_dim(&self) -> Option<usize> {
+        Some(self.dim)
+    }
+}
+
+impl ScannableVectorStore for RocksDbVectorStore {
+    fn scan(&self, start: usize, limit: usize) -> io::Result<Vec<(usize, SparseVec)>> {
+        RocksDbVectorStore::scan(self, start, limit)
+    }
+}
@@ -0,0 +1,339 @@
+//! SQLite virtual tables over an engram.
+//!
+//! [`EngramTables::register`] adds two eponymous virtual tables to a
+//! [`rusqlite::Connection`], so engram metadata can be queried and joined
+//! with ordinary tables:
+//!
+//! - `embr_files(path, size, is_text, chunks, blake3)`: one row per
+//!   manifest entry.
+//! - `embr_search(rank, chunk_id, cosine, path)`: a table-valued function
+//!   over [`EmbrFS::similar_chunks`]. Called as `embr_search(query [, k])`
+//!   (query is TEXT or BLOB, `k` defaults to [`DEFAULT_SEARCH_K`]); a hit
+//!   referenced by several files yields one row per path, and a hit no file
+//!   references yields one row with a NULL path.
+//!
+//! ```sql
+//! SELECT f.path, f.size, s.cosine
+//! FROM embr_search('fn main', 5) AS s JOIN embr_files AS f USING (path);
+//! ```
+//!
+//! [`EngramTables::register_as`] uses another prefix than `embr`, so several
+//! engrams can be registered on one connection. Tables are read-only and
+//! live as long as the connection.
+//!
+//! Only available with the `sqlite` feature.
+
+use crate::embrfs::{EmbrFS, Engram, Manifest};
+use crate::vsa::ReversibleVSAConfig;
+use rusqlite::types::ValueRef;
+use rusqlite::vtab::{
+    eponymous_only_module, Context, IndexConstraintOp, IndexInfo, VTab, VTabConfig, VTabConnection, VTabCursor,
+    Values,
+};
+use rusqlite::{ffi, Connection};
+use std::io;
+use std::os::raw::c_int;
+use std::sync::Arc;
+
+/// Hits returned by `embr_search` when `k` is not given.
+pub const DEFAULT_SEARCH_K: usize = 10;
+
+/// Upper bound on `k` for `embr_search`.
+pub const MAX_SEARCH_K: usize = 1000;
+
+struct Source {
+    fs: EmbrFS,
+    config: ReversibleVSAConfig,
+}
+
+/// One engram, ready to be registered on SQLite connections.
+#[derive(Clone)]
+pub struct EngramTables {
+    source: Arc<Source>,
+}
+
+impl EngramTables {
+    pub fn new(engram: Engram, manifest: Manifest) -> Self {
+        Self::with_config(engram, manifest, ReversibleVSAConfig::default())
+    }
+
+    pub fn with_config(engram: Engram, manifest: Manifest, config: ReversibleVSAConfig) -> Self {
+        let mut fs = EmbrFS::new();
+        fs.engram = engram;
+        fs.manifest = manifest;
+        Self {
+            source: Arc::new(Source { fs, config }),
+        }
+    }
+
+    /// Register `embr_files` and `embr_search` on `conn`.
+    pub fn register(&self, conn: &Connection) -> io::Result<()> {
+        self.register_as(conn, "embr")
+    }
+
+    /// Register `<prefix>_files` and `<prefix>_search` on `conn`.
+    pub fn register_as(&self, conn: &Connection, prefix: &str) -> io::Result<()> {
+        conn.create_module(
+            &format!("{prefix}_files"),
+            eponymous_only_module::<FilesTab>(),
+            Some(Arc::clone(&self.source)),
+        )
+        .map_err(sqlite_error)?;
+        conn.create_module(
+            &format!("{prefix}_search"),
+            eponymous_only_module::<SearchTab>(),
+            Some(Arc::clone(&self.source)),
+        )
+        .map_err(sqlite_error)
+    }
+}
+
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::other(format!("sqlite: {err}"))
+}
+
+fn connect_source(db: &mut VTabConnection, aux: Option<&Arc<Source>>) -> rusqlite::Result<Arc<Source>> {
+    db.config(VTabConfig::Innocuous)?;
+    aux.cloned()
+        .ok_or_else(|| rusqlite::Error::ModuleError("engram tables registered without an engram".into()))
+}
+
+#[repr(C)]
+struct FilesTab {
+    /// Must be first.
+    base: ffi::sqlite3_vtab,
+    source: Arc<Source>,
+}
+
+unsafe impl<'vtab> VTab<'vtab> for FilesTab {
+    type Aux = Arc<Source>;
+    type Cursor = FilesCursor;
+
+    fn connect(db: &mut VTabConnection, aux: Option<&Arc<Source>>, _args: &[&[u8]]) -> rusqlite::Result<(String, Self)> {
+        let source = connect_source(db, aux)?;
+        Ok((
+            "CREATE TABLE x(path TEXT, size INTEGER, is_text INTEGER, chunks INTEGER, blake3 TEXT)".into(),
+            FilesTab {
+                base: ffi::sqlite3_vtab::default(),
+                source,
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        let rows = self.source.fs.manifest.files.len();
+        info.set_estimated_rows(rows as i64);
+        info.set_estimated_cost(rows as f64);
+        Ok(())
+    }
+
+    fn open(&'vtab mut self) -> rusqlite::Result<FilesCursor> {
+        Ok(FilesCursor {
+            base: ffi::sqlite3_vtab_cursor::default(),
+            source: Arc::clone(&self.source),
+            row: 0,
+        })
+    }
+}
+
+#[repr(C)]
+struct FilesCursor {
+    /// Must be first.
+    base: ffi::sqlite3_vtab_cursor,
+    source: Arc<Source>,
+    row: usize,
+}
+
+unsafe impl VTabCursor for FilesCursor {
+    fn filter(&mut self, _idx_num: c_int, _idx_str: Option<&str>, _args: &Values<'_>) -> rusqlite::Result<()> {
+        self.row = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.row += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row >= self.source.fs.manifest.files.len()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let file = &self.source.fs.manifest.files[self.row];
+        match i {
+            0 => ctx.set_result(&file.path),
+            1 => ctx.set_result(&(file.size as i64)),
+            2 => ctx.set_result(&file.is_text),
+            3 => ctx.set_result(&(file.chunks.len() as i64)),
+            _ => ctx.set_result(&file.blake3),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(self.row as i64)
+    }
+}
+
+// Hidden argument columns of embr_search.
+const SEARCH_COLUMN_QUERY: c_int = 4;
+const SEARCH_COLUMN_K: c_int = 5;
+// idx_num bits: which arguments filter() receives, in this order.
+const HAS_QUERY: c_int = 1;
+const HAS_K: c_int = 2;
+
+#[repr(C)]
+struct SearchTab {
+    /// Must be first.
+    base: ffi::sqlite3_vtab,
+    source: Arc<Source>,
+}
+
+unsafe impl<'vtab> VTab<'vtab> for SearchTab {
+    type Aux = Arc<Source>;
+    type Cursor = SearchCursor;
+
+    fn connect(db: &mut VTabConnection, aux: Option<&Arc<Source>>, _args: &[&[u8]]) -> rusqlite::Result<(String, Self)> {
+        let source = connect_source(db, aux)?;
+        Ok((
+            "CREATE TABLE x(rank INTEGER, chunk_id INTEGER, cosine REAL, path TEXT, query HIDDEN, k HIDDEN)".into(),
+            SearchTab {
+                base: ffi::sqlite3_vtab::default(),
+                source,
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        let mut query = None;
+        let mut k = None;
+        for (i, constraint) in info.constraints().enumerate() {
+            let slot = match constraint.column() {
+                SEARCH_COLUMN_QUERY => &mut query,
+                SEARCH_COLUMN_K => &mut k,
+                _ => continue,
+            };
+            if constraint.operator() != IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ {
+                continue;
+            }
+            if !constraint.is_usable() {
+                // The argument comes from a table not scanned yet; reject
+                // this plan so the planner picks an order where it is known.
+                return Err(rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_CONSTRAINT), None));
+            }
+            *slot = Some(i);
+        }
+        let mut idx_num = 0;
+        let mut argv = 0;
+        for (slot, bit) in [(query, HAS_QUERY), (k, HAS_K)] {
+            if let Some(i) = slot {
+                idx_num |= bit;
+                argv += 1;
+                let mut usage = info.constraint_usage(i);
+                usage.set_argv_index(argv);
+                usage.set_omit(true);
+            }
+        }
+        info.set_idx_num(idx_num);
+        info.set_estimated_rows(DEFAULT_SEARCH_K as i64);
+        info.set_estimated_cost(self.source.fs.engram.codebook.len() as f64);
+        Ok(())
+    }
+
+    fn open(&'vtab mut self) -> rusqlite::Result<SearchCursor> {
+        Ok(SearchCursor {
+            base: ffi::sqlite3_vtab_cursor::default(),
+            source: Arc::clone(&self.source),
+            rows: Vec::new(),
+            row: 0,
+            query: None,
+            k: DEFAULT_SEARCH_K,
+        })
+    }
+}
+
+struct SearchRow {
+    rank: i64,
+    chunk_id: i64,
+    cosine: f64,
+    path: Option<String>,
+}
+
+#[repr(C)]
+struct SearchCursor {
+    /// Must be first.
+    base: ffi::sqlite3_vtab_cursor,
+    source: Arc<Source>,
+    rows: Vec<SearchRow>,
+    row: usize,
+    query: Option<Vec<u8>>,
+    k: usize,
+}
+
+unsafe impl VTabCursor for SearchCursor {
+    fn filter(&mut self, idx_num: c_int, _idx_str: Option<&str>, args: &Values<'_>) -> rusqlite::Result<()> {
+        self.rows.clear();
+        self.row = 0;
+        if idx_num & HAS_QUERY == 0 {
+            return Err(rusqlite::Error::ModuleError(
+                "embr_search needs a query, e.g. embr_search('text', 10)".into(),
+            ));
+        }
+        self.query = match args.iter().next() {
+            Some(ValueRef::Text(b) | ValueRef::Blob(b)) => Some(b.to_vec()),
+            Some(ValueRef::Integer(n)) => Some(n.to_string().into_bytes()),
+            Some(ValueRef::Real(x)) => Some(x.to_string().into_bytes()),
+            Some(ValueRef::Null) | None => None,
+        };
+        self.k = if idx_num & HAS_K != 0 {
+            args.get::<Option<i64>>(1)?
+                .map_or(DEFAULT_SEARCH_K, |k| k.clamp(1, MAX_SEARCH_K as i64) as usize)
+        } else {
+            DEFAULT_SEARCH_K
+        };
+        // A NULL query matches nothing, as NULL arguments do in SQLite's
+        // own table-valued functions.
+        let Some(query) = &self.query else {
+            return Ok(());
+        };
+        let hits = self.source.fs.similar_chunks(query, self.k, &self.source.config);
+        for (rank, (chunk_id, cosine, paths)) in hits.into_iter().enumerate() {
+            let row = |path| SearchRow {
+                rank: rank as i64 + 1,
+                chunk_id: chunk_id as i64,
+                cosine,
+                path,
+            };
+            if paths.is_empty() {
+                self.rows.push(row(None));
+            }
+            self.rows.extend(paths.into_iter().map(|p| row(Some(p))));
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.row += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row >= self.rows.len()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let row = &self.rows[self.row];
+        match i {
+            0 => ctx.set_result(&row.rank),
+            1 => ctx.set_result(&row.chunk_id),
+            2 => ctx.set_result(&row.cosine),
+            3 => ctx.set_result(&row.path),
+            SEARCH_COLUMN_QUERY => ctx.set_result(&self.query),
+            _ => ctx.set_result(&(self.k as i64)),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(self.row as i64)
+    }
+}
@@ -0,0 +1,418 @@
+//! Sync chunk vectors and manifest metadata into Qdrant or Milvus.
+//!
+//! Every codebook vector becomes one point keyed by chunk id. Vectors are
+//! sent sparse (sorted indices with `±1/√nnz` values), so inner product in
+//! the target database equals cosine in the engram, and a 10 000-dimension
+//! chunk costs a few hundred numbers on the wire rather than ten thousand.
+//! Each point carries a JSON payload describing where the chunk is used;
+//! [`default_payload`] lists the referencing paths and byte ranges, and
+//! [`VectorSync::with_payload`] swaps in a custom mapping.
+//!
+//! [`VectorSync::sync_all`] pushes a whole engram in batches.
+//! [`VectorSync::sync_delta`] pushes only what an [`EngramDelta`] touched:
+//! changed chunks, chunks whose referencing files changed, and deletions for
+//! removed chunks. Run it after each incremental ingest to keep the
+//! collection current without a full re-upload.
+//!
+//! Both backends are driven over their REST APIs with a blocking client:
+//! Qdrant's `/collections` endpoints (sparse vector named
+//! [`QDRANT_VECTOR_NAME`]) and Milvus' `/v2/vectordb` endpoints (a
+//! `SparseFloatVector` field with an `IP` index and dynamic fields for the
+//! payload). Only available with the `vector-sync` feature.
+
+use crate::delta::EngramDelta;
+use crate::embrfs::{EmbrFS, Engram, FileEntry, Manifest};
+use crate::vsa::SparseVec;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io;
+
+/// Points per upsert request used by [`VectorSync::new`].
+pub const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// Name of the sparse vector in Qdrant collections.
+pub const QDRANT_VECTOR_NAME: &str = "chunk";
+
+/// One chunk ready to be upserted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncPoint {
+    pub id: u64,
+    /// Ascending dimension indices of the non-zero entries.
+    pub indices: Vec<u32>,
+    /// Values at `indices`, scaled to unit L2 norm.
+    pub values: Vec<f32>,
+    pub payload: Map<String, Value>,
+}
+
+/// One place a chunk appears in the manifest.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkRef<'a> {
+    pub file: &'a FileEntry,
+    /// Position of the chunk within `file.chunks`.
+    pub index: usize,
+    /// Byte offset of the chunk within the file.
+    pub offset: u64,
+    pub len: usize,
+}
+
+/// Counts from one sync run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub upserted: usize,
+    pub deleted: usize,
+    /// Upsert and delete requests sent.
+    pub requests: usize,
+}
+
+/// A vector database collection that chunk points can be written to.
+pub trait VectorSink {
+    /// Create the collection if it does not exist yet.
+    fn ensure_collection(&self) -> io::Result<()>;
+    /// Insert or replace `points`.
+    fn upsert(&self, points: &[SyncPoint]) -> io::Result<()>;
+    /// Remove the points with these ids; unknown ids are ignored.
+    fn delete(&self, ids: &[u64]) -> io::Result<()>;
+}
+
+/// `vec` as unit-length sparse entries; an index in both `pos` and `neg`
+/// cancels out.
+fn sparse_unit(vec: &SparseVec) -> (Vec<u32>, Vec<f32>) {
+    let mut entries: BTreeMap<usize, i32> = BTreeMap::new();
+    for &i in &vec.pos {
+        *entries.entry(i).or_default() += 1;
+    }
+    for &i in &vec.neg {
+        *entries.entry(i).or_default() -= 1;
+    }
+    entries.retain(|_, v| *v != 0);
+    let scale = 1.0 / (entries.len().max(1) as f32).sqrt();
+    entries
+        .into_iter()
+        .map(|(i, v)| (i as u32, v.signum() as f32 * scale))
+        .unzip()
+}
+
+/// Payload with the referencing paths and, per reference, its position,
+/// byte offset and length.
+pub fn default_payload(id: usize, refs: &[ChunkRef<'_>]) -> Map<String, Value> {
+    let paths: BTreeSet<&str> = refs.iter().map(|r| r.file.path.as_str()).collect();
+    let mut payload = Map::new();
+    payload.insert("chunk_id".into(), json!(id));
+    payload.insert("paths".into(), json!(paths));
+    payload.insert(
+        "refs".into(),
+        refs.iter()
+            .map(|r| json!({ "path": r.file.path, "index": r.index, "offset": r.offset, "len": r.len }))
+            .collect(),
+    );
+    payload
+}
+
+type PayloadFn = Box<dyn Fn(usize, &[ChunkRef<'_>]) -> Map<String, Value> + Send + Sync>;
+
+/// Batched writer of engram chunks into a [`VectorSink`].
+pub struct VectorSync<S> {
+    sink: S,
+    batch_size: usize,
+    payload: PayloadFn,
+}
+
+impl<S: VectorSink> VectorSync<S> {
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            batch_size: DEFAULT_BATCH_SIZE,
+            payload: Box::new(default_payload),
+        }
+    }
+
+    /// Points per request (at least 1).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Replace [`default_payload`] with a custom mapping.
+    pub fn with_payload<F>(mut self, payload: F) -> Self
+    where
+        F: Fn(usize, &[ChunkRef<'_>]) -> Map<String, Value> + Send + Sync + 'static,
+    {
+        self.payload = Box::new(payload);
+        self
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Points for `ids` (or every chunk when `None`), in ascending id order.
+    /// Ids missing from the codebook are skipped.
+    pub fn points(&self, engram: &Engram, manifest: &Manifest, ids: Option<&HashSet<usize>>) -> Vec<SyncPoint> {
+        let mut refs: BTreeMap<usize, Vec<ChunkRef<'_>>> = BTreeMap::new();
+        for file in &manifest.files {
+            let mut offset = 0u64;
+            for (index, &id) in file.chunks.iter().enumerate() {
+                let len = EmbrFS::chunk_len(file, index, manifest.chunk_size());
+                if ids.is_none_or(|ids| ids.contains(&id)) {
+                    refs.entry(id).or_default().push(ChunkRef {
+                        file,
+                        index,
+                        offset,
+                        len,
+                    });
+                }
+                offset += len as u64;
+            }
+        }
+
+        let mut wanted: Vec<usize> = match ids {
+            Some(ids) => ids.iter().copied().collect(),
+            None => engram.codebook.keys().copied().collect(),
+        };
+        wanted.sort_unstable();
+        wanted
+            .into_iter()
+            .filter_map(|id| {
+                let vec = engram.codebook.get(&id)?;
+                let (indices, values) = sparse_unit(vec);
+                let chunk_refs = refs.get(&id).map(Vec::as_slice).unwrap_or_default();
+                Some(SyncPoint {
+                    id: id as u64,
+                    indices,
+                    values,
+                    payload: (self.payload)(id, chunk_refs),
+                })
+            })
+            .collect()
+    }
+
+    fn push(&self, points: &[SyncPoint], deleted: &[u64]) -> io::Result<SyncSummary> {
+        self.sink.ensure_collection()?;
+        let mut summary = SyncSummary::default();
+        for batch in points.chunks(self.batch_size) {
+            self.sink.upsert(batch)?;
+            summary.upserted += batch.len();
+            summary.requests += 1;
+        }
+        for batch in deleted.chunks(self.batch_size) {
+            self.sink.delete(batch)?;
+            summary.deleted += batch.len();
+            summary.requests += 1;
+        }
+        Ok(summary)
+    }
+
+    /// Upsert every chunk in `engram`.
+    ///
+    /// Points for chunks that no longer exist are left in place; use
+    /// [`VectorSync::sync_delta`] to propagate removals.
+    pub fn sync_all(&self, engram: &Engram, manifest: &Manifest) -> io::Result<SyncSummary> {
+        self.push(&self.points(engram, manifest, None), &[])
+    }
+
+    /// Apply the changes in `delta` to the collection.
+    ///
+    /// `base_manifest` is the manifest the delta was computed against and
+    /// `engram`/`manifest` the target it produces. Chunks whose vectors changed
+    /// are upserted along with chunks referenced by added, changed or removed
+    /// files (their payloads change); removed chunks are deleted.
+    pub fn sync_delta(
+        &self,
+        base_manifest: &Manifest,
+        delta: &EngramDelta,
+        engram: &Engram,
+        manifest: &Manifest,
+    ) -> io::Result<SyncSummary> {
+        let changed_paths: HashSet<&str> = delta
+            .manifest
+            .removed
+            .iter()
+            .chain(delta.manifest.upserted.iter().map(|f| &f.path))
+            .map(String::as_str)
+            .collect();
+        let mut touched: HashSet<usize> = delta.upserted_chunks.iter().map(|(id, _)| *id as usize).collect();
+        for file in base_manifest.files.iter().chain(&delta.manifest.upserted) {
+            if changed_paths.contains(file.path.as_str()) {
+                touched.extend(file.chunks.iter().copied());
+            }
+        }
+        for id in &delta.removed_chunks {
+            touched.remove(&(*id as usize));
+        }
+        self.push(&self.points(engram, manifest, Some(&touched)), &delta.removed_chunks)
+    }
+}
+
+/// Blocking JSON request (no body when `body` is null) with an optional auth
+/// header; non-2xx replies become errors carrying the response body.
+fn send(agent: &ureq::Agent, method: &str, url: &str, auth: Option<(&str, &str)>, body: &Value) -> io::Result<Value> {
+    let mut request = agent.request(method, url).set("Content-Type", "application/json");
+    if let Some((name, value)) = auth {
+        request = request.set(name, value);
+    }
+    let sent = if body.is_null() {
+        request.call()
+    } else {
+        request.send_string(&body.to_string())
+    };
+    let response = match sent {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            let detail = response.into_string().unwrap_or_default();
+            return Err(io::Error::other(format!("{method} {url}: HTTP {code}: {detail}")));
+        }
+        Err(err) => return Err(io::Error::other(format!("{method} {url}: {err}"))),
+    };
+    let text = response.into_string()?;
+    if text.is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// A Qdrant collection reached over its REST API.
+pub struct QdrantSink {
+    agent: ureq::Agent,
+    base_url: String,
+    collection: String,
+    api_key: Option<String>,
+}
+
+impl QdrantSink {
+    /// `base_url` is the REST endpoint, e.g. `http://localhost:6333`.
+    pub fn new(base_url: impl Into<String>, collection: impl Into<String>) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            collection: collection.into(),
+            api_key: None,
+        }
+    }
+
+    /// Sent as the `api-key` header.
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    fn call(&self, method: &str, path: &str, body: &Value) -> io::Result<Value> {
+        let url = format!("{}/collections/{}{}", self.base_url, self.collection, path);
+        send(&self.agent, method, &url, self.api_key.as_deref().map(|k| ("api-key", k)), body)
+    }
+}
+
+impl VectorSink for QdrantSink {
+    fn ensure_collection(&self) -> io::Result<()> {
+        let reply = self.call("GET", "/exists", &Value::Null)?;
+        if reply["result"]["exists"].as_bool() == Some(true) {
+            return Ok(());
+        }
+        let config = json!({ "sparse_vectors": { QDRANT_VECTOR_NAME: {} } });
+        self.call("PUT", "", &config).map(|_| ())
+    }
+
+    fn upsert(&self, points: &[SyncPoint]) -> io::Result<()> {
+        let points: Vec<Value> = points
+            .iter()
+            .map(|p| {
+                json!({
+                    "id": p.id,
+                    "vector": { QDRANT_VECTOR_NAME: { "indices": p.indices, "values": p.values } },
+                    "payload": p.payload,
+                })
+            })
+            .collect();
+        self.call("PUT", "/points?wait=true", &json!({ "points": points }))
+            .map(|_| ())
+    }
+
+    fn delete(&self, ids: &[u64]) -> io::Result<()> {
+        self.call("POST", "/points/delete?wait=true", &json!({ "points": ids }))
+            .map(|_| ())
+    }
+}
+
+/// A Milvus collection reached over its v2 REST API.
+pub struct MilvusSink {
+    agent: ureq::Agent,
+    base_url: String,
+    collection: String,
+    token: Option<String>,
+}
+
+impl MilvusSink {
+    /// `base_url` is the REST endpoint, e.g. `http://localhost:19530`.
+    pub fn new(base_url: impl Into<String>, collection: impl Into<String>) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            collection: collection.into(),
+            token: None,
+        }
+    }
+
+    /// Sent as a bearer token (`user:password` or an API key).
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(format!("Bearer {}", token.into()));
+        self
+    }
+
+    /// Milvus answers HTTP 200 with a non-zero `code` on failure.
+    fn call(&self, path: &str, mut body: Value) -> io::Result<Value> {
+        body["collectionName"] = json!(self.collection);
+        let url = format!("{}/v2/vectordb{}", self.base_url, path);
+        let reply = send(&self.agent, "POST", &url, self.token.as_deref().map(|t| ("Authorization", t)), &body)?;
+        match reply["code"].as_i64() {
+            Some(0) => Ok(reply),
+            _ => Err(io::Error::other(format!("POST {url}: {reply}"))),
+        }
+    }
+}
+
+impl VectorSink for MilvusSink {
+    fn ensure_collection(&self) -> io::Result<()> {
+        let reply = self.call("/collections/has", json!({}))?;
+        if reply["data"]["has"].as_bool() == Some(true) {
+            return Ok(());
+        }
+        let schema = json!({
+            "schema": {
+                "autoId": false,
+                "enableDynamicField": true,
+                "fields": [
+                    { "fieldName": "id", "dataType": "Int64", "isPrimary": true },
+                    { "fieldName": "vector", "dataType": "SparseFloatVector" },
+                ],
+            },
+            "indexParams": [
+                { "fieldName": "vector", "indexName": "vector", "metricType": "IP", "indexType": "SPARSE_INVERTED_INDEX" },
+            ],
+        });
+        self.call("/collections/create", schema).map(|_| ())
+    }
+
+    fn upsert(&self, points: &[SyncPoint]) -> io::Result<()> {
+        let data: Vec<Value> = points
+            .iter()
+            .map(|p| {
+                let mut row = p.payload.clone();
+                let vector: Map<String, Value> = p
+                    .indices
+                    .iter()
+                    .zip(&p.values)
+                    .map(|(i, v)| (i.to_string(), json!(v)))
+                    .collect();
+                row.insert("id".into(), json!(p.id));
+                row.insert("vector".into(), Value::Object(vector));
+                Value::Object(row)
+            })
+            .collect();
+        self.call("/entities/upsert", json!({ "data": data })).map(|_| ())
+    }
+
+    fn delete(&self, ids: &[u64]) -> io::Result<()> {
+        let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+        self.call("/entities/delete", json!({ "filter": format!("id in [{}]", ids.join(",")) }))
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,590 @@
+//! Append-only record log for incremental engram saves.
+//!
+//! Rewriting a whole engram to persist a handful of new chunks is O(codebook).
+//! The append log instead stores every chunk, tombstone, manifest and metadata
+//! blob as an individual record, and each save appends only the records that
+//! changed, sealed by a commit record whose index lists them.
+//!
+//! # Layout
+//!
+//! ```text
+//! "EDNA" version:u16 reserved:u16
+//! record*                       -- chunk / tombstone / manifest / meta
+//! commit-record trailer         -- one per save
+//! ...repeated per save...
+//! ```
+//!
+//! Every record is `kind:u8 pad:[u8;3] len:u32 id:u64 payload digest:u64`,
+//! where the digest is XXH3-64 over the header and payload. A commit record's
+//! payload is `prev_commit:u64 count:u64` followed by `count` index entries of
+//! `kind:u8 id:u64 offset:u64 payload_digest:u64`. It is followed by a 12-byte
+//! trailer `commit_offset:u64 "EDNC"`, so the newest commit is found from the
+//! last 12 bytes of the file and older ones by following `prev_commit`.
+//!
+//! # Crash safety
+//!
+//! A save becomes visible only once its trailer is written. If a process dies
+//! mid-append, the file ends in a torn segment; [`AppendLog::open`] notices the
+//! bad trailer, scans forward for the last intact commit and truncates the rest.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+const MAGIC: [u8; 4] = *b"EDNA";
+pub(crate) const VERSION: u16 = 1;
+const FILE_HEADER_LEN: u64 = 8;
+const RECORD_HEADER_LEN: usize = 16;
+const RECORD_DIGEST_LEN: usize = 8;
+const TRAILER_MAGIC: [u8; 4] = *b"EDNC";
+const TRAILER_LEN: u64 = 12;
+const COMMIT_ENTRY_LEN: usize = 25;
+const NO_COMMIT: u64 = u64::MAX;
+/// Largest record a reader will allocate for; guards against corrupt lengths.
+const MAX_RECORD_LEN: u32 = 1 << 30;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RecordKind {
+    /// A codebook entry, keyed by chunk id.
+    Chunk = 1,
+    /// Removes the chunk with the same id.
+    Tombstone = 2,
+    /// The serialized manifest (id is always 0).
+    Manifest = 3,
+    /// Engram-wide metadata such as the root vector (id is always 0).
+    Meta = 4,
+    Commit = 5,
+}
+
+impl RecordKind {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(Self::Chunk),
+            2 => Some(Self::Tombstone),
+            3 => Some(Self::Manifest),
+            4 => Some(Self::Meta),
+            5 => Some(Self::Commit),
+            _ => None,
+        }
+    }
+}
+
+/// Location of a live record within the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordRef {
+    pub offset: u64,
+    /// XXH3-64 of the payload, used to skip rewriting unchanged records.
+    pub digest: u64,
+}
+
+/// A record queued for the next [`AppendLog::append`].
+#[derive(Clone, Debug)]
+pub struct PendingRecord {
+    pub kind: RecordKind,
+    pub id: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Digest used for change detection of record payloads.
+pub fn payload_digest(payload: &[u8]) -> u64 {
+    xxh3_64(payload)
+}
+
+/// Whether the file at `path` starts like an append log.
+pub fn is_append_log<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let mut magic = [0u8; 4];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+struct RawRecord {
+    kind: RecordKind,
+    payload: Vec<u8>,
+}
+
+fn corrupt(offset: u64, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("append log {} at byte {}", what, offset),
+    )
+}
+
+fn encode_record(kind: RecordKind, id: u64, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|&l| l <= MAX_RECORD_LEN)
+        .ok_or_else(|| io::Error::other("append log record too large"))?;
+    let mut out = Vec::with_capacity(RECORD_HEADER_LEN + payload.len() + RECORD_DIGEST_LEN);
+    out.push(kind as u8);
+    out.extend_from_slice(&[0u8; 3]);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&id.to_le_bytes());
+    out.extend_from_slice(payload);
+    let digest = xxh3_64(&out);
+    out.extend_from_slice(&digest.to_le_bytes());
+    Ok(out)
+}
+
+/// Read and verify one record at the reader's current position.
+fn read_record<R: Read>(r: &mut R, offset: u64) -> io::Result<RawRecord> {
+    let mut header = [0u8; RECORD_HEADER_LEN];
+    r.read_exact(&mut header).map_err(|_| corrupt(offset, "record truncated"))?;
+    let kind = RecordKind::from_u8(header[0]).ok_or_else(|| corrupt(offset, "unknown record kind"))?;
+    let len = u32::from_le_bytes(header[4..8].try_into().expect("fixed slice"));
+    if len > MAX_RECORD_LEN {
+        return Err(corrupt(offset, "record length out of range"));
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    r.read_exact(&mut payload).map_err(|_| corrupt(offset, "record truncated"))?;
+    let mut digest = [0u8; RECORD_DIGEST_LEN];
+    r.read_exact(&mut digest).map_err(|_| corrupt(offset, "record truncated"))?;
+
+    let mut hasher = Xxh3::new();
+    hasher.update(&header);
+    hasher.update(&payload);
+    if hasher.digest() != u64::from_le_bytes(digest) {
+        return Err(corrupt(offset, "record checksum mismatch"));
+    }
+    Ok(RawRecord { kind, payload })
+}
+
+fn record_len(payload_len: usize) -> u64 {
+    (RECORD_HEADER_LEN + payload_len + RECORD_DIGEST_LEN) as u64
+}
+
+struct CommitEntry {
+    kind: RecordKind,
+    id: u64,
+    offset: u64,
+    digest: u64,
+}
+
+fn encode_commit(prev: u64, entries: &[CommitEntry]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + entries.len() * COMMIT_ENTRY_LEN);
+    out.extend_from_slice(&prev.to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for e in entries {
+        out.push(e.kind as u8);
+        out.extend_from_slice(&e.id.to_le_bytes());
+        out.extend_from_slice(&e.offset.to_le_bytes());
+        out.extend_from_slice(&e.digest.to_le_bytes());
+    }
+    out
+}
+
+fn decode_commit(payload: &[u8], offset: u64) -> io::Result<(u64, Vec<CommitEntry>)> {
+    let bad = || corrupt(offset, "malformed commit");
+    if payload.len() < 16 {
+        return Err(bad());
+    }
+    let prev = u64::from_le_bytes(payload[..8].try_into().expect("fixed slice"));
+    let count = u64::from_le_bytes(payload[8..16].try_into().expect("fixed slice")) as usize;
+    let body = &payload[16..];
+    if body.len() != count.checked_mul(COMMIT_ENTRY_LEN).ok_or_else(bad)? {
+        return Err(bad());
+    }
+    let entries = body
+        .chunks_exact(COMMIT_ENTRY_LEN)
+        .map(|e| {
+            Ok(CommitEntry {
+                kind: RecordKind::from_u8(e[0]).ok_or_else(bad)?,
+                id: u64::from_le_bytes(e[1..9].try_into().expect("fixed slice")),
+                offset: u64::from_le_bytes(e[9..17].try_into().expect("fixed slice")),
+                digest: u64::from_le_bytes(e[17..25].try_into().expect("fixed slice")),
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok((prev, entries))
+}
+
+/// Check the file header at the start of `r`.
+pub(crate) fn check_header<R: Read>(r: &mut R) -> io::Result<()> {
+    let mut header = [0u8; FILE_HEADER_LEN as usize];
+    r.read_exact(&mut header)
+        .map_err(|_| corrupt(0, "header truncated"))?;
+    if header[..4] != MAGIC {
+        return Err(corrupt(0, "has a bad magic number"));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(io::Error::other(format!("unsupported append log version {}", version)));
+    }
+    Ok(())
+}
+
+fn read_at<R: Read + Seek>(r: &mut R, offset: u64) -> io::Result<RawRecord> {
+    r.seek(SeekFrom::Start(offset))?;
+    read_record(r, offset)
+}
+
+/// Read and verify the payload of the record `rec` points at.
+pub(crate) fn read_payload<R: Read + Seek>(r: &mut R, rec: RecordRef) -> io::Result<Vec<u8>> {
+    let record = read_at(r, rec.offset)?;
+    if payload_digest(&record.payload) != rec.digest {
+        return Err(corrupt(rec.offset, "record does not match its index entry"));
+    }
+    Ok(record.payload)
+}
+
+/// Locate the newest commit via the trailer and check it ends the file.
+fn tail_commit<R: Read + Seek>(r: &mut R, len: u64) -> io::Result<u64> {
+    if len < FILE_HEADER_LEN + TRAILER_LEN {
+        return Err(corrupt(len, "trailer missing"));
+    }
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    r.seek(SeekFrom::Start(len - TRAILER_LEN))?;
+    r.read_exact(&mut trailer)?;
+    if trailer[8..] != TRAILER_MAGIC {
+        return Err(corrupt(len - TRAILER_LEN, "trailer missing"));
+    }
+    let commit = u64::from_le_bytes(trailer[..8].try_into().expect("fixed slice"));
+    if commit < FILE_HEADER_LEN || commit >= len {
+        return Err(corrupt(len - TRAILER_LEN, "trailer out of range"));
+    }
+    let record = read_at(r, commit)?;
+    if record.kind != RecordKind::Commit || commit + record_len(record.payload.len()) + TRAILER_LEN != len {
+        return Err(corrupt(commit, "trailer does not point at the final commit"));
+    }
+    Ok(commit)
+}
+
+/// Walk records from the start, returning the last intact commit and the
+/// offset just past its trailer.
+fn scan_for_last_commit<R: Read + Seek>(r: &mut R) -> io::Result<(u64, u64)> {
+    r.seek(SeekFrom::Start(FILE_HEADER_LEN))?;
+    let mut reader = io::BufReader::new(r);
+    let mut offset = FILE_HEADER_LEN;
+    let mut last = (NO_COMMIT, FILE_HEADER_LEN);
+    while let Ok(record) = read_record(&mut reader, offset) {
+        let start = offset;
+        offset += record_len(record.payload.len());
+        if record.kind == RecordKind::Commit {
+            let mut trailer = [0u8; TRAILER_LEN as usize];
+            if reader.read_exact(&mut trailer).is_err()
+                || trailer[8..] != TRAILER_MAGIC
+                || u64::from_le_bytes(trailer[..8].try_into().expect("fixed slice")) != start
+            {
+                break;
+            }
+            offset += TRAILER_LEN;
+            last = (start, offset);
+        }
+    }
+    Ok(last)
+}
+
+/// Find the newest complete commit in a log of `len` bytes.
+///
+/// Returns `(commit_offset, end)`, where `end` is where the valid log stops;
+/// anything past it is a torn tail. `commit_offset` is `u64::MAX` when the
+/// log holds no complete commit.
+pub(crate) fn locate_last_commit<R: Read + Seek>(r: &mut R, len: u64) -> io::Result<(u64, u64)> {
+    if len <= FILE_HEADER_LEN {
+        return Ok((NO_COMMIT, FILE_HEADER_LEN));
+    }
+    match tail_commit(r, len) {
+        Ok(commit) => Ok((commit, len)),
+        Err(_) => scan_for_last_commit(r),
+    }
+}
+
+/// Live records as of a given commit.
+#[derive(Default)]
+pub(crate) struct LogIndex {
+    pub(crate) chunks: HashMap<u64, RecordRef>,
+    pub(crate) manifest: Option<RecordRef>,
+    pub(crate) meta: Option<RecordRef>,
+}
+
+impl LogIndex {
+    /// Build the index by following commits from `last_commit` back to the
+    /// first; newer entries shadow older ones.
+    pub(crate) fn load<R: Read + Seek>(r: &mut R, last_commit: u64) -> io::Result<Self> {
+        let mut index = Self::default();
+        let mut deleted = HashSet::new();
+        let mut commit = last_commit;
+        while commit != NO_COMMIT {
+            let record = read_at(r, commit)?;
+            if record.kind != RecordKind::Commit {
+                return Err(corrupt(commit, "commit chain points at a non-commit record"));
+            }
+            let (prev, entries) = decode_commit(&record.payload, commit)?;
+            for e in entries {
+                let rec = RecordRef {
+                    offset: e.offset,
+                    digest: e.digest,
+                };
+                match e.kind {
+                    RecordKind::Chunk => {
+                        if !deleted.contains(&e.id) {
+                            index.chunks.entry(e.id).or_insert(rec);
+                        }
+                    }
+                    RecordKind::Tombstone => {
+                        if !index.chunks.contains_key(&e.id) {
+                            deleted.insert(e.id);
+                        }
+                    }
+                    RecordKind::Manifest => {
+                        index.manifest.get_or_insert(rec);
+                    }
+                    RecordKind::Meta => {
+                        index.meta.get_or_insert(rec);
+                    }
+                    RecordKind::Commit => return Err(corrupt(commit, "commit indexes another commit")),
+                }
+            }
+            if prev != NO_COMMIT && prev >= commit {
+                return Err(corrupt(commit, "commit chain is not monotonic"));
+            }
+            commit = prev;
+        }
+        Ok(index)
+    }
+
+    /// Apply a freshly written commit on top of the index.
+    fn apply(&mut self, entries: Vec<CommitEntry>) {
+        for e in entries {
+            let rec = RecordRef {
+                offset: e.offset,
+                digest: e.digest,
+            };
+            match e.kind {
+                RecordKind::Chunk => {
+                    self.chunks.insert(e.id, rec);
+                }
+                RecordKind::Tombstone => {
+                    self.chunks.remove(&e.id);
+                }
+                RecordKind::Manifest => self.manifest = Some(rec),
+                RecordKind::Meta => self.meta = Some(rec),
+                RecordKind::Commit => {}
+            }
+        }
+    }
+}
+
+/// An open append-only log with its live-record index.
+pub struct AppendLog {
+    file: File,
+    path: PathBuf,
+    index: LogIndex,
+    last_commit: u64,
+    end: u64,
+    recovered_bytes: u64,
+}
+
+impl AppendLog {
+    /// Open a log, creating it if missing.
+    ///
+    /// A torn tail left by an interrupted save is truncated back to the last
+    /// intact commit; [`AppendLog::recovered_bytes`] reports how much was cut.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let len = file.metadata()?.len();
+
+        if len == 0 {
+            let mut header = Vec::with_capacity(FILE_HEADER_LEN as usize);
+            header.extend_from_slice(&MAGIC);
+            header.extend_from_slice(&VERSION.to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes());
+            file.write_all(&header)?;
+            file.sync_data()?;
+        } else {
+            check_header(&mut file)?;
+        }
+
+        let len = len.max(FILE_HEADER_LEN);
+        let (last_commit, end) = locate_last_commit(&mut file, len)?;
+        if end < len {
+            file.set_len(end)?;
+            file.sync_data()?;
+        }
+        let index = LogIndex::load(&mut file, last_commit)?;
+
+        Ok(Self {
+            file,
+            path,
+            index,
+            last_commit,
+            end,
+            recovered_bytes: len - end,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes discarded from a torn tail when the log was opened.
+    pub fn recovered_bytes(&self) -> u64 {
+        self.recovered_bytes
+    }
+
+    /// Current size of the log in bytes.
+    pub fn len(&self) -> u64 {
+        self.end
+    }
+
+    /// True when no save has been committed yet.
+    pub fn is_empty(&self) -> bool {
+        self.last_commit == NO_COMMIT
+    }
+
+    pub fn chunk(&self, id: u64) -> Option<RecordRef> {
+        self.index.chunks.get(&id).copied()
+    }
+
+    pub fn chunk_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.index.chunks.keys().copied()
+    }
+
+    pub fn manifest(&self) -> Option<RecordRef> {
+        self.index.manifest
+    }
+
+    pub fn meta(&self) -> Option<RecordRef> {
+        self.index.meta
+    }
+
+    /// Read and verify the payload of a live record.
+    pub fn read(&self, r: RecordRef) -> io::Result<Vec<u8>> {
+        read_payload(&mut &self.file, r)
+    }
+
+    /// Append `records` as one commit. Returns the number of bytes written.
+    ///
+    /// Nothing is written for an empty batch. The commit is synced to disk
+    /// before the in-memory index is updated.
+    pub fn append(&mut self, records: Vec<PendingRecord>) -> io::Result<u64> {
+        if records.is_empty() {
+            return Ok(0);
+        }
+        let start = self.end;
+        let result = self.write_segment(start, &records);
+        if result.is_err() {
+            // Best effort: drop the partial segment so later appends stay reachable.
+            let _ = self.file.set_len(start);
+        }
+        let (commit, entries) = result?;
+        self.index.apply(entries);
+        self.last_commit = commit;
+        Ok(self.end - start)
+    }
+
+    fn write_segment(&mut self, start: u64, records: &[PendingRecord]) -> io::Result<(u64, Vec<CommitEntry>)> {
+        self.file.seek(SeekFrom::Start(start))?;
+        let mut writer = BufWriter::new(&self.file);
+        let mut offset = start;
+        let mut entries = Vec::with_capacity(records.len());
+        for rec in records {
+            if rec.kind == RecordKind::Commit {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "commit records are written by the log itself"));
+            }
+            let bytes = encode_record(rec.kind, rec.id, &rec.payload)?;
+            writer.write_all(&bytes)?;
+            entries.push(CommitEntry {
+                kind: rec.kind,
+                id: rec.id,
+                offset,
+                digest: payload_digest(&rec.payload),
+            });
+            offset += bytes.len() as u64;
+        }
+
+        let commit = offset;
+        let bytes = encode_record(RecordKind::Commit, 0, &encode_commit(self.last_commit, &entries))?;
+        writer.write_all(&bytes)?;
+        writer.write_all(&commit.to_le_bytes())?;
+        writer.write_all(&TRAILER_MAGIC)?;
+        writer.flush()?;
+        drop(writer);
+        self.file.sync_data()?;
+
+        self.end = commit + bytes.len() as u64 + TRAILER_LEN;
+        Ok((commit, entries))
+    }
+
+    /// Write only the live records to a fresh log at `dest` as a single commit.
+    pub fn compact_to<P: AsRef<Path>>(&self, dest: P) -> io::Result<AppendLog> {
+        self.compact_ordered_to(dest, &[])
+    }
+
+    /// [`AppendLog::compact_to`], placing chunks in the order of `order`.
+    /// Live chunks missing from `order` follow by ascending id; ids that
+    /// are not live are skipped.
+    pub fn compact_ordered_to<P: AsRef<Path>>(&self, dest: P, order: &[u64]) -> io::Result<AppendLog> {
+        let mut records = Vec::with_capacity(self.index.chunks.len() + 2);
+        for (kind, r) in [(RecordKind::Meta, self.index.meta), (RecordKind::Manifest, self.index.manifest)] {
+            if let Some(r) = r {
+                records.push(PendingRecord {
+                    kind,
+                    id: 0,
+                    payload: self.read(r)?,
+                });
+            }
+        }
+        let mut placed = HashSet::with_capacity(self.index.chunks.len());
+        let mut ids: Vec<u64> = order
+            .iter()
+            .copied()
+            .filter(|id| self.index.chunks.contains_key(id) && placed.insert(*id))
+            .collect();
+        let mut rest: Vec<u64> = self.index.chunks.keys().copied().filter(|id| !placed.contains(id)).collect();
+        rest.sort_unstable();
+        ids.extend(rest);
+        for id in ids {
+            records.push(PendingRecord {
+                kind: RecordKind::Chunk,
+                id,
+                payload: self.read(self.index.chunks[&id])?,
+            });
+        }
+
+        if dest.as_ref().exists() {
+            std::fs::remove_file(&dest)?;
+        }
+        let mut out = AppendLog::open(dest)?;
+        out.append(records)?;
+        Ok(out)
+    }
+}
+
+/// What [`crate::EmbrFS::compact`] did to a log.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactStats {
+    /// Live chunk records carried over.
+    pub chunks: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactStats {
+    /// Bytes of superseded records, tombstones and commits dropped.
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// What an incremental save wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppendStats {
+    pub chunks_written: usize,
+    pub tombstones: usize,
+    pub manifest_written: bool,
+    pub meta_written: bool,
+    pub bytes_appended: u64,
+}
@@ -0,0 +1,157 @@
+//! Async (tokio) variants of envelope and sub-engram I/O.
+//!
+//! Server integrations cannot afford to block a runtime worker on a large
+//! engram read. The functions here read and write through `tokio::fs` and
+//! push codec work (decompression, checksums, decryption, bincode) onto the
+//! blocking pool, so the synchronous APIs and these async ones share the same
+//! decoding path and cannot drift apart.
+//!
+//! [`DirectorySubEngramStore::prefetch`] loads many sub-engrams concurrently
+//! ahead of a hierarchical query; the result implements [`SubEngramStore`]
+//! and can be handed straight to the synchronous query functions.
+//!
+//! Only available with the `async` feature. Every function must be called
+//! from within a tokio runtime.
+
+use crate::embrfs::{
+    decode_sub_engram, DirectorySubEngramStore, EmbrFS, Engram, Manifest, SubEngram, SubEngramDigest, SubEngramStore,
+};
+use crate::envelope::{unwrap_auto_with_keys, wrap_or_legacy, BinaryWriteOptions, Keyring, PayloadKind};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Default number of sub-engram reads kept in flight by `prefetch`.
+pub const DEFAULT_PREFETCH_CONCURRENCY: usize = 16;
+
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(io::Error::other)?
+}
+
+/// Read a whole envelope (or legacy raw payload) and return its decoded bytes.
+pub async fn read_envelope<P: AsRef<Path>>(path: P, expected_kind: PayloadKind, keys: &Keyring) -> io::Result<Vec<u8>> {
+    let data = tokio::fs::read(path).await?;
+    let keys = keys.clone();
+    blocking(move || unwrap_auto_with_keys(expected_kind, &data, &keys)).await
+}
+
+/// Wrap `payload` according to `opts` and write it to `path`.
+pub async fn write_envelope<P: AsRef<Path>>(
+    path: P,
+    kind: PayloadKind,
+    opts: BinaryWriteOptions,
+    payload: Vec<u8>,
+) -> io::Result<()> {
+    let wrapped = blocking(move || wrap_or_legacy(kind, opts, &payload)).await?;
+    tokio::fs::write(path, wrapped).await
+}
+
+/// Async [`EmbrFS::load_engram_with_keys`], including part-file and rkyv engrams.
+pub async fn load_engram<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Engram> {
+    let path = path.as_ref().to_path_buf();
+    let keys = keys.clone();
+    blocking(move || Ok(EmbrFS::load_engram_with_keys(path, &keys)?)).await
+}
+
+/// Async [`EmbrFS::load_manifest_with_keys`].
+pub async fn load_manifest<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Manifest> {
+    let path = path.as_ref().to_path_buf();
+    let keys = keys.clone();
+    blocking(move || Ok(EmbrFS::load_manifest_with_keys(path, &keys)?)).await
+}
+
+/// Sub-engrams loaded ahead of time by [`DirectorySubEngramStore::prefetch`].
+#[derive(Clone, Debug, Default)]
+pub struct PrefetchedSubEngrams {
+    loaded: HashMap<String, SubEngram>,
+    missing: Vec<String>,
+}
+
+impl PrefetchedSubEngrams {
+    pub fn len(&self) -> usize {
+        self.loaded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loaded.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&SubEngram> {
+        self.loaded.get(id)
+    }
+
+    /// Requested ids that were absent or failed to decode, sorted.
+    pub fn missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    pub fn into_inner(self) -> HashMap<String, SubEngram> {
+        self.loaded
+    }
+}
+
+impl SubEngramStore for PrefetchedSubEngrams {
+    fn load(&self, id: &str) -> Option<SubEngram> {
+        self.loaded.get(id).cloned()
+    }
+}
+
+async fn read_sub_engram(id: String, path: PathBuf, digest: Option<SubEngramDigest>) -> Option<SubEngram> {
+    let data = tokio::fs::read(path).await.ok()?;
+    tokio::task::spawn_blocking(move || decode_sub_engram(&id, &data, digest.as_ref()).ok())
+        .await
+        .ok()
+        .flatten()
+}
+
+impl DirectorySubEngramStore {
+    /// Async [`SubEngramStore::load`].
+    pub async fn load_async(&self, id: &str) -> Option<SubEngram> {
+        read_sub_engram(id.to_string(), self.path_for_id(id), self.digest_for(id).cloned()).await
+    }
+
+    /// Load `ids` with at most `concurrency` reads in flight.
+    ///
+    /// Ids that cannot be loaded are reported in
+    /// [`PrefetchedSubEngrams::missing`] rather than failing the batch,
+    /// matching how [`SubEngramStore::load`] treats them.
+    pub async fn prefetch<I, S>(&self, ids: I, concurrency: usize) -> PrefetchedSubEngrams
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+        let mut requested: Vec<String> = Vec::new();
+        for id in ids {
+            let id = id.into();
+            let path = self.path_for_id(&id);
+            let digest = self.digest_for(&id).cloned();
+            let permits = Arc::clone(&permits);
+            requested.push(id.clone());
+            tasks.spawn(async move {
+                // The semaphore is never closed, so acquiring only waits.
+                let _permit = permits.acquire_owned().await;
+                (id.clone(), read_sub_engram(id, path, digest).await)
+            });
+        }
+
+        let mut loaded = HashMap::with_capacity(requested.len());
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((id, Some(sub))) = joined {
+                loaded.insert(id, sub);
+            }
+        }
+        let mut missing: Vec<String> = requested.into_iter().filter(|id| !loaded.contains_key(id)).collect();
+        missing.sort();
+        missing.dedup();
+        PrefetchedSubEngrams { loaded, missing }
+    }
+}
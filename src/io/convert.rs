@@ -0,0 +1,232 @@
+//! Rewriting an engram into another on-disk format (`embeddenator convert`).
+//!
+//! The engram is written to a staging directory beside the destination,
+//! read back with the loader for the new format, and every file is
+//! reconstructed from both the old and the new copy and compared by digest.
+//! Only when all files match is the destination replaced, so a failed or
+//! interrupted conversion leaves it untouched.
+//!
+//! Formats differ in container only: vectors are always stored sparse, and
+//! the in-memory root representation (see [`crate::info::EngramInfo`]) is
+//! picked at load time. VSA encoding parameters are not recorded in an
+//! engram, so changing them means re-ingesting rather than converting.
+
+use crate::embrfs::{ChecksumMismatch, EmbrFS, Engram, FileEntry};
+use crate::envelope::{
+    BinaryWriteOptions, ChecksumCodec, CompressionCodec, EncryptionCodec, Keyring,
+};
+use crate::error::EmbrError;
+use crate::info::StorageFormat;
+use crate::multipart;
+use crate::seal;
+use crate::shards;
+use crate::vsa::ReversibleVSAConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Container written by [`convert`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetFormat {
+    /// Bincode in an envelope; bare bincode with default write options.
+    #[default]
+    Envelope,
+    /// rkyv archive (requires the `rkyv` feature).
+    Rkyv,
+    /// Append-only log holding the engram and the manifest.
+    AppendLog,
+    /// Protobuf in an envelope, readable across releases (requires the
+    /// `proto` feature; see [`crate::wire`]).
+    Proto,
+}
+
+/// How [`convert`] writes the engram.
+#[derive(Clone, Debug, Default)]
+pub struct ConvertOptions {
+    pub format: TargetFormat,
+    /// Compression, checksums and encryption; envelope and proto formats only.
+    pub write: BinaryWriteOptions,
+    /// Split into part files of at most this many bytes; envelope format only.
+    pub part_size: Option<u64>,
+    /// Shard the codebook by this many chunk ids (see [`crate::shards`]);
+    /// envelope format only, and not combined with `part_size`.
+    pub shard_chunks: Option<u64>,
+}
+
+/// Outcome of a successful [`convert`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConvertReport {
+    /// Format of the written engram.
+    pub format: StorageFormat,
+    /// Files reconstructed identically from the new engram.
+    pub files_verified: usize,
+    /// Bytes of file content compared.
+    pub bytes_verified: u64,
+}
+
+/// Write `embrfs.engram` to `dest` as described by `opts`, verify that every
+/// file of `embrfs.manifest` reconstructs identically from the written copy,
+/// then move it into place. `dest` may be the file the engram was loaded from.
+///
+/// A file that reconstructs differently, or whose reconstruction fails its
+/// recorded checksum, fails the conversion with [`EmbrError::ManifestMismatch`].
+pub fn convert(
+    embrfs: &EmbrFS,
+    dest: &Path,
+    opts: &ConvertOptions,
+    config: &ReversibleVSAConfig,
+) -> io::Result<ConvertReport> {
+    let w = &opts.write;
+    let plain = w.codec == CompressionCodec::None
+        && w.level.is_none()
+        && w.checksum == ChecksumCodec::None
+        && w.encryption == EncryptionCodec::None;
+    let layout = opts.part_size.is_some() || opts.shard_chunks.is_some();
+    let enveloped = matches!(opts.format, TargetFormat::Envelope | TargetFormat::Proto);
+    if !enveloped && (layout || !plain) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "compression, checksums, encryption, parts and shards apply to the envelope format only",
+        ));
+    }
+    if opts.format == TargetFormat::Proto && layout {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "parts and shards apply to the envelope format only",
+        ));
+    }
+    if opts.part_size.is_some() && opts.shard_chunks.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "an engram is either split into parts or sharded, not both",
+        ));
+    }
+    seal::ensure_unsealed(dest)?;
+    let name = dest.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "destination needs a file name")
+    })?;
+    let dir = match dest.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let staging = tempfile::Builder::new()
+        .prefix(".embeddenator-convert")
+        .tempdir_in(dir)?;
+    let staged = staging.path().join(name);
+
+    let converted = match opts.format {
+        TargetFormat::Envelope => {
+            match (opts.part_size, opts.shard_chunks) {
+                (Some(part_size), _) => {
+                    embrfs.save_engram_parts(&staged, part_size, opts.write)?;
+                }
+                (None, Some(shard_chunks)) => {
+                    embrfs.save_engram_shards(&staged, shard_chunks, opts.write)?;
+                }
+                (None, None) => embrfs.save_engram_with_options(&staged, opts.write)?,
+            }
+            let keys: Keyring = opts.write.key.into_iter().collect();
+            EmbrFS::load_engram_with_keys(&staged, &keys)?
+        }
+        TargetFormat::Rkyv => {
+            embrfs.save_engram_rkyv(&staged)?;
+            EmbrFS::load_engram(&staged)?
+        }
+        TargetFormat::Proto => {
+            embrfs.save_engram_proto(&staged, opts.write)?;
+            let keys: Keyring = opts.write.key.into_iter().collect();
+            EmbrFS::load_engram_with_keys(&staged, &keys)?
+        }
+        TargetFormat::AppendLog => {
+            embrfs.save_append_log(&staged)?;
+            let (engram, manifest) = EmbrFS::load_append_log(&staged)?;
+            if serde_json::to_value(&manifest)? != serde_json::to_value(&embrfs.manifest)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "manifest read back from the log differs",
+                ));
+            }
+            engram
+        }
+    };
+
+    let mut report = ConvertReport {
+        format: StorageFormat::detect(&staged)?,
+        files_verified: 0,
+        bytes_verified: 0,
+    };
+    let mut mismatches = Vec::new();
+    let chunk_size = embrfs.manifest.chunk_size();
+    for entry in &embrfs.manifest.files {
+        let (before, _) = file_digest(&embrfs.engram, entry, config, chunk_size)?;
+        let (after, mismatch) = file_digest(&converted, entry, config, chunk_size)?;
+        if let Some(mismatch) = mismatch {
+            mismatches.push(mismatch);
+            continue;
+        }
+        if before != after {
+            mismatches.push(ChecksumMismatch {
+                path: entry.path.clone(),
+                expected: before.to_hex().to_string(),
+                actual: after.to_hex().to_string(),
+                chunk_ids: Vec::new(),
+            });
+            continue;
+        }
+        report.files_verified += 1;
+        report.bytes_verified += entry.size as u64;
+    }
+    if !mismatches.is_empty() {
+        return Err(EmbrError::ManifestMismatch(mismatches).into());
+    }
+
+    // Parts or shards of the engram being replaced that the new copy doesn't
+    // reuse.
+    let mut stale_parts: Vec<String> = if multipart::is_part_index(dest).unwrap_or(false) {
+        multipart::read_part_index(dest)?
+            .parts
+            .into_iter()
+            .map(|p| p.file)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    stale_parts.extend(shards::existing_shard_files(dest)?);
+    // Move parts first and the file named `dest` last, so readers never see
+    // an index pointing at parts that aren't there yet.
+    let mut written: Vec<_> = fs::read_dir(staging.path())?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect::<io::Result<_>>()?;
+    written.sort_by_key(|n| n == name);
+    for file in &written {
+        fs::rename(staging.path().join(file), dir.join(file))?;
+    }
+    let kept: HashSet<_> = written
+        .iter()
+        .map(|n| n.to_string_lossy().into_owned())
+        .collect();
+    for part in stale_parts.iter().filter(|p| !kept.contains(*p)) {
+        fs::remove_file(dir.join(part))?;
+    }
+    Ok(report)
+}
+
+/// blake3 of a file as reconstructed from `engram`, and the mismatch with
+/// its recorded checksum if any. The digest is computed even for legacy
+/// entries without a checksum, so those are compared too.
+fn file_digest(
+    engram: &Engram,
+    entry: &FileEntry,
+    config: &ReversibleVSAConfig,
+    chunk_size: usize,
+) -> io::Result<(blake3::Hash, Option<ChecksumMismatch>)> {
+    let mut hasher = blake3::Hasher::new();
+    let mismatch = EmbrFS::reconstruct_file(engram, entry, config, chunk_size, |data| {
+        hasher.update(data);
+        Ok(())
+    })?;
+    Ok((hasher.finalize(), mismatch))
+}
@@ -0,0 +1,279 @@
+//! Delta envelopes: a new engram stored as a diff against a base.
+//!
+//! Shipping a nightly dataset update should not mean re-uploading every
+//! chunk. An [`EngramDelta`] names its base by content digest and carries only
+//! the chunk records that were added or changed, the ids that were removed,
+//! and the manifest entries that differ. [`EngramDelta::apply`] rebuilds the
+//! target from the base; [`flatten`] folds a chain of deltas.
+//!
+//! Digests are computed over the engram's contents (see [`engram_digest`]),
+//! not over a file, so a base may be stored with any envelope options and
+//! still be recognised. Applying checks the base digest before touching
+//! anything and the target digest afterwards.
+//!
+//! On disk a delta is an EDN1 envelope of kind [`PayloadKind::Delta`] holding
+//! the bincode-encoded [`EngramDelta`]. It is always checksummed, and may be
+//! compressed or encrypted like any other envelope.
+
+use crate::correction::{CorrectionStore, CorrectionTotals};
+use crate::embrfs::{bincode_io_error, decode_log_chunk, decode_log_meta, EncodingConfig, Engram, FileEntry, Manifest};
+use crate::retention::RetentionPolicy;
+use crate::envelope::{BinaryWriteOptions, ChecksumCodec, EnvelopeReader, EnvelopeWriter, Keyring, PayloadKind};
+use crate::signing::to_hex;
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Content digest of an engram: blake3 over its meta record followed by every
+/// chunk record in id order.
+pub fn engram_digest(engram: &Engram) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&engram.encode_meta_record()?);
+    for id in engram.chunk_record_ids() {
+        let record = engram.encode_chunk_record(id)?;
+        hasher.update(&id.to_le_bytes());
+        hasher.update(&(record.len() as u64).to_le_bytes());
+        hasher.update(&record);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Manifest changes between a base and a target.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ManifestDelta {
+    /// Paths present in the base but not the target.
+    pub removed: Vec<String>,
+    /// Entries that are new or differ from the base, in target order.
+    pub upserted: Vec<FileEntry>,
+    pub total_chunks: usize,
+    pub namespaces: BTreeMap<String, String>,
+    pub retention: Option<RetentionPolicy>,
+    pub encoding: Option<EncodingConfig>,
+}
+
+/// A target engram and manifest expressed relative to a base.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EngramDelta {
+    pub base_digest: [u8; 32],
+    pub target_digest: [u8; 32],
+    /// Target meta record (root vector and correction totals).
+    meta: Vec<u8>,
+    /// Chunk records added or changed, as `(id, record)`.
+    pub upserted_chunks: Vec<(u64, Vec<u8>)>,
+    pub removed_chunks: Vec<u64>,
+    pub manifest: ManifestDelta,
+}
+
+impl EngramDelta {
+    /// Compute the delta that turns `base` into `target`.
+    pub fn between(
+        base: &Engram,
+        base_manifest: &Manifest,
+        target: &Engram,
+        target_manifest: &Manifest,
+    ) -> io::Result<Self> {
+        let base_ids: HashSet<u64> = base.chunk_record_ids().into_iter().collect();
+        let mut upserted_chunks = Vec::new();
+        let target_ids = target.chunk_record_ids();
+        for &id in &target_ids {
+            let record = target.encode_chunk_record(id)?;
+            if !base_ids.contains(&id) || base.encode_chunk_record(id)? != record {
+                upserted_chunks.push((id, record));
+            }
+        }
+        let target_set: HashSet<u64> = target_ids.into_iter().collect();
+        let mut removed_chunks: Vec<u64> = base_ids.difference(&target_set).copied().collect();
+        removed_chunks.sort_unstable();
+
+        let base_files: HashMap<&str, &FileEntry> =
+            base_manifest.files.iter().map(|f| (f.path.as_str(), f)).collect();
+        let target_paths: HashSet<&str> = target_manifest.files.iter().map(|f| f.path.as_str()).collect();
+        let manifest = ManifestDelta {
+            removed: base_manifest
+                .files
+                .iter()
+                .filter(|f| !target_paths.contains(f.path.as_str()))
+                .map(|f| f.path.clone())
+                .collect(),
+            upserted: target_manifest
+                .files
+                .iter()
+                .filter(|f| base_files.get(f.path.as_str()) != Some(f))
+                .cloned()
+                .collect(),
+            total_chunks: target_manifest.total_chunks,
+            namespaces: target_manifest.namespaces.clone(),
+            retention: target_manifest.retention.clone(),
+            encoding: target_manifest.encoding.clone(),
+        };
+
+        Ok(Self {
+            base_digest: engram_digest(base)?,
+            target_digest: engram_digest(target)?,
+            meta: target.encode_meta_record()?,
+            upserted_chunks,
+            removed_chunks,
+            manifest,
+        })
+    }
+
+    /// True when the target is identical to the base.
+    pub fn is_empty(&self) -> bool {
+        self.base_digest == self.target_digest && self.manifest.removed.is_empty() && self.manifest.upserted.is_empty()
+    }
+
+    /// Rebuild the target from `base`.
+    ///
+    /// Fails with `InvalidInput` if `base` is not the engram this delta was
+    /// computed against. Files that are new in the target are appended after
+    /// the base's surviving entries.
+    pub fn apply(&self, base: &Engram, base_manifest: &Manifest) -> io::Result<(Engram, Manifest)> {
+        let actual = engram_digest(base)?;
+        if actual != self.base_digest {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "delta expects base engram {}, got {}",
+                    to_hex(&self.base_digest),
+                    to_hex(&actual)
+                ),
+            ));
+        }
+
+        let (root, totals): (SparseVec, CorrectionTotals) = decode_log_meta(&self.meta)?;
+        let mut codebook = base.codebook.clone();
+        let mut corrections: HashMap<u64, _> = base.corrections.iter().map(|(id, c)| (id, c.clone())).collect();
+        for id in &self.removed_chunks {
+            codebook.remove(&(*id as usize));
+            corrections.remove(id);
+        }
+        for (id, record) in &self.upserted_chunks {
+            let (vec, correction) = decode_log_chunk(record)?;
+            match vec {
+                Some(vec) => codebook.insert(*id as usize, vec),
+                None => codebook.remove(&(*id as usize)),
+            };
+            match correction {
+                Some(correction) => corrections.insert(*id, correction),
+                None => corrections.remove(id),
+            };
+        }
+        let engram = Engram {
+            root,
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, totals),
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        };
+        if engram_digest(&engram)? != self.target_digest {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "applying the delta did not produce the recorded target engram",
+            ));
+        }
+
+        let removed: HashSet<&str> = self.manifest.removed.iter().map(String::as_str).collect();
+        let mut upserts: HashMap<&str, &FileEntry> =
+            self.manifest.upserted.iter().map(|f| (f.path.as_str(), f)).collect();
+        let mut files: Vec<FileEntry> = base_manifest
+            .files
+            .iter()
+            .filter(|f| !removed.contains(f.path.as_str()))
+            .map(|f| upserts.remove(f.path.as_str()).unwrap_or(f).clone())
+            .collect();
+        files.extend(
+            self.manifest
+                .upserted
+                .iter()
+                .filter(|f| upserts.contains_key(f.path.as_str()))
+                .cloned(),
+        );
+        // The delta carries no inode numbers: the base's stay, and new paths
+        // are numbered after them as an ingest would.
+        let mut inodes = base_manifest.inodes.clone();
+        for file in &self.manifest.upserted {
+            inodes.assign(&file.path);
+        }
+        let manifest = Manifest {
+            files,
+            total_chunks: self.manifest.total_chunks,
+            namespaces: self.manifest.namespaces.clone(),
+            retention: self.manifest.retention.clone(),
+            encoding: self.manifest.encoding.clone(),
+            inodes,
+            path_normalization: base_manifest.path_normalization,
+            // Deltas do not carry provenance: the target was not ingested
+            // as such here.
+            provenance: None,
+            // Deltas diff the engram's own codebook, not key groups.
+            namespace_keys: None,
+        };
+        Ok((engram, manifest))
+    }
+
+    /// Write the delta as an envelope. Checksums default to XXH3 when `opts`
+    /// does not pick a codec.
+    pub fn save<P: AsRef<Path>>(&self, path: P, opts: BinaryWriteOptions) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?), opts)?.flush()
+    }
+
+    /// The envelope [`EngramDelta::save`] would write, in memory.
+    pub fn to_bytes(&self, opts: BinaryWriteOptions) -> io::Result<Vec<u8>> {
+        self.write_to(Vec::new(), opts)
+    }
+
+    fn write_to<W: Write>(&self, out: W, mut opts: BinaryWriteOptions) -> io::Result<W> {
+        if opts.checksum == ChecksumCodec::None {
+            opts.checksum = ChecksumCodec::Xxh3;
+        }
+        let mut writer = EnvelopeWriter::new(out, PayloadKind::Delta, opts)?;
+        bincode::serialize_into(&mut writer, self).map_err(|e| bincode_io_error(*e))?;
+        writer.finish()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::load_with_keys(path, &Keyring::default())
+    }
+
+    /// Load a delta, decrypting it with `keys` if needed.
+    pub fn load_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let path = path.as_ref();
+        if PayloadKind::sniff_file(path)? != Some(PayloadKind::Delta) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a delta envelope"));
+        }
+        Self::read_from(BufReader::new(File::open(path)?), keys)
+    }
+
+    /// Decode a delta from bytes produced by [`EngramDelta::to_bytes`] or
+    /// read from a file written by [`EngramDelta::save`].
+    pub fn from_bytes(data: &[u8], keys: &Keyring) -> io::Result<Self> {
+        if PayloadKind::sniff(data) != Some(PayloadKind::Delta) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a delta envelope"));
+        }
+        Self::read_from(data, keys)
+    }
+
+    fn read_from<R: Read>(input: R, keys: &Keyring) -> io::Result<Self> {
+        let mut reader = EnvelopeReader::with_keys(input, PayloadKind::Delta, keys)?;
+        let delta = bincode::deserialize_from(&mut reader).map_err(|e| bincode_io_error(*e))?;
+        io::copy(&mut reader, &mut io::sink())?;
+        Ok(delta)
+    }
+}
+
+/// Apply `deltas` to `base` in order, returning the final engram and manifest.
+pub fn flatten(base: &Engram, base_manifest: &Manifest, deltas: &[EngramDelta]) -> io::Result<(Engram, Manifest)> {
+    let Some((first, rest)) = deltas.split_first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no deltas to flatten"));
+    };
+    let (mut engram, mut manifest) = first.apply(base, base_manifest)?;
+    for delta in rest {
+        (engram, manifest) = delta.apply(&engram, &manifest)?;
+    }
+    Ok((engram, manifest))
+}
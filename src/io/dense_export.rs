@@ -0,0 +1,239 @@
+//! Dense matrix export of codebook vectors for ANN tooling.
+//!
+//! Chunk vectors are sparse ternary; FAISS, Annoy, hnswlib and friends want
+//! dense rows. This module expands every codebook vector to a `DIM`-wide row
+//! and writes the matrix in one of two forms:
+//!
+//! - [`to_npy`]: `vectors.npy` (shape `(rows, DIM)`, `int8` trits or
+//!   `float32`) plus `ids.npy` (`uint64` chunk ids, one per row). Loadable
+//!   with `numpy.load` and from there into any ANN library.
+//! - [`to_faiss`]: a single `chunks.faiss` file holding an `IndexIDMap`
+//!   around an `IndexFlatIP`, readable with `faiss.read_index`. Searches
+//!   return chunk ids directly.
+//!
+//! Float rows are scaled to unit length, so inner product equals
+//! [`SparseVec::cosine`] and scores line up with the built-in retrieval.
+//! Bundled vectors can carry an index in both `pos` and `neg`; such entries
+//! cancel to `0` in the dense row, so cosines involving them can differ
+//! slightly from the sparse computation.
+//! Rows are in ascending chunk id order and are streamed to disk, so memory
+//! use stays at one row regardless of codebook size.
+
+use crate::embrfs::Engram;
+use crate::vsa::{SparseVec, DIM};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+/// File name of the dense matrix written by [`to_npy`].
+pub const VECTORS_NPY: &str = "vectors.npy";
+/// File name of the row-to-chunk-id array written by [`to_npy`].
+pub const IDS_NPY: &str = "ids.npy";
+/// File name of the index written by [`to_faiss`].
+pub const FAISS_INDEX: &str = "chunks.faiss";
+
+/// Element type of exported `.npy` matrices.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DenseDtype {
+    /// Raw trits: `-1`, `0` or `1`.
+    Int8,
+    /// Trits scaled so each non-empty row has unit L2 norm.
+    #[default]
+    Float32,
+}
+
+/// Shape of an exported matrix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DenseSummary {
+    pub rows: usize,
+    pub dim: usize,
+}
+
+fn sorted_ids(engram: &Engram) -> Vec<usize> {
+    let mut ids: Vec<usize> = engram.codebook.keys().copied().collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// Expand `vec` into `row` as trits, rejecting indices outside `DIM`.
+///
+/// An index present in both `pos` and `neg` cancels to `0`. Returns the
+/// number of non-zero entries.
+pub(crate) fn fill_row(id: usize, vec: &SparseVec, row: &mut [i8]) -> io::Result<usize> {
+    row.fill(0);
+    let dim = row.len();
+    for (&idx, trit) in vec.pos.iter().map(|i| (i, 1)).chain(vec.neg.iter().map(|i| (i, -1))) {
+        let slot = row.get_mut(idx).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("chunk {id} has index {idx} outside dimension {dim}"),
+            )
+        })?;
+        *slot += trit;
+    }
+    Ok(row.iter().filter(|&&t| t != 0).count())
+}
+
+fn write_f32_row<W: Write>(out: &mut W, row: &[i8], nnz: usize) -> io::Result<()> {
+    let scale = if nnz == 0 { 0.0 } else { 1.0 / (nnz as f32).sqrt() };
+    for &t in row {
+        out.write_all(&(t as f32 * scale).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Write an NPY 1.0 header; the data that follows must match `descr` and `shape`.
+pub(crate) fn write_npy_header<W: Write>(out: &mut W, descr: &str, shape: &[usize]) -> io::Result<()> {
+    let shape = match shape {
+        [n] => format!("({n},)"),
+        dims => format!("({})", dims.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {shape}, }}");
+    // Magic (6) + version (2) + length (2) + header, padded to 64 bytes and
+    // terminated by a newline.
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())
+}
+
+/// Element type and shape from an NPY header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NpyHeader {
+    pub descr: String,
+    pub shape: Vec<usize>,
+}
+
+/// Read an NPY header of any version, leaving `input` at the data. Arrays
+/// in Fortran order are refused, as nothing here writes or reads them.
+pub(crate) fn read_npy_header<R: Read>(input: &mut R) -> io::Result<NpyHeader> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not a C-order NPY array: {msg}"));
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic[..6] != b"\x93NUMPY" {
+        return Err(invalid("bad magic"));
+    }
+    let len = match magic[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            input.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            input.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        _ => return Err(invalid("unknown version")),
+    };
+    let mut header = vec![0u8; len];
+    input.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|_| invalid("header is not text"))?;
+    let value = |key: &str| {
+        let at = header.find(&format!("'{key}':"))? + key.len() + 3;
+        Some(header[at..].trim_start())
+    };
+    if !value("fortran_order").is_some_and(|v| v.starts_with("False")) {
+        return Err(invalid("fortran_order is not False"));
+    }
+    let descr = value("descr")
+        .and_then(|v| v.strip_prefix('\''))
+        .and_then(|v| v.split('\'').next())
+        .ok_or_else(|| invalid("no descr"))?
+        .to_string();
+    let shape = value("shape")
+        .and_then(|v| v.strip_prefix('('))
+        .and_then(|v| v.split(')').next())
+        .ok_or_else(|| invalid("no shape"))?
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| d.parse().map_err(|_| invalid("shape is not a tuple of integers")))
+        .collect::<io::Result<Vec<usize>>>()?;
+    Ok(NpyHeader { descr, shape })
+}
+
+/// Write `ids` as `ids.npy` into `dir`.
+pub(crate) fn write_ids_npy(dir: &Path, ids: &[usize]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(dir.join(IDS_NPY))?);
+    write_npy_header(&mut out, "<u8", &[ids.len()])?;
+    for &id in ids {
+        out.write_all(&(id as u64).to_le_bytes())?;
+    }
+    out.flush()
+}
+
+/// Write `vectors.npy` and `ids.npy` into `dir`, creating it if needed.
+pub fn to_npy<P: AsRef<Path>>(engram: &Engram, dtype: DenseDtype, dir: P) -> io::Result<DenseSummary> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+    let ids = sorted_ids(engram);
+
+    let mut out = BufWriter::new(File::create(dir.join(VECTORS_NPY))?);
+    let descr = match dtype {
+        DenseDtype::Int8 => "|i1",
+        DenseDtype::Float32 => "<f4",
+    };
+    write_npy_header(&mut out, descr, &[ids.len(), DIM])?;
+    let mut row = vec![0i8; DIM];
+    for &id in &ids {
+        let nnz = fill_row(id, &engram.codebook[&id], &mut row)?;
+        match dtype {
+            DenseDtype::Int8 => out.write_all(&row.iter().map(|&t| t as u8).collect::<Vec<_>>())?,
+            DenseDtype::Float32 => write_f32_row(&mut out, &row, nnz)?,
+        }
+    }
+    out.flush()?;
+    write_ids_npy(dir, &ids)?;
+
+    Ok(DenseSummary {
+        rows: ids.len(),
+        dim: DIM,
+    })
+}
+
+/// FAISS `MetricType::METRIC_INNER_PRODUCT`.
+const FAISS_METRIC_INNER_PRODUCT: i32 = 0;
+
+/// Common `Index` fields as serialized by FAISS' `write_index_header`.
+fn write_faiss_header<W: Write>(out: &mut W, fourcc: &[u8; 4], rows: usize) -> io::Result<()> {
+    out.write_all(fourcc)?;
+    out.write_all(&(DIM as i32).to_le_bytes())?;
+    out.write_all(&(rows as i64).to_le_bytes())?;
+    // Two legacy fields FAISS still writes and ignores on read.
+    out.write_all(&(1i64 << 20).to_le_bytes())?;
+    out.write_all(&(1i64 << 20).to_le_bytes())?;
+    out.write_all(&[1u8])?; // is_trained
+    out.write_all(&FAISS_METRIC_INNER_PRODUCT.to_le_bytes())
+}
+
+/// Write `chunks.faiss` into `dir`, creating it if needed.
+///
+/// The index stores unit-length float rows, so search scores are cosines.
+pub fn to_faiss<P: AsRef<Path>>(engram: &Engram, dir: P) -> io::Result<DenseSummary> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+    let ids = sorted_ids(engram);
+
+    let mut out = BufWriter::new(File::create(dir.join(FAISS_INDEX))?);
+    write_faiss_header(&mut out, b"IxMp", ids.len())?;
+    write_faiss_header(&mut out, b"IxFI", ids.len())?;
+    out.write_all(&((ids.len() * DIM) as u64).to_le_bytes())?;
+    let mut row = vec![0i8; DIM];
+    for &id in &ids {
+        let nnz = fill_row(id, &engram.codebook[&id], &mut row)?;
+        write_f32_row(&mut out, &row, nnz)?;
+    }
+    out.write_all(&(ids.len() as u64).to_le_bytes())?;
+    for &id in &ids {
+        out.write_all(&(id as i64).to_le_bytes())?;
+    }
+    out.flush()?;
+
+    Ok(DenseSummary {
+        rows: ids.len(),
+        dim: DIM,
+    })
+}
@@ -0,0 +1,442 @@
+//! Serving one logical engram from several nodes.
+//!
+//! [`crate::shards`] splits a codebook across files on one host; this module
+//! splits it across hosts. [`split`] partitions an engram's chunks between N
+//! nodes by [`Placement`], either hashing chunk ids or cutting the id space
+//! into contiguous ranges, and a [`DistributedEngram`] coordinator in front
+//! of the nodes answers as the whole engram would: it routes chunk reads to
+//! the node holding each chunk, and fans similarity searches out to every
+//! node at once and merges their hits.
+//!
+//! A node is anything implementing [`EngramNode`]. [`LocalNode`] serves a
+//! partition held in memory; a node on another machine is an implementation
+//! of the trait over whatever transport connects the hosts. Requests to
+//! different nodes run on their own threads, so a slow node costs the
+//! latency of that node, not the sum over all of them.
+//!
+//! The coordinator keeps the root vector and the correction totals of the
+//! original engram. Each partition is a regular [`Engram`] whose root
+//! bundles only its own chunks and whose correction totals are empty.
+
+use crate::correction::{ChunkCorrection, CorrectionStore, CorrectionTotals};
+use crate::embrfs::{ChecksumMismatch, EmbrFS, Engram, FileEntry};
+use crate::error::EmbrError;
+use crate::retrieval::TernaryInvertedIndex;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Chunk ids a file read asks the nodes for at once.
+const FETCH_BATCH: usize = 256;
+
+/// How chunk ids are assigned to nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Placement {
+    /// By a hash of the chunk id, spreading chunks evenly whatever their ids.
+    Hash,
+    /// Node `n` holds ids `[n * span, (n + 1) * span)`; the last node also
+    /// holds every id past the end. Keeps the chunks of a file, which are
+    /// mostly numbered in sequence, on few nodes.
+    Range { span: u64 },
+}
+
+impl Placement {
+    /// Ranges that cut `engram`'s ids, up to its highest, into `nodes`
+    /// equal parts.
+    pub fn ranges_for(engram: &Engram, nodes: usize) -> Self {
+        let end = engram.chunk_record_ids().last().map_or(0, |&id| id + 1);
+        Placement::Range {
+            span: end.div_ceil(nodes.max(1) as u64).max(1),
+        }
+    }
+
+    /// Node, of `nodes`, that holds `chunk_id`.
+    pub fn node_for(&self, chunk_id: usize, nodes: usize) -> usize {
+        let nodes = nodes.max(1);
+        match *self {
+            Placement::Hash => (mix(chunk_id as u64) % nodes as u64) as usize,
+            Placement::Range { span } => ((chunk_id as u64 / span.max(1)) as usize).min(nodes - 1),
+        }
+    }
+}
+
+/// SplitMix64 finalizer, so sequential ids spread over the nodes.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Partition `engram` into `nodes` engrams by `placement`. Every chunk
+/// vector and correction lands in exactly one of them.
+pub fn split(engram: &Engram, nodes: usize, placement: Placement) -> Vec<Engram> {
+    let nodes = nodes.max(1);
+    let mut codebooks: Vec<HashMap<usize, SparseVec>> = vec![HashMap::new(); nodes];
+    let mut corrections: Vec<HashMap<u64, ChunkCorrection>> = vec![HashMap::new(); nodes];
+    for (&id, vec) in &engram.codebook {
+        codebooks[placement.node_for(id, nodes)].insert(id, vec.clone());
+    }
+    for (id, correction) in engram.corrections.iter() {
+        corrections[placement.node_for(id as usize, nodes)].insert(id, correction.clone());
+    }
+    codebooks
+        .into_iter()
+        .zip(corrections)
+        .map(|(codebook, corrections)| {
+            let mut part = Engram {
+                root: SparseVec::new(),
+                codebook,
+                corrections: CorrectionStore::from_parts(corrections, CorrectionTotals::default()),
+                root_counts: None,
+                precision: None,
+                differential: None,
+                schema: None,
+            };
+            part.rebuild_root();
+            part
+        })
+        .collect()
+}
+
+/// A chunk's vector and correction, either of which a node may lack.
+pub type ChunkRecord = (Option<SparseVec>, Option<ChunkCorrection>);
+
+/// One partition of a distributed engram, as the coordinator sees it.
+pub trait EngramNode: Send + Sync {
+    /// Chunk ids the node holds a vector or correction for, ascending.
+    fn chunk_ids(&self) -> io::Result<Vec<usize>>;
+
+    /// Records for `ids`, in the same order.
+    fn fetch(&self, ids: &[usize]) -> io::Result<Vec<ChunkRecord>>;
+
+    /// The node's `k` chunks most similar to `query` with their cosine,
+    /// most similar first.
+    fn search(&self, query: &SparseVec, k: usize) -> io::Result<Vec<(usize, f64)>>;
+}
+
+/// A partition served from memory, with an inverted index for search.
+pub struct LocalNode {
+    engram: Engram,
+    index: TernaryInvertedIndex,
+}
+
+impl LocalNode {
+    pub fn new(engram: Engram) -> Self {
+        let index = engram.build_codebook_index();
+        Self { engram, index }
+    }
+
+    pub fn engram(&self) -> &Engram {
+        &self.engram
+    }
+}
+
+impl EngramNode for LocalNode {
+    fn chunk_ids(&self) -> io::Result<Vec<usize>> {
+        Ok(self
+            .engram
+            .chunk_record_ids()
+            .into_iter()
+            .map(|id| id as usize)
+            .collect())
+    }
+
+    fn fetch(&self, ids: &[usize]) -> io::Result<Vec<ChunkRecord>> {
+        Ok(ids
+            .iter()
+            .map(|&id| {
+                (
+                    self.engram.codebook.get(&id).cloned(),
+                    self.engram.corrections.get(id as u64).cloned(),
+                )
+            })
+            .collect())
+    }
+
+    fn search(&self, query: &SparseVec, k: usize) -> io::Result<Vec<(usize, f64)>> {
+        let candidate_k = k.saturating_mul(10).max(200);
+        Ok(self
+            .engram
+            .query_codebook_with_index(&self.index, query, candidate_k, k)
+            .into_iter()
+            .map(|hit| (hit.id, hit.cosine))
+            .collect())
+    }
+}
+
+/// A similarity search hit and the node that returned it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DistributedHit {
+    pub chunk_id: usize,
+    pub cosine: f64,
+    pub node: usize,
+}
+
+/// Coordinator presenting partitioned nodes as one engram.
+pub struct DistributedEngram {
+    root: SparseVec,
+    totals: CorrectionTotals,
+    placement: Placement,
+    nodes: Vec<Box<dyn EngramNode>>,
+}
+
+impl DistributedEngram {
+    /// Coordinate `nodes`, partitioned by `placement` in this order, for an
+    /// engram with the given root vector.
+    ///
+    /// # Panics
+    ///
+    /// If `nodes` is empty.
+    pub fn new(root: SparseVec, placement: Placement, nodes: Vec<Box<dyn EngramNode>>) -> Self {
+        assert!(!nodes.is_empty(), "a distributed engram needs at least one node");
+        Self {
+            root,
+            totals: CorrectionTotals::default(),
+            placement,
+            nodes,
+        }
+    }
+
+    /// [`split`] `engram` into `nodes` in-process [`LocalNode`]s.
+    pub fn local(engram: &Engram, nodes: usize, placement: Placement) -> Self {
+        let nodes = split(engram, nodes, placement)
+            .into_iter()
+            .map(|part| Box::new(LocalNode::new(part)) as Box<dyn EngramNode>)
+            .collect();
+        Self {
+            root: engram.root.clone(),
+            totals: engram.corrections.totals(),
+            placement,
+            nodes,
+        }
+    }
+
+    pub fn root(&self) -> &SparseVec {
+        &self.root
+    }
+
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Node holding `chunk_id`.
+    pub fn node_for(&self, chunk_id: usize) -> usize {
+        self.placement.node_for(chunk_id, self.nodes.len())
+    }
+
+    /// Run `request` against each of the nodes numbered in `targets`
+    /// concurrently, returning the results in the same order or the first
+    /// error, naming its node.
+    fn fan_out<T, F>(&self, targets: &[usize], request: F) -> io::Result<Vec<T>>
+    where
+        T: Send,
+        F: Fn(usize, &dyn EngramNode) -> io::Result<T> + Sync,
+    {
+        let tag = |n: usize, e: io::Error| io::Error::new(e.kind(), format!("node {}: {}", n, e));
+        if let [n] = *targets {
+            return request(n, self.nodes[n].as_ref())
+                .map(|t| vec![t])
+                .map_err(|e| tag(n, e));
+        }
+        let request = &request;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = targets
+                .iter()
+                .map(|&n| {
+                    let node = self.nodes[n].as_ref();
+                    (n, scope.spawn(move || request(n, node)))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|(n, handle)| {
+                    handle
+                        .join()
+                        .expect("node request panicked")
+                        .map_err(|e| tag(n, e))
+                })
+                .collect()
+        })
+    }
+
+    fn all_nodes(&self) -> Vec<usize> {
+        (0..self.nodes.len()).collect()
+    }
+
+    /// Chunks held across all nodes.
+    pub fn chunk_count(&self) -> io::Result<usize> {
+        Ok(self
+            .fan_out(&self.all_nodes(), |_, node| node.chunk_ids())?
+            .iter()
+            .map(Vec::len)
+            .sum())
+    }
+
+    /// Records for `ids`, in the same order, asking each node that holds
+    /// some of them once.
+    pub fn fetch(&self, ids: &[usize]) -> io::Result<Vec<ChunkRecord>> {
+        let mut by_node: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for &id in ids {
+            by_node[self.node_for(id)].push(id);
+        }
+        let targets: Vec<usize> = (0..by_node.len())
+            .filter(|&n| !by_node[n].is_empty())
+            .collect();
+        let replies = self.fan_out(&targets, |n, node| node.fetch(&by_node[n]))?;
+        let mut found: HashMap<usize, ChunkRecord> = HashMap::with_capacity(ids.len());
+        for (&n, records) in targets.iter().zip(replies) {
+            found.extend(by_node[n].iter().copied().zip(records));
+        }
+        Ok(ids
+            .iter()
+            .map(|id| found.get(id).cloned().unwrap_or((None, None)))
+            .collect())
+    }
+
+    /// Codebook vector for one chunk, from the node holding it.
+    pub fn chunk(&self, chunk_id: usize) -> io::Result<Option<SparseVec>> {
+        Ok(self.fetch(&[chunk_id])?.remove(0).0)
+    }
+
+    /// Correction record for one chunk, from the node holding it.
+    pub fn correction(&self, chunk_id: usize) -> io::Result<Option<ChunkCorrection>> {
+        Ok(self.fetch(&[chunk_id])?.remove(0).1)
+    }
+
+    /// The `k` chunks most similar to `query` across all nodes, most
+    /// similar first; equal cosines go to the lower chunk id. Each node
+    /// returns its own top `k`, so the merge misses nothing a node found.
+    pub fn search(&self, query: &SparseVec, k: usize) -> io::Result<Vec<DistributedHit>> {
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        let replies = self.fan_out(&self.all_nodes(), |_, node| node.search(query, k))?;
+        let mut hits: Vec<DistributedHit> = replies
+            .into_iter()
+            .enumerate()
+            .flat_map(|(node, hits)| {
+                hits.into_iter()
+                    .map(move |(chunk_id, cosine)| DistributedHit {
+                        chunk_id,
+                        cosine,
+                        node,
+                    })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.cosine
+                .total_cmp(&a.cosine)
+                .then(a.chunk_id.cmp(&b.chunk_id))
+        });
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    /// Reconstruct one file into `out`, fetching its chunks from the nodes
+    /// a batch at a time.
+    ///
+    /// As with [`crate::Envelope::write_file`], a checksum mismatch is an
+    /// `InvalidData` error returned after every byte has been written.
+    pub fn write_file<W: Write>(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let mut hasher = blake3::Hasher::new();
+        let mut bad_chunks = Vec::new();
+        for (batch_idx, ids) in file_entry.chunks.chunks(FETCH_BATCH).enumerate() {
+            for (i, (&chunk_id, record)) in ids.iter().zip(self.fetch(ids)?).enumerate() {
+                let (Some(vec), correction) = record else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{}: chunk {} is missing from the engram",
+                            file_entry.path, chunk_id
+                        ),
+                    ));
+                };
+                let decoded = vec.decode_data(
+                    config,
+                    Some(&file_entry.path),
+                    EmbrFS::chunk_len(file_entry, batch_idx * FETCH_BATCH + i, chunk_size),
+                );
+                let chunk = match correction {
+                    Some(c) => {
+                        let fixed = c.apply(&decoded);
+                        if c.verify(&fixed) {
+                            fixed
+                        } else {
+                            bad_chunks.push(chunk_id);
+                            decoded
+                        }
+                    }
+                    None => decoded,
+                };
+                hasher.update(&chunk);
+                out.write_all(&chunk)?;
+            }
+        }
+
+        let Some(expected) = file_entry.blake3.as_ref() else {
+            return Ok(());
+        };
+        let actual = hasher.finalize().to_hex().to_string();
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(());
+        }
+        Err(EmbrError::ManifestMismatch(vec![ChecksumMismatch {
+            path: file_entry.path.clone(),
+            expected: expected.clone(),
+            actual,
+            chunk_ids: bad_chunks,
+        }])
+        .into())
+    }
+
+    /// Reconstruct one file in memory; see [`DistributedEngram::write_file`].
+    pub fn read_file(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+    ) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(file_entry.size);
+        self.write_file(file_entry, config, chunk_size, &mut out)?;
+        Ok(out)
+    }
+
+    /// Gather every node's chunks into one in-memory engram.
+    pub fn to_engram(&self) -> io::Result<Engram> {
+        let replies = self.fan_out(&self.all_nodes(), |_, node| {
+            let ids = node.chunk_ids()?;
+            let records = node.fetch(&ids)?;
+            Ok(ids.into_iter().zip(records).collect::<Vec<_>>())
+        })?;
+        let mut codebook = HashMap::new();
+        let mut corrections = HashMap::new();
+        for (id, (vec, correction)) in replies.into_iter().flatten() {
+            if let Some(vec) = vec {
+                codebook.insert(id, vec);
+            }
+            if let Some(correction) = correction {
+                corrections.insert(id as u64, correction);
+            }
+        }
+        Ok(Engram {
+            root: self.root.clone(),
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, self.totals),
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        })
+    }
+}
@@ -1,20 +1,106 @@
-use std::io;
+use crate::error::EmbrError;
+use crate::signing::{from_hex, to_hex};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use xxhash_rust::xxh3::Xxh3;
 
 const MAGIC: [u8; 4] = *b"EDN1";
-const HEADER_LEN: usize = 16;
+pub(crate) const HEADER_LEN: usize = 16;
+
+/// Header flag: payload is a sequence of independently compressed frames.
+const FLAG_FRAMED: u16 = 1;
+/// Header flag: frames carry digests and the trailer carries a root digest.
+const FLAG_CHECKSUM: u16 = 2;
+/// Header flag: frames (and any dictionary) are sealed with an AEAD cipher.
+const FLAG_ENCRYPTED: u16 = 4;
+/// Header flag: a seal record of the header's length follows, then the
+/// sealed file, itself an envelope or legacy payload (see [`crate::seal`]).
+const FLAG_SEALED: u16 = 8;
+const ENCRYPTION_EXT_LEN: usize = 16;
+/// AEAD position used for the dictionary section, distinct from any frame index.
+const DICT_SECTION_INDEX: u64 = u64::MAX;
+const FRAME_HEADER_LEN: usize = 8;
+
+/// Default uncompressed frame size used by [`EnvelopeWriter`].
+pub const DEFAULT_FRAME_SIZE: usize = 1 << 20;
+/// Largest frame a reader will accept; guards allocations on corrupt input.
+pub const MAX_FRAME_SIZE: usize = 64 << 20;
+
+/// Frame size for dictionary-compressed envelopes, matching the ingest chunk size.
+pub const DICT_FRAME_SIZE: usize = 4096;
+/// Upper bound on a trained dictionary.
+pub const DEFAULT_DICT_SIZE: usize = 64 << 10;
+const MAX_DICT_SAMPLES: usize = 1024;
+/// Below this many samples training is skipped and frames use plain zstd.
+const MIN_DICT_SAMPLES: usize = 8;
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PayloadKind {
     EngramBincode = 1,
     SubEngramBincode = 2,
+    ManifestJson = 3,
+    /// Master index of a payload split across part files.
+    PartIndex = 4,
+    /// rkyv archive of an engram, read in place (never compressed).
+    EngramRkyv = 5,
+    /// An engram and manifest stored as a diff against a base engram.
+    Delta = 6,
+    /// Per-chunk semantic signatures stored beside an engram.
+    SemanticSignatures = 7,
+    /// Index of an engram whose codebook is sharded by chunk id.
+    ShardIndex = 8,
+    /// Codebook vectors and corrections for one range of chunk ids.
+    CodebookShard = 9,
+    /// Chunk records shared by several engrams, keyed by digest.
+    SharedCodebook = 10,
+    /// An engram whose chunks are references into a shared codebook.
+    CodebookRef = 11,
+    /// A standalone [`crate::Codebook`] for differential encoding.
+    Codebook = 12,
+    /// An engram as an `embeddenator.v1.Engram` protobuf message (see [`crate::wire`]).
+    EngramProto = 13,
+    /// Per-chunk codebook vectors stored beside an engram, for retrieval.
+    ChunkVectors = 14,
+    /// Chunk records encrypted under one namespace key (see [`crate::namespace_keys`]).
+    KeyedChunks = 15,
 }
 
 impl PayloadKind {
+    /// Payload kind named by an envelope header, or `None` for raw data.
+    pub fn sniff(header: &[u8]) -> Option<Self> {
+        if header.len() < HEADER_LEN || header[..4] != MAGIC {
+            return None;
+        }
+        Self::from_u8(header[4])
+    }
+
+    /// Payload kind of the envelope stored at `path`, if it is one.
+    pub fn sniff_file<P: AsRef<std::path::Path>>(path: P) -> io::Result<Option<Self>> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        std::fs::File::open(path)?.take(HEADER_LEN as u64).read_to_end(&mut header)?;
+        Ok(Self::sniff(&header))
+    }
+
     fn from_u8(v: u8) -> Option<Self> {
         match v {
             1 => Some(Self::EngramBincode),
             2 => Some(Self::SubEngramBincode),
+            3 => Some(Self::ManifestJson),
+            4 => Some(Self::PartIndex),
+            5 => Some(Self::EngramRkyv),
+            6 => Some(Self::Delta),
+            7 => Some(Self::SemanticSignatures),
+            8 => Some(Self::ShardIndex),
+            9 => Some(Self::CodebookShard),
+            10 => Some(Self::SharedCodebook),
+            11 => Some(Self::CodebookRef),
+            12 => Some(Self::Codebook),
+            13 => Some(Self::EngramProto),
+            14 => Some(Self::ChunkVectors),
+            15 => Some(Self::KeyedChunks),
             _ => None,
         }
     }
@@ -26,23 +112,160 @@ pub enum CompressionCodec {
     None = 0,
     Zstd = 1,
     Lz4 = 2,
+    /// Per-frame zstd with a dictionary trained on the payload itself.
+    ZstdDict = 3,
 }
 
 impl CompressionCodec {
-    fn from_u8(v: u8) -> Option<Self> {
+    pub(crate) fn from_u8(v: u8) -> Option<Self> {
         match v {
             0 => Some(Self::None),
             1 => Some(Self::Zstd),
             2 => Some(Self::Lz4),
+            3 => Some(Self::ZstdDict),
+            _ => None,
+        }
+    }
+}
+
+/// Content checksum recorded in framed envelopes.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChecksumCodec {
+    #[default]
+    None = 0,
+    Blake3 = 1,
+    /// 64-bit XXH3: much cheaper than blake3, detects corruption but not tampering.
+    Xxh3 = 2,
+}
+
+impl ChecksumCodec {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::None),
+            1 => Some(Self::Blake3),
+            2 => Some(Self::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Digest size in bytes.
+    pub fn digest_len(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Blake3 => 32,
+            Self::Xxh3 => 8,
+        }
+    }
+}
+
+/// Authenticated encryption applied to each frame after compression.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncryptionCodec {
+    #[default]
+    None = 0,
+    /// XChaCha20-Poly1305 with a random 24-byte nonce per frame.
+    XChaCha20Poly1305 = 1,
+}
+
+impl EncryptionCodec {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::None),
+            1 => Some(Self::XChaCha20Poly1305),
             _ => None,
         }
     }
 }
 
+/// A 256-bit symmetric key tagged with the id recorded in envelope headers.
+///
+/// The id lets readers pick the right key from a [`Keyring`] after rotation.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EncryptionKey {
+    pub key_id: u32,
+    pub key: [u8; 32],
+}
+
+impl EncryptionKey {
+    pub fn new(key_id: u32, key: [u8; 32]) -> Self {
+        Self { key_id, key }
+    }
+
+    /// Parse a hex-encoded 32-byte key.
+    pub fn from_hex(key_id: u32, hex: &str) -> io::Result<Self> {
+        let key = from_hex(hex)?
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "encryption key must be 32 bytes"))?;
+        Ok(Self { key_id, key })
+    }
+
+    /// Short non-secret identifier stored in the header to detect a wrong key
+    /// before any frame fails authentication.
+    fn fingerprint(&self) -> [u8; 8] {
+        let derived = blake3::derive_key("embeddenator envelope key fingerprint v1", &self.key);
+        derived[..8].try_into().expect("fixed slice")
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("key_id", &self.key_id)
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Decryption keys available to a reader, indexed by key id.
+///
+/// Holding both the previous and the current key lets envelopes written
+/// before and after a rotation be read side by side.
+#[derive(Clone, Debug, Default)]
+pub struct Keyring {
+    keys: BTreeMap<u32, EncryptionKey>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key, replacing any existing key with the same id.
+    pub fn insert(&mut self, key: EncryptionKey) {
+        self.keys.insert(key.key_id, key);
+    }
+
+    pub fn get(&self, key_id: u32) -> Option<&EncryptionKey> {
+        self.keys.get(&key_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl FromIterator<EncryptionKey> for Keyring {
+    fn from_iter<I: IntoIterator<Item = EncryptionKey>>(iter: I) -> Self {
+        let mut ring = Self::new();
+        for key in iter {
+            ring.insert(key);
+        }
+        ring
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct BinaryWriteOptions {
     pub codec: CompressionCodec,
     pub level: Option<i32>,
+    /// Per-frame and root checksums. Anything other than `None` forces the
+    /// framed layout, even for uncompressed payloads.
+    pub checksum: ChecksumCodec,
+    /// Frame encryption; requires `key`. Also forces the framed layout.
+    pub encryption: EncryptionCodec,
+    pub key: Option<EncryptionKey>,
 }
 
 impl Default for BinaryWriteOptions {
@@ -50,15 +273,104 @@ impl Default for BinaryWriteOptions {
         Self {
             codec: CompressionCodec::None,
             level: None,
+            checksum: ChecksumCodec::None,
+            encryption: EncryptionCodec::None,
+            key: None,
         }
     }
 }
 
+impl BinaryWriteOptions {
+    /// Whether these options can only be expressed by the framed layout.
+    fn needs_frames(&self) -> bool {
+        self.checksum != ChecksumCodec::None || self.encryption != EncryptionCodec::None
+    }
+}
+
+/// Write options that reproduce the compression, checksum and encryption of
+/// the envelope at `path`, so rewriting it in place keeps them.
+///
+/// Missing files, raw (non-envelope) files and sealed envelopes give the
+/// defaults. An encrypted envelope needs its key in `keys`.
+pub fn write_options_of<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<BinaryWriteOptions> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BinaryWriteOptions::default()),
+        Err(e) => return Err(e),
+    };
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut file).take(HEADER_LEN as u64).read_to_end(&mut header)?;
+    let Some(parsed) = EnvelopeHeader::parse(&header).filter(|h| !h.sealed) else {
+        return Ok(BinaryWriteOptions::default());
+    };
+    let mut opts = BinaryWriteOptions {
+        codec: parsed.compression,
+        ..Default::default()
+    };
+    if parsed.checksummed {
+        let mut ext = [0u8; 4];
+        file.read_exact(&mut ext)?;
+        opts.checksum = ChecksumCodec::from_u8(ext[0]).ok_or_else(|| invalid_envelope("unknown envelope checksum codec"))?;
+    }
+    if parsed.encrypted {
+        let mut ext = [0u8; ENCRYPTION_EXT_LEN];
+        file.read_exact(&mut ext)?;
+        opts.encryption = EncryptionCodec::from_u8(ext[0]).ok_or_else(|| invalid_envelope("unknown envelope encryption codec"))?;
+        opts.key = Some(*envelope_key(&ext, keys)?);
+    }
+    Ok(opts)
+}
+
+/// The keyring entry matching an envelope's encryption extension, checked
+/// against the recorded fingerprint.
+fn envelope_key<'a>(ext: &[u8; ENCRYPTION_EXT_LEN], keys: &'a Keyring) -> io::Result<&'a EncryptionKey> {
+    let key_id = u32::from_le_bytes(ext[4..8].try_into().expect("fixed slice"));
+    let key = keys.get(key_id).ok_or_else(|| {
+        io::Error::from(EmbrError::MissingKey(format!(
+            "envelope is encrypted with key id {}, which is not in the keyring",
+            key_id
+        )))
+    })?;
+    if key.fingerprint()[..] != ext[8..16] {
+        return Err(EmbrError::MissingKey(format!(
+            "keyring entry for key id {} does not match the envelope's key",
+            key_id
+        ))
+        .into());
+    }
+    Ok(key)
+}
+
+/// Header of an unframed, uncompressed envelope holding `len` payload bytes.
+pub(crate) fn plain_header(kind: PayloadKind, len: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4] = kind as u8;
+    header[5] = CompressionCodec::None as u8;
+    header[8..].copy_from_slice(&len.to_le_bytes());
+    header
+}
+
 pub fn wrap_or_legacy(kind: PayloadKind, opts: BinaryWriteOptions, raw: &[u8]) -> io::Result<Vec<u8>> {
-    if opts.codec == CompressionCodec::None {
+    if opts.codec == CompressionCodec::None && !opts.needs_frames() {
         return Ok(raw.to_vec());
     }
 
+    if opts.codec == CompressionCodec::ZstdDict {
+        let mut sampler = DictionarySampler::new(DICT_FRAME_SIZE);
+        sampler.write_all(raw)?;
+        let dict = sampler.train(DEFAULT_DICT_SIZE)?;
+        let mut writer = EnvelopeWriter::with_dictionary(Vec::new(), kind, opts, dict)?;
+        writer.write_all(raw)?;
+        return writer.finish();
+    }
+
+    if opts.needs_frames() {
+        let mut writer = EnvelopeWriter::new(Vec::new(), kind, opts)?;
+        writer.write_all(raw)?;
+        return writer.finish();
+    }
+
     let compressed = compress(opts.codec, raw, opts.level)?;
 
     let mut out = Vec::with_capacity(HEADER_LEN + compressed.len());
@@ -73,47 +385,1207 @@ pub fn wrap_or_legacy(kind: PayloadKind, opts: BinaryWriteOptions, raw: &[u8]) -
 }
 
 pub fn unwrap_auto(expected_kind: PayloadKind, data: &[u8]) -> io::Result<Vec<u8>> {
+    unwrap_auto_with_keys(expected_kind, data, &Keyring::default())
+}
+
+/// Like [`unwrap_auto`], decrypting encrypted envelopes with `keys`.
+pub fn unwrap_auto_with_keys(expected_kind: PayloadKind, data: &[u8], keys: &Keyring) -> io::Result<Vec<u8>> {
     if data.len() < HEADER_LEN || data[..4] != MAGIC {
         return Ok(data.to_vec());
     }
 
-    let kind = PayloadKind::from_u8(data[4]).ok_or_else(|| io::Error::other("unknown envelope payload kind"))?;
+    let flags = u16::from_le_bytes([data[6], data[7]]);
+    if flags & FLAG_SEALED != 0 {
+        if PayloadKind::from_u8(data[4]) != Some(expected_kind) {
+            return Err(invalid_envelope("unexpected envelope payload kind"));
+        }
+        let (_, sealed) = split_sealed(data).ok_or_else(|| invalid_envelope("truncated seal record"))?;
+        return unwrap_auto_with_keys(expected_kind, sealed, keys);
+    }
+    if flags & FLAG_FRAMED != 0 {
+        let mut out = Vec::new();
+        EnvelopeReader::with_keys(data, expected_kind, keys)?.read_to_end(&mut out)?;
+        return Ok(out);
+    }
+    if flags & (FLAG_CHECKSUM | FLAG_ENCRYPTED) != 0 {
+        return Err(invalid_envelope("checksummed or encrypted envelope is missing its frame layout"));
+    }
+
+    let kind = PayloadKind::from_u8(data[4]).ok_or_else(|| invalid_envelope("unknown envelope payload kind"))?;
     if kind != expected_kind {
-        return Err(io::Error::other("unexpected envelope payload kind"));
+        return Err(invalid_envelope("unexpected envelope payload kind"));
     }
 
-    let codec = CompressionCodec::from_u8(data[5]).ok_or_else(|| io::Error::other("unknown envelope compression codec"))?;
+    let codec = CompressionCodec::from_u8(data[5]).ok_or_else(|| invalid_envelope("unknown envelope compression codec"))?;
     let uncompressed_len = u64::from_le_bytes(data[8..16].try_into().expect("slice length checked")) as usize;
 
     let payload = &data[HEADER_LEN..];
     let decoded = match codec {
         CompressionCodec::None => payload.to_vec(),
-        CompressionCodec::Zstd | CompressionCodec::Lz4 => decompress(codec, payload)?,
+        CompressionCodec::Zstd | CompressionCodec::Lz4 | CompressionCodec::ZstdDict => decompress(codec, payload)?,
     };
 
     if decoded.len() != uncompressed_len {
-        return Err(io::Error::other("envelope size mismatch"));
+        return Err(invalid_envelope("envelope size mismatch"));
     }
 
     Ok(decoded)
 }
 
-fn compress(codec: CompressionCodec, raw: &[u8], level: Option<i32>) -> io::Result<Vec<u8>> {
+/// An envelope sealing `sealed`, the complete bytes of a file of `kind`,
+/// with `record`.
+pub(crate) fn wrap_sealed(kind: PayloadKind, record: &[u8], sealed: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + record.len() + sealed.len());
+    out.extend_from_slice(&encode_header(kind, CompressionCodec::None, FLAG_SEALED, record.len() as u64));
+    out.extend_from_slice(record);
+    out.extend_from_slice(sealed);
+    out
+}
+
+/// The seal record and sealed bytes of a sealed envelope, or `None` if
+/// `data` is not one.
+pub(crate) fn split_sealed(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if !EnvelopeHeader::parse(data).is_some_and(|h| h.sealed) {
+        return None;
+    }
+    let len = usize::try_from(u64::from_le_bytes(data[8..16].try_into().ok()?)).ok()?;
+    let rest = &data[HEADER_LEN..];
+    (len <= rest.len()).then(|| rest.split_at(len))
+}
+
+fn encode_header(kind: PayloadKind, codec: CompressionCodec, flags: u16, len: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4] = kind as u8;
+    header[5] = codec as u8;
+    header[6..8].copy_from_slice(&flags.to_le_bytes());
+    header[8..16].copy_from_slice(&len.to_le_bytes());
+    header
+}
+
+/// Storage options named by an envelope header, for reporting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub kind: PayloadKind,
+    pub compression: CompressionCodec,
+    pub framed: bool,
+    pub checksummed: bool,
+    pub encrypted: bool,
+    /// The envelope seals another; the other fields describe the seal's
+    /// wrapper, not the sealed file.
+    pub sealed: bool,
+}
+
+impl EnvelopeHeader {
+    /// Parse the first [`HEADER_LEN`] bytes of an envelope; `None` for raw
+    /// data or an unknown kind or codec.
+    pub fn parse(header: &[u8]) -> Option<Self> {
+        let kind = PayloadKind::sniff(header)?;
+        let flags = u16::from_le_bytes([header[6], header[7]]);
+        Some(Self {
+            kind,
+            compression: CompressionCodec::from_u8(header[5])?,
+            framed: flags & FLAG_FRAMED != 0,
+            checksummed: flags & FLAG_CHECKSUM != 0,
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            sealed: flags & FLAG_SEALED != 0,
+        })
+    }
+}
+
+/// Incremental digest state for a [`ChecksumCodec`].
+enum Digester {
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Digester {
+    fn new(codec: ChecksumCodec) -> Option<Self> {
+        match codec {
+            ChecksumCodec::None => None,
+            ChecksumCodec::Blake3 => Some(Self::Blake3(Box::default())),
+            ChecksumCodec::Xxh3 => Some(Self::Xxh3(Box::default())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Blake3(h) => {
+                h.update(data);
+            }
+            Self::Xxh3(h) => h.update(data),
+        }
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        match self {
+            Self::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Self::Xxh3(h) => h.digest().to_le_bytes().to_vec(),
+        }
+    }
+}
+
+fn digest_parts(codec: ChecksumCodec, parts: &[&[u8]]) -> Vec<u8> {
+    match Digester::new(codec) {
+        Some(mut d) => {
+            for part in parts {
+                d.update(part);
+            }
+            d.finalize()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Writer adapter that tracks the output offset and feeds the root digest.
+struct DigestWriter<W: Write> {
+    inner: W,
+    root: Option<Digester>,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(data)?;
+        if let Some(root) = &mut self.root {
+            root.update(&data[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader adapter that tracks the input offset and feeds the root digest.
+struct DigestReader<R: Read> {
+    inner: R,
+    offset: u64,
+    root: Option<Digester>,
+}
+
+impl<R: Read> DigestReader<R> {
+    fn read_exact_at(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let at = self.offset;
+        self.read_exact(buf).map_err(|_| truncated(at))
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(root) = &mut self.root {
+            root.update(&buf[..n]);
+        }
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+/// Incremental envelope encoder over any `Write`.
+///
+/// Input is split into frames of at most `frame_size` bytes, each compressed
+/// independently, so memory use is bounded by one frame regardless of payload
+/// size. With `CompressionCodec::None` and no checksum the bytes are passed
+/// through unchanged, matching the legacy raw layout produced by
+/// [`wrap_or_legacy`].
+///
+/// With a [`ChecksumCodec`], every frame is followed by a digest of its header
+/// and stored bytes, and the end marker is followed by a root digest of all
+/// preceding bytes. The header only names the checksum codec: the root digest
+/// lives in the trailer because the writer cannot rewind.
+///
+/// With an [`EncryptionCodec`], each compressed frame is sealed with the AEAD
+/// cipher, bound to the envelope header and its frame index so frames cannot
+/// be reordered or moved between envelopes. The header records the key id
+/// and a key fingerprint, never the key itself.
+///
+/// [`EnvelopeWriter::finish`] must be called to write the end marker; a writer
+/// dropped without finishing leaves a truncated envelope that readers reject.
+pub struct EnvelopeWriter<W: Write> {
+    inner: DigestWriter<W>,
+    opts: BinaryWriteOptions,
+    passthrough: bool,
+    frame_size: usize,
+    buf: Vec<u8>,
+    total: u64,
+    index: u64,
+    dict: Option<zdict::DictCodec>,
+    cipher: Option<aead::Cipher>,
+}
+
+impl<W: Write> EnvelopeWriter<W> {
+    /// Start an envelope. `CompressionCodec::ZstdDict` needs a dictionary and
+    /// must go through [`EnvelopeWriter::with_dictionary`] instead.
+    pub fn new(inner: W, kind: PayloadKind, opts: BinaryWriteOptions) -> io::Result<Self> {
+        if opts.codec == CompressionCodec::ZstdDict {
+            return Err(io::Error::other(
+                "zstd dictionary envelopes must be created with EnvelopeWriter::with_dictionary",
+            ));
+        }
+        let passthrough = opts.codec == CompressionCodec::None && !opts.needs_frames();
+        if !passthrough {
+            // Surface a missing codec before anything is written.
+            compress(opts.codec, &[], opts.level)?;
+        }
+        let mut writer = Self::start(inner, opts, passthrough, DEFAULT_FRAME_SIZE, None);
+        if !passthrough {
+            writer.write_preamble(kind)?;
+        }
+        Ok(writer)
+    }
+
+    /// Start a `ZstdDict` envelope that stores `dict` after the header and
+    /// compresses every [`DICT_FRAME_SIZE`] frame against it. `opts.codec` is
+    /// ignored; level and checksum are honored.
+    ///
+    /// An empty dictionary is allowed and degrades to plain per-frame zstd.
+    pub fn with_dictionary(inner: W, kind: PayloadKind, opts: BinaryWriteOptions, dict: Vec<u8>) -> io::Result<Self> {
+        let dict_len = u32::try_from(dict.len()).map_err(|_| io::Error::other("zstd dictionary too large"))?;
+        let codec = zdict::DictCodec::new(&dict, opts.level.unwrap_or(0))?;
+        let opts = BinaryWriteOptions {
+            codec: CompressionCodec::ZstdDict,
+            ..opts
+        };
+        let mut writer = Self::start(inner, opts, false, DICT_FRAME_SIZE, Some(codec));
+        writer.write_preamble(kind)?;
+        match &writer.cipher {
+            Some(cipher) => {
+                let sealed = cipher.seal(DICT_SECTION_INDEX, &dict)?;
+                let sealed_len = u32::try_from(sealed.len()).map_err(|_| io::Error::other("zstd dictionary too large"))?;
+                writer.inner.write_all(&sealed_len.to_le_bytes())?;
+                writer.inner.write_all(&sealed)?;
+            }
+            None => {
+                writer.inner.write_all(&dict_len.to_le_bytes())?;
+                writer.inner.write_all(&dict)?;
+            }
+        }
+        Ok(writer)
+    }
+
+    fn start(
+        inner: W,
+        opts: BinaryWriteOptions,
+        passthrough: bool,
+        frame_size: usize,
+        dict: Option<zdict::DictCodec>,
+    ) -> Self {
+        Self {
+            inner: DigestWriter {
+                inner,
+                root: Digester::new(opts.checksum),
+            },
+            opts,
+            passthrough,
+            frame_size,
+            buf: Vec::new(),
+            total: 0,
+            index: 0,
+            dict,
+            cipher: None,
+        }
+    }
+
+    fn write_preamble(&mut self, kind: PayloadKind) -> io::Result<()> {
+        let mut flags = FLAG_FRAMED;
+        if self.opts.checksum != ChecksumCodec::None {
+            flags |= FLAG_CHECKSUM;
+        }
+        let key = match self.opts.encryption {
+            EncryptionCodec::None => None,
+            _ => {
+                flags |= FLAG_ENCRYPTED;
+                Some(self.opts.key.ok_or_else(|| io::Error::other("envelope encryption requires a key"))?)
+            }
+        };
+
+        let header = encode_header(kind, self.opts.codec, flags, 0);
+        if let Some(key) = &key {
+            // Fails early when the `encryption` feature is missing.
+            self.cipher = Some(aead::Cipher::new(self.opts.encryption, &key.key, header)?);
+        }
+
+        self.inner.write_all(&header)?;
+        if self.opts.checksum != ChecksumCodec::None {
+            self.inner.write_all(&[self.opts.checksum as u8, 0, 0, 0])?;
+        }
+        if let Some(key) = &key {
+            let mut ext = [0u8; ENCRYPTION_EXT_LEN];
+            ext[0] = self.opts.encryption as u8;
+            ext[4..8].copy_from_slice(&key.key_id.to_le_bytes());
+            ext[8..16].copy_from_slice(&key.fingerprint());
+            self.inner.write_all(&ext)?;
+        }
+        Ok(())
+    }
+
+    /// Set the uncompressed frame size (clamped to `1..=MAX_FRAME_SIZE`).
+    pub fn with_frame_size(mut self, frame_size: usize) -> Self {
+        self.frame_size = frame_size.clamp(1, MAX_FRAME_SIZE);
+        self
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let mut stored = match &mut self.dict {
+            Some(dict) => dict.compress(&self.buf)?,
+            None => compress(self.opts.codec, &self.buf, self.opts.level)?,
+        };
+        if let Some(cipher) = &self.cipher {
+            stored = cipher.seal(self.index, &stored)?;
+        }
+        let stored_len = u32::try_from(stored.len()).map_err(|_| io::Error::other("envelope frame too large"))?;
+        let mut fh = [0u8; FRAME_HEADER_LEN];
+        fh[..4].copy_from_slice(&(self.buf.len() as u32).to_le_bytes());
+        fh[4..].copy_from_slice(&stored_len.to_le_bytes());
+        self.inner.write_all(&fh)?;
+        self.inner.write_all(&stored)?;
+        self.inner.write_all(&digest_parts(self.opts.checksum, &[&fh, &stored]))?;
+        self.total += self.buf.len() as u64;
+        self.index += 1;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Flush the final frame and end marker, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.passthrough {
+            self.write_frame()?;
+            self.inner.write_all(&[0u8; FRAME_HEADER_LEN])?;
+            self.inner.write_all(&self.total.to_le_bytes())?;
+            if let Some(root) = self.inner.root.take() {
+                self.inner.inner.write_all(&root.finalize())?;
+            }
+        }
+        self.inner.flush()?;
+        Ok(self.inner.inner)
+    }
+}
+
+impl<W: Write> Write for EnvelopeWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.passthrough {
+            return self.inner.write(data);
+        }
+        let n = data.len().min(self.frame_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == self.frame_size {
+            self.write_frame()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A checksum recorded in an envelope did not match the bytes on disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeCorruption {
+    pub checksum: ChecksumCodec,
+    /// Zero-based frame index, or `None` for the whole-file root digest.
+    pub frame: Option<u64>,
+    /// Byte offset of the covered range within the envelope.
+    pub offset: u64,
+    /// Length of the covered range in bytes.
+    pub len: u64,
+    /// Hex-encoded recorded digest.
+    pub expected: String,
+    /// Hex-encoded digest of the bytes actually read.
+    pub actual: String,
+}
+
+impl std::fmt::Display for EnvelopeCorruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.frame {
+            Some(frame) => write!(f, "envelope checksum mismatch in frame {}", frame)?,
+            None => write!(f, "envelope root checksum mismatch")?,
+        }
+        write!(
+            f,
+            " at bytes {}..{} ({:?}: expected {}, got {})",
+            self.offset,
+            self.offset + self.len,
+            self.checksum,
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl std::error::Error for EnvelopeCorruption {}
+
+fn corruption(
+    checksum: ChecksumCodec,
+    frame: Option<u64>,
+    offset: u64,
+    len: u64,
+    expected: &[u8],
+    actual: &[u8],
+) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        EnvelopeCorruption {
+            checksum,
+            frame,
+            offset,
+            len,
+            expected: to_hex(expected),
+            actual: to_hex(actual),
+        },
+    )
+}
+
+/// How the frames of one envelope are stored, read from its preamble.
+struct FrameCodec {
+    codec: CompressionCodec,
+    checksum: ChecksumCodec,
+    dict: Option<zdict::DictCodec>,
+    cipher: Option<aead::Cipher>,
+}
+
+impl FrameCodec {
+    /// Read the preamble following the header of a framed envelope: the
+    /// checksum and encryption extensions and any dictionary. Starts the
+    /// root digest of `inner` when the envelope is checksummed.
+    fn read_preamble<R: Read>(
+        inner: &mut DigestReader<R>,
+        header: &[u8],
+        expected_kind: PayloadKind,
+        keys: &Keyring,
+    ) -> io::Result<Self> {
+        let flags = u16::from_le_bytes([header[6], header[7]]);
+        let kind = PayloadKind::from_u8(header[4]).ok_or_else(|| invalid_envelope("unknown envelope payload kind"))?;
+        if kind != expected_kind {
+            return Err(invalid_envelope("unexpected envelope payload kind"));
+        }
+        let codec = CompressionCodec::from_u8(header[5]).ok_or_else(|| invalid_envelope("unknown envelope compression codec"))?;
+
+        let mut checksum = ChecksumCodec::None;
+        if flags & FLAG_CHECKSUM != 0 {
+            let mut ext = [0u8; 4];
+            inner.read_exact_at(&mut ext)?;
+            checksum = ChecksumCodec::from_u8(ext[0]).ok_or_else(|| invalid_envelope("unknown envelope checksum codec"))?;
+            inner.root = Digester::new(checksum);
+            if let Some(root) = &mut inner.root {
+                root.update(header);
+                root.update(&ext);
+            }
+        }
+
+        let mut cipher = None;
+        if flags & FLAG_ENCRYPTED != 0 {
+            let mut ext = [0u8; ENCRYPTION_EXT_LEN];
+            inner.read_exact_at(&mut ext)?;
+            let encryption = EncryptionCodec::from_u8(ext[0]).ok_or_else(|| invalid_envelope("unknown envelope encryption codec"))?;
+            let key = envelope_key(&ext, keys)?;
+            let aad_header: [u8; HEADER_LEN] = header.try_into().expect("header length checked");
+            cipher = Some(aead::Cipher::new(encryption, &key.key, aad_header)?);
+        }
+
+        let dict = if codec == CompressionCodec::ZstdDict {
+            let mut len = [0u8; 4];
+            inner.read_exact_at(&mut len)?;
+            let len = u32::from_le_bytes(len) as usize;
+            if len > MAX_FRAME_SIZE {
+                return Err(invalid_envelope("zstd dictionary exceeds maximum size"));
+            }
+            let mut bytes = vec![0u8; len];
+            inner.read_exact_at(&mut bytes)?;
+            if let Some(cipher) = &cipher {
+                bytes = cipher.open(DICT_SECTION_INDEX, &bytes).map_err(|_| {
+                    io::Error::from(EmbrError::Crypto("envelope dictionary failed authentication".into()))
+                })?;
+            }
+            Some(zdict::DictCodec::new(&bytes, 0)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            codec,
+            checksum,
+            dict,
+            cipher,
+        })
+    }
+
+    /// Decrypt and decompress frame `index`, which starts at byte `start`.
+    fn open_frame(&mut self, index: u64, start: u64, raw_len: usize, mut stored: Vec<u8>) -> io::Result<Vec<u8>> {
+        if let Some(cipher) = &self.cipher {
+            stored = cipher.open(index, &stored).map_err(|_| {
+                io::Error::from(EmbrError::Crypto(format!(
+                    "envelope frame {} at byte {} failed authentication (wrong key or tampered data)",
+                    index, start
+                )))
+            })?;
+        }
+
+        let decoded = match &mut self.dict {
+            Some(dict) => dict.decompress(&stored, raw_len)?,
+            None => decompress(self.codec, &stored)?,
+        };
+        if decoded.len() != raw_len {
+            return Err(invalid_envelope(format!(
+                "envelope frame {} at byte {} size mismatch",
+                index, start
+            )));
+        }
+        Ok(decoded)
+    }
+}
+
+fn frame_too_large(raw_len: usize, stored_len: usize) -> bool {
+    raw_len > MAX_FRAME_SIZE || stored_len > MAX_FRAME_SIZE + (MAX_FRAME_SIZE >> 4)
+}
+
+struct FramedReader<R: Read> {
+    inner: DigestReader<R>,
+    codec: FrameCodec,
+    frame: Cursor<Vec<u8>>,
+    index: u64,
+    total: u64,
+    done: bool,
+}
+
+impl<R: Read> FramedReader<R> {
+    fn corruption(&self, frame: Option<u64>, offset: u64, expected: &[u8], actual: &[u8]) -> io::Error {
+        corruption(
+            self.codec.checksum,
+            frame,
+            offset,
+            self.inner.offset - offset,
+            expected,
+            actual,
+        )
+    }
+
+    /// Load the next frame into `self.frame`, or consume the end marker.
+    fn next_frame(&mut self) -> io::Result<()> {
+        let start = self.inner.offset;
+        let mut fh = [0u8; FRAME_HEADER_LEN];
+        self.inner.read_exact_at(&mut fh)?;
+        let raw_len = u32::from_le_bytes(fh[..4].try_into().expect("fixed slice")) as usize;
+        let stored_len = u32::from_le_bytes(fh[4..].try_into().expect("fixed slice")) as usize;
+
+        if raw_len == 0 && stored_len == 0 {
+            let mut trailer = [0u8; 8];
+            self.inner.read_exact_at(&mut trailer)?;
+            if let Some(root) = self.inner.root.take() {
+                let actual = root.finalize();
+                let mut expected = vec![0u8; self.codec.checksum.digest_len()];
+                let at = self.inner.offset;
+                self.inner.inner.read_exact(&mut expected).map_err(|_| truncated(at))?;
+                if expected != actual {
+                    return Err(self.corruption(None, 0, &expected, &actual));
+                }
+            }
+            if u64::from_le_bytes(trailer) != self.total {
+                return Err(invalid_envelope("envelope size mismatch"));
+            }
+            self.done = true;
+            return Ok(());
+        }
+        if frame_too_large(raw_len, stored_len) {
+            return Err(invalid_envelope(format!(
+                "envelope frame {} at byte {} exceeds maximum size",
+                self.index, start
+            )));
+        }
+
+        let mut stored = vec![0u8; stored_len];
+        self.inner.read_exact_at(&mut stored)?;
+        if self.codec.checksum != ChecksumCodec::None {
+            let actual = digest_parts(self.codec.checksum, &[&fh, &stored]);
+            let mut expected = vec![0u8; actual.len()];
+            self.inner.read_exact_at(&mut expected)?;
+            if expected != actual {
+                return Err(self.corruption(Some(self.index), start, &expected, &actual));
+            }
+        }
+
+        let decoded = self.codec.open_frame(self.index, start, raw_len, stored)?;
+        self.index += 1;
+        self.total += raw_len as u64;
+        self.frame = Cursor::new(decoded);
+        Ok(())
+    }
+}
+
+enum ReaderState<R: Read> {
+    /// Legacy raw bytes: replay any sniffed prefix, then the inner reader.
+    Raw(io::Chain<Cursor<Vec<u8>>, R>),
+    /// Single-blob envelope, decoded up front.
+    Buffered(Cursor<Vec<u8>>),
+    Framed(Box<FramedReader<R>>),
+}
+
+/// Incremental envelope decoder over any `Read`.
+///
+/// Accepts everything [`unwrap_auto`] accepts. Framed envelopes written by
+/// [`EnvelopeWriter`] are decoded one frame at a time; legacy raw payloads
+/// are streamed through unchanged. Single-blob envelopes from
+/// [`wrap_or_legacy`] have no frame boundaries and are decoded in memory.
+///
+/// Frame checksums are verified as each frame is loaded. The root checksum is
+/// verified when the end marker is reached, so callers that stop early (e.g. a
+/// deserializer that knows its own length) should drain the reader to EOF.
+/// Mismatches surface as `io::ErrorKind::InvalidData` wrapping an
+/// [`EnvelopeCorruption`].
+///
+/// Encrypted envelopes need the matching key, supplied through
+/// [`EnvelopeReader::with_keys`].
+pub struct EnvelopeReader<R: Read> {
+    state: ReaderState<R>,
+}
+
+impl<R: Read> EnvelopeReader<R> {
+    pub fn new(inner: R, expected_kind: PayloadKind) -> io::Result<Self> {
+        Self::with_keys(inner, expected_kind, &Keyring::default())
+    }
+
+    /// Open an envelope, decrypting with the key whose id the header names.
+    pub fn with_keys(mut inner: R, expected_kind: PayloadKind, keys: &Keyring) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        (&mut inner).take(HEADER_LEN as u64).read_to_end(&mut header)?;
+
+        if header.len() < HEADER_LEN || header[..4] != MAGIC {
+            return Ok(Self {
+                state: ReaderState::Raw(Cursor::new(header).chain(inner)),
+            });
+        }
+
+        let flags = u16::from_le_bytes([header[6], header[7]]);
+        if flags & FLAG_SEALED != 0 {
+            if PayloadKind::from_u8(header[4]) != Some(expected_kind) {
+                return Err(invalid_envelope("unexpected envelope payload kind"));
+            }
+            // Skip the seal record; the sealed file follows.
+            let len = u64::from_le_bytes(header[8..16].try_into().expect("slice length checked"));
+            let skipped = io::copy(&mut (&mut inner).take(len), &mut io::sink())?;
+            if skipped != len {
+                return Err(truncated(HEADER_LEN as u64 + skipped));
+            }
+            return Self::with_keys(inner, expected_kind, keys);
+        }
+        if flags & FLAG_FRAMED == 0 {
+            let mut data = header;
+            inner.read_to_end(&mut data)?;
+            let decoded = unwrap_auto(expected_kind, &data)?;
+            return Ok(Self {
+                state: ReaderState::Buffered(Cursor::new(decoded)),
+            });
+        }
+
+        let mut inner = DigestReader {
+            inner,
+            offset: HEADER_LEN as u64,
+            root: None,
+        };
+        let codec = FrameCodec::read_preamble(&mut inner, &header, expected_kind, keys)?;
+        Ok(Self {
+            state: ReaderState::Framed(Box::new(FramedReader {
+                inner,
+                codec,
+                frame: Cursor::new(Vec::new()),
+                index: 0,
+                total: 0,
+                done: false,
+            })),
+        })
+    }
+}
+
+fn invalid_envelope(msg: impl Into<String>) -> io::Error {
+    EmbrError::InvalidEnvelope(msg.into()).into()
+}
+
+fn truncated(offset: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("truncated envelope at byte {}", offset),
+    )
+}
+
+impl<R: Read> Read for EnvelopeReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let framed = match &mut self.state {
+            ReaderState::Raw(r) => return r.read(out),
+            ReaderState::Buffered(c) => return c.read(out),
+            ReaderState::Framed(framed) => framed,
+        };
+
+        loop {
+            let n = framed.frame.read(out)?;
+            if n > 0 || out.is_empty() || framed.done {
+                return Ok(n);
+            }
+            framed.next_frame()?;
+        }
+    }
+}
+
+/// Location of one frame within a framed envelope.
+struct FrameRef {
+    /// File offset of the frame header.
+    offset: u64,
+    /// Payload offset of the frame's first byte.
+    start: u64,
+    raw_len: u32,
+    stored_len: u32,
+}
+
+enum Layout {
+    /// Legacy raw bytes, read in place.
+    Raw { len: u64 },
+    /// Single-blob envelope, decoded on open.
+    Buffered(Vec<u8>),
+    Framed {
+        codec: FrameCodec,
+        frames: Vec<FrameRef>,
+        len: u64,
+    },
+}
+
+/// Random-access decoder over an envelope in a seekable source.
+///
+/// [`SeekableEnvelope::open`] walks the frame headers once, seeking over the
+/// frame bodies, and keeps a table from payload offsets to frames. Reads
+/// then decode only the frames they touch, keeping the last one decoded.
+/// Legacy raw payloads are read in place; single-blob envelopes have no
+/// frame boundaries and are decoded in memory on open.
+///
+/// Each frame's checksum is verified when the frame is decoded. The root
+/// checksum covers the whole envelope and is never checked here, so a
+/// reader that touches every byte should use [`EnvelopeReader`] instead.
+pub struct SeekableEnvelope<R: Read + Seek> {
+    inner: R,
+    layout: Layout,
+    pos: u64,
+    frame: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableEnvelope<R> {
+    /// Index the envelope in `inner`, decrypting with the key whose id the
+    /// header names.
+    pub fn open(mut inner: R, expected_kind: PayloadKind, keys: &Keyring) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(0))?;
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        (&mut inner).take(HEADER_LEN as u64).read_to_end(&mut header)?;
+
+        let layout = if header.len() < HEADER_LEN || header[..4] != MAGIC {
+            Layout::Raw {
+                len: inner.seek(SeekFrom::End(0))?,
+            }
+        } else if u16::from_le_bytes([header[6], header[7]]) & FLAG_FRAMED == 0 {
+            let mut data = header;
+            inner.read_to_end(&mut data)?;
+            Layout::Buffered(unwrap_auto_with_keys(expected_kind, &data, keys)?)
+        } else {
+            let mut reader = DigestReader {
+                inner: &mut inner,
+                offset: HEADER_LEN as u64,
+                root: None,
+            };
+            let codec = FrameCodec::read_preamble(&mut reader, &header, expected_kind, keys)?;
+            let mut offset = reader.offset;
+            let digest_len = codec.checksum.digest_len() as i64;
+            let mut frames = Vec::new();
+            let mut len = 0u64;
+            loop {
+                let mut fh = [0u8; FRAME_HEADER_LEN];
+                inner.read_exact(&mut fh).map_err(|_| truncated(offset))?;
+                let raw_len = u32::from_le_bytes(fh[..4].try_into().expect("fixed slice"));
+                let stored_len = u32::from_le_bytes(fh[4..].try_into().expect("fixed slice"));
+                if raw_len == 0 && stored_len == 0 {
+                    let mut trailer = [0u8; 8];
+                    inner
+                        .read_exact(&mut trailer)
+                        .map_err(|_| truncated(offset + FRAME_HEADER_LEN as u64))?;
+                    if u64::from_le_bytes(trailer) != len {
+                        return Err(invalid_envelope("envelope size mismatch"));
+                    }
+                    break;
+                }
+                if frame_too_large(raw_len as usize, stored_len as usize) {
+                    return Err(invalid_envelope(format!(
+                        "envelope frame {} at byte {} exceeds maximum size",
+                        frames.len(),
+                        offset
+                    )));
+                }
+                frames.push(FrameRef {
+                    offset,
+                    start: len,
+                    raw_len,
+                    stored_len,
+                });
+                len += raw_len as u64;
+                offset = inner.seek(SeekFrom::Current(stored_len as i64 + digest_len))?;
+            }
+            Layout::Framed { codec, frames, len }
+        };
+        Ok(Self {
+            inner,
+            layout,
+            pos: 0,
+            frame: None,
+        })
+    }
+
+    /// Length of the decoded payload.
+    pub fn len(&self) -> u64 {
+        match &self.layout {
+            Layout::Raw { len } | Layout::Framed { len, .. } => *len,
+            Layout::Buffered(data) => data.len() as u64,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frames in the envelope; 0 unless it is framed.
+    pub fn frame_count(&self) -> usize {
+        match &self.layout {
+            Layout::Framed { frames, .. } => frames.len(),
+            _ => 0,
+        }
+    }
+
+    /// Load frame `n` into `self.frame`, reading and verifying it.
+    fn load_frame(&mut self, n: usize) -> io::Result<()> {
+        if matches!(&self.frame, Some((i, _)) if *i == n) {
+            return Ok(());
+        }
+        let Layout::Framed { codec, frames, .. } = &mut self.layout else {
+            unreachable!("frames are only loaded from framed envelopes");
+        };
+        let frame = &frames[n];
+        self.inner.seek(SeekFrom::Start(frame.offset))?;
+        let mut fh = [0u8; FRAME_HEADER_LEN];
+        let mut stored = vec![0u8; frame.stored_len as usize];
+        let mut expected = vec![0u8; codec.checksum.digest_len()];
+        let at = frame.offset;
+        self.inner.read_exact(&mut fh).map_err(|_| truncated(at))?;
+        self.inner.read_exact(&mut stored).map_err(|_| truncated(at))?;
+        self.inner.read_exact(&mut expected).map_err(|_| truncated(at))?;
+        if codec.checksum != ChecksumCodec::None {
+            let actual = digest_parts(codec.checksum, &[&fh, &stored]);
+            if expected != actual {
+                let len = (FRAME_HEADER_LEN + stored.len()) as u64;
+                return Err(corruption(codec.checksum, Some(n as u64), at, len, &expected, &actual));
+            }
+        }
+        let decoded = codec.open_frame(n as u64, at, frame.raw_len as usize, stored)?;
+        self.frame = Some((n, decoded));
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableEnvelope<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len() || out.is_empty() {
+            return Ok(0);
+        }
+        let n = match &self.layout {
+            Layout::Raw { len } => {
+                self.inner.seek(SeekFrom::Start(self.pos))?;
+                let want = out.len().min((len - self.pos) as usize);
+                self.inner.read(&mut out[..want])?
+            }
+            Layout::Buffered(data) => {
+                let rest = &data[self.pos as usize..];
+                let n = out.len().min(rest.len());
+                out[..n].copy_from_slice(&rest[..n]);
+                n
+            }
+            Layout::Framed { frames, .. } => {
+                let i = frames.partition_point(|f| f.start <= self.pos) - 1;
+                let start = frames[i].start;
+                self.load_frame(i)?;
+                let (_, frame) = self.frame.as_ref().expect("frame loaded above");
+                let rest = &frame[(self.pos - start) as usize..];
+                let n = out.len().min(rest.len());
+                out[..n].copy_from_slice(&rest[..n]);
+                n
+            }
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableEnvelope<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(d) => self.len().checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative or overflowing position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Collects frame-sized samples of a payload for dictionary training.
+///
+/// Write the payload through the sampler first (e.g. a serialization pass),
+/// then call [`DictionarySampler::train`]. At most `MAX_DICT_SAMPLES` frames are
+/// kept; once full, every other sample is dropped and the stride doubles, so
+/// samples stay spread evenly across payloads of unknown length.
+pub struct DictionarySampler {
+    frame_size: usize,
+    stride: usize,
+    seen: usize,
+    total: u64,
+    current: Vec<u8>,
+    samples: Vec<Vec<u8>>,
+}
+
+impl DictionarySampler {
+    pub fn new(frame_size: usize) -> Self {
+        Self {
+            frame_size: frame_size.max(1),
+            stride: 1,
+            seen: 0,
+            total: 0,
+            current: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn push_frame(&mut self) {
+        let frame = std::mem::take(&mut self.current);
+        if self.seen.is_multiple_of(self.stride) {
+            self.samples.push(frame);
+            if self.samples.len() == MAX_DICT_SAMPLES {
+                let mut i = 0;
+                self.samples.retain(|_| {
+                    i += 1;
+                    i % 2 == 1
+                });
+                self.stride *= 2;
+            }
+        }
+        self.seen += 1;
+    }
+
+    /// Number of samples currently held.
+    pub fn sample_count(&self) -> usize {
+        self.samples.len() + usize::from(!self.current.is_empty())
+    }
+
+    /// Train a dictionary of at most `max_size` bytes.
+    ///
+    /// The dictionary is stored in the envelope, so it is further capped at
+    /// ~1% of the payload (zstd's rule of thumb) to keep it from dominating
+    /// smaller artifacts. Returns an empty dictionary when there are too few samples, or when
+    /// zstd declines to train on them (tiny or highly uniform payloads).
+    pub fn train(mut self, max_size: usize) -> io::Result<Vec<u8>> {
+        if !self.current.is_empty() {
+            self.push_frame();
+        }
+        if self.samples.len() < MIN_DICT_SAMPLES {
+            return Ok(Vec::new());
+        }
+        let max_size = max_size.min((self.total / 100) as usize);
+        zdict::train(&self.samples, max_size)
+    }
+}
+
+impl Write for DictionarySampler {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(self.frame_size - self.current.len());
+        self.current.extend_from_slice(&data[..n]);
+        self.total += n as u64;
+        if self.current.len() == self.frame_size {
+            self.push_frame();
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "compression-zstd")]
+mod zdict {
+    use std::io;
+    use zstd::bulk::{Compressor, Decompressor};
+
+    pub(super) struct DictCodec {
+        compressor: Compressor<'static>,
+        decompressor: Decompressor<'static>,
+    }
+
+    impl DictCodec {
+        pub(super) fn new(dict: &[u8], level: i32) -> io::Result<Self> {
+            Ok(Self {
+                compressor: Compressor::with_dictionary(level, dict)?,
+                decompressor: Decompressor::with_dictionary(dict)?,
+            })
+        }
+
+        pub(super) fn compress(&mut self, raw: &[u8]) -> io::Result<Vec<u8>> {
+            self.compressor.compress(raw)
+        }
+
+        pub(super) fn decompress(&mut self, payload: &[u8], raw_len: usize) -> io::Result<Vec<u8>> {
+            self.decompressor.decompress(payload, raw_len)
+        }
+    }
+
+    pub(super) fn train(samples: &[Vec<u8>], max_size: usize) -> io::Result<Vec<u8>> {
+        Ok(zstd::dict::from_samples(samples, max_size).unwrap_or_default())
+    }
+}
+
+#[cfg(not(feature = "compression-zstd"))]
+mod zdict {
+    use std::io;
+
+    fn zstd_disabled() -> io::Error {
+        io::Error::other("zstd compression support not enabled (enable feature `compression-zstd`)")
+    }
+
+    pub(super) struct DictCodec;
+
+    impl DictCodec {
+        pub(super) fn new(_: &[u8], _: i32) -> io::Result<Self> {
+            Err(zstd_disabled())
+        }
+
+        pub(super) fn compress(&mut self, _: &[u8]) -> io::Result<Vec<u8>> {
+            Err(zstd_disabled())
+        }
+
+        pub(super) fn decompress(&mut self, _: &[u8], _: usize) -> io::Result<Vec<u8>> {
+            Err(zstd_disabled())
+        }
+    }
+
+    pub(super) fn train(_: &[Vec<u8>], _: usize) -> io::Result<Vec<u8>> {
+        Err(zstd_disabled())
+    }
+}
+
+#[cfg(feature = "encryption")]
+mod aead {
+    use super::{EncryptionCodec, HEADER_LEN};
+    use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+    use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+    use rand::RngCore;
+    use std::io;
+
+    const NONCE_LEN: usize = 24;
+
+    pub(super) struct Cipher {
+        cipher: XChaCha20Poly1305,
+        header: [u8; HEADER_LEN],
+    }
+
+    impl Cipher {
+        pub(super) fn new(codec: EncryptionCodec, key: &[u8; 32], header: [u8; HEADER_LEN]) -> io::Result<Self> {
+            match codec {
+                EncryptionCodec::XChaCha20Poly1305 => Ok(Self {
+                    cipher: XChaCha20Poly1305::new(key.into()),
+                    header,
+                }),
+                EncryptionCodec::None => Err(io::Error::other("no encryption codec selected")),
+            }
+        }
+
+        fn aad(&self, index: u64) -> [u8; HEADER_LEN + 8] {
+            let mut aad = [0u8; HEADER_LEN + 8];
+            aad[..HEADER_LEN].copy_from_slice(&self.header);
+            aad[HEADER_LEN..].copy_from_slice(&index.to_le_bytes());
+            aad
+        }
+
+        /// Encrypt `plain` as section `index`, returning `nonce || ciphertext`.
+        pub(super) fn seal(&self, index: u64, plain: &[u8]) -> io::Result<Vec<u8>> {
+            let mut nonce = [0u8; NONCE_LEN];
+            rand::thread_rng().fill_bytes(&mut nonce);
+            let aad = self.aad(index);
+            let sealed = self
+                .cipher
+                .encrypt(XNonce::from_slice(&nonce), Payload { msg: plain, aad: &aad })
+                .map_err(|_| io::Error::other("envelope encryption failed"))?;
+            let mut out = Vec::with_capacity(NONCE_LEN + sealed.len());
+            out.extend_from_slice(&nonce);
+            out.extend_from_slice(&sealed);
+            Ok(out)
+        }
+
+        pub(super) fn open(&self, index: u64, data: &[u8]) -> io::Result<Vec<u8>> {
+            if data.len() < NONCE_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "sealed section too short"));
+            }
+            let (nonce, sealed) = data.split_at(NONCE_LEN);
+            let aad = self.aad(index);
+            self.cipher
+                .decrypt(XNonce::from_slice(nonce), Payload { msg: sealed, aad: &aad })
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "envelope authentication failed"))
+        }
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+mod aead {
+    use super::{EncryptionCodec, HEADER_LEN};
+    use std::io;
+
+    fn encryption_disabled() -> io::Error {
+        io::Error::other("encryption support not enabled (enable feature `encryption`)")
+    }
+
+    pub(super) struct Cipher;
+
+    impl Cipher {
+        pub(super) fn new(_: EncryptionCodec, _: &[u8; 32], _: [u8; HEADER_LEN]) -> io::Result<Self> {
+            Err(encryption_disabled())
+        }
+
+        pub(super) fn seal(&self, _: u64, _: &[u8]) -> io::Result<Vec<u8>> {
+            Err(encryption_disabled())
+        }
+
+        pub(super) fn open(&self, _: u64, _: &[u8]) -> io::Result<Vec<u8>> {
+            Err(encryption_disabled())
+        }
+    }
+}
+
+pub(crate) fn compress(codec: CompressionCodec, raw: &[u8], level: Option<i32>) -> io::Result<Vec<u8>> {
     match codec {
         CompressionCodec::None => Ok(raw.to_vec()),
         CompressionCodec::Zstd => compress_zstd(raw, level),
         CompressionCodec::Lz4 => compress_lz4(raw),
+        CompressionCodec::ZstdDict => Err(dict_requires_frames()),
     }
 }
 
-fn decompress(codec: CompressionCodec, payload: &[u8]) -> io::Result<Vec<u8>> {
+pub(crate) fn decompress(codec: CompressionCodec, payload: &[u8]) -> io::Result<Vec<u8>> {
     match codec {
         CompressionCodec::None => Ok(payload.to_vec()),
         CompressionCodec::Zstd => decompress_zstd(payload),
         CompressionCodec::Lz4 => decompress_lz4(payload),
+        CompressionCodec::ZstdDict => Err(dict_requires_frames()),
     }
 }
 
+fn dict_requires_frames() -> io::Error {
+    io::Error::other("zstd dictionary compression requires a framed envelope")
+}
+
 fn compress_zstd(_raw: &[u8], _level: Option<i32>) -> io::Result<Vec<u8>> {
     #[cfg(feature = "compression-zstd")]
     {
@@ -0,0 +1,242 @@
+//! Parquet export of manifest and codebook metadata.
+//!
+//! [`to_parquet`] writes two tables into an output directory so engram
+//! contents can be explored with DuckDB, Polars or any other Parquet reader:
+//!
+//! - `files.parquet`: one row per manifest entry (path, namespace, extension,
+//!   size, text flag, chunk count, blake3).
+//! - `chunks.parquet`: one row per chunk reference (file path, position in
+//!   the file, chunk id, byte offset and length, content hash, correction
+//!   kind and size, vector sparsity, and the 64-bit probe signature used by
+//!   [`crate::signature::TernarySignatureIndex`]).
+//!
+//! Only metadata is exported; no file contents are decoded. Files share
+//! chunks, so `chunk_id` repeats across rows of `chunks.parquet` for
+//! deduplicated content.
+//!
+//! Requires the `parquet` feature; without it `to_parquet` returns an error.
+
+use crate::correction::CorrectionType;
+use crate::embrfs::{EmbrFS, Engram, Manifest};
+use crate::ingest_stats::extension_of;
+use crate::signature::{default_probe_dims, signature_for, DEFAULT_SIGNATURE_PROBES};
+use crate::signing::to_hex;
+use std::io;
+use std::path::Path;
+
+/// File name of the file-level table inside the export directory.
+pub const FILES_TABLE: &str = "files.parquet";
+/// File name of the chunk-level table inside the export directory.
+pub const CHUNKS_TABLE: &str = "chunks.parquet";
+
+/// Row counts of an export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub files: usize,
+    pub chunks: usize,
+}
+
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+struct FileRow {
+    path: String,
+    namespace: Option<String>,
+    extension: String,
+    size: u64,
+    is_text: bool,
+    chunks: u32,
+    blake3: Option<String>,
+}
+
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+struct ChunkRow {
+    path: String,
+    chunk_index: u32,
+    chunk_id: u64,
+    offset: u64,
+    len: u64,
+    content_hash: Option<String>,
+    correction: Option<&'static str>,
+    correction_bytes: u64,
+    pos: u32,
+    neg: u32,
+    signature: Option<u64>,
+}
+
+fn correction_kind(c: &CorrectionType) -> &'static str {
+    match c {
+        CorrectionType::None => "none",
+        CorrectionType::BitFlips(_) => "bit_flips",
+        CorrectionType::TritFlips(_) => "trit_flips",
+        CorrectionType::BlockReplace { .. } => "block_replace",
+        CorrectionType::Verbatim(_) => "verbatim",
+        CorrectionType::Delta { .. } => "delta",
+    }
+}
+
+fn namespace_of(manifest: &Manifest, path: &str) -> Option<String> {
+    let (first, _) = path.split_once('/')?;
+    manifest.namespaces.contains_key(first).then(|| first.to_string())
+}
+
+fn rows(engram: &Engram, manifest: &Manifest) -> (Vec<FileRow>, Vec<ChunkRow>) {
+    let probes = default_probe_dims(DEFAULT_SIGNATURE_PROBES);
+    let mut files = Vec::with_capacity(manifest.files.len());
+    let mut chunks = Vec::new();
+
+    for file in &manifest.files {
+        files.push(FileRow {
+            path: file.path.clone(),
+            namespace: namespace_of(manifest, &file.path),
+            extension: extension_of(&file.path),
+            size: file.size as u64,
+            is_text: file.is_text,
+            chunks: file.chunks.len() as u32,
+            blake3: file.blake3.clone(),
+        });
+
+        let mut offset = 0u64;
+        for (idx, &chunk_id) in file.chunks.iter().enumerate() {
+            let len = EmbrFS::chunk_len(file, idx, manifest.chunk_size()) as u64;
+            let correction = engram.corrections.get(chunk_id as u64);
+            let vec = engram.codebook.get(&chunk_id);
+            chunks.push(ChunkRow {
+                path: file.path.clone(),
+                chunk_index: idx as u32,
+                chunk_id: chunk_id as u64,
+                offset,
+                len,
+                content_hash: correction.map(|c| to_hex(&c.hash)),
+                correction: correction.map(|c| correction_kind(&c.correction)),
+                correction_bytes: correction.map(|c| c.storage_size() as u64).unwrap_or(0),
+                pos: vec.map(|v| v.pos.len() as u32).unwrap_or(0),
+                neg: vec.map(|v| v.neg.len() as u32).unwrap_or(0),
+                signature: vec.map(|v| signature_for(v, &probes)),
+            });
+            offset += len;
+        }
+    }
+    (files, chunks)
+}
+
+/// Write `files.parquet` and `chunks.parquet` into `dir`, creating it if needed.
+pub fn to_parquet<P: AsRef<Path>>(engram: &Engram, manifest: &Manifest, dir: P) -> io::Result<ExportSummary> {
+    let dir = dir.as_ref();
+    imp::check_enabled()?;
+    let (files, chunks) = rows(engram, manifest);
+    std::fs::create_dir_all(dir)?;
+    imp::write_files(&dir.join(FILES_TABLE), &files)?;
+    imp::write_chunks(&dir.join(CHUNKS_TABLE), &chunks)?;
+    Ok(ExportSummary {
+        files: files.len(),
+        chunks: chunks.len(),
+    })
+}
+
+#[cfg(feature = "parquet")]
+mod imp {
+    use super::*;
+    use arrow_array::{ArrayRef, BooleanArray, RecordBatch, StringArray, UInt32Array, UInt64Array};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    /// Rows per record batch, bounding memory for very large manifests.
+    const BATCH_ROWS: usize = 64 * 1024;
+
+    pub(super) fn check_enabled() -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_table<T>(
+        path: &Path,
+        schema: SchemaRef,
+        rows: &[T],
+        columns: impl Fn(&[T]) -> Vec<ArrayRef>,
+    ) -> io::Result<()> {
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props))
+            .map_err(io::Error::other)?;
+        for batch in rows.chunks(BATCH_ROWS) {
+            let batch = RecordBatch::try_new(schema.clone(), columns(batch)).map_err(io::Error::other)?;
+            writer.write(&batch).map_err(io::Error::other)?;
+        }
+        writer.close().map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    pub(super) fn write_files(path: &Path, rows: &[FileRow]) -> io::Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("path", DataType::Utf8, false),
+            Field::new("namespace", DataType::Utf8, true),
+            Field::new("extension", DataType::Utf8, false),
+            Field::new("size", DataType::UInt64, false),
+            Field::new("is_text", DataType::Boolean, false),
+            Field::new("chunks", DataType::UInt32, false),
+            Field::new("blake3", DataType::Utf8, true),
+        ]));
+        write_table(path, schema, rows, |rows| {
+            vec![
+                Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r.path))) as ArrayRef,
+                Arc::new(StringArray::from_iter(rows.iter().map(|r| r.namespace.as_deref()))),
+                Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r.extension))),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.size))),
+                Arc::new(BooleanArray::from_iter(rows.iter().map(|r| Some(r.is_text)))),
+                Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.chunks))),
+                Arc::new(StringArray::from_iter(rows.iter().map(|r| r.blake3.as_deref()))),
+            ]
+        })
+    }
+
+    pub(super) fn write_chunks(path: &Path, rows: &[ChunkRow]) -> io::Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("path", DataType::Utf8, false),
+            Field::new("chunk_index", DataType::UInt32, false),
+            Field::new("chunk_id", DataType::UInt64, false),
+            Field::new("offset", DataType::UInt64, false),
+            Field::new("len", DataType::UInt64, false),
+            Field::new("content_hash", DataType::Utf8, true),
+            Field::new("correction", DataType::Utf8, true),
+            Field::new("correction_bytes", DataType::UInt64, false),
+            Field::new("pos", DataType::UInt32, false),
+            Field::new("neg", DataType::UInt32, false),
+            Field::new("signature", DataType::UInt64, true),
+        ]));
+        write_table(path, schema, rows, |rows| {
+            vec![
+                Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r.path))) as ArrayRef,
+                Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.chunk_index))),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.chunk_id))),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.offset))),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.len))),
+                Arc::new(StringArray::from_iter(rows.iter().map(|r| r.content_hash.as_deref()))),
+                Arc::new(StringArray::from_iter(rows.iter().map(|r| r.correction))),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.correction_bytes))),
+                Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.pos))),
+                Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.neg))),
+                Arc::new(UInt64Array::from_iter(rows.iter().map(|r| r.signature))),
+            ]
+        })
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+mod imp {
+    use super::*;
+
+    pub(super) fn check_enabled() -> io::Result<()> {
+        Err(io::Error::other("parquet export not enabled (enable feature `parquet`)"))
+    }
+
+    pub(super) fn write_files(_: &Path, _: &[FileRow]) -> io::Result<()> {
+        check_enabled()
+    }
+
+    pub(super) fn write_chunks(_: &Path, _: &[ChunkRow]) -> io::Result<()> {
+        check_enabled()
+    }
+}
@@ -0,0 +1,393 @@
+//! Generational engram store: snapshots that age into deltas and move off-host.
+//!
+//! Every [`GenerationalStore::commit`] records the current engram and
+//! manifest as a new generation. A [`GenerationPolicy`] then decides where
+//! each older generation lives:
+//!
+//! - the newest [`GenerationPolicy::keep_full`] generations stay as full
+//!   engram and manifest files, so reading them costs one load;
+//! - older ones are rewritten as a [delta](crate::delta) against the next
+//!   newer generation, which usually shrinks them to the chunks that changed;
+//! - deltas beyond the newest [`GenerationPolicy::keep_local`] generations
+//!   are pushed to a remote [`GenerationTier`] and deleted locally.
+//!
+//! The newest generation is always full, so every delta chain ends at a local
+//! file. [`GenerationalStore::read`] follows the chain from there, fetching
+//! remote deltas as it goes; callers never see which tier a generation is in.
+//!
+//! A store is a directory:
+//!
+//! ```text
+//! generations.json       generations, oldest first, and where each one lives
+//! <id>.engram, <id>.json generation <id> in full
+//! <id>.delta             generation <id> as a delta against a newer one
+//! ```
+//!
+//! The policy is recorded in the index, so later opens tier the same way.
+//! The index is replaced last and atomically. A crash during tiering leaves
+//! at most a stray file next to an index that still names what is present.
+
+use crate::delta::{engram_digest, EngramDelta};
+use crate::embrfs::{EmbrFS, Engram, Manifest};
+use crate::envelope::{BinaryWriteOptions, Keyring};
+use crate::signing::to_hex;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Index file name within the store directory.
+pub const GENERATIONS_INDEX: &str = "generations.json";
+const GENERATIONS_VERSION: u32 = 1;
+
+/// How many generations stay full and how many stay on this host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationPolicy {
+    /// Newest generations kept as full engram and manifest files (at least 1).
+    pub keep_full: usize,
+    /// Newest generations kept locally; older deltas go to the remote tier.
+    /// Never fewer than `keep_full`. Ignored when the store has no remote.
+    pub keep_local: usize,
+}
+
+impl Default for GenerationPolicy {
+    fn default() -> Self {
+        Self { keep_full: 2, keep_local: 8 }
+    }
+}
+
+/// Where a generation's file lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationLocation {
+    Local,
+    Remote,
+}
+
+/// One recorded generation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationRecord {
+    pub id: u64,
+    /// Commit time, seconds since the Unix epoch.
+    pub created: u64,
+    pub files: usize,
+    pub chunks: usize,
+    /// [`engram_digest`] of the generation's engram, hex encoded.
+    pub digest: String,
+    /// Generation this one is stored as a delta against; `None` when full.
+    pub base: Option<u64>,
+    pub location: GenerationLocation,
+    /// Size of the generation's files where they live.
+    pub stored_bytes: u64,
+}
+
+impl GenerationRecord {
+    pub fn is_full(&self) -> bool {
+        self.base.is_none()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GenerationIndex {
+    version: u32,
+    next_id: u64,
+    #[serde(default)]
+    policy: GenerationPolicy,
+    generations: Vec<GenerationRecord>,
+}
+
+/// Blob storage for generations that have left the host.
+///
+/// Names are plain file names (`<id>.delta`). Implementations over object
+/// stores or other hosts plug in here; [`DirectoryTier`] covers mounted
+/// network filesystems and tests.
+pub trait GenerationTier: Send + Sync {
+    fn put(&self, name: &str, data: &[u8]) -> io::Result<()>;
+    /// Fails with `NotFound` when no blob has that name.
+    fn get(&self, name: &str) -> io::Result<Vec<u8>>;
+}
+
+/// A [`GenerationTier`] that keeps blobs as files in a directory.
+pub struct DirectoryTier {
+    dir: PathBuf,
+}
+
+impl DirectoryTier {
+    /// Store blobs in `dir`, creating it if needed.
+    pub fn new<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self { dir: dir.as_ref().to_path_buf() })
+    }
+}
+
+impl GenerationTier for DirectoryTier {
+    fn put(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{name}.tmp"));
+        let file = File::create(&tmp)?;
+        (&file).write_all(data)?;
+        file.sync_data()?;
+        fs::rename(tmp, self.dir.join(name))
+    }
+
+    fn get(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.dir.join(name))
+    }
+}
+
+/// What one pass of [`GenerationalStore::apply_policy`] moved.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierReport {
+    /// Generations rewritten from full files to deltas.
+    pub compacted: Vec<u64>,
+    /// Generations pushed to the remote tier.
+    pub pushed: Vec<u64>,
+    /// Local bytes released by both.
+    pub bytes_freed: u64,
+}
+
+impl TierReport {
+    pub fn is_empty(&self) -> bool {
+        self.compacted.is_empty() && self.pushed.is_empty()
+    }
+}
+
+/// An opened generational store.
+pub struct GenerationalStore {
+    dir: PathBuf,
+    index: GenerationIndex,
+    remote: Option<Box<dyn GenerationTier>>,
+    write_options: BinaryWriteOptions,
+    keys: Keyring,
+}
+
+impl GenerationalStore {
+    /// Open the store in `dir`, creating an empty one if there is none.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let index = match fs::read(dir.join(GENERATIONS_INDEX)) {
+            Ok(data) => {
+                let index: GenerationIndex = serde_json::from_slice(&data)?;
+                if index.version != GENERATIONS_VERSION {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unsupported generation store version {}", index.version),
+                    ));
+                }
+                index
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => GenerationIndex {
+                version: GENERATIONS_VERSION,
+                next_id: 1,
+                policy: GenerationPolicy::default(),
+                generations: Vec::new(),
+            },
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            dir,
+            index,
+            remote: None,
+            write_options: BinaryWriteOptions::default(),
+            keys: Keyring::default(),
+        })
+    }
+
+    /// Tier by `policy` instead of the one recorded in the index, and record
+    /// it there on the next write.
+    pub fn with_policy(mut self, policy: GenerationPolicy) -> Self {
+        self.index.policy = policy;
+        self
+    }
+
+    /// Push generations past [`GenerationPolicy::keep_local`] to `remote`.
+    pub fn with_remote(mut self, remote: Box<dyn GenerationTier>) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Envelope options for the files this store writes.
+    pub fn with_write_options(mut self, opts: BinaryWriteOptions) -> Self {
+        self.write_options = opts;
+        self
+    }
+
+    /// Keys for reading generations written encrypted.
+    pub fn with_keys(mut self, keys: Keyring) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn policy(&self) -> GenerationPolicy {
+        self.index.policy
+    }
+
+    /// Recorded generations, oldest first.
+    pub fn generations(&self) -> &[GenerationRecord] {
+        &self.index.generations
+    }
+
+    pub fn latest(&self) -> Option<&GenerationRecord> {
+        self.index.generations.last()
+    }
+
+    /// Record the engram and manifest of `fs` as the newest generation, then
+    /// apply the policy to the older ones.
+    pub fn commit(&mut self, fs: &EmbrFS) -> io::Result<(GenerationRecord, TierReport)> {
+        let id = self.index.next_id;
+        let (engram_path, manifest_path) = self.full_paths(id);
+        fs.save_engram_with_options(&engram_path, self.write_options)?;
+        fs.save_manifest_with_options(&manifest_path, self.write_options)?;
+        let record = GenerationRecord {
+            id,
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            files: fs.manifest.files.len(),
+            chunks: fs.engram.codebook.len(),
+            digest: to_hex(&engram_digest(&fs.engram)?),
+            base: None,
+            location: GenerationLocation::Local,
+            stored_bytes: file_len(&engram_path)? + file_len(&manifest_path)?,
+        };
+        self.index.next_id = id + 1;
+        self.index.generations.push(record.clone());
+        let report = self.apply_policy()?;
+        Ok((record, report))
+    }
+
+    /// Rebuild generation `id`, whichever tier it is in.
+    pub fn read(&self, id: u64) -> io::Result<(Engram, Manifest)> {
+        // Walk towards newer generations until one is stored in full.
+        let mut chain = Vec::new();
+        let mut record = self.record(id)?;
+        while let Some(base) = record.base {
+            chain.push(record);
+            record = self.record(base)?;
+        }
+        let (mut engram, mut manifest) = self.read_full(record.id)?;
+        for record in chain.into_iter().rev() {
+            let delta = self.load_delta(record)?;
+            (engram, manifest) = delta.apply(&engram, &manifest)?;
+        }
+        Ok((engram, manifest))
+    }
+
+    /// Rewrite and move generations until the store matches its policy.
+    pub fn apply_policy(&mut self) -> io::Result<TierReport> {
+        let mut report = TierReport::default();
+        let count = self.index.generations.len();
+        let keep_full = self.index.policy.keep_full.max(1);
+        let keep_local = self.index.policy.keep_local.max(keep_full);
+
+        // Newest first, so each compaction can reuse the contents of the
+        // generation rebuilt just before it.
+        let mut newer: Option<(u64, Engram, Manifest)> = None;
+        for pos in (0..count.saturating_sub(keep_full)).rev() {
+            if !self.index.generations[pos].is_full() {
+                newer = None;
+                continue;
+            }
+            let id = self.index.generations[pos].id;
+            let base_id = self.index.generations[pos + 1].id;
+            let (base_engram, base_manifest) = match newer.take() {
+                Some((newer_id, engram, manifest)) if newer_id == base_id => (engram, manifest),
+                _ => self.read(base_id)?,
+            };
+            let (engram, manifest) = self.read_full(id)?;
+            let delta = EngramDelta::between(&base_engram, &base_manifest, &engram, &manifest)?;
+            let delta_path = self.delta_path(id);
+            delta.save(&delta_path, self.write_options)?;
+
+            let record = &mut self.index.generations[pos];
+            let freed = record.stored_bytes;
+            record.base = Some(base_id);
+            record.stored_bytes = file_len(&delta_path)?;
+            report.bytes_freed += freed.saturating_sub(record.stored_bytes);
+            self.save_index()?;
+            let (engram_path, manifest_path) = self.full_paths(id);
+            fs::remove_file(engram_path)?;
+            fs::remove_file(manifest_path)?;
+            report.compacted.push(id);
+            newer = Some((id, engram, manifest));
+        }
+        report.compacted.reverse();
+
+        if let Some(remote) = &self.remote {
+            for pos in 0..count.saturating_sub(keep_local) {
+                let record = &self.index.generations[pos];
+                if record.location == GenerationLocation::Remote || record.is_full() {
+                    continue;
+                }
+                let id = record.id;
+                let delta_path = self.delta_path(id);
+                remote.put(&delta_name(id), &fs::read(&delta_path)?)?;
+                self.index.generations[pos].location = GenerationLocation::Remote;
+                self.save_index()?;
+                report.bytes_freed += file_len(&delta_path)?;
+                fs::remove_file(delta_path)?;
+                report.pushed.push(id);
+            }
+        }
+        self.save_index()?;
+        Ok(report)
+    }
+
+    fn record(&self, id: u64) -> io::Result<&GenerationRecord> {
+        self.index
+            .generations
+            .iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no generation {id}")))
+    }
+
+    fn read_full(&self, id: u64) -> io::Result<(Engram, Manifest)> {
+        let (engram_path, manifest_path) = self.full_paths(id);
+        Ok((
+            EmbrFS::load_engram_with_keys(engram_path, &self.keys)?,
+            EmbrFS::load_manifest_with_keys(manifest_path, &self.keys)?,
+        ))
+    }
+
+    fn load_delta(&self, record: &GenerationRecord) -> io::Result<EngramDelta> {
+        match record.location {
+            GenerationLocation::Local => EngramDelta::load_with_keys(self.delta_path(record.id), &self.keys),
+            GenerationLocation::Remote => {
+                let remote = self.remote.as_ref().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("generation {} is in the remote tier, but no remote is configured", record.id),
+                    )
+                })?;
+                EngramDelta::from_bytes(&remote.get(&delta_name(record.id))?, &self.keys)
+            }
+        }
+    }
+
+    fn full_paths(&self, id: u64) -> (PathBuf, PathBuf) {
+        (self.dir.join(format!("{id}.engram")), self.dir.join(format!("{id}.json")))
+    }
+
+    fn delta_path(&self, id: u64) -> PathBuf {
+        self.dir.join(delta_name(id))
+    }
+
+    fn save_index(&self) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{GENERATIONS_INDEX}.tmp"));
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer_pretty(&mut out, &self.index)?;
+        out.flush()?;
+        fs::rename(tmp, self.dir.join(GENERATIONS_INDEX))
+    }
+}
+
+fn delta_name(id: u64) -> String {
+    format!("{id}.delta")
+}
+
+fn file_len(path: &Path) -> io::Result<u64> {
+    Ok(fs::metadata(path)?.len())
+}
@@ -0,0 +1,321 @@
+//! On-disk, lazily decoded reader for whole-engram envelopes.
+//!
+//! [`EmbrFS::load_engram`] deserializes every codebook vector before
+//! returning, so loading takes as long as reading the whole file and needs
+//! as much memory again. [`LazyEngram::open`] walks the serialized engram
+//! once, reading only the length prefix of each codebook vector and seeking
+//! over its body, and keeps a sorted table of where every vector and
+//! correction record starts. Chunks are read back from the file when asked
+//! for; only the root vector, the offset table and the correction totals
+//! stay in memory.
+//!
+//! On an uncompressed engram (bare bincode, or an envelope with only a
+//! checksum) opening touches a few bytes per chunk. Compressed or encrypted
+//! envelopes can only be read a frame at a time, so opening decodes each
+//! frame once, still without holding more than one in memory; for engrams
+//! that are opened often, prefer no compression or sharding (see
+//! [`crate::shards`]).
+//!
+//! Split engrams are read as one sequential stream and can't be opened
+//! lazily; nor can rkyv archives or append logs, which have their own
+//! in-place readers ([`crate::RkyvEngram`], [`crate::Envelope`]).
+
+use crate::append_log;
+use crate::correction::{self, ChunkCorrection, CorrectionStore, CorrectionTotals, CorrectionType};
+use crate::embrfs::{bincode_io_error, ChecksumMismatch, EmbrFS, Engram, FileEntry};
+use crate::envelope::{Keyring, PayloadKind, SeekableEnvelope};
+use crate::error::EmbrError;
+use crate::multipart;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Whether the engram at `path` is a whole-engram envelope (or legacy bare
+/// bincode) that [`LazyEngram::open`] can read.
+pub fn is_lazy_loadable<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    Ok(match PayloadKind::sniff_file(path)? {
+        Some(kind) => kind == PayloadKind::EngramBincode,
+        None => !append_log::is_append_log(path)? && !multipart::is_part_index(path)?,
+    })
+}
+
+/// Read-only view of an engram file that decodes chunks on access.
+pub struct LazyEngram {
+    reader: Mutex<BufReader<SeekableEnvelope<File>>>,
+    root: SparseVec,
+    /// Chunk id and payload offset of its codebook vector, sorted by id.
+    chunks: Vec<(u64, u64)>,
+    /// Chunk id and payload offset of its correction record, sorted by id.
+    corrections: Vec<(u64, u64)>,
+    totals: CorrectionTotals,
+}
+
+impl LazyEngram {
+    /// Index the engram at `path` without decoding its codebook.
+    pub fn open<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !is_lazy_loadable(path)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is not a whole-engram envelope; load it with EmbrFS::load_engram instead",
+                    path.display()
+                ),
+            ));
+        }
+        let envelope = SeekableEnvelope::open(File::open(path)?, PayloadKind::EngramBincode, keys)?;
+        let len = envelope.len();
+        let mut reader = BufReader::new(envelope);
+
+        let root: SparseVec = decode(&mut reader)?;
+        let mut chunks = Vec::new();
+        for _ in 0..read_len(&mut reader, len)? {
+            let id = read_u64(&mut reader)?;
+            chunks.push((id, reader.stream_position()?));
+            for _ in 0..2 {
+                let nnz = read_len(&mut reader, len)?;
+                skip(&mut reader, nnz * 8, len)?;
+            }
+        }
+        let mut corrections = Vec::new();
+        for _ in 0..read_len(&mut reader, len)? {
+            let id = read_u64(&mut reader)?;
+            corrections.push((id, reader.stream_position()?));
+            decode::<ChunkCorrection, _>(&mut reader)?;
+        }
+        let totals: CorrectionTotals = decode(&mut reader)?;
+        if reader.stream_position()? != len {
+            return Err(invalid("trailing bytes after the engram"));
+        }
+        chunks.sort_unstable();
+        corrections.sort_unstable();
+
+        Ok(Self {
+            reader: Mutex::new(reader),
+            root,
+            chunks,
+            corrections,
+            totals,
+        })
+    }
+
+    pub fn root(&self) -> &SparseVec {
+        &self.root
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Chunk ids in ascending order.
+    pub fn chunk_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.chunks.iter().map(|&(id, _)| id as usize)
+    }
+
+    pub fn contains_chunk(&self, chunk_id: usize) -> bool {
+        offset_of(&self.chunks, chunk_id).is_some()
+    }
+
+    /// Heap bytes of the offset tables held in memory.
+    pub fn index_bytes(&self) -> u64 {
+        ((self.chunks.capacity() + self.corrections.capacity()) * std::mem::size_of::<(u64, u64)>())
+            as u64
+    }
+
+    fn read_at<T: DeserializeOwned>(&self, offset: u64) -> io::Result<T> {
+        let mut reader = self
+            .reader
+            .lock()
+            .expect("lazy engram reader lock poisoned");
+        reader.seek(SeekFrom::Start(offset))?;
+        decode(&mut *reader)
+    }
+
+    /// Read the codebook vector for one chunk from disk.
+    pub fn chunk(&self, chunk_id: usize) -> io::Result<Option<SparseVec>> {
+        offset_of(&self.chunks, chunk_id)
+            .map(|offset| self.read_at(offset))
+            .transpose()
+    }
+
+    /// Read the correction record for one chunk from disk.
+    pub fn correction(&self, chunk_id: usize) -> io::Result<Option<ChunkCorrection>> {
+        offset_of(&self.corrections, chunk_id)
+            .map(|offset| self.read_at(offset))
+            .transpose()
+    }
+
+    /// Original bytes of chunk `chunk_id`, `len` bytes of the file at
+    /// `path`, decoded and corrected as [`Engram`] does it (binary deltas
+    /// included). `None` if the chunk is absent from the codebook; a chunk
+    /// whose correction does not verify is returned as decoded.
+    pub fn chunk_bytes(
+        &self,
+        chunk_id: usize,
+        path: &str,
+        len: usize,
+        config: &ReversibleVSAConfig,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let Some(vec) = self.chunk(chunk_id)? else {
+            return Ok(None);
+        };
+        let decoded = vec.decode_data(config, Some(path), len);
+        let Some(c) = self.correction(chunk_id)? else {
+            return Ok(Some(decoded));
+        };
+        let fixed = match &c.correction {
+            CorrectionType::Delta {
+                base,
+                base_path,
+                base_len,
+                ops,
+            } if (*base as usize) < chunk_id => self
+                .chunk_bytes(*base as usize, base_path, *base_len as usize, config)?
+                .and_then(|base| correction::apply_delta(&base, ops)),
+            _ => Some(c.apply(&decoded)),
+        };
+        Ok(Some(fixed.filter(|f| c.verify(f)).unwrap_or(decoded)))
+    }
+
+    /// Reconstruct one file, reading only the chunks it references.
+    ///
+    /// A checksum mismatch is returned as an `InvalidData` error.
+    pub fn read_file(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+    ) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(file_entry.size);
+        self.write_file(file_entry, config, chunk_size, &mut out)?;
+        Ok(out)
+    }
+
+    /// Reconstruct one file into `out` chunk by chunk.
+    ///
+    /// As with [`crate::Envelope::write_file`], a checksum mismatch is an
+    /// `InvalidData` error returned after every byte has been written.
+    pub fn write_file<W: Write>(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let mut hasher = blake3::Hasher::new();
+        let mut bad_chunks = Vec::new();
+        for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
+            let Some(vec) = self.chunk(chunk_id)? else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{}: chunk {} is missing from the engram",
+                        file_entry.path, chunk_id
+                    ),
+                ));
+            };
+            let decoded = vec.decode_data(
+                config,
+                Some(&file_entry.path),
+                EmbrFS::chunk_len(file_entry, chunk_idx, chunk_size),
+            );
+            let chunk = match self.correction(chunk_id)? {
+                Some(c) => {
+                    let fixed = c.apply(&decoded);
+                    if c.verify(&fixed) {
+                        fixed
+                    } else {
+                        bad_chunks.push(chunk_id);
+                        decoded
+                    }
+                }
+                None => decoded,
+            };
+            hasher.update(&chunk);
+            out.write_all(&chunk)?;
+        }
+
+        let Some(expected) = file_entry.blake3.as_ref() else {
+            return Ok(());
+        };
+        let actual = hasher.finalize().to_hex().to_string();
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(());
+        }
+        Err(EmbrError::ManifestMismatch(vec![ChecksumMismatch {
+            path: file_entry.path.clone(),
+            expected: expected.clone(),
+            actual,
+            chunk_ids: bad_chunks,
+        }])
+        .into())
+    }
+
+    /// Read every chunk into a regular in-memory [`Engram`].
+    pub fn to_engram(&self) -> io::Result<Engram> {
+        let mut codebook = HashMap::with_capacity(self.chunks.len());
+        for &(id, offset) in &self.chunks {
+            codebook.insert(id as usize, self.read_at(offset)?);
+        }
+        let mut corrections = HashMap::with_capacity(self.corrections.len());
+        for &(id, offset) in &self.corrections {
+            corrections.insert(id, self.read_at(offset)?);
+        }
+        Ok(Engram {
+            root: self.root.clone(),
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, self.totals),
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        })
+    }
+}
+
+fn offset_of(table: &[(u64, u64)], chunk_id: usize) -> Option<u64> {
+    table
+        .binary_search_by_key(&(chunk_id as u64), |&(id, _)| id)
+        .ok()
+        .map(|i| table[i].1)
+}
+
+fn decode<T: DeserializeOwned, R: Read>(reader: &mut R) -> io::Result<T> {
+    bincode::deserialize_from(reader).map_err(|e| bincode_io_error(*e))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// A sequence length prefix, rejected if the elements couldn't fit in the
+/// `len`-byte payload; guards allocations on input that isn't an engram.
+fn read_len<R: Read>(reader: &mut R, len: u64) -> io::Result<u64> {
+    let n = read_u64(reader)?;
+    if n > len {
+        return Err(invalid("sequence length exceeds the payload"));
+    }
+    Ok(n)
+}
+
+fn skip<R: Read + Seek>(reader: &mut BufReader<R>, n: u64, len: u64) -> io::Result<()> {
+    let target = reader.stream_position()? + n;
+    if target > len {
+        return Err(invalid("codebook vector runs past the end of the payload"));
+    }
+    reader.seek_relative(n as i64)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed engram: {}", msg),
+    )
+}
@@ -0,0 +1,197 @@
+//! Memory-mapped, lazily decoded reader for append-log engrams.
+//!
+//! [`EmbrFS::load_append_log`] deserializes every chunk before returning, which
+//! is wasteful when a caller only needs a few files out of a very large engram.
+//! [`Envelope::open_mmap`] maps the log, parses just the file header and the
+//! commit index, and decodes individual chunks, the manifest or the root
+//! vector only when asked. Opening costs O(commits), not O(codebook).
+//!
+//! The reader never writes: a torn tail from an interrupted save is ignored
+//! rather than truncated, so the view matches the last complete commit.
+//!
+//! Requires the `mmap` feature; without it `open_mmap` returns an error.
+
+use crate::append_log::{self, LogIndex, RecordRef};
+use crate::correction::ChunkCorrection;
+use crate::embrfs::{decode_log_chunk, decode_log_meta, ChecksumMismatch, EmbrFS, FileEntry, Manifest};
+use crate::error::EmbrError;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use std::fs::File;
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+
+/// Read-only, lazily decoded view of an append-log engram.
+pub struct Envelope {
+    map: imp::Map,
+    index: LogIndex,
+    ignored_bytes: u64,
+}
+
+impl Envelope {
+    /// Map the log at `path` and load its index without decoding payloads.
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let map = imp::map(&file)?;
+        let mut cursor = Cursor::new(&map[..]);
+        append_log::check_header(&mut cursor)?;
+        let len = map.len() as u64;
+        let (last_commit, end) = append_log::locate_last_commit(&mut cursor, len)?;
+        let index = LogIndex::load(&mut cursor, last_commit)?;
+        Ok(Self {
+            map,
+            index,
+            ignored_bytes: len - end,
+        })
+    }
+
+    /// Bytes past the last complete commit (a torn tail) that were ignored.
+    pub fn ignored_bytes(&self) -> u64 {
+        self.ignored_bytes
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.index.chunks.len()
+    }
+
+    pub fn chunk_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.index.chunks.keys().map(|&id| id as usize)
+    }
+
+    pub fn contains_chunk(&self, chunk_id: usize) -> bool {
+        self.index.chunks.contains_key(&(chunk_id as u64))
+    }
+
+    fn payload(&self, rec: RecordRef) -> io::Result<Vec<u8>> {
+        append_log::read_payload(&mut Cursor::new(&self.map[..]), rec)
+    }
+
+    fn chunk_entry(&self, chunk_id: usize) -> io::Result<Option<(Option<SparseVec>, Option<ChunkCorrection>)>> {
+        match self.index.chunks.get(&(chunk_id as u64)) {
+            Some(&rec) => decode_log_chunk(&self.payload(rec)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decode the codebook vector for one chunk.
+    pub fn chunk(&self, chunk_id: usize) -> io::Result<Option<SparseVec>> {
+        Ok(self.chunk_entry(chunk_id)?.and_then(|(vec, _)| vec))
+    }
+
+    /// Decode the correction record for one chunk.
+    pub fn correction(&self, chunk_id: usize) -> io::Result<Option<ChunkCorrection>> {
+        Ok(self.chunk_entry(chunk_id)?.and_then(|(_, correction)| correction))
+    }
+
+    /// Decode the engram's root vector.
+    pub fn root(&self) -> io::Result<SparseVec> {
+        let rec = self.index.meta.ok_or_else(|| missing("engram metadata"))?;
+        Ok(decode_log_meta(&self.payload(rec)?)?.0)
+    }
+
+    /// Decode the manifest.
+    pub fn manifest(&self) -> io::Result<Manifest> {
+        let rec = self.index.manifest.ok_or_else(|| missing("manifest"))?;
+        Ok(Manifest::from_json_slice(&self.payload(rec)?)?)
+    }
+
+    /// Reconstruct one file, decoding only the chunks it references.
+    /// `config` and `chunk_size` are those the manifest records
+    /// ([`crate::Manifest::encoding`]).
+    ///
+    /// A checksum mismatch is returned as an `InvalidData` error.
+    pub fn read_file(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+    ) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(file_entry.size);
+        self.write_file(file_entry, config, chunk_size, &mut out)?;
+        Ok(out)
+    }
+
+    /// Reconstruct one file into `out` chunk by chunk, so at most one
+    /// decoded chunk is held in memory.
+    ///
+    /// The whole-file checksum can only be compared at the end: on a
+    /// mismatch every byte has already been written when the `InvalidData`
+    /// error is returned.
+    pub fn write_file<W: Write>(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let mut hasher = blake3::Hasher::new();
+        let mut bad_chunks = Vec::new();
+        for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
+            let Some((Some(vec), correction)) = self.chunk_entry(chunk_id)? else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: chunk {} is missing from the engram", file_entry.path, chunk_id),
+                ));
+            };
+            let decoded = vec.decode_data(config, Some(&file_entry.path), EmbrFS::chunk_len(file_entry, chunk_idx, chunk_size));
+            let chunk = match correction {
+                Some(c) => {
+                    let fixed = c.apply(&decoded);
+                    if c.verify(&fixed) {
+                        fixed
+                    } else {
+                        bad_chunks.push(chunk_id);
+                        decoded
+                    }
+                }
+                None => decoded,
+            };
+            hasher.update(&chunk);
+            out.write_all(&chunk)?;
+        }
+
+        let Some(expected) = file_entry.blake3.as_ref() else {
+            return Ok(());
+        };
+        let actual = hasher.finalize().to_hex().to_string();
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(());
+        }
+        Err(EmbrError::ManifestMismatch(vec![ChecksumMismatch {
+            path: file_entry.path.clone(),
+            expected: expected.clone(),
+            actual,
+            chunk_ids: bad_chunks,
+        }])
+        .into())
+    }
+}
+
+fn missing(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("append log has no {}", what))
+}
+
+#[cfg(feature = "mmap")]
+mod imp {
+    use super::*;
+
+    pub(super) type Map = memmap2::Mmap;
+
+    pub(super) fn map(file: &File) -> io::Result<Map> {
+        // SAFETY: the mapping is read-only, and append-log writers only append
+        // past the mapped length. The one exception is `AppendLog::open`
+        // truncating a torn tail, which callers must not run concurrently
+        // with a reader over the same file.
+        unsafe { memmap2::Mmap::map(file) }
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+mod imp {
+    use super::*;
+
+    pub(super) type Map = Box<[u8]>;
+
+    pub(super) fn map(_: &File) -> io::Result<Map> {
+        Err(io::Error::other("memory-mapped reads not enabled (enable feature `mmap`)"))
+    }
+}
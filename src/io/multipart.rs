@@ -0,0 +1,272 @@
+//! Splitting one logical engram across several part files.
+//!
+//! Object stores and FAT-formatted media cap individual file sizes, so a large
+//! engram can be written as `N` part files plus a small master index:
+//!
+//! ```text
+//! root.engram            -- EDN1 envelope, kind PartIndex, JSON PartIndex
+//! root.engram.part0000   -- bytes [0, part_size)
+//! root.engram.part0001   -- bytes [part_size, 2 * part_size)
+//! ...
+//! ```
+//!
+//! The parts are a plain byte split of whatever the engram writer produced,
+//! so compression, checksums and encryption apply to the logical stream as
+//! usual. The index records each part's length and blake3 digest; readers
+//! check both while streaming, so a swapped or truncated part is reported by
+//! name. Because the index is the file passed around as "the engram", loaders
+//! such as [`crate::EmbrFS::load_engram`] span parts transparently, and a
+//! detached signature over the index covers every part through its digests.
+
+use crate::envelope::{BinaryWriteOptions, ChecksumCodec, EnvelopeReader, EnvelopeWriter, PayloadKind};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Current part index document version.
+pub const PART_INDEX_VERSION: u32 = 1;
+
+/// One part file of a split payload.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartEntry {
+    /// File name, relative to the directory holding the index.
+    pub file: String,
+    pub len: u64,
+    /// Hex-encoded blake3 of the part's bytes.
+    pub blake3: String,
+}
+
+/// Master index listing the parts of a split payload, in order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartIndex {
+    pub version: u32,
+    pub part_size: u64,
+    pub total_len: u64,
+    pub parts: Vec<PartEntry>,
+}
+
+fn part_file_name(index_path: &Path, n: usize) -> io::Result<String> {
+    let name = index_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "part index path needs a UTF-8 file name"))?;
+    Ok(format!("{}.part{:04}", name, n))
+}
+
+fn index_dir(index_path: &Path) -> &Path {
+    index_path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+/// Write adapter that spreads its input over part files of at most
+/// `part_size` bytes, then writes the master index on [`PartWriter::finish`].
+pub struct PartWriter {
+    index_path: PathBuf,
+    part_size: u64,
+    current: Option<(BufWriter<File>, blake3::Hasher, u64)>,
+    parts: Vec<PartEntry>,
+    total_len: u64,
+}
+
+impl PartWriter {
+    pub fn create<P: AsRef<Path>>(index_path: P, part_size: u64) -> io::Result<Self> {
+        if part_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "part size must be positive"));
+        }
+        let index_path = index_path.as_ref().to_path_buf();
+        part_file_name(&index_path, 0)?;
+        Ok(Self {
+            index_path,
+            part_size,
+            current: None,
+            parts: Vec::new(),
+            total_len: 0,
+        })
+    }
+
+    fn close_part(&mut self) -> io::Result<()> {
+        if let Some((mut file, hasher, len)) = self.current.take() {
+            file.flush()?;
+            file.get_ref().sync_data()?;
+            self.parts.push(PartEntry {
+                file: part_file_name(&self.index_path, self.parts.len())?,
+                len,
+                blake3: hasher.finalize().to_hex().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Close the last part, write the index and remove stale parts left by an
+    /// earlier, longer save to the same path.
+    pub fn finish(mut self) -> io::Result<PartIndex> {
+        self.close_part()?;
+        let index = PartIndex {
+            version: PART_INDEX_VERSION,
+            part_size: self.part_size,
+            total_len: self.total_len,
+            parts: self.parts,
+        };
+
+        let opts = BinaryWriteOptions {
+            checksum: ChecksumCodec::Xxh3,
+            ..Default::default()
+        };
+        let file = BufWriter::new(File::create(&self.index_path)?);
+        let mut writer = EnvelopeWriter::new(file, PayloadKind::PartIndex, opts)?;
+        serde_json::to_writer_pretty(&mut writer, &index)?;
+        writer.finish()?.flush()?;
+
+        let dir = index_dir(&self.index_path);
+        for n in index.parts.len().. {
+            let stale = dir.join(part_file_name(&self.index_path, n)?);
+            if !stale.exists() {
+                break;
+            }
+            fs::remove_file(stale)?;
+        }
+        Ok(index)
+    }
+}
+
+impl Write for PartWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if self.current.as_ref().is_some_and(|(_, _, len)| *len == self.part_size) {
+            self.close_part()?;
+        }
+        if self.current.is_none() {
+            let name = part_file_name(&self.index_path, self.parts.len())?;
+            let file = File::create(index_dir(&self.index_path).join(name))?;
+            self.current = Some((BufWriter::new(file), blake3::Hasher::new(), 0));
+        }
+
+        let (file, hasher, len) = self.current.as_mut().expect("part opened above");
+        let n = data.len().min((self.part_size - *len) as usize);
+        file.write_all(&data[..n])?;
+        hasher.update(&data[..n]);
+        *len += n as u64;
+        self.total_len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((file, _, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Load a master index written by [`PartWriter`].
+pub fn read_part_index<P: AsRef<Path>>(index_path: P) -> io::Result<PartIndex> {
+    let file = BufReader::new(File::open(index_path)?);
+    let mut reader = EnvelopeReader::new(file, PayloadKind::PartIndex)?;
+    let index: PartIndex = serde_json::from_reader(&mut reader)?;
+    io::copy(&mut reader, &mut io::sink())?;
+    if index.version != PART_INDEX_VERSION {
+        return Err(io::Error::other(format!("unsupported part index version {}", index.version)));
+    }
+    if index.parts.iter().map(|p| p.len).sum::<u64>() != index.total_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "part index lengths do not add up"));
+    }
+    Ok(index)
+}
+
+/// Whether the file at `path` is a master index rather than a payload.
+pub fn is_part_index<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    Ok(PayloadKind::sniff_file(path)? == Some(PayloadKind::PartIndex))
+}
+
+/// Read adapter that streams the parts listed in an index as one payload,
+/// verifying each part's length and digest as it is consumed.
+pub struct PartReader {
+    dir: PathBuf,
+    parts: std::vec::IntoIter<PartEntry>,
+    current: Option<(BufReader<File>, blake3::Hasher, PartEntry, u64)>,
+}
+
+impl PartReader {
+    /// Open the index at `index_path`, checking that every part is present
+    /// with the recorded size before any bytes are returned.
+    pub fn open<P: AsRef<Path>>(index_path: P) -> io::Result<Self> {
+        let index_path = index_path.as_ref();
+        let index = read_part_index(index_path)?;
+        let dir = index_dir(index_path).to_path_buf();
+        for part in &index.parts {
+            if Path::new(&part.file).components().count() != 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("part name {:?} must be a bare file name", part.file),
+                ));
+            }
+            let len = fs::metadata(dir.join(&part.file))
+                .map_err(|e| io::Error::new(e.kind(), format!("part {}: {}", part.file, e)))?
+                .len();
+            if len != part.len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("part {} is {} bytes, index expects {}", part.file, len, part.len),
+                ));
+            }
+        }
+        Ok(Self {
+            dir,
+            parts: index.parts.into_iter(),
+            current: None,
+        })
+    }
+}
+
+impl Read for PartReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.current.is_none() {
+                let Some(part) = self.parts.next() else {
+                    return Ok(0);
+                };
+                let file = File::open(self.dir.join(&part.file))?;
+                self.current = Some((BufReader::new(file), blake3::Hasher::new(), part, 0));
+            }
+
+            let (file, hasher, part, read) = self.current.as_mut().expect("part opened above");
+            let want = out.len().min((part.len - *read) as usize);
+            let n = if want == 0 { 0 } else { file.read(&mut out[..want])? };
+            if n > 0 {
+                hasher.update(&out[..n]);
+                *read += n as u64;
+                return Ok(n);
+            }
+
+            if *read != part.len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("part {} ended after {} of {} bytes", part.file, read, part.len),
+                ));
+            }
+            let actual = hasher.finalize().to_hex().to_string();
+            if !actual.eq_ignore_ascii_case(&part.blake3) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("part {} does not match its recorded blake3 digest", part.file),
+                ));
+            }
+            self.current = None;
+        }
+    }
+}
+
+/// Open `path` for reading, spanning part files if it is a master index.
+pub fn open_spanning<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    if is_part_index(path)? {
+        Ok(Box::new(PartReader::open(path)?))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
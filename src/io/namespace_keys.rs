@@ -0,0 +1,502 @@
+//! Encryption keys per namespace.
+//!
+//! Envelope encryption ([`BinaryWriteOptions::key`]) puts a whole engram
+//! behind one key. Key rules instead put the chunks of each namespace, or
+//! of any other path prefix, under a key of its own, so one engram can hold
+//! data for several parties and each can read only its own part:
+//!
+//! ```text
+//! root.engram                          -- chunks of files no rule covers
+//! root.engram.key0002.3fa4c1d2e5b6a798 -- EDN1 envelope, kind KeyedChunks, encrypted with key 2
+//! root.engram.key0003.90be7714a0c3d5f1 -- the same for key 3
+//! ...
+//! ```
+//!
+//! The manifest's [`KeyManifest`] holds the rules and, for each key, the
+//! group file with its size and blake3 digest. No key material is stored.
+//! [`open`] decrypts the groups whose key a [`KeyProvider`] has into a
+//! loaded engram. Files under the other keys stay unreadable, and
+//! [`OpenReport::require`] names the key they need.
+//!
+//! [`rekey`] applies a new set of rules. A group is written again only if
+//! its chunks or its key changed, so rotating the key of one namespace
+//! re-encrypts that namespace's chunks and leaves the engram and every other
+//! group as they are. A group is written to a new file named after its
+//! digest and the file it replaces is deleted only after the manifest
+//! naming the new one is saved, so a crash never leaves the saved manifest
+//! naming a group that is gone.
+//!
+//! A chunk is stored in every group that has a file using it, and in the
+//! engram itself if a file no rule covers uses it. The root vector still
+//! bundles every chunk. The manifest's paths and sizes stay readable unless
+//! the manifest is itself encrypted.
+
+use crate::correction::ChunkCorrection;
+use crate::embrfs::{decode_bounded, EmbrFS, Engram, FileEntry, LoadLimits, Manifest};
+use crate::envelope::{BinaryWriteOptions, EncryptionCodec, EncryptionKey, EnvelopeReader, Keyring, PayloadKind};
+use crate::error::EmbrError;
+use crate::seal;
+use crate::shards::write_envelope;
+use crate::txn::Transaction;
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Source of the keys [`open`] and [`rekey`] need.
+///
+/// [`Keyring`] holds keys in memory. An external key management service
+/// plugs in by implementing this trait, or as a closure of the same
+/// signature, and is asked for each key id when it is first needed.
+pub trait KeyProvider {
+    /// Key `key_id`, or `None` if this provider does not have it.
+    fn key(&self, key_id: u32) -> io::Result<Option<EncryptionKey>>;
+}
+
+impl KeyProvider for Keyring {
+    fn key(&self, key_id: u32) -> io::Result<Option<EncryptionKey>> {
+        Ok(self.get(key_id).copied())
+    }
+}
+
+impl<F> KeyProvider for F
+where
+    F: Fn(u32) -> io::Result<Option<EncryptionKey>>,
+{
+    fn key(&self, key_id: u32) -> io::Result<Option<EncryptionKey>> {
+        self(key_id)
+    }
+}
+
+/// Files under `prefix` are encrypted with key `key_id`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRule {
+    /// A namespace or other path prefix. It matches whole path components,
+    /// so `a` covers `a/x` but not `ab/x`.
+    pub prefix: String,
+    pub key_id: u32,
+}
+
+impl KeyRule {
+    pub fn new(prefix: impl Into<String>, key_id: u32) -> Self {
+        Self {
+            prefix: prefix.into(),
+            key_id,
+        }
+    }
+
+    fn covers(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        match path.strip_prefix(prefix) {
+            Some(rest) => prefix.is_empty() || rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+}
+
+/// The chunks encrypted with one key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyGroup {
+    pub key_id: u32,
+    /// File name, relative to the directory holding the engram.
+    pub file: String,
+    pub chunks: usize,
+    pub len: u64,
+    /// Hex-encoded blake3 of the group file.
+    pub blake3: String,
+    /// Hex-encoded blake3 of the group's chunk ids, to tell whether new
+    /// rules leave the group as it is.
+    pub ids_digest: String,
+}
+
+/// Key rules of an engram and the groups they produced; see the
+/// [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyManifest {
+    pub rules: Vec<KeyRule>,
+    /// Sorted by key id.
+    pub groups: Vec<KeyGroup>,
+}
+
+impl KeyManifest {
+    /// Key the file at `path` is encrypted with, or `None` if it is stored
+    /// in the engram. The longest matching prefix wins.
+    pub fn key_for(&self, path: &str) -> Option<u32> {
+        key_for(&self.rules, path)
+    }
+
+    pub fn group(&self, key_id: u32) -> Option<&KeyGroup> {
+        self.groups.iter().find(|g| g.key_id == key_id)
+    }
+}
+
+fn key_for(rules: &[KeyRule], path: &str) -> Option<u32> {
+    rules
+        .iter()
+        .filter(|r| r.covers(path))
+        .max_by_key(|r| r.prefix.trim_end_matches('/').len())
+        .map(|r| r.key_id)
+}
+
+/// Groups [`open`] decrypted and those it could not.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct OpenReport {
+    pub opened: Vec<u32>,
+    /// Groups whose key the provider did not have.
+    pub locked: Vec<u32>,
+    /// Chunk records added to the engram.
+    pub chunks: usize,
+}
+
+impl OpenReport {
+    /// Fail with [`EmbrError::MissingKey`] if any of `files` is under a key
+    /// that stayed locked.
+    pub fn require<'a>(&self, keys: &KeyManifest, files: impl IntoIterator<Item = &'a FileEntry>) -> io::Result<()> {
+        for file in files {
+            if let Some(id) = keys.key_for(&file.path).filter(|id| self.locked.contains(id)) {
+                return Err(EmbrError::MissingKey(format!(
+                    "{} is encrypted with namespace key id {}, which was not provided",
+                    file.path, id
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What [`rekey`] did, by key id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RekeyReport {
+    /// Groups written, with their chunk counts.
+    pub written: BTreeMap<u32, usize>,
+    /// Groups left as they were.
+    pub unchanged: Vec<u32>,
+    /// Keys no rule uses any more.
+    pub removed: Vec<u32>,
+    /// Group files the new manifest no longer names, to delete with
+    /// [`remove_stale`] once it is saved.
+    pub stale: Vec<String>,
+    /// The engram gained or lost chunks and needs saving again.
+    pub engram_changed: bool,
+}
+
+/// Decoded contents of one group file.
+#[derive(Default, Serialize, Deserialize)]
+struct Group {
+    codebook: BTreeMap<usize, SparseVec>,
+    corrections: BTreeMap<u64, ChunkCorrection>,
+}
+
+/// `<engram>.key<id>.<generation>`; the generation tells apart the files
+/// successive rekeys write for one key.
+fn group_file_name(engram_path: &Path, key_id: u32, generation: &str) -> io::Result<String> {
+    let name = engram_path.file_name().and_then(|n| n.to_str()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "engram path needs a UTF-8 file name",
+        )
+    })?;
+    Ok(format!("{}.key{:04}.{}", name, key_id, generation))
+}
+
+fn engram_dir(engram_path: &Path) -> &Path {
+    engram_path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+fn require_key(keys: &dyn KeyProvider, key_id: u32, purpose: &str) -> io::Result<EncryptionKey> {
+    keys.key(key_id)?.ok_or_else(|| {
+        EmbrError::MissingKey(format!("namespace key id {} is needed to {}", key_id, purpose)).into()
+    })
+}
+
+/// Read group `entry`, check its digest and decrypt it with `key`.
+fn read_group(dir: &Path, entry: &KeyGroup, key: EncryptionKey) -> io::Result<Group> {
+    let data = fs::read(dir.join(&entry.file))?;
+    if !blake3::hash(&data).to_hex().eq_ignore_ascii_case(&entry.blake3) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("key group {} does not match its recorded blake3 digest", entry.file),
+        ));
+    }
+    let keys: Keyring = [key].into_iter().collect();
+    let mut reader = EnvelopeReader::with_keys(&data[..], PayloadKind::KeyedChunks, &keys)?;
+    let group = decode_bounded(&mut reader, LoadLimits::default().max_bytes)?;
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(group)
+}
+
+/// Decrypt every group of `manifest` whose key `keys` has into `engram`,
+/// which was loaded from `engram_path`.
+pub fn open(engram: &mut Engram, manifest: &Manifest, engram_path: &Path, keys: &dyn KeyProvider) -> io::Result<OpenReport> {
+    let mut report = OpenReport::default();
+    let Some(key_manifest) = &manifest.namespace_keys else {
+        return Ok(report);
+    };
+    let dir = engram_dir(engram_path);
+    for entry in &key_manifest.groups {
+        let Some(key) = keys.key(entry.key_id)? else {
+            report.locked.push(entry.key_id);
+            continue;
+        };
+        let group = read_group(dir, entry, key)?;
+        for (id, vec) in group.codebook {
+            if let Entry::Vacant(slot) = engram.codebook.entry(id) {
+                slot.insert(vec);
+                report.chunks += 1;
+            }
+        }
+        // The engram's totals already count every chunk, grouped or not.
+        for (id, correction) in group.corrections {
+            if engram.corrections.get(id).is_none() {
+                engram.corrections.replace(id, Some(correction));
+            }
+        }
+        report.opened.push(entry.key_id);
+    }
+    Ok(report)
+}
+
+/// Put the files of `manifest` under `rules`, writing the groups beside
+/// `engram_path` and recording them in the manifest. Empty `rules` move
+/// every chunk back into the engram.
+///
+/// `engram` is the engram stored at `engram_path`, opened or not. Chunks it
+/// lacks are read from the current groups, so the keys of groups whose
+/// chunks move need to be available, as do the keys of groups written.
+/// `opts` gives the compression and checksums of the group files; they are
+/// always encrypted with their own key.
+///
+/// New group files never replace a file the saved manifest names, and none
+/// is deleted: the files the new manifest no longer names are listed in
+/// [`RekeyReport::stale`], for [`remove_stale`] once the engram and manifest
+/// are saved. [`rekey_saved`] does all three. On error `engram` and
+/// `manifest` are left as they were and the group files written so far are
+/// removed.
+pub fn rekey(
+    engram: &mut Engram,
+    manifest: &mut Manifest,
+    engram_path: &Path,
+    rules: Vec<KeyRule>,
+    keys: &dyn KeyProvider,
+    opts: BinaryWriteOptions,
+) -> io::Result<RekeyReport> {
+    group_file_name(engram_path, 0, "")?;
+    seal::ensure_unsealed(engram_path)?;
+    let dir = engram_dir(engram_path);
+    let mut written = Vec::new();
+    let result = plan(engram, manifest, engram_path, &rules, keys, opts, &mut written);
+    let (groups, moves, report) = match result {
+        Ok(planned) => planned,
+        Err(e) => {
+            for file in &written {
+                let _ = fs::remove_file(dir.join(file));
+            }
+            return Err(e);
+        }
+    };
+
+    // Nothing fails from here on, so the engram and manifest change together.
+    for (id, vec, correction) in moves.restored {
+        engram.codebook.insert(id, vec);
+        engram.corrections.replace(id as u64, correction);
+    }
+    for id in moves.dropped {
+        engram.codebook.remove(&id);
+        engram.corrections.replace(id as u64, None);
+    }
+    manifest.namespace_keys = (!rules.is_empty()).then(|| Box::new(KeyManifest { rules, groups }));
+    Ok(report)
+}
+
+/// Chunks [`rekey`] moves into and out of the engram.
+#[derive(Default)]
+struct Moves {
+    restored: Vec<(usize, SparseVec, Option<ChunkCorrection>)>,
+    dropped: Vec<usize>,
+}
+
+/// Write the groups `rules` call for and work out the chunks moving into
+/// and out of `engram`, changing neither it nor `manifest`. Every file
+/// written is pushed to `written`.
+fn plan(
+    engram: &Engram,
+    manifest: &Manifest,
+    engram_path: &Path,
+    rules: &[KeyRule],
+    keys: &dyn KeyProvider,
+    opts: BinaryWriteOptions,
+    written: &mut Vec<String>,
+) -> io::Result<(Vec<KeyGroup>, Moves, RekeyReport)> {
+    let dir = engram_dir(engram_path);
+    let old = manifest.namespace_keys.as_deref().cloned().unwrap_or_default();
+    let mut report = RekeyReport::default();
+
+    let mut plain: BTreeSet<usize> = BTreeSet::new();
+    let mut grouped: BTreeMap<u32, BTreeSet<usize>> = BTreeMap::new();
+    for file in &manifest.files {
+        let ids = match key_for(rules, &file.path) {
+            Some(id) => grouped.entry(id).or_default(),
+            None => &mut plain,
+        };
+        ids.extend(file.chunks.iter().copied());
+    }
+
+    // Chunks the in-memory engram lacks come from the current groups,
+    // each decrypted once, when first needed.
+    let mut sources: HashMap<u32, Group> = HashMap::new();
+    let fetch = |id: usize, sources: &mut HashMap<u32, Group>| -> io::Result<(Option<SparseVec>, Option<ChunkCorrection>)> {
+        for entry in &old.groups {
+            let group = match sources.entry(entry.key_id) {
+                Entry::Occupied(group) => group.into_mut(),
+                Entry::Vacant(slot) => {
+                    let key = require_key(keys, entry.key_id, "read the chunks moving out of its group")?;
+                    slot.insert(read_group(dir, entry, key)?)
+                }
+            };
+            if let Some(vec) = group.codebook.get(&id) {
+                return Ok((Some(vec.clone()), group.corrections.get(&(id as u64)).cloned()));
+            }
+        }
+        Ok((None, None))
+    };
+
+    let mut groups = Vec::with_capacity(grouped.len());
+    for (&key_id, ids) in &grouped {
+        let ids_digest = ids_digest(ids);
+        if let Some(entry) = old.group(key_id).filter(|g| g.ids_digest == ids_digest) {
+            if fs::metadata(dir.join(&entry.file)).is_ok_and(|m| m.len() == entry.len) {
+                groups.push(entry.clone());
+                report.unchanged.push(key_id);
+                continue;
+            }
+        }
+        let key = require_key(keys, key_id, "encrypt its group")?;
+        let mut group = Group::default();
+        for &id in ids {
+            let (vec, correction) = match engram.codebook.get(&id) {
+                Some(vec) => (Some(vec.clone()), engram.corrections.get(id as u64).cloned()),
+                None => fetch(id, &mut sources)?,
+            };
+            if let Some(vec) = vec {
+                group.codebook.insert(id, vec);
+            }
+            if let Some(correction) = correction {
+                group.corrections.insert(id as u64, correction);
+            }
+        }
+        let entry = write_group(engram_path, key_id, &group, ids_digest, key, opts)?;
+        written.push(entry.file.clone());
+        report.written.insert(key_id, entry.chunks);
+        groups.push(entry);
+    }
+
+    // Chunks a rule no longer covers go back into the engram, and chunks
+    // only grouped files use leave it.
+    let mut moves = Moves::default();
+    for &id in &plain {
+        if !engram.codebook.contains_key(&id) {
+            if let (Some(vec), correction) = fetch(id, &mut sources)? {
+                moves.restored.push((id, vec, correction));
+            }
+        }
+    }
+    let only_grouped: BTreeSet<usize> = grouped.values().flat_map(|ids| ids.difference(&plain)).copied().collect();
+    moves.dropped = only_grouped.into_iter().filter(|id| engram.codebook.contains_key(id)).collect();
+    report.engram_changed = !moves.restored.is_empty() || !moves.dropped.is_empty();
+
+    for entry in &old.groups {
+        if !grouped.contains_key(&entry.key_id) {
+            report.removed.push(entry.key_id);
+        }
+        if !groups.iter().any(|g| g.file == entry.file) {
+            report.stale.push(entry.file.clone());
+        }
+    }
+    Ok((groups, moves, report))
+}
+
+/// Delete the group files [`rekey`] left [stale](RekeyReport::stale). Call
+/// it only once the engram and the manifest [`rekey`] changed are saved.
+pub fn remove_stale(engram_path: &Path, report: &RekeyReport) -> io::Result<()> {
+    let dir = engram_dir(engram_path);
+    for file in &report.stale {
+        match fs::remove_file(dir.join(file)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// [`rekey`] `fsys` as saved at `engram_path` and `manifest_path`, then
+/// replace both in one [`Transaction`] and remove the stale groups. A crash
+/// at any point leaves the saved manifest naming group files that exist;
+/// the engram is rewritten only if its chunks changed.
+pub fn rekey_saved(
+    fsys: &mut EmbrFS,
+    engram_path: &Path,
+    manifest_path: &Path,
+    rules: Vec<KeyRule>,
+    keys: &dyn KeyProvider,
+    opts: BinaryWriteOptions,
+) -> io::Result<RekeyReport> {
+    seal::ensure_unsealed(manifest_path)?;
+    let report = rekey(&mut fsys.engram, &mut fsys.manifest, engram_path, rules, keys, opts)?;
+    // On error the new groups stay: a failed commit may still be finished
+    // by the next recovery, and then the manifest names them.
+    let mut txn = Transaction::begin(engram_path)?;
+    if report.engram_changed {
+        txn.stage(engram_path, |out| fsys.write_engram(out, opts).map(|_| ()))?;
+    }
+    txn.stage(manifest_path, |out| fsys.write_manifest(out, BinaryWriteOptions::default()).map(|_| ()))?;
+    txn.commit()?;
+    remove_stale(engram_path, &report)?;
+    Ok(report)
+}
+
+fn ids_digest(ids: &BTreeSet<usize>) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for &id in ids {
+        hasher.update(&(id as u64).to_le_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Encrypt `group` with `key` and write it to a new file named after its
+/// digest.
+fn write_group(
+    engram_path: &Path,
+    key_id: u32,
+    group: &Group,
+    ids_digest: String,
+    key: EncryptionKey,
+    opts: BinaryWriteOptions,
+) -> io::Result<KeyGroup> {
+    let opts = BinaryWriteOptions {
+        encryption: EncryptionCodec::XChaCha20Poly1305,
+        key: Some(key),
+        ..opts
+    };
+    let data = write_envelope(Vec::new(), PayloadKind::KeyedChunks, opts, group)?;
+    let blake3 = blake3::hash(&data).to_hex().to_string();
+
+    let file = group_file_name(engram_path, key_id, &blake3[..16])?;
+    let dir = engram_dir(engram_path);
+    let staged = dir.join(format!("{}.tmp", file));
+    let mut out = File::create(&staged)?;
+    out.write_all(&data)?;
+    out.sync_data()?;
+    fs::rename(&staged, dir.join(&file))?;
+    Ok(KeyGroup {
+        key_id,
+        file,
+        chunks: group.codebook.len(),
+        len: data.len() as u64,
+        blake3,
+        ids_digest,
+    })
+}
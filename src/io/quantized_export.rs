@@ -0,0 +1,293 @@
+//! Chunk signatures as quantized tensors, and back.
+//!
+//! [`crate::dense_export`] widens chunk vectors for ANN libraries; this
+//! module keeps them ternary, in the two layouts tensor tooling handles
+//! without a custom decoder:
+//!
+//! - [`QuantizedFormat::Int8`]: one `int8` per trit (`-1`, `0`, `1`), shape
+//!   `(rows, dim)`.
+//! - [`QuantizedFormat::Packed2`]: four trits per `uint8`, shape
+//!   `(rows, ceil(dim / 4))`. Trit `j` of a row sits in bits
+//!   `2 * (j % 4)` and up of byte `j / 4`, as `00` for `0`, `01` for `1`
+//!   and `10` for `-1`; `11` never occurs, and the bits past `dim` in the
+//!   last byte are zero. A row costs a quarter of its int8 form.
+//!
+//! [`export`] writes the matrix as [`SIGNATURES_NPY`], the chunk id of each
+//! row as [`IDS_NPY`](crate::dense_export::IDS_NPY), and a
+//! [`QuantizedMetadata`] as [`METADATA_JSON`]: the layout, the dimension,
+//! and the density the vectors had, so a consumer can tell a sparse
+//! signature set from a dense one before loading it. Rows are in ascending
+//! chunk id order.
+//!
+//! [`import`] reads such a directory back, written here or by any pipeline
+//! that follows the layout, and refuses it unless the metadata, the array
+//! headers and every trit agree: a wrong dimension or shape, an invalid
+//! code, set padding bits, repeated ids, or a density that does not match
+//! the data all fail with [`io::ErrorKind::InvalidData`]. The result is a
+//! [`ChunkVectors`] retrieval can query directly.
+//!
+//! An index a bundled vector holds in both `pos` and `neg` cancels to `0`,
+//! as in the dense export, so such vectors come back without it.
+
+use crate::chunk_vectors::ChunkVectors;
+use crate::dense_export::{self, IDS_NPY};
+use crate::embrfs::Engram;
+use crate::vsa::{SparseVec, DIM};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// File name of the trit matrix written by [`export`].
+pub const SIGNATURES_NPY: &str = "signatures.npy";
+/// File name of the metadata written by [`export`].
+pub const METADATA_JSON: &str = "signatures.json";
+/// Version of the layout [`export`] writes and [`import`] reads.
+pub const QUANTIZED_FORMAT_VERSION: u32 = 1;
+
+/// How trits are stored; see the [module docs](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantizedFormat {
+    /// One `int8` per trit.
+    Int8,
+    /// Four trits per `uint8`.
+    #[default]
+    Packed2,
+}
+
+impl QuantizedFormat {
+    /// Bytes of one row of `dim` trits.
+    pub fn row_bytes(self, dim: usize) -> usize {
+        match self {
+            QuantizedFormat::Int8 => dim,
+            QuantizedFormat::Packed2 => dim.div_ceil(4),
+        }
+    }
+
+    fn npy_descr(self) -> &'static str {
+        match self {
+            QuantizedFormat::Int8 => "|i1",
+            QuantizedFormat::Packed2 => "|u1",
+        }
+    }
+}
+
+/// What [`METADATA_JSON`] records about an export.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedMetadata {
+    pub version: u32,
+    pub format: QuantizedFormat,
+    /// Trits per row.
+    pub dim: usize,
+    pub rows: usize,
+    /// Bytes per row of [`SIGNATURES_NPY`].
+    pub row_bytes: usize,
+    /// Non-zero trits over all rows.
+    pub nnz: u64,
+    /// `nnz` over `rows * dim`: the fraction of trits that are not zero.
+    pub density: f64,
+}
+
+/// Pack a row of trits as [`QuantizedFormat::Packed2`].
+pub fn pack_trits(row: &[i8], out: &mut Vec<u8>) {
+    out.clear();
+    out.extend(row.chunks(4).map(|four| {
+        four.iter().enumerate().fold(0u8, |byte, (j, &t)| {
+            let code = match t {
+                1 => 0b01,
+                -1 => 0b10,
+                _ => 0b00,
+            };
+            byte | code << (2 * j)
+        })
+    }));
+}
+
+/// Unpack `bytes`, a [`QuantizedFormat::Packed2`] row, into the `row.len()`
+/// trits of `row`; `None` on a `11` code or set padding bits.
+pub fn unpack_trits(bytes: &[u8], row: &mut [i8]) -> Option<()> {
+    if bytes.len() != row.len().div_ceil(4) {
+        return None;
+    }
+    for (j, slot) in row.iter_mut().enumerate() {
+        *slot = match bytes[j / 4] >> (2 * (j % 4)) & 0b11 {
+            0b00 => 0,
+            0b01 => 1,
+            0b10 => -1,
+            _ => return None,
+        };
+    }
+    let used = row.len() % 4;
+    match bytes.last() {
+        Some(&last) if used != 0 && last >> (2 * used) != 0 => None,
+        _ => Some(()),
+    }
+}
+
+/// Write the vectors of `engram`'s codebook into `dir`, creating it if
+/// needed.
+pub fn export_engram<P: AsRef<Path>>(engram: &Engram, format: QuantizedFormat, dir: P) -> io::Result<QuantizedMetadata> {
+    export(&engram.codebook, format, dir)
+}
+
+/// Write `vectors`, by chunk id, into `dir` in `format`, creating it if
+/// needed; see the [module docs](self).
+pub fn export<P: AsRef<Path>>(
+    vectors: &HashMap<usize, SparseVec>,
+    format: QuantizedFormat,
+    dir: P,
+) -> io::Result<QuantizedMetadata> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+    let mut ids: Vec<usize> = vectors.keys().copied().collect();
+    ids.sort_unstable();
+    let row_bytes = format.row_bytes(DIM);
+
+    let mut out = BufWriter::new(File::create(dir.join(SIGNATURES_NPY))?);
+    dense_export::write_npy_header(&mut out, format.npy_descr(), &[ids.len(), row_bytes])?;
+    let mut row = vec![0i8; DIM];
+    let mut packed = Vec::with_capacity(row_bytes);
+    let mut nnz = 0u64;
+    for &id in &ids {
+        nnz += dense_export::fill_row(id, &vectors[&id], &mut row)? as u64;
+        match format {
+            QuantizedFormat::Int8 => out.write_all(&row.iter().map(|&t| t as u8).collect::<Vec<_>>())?,
+            QuantizedFormat::Packed2 => {
+                pack_trits(&row, &mut packed);
+                out.write_all(&packed)?;
+            }
+        }
+    }
+    out.flush()?;
+    dense_export::write_ids_npy(dir, &ids)?;
+
+    let metadata = QuantizedMetadata {
+        version: QUANTIZED_FORMAT_VERSION,
+        format,
+        dim: DIM,
+        rows: ids.len(),
+        row_bytes,
+        nnz,
+        density: density(nnz, ids.len(), DIM),
+    };
+    let mut out = BufWriter::new(File::create(dir.join(METADATA_JSON))?);
+    serde_json::to_writer_pretty(&mut out, &metadata)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(metadata)
+}
+
+fn density(nnz: u64, rows: usize, dim: usize) -> f64 {
+    let trits = rows as f64 * dim as f64;
+    if trits == 0.0 {
+        0.0
+    } else {
+        nnz as f64 / trits
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read and check an export in `dir`; see the [module docs](self).
+pub fn import<P: AsRef<Path>>(dir: P) -> io::Result<(QuantizedMetadata, ChunkVectors)> {
+    let dir = dir.as_ref();
+    let metadata: QuantizedMetadata = serde_json::from_reader(BufReader::new(File::open(dir.join(METADATA_JSON))?))
+        .map_err(|e| invalid(format!("{}: {}", METADATA_JSON, e)))?;
+    if metadata.version != QUANTIZED_FORMAT_VERSION {
+        return Err(invalid(format!(
+            "{} is version {}; this build reads version {}",
+            METADATA_JSON, metadata.version, QUANTIZED_FORMAT_VERSION
+        )));
+    }
+    if metadata.dim != DIM {
+        return Err(invalid(format!(
+            "signatures have dimension {}; this build uses {}",
+            metadata.dim, DIM
+        )));
+    }
+    let row_bytes = metadata.format.row_bytes(metadata.dim);
+    if metadata.row_bytes != row_bytes {
+        return Err(invalid(format!(
+            "{} records {} bytes per row; {:?} rows of dimension {} take {}",
+            METADATA_JSON, metadata.row_bytes, metadata.format, metadata.dim, row_bytes
+        )));
+    }
+
+    let ids = read_ids(dir, metadata.rows)?;
+    let mut input = BufReader::new(File::open(dir.join(SIGNATURES_NPY))?);
+    let header = dense_export::read_npy_header(&mut input)?;
+    if header.descr != metadata.format.npy_descr() || header.shape != [metadata.rows, row_bytes] {
+        return Err(invalid(format!(
+            "{} holds {} {:?}; {} describes {} {:?}",
+            SIGNATURES_NPY,
+            header.descr,
+            header.shape,
+            METADATA_JSON,
+            metadata.format.npy_descr(),
+            [metadata.rows, row_bytes]
+        )));
+    }
+
+    let mut bytes = vec![0u8; row_bytes];
+    let mut row = vec![0i8; metadata.dim];
+    let mut vectors = HashMap::with_capacity(ids.len());
+    let mut nnz = 0u64;
+    for &id in &ids {
+        input.read_exact(&mut bytes)?;
+        let bad = || invalid(format!("chunk {} has a trit code other than -1, 0 or 1", id));
+        match metadata.format {
+            QuantizedFormat::Int8 => {
+                for (slot, &b) in row.iter_mut().zip(&bytes) {
+                    *slot = match b as i8 {
+                        t @ -1..=1 => t,
+                        _ => return Err(bad()),
+                    };
+                }
+            }
+            QuantizedFormat::Packed2 => unpack_trits(&bytes, &mut row).ok_or_else(bad)?,
+        }
+        let vec = SparseVec {
+            pos: (0..row.len()).filter(|&i| row[i] == 1).collect(),
+            neg: (0..row.len()).filter(|&i| row[i] == -1).collect(),
+        };
+        nnz += (vec.pos.len() + vec.neg.len()) as u64;
+        vectors.insert(id, vec);
+    }
+    if input.read(&mut [0u8])? != 0 {
+        return Err(invalid(format!("{} has data past its {} rows", SIGNATURES_NPY, metadata.rows)));
+    }
+    if nnz != metadata.nnz || (density(nnz, metadata.rows, metadata.dim) - metadata.density).abs() > 1e-9 {
+        return Err(invalid(format!(
+            "{} records {} non-zero trits (density {}); the rows hold {}",
+            METADATA_JSON, metadata.nnz, metadata.density, nnz
+        )));
+    }
+    Ok((metadata, ChunkVectors { vectors }))
+}
+
+/// The `rows` chunk ids of `dir`'s [`IDS_NPY`], checked to ascend.
+fn read_ids(dir: &Path, rows: usize) -> io::Result<Vec<usize>> {
+    let mut input = BufReader::new(File::open(dir.join(IDS_NPY))?);
+    let header = dense_export::read_npy_header(&mut input)?;
+    if header.descr != "<u8" || header.shape != [rows] {
+        return Err(invalid(format!(
+            "{} holds {} {:?}; expected <u8 [{}]",
+            IDS_NPY, header.descr, header.shape, rows
+        )));
+    }
+    let mut ids = Vec::with_capacity(rows);
+    let mut word = [0u8; 8];
+    for _ in 0..rows {
+        input.read_exact(&mut word)?;
+        let id = usize::try_from(u64::from_le_bytes(word)).map_err(|e| invalid(e.to_string()))?;
+        if ids.last().is_some_and(|&last| last >= id) {
+            return Err(invalid(format!("{} is not in ascending order at chunk {}", IDS_NPY, id)));
+        }
+        ids.push(id);
+    }
+    Ok(ids)
+}
@@ -0,0 +1,642 @@
+//! Delta sync of an engram with a copy on another host.
+//!
+//! `embeddenator push` and `embeddenator pull` work like rsync: the two
+//! sides exchange the digest of every chunk record (see
+//! [`MerkleTree::build`]) and only records whose digest differs or that one
+//! side lacks cross the link, in batches of [`SyncOptions::batch_chunks`]
+//! records, optionally compressed. The root vector, correction totals and
+//! manifest follow as one small state message, so updating a multi-gigabyte
+//! engram after an incremental ingest costs roughly the new chunks.
+//!
+//! The receiving side applies the records to its copy and replaces its
+//! engram and manifest in one [`Transaction`](crate::txn::Transaction), so an
+//! interrupted sync leaves the old pair in place; a [sealed](crate::seal)
+//! receiver refuses the update. Each side reads its files with its own
+//! [`Keyring`], and the replacement keeps the compression, checksum and
+//! encryption of the copy it replaces (see [`envelope::write_options_of`]).
+//!
+//! # Transport
+//!
+//! The protocol runs over any byte stream pair. [`RemoteSession::spawn`]
+//! starts the other side the way rsync does: for `host:PATH` it runs
+//! `ssh -- host embeddenator sync-server PATH MANIFEST` (the shell command and
+//! the remote executable are configurable), and for a plain path it runs the
+//! current executable locally. [`serve`] is the other end, reading requests
+//! from stdin and answering on stdout.
+//!
+//! # Wire format
+//!
+//! Each message is a little-endian `u32` length followed by a bincode-encoded
+//! message. Both sides open with their protocol version and end with `Bye`;
+//! any failure on the serving side is sent back as an error message, and the
+//! client reports it as coming from the remote.
+
+use crate::correction::CorrectionStore;
+use crate::embrfs::{decode_log_chunk, decode_log_meta, EmbrFS, Engram, Manifest};
+use crate::envelope::{self, CompressionCodec, Keyring};
+use crate::replica::MerkleTree;
+use crate::manifest_schema::Versioned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Version sent in the opening message; both sides must match.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Largest message either side accepts.
+const MAX_MESSAGE_BYTES: u32 = 1 << 30;
+
+/// How records are sent.
+#[derive(Clone, Copy, Debug)]
+pub struct SyncOptions {
+    /// Compression of record batches: `None`, `Zstd` or `Lz4` (the codec's
+    /// feature must be enabled on both sides).
+    pub codec: CompressionCodec,
+    /// Chunk records per batch.
+    pub batch_chunks: usize,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            codec: CompressionCodec::None,
+            batch_chunks: 256,
+        }
+    }
+}
+
+/// What one [`push`] or [`pull`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferReport {
+    /// Chunk records sent (push) or received (pull).
+    pub chunks_transferred: usize,
+    /// Chunk records the receiver dropped because the sender lacks them.
+    pub chunks_removed: usize,
+    /// Bytes written to the link, framing included.
+    pub bytes_sent: u64,
+    /// Bytes read from the link, framing included.
+    pub bytes_received: u64,
+    /// Both copies were already identical; nothing was written.
+    pub up_to_date: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+    Hello {
+        version: u32,
+    },
+    GetDigests,
+    /// Every chunk record digest, plus the digest of the state message.
+    Digests {
+        chunks: Vec<(u64, [u8; 32])>,
+        state: [u8; 32],
+    },
+    GetChunks {
+        ids: Vec<u64>,
+        codec: u8,
+    },
+    Chunks(Batch),
+    GetState,
+    State(State),
+    PutChunks(Batch),
+    Commit {
+        removed: Vec<u64>,
+        state: State,
+    },
+    Committed,
+    Error(String),
+    Bye,
+}
+
+/// Chunk records as bincode `Vec<(id, record)>`, compressed with `codec`.
+#[derive(Serialize, Deserialize)]
+struct Batch {
+    codec: u8,
+    payload: Vec<u8>,
+}
+
+/// Everything but the chunk records: the root and correction totals (an
+/// append-log meta record) and the manifest as JSON.
+#[derive(Clone, Serialize, Deserialize)]
+struct State {
+    meta: Vec<u8>,
+    manifest: Vec<u8>,
+}
+
+impl State {
+    fn of(engram: &Engram, manifest: &Manifest) -> io::Result<Self> {
+        Ok(Self {
+            meta: engram.encode_meta_record()?,
+            manifest: serde_json::to_vec(&Versioned::new(manifest))?,
+        })
+    }
+
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(self.meta.len() as u64).to_le_bytes());
+        hasher.update(&self.meta);
+        hasher.update(&self.manifest);
+        hasher.finalize().into()
+    }
+}
+
+/// Send the engram at `engram_path` and its manifest, decrypted with `keys`,
+/// to the peer on `reader`/`writer`, which replaces its copy with them.
+pub fn push<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    engram_path: &Path,
+    manifest_path: &Path,
+    keys: &Keyring,
+    opts: &SyncOptions,
+) -> io::Result<TransferReport> {
+    let engram = EmbrFS::load_engram_with_keys(engram_path, keys)?;
+    let manifest = EmbrFS::load_manifest_with_keys(manifest_path, keys)?;
+    let mut link = Link::open(reader, writer)?;
+    let (theirs, their_state) = link.digests()?;
+    let ours = MerkleTree::build(&engram)?;
+    let state = State::of(&engram, &manifest)?;
+    let mut report = TransferReport::default();
+
+    let diff = ours.diff(&theirs);
+    if diff.chunks.is_empty() && state.digest() == their_state {
+        report.up_to_date = true;
+    } else {
+        let (send, removed): (Vec<u64>, Vec<u64>) = diff
+            .chunks
+            .iter()
+            .partition(|id| ours.chunk_digest(**id).is_some());
+        for ids in send.chunks(opts.batch_chunks.max(1)) {
+            link.send(&Message::PutChunks(encode_batch(&engram, ids, opts.codec)?))?;
+        }
+        report.chunks_transferred = send.len();
+        report.chunks_removed = removed.len();
+        link.send(&Message::Commit { removed, state })?;
+        match link.recv()? {
+            Message::Committed => {}
+            other => return Err(link.unexpected(other)),
+        }
+    }
+    link.close(&mut report)?;
+    Ok(report)
+}
+
+/// Replace the engram at `engram_path` and its manifest with the peer's,
+/// fetching only the chunk records that differ. A missing local engram is
+/// created; an existing one is read with `keys` and keeps its envelope
+/// options.
+pub fn pull<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    engram_path: &Path,
+    manifest_path: &Path,
+    keys: &Keyring,
+    opts: &SyncOptions,
+) -> io::Result<TransferReport> {
+    crate::seal::ensure_unsealed(engram_path)?;
+    let mut fs = load_or_empty(engram_path, manifest_path, keys)?;
+    let mut link = Link::open(reader, writer)?;
+    let (theirs, their_state) = link.digests()?;
+    let ours = MerkleTree::build(&fs.engram)?;
+    let mut report = TransferReport::default();
+
+    let diff = theirs.diff(&ours);
+    if diff.chunks.is_empty() && State::of(&fs.engram, &fs.manifest)?.digest() == their_state {
+        report.up_to_date = true;
+        link.close(&mut report)?;
+        return Ok(report);
+    }
+    let (fetch, removed): (Vec<u64>, Vec<u64>) = diff
+        .chunks
+        .iter()
+        .partition(|id| theirs.chunk_digest(**id).is_some());
+    let mut records = Vec::with_capacity(fetch.len());
+    for ids in fetch.chunks(opts.batch_chunks.max(1)) {
+        link.send(&Message::GetChunks {
+            ids: ids.to_vec(),
+            codec: opts.codec as u8,
+        })?;
+        match link.recv()? {
+            Message::Chunks(batch) => records.extend(decode_batch(&batch)?),
+            other => return Err(link.unexpected(other)),
+        }
+    }
+    link.send(&Message::GetState)?;
+    let state = match link.recv()? {
+        Message::State(state) => state,
+        other => return Err(link.unexpected(other)),
+    };
+    link.close(&mut report)?;
+
+    report.chunks_transferred = records.len();
+    report.chunks_removed = removed.len();
+    apply(&mut fs, records, &removed, &state)?;
+    save(&fs, engram_path, manifest_path, keys)?;
+    Ok(report)
+}
+
+/// Answer one [`push`] or [`pull`] for the engram at `engram_path` and its
+/// manifest, which need not exist yet, reading them with `keys`. Returns when
+/// the client says goodbye.
+pub fn serve<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    engram_path: &Path,
+    manifest_path: &Path,
+    keys: &Keyring,
+) -> io::Result<()> {
+    let mut link = Link::new(reader, writer);
+    match serve_session(&mut link, engram_path, manifest_path, keys) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            // Best effort: the client may already be gone.
+            let _ = link.send(&Message::Error(e.to_string()));
+            Err(e)
+        }
+    }
+}
+
+fn serve_session<R: Read, W: Write>(
+    link: &mut Link<R, W>,
+    engram_path: &Path,
+    manifest_path: &Path,
+    keys: &Keyring,
+) -> io::Result<()> {
+    match link.recv()? {
+        Message::Hello { version } if version == PROTOCOL_VERSION => {}
+        Message::Hello { version } => {
+            return Err(invalid(format!(
+                "client speaks sync protocol {}, this side {}",
+                version, PROTOCOL_VERSION
+            )))
+        }
+        other => return Err(link.unexpected(other)),
+    }
+    link.send(&Message::Hello {
+        version: PROTOCOL_VERSION,
+    })?;
+    let mut fs = load_or_empty(engram_path, manifest_path, keys)?;
+    let mut received = Vec::new();
+    loop {
+        match link.recv()? {
+            Message::GetDigests => {
+                let chunks = MerkleTree::build(&fs.engram)?
+                    .chunk_digests()
+                    .iter()
+                    .map(|(&id, &digest)| (id, digest))
+                    .collect();
+                let state = State::of(&fs.engram, &fs.manifest)?.digest();
+                link.send(&Message::Digests { chunks, state })?;
+            }
+            Message::GetChunks { ids, codec } => {
+                let codec = CompressionCodec::from_u8(codec)
+                    .ok_or_else(|| invalid(format!("unknown compression codec {}", codec)))?;
+                link.send(&Message::Chunks(encode_batch(&fs.engram, &ids, codec)?))?;
+            }
+            Message::GetState => {
+                link.send(&Message::State(State::of(&fs.engram, &fs.manifest)?))?
+            }
+            Message::PutChunks(batch) => received.extend(decode_batch(&batch)?),
+            Message::Commit { removed, state } => {
+                apply(&mut fs, std::mem::take(&mut received), &removed, &state)?;
+                save(&fs, engram_path, manifest_path, keys)?;
+                link.send(&Message::Committed)?;
+            }
+            Message::Bye => {
+                link.send(&Message::Bye)?;
+                return Ok(());
+            }
+            other => return Err(link.unexpected(other)),
+        }
+    }
+}
+
+/// The other side of a sync, running as a child process.
+pub struct RemoteSession {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    stdout: Option<BufReader<ChildStdout>>,
+}
+
+impl RemoteSession {
+    /// Start `sync-server` for `remote`, which is `[user@]host:PATH` (run
+    /// through `rsh`, `ssh` by default, as `remote_bin`) or a local path
+    /// (run with the current executable). `remote_manifest` defaults to
+    /// `manifest.json` beside the remote engram. `remote_keys` are passed to
+    /// the server as `--key ID=FILE`, with FILE a path on the remote side.
+    pub fn spawn(
+        remote: &str,
+        remote_manifest: Option<&str>,
+        rsh: Option<&str>,
+        remote_bin: &str,
+        remote_keys: &[(u32, PathBuf)],
+    ) -> io::Result<Self> {
+        let (host, engram) = split_remote(remote);
+        if engram.is_empty() {
+            return Err(invalid(format!("{:?} names no engram path", remote)));
+        }
+        let manifest = match remote_manifest {
+            Some(path) => path.to_string(),
+            None => sibling_manifest(engram),
+        };
+        let mut cmd = match host {
+            Some(host) => {
+                // ssh would read `-oProxyCommand=...` as an option, not a host.
+                if host.starts_with('-') {
+                    return Err(invalid(format!("{:?} is not a host name", host)));
+                }
+                let mut words = rsh.unwrap_or("ssh").split_whitespace();
+                let program = words
+                    .next()
+                    .ok_or_else(|| invalid("empty --rsh".to_string()))?;
+                let mut cmd = Command::new(program);
+                // The remote shell joins its arguments into one command line.
+                cmd.args(words)
+                    .arg("--")
+                    .arg(host)
+                    .arg(remote_bin)
+                    .arg("sync-server")
+                    .arg(shell_quote(engram))
+                    .arg(shell_quote(&manifest));
+                for (id, path) in remote_keys {
+                    cmd.arg(format!("--key={}", shell_quote(&format!("{}={}", id, path.display()))));
+                }
+                cmd
+            }
+            None => {
+                let mut cmd = Command::new(std::env::current_exe()?);
+                cmd.arg("sync-server").arg(engram).arg(&manifest);
+                for (id, path) in remote_keys {
+                    let mut spec = OsString::from(format!("--key={}=", id));
+                    spec.push(path);
+                    cmd.arg(spec);
+                }
+                cmd
+            }
+        };
+        let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().map(BufWriter::new);
+        let stdout = child.stdout.take().map(BufReader::new);
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// [`push`] to the remote, then wait for it to exit.
+    pub fn push(
+        mut self,
+        engram_path: &Path,
+        manifest_path: &Path,
+        keys: &Keyring,
+        opts: &SyncOptions,
+    ) -> io::Result<TransferReport> {
+        let (reader, writer) = self.streams();
+        let report = push(reader, writer, engram_path, manifest_path, keys, opts);
+        self.finish(report)
+    }
+
+    /// [`pull`] from the remote, then wait for it to exit.
+    pub fn pull(
+        mut self,
+        engram_path: &Path,
+        manifest_path: &Path,
+        keys: &Keyring,
+        opts: &SyncOptions,
+    ) -> io::Result<TransferReport> {
+        let (reader, writer) = self.streams();
+        let report = pull(reader, writer, engram_path, manifest_path, keys, opts);
+        self.finish(report)
+    }
+
+    fn streams(&mut self) -> (BufReader<ChildStdout>, BufWriter<ChildStdin>) {
+        (
+            self.stdout.take().expect("streams taken once"),
+            self.stdin.take().expect("streams taken once"),
+        )
+    }
+
+    fn finish(mut self, report: io::Result<TransferReport>) -> io::Result<TransferReport> {
+        let status = self.child.wait()?;
+        match report {
+            Ok(report) if status.success() => Ok(report),
+            Ok(_) => Err(io::Error::other(format!(
+                "sync server exited with {}",
+                status
+            ))),
+            // A dead server shows up as a broken pipe or a short read; say
+            // so rather than report the pipe error.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof
+                ) =>
+            {
+                Err(io::Error::other(format!(
+                    "sync server closed the connection ({})",
+                    status
+                )))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Split `host:path` from a local path. As with rsync, a colon counts only
+/// if it comes before the first slash.
+fn split_remote(remote: &str) -> (Option<&str>, &str) {
+    match remote.split_once(':') {
+        Some((host, path)) if !host.is_empty() && !host.contains('/') => (Some(host), path),
+        _ => (None, remote),
+    }
+}
+
+fn sibling_manifest(engram: &str) -> String {
+    match engram.rfind('/') {
+        Some(slash) => format!("{}manifest.json", &engram[..=slash]),
+        None => "manifest.json".to_string(),
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+fn load_or_empty(engram_path: &Path, manifest_path: &Path, keys: &Keyring) -> io::Result<EmbrFS> {
+    let mut fs = EmbrFS::new();
+    if engram_path.exists() {
+        fs.engram = EmbrFS::load_engram_with_keys(engram_path, keys)?;
+        fs.manifest = EmbrFS::load_manifest_with_keys(manifest_path, keys)?;
+    }
+    Ok(fs)
+}
+
+fn encode_batch(engram: &Engram, ids: &[u64], codec: CompressionCodec) -> io::Result<Batch> {
+    if codec == CompressionCodec::ZstdDict {
+        return Err(invalid("sync batches cannot use zstd-dict".to_string()));
+    }
+    let records = ids
+        .iter()
+        .map(|&id| Ok((id, engram.encode_chunk_record(id)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let raw = bincode::serialize(&records).map_err(io::Error::other)?;
+    Ok(Batch {
+        codec: codec as u8,
+        payload: envelope::compress(codec, &raw, None)?,
+    })
+}
+
+fn decode_batch(batch: &Batch) -> io::Result<Vec<(u64, Vec<u8>)>> {
+    let codec = CompressionCodec::from_u8(batch.codec)
+        .ok_or_else(|| invalid(format!("unknown compression codec {}", batch.codec)))?;
+    let raw = envelope::decompress(codec, &batch.payload)?;
+    bincode::deserialize(&raw).map_err(|e| invalid(format!("malformed chunk batch: {}", e)))
+}
+
+/// Apply received records, removals and state to `fs`.
+fn apply(
+    fs: &mut EmbrFS,
+    records: Vec<(u64, Vec<u8>)>,
+    removed: &[u64],
+    state: &State,
+) -> io::Result<()> {
+    let engram = &mut fs.engram;
+    let mut corrections: HashMap<u64, _> = engram
+        .corrections
+        .iter()
+        .map(|(id, c)| (id, c.clone()))
+        .collect();
+    for &id in removed {
+        engram.codebook.remove(&(id as usize));
+        corrections.remove(&id);
+    }
+    for (id, record) in records {
+        let (vec, correction) = decode_log_chunk(&record)?;
+        match vec {
+            Some(vec) => engram.codebook.insert(id as usize, vec),
+            None => engram.codebook.remove(&(id as usize)),
+        };
+        match correction {
+            Some(correction) => corrections.insert(id, correction),
+            None => corrections.remove(&id),
+        };
+    }
+    let (root, totals) = decode_log_meta(&state.meta)?;
+    engram.root = root;
+    // The log carries the root alone; counts from before no longer match it.
+    engram.root_counts = None;
+    engram.corrections = CorrectionStore::from_parts(corrections, totals);
+    fs.manifest = Manifest::from_json_slice(&state.manifest)
+        .map_err(|e| invalid(format!("malformed manifest: {}", e)))?;
+    Ok(())
+}
+
+/// Replace the engram and manifest, keeping the envelope options of the
+/// files being replaced so an encrypted or compressed copy stays that way.
+fn save(fs: &EmbrFS, engram_path: &Path, manifest_path: &Path, keys: &Keyring) -> io::Result<()> {
+    fs.save_transactional(
+        engram_path,
+        manifest_path,
+        envelope::write_options_of(engram_path, keys)?,
+        envelope::write_options_of(manifest_path, keys)?,
+    )?;
+    Ok(())
+}
+
+/// Framed, byte-counting message stream.
+struct Link<R, W> {
+    reader: R,
+    writer: W,
+    sent: u64,
+    received: u64,
+}
+
+impl<R: Read, W: Write> Link<R, W> {
+    fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Client side: exchange versions.
+    fn open(reader: R, writer: W) -> io::Result<Self> {
+        let mut link = Self::new(reader, writer);
+        link.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+        })?;
+        match link.recv()? {
+            Message::Hello { version } if version == PROTOCOL_VERSION => Ok(link),
+            Message::Hello { version } => Err(invalid(format!(
+                "remote speaks sync protocol {}, this side {}",
+                version, PROTOCOL_VERSION
+            ))),
+            other => Err(link.unexpected(other)),
+        }
+    }
+
+    fn digests(&mut self) -> io::Result<(MerkleTree, [u8; 32])> {
+        self.send(&Message::GetDigests)?;
+        match self.recv()? {
+            Message::Digests { chunks, state } => {
+                let chunks: BTreeMap<u64, [u8; 32]> = chunks.into_iter().collect();
+                Ok((MerkleTree::from_chunk_digests(chunks), state))
+            }
+            other => Err(self.unexpected(other)),
+        }
+    }
+
+    fn close(&mut self, report: &mut TransferReport) -> io::Result<()> {
+        self.send(&Message::Bye)?;
+        match self.recv()? {
+            Message::Bye => {}
+            other => return Err(self.unexpected(other)),
+        }
+        report.bytes_sent = self.sent;
+        report.bytes_received = self.received;
+        Ok(())
+    }
+
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        let body = bincode::serialize(message).map_err(io::Error::other)?;
+        let len = u32::try_from(body.len())
+            .ok()
+            .filter(|&len| len <= MAX_MESSAGE_BYTES)
+            .ok_or_else(|| invalid("sync message too large; lower the batch size".to_string()))?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&body)?;
+        self.writer.flush()?;
+        self.sent += 4 + body.len() as u64;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> io::Result<Message> {
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_MESSAGE_BYTES {
+            return Err(invalid(format!("sync message of {} bytes", len)));
+        }
+        let mut body = vec![0u8; len as usize];
+        self.reader.read_exact(&mut body)?;
+        self.received += 4 + len as u64;
+        bincode::deserialize(&body).map_err(|e| invalid(format!("malformed sync message: {}", e)))
+    }
+
+    fn unexpected(&self, message: Message) -> io::Error {
+        match message {
+            Message::Error(msg) => io::Error::other(format!("remote: {}", msg)),
+            _ => invalid("unexpected sync message".to_string()),
+        }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,340 @@
+//! Replicas of an engram: Merkle-tree comparison, sync and repair.
+//!
+//! Copies of one engram kept on several hosts drift apart when a disk flips
+//! a bit or a copy is interrupted. Comparing them chunk by chunk means
+//! reading and hashing every record on both sides; a [`MerkleTree`] over
+//! the chunk digests lets two replicas compare one root digest and, only
+//! where it differs, descend into the subtrees that differ, so the cost of
+//! finding divergence grows with how much diverged rather than with the
+//! size of the engram.
+//!
+//! The tree is fixed over the whole chunk-id space: each leaf covers
+//! [`LEAF_IDS`] consecutive ids and each level above halves the number of
+//! nodes, so two replicas' trees line up node for node whatever ids they
+//! hold. Empty subtrees hash to all zeroes and are not stored.
+//!
+//! [`sync_from`] makes a replica identical to a peer by copying only the
+//! differing records. [`repair`] (`embeddenator repair`) is for when no
+//! replica is known to be right: it verifies files against the manifest and
+//! replaces the records of each damaged file with a peer's, keeping them
+//! only if the file then verifies.
+
+use crate::correction::{ChunkCorrection, CorrectionStore};
+use crate::embrfs::{EmbrFS, Engram, Manifest};
+use crate::signing::to_hex;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+
+/// Bits of the chunk id below the leaf index.
+const LEAF_BITS: u32 = 6;
+
+/// Consecutive chunk ids covered by one leaf.
+pub const LEAF_IDS: u64 = 1 << LEAF_BITS;
+
+/// Levels above the leaves; the root is the single node at the top.
+const HEIGHT: usize = (u64::BITS - LEAF_BITS) as usize;
+
+/// Digest of an empty subtree.
+const EMPTY: [u8; 32] = [0; 32];
+
+/// Merkle tree over the chunk records of an engram.
+#[derive(Clone, Debug, Default)]
+pub struct MerkleTree {
+    /// Digest of each chunk record, by id.
+    chunks: BTreeMap<u64, [u8; 32]>,
+    /// Non-empty nodes by index, leaves first. Node `i` of level `l` covers
+    /// ids `[i << (LEAF_BITS + l), (i + 1) << (LEAF_BITS + l))`.
+    levels: Vec<HashMap<u64, [u8; 32]>>,
+}
+
+/// Chunks on which two replicas differ, from [`MerkleTree::diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleDiff {
+    /// Ids whose record differs or is held by one side only, ascending.
+    pub chunks: Vec<u64>,
+    /// Tree nodes compared to find them.
+    pub nodes_compared: usize,
+}
+
+impl MerkleTree {
+    /// Hash every chunk record of `engram` and build the tree over them.
+    pub fn build(engram: &Engram) -> io::Result<Self> {
+        let mut chunks = BTreeMap::new();
+        for id in engram.chunk_record_ids() {
+            let record = engram.encode_chunk_record(id)?;
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(&id.to_le_bytes());
+            hasher.update(&(record.len() as u64).to_le_bytes());
+            hasher.update(&record);
+            chunks.insert(id, hasher.finalize().into());
+        }
+        Ok(Self::from_chunk_digests(chunks))
+    }
+
+    /// Build the tree over digests computed elsewhere, e.g. sent by a peer.
+    pub fn from_chunk_digests(chunks: BTreeMap<u64, [u8; 32]>) -> Self {
+        let mut leaves: BTreeMap<u64, blake3::Hasher> = BTreeMap::new();
+        for (&id, digest) in &chunks {
+            let leaf = leaves.entry(id >> LEAF_BITS).or_default();
+            leaf.update(&id.to_le_bytes());
+            leaf.update(digest);
+        }
+        let mut levels = vec![leaves
+            .into_iter()
+            .map(|(i, hasher)| (i, *hasher.finalize().as_bytes()))
+            .collect::<HashMap<_, _>>()];
+        for _ in 0..HEIGHT {
+            let below = levels.last().expect("the leaf level exists");
+            let parents: HashSet<u64> = below.keys().map(|i| i >> 1).collect();
+            let level = parents
+                .into_iter()
+                .map(|i| {
+                    let left = below.get(&(i << 1)).unwrap_or(&EMPTY);
+                    let right = below.get(&(i << 1 | 1)).unwrap_or(&EMPTY);
+                    let mut hasher = blake3::Hasher::new();
+                    hasher.update(left);
+                    hasher.update(right);
+                    (i, *hasher.finalize().as_bytes())
+                })
+                .collect();
+            levels.push(level);
+        }
+        Self { chunks, levels }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node(HEIGHT, 0)
+    }
+
+    /// Hex-encoded root digest.
+    pub fn root_hex(&self) -> String {
+        to_hex(&self.root())
+    }
+
+    /// Digest of node `index` of `level` (0 being the leaves).
+    pub fn node(&self, level: usize, index: u64) -> [u8; 32] {
+        self.levels
+            .get(level)
+            .and_then(|nodes| nodes.get(&index))
+            .copied()
+            .unwrap_or(EMPTY)
+    }
+
+    /// Chunk records covered.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn chunk_digest(&self, chunk_id: u64) -> Option<&[u8; 32]> {
+        self.chunks.get(&chunk_id)
+    }
+
+    /// Every chunk digest, by id; what a replica sends to be compared.
+    pub fn chunk_digests(&self) -> &BTreeMap<u64, [u8; 32]> {
+        &self.chunks
+    }
+
+    /// Chunks on which `self` and `other` differ, found by descending only
+    /// into subtrees whose digests differ.
+    pub fn diff(&self, other: &MerkleTree) -> MerkleDiff {
+        let mut diff = MerkleDiff::default();
+        let mut pending = vec![(HEIGHT, 0u64)];
+        while let Some((level, index)) = pending.pop() {
+            diff.nodes_compared += 1;
+            if self.node(level, index) == other.node(level, index) {
+                continue;
+            }
+            if level > 0 {
+                pending.push((level - 1, index << 1 | 1));
+                pending.push((level - 1, index << 1));
+                continue;
+            }
+            let ids = (index << LEAF_BITS)..=(index << LEAF_BITS | (LEAF_IDS - 1));
+            let mut differing: Vec<u64> = self
+                .chunks
+                .range(ids.clone())
+                .chain(other.chunks.range(ids))
+                .map(|(&id, _)| id)
+                .filter(|id| self.chunks.get(id) != other.chunks.get(id))
+                .collect();
+            differing.sort_unstable();
+            differing.dedup();
+            diff.chunks.extend(differing);
+        }
+        diff
+    }
+}
+
+/// Outcome of [`sync_from`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Chunk records copied from the peer.
+    pub copied: usize,
+    /// Chunk records the peer doesn't hold, removed.
+    pub removed: usize,
+    /// Tree nodes compared to find them.
+    pub nodes_compared: usize,
+}
+
+/// Make `local` identical to `peer`, copying only the chunk records their
+/// trees say differ, then the peer's root and correction totals.
+pub fn sync_from(local: &mut Engram, peer: &Engram) -> io::Result<SyncReport> {
+    let diff = MerkleTree::build(local)?.diff(&MerkleTree::build(peer)?);
+    let mut report = SyncReport {
+        nodes_compared: diff.nodes_compared,
+        ..Default::default()
+    };
+    let mut corrections: HashMap<u64, _> = local
+        .corrections
+        .iter()
+        .map(|(id, c)| (id, c.clone()))
+        .collect();
+    for &id in &diff.chunks {
+        let vec = peer.codebook.get(&(id as usize));
+        let correction = peer.corrections.get(id);
+        if vec.is_none() && correction.is_none() {
+            report.removed += 1;
+        } else {
+            report.copied += 1;
+        }
+        match vec {
+            Some(vec) => local.codebook.insert(id as usize, vec.clone()),
+            None => local.codebook.remove(&(id as usize)),
+        };
+        match correction {
+            Some(correction) => corrections.insert(id, correction.clone()),
+            None => corrections.remove(&id),
+        };
+    }
+    local.root = peer.root.clone();
+    local.root_counts = peer.root_counts.clone();
+    local.corrections = CorrectionStore::from_parts(corrections, peer.corrections.totals());
+    Ok(report)
+}
+
+/// A chunk record [`repair`] took from a peer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairedChunk {
+    pub chunk_id: usize,
+    /// Index of the peer, in the order given.
+    pub peer: usize,
+}
+
+/// Outcome of [`repair`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairReport {
+    /// Files with a checksum that were verified.
+    pub files_checked: usize,
+    /// Files that failed verification, in manifest order.
+    pub damaged: Vec<String>,
+    pub repaired: Vec<RepairedChunk>,
+    /// Damaged files no peer could restore.
+    pub unrepaired: Vec<String>,
+}
+
+impl RepairReport {
+    /// True when every file now verifies.
+    pub fn is_ok(&self) -> bool {
+        self.unrepaired.is_empty()
+    }
+}
+
+/// Verify every file of `manifest` in `local` and restore the damaged ones
+/// from `peers`.
+///
+/// For each damaged file, peers are tried in order: the file's chunk
+/// records that differ from the peer's (per [`MerkleTree::diff`]) are
+/// replaced with the peer's, and kept only if the file then verifies. A
+/// peer may itself be damaged elsewhere; only the records of files it
+/// restores are taken from it. Only chunk records change; the root vector
+/// and correction totals are left as they are.
+pub fn repair(
+    local: &mut Engram,
+    manifest: &Manifest,
+    peers: &[&Engram],
+    config: &ReversibleVSAConfig,
+) -> io::Result<RepairReport> {
+    let mut report = RepairReport::default();
+    let mut damaged = Vec::new();
+    for file in &manifest.files {
+        if file.blake3.is_none() {
+            continue;
+        }
+        report.files_checked += 1;
+        if EmbrFS::reconstruct_file(local, file, config, manifest.chunk_size(), |_| Ok(()))?.is_some() {
+            report.damaged.push(file.path.clone());
+            damaged.push(file);
+        }
+    }
+    if damaged.is_empty() {
+        return Ok(report);
+    }
+
+    let local_tree = MerkleTree::build(local)?;
+    let mut differing: Vec<Option<HashSet<u64>>> = vec![None; peers.len()];
+    let mut taken: HashSet<usize> = HashSet::new();
+    for file in damaged {
+        let mut restored = false;
+        for (p, peer) in peers.iter().enumerate() {
+            if differing[p].is_none() {
+                let diff = local_tree.diff(&MerkleTree::build(peer)?);
+                differing[p] = Some(diff.chunks.into_iter().collect());
+            }
+            let differs = differing[p].as_ref().expect("computed above");
+            let mut ids: Vec<usize> = file
+                .chunks
+                .iter()
+                .copied()
+                .filter(|&id| differs.contains(&(id as u64)) && !taken.contains(&id))
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            if ids.is_empty() {
+                continue;
+            }
+
+            let saved: Vec<_> = ids.iter().map(|&id| swap_record(local, peer, id)).collect();
+            if EmbrFS::reconstruct_file(local, file, config, manifest.chunk_size(), |_| Ok(()))?.is_none() {
+                for &chunk_id in &ids {
+                    taken.insert(chunk_id);
+                    report.repaired.push(RepairedChunk { chunk_id, peer: p });
+                }
+                restored = true;
+                break;
+            }
+            for (&id, (vec, correction)) in ids.iter().zip(saved) {
+                match vec {
+                    Some(vec) => local.codebook.insert(id, vec),
+                    None => local.codebook.remove(&id),
+                };
+                local.corrections.replace(id as u64, correction);
+            }
+        }
+        if !restored {
+            report.unrepaired.push(file.path.clone());
+        }
+    }
+    Ok(report)
+}
+
+/// Put `peer`'s record for `id` in `local`, returning `local`'s old one.
+fn swap_record(
+    local: &mut Engram,
+    peer: &Engram,
+    id: usize,
+) -> (Option<SparseVec>, Option<ChunkCorrection>) {
+    let vec = match peer.codebook.get(&id) {
+        Some(vec) => local.codebook.insert(id, vec.clone()),
+        None => local.codebook.remove(&id),
+    };
+    let correction = local
+        .corrections
+        .replace(id as u64, peer.corrections.get(id as u64).cloned());
+    (vec, correction)
+}
@@ -0,0 +1,262 @@
+//! rkyv-archived engram payloads for near-instant loads.
+//!
+//! Deserializing a large codebook with bincode allocates every vector up
+//! front. An rkyv archive instead lays the codebook out so it can be used in
+//! place: [`RkyvEngram::open`] validates the archive once (bounds, pointers,
+//! enum tags) and then hands out chunks straight from the file buffer, which
+//! is memory-mapped when the `mmap` feature is also enabled.
+//!
+//! The file is an unframed EDN1 envelope of kind [`PayloadKind::EngramRkyv`]
+//! followed by the archive. Its 16-byte header keeps the archive 16-byte
+//! aligned in a mapping. Archives are never compressed or encrypted, since
+//! either would defeat reading in place; the correction store, which is only
+//! needed on extraction, is kept as an embedded bincode blob.
+//!
+//! Requires the `rkyv` feature; without it saving and opening return errors.
+
+use crate::correction::CorrectionStore;
+use crate::embrfs::Engram;
+use crate::envelope::{plain_header, PayloadKind, HEADER_LEN};
+use crate::vsa::SparseVec;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Write `engram` as an rkyv archive at `path`.
+pub fn save<P: AsRef<Path>>(engram: &Engram, path: P) -> io::Result<()> {
+    let archive = imp::archive(engram)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&plain_header(PayloadKind::EngramRkyv, archive.len() as u64))?;
+    out.write_all(&archive)?;
+    out.flush()
+}
+
+/// A validated rkyv engram archive, read in place.
+pub struct RkyvEngram {
+    inner: imp::Archive,
+}
+
+impl RkyvEngram {
+    /// Open and validate the archive at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if PayloadKind::sniff_file(path)? != Some(PayloadKind::EngramRkyv) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an rkyv engram archive"));
+        }
+        Ok(Self {
+            inner: imp::Archive::open(path, HEADER_LEN)?,
+        })
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.inner.chunk_count()
+    }
+
+    /// Chunk ids in ascending order.
+    pub fn chunk_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.inner.chunk_ids()
+    }
+
+    /// Copy one codebook vector out of the archive, or `None` if absent.
+    pub fn chunk(&self, chunk_id: usize) -> Option<SparseVec> {
+        self.inner.chunk(chunk_id)
+    }
+
+    pub fn root(&self) -> SparseVec {
+        self.inner.root()
+    }
+
+    /// Decode the embedded correction store.
+    pub fn corrections(&self) -> io::Result<CorrectionStore> {
+        self.inner.corrections()
+    }
+
+    /// Materialize a regular [`Engram`].
+    pub fn to_engram(&self) -> io::Result<Engram> {
+        Ok(Engram {
+            root: self.root(),
+            codebook: self
+                .chunk_ids()
+                .filter_map(|id| self.chunk(id).map(|v| (id, v)))
+                .collect(),
+            corrections: self.corrections()?,
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        })
+    }
+}
+
+#[cfg(feature = "rkyv")]
+mod imp {
+    use super::*;
+    use rkyv::rancor;
+
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    struct VecArchive {
+        pos: Vec<u64>,
+        neg: Vec<u64>,
+    }
+
+    /// On-disk layout. `ids` is sorted and parallel to `chunks`, so lookups
+    /// are a binary search over the archived slice.
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    struct EngramArchive {
+        root: VecArchive,
+        ids: Vec<u64>,
+        chunks: Vec<VecArchive>,
+        corrections: Vec<u8>,
+    }
+
+    fn to_archive(v: &SparseVec) -> VecArchive {
+        VecArchive {
+            pos: v.pos.iter().map(|&i| i as u64).collect(),
+            neg: v.neg.iter().map(|&i| i as u64).collect(),
+        }
+    }
+
+    fn from_archived(v: &ArchivedVecArchive) -> SparseVec {
+        SparseVec {
+            pos: v.pos.iter().map(|i| i.to_native() as usize).collect(),
+            neg: v.neg.iter().map(|i| i.to_native() as usize).collect(),
+        }
+    }
+
+    fn invalid(err: rancor::Error) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("invalid rkyv engram archive: {}", err))
+    }
+
+    pub(super) fn archive(engram: &Engram) -> io::Result<Vec<u8>> {
+        let mut ids: Vec<usize> = engram.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        let value = EngramArchive {
+            root: to_archive(&engram.root),
+            chunks: ids.iter().map(|id| to_archive(&engram.codebook[id])).collect(),
+            ids: ids.into_iter().map(|id| id as u64).collect(),
+            corrections: engram.corrections.to_bytes(),
+        };
+        Ok(rkyv::to_bytes::<rancor::Error>(&value).map_err(invalid)?.to_vec())
+    }
+
+    #[cfg(feature = "mmap")]
+    type Backing = memmap2::Mmap;
+    #[cfg(not(feature = "mmap"))]
+    type Backing = rkyv::util::AlignedVec;
+
+    #[cfg(feature = "mmap")]
+    fn load(path: &Path, offset: usize) -> io::Result<(Backing, usize)> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only; archives are written once and not
+        // modified in place. Pages are page-aligned, so `offset` (16) keeps
+        // the archive aligned for rkyv.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok((map, offset))
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn load(path: &Path, offset: usize) -> io::Result<(Backing, usize)> {
+        let data = std::fs::read(path)?;
+        let mut aligned = rkyv::util::AlignedVec::with_capacity(data.len().saturating_sub(offset));
+        aligned.extend_from_slice(data.get(offset..).unwrap_or_default());
+        Ok((aligned, 0))
+    }
+
+    pub(super) struct Archive {
+        backing: Backing,
+        offset: usize,
+    }
+
+    impl Archive {
+        pub(super) fn open(path: &Path, header_len: usize) -> io::Result<Self> {
+            let (backing, offset) = load(path, header_len)?;
+            let archive = Self { backing, offset };
+            let bytes = archive.bytes()?;
+            let root = rkyv::access::<ArchivedEngramArchive, rancor::Error>(bytes).map_err(invalid)?;
+            if root.ids.len() != root.chunks.len() || !root.ids.windows(2).all(|w| w[0] < w[1]) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "rkyv engram chunk index is malformed"));
+            }
+            Ok(archive)
+        }
+
+        fn bytes(&self) -> io::Result<&[u8]> {
+            self.backing
+                .get(self.offset..)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated rkyv engram archive"))
+        }
+
+        fn get(&self) -> &ArchivedEngramArchive {
+            let bytes = &self.backing[self.offset..];
+            // SAFETY: `open` validated these exact bytes, and the backing
+            // buffer is immutable for the lifetime of `self`.
+            unsafe { rkyv::access_unchecked::<ArchivedEngramArchive>(bytes) }
+        }
+
+        pub(super) fn chunk_count(&self) -> usize {
+            self.get().ids.len()
+        }
+
+        pub(super) fn chunk_ids(&self) -> impl Iterator<Item = usize> + '_ {
+            self.get().ids.iter().map(|id| id.to_native() as usize)
+        }
+
+        pub(super) fn chunk(&self, chunk_id: usize) -> Option<SparseVec> {
+            let archived = self.get();
+            let idx = archived
+                .ids
+                .binary_search_by_key(&(chunk_id as u64), |id| id.to_native())
+                .ok()?;
+            Some(from_archived(&archived.chunks[idx]))
+        }
+
+        pub(super) fn root(&self) -> SparseVec {
+            from_archived(&self.get().root)
+        }
+
+        pub(super) fn corrections(&self) -> io::Result<CorrectionStore> {
+            CorrectionStore::from_bytes(self.get().corrections.as_slice())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "rkyv engram correction store is corrupt"))
+        }
+    }
+}
+
+#[cfg(not(feature = "rkyv"))]
+mod imp {
+    use super::*;
+
+    fn rkyv_disabled() -> io::Error {
+        io::Error::other("rkyv support not enabled (enable feature `rkyv`)")
+    }
+
+    pub(super) fn archive(_: &Engram) -> io::Result<Vec<u8>> {
+        Err(rkyv_disabled())
+    }
+
+    pub(super) enum Archive {}
+
+    impl Archive {
+        pub(super) fn open(_: &Path, _: usize) -> io::Result<Self> {
+            Err(rkyv_disabled())
+        }
+
+        pub(super) fn chunk_count(&self) -> usize {
+            match *self {}
+        }
+
+        pub(super) fn chunk_ids(&self) -> std::iter::Empty<usize> {
+            match *self {}
+        }
+
+        pub(super) fn chunk(&self, _: usize) -> Option<SparseVec> {
+            match *self {}
+        }
+
+        pub(super) fn root(&self) -> SparseVec {
+            match *self {}
+        }
+
+        pub(super) fn corrections(&self) -> io::Result<CorrectionStore> {
+            match *self {}
+        }
+    }
+}
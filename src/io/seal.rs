@@ -0,0 +1,208 @@
+//! Write-once sealing of engrams kept for regulatory retention.
+//!
+//! [`seal_files`] turns an engram and its manifest into a sealed pair. Each
+//! file is rewritten as an envelope flagged as sealed that holds a [`Seal`]
+//! record followed by the file's previous bytes, unchanged, so a sealed
+//! engram loads exactly as it did before sealing. The record carries an
+//! Ed25519 [`DetachedSignature`] over the SHA-256 digests of those sealed
+//! bytes, and both files carry the same record.
+//!
+//! From then on every save that would replace either file fails with
+//! [`EmbrError::Immutable`]: the [`EmbrFS`](crate::EmbrFS) save methods,
+//! [`Transaction::stage`] and the CLI commands built on them. [`verify_seal`]
+//! proves the content has not changed since sealing by recomputing both
+//! digests and checking the signature, optionally against a trusted key.
+//!
+//! Sealing guards against changes made through this library. Pair it with
+//! storage-level retention (object lock, `chattr +i`) to prevent deletion.
+//! Only single-file engrams can be sealed, not split, sharded, rkyv,
+//! append-log or shared-codebook ones.
+
+use crate::append_log;
+use crate::envelope::{split_sealed, wrap_sealed, EnvelopeHeader, PayloadKind, HEADER_LEN};
+use crate::error::{EmbrError, Result};
+use crate::signing::{self, DetachedSignature};
+use crate::txn::Transaction;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current seal record version.
+pub const SEAL_VERSION: u32 = 1;
+
+/// Record stored at the front of a sealed engram and manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seal {
+    pub version: u32,
+    /// Unix seconds at sealing.
+    pub sealed_at: u64,
+    /// Signature over the SHA-256 of the sealed engram and manifest bytes.
+    pub signature: DetachedSignature,
+}
+
+/// Seal the engram at `engram_path` and the manifest at `manifest_path`,
+/// signing with the hex-encoded Ed25519 seed `secret_hex`. Both files are
+/// replaced in one [`Transaction`].
+pub fn seal_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    engram_path: P,
+    manifest_path: Q,
+    secret_hex: &str,
+) -> Result<Seal> {
+    let (engram_path, manifest_path) = (engram_path.as_ref(), manifest_path.as_ref());
+    ensure_unsealed(engram_path)?;
+    ensure_unsealed(manifest_path)?;
+    if append_log::is_append_log(engram_path)?
+        || !matches!(
+            PayloadKind::sniff_file(engram_path)?,
+            None | Some(PayloadKind::EngramBincode)
+        )
+    {
+        return Err(invalid(format!(
+            "{}: only single-file engrams can be sealed",
+            engram_path.display()
+        )));
+    }
+    if !matches!(
+        PayloadKind::sniff_file(manifest_path)?,
+        None | Some(PayloadKind::ManifestJson)
+    ) {
+        return Err(invalid(format!(
+            "{} is not a manifest",
+            manifest_path.display()
+        )));
+    }
+
+    let engram = fs::read(engram_path)?;
+    let manifest = fs::read(manifest_path)?;
+    let signature = signing::sign_digests(&sha256(&engram), &sha256(&manifest), secret_hex)?;
+    let seal = Seal {
+        version: SEAL_VERSION,
+        sealed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        signature,
+    };
+    let record = serde_json::to_vec(&seal)?;
+
+    let mut txn = Transaction::begin(engram_path)?;
+    txn.stage(engram_path, |out| {
+        out.write_all(&wrap_sealed(PayloadKind::EngramBincode, &record, &engram))
+    })?;
+    txn.stage(manifest_path, |out| {
+        out.write_all(&wrap_sealed(PayloadKind::ManifestJson, &record, &manifest))
+    })?;
+    txn.commit()?;
+    Ok(seal)
+}
+
+/// Check that the engram and manifest are still exactly as sealed, and that
+/// the seal was signed by `trusted_public_hex` if given.
+pub fn verify_seal<P: AsRef<Path>, Q: AsRef<Path>>(
+    engram_path: P,
+    manifest_path: Q,
+    trusted_public_hex: Option<&str>,
+) -> Result<Seal> {
+    let (engram_path, manifest_path) = (engram_path.as_ref(), manifest_path.as_ref());
+    let engram = fs::read(engram_path)?;
+    let manifest = fs::read(manifest_path)?;
+    let (record, sealed_engram) = split_sealed(&engram).ok_or_else(|| not_sealed(engram_path))?;
+    let (manifest_record, sealed_manifest) =
+        split_sealed(&manifest).ok_or_else(|| not_sealed(manifest_path))?;
+    if record != manifest_record {
+        return Err(EmbrError::Crypto(
+            "engram and manifest carry different seals".to_string(),
+        ));
+    }
+    let seal = parse_record(record)?;
+    signing::verify_digests(
+        &sha256(sealed_engram),
+        &sha256(sealed_manifest),
+        &seal.signature,
+        trusted_public_hex,
+    )?;
+    Ok(seal)
+}
+
+/// The seal of the file at `path`, or `None` if it is not sealed. The seal
+/// is not verified.
+pub fn read_seal<P: AsRef<Path>>(path: P) -> Result<Option<Seal>> {
+    match read_sealed_prefix(path.as_ref())? {
+        Some((record, _)) => Ok(Some(parse_record(&record)?)),
+        None => Ok(None),
+    }
+}
+
+/// Whether the file at `path` is sealed. A missing path, or one that is not
+/// a regular file, is not.
+pub fn is_sealed<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_file() => {}
+        Ok(_) => return Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(EnvelopeHeader::parse(&header).is_some_and(|h| h.sealed))
+}
+
+/// Fail with [`EmbrError::Immutable`] if `path` holds a sealed file.
+pub(crate) fn ensure_unsealed(path: &Path) -> io::Result<()> {
+    if is_sealed(path)? {
+        return Err(EmbrError::Immutable(format!("{} is sealed", path.display())).into());
+    }
+    Ok(())
+}
+
+/// The seal record of the file at `path` and the header of the file it
+/// seals, or `None` if it is not sealed.
+pub(crate) fn read_sealed_prefix(path: &Path) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut file = File::open(path)?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut file)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    if !EnvelopeHeader::parse(&header).is_some_and(|h| h.sealed) {
+        return Ok(None);
+    }
+    let len = u64::from_le_bytes(header[8..16].try_into().expect("header length checked"));
+    let mut record = Vec::new();
+    (&mut file).take(len).read_to_end(&mut record)?;
+    if record.len() as u64 != len {
+        return Err(EmbrError::InvalidEnvelope("truncated seal record".to_string()).into());
+    }
+    let mut sealed_header = Vec::with_capacity(HEADER_LEN);
+    file.take(HEADER_LEN as u64)
+        .read_to_end(&mut sealed_header)?;
+    Ok(Some((record, sealed_header)))
+}
+
+fn parse_record(record: &[u8]) -> Result<Seal> {
+    let seal: Seal = serde_json::from_slice(record)
+        .map_err(|e| EmbrError::InvalidEnvelope(format!("malformed seal record: {}", e)))?;
+    if seal.version != SEAL_VERSION {
+        return Err(EmbrError::InvalidEnvelope(format!(
+            "unsupported seal version {}",
+            seal.version
+        )));
+    }
+    Ok(seal)
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn not_sealed(path: &Path) -> EmbrError {
+    invalid(format!("{} is not sealed", path.display()))
+}
+
+fn invalid(msg: String) -> EmbrError {
+    io::Error::new(io::ErrorKind::InvalidInput, msg).into()
+}
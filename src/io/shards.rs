@@ -0,0 +1,508 @@
+//! Sharding an engram's codebook by chunk-id range.
+//!
+//! A whole-engram envelope must be decoded in full before any file can be
+//! read, which caps engram size at what fits in memory. A sharded engram is
+//! written as a small index plus one envelope per range of chunk ids:
+//!
+//! ```text
+//! root.engram             -- EDN1 envelope, kind ShardIndex, JSON ShardIndex
+//! root.engram.shard0000   -- EDN1 envelope, kind CodebookShard, ids [0, n)
+//! root.engram.shard0001   -- ids [n, 2n)
+//! ...
+//! ```
+//!
+//! The index holds the root vector, the correction totals and, for every
+//! shard, its id range, size and blake3 digest. [`ShardedEngram`] opens the
+//! index alone and loads a shard the first time one of its chunks is asked
+//! for, keeping only the most recently used few in memory, so a host can
+//! serve codebooks far larger than its RAM. Ranges with no chunks get no
+//! shard file.
+//!
+//! Each shard is its own envelope, written with the caller's compression,
+//! checksum and encryption options. The index uses the same options but is
+//! always checksummed, so that it has a header to be recognized by. As with
+//! split engrams, the index is "the engram" that other commands are given,
+//! and a detached signature over it covers every shard through its digest.
+
+use crate::correction::{ChunkCorrection, CorrectionStore, CorrectionTotals};
+use crate::embrfs::{bincode_io_error, decode_bounded, ChecksumMismatch, EmbrFS, Engram, FileEntry, LoadLimits};
+use crate::envelope::{
+    BinaryWriteOptions, ChecksumCodec, CompressionCodec, DictionarySampler, EnvelopeReader,
+    EnvelopeWriter, Keyring, PayloadKind, DEFAULT_DICT_SIZE, DICT_FRAME_SIZE,
+};
+use crate::error::EmbrError;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Current shard index document version.
+pub const SHARD_INDEX_VERSION: u32 = 1;
+
+/// Shards [`ShardedEngram`] keeps decoded at once unless told otherwise.
+pub const DEFAULT_RESIDENT_SHARDS: usize = 4;
+
+/// One shard file of a sharded codebook.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardEntry {
+    /// File name, relative to the directory holding the index.
+    pub file: String,
+    /// First chunk id of the range.
+    pub first_id: u64,
+    /// One past the last chunk id of the range.
+    pub end_id: u64,
+    /// Chunk records (vectors and corrections) stored in the shard.
+    pub chunks: usize,
+    pub len: u64,
+    /// Hex-encoded blake3 of the shard file.
+    pub blake3: String,
+}
+
+/// Index of a sharded engram; shards are sorted by id range.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShardIndex {
+    pub version: u32,
+    /// Chunk ids per shard range.
+    pub shard_chunks: u64,
+    pub root: SparseVec,
+    corrections: CorrectionTotals,
+    pub shards: Vec<ShardEntry>,
+}
+
+impl ShardIndex {
+    /// Shard holding `chunk_id`, if its range has one.
+    pub fn shard_for(&self, chunk_id: u64) -> Option<usize> {
+        let n = self.shards.partition_point(|s| s.end_id <= chunk_id);
+        (n < self.shards.len() && self.shards[n].first_id <= chunk_id).then_some(n)
+    }
+
+    /// Bytes on disk of all shard files.
+    pub fn total_len(&self) -> u64 {
+        self.shards.iter().map(|s| s.len).sum()
+    }
+}
+
+/// Decoded contents of one shard.
+#[derive(Default, Serialize, Deserialize)]
+struct Shard {
+    codebook: HashMap<usize, SparseVec>,
+    corrections: HashMap<u64, ChunkCorrection>,
+}
+
+fn shard_file_name(index_path: &Path, n: u64) -> io::Result<String> {
+    let name = index_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "shard index path needs a UTF-8 file name",
+            )
+        })?;
+    Ok(format!("{}.shard{:04}", name, n))
+}
+
+fn index_dir(index_path: &Path) -> &Path {
+    index_path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+/// Serialize `value` into an envelope, training a dictionary first for
+/// `ZstdDict`.
+pub(crate) fn write_envelope<W: Write, T: Serialize>(
+    out: W,
+    kind: PayloadKind,
+    opts: BinaryWriteOptions,
+    value: &T,
+) -> io::Result<W> {
+    let mut writer = if opts.codec == CompressionCodec::ZstdDict {
+        let mut sampler = DictionarySampler::new(DICT_FRAME_SIZE);
+        bincode::serialize_into(&mut sampler, value).map_err(|e| bincode_io_error(*e))?;
+        let dict = sampler.train(DEFAULT_DICT_SIZE)?;
+        EnvelopeWriter::with_dictionary(out, kind, opts, dict)?
+    } else {
+        EnvelopeWriter::new(out, kind, opts)?
+    };
+    bincode::serialize_into(&mut writer, value).map_err(|e| bincode_io_error(*e))?;
+    writer.finish()
+}
+
+/// Write `engram` as an index at `index_path` plus one shard per
+/// `shard_chunks` chunk ids, removing shards left by an earlier save.
+pub fn save(
+    engram: &Engram,
+    index_path: &Path,
+    shard_chunks: u64,
+    opts: BinaryWriteOptions,
+) -> io::Result<ShardIndex> {
+    if shard_chunks == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "shard size must be positive",
+        ));
+    }
+    shard_file_name(index_path, 0)?;
+    let dir = index_dir(index_path);
+
+    let mut ranges: BTreeMap<u64, Shard> = BTreeMap::new();
+    for (&id, vec) in &engram.codebook {
+        let shard = ranges.entry(id as u64 / shard_chunks).or_default();
+        shard.codebook.insert(id, vec.clone());
+    }
+    for (id, correction) in engram.corrections.iter() {
+        let shard = ranges.entry(id / shard_chunks).or_default();
+        shard.corrections.insert(id, correction.clone());
+    }
+
+    let mut shards = Vec::with_capacity(ranges.len());
+    let mut written = HashSet::new();
+    for (range, shard) in &ranges {
+        let file = shard_file_name(index_path, *range)?;
+        let data = write_envelope(Vec::new(), PayloadKind::CodebookShard, opts, shard)?;
+        let mut out = File::create(dir.join(&file))?;
+        out.write_all(&data)?;
+        out.sync_data()?;
+        shards.push(ShardEntry {
+            first_id: range * shard_chunks,
+            end_id: (range + 1).saturating_mul(shard_chunks),
+            chunks: shard.codebook.len()
+                + shard
+                    .corrections
+                    .keys()
+                    .filter(|id| !shard.codebook.contains_key(&(**id as usize)))
+                    .count(),
+            len: data.len() as u64,
+            blake3: blake3::hash(&data).to_hex().to_string(),
+            file: file.clone(),
+        });
+        written.insert(file);
+    }
+
+    let index = ShardIndex {
+        version: SHARD_INDEX_VERSION,
+        shard_chunks,
+        root: engram.root.clone(),
+        corrections: engram.corrections.totals(),
+        shards,
+    };
+    // The index is too small to train a dictionary on, and must always be
+    // framed so that its header identifies it.
+    let index_opts = BinaryWriteOptions {
+        codec: match opts.codec {
+            CompressionCodec::ZstdDict => CompressionCodec::Zstd,
+            codec => codec,
+        },
+        checksum: match opts.checksum {
+            ChecksumCodec::None => ChecksumCodec::Xxh3,
+            checksum => checksum,
+        },
+        ..opts
+    };
+    let file = BufWriter::new(File::create(index_path)?);
+    let mut writer = EnvelopeWriter::new(file, PayloadKind::ShardIndex, index_opts)?;
+    serde_json::to_writer(&mut writer, &index)?;
+    writer.finish()?.flush()?;
+
+    for stale in existing_shard_files(index_path)? {
+        if !written.contains(&stale) {
+            fs::remove_file(dir.join(stale))?;
+        }
+    }
+    Ok(index)
+}
+
+/// Shard files present beside `index_path`, whether or not its index lists them.
+pub fn existing_shard_files(index_path: &Path) -> io::Result<Vec<String>> {
+    let Some(prefix) = index_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| format!("{n}.shard"))
+    else {
+        return Ok(Vec::new());
+    };
+    let dir = match index_dir(index_path) {
+        d if d.as_os_str().is_empty() => Path::new("."),
+        d => d,
+    };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name
+            .strip_prefix(&prefix)
+            .is_some_and(|n| n.len() >= 4 && n.bytes().all(|b| b.is_ascii_digit()))
+        {
+            files.push(name);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the file at `path` is a shard index.
+pub fn is_shard_index<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    Ok(PayloadKind::sniff_file(path)? == Some(PayloadKind::ShardIndex))
+}
+
+/// Load the index at `index_path`, decrypting it with `keys` if needed.
+pub fn read_shard_index<P: AsRef<Path>>(index_path: P, keys: &Keyring) -> io::Result<ShardIndex> {
+    let file = BufReader::new(File::open(index_path)?);
+    let mut reader = EnvelopeReader::with_keys(file, PayloadKind::ShardIndex, keys)?;
+    let index: ShardIndex = serde_json::from_reader(&mut reader)?;
+    io::copy(&mut reader, &mut io::sink())?;
+    if index.version != SHARD_INDEX_VERSION {
+        return Err(io::Error::other(format!(
+            "unsupported shard index version {}",
+            index.version
+        )));
+    }
+    for shard in &index.shards {
+        if Path::new(&shard.file).components().count() != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("shard name {:?} must be a bare file name", shard.file),
+            ));
+        }
+    }
+    if index.shards.windows(2).any(|w| w[0].end_id > w[1].first_id) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "shard ranges overlap or are out of order",
+        ));
+    }
+    Ok(index)
+}
+
+/// Read-only view of a sharded engram that loads shards on demand.
+///
+/// Safe to share between threads; concurrent readers of different shards
+/// decode them in parallel.
+pub struct ShardedEngram {
+    dir: PathBuf,
+    index: ShardIndex,
+    keys: Keyring,
+    max_resident: usize,
+    /// Decoded shards, most recently used last.
+    resident: Mutex<Vec<(usize, Arc<Shard>)>>,
+}
+
+impl ShardedEngram {
+    /// Open the index at `path`, checking that every shard is present with
+    /// the recorded size. No shard is decoded yet.
+    pub fn open<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let path = path.as_ref();
+        let index = read_shard_index(path, keys)?;
+        let dir = index_dir(path).to_path_buf();
+        for shard in &index.shards {
+            let len = fs::metadata(dir.join(&shard.file))
+                .map_err(|e| io::Error::new(e.kind(), format!("shard {}: {}", shard.file, e)))?
+                .len();
+            if len != shard.len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "shard {} is {} bytes, index expects {}",
+                        shard.file, len, shard.len
+                    ),
+                ));
+            }
+        }
+        Ok(Self {
+            dir,
+            index,
+            keys: keys.clone(),
+            max_resident: DEFAULT_RESIDENT_SHARDS,
+            resident: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Keep at most `max` decoded shards in memory (at least one).
+    pub fn with_max_resident(mut self, max: usize) -> Self {
+        self.max_resident = max.max(1);
+        self
+    }
+
+    pub fn index(&self) -> &ShardIndex {
+        &self.index
+    }
+
+    pub fn root(&self) -> &SparseVec {
+        &self.index.root
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.index.shards.iter().map(|s| s.chunks).sum()
+    }
+
+    /// Shards currently decoded in memory.
+    pub fn resident_shards(&self) -> usize {
+        self.resident
+            .lock()
+            .expect("shard cache lock poisoned")
+            .len()
+    }
+
+    fn shard(&self, n: usize) -> io::Result<Arc<Shard>> {
+        {
+            let mut resident = self.resident.lock().expect("shard cache lock poisoned");
+            if let Some(pos) = resident.iter().position(|(i, _)| *i == n) {
+                let entry = resident.remove(pos);
+                let shard = entry.1.clone();
+                resident.push(entry);
+                return Ok(shard);
+            }
+        }
+
+        // Decode outside the lock; a racing reader may decode the same
+        // shard, which only costs time.
+        let shard = Arc::new(self.decode_uncached(n)?);
+
+        let mut resident = self.resident.lock().expect("shard cache lock poisoned");
+        resident.retain(|(i, _)| *i != n);
+        if resident.len() >= self.max_resident {
+            resident.remove(0);
+        }
+        resident.push((n, shard.clone()));
+        Ok(shard)
+    }
+
+    fn chunk_entry(
+        &self,
+        chunk_id: usize,
+    ) -> io::Result<(Option<SparseVec>, Option<ChunkCorrection>)> {
+        let Some(n) = self.index.shard_for(chunk_id as u64) else {
+            return Ok((None, None));
+        };
+        let shard = self.shard(n)?;
+        Ok((
+            shard.codebook.get(&chunk_id).cloned(),
+            shard.corrections.get(&(chunk_id as u64)).cloned(),
+        ))
+    }
+
+    /// Codebook vector for one chunk, loading its shard if needed.
+    pub fn chunk(&self, chunk_id: usize) -> io::Result<Option<SparseVec>> {
+        Ok(self.chunk_entry(chunk_id)?.0)
+    }
+
+    /// Correction record for one chunk, loading its shard if needed.
+    pub fn correction(&self, chunk_id: usize) -> io::Result<Option<ChunkCorrection>> {
+        Ok(self.chunk_entry(chunk_id)?.1)
+    }
+
+    /// Reconstruct one file into `out`, loading only the shards it touches.
+    ///
+    /// As with [`crate::Envelope::write_file`], a checksum mismatch is an
+    /// `InvalidData` error returned after every byte has been written.
+    pub fn write_file<W: Write>(
+        &self,
+        file_entry: &FileEntry,
+        config: &ReversibleVSAConfig,
+        chunk_size: usize,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let mut hasher = blake3::Hasher::new();
+        let mut bad_chunks = Vec::new();
+        for (chunk_idx, &chunk_id) in file_entry.chunks.iter().enumerate() {
+            let (Some(vec), correction) = self.chunk_entry(chunk_id)? else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{}: chunk {} is missing from the engram",
+                        file_entry.path, chunk_id
+                    ),
+                ));
+            };
+            let decoded = vec.decode_data(
+                config,
+                Some(&file_entry.path),
+                EmbrFS::chunk_len(file_entry, chunk_idx, chunk_size),
+            );
+            let chunk = match correction {
+                Some(c) => {
+                    let fixed = c.apply(&decoded);
+                    if c.verify(&fixed) {
+                        fixed
+                    } else {
+                        bad_chunks.push(chunk_id);
+                        decoded
+                    }
+                }
+                None => decoded,
+            };
+            hasher.update(&chunk);
+            out.write_all(&chunk)?;
+        }
+
+        let Some(expected) = file_entry.blake3.as_ref() else {
+            return Ok(());
+        };
+        let actual = hasher.finalize().to_hex().to_string();
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(());
+        }
+        Err(EmbrError::ManifestMismatch(vec![ChecksumMismatch {
+            path: file_entry.path.clone(),
+            expected: expected.clone(),
+            actual,
+            chunk_ids: bad_chunks,
+        }])
+        .into())
+    }
+
+    /// Decode every shard into one in-memory engram.
+    pub fn to_engram(&self) -> io::Result<Engram> {
+        let mut codebook = HashMap::new();
+        let mut corrections = HashMap::new();
+        for n in 0..self.index.shards.len() {
+            // Bypass the resident set so a full load doesn't evict hot shards.
+            let shard = match self.resident_shard(n) {
+                Some(shard) => shard,
+                None => Arc::new(self.decode_uncached(n)?),
+            };
+            codebook.extend(shard.codebook.iter().map(|(&id, v)| (id, v.clone())));
+            corrections.extend(shard.corrections.iter().map(|(&id, c)| (id, c.clone())));
+        }
+        Ok(Engram {
+            root: self.index.root.clone(),
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, self.index.corrections),
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        })
+    }
+
+    fn resident_shard(&self, n: usize) -> Option<Arc<Shard>> {
+        let resident = self.resident.lock().expect("shard cache lock poisoned");
+        resident
+            .iter()
+            .find(|(i, _)| *i == n)
+            .map(|(_, s)| s.clone())
+    }
+
+    /// Read shard `n`, check its digest and decode it.
+    fn decode_uncached(&self, n: usize) -> io::Result<Shard> {
+        let entry = &self.index.shards[n];
+        let data = fs::read(self.dir.join(&entry.file))?;
+        if !blake3::hash(&data)
+            .to_hex()
+            .eq_ignore_ascii_case(&entry.blake3)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "shard {} does not match its recorded blake3 digest",
+                    entry.file
+                ),
+            ));
+        }
+        let mut reader =
+            EnvelopeReader::with_keys(&data[..], PayloadKind::CodebookShard, &self.keys)?;
+        let shard = decode_bounded(&mut reader, LoadLimits::default().max_bytes)?;
+        io::copy(&mut reader, &mut io::sink())?;
+        Ok(shard)
+    }
+}
@@ -0,0 +1,554 @@
+//! A codebook shared by many engrams.
+//!
+//! Successive versions of a dataset re-encode mostly the same chunks, and
+//! every engram embeds its own full copy of them. A shared codebook is a
+//! standalone file holding chunk records (a codebook vector and its
+//! correction) keyed by the blake3 digest of the record; an engram saved
+//! against it is written as a small reference instead:
+//!
+//! ```text
+//! datasets.codebook   -- EDN1 envelope, kind SharedCodebook: records by digest
+//! v1.engram           -- EDN1 envelope, kind CodebookRef: root, chunk id -> digest
+//! v2.engram           -- ...
+//! ```
+//!
+//! A record is stored once however many engrams use it. The codebook counts,
+//! per record, the engrams referencing it, and registers every referencing
+//! engram by id. Saving an engram over an older reference to the same
+//! codebook releases the old one first, so counts stay exact across resaves;
+//! [`release`] drops an engram that is about to be deleted, and [`gc`]
+//! removes records no engram references, optionally recounting from the
+//! engrams actually present on disk.
+//!
+//! Loading a reference checks each record against its digest, so a detached
+//! signature over the reference also covers the records it uses. Writers
+//! are not coordinated: two processes saving against one codebook at the
+//! same time lose one of the updates.
+
+use crate::correction::{ChunkCorrection, CorrectionStore, CorrectionTotals};
+use crate::embrfs::{bincode_io_error, decode_bounded, Engram, LoadLimits};
+use crate::envelope::{
+    BinaryWriteOptions, ChecksumCodec, CompressionCodec, EnvelopeReader, EnvelopeWriter, Keyring,
+    PayloadKind,
+};
+use crate::job::{JobControl, JobProgress};
+use crate::signing::to_hex;
+use crate::vsa::SparseVec;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the codebook and reference layouts.
+pub const SHARED_CODEBOOK_VERSION: u32 = 1;
+
+/// blake3 digest identifying a chunk record.
+pub type RecordDigest = [u8; 32];
+
+/// One chunk's vector and correction, as stored in a shared codebook.
+///
+/// The correction's `chunk_id` is zeroed so that identical chunks at
+/// different ids in different engrams share a record.
+#[derive(Clone, Serialize, Deserialize)]
+struct SharedRecord {
+    vector: Option<SparseVec>,
+    correction: Option<ChunkCorrection>,
+    /// Engrams referencing this record.
+    refs: u64,
+}
+
+impl SharedRecord {
+    fn digest(&self) -> io::Result<RecordDigest> {
+        let bytes = bincode::serialize(&(&self.vector, &self.correction))
+            .map_err(|e| bincode_io_error(*e))?;
+        Ok(*blake3::hash(&bytes).as_bytes())
+    }
+}
+
+/// Chunk records shared by the engrams that reference them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SharedCodebook {
+    pub version: u32,
+    /// Random id that references name, so a reference can't be resolved
+    /// against the wrong codebook.
+    pub id: String,
+    records: BTreeMap<RecordDigest, SharedRecord>,
+    /// Registered engrams: id -> path it was last saved to.
+    pub referrers: BTreeMap<String, String>,
+}
+
+impl Default for SharedCodebook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedCodebook {
+    /// An empty codebook with a fresh id.
+    pub fn new() -> Self {
+        SharedCodebook {
+            version: SHARED_CODEBOOK_VERSION,
+            id: to_hex(&rand::random::<[u8; 16]>()),
+            records: BTreeMap::new(),
+            referrers: BTreeMap::new(),
+        }
+    }
+
+    /// Load the codebook at `path`, decrypting it with `keys` if needed.
+    pub fn open<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let codebook: Self = read_envelope(path.as_ref(), PayloadKind::SharedCodebook, keys)?;
+        if codebook.version != SHARED_CODEBOOK_VERSION {
+            return Err(io::Error::other(format!(
+                "unsupported shared codebook version {}",
+                codebook.version
+            )));
+        }
+        Ok(codebook)
+    }
+
+    /// Load the codebook at `path`, or start a new one if there is none.
+    pub fn open_or_new<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        match Self::open(&path, keys) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            other => other,
+        }
+    }
+
+    /// Write the codebook to `path`, replacing it atomically.
+    pub fn save<P: AsRef<Path>>(&self, path: P, opts: BinaryWriteOptions) -> io::Result<()> {
+        write_envelope(path.as_ref(), PayloadKind::SharedCodebook, opts, self)
+    }
+
+    /// Distinct records stored.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Engrams referencing the record with `digest`, or `None` if absent.
+    pub fn refs(&self, digest: &RecordDigest) -> Option<u64> {
+        self.records.get(digest).map(|r| r.refs)
+    }
+
+    /// Records no registered engram references.
+    pub fn unreferenced(&self) -> usize {
+        self.records.values().filter(|r| r.refs == 0).count()
+    }
+
+    /// Add the records of `engram`, count one reference to each distinct
+    /// record it uses and register it under a new id. Returns the
+    /// reference to write for it and how many records were new.
+    fn add(
+        &mut self,
+        engram: &Engram,
+        engram_path: &Path,
+        codebook: String,
+    ) -> io::Result<(CodebookRef, usize)> {
+        let mut chunks = Vec::new();
+        let mut used = BTreeSet::new();
+        let mut added = 0;
+        for id in engram.chunk_record_ids() {
+            let record = SharedRecord {
+                vector: engram.codebook.get(&(id as usize)).cloned(),
+                correction: engram.corrections.get(id).map(|c| ChunkCorrection {
+                    chunk_id: 0,
+                    ..c.clone()
+                }),
+                refs: 0,
+            };
+            let digest = record.digest()?;
+            if let Entry::Vacant(slot) = self.records.entry(digest) {
+                slot.insert(record);
+                added += 1;
+            }
+            used.insert(digest);
+            chunks.push((id, digest));
+        }
+        for digest in &used {
+            self.records.get_mut(digest).expect("inserted above").refs += 1;
+        }
+
+        let id = to_hex(&rand::random::<[u8; 16]>());
+        self.referrers
+            .insert(id.clone(), engram_path.display().to_string());
+        let reference = CodebookRef {
+            version: SHARED_CODEBOOK_VERSION,
+            id,
+            codebook,
+            codebook_id: self.id.clone(),
+            root: engram.root.clone(),
+            chunks,
+            corrections: engram.corrections.totals(),
+        };
+        Ok((reference, added))
+    }
+
+    /// Drop one reference to every record `reference` uses and unregister
+    /// it. A reference that isn't registered is left alone, so releasing
+    /// twice doesn't undercount.
+    fn remove(&mut self, reference: &CodebookRef) {
+        if self.referrers.remove(&reference.id).is_none() {
+            return;
+        }
+        for digest in reference.digests() {
+            if let Some(record) = self.records.get_mut(&digest) {
+                record.refs = record.refs.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Rebuild an [`Engram`] from `reference`, checking every record
+    /// against its digest.
+    pub fn resolve(&self, reference: &CodebookRef) -> io::Result<Engram> {
+        if reference.codebook_id != self.id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "engram references codebook {}, but {} was found",
+                    reference.codebook_id, self.id
+                ),
+            ));
+        }
+        let mut codebook = HashMap::with_capacity(reference.chunks.len());
+        let mut corrections = HashMap::new();
+        for &(id, digest) in &reference.chunks {
+            let record = self.records.get(&digest).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "chunk {} ({}) is missing from the shared codebook",
+                        id,
+                        to_hex(&digest)
+                    ),
+                )
+            })?;
+            if record.digest()? != digest {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "shared codebook record {} does not match its digest",
+                        to_hex(&digest)
+                    ),
+                ));
+            }
+            if let Some(vector) = &record.vector {
+                codebook.insert(id as usize, vector.clone());
+            }
+            if let Some(correction) = &record.correction {
+                corrections.insert(
+                    id,
+                    ChunkCorrection {
+                        chunk_id: id,
+                        ..correction.clone()
+                    },
+                );
+            }
+        }
+        Ok(Engram {
+            root: reference.root.clone(),
+            codebook,
+            corrections: CorrectionStore::from_parts(corrections, reference.corrections),
+            root_counts: None,
+            precision: None,
+            differential: None,
+            schema: None,
+        })
+    }
+}
+
+/// An engram stored as references into a [`SharedCodebook`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CodebookRef {
+    pub version: u32,
+    /// Id under which the codebook registers this engram.
+    pub id: String,
+    /// Path of the codebook, relative to the engram's directory unless
+    /// absolute.
+    pub codebook: String,
+    pub codebook_id: String,
+    pub root: SparseVec,
+    /// Chunk id and the digest of its record, by ascending id.
+    pub chunks: Vec<(u64, RecordDigest)>,
+    corrections: CorrectionTotals,
+}
+
+impl CodebookRef {
+    /// Load the reference at `path`.
+    pub fn open<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let reference: Self = read_envelope(path.as_ref(), PayloadKind::CodebookRef, keys)?;
+        if reference.version != SHARED_CODEBOOK_VERSION {
+            return Err(io::Error::other(format!(
+                "unsupported codebook reference version {}",
+                reference.version
+            )));
+        }
+        Ok(reference)
+    }
+
+    /// Path of the codebook, resolved against the directory of the
+    /// reference at `engram_path`.
+    pub fn codebook_path(&self, engram_path: &Path) -> PathBuf {
+        engram_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&self.codebook)
+    }
+
+    fn digests(&self) -> BTreeSet<RecordDigest> {
+        self.chunks.iter().map(|&(_, digest)| digest).collect()
+    }
+}
+
+/// Outcome of saving an engram against a shared codebook.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedSaveReport {
+    /// Chunk records the engram references.
+    pub chunks: usize,
+    /// Records that were not in the codebook yet.
+    pub records_added: usize,
+    /// Distinct records in the codebook after the save.
+    pub records_total: usize,
+}
+
+/// Outcome of [`gc`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    /// Registered engrams before and after the collection.
+    pub engrams_before: usize,
+    pub engrams_after: usize,
+    /// Records before the collection and records removed.
+    pub records_before: usize,
+    pub records_removed: usize,
+}
+
+/// Whether the file at `path` is an engram stored as a [`CodebookRef`].
+pub fn is_codebook_ref<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    Ok(PayloadKind::sniff_file(path)? == Some(PayloadKind::CodebookRef))
+}
+
+/// Save `engram` to `engram_path` as a reference into the codebook at
+/// `codebook_path`, creating the codebook if needed. Both files are written
+/// with `opts`, and always checksummed so that their headers identify them.
+///
+/// If `engram_path` already holds a reference into this codebook, that
+/// engram is released first.
+pub fn save(
+    engram: &Engram,
+    engram_path: &Path,
+    codebook_path: &Path,
+    opts: BinaryWriteOptions,
+) -> io::Result<SharedSaveReport> {
+    let keys: Keyring = opts.key.into_iter().collect();
+    let mut codebook = SharedCodebook::open_or_new(codebook_path, &keys)?;
+    if is_codebook_ref(engram_path).unwrap_or(false) {
+        let old = CodebookRef::open(engram_path, &keys)?;
+        if old.codebook_id == codebook.id {
+            codebook.remove(&old);
+        }
+    }
+
+    let (reference, added) = codebook.add(
+        engram,
+        engram_path,
+        codebook_reference(engram_path, codebook_path)?,
+    )?;
+    // Codebook first, so the new reference never names missing records.
+    codebook.save(codebook_path, opts)?;
+    write_envelope(engram_path, PayloadKind::CodebookRef, opts, &reference)?;
+    Ok(SharedSaveReport {
+        chunks: reference.chunks.len(),
+        records_added: added,
+        records_total: codebook.len(),
+    })
+}
+
+/// Load the engram referenced by the file at `engram_path`.
+pub fn load(engram_path: &Path, keys: &Keyring) -> io::Result<Engram> {
+    let reference = CodebookRef::open(engram_path, keys)?;
+    SharedCodebook::open(reference.codebook_path(engram_path), keys)?.resolve(&reference)
+}
+
+/// Unregister the engram at `engram_path` from its codebook, dropping its
+/// references, before the engram is deleted. The codebook is rewritten with
+/// `opts`; records are only removed by [`gc`].
+pub fn release(engram_path: &Path, keys: &Keyring, opts: BinaryWriteOptions) -> io::Result<()> {
+    let reference = CodebookRef::open(engram_path, keys)?;
+    let codebook_path = reference.codebook_path(engram_path);
+    let mut codebook = SharedCodebook::open(&codebook_path, keys)?;
+    if reference.codebook_id != codebook.id {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "engram references a different codebook",
+        ));
+    }
+    codebook.remove(&reference);
+    codebook.save(&codebook_path, opts)
+}
+
+/// Remove the records of the codebook at `codebook_path` that no engram
+/// references, rewriting it with `opts`.
+///
+/// With `engrams`, reference counts are first rebuilt from those engrams,
+/// which must then be every engram still using the codebook: registered
+/// engrams not among them are unregistered, and records only they used are
+/// removed. Engrams referencing another codebook are an error. Without
+/// `engrams`, the stored counts are trusted. With `dry_run` nothing is
+/// written.
+pub fn gc(
+    codebook_path: &Path,
+    engrams: Option<&[PathBuf]>,
+    keys: &Keyring,
+    opts: BinaryWriteOptions,
+    dry_run: bool,
+) -> io::Result<GcReport> {
+    gc_with_control(codebook_path, engrams, keys, opts, dry_run, &JobControl::default())
+}
+
+/// [`gc`] under `control`: engram reads are throttled to its rate limit,
+/// and once it is cancelled collection stops before the next engram with
+/// [`EmbrError::Cancelled`](crate::EmbrError::Cancelled), leaving the
+/// codebook untouched.
+pub fn gc_with_control(
+    codebook_path: &Path,
+    engrams: Option<&[PathBuf]>,
+    keys: &Keyring,
+    opts: BinaryWriteOptions,
+    dry_run: bool,
+    control: &JobControl,
+) -> io::Result<GcReport> {
+    let mut codebook = SharedCodebook::open(codebook_path, keys)?;
+    let mut report = GcReport {
+        engrams_before: codebook.referrers.len(),
+        records_before: codebook.len(),
+        ..Default::default()
+    };
+
+    if let Some(engrams) = engrams {
+        let mut referrers = BTreeMap::new();
+        for record in codebook.records.values_mut() {
+            record.refs = 0;
+        }
+        let mut progress = JobProgress::default();
+        for path in engrams {
+            control.checkpoint(progress)?;
+            let bytes = std::fs::metadata(path)?.len();
+            control.throttle(bytes);
+            progress.files += 1;
+            progress.bytes += bytes;
+            let reference = CodebookRef::open(path, keys)?;
+            if reference.codebook_id != codebook.id {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} references a different codebook", path.display()),
+                ));
+            }
+            if referrers
+                .insert(reference.id.clone(), path.display().to_string())
+                .is_some()
+            {
+                continue;
+            }
+            for digest in reference.digests() {
+                let record = codebook.records.get_mut(&digest).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} uses record {}, which the codebook lacks",
+                            path.display(),
+                            to_hex(&digest)
+                        ),
+                    )
+                })?;
+                record.refs += 1;
+            }
+        }
+        codebook.referrers = referrers;
+    }
+
+    codebook.records.retain(|_, r| r.refs > 0);
+    report.engrams_after = codebook.referrers.len();
+    report.records_removed = report.records_before - codebook.len();
+    if !dry_run {
+        codebook.save(codebook_path, opts)?;
+    }
+    Ok(report)
+}
+
+/// How a reference names its codebook: a bare file name when both sit in
+/// the same directory, otherwise an absolute path.
+fn codebook_reference(engram_path: &Path, codebook_path: &Path) -> io::Result<String> {
+    let parent = |p: &Path| -> io::Result<PathBuf> {
+        match p.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d.canonicalize(),
+            _ => Path::new(".").canonicalize(),
+        }
+    };
+    let name = codebook_path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "codebook path needs a file name",
+        )
+    })?;
+    let dir = parent(codebook_path)?;
+    let reference = if dir == parent(engram_path)? {
+        PathBuf::from(name)
+    } else {
+        dir.join(name)
+    };
+    reference
+        .into_os_string()
+        .into_string()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "codebook path must be UTF-8"))
+}
+
+fn read_envelope<T: DeserializeOwned>(
+    path: &Path,
+    kind: PayloadKind,
+    keys: &Keyring,
+) -> io::Result<T> {
+    let file = BufReader::new(File::open(path)?);
+    let mut reader = EnvelopeReader::with_keys(file, kind, keys)?;
+    let value = decode_bounded(&mut reader, LoadLimits::default().max_bytes)?;
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(value)
+}
+
+/// Write `value` beside `path` and rename it into place.
+fn write_envelope<T: Serialize>(
+    path: &Path,
+    kind: PayloadKind,
+    opts: BinaryWriteOptions,
+    value: &T,
+) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path needs a file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    // Always framed, so the header identifies the file; dictionary training
+    // is skipped in favour of plain zstd.
+    let opts = BinaryWriteOptions {
+        codec: match opts.codec {
+            CompressionCodec::ZstdDict => CompressionCodec::Zstd,
+            codec => codec,
+        },
+        checksum: match opts.checksum {
+            ChecksumCodec::None => ChecksumCodec::Xxh3,
+            checksum => checksum,
+        },
+        ..opts
+    };
+    let file = File::create(&tmp)?;
+    let mut writer = EnvelopeWriter::new(BufWriter::new(&file), kind, opts)?;
+    bincode::serialize_into(&mut writer, value).map_err(|e| bincode_io_error(*e))?;
+    writer.finish()?.flush()?;
+    file.sync_data()?;
+    fs::rename(&tmp, path)
+}
@@ -0,0 +1,373 @@
+//! Detached Ed25519 signatures for engram artifacts.
+//!
+//! A signature covers the SHA-256 digest of the on-disk engram bytes (after
+//! any envelope wrapping) together with the SHA-256 digest of the manifest
+//! JSON. It is stored next to the engram as a small JSON document
+//! (`root.engram.sig` by convention), so signed and unsigned engrams remain
+//! byte-identical and older readers are unaffected.
+//!
+//! A signature only says who signed when it is checked against a key the
+//! reader trusts, so [`VerifyMode::Enforce`] requires one. [`read_checked`]
+//! returns the bytes it verified, for loading without reading the files a
+//! second time.
+//!
+//! Signing and verification require the `signing` feature. Without it, the
+//! signature format types are still available but sign/verify calls return an
+//! error, mirroring how missing compression codecs are reported.
+
+use crate::error::EmbrError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Domain-separation prefix for the signed message.
+const SIGNING_CONTEXT: &[u8] = b"embeddenator-detached-sig-v1";
+
+/// Current detached signature document version.
+pub const SIGNATURE_VERSION: u32 = 1;
+
+/// How signature verification failures are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerifyMode {
+    /// Do not check signatures.
+    #[default]
+    Off,
+    /// Check signatures, but only emit a warning on failure.
+    Warn,
+    /// Check signatures against a trusted public key and fail the operation
+    /// on any mismatch.
+    Enforce,
+}
+
+/// Detached signature over an engram and its manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    pub version: u32,
+    pub algorithm: String,
+    /// Hex-encoded Ed25519 public key of the signer.
+    pub public_key: String,
+    /// Hex-encoded SHA-256 of the engram file bytes.
+    pub engram_digest: String,
+    /// Hex-encoded SHA-256 of the manifest file bytes.
+    pub manifest_digest: String,
+    /// Hex-encoded Ed25519 signature.
+    pub signature: String,
+}
+
+impl DetachedSignature {
+    /// Save the signature document as pretty JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Load a signature document from JSON.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let sig = serde_json::from_reader(file)?;
+        Ok(sig)
+    }
+}
+
+/// Conventional location of the detached signature for an engram file.
+pub fn default_signature_path<P: AsRef<Path>>(engram_path: P) -> PathBuf {
+    let mut s = engram_path.as_ref().as_os_str().to_os_string();
+    s.push(".sig");
+    PathBuf::from(s)
+}
+
+/// SHA-256 digest of a file's full contents, read as a stream.
+pub fn file_digest<P: AsRef<Path>>(path: P) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+fn signed_message(engram_digest: &[u8; 32], manifest_digest: &[u8; 32]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(SIGNING_CONTEXT.len() + 64);
+    msg.extend_from_slice(SIGNING_CONTEXT);
+    msg.extend_from_slice(engram_digest);
+    msg.extend_from_slice(manifest_digest);
+    msg
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> io::Result<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "odd-length hex string"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid hex string"))
+        })
+        .collect()
+}
+
+fn from_hex_array<const N: usize>(s: &str, what: &str) -> io::Result<[u8; N]> {
+    from_hex(s)?.try_into().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{what} must be {N} bytes"),
+        )
+    })
+}
+
+/// Generate a new signing key, returning `(secret_hex, public_hex)`.
+///
+/// The secret is the 32-byte Ed25519 seed.
+pub fn generate_keypair() -> io::Result<(String, String)> {
+    imp::generate_keypair()
+}
+
+/// Sign an engram and manifest already written to disk.
+///
+/// `secret_hex` is the hex-encoded 32-byte Ed25519 seed.
+pub fn sign_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    engram_path: P,
+    manifest_path: Q,
+    secret_hex: &str,
+) -> io::Result<DetachedSignature> {
+    let engram_digest = file_digest(engram_path)?;
+    let manifest_digest = file_digest(manifest_path)?;
+    sign_digests(&engram_digest, &manifest_digest, secret_hex)
+}
+
+/// Sign precomputed engram and manifest digests.
+pub fn sign_digests(
+    engram_digest: &[u8; 32],
+    manifest_digest: &[u8; 32],
+    secret_hex: &str,
+) -> io::Result<DetachedSignature> {
+    imp::sign(engram_digest, manifest_digest, secret_hex)
+}
+
+/// Verify a detached signature against files on disk.
+///
+/// If `trusted_public_hex` is provided, the signature's embedded public key
+/// must match it; otherwise only self-consistency is checked.
+pub fn verify_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    engram_path: P,
+    manifest_path: Q,
+    sig: &DetachedSignature,
+    trusted_public_hex: Option<&str>,
+) -> io::Result<()> {
+    let engram_digest = file_digest(engram_path)?;
+    let manifest_digest = file_digest(manifest_path)?;
+    verify_digests(&engram_digest, &manifest_digest, sig, trusted_public_hex)
+}
+
+/// Verify a detached signature against precomputed digests.
+pub fn verify_digests(
+    engram_digest: &[u8; 32],
+    manifest_digest: &[u8; 32],
+    sig: &DetachedSignature,
+    trusted_public_hex: Option<&str>,
+) -> io::Result<()> {
+    if sig.version != SIGNATURE_VERSION || sig.algorithm != "ed25519" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "unsupported signature (version {}, algorithm {})",
+                sig.version, sig.algorithm
+            ),
+        ));
+    }
+
+    if let Some(trusted) = trusted_public_hex {
+        if !trusted.trim().eq_ignore_ascii_case(&sig.public_key) {
+            return Err(invalid("signature was made by an untrusted key"));
+        }
+    }
+
+    if from_hex_array::<32>(&sig.engram_digest, "engram digest")? != *engram_digest {
+        return Err(invalid("engram digest does not match signature"));
+    }
+    if from_hex_array::<32>(&sig.manifest_digest, "manifest digest")? != *manifest_digest {
+        return Err(invalid("manifest digest does not match signature"));
+    }
+
+    imp::verify(&signed_message(engram_digest, manifest_digest), sig)
+}
+
+/// Verify according to `mode`: `Enforce` propagates failures and needs
+/// `trusted_public_hex`, `Warn` logs them.
+pub fn check_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    engram_path: P,
+    manifest_path: Q,
+    sig: &DetachedSignature,
+    trusted_public_hex: Option<&str>,
+    mode: VerifyMode,
+) -> io::Result<()> {
+    if mode == VerifyMode::Off {
+        return Ok(());
+    }
+    require_trusted(trusted_public_hex, mode)?;
+    settle(verify_files(engram_path, manifest_path, sig, trusted_public_hex), mode)
+}
+
+/// Read the engram and manifest and check them as [`check_files`] does,
+/// returning the bytes that were checked. Loading from them rather than
+/// from the paths leaves no window for the files to change in between.
+pub fn read_checked<P: AsRef<Path>, Q: AsRef<Path>>(
+    engram_path: P,
+    manifest_path: Q,
+    sig: &DetachedSignature,
+    trusted_public_hex: Option<&str>,
+    mode: VerifyMode,
+) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let engram = fs::read(engram_path)?;
+    let manifest = fs::read(manifest_path)?;
+    if mode != VerifyMode::Off {
+        require_trusted(trusted_public_hex, mode)?;
+        let (engram_digest, manifest_digest) = (Sha256::digest(&engram).into(), Sha256::digest(&manifest).into());
+        settle(verify_digests(&engram_digest, &manifest_digest, sig, trusted_public_hex), mode)?;
+    }
+    Ok((engram, manifest))
+}
+
+/// Without a trusted key any re-signed engram verifies, so `Enforce`
+/// refuses to go on and `Warn` says the signer was not checked.
+fn require_trusted(trusted_public_hex: Option<&str>, mode: VerifyMode) -> io::Result<()> {
+    match (trusted_public_hex, mode) {
+        (Some(_), _) | (None, VerifyMode::Off) => Ok(()),
+        (None, VerifyMode::Warn) => {
+            crate::logging::warn("WARNING: no trusted key given; the signer is not checked");
+            Ok(())
+        }
+        (None, VerifyMode::Enforce) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "enforcing a signature needs a trusted public key",
+        )),
+    }
+}
+
+fn settle(result: io::Result<()>, mode: VerifyMode) -> io::Result<()> {
+    match result {
+        Err(e) if mode == VerifyMode::Warn => {
+            crate::logging::warn(&format!("WARNING: signature check failed: {e}"));
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    EmbrError::Crypto(msg.to_string()).into()
+}
+
+#[cfg(feature = "signing")]
+mod imp {
+    use super::*;
+    use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+    pub(super) fn generate_keypair() -> io::Result<(String, String)> {
+        use rand::RngCore;
+        let mut seed = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut seed);
+        let key = SigningKey::from_bytes(&seed);
+        Ok((to_hex(&seed), to_hex(key.verifying_key().as_bytes())))
+    }
+
+    pub(super) fn sign(
+        engram_digest: &[u8; 32],
+        manifest_digest: &[u8; 32],
+        secret_hex: &str,
+    ) -> io::Result<DetachedSignature> {
+        let seed: [u8; 32] = from_hex_array(secret_hex, "signing key")?;
+        let key = SigningKey::from_bytes(&seed);
+        let sig = key.sign(&signed_message(engram_digest, manifest_digest));
+        Ok(DetachedSignature {
+            version: SIGNATURE_VERSION,
+            algorithm: "ed25519".to_string(),
+            public_key: to_hex(key.verifying_key().as_bytes()),
+            engram_digest: to_hex(engram_digest),
+            manifest_digest: to_hex(manifest_digest),
+            signature: to_hex(&sig.to_bytes()),
+        })
+    }
+
+    pub(super) fn verify(message: &[u8], sig: &DetachedSignature) -> io::Result<()> {
+        let public: [u8; 32] = from_hex_array(&sig.public_key, "public key")?;
+        let key = VerifyingKey::from_bytes(&public).map_err(|_| invalid("malformed public key"))?;
+        let raw: [u8; 64] = from_hex_array(&sig.signature, "signature")?;
+        key.verify_strict(message, &Signature::from_bytes(&raw))
+            .map_err(|_| invalid("signature verification failed"))
+    }
+}
+
+#[cfg(not(feature = "signing"))]
+mod imp {
+    use super::*;
+
+    fn signing_disabled() -> io::Error {
+        io::Error::other("signature support not enabled (enable feature `signing`)")
+    }
+
+    pub(super) fn generate_keypair() -> io::Result<(String, String)> {
+        Err(signing_disabled())
+    }
+
+    pub(super) fn sign(_: &[u8; 32], _: &[u8; 32], _: &str) -> io::Result<DetachedSignature> {
+        Err(signing_disabled())
+    }
+
+    pub(super) fn verify(_: &[u8], _: &DetachedSignature) -> io::Result<()> {
+        Err(signing_disabled())
+    }
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify_roundtrip() {
+        let (secret, public) = generate_keypair().unwrap();
+        let engram = [1u8; 32];
+        let manifest = [2u8; 32];
+        let sig = sign_digests(&engram, &manifest, &secret).unwrap();
+        assert_eq!(sig.public_key, public);
+        verify_digests(&engram, &manifest, &sig, Some(&public)).unwrap();
+    }
+
+    #[test]
+    fn tampered_digest_or_untrusted_key_is_rejected() {
+        let (secret, _) = generate_keypair().unwrap();
+        let (_, other_public) = generate_keypair().unwrap();
+        let engram = [1u8; 32];
+        let manifest = [2u8; 32];
+        let sig = sign_digests(&engram, &manifest, &secret).unwrap();
+
+        assert!(verify_digests(&[3u8; 32], &manifest, &sig, None).is_err());
+        assert!(verify_digests(&engram, &manifest, &sig, Some(&other_public)).is_err());
+
+        let mut forged = sig.clone();
+        forged.engram_digest = to_hex(&[3u8; 32]);
+        assert!(verify_digests(&[3u8; 32], &manifest, &forged, None).is_err());
+    }
+
+    #[test]
+    fn enforce_needs_a_trusted_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let (engram, manifest) = (dir.path().join("root.engram"), dir.path().join("manifest.json"));
+        fs::write(&engram, b"engram bytes").unwrap();
+        fs::write(&manifest, b"{}").unwrap();
+        let (secret, public) = generate_keypair().unwrap();
+        let sig = sign_files(&engram, &manifest, &secret).unwrap();
+
+        let checked = read_checked(&engram, &manifest, &sig, Some(&public), VerifyMode::Enforce).unwrap();
+        assert_eq!(checked, (b"engram bytes".to_vec(), b"{}".to_vec()));
+        let err = check_files(&engram, &manifest, &sig, None, VerifyMode::Enforce).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        check_files(&engram, &manifest, &sig, None, VerifyMode::Warn).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+//! Entropy-coded storage for sets of sparse ternary vectors.
+//!
+//! A chunk signature is two ascending lists of indices below the vector
+//! dimension: where it is `+1` and where it is `-1`. Stored as plain
+//! integers each index costs 8 bytes, and as varint gaps about one to two;
+//! its information content is closer to `2 + log2(dim / n)` bits for a list
+//! of `n` indices. This module reaches that bound with Elias–Fano coding:
+//! each index is split into `l = floor(log2(dim / n))` low bits, stored
+//! verbatim, and the remaining high bits, stored as the unary gaps between
+//! consecutive values. Decoding is a single forward pass with no tables.
+//!
+//! [`encode_map`] writes a whole id → vector map: [`MAGIC`], the dimension,
+//! the ascending chunk ids (themselves an Elias–Fano list, nearly free for
+//! the dense ids an engram assigns), then each vector's `pos` and `neg`
+//! lists. The magic lets loaders tell the format from the older encodings
+//! they still read; see [`is_coded`].
+
+use crate::vsa::SparseVec;
+use std::collections::HashMap;
+use std::io;
+
+/// First bytes of every [`encode_map`] output.
+pub const MAGIC: [u8; 4] = *b"TEF1";
+
+/// Whether `bytes` start like [`encode_map`] output.
+pub fn is_coded(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Append the strictly ascending `values`, all below `universe`.
+///
+/// # Panics
+///
+/// If `values` is not strictly ascending or reaches `universe`.
+pub fn encode_indices(values: &[usize], universe: usize, out: &mut Vec<u8>) {
+    put_varint(out, values.len() as u64);
+    let n = values.len();
+    if n == 0 {
+        return;
+    }
+    assert!(values.windows(2).all(|w| w[0] < w[1]), "indices must be strictly ascending");
+    assert!(values[n - 1] < universe, "index {} out of range {}", values[n - 1], universe);
+    let l = low_bits(universe, n);
+
+    let mut bits = BitWriter::new(out);
+    for &v in values {
+        bits.put(v as u64, l);
+    }
+    bits.flush();
+    let mut prev_high = 0;
+    for &v in values {
+        let high = v >> l;
+        for _ in prev_high..high {
+            bits.put(0, 1);
+        }
+        bits.put(1, 1);
+        prev_high = high;
+    }
+    bits.flush();
+}
+
+/// Read a list written by [`encode_indices`] from the front of `input`,
+/// rejecting lists that are not strictly ascending and below `universe`.
+pub fn decode_indices(input: &mut &[u8], universe: usize) -> io::Result<Vec<usize>> {
+    let n = take_varint(input)?;
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    // Every value costs at least one bit of the high part.
+    if n > universe as u64 || n > (input.len() as u64).saturating_mul(8) {
+        return Err(invalid(format!("list claims {} indices", n)));
+    }
+    let n = n as usize;
+    let l = low_bits(universe, n);
+
+    let mut bits = BitReader::new(input);
+    let mut values = Vec::with_capacity(n);
+    for _ in 0..n {
+        values.push(bits.take(l)? as usize);
+    }
+    bits.align();
+    let mut high = 0usize;
+    for value in values.iter_mut() {
+        while bits.take(1)? == 0 {
+            high += 1;
+            if high > universe >> l {
+                return Err(invalid("index out of range"));
+            }
+        }
+        *value |= high << l;
+        if *value >= universe {
+            return Err(invalid("index out of range"));
+        }
+    }
+    bits.align();
+    if values.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(invalid("indices are not strictly ascending"));
+    }
+    Ok(values)
+}
+
+/// Append one vector: its `pos` list, then its `neg` list.
+pub fn encode_vector(vec: &SparseVec, dim: usize, out: &mut Vec<u8>) {
+    encode_indices(&vec.pos, dim, out);
+    encode_indices(&vec.neg, dim, out);
+}
+
+/// Read a vector written by [`encode_vector`]. An index may be in both
+/// lists, as in the bundled vectors [`SparseVec::validate`] accepts.
+pub fn decode_vector(input: &mut &[u8], dim: usize) -> io::Result<SparseVec> {
+    let pos = decode_indices(input, dim)?;
+    let neg = decode_indices(input, dim)?;
+    Ok(SparseVec { pos, neg })
+}
+
+/// Coded bytes of `vectors` (see the module docs).
+pub fn encode_map(vectors: &HashMap<usize, SparseVec>, dim: usize) -> Vec<u8> {
+    let mut ids: Vec<usize> = vectors.keys().copied().collect();
+    ids.sort_unstable();
+    let mut out = MAGIC.to_vec();
+    put_varint(&mut out, dim as u64);
+    let universe = ids.last().map_or(0, |&id| id + 1);
+    put_varint(&mut out, universe as u64);
+    encode_indices(&ids, universe, &mut out);
+    for id in &ids {
+        encode_vector(&vectors[id], dim, &mut out);
+    }
+    out
+}
+
+/// Decode [`encode_map`] output, calling `visit` with each id and vector
+/// in ascending id order; returns the dimension the vectors were written
+/// with. Lets a loader build its own structure (an inverted index, say)
+/// without holding the map.
+pub fn decode_each<F>(bytes: &[u8], mut visit: F) -> io::Result<usize>
+where
+    F: FnMut(usize, SparseVec) -> io::Result<()>,
+{
+    let mut input = bytes
+        .strip_prefix(&MAGIC)
+        .ok_or_else(|| invalid("not entropy-coded vectors"))?;
+    let dim = usize::try_from(take_varint(&mut input)?).map_err(|_| invalid("dimension too large"))?;
+    let universe = usize::try_from(take_varint(&mut input)?).map_err(|_| invalid("chunk id too large"))?;
+    let ids = decode_indices(&mut input, universe)?;
+    for id in ids {
+        visit(id, decode_vector(&mut input, dim)?)?;
+    }
+    if !input.is_empty() {
+        return Err(invalid("trailing bytes after the last vector"));
+    }
+    Ok(dim)
+}
+
+/// Decode [`encode_map`] output into its dimension and map.
+pub fn decode_map(bytes: &[u8]) -> io::Result<(usize, HashMap<usize, SparseVec>)> {
+    let mut vectors = HashMap::new();
+    let dim = decode_each(bytes, |id, vec| {
+        vectors.insert(id, vec);
+        Ok(())
+    })?;
+    Ok((dim, vectors))
+}
+
+/// Low bits kept verbatim for `n` values below `universe`.
+fn low_bits(universe: usize, n: usize) -> u32 {
+    if universe > n {
+        (universe / n).ilog2()
+    } else {
+        0
+    }
+}
+
+pub(crate) fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+pub(crate) fn take_varint(input: &mut &[u8]) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or_else(|| invalid("vector data ends early"))?;
+        *input = rest;
+        v |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(invalid("varint longer than 64 bits"))
+}
+
+/// Least significant bit first.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    acc: u64,
+    len: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        Self { out, acc: 0, len: 0 }
+    }
+
+    fn put(&mut self, value: u64, bits: u32) {
+        for i in 0..bits {
+            self.acc |= ((value >> i) & 1) << self.len;
+            self.len += 1;
+            if self.len == 8 {
+                self.out.push(self.acc as u8);
+                self.acc = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Pad to a whole byte.
+    fn flush(&mut self) {
+        if self.len > 0 {
+            self.out.push(self.acc as u8);
+            self.acc = 0;
+            self.len = 0;
+        }
+    }
+}
+
+struct BitReader<'a, 'b> {
+    input: &'a mut &'b [u8],
+    /// Bits of `input[0]` already read.
+    used: u32,
+}
+
+impl<'a, 'b> BitReader<'a, 'b> {
+    fn new(input: &'a mut &'b [u8]) -> Self {
+        Self { input, used: 0 }
+    }
+
+    fn take(&mut self, bits: u32) -> io::Result<u64> {
+        let mut value = 0;
+        for i in 0..bits {
+            let &byte = self.input.first().ok_or_else(|| invalid("vector data ends early"))?;
+            value |= u64::from((byte >> self.used) & 1) << i;
+            self.used += 1;
+            if self.used == 8 {
+                self.align();
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skip the padding of a started byte.
+    fn align(&mut self) {
+        if self.used > 0 {
+            *self.input = &self.input[1..];
+            self.used = 0;
+        }
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
@@ -0,0 +1,430 @@
+//! Crash-safe replacement of an engram and the files saved alongside it.
+//!
+//! [`EmbrFS::save_engram`](crate::EmbrFS::save_engram) truncates and rewrites
+//! the file in place, so a crash midway leaves a torn envelope, and an engram
+//! saved next to its manifest can end up paired with the other one's old
+//! version. A [`Transaction`] replaces a group of files so that every state
+//! left on disk is either all old or all new:
+//!
+//! 1. Each new file is written to a staging file beside its target
+//!    (`.name.txn`) and synced. Its path is logged to the write-ahead log
+//!    (`<primary>.wal`) and synced before the staging file is created.
+//! 2. A commit record listing every staging file with its blake3 digest is
+//!    appended to the log and synced. This is the commit point.
+//! 3. Each staging file is renamed over its target, the directories are
+//!    synced and the log is removed.
+//!
+//! A crash before step 2 leaves the targets untouched and a log without a
+//! commit record; a crash during step 3 leaves a committed log. Either is
+//! settled by [`recover`], which discards the staged files or finishes the
+//! renames. [`Transaction::begin`] and
+//! [`EmbrFS::load_engram`](crate::EmbrFS::load_engram) run it first.
+//!
+//! # Log layout
+//!
+//! ```text
+//! "EDNW" version:u16 reserved:u16
+//! record*    -- kind:u8 len:u32 payload digest:u64
+//! ```
+//!
+//! A stage record holds a staging path and the commit record the list of
+//! `{target, staged, blake3}`, both as JSON; the digest is XXH3-64 over kind,
+//! length and payload. A torn or mismatching record ends the log.
+//!
+//! Writers exclude each other through an OS lock on `<primary>.lock`, which
+//! is left in place. Readers take no lock: one racing step 3 of a live
+//! commit can still see some targets replaced and others not.
+
+use crate::seal;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::xxh3_64;
+
+const MAGIC: [u8; 4] = *b"EDNW";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 8;
+const RECORD_HEADER_LEN: usize = 5;
+const RECORD_DIGEST_LEN: usize = 8;
+const KIND_STAGE: u8 = 1;
+const KIND_COMMIT: u8 = 2;
+/// Largest record a reader will allocate for; guards against corrupt lengths.
+const MAX_RECORD_LEN: u32 = 1 << 20;
+
+/// A staged file named in a commit record.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CommitEntry {
+    target: PathBuf,
+    staged: PathBuf,
+    blake3: String,
+}
+
+/// What a committed [`Transaction`] replaced.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxnReport {
+    /// Targets replaced, in staging order.
+    pub files: Vec<PathBuf>,
+    /// Bytes written across the staged files.
+    pub bytes: u64,
+}
+
+/// What [`recover`] found for a primary path.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recovery {
+    /// No transaction was pending.
+    Clean,
+    /// An uncommitted transaction was discarded; the targets kept their old
+    /// contents.
+    RolledBack { discarded: usize },
+    /// A committed transaction was finished; the targets hold the new
+    /// contents.
+    RolledForward { applied: usize },
+}
+
+/// A group of file replacements committed atomically; see the
+/// [module docs](self).
+///
+/// Dropping a transaction without calling [`Transaction::commit`] discards
+/// what was staged.
+pub struct Transaction {
+    wal_path: PathBuf,
+    wal: Option<File>,
+    /// Every staging path logged so far, including failed ones.
+    staged: Vec<PathBuf>,
+    entries: Vec<CommitEntry>,
+    bytes: u64,
+    /// Held until the transaction ends; the OS lock goes with the handle.
+    _lock: File,
+}
+
+impl Transaction {
+    /// Start a transaction logged beside `primary`, usually the engram path.
+    ///
+    /// Blocks while another writer holds the same primary, then settles any
+    /// transaction a crashed writer left behind.
+    pub fn begin<P: AsRef<Path>>(primary: P) -> io::Result<Self> {
+        let primary = absolute(primary.as_ref())?;
+        let lock = open_lock(&primary)?;
+        lock.lock()?;
+        let wal_path = sibling(&primary, "", ".wal");
+        settle(&wal_path)?;
+        Ok(Self {
+            wal_path,
+            wal: None,
+            staged: Vec::new(),
+            entries: Vec::new(),
+            bytes: 0,
+            _lock: lock,
+        })
+    }
+
+    /// Stage the new contents of `target`, written by `write`. The target is
+    /// untouched until [`Transaction::commit`]. Fails if `target` is
+    /// [sealed](crate::seal).
+    pub fn stage<P, F>(&mut self, target: P, write: F) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&mut dyn Write) -> io::Result<()>,
+    {
+        let target = absolute(target.as_ref())?;
+        seal::ensure_unsealed(&target)?;
+        if self.entries.iter().any(|e| e.target == target) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is already staged in this transaction", target.display()),
+            ));
+        }
+        let staged = sibling(&target, ".", ".txn");
+        self.log(KIND_STAGE, &serde_json::to_vec(&staged)?)?;
+        self.staged.push(staged.clone());
+
+        let file = File::create(&staged)?;
+        let mut out = HashingWriter {
+            inner: BufWriter::new(&file),
+            hasher: blake3::Hasher::new(),
+            len: 0,
+        };
+        write(&mut out)?;
+        out.inner.flush()?;
+        let (digest, len) = (out.hasher.finalize(), out.len);
+        drop(out);
+        file.sync_all()?;
+
+        self.bytes += len;
+        self.entries.push(CommitEntry {
+            target,
+            staged,
+            blake3: digest.to_hex().to_string(),
+        });
+        Ok(())
+    }
+
+    /// Make the staged files durable as one unit, then move them over their
+    /// targets.
+    ///
+    /// Once the commit record is synced the transaction counts as done: if
+    /// replacing a target then fails, the error is returned and the next
+    /// [`recover`] finishes the job.
+    pub fn commit(mut self) -> io::Result<TxnReport> {
+        let entries = std::mem::take(&mut self.entries);
+        if entries.is_empty() {
+            return Ok(TxnReport::default());
+        }
+        let record = serde_json::to_vec(&entries)?;
+        self.log(KIND_COMMIT, &record)?;
+        self.wal = None;
+        apply(&self.wal_path, &entries)?;
+        Ok(TxnReport {
+            files: entries.into_iter().map(|e| e.target).collect(),
+            bytes: self.bytes,
+        })
+    }
+
+    /// Append one record to the log, creating it first if needed, and sync.
+    fn log(&mut self, kind: u8, payload: &[u8]) -> io::Result<()> {
+        if self.wal.is_none() {
+            let mut wal = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&self.wal_path)?;
+            wal.write_all(&MAGIC)?;
+            wal.write_all(&VERSION.to_le_bytes())?;
+            wal.write_all(&[0u8; 2])?;
+            sync_parent(&self.wal_path)?;
+            self.wal = Some(wal);
+        }
+        let wal = self.wal.as_mut().expect("opened above");
+        wal.write_all(&encode_record(kind, payload)?)?;
+        wal.sync_data()
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        // Uncommitted: best effort, since a later recover() discards the
+        // same files.
+        if self.wal.take().is_some() {
+            for staged in &self.staged {
+                let _ = fs::remove_file(staged);
+            }
+            let _ = fs::remove_file(&self.wal_path);
+        }
+    }
+}
+
+/// Settle a transaction left pending beside `primary` by a crashed writer,
+/// waiting for any live writer to finish first.
+pub fn recover<P: AsRef<Path>>(primary: P) -> io::Result<Recovery> {
+    let primary = primary.as_ref();
+    let wal_path = sibling(primary, "", ".wal");
+    if !wal_path.exists() {
+        return Ok(Recovery::Clean);
+    }
+    let lock = open_lock(primary)?;
+    lock.lock()?;
+    settle(&wal_path)
+}
+
+/// [`recover`] for readers: does nothing if no log exists or a writer
+/// currently holds the primary.
+pub(crate) fn recover_if_idle(primary: &Path) -> io::Result<Recovery> {
+    let wal_path = sibling(primary, "", ".wal");
+    if !wal_path.exists() {
+        return Ok(Recovery::Clean);
+    }
+    let lock = open_lock(primary)?;
+    match lock.try_lock() {
+        Ok(()) => settle(&wal_path),
+        Err(fs::TryLockError::WouldBlock) => Ok(Recovery::Clean),
+        Err(fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Roll the log at `wal_path` forward or back. The caller holds the lock.
+fn settle(wal_path: &Path) -> io::Result<Recovery> {
+    let data = match fs::read(wal_path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Recovery::Clean),
+        Err(e) => return Err(e),
+    };
+    if data.len() >= HEADER_LEN && data[..4] == MAGIC {
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: unsupported write-ahead log version {}",
+                    wal_path.display(),
+                    version
+                ),
+            ));
+        }
+    }
+
+    let mut staged = Vec::new();
+    let mut pos = HEADER_LEN;
+    while let Some((kind, payload, next)) = decode_record(&data, pos) {
+        match kind {
+            KIND_STAGE => staged.push(serde_json::from_slice::<PathBuf>(payload)?),
+            KIND_COMMIT => {
+                let entries: Vec<CommitEntry> = serde_json::from_slice(payload)?;
+                let applied = entries.len();
+                apply(wal_path, &entries)?;
+                return Ok(Recovery::RolledForward { applied });
+            }
+            _ => break,
+        }
+        pos = next;
+    }
+
+    for path in &staged {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    fs::remove_file(wal_path)?;
+    sync_parent(wal_path)?;
+    Ok(Recovery::RolledBack {
+        discarded: staged.len(),
+    })
+}
+
+/// Move committed staging files over their targets and retire the log.
+///
+/// Safe to repeat: a missing staging file is accepted once its target holds
+/// the committed digest.
+fn apply(wal_path: &Path, entries: &[CommitEntry]) -> io::Result<()> {
+    for entry in entries {
+        if entry.staged.exists() {
+            fs::rename(&entry.staged, &entry.target)?;
+            sync_parent(&entry.target)?;
+        } else if file_digest(&entry.target)? != entry.blake3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: committed file {} is missing and the target does not match it",
+                    wal_path.display(),
+                    entry.staged.display()
+                ),
+            ));
+        }
+    }
+    fs::remove_file(wal_path)?;
+    sync_parent(wal_path)
+}
+
+fn encode_record(kind: u8, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|&l| l <= MAX_RECORD_LEN)
+        .ok_or_else(|| io::Error::other("write-ahead log record too large"))?;
+    let mut out = Vec::with_capacity(RECORD_HEADER_LEN + payload.len() + RECORD_DIGEST_LEN);
+    out.push(kind);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(payload);
+    let digest = xxh3_64(&out);
+    out.extend_from_slice(&digest.to_le_bytes());
+    Ok(out)
+}
+
+/// The record at `pos` as (kind, payload, next position), or `None` if it is
+/// torn or fails its digest.
+fn decode_record(data: &[u8], pos: usize) -> Option<(u8, &[u8], usize)> {
+    let header = data.get(pos..pos + RECORD_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[1..5].try_into().ok()?);
+    if len > MAX_RECORD_LEN {
+        return None;
+    }
+    let body_end = pos + RECORD_HEADER_LEN + len as usize;
+    let digest = data.get(body_end..body_end + RECORD_DIGEST_LEN)?;
+    if xxh3_64(&data[pos..body_end]).to_le_bytes() != digest {
+        return None;
+    }
+    Some((
+        header[0],
+        &data[pos + RECORD_HEADER_LEN..body_end],
+        body_end + RECORD_DIGEST_LEN,
+    ))
+}
+
+/// `path` with `prefix` and `suffix` added around its file name.
+fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(prefix);
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// `path` made absolute, so logged paths survive a change of directory.
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    if path.file_name().is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} does not name a file", path.display()),
+        ));
+    }
+    std::path::absolute(path)
+}
+
+fn open_lock(primary: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(sibling(primary, "", ".lock"))
+}
+
+fn file_digest(path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            let mut buf = vec![0u8; 1 << 16];
+            loop {
+                let n = file.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(String::new()),
+        Err(e) => return Err(e),
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Sync the directory holding `path`, making a create, rename or removal in
+/// it durable. Directories can't be opened for syncing on Windows, where
+/// renames are already durable.
+fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: blake3::Hasher,
+    len: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
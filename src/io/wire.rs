@@ -0,0 +1,1142 @@
+//! Protobuf wire format for cross-language engram exchange.
+//!
+//! The bincode layouts used by engram files are Rust-specific. For services
+//! written in other languages, [`encode_engram`] and [`encode_manifest`]
+//! produce protobuf messages following the schema in
+//! `proto/embeddenator.proto` (also available as [`PROTO_SCHEMA`]), and the
+//! matching `decode_*` functions read them back. Any protobuf toolchain can
+//! generate bindings from that file.
+//!
+//! The Rust message types live in `pb` (with the `proto` feature) and
+//! mirror the schema field for field; `From`/`TryFrom` conversions connect
+//! them to the native types.
+//! Decoding validates what bincode would have taken on trust: vector
+//! indices must be strictly ascending, trits in range, and hashes 8 bytes.
+//!
+//! Unlike the bincode layouts, these messages survive schema changes: every
+//! field has a fixed tag, readers skip tags they do not know, and the
+//! `version` field is bumped only when an existing field changes meaning.
+//! Engram files can be stored this way too (`convert --to proto`, see
+//! [`EmbrFS::save_engram_proto`]); fixtures under `tests/fixtures/wire`
+//! pin the encoding across releases.
+//!
+//! [`EmbrFS::save_engram_proto`]: crate::EmbrFS::save_engram_proto
+//!
+//! Requires the `proto` feature; without it encoding and decoding return
+//! errors.
+
+use crate::embrfs::{Engram, Manifest};
+use crate::hybrid::HybridTritVec;
+use std::io;
+
+/// The protobuf schema the wire format follows.
+pub const PROTO_SCHEMA: &str = include_str!("../../proto/embeddenator.proto");
+
+/// Schema version written into `Engram` and `Manifest` messages.
+pub const WIRE_VERSION: u32 = 1;
+
+/// Encode an engram as an `embeddenator.v1.Engram` message.
+pub fn encode_engram(engram: &Engram) -> io::Result<Vec<u8>> {
+    imp::encode_engram(engram)
+}
+
+/// Decode an `embeddenator.v1.Engram` message.
+pub fn decode_engram(bytes: &[u8]) -> io::Result<Engram> {
+    imp::decode_engram(bytes)
+}
+
+/// Encode a manifest as an `embeddenator.v1.Manifest` message.
+pub fn encode_manifest(manifest: &Manifest) -> io::Result<Vec<u8>> {
+    imp::encode_manifest(manifest)
+}
+
+/// Decode an `embeddenator.v1.Manifest` message.
+pub fn decode_manifest(bytes: &[u8]) -> io::Result<Manifest> {
+    imp::decode_manifest(bytes)
+}
+
+/// Encode a vector of logical dimension `dim` as an `embeddenator.v1.HybridVec`
+/// message, keeping its representation.
+pub fn encode_hybrid(vec: &HybridTritVec, dim: usize) -> io::Result<Vec<u8>> {
+    imp::encode_hybrid(vec, dim)
+}
+
+/// Decode an `embeddenator.v1.HybridVec` message into the vector and its
+/// logical dimension.
+pub fn decode_hybrid(bytes: &[u8]) -> io::Result<(HybridTritVec, usize)> {
+    imp::decode_hybrid(bytes)
+}
+
+/// Message types for `embeddenator.v1`, kept in step with
+/// `proto/embeddenator.proto`.
+#[cfg(feature = "proto")]
+pub mod pb {
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SparseVec {
+        #[prost(uint64, repeated, tag = "1")]
+        pub pos: Vec<u64>,
+        #[prost(uint64, repeated, tag = "2")]
+        pub neg: Vec<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CodebookEntry {
+        #[prost(uint64, tag = "1")]
+        pub id: u64,
+        #[prost(message, optional, tag = "2")]
+        pub vec: Option<SparseVec>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BitFlip {
+        #[prost(uint64, tag = "1")]
+        pub position: u64,
+        #[prost(uint32, tag = "2")]
+        pub mask: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BitFlips {
+        #[prost(message, repeated, tag = "1")]
+        pub flips: Vec<BitFlip>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TritFlip {
+        #[prost(uint64, tag = "1")]
+        pub position: u64,
+        #[prost(sint32, tag = "2")]
+        pub was: i32,
+        #[prost(sint32, tag = "3")]
+        pub should_be: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TritFlips {
+        #[prost(message, repeated, tag = "1")]
+        pub flips: Vec<TritFlip>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BlockReplace {
+        #[prost(uint64, tag = "1")]
+        pub offset: u64,
+        #[prost(bytes = "vec", tag = "2")]
+        pub original: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum DeltaOpKind {
+        #[prost(message, tag = "1")]
+        Copy(DeltaCopy),
+        #[prost(bytes = "vec", tag = "2")]
+        Insert(Vec<u8>),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DeltaCopy {
+        #[prost(uint64, tag = "1")]
+        pub offset: u64,
+        #[prost(uint64, tag = "2")]
+        pub len: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DeltaOp {
+        #[prost(oneof = "DeltaOpKind", tags = "1, 2")]
+        pub op: Option<DeltaOpKind>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ChunkDelta {
+        #[prost(uint64, tag = "1")]
+        pub base: u64,
+        #[prost(string, tag = "2")]
+        pub base_path: String,
+        #[prost(uint64, tag = "3")]
+        pub base_len: u64,
+        #[prost(message, repeated, tag = "4")]
+        pub ops: Vec<DeltaOp>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Correction {
+        #[prost(message, tag = "4")]
+        BitFlips(BitFlips),
+        #[prost(message, tag = "5")]
+        TritFlips(TritFlips),
+        #[prost(message, tag = "6")]
+        BlockReplace(BlockReplace),
+        #[prost(bytes = "vec", tag = "7")]
+        Verbatim(Vec<u8>),
+        #[prost(message, tag = "8")]
+        Delta(ChunkDelta),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ChunkCorrection {
+        #[prost(uint64, tag = "1")]
+        pub chunk_id: u64,
+        #[prost(bytes = "vec", tag = "2")]
+        pub hash: Vec<u8>,
+        #[prost(sint32, tag = "3")]
+        pub parity: i32,
+        #[prost(oneof = "Correction", tags = "4, 5, 6, 7, 8")]
+        pub correction: Option<Correction>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CorrectionTotals {
+        #[prost(uint64, tag = "1")]
+        pub total_correction_bytes: u64,
+        #[prost(uint64, tag = "2")]
+        pub total_original_bytes: u64,
+        #[prost(uint64, tag = "3")]
+        pub perfect_chunks: u64,
+        #[prost(uint64, tag = "4")]
+        pub corrected_chunks: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Engram {
+        #[prost(uint32, tag = "1")]
+        pub version: u32,
+        #[prost(message, optional, tag = "2")]
+        pub root: Option<SparseVec>,
+        #[prost(message, repeated, tag = "3")]
+        pub codebook: Vec<CodebookEntry>,
+        #[prost(message, repeated, tag = "4")]
+        pub corrections: Vec<ChunkCorrection>,
+        #[prost(message, optional, tag = "5")]
+        pub totals: Option<CorrectionTotals>,
+        #[prost(message, optional, tag = "6")]
+        pub root_counts: Option<RootCounts>,
+        #[prost(message, optional, tag = "7")]
+        pub precision: Option<DepthMap>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DepthMap {
+        #[prost(btree_map = "uint64, uint32", tag = "1")]
+        pub dimensions: BTreeMap<u64, u32>,
+        #[prost(btree_map = "uint64, uint32", tag = "2")]
+        pub coefficients: BTreeMap<u64, u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RootCounts {
+        #[prost(uint64, tag = "1")]
+        pub len: u64,
+        #[prost(uint64, tag = "2")]
+        pub count: u64,
+        #[prost(message, repeated, tag = "3")]
+        pub pos: Vec<BitPlane>,
+        #[prost(message, repeated, tag = "4")]
+        pub neg: Vec<BitPlane>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BitPlane {
+        #[prost(fixed64, repeated, tag = "1")]
+        pub words: Vec<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FileEntry {
+        #[prost(string, tag = "1")]
+        pub path: String,
+        #[prost(bool, tag = "2")]
+        pub is_text: bool,
+        #[prost(uint64, tag = "3")]
+        pub size: u64,
+        #[prost(uint64, repeated, tag = "4")]
+        pub chunks: Vec<u64>,
+        #[prost(string, optional, tag = "5")]
+        pub blake3: Option<String>,
+        #[prost(uint64, optional, tag = "6")]
+        pub ingested_at: Option<u64>,
+        #[prost(message, optional, tag = "7")]
+        pub signature: Option<SparseVec>,
+        #[prost(message, optional, tag = "8")]
+        pub unix: Option<UnixMeta>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct UnixMeta {
+        #[prost(uint32, tag = "1")]
+        pub uid: u32,
+        #[prost(uint32, tag = "2")]
+        pub gid: u32,
+        #[prost(uint32, tag = "3")]
+        pub mode: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RetentionClass {
+        #[prost(uint64, optional, tag = "1")]
+        pub ttl: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RetentionRule {
+        #[prost(string, tag = "1")]
+        pub path: String,
+        #[prost(uint64, optional, tag = "2")]
+        pub ttl: Option<u64>,
+        #[prost(string, optional, tag = "3")]
+        pub class: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RetentionPolicy {
+        #[prost(btree_map = "string, message", tag = "1")]
+        pub classes: BTreeMap<String, RetentionClass>,
+        #[prost(message, repeated, tag = "2")]
+        pub rules: Vec<RetentionRule>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VsaConfig {
+        #[prost(uint64, tag = "1")]
+        pub block_size: u64,
+        #[prost(uint64, tag = "2")]
+        pub max_path_depth: u64,
+        #[prost(uint64, tag = "3")]
+        pub base_shift: u64,
+        #[prost(uint64, tag = "4")]
+        pub target_sparsity: u64,
+        /// Absent for the pairwise default.
+        #[prost(oneof = "RootStrategy", tags = "5, 6, 7")]
+        pub root_strategy: Option<RootStrategy>,
+        /// Absent for reversible chunk vectors.
+        #[prost(message, optional, tag = "8")]
+        pub similarity: Option<SimilarityEncoding>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SimilarityEncoding {
+        #[prost(uint64, tag = "1")]
+        pub shingle: u64,
+        #[prost(uint64, tag = "2")]
+        pub nonzero: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum RootStrategy {
+        #[prost(bool, tag = "5")]
+        CarrySave(bool),
+        #[prost(uint64, tag = "6")]
+        TreeFanIn(u64),
+        #[prost(uint64, tag = "7")]
+        ThinnedMaxNonzero(u64),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AdaptiveTritDepth {
+        #[prost(uint32, tag = "1")]
+        pub base_depth: u32,
+        #[prost(uint32, tag = "2")]
+        pub max_depth: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum TritDepth {
+        #[prost(uint32, tag = "2")]
+        Uniform(u32),
+        #[prost(bytes = "vec", tag = "3")]
+        Variable(Vec<u8>),
+        #[prost(message, tag = "4")]
+        Adaptive(AdaptiveTritDepth),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DimensionalConfig {
+        #[prost(uint64, tag = "1")]
+        pub num_dimensions: u64,
+        #[prost(oneof = "TritDepth", tags = "2, 3, 4")]
+        pub trit_depth: Option<TritDepth>,
+        #[prost(double, tag = "5")]
+        pub target_sparsity: f64,
+        #[prost(bool, tag = "6")]
+        pub adaptive_precision: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EncodingConfig {
+        #[prost(message, optional, tag = "1")]
+        pub vsa: Option<VsaConfig>,
+        #[prost(message, optional, tag = "2")]
+        pub dimensional: Option<DimensionalConfig>,
+        #[prost(uint64, tag = "3")]
+        pub chunk_size: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Manifest {
+        #[prost(uint32, tag = "1")]
+        pub version: u32,
+        #[prost(message, repeated, tag = "2")]
+        pub files: Vec<FileEntry>,
+        #[prost(uint64, tag = "3")]
+        pub total_chunks: u64,
+        #[prost(btree_map = "string, string", tag = "4")]
+        pub namespaces: BTreeMap<String, String>,
+        #[prost(message, optional, tag = "5")]
+        pub retention: Option<RetentionPolicy>,
+        #[prost(message, optional, tag = "6")]
+        pub encoding: Option<EncodingConfig>,
+        #[prost(message, optional, tag = "7")]
+        pub inodes: Option<InodeTable>,
+        #[prost(message, optional, tag = "8")]
+        pub path_normalization: Option<PathNormalization>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PathNormalization {
+        #[prost(uint32, tag = "1")]
+        pub unicode: u32,
+        #[prost(bool, tag = "2")]
+        pub lowercase: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InodeTable {
+        #[prost(uint64, tag = "1")]
+        pub next: u64,
+        #[prost(btree_map = "string, uint64", tag = "2")]
+        pub paths: BTreeMap<String, u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BitslicedVec {
+        #[prost(fixed64, repeated, tag = "1")]
+        pub pos: Vec<u64>,
+        #[prost(fixed64, repeated, tag = "2")]
+        pub neg: Vec<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TritBlock {
+        #[prost(uint32, tag = "1")]
+        pub id: u32,
+        #[prost(fixed64, tag = "2")]
+        pub pos: u64,
+        #[prost(fixed64, tag = "3")]
+        pub neg: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BlockSparseVec {
+        #[prost(message, repeated, tag = "1")]
+        pub blocks: Vec<TritBlock>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Repr {
+        #[prost(message, tag = "3")]
+        Sparse(SparseVec),
+        #[prost(message, tag = "4")]
+        Bitsliced(BitslicedVec),
+        #[prost(message, tag = "5")]
+        BlockSparse(BlockSparseVec),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct HybridVec {
+        #[prost(uint32, tag = "1")]
+        pub version: u32,
+        #[prost(uint64, tag = "2")]
+        pub dim: u64,
+        #[prost(oneof = "Repr", tags = "3, 4, 5")]
+        pub repr: Option<Repr>,
+    }
+}
+
+#[cfg(feature = "proto")]
+mod imp {
+    use super::*;
+    use crate::correction::{ChunkCorrection, CorrectionStore, CorrectionTotals, CorrectionType, DeltaOp};
+    use crate::dimensional::{DepthMap, DimensionalConfig, TritDepthConfig};
+    use crate::embrfs::{EncodingConfig, FileEntry, UnixMeta, DEFAULT_CHUNK_SIZE};
+    use crate::inodes::{InodeTable, FIRST_INODE};
+    use crate::path_norm::{PathNormalization, UnicodeForm};
+    use crate::retention::{RetentionClass, RetentionPolicy, RetentionRule};
+    use crate::bitsliced::{BitslicedTritVec, CountedBundle};
+    use crate::block_sparse::{Block, BlockSparseTritVec};
+    use crate::ternary::Trit;
+    use crate::vsa::{ChunkEncoding, ReversibleVSAConfig, RootStrategy, SparseVec};
+    use prost::Message;
+    use std::collections::{BTreeMap, HashMap};
+
+    fn invalid(msg: impl Into<String>) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg.into())
+    }
+
+    fn index(v: u64) -> io::Result<usize> {
+        usize::try_from(v).map_err(|_| invalid(format!("index {} does not fit in usize", v)))
+    }
+
+    fn trit(v: i32) -> io::Result<Trit> {
+        i8::try_from(v)
+            .ok()
+            .and_then(Trit::from_i8_exact)
+            .ok_or_else(|| invalid(format!("trit value {} is not -1, 0 or 1", v)))
+    }
+
+    fn ascending(indices: &[usize]) -> bool {
+        indices.windows(2).all(|w| w[0] < w[1])
+    }
+
+    impl From<&SparseVec> for pb::SparseVec {
+        fn from(v: &SparseVec) -> Self {
+            Self {
+                pos: v.pos.iter().map(|&i| i as u64).collect(),
+                neg: v.neg.iter().map(|&i| i as u64).collect(),
+            }
+        }
+    }
+
+    impl TryFrom<pb::SparseVec> for SparseVec {
+        type Error = io::Error;
+
+        fn try_from(v: pb::SparseVec) -> io::Result<Self> {
+            let pos = v.pos.into_iter().map(index).collect::<io::Result<Vec<_>>>()?;
+            let neg = v.neg.into_iter().map(index).collect::<io::Result<Vec<_>>>()?;
+            if !ascending(&pos) || !ascending(&neg) {
+                return Err(invalid("sparse vector indices must be strictly ascending"));
+            }
+            Ok(SparseVec { pos, neg })
+        }
+    }
+
+    impl From<&ChunkCorrection> for pb::ChunkCorrection {
+        fn from(c: &ChunkCorrection) -> Self {
+            let correction = match &c.correction {
+                CorrectionType::None => None,
+                CorrectionType::BitFlips(flips) => Some(pb::Correction::BitFlips(pb::BitFlips {
+                    flips: flips
+                        .iter()
+                        .map(|&(position, mask)| pb::BitFlip {
+                            position,
+                            mask: mask as u32,
+                        })
+                        .collect(),
+                })),
+                CorrectionType::TritFlips(flips) => Some(pb::Correction::TritFlips(pb::TritFlips {
+                    flips: flips
+                        .iter()
+                        .map(|&(position, was, should_be)| pb::TritFlip {
+                            position,
+                            was: was.to_i8() as i32,
+                            should_be: should_be.to_i8() as i32,
+                        })
+                        .collect(),
+                })),
+                CorrectionType::BlockReplace { offset, original } => {
+                    Some(pb::Correction::BlockReplace(pb::BlockReplace {
+                        offset: *offset,
+                        original: original.clone(),
+                    }))
+                }
+                CorrectionType::Verbatim(data) => Some(pb::Correction::Verbatim(data.clone())),
+                CorrectionType::Delta {
+                    base,
+                    base_path,
+                    base_len,
+                    ops,
+                } => Some(pb::Correction::Delta(pb::ChunkDelta {
+                    base: *base,
+                    base_path: base_path.clone(),
+                    base_len: *base_len,
+                    ops: ops
+                        .iter()
+                        .map(|op| pb::DeltaOp {
+                            op: Some(match op {
+                                DeltaOp::Copy { offset, len } => pb::DeltaOpKind::Copy(pb::DeltaCopy {
+                                    offset: *offset,
+                                    len: *len,
+                                }),
+                                DeltaOp::Insert(bytes) => pb::DeltaOpKind::Insert(bytes.clone()),
+                            }),
+                        })
+                        .collect(),
+                })),
+            };
+            Self {
+                chunk_id: c.chunk_id,
+                hash: c.hash.to_vec(),
+                parity: c.parity.to_i8() as i32,
+                correction,
+            }
+        }
+    }
+
+    impl TryFrom<pb::ChunkCorrection> for ChunkCorrection {
+        type Error = io::Error;
+
+        fn try_from(c: pb::ChunkCorrection) -> io::Result<Self> {
+            let hash: [u8; 8] = c.hash.as_slice().try_into().map_err(|_| {
+                invalid(format!("chunk {}: correction hash is {} bytes, expected 8", c.chunk_id, c.hash.len()))
+            })?;
+            let correction = match c.correction {
+                None => CorrectionType::None,
+                Some(pb::Correction::BitFlips(b)) => CorrectionType::BitFlips(
+                    b.flips
+                        .into_iter()
+                        .map(|f| {
+                            let mask = u8::try_from(f.mask)
+                                .map_err(|_| invalid(format!("bit flip mask {} exceeds one byte", f.mask)))?;
+                            Ok((f.position, mask))
+                        })
+                        .collect::<io::Result<_>>()?,
+                ),
+                Some(pb::Correction::TritFlips(t)) => CorrectionType::TritFlips(
+                    t.flips
+                        .into_iter()
+                        .map(|f| Ok((f.position, trit(f.was)?, trit(f.should_be)?)))
+                        .collect::<io::Result<_>>()?,
+                ),
+                Some(pb::Correction::BlockReplace(b)) => CorrectionType::BlockReplace {
+                    offset: b.offset,
+                    original: b.original,
+                },
+                Some(pb::Correction::Verbatim(data)) => CorrectionType::Verbatim(data),
+                Some(pb::Correction::Delta(d)) => CorrectionType::Delta {
+                    base: d.base,
+                    base_path: d.base_path,
+                    base_len: d.base_len,
+                    ops: d
+                        .ops
+                        .into_iter()
+                        .map(|op| match op.op {
+                            Some(pb::DeltaOpKind::Copy(c)) => Ok(DeltaOp::Copy {
+                                offset: c.offset,
+                                len: c.len,
+                            }),
+                            Some(pb::DeltaOpKind::Insert(bytes)) => Ok(DeltaOp::Insert(bytes)),
+                            None => Err(invalid(format!("chunk {}: delta op has no kind", c.chunk_id))),
+                        })
+                        .collect::<io::Result<_>>()?,
+                },
+            };
+            Ok(ChunkCorrection {
+                chunk_id: c.chunk_id,
+                correction,
+                hash,
+                parity: trit(c.parity)?,
+            })
+        }
+    }
+
+    impl From<&Engram> for pb::Engram {
+        fn from(engram: &Engram) -> Self {
+            let mut ids: Vec<usize> = engram.codebook.keys().copied().collect();
+            ids.sort_unstable();
+            let mut corrections: Vec<pb::ChunkCorrection> =
+                engram.corrections.iter().map(|(_, c)| c.into()).collect();
+            corrections.sort_unstable_by_key(|c| c.chunk_id);
+            let totals = engram.corrections.totals();
+            Self {
+                version: WIRE_VERSION,
+                root: Some((&engram.root).into()),
+                codebook: ids
+                    .into_iter()
+                    .map(|id| pb::CodebookEntry {
+                        id: id as u64,
+                        vec: Some((&engram.codebook[&id]).into()),
+                    })
+                    .collect(),
+                corrections,
+                totals: Some(pb::CorrectionTotals {
+                    total_correction_bytes: totals.total_correction_bytes,
+                    total_original_bytes: totals.total_original_bytes,
+                    perfect_chunks: totals.perfect_chunks,
+                    corrected_chunks: totals.corrected_chunks,
+                }),
+                root_counts: engram.root_counts.as_ref().map(|counts| {
+                    let (pos, neg) = counts.planes();
+                    let planes = |planes: &[Vec<u64>]| {
+                        planes.iter().map(|words| pb::BitPlane { words: words.clone() }).collect()
+                    };
+                    pb::RootCounts {
+                        len: counts.len() as u64,
+                        count: counts.count(),
+                        pos: planes(pos),
+                        neg: planes(neg),
+                    }
+                }),
+                precision: engram.precision.as_ref().map(|depths| {
+                    let map = |depths: &BTreeMap<usize, u8>| {
+                        depths.iter().map(|(&key, &depth)| (key as u64, depth as u32)).collect()
+                    };
+                    pb::DepthMap {
+                        dimensions: map(&depths.dimensions),
+                        coefficients: map(&depths.coefficients),
+                    }
+                }),
+            }
+        }
+    }
+
+    fn check_version(what: &str, version: u32) -> io::Result<()> {
+        if version != WIRE_VERSION {
+            return Err(io::Error::other(format!("unsupported {} wire version {}", what, version)));
+        }
+        Ok(())
+    }
+
+    impl TryFrom<pb::Engram> for Engram {
+        type Error = io::Error;
+
+        fn try_from(msg: pb::Engram) -> io::Result<Self> {
+            check_version("engram", msg.version)?;
+            let root = msg.root.ok_or_else(|| invalid("engram message has no root vector"))?;
+            let mut codebook = HashMap::with_capacity(msg.codebook.len());
+            for entry in msg.codebook {
+                let id = index(entry.id)?;
+                let vec = entry
+                    .vec
+                    .ok_or_else(|| invalid(format!("codebook entry {} has no vector", id)))?;
+                if codebook.insert(id, vec.try_into()?).is_some() {
+                    return Err(invalid(format!("duplicate codebook entry {}", id)));
+                }
+            }
+            let mut corrections = HashMap::with_capacity(msg.corrections.len());
+            for c in msg.corrections {
+                let c = ChunkCorrection::try_from(c)?;
+                let id = c.chunk_id;
+                if corrections.insert(id, c).is_some() {
+                    return Err(invalid(format!("duplicate correction for chunk {}", id)));
+                }
+            }
+            let totals = msg.totals.unwrap_or_default();
+            let totals = CorrectionTotals {
+                total_correction_bytes: totals.total_correction_bytes,
+                total_original_bytes: totals.total_original_bytes,
+                perfect_chunks: totals.perfect_chunks,
+                corrected_chunks: totals.corrected_chunks,
+            };
+            let root_counts = match msg.root_counts {
+                Some(counts) => {
+                    let planes = |planes: Vec<pb::BitPlane>| planes.into_iter().map(|p| p.words).collect();
+                    let counts =
+                        CountedBundle::from_planes(index(counts.len)?, counts.count, planes(counts.pos), planes(counts.neg));
+                    counts.validate().map_err(|e| invalid(format!("engram root counts: {}", e)))?;
+                    Some(counts)
+                }
+                None => None,
+            };
+            let precision = match msg.precision {
+                Some(depths) => {
+                    let map = |depths: BTreeMap<u64, u32>| {
+                        depths
+                            .into_iter()
+                            .map(|(key, depth)| {
+                                let depth = u8::try_from(depth)
+                                    .map_err(|_| invalid(format!("trit depth {} out of range", depth)))?;
+                                Ok((index(key)?, depth))
+                            })
+                            .collect::<io::Result<_>>()
+                    };
+                    Some(DepthMap {
+                        dimensions: map(depths.dimensions)?,
+                        coefficients: map(depths.coefficients)?,
+                    })
+                }
+                None => None,
+            };
+            Ok(Engram {
+                root: root.try_into()?,
+                codebook,
+                corrections: CorrectionStore::from_parts(corrections, totals),
+                root_counts,
+                precision,
+                differential: None,
+                schema: None,
+            })
+        }
+    }
+
+    impl From<&FileEntry> for pb::FileEntry {
+        fn from(f: &FileEntry) -> Self {
+            Self {
+                path: f.path.clone(),
+                is_text: f.is_text,
+                size: f.size as u64,
+                chunks: f.chunks.iter().map(|&c| c as u64).collect(),
+                blake3: f.blake3.clone(),
+                ingested_at: f.ingested_at,
+                signature: f.signature.as_ref().map(Into::into),
+                unix: f.unix.map(|u| pb::UnixMeta {
+                    uid: u.uid,
+                    gid: u.gid,
+                    mode: u.mode,
+                }),
+            }
+        }
+    }
+
+    impl TryFrom<pb::FileEntry> for FileEntry {
+        type Error = io::Error;
+
+        fn try_from(f: pb::FileEntry) -> io::Result<Self> {
+            Ok(FileEntry {
+                size: index(f.size)?,
+                chunks: f.chunks.into_iter().map(index).collect::<io::Result<_>>()?,
+                path: f.path,
+                is_text: f.is_text,
+                blake3: f.blake3,
+                ingested_at: f.ingested_at,
+                signature: f.signature.map(TryInto::try_into).transpose()?,
+                unix: f.unix.map(|u| UnixMeta {
+                    uid: u.uid,
+                    gid: u.gid,
+                    mode: u.mode,
+                }),
+            })
+        }
+    }
+
+    fn depth(v: u32) -> io::Result<u8> {
+        u8::try_from(v).map_err(|_| invalid(format!("trit depth {} does not fit in u8", v)))
+    }
+
+    impl From<&EncodingConfig> for pb::EncodingConfig {
+        fn from(e: &EncodingConfig) -> Self {
+            let (vsa, dim) = (&e.vsa, &e.dimensional);
+            Self {
+                vsa: Some(pb::VsaConfig {
+                    block_size: vsa.block_size as u64,
+                    max_path_depth: vsa.max_path_depth as u64,
+                    base_shift: vsa.base_shift as u64,
+                    target_sparsity: vsa.target_sparsity as u64,
+                    root_strategy: match vsa.root_strategy {
+                        RootStrategy::Pairwise => None,
+                        RootStrategy::CarrySave => Some(pb::RootStrategy::CarrySave(true)),
+                        RootStrategy::Tree { fan_in } => Some(pb::RootStrategy::TreeFanIn(fan_in as u64)),
+                        RootStrategy::Thinned { max_nonzero } => {
+                            Some(pb::RootStrategy::ThinnedMaxNonzero(max_nonzero as u64))
+                        }
+                    },
+                    similarity: match vsa.chunk_encoding {
+                        ChunkEncoding::Reversible => None,
+                        ChunkEncoding::Similarity { shingle, nonzero } => Some(pb::SimilarityEncoding {
+                            shingle: shingle as u64,
+                            nonzero: nonzero as u64,
+                        }),
+                    },
+                }),
+                dimensional: Some(pb::DimensionalConfig {
+                    num_dimensions: dim.num_dimensions as u64,
+                    trit_depth: Some(match &dim.trit_depth {
+                        TritDepthConfig::Uniform(d) => pb::TritDepth::Uniform((*d).into()),
+                        TritDepthConfig::Variable(depths) => pb::TritDepth::Variable(depths.clone()),
+                        TritDepthConfig::Adaptive { base_depth, max_depth } => {
+                            pb::TritDepth::Adaptive(pb::AdaptiveTritDepth {
+                                base_depth: (*base_depth).into(),
+                                max_depth: (*max_depth).into(),
+                            })
+                        }
+                    }),
+                    target_sparsity: dim.target_sparsity,
+                    adaptive_precision: dim.adaptive_precision,
+                }),
+                chunk_size: e.chunk_size as u64,
+            }
+        }
+    }
+
+    impl TryFrom<pb::EncodingConfig> for EncodingConfig {
+        type Error = io::Error;
+
+        fn try_from(e: pb::EncodingConfig) -> io::Result<Self> {
+            let vsa = e.vsa.ok_or_else(|| invalid("encoding config without vsa settings"))?;
+            let dim = e
+                .dimensional
+                .ok_or_else(|| invalid("encoding config without dimensional settings"))?;
+            let trit_depth = match dim.trit_depth {
+                Some(pb::TritDepth::Uniform(d)) => TritDepthConfig::Uniform(depth(d)?),
+                Some(pb::TritDepth::Variable(depths)) => TritDepthConfig::Variable(depths),
+                Some(pb::TritDepth::Adaptive(a)) => TritDepthConfig::Adaptive {
+                    base_depth: depth(a.base_depth)?,
+                    max_depth: depth(a.max_depth)?,
+                },
+                None => return Err(invalid("dimensional config without a trit depth")),
+            };
+            let root_strategy = match vsa.root_strategy {
+                None | Some(pb::RootStrategy::CarrySave(false)) => RootStrategy::Pairwise,
+                Some(pb::RootStrategy::CarrySave(true)) => RootStrategy::CarrySave,
+                Some(pb::RootStrategy::TreeFanIn(n)) => RootStrategy::Tree { fan_in: index(n)? },
+                Some(pb::RootStrategy::ThinnedMaxNonzero(n)) => RootStrategy::Thinned { max_nonzero: index(n)? },
+            };
+            let chunk_encoding = match vsa.similarity {
+                None => ChunkEncoding::Reversible,
+                Some(s) => ChunkEncoding::Similarity {
+                    shingle: index(s.shingle)?,
+                    nonzero: index(s.nonzero)?,
+                },
+            };
+            let encoding = EncodingConfig {
+                vsa: ReversibleVSAConfig {
+                    block_size: index(vsa.block_size)?,
+                    max_path_depth: index(vsa.max_path_depth)?,
+                    base_shift: index(vsa.base_shift)?,
+                    target_sparsity: index(vsa.target_sparsity)?,
+                    root_strategy,
+                    chunk_encoding,
+                },
+                dimensional: DimensionalConfig {
+                    num_dimensions: index(dim.num_dimensions)?,
+                    trit_depth,
+                    target_sparsity: dim.target_sparsity,
+                    adaptive_precision: dim.adaptive_precision,
+                },
+                chunk_size: match e.chunk_size {
+                    0 => DEFAULT_CHUNK_SIZE,
+                    n => index(n)?,
+                },
+            };
+            encoding.validate().map_err(|e| invalid(e.to_string()))?;
+            Ok(encoding)
+        }
+    }
+
+    impl From<&Manifest> for pb::Manifest {
+        fn from(m: &Manifest) -> Self {
+            Self {
+                version: WIRE_VERSION,
+                files: m.files.iter().map(Into::into).collect(),
+                total_chunks: m.total_chunks as u64,
+                namespaces: m.namespaces.clone(),
+                retention: m.retention.as_ref().map(|r| pb::RetentionPolicy {
+                    classes: r
+                        .classes
+                        .iter()
+                        .map(|(name, c)| (name.clone(), pb::RetentionClass { ttl: c.ttl }))
+                        .collect(),
+                    rules: r
+                        .rules
+                        .iter()
+                        .map(|rule| pb::RetentionRule {
+                            path: rule.path.clone(),
+                            ttl: rule.ttl,
+                            class: rule.class.clone(),
+                        })
+                        .collect(),
+                }),
+                encoding: m.encoding.as_ref().map(Into::into),
+                inodes: (!m.inodes.is_empty()).then(|| pb::InodeTable {
+                    next: m.inodes.next,
+                    paths: m.inodes.paths.clone(),
+                }),
+                path_normalization: (!m.path_normalization.is_identity()).then_some(pb::PathNormalization {
+                    unicode: match m.path_normalization.unicode {
+                        UnicodeForm::AsIs => 0,
+                        UnicodeForm::Nfc => 1,
+                        UnicodeForm::Nfd => 2,
+                    },
+                    lowercase: m.path_normalization.lowercase,
+                }),
+            }
+        }
+    }
+
+    impl TryFrom<pb::Manifest> for Manifest {
+        type Error = io::Error;
+
+        fn try_from(m: pb::Manifest) -> io::Result<Self> {
+            check_version("manifest", m.version)?;
+            Ok(Manifest {
+                files: m.files.into_iter().map(TryInto::try_into).collect::<io::Result<_>>()?,
+                total_chunks: index(m.total_chunks)?,
+                namespaces: m.namespaces,
+                retention: m.retention.map(|r| RetentionPolicy {
+                    classes: r
+                        .classes
+                        .into_iter()
+                        .map(|(name, c)| (name, RetentionClass { ttl: c.ttl }))
+                        .collect(),
+                    rules: r
+                        .rules
+                        .into_iter()
+                        .map(|rule| RetentionRule {
+                            path: rule.path,
+                            ttl: rule.ttl,
+                            class: rule.class,
+                        })
+                        .collect(),
+                }),
+                encoding: m.encoding.map(TryInto::try_into).transpose()?,
+                inodes: m.inodes.map(TryInto::try_into).transpose()?.unwrap_or_default(),
+                path_normalization: m.path_normalization.map(TryInto::try_into).transpose()?.unwrap_or_default(),
+                // The wire schema has no provenance or key sections.
+                provenance: None,
+                namespace_keys: None,
+            })
+        }
+    }
+
+    impl TryFrom<pb::PathNormalization> for PathNormalization {
+        type Error = io::Error;
+
+        fn try_from(n: pb::PathNormalization) -> io::Result<Self> {
+            let unicode = match n.unicode {
+                0 => UnicodeForm::AsIs,
+                1 => UnicodeForm::Nfc,
+                2 => UnicodeForm::Nfd,
+                other => return Err(invalid(format!("unknown unicode normalization form {}", other))),
+            };
+            Ok(PathNormalization {
+                unicode,
+                lowercase: n.lowercase,
+            })
+        }
+    }
+
+    impl TryFrom<pb::InodeTable> for InodeTable {
+        type Error = io::Error;
+
+        fn try_from(t: pb::InodeTable) -> io::Result<Self> {
+            if t.paths.values().any(|&ino| ino < FIRST_INODE || ino >= t.next) {
+                return Err(invalid("inode number outside the table's range"));
+            }
+            Ok(InodeTable { next: t.next, paths: t.paths })
+        }
+    }
+
+    fn decode_error(err: prost::DecodeError) -> io::Error {
+        invalid(format!("malformed protobuf message: {}", err))
+    }
+
+    pub(super) fn encode_engram(engram: &Engram) -> io::Result<Vec<u8>> {
+        Ok(pb::Engram::from(engram).encode_to_vec())
+    }
+
+    pub(super) fn decode_engram(bytes: &[u8]) -> io::Result<Engram> {
+        pb::Engram::decode(bytes).map_err(decode_error)?.try_into()
+    }
+
+    pub(super) fn encode_manifest(manifest: &Manifest) -> io::Result<Vec<u8>> {
+        Ok(pb::Manifest::from(manifest).encode_to_vec())
+    }
+
+    pub(super) fn decode_manifest(bytes: &[u8]) -> io::Result<Manifest> {
+        pb::Manifest::decode(bytes).map_err(decode_error)?.try_into()
+    }
+
+    pub(super) fn encode_hybrid(vec: &HybridTritVec, dim: usize) -> io::Result<Vec<u8>> {
+        let repr = match vec {
+            HybridTritVec::Sparse(v) => pb::Repr::Sparse(v.into()),
+            HybridTritVec::Bitsliced(v) => {
+                if v.len() != dim {
+                    return Err(invalid(format!("bitsliced vector has {} trits, not {}", v.len(), dim)));
+                }
+                pb::Repr::Bitsliced(pb::BitslicedVec {
+                    pos: v.pos_plane().to_vec(),
+                    neg: v.neg_plane().to_vec(),
+                })
+            }
+            HybridTritVec::BlockSparse(v) => {
+                if v.dim() != dim {
+                    return Err(invalid(format!("block-sparse vector has dimension {}, not {}", v.dim(), dim)));
+                }
+                pb::Repr::BlockSparse(pb::BlockSparseVec {
+                    blocks: v
+                        .blocks()
+                        .iter()
+                        .map(|&(id, block)| pb::TritBlock { id, pos: block.pos, neg: block.neg })
+                        .collect(),
+                })
+            }
+        };
+        let msg = pb::HybridVec {
+            version: WIRE_VERSION,
+            dim: dim as u64,
+            repr: Some(repr),
+        };
+        Ok(msg.encode_to_vec())
+    }
+
+    pub(super) fn decode_hybrid(bytes: &[u8]) -> io::Result<(HybridTritVec, usize)> {
+        let msg = pb::HybridVec::decode(bytes).map_err(decode_error)?;
+        check_version("hybrid vector", msg.version)?;
+        let dim = index(msg.dim)?;
+        let vec = match msg.repr.ok_or_else(|| invalid("hybrid vector message has no representation"))? {
+            pb::Repr::Sparse(v) => {
+                let v = SparseVec::try_from(v)?;
+                if v.pos.iter().chain(&v.neg).any(|&i| i >= dim) {
+                    return Err(invalid(format!("sparse vector index out of range for dimension {}", dim)));
+                }
+                HybridTritVec::Sparse(v)
+            }
+            pb::Repr::Bitsliced(v) => {
+                let words = BitslicedTritVec::word_count(dim);
+                if v.pos.len() != words || v.neg.len() != words {
+                    return Err(invalid(format!(
+                        "bitsliced planes hold {} and {} words, dimension {} needs {}",
+                        v.pos.len(),
+                        v.neg.len(),
+                        dim,
+                        words
+                    )));
+                }
+                let tail = if dim % 64 == 0 { 0 } else { !0u64 << (dim % 64) };
+                for (i, (&p, &n)) in v.pos.iter().zip(&v.neg).enumerate() {
+                    if p & n != 0 {
+                        return Err(invalid(format!("bitsliced word {} sets a trit to both +1 and -1", i)));
+                    }
+                    if i + 1 == words && (p | n) & tail != 0 {
+                        return Err(invalid(format!("bitsliced vector sets trits past dimension {}", dim)));
+                    }
+                }
+                HybridTritVec::Bitsliced(BitslicedTritVec::from_raw(dim, v.pos, v.neg))
+            }
+            pb::Repr::BlockSparse(v) => {
+                let blocks = v
+                    .blocks
+                    .into_iter()
+                    .map(|b| (b.id, Block { pos: b.pos, neg: b.neg }))
+                    .collect();
+                let v = BlockSparseTritVec::from_blocks(dim, blocks)
+                    .map_err(|e| invalid(format!("block-sparse vector: {}", e)))?;
+                HybridTritVec::BlockSparse(v)
+            }
+        };
+        Ok((vec, dim))
+    }
+}
+
+#[cfg(not(feature = "proto"))]
+mod imp {
+    use super::*;
+
+    fn proto_disabled() -> io::Error {
+        io::Error::other("protobuf wire format not enabled (enable feature `proto`)")
+    }
+
+    pub(super) fn encode_engram(_: &Engram) -> io::Result<Vec<u8>> {
+        Err(proto_disabled())
+    }
+
+    pub(super) fn decode_engram(_: &[u8]) -> io::Result<Engram> {
+        Err(proto_disabled())
+    }
+
+    pub(super) fn encode_manifest(_: &Manifest) -> io::Result<Vec<u8>> {
+        Err(proto_disabled())
+    }
+
+    pub(super) fn decode_manifest(_: &[u8]) -> io::Result<Manifest> {
+        Err(proto_disabled())
+    }
+
+    pub(super) fn encode_hybrid(_: &HybridTritVec, _: usize) -> io::Result<Vec<u8>> {
+        Err(proto_disabled())
+    }
+
+    pub(super) fn decode_hybrid(_: &[u8]) -> io::Result<(HybridTritVec, usize)> {
+        Err(proto_disabled())
+    }
+}
@@ -55,103 +55,568 @@
 //! - [`vsa`]: Vector Symbolic Architecture implementation
 //! - [`embrfs`]: Holographic filesystem layer
 //! - [`cli`]: Command-line interface
+//!
+//! # WebAssembly
+//!
+//! The library builds for `wasm32-unknown-unknown` with default features.
+//! Without a filesystem, read engrams and manifests from memory with
+//! [`EmbrFS::engram_from_bytes`] and [`EmbrFS::manifest_from_bytes`], and
+//! rebuild files with [`EmbrFS::reconstruct_bytes`]. FUSE, io_uring and the
+//! server features are not available there.
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature the crate is `no_std` + `alloc` and
+//! keeps only the vector types: [`SparseVec`], [`BitslicedTritVec`],
+//! [`BlockSparseTritVec`], [`PackedTritVec`] and the [`ternary`] primitives.
+//! SIMD kernels are then chosen at compile time rather than detected at run
+//! time, and random vectors need a caller-supplied seed.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
 #[path = "cli/mod.rs"]
 pub mod cli;
 
+#[cfg(feature = "std")]
 #[path = "core/codebook.rs"]
 pub mod codebook;
 
+#[cfg(feature = "std")]
 #[path = "core/correction.rs"]
 pub mod correction;
 
+#[cfg(feature = "std")]
+#[path = "core/batch_projection.rs"]
+pub mod batch_projection;
+
+#[cfg(feature = "std")]
+#[path = "core/error.rs"]
+pub mod error;
+
+#[cfg(feature = "std")]
 #[path = "vsa/dimensional.rs"]
 pub mod dimensional;
 
+#[cfg(feature = "std")]
 #[path = "io/envelope.rs"]
 pub mod envelope;
 
+#[cfg(feature = "std")]
+#[path = "io/signing.rs"]
+pub mod signing;
+
+#[cfg(feature = "std")]
+#[path = "io/append_log.rs"]
+pub mod append_log;
+
+#[cfg(feature = "std")]
+#[path = "io/lazy_envelope.rs"]
+pub mod lazy_envelope;
+
+#[cfg(feature = "std")]
+#[path = "io/lazy_engram.rs"]
+pub mod lazy_engram;
+
+#[cfg(feature = "std")]
+#[path = "io/multipart.rs"]
+pub mod multipart;
+
+#[cfg(feature = "std")]
+#[path = "io/shards.rs"]
+pub mod shards;
+#[cfg(feature = "std")]
+#[path = "io/distributed.rs"]
+pub mod distributed;
+#[cfg(feature = "std")]
+#[path = "io/replica.rs"]
+pub mod replica;
+#[cfg(feature = "std")]
+#[path = "io/txn.rs"]
+pub mod txn;
+#[cfg(feature = "std")]
+#[path = "io/seal.rs"]
+pub mod seal;
+#[cfg(feature = "std")]
+#[path = "io/namespace_keys.rs"]
+pub mod namespace_keys;
+#[cfg(feature = "std")]
+#[path = "io/remote_sync.rs"]
+pub mod remote_sync;
+
+#[cfg(feature = "std")]
+#[path = "io/shared_codebook.rs"]
+pub mod shared_codebook;
+
+#[cfg(feature = "std")]
+#[path = "io/rkyv_engram.rs"]
+pub mod rkyv_engram;
+
+#[cfg(feature = "std")]
+#[path = "io/delta.rs"]
+pub mod delta;
+#[cfg(feature = "std")]
+#[path = "io/generations.rs"]
+pub mod generations;
+
+#[cfg(feature = "std")]
+#[path = "io/export.rs"]
+pub mod export;
+
+#[cfg(feature = "std")]
+#[path = "io/dense_export.rs"]
+pub mod dense_export;
+#[cfg(feature = "std")]
+#[path = "io/quantized_export.rs"]
+pub mod quantized_export;
+#[cfg(feature = "std")]
+#[path = "io/trit_coding.rs"]
+pub mod trit_coding;
+
+#[cfg(feature = "std")]
+#[path = "io/wire.rs"]
+pub mod wire;
+
+#[cfg(feature = "std")]
+#[path = "io/convert.rs"]
+pub mod convert;
+
+#[cfg(feature = "std")]
+#[path = "io/bulk_io.rs"]
+pub mod bulk_io;
+
+#[cfg(feature = "async")]
+#[path = "io/async_io.rs"]
+pub mod async_io;
+
+#[cfg(feature = "std")]
 #[path = "fs/embrfs.rs"]
 pub mod embrfs;
 
+#[cfg(feature = "std")]
+#[path = "fs/ingest_stats.rs"]
+pub mod ingest_stats;
+
+#[cfg(feature = "std")]
+#[path = "fs/ingest_filter.rs"]
+pub mod ingest_filter;
+#[cfg(feature = "std")]
+#[path = "fs/ingest_queue.rs"]
+pub mod ingest_queue;
+
+#[cfg(feature = "std")]
+#[path = "fs/retention.rs"]
+pub mod retention;
+#[cfg(feature = "std")]
+#[path = "fs/job.rs"]
+pub mod job;
+#[cfg(feature = "std")]
+#[path = "fs/reproducible.rs"]
+pub mod reproducible;
+#[cfg(feature = "std")]
+#[path = "fs/ingest_provenance.rs"]
+pub mod ingest_provenance;
+#[cfg(feature = "std")]
+#[path = "fs/differential.rs"]
+pub mod differential;
+#[cfg(feature = "std")]
+#[path = "fs/role_schema.rs"]
+pub mod role_schema;
+
+#[cfg(feature = "std")]
+#[path = "fs/chunk_refs.rs"]
+pub mod chunk_refs;
+#[cfg(feature = "std")]
+#[path = "fs/merge.rs"]
+pub mod merge;
+#[cfg(feature = "std")]
+#[path = "fs/reader.rs"]
+pub mod reader;
+#[cfg(feature = "std")]
+#[path = "fs/access.rs"]
+pub mod access;
+#[cfg(feature = "std")]
+#[path = "fs/scrub.rs"]
+pub mod scrub;
+#[cfg(feature = "std")]
+#[path = "fs/buffer_pool.rs"]
+pub mod buffer_pool;
+
+#[cfg(feature = "std")]
+#[path = "fs/stream_ingest.rs"]
+pub mod stream_ingest;
+
+#[cfg(feature = "std")]
+#[path = "fs/listing.rs"]
+pub mod listing;
+
+#[cfg(feature = "std")]
 #[path = "fs/fuse_shim.rs"]
 pub mod fuse_shim;
+#[cfg(feature = "std")]
+#[path = "fs/vfs.rs"]
+pub mod vfs;
+#[cfg(feature = "std")]
+#[path = "fs/inodes.rs"]
+pub mod inodes;
+#[cfg(feature = "std")]
+#[path = "fs/manifest_schema.rs"]
+pub mod manifest_schema;
+#[cfg(feature = "std")]
+#[path = "fs/safe_path.rs"]
+pub mod safe_path;
+#[cfg(feature = "std")]
+#[path = "fs/path_norm.rs"]
+pub mod path_norm;
 
+#[cfg(feature = "std")]
 #[path = "interop/kernel_interop.rs"]
 pub mod kernel_interop;
 
+#[cfg(feature = "std")]
+#[path = "interop/git_archive.rs"]
+pub mod git_archive;
+
+#[cfg(feature = "lmdb")]
+#[path = "interop/lmdb_store.rs"]
+pub mod lmdb_store;
+
+#[cfg(feature = "rocksdb")]
+#[path = "interop/rocksdb_store.rs"]
+pub mod rocksdb_store;
+
+#[cfg(feature = "vector-sync")]
+#[path = "interop/vector_sync.rs"]
+pub mod vector_sync;
+
+#[cfg(feature = "onnx")]
+#[path = "interop/onnx_embed.rs"]
+pub mod onnx_embed;
+
+#[cfg(feature = "kafka")]
+#[path = "interop/kafka_source.rs"]
+pub mod kafka_source;
+
+#[cfg(feature = "s3")]
+#[path = "interop/object_fetch.rs"]
+pub mod object_fetch;
+
+#[cfg(feature = "grpc")]
+#[path = "interop/grpc.rs"]
+pub mod grpc;
+
+#[cfg(feature = "http")]
+#[path = "interop/http_api.rs"]
+pub mod http_api;
+
+#[cfg(feature = "nbd")]
+#[path = "interop/nbd.rs"]
+pub mod nbd;
+
+#[cfg(feature = "sqlite")]
+#[path = "interop/sqlite_vtab.rs"]
+pub mod sqlite_vtab;
+
+#[cfg(feature = "ffi")]
+#[path = "interop/ffi.rs"]
+pub mod ffi;
+
+#[cfg(feature = "std")]
 #[path = "obs/logging.rs"]
 pub mod logging;
 
+#[cfg(feature = "std")]
 #[path = "obs/metrics.rs"]
 pub mod metrics;
 
+#[cfg(feature = "std")]
 #[path = "obs/hires_timing.rs"]
 pub mod hires_timing;
 
+#[cfg(feature = "std")]
+#[path = "obs/latency.rs"]
+pub mod latency;
+
+#[cfg(feature = "std")]
+#[path = "obs/profile.rs"]
+pub mod profile;
+
+#[cfg(feature = "std")]
+#[path = "obs/access_stats.rs"]
+pub mod access_stats;
+
+#[cfg(feature = "std")]
+#[path = "obs/capacity.rs"]
+pub mod capacity;
+
+#[cfg(feature = "std")]
+#[path = "obs/provenance.rs"]
+pub mod provenance;
+
+#[cfg(feature = "std")]
+#[path = "obs/stream_monitor.rs"]
+pub mod stream_monitor;
+
+#[cfg(feature = "std")]
+#[path = "obs/bench.rs"]
+pub mod bench;
+
+#[cfg(feature = "std")]
+#[path = "obs/memory.rs"]
+pub mod memory;
+
+#[cfg(feature = "std")]
+#[path = "obs/info.rs"]
+pub mod info;
+
+#[cfg(feature = "std")]
+#[path = "obs/health.rs"]
+pub mod health;
+
+#[cfg(feature = "std")]
 #[path = "core/resonator.rs"]
 pub mod resonator;
 
+#[cfg(feature = "std")]
 #[path = "retrieval/retrieval.rs"]
 pub mod retrieval;
 
+#[cfg(feature = "std")]
 #[path = "retrieval/signature.rs"]
 pub mod signature;
 
+#[cfg(feature = "std")]
+#[path = "retrieval/block_prefilter.rs"]
+pub mod block_prefilter;
+
+#[cfg(feature = "std")]
+#[path = "retrieval/semantic.rs"]
+pub mod semantic;
+
+#[cfg(feature = "std")]
+#[path = "retrieval/chunk_vectors.rs"]
+pub mod chunk_vectors;
+
+#[cfg(feature = "std")]
 #[path = "vsa/simd_cosine.rs"]
 pub mod simd_cosine;
 
+#[cfg(feature = "std")]
+#[path = "vsa/simd.rs"]
+pub mod simd;
+
 #[path = "vsa/ternary.rs"]
 pub mod ternary;
 
 #[path = "vsa/ternary_vec.rs"]
 pub mod ternary_vec;
 
+#[cfg(feature = "std")]
+#[path = "vsa/word6_vec.rs"]
+pub mod word6_vec;
+
 #[path = "vsa/bitsliced.rs"]
 pub mod bitsliced;
 
 #[path = "vsa/block_sparse.rs"]
 pub mod block_sparse;
 
+#[cfg(feature = "std")]
 #[path = "vsa/hybrid.rs"]
 pub mod hybrid;
 
+#[cfg(feature = "std")]
 #[path = "vsa/soft_ternary.rs"]
 pub mod soft_ternary;
 
+#[cfg(feature = "std")]
+#[path = "vsa/projection.rs"]
+pub mod projection;
+
 #[path = "vsa/vsa.rs"]
 pub mod vsa;
 
-/// Testing utilities: metrics, integrity validation, chaos injection.
-#[cfg(test)]
+#[cfg(not(feature = "std"))]
+#[path = "vsa/float.rs"]
+mod float;
+
+/// Testing utilities: metrics, storage footprints, assertions.
+#[cfg(all(test, feature = "std"))]
 pub mod testing;
 
+/// Fault injection and integrity checks for testing recovery paths.
+#[cfg(all(any(test, feature = "chaos"), feature = "std"))]
+#[path = "testing/chaos.rs"]
+pub mod chaos;
+
 // Re-export main types for convenience
-pub use codebook::{Codebook, BalancedTernaryWord, ProjectionResult, SemanticOutlier, WordMetadata};
-pub use correction::{CorrectionStore, CorrectionStats, ChunkCorrection, CorrectionType, ReconstructionVerifier};
+#[cfg(feature = "std")]
+pub use append_log::{AppendLog, AppendStats, CompactStats};
+#[cfg(feature = "std")]
+pub use codebook::{
+    BalancedTernaryWord, BasisSimilarityDetector, BasisTrainingReport, ChunkCache, ChunkCacheStats, ChunkCluster,
+    ChunkClusters, Codebook, CodebookDiff, EntropyDetector, OutlierConfig, OutlierDetector, OutlierRule, OutlierWindow, ProjectionResult,
+    ResidualDetector, SemanticOutlier, WordMetadata,
+};
+#[cfg(feature = "std")]
+pub use batch_projection::ProjectionBackend;
+#[cfg(feature = "std")]
+pub use correction::{CorrectionStore, CorrectionStats, ChunkCorrection, CorrectionType, DeltaOp, ReconstructionVerifier};
+#[cfg(feature = "std")]
 pub use dimensional::{
     Trit as DimTrit, Tryte, DimensionalConfig, TritDepthConfig,
-    HyperVec, DifferentialEncoder, DifferentialEncoding,
+    HyperVec, DifferentialEncoder, DifferentialEncoding, DepthMap,
+};
+#[cfg(feature = "std")]
+pub use envelope::{
+    BinaryWriteOptions, ChecksumCodec, CompressionCodec, EncryptionCodec, EncryptionKey, EnvelopeHeader,
+    EnvelopeReader, EnvelopeWriter, Keyring, PayloadKind, SeekableEnvelope,
 };
-pub use envelope::{BinaryWriteOptions, CompressionCodec, PayloadKind};
-pub use embrfs::{EmbrFS, Engram, FileEntry, Manifest, DEFAULT_CHUNK_SIZE};
+#[cfg(feature = "std")]
+pub use embrfs::{
+    ChecksumMismatch, ChunkIndex, EmbrFS, EncodingConfig, Engram, EngramCorruption, FileEntry, FileMatch, FileSignatures,
+    IngestEstimate, IngestLimits, LoadLimits, Manifest, PrecisionReport, QuotaExceeded, QuotaKind, UnixMeta, VerifyReport, DEFAULT_CHUNK_SIZE,
+};
+#[cfg(feature = "std")]
 pub use embrfs::{
     DirectorySubEngramStore, HierarchicalChunkHit, HierarchicalManifest, HierarchicalQueryBounds,
-    SubEngram, SubEngramStore, UnifiedManifest, load_hierarchical_manifest,
+    SubEngram, SubEngramCorruption, SubEngramDigest, SubEngramFault, SubEngramStore, SubtreeDiff, TieredSubEngramStore, UnifiedManifest, diff_hierarchical_manifests, load_hierarchical_manifest,
     query_hierarchical_codebook, query_hierarchical_codebook_with_store, save_hierarchical_manifest,
-    save_sub_engrams_dir,
+    save_sub_engrams_dir, explain_hierarchical_query, explain_hierarchical_query_with_store, ChunkContribution,
+    ExplainedHit, HierarchicalExplanation, NodeOutcome, NodeTrace, HierarchicalQueryResults, QueryTruncation,
+    query_hierarchical_budgeted, query_hierarchical_budgeted_with_store,
+};
+#[cfg(feature = "std")]
+pub use capacity::{CapacityMonitor, CapacityReport, MembershipResult, MembershipVerdict};
+#[cfg(feature = "std")]
+pub use error::EmbrError;
+#[cfg(feature = "std")]
+pub use latency::{LatencyHistogram, LatencySnapshot};
+#[cfg(feature = "std")]
+pub use access_stats::{AccessCounts, AccessKind, AccessSnapshot, AccessStats, FileHeat};
+#[cfg(feature = "std")]
+pub use provenance::{ChunkProvenance, ChunkSource, ExtractReport, FileProvenance};
+#[cfg(feature = "std")]
+pub use stream_monitor::{DriftAlert, StreamMonitor, WindowReport};
+#[cfg(feature = "std")]
+pub use memory::MemoryUsage;
+#[cfg(feature = "std")]
+pub use info::{EngramInfo, StorageFormat};
+#[cfg(feature = "std")]
+pub use health::{CheckStatus, Health, HealthReport};
+#[cfg(feature = "std")]
+pub use lazy_envelope::Envelope;
+#[cfg(feature = "std")]
+pub use lazy_engram::LazyEngram;
+#[cfg(feature = "std")]
+pub use multipart::{PartIndex, PartReader, PartWriter};
+#[cfg(feature = "std")]
+pub use shards::{ShardEntry, ShardIndex, ShardedEngram};
+#[cfg(feature = "std")]
+pub use distributed::{DistributedEngram, DistributedHit, EngramNode, LocalNode, Placement};
+#[cfg(feature = "std")]
+pub use replica::{MerkleDiff, MerkleTree, RepairReport, SyncReport};
+#[cfg(feature = "std")]
+pub use txn::{Recovery, Transaction, TxnReport};
+#[cfg(feature = "std")]
+pub use seal::Seal;
+#[cfg(feature = "std")]
+pub use namespace_keys::{KeyGroup, KeyManifest, KeyProvider, KeyRule, OpenReport, RekeyReport};
+#[cfg(feature = "std")]
+pub use remote_sync::{SyncOptions, TransferReport};
+#[cfg(feature = "std")]
+pub use shared_codebook::{CodebookRef, GcReport, SharedCodebook, SharedSaveReport};
+#[cfg(feature = "std")]
+pub use rkyv_engram::RkyvEngram;
+#[cfg(feature = "std")]
+pub use delta::{EngramDelta, ManifestDelta};
+#[cfg(feature = "std")]
+pub use generations::{
+    DirectoryTier, GenerationLocation, GenerationPolicy, GenerationRecord, GenerationTier, GenerationalStore, TierReport,
+};
+#[cfg(feature = "std")]
+pub use convert::{ConvertOptions, ConvertReport, TargetFormat};
+#[cfg(feature = "std")]
+pub use ingest_stats::{HistogramBin, IngestStats, StatsBucket};
+#[cfg(feature = "std")]
+pub use ingest_filter::{
+    CompressedContent, Detection, ExtensionFilter, FilterAction, Finding, IngestFilter, IngestFilters, SecretScanner,
+    SizeLimit,
 };
-pub use fuse_shim::{EngramFS, EngramFSBuilder, FileAttr, FileKind};
+#[cfg(feature = "std")]
+pub use ingest_queue::{IngestPipeline, IngestQueue, QueueStats, Watermarks};
+#[cfg(feature = "std")]
+pub use chunk_refs::{ChunkRefs, ReclaimReport};
+#[cfg(feature = "std")]
+pub use reproducible::IngestClock;
+#[cfg(feature = "std")]
+pub use ingest_provenance::{Chunking, IngestProvenance, ProvenanceIssue};
+#[cfg(feature = "std")]
+pub use differential::{DifferentialChunk, DifferentialChunks, DifferentialReport};
+#[cfg(feature = "std")]
+pub use role_schema::{RoleBinding, RoleHit, RoleSchema, RoleValue};
+#[cfg(feature = "std")]
+pub use job::{CancellationToken, JobControl, JobProgress};
+#[cfg(feature = "std")]
+pub use retention::{PurgedFile, RetentionAudit, RetentionClass, RetentionPolicy, RetentionRule};
+#[cfg(feature = "std")]
+pub use merge::{MergeConflict, MergeReport};
+#[cfg(feature = "std")]
+pub use reader::{EngramReader, PrefetchReport};
+#[cfg(feature = "std")]
+pub use buffer_pool::{BufferPool, BufferPoolStats, PooledBuffer};
+#[cfg(feature = "std")]
+pub use access::{Access, AccessPolicy, Grants, Principal};
+#[cfg(feature = "std")]
+pub use fuse_shim::{DirectIo, EngramFS, EngramFSBuilder, FileAttr, FileKind, LayerPrecedence, PREFETCH_IOCTL};
+#[cfg(feature = "std")]
+pub use vfs::VirtualFs;
+#[cfg(feature = "std")]
+pub use inodes::InodeTable;
+#[cfg(feature = "std")]
+pub use manifest_schema::MANIFEST_SCHEMA_VERSION;
+#[cfg(feature = "std")]
+pub use safe_path::{PathPolicy, PathProblem, UnsafePath};
+#[cfg(feature = "std")]
+pub use path_norm::{PathCollision, PathNormalization, UnicodeForm};
+#[cfg(feature = "std")]
 pub use kernel_interop::{
-    CandidateGenerator, KernelInteropError, SparseVecBackend, VectorStore, VsaBackend,
+    CandidateGenerator, HybridBackend, HybridStoreView, KernelInteropError, ScannableVectorStore, SparseVecBackend,
+    VectorStore, VsaBackend,
     rerank_top_k_by_cosine,
 };
+#[cfg(feature = "std")]
 pub use resonator::Resonator;
+#[cfg(feature = "std")]
+pub use signing::{DetachedSignature, VerifyMode};
+#[cfg(feature = "std")]
 pub use retrieval::{RerankedResult, SearchResult, TernaryInvertedIndex};
+#[cfg(feature = "std")]
+pub use chunk_vectors::ChunkVectors;
+#[cfg(feature = "std")]
+pub use block_prefilter::{BlockPrefilterIndex, BlockPrefilterOptions, PrefilteredStore};
+#[cfg(feature = "std")]
+pub use retrieval::query_cache::{QueryCache, QueryCacheStats, QueryKey};
+#[cfg(feature = "std")]
+pub use retrieval::federation::{FederatedHit, FederatedIndex, ScoreNormalization};
+#[cfg(feature = "std")]
+pub use retrieval::align::{AlignOptions, Alignment};
+#[cfg(feature = "std")]
+pub use retrieval::eval::{EvalOptions, EvalReport, QuerySet};
+#[cfg(feature = "std")]
+pub use retrieval::similar::{find_similar, RegionMatch, SimilarFile, SimilarOptions};
 pub use ternary::{Trit, Tryte3, Word6, ParityTrit, CorrectionEntry};
 pub use ternary_vec::PackedTritVec;
-pub use bitsliced::{BitslicedTritVec, CarrySaveBundle, has_avx512, has_avx2, simd_features_string};
+#[cfg(feature = "std")]
+pub use word6_vec::Word6Vec;
+pub use bitsliced::{BitslicedTritVec, CarrySaveBundle, CountedBundle, has_avx512, has_avx2, simd_features_string};
 pub use block_sparse::{Block, BlockSparseTritVec, BlockError};
-pub use hybrid::{HybridTritVec, DENSITY_THRESHOLD, MIN_BITSLICED_DIM};
+#[cfg(feature = "std")]
+pub use hybrid::{HybridRepr, HybridThresholds, HybridTritVec, DENSITY_THRESHOLD, MIN_BITSLICED_DIM};
+#[cfg(feature = "std")]
 pub use soft_ternary::SoftTernaryVec;
-pub use vsa::{SparseVec, ReversibleVSAConfig, DIM};
+pub use vsa::{SparseVec, SparseVecError, ReversibleVSAConfig, RootStrategy, ChunkEncoding, TreeBundle, DIM};
+#[cfg(feature = "std")]
+pub use vsa::matrix::TritMatrix;
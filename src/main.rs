@@ -1,11 +1,6 @@
 use embeddenator::cli;
-use embeddenator::logging;
 use std::process;
 
 fn main() {
-    logging::init();
-    if let Err(e) = cli::run() {
-        eprintln!("Error: {}", e);
-        process::exit(1);
-    }
+    process::exit(cli::run_and_report());
 }
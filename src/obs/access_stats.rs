@@ -0,0 +1,260 @@
+//! Which chunks and files get read, and how long that takes.
+//!
+//! An [`AccessStats`] counts accesses per chunk id and per logical path,
+//! split by what made them: reads through a mount or an
+//! [`EngramReader`](crate::reader::EngramReader), extraction, and queries
+//! (a query counts once for every chunk it returns). Each access also adds
+//! its latency; an access touching several chunks charges each of them an
+//! equal share, since the chunks are decoded one after the other.
+//!
+//! One recorder is shared by everything reading the same engram:
+//! [`EmbrFS::access_stats`](crate::EmbrFS::access_stats) reports the one an
+//! `EmbrFS` hands to readers built from it, and mounts built from such a
+//! reader record into it too. The counts then steer what is kept in memory.
+//! A [`ChunkCache`](crate::codebook::ChunkCache) given the recorder evicts
+//! the least accessed of its oldest entries rather than simply the oldest,
+//! and a [`TieredSubEngramStore`](crate::TieredSubEngramStore) does the same
+//! with sub-engrams, by the accesses of the chunks they hold.
+//!
+//! Counts live in memory only. [`AccessSnapshot`] serializes, so tools can
+//! keep them across runs by merging each run's snapshot into a saved one,
+//! as the CLI's `--access-stats` options do; [`AccessSnapshot::heatmap`]
+//! lays them out file by file.
+
+use crate::embrfs::Manifest;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How many of a cache's least recently used entries are weighed against
+/// each other when one has to go.
+pub const EVICTION_WINDOW: usize = 8;
+
+/// What an access was for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    /// File bytes read through a mount or a reader.
+    Read,
+    /// A file written out by extraction.
+    Extract,
+    /// A chunk returned by a similarity query.
+    Query,
+}
+
+/// Accesses of one chunk or file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessCounts {
+    pub reads: u64,
+    pub extracts: u64,
+    pub queries: u64,
+    /// Latency of all of them together.
+    pub total_ns: u64,
+    /// Latency of the slowest one.
+    pub max_ns: u64,
+}
+
+impl AccessCounts {
+    /// Accesses of every kind.
+    pub fn total(&self) -> u64 {
+        self.reads + self.extracts + self.queries
+    }
+
+    pub fn mean_latency(&self) -> Duration {
+        Duration::from_nanos(self.total_ns.checked_div(self.total()).unwrap_or(0))
+    }
+
+    pub fn max_latency(&self) -> Duration {
+        Duration::from_nanos(self.max_ns)
+    }
+
+    fn add(&mut self, kind: AccessKind, ns: u64) {
+        match kind {
+            AccessKind::Read => self.reads += 1,
+            AccessKind::Extract => self.extracts += 1,
+            AccessKind::Query => self.queries += 1,
+        }
+        self.total_ns = self.total_ns.saturating_add(ns);
+        self.max_ns = self.max_ns.max(ns);
+    }
+
+    fn merge(&mut self, other: &AccessCounts) {
+        self.reads += other.reads;
+        self.extracts += other.extracts;
+        self.queries += other.queries;
+        self.total_ns = self.total_ns.saturating_add(other.total_ns);
+        self.max_ns = self.max_ns.max(other.max_ns);
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    chunks: HashMap<u64, AccessCounts>,
+    files: HashMap<String, AccessCounts>,
+}
+
+/// Access counts recorded from any thread; see the [module docs](self).
+#[derive(Debug, Default)]
+pub struct AccessStats {
+    state: Mutex<State>,
+}
+
+impl AccessStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // Every update is a few additions, so a panic elsewhere while the
+        // lock is held leaves nothing half-updated.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record one access of `path` that touched `chunks` and took `elapsed`.
+    pub fn record_file(&self, path: &str, chunks: &[usize], kind: AccessKind, elapsed: Duration) {
+        let ns = elapsed.as_nanos().min(u128::from(u64::MAX)) as u64;
+        let mut state = self.lock();
+        match state.files.get_mut(path) {
+            Some(counts) => counts.add(kind, ns),
+            None => state.files.entry(path.to_string()).or_default().add(kind, ns),
+        }
+        Self::charge(&mut state, chunks.iter().map(|&id| id as u64), chunks.len(), kind, ns);
+    }
+
+    /// Record one access of each of `chunks` that together took `elapsed`,
+    /// e.g. the chunks a query returned.
+    pub fn record_chunks(&self, chunks: &[usize], kind: AccessKind, elapsed: Duration) {
+        let ns = elapsed.as_nanos().min(u128::from(u64::MAX)) as u64;
+        let mut state = self.lock();
+        Self::charge(&mut state, chunks.iter().map(|&id| id as u64), chunks.len(), kind, ns);
+    }
+
+    fn charge(state: &mut State, ids: impl Iterator<Item = u64>, n: usize, kind: AccessKind, ns: u64) {
+        let share = ns.checked_div(n as u64).unwrap_or(0);
+        for id in ids {
+            state.chunks.entry(id).or_default().add(kind, share);
+        }
+    }
+
+    /// Accesses of chunk `id` so far, of every kind.
+    pub fn heat(&self, id: u64) -> u64 {
+        self.lock().chunks.get(&id).map_or(0, AccessCounts::total)
+    }
+
+    /// Accesses of all of `ids` together.
+    pub fn total_heat(&self, ids: impl IntoIterator<Item = u64>) -> u64 {
+        let state = self.lock();
+        ids.into_iter()
+            .map(|id| state.chunks.get(&id).map_or(0, AccessCounts::total))
+            .sum()
+    }
+
+    /// Add the counts of `snapshot`, e.g. ones saved by an earlier run.
+    pub fn merge(&self, snapshot: &AccessSnapshot) {
+        let mut state = self.lock();
+        for (&id, counts) in &snapshot.chunks {
+            state.chunks.entry(id).or_default().merge(counts);
+        }
+        for (path, counts) in &snapshot.files {
+            state.files.entry(path.clone()).or_default().merge(counts);
+        }
+    }
+
+    pub fn snapshot(&self) -> AccessSnapshot {
+        let state = self.lock();
+        AccessSnapshot {
+            chunks: state.chunks.iter().map(|(&id, &counts)| (id, counts)).collect(),
+            files: state.files.iter().map(|(path, &counts)| (path.clone(), counts)).collect(),
+        }
+    }
+
+    /// Forget every count.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.chunks.clear();
+        state.files.clear();
+    }
+}
+
+/// Counts read from an [`AccessStats`] at one moment.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessSnapshot {
+    pub chunks: BTreeMap<u64, AccessCounts>,
+    pub files: BTreeMap<String, AccessCounts>,
+}
+
+/// One file of an [`AccessSnapshot::heatmap`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileHeat {
+    pub path: String,
+    /// Accesses of the file itself.
+    pub counts: AccessCounts,
+    /// The file's chunks in order, split into at most `width` runs of
+    /// equal length, with the accesses of the busiest chunk of each run.
+    pub cells: Vec<u64>,
+}
+
+impl AccessSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.files.is_empty()
+    }
+
+    /// Add the counts of `other`.
+    pub fn merge(&mut self, other: &AccessSnapshot) {
+        for (&id, counts) in &other.chunks {
+            self.chunks.entry(id).or_default().merge(counts);
+        }
+        for (path, counts) in &other.files {
+            self.files.entry(path.clone()).or_default().merge(counts);
+        }
+    }
+
+    /// The `n` most accessed files, busiest first.
+    pub fn hottest_files(&self, n: usize) -> Vec<(&str, AccessCounts)> {
+        let mut files: Vec<(&str, AccessCounts)> = self.files.iter().map(|(p, &c)| (p.as_str(), c)).collect();
+        files.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then_with(|| a.0.cmp(b.0)));
+        files.truncate(n);
+        files
+    }
+
+    /// The `n` most accessed chunks, busiest first.
+    pub fn hottest_chunks(&self, n: usize) -> Vec<(u64, AccessCounts)> {
+        let mut chunks: Vec<(u64, AccessCounts)> = self.chunks.iter().map(|(&id, &c)| (id, c)).collect();
+        chunks.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then_with(|| a.0.cmp(&b.0)));
+        chunks.truncate(n);
+        chunks
+    }
+
+    /// Chunk accesses of every file of `manifest` that was accessed at all,
+    /// busiest first, with each file's chunks folded into at most `width`
+    /// cells.
+    pub fn heatmap(&self, manifest: &Manifest, width: usize) -> Vec<FileHeat> {
+        let width = width.max(1);
+        let mut rows: Vec<FileHeat> = manifest
+            .files
+            .iter()
+            .filter_map(|file| {
+                let heat: Vec<u64> = file
+                    .chunks
+                    .iter()
+                    .map(|&id| self.chunks.get(&(id as u64)).map_or(0, AccessCounts::total))
+                    .collect();
+                let counts = self.files.get(&file.path).copied().unwrap_or_default();
+                if counts.total() == 0 && heat.iter().all(|&h| h == 0) {
+                    return None;
+                }
+                let run = heat.len().div_ceil(width).max(1);
+                Some(FileHeat {
+                    path: file.path.clone(),
+                    counts,
+                    cells: heat.chunks(run).map(|run| run.iter().copied().max().unwrap_or(0)).collect(),
+                })
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            let busiest = |row: &FileHeat| row.counts.total().max(row.cells.iter().copied().max().unwrap_or(0));
+            busiest(b).cmp(&busiest(a)).then_with(|| a.path.cmp(&b.path))
+        });
+        rows
+    }
+}
@@ -0,0 +1,399 @@
+//! Built-in benchmark harness behind `embeddenator bench`.
+//!
+//! Two kinds of measurements, reported together as one JSON document:
+//!
+//! - **Micro**: `bind`, `bundle`, `dot` and `cosine` on every vector
+//!   representation (sparse, packed, bitsliced, block-sparse) at each
+//!   requested dimension. Operands are random ternary vectors of the
+//!   configured density, generated from a fixed seed.
+//! - **Macro**: ingest and extract throughput of a generated corpus (a mix
+//!   of text-like and random files, also seeded) through [`EmbrFS`].
+//!
+//! Each micro benchmark doubles its iteration count until one batch runs
+//! for at least [`BenchOptions::min_time`], and reports that batch. Results
+//! from different hosts or builds can be compared with
+//! [`BenchReport::regressions`].
+//!
+//! [`calibrate`] reuses the micro benchmark timer to find where
+//! [`HybridTritVec`](crate::HybridTritVec) should switch representations on
+//! the local CPU (`embeddenator bench --calibrate`).
+
+use crate::bitsliced::{simd_features_string, BitslicedTritVec};
+use crate::block_sparse::BlockSparseTritVec;
+use crate::embrfs::EmbrFS;
+use crate::hybrid::HybridThresholds;
+use crate::ternary_vec::PackedTritVec;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::hint::black_box;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const SEED: u64 = 0x5eed_be4c;
+
+/// What to run and for how long.
+#[derive(Clone, Debug)]
+pub struct BenchOptions {
+    /// Vector dimensions for the micro benchmarks.
+    pub dims: Vec<usize>,
+    /// Fraction of non-zero trits in micro benchmark operands.
+    pub density: f64,
+    /// Minimum duration of the measured batch of each micro benchmark.
+    pub min_time: Duration,
+    /// Size of the generated corpus for the macro benchmarks.
+    pub corpus_bytes: usize,
+    pub micro: bool,
+    pub macro_: bool,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            dims: vec![1024, 10_000, 65_536],
+            density: 0.01,
+            min_time: Duration::from_millis(200),
+            corpus_bytes: 8 << 20,
+            micro: true,
+            macro_: true,
+        }
+    }
+}
+
+impl BenchOptions {
+    /// A short run for smoke tests: one small dimension and a small corpus.
+    pub fn quick() -> Self {
+        BenchOptions {
+            dims: vec![1024],
+            min_time: Duration::from_millis(5),
+            corpus_bytes: 256 << 10,
+            ..Self::default()
+        }
+    }
+}
+
+/// Where the benchmarks ran.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HostInfo {
+    pub arch: String,
+    pub os: String,
+    pub cpus: usize,
+    /// SIMD paths available to the bitsliced kernels.
+    pub simd: String,
+    /// Whether this binary was built in debug mode.
+    pub debug_build: bool,
+}
+
+/// One vector operation on one representation and dimension.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MicroResult {
+    pub op: String,
+    pub repr: String,
+    pub dim: usize,
+    /// Mean non-zero trits per operand.
+    pub nnz: usize,
+    pub iterations: u64,
+    pub ns_per_op: f64,
+    pub ops_per_sec: f64,
+}
+
+/// Throughput of one end-to-end operation over the generated corpus.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MacroResult {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub chunks: usize,
+    pub secs: f64,
+    pub mib_per_sec: f64,
+}
+
+/// Complete output of one `bench` run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub version: String,
+    pub host: HostInfo,
+    pub micro: Vec<MicroResult>,
+    #[serde(rename = "macro")]
+    pub macro_: Vec<MacroResult>,
+}
+
+/// A benchmark that got slower than its baseline by more than the tolerance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regression {
+    /// `micro/<op>/<repr>/<dim>` or `macro/<name>`.
+    pub name: String,
+    /// Seconds per operation (micro) or per MiB (macro) in the baseline.
+    pub baseline: f64,
+    pub current: f64,
+    /// `current / baseline - 1`, e.g. 0.3 for 30% slower.
+    pub slowdown: f64,
+}
+
+impl BenchReport {
+    /// Benchmarks present in both reports that are more than `tolerance`
+    /// (a fraction, e.g. 0.2) slower than in `baseline`.
+    pub fn regressions(&self, baseline: &BenchReport, tolerance: f64) -> Vec<Regression> {
+        let costs = |report: &BenchReport| {
+            let micro = report.micro.iter().map(|m| {
+                (format!("micro/{}/{}/{}", m.op, m.repr, m.dim), m.ns_per_op * 1e-9)
+            });
+            let macro_ = report
+                .macro_
+                .iter()
+                .filter(|m| m.mib_per_sec > 0.0)
+                .map(|m| (format!("macro/{}", m.name), 1.0 / m.mib_per_sec));
+            micro.chain(macro_).collect::<Vec<_>>()
+        };
+        let before: std::collections::HashMap<String, f64> = costs(baseline).into_iter().collect();
+        costs(self)
+            .into_iter()
+            .filter_map(|(name, current)| {
+                let baseline = *before.get(&name)?;
+                let slowdown = current / baseline - 1.0;
+                (baseline > 0.0 && slowdown > tolerance).then_some(Regression {
+                    name,
+                    baseline,
+                    current,
+                    slowdown,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Run the benchmarks selected by `opts`.
+pub fn run(opts: &BenchOptions) -> io::Result<BenchReport> {
+    if !(0.0..=1.0).contains(&opts.density) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "density must be between 0 and 1"));
+    }
+    let mut report = BenchReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        host: HostInfo {
+            arch: std::env::consts::ARCH.to_string(),
+            os: std::env::consts::OS.to_string(),
+            cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            simd: simd_features_string(),
+            debug_build: cfg!(debug_assertions),
+        },
+        micro: Vec::new(),
+        macro_: Vec::new(),
+    };
+    if opts.micro {
+        for &dim in &opts.dims {
+            if dim == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "dimensions must be non-zero"));
+            }
+            micro_dim(dim, opts, &mut report.micro);
+        }
+    }
+    if opts.macro_ {
+        report.macro_ = macro_corpus(opts.corpus_bytes)?;
+    }
+    Ok(report)
+}
+
+fn random_sparse(rng: &mut StdRng, dim: usize, density: f64) -> SparseVec {
+    let mut v = SparseVec::new();
+    for i in 0..dim {
+        if rng.gen_bool(density) {
+            if rng.gen_bool(0.5) {
+                v.pos.push(i);
+            } else {
+                v.neg.push(i);
+            }
+        }
+    }
+    v
+}
+
+/// Time `f`, doubling the batch size until a batch takes `min_time`.
+fn time_op<R>(min_time: Duration, mut f: impl FnMut() -> R) -> (u64, f64) {
+    let mut iterations = 1u64;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iterations {
+            black_box(f());
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= min_time || iterations >= 1 << 30 {
+            return (iterations, elapsed.as_nanos() as f64 / iterations as f64);
+        }
+        iterations *= 2;
+    }
+}
+
+fn micro_dim(dim: usize, opts: &BenchOptions, out: &mut Vec<MicroResult>) {
+    let mut rng = StdRng::seed_from_u64(SEED ^ dim as u64);
+    let a = random_sparse(&mut rng, dim, opts.density);
+    let b = random_sparse(&mut rng, dim, opts.density);
+    let nnz = (a.pos.len() + a.neg.len() + b.pos.len() + b.neg.len()) / 2;
+    let mut record = |op: &str, repr: &str, (iterations, ns_per_op): (u64, f64)| {
+        out.push(MicroResult {
+            op: op.to_string(),
+            repr: repr.to_string(),
+            dim,
+            nnz,
+            iterations,
+            ns_per_op,
+            ops_per_sec: if ns_per_op > 0.0 { 1e9 / ns_per_op } else { 0.0 },
+        });
+    };
+    let t = opts.min_time;
+
+    record("bind", "sparse", time_op(t, || a.bind(&b)));
+    record("bundle", "sparse", time_op(t, || a.bundle(&b)));
+    record("cosine", "sparse", time_op(t, || a.cosine(&b)));
+
+    let (pa, pb) = (PackedTritVec::from_sparsevec(&a, dim), PackedTritVec::from_sparsevec(&b, dim));
+    record("bind", "packed", time_op(t, || pa.bind(&pb)));
+    record("bundle", "packed", time_op(t, || pa.bundle(&pb)));
+    record("dot", "packed", time_op(t, || pa.dot(&pb)));
+    record("cosine", "packed", time_op(t, || pa.cosine(&pb)));
+
+    let (ba, bb) = (BitslicedTritVec::from_sparse(&a, dim), BitslicedTritVec::from_sparse(&b, dim));
+    record("bind", "bitsliced", time_op(t, || ba.bind_dispatch(&bb)));
+    record("bundle", "bitsliced", time_op(t, || ba.bundle_dispatch(&bb)));
+    record("dot", "bitsliced", time_op(t, || ba.dot_dispatch(&bb)));
+    record("cosine", "bitsliced", time_op(t, || ba.cosine(&bb)));
+
+    let (ka, kb) = (BlockSparseTritVec::from_sparse(&a, dim), BlockSparseTritVec::from_sparse(&b, dim));
+    record("bind", "block_sparse", time_op(t, || ka.bind_dispatch(&kb)));
+    record("bundle", "block_sparse", time_op(t, || ka.bundle_dispatch(&kb)));
+    record("dot", "block_sparse", time_op(t, || ka.dot_dispatch(&kb)));
+    record("cosine", "block_sparse", time_op(t, || ka.cosine_dispatch(&kb)));
+}
+
+/// Dimension at which the sparse/bitsliced density crossover is measured.
+const CALIBRATION_DIM: usize = 10_000;
+const DENSITY_LADDER: &[f64] = &[0.0005, 0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2];
+const SMALL_DIM_LADDER: &[usize] = &[32, 64, 128, 256, 512, 1024, 2048, 4096];
+const LARGE_DIM_LADDER: &[usize] = &[16_384, 32_768, 65_536, 131_072, 262_144, 524_288, 1_048_576];
+
+/// Measure the [`HybridThresholds`] for this CPU.
+///
+/// Each threshold is the first rung of a fixed ladder (densities or
+/// dimensions) at which the representation favoured above the threshold
+/// costs no more than the one below it, cost being one `bind` plus one
+/// `cosine`. With no crossover on the ladder the last rung is used, except
+/// for `min_block_sparse_dim`: block-sparse also saves memory at large
+/// dimensions, so it keeps its built-in value when it never wins on speed.
+pub fn calibrate(min_time: Duration) -> HybridThresholds {
+    let defaults = HybridThresholds::default();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut pair = |dim: usize, density: f64| {
+        (random_sparse(&mut rng, dim, density), random_sparse(&mut rng, dim, density))
+    };
+    let sparse = |a: &SparseVec, b: &SparseVec| {
+        time_op(min_time, || a.bind(b)).1 + time_op(min_time, || a.cosine(b)).1
+    };
+    let bitsliced = |a: &SparseVec, b: &SparseVec, dim: usize| {
+        let (a, b) = (BitslicedTritVec::from_sparse(a, dim), BitslicedTritVec::from_sparse(b, dim));
+        time_op(min_time, || a.bind_dispatch(&b)).1 + time_op(min_time, || a.cosine(&b)).1
+    };
+    let block_sparse = |a: &SparseVec, b: &SparseVec, dim: usize| {
+        let (a, b) = (BlockSparseTritVec::from_sparse(a, dim), BlockSparseTritVec::from_sparse(b, dim));
+        time_op(min_time, || a.bind_dispatch(&b)).1 + time_op(min_time, || a.cosine_dispatch(&b)).1
+    };
+
+    let density = first_rung(DENSITY_LADDER, |d| {
+        let (a, b) = pair(CALIBRATION_DIM, d);
+        bitsliced(&a, &b, CALIBRATION_DIM) <= sparse(&a, &b)
+    });
+    // Probe dimensions at a density where bitsliced should already win.
+    let dense = (density * 2.0).min(1.0);
+    let min_bitsliced_dim = first_rung(SMALL_DIM_LADDER, |dim| {
+        let (a, b) = pair(dim, dense);
+        bitsliced(&a, &b, dim) <= sparse(&a, &b)
+    });
+    let probe = defaults.block_sparse_density / 4.0;
+    let min_block_sparse_dim = LARGE_DIM_LADDER
+        .iter()
+        .copied()
+        .find(|&dim| {
+            let (a, b) = pair(dim, probe);
+            block_sparse(&a, &b, dim) <= bitsliced(&a, &b, dim)
+        })
+        .unwrap_or(defaults.min_block_sparse_dim);
+    let block_sparse_density = first_rung(DENSITY_LADDER, |d| {
+        let (a, b) = pair(min_block_sparse_dim, d);
+        bitsliced(&a, &b, min_block_sparse_dim) <= block_sparse(&a, &b, min_block_sparse_dim)
+    });
+
+    HybridThresholds {
+        density,
+        min_bitsliced_dim,
+        min_block_sparse_dim,
+        block_sparse_density,
+    }
+}
+
+/// First rung of `ladder` for which `wins` holds, else the last rung.
+fn first_rung<T: Copy>(ladder: &[T], mut wins: impl FnMut(T) -> bool) -> T {
+    ladder.iter().copied().find(|&rung| wins(rung)).unwrap_or(ladder[ladder.len() - 1])
+}
+
+const CORPUS_WORDS: &[&str] = &[
+    "engram", "vector", "bundle", "bind", "chunk", "manifest", "ternary", "sparse", "cosine", "root",
+    "codebook", "extract", "ingest", "fn", "let", "mut", "impl", "struct", "return", "self",
+];
+
+/// Write `total` bytes of files under `dir`: alternating text-like and
+/// random files of 4 KiB to 256 KiB. Returns the file count.
+fn write_corpus(dir: &Path, total: usize) -> io::Result<usize> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut written = 0;
+    let mut files = 0;
+    while written < total {
+        let len = rng.gen_range(4 << 10..=256 << 10).min(total - written);
+        let mut data = Vec::with_capacity(len);
+        if files % 2 == 0 {
+            while data.len() < len {
+                data.extend_from_slice(CORPUS_WORDS[rng.gen_range(0..CORPUS_WORDS.len())].as_bytes());
+                data.push(if rng.gen_ratio(1, 8) { b'\n' } else { b' ' });
+            }
+            data.truncate(len);
+        } else {
+            data.resize(len, 0);
+            rng.fill(&mut data[..]);
+        }
+        let sub = dir.join(format!("d{}", files % 8));
+        std::fs::create_dir_all(&sub)?;
+        std::fs::write(sub.join(format!("f{files}.{}", if files % 2 == 0 { "txt" } else { "bin" })), &data)?;
+        written += len;
+        files += 1;
+    }
+    Ok(files)
+}
+
+fn macro_corpus(corpus_bytes: usize) -> io::Result<Vec<MacroResult>> {
+    let tmp = tempfile::tempdir()?;
+    let input = tmp.path().join("corpus");
+    let output = tmp.path().join("extracted");
+    let files = write_corpus(&input, corpus_bytes)?;
+    let config = ReversibleVSAConfig::default();
+
+    let mut fs = EmbrFS::new();
+    fs.capacity_monitor = None;
+    let start = Instant::now();
+    fs.ingest_directory(&input, false, &config)?;
+    let ingest_secs = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    EmbrFS::extract(&fs.engram, &fs.manifest, &output, false, &config)?;
+    let extract_secs = start.elapsed().as_secs_f64();
+
+    let bytes: u64 = fs.manifest.files.iter().map(|f| f.size as u64).sum();
+    let chunks = fs.manifest.total_chunks;
+    let result = |name: &str, secs: f64| MacroResult {
+        name: name.to_string(),
+        files,
+        bytes,
+        chunks,
+        secs,
+        mib_per_sec: if secs > 0.0 { bytes as f64 / (1 << 20) as f64 / secs } else { 0.0 },
+    };
+    Ok(vec![result("ingest", ingest_secs), result("extract", extract_secs)])
+}
@@ -0,0 +1,301 @@
+//! Capacity and noise-floor analysis of the engram root bundle.
+//!
+//! Every ingested chunk is bundled into `Engram::root`. Each addition dilutes
+//! the others: in an ideal superposition of `n` near-orthogonal vectors a
+//! member's cosine with the bundle is about `1/sqrt(n)`, while two unrelated
+//! vectors have a cosine of about `0 ± 1/sqrt(DIM)` (the noise floor). Once
+//! members sit within [`NOISE_MARGIN`] standard deviations of the noise
+//! floor, the root can no longer tell them apart from chunks it never saw.
+//!
+//! [`CapacityReport`] compares the expected and observed member cosines,
+//! and extrapolates how many more chunks fit before the mean member cosine
+//! reaches that threshold. The extrapolation assumes `1/sqrt(n)` decay from
+//! the current observation; the pairwise bundle used during ingest decays
+//! faster, so the estimate shrinks as it is re-measured.
+//!
+//! During ingest, [`CapacityMonitor`] re-checks a sample of members as the
+//! engram grows and warns once when saturation crosses its threshold.
+//!
+//! The same statistics make the root a Bloom-filter-like membership test:
+//! [`MembershipResult`] compares a chunk's cosine with the root against the
+//! member cosine expected for the bundle's size, and reports how likely an
+//! unrelated chunk is to score as high. Like a Bloom filter it can be
+//! wrong; unlike one it can also miss members once the root saturates.
+
+use crate::embrfs::Engram;
+use crate::logging;
+use crate::vsa::DIM;
+use serde::{Deserialize, Serialize};
+
+/// Standard deviations above the noise floor a member cosine needs for the
+/// member to stay distinguishable from unrelated vectors.
+pub const NOISE_MARGIN: f64 = 3.0;
+
+/// Standard deviations above the noise floor a membership test asks for
+/// while the root is small and members score far above it.
+pub const MEMBERSHIP_MARGIN: f64 = 5.0;
+
+/// Saturation at which [`CapacityMonitor`] warns.
+pub const DEFAULT_WARN_SATURATION: f64 = 0.8;
+
+/// Members sampled by [`CapacityMonitor`] on each check.
+pub const DEFAULT_MONITOR_SAMPLE: usize = 256;
+
+/// Fewest new chunks between two [`CapacityMonitor`] checks.
+const MIN_CHECK_INTERVAL: usize = 32;
+
+/// Standard deviation of the cosine between two unrelated vectors.
+pub fn noise_floor() -> f64 {
+    1.0 / (DIM as f64).sqrt()
+}
+
+/// Member cosine below which retrieval against the root degrades.
+pub fn retrieval_threshold() -> f64 {
+    NOISE_MARGIN * noise_floor()
+}
+
+/// Cosine of a member with an ideal bundle of `members` vectors.
+pub fn expected_cosine(members: usize) -> f64 {
+    if members == 0 {
+        0.0
+    } else {
+        1.0 / (members as f64).sqrt()
+    }
+}
+
+/// Saturation of the root bundle, measured on a sample of its members.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CapacityReport {
+    /// Chunk vectors bundled into the root.
+    pub members: usize,
+    /// Members whose cosine with the root was measured.
+    pub sampled: usize,
+    /// Fraction of root dimensions that are non-zero.
+    pub root_density: f64,
+    /// Member cosine of an ideal bundle of `members` vectors.
+    pub expected_cosine: f64,
+    /// Mean cosine of the sampled members with the root.
+    pub observed_cosine: f64,
+    /// Lowest cosine among the sampled members.
+    pub min_cosine: f64,
+    /// See [`noise_floor`].
+    pub noise_floor: f64,
+    /// See [`retrieval_threshold`].
+    pub threshold: f64,
+    /// Sampled members whose cosine is below `threshold`.
+    pub below_threshold: usize,
+    /// Fraction of the estimated capacity in use; 1.0 or more means the mean
+    /// member is already indistinguishable from noise.
+    pub saturation: f64,
+    /// Estimated chunks that can still be bundled before reaching saturation 1.0.
+    pub remaining: usize,
+}
+
+impl CapacityReport {
+    /// Measure every member of `engram`.
+    pub fn analyze(engram: &Engram) -> Self {
+        Self::analyze_sampled(engram, usize::MAX)
+    }
+
+    /// Measure at most `max_samples` members, spread evenly over chunk ids.
+    pub fn analyze_sampled(engram: &Engram, max_samples: usize) -> Self {
+        let members = engram.codebook.len();
+        let threshold = retrieval_threshold();
+        let mut report = CapacityReport {
+            members,
+            root_density: (engram.root.pos.len() + engram.root.neg.len()) as f64 / DIM as f64,
+            expected_cosine: expected_cosine(members),
+            noise_floor: noise_floor(),
+            threshold,
+            ..Default::default()
+        };
+        if members == 0 {
+            report.remaining = (1.0 / (threshold * threshold)) as usize;
+            return report;
+        }
+
+        let mut ids: Vec<usize> = engram.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        let step = members.div_ceil(max_samples.max(1));
+        let cosines: Vec<f64> = ids
+            .iter()
+            .step_by(step)
+            .map(|id| engram.codebook[id].cosine(&engram.root))
+            .collect();
+
+        report.sampled = cosines.len();
+        report.observed_cosine = cosines.iter().sum::<f64>() / cosines.len() as f64;
+        report.min_cosine = cosines.iter().copied().fold(f64::INFINITY, f64::min);
+        report.below_threshold = cosines.iter().filter(|&&c| c < threshold).count();
+        if report.observed_cosine > 0.0 {
+            // Under 1/sqrt(n) decay, capacity = n * (observed / threshold)^2.
+            report.saturation = (threshold / report.observed_cosine).powi(2);
+            let capacity = members as f64 / report.saturation;
+            report.remaining = (capacity - members as f64).max(0.0) as usize;
+        } else {
+            report.saturation = f64::MAX;
+        }
+        report
+    }
+
+    /// Whether saturation has reached `warn_at`.
+    pub fn is_near_capacity(&self, warn_at: f64) -> bool {
+        self.saturation >= warn_at
+    }
+}
+
+/// Chance that a chunk never bundled into the root scores a cosine of at
+/// least `score` with it: the upper tail of the normal noise distribution.
+pub fn false_positive_rate(score: f64) -> f64 {
+    0.5 * erfc(score / (noise_floor() * std::f64::consts::SQRT_2))
+}
+
+/// Complementary error function (Abramowitz and Stegun 7.1.26, absolute
+/// error below 1.5e-7).
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let tail = poly * (-x * x).exp();
+    if x >= 0.0 {
+        tail
+    } else {
+        2.0 - tail
+    }
+}
+
+/// What a membership test against the root concluded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipVerdict {
+    /// The score clears the threshold: probably ingested.
+    Likely,
+    /// The score is below the threshold: probably never ingested.
+    Unlikely,
+    /// The root holds so many chunks that members score within the noise;
+    /// the test cannot tell.
+    Saturated,
+}
+
+/// A chunk's cosine with the root, judged against the bundle's size.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MembershipResult {
+    /// Cosine of the chunk vector with the root.
+    pub score: f64,
+    pub verdict: MembershipVerdict,
+    /// Chunk vectors bundled into the root.
+    pub members: usize,
+    /// See [`expected_cosine`].
+    pub expected_score: f64,
+    /// Score at or above which the verdict is [`MembershipVerdict::Likely`]:
+    /// half the expected member cosine, kept between [`retrieval_threshold`]
+    /// and [`MEMBERSHIP_MARGIN`] noise floors. Pairwise roots favour recent
+    /// members, so early ones can score well below the expected cosine.
+    pub threshold: f64,
+    /// See [`false_positive_rate`]; for a whole file, the chance that every
+    /// chunk of an unrelated file scores as high.
+    pub false_positive_rate: f64,
+}
+
+impl MembershipResult {
+    /// Judge `score` against a root of `members` chunk vectors.
+    pub fn from_score(score: f64, members: usize) -> Self {
+        let expected_score = expected_cosine(members);
+        let threshold = (expected_score / 2.0).clamp(retrieval_threshold(), MEMBERSHIP_MARGIN * noise_floor());
+        let verdict = if expected_score <= retrieval_threshold() {
+            MembershipVerdict::Saturated
+        } else if score >= threshold {
+            MembershipVerdict::Likely
+        } else {
+            MembershipVerdict::Unlikely
+        };
+        MembershipResult {
+            score,
+            verdict,
+            members,
+            expected_score,
+            threshold,
+            false_positive_rate: false_positive_rate(score),
+        }
+    }
+
+    /// Combine the results for the chunks of one file. Its score is the
+    /// weakest chunk's, it is likely only if every chunk is, and chunks are
+    /// treated as independent for the false-positive rate. `None` for no
+    /// chunks.
+    pub fn combine(chunks: &[MembershipResult]) -> Option<Self> {
+        let weakest = chunks.iter().min_by(|a, b| a.score.total_cmp(&b.score))?;
+        let verdict = if chunks.iter().any(|c| c.verdict == MembershipVerdict::Saturated) {
+            MembershipVerdict::Saturated
+        } else {
+            weakest.verdict
+        };
+        Some(MembershipResult {
+            verdict,
+            false_positive_rate: chunks.iter().map(|c| c.false_positive_rate).product(),
+            ..weakest.clone()
+        })
+    }
+
+    pub fn is_likely(&self) -> bool {
+        self.verdict == MembershipVerdict::Likely
+    }
+}
+
+/// Periodic saturation check run by `EmbrFS` during ingest.
+///
+/// Checks happen after a file is ingested, once the engram has grown by a
+/// sixteenth (and at least 32 chunks) since the last check.
+#[derive(Clone, Debug)]
+pub struct CapacityMonitor {
+    /// Saturation at which to warn.
+    pub warn_at: f64,
+    /// Members sampled per check.
+    pub sample: usize,
+    next_check: usize,
+    warned: bool,
+}
+
+impl Default for CapacityMonitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_WARN_SATURATION)
+    }
+}
+
+impl CapacityMonitor {
+    pub fn new(warn_at: f64) -> Self {
+        CapacityMonitor {
+            warn_at,
+            sample: DEFAULT_MONITOR_SAMPLE,
+            next_check: MIN_CHECK_INTERVAL,
+            warned: false,
+        }
+    }
+
+    /// Check `engram` if it has grown enough; returns the report when a
+    /// check ran. Warns the first time saturation reaches `warn_at`, and
+    /// again if it drops below and later climbs back.
+    pub fn observe(&mut self, engram: &Engram) -> Option<CapacityReport> {
+        let members = engram.codebook.len();
+        if members < self.next_check {
+            return None;
+        }
+        self.next_check = members + (members / 16).max(MIN_CHECK_INTERVAL);
+
+        let report = CapacityReport::analyze_sampled(engram, self.sample);
+        let near = report.is_near_capacity(self.warn_at);
+        if near && !self.warned {
+            logging::warn(&format!(
+                "warning: engram root is {:.0}% saturated ({} chunks, mean member cosine {:.4}, threshold {:.4}); \
+                 about {} more chunks fit before similarity against the root degrades. \
+                 Consider bundle-hier for large trees.",
+                report.saturation.min(9.99) * 100.0,
+                report.members,
+                report.observed_cosine,
+                report.threshold,
+                report.remaining,
+            ));
+        }
+        self.warned = near;
+        Some(report)
+    }
+}
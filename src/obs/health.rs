@@ -0,0 +1,337 @@
+//! Liveness and readiness for the server modes.
+//!
+//! A [`Health`] handle collects what a server knows about its own state and
+//! turns it into a [`HealthReport`] of named checks, each `ok`, `degraded`
+//! or `failing`:
+//!
+//! - `engram:<name>` for every engram the server holds: `failing` while it
+//!   loads or after its load failed. A server that only makes sense with an
+//!   engram ([`Health::requiring_engram`]) also fails `engram` while it has
+//!   none.
+//! - `store:<name>` for every [`SubEngramStore`] registered, `failing` when
+//!   [`SubEngramStore::probe`] cannot reach it.
+//! - `cache:<name>` for every [`ChunkCache`] registered, `degraded` once it
+//!   is full past [`Health::set_cache_pressure`] and has started evicting.
+//! - `scrub`, from the last [`ScrubStatus`] recorded or found in the status
+//!   file `embeddenator scrub --status-file` writes: `failing` if it left
+//!   chunks it could not restore, `degraded` if it found damage it did not
+//!   repair.
+//!
+//! [`Health::respond`] answers the two probes orchestrators poll: `/healthz`
+//! with `200` whenever the process can answer at all, and `/readyz` with
+//! `503` while any check is failing, both with the report as JSON. The HTTP
+//! API serves them on its own port; [`spawn`] serves them on a separate
+//! listener for the gRPC server and anything else without an HTTP front.
+
+use crate::codebook::ChunkCache;
+use crate::embrfs::SubEngramStore;
+use crate::scrub::ScrubStatus;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Fill fraction past which an evicting chunk cache counts as under
+/// pressure.
+pub const DEFAULT_CACHE_PRESSURE: f64 = 0.95;
+
+/// State of one check, or of the whole report; ordered from best to worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Degraded,
+    Failing,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Outcome of [`Health::report`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// The worst status among `checks`, `ok` without any.
+    pub status: CheckStatus,
+    /// No check is failing.
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum EngramState {
+    Loading,
+    Loaded { files: usize, chunks: usize },
+    Failed(String),
+}
+
+#[derive(Default)]
+struct State {
+    require_engram: bool,
+    engrams: BTreeMap<String, EngramState>,
+    stores: Vec<(String, Arc<dyn SubEngramStore + Send + Sync>)>,
+    caches: Vec<(String, Arc<ChunkCache>)>,
+    cache_pressure: Option<f64>,
+    scrub: Option<ScrubStatus>,
+    scrub_file: Option<PathBuf>,
+}
+
+/// Shared handle to a server's health; clones report on the same state.
+#[derive(Clone, Default)]
+pub struct Health {
+    state: Arc<Mutex<State>>,
+}
+
+impl Health {
+    /// Health with nothing registered, ready until something fails.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Health that is not ready while no engram is loaded.
+    pub fn requiring_engram() -> Self {
+        let health = Self::new();
+        health.lock().require_engram = true;
+        health
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // Every update is a single assignment; a panic leaves nothing half-done.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn engram_loading(&self, name: &str) {
+        self.lock().engrams.insert(name.to_string(), EngramState::Loading);
+    }
+
+    pub fn engram_loaded(&self, name: &str, files: usize, chunks: usize) {
+        self.lock()
+            .engrams
+            .insert(name.to_string(), EngramState::Loaded { files, chunks });
+    }
+
+    /// Record that loading `name` failed with `error`; it stays failing
+    /// until loaded again or dropped.
+    pub fn engram_failed(&self, name: &str, error: &dyn std::fmt::Display) {
+        self.lock()
+            .engrams
+            .insert(name.to_string(), EngramState::Failed(error.to_string()));
+    }
+
+    pub fn engram_dropped(&self, name: &str) {
+        self.lock().engrams.remove(name);
+    }
+
+    /// Probe `store` on every report.
+    pub fn add_store(&self, name: &str, store: Arc<dyn SubEngramStore + Send + Sync>) {
+        self.lock().stores.push((name.to_string(), store));
+    }
+
+    /// Report the fill and evictions of `cache`.
+    pub fn add_chunk_cache(&self, name: &str, cache: Arc<ChunkCache>) {
+        self.lock().caches.push((name.to_string(), cache));
+    }
+
+    /// Count a chunk cache as under pressure once it is full past
+    /// `fraction` and evicting (default [`DEFAULT_CACHE_PRESSURE`]).
+    pub fn set_cache_pressure(&self, fraction: f64) {
+        self.lock().cache_pressure = Some(fraction);
+    }
+
+    /// Record the outcome of a scrub pass run in this process.
+    pub fn record_scrub(&self, status: ScrubStatus) {
+        self.lock().scrub = Some(status);
+    }
+
+    /// Read the last scrub result from `path` on every report, as left by
+    /// a separate `embeddenator scrub --status-file`. Newer than a result
+    /// given to [`Health::record_scrub`], it replaces it.
+    pub fn set_scrub_status_file(&self, path: impl Into<PathBuf>) {
+        self.lock().scrub_file = Some(path.into());
+    }
+
+    pub fn report(&self) -> HealthReport {
+        let state = self.lock();
+        let mut checks = Vec::new();
+
+        if state.engrams.is_empty() {
+            let status = if state.require_engram { CheckStatus::Failing } else { CheckStatus::Ok };
+            checks.push(Check::new("engram", status, "no engram loaded"));
+        }
+        for (name, engram) in &state.engrams {
+            let name = format!("engram:{}", name);
+            checks.push(match engram {
+                EngramState::Loading => Check::new(name, CheckStatus::Failing, "loading"),
+                EngramState::Loaded { files, chunks } => {
+                    Check::new(name, CheckStatus::Ok, format!("{} files, {} chunks", files, chunks))
+                }
+                EngramState::Failed(error) => Check::new(name, CheckStatus::Failing, format!("load failed: {}", error)),
+            });
+        }
+
+        for (name, store) in &state.stores {
+            let name = format!("store:{}", name);
+            checks.push(match store.probe() {
+                Ok(()) => Check::new(name, CheckStatus::Ok, "reachable"),
+                Err(e) => Check::new(name, CheckStatus::Failing, format!("unreachable: {}", e)),
+            });
+        }
+
+        let pressure = state.cache_pressure.unwrap_or(DEFAULT_CACHE_PRESSURE);
+        for (name, cache) in &state.caches {
+            let stats = cache.stats();
+            let fill = if stats.max_bytes == 0 {
+                0.0
+            } else {
+                stats.bytes as f64 / stats.max_bytes as f64
+            };
+            let status = if fill >= pressure && stats.evictions > 0 {
+                CheckStatus::Degraded
+            } else {
+                CheckStatus::Ok
+            };
+            checks.push(Check::new(
+                format!("cache:{}", name),
+                status,
+                format!(
+                    "{} of {} bytes held ({:.0}%), {} evictions, {} hits, {} misses",
+                    stats.bytes,
+                    stats.max_bytes,
+                    fill * 100.0,
+                    stats.evictions,
+                    stats.hits,
+                    stats.misses
+                ),
+            ));
+        }
+
+        let from_file = state.scrub_file.as_ref().map(ScrubStatus::load);
+        checks.push(scrub_check(from_file, state.scrub.as_ref()));
+
+        let status = checks.iter().map(|c| c.status).max().unwrap_or(CheckStatus::Ok);
+        HealthReport {
+            status,
+            ready: status != CheckStatus::Failing,
+            checks,
+        }
+    }
+
+    /// Answer a probe of `path` (a query string is ignored): the HTTP status
+    /// and report for `/healthz` and `/readyz`, `None` for anything else.
+    pub fn respond(&self, path: &str) -> Option<(u16, HealthReport)> {
+        let path = path.split('?').next().unwrap_or_default();
+        match path {
+            "/healthz" => Some((200, self.report())),
+            "/readyz" => {
+                let report = self.report();
+                Some((if report.ready { 200 } else { 503 }, report))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn scrub_check(from_file: Option<io::Result<ScrubStatus>>, recorded: Option<&ScrubStatus>) -> Check {
+    let status = match from_file {
+        // No pass has finished yet.
+        Some(Err(e)) if e.kind() == io::ErrorKind::NotFound => recorded.cloned(),
+        Some(Err(e)) => {
+            return Check::new("scrub", CheckStatus::Degraded, format!("status file unreadable: {}", e));
+        }
+        Some(Ok(status)) => match recorded {
+            Some(recorded) if recorded.finished_unix > status.finished_unix => Some(recorded.clone()),
+            _ => Some(status),
+        },
+        None => recorded.cloned(),
+    };
+    let Some(status) = status else {
+        return Check::new("scrub", CheckStatus::Ok, "no scrub recorded");
+    };
+    let report = &status.report;
+    let left = if report.repaired {
+        report.irreparable()
+    } else {
+        report.damaged.len()
+    };
+    let detail = format!(
+        "pass finished at {}: {} checked, {} damaged, {} irreparable",
+        status.finished_unix,
+        report.chunks_checked,
+        report.damaged.len(),
+        report.irreparable()
+    );
+    let status = if report.irreparable() > 0 {
+        CheckStatus::Failing
+    } else if left > 0 {
+        CheckStatus::Degraded
+    } else {
+        CheckStatus::Ok
+    };
+    Check::new("scrub", status, detail)
+}
+
+/// Serve `/healthz` and `/readyz` for `health` on `listener`, one plain
+/// HTTP/1.1 request per connection, on a thread of its own.
+pub fn spawn(listener: TcpListener, health: Health) -> io::Result<JoinHandle<()>> {
+    std::thread::Builder::new()
+        .name("health".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A stalled or broken probe only loses its own answer.
+                let _ = answer(stream, &health);
+            }
+        })
+}
+
+fn answer(stream: std::net::TcpStream, health: &Health) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers; probes carry no body.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let (code, reason, body) = match (method, health.respond(path)) {
+        ("GET" | "HEAD", Some((code, report))) => (
+            code,
+            if code == 200 { "OK" } else { "Service Unavailable" },
+            serde_json::to_string(&report)?,
+        ),
+        ("GET" | "HEAD", None) => (404, "Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => (405, "Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        code,
+        reason,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(body.as_bytes())?;
+    }
+    stream.flush()
+}
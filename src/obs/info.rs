@@ -0,0 +1,229 @@
+//! Engram-level summary for `embeddenator info`.
+//!
+//! [`StorageFormat`] describes how an engram file is laid out on disk, read
+//! from its header alone. [`EngramInfo`] adds what can only be learned from
+//! the loaded engram: vector dimension and density, codebook size, and (when
+//! a manifest is available) the dedup and compression totals also reported
+//! by [`IngestStats`] and the provenance recorded at ingest, checked against
+//! what was loaded.
+
+use crate::append_log;
+use crate::bitsliced::simd_features_string;
+use crate::embrfs::{Engram, Manifest};
+use crate::envelope::{CompressionCodec, EnvelopeHeader, PayloadKind, HEADER_LEN};
+use crate::hybrid::{HybridRepr, HybridThresholds};
+use crate::ingest_provenance::{IngestProvenance, ProvenanceIssue};
+use crate::ingest_stats::{IngestStats, StatsBucket};
+use crate::multipart;
+use crate::seal;
+use crate::shards;
+use crate::shared_codebook;
+use crate::vsa::DIM;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// On-disk layout of an engram file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageFormat {
+    /// `bincode` (bare, unenveloped), `envelope`, `rkyv`, `proto`,
+    /// `append-log`, `split`, `sharded` or `codebook-ref`.
+    pub container: String,
+    /// Version of the container format.
+    pub version: u32,
+    /// `none`, `zstd`, `lz4` or `zstd-dict`.
+    pub compression: String,
+    pub checksummed: bool,
+    pub encrypted: bool,
+    /// Sealed against changes (see [`crate::seal`]); the other fields
+    /// describe the sealed file.
+    #[serde(default)]
+    pub sealed: bool,
+    /// Bytes on disk, summed over all parts or shards.
+    pub file_bytes: u64,
+}
+
+impl StorageFormat {
+    /// Read the format of the engram at `path` from its header.
+    pub fn detect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if append_log::is_append_log(path)? {
+            return Ok(StorageFormat {
+                container: "append-log".to_string(),
+                version: append_log::VERSION as u32,
+                compression: "none".to_string(),
+                checksummed: true,
+                file_bytes: fs::metadata(path)?.len(),
+                ..Default::default()
+            });
+        }
+
+        let split = multipart::is_part_index(path)?;
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        multipart::open_spanning(path)?
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        let sealed = match seal::read_sealed_prefix(path)? {
+            Some((_, sealed_header)) => {
+                header = sealed_header;
+                true
+            }
+            None => false,
+        };
+        let mut format = match EnvelopeHeader::parse(&header) {
+            Some(h) => StorageFormat {
+                container: match h.kind {
+                    PayloadKind::EngramRkyv => "rkyv",
+                    PayloadKind::EngramProto => "proto",
+                    _ => "envelope",
+                }
+                .to_string(),
+                version: 1,
+                compression: codec_name(h.compression).to_string(),
+                checksummed: h.checksummed,
+                encrypted: h.encrypted,
+                sealed,
+                file_bytes: 0,
+            },
+            None => StorageFormat {
+                container: "bincode".to_string(),
+                compression: "none".to_string(),
+                sealed,
+                ..Default::default()
+            },
+        };
+        if PayloadKind::sniff(&header) == Some(PayloadKind::ShardIndex) {
+            // Encrypted indexes can't be read without keys, so size the
+            // shards from the directory rather than from the index.
+            format.container = "sharded".to_string();
+            format.version = shards::SHARD_INDEX_VERSION;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            format.file_bytes = fs::metadata(path)?.len();
+            for file in shards::existing_shard_files(path)? {
+                format.file_bytes += fs::metadata(dir.join(file))?.len();
+            }
+        } else if PayloadKind::sniff(&header) == Some(PayloadKind::CodebookRef) {
+            // The codebook is shared, so only the reference counts.
+            format.container = "codebook-ref".to_string();
+            format.version = shared_codebook::SHARED_CODEBOOK_VERSION;
+            format.file_bytes = fs::metadata(path)?.len();
+        } else if split {
+            let index = multipart::read_part_index(path)?;
+            format.container = "split".to_string();
+            format.version = index.version;
+            format.file_bytes = index.total_len;
+        } else {
+            format.file_bytes = fs::metadata(path)?.len();
+        }
+        Ok(format)
+    }
+}
+
+fn codec_name(codec: CompressionCodec) -> &'static str {
+    match codec {
+        CompressionCodec::None => "none",
+        CompressionCodec::Zstd => "zstd",
+        CompressionCodec::Lz4 => "lz4",
+        CompressionCodec::ZstdDict => "zstd-dict",
+    }
+}
+
+/// Summary of one engram.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngramInfo {
+    /// Version of this library.
+    pub library_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageFormat>,
+    pub dimension: usize,
+    /// Representation the hybrid dispatcher picks for the root vector:
+    /// `sparse`, `bitsliced` or `block-sparse`. Codebook vectors are stored
+    /// sparse.
+    pub root_representation: String,
+    /// Non-zero trits in the root vector.
+    pub root_nnz: usize,
+    pub root_density: f64,
+    /// Chunk vectors in the codebook.
+    pub chunks: usize,
+    /// Chunks with a stored correction.
+    pub corrections: usize,
+    /// Mean non-zero trits per chunk vector.
+    pub mean_chunk_nnz: f64,
+    pub mean_chunk_density: f64,
+    /// Estimated heap bytes of the loaded codebook.
+    pub codebook_bytes: u64,
+    /// Dedup and compression totals; needs the manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totals: Option<StatsBucket>,
+    /// SIMD features detected on this host.
+    pub simd: String,
+    /// Provenance recorded at ingest; needs the manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<IngestProvenance>,
+    /// Recorded digests that no longer match the engram and manifest;
+    /// present whenever `provenance` is and could be checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance_issues: Option<Vec<ProvenanceIssue>>,
+}
+
+impl EngramInfo {
+    pub fn compute(engram: &Engram, manifest: Option<&Manifest>) -> Self {
+        let root_nnz = engram.root.pos.len() + engram.root.neg.len();
+        let chunk_nnz: usize = engram
+            .codebook
+            .values()
+            .map(|v| v.pos.len() + v.neg.len())
+            .sum();
+        let chunks = engram.codebook.len();
+        let mean_chunk_nnz = if chunks == 0 {
+            0.0
+        } else {
+            chunk_nnz as f64 / chunks as f64
+        };
+        let root_representation = repr_name(HybridThresholds::BUILT_IN.select(DIM, root_nnz as f64 / DIM as f64));
+        let provenance = manifest.and_then(|m| m.provenance.as_deref().cloned());
+        let provenance_issues = match (&provenance, manifest) {
+            (Some(p), Some(m)) => p.check(engram, m).ok(),
+            _ => None,
+        };
+        EngramInfo {
+            library_version: env!("CARGO_PKG_VERSION").to_string(),
+            storage: None,
+            dimension: DIM,
+            root_representation: root_representation.to_string(),
+            root_nnz,
+            root_density: root_nnz as f64 / DIM as f64,
+            chunks,
+            corrections: engram.corrections.iter().count(),
+            mean_chunk_nnz,
+            mean_chunk_density: mean_chunk_nnz / DIM as f64,
+            codebook_bytes: engram.memory_usage().codebook_bytes,
+            totals: manifest.map(|m| IngestStats::compute(engram, m).total),
+            simd: simd_features_string(),
+            provenance,
+            provenance_issues,
+        }
+    }
+
+    /// Attach the on-disk format of the file the engram was loaded from.
+    pub fn with_storage(mut self, storage: StorageFormat) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Report the root representation `thresholds` select instead of the
+    /// built-in one.
+    pub fn with_thresholds(mut self, thresholds: &HybridThresholds) -> Self {
+        self.root_representation = repr_name(thresholds.select(self.dimension, self.root_density)).to_string();
+        self
+    }
+}
+
+fn repr_name(repr: HybridRepr) -> &'static str {
+    match repr {
+        HybridRepr::Sparse => "sparse",
+        HybridRepr::Bitsliced => "bitsliced",
+        HybridRepr::BlockSparse => "block-sparse",
+    }
+}
@@ -0,0 +1,95 @@
+//! Lock-free latency histograms.
+//!
+//! A [`LatencyHistogram`] counts durations in power-of-two microsecond
+//! buckets with relaxed atomics, so any number of threads can record into
+//! one without contention beyond the cache line. Quantiles read from a
+//! [`LatencySnapshot`] are the upper bound of the bucket they fall in,
+//! accurate to within a factor of two, which is enough to tell a cache hit
+//! from a decode from a stall on disk.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Buckets per histogram. Bucket 0 counts durations under 1µs and bucket
+/// `i` those in `[2^(i-1), 2^i)` µs; the last also takes anything longer.
+pub const LATENCY_BUCKETS: usize = 32;
+
+/// Durations recorded from any thread.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS],
+            total_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, dur: Duration) {
+        let ns = dur.as_nanos().min(u128::from(u64::MAX)) as u64;
+        let bucket = (u64::BITS - (ns / 1_000).leading_zeros()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.total_ns.fetch_add(ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let buckets: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        LatencySnapshot {
+            count: buckets.iter().sum(),
+            total_ns: self.total_ns.load(Ordering::Relaxed),
+            max_ns: self.max_ns.load(Ordering::Relaxed),
+            buckets,
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts read from a [`LatencyHistogram`] at one moment.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub total_ns: u64,
+    pub max_ns: u64,
+    /// Count per bucket; see [`LATENCY_BUCKETS`].
+    pub buckets: Vec<u64>,
+}
+
+impl LatencySnapshot {
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.total_ns.checked_div(self.count).unwrap_or(0))
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_ns)
+    }
+
+    /// Upper bound of the bucket holding quantile `q` (0 to 1), capped at
+    /// the largest duration seen; zero when nothing was recorded.
+    pub fn quantile(&self, q: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Duration::from_micros(1 << i).min(self.max());
+            }
+        }
+        self.max()
+    }
+}
@@ -1,29 +1,215 @@
-#[cfg(feature = "logging")]
-use std::io;
+//! Structured logs, and the correlation ids tying them to one operation.
+//!
+//! With the `logging` feature the library reports what it does as `tracing`
+//! events. Each carries a `subsystem` field (`fs`, `retrieval`, `io`,
+//! `http`, ...) and the fields of the [`Operation`] it happened under: its
+//! `operation` name and `correlation_id`. One CLI command or server request
+//! can so be followed from its entry point through every layer it touched.
+//! [`init_with`] installs a subscriber printing the events to stderr, as text
+//! or as one JSON object per line; the CLI takes the level and format from
+//! the `log_level` and `log_format` settings of its configuration file.
+//! Without the feature events are dropped, and [`warn`] prints to stderr.
+//!
+//! An operation is entered on the thread doing its work with
+//! [`Operation::enter`]. Work handed to other threads takes it along by
+//! entering [`current`] there; with `async`, [`scope`] holds one over a
+//! future across its awaits.
 
-/// Initialize structured logging.
-///
-/// Behavior:
-/// - With `--features logging`: installs a `tracing_subscriber` configured by
-///   `EMBEDDENATOR_LOG` or `RUST_LOG`.
-/// - Without the feature: no-op.
-///
-/// By default (no env var), logging is disabled.
-#[cfg(feature = "logging")]
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// How [`init_with`] prints events.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One human-readable line per event, operation fields first.
+    #[default]
+    Text,
+    /// One JSON object per event: `timestamp` (Unix seconds), `level`,
+    /// `target`, the fields of the enclosing operations, then the event's
+    /// own fields, `message` among them.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {:?} (expected text or json)", s)),
+        }
+    }
+}
+
+/// What [`init_with`] installs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogConfig {
+    /// `tracing` filter directives such as `info` or
+    /// `embeddenator=debug`; logging stays off when `None`.
+    /// `EMBEDDENATOR_LOG` or `RUST_LOG` replace it when set.
+    pub level: Option<String>,
+    pub format: LogFormat,
+}
+
+/// Initialize structured logging from `EMBEDDENATOR_LOG` or `RUST_LOG`
+/// alone, as text; see [`init_with`].
 pub fn init() {
+    init_with(&LogConfig::default());
+}
+
+/// Install the subscriber `config` describes, once per process; later
+/// calls do nothing. Without the `logging` feature this is a no-op.
+#[cfg(feature = "logging")]
+pub fn init_with(config: &LogConfig) {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::EnvFilter;
+
     let filter = std::env::var("EMBEDDENATOR_LOG")
         .ok()
         .or_else(|| std::env::var("RUST_LOG").ok())
+        .or_else(|| config.level.clone())
         .unwrap_or_else(|| "off".to_string());
+    let registry = tracing_subscriber::registry().with(EnvFilter::new(filter));
+    let _ = match config.format {
+        LogFormat::Text => registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .try_init(),
+        LogFormat::Json => registry.with(json::JsonLayer).try_init(),
+    };
+}
+
+#[cfg(not(feature = "logging"))]
+pub fn init_with(_config: &LogConfig) {}
+
+/// One unit of work (a CLI command, a server request) whose events share a
+/// correlation id.
+#[derive(Clone, Debug)]
+pub struct Operation {
+    name: Arc<str>,
+    id: Arc<str>,
+    #[cfg(feature = "logging")]
+    span: tracing::Span,
+}
+
+impl Operation {
+    /// Operation `name` under a fresh random id.
+    pub fn new(name: impl Into<Arc<str>>) -> Self {
+        Self::with_id(name, format!("{:016x}", rand::random::<u64>()))
+    }
+
+    /// Operation `name` under an id chosen elsewhere, e.g. the
+    /// `X-Request-Id` of the request that started it.
+    pub fn with_id(name: impl Into<Arc<str>>, id: impl Into<Arc<str>>) -> Self {
+        let (name, id) = (name.into(), id.into());
+        Self {
+            #[cfg(feature = "logging")]
+            span: tracing::info_span!(target: "embeddenator", "operation", operation = %name, correlation_id = %id),
+            name,
+            id,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The correlation id every event under this operation carries.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The span events under this operation are recorded in, for
+    /// frameworks that enter spans themselves.
+    #[cfg(feature = "logging")]
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Make this the current operation of this thread until the guard is
+    /// dropped.
+    pub fn enter(&self) -> OperationGuard {
+        CURRENT.with(|stack| stack.borrow_mut().push(self.clone()));
+        OperationGuard {
+            #[cfg(feature = "logging")]
+            _span: self.span.clone().entered(),
+            _thread: PhantomData,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Vec<Operation>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(feature = "async")]
+tokio::task_local! {
+    static TASK_OPERATION: Operation;
+}
 
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_writer(io::stderr)
-        .try_init();
+/// Keeps an [`Operation`] current until dropped; see [`Operation::enter`].
+#[must_use = "the operation is current until the guard is dropped"]
+pub struct OperationGuard {
+    #[cfg(feature = "logging")]
+    _span: tracing::span::EnteredSpan,
+    /// Guards are dropped on the thread that entered them.
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|stack| stack.borrow_mut().pop());
+    }
+}
+
+/// The innermost operation entered on this thread, or else the one
+/// [`scope`] holds over the running task.
+pub fn current() -> Option<Operation> {
+    let entered = CURRENT.with(|stack| stack.borrow().last().cloned());
+    #[cfg(feature = "async")]
+    let entered = entered.or_else(|| TASK_OPERATION.try_with(Operation::clone).ok());
+    entered
+}
+
+/// Run `future` with `op` as its current operation across every await.
+#[cfg(feature = "async")]
+pub async fn scope<F: std::future::Future>(op: Operation, future: F) -> F::Output {
+    #[cfg(feature = "logging")]
+    let future = tracing::Instrument::instrument(future, op.span.clone());
+    TASK_OPERATION.scope(op, future).await
+}
+
+/// Report an event of `subsystem` at info level.
+#[cfg(feature = "logging")]
+pub fn info(subsystem: &'static str, message: fmt::Arguments<'_>) {
+    tracing::info!(target: "embeddenator", subsystem, "{}", message);
+}
+
+#[cfg(not(feature = "logging"))]
+pub fn info(_subsystem: &'static str, _message: fmt::Arguments<'_>) {}
+
+/// Report an event of `subsystem` at debug level.
+#[cfg(feature = "logging")]
+pub fn debug(subsystem: &'static str, message: fmt::Arguments<'_>) {
+    tracing::debug!(target: "embeddenator", subsystem, "{}", message);
 }
 
 #[cfg(not(feature = "logging"))]
-pub fn init() {}
+pub fn debug(_subsystem: &'static str, _message: fmt::Arguments<'_>) {}
+
+/// Progress of a long operation: printed on stdout when the caller asked
+/// for `verbose` output, and reported as an info event either way.
+pub fn progress(verbose: bool, subsystem: &'static str, message: fmt::Arguments<'_>) {
+    if verbose {
+        println!("{}", message);
+    }
+    info(subsystem, message);
+}
 
 /// Emit a warning in the best available way.
 ///
@@ -39,3 +225,90 @@ pub fn warn(message: &str) {
 pub fn warn(message: &str) {
     eprintln!("{message}");
 }
+
+#[cfg(feature = "logging")]
+mod json {
+    //! A `tracing` layer printing one JSON object per event.
+
+    use serde_json::{Map, Value};
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::Context;
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    pub struct JsonLayer;
+
+    /// Fields recorded on a span, kept in its extensions.
+    struct SpanFields(Map<String, Value>);
+
+    struct Visitor<'a>(&'a mut Map<String, Value>);
+
+    impl Visit for Visitor<'_> {
+        fn record_i64(&mut self, field: &Field, value: i64) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_bool(&mut self, field: &Field, value: bool) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for JsonLayer {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Map::new();
+            attrs.record(&mut Visitor(&mut fields));
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(SpanFields(fields));
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+                    values.record(&mut Visitor(&mut fields.0));
+                }
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let meta = event.metadata();
+            let mut line = Map::new();
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64());
+            line.insert("timestamp".into(), now.into());
+            line.insert("level".into(), meta.level().as_str().into());
+            line.insert("target".into(), meta.target().into());
+            if let Some(scope) = ctx.event_scope(event) {
+                for span in scope.from_root() {
+                    if let Some(fields) = span.extensions().get::<SpanFields>() {
+                        line.extend(fields.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    }
+                }
+            }
+            event.record(&mut Visitor(&mut line));
+            let mut out = Value::Object(line).to_string();
+            out.push('\n');
+            // Like the text formatter, a failed write to stderr is dropped.
+            let _ = std::io::stderr().lock().write_all(out.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Memory accounting for loaded engrams.
+//!
+//! [`MemoryUsage`] breaks the heap footprint of an engram and the structures
+//! around it into parts, so hosts can be sized from the data instead of from
+//! process RSS. Figures are estimates derived from container capacities
+//! (allocator overhead and fragmentation are not included), and are cheap
+//! enough to compute on demand: one pass over the codebook and manifest.
+//!
+//! Sources:
+//! - [`EmbrFS::memory_usage`](crate::embrfs::EmbrFS::memory_usage)
+//! - [`Engram::memory_usage`](crate::embrfs::Engram::memory_usage)
+//! - [`EngramFS::memory_usage`](crate::fuse_shim::EngramFS::memory_usage)
+//! - [`TernaryInvertedIndex::memory_bytes`](crate::retrieval::TernaryInvertedIndex::memory_bytes)
+//!
+//! [`MemoryUsage::publish`] copies a breakdown into the process metrics
+//! (see [`crate::metrics`]) as gauges.
+
+use crate::metrics::metrics;
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::AddAssign;
+
+/// Estimated heap bytes per structure.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// Chunk vectors and the map holding them.
+    pub codebook_bytes: u64,
+    /// The root bundle vector.
+    pub root_bytes: u64,
+    /// Per-chunk correction records.
+    pub corrections_bytes: u64,
+    /// File entries, paths and chunk lists (or the inode tables of a mount).
+    pub manifest_bytes: u64,
+    /// Lookup structures: dedup chunk index, semantic signatures, inverted
+    /// indices and resonator patterns.
+    pub index_bytes: u64,
+    /// Decoded data held for reuse, such as the chunk cache of a mount.
+    pub cache_bytes: u64,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.codebook_bytes
+            + self.root_bytes
+            + self.corrections_bytes
+            + self.manifest_bytes
+            + self.index_bytes
+            + self.cache_bytes
+    }
+
+    /// Record this breakdown as the current memory gauges in
+    /// [`metrics`](crate::metrics::metrics). No-op without the `metrics`
+    /// feature.
+    pub fn publish(&self) {
+        metrics().set_memory_usage(self);
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.codebook_bytes += other.codebook_bytes;
+        self.root_bytes += other.root_bytes;
+        self.corrections_bytes += other.corrections_bytes;
+        self.manifest_bytes += other.manifest_bytes;
+        self.index_bytes += other.index_bytes;
+        self.cache_bytes += other.cache_bytes;
+    }
+}
+
+/// Heap bytes of a vector's buffer, excluding what its elements own.
+pub(crate) fn vec_bytes<T>(v: &Vec<T>) -> u64 {
+    (v.capacity() * size_of::<T>()) as u64
+}
+
+/// Heap bytes of a hash table's buckets and control bytes, excluding what
+/// keys and values own. Tables keep 1/8 of their buckets free.
+pub(crate) fn map_bytes<K, V, S>(map: &HashMap<K, V, S>) -> u64 {
+    let buckets = map.capacity() * 8 / 7;
+    (buckets * (size_of::<(K, V)>() + 1)) as u64
+}
+
+/// Heap bytes one `(K, V)` entry takes in a hash table, buckets left free
+/// included.
+pub(crate) fn slot_bytes<K, V>() -> u64 {
+    ((size_of::<(K, V)>() + 1) * 8 / 7) as u64
+}
+
+pub(crate) fn sparse_vec_bytes(v: &SparseVec) -> u64 {
+    vec_bytes(&v.pos) + vec_bytes(&v.neg)
+}
+
+/// Heap bytes of `map` plus the vectors it holds.
+pub(crate) fn vector_map_bytes<K, S>(map: &HashMap<K, SparseVec, S>) -> u64 {
+    map_bytes(map) + map.values().map(sparse_vec_bytes).sum::<u64>()
+}
@@ -1,3 +1,4 @@
+use crate::memory::MemoryUsage;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
@@ -19,6 +20,14 @@ pub struct MetricsSnapshot {
     pub index_cache_misses: u64,
     pub index_cache_evictions: u64,
 
+    pub chunk_cache_hits: u64,
+    pub chunk_cache_misses: u64,
+    pub chunk_cache_evictions: u64,
+
+    pub query_cache_hits: u64,
+    pub query_cache_misses: u64,
+    pub query_cache_evictions: u64,
+
     pub retrieval_query_calls: u64,
     pub retrieval_query_ns_total: u64,
     pub retrieval_query_ns_max: u64,
@@ -30,6 +39,19 @@ pub struct MetricsSnapshot {
     pub hier_query_calls: u64,
     pub hier_query_ns_total: u64,
     pub hier_query_ns_max: u64,
+
+    pub simd_paths_disabled: u64,
+
+    pub scrub_chunks_checked: u64,
+    pub scrub_chunks_repaired: u64,
+    pub scrub_chunks_irreparable: u64,
+
+    pub memory_codebook_bytes: u64,
+    pub memory_root_bytes: u64,
+    pub memory_corrections_bytes: u64,
+    pub memory_manifest_bytes: u64,
+    pub memory_index_bytes: u64,
+    pub memory_cache_bytes: u64,
 }
 
 pub struct Metrics {
@@ -49,6 +71,14 @@ pub struct Metrics {
     index_cache_misses: AtomicU64,
     index_cache_evictions: AtomicU64,
 
+    chunk_cache_hits: AtomicU64,
+    chunk_cache_misses: AtomicU64,
+    chunk_cache_evictions: AtomicU64,
+
+    query_cache_hits: AtomicU64,
+    query_cache_misses: AtomicU64,
+    query_cache_evictions: AtomicU64,
+
     retrieval_query_calls: AtomicU64,
     retrieval_query_ns_total: AtomicU64,
     retrieval_query_ns_max: AtomicU64,
@@ -60,6 +90,20 @@ pub struct Metrics {
     hier_query_calls: AtomicU64,
     hier_query_ns_total: AtomicU64,
     hier_query_ns_max: AtomicU64,
+
+    simd_paths_disabled: AtomicU64,
+
+    scrub_chunks_checked: AtomicU64,
+    scrub_chunks_repaired: AtomicU64,
+    scrub_chunks_irreparable: AtomicU64,
+
+    // Gauges: last breakdown passed to `set_memory_usage`.
+    memory_codebook_bytes: AtomicU64,
+    memory_root_bytes: AtomicU64,
+    memory_corrections_bytes: AtomicU64,
+    memory_manifest_bytes: AtomicU64,
+    memory_index_bytes: AtomicU64,
+    memory_cache_bytes: AtomicU64,
 }
 
 impl Metrics {
@@ -81,6 +125,14 @@ impl Metrics {
             index_cache_misses: AtomicU64::new(0),
             index_cache_evictions: AtomicU64::new(0),
 
+            chunk_cache_hits: AtomicU64::new(0),
+            chunk_cache_misses: AtomicU64::new(0),
+            chunk_cache_evictions: AtomicU64::new(0),
+
+            query_cache_hits: AtomicU64::new(0),
+            query_cache_misses: AtomicU64::new(0),
+            query_cache_evictions: AtomicU64::new(0),
+
             retrieval_query_calls: AtomicU64::new(0),
             retrieval_query_ns_total: AtomicU64::new(0),
             retrieval_query_ns_max: AtomicU64::new(0),
@@ -92,6 +144,19 @@ impl Metrics {
             hier_query_calls: AtomicU64::new(0),
             hier_query_ns_total: AtomicU64::new(0),
             hier_query_ns_max: AtomicU64::new(0),
+
+            simd_paths_disabled: AtomicU64::new(0),
+
+            scrub_chunks_checked: AtomicU64::new(0),
+            scrub_chunks_repaired: AtomicU64::new(0),
+            scrub_chunks_irreparable: AtomicU64::new(0),
+
+            memory_codebook_bytes: AtomicU64::new(0),
+            memory_root_bytes: AtomicU64::new(0),
+            memory_corrections_bytes: AtomicU64::new(0),
+            memory_manifest_bytes: AtomicU64::new(0),
+            memory_index_bytes: AtomicU64::new(0),
+            memory_cache_bytes: AtomicU64::new(0),
         }
     }
 
@@ -113,6 +178,14 @@ impl Metrics {
             index_cache_misses: self.index_cache_misses.load(Ordering::Relaxed),
             index_cache_evictions: self.index_cache_evictions.load(Ordering::Relaxed),
 
+            chunk_cache_hits: self.chunk_cache_hits.load(Ordering::Relaxed),
+            chunk_cache_misses: self.chunk_cache_misses.load(Ordering::Relaxed),
+            chunk_cache_evictions: self.chunk_cache_evictions.load(Ordering::Relaxed),
+
+            query_cache_hits: self.query_cache_hits.load(Ordering::Relaxed),
+            query_cache_misses: self.query_cache_misses.load(Ordering::Relaxed),
+            query_cache_evictions: self.query_cache_evictions.load(Ordering::Relaxed),
+
             retrieval_query_calls: self.retrieval_query_calls.load(Ordering::Relaxed),
             retrieval_query_ns_total: self.retrieval_query_ns_total.load(Ordering::Relaxed),
             retrieval_query_ns_max: self.retrieval_query_ns_max.load(Ordering::Relaxed),
@@ -124,6 +197,19 @@ impl Metrics {
             hier_query_calls: self.hier_query_calls.load(Ordering::Relaxed),
             hier_query_ns_total: self.hier_query_ns_total.load(Ordering::Relaxed),
             hier_query_ns_max: self.hier_query_ns_max.load(Ordering::Relaxed),
+
+            simd_paths_disabled: self.simd_paths_disabled.load(Ordering::Relaxed),
+
+            scrub_chunks_checked: self.scrub_chunks_checked.load(Ordering::Relaxed),
+            scrub_chunks_repaired: self.scrub_chunks_repaired.load(Ordering::Relaxed),
+            scrub_chunks_irreparable: self.scrub_chunks_irreparable.load(Ordering::Relaxed),
+
+            memory_codebook_bytes: self.memory_codebook_bytes.load(Ordering::Relaxed),
+            memory_root_bytes: self.memory_root_bytes.load(Ordering::Relaxed),
+            memory_corrections_bytes: self.memory_corrections_bytes.load(Ordering::Relaxed),
+            memory_manifest_bytes: self.memory_manifest_bytes.load(Ordering::Relaxed),
+            memory_index_bytes: self.memory_index_bytes.load(Ordering::Relaxed),
+            memory_cache_bytes: self.memory_cache_bytes.load(Ordering::Relaxed),
         }
     }
 
@@ -209,6 +295,48 @@ impl Metrics {
         }
     }
 
+    pub fn inc_chunk_cache_hit(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.chunk_cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn inc_chunk_cache_miss(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.chunk_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn inc_chunk_cache_eviction(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.chunk_cache_evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn inc_query_cache_hit(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.query_cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn inc_query_cache_miss(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.query_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn inc_query_cache_eviction(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.query_cache_evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     pub fn record_retrieval_query(&self, _dur: Duration) {
         #[cfg(feature = "metrics")]
         {
@@ -233,6 +361,36 @@ impl Metrics {
         }
     }
 
+    /// A SIMD path was turned off by the `simd` self-test.
+    pub fn inc_simd_path_disabled(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.simd_paths_disabled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Chunks verified, repaired and found beyond repair by one scrub pass.
+    pub fn record_scrub(&self, _checked: u64, _repaired: u64, _irreparable: u64) {
+        #[cfg(feature = "metrics")]
+        {
+            self.scrub_chunks_checked.fetch_add(_checked, Ordering::Relaxed);
+            self.scrub_chunks_repaired.fetch_add(_repaired, Ordering::Relaxed);
+            self.scrub_chunks_irreparable.fetch_add(_irreparable, Ordering::Relaxed);
+        }
+    }
+
+    pub fn set_memory_usage(&self, _usage: &MemoryUsage) {
+        #[cfg(feature = "metrics")]
+        {
+            self.memory_codebook_bytes.store(_usage.codebook_bytes, Ordering::Relaxed);
+            self.memory_root_bytes.store(_usage.root_bytes, Ordering::Relaxed);
+            self.memory_corrections_bytes.store(_usage.corrections_bytes, Ordering::Relaxed);
+            self.memory_manifest_bytes.store(_usage.manifest_bytes, Ordering::Relaxed);
+            self.memory_index_bytes.store(_usage.index_bytes, Ordering::Relaxed);
+            self.memory_cache_bytes.store(_usage.cache_bytes, Ordering::Relaxed);
+        }
+    }
+
     pub fn record_hier_query(&self, _dur: Duration) {
         #[cfg(feature = "metrics")]
         {
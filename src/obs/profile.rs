@@ -0,0 +1,217 @@
+//! Scoped timers that add up into a call tree, for flamegraphs.
+//!
+//! `let _t = profile::scope("bundle_many");` times everything up to the end
+//! of the enclosing block. Scopes opened while another is open on the same
+//! thread nest under it, so the timings form a tree keyed by the stack of
+//! names above each scope. [`Profile::folded`] writes that tree in the
+//! folded-stack format `flamegraph.pl` and `inferno-flamegraph` read: one
+//! `outer;inner;leaf <self µs>` line per stack, with the time spent in the
+//! scope itself and not in the scopes under it.
+//!
+//! Profiling is off until [`enable`] is called, and a scope then costs one
+//! relaxed atomic load. Enabled, it takes two clock reads and a thread-local
+//! update; a thread's timings are merged into the process-wide profile each
+//! time its outermost scope closes. To bound the cost on hot paths,
+//! [`enable`] can sample: with `sample_every = n` only every `n`-th
+//! outermost scope is timed, together with everything under it, so the
+//! profile keeps its shape at `1/n` of the time recorded.
+//!
+//! The CLI's global `--flamegraph FILE` option profiles one command this way.
+
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SAMPLE_EVERY: AtomicU64 = AtomicU64::new(1);
+/// Outermost scopes opened so far, for sampling.
+static ROOTS: AtomicU64 = AtomicU64::new(0);
+static PROFILE: Mutex<BTreeMap<Vec<&'static str>, ScopeStats>> = Mutex::new(BTreeMap::new());
+
+/// Time one scope at every stack it appears under.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ScopeStats {
+    pub calls: u64,
+    /// Time from opening to closing, summed over calls.
+    pub total_ns: u64,
+    /// `total_ns` less the time spent in scopes nested under it.
+    pub self_ns: u64,
+}
+
+impl ScopeStats {
+    fn merge(&mut self, other: &ScopeStats) {
+        self.calls += other.calls;
+        self.total_ns += other.total_ns;
+        self.self_ns += other.self_ns;
+    }
+}
+
+struct Frame {
+    name: &'static str,
+    start: Instant,
+    child_ns: u64,
+}
+
+#[derive(Default)]
+struct ThreadState {
+    frames: Vec<Frame>,
+    /// Depth of scopes opened under an outermost scope left out by sampling.
+    skipped: usize,
+    /// Timings not yet merged into [`PROFILE`].
+    pending: HashMap<Vec<&'static str>, ScopeStats>,
+}
+
+thread_local! {
+    static STATE: RefCell<ThreadState> = RefCell::new(ThreadState::default());
+}
+
+/// Start profiling, timing every `sample_every`-th outermost scope (every
+/// one for `1`). Timings from earlier runs are kept; see [`reset`].
+pub fn enable(sample_every: u64) {
+    SAMPLE_EVERY.store(sample_every.max(1), Ordering::Relaxed);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stop profiling. Scopes already open still record when they close.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Forget every timing recorded so far.
+pub fn reset() {
+    lock().clear();
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<Vec<&'static str>, ScopeStats>> {
+    // Merging is plain additions; a panic elsewhere leaves nothing half-done.
+    PROFILE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Timings merged so far, from every thread whose outermost scope closed.
+pub fn snapshot() -> Profile {
+    Profile {
+        stacks: lock().iter().map(|(stack, &stats)| (stack.join(";"), stats)).collect(),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Off,
+    Skipped,
+    Timed,
+}
+
+/// An open scope, timed until it is dropped; see [`scope`].
+#[must_use = "a scope is timed until it is dropped"]
+pub struct Scope {
+    kind: Kind,
+    /// Scopes close on the thread that opened them.
+    _thread: PhantomData<*const ()>,
+}
+
+/// Open a scope named `name`, nested under any open on this thread.
+pub fn scope(name: &'static str) -> Scope {
+    let off = Scope {
+        kind: Kind::Off,
+        _thread: PhantomData,
+    };
+    if !ENABLED.load(Ordering::Relaxed) {
+        return off;
+    }
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if state.skipped > 0 {
+            state.skipped += 1;
+            return Scope { kind: Kind::Skipped, ..off };
+        }
+        if state.frames.is_empty() {
+            let n = ROOTS.fetch_add(1, Ordering::Relaxed);
+            if !n.is_multiple_of(SAMPLE_EVERY.load(Ordering::Relaxed)) {
+                state.skipped = 1;
+                return Scope { kind: Kind::Skipped, ..off };
+            }
+        }
+        state.frames.push(Frame {
+            name,
+            start: Instant::now(),
+            child_ns: 0,
+        });
+        Scope { kind: Kind::Timed, ..off }
+    })
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        match self.kind {
+            Kind::Off => {}
+            Kind::Skipped => STATE.with(|state| state.borrow_mut().skipped -= 1),
+            Kind::Timed => STATE.with(|state| {
+                let mut state = state.borrow_mut();
+                let stack: Vec<&'static str> = state.frames.iter().map(|f| f.name).collect();
+                let Some(frame) = state.frames.pop() else { return };
+                let total_ns = frame.start.elapsed().as_nanos().min(u128::from(u64::MAX)) as u64;
+                if let Some(parent) = state.frames.last_mut() {
+                    parent.child_ns += total_ns;
+                }
+                state.pending.entry(stack).or_default().merge(&ScopeStats {
+                    calls: 1,
+                    total_ns,
+                    self_ns: total_ns.saturating_sub(frame.child_ns),
+                });
+                if state.frames.is_empty() {
+                    let mut profile = lock();
+                    for (stack, stats) in state.pending.drain() {
+                        profile.entry(stack).or_default().merge(&stats);
+                    }
+                }
+            }),
+        }
+    }
+}
+
+/// Timings read by [`snapshot`], by stack of scope names joined with `;`,
+/// outermost first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Profile {
+    pub stacks: BTreeMap<String, ScopeStats>,
+}
+
+impl Profile {
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    /// Time spent under the scopes named `name` wherever they appear, not
+    /// counting them twice where they nest in one another.
+    pub fn total_ns(&self, name: &str) -> u64 {
+        self.stacks
+            .iter()
+            .filter(|(stack, _)| {
+                let mut names = stack.rsplit(';');
+                names.next() == Some(name) && !names.any(|n| n == name)
+            })
+            .map(|(_, stats)| stats.total_ns)
+            .sum()
+    }
+
+    /// The profile in folded-stack format: one `stack self_µs` line per
+    /// stack, leaving out stacks with no self time of a whole microsecond.
+    pub fn folded(&self) -> String {
+        let mut out = String::new();
+        for (stack, stats) in &self.stacks {
+            let us = stats.self_ns / 1_000;
+            if us > 0 {
+                out.push_str(&format!("{} {}\n", stack, us));
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,205 @@
+//! Where each reconstructed chunk came from, and how far to trust it.
+//!
+//! Extraction rebuilds a chunk in one of three ways: by decoding its
+//! codebook vector alone, by applying a binary delta to an earlier chunk
+//! ([`CorrectionType::Delta`]), or by decoding the vector and patching the
+//! result with a stored correction. [`ChunkProvenance`] records which, with
+//! a confidence score: 1 for a chunk that matches the hash recorded at
+//! ingest, 0 for one that does not or could not be rebuilt, and for chunks
+//! with no recorded hash (engrams from before the correction store) the
+//! cosine between the stored vector and the re-encoded result, relative to
+//! the stored vector's cosine with itself (below 1 when a reversible
+//! encoding sets an index both ways).
+//!
+//! [`FileProvenance`] aggregates the chunks of one file and
+//! [`ExtractReport`] those of a whole extraction, as produced by
+//! [`EmbrFS::extract_with_provenance`](crate::EmbrFS::extract_with_provenance).
+
+use crate::correction::CorrectionType;
+use crate::embrfs::Engram;
+use crate::path_norm::PathCollision;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+
+/// How a chunk was reconstructed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkSource {
+    /// Decoded from its codebook vector with nothing to correct.
+    Codebook,
+    /// Rebuilt from an earlier chunk and a binary delta.
+    Differential,
+    /// Decoded from its codebook vector and patched by a stored correction.
+    Corrected,
+    /// Neither a codebook vector nor a usable correction was found.
+    Missing,
+}
+
+/// Provenance of one reconstructed chunk.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChunkProvenance {
+    pub chunk_id: usize,
+    /// Bytes of the file the chunk covers.
+    pub len: usize,
+    pub source: ChunkSource,
+    /// Whether the bytes match the hash recorded at ingest; `None` when no
+    /// hash was recorded.
+    pub verified: Option<bool>,
+    /// From 0 to 1; see the module docs.
+    pub confidence: f64,
+}
+
+impl ChunkProvenance {
+    /// Provenance of chunk `chunk_id`, `len` bytes of the file at `path`,
+    /// which reconstructed to `data` (`None` if it could not be rebuilt).
+    pub fn of(
+        engram: &Engram,
+        chunk_id: usize,
+        path: &str,
+        len: usize,
+        data: Option<&[u8]>,
+        config: &ReversibleVSAConfig,
+    ) -> Self {
+        let correction = engram.corrections.get(chunk_id as u64);
+        let vector = engram.codebook.get(&chunk_id);
+        let Some(data) = data else {
+            return ChunkProvenance {
+                chunk_id,
+                len,
+                source: ChunkSource::Missing,
+                verified: None,
+                confidence: 0.0,
+            };
+        };
+        let source = match correction.map(|c| &c.correction) {
+            Some(CorrectionType::Delta { .. }) => ChunkSource::Differential,
+            Some(CorrectionType::None) | None => ChunkSource::Codebook,
+            Some(_) => ChunkSource::Corrected,
+        };
+        let verified = correction.map(|c| c.verify(data));
+        let confidence = match (verified, vector) {
+            (Some(ok), _) => f64::from(u8::from(ok)),
+            (None, Some(vector)) => {
+                let reencoded = SparseVec::encode_chunk(data, config, Some(path));
+                let own = vector.cosine(vector);
+                if own > 0.0 {
+                    (reencoded.cosine(vector) / own).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                }
+            }
+            (None, None) => 0.0,
+        };
+        ChunkProvenance {
+            chunk_id,
+            len,
+            source,
+            verified,
+            confidence,
+        }
+    }
+}
+
+/// Chunks per [`ChunkSource`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceCounts {
+    pub codebook: usize,
+    pub differential: usize,
+    pub corrected: usize,
+    pub missing: usize,
+}
+
+impl SourceCounts {
+    pub fn add(&mut self, source: ChunkSource) {
+        match source {
+            ChunkSource::Codebook => self.codebook += 1,
+            ChunkSource::Differential => self.differential += 1,
+            ChunkSource::Corrected => self.corrected += 1,
+            ChunkSource::Missing => self.missing += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.codebook + self.differential + self.corrected + self.missing
+    }
+}
+
+impl std::fmt::Display for SourceCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} codebook, {} differential, {} corrected, {} missing",
+            self.codebook, self.differential, self.corrected, self.missing
+        )
+    }
+}
+
+/// Provenance of every chunk of one file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FileProvenance {
+    pub path: String,
+    pub sources: SourceCounts,
+    /// Chunk confidence averaged over the file's bytes; 1 for an empty file.
+    pub confidence: f64,
+    /// Lowest chunk confidence; 1 for an empty file.
+    pub min_confidence: f64,
+    pub chunks: Vec<ChunkProvenance>,
+}
+
+impl FileProvenance {
+    pub fn new(path: impl Into<String>, chunks: Vec<ChunkProvenance>) -> Self {
+        let mut sources = SourceCounts::default();
+        for chunk in &chunks {
+            sources.add(chunk.source);
+        }
+        FileProvenance {
+            path: path.into(),
+            sources,
+            confidence: weighted_confidence(chunks.iter().map(|c| (c.len, c.confidence))),
+            min_confidence: chunks.iter().map(|c| c.confidence).fold(1.0, f64::min),
+            chunks,
+        }
+    }
+}
+
+/// Provenance of an extraction, file by file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtractReport {
+    pub sources: SourceCounts,
+    /// Chunk confidence averaged over every extracted byte.
+    pub confidence: f64,
+    /// Lowest file confidence.
+    pub min_confidence: f64,
+    pub files: Vec<FileProvenance>,
+    /// Paths the output directory's filesystem stored as one file; see
+    /// [`crate::path_norm`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collisions: Vec<PathCollision>,
+}
+
+impl ExtractReport {
+    pub fn new(files: Vec<FileProvenance>) -> Self {
+        let mut sources = SourceCounts::default();
+        for chunk in files.iter().flat_map(|f| &f.chunks) {
+            sources.add(chunk.source);
+        }
+        ExtractReport {
+            sources,
+            confidence: weighted_confidence(files.iter().flat_map(|f| &f.chunks).map(|c| (c.len, c.confidence))),
+            min_confidence: files.iter().map(|f| f.min_confidence).fold(1.0, f64::min),
+            files,
+            collisions: Vec::new(),
+        }
+    }
+}
+
+/// Mean of `(weight, confidence)` pairs by weight; 1 when there are none
+/// or they all weigh nothing.
+fn weighted_confidence(items: impl Iterator<Item = (usize, f64)>) -> f64 {
+    let (weight, sum) = items.fold((0usize, 0.0), |(w, s), (len, c)| (w + len, s + len as f64 * c));
+    if weight == 0 {
+        1.0
+    } else {
+        sum / weight as f64
+    }
+}
@@ -0,0 +1,219 @@
+//! Drift detection for live data feeds against a baseline engram.
+//!
+//! A [`StreamMonitor`] cuts an unbounded byte stream into chunks of the
+//! baseline's chunk size ([`DEFAULT_CHUNK_SIZE`] unless its manifest
+//! records another), encodes them as ingest would, and bundles every
+//! `window_chunks` of them into a window vector. Windows vote into a
+//! [`SoftTernaryVec`] sketch whose magnitudes drop by one every
+//! `decay_every` windows, so the sketch follows roughly the last
+//! `7 × decay_every` windows and forgets older data. After each window the
+//! sketch is compared with the reference root; a cosine below `low`
+//! (the stream no longer looks like the baseline) or above `high` (it looks
+//! suspiciously like a replay of it) flags the window.
+//!
+//! Only chunks whose vectors resemble the baseline's register. With
+//! [`ChunkEncoding::Similarity`](crate::vsa::ChunkEncoding::Similarity)
+//! that includes content close to the baseline; with reversible encoding,
+//! whose vectors also depend on the file path, only byte-identical chunks
+//! do. Stream chunks are encoded without a path.
+
+use crate::bitsliced::BitslicedTritVec;
+use crate::capacity;
+use crate::embrfs::{Engram, Manifest, DEFAULT_CHUNK_SIZE};
+use crate::soft_ternary::SoftTernaryVec;
+use crate::vsa::{ReversibleVSAConfig, SparseVec, DIM};
+use serde::{Deserialize, Serialize};
+
+/// Chunks bundled into each window by default: 64 KiB of stream.
+pub const DEFAULT_WINDOW_CHUNKS: usize = 16;
+
+/// Windows between decays of the sketch by default.
+pub const DEFAULT_DECAY_EVERY: usize = 2;
+
+/// Which side of the band a flagged window fell on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftAlert {
+    /// The sketch no longer resembles the reference.
+    Below,
+    /// The sketch resembles the reference more than `high` allows.
+    Above,
+}
+
+/// What the monitor saw in one window.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowReport {
+    /// Windows before this one.
+    pub index: u64,
+    /// Stream offset of the window's first byte.
+    pub offset: u64,
+    pub len: usize,
+    /// Cosine of this window alone with the reference.
+    pub window_similarity: f64,
+    /// Cosine of the decayed sketch, this window included, with the
+    /// reference. Alerts are raised on this.
+    pub similarity: f64,
+    pub alert: Option<DriftAlert>,
+}
+
+/// Watches a byte stream for drift away from (or towards) a reference
+/// vector.
+#[derive(Clone, Debug)]
+pub struct StreamMonitor {
+    reference: SparseVec,
+    reference_bits: BitslicedTritVec,
+    config: ReversibleVSAConfig,
+    chunk_size: usize,
+    low: f64,
+    high: Option<f64>,
+    window_chunks: usize,
+    decay_every: usize,
+    sketch: SoftTernaryVec,
+    chunk: Vec<u8>,
+    window: Vec<SparseVec>,
+    window_offset: u64,
+    window_len: usize,
+    offset: u64,
+    windows: u64,
+}
+
+impl StreamMonitor {
+    /// Monitor against `reference`, encoding chunks with `config`. Flags
+    /// windows below [`capacity::retrieval_threshold`], the similarity of
+    /// data unrelated to the reference, until
+    /// [`StreamMonitor::with_thresholds`] says otherwise.
+    pub fn new(reference: &SparseVec, config: ReversibleVSAConfig) -> Self {
+        StreamMonitor {
+            reference: reference.clone(),
+            reference_bits: BitslicedTritVec::from_sparse(reference, DIM),
+            config,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            low: capacity::retrieval_threshold(),
+            high: None,
+            window_chunks: DEFAULT_WINDOW_CHUNKS,
+            decay_every: DEFAULT_DECAY_EVERY,
+            sketch: SoftTernaryVec::new_zero(DIM),
+            chunk: Vec::with_capacity(DEFAULT_CHUNK_SIZE),
+            window: Vec::new(),
+            window_offset: 0,
+            window_len: 0,
+            offset: 0,
+            windows: 0,
+        }
+    }
+
+    /// Monitor against the root of `engram`, encoding chunks the way
+    /// `manifest` records they were ingested.
+    pub fn for_engram(engram: &Engram, manifest: &Manifest) -> Self {
+        let mut monitor = Self::new(&engram.root, manifest.encoding().vsa);
+        monitor.chunk_size = manifest.chunk_size();
+        monitor
+    }
+
+    /// Flag windows whose sketch similarity is below `low` or, if given,
+    /// above `high`.
+    pub fn with_thresholds(mut self, low: f64, high: Option<f64>) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// Bundle `chunks` chunks (at least 1) into each window.
+    pub fn with_window_chunks(mut self, chunks: usize) -> Self {
+        self.window_chunks = chunks.max(1);
+        self
+    }
+
+    /// Decay the sketch once every `windows` windows; 0 never decays.
+    pub fn with_decay_every(mut self, windows: usize) -> Self {
+        self.decay_every = windows;
+        self
+    }
+
+    /// Feed the next bytes of the stream; returns a report for every window
+    /// they complete.
+    pub fn push(&mut self, mut data: &[u8]) -> Vec<WindowReport> {
+        let mut reports = Vec::new();
+        while !data.is_empty() {
+            let take = (self.chunk_size - self.chunk.len()).min(data.len());
+            self.chunk.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.chunk.len() == self.chunk_size {
+                self.close_chunk();
+                if self.window.len() == self.window_chunks {
+                    reports.push(self.close_window());
+                }
+            }
+        }
+        reports
+    }
+
+    /// Close the window in progress, short as it may be; `None` if it
+    /// holds no bytes.
+    pub fn finish(&mut self) -> Option<WindowReport> {
+        if !self.chunk.is_empty() {
+            self.close_chunk();
+        }
+        (!self.window.is_empty()).then(|| self.close_window())
+    }
+
+    /// Cosine of the current sketch with the reference.
+    pub fn similarity(&self) -> f64 {
+        let norm = (self.sketch.norm_squared() as f64 * self.reference_bits.nnz() as f64).sqrt();
+        if norm == 0.0 {
+            return 0.0;
+        }
+        self.sketch.dot_with_hard_fast(&self.reference_bits) as f64 / norm
+    }
+
+    /// Positions where the sketch has any vote, as a hard vector.
+    pub fn sketch(&self) -> SparseVec {
+        self.sketch.harden_any().to_sparse()
+    }
+
+    /// Windows completed so far.
+    pub fn windows(&self) -> u64 {
+        self.windows
+    }
+
+    /// Bytes fed so far.
+    pub fn bytes(&self) -> u64 {
+        self.offset + self.chunk.len() as u64
+    }
+
+    fn close_chunk(&mut self) {
+        self.window.push(SparseVec::encode_chunk(&self.chunk, &self.config, None));
+        self.window_len += self.chunk.len();
+        self.offset += self.chunk.len() as u64;
+        self.chunk.clear();
+    }
+
+    fn close_window(&mut self) -> WindowReport {
+        let vector = SparseVec::bundle_sum_many(&self.window);
+        if self.decay_every > 0 && self.windows > 0 && self.windows.is_multiple_of(self.decay_every as u64) {
+            self.sketch.decay();
+        }
+        self.sketch.accumulate(&BitslicedTritVec::from_sparse(&vector, DIM));
+        let similarity = self.similarity();
+        let alert = if similarity < self.low {
+            Some(DriftAlert::Below)
+        } else if self.high.is_some_and(|high| similarity > high) {
+            Some(DriftAlert::Above)
+        } else {
+            None
+        };
+        let report = WindowReport {
+            index: self.windows,
+            offset: self.window_offset,
+            len: self.window_len,
+            window_similarity: vector.cosine(&self.reference),
+            similarity,
+            alert,
+        };
+        self.windows += 1;
+        self.window.clear();
+        self.window_offset = self.offset;
+        self.window_len = 0;
+        report
+    }
+}
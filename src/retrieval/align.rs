@@ -0,0 +1,275 @@
+//! Mapping one engram's vector space onto another's.
+//!
+//! Engrams encoded with different settings give the same bytes different
+//! vectors, so their cosines compare nothing. An [`Alignment`] is learned
+//! from anchors, chunks both engrams hold: [`anchor_pairs`] pairs the
+//! chunks of files whose blake3 digests agree. Learning works on slots, a
+//! dimension with the sign of its trit, since chunk vectors may hold a
+//! dimension with both signs. For every source slot the anchors it is set
+//! in vote on the target slots set alongside them, and a target's agreement
+//! is the Jaccard index of the two slots' anchors; the best `fanout` are
+//! kept, weighted by their agreement. With `fanout` 1 and a source space
+//! that relabels the target's dimensions and flips some signs, the map is
+//! that signed permutation wherever the anchors tell slots apart. Slots the
+//! anchors cannot tell apart tie: every target tied with the last one kept
+//! is kept too, and they share the weight. Slots set in fewer than
+//! `min_support` anchors, or whose best agreement is at most one half, stay
+//! unmapped. A few hundred anchors map nearly every slot of a default-width
+//! space; a few dozen map about half.
+//!
+//! [`Alignment::project`] sums the weights of a vector's slots into target
+//! slots and keeps as many of the strongest as the vector had. Projected
+//! codebooks and [`FileSignatures`] are then searched like the target's
+//! own, and [`FederatedIndex::add_aligned_engram`](crate::FederatedIndex::add_aligned_engram)
+//! searches a projected engram next to the engram it was aligned to.
+//! Dimensions are only indices here, so the two spaces need not be equally
+//! wide.
+
+use crate::embrfs::{Engram, FileSignatures, Manifest};
+use crate::vsa::SparseVec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// How an [`Alignment`] is learned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlignOptions {
+    /// Target slots kept per source slot; 1 learns a permutation.
+    pub fanout: usize,
+    /// Anchors a source slot must be set in to be mapped.
+    pub min_support: usize,
+    /// Anchors learned from at most, the first in source chunk id order.
+    pub max_anchors: usize,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            fanout: 1,
+            min_support: 2,
+            max_anchors: 512,
+        }
+    }
+}
+
+/// A learned map from a source engram's space into a target's; see the
+/// [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Alignment {
+    /// Anchor pairs learned from.
+    pub anchors: usize,
+    /// [`AlignOptions::fanout`] learned with.
+    pub fanout: usize,
+    /// Mean cosine of the projected source anchors to their target chunks,
+    /// each over the target chunk's cosine with itself, which is below 1
+    /// when it holds a dimension with both signs. 1 when every anchor
+    /// projects onto its target.
+    pub fit: f64,
+    /// Fraction of the source slots set in any anchor that are mapped.
+    pub coverage: f64,
+    /// Source slot to the index of its targets in `targets`. Slot `2 * d`
+    /// is a positive trit at dimension `d`, `2 * d + 1` a negative one.
+    map: BTreeMap<usize, usize>,
+    /// Target slots and their weights, each list stored once however many
+    /// source slots tie on it.
+    targets: Vec<Vec<(usize, f64)>>,
+}
+
+/// Chunk ids `(source, target)` holding the same bytes: the chunks of files
+/// with equal blake3 digests and chunk counts, paired in order. Sorted by
+/// source id, one pair per source chunk.
+pub fn anchor_pairs(source: &Manifest, target: &Manifest) -> Vec<(usize, usize)> {
+    let targets: HashMap<(&str, usize), &[usize]> = target
+        .files
+        .iter()
+        .filter_map(|f| Some(((f.blake3.as_deref()?, f.chunks.len()), f.chunks.as_slice())))
+        .collect();
+    let mut pairs: BTreeMap<usize, usize> = BTreeMap::new();
+    for file in &source.files {
+        let Some(digest) = file.blake3.as_deref() else {
+            continue;
+        };
+        if let Some(chunks) = targets.get(&(digest, file.chunks.len())) {
+            for (&s, &t) in file.chunks.iter().zip(chunks.iter()) {
+                pairs.entry(s).or_insert(t);
+            }
+        }
+    }
+    pairs.into_iter().collect()
+}
+
+impl Alignment {
+    /// Learn the map from `source` into `target` over their
+    /// [`anchor_pairs`]. Fails if the engrams share no chunk.
+    pub fn between(
+        source: (&Engram, &Manifest),
+        target: (&Engram, &Manifest),
+        options: AlignOptions,
+    ) -> io::Result<Self> {
+        let pairs: Vec<(&SparseVec, &SparseVec)> = anchor_pairs(source.1, target.1)
+            .into_iter()
+            .filter_map(|(s, t)| Some((source.0.codebook.get(&s)?, target.0.codebook.get(&t)?)))
+            .collect();
+        if pairs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the engrams share no files to align them by",
+            ));
+        }
+        Ok(Self::learn(&pairs, options))
+    }
+
+    /// Learn the map from pairs of source and target vectors of the same
+    /// content.
+    pub fn learn(pairs: &[(&SparseVec, &SparseVec)], options: AlignOptions) -> Self {
+        let pairs = &pairs[..pairs.len().min(options.max_anchors)];
+        let mut postings: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (a, (source, _)) in pairs.iter().enumerate() {
+            for slot in slots(source) {
+                postings.entry(slot).or_default().push(a);
+            }
+        }
+        let width = pairs.iter().flat_map(|(_, t)| slots(t)).max().map_or(0, |slot| slot + 1);
+        let mut target_support = vec![0usize; width];
+        for (_, target) in pairs {
+            for slot in slots(target) {
+                target_support[slot] += 1;
+            }
+        }
+
+        let fanout = options.fanout.max(1);
+        let mut votes = vec![0usize; width];
+        let mut touched = Vec::new();
+        let mut map = BTreeMap::new();
+        let mut targets: Vec<Vec<(usize, f64)>> = Vec::new();
+        let mut known: HashMap<Vec<(usize, u64)>, usize> = HashMap::new();
+        for (&slot, anchors) in &postings {
+            if anchors.len() < options.min_support.max(1) {
+                continue;
+            }
+            for &a in anchors {
+                for target in slots(pairs[a].1) {
+                    if votes[target] == 0 {
+                        touched.push(target);
+                    }
+                    votes[target] += 1;
+                }
+            }
+            // A target set in most anchors shares most of any slot's anchors,
+            // so its votes alone would win; Jaccard charges it the rest.
+            let support = anchors.len();
+            let mut candidates: Vec<(usize, f64)> = touched
+                .drain(..)
+                .map(|target| {
+                    let n = std::mem::take(&mut votes[target]);
+                    (target, n as f64 / (support + target_support[target] - n) as f64)
+                })
+                .filter(|&(_, agreement)| agreement > 0.5)
+                .collect();
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            let Some(&(_, cut)) = candidates.get(fanout.min(candidates.len()).saturating_sub(1)) else {
+                continue;
+            };
+            candidates.retain(|&(_, agreement)| agreement >= cut);
+            let share = candidates.len() as f64;
+            let weighted: Vec<(usize, f64)> = candidates.into_iter().map(|(target, a)| (target, a / share)).collect();
+            let key = weighted.iter().map(|&(target, w)| (target, w.to_bits())).collect();
+            let index = *known.entry(key).or_insert_with(|| {
+                targets.push(weighted);
+                targets.len() - 1
+            });
+            map.insert(slot, index);
+        }
+
+        let mut alignment = Self {
+            anchors: pairs.len(),
+            fanout,
+            fit: 0.0,
+            coverage: if postings.is_empty() { 0.0 } else { map.len() as f64 / postings.len() as f64 },
+            map,
+            targets,
+        };
+        if !pairs.is_empty() {
+            alignment.fit = pairs
+                .iter()
+                .map(|(s, t)| match t.cosine(t) {
+                    own if own > 0.0 => alignment.project(s).cosine(t) / own,
+                    _ => 0.0,
+                })
+                .sum::<f64>()
+                / pairs.len() as f64;
+        }
+        alignment
+    }
+
+    /// Number of source dimensions with a mapped slot.
+    pub fn dimensions(&self) -> usize {
+        self.map.keys().map(|slot| slot / 2).collect::<HashSet<_>>().len()
+    }
+
+    /// `v` in the target space, with as many nonzero trits as `v` has
+    /// where its slots map to that many.
+    pub fn project(&self, v: &SparseVec) -> SparseVec {
+        let mut sums: HashMap<usize, f64> = HashMap::new();
+        for slot in slots(v) {
+            for &(target, weight) in self.map.get(&slot).map_or(&[][..], |&i| self.targets[i].as_slice()) {
+                *sums.entry(target).or_insert(0.0) += weight;
+            }
+        }
+        let mut ranked: Vec<(usize, f64)> = sums.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(v.pos.len() + v.neg.len());
+        let mut out = SparseVec::new();
+        for (target, _) in ranked {
+            if target % 2 == 0 {
+                out.pos.push(target / 2);
+            } else {
+                out.neg.push(target / 2);
+            }
+        }
+        out.pos.sort_unstable();
+        out.neg.sort_unstable();
+        out
+    }
+
+    /// Every chunk vector of `engram`, projected.
+    pub fn project_codebook(&self, engram: &Engram) -> HashMap<usize, SparseVec> {
+        engram.codebook.iter().map(|(&id, v)| (id, self.project(v))).collect()
+    }
+
+    /// Every file signature, projected.
+    pub fn project_signatures(&self, signatures: &FileSignatures) -> FileSignatures {
+        signatures.map(|v| self.project(v))
+    }
+
+    /// Source slots mapped to more targets than `fanout`, because the
+    /// anchors could not tell the targets apart; 0 for a learned permutation.
+    pub fn ties(&self) -> usize {
+        self.map.values().filter(|&&i| self.targets[i].len() > self.fanout).count()
+    }
+
+    /// Write the alignment as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    /// Read an alignment written by [`Alignment::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+}
+
+/// The slots of `v`'s nonzero trits.
+fn slots(v: &SparseVec) -> impl Iterator<Item = usize> + '_ {
+    v.pos.iter().map(|&d| 2 * d).chain(v.neg.iter().map(|&d| 2 * d + 1))
+}
+
+/// Conventional location of the alignment of an engram file into another.
+pub fn default_alignment_path<P: AsRef<Path>>(engram_path: P) -> PathBuf {
+    let mut s = engram_path.as_ref().as_os_str().to_os_string();
+    s.push(".align");
+    PathBuf::from(s)
+}
@@ -0,0 +1,202 @@
+//! Block-sparse prefiltering for candidate generation over large stores.
+//!
+//! Each vector is viewed as a [`BlockSparseTritVec`] of 64-trit blocks and
+//! indexed under its few *high-magnitude* blocks, the ones holding the most
+//! non-zero trits. Two similar vectors put most of their weight in the same
+//! blocks, so a query's candidates are found by intersecting the posting
+//! lists of its own high-magnitude blocks: an id qualifies when it appears in
+//! at least [`BlockPrefilterOptions::min_shared`] of them, and ids sharing
+//! more blocks rank first.
+//!
+//! [`BlockPrefilterIndex::build`] streams any [`ScannableVectorStore`] in
+//! batches, so an LMDB or RocksDB store with millions of vectors is indexed
+//! without loading it; the index itself holds only ids. [`PrefilteredStore`]
+//! pairs an index with its store and scores the surviving candidates by exact
+//! cosine, fetching just those vectors.
+
+use crate::block_sparse::BlockSparseTritVec;
+use crate::kernel_interop::{CandidateGenerator, ScannableVectorStore};
+use crate::vsa::{SparseVec, DIM};
+use std::collections::HashMap;
+use std::io;
+
+/// Build- and query-time knobs for [`BlockPrefilterIndex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockPrefilterOptions {
+    /// High-magnitude blocks each vector (and each query) is indexed under.
+    pub blocks_per_vector: usize,
+    /// Query blocks a vector must share to become a candidate. Setting it to
+    /// `blocks_per_vector` asks for a strict intersection.
+    pub min_shared: usize,
+    /// Vectors read from the store per scan while building.
+    pub scan_batch: usize,
+}
+
+impl Default for BlockPrefilterOptions {
+    fn default() -> Self {
+        Self {
+            blocks_per_vector: 8,
+            min_shared: 2,
+            scan_batch: 4_096,
+        }
+    }
+}
+
+/// Posting lists from block id to the ids of vectors indexed under it.
+#[derive(Clone, Debug)]
+pub struct BlockPrefilterIndex {
+    dim: usize,
+    opts: BlockPrefilterOptions,
+    postings: HashMap<u32, Vec<usize>>,
+    vectors: usize,
+}
+
+impl BlockPrefilterIndex {
+    /// An empty index for `dim`-dimensional vectors.
+    pub fn new(dim: usize, opts: BlockPrefilterOptions) -> Self {
+        Self {
+            dim,
+            opts,
+            postings: HashMap::new(),
+            vectors: 0,
+        }
+    }
+
+    /// Index every vector in `store`, reading it `opts.scan_batch` vectors at
+    /// a time in id order.
+    pub fn build<S: ScannableVectorStore + ?Sized>(store: &S, dim: usize, opts: BlockPrefilterOptions) -> io::Result<Self> {
+        let mut index = Self::new(dim, opts);
+        let batch = opts.scan_batch.max(1);
+        let mut start = 0;
+        loop {
+            let vectors = store.scan(start, batch)?;
+            let Some(&(last, _)) = vectors.last() else { break };
+            for (id, vec) in &vectors {
+                index.insert(*id, vec);
+            }
+            if vectors.len() < batch {
+                break;
+            }
+            start = last + 1;
+        }
+        Ok(index)
+    }
+
+    /// Index `vec` under `id`, which must not be indexed already.
+    pub fn insert(&mut self, id: usize, vec: &SparseVec) {
+        for block in self.top_blocks(vec) {
+            self.postings.entry(block).or_default().push(id);
+        }
+        self.vectors += 1;
+    }
+
+    /// Number of vectors indexed.
+    pub fn len(&self) -> usize {
+        self.vectors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors == 0
+    }
+
+    pub fn options(&self) -> BlockPrefilterOptions {
+        self.opts
+    }
+
+    /// Ids sharing at least `min_shared` high-magnitude blocks with `query`,
+    /// most shared blocks first (ties by ascending id), at most
+    /// `max_candidates` of them.
+    pub fn candidates_with_options(&self, query: &SparseVec, min_shared: usize, max_candidates: usize) -> Vec<usize> {
+        if max_candidates == 0 {
+            return Vec::new();
+        }
+        let blocks = self.top_blocks(query);
+        let min_shared = min_shared.clamp(1, blocks.len().max(1));
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for block in &blocks {
+            for &id in self.postings.get(block).map(Vec::as_slice).unwrap_or_default() {
+                *shared.entry(id).or_default() += 1;
+            }
+        }
+        let mut hits: Vec<(usize, usize)> = shared.into_iter().filter(|&(_, n)| n >= min_shared).collect();
+        hits.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits.truncate(max_candidates);
+        hits.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// The `blocks_per_vector` blocks of `vec` with the most non-zero trits,
+    /// ties going to the lower block id.
+    fn top_blocks(&self, vec: &SparseVec) -> Vec<u32> {
+        let blocks = BlockSparseTritVec::from_sparse(vec, self.dim);
+        let mut ranked: Vec<(u32, u32)> = blocks.blocks().iter().map(|(id, b)| (*id, b.nnz())).collect();
+        ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(self.opts.blocks_per_vector);
+        ranked.into_iter().map(|(id, _)| id).collect()
+    }
+}
+
+impl CandidateGenerator<SparseVec> for BlockPrefilterIndex {
+    type Candidate = usize;
+
+    /// Up to `k` candidate ids, using the index's `min_shared`.
+    fn candidates(&self, query: &SparseVec, k: usize) -> Vec<Self::Candidate> {
+        self.candidates_with_options(query, self.opts.min_shared, k)
+    }
+}
+
+/// A store paired with its [`BlockPrefilterIndex`]: candidates come back
+/// scored by exact cosine, with only the prefiltered vectors fetched.
+pub struct PrefilteredStore<S> {
+    store: S,
+    index: BlockPrefilterIndex,
+    /// Prefiltered ids fetched and scored per result requested.
+    oversample: usize,
+}
+
+impl<S: ScannableVectorStore> PrefilteredStore<S> {
+    /// Index `store` with the default options for [`DIM`]-dimensional vectors.
+    pub fn build(store: S) -> io::Result<Self> {
+        Self::build_with_options(store, DIM, BlockPrefilterOptions::default())
+    }
+
+    pub fn build_with_options(store: S, dim: usize, opts: BlockPrefilterOptions) -> io::Result<Self> {
+        let index = BlockPrefilterIndex::build(&store, dim, opts)?;
+        Ok(Self::from_parts(store, index))
+    }
+
+    /// Pair `store` with an index already built over it.
+    pub fn from_parts(store: S, index: BlockPrefilterIndex) -> Self {
+        Self { store, index, oversample: 10 }
+    }
+
+    /// Score `factor` prefiltered ids per result (default 10).
+    pub fn with_oversample(mut self, factor: usize) -> Self {
+        self.oversample = factor.max(1);
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn index(&self) -> &BlockPrefilterIndex {
+        &self.index
+    }
+}
+
+impl<S: ScannableVectorStore> CandidateGenerator<SparseVec> for PrefilteredStore<S> {
+    type Candidate = (usize, f64);
+
+    /// The top `k` prefiltered vectors by cosine. Ids the store no longer
+    /// holds are skipped.
+    fn candidates(&self, query: &SparseVec, k: usize) -> Vec<Self::Candidate> {
+        let ids = self.index.candidates(query, k.saturating_mul(self.oversample));
+        let mut scored: Vec<(usize, f64)> = ids
+            .into_iter()
+            .filter_map(|id| self.store.get(id).map(|vec| (id, query.cosine(&vec))))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(k);
+        scored
+    }
+}
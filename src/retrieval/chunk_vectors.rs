@@ -0,0 +1,235 @@
+//! Chunk-id → vector reverse index, persisted beside an engram.
+//!
+//! Retrieval only needs each chunk's ternary vector, yet an engram file
+//! also carries the root and every correction, which dominate its size.
+//! [`ChunkVectors`] holds just the vectors and is saved next to the engram
+//! (see [`default_vectors_path`]) as an envelope of kind
+//! [`PayloadKind::ChunkVectors`], so queries can load, index and rerank
+//! chunks without reading any chunk data.
+//!
+//! On disk the vectors are Elias–Fano coded (see [`crate::trit_coding`]).
+//! Files written before that hold each vector's positive then negative
+//! indices as LEB128 varints of the gaps between consecutive indices;
+//! [`ChunkVectors::decode`] and the index loaders read both.
+
+use crate::embrfs::{bincode_io_error, Engram};
+use crate::envelope::{BinaryWriteOptions, ChecksumCodec, EnvelopeReader, EnvelopeWriter, Keyring, PayloadKind};
+use crate::retrieval::{RerankedResult, TernaryInvertedIndex};
+use crate::trit_coding::{self, put_varint, take_varint};
+use crate::vsa::{SparseVec, DIM};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// `<engram>.vectors`, where the CLI keeps an engram's chunk vectors.
+pub fn default_vectors_path<P: AsRef<Path>>(engram_path: P) -> PathBuf {
+    let mut s = engram_path.as_ref().as_os_str().to_os_string();
+    s.push(".vectors");
+    PathBuf::from(s)
+}
+
+/// Vector per chunk id, as held by an engram's codebook.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkVectors {
+    pub vectors: HashMap<usize, SparseVec>,
+}
+
+/// Serialized form: ids in ascending order, and their vectors packed back
+/// to back in the same order.
+#[derive(Serialize, Deserialize)]
+struct PackedVectors {
+    dim: u64,
+    ids: Vec<u64>,
+    trits: Vec<u8>,
+}
+
+impl ChunkVectors {
+    /// The vectors of every chunk in `engram`'s codebook.
+    pub fn from_engram(engram: &Engram) -> Self {
+        Self {
+            vectors: engram.codebook.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&SparseVec> {
+        self.vectors.get(&id)
+    }
+
+    /// Inverted index over the vectors, for [`ChunkVectors::query`].
+    pub fn build_index(&self) -> TernaryInvertedIndex {
+        TernaryInvertedIndex::build_from_map(&self.vectors)
+    }
+
+    /// Top `k` chunks by cosine with `query`, reranked from `candidate_k`
+    /// index candidates; the same answer as
+    /// [`Engram::query_codebook_with_index`] on the engram they came from.
+    pub fn query(&self, index: &TernaryInvertedIndex, query: &SparseVec, candidate_k: usize, k: usize) -> Vec<RerankedResult> {
+        if k == 0 || self.vectors.is_empty() {
+            return Vec::new();
+        }
+        index.query_top_k_reranked(query, &self.vectors, candidate_k, k)
+    }
+
+    /// Compressed bytes of the vectors (see the module docs).
+    pub fn encode(&self) -> Vec<u8> {
+        trit_coding::encode_map(&self.vectors, DIM)
+    }
+
+    /// The gap-varint bytes of the vectors, as files were written before
+    /// [`ChunkVectors::encode`] coded them; for readers that predate it.
+    pub fn encode_varint(&self) -> Vec<u8> {
+        let mut ids: Vec<usize> = self.vectors.keys().copied().collect();
+        ids.sort_unstable();
+        let mut trits = Vec::new();
+        for id in &ids {
+            let vec = &self.vectors[id];
+            for indices in [&vec.pos, &vec.neg] {
+                put_varint(&mut trits, indices.len() as u64);
+                let mut prev = 0;
+                for &i in indices {
+                    put_varint(&mut trits, (i - prev) as u64);
+                    prev = i;
+                }
+            }
+        }
+        let packed = PackedVectors {
+            dim: DIM as u64,
+            ids: ids.into_iter().map(|id| id as u64).collect(),
+            trits,
+        };
+        bincode::serialize(&packed).expect("serializing to memory cannot fail")
+    }
+
+    /// Decode either encoding, rejecting vectors that are not sorted and
+    /// in range.
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut vectors = HashMap::new();
+        for_each_vector(bytes, |id, vec| {
+            if vectors.insert(id, vec).is_some() {
+                return Err(invalid(format!("chunk {} is stored twice", id)));
+            }
+            Ok(())
+        })?;
+        Ok(Self { vectors })
+    }
+
+    /// Inverted index over encoded vectors, built while decoding them so
+    /// the vectors themselves are never held; for candidate generation
+    /// without reranking.
+    pub fn decode_index(bytes: &[u8]) -> io::Result<TernaryInvertedIndex> {
+        let mut index = TernaryInvertedIndex::new();
+        for_each_vector(bytes, |id, vec| {
+            index.add(id, &vec);
+            Ok(())
+        })?;
+        index.finalize();
+        Ok(index)
+    }
+
+    /// Write the vectors as an envelope. Checksums default to XXH3 when
+    /// `opts` does not pick a codec, so the file is always framed.
+    pub fn save<P: AsRef<Path>>(&self, path: P, mut opts: BinaryWriteOptions) -> io::Result<()> {
+        if opts.checksum == ChecksumCodec::None {
+            opts.checksum = ChecksumCodec::Xxh3;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = EnvelopeWriter::new(file, PayloadKind::ChunkVectors, opts)?;
+        writer.write_all(&self.encode())?;
+        writer.finish()?.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::load_with_keys(path, &Keyring::default())
+    }
+
+    /// Load vectors, decrypting them with `keys` if needed.
+    pub fn load_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        Self::decode(&read_payload(path.as_ref(), keys)?)
+    }
+
+    /// Load only the inverted index over the vectors at `path`; see
+    /// [`ChunkVectors::decode_index`].
+    pub fn load_index<P: AsRef<Path>>(path: P) -> io::Result<TernaryInvertedIndex> {
+        Self::load_index_with_keys(path, &Keyring::default())
+    }
+
+    /// [`ChunkVectors::load_index`], decrypting with `keys` if needed.
+    pub fn load_index_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<TernaryInvertedIndex> {
+        Self::decode_index(&read_payload(path.as_ref(), keys)?)
+    }
+}
+
+fn read_payload(path: &Path, keys: &Keyring) -> io::Result<Vec<u8>> {
+    if PayloadKind::sniff_file(path)? != Some(PayloadKind::ChunkVectors) {
+        return Err(invalid("not a chunk vectors envelope"));
+    }
+    let file = BufReader::new(File::open(path)?);
+    let mut reader = EnvelopeReader::with_keys(file, PayloadKind::ChunkVectors, keys)?;
+    let mut bytes = Vec::new();
+    io::Read::read_to_end(&mut reader, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Decode each vector of either encoding, checked against [`DIM`].
+fn for_each_vector<F>(bytes: &[u8], mut visit: F) -> io::Result<()>
+where
+    F: FnMut(usize, SparseVec) -> io::Result<()>,
+{
+    if trit_coding::is_coded(bytes) {
+        let dim = trit_coding::decode_each(bytes, |id, vec| {
+            vec.validate(DIM).map_err(|e| invalid(format!("chunk {}: {}", id, e)))?;
+            visit(id, vec)
+        })?;
+        if dim != DIM {
+            return Err(invalid(format!("vectors have dimension {}, expected {}", dim, DIM)));
+        }
+        return Ok(());
+    }
+    let packed: PackedVectors = bincode::deserialize(bytes).map_err(|e| bincode_io_error(*e))?;
+    if packed.dim != DIM as u64 {
+        return Err(invalid(format!("vectors have dimension {}, expected {}", packed.dim, DIM)));
+    }
+    let mut input = packed.trits.as_slice();
+    for id in packed.ids {
+        let id = usize::try_from(id).map_err(|_| invalid(format!("chunk id {} does not fit in usize", id)))?;
+        let mut vec = SparseVec::new();
+        for indices in [&mut vec.pos, &mut vec.neg] {
+            let count = take_varint(&mut input)?;
+            if count > DIM as u64 {
+                return Err(invalid(format!("chunk {} claims {} nonzero trits", id, count)));
+            }
+            let mut prev = 0u64;
+            for n in 0..count {
+                let gap = take_varint(&mut input)?;
+                if n > 0 && gap == 0 {
+                    return Err(invalid(format!("chunk {} repeats an index", id)));
+                }
+                prev = prev
+                    .checked_add(gap)
+                    .filter(|&i| i < DIM as u64)
+                    .ok_or_else(|| invalid(format!("chunk {} has an index out of range", id)))?;
+                indices.push(prev as usize);
+            }
+        }
+        vec.validate(DIM).map_err(|e| invalid(format!("chunk {}: {}", id, e)))?;
+        visit(id, vec)?;
+    }
+    if !input.is_empty() {
+        return Err(invalid("trailing bytes after the last vector"));
+    }
+    Ok(())
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
@@ -0,0 +1,448 @@
+//! Retrieval quality against a labeled query set.
+//!
+//! A [`QuerySet`] lists queries (literal text or a query file) with the
+//! files, or chunk ids, a good search should return for each. [`evaluate`]
+//! runs every query through each [`Stage`] of the retrieval stack over one
+//! engram and reports recall@k and precision@k at each cutoff, and the mean
+//! reciprocal rank of the first relevant result. Reports serialize to JSON,
+//! so a run before an encoder or index change can be kept and compared with
+//! one after it.
+//!
+//! Queries are encoded as `query` encodes them, sweeping the path-depth
+//! shifts and keeping each chunk's best score. Queries labeled with files
+//! are scored on the files in order of their best chunk; queries labeled
+//! with chunk ids on the chunks themselves.
+
+use crate::embrfs::{Engram, Manifest};
+use crate::retrieval::TernaryInvertedIndex;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Cutoffs [`EvalOptions::default`] reports.
+pub const DEFAULT_CUTOFFS: [usize; 3] = [1, 5, 10];
+
+/// One query and what it should find.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LabeledQuery {
+    /// Name in reports; the 1-based line number when empty.
+    #[serde(default)]
+    pub id: String,
+    /// Query text, encoded as `query-text` encodes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Query file, relative to the query set's directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Logical paths of the relevant files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relevant: Vec<String>,
+    /// Ids of the relevant chunks, for queries labeled below file level.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relevant_chunks: Vec<usize>,
+}
+
+impl LabeledQuery {
+    /// The bytes to encode: the text, or the contents of the file.
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        match (&self.text, &self.file) {
+            (Some(text), None) => Ok(text.as_bytes().to_vec()),
+            (None, Some(file)) => std::fs::read(file),
+            _ => Err(invalid(format!("query {:?} needs exactly one of text and file", self.id))),
+        }
+    }
+}
+
+/// Labeled queries, read from JSON Lines: one [`LabeledQuery`] object per
+/// line, e.g. `{"id": "open", "text": "fn open(", "relevant": ["src/fs.rs"]}`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuerySet {
+    pub queries: Vec<LabeledQuery>,
+}
+
+impl QuerySet {
+    /// Read `path`, resolving query files against its directory. Blank
+    /// lines and lines starting with `#` are skipped.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let mut set = Self::parse(&text)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for query in &mut set.queries {
+            if let Some(file) = &mut query.file {
+                *file = dir.join(&*file);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Parse JSON Lines as [`QuerySet::load`] does, leaving query files as
+    /// written.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut queries = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut query: LabeledQuery =
+                serde_json::from_str(line).map_err(|e| invalid(format!("line {}: {}", n + 1, e)))?;
+            if query.id.is_empty() {
+                query.id = (n + 1).to_string();
+            }
+            if query.text.is_some() == query.file.is_some() {
+                return Err(invalid(format!("line {}: give exactly one of text and file", n + 1)));
+            }
+            if query.relevant.is_empty() == query.relevant_chunks.is_empty() {
+                return Err(invalid(format!(
+                    "line {}: label either relevant files or relevant_chunks",
+                    n + 1
+                )));
+            }
+            queries.push(query);
+        }
+        Ok(Self { queries })
+    }
+}
+
+/// A layer of the retrieval stack, scored on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Inverted-index candidates, ordered by their approximate dot score.
+    Index,
+    /// Index candidates reranked by exact cosine: what `query` returns.
+    Reranked,
+    /// Exact cosine against every chunk; the ceiling for the other two.
+    Exhaustive,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::Index, Stage::Reranked, Stage::Exhaustive];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Index => "index",
+            Stage::Reranked => "reranked",
+            Stage::Exhaustive => "exhaustive",
+        }
+    }
+}
+
+/// What [`evaluate`] measures.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalOptions {
+    /// The k of recall@k and precision@k; the largest is also the depth
+    /// reciprocal ranks are counted to.
+    pub cutoffs: Vec<usize>,
+    pub stages: Vec<Stage>,
+    /// Index candidates reranked per shift; `None` uses what `query` uses.
+    pub candidate_k: Option<usize>,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            cutoffs: DEFAULT_CUTOFFS.to_vec(),
+            stages: Stage::ALL.to_vec(),
+            candidate_k: None,
+        }
+    }
+}
+
+/// Recall and precision at one cutoff, averaged over the queries.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CutoffMetrics {
+    pub k: usize,
+    pub recall: f64,
+    pub precision: f64,
+}
+
+/// How one query fared in one stage.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueryOutcome {
+    pub id: String,
+    /// 1-based rank of the first relevant result, if it was within the
+    /// deepest cutoff.
+    pub first_relevant: Option<usize>,
+    /// Relevant results within the deepest cutoff, out of `relevant`.
+    pub found: usize,
+    pub relevant: usize,
+}
+
+/// Metrics of one stage over the whole query set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub cutoffs: Vec<CutoffMetrics>,
+    /// Mean reciprocal rank; a query with nothing relevant within the
+    /// deepest cutoff counts 0.
+    pub mrr: f64,
+    /// Mean wall-clock time per query.
+    pub mean_millis: f64,
+    pub queries: Vec<QueryOutcome>,
+}
+
+impl StageReport {
+    pub fn at(&self, k: usize) -> Option<&CutoffMetrics> {
+        self.cutoffs.iter().find(|c| c.k == k)
+    }
+}
+
+/// Result of [`evaluate`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub queries: usize,
+    pub stages: Vec<StageReport>,
+}
+
+/// One metric of [`EvalReport::compare`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MetricChange {
+    /// `<stage>/recall@<k>`, `<stage>/precision@<k>` or `<stage>/mrr@<k>`,
+    /// with the deepest cutoff as the k of the last.
+    pub name: String,
+    pub baseline: f64,
+    pub current: f64,
+    /// `current - baseline`; negative is worse.
+    pub delta: f64,
+}
+
+impl EvalReport {
+    pub fn stage(&self, stage: Stage) -> Option<&StageReport> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+
+    /// Every metric present in both reports, as it moved from `baseline`.
+    pub fn compare(&self, baseline: &EvalReport) -> Vec<MetricChange> {
+        let before: HashMap<String, f64> = baseline.metrics().collect();
+        self.metrics()
+            .filter_map(|(name, current)| {
+                let baseline = *before.get(&name)?;
+                Some(MetricChange {
+                    name,
+                    baseline,
+                    current,
+                    delta: current - baseline,
+                })
+            })
+            .collect()
+    }
+
+    fn metrics(&self) -> impl Iterator<Item = (String, f64)> + '_ {
+        self.stages.iter().flat_map(|s| {
+            let stage = s.stage.name();
+            let at = s.cutoffs.iter().flat_map(move |c| {
+                [
+                    (format!("{}/recall@{}", stage, c.k), c.recall),
+                    (format!("{}/precision@{}", stage, c.k), c.precision),
+                ]
+            });
+            // Reciprocal ranks depend on the depth they were counted to.
+            let depth = s.cutoffs.iter().map(|c| c.k).max().unwrap_or(0);
+            at.chain(std::iter::once((format!("{}/mrr@{}", stage, depth), s.mrr)))
+        })
+    }
+}
+
+/// Fraction of `relevant` among the first `k` of `ranked`.
+pub fn recall_at_k<T: Eq + Hash>(ranked: &[T], relevant: &HashSet<T>, k: usize) -> f64 {
+    if relevant.is_empty() {
+        return 0.0;
+    }
+    hits_at(ranked, relevant, k) as f64 / relevant.len() as f64
+}
+
+/// Fraction of the first `k` slots of `ranked` holding a relevant result;
+/// empty slots of a short ranking count as misses.
+pub fn precision_at_k<T: Eq + Hash>(ranked: &[T], relevant: &HashSet<T>, k: usize) -> f64 {
+    if k == 0 {
+        return 0.0;
+    }
+    hits_at(ranked, relevant, k) as f64 / k as f64
+}
+
+/// `1 / rank` of the first relevant result of `ranked`, or 0 without one.
+pub fn reciprocal_rank<T: Eq + Hash>(ranked: &[T], relevant: &HashSet<T>) -> f64 {
+    ranked
+        .iter()
+        .position(|r| relevant.contains(r))
+        .map_or(0.0, |i| 1.0 / (i + 1) as f64)
+}
+
+fn hits_at<T: Eq + Hash>(ranked: &[T], relevant: &HashSet<T>, k: usize) -> usize {
+    ranked.iter().take(k).filter(|r| relevant.contains(*r)).count()
+}
+
+/// Run `set` against `engram` and score each stage of `options`.
+///
+/// Fails on queries whose file cannot be read, and on labels naming files
+/// that are not in `manifest` (usually a typo that would silently read as a
+/// miss).
+pub fn evaluate(engram: &Engram, manifest: &Manifest, set: &QuerySet, options: &EvalOptions) -> io::Result<EvalReport> {
+    let depth = options.cutoffs.iter().copied().max().unwrap_or(0);
+    if depth == 0 {
+        return Err(invalid("at least one cutoff above 0 is needed".to_string()));
+    }
+    let known: HashSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    for query in &set.queries {
+        if let Some(path) = query.relevant.iter().find(|p| !known.contains(p.as_str())) {
+            return Err(invalid(format!("query {:?}: no file {:?} in the manifest", query.id, path)));
+        }
+    }
+
+    let config = manifest.encoding().vsa;
+    let index = engram.build_codebook_index();
+    let searcher = Searcher {
+        engram,
+        index: &index,
+        config: &config,
+        // Files share chunks, so more chunks than files are fetched to fill
+        // `depth` file slots; the same widening `query` does.
+        fetch: depth.saturating_mul(10).max(100),
+        candidate_k: options.candidate_k,
+    };
+    let encoded = set
+        .queries
+        .iter()
+        .map(|q| Ok(SparseVec::encode_chunk(&q.bytes()?, &config, None)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let stages = options
+        .stages
+        .iter()
+        .map(|&stage| {
+            let mut sums = vec![(0.0, 0.0); options.cutoffs.len()];
+            let mut rr = 0.0;
+            let mut outcomes = Vec::with_capacity(set.queries.len());
+            let started = Instant::now();
+            for (query, vector) in set.queries.iter().zip(&encoded) {
+                let chunks = searcher.rank(stage, vector);
+                let (found, first, recall, precision) = if query.relevant.is_empty() {
+                    let relevant: HashSet<usize> = query.relevant_chunks.iter().copied().collect();
+                    score(&chunks, &relevant, depth, &options.cutoffs)
+                } else {
+                    let files = files_by_best_chunk(manifest, &chunks);
+                    let relevant: HashSet<&str> = query.relevant.iter().map(String::as_str).collect();
+                    score(&files, &relevant, depth, &options.cutoffs)
+                };
+                for (sum, (r, p)) in sums.iter_mut().zip(recall.into_iter().zip(precision)) {
+                    sum.0 += r;
+                    sum.1 += p;
+                }
+                rr += first.map_or(0.0, |rank| 1.0 / rank as f64);
+                outcomes.push(QueryOutcome {
+                    id: query.id.clone(),
+                    first_relevant: first,
+                    found,
+                    relevant: query.relevant.len().max(query.relevant_chunks.len()),
+                });
+            }
+            let n = set.queries.len().max(1) as f64;
+            StageReport {
+                stage,
+                cutoffs: options
+                    .cutoffs
+                    .iter()
+                    .zip(sums)
+                    .map(|(&k, (recall, precision))| CutoffMetrics {
+                        k,
+                        recall: recall / n,
+                        precision: precision / n,
+                    })
+                    .collect(),
+                mrr: rr / n,
+                mean_millis: started.elapsed().as_secs_f64() * 1000.0 / n,
+                queries: outcomes,
+            }
+        })
+        .collect();
+    Ok(EvalReport {
+        queries: set.queries.len(),
+        stages,
+    })
+}
+
+/// Relevant results within `depth`, the rank of the first, and recall and
+/// precision at each cutoff.
+fn score<T: Eq + Hash>(
+    ranked: &[T],
+    relevant: &HashSet<T>,
+    depth: usize,
+    cutoffs: &[usize],
+) -> (usize, Option<usize>, Vec<f64>, Vec<f64>) {
+    let ranked = &ranked[..ranked.len().min(depth)];
+    let first = ranked.iter().position(|r| relevant.contains(r)).map(|i| i + 1);
+    (
+        hits_at(ranked, relevant, depth),
+        first,
+        cutoffs.iter().map(|&k| recall_at_k(ranked, relevant, k)).collect(),
+        cutoffs.iter().map(|&k| precision_at_k(ranked, relevant, k)).collect(),
+    )
+}
+
+/// Files in the order of their best-ranked chunk among `chunks`.
+fn files_by_best_chunk<'a>(manifest: &'a Manifest, chunks: &[usize]) -> Vec<&'a str> {
+    let mut files_of: HashMap<usize, Vec<&str>> = HashMap::new();
+    for file in &manifest.files {
+        for &id in &file.chunks {
+            files_of.entry(id).or_default().push(&file.path);
+        }
+    }
+    let mut seen = HashSet::new();
+    chunks
+        .iter()
+        .flat_map(|id| files_of.get(id).into_iter().flatten().copied())
+        .filter(|path| seen.insert(*path))
+        .collect()
+}
+
+struct Searcher<'a> {
+    engram: &'a Engram,
+    index: &'a TernaryInvertedIndex,
+    config: &'a ReversibleVSAConfig,
+    fetch: usize,
+    candidate_k: Option<usize>,
+}
+
+impl Searcher<'_> {
+    /// Chunk ids best first, merged over the path-depth shifts.
+    fn rank(&self, stage: Stage, base: &SparseVec) -> Vec<usize> {
+        let candidate_k = self.candidate_k.unwrap_or_else(|| self.fetch.saturating_mul(10).max(200));
+        let mut best: HashMap<usize, f64> = HashMap::new();
+        for depth in 0..self.config.max_path_depth.max(1) {
+            let query = base.permute(depth * self.config.base_shift);
+            let hits: Vec<(usize, f64)> = match stage {
+                Stage::Index => self
+                    .index
+                    .query_top_k(&query, self.fetch)
+                    .into_iter()
+                    .map(|h| (h.id, h.score as f64))
+                    .collect(),
+                Stage::Reranked => self
+                    .engram
+                    .query_codebook_with_index(self.index, &query, candidate_k, self.fetch)
+                    .into_iter()
+                    .map(|h| (h.id, h.cosine))
+                    .collect(),
+                Stage::Exhaustive => self.engram.codebook.iter().map(|(&id, v)| (id, query.cosine(v))).collect(),
+            };
+            for (id, s) in hits {
+                let score = best.entry(id).or_insert(f64::MIN);
+                *score = score.max(s);
+            }
+        }
+        let mut ranked: Vec<(usize, f64)> = best.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(self.fetch);
+        ranked.into_iter().map(|(id, _)| id).collect()
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,347 @@
+//! Top-k search across several engrams without merging them.
+//!
+//! A [`FederatedIndex`] holds named sources: loaded engrams (through an
+//! [`EngramReader`], so its codebook index is built once and can be shared
+//! with a server), bare inverted indices over a vector collection, and
+//! engrams encoded differently whose chunks an [`Alignment`] projects into
+//! the space queries are encoded in. A query asks every source for its own
+//! best hits and merges them into one ranking.
+//!
+//! Raw cosines from different sources are not directly comparable: a large
+//! codebook has more near misses, and sources encoded with different
+//! settings score on different scales. Each source's hits are therefore
+//! rescaled against that source's own result list first (see
+//! [`ScoreNormalization`]) and then multiplied by the source's weight. Every
+//! [`FederatedHit`] keeps the raw cosine next to the merged score, along with
+//! the source name, chunk id and file paths it came from.
+
+use crate::embrfs::EmbrFS;
+use crate::reader::EngramReader;
+use crate::retrieval::align::Alignment;
+use crate::retrieval::TernaryInvertedIndex;
+use crate::vsa::{ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+/// Hits fetched from each source before merging, at least; score statistics
+/// over fewer hits are too noisy to normalize with.
+const MIN_SOURCE_HITS: usize = 10;
+
+/// How each source's cosines are rescaled before ranking across sources.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreNormalization {
+    /// Raw cosines; only meaningful when the sources are alike.
+    None,
+    /// `(s - min) / (max - min)` over the source's hits, so each source's
+    /// best hit scores 1.
+    MinMax,
+    /// Standard score over the source's hits: how far a hit stands out from
+    /// the rest of its own source.
+    #[default]
+    ZScore,
+}
+
+/// One result of a federated query.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FederatedHit {
+    /// Name the source was registered under.
+    pub source: String,
+    /// Chunk id (or vector id) within the source.
+    pub chunk: usize,
+    /// Normalized, weighted score used for the ranking.
+    pub score: f64,
+    /// Cosine as reported by the source.
+    pub cosine: f64,
+    /// Files of the source that reference the chunk; empty for index sources.
+    pub paths: Vec<String>,
+}
+
+enum SourceKind {
+    Engram(Arc<EngramReader>),
+    Index {
+        index: TernaryInvertedIndex,
+        vectors: HashMap<usize, SparseVec>,
+    },
+    /// An engram's chunks projected by an alignment; the reader names the
+    /// files.
+    Aligned {
+        reader: Arc<EngramReader>,
+        index: TernaryInvertedIndex,
+        vectors: HashMap<usize, SparseVec>,
+    },
+}
+
+struct Source {
+    name: String,
+    weight: f64,
+    kind: SourceKind,
+}
+
+/// Searchable set of named sources; see the [module docs](self).
+#[derive(Default)]
+pub struct FederatedIndex {
+    sources: Vec<Source>,
+    normalization: ScoreNormalization,
+    config: ReversibleVSAConfig,
+}
+
+impl FederatedIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rescale source scores with `normalization` (z-score by default).
+    pub fn with_normalization(mut self, normalization: ScoreNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Encode byte queries for index sources with `config`. Engram sources
+    /// use the config of their reader.
+    pub fn with_config(mut self, config: ReversibleVSAConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Register an engram under `name`.
+    pub fn add_engram(
+        &mut self,
+        name: impl Into<String>,
+        reader: Arc<EngramReader>,
+    ) -> io::Result<()> {
+        self.add(name.into(), SourceKind::Engram(reader))
+    }
+
+    /// Register an engram encoded differently from the others under `name`,
+    /// searched through its chunks projected by `alignment`. Byte queries
+    /// are encoded with the index's config, as for index sources.
+    pub fn add_aligned_engram(
+        &mut self,
+        name: impl Into<String>,
+        reader: Arc<EngramReader>,
+        alignment: &Alignment,
+    ) -> io::Result<()> {
+        let vectors = alignment.project_codebook(reader.engram());
+        let index = TernaryInvertedIndex::build_from_map(&vectors);
+        self.add(name.into(), SourceKind::Aligned { reader, index, vectors })
+    }
+
+    /// Register a prebuilt index over `vectors` under `name`.
+    pub fn add_index(
+        &mut self,
+        name: impl Into<String>,
+        index: TernaryInvertedIndex,
+        vectors: HashMap<usize, SparseVec>,
+    ) -> io::Result<()> {
+        self.add(name.into(), SourceKind::Index { index, vectors })
+    }
+
+    /// Multiply the normalized scores of `name` by `weight`.
+    pub fn set_weight(&mut self, name: &str, weight: f64) -> io::Result<()> {
+        let source = self
+            .sources
+            .iter_mut()
+            .find(|s| s.name == name)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("no source {:?}", name))
+            })?;
+        source.weight = weight;
+        Ok(())
+    }
+
+    /// Drop the source registered as `name`; returns whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.sources.len();
+        self.sources.retain(|s| s.name != name);
+        self.sources.len() != before
+    }
+
+    /// Registered source names, in registration order.
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|s| s.name.as_str())
+    }
+
+    /// The `k` best chunks for the bytes `data` across every source. Engram
+    /// sources sweep the path-depth shifts as
+    /// [`EngramReader::similar_chunks`] does.
+    pub fn query(&self, data: &[u8], k: usize) -> Vec<FederatedHit> {
+        let fetch = k.max(MIN_SOURCE_HITS);
+        let mut encoded = None;
+        self.merge(k, |source| match &source.kind {
+            SourceKind::Engram(reader) => reader.similar_chunks(data, fetch),
+            SourceKind::Index { index, vectors } => {
+                let query =
+                    encoded.get_or_insert_with(|| SparseVec::encode_data(data, &self.config, None));
+                index_hits(index, vectors, query, fetch)
+            }
+            SourceKind::Aligned { reader, index, vectors } => {
+                // Projected chunks keep the path shifts they were encoded
+                // with, so the query sweeps them as engram sources do.
+                let base = SparseVec::encode_chunk(data, &self.config, None);
+                let queries: Vec<SparseVec> = (0..self.config.max_path_depth.max(1))
+                    .map(|depth| base.permute(depth * self.config.base_shift))
+                    .collect();
+                aligned_hits(reader, index, vectors, &queries, fetch)
+            }
+        })
+    }
+
+    /// The `k` best chunks for an already encoded query vector.
+    pub fn query_vector(&self, query: &SparseVec, k: usize) -> Vec<FederatedHit> {
+        let fetch = k.max(MIN_SOURCE_HITS);
+        self.merge(k, |source| match &source.kind {
+            SourceKind::Engram(reader) => {
+                let hits = reader.query_codebook(query, fetch);
+                let mut paths = EmbrFS::chunk_paths(reader.manifest(), hits.iter().map(|h| h.id));
+                hits.into_iter()
+                    .map(|h| (h.id, h.cosine, paths.remove(&h.id).unwrap_or_default()))
+                    .collect()
+            }
+            SourceKind::Index { index, vectors } => index_hits(index, vectors, query, fetch),
+            SourceKind::Aligned { reader, index, vectors } => {
+                aligned_hits(reader, index, vectors, std::slice::from_ref(query), fetch)
+            }
+        })
+    }
+
+    fn add(&mut self, name: String, kind: SourceKind) -> io::Result<()> {
+        if self.sources.iter().any(|s| s.name == name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("source {:?} is already registered", name),
+            ));
+        }
+        self.sources.push(Source {
+            name,
+            weight: 1.0,
+            kind,
+        });
+        Ok(())
+    }
+
+    /// Normalize each source's hits, then rank them all by score. Ties go
+    /// to the earlier source, then the lower chunk id.
+    fn merge<F>(&self, k: usize, mut search: F) -> Vec<FederatedHit>
+    where
+        F: FnMut(&Source) -> Vec<(usize, f64, Vec<String>)>,
+    {
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut ranked: Vec<(usize, FederatedHit)> = Vec::new();
+        for (order, source) in self.sources.iter().enumerate() {
+            let hits = search(source);
+            let cosines: Vec<f64> = hits.iter().map(|h| h.1).collect();
+            let scale = Scale::fit(self.normalization, &cosines);
+            ranked.extend(hits.into_iter().map(|(chunk, cosine, paths)| {
+                let hit = FederatedHit {
+                    source: source.name.clone(),
+                    chunk,
+                    score: scale.apply(cosine) * source.weight,
+                    cosine,
+                    paths,
+                };
+                (order, hit)
+            }));
+        }
+        ranked.sort_by(|(oa, a), (ob, b)| {
+            b.score
+                .total_cmp(&a.score)
+                .then(oa.cmp(ob))
+                .then(a.chunk.cmp(&b.chunk))
+        });
+        ranked.truncate(k);
+        ranked.into_iter().map(|(_, hit)| hit).collect()
+    }
+}
+
+fn index_hits(
+    index: &TernaryInvertedIndex,
+    vectors: &HashMap<usize, SparseVec>,
+    query: &SparseVec,
+    k: usize,
+) -> Vec<(usize, f64, Vec<String>)> {
+    let candidate_k = k.saturating_mul(10).max(50);
+    index
+        .query_top_k_reranked(query, vectors, candidate_k, k)
+        .into_iter()
+        .map(|h| (h.id, h.cosine, Vec::new()))
+        .collect()
+}
+
+/// The `k` best chunks for any of `queries`, each at its best cosine.
+fn aligned_hits(
+    reader: &EngramReader,
+    index: &TernaryInvertedIndex,
+    vectors: &HashMap<usize, SparseVec>,
+    queries: &[SparseVec],
+    k: usize,
+) -> Vec<(usize, f64, Vec<String>)> {
+    let mut best: HashMap<usize, f64> = HashMap::new();
+    for query in queries {
+        for (id, cosine, _) in index_hits(index, vectors, query, k) {
+            let score = best.entry(id).or_insert(f64::MIN);
+            *score = score.max(cosine);
+        }
+    }
+    let mut top: Vec<(usize, f64)> = best.into_iter().collect();
+    top.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    top.truncate(k);
+    let mut paths = EmbrFS::chunk_paths(reader.manifest(), top.iter().map(|&(id, _)| id));
+    top.into_iter()
+        .map(|(id, cosine)| (id, cosine, paths.remove(&id).unwrap_or_default()))
+        .collect()
+}
+
+/// Affine map `(s - offset) / spread` fitted to one source's cosines.
+struct Scale {
+    offset: f64,
+    spread: f64,
+}
+
+impl Scale {
+    fn fit(normalization: ScoreNormalization, cosines: &[f64]) -> Self {
+        let identity = Self {
+            offset: 0.0,
+            spread: 1.0,
+        };
+        if cosines.is_empty() {
+            return identity;
+        }
+        match normalization {
+            ScoreNormalization::None => identity,
+            ScoreNormalization::MinMax => {
+                let min = cosines.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = cosines.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                // A single hit, or all equal: every hit is the best one.
+                if max - min <= f64::EPSILON {
+                    return Self {
+                        offset: min - 1.0,
+                        spread: 1.0,
+                    };
+                }
+                Self {
+                    offset: min,
+                    spread: max - min,
+                }
+            }
+            ScoreNormalization::ZScore => {
+                let n = cosines.len() as f64;
+                let mean = cosines.iter().sum::<f64>() / n;
+                let var = cosines.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / n;
+                let spread = var.sqrt();
+                Self {
+                    offset: mean,
+                    spread: if spread > f64::EPSILON { spread } else { 1.0 },
+                }
+            }
+        }
+    }
+
+    fn apply(&self, cosine: f64) -> f64 {
+        (cosine - self.offset) / self.spread
+    }
+}
@@ -0,0 +1,285 @@
+//! Results of repeated similarity searches.
+//!
+//! Dashboards and monitoring jobs tend to issue the same handful of queries
+//! over and over against an engram that rarely changes. A [`QueryCache`]
+//! keeps their answers in an LRU keyed by a [`QueryKey`]: a blake3 digest of
+//! the query vector (or raw query bytes) together with every parameter that
+//! shapes the answer, such as `k` and the set of allowed chunk ids.
+//!
+//! Both final rankings and the intermediate candidate sets are cached, so a
+//! query repeated with a different `k` but the same candidate budget skips
+//! the posting-list scan and only reranks.
+//!
+//! Every entry remembers the [`TernaryInvertedIndex::revision`] it was
+//! computed against. The first lookup against a different revision, i.e.
+//! after the index was modified or replaced, empties the cache. Changes the
+//! index cannot see, such as new vectors for existing ids in the rerank map,
+//! must be announced with [`QueryCache::invalidate`].
+
+use crate::metrics::metrics;
+use crate::retrieval::{rerank_candidates_by_cosine, RerankedResult, SearchResult, TernaryInvertedIndex};
+use crate::vsa::SparseVec;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// Default entry budget of a [`QueryCache`].
+pub const DEFAULT_QUERY_CACHE_ENTRIES: usize = 1024;
+
+/// A chunk ranked by a query: id, cosine and the files referencing it.
+pub type ChunkHit = (usize, f64, Vec<String>);
+
+/// Identity of a query and the parameters that shape its answer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QueryKey([u8; 32]);
+
+impl QueryKey {
+    /// Key for a query vector.
+    pub fn new(query: &SparseVec) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"vec");
+        hasher.update(&(query.pos.len() as u64).to_le_bytes());
+        for &i in query.pos.iter().chain(&query.neg) {
+            hasher.update(&(i as u64).to_le_bytes());
+        }
+        QueryKey(*hasher.finalize().as_bytes())
+    }
+
+    /// Key for a query given as raw bytes, to be encoded by the search.
+    pub fn for_data(data: &[u8]) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"data");
+        hasher.update(data);
+        QueryKey(*hasher.finalize().as_bytes())
+    }
+
+    /// This key refined by one more parameter.
+    pub fn with_param(self, value: u64) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.0);
+        hasher.update(&value.to_le_bytes());
+        QueryKey(*hasher.finalize().as_bytes())
+    }
+
+    /// This key refined by the set of chunk ids a filtered query may return.
+    pub fn with_filter(self, allowed: &HashSet<usize>) -> Self {
+        let mut ids: Vec<usize> = allowed.iter().copied().collect();
+        ids.sort_unstable();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.0);
+        hasher.update(b"filter");
+        for id in ids {
+            hasher.update(&(id as u64).to_le_bytes());
+        }
+        QueryKey(*hasher.finalize().as_bytes())
+    }
+}
+
+/// Counters and occupancy of a [`QueryCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Times the cache was emptied, explicitly or by an index change.
+    pub invalidations: u64,
+    pub entries: usize,
+    pub max_entries: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Kind {
+    Candidates,
+    Reranked,
+    Chunks,
+}
+
+#[derive(Clone)]
+enum Cached {
+    Candidates(Arc<[SearchResult]>),
+    Reranked(Arc<[RerankedResult]>),
+    Chunks(Arc<[ChunkHit]>),
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Index revision the entries were computed against.
+    revision: Option<u64>,
+    /// Key -> (answer, tick of last use).
+    map: HashMap<(Kind, QueryKey), (Cached, u64)>,
+    /// Tick of last use -> key, oldest first.
+    order: BTreeMap<u64, (Kind, QueryKey)>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+    invalidations: u64,
+}
+
+impl CacheState {
+    fn clear(&mut self) {
+        if !self.map.is_empty() {
+            self.invalidations += 1;
+        }
+        self.map.clear();
+        self.order.clear();
+    }
+
+    /// Forget entries computed against any revision other than `revision`.
+    fn sync_revision(&mut self, revision: u64) {
+        if self.revision != Some(revision) {
+            self.clear();
+            self.revision = Some(revision);
+        }
+    }
+}
+
+/// LRU cache of query results over one index; see the
+/// [module docs](self). Safe to share between threads through an `Arc`.
+pub struct QueryCache {
+    state: Mutex<CacheState>,
+    max_entries: usize,
+}
+
+impl QueryCache {
+    /// A cache holding at most `max_entries` answers; 0 disables it.
+    pub fn new(max_entries: usize) -> Self {
+        QueryCache {
+            state: Mutex::new(CacheState::default()),
+            max_entries,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // Every statement leaves the state consistent, so a poisoned lock
+        // is safe to keep using.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn get(&self, revision: u64, key: (Kind, QueryKey)) -> Option<Cached> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        state.sync_revision(revision);
+        state.tick += 1;
+        match state.map.get_mut(&key) {
+            Some((cached, last_use)) => {
+                state.order.remove(last_use);
+                *last_use = state.tick;
+                state.order.insert(state.tick, key);
+                state.hits += 1;
+                metrics().inc_query_cache_hit();
+                Some(cached.clone())
+            }
+            None => {
+                state.misses += 1;
+                metrics().inc_query_cache_miss();
+                None
+            }
+        }
+    }
+
+    fn insert(&self, revision: u64, key: (Kind, QueryKey), cached: Cached) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        let state = &mut *state;
+        // The index may have moved on while the answer was computed.
+        if state.revision != Some(revision) {
+            return;
+        }
+        state.tick += 1;
+        if let Some((_, last_use)) = state.map.insert(key, (cached, state.tick)) {
+            state.order.remove(&last_use);
+        }
+        state.order.insert(state.tick, key);
+        while state.map.len() > self.max_entries {
+            let Some((_, victim)) = state.order.pop_first() else {
+                break;
+            };
+            state.map.remove(&victim);
+            state.evictions += 1;
+            metrics().inc_query_cache_eviction();
+        }
+    }
+
+    /// [`TernaryInvertedIndex::query_top_k`], cached. The answer is the
+    /// candidate set the reranking methods start from.
+    pub fn top_k(&self, index: &TernaryInvertedIndex, query: &SparseVec, k: usize) -> Arc<[SearchResult]> {
+        let key = (Kind::Candidates, QueryKey::new(query).with_param(k as u64));
+        if let Some(Cached::Candidates(hits)) = self.get(index.revision(), key) {
+            return hits;
+        }
+        let hits: Arc<[SearchResult]> = index.query_top_k(query, k).into();
+        self.insert(index.revision(), key, Cached::Candidates(hits.clone()));
+        hits
+    }
+
+    /// [`TernaryInvertedIndex::query_top_k_reranked`], cached, with the
+    /// candidate set cached separately. `vectors` must be the collection
+    /// the index was built over.
+    pub fn top_k_reranked(
+        &self,
+        index: &TernaryInvertedIndex,
+        query: &SparseVec,
+        vectors: &HashMap<usize, SparseVec>,
+        candidate_k: usize,
+        k: usize,
+    ) -> Arc<[RerankedResult]> {
+        let key = QueryKey::new(query).with_param(candidate_k as u64).with_param(k as u64);
+        if let Some(Cached::Reranked(hits)) = self.get(index.revision(), (Kind::Reranked, key)) {
+            return hits;
+        }
+        let candidates = self.top_k(index, query, candidate_k);
+        let hits: Arc<[RerankedResult]> = rerank_candidates_by_cosine(query, &candidates, vectors, k).into();
+        self.insert(index.revision(), (Kind::Reranked, key), Cached::Reranked(hits.clone()));
+        hits
+    }
+
+    /// The cached chunk ranking under `key`, or the result of `search`
+    /// (then cached). For searches that are more than one index query, such
+    /// as [`crate::reader::EngramReader::similar_chunks`]; `key` must cover
+    /// every input of `search` besides `index`.
+    pub fn chunks_with<F>(&self, index: &TernaryInvertedIndex, key: QueryKey, search: F) -> Arc<[ChunkHit]>
+    where
+        F: FnOnce() -> Vec<ChunkHit>,
+    {
+        if let Some(Cached::Chunks(hits)) = self.get(index.revision(), (Kind::Chunks, key)) {
+            return hits;
+        }
+        let hits: Arc<[ChunkHit]> = search().into();
+        self.insert(index.revision(), (Kind::Chunks, key), Cached::Chunks(hits.clone()));
+        hits
+    }
+
+    /// Drop every cached answer; counters are kept. Call when the vectors
+    /// behind an index change without the index itself changing.
+    pub fn invalidate(&self) {
+        self.lock().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> QueryCacheStats {
+        let state = self.lock();
+        QueryCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            evictions: state.evictions,
+            invalidations: state.invalidations,
+            entries: state.map.len(),
+            max_entries: self.max_entries,
+        }
+    }
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUERY_CACHE_ENTRIES)
+    }
+}
@@ -5,9 +5,22 @@
 //! 1) Build an index over a collection (e.g., an Engram codebook).
 //! 2) Query to generate candidates with approximate dot scores.
 //! 3) Optionally rerank candidates using exact cosine similarity.
+//!
+//! [`federation`] ranks queries across several engrams at once, [`align`]
+//! maps an engram encoded differently into another's space for it,
+//! [`query_cache`] keeps the results of repeated queries, [`similar`] finds
+//! the ingested files closest to a local one, and [`eval`] measures how well
+//! the whole stack finds what a labeled query set expects.
+
+pub mod align;
+pub mod eval;
+pub mod federation;
+pub mod query_cache;
+pub mod similar;
 
 use crate::vsa::{SparseVec, DIM};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[cfg(feature = "metrics")]
 use crate::metrics::metrics;
@@ -40,6 +53,14 @@ pub struct TernaryInvertedIndex {
     pos_postings: Vec<Vec<usize>>,
     neg_postings: Vec<Vec<usize>>,
     max_id: usize,
+    revision: u64,
+}
+
+/// Source of [`TernaryInvertedIndex::revision`] values, unique per process.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
 }
 
 impl TernaryInvertedIndex {
@@ -48,9 +69,26 @@ impl TernaryInvertedIndex {
             pos_postings: vec![Vec::new(); DIM],
             neg_postings: vec![Vec::new(); DIM],
             max_id: 0,
+            revision: next_revision(),
         }
     }
 
+    /// Identifies the current contents of the index: it changes on every
+    /// [`add`](Self::add) and [`finalize`](Self::finalize) and differs
+    /// between indices built separately. A [`query_cache::QueryCache`] drops
+    /// results computed against any other revision.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Estimated heap bytes of the posting lists.
+    pub fn memory_bytes(&self) -> u64 {
+        let lists = |postings: &Vec<Vec<usize>>| {
+            crate::memory::vec_bytes(postings) + postings.iter().map(crate::memory::vec_bytes).sum::<u64>()
+        };
+        lists(&self.pos_postings) + lists(&self.neg_postings)
+    }
+
     /// Build an index from `(id, vector)` pairs.
     ///
     /// IDs do not need to be contiguous.
@@ -81,6 +119,7 @@ impl TernaryInvertedIndex {
     /// Call `finalize()` before querying for best performance.
     pub fn add(&mut self, id: usize, vec: &SparseVec) {
         self.max_id = self.max_id.max(id);
+        self.revision = next_revision();
         for &d in &vec.pos {
             if d < DIM {
                 self.pos_postings[d].push(id);
@@ -95,6 +134,7 @@ impl TernaryInvertedIndex {
 
     /// Sort and deduplicate postings lists.
     pub fn finalize(&mut self) {
+        self.revision = next_revision();
         for posting in &mut self.pos_postings {
             posting.sort_unstable();
             posting.dedup();
@@ -109,6 +149,7 @@ impl TernaryInvertedIndex {
     ///
     /// Score is the sparse ternary dot product derived from index hits.
     pub fn query_top_k(&self, query: &SparseVec, k: usize) -> Vec<SearchResult> {
+        let _t = crate::profile::scope("index_candidates");
         if k == 0 {
             return Vec::new();
         }
@@ -189,6 +230,7 @@ impl TernaryInvertedIndex {
         k: usize,
     ) -> Vec<RerankedResult> {
         let candidates = self.query_top_k(query, candidate_k);
+        let _t = crate::profile::scope("rerank");
         rerank_candidates_by_cosine(query, &candidates, vectors, k)
     }
 }
@@ -0,0 +1,234 @@
+//! Semantic chunk signatures and hybrid retrieval.
+//!
+//! Exact chunk vectors (the codebook) capture byte content: two chunks match
+//! when they share bytes at similar offsets. Semantic signatures capture
+//! meaning instead. A [`ChunkEmbedder`] (for example the ONNX model runner
+//! behind the `onnx` feature) turns each chunk into a float embedding, and a
+//! [`ProjectionEncoder`] maps that onto a sparse ternary vector so it can be
+//! stored and searched like any other codebook entry.
+//!
+//! [`SemanticSignatures`] holds one such vector per chunk id, alongside the
+//! model and projection that produced them, and is saved next to the engram
+//! (see [`default_signatures_path`]) as an envelope of kind
+//! [`PayloadKind::SemanticSignatures`], its vectors Elias–Fano coded (see
+//! [`crate::trit_coding`]); files written before hold them as plain index
+//! lists, and load as well. [`hybrid_query`] searches both sets and blends
+//! the two cosines per chunk.
+
+use crate::embrfs::{bincode_io_error, Engram};
+use crate::envelope::{BinaryWriteOptions, ChecksumCodec, EnvelopeReader, EnvelopeWriter, Keyring, PayloadKind};
+use crate::projection::{ProjectionConfig, ProjectionEncoder};
+use crate::retrieval::TernaryInvertedIndex;
+use crate::trit_coding;
+use crate::vsa::{SparseVec, DIM};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Weight of the semantic cosine used by the CLI when none is given.
+pub const DEFAULT_SEMANTIC_WEIGHT: f64 = 0.5;
+
+/// `<engram>.semantic`, where the CLI keeps an engram's semantic signatures.
+pub fn default_signatures_path<P: AsRef<Path>>(engram_path: P) -> PathBuf {
+    let mut s = engram_path.as_ref().as_os_str().to_os_string();
+    s.push(".semantic");
+    PathBuf::from(s)
+}
+
+/// Produces a dense embedding for a chunk of bytes.
+pub trait ChunkEmbedder: Send + Sync {
+    /// Identifies the model (and tokenizer) so signatures from different
+    /// models are never compared.
+    fn model_id(&self) -> String;
+
+    fn embed(&self, data: &[u8]) -> io::Result<Vec<f32>>;
+}
+
+/// An embedder paired with the projection applied to its output.
+pub struct SemanticEncoder {
+    embedder: Box<dyn ChunkEmbedder>,
+    projection: ProjectionEncoder,
+}
+
+impl SemanticEncoder {
+    pub fn new(embedder: Box<dyn ChunkEmbedder>, projection: ProjectionConfig) -> Self {
+        Self {
+            embedder,
+            projection: ProjectionEncoder::new(projection),
+        }
+    }
+
+    pub fn model_id(&self) -> String {
+        self.embedder.model_id()
+    }
+
+    pub fn projection(&self) -> ProjectionConfig {
+        self.projection.config()
+    }
+
+    /// Semantic signature of `data`.
+    pub fn encode(&self, data: &[u8]) -> io::Result<SparseVec> {
+        Ok(self.projection.encode(&self.embedder.embed(data)?))
+    }
+
+    /// Whether `signatures` were produced by this model and projection.
+    pub fn matches(&self, signatures: &SemanticSignatures) -> bool {
+        signatures.model == self.model_id() && signatures.projection == self.projection()
+    }
+
+    /// Empty signature set tagged with this encoder's model and projection.
+    pub fn empty_signatures(&self) -> SemanticSignatures {
+        SemanticSignatures {
+            model: self.model_id(),
+            projection: self.projection(),
+            vectors: HashMap::new(),
+        }
+    }
+}
+
+/// Semantic signature per chunk id.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SemanticSignatures {
+    /// [`ChunkEmbedder::model_id`] of the model that produced the vectors.
+    pub model: String,
+    pub projection: ProjectionConfig,
+    #[serde(serialize_with = "crate::reproducible::sorted_map")]
+    pub vectors: HashMap<usize, SparseVec>,
+}
+
+impl SemanticSignatures {
+    /// Write the signatures as an envelope. Checksums default to XXH3 when
+    /// `opts` does not pick a codec, so the file is always framed.
+    pub fn save<P: AsRef<Path>>(&self, path: P, mut opts: BinaryWriteOptions) -> io::Result<()> {
+        if opts.checksum == ChecksumCodec::None {
+            opts.checksum = ChecksumCodec::Xxh3;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = EnvelopeWriter::new(file, PayloadKind::SemanticSignatures, opts)?;
+        let coded = CodedSignatures {
+            model: self.model.clone(),
+            projection: self.projection,
+            vectors: trit_coding::encode_map(&self.vectors, DIM),
+        };
+        writer.write_all(&trit_coding::MAGIC)?;
+        bincode::serialize_into(&mut writer, &coded).map_err(|e| bincode_io_error(*e))?;
+        writer.finish()?.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::load_with_keys(path, &Keyring::default())
+    }
+
+    /// Load signatures, decrypting them with `keys` if needed.
+    pub fn load_with_keys<P: AsRef<Path>>(path: P, keys: &Keyring) -> io::Result<Self> {
+        let path = path.as_ref();
+        if PayloadKind::sniff_file(path)? != Some(PayloadKind::SemanticSignatures) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a semantic signatures envelope",
+            ));
+        }
+        let file = BufReader::new(File::open(path)?);
+        let mut reader = EnvelopeReader::with_keys(file, PayloadKind::SemanticSignatures, keys)?;
+        let mut bytes = Vec::new();
+        io::Read::read_to_end(&mut reader, &mut bytes)?;
+        // Uncoded files start with the length of the model id, which is
+        // never as large as the magic would read.
+        let Some(coded) = bytes.strip_prefix(&trit_coding::MAGIC) else {
+            return bincode::deserialize(&bytes).map_err(|e| bincode_io_error(*e));
+        };
+        let coded: CodedSignatures = bincode::deserialize(coded).map_err(|e| bincode_io_error(*e))?;
+        let (dim, vectors) = trit_coding::decode_map(&coded.vectors)?;
+        if dim != DIM {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("signatures have dimension {}, expected {}", dim, DIM),
+            ));
+        }
+        Ok(Self {
+            model: coded.model,
+            projection: coded.projection,
+            vectors,
+        })
+    }
+}
+
+/// Serialized form of [`SemanticSignatures`], after [`trit_coding::MAGIC`].
+#[derive(Serialize, Deserialize)]
+struct CodedSignatures {
+    model: String,
+    projection: ProjectionConfig,
+    /// [`trit_coding::encode_map`] output.
+    vectors: Vec<u8>,
+}
+
+/// One chunk ranked by [`hybrid_query`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HybridHit {
+    pub id: usize,
+    /// Cosine against the exact (codebook) vector; 0 without an exact query.
+    pub exact: f64,
+    /// Cosine against the semantic signature; 0 when either side is missing.
+    pub semantic: f64,
+    /// `(1 - semantic_weight) * exact + semantic_weight * semantic`.
+    pub score: f64,
+}
+
+/// Top `k` chunks by blended exact and semantic cosine.
+///
+/// Candidates are the best matches from each side (over-fetched), rescored
+/// on both; pass `None` for a side to skip it. `semantic_weight` is clamped to
+/// `[0, 1]`.
+pub fn hybrid_query(
+    engram: &Engram,
+    signatures: &SemanticSignatures,
+    exact_query: Option<&SparseVec>,
+    semantic_query: Option<&SparseVec>,
+    k: usize,
+    semantic_weight: f64,
+) -> Vec<HybridHit> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let weight = semantic_weight.clamp(0.0, 1.0);
+    let candidate_k = k.saturating_mul(10).max(50);
+
+    let mut candidates: HashSet<usize> = HashSet::new();
+    if let Some(query) = exact_query {
+        candidates.extend(engram.query_codebook(query, candidate_k).into_iter().map(|h| h.id));
+    }
+    if let Some(query) = semantic_query {
+        let index = TernaryInvertedIndex::build_from_map(&signatures.vectors);
+        candidates.extend(
+            index
+                .query_top_k_reranked(query, &signatures.vectors, candidate_k, candidate_k)
+                .into_iter()
+                .map(|h| h.id),
+        );
+    }
+
+    let cosine = |query: Option<&SparseVec>, vectors: &HashMap<usize, SparseVec>, id: usize| {
+        match (query, vectors.get(&id)) {
+            (Some(q), Some(v)) => q.cosine(v),
+            _ => 0.0,
+        }
+    };
+    let mut hits: Vec<HybridHit> = candidates
+        .into_iter()
+        .map(|id| {
+            let exact = cosine(exact_query, &engram.codebook, id);
+            let semantic = cosine(semantic_query, &signatures.vectors, id);
+            HybridHit {
+                id,
+                exact,
+                semantic,
+                score: (1.0 - weight) * exact + weight * semantic,
+            }
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    hits.truncate(k);
+    hits
+}
@@ -139,7 +139,7 @@ impl CandidateGenerator<SparseVec> for TernarySignatureIndex {
     }
 }
 
-fn default_probe_dims(count: usize) -> Vec<usize> {
+pub(crate) fn default_probe_dims(count: usize) -> Vec<usize> {
     let mut out = Vec::with_capacity(count);
     let mut seen = HashSet::with_capacity(count * 2);
 
@@ -158,7 +158,7 @@ fn default_probe_dims(count: usize) -> Vec<usize> {
     out
 }
 
-fn splitmix64(mut x: u64) -> u64 {
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
     x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
     let mut z = x;
     z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
@@ -170,7 +170,7 @@ fn splitmix64(mut x: u64) -> u64 {
 /// - 0 => 00
 /// - +1 => 01
 /// - -1 => 10
-fn signature_for(vec: &SparseVec, probe_dims: &[usize]) -> u64 {
+pub(crate) fn signature_for(vec: &SparseVec, probe_dims: &[usize]) -> u64 {
     let mut sig: u64 = 0;
     for (i, &d) in probe_dims.iter().enumerate() {
         let lane = match sign_at(vec, d) {
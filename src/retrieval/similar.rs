@@ -0,0 +1,223 @@
+//! Find the ingested files most similar to a local file.
+//!
+//! The local file is chunked and encoded exactly as ingest would have done,
+//! with the encoding the manifest records, and then matched in two passes:
+//!
+//! 1. *Candidates*: the bundled query is ranked against the engram's
+//!    [`FileSignatures`], and each query chunk is looked up in the codebook
+//!    index. Any file reached either way is a candidate.
+//! 2. *Rerank*: each candidate is scored by the exact cosine between the
+//!    query and its signature with the file's path shift undone, and every
+//!    query chunk is paired with the candidate chunk it is closest to. Those
+//!    pairs are the [`RegionMatch`]es reported for the file.
+//!
+//! Chunk vectors of [`ChunkEncoding::Reversible`] engrams carry a
+//! permutation derived from their path, unknown for the local file; the
+//! codebook lookup sweeps every path bucket, and the rerank removes the shift
+//! of the candidate's own path so scores compare across files.
+
+use crate::embrfs::{EmbrFS, Engram, FileSignatures, Manifest};
+use crate::vsa::{ChunkEncoding, ReversibleVSAConfig, SparseVec};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// How many files and regions [`find_similar`] returns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimilarOptions {
+    /// Files returned.
+    pub k: usize,
+    /// Regions reported per file, best first.
+    pub regions_per_file: usize,
+    /// Regions scoring below this cosine are left out.
+    pub min_region_cosine: f64,
+    /// Only consider files in this namespace.
+    pub namespace: Option<String>,
+}
+
+impl Default for SimilarOptions {
+    fn default() -> Self {
+        Self {
+            k: 10,
+            regions_per_file: 5,
+            min_region_cosine: 0.3,
+            namespace: None,
+        }
+    }
+}
+
+/// A chunk of the local file paired with the chunk of an ingested file it
+/// is closest to. Offsets and lengths are in bytes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegionMatch {
+    pub query_offset: u64,
+    pub query_len: usize,
+    /// Offset of the matching chunk within the ingested file.
+    pub offset: u64,
+    pub len: usize,
+    pub chunk_id: usize,
+    pub cosine: f64,
+}
+
+/// An ingested file ranked by [`find_similar`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SimilarFile {
+    pub path: String,
+    /// Cosine between the bundled query and the file signature.
+    pub score: f64,
+    /// Fraction of the query's chunks whose region lies in this file.
+    pub coverage: f64,
+    pub chunks: usize,
+    pub regions: Vec<RegionMatch>,
+}
+
+/// Files of `manifest` most similar to `data`, best first.
+///
+/// Empty when `data` is empty or nothing in the engram shares a chunk
+/// candidate or signature neighbourhood with it.
+pub fn find_similar(engram: &Engram, manifest: &Manifest, data: &[u8], opts: &SimilarOptions) -> Vec<SimilarFile> {
+    if data.is_empty() || opts.k == 0 {
+        return Vec::new();
+    }
+    let config = manifest.encoding().vsa;
+    let chunk_size = manifest.chunk_size();
+    let query_chunks: Vec<SparseVec> = data
+        .chunks(chunk_size)
+        .map(|chunk| SparseVec::encode_chunk(chunk, &config, None))
+        .collect();
+    let query = SparseVec::bundle_sum_many(&query_chunks);
+
+    let in_namespace: Option<HashSet<&str>> = opts
+        .namespace
+        .as_deref()
+        .map(|ns| manifest.files_in_namespace(ns).map(|f| f.path.as_str()).collect());
+    let keep = |path: &str| in_namespace.as_ref().is_none_or(|paths| paths.contains(path));
+
+    // Pass 1: candidate files from signatures and from chunk lookups.
+    let candidate_files = opts.k.saturating_mul(4).max(20);
+    let signatures = FileSignatures::build_filtered(engram, manifest, |f| keep(&f.path));
+    let mut candidates: HashSet<String> = signatures
+        .query_any_shift(&query, &config, candidate_files)
+        .into_iter()
+        .map(|hit| hit.path)
+        .collect();
+
+    let mut files_of_chunk: HashMap<usize, Vec<&str>> = HashMap::new();
+    for file in manifest.files.iter().filter(|f| keep(&f.path)) {
+        for &id in &file.chunks {
+            files_of_chunk.entry(id).or_default().push(file.path.as_str());
+        }
+    }
+    let index = engram.build_codebook_index();
+    for chunk in &query_chunks {
+        for shift in bucket_shifts(&config) {
+            for hit in engram.query_codebook_with_index(&index, &chunk.permute(shift), 50, 5) {
+                for path in files_of_chunk.get(&hit.id).into_iter().flatten() {
+                    candidates.insert((*path).to_string());
+                }
+            }
+        }
+    }
+
+    // Pass 2: exact rerank of each candidate with its own path shift undone.
+    let mut ranked: Vec<SimilarFile> = manifest
+        .files
+        .iter()
+        .filter(|f| candidates.contains(&f.path) && !f.chunks.is_empty())
+        .filter_map(|file| {
+            let shift = unshift(&config, &file.path);
+            let signature = EmbrFS::file_signature(engram, file)?.inverse_permute(shift);
+            let chunks: Vec<(usize, SparseVec)> = file
+                .chunks
+                .iter()
+                .filter_map(|&id| Some((id, engram.codebook.get(&id)?.inverse_permute(shift))))
+                .collect();
+            Some((file, query.cosine(&signature), chunks))
+        })
+        .map(|(file, score, chunks)| SimilarFile {
+            path: file.path.clone(),
+            score,
+            coverage: 0.0,
+            chunks: file.chunks.len(),
+            regions: best_regions(&query_chunks, &chunks, chunk_size, file.size, data.len()),
+        })
+        .collect();
+
+    // A query chunk counts towards the coverage of the file holding its
+    // closest region overall.
+    let mut owners = vec![0usize; ranked.len()];
+    for q in 0..query_chunks.len() {
+        let query_offset = (q * chunk_size) as u64;
+        let owner = ranked
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| f.regions.iter().find(|r| r.query_offset == query_offset).map(|r| (i, r.cosine)))
+            .filter(|&(_, cosine)| cosine >= opts.min_region_cosine)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        if let Some((i, _)) = owner {
+            owners[i] += 1;
+        }
+    }
+    for (file, owned) in ranked.iter_mut().zip(owners) {
+        file.coverage = owned as f64 / query_chunks.len() as f64;
+        file.regions.retain(|r| r.cosine >= opts.min_region_cosine);
+        file.regions
+            .sort_by(|a, b| b.cosine.partial_cmp(&a.cosine).unwrap_or(std::cmp::Ordering::Equal));
+        file.regions.truncate(opts.regions_per_file);
+    }
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    ranked.truncate(opts.k);
+    ranked
+}
+
+/// Shifts a chunk encoded without a path may need to line up with one
+/// encoded at some path.
+fn bucket_shifts(config: &ReversibleVSAConfig) -> impl Iterator<Item = usize> + '_ {
+    let buckets = match config.chunk_encoding {
+        ChunkEncoding::Reversible => config.max_path_depth.max(1),
+        ChunkEncoding::Similarity { .. } => 1,
+    };
+    (0..buckets).map(|depth| depth * config.base_shift)
+}
+
+/// The shift ingest applied to the chunks of the file at `path`.
+fn unshift(config: &ReversibleVSAConfig, path: &str) -> usize {
+    match config.chunk_encoding {
+        ChunkEncoding::Reversible => config.path_shift(path),
+        ChunkEncoding::Similarity { .. } => 0,
+    }
+}
+
+/// For each query chunk, the file chunk closest to it, in query order.
+fn best_regions(
+    query_chunks: &[SparseVec],
+    chunks: &[(usize, SparseVec)],
+    chunk_size: usize,
+    file_size: usize,
+    query_size: usize,
+) -> Vec<RegionMatch> {
+    query_chunks
+        .iter()
+        .enumerate()
+        .filter_map(|(q, query)| {
+            let (pos, (id, cosine)) = chunks
+                .iter()
+                .map(|(id, vec)| (*id, query.cosine(vec)))
+                .enumerate()
+                .max_by(|a, b| a.1 .1.partial_cmp(&b.1 .1).unwrap_or(std::cmp::Ordering::Equal))?;
+            Some(RegionMatch {
+                query_offset: (q * chunk_size) as u64,
+                query_len: query_size.saturating_sub(q * chunk_size).min(chunk_size),
+                offset: (pos * chunk_size) as u64,
+                len: file_size.saturating_sub(pos * chunk_size).min(chunk_size),
+                chunk_id: id,
+                cosine,
+            })
+        })
+        .collect()
+}
@@ -6,6 +6,9 @@
 //! - Storage footprint calculations
 //! - Resilience testing helpers (chaos injection, noise tolerance)
 //!
+//! Integrity validation and chaos injection live in [`crate::chaos`], which
+//! downstream crates get with the `chaos` feature; they are re-exported here.
+//!
 //! # Usage
 //!
 //! ```rust,ignore
@@ -22,6 +25,8 @@
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+pub use crate::chaos::{ChaosInjector, IntegrityReport, IntegrityValidator};
+
 // ============================================================================
 // PERFORMANCE METRICS
 // ============================================================================
@@ -239,274 +244,6 @@ impl TimingStats {
     }
 }
 
-// ============================================================================
-// DATA INTEGRITY VALIDATION
-// ============================================================================
-
-/// Results from integrity validation.
-#[derive(Clone, Debug, Default)]
-pub struct IntegrityReport {
-    /// Total checks performed
-    pub checks_total: u64,
-    /// Checks that passed
-    pub checks_passed: u64,
-    /// Detected bitflips (single bit errors)
-    pub bitflips_detected: u64,
-    /// Multi-bit corruption events
-    pub corruption_events: u64,
-    /// Algebraic invariant violations
-    pub invariant_violations: u64,
-    /// Specific failure messages
-    pub failures: Vec<String>,
-}
-
-impl IntegrityReport {
-    /// Check if all validations passed.
-    pub fn is_ok(&self) -> bool {
-        self.checks_passed == self.checks_total && self.failures.is_empty()
-    }
-
-    /// Pass rate as percentage.
-    pub fn pass_rate(&self) -> f64 {
-        if self.checks_total == 0 {
-            100.0
-        } else {
-            (self.checks_passed as f64 / self.checks_total as f64) * 100.0
-        }
-    }
-
-    /// Record a passed check.
-    pub fn pass(&mut self) {
-        self.checks_total += 1;
-        self.checks_passed += 1;
-    }
-
-    /// Record a failed check with message.
-    pub fn fail(&mut self, msg: impl Into<String>) {
-        self.checks_total += 1;
-        self.failures.push(msg.into());
-    }
-
-    /// Record detected bitflip.
-    pub fn record_bitflip(&mut self) {
-        self.bitflips_detected += 1;
-    }
-
-    /// Record corruption event.
-    pub fn record_corruption(&mut self) {
-        self.corruption_events += 1;
-    }
-
-    /// Record invariant violation.
-    pub fn record_invariant_violation(&mut self, msg: impl Into<String>) {
-        self.invariant_violations += 1;
-        self.failures.push(format!("INVARIANT: {}", msg.into()));
-    }
-}
-
-/// Validates data integrity for VSA operations.
-pub struct IntegrityValidator {
-    /// Enable verbose logging
-    pub verbose: bool,
-}
-
-impl IntegrityValidator {
-    pub fn new() -> Self {
-        Self { verbose: false }
-    }
-
-    pub fn verbose(mut self) -> Self {
-        self.verbose = true;
-        self
-    }
-
-    /// Validate bitsliced vector invariants.
-    ///
-    /// Checks:
-    /// - No position has both pos and neg bits set
-    /// - Length matches word count
-    /// - Trailing bits are zero
-    pub fn validate_bitsliced(&self, v: &crate::bitsliced::BitslicedTritVec) -> IntegrityReport {
-        let mut report = IntegrityReport::default();
-
-        // Check no overlapping pos/neg bits
-        let words = crate::bitsliced::BitslicedTritVec::word_count(v.len());
-        for w in 0..words {
-            let overlap = v.pos_word(w) & v.neg_word(w);
-            if overlap != 0 {
-                let count = overlap.count_ones();
-                report.record_corruption();
-                report.fail(format!(
-                    "Word {} has {} positions with both pos and neg set",
-                    w, count
-                ));
-            } else {
-                report.pass();
-            }
-        }
-
-        // Check trailing bits in last word are zero
-        if words > 0 {
-            let trailing_bits = v.len() % 64;
-            if trailing_bits != 0 {
-                let mask = !((1u64 << trailing_bits) - 1);
-                let pos_trailing = v.pos_word(words - 1) & mask;
-                let neg_trailing = v.neg_word(words - 1) & mask;
-                if pos_trailing != 0 || neg_trailing != 0 {
-                    report.fail(format!(
-                        "Trailing bits not zero: pos={:016x}, neg={:016x}",
-                        pos_trailing, neg_trailing
-                    ));
-                } else {
-                    report.pass();
-                }
-            }
-        }
-
-        report
-    }
-
-    /// Validate algebraic invariants for bind operation.
-    ///
-    /// Checks:
-    /// - Self-inverse: A ⊙ A = all +1 at non-zero positions
-    /// - Commutativity: A ⊙ B = B ⊙ A
-    pub fn validate_bind_invariants(
-        &self,
-        a: &crate::bitsliced::BitslicedTritVec,
-        b: &crate::bitsliced::BitslicedTritVec,
-    ) -> IntegrityReport {
-        let mut report = IntegrityReport::default();
-
-        // Self-inverse check
-        let a_squared = a.bind(a);
-        let a_nnz = a.nnz();
-        let a2_pos = a_squared.to_sparse().pos.len();
-        let a2_neg = a_squared.to_sparse().neg.len();
-        
-        if a2_neg != 0 {
-            report.record_invariant_violation(format!(
-                "Self-inverse violation: A⊙A has {} negative trits (should be 0)",
-                a2_neg
-            ));
-        } else if a2_pos != a_nnz {
-            report.record_invariant_violation(format!(
-                "Self-inverse violation: A⊙A has {} positive trits (expected {})",
-                a2_pos, a_nnz
-            ));
-        } else {
-            report.pass();
-        }
-
-        // Commutativity check
-        let ab = a.bind(b);
-        let ba = b.bind(a);
-        let ab_sparse = ab.to_sparse();
-        let ba_sparse = ba.to_sparse();
-        
-        if ab_sparse.pos != ba_sparse.pos || ab_sparse.neg != ba_sparse.neg {
-            report.record_invariant_violation("Commutativity violation: A⊙B ≠ B⊙A");
-        } else {
-            report.pass();
-        }
-
-        report
-    }
-
-    /// Validate bundle operation properties.
-    pub fn validate_bundle_invariants(
-        &self,
-        a: &crate::bitsliced::BitslicedTritVec,
-        b: &crate::bitsliced::BitslicedTritVec,
-    ) -> IntegrityReport {
-        let mut report = IntegrityReport::default();
-
-        // Commutativity check
-        let ab = a.bundle(b);
-        let ba = b.bundle(a);
-        let ab_sparse = ab.to_sparse();
-        let ba_sparse = ba.to_sparse();
-
-        if ab_sparse.pos != ba_sparse.pos || ab_sparse.neg != ba_sparse.neg {
-            report.record_invariant_violation("Bundle commutativity violation: A⊕B ≠ B⊕A");
-        } else {
-            report.pass();
-        }
-
-        // Conflict cancel: P + N = Z
-        let conflict_pos: Vec<usize> = a.to_sparse().pos.iter()
-            .filter(|&&i| b.to_sparse().neg.contains(&i))
-            .copied()
-            .collect();
-        
-        for &pos in &conflict_pos {
-            let result_trit = ab.get(pos);
-            if result_trit != crate::ternary::Trit::Z {
-                report.fail(format!(
-                    "Conflict cancel violation at {}: P+N={:?} (expected Z)",
-                    pos, result_trit
-                ));
-            } else {
-                report.pass();
-            }
-        }
-
-        report
-    }
-
-    /// Detect potential bitflips by comparing two vectors.
-    pub fn detect_bitflips(
-        &self,
-        expected: &crate::bitsliced::BitslicedTritVec,
-        actual: &crate::bitsliced::BitslicedTritVec,
-    ) -> IntegrityReport {
-        let mut report = IntegrityReport::default();
-
-        if expected.len() != actual.len() {
-            report.fail(format!(
-                "Length mismatch: expected {}, got {}",
-                expected.len(), actual.len()
-            ));
-            return report;
-        }
-
-        let words = crate::bitsliced::BitslicedTritVec::word_count(expected.len());
-        let mut total_flips = 0u64;
-
-        for w in 0..words {
-            let pos_diff = expected.pos_word(w) ^ actual.pos_word(w);
-            let neg_diff = expected.neg_word(w) ^ actual.neg_word(w);
-            
-            let pos_flips = pos_diff.count_ones();
-            let neg_flips = neg_diff.count_ones();
-            
-            total_flips += pos_flips as u64 + neg_flips as u64;
-            
-            if pos_flips == 1 && neg_flips == 0 {
-                report.record_bitflip();
-            } else if pos_flips == 0 && neg_flips == 1 {
-                report.record_bitflip();
-            } else if pos_flips + neg_flips > 0 {
-                report.record_corruption();
-            }
-        }
-
-        if total_flips == 0 {
-            report.pass();
-        } else {
-            report.fail(format!("Detected {} total bit differences", total_flips));
-        }
-
-        report
-    }
-}
-
-impl Default for IntegrityValidator {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 // ============================================================================
 // STORAGE FOOTPRINT CALCULATIONS
 // ============================================================================
@@ -644,106 +381,6 @@ impl StorageFootprint {
     }
 }
 
-// ============================================================================
-// CHAOS / RESILIENCE TESTING
-// ============================================================================
-
-/// Chaos injection utilities for resilience testing.
-pub struct ChaosInjector {
-    /// Random seed for reproducibility
-    seed: u64,
-    /// Injection probability (0.0 - 1.0)
-    probability: f64,
-}
-
-impl ChaosInjector {
-    /// Create new chaos injector with seed.
-    pub fn new(seed: u64) -> Self {
-        Self {
-            seed,
-            probability: 0.01, // 1% default
-        }
-    }
-
-    /// Set injection probability.
-    pub fn with_probability(mut self, p: f64) -> Self {
-        self.probability = p.clamp(0.0, 1.0);
-        self
-    }
-
-    /// Inject random bitflips into a bitsliced vector.
-    pub fn inject_bitflips(
-        &self,
-        v: &mut crate::bitsliced::BitslicedTritVec,
-        count: usize,
-    ) -> Vec<usize> {
-        use std::collections::HashSet;
-
-        let mut flipped = Vec::new();
-        let mut seen = HashSet::new();
-        let mut state = self.seed;
-
-        for _ in 0..count {
-            // Simple LCG for reproducibility
-            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
-            let pos = (state as usize) % v.len();
-
-            if seen.insert(pos) {
-                let current = v.get(pos);
-                let new_trit = match current {
-                    crate::ternary::Trit::P => crate::ternary::Trit::N,
-                    crate::ternary::Trit::N => crate::ternary::Trit::P,
-                    crate::ternary::Trit::Z => {
-                        if state % 2 == 0 {
-                            crate::ternary::Trit::P
-                        } else {
-                            crate::ternary::Trit::N
-                        }
-                    }
-                };
-                v.set(pos, new_trit);
-                flipped.push(pos);
-            }
-        }
-
-        flipped
-    }
-
-    /// Inject noise by randomly setting trits to zero.
-    pub fn inject_erasures(
-        &self,
-        v: &mut crate::bitsliced::BitslicedTritVec,
-        count: usize,
-    ) -> Vec<usize> {
-        let mut erased = Vec::new();
-        let mut state = self.seed.wrapping_add(12345);
-
-        for _ in 0..count {
-            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
-            let pos = (state as usize) % v.len();
-
-            if v.get(pos) != crate::ternary::Trit::Z {
-                v.set(pos, crate::ternary::Trit::Z);
-                erased.push(pos);
-            }
-        }
-
-        erased
-    }
-
-    /// Create corrupted copy with specified error rate.
-    pub fn corrupt_copy(
-        &self,
-        v: &crate::bitsliced::BitslicedTritVec,
-        error_rate: f64,
-    ) -> crate::bitsliced::BitslicedTritVec {
-        let mut corrupted = v.clone();
-        let errors = ((v.len() as f64) * error_rate) as usize;
-        self.inject_bitflips(&mut corrupted, errors);
-        corrupted
-    }
-}
-
 // ============================================================================
 // TEST ASSERTIONS
 // ============================================================================
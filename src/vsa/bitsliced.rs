@@ -42,8 +42,12 @@
 
 use crate::ternary::Trit;
 use crate::vsa::SparseVec;
+use alloc::{format, string::{String, ToString}, vec, vec::Vec};
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicU8, Ordering};
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+use core::sync::atomic::{AtomicU8, Ordering};
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
 // ============================================================================
 // CPU FEATURE DETECTION (Runtime)
@@ -51,22 +55,27 @@ use std::sync::atomic::{AtomicU8, Ordering};
 
 /// Cached AVX-512 detection result.
 /// 0 = not checked, 1 = not available, 2 = available
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
 static AVX512_AVAILABLE: AtomicU8 = AtomicU8::new(0);
 
 /// Cached AVX2 detection result.
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
 static AVX2_AVAILABLE: AtomicU8 = AtomicU8::new(0);
 
 /// Check if AVX-512F is available at runtime (cached after first call).
 ///
 /// This enables automatic dispatch to SIMD-optimized code paths without
-/// requiring compile-time feature flags.
+/// requiring compile-time feature flags. The first call also runs the
+/// [`crate::simd`] self-test; AVX-512 kernels that fail it count as
+/// unavailable.
 #[inline]
 pub fn has_avx512() -> bool {
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
     {
         match AVX512_AVAILABLE.load(Ordering::Relaxed) {
             0 => {
-                let available = std::arch::is_x86_feature_detected!("avx512f");
+                let available = std::arch::is_x86_feature_detected!("avx512f")
+                    && crate::simd::is_enabled(crate::simd::SimdPath::Avx512);
                 AVX512_AVAILABLE.store(if available { 2 } else { 1 }, Ordering::Relaxed);
                 available
             }
@@ -74,20 +83,26 @@ pub fn has_avx512() -> bool {
             _ => false,
         }
     }
+    #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+    {
+        cfg!(target_feature = "avx512f")
+    }
     #[cfg(not(target_arch = "x86_64"))]
     {
         false
     }
 }
 
-/// Check if AVX2 is available at runtime (cached after first call).
+/// Check if AVX2 is available at runtime (cached after first call), and
+/// not disabled by the [`crate::simd`] self-test.
 #[inline]
 pub fn has_avx2() -> bool {
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
     {
         match AVX2_AVAILABLE.load(Ordering::Relaxed) {
             0 => {
-                let available = std::arch::is_x86_feature_detected!("avx2");
+                let available = std::arch::is_x86_feature_detected!("avx2")
+                    && crate::simd::is_enabled(crate::simd::SimdPath::Avx2);
                 AVX2_AVAILABLE.store(if available { 2 } else { 1 }, Ordering::Relaxed);
                 available
             }
@@ -95,6 +110,10 @@ pub fn has_avx2() -> bool {
             _ => false,
         }
     }
+    #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+    {
+        cfg!(target_feature = "avx2")
+    }
     #[cfg(not(target_arch = "x86_64"))]
     {
         false
@@ -110,11 +129,21 @@ pub fn simd_features_string() -> String {
     if has_avx2() {
         features.push("AVX2");
     }
-    if features.is_empty() {
+    #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+    let mut out = if features.is_empty() {
         "scalar only".to_string()
     } else {
         features.join(", ")
+    };
+    #[cfg(feature = "std")]
+    {
+        let disabled = &crate::simd::self_test().disabled;
+        if !disabled.is_empty() {
+            let paths: Vec<String> = disabled.iter().map(|(path, _)| path.to_string()).collect();
+            out.push_str(&format!(" ({} disabled by self-test)", paths.join(", ")));
+        }
     }
+    out
 }
 
 // ============================================================================
@@ -709,7 +738,7 @@ impl BitslicedTritVec {
     /// Negate in place.
     #[inline]
     pub fn negate_in_place(&mut self) {
-        std::mem::swap(&mut self.pos, &mut self.neg);
+        core::mem::swap(&mut self.pos, &mut self.neg);
     }
 
     // ========================================================================
@@ -869,7 +898,7 @@ impl BitslicedTritVec {
 fn pext_u64(src: u64, mask: u64) -> u64 {
     #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
     {
-        return unsafe { std::arch::x86_64::_pext_u64(src, mask) };
+        return unsafe { core::arch::x86_64::_pext_u64(src, mask) };
     }
 
     #[allow(unreachable_code)]
@@ -897,7 +926,7 @@ fn pext_u64(src: u64, mask: u64) -> u64 {
 fn pdep_u64(src: u64, mask: u64) -> u64 {
     #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
     {
-        return unsafe { std::arch::x86_64::_pdep_u64(src, mask) };
+        return unsafe { core::arch::x86_64::_pdep_u64(src, mask) };
     }
 
     #[allow(unreachable_code)]
@@ -1071,6 +1100,169 @@ impl CarrySaveBundle {
     }
 }
 
+// ============================================================================
+// COUNTED BUNDLE
+// ============================================================================
+
+/// Exact per-position vote counts of a bundle, so vectors can be removed
+/// again.
+///
+/// [`CarrySaveBundle`] folds its counters back into a single vote every
+/// three vectors, which loses the information needed to take a vector out.
+/// `CountedBundle` keeps the same bit-sliced layout but grows a plane per
+/// counter bit as needed, so the counts stay exact: after adding vectors
+/// and subtracting some of them, [`CountedBundle::finalize`] is the
+/// majority vote of the ones left, whatever the order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountedBundle {
+    len: usize,
+    /// Bit `k` of every position's positive vote count is bit `k` of plane `k`.
+    pos: Vec<Vec<u64>>,
+    /// Negative vote counts, laid out like `pos`.
+    neg: Vec<Vec<u64>>,
+    /// Vectors added and not subtracted.
+    count: u64,
+}
+
+impl CountedBundle {
+    /// Empty counts for vectors of `len` trits.
+    pub fn new(len: usize) -> Self {
+        Self { len, pos: Vec::new(), neg: Vec::new(), count: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of vectors added and not subtracted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Add one vote per nonzero trit of `vec`.
+    pub fn add(&mut self, vec: &BitslicedTritVec) {
+        let words = BitslicedTritVec::word_count(self.len);
+        Self::add_planes(&mut self.pos, words, &vec.pos);
+        Self::add_planes(&mut self.neg, words, &vec.neg);
+        self.count += 1;
+    }
+
+    /// Take back the votes of a vector added before. Returns false, leaving
+    /// the counts untouched, if some trit of `vec` has no vote to take back,
+    /// i.e. `vec` was never added.
+    #[must_use]
+    pub fn subtract(&mut self, vec: &BitslicedTritVec) -> bool {
+        let words = BitslicedTritVec::word_count(self.len);
+        let covered = |planes: &[Vec<u64>], input: &[u64]| {
+            (0..words.min(input.len())).all(|w| input[w] & !planes.iter().fold(0, |any, p| any | p[w]) == 0)
+        };
+        if self.count == 0 || !covered(&self.pos, &vec.pos) || !covered(&self.neg, &vec.neg) {
+            return false;
+        }
+        Self::subtract_planes(&mut self.pos, words, &vec.pos);
+        Self::subtract_planes(&mut self.neg, words, &vec.neg);
+        self.count -= 1;
+        true
+    }
+
+    /// Majority vote of the counted vectors: `P` where positive votes
+    /// outnumber negative ones, `N` where the reverse holds, `Z` otherwise.
+    pub fn finalize(&self) -> BitslicedTritVec {
+        let words = BitslicedTritVec::word_count(self.len);
+        let mut out = BitslicedTritVec::new_zero(self.len);
+        let depth = self.pos.len().max(self.neg.len());
+        for w in 0..words {
+            let (mut gt, mut lt, mut eq) = (0u64, 0u64, !0u64);
+            for k in (0..depth).rev() {
+                let a = self.pos.get(k).map_or(0, |p| p[w]);
+                let b = self.neg.get(k).map_or(0, |p| p[w]);
+                gt |= eq & a & !b;
+                lt |= eq & !a & b;
+                eq &= !(a ^ b);
+            }
+            out.pos[w] = gt;
+            out.neg[w] = lt;
+        }
+        out
+    }
+
+    /// Check the plane layout, for counts read from storage.
+    pub fn validate(&self) -> Result<(), String> {
+        let words = BitslicedTritVec::word_count(self.len);
+        let tail = match self.len % 64 {
+            0 => 0,
+            bits => !0u64 << bits,
+        };
+        for (sign, planes) in [("positive", &self.pos), ("negative", &self.neg)] {
+            if planes.len() > 64 {
+                return Err(format!("{} counts have {} bit planes", sign, planes.len()));
+            }
+            for plane in planes {
+                if plane.len() != words {
+                    return Err(format!("{} count plane has {} words, expected {}", sign, plane.len(), words));
+                }
+                if plane.last().is_some_and(|&last| last & tail != 0) {
+                    return Err(format!("{} counts set past trit {}", sign, self.len));
+                }
+            }
+            if planes.last().is_some_and(|p| p.iter().all(|&w| w == 0)) {
+                return Err(format!("{} counts end in an empty bit plane", sign));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bit planes of the positive and negative counts, least significant
+    /// first, each of `word_count(len)` words.
+    pub fn planes(&self) -> (&[Vec<u64>], &[Vec<u64>]) {
+        (&self.pos, &self.neg)
+    }
+
+    /// Counts from stored planes; check them with [`CountedBundle::validate`].
+    pub fn from_planes(len: usize, count: u64, pos: Vec<Vec<u64>>, neg: Vec<Vec<u64>>) -> Self {
+        Self { len, pos, neg, count }
+    }
+
+    fn add_planes(planes: &mut Vec<Vec<u64>>, words: usize, input: &[u64]) {
+        for (w, &bits) in input.iter().enumerate().take(words) {
+            let mut carry = bits;
+            let mut k = 0;
+            while carry != 0 {
+                if k == planes.len() {
+                    planes.push(vec![0; words]);
+                }
+                let plane = &mut planes[k][w];
+                let next = *plane & carry;
+                *plane ^= carry;
+                carry = next;
+                k += 1;
+            }
+        }
+    }
+
+    /// Subtract one from every count `input` marks; none may be zero.
+    fn subtract_planes(planes: &mut Vec<Vec<u64>>, words: usize, input: &[u64]) {
+        for (w, &bits) in input.iter().enumerate().take(words) {
+            let mut borrow = bits;
+            let mut k = 0;
+            while borrow != 0 {
+                let plane = &mut planes[k][w];
+                let next = !*plane & borrow;
+                *plane ^= borrow;
+                borrow = next;
+                k += 1;
+            }
+        }
+        while planes.last().is_some_and(|p| p.iter().all(|&w| w == 0)) {
+            planes.pop();
+        }
+    }
+}
+
 // ============================================================================
 // SIMD ACCELERATION (Optional)
 // ============================================================================
@@ -1082,7 +1274,7 @@ pub mod avx512 {
     //! These functions process 512 trits per iteration (8 × u64 per plane).
 
     use super::BitslicedTritVec;
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
     /// AVX-512 bind: processes 512 trits per iteration.
     ///
@@ -1223,10 +1415,10 @@ pub mod avx512 {
             let np = _mm512_and_si512(an, bp);
 
             // Extract and popcount each word (no AVX-512 POPCNT, use scalar)
-            let pp_arr: [u64; 8] = std::mem::transmute(pp);
-            let nn_arr: [u64; 8] = std::mem::transmute(nn);
-            let pn_arr: [u64; 8] = std::mem::transmute(pn);
-            let np_arr: [u64; 8] = std::mem::transmute(np);
+            let pp_arr: [u64; 8] = core::mem::transmute(pp);
+            let nn_arr: [u64; 8] = core::mem::transmute(nn);
+            let pn_arr: [u64; 8] = core::mem::transmute(pn);
+            let np_arr: [u64; 8] = core::mem::transmute(np);
 
             for i in 0..8 {
                 acc += (pp_arr[i].count_ones() + nn_arr[i].count_ones()) as i32;
@@ -1493,6 +1685,54 @@ mod tests {
         assert_eq!(shifted.get(32), Trit::P, "pos 32 should have src[0]=P");
     }
 
+    #[test]
+    fn test_counted_bundle_subtract_restores_majority() {
+        let dim = 200;
+        let vecs: Vec<BitslicedTritVec> = (0..9u64)
+            .map(|seed| {
+                let mut v = BitslicedTritVec::new_zero(dim);
+                for i in 0..dim {
+                    match (i as u64 * 7 + seed * 13) % 5 {
+                        0 | 1 => v.set(i, Trit::P),
+                        2 => v.set(i, Trit::N),
+                        _ => {}
+                    }
+                }
+                v
+            })
+            .collect();
+        let majority = |vs: &[&BitslicedTritVec]| {
+            let mut out = BitslicedTritVec::new_zero(dim);
+            for i in 0..dim {
+                let sum: i32 = vs.iter().map(|v| v.get(i).to_i8() as i32).sum();
+                out.set(i, Trit::from_i8_exact(sum.signum() as i8).unwrap());
+            }
+            out
+        };
+
+        let mut counts = CountedBundle::new(dim);
+        for v in &vecs {
+            counts.add(v);
+        }
+        assert_eq!(counts.count(), 9);
+        assert_eq!(counts.finalize(), majority(&vecs.iter().collect::<Vec<_>>()));
+
+        for i in [4, 0, 7] {
+            assert!(counts.subtract(&vecs[i]));
+        }
+        let rest: Vec<&BitslicedTritVec> =
+            vecs.iter().enumerate().filter(|(i, _)| ![4, 0, 7].contains(i)).map(|(_, v)| v).collect();
+        assert_eq!(counts.finalize(), majority(&rest));
+        assert!(counts.validate().is_ok());
+
+        // Removing everything leaves no planes behind.
+        for v in rest {
+            assert!(counts.subtract(v));
+        }
+        assert_eq!(counts, CountedBundle::new(dim));
+        assert!(!counts.subtract(&vecs[0]), "subtracted a vector that was never added");
+    }
+
     #[test]
     fn test_simd_feature_detection() {
         // Just verify the detection doesn't panic
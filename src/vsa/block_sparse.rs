@@ -49,11 +49,14 @@
 //! assert!(bound.nnz() > 0);
 //! ```
 
-use std::fmt;
+use core::fmt;
 use serde::{Deserialize, Serialize};
 
 use crate::vsa::SparseVec;
 use crate::bitsliced::BitslicedTritVec;
+use alloc::{vec, vec::Vec};
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
 // Re-export SIMD detection from bitsliced module for runtime dispatch
 pub use crate::bitsliced::{has_avx2, has_avx512};
@@ -77,6 +80,13 @@ pub enum BlockError {
         /// Index in the blocks vec where unsorted pair was found.
         index: usize,
     },
+    /// A block lies entirely beyond the vector's dimension.
+    BlockOutOfRange {
+        /// The block ID past the end.
+        block_id: u32,
+        /// Dimension of the vector.
+        dim: usize,
+    },
     /// Dimension mismatch between vectors in an operation.
     DimensionMismatch {
         /// Expected dimension.
@@ -101,6 +111,9 @@ impl fmt::Display for BlockError {
             BlockError::UnsortedBlocks { index } => {
                 write!(f, "Blocks not sorted: violation at index {}", index)
             }
+            BlockError::BlockOutOfRange { block_id, dim } => {
+                write!(f, "Block {} lies beyond dimension {}", block_id, dim)
+            }
             BlockError::DimensionMismatch { expected, got } => {
                 write!(f, "Dimension mismatch: expected {}, got {}", expected, got)
             }
@@ -108,7 +121,7 @@ impl fmt::Display for BlockError {
     }
 }
 
-impl std::error::Error for BlockError {}
+impl core::error::Error for BlockError {}
 
 // ============================================================================
 // BLOCK: 64-TRIT ALIGNED UNIT
@@ -301,7 +314,11 @@ impl Block {
 /// 1. Blocks are sorted by `block_id` in ascending order
 /// 2. Each block satisfies `(pos & neg) == 0`
 /// 3. No zero blocks are stored (garbage collected on operations)
+///
+/// Deserialization checks invariants 1 and 2 and that every block lies
+/// within the dimension, and fails with a [`BlockError`] otherwise.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawBlockSparse")]
 pub struct BlockSparseTritVec {
     /// Logical dimension count.
     dim: usize,
@@ -309,6 +326,31 @@ pub struct BlockSparseTritVec {
     blocks: Vec<(u32, Block)>,
 }
 
+/// Unchecked serialized form of [`BlockSparseTritVec`].
+#[derive(Deserialize)]
+struct RawBlockSparse {
+    dim: usize,
+    blocks: Vec<(u32, Block)>,
+}
+
+impl TryFrom<RawBlockSparse> for BlockSparseTritVec {
+    type Error = BlockError;
+
+    fn try_from(raw: RawBlockSparse) -> Result<Self, BlockError> {
+        let v = BlockSparseTritVec {
+            dim: raw.dim,
+            blocks: raw.blocks,
+        };
+        v.validate()?;
+        if let Some(&(block_id, _)) = v.blocks.last() {
+            if block_id as usize * 64 >= v.dim {
+                return Err(BlockError::BlockOutOfRange { block_id, dim: v.dim });
+            }
+        }
+        Ok(v)
+    }
+}
+
 impl Default for BlockSparseTritVec {
     fn default() -> Self {
         Self::new(0)
@@ -365,7 +407,7 @@ impl BlockSparseTritVec {
     /// assert_eq!(block_sparse.block_count(), 3); // blocks 0, 1, 2
     /// ```
     pub fn from_sparse(sparse: &SparseVec, dim: usize) -> Self {
-        use std::collections::BTreeMap;
+        use alloc::collections::BTreeMap;
 
         // Group indices by block
         let mut block_map: BTreeMap<u32, Block> = BTreeMap::new();
@@ -393,6 +435,13 @@ impl BlockSparseTritVec {
         Self { dim, blocks }
     }
 
+    /// Build from `(block_id, block)` pairs, checking what deserialization
+    /// checks: ids strictly ascending, no trit both +1 and -1, and no block
+    /// past `dim`.
+    pub fn from_blocks(dim: usize, blocks: Vec<(u32, Block)>) -> Result<Self, BlockError> {
+        RawBlockSparse { dim, blocks }.try_into()
+    }
+
     /// Convert from a dense `BitslicedTritVec` to block-sparse.
     ///
     /// Extracts non-zero blocks from the dense representation.
@@ -616,13 +665,23 @@ impl BlockSparseTritVec {
     ///
     /// Debug builds panic on dimension mismatch.
     pub fn bind(&self, other: &Self) -> Self {
+        let mut out = Self::new(self.dim);
+        self.bind_into(other, &mut out);
+        out
+    }
+
+    /// [`bind`](Self::bind) into a pre-allocated output, reusing its block
+    /// storage (avoids allocation in hot loops). `out` is overwritten.
+    pub fn bind_into(&self, other: &Self, out: &mut Self) {
         debug_assert_eq!(
             self.dim, other.dim,
             "Dimension mismatch in bind: {} vs {}",
             self.dim, other.dim
         );
 
-        let mut result = Vec::new();
+        out.dim = self.dim;
+        let result = &mut out.blocks;
+        result.clear();
         let mut i = 0;
         let mut j = 0;
 
@@ -631,15 +690,15 @@ impl BlockSparseTritVec {
             let (id_b, block_b) = other.blocks[j];
 
             match id_a.cmp(&id_b) {
-                std::cmp::Ordering::Less => {
+                core::cmp::Ordering::Less => {
                     // Block only in self: bind with zero = zero
                     i += 1;
                 }
-                std::cmp::Ordering::Greater => {
+                core::cmp::Ordering::Greater => {
                     // Block only in other: bind with zero = zero
                     j += 1;
                 }
-                std::cmp::Ordering::Equal => {
+                core::cmp::Ordering::Equal => {
                     // Both have this block
                     let bound = block_a.bind(&block_b);
                     if !bound.is_zero() {
@@ -650,11 +709,6 @@ impl BlockSparseTritVec {
                 }
             }
         }
-
-        Self {
-            dim: self.dim,
-            blocks: result,
-        }
     }
 
     /// Bundle two block-sparse vectors (saturating add).
@@ -665,13 +719,23 @@ impl BlockSparseTritVec {
     ///
     /// Debug builds panic on dimension mismatch.
     pub fn bundle(&self, other: &Self) -> Self {
+        let mut out = Self::with_capacity(self.dim, self.blocks.len() + other.blocks.len());
+        self.bundle_into(other, &mut out);
+        out
+    }
+
+    /// [`bundle`](Self::bundle) into a pre-allocated output, reusing its
+    /// block storage. `out` is overwritten.
+    pub fn bundle_into(&self, other: &Self, out: &mut Self) {
         debug_assert_eq!(
             self.dim, other.dim,
             "Dimension mismatch in bundle: {} vs {}",
             self.dim, other.dim
         );
 
-        let mut result = Vec::with_capacity(self.blocks.len() + other.blocks.len());
+        out.dim = self.dim;
+        let result = &mut out.blocks;
+        result.clear();
         let mut i = 0;
         let mut j = 0;
 
@@ -680,17 +744,17 @@ impl BlockSparseTritVec {
             let (id_b, block_b) = other.blocks[j];
 
             match id_a.cmp(&id_b) {
-                std::cmp::Ordering::Less => {
+                core::cmp::Ordering::Less => {
                     // Block only in self: bundle with zero = self
                     result.push((id_a, block_a));
                     i += 1;
                 }
-                std::cmp::Ordering::Greater => {
+                core::cmp::Ordering::Greater => {
                     // Block only in other: bundle with zero = other
                     result.push((id_b, block_b));
                     j += 1;
                 }
-                std::cmp::Ordering::Equal => {
+                core::cmp::Ordering::Equal => {
                     // Both have this block
                     let bundled = block_a.bundle(&block_b);
                     if !bundled.is_zero() {
@@ -703,19 +767,8 @@ impl BlockSparseTritVec {
         }
 
         // Append remaining blocks
-        while i < self.blocks.len() {
-            result.push(self.blocks[i]);
-            i += 1;
-        }
-        while j < other.blocks.len() {
-            result.push(other.blocks[j]);
-            j += 1;
-        }
-
-        Self {
-            dim: self.dim,
-            blocks: result,
-        }
+        result.extend_from_slice(&self.blocks[i..]);
+        result.extend_from_slice(&other.blocks[j..]);
     }
 
     /// Dot product between two block-sparse vectors.
@@ -741,9 +794,9 @@ impl BlockSparseTritVec {
             let (id_b, block_b) = &other.blocks[j];
 
             match id_a.cmp(id_b) {
-                std::cmp::Ordering::Less => i += 1,
-                std::cmp::Ordering::Greater => j += 1,
-                std::cmp::Ordering::Equal => {
+                core::cmp::Ordering::Less => i += 1,
+                core::cmp::Ordering::Greater => j += 1,
+                core::cmp::Ordering::Equal => {
                     sum += block_a.dot(block_b) as i64;
                     i += 1;
                     j += 1;
@@ -784,6 +837,8 @@ impl BlockSparseTritVec {
 
     /// Bundle multiple vectors efficiently using pairwise reduction.
     pub fn bundle_many(vectors: &[Self]) -> Option<Self> {
+        #[cfg(feature = "std")]
+        let _t = crate::profile::scope("bundle_many");
         if vectors.is_empty() {
             return None;
         }
@@ -845,9 +900,9 @@ impl BlockSparseTritVec {
             let (id_b, block_b) = other.blocks[j];
 
             match id_a.cmp(&id_b) {
-                std::cmp::Ordering::Less => i += 1,
-                std::cmp::Ordering::Greater => j += 1,
-                std::cmp::Ordering::Equal => {
+                core::cmp::Ordering::Less => i += 1,
+                core::cmp::Ordering::Greater => j += 1,
+                core::cmp::Ordering::Equal => {
                     intersecting_a.push((id_a, block_a));
                     intersecting_b.push((id_b, block_b));
                     result_ids.push(id_a);
@@ -938,15 +993,15 @@ impl BlockSparseTritVec {
             let (id_b, block_b) = other.blocks[j];
 
             match id_a.cmp(&id_b) {
-                std::cmp::Ordering::Less => {
+                core::cmp::Ordering::Less => {
                     all_blocks.push((id_a, block_a, Block::ZERO, Source::OnlyA));
                     i += 1;
                 }
-                std::cmp::Ordering::Greater => {
+                core::cmp::Ordering::Greater => {
                     all_blocks.push((id_b, Block::ZERO, block_b, Source::OnlyB));
                     j += 1;
                 }
-                std::cmp::Ordering::Equal => {
+                core::cmp::Ordering::Equal => {
                     all_blocks.push((id_a, block_a, block_b, Source::Both));
                     i += 1;
                     j += 1;
@@ -967,6 +1022,7 @@ impl BlockSparseTritVec {
         }
 
         // Count overlapping blocks
+        #[cfg(target_arch = "x86_64")]
         let overlap_count = all_blocks.iter().filter(|(_, _, _, s)| matches!(s, Source::Both)).count();
         
         #[cfg(target_arch = "x86_64")]
@@ -1043,50 +1099,52 @@ impl BlockSparseTritVec {
             "Dimension mismatch in dot_dispatch: {} vs {}",
             self.dim, other.dim
         );
+        self.dot_with(&other.blocks)
+    }
 
-        // Collect intersecting blocks
-        let mut intersecting_a = Vec::new();
-        let mut intersecting_b = Vec::new();
-        
-        let mut i = 0;
-        let mut j = 0;
-
-        while i < self.blocks.len() && j < other.blocks.len() {
-            let (id_a, block_a) = &self.blocks[i];
-            let (id_b, block_b) = &other.blocks[j];
+    /// Dot product against blocks streamed in ascending block-id order, for
+    /// example straight out of another vector or a memory-mapped file.
+    ///
+    /// Intersecting blocks are gathered into fixed stack batches for the
+    /// AVX-512 kernel (when available), so nothing is allocated.
+    pub fn dot_with<'a, I>(&self, other: I) -> i64
+    where
+        I: IntoIterator<Item = &'a (u32, Block)>,
+    {
+        const BATCH: usize = 64;
+        let mut batch_a = [(0u32, Block::ZERO); BATCH];
+        let mut batch_b = [(0u32, Block::ZERO); BATCH];
+        let mut len = 0;
+        let mut sum: i64 = 0;
 
-            match id_a.cmp(id_b) {
-                std::cmp::Ordering::Less => i += 1,
-                std::cmp::Ordering::Greater => j += 1,
-                std::cmp::Ordering::Equal => {
-                    intersecting_a.push((*id_a, *block_a));
-                    intersecting_b.push((*id_b, *block_b));
-                    i += 1;
-                    j += 1;
+        let flush = |a: &[(u32, Block)], b: &[(u32, Block)]| -> i64 {
+            #[cfg(target_arch = "x86_64")]
+            {
+                if a.len() >= 4 && has_avx512() {
+                    // SAFETY: AVX-512 availability checked above
+                    return unsafe { avx512::dot_blocks_avx512(a, b) };
                 }
             }
-        }
-
-        if intersecting_a.is_empty() {
-            return 0;
-        }
+            a.iter().zip(b).map(|((_, x), (_, y))| x.dot(y) as i64).sum()
+        };
 
-        #[cfg(target_arch = "x86_64")]
-        {
-            if has_avx512() && intersecting_a.len() >= 4 {
-                // SAFETY: AVX-512 availability checked above
-                return unsafe {
-                    avx512::dot_blocks_avx512(&intersecting_a, &intersecting_b)
-                };
+        let mut mine = self.blocks.iter().peekable();
+        for &(id_b, block_b) in other {
+            while mine.next_if(|(id_a, _)| *id_a < id_b).is_some() {}
+            let Some(&&(id_a, block_a)) = mine.peek() else {
+                break;
+            };
+            if id_a == id_b {
+                batch_a[len] = (id_a, block_a);
+                batch_b[len] = (id_b, block_b);
+                len += 1;
+                if len == BATCH {
+                    sum += flush(&batch_a, &batch_b);
+                    len = 0;
+                }
             }
         }
-
-        // Scalar fallback
-        intersecting_a
-            .iter()
-            .zip(intersecting_b.iter())
-            .map(|((_, a), (_, b))| a.dot(b) as i64)
-            .sum()
+        sum + flush(&batch_a[..len], &batch_b[..len])
     }
 
     /// Cosine similarity with automatic SIMD dispatch.
@@ -1815,6 +1873,45 @@ mod tests {
         assert_eq!(v1.bind_dispatch(&v2).block_count(), 0);
         assert_eq!(v1.dot_dispatch(&v2), 0);
     }
+
+    #[test]
+    fn test_into_variants_reuse_output() {
+        let dim = 100_000;
+        let mut v1 = BlockSparseTritVec::new(dim);
+        let mut v2 = BlockSparseTritVec::new(dim);
+        for i in 0..40u64 {
+            v1.insert_block(i as u32 * 3, Block::new(i + 1, (i + 1) << 32));
+            v2.insert_block(i as u32 * 2, Block::new((i + 1) << 16, 0xF0));
+        }
+
+        let mut out = BlockSparseTritVec::with_capacity(dim, 128);
+        out.insert_block(7, Block::new(1, 0));
+        let storage = out.blocks.as_ptr();
+        v1.bind_into(&v2, &mut out);
+        assert_eq!(out, v1.bind(&v2));
+        v1.bundle_into(&v2, &mut out);
+        assert_eq!(out, v1.bundle(&v2));
+        assert_eq!(out.blocks.as_ptr(), storage, "output storage was reallocated");
+    }
+
+    #[test]
+    fn test_dot_with_streamed_blocks() {
+        let dim = 1_000_000;
+        let mut v1 = BlockSparseTritVec::new(dim);
+        let mut v2 = BlockSparseTritVec::new(dim);
+        // More intersecting blocks than one batch, with gaps on both sides.
+        for i in 0..300u32 {
+            v1.insert_block(i * 2, Block::new(0xFFFF_0000 | i as u64, 0xF << 40));
+            if i % 5 != 0 {
+                v2.insert_block(i * 3, Block::new(0xAAAA_AAAA_AAAA_AAAA, 0x5555_5555_5555_5555));
+            }
+        }
+
+        let expected = v1.dot(&v2);
+        assert_ne!(expected, 0);
+        assert_eq!(v1.dot_with(v2.iter()), expected);
+        assert_eq!(v1.dot_dispatch(&v2), expected);
+    }
 }
 
 // ============================================================================
@@ -1853,9 +1950,10 @@ mod tests {
 #[cfg(target_arch = "x86_64")]
 pub mod avx512 {
     use super::Block;
+    use alloc::vec::Vec;
     
     #[cfg(target_arch = "x86_64")]
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
     /// Process multiple blocks with AVX-512 bind operation.
     ///
@@ -1916,8 +2014,8 @@ pub mod avx512 {
             let out_neg = _mm256_or_si256(pn, np);
             
             // Extract results
-            let out_pos_arr: [u64; 4] = std::mem::transmute(out_pos);
-            let out_neg_arr: [u64; 4] = std::mem::transmute(out_neg);
+            let out_pos_arr: [u64; 4] = core::mem::transmute(out_pos);
+            let out_neg_arr: [u64; 4] = core::mem::transmute(out_neg);
             
             // Store non-zero results
             for i in 0..4 {
@@ -1995,8 +2093,8 @@ pub mod avx512 {
             );
             
             // Extract results
-            let out_pos_arr: [u64; 4] = std::mem::transmute(out_pos);
-            let out_neg_arr: [u64; 4] = std::mem::transmute(out_neg);
+            let out_pos_arr: [u64; 4] = core::mem::transmute(out_pos);
+            let out_neg_arr: [u64; 4] = core::mem::transmute(out_neg);
             
             for i in 0..4 {
                 let pos = out_pos_arr[i];
@@ -2059,10 +2157,10 @@ pub mod avx512 {
             let np = _mm256_and_si256(an, bp);
             
             // Extract and popcount
-            let pp_arr: [u64; 4] = std::mem::transmute(pp);
-            let nn_arr: [u64; 4] = std::mem::transmute(nn);
-            let pn_arr: [u64; 4] = std::mem::transmute(pn);
-            let np_arr: [u64; 4] = std::mem::transmute(np);
+            let pp_arr: [u64; 4] = core::mem::transmute(pp);
+            let nn_arr: [u64; 4] = core::mem::transmute(nn);
+            let pn_arr: [u64; 4] = core::mem::transmute(pn);
+            let np_arr: [u64; 4] = core::mem::transmute(np);
             
             for i in 0..4 {
                 acc += (pp_arr[i].count_ones() + nn_arr[i].count_ones()) as i64;
@@ -2089,6 +2187,7 @@ pub mod avx512 {
 #[cfg(not(target_arch = "x86_64"))]
 pub mod avx512 {
     use super::Block;
+    use alloc::vec::Vec;
 
     /// Stub: AVX-512 not available on this architecture.
     pub unsafe fn bind_blocks_avx512(
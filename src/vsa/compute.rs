@@ -0,0 +1,284 @@
+//! Algebra expressions over named vectors.
+//!
+//! [`eval`] runs an expression such as
+//!
+//! ```text
+//! cleanup(unbind(bundle(bind(colour, red), bind(shape, round)), colour))
+//! ```
+//!
+//! against an [`ItemMemory`] of bindings, so a query that would take a
+//! sequence of calls to [`SparseVec`] methods is one string. [`bindings`]
+//! names the vectors of an engram: `root`, `chunk:<id>` for each chunk,
+//! `file:<path>` for each file's signature and, from its
+//! [`RoleSchema`](crate::role_schema::RoleSchema), `role:<label>` for each
+//! role and `record:<path>` for each bound path's record.
+//!
+//! | Expression | Result |
+//! |---|---|
+//! | `name` or `"quoted name"` | the bound vector |
+//! | `symbol("name")` | the seeded symbol of [`ItemMemory::symbol`], or the binding if there is one |
+//! | `bind(a, b, …)` | [`SparseVec::bind`], left to right |
+//! | `unbind(a, key)` | `a` bound with `key` again, which undoes a bind by a bipolar key |
+//! | `bundle(a, b, …)` | [`SparseVec::bundle_sum_many`] |
+//! | `permute(a, n)` | [`SparseVec::permute`] by `n`; a negative `n` inverts it |
+//! | `cleanup(a)` | the binding most similar to `a` ([`ItemMemory::cleanup`]) |
+//!
+//! A bare name runs up to a delimiter (`(`, `)`, `,`, `"` or whitespace),
+//! so `chunk:12` and `file:src/main.rs` need no quotes.
+
+use crate::embrfs::{Engram, FileSignatures, Manifest};
+use crate::vsa::record::ItemMemory;
+use crate::vsa::SparseVec;
+use std::fmt;
+
+/// A parsed expression; see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Name(String),
+    Symbol(String),
+    Bind(Vec<Expr>),
+    Unbind(Box<Expr>, Box<Expr>),
+    Bundle(Vec<Expr>),
+    Permute(Box<Expr>, i64),
+    Cleanup(Box<Expr>),
+}
+
+/// Why an expression did not parse or evaluate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComputeError {
+    /// The expression is malformed at byte `position`.
+    Syntax { position: usize, message: String },
+    /// No binding has this name.
+    UnknownName(String),
+    /// `cleanup` with no bindings to clean up against.
+    EmptyMemory,
+}
+
+impl fmt::Display for ComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComputeError::Syntax { position, message } => write!(f, "syntax error at {}: {}", position, message),
+            ComputeError::UnknownName(name) => write!(f, "unknown name {:?}", name),
+            ComputeError::EmptyMemory => f.write_str("cleanup needs at least one binding"),
+        }
+    }
+}
+
+impl std::error::Error for ComputeError {}
+
+/// Parse `expr` and evaluate it against `bindings`.
+pub fn eval(expr: &str, bindings: &ItemMemory) -> Result<SparseVec, ComputeError> {
+    Expr::parse(expr)?.eval(bindings)
+}
+
+/// The named vectors of an engram: `root`, `chunk:<id>` for every chunk,
+/// `file:<path>` for every file signature, and `role:<label>` and
+/// `record:<path>` from its role schema, with symbols seeded by `seed`.
+pub fn bindings(engram: &Engram, manifest: &Manifest, seed: u64) -> ItemMemory {
+    let mut memory = ItemMemory::new(seed);
+    memory.insert("root", engram.root.clone());
+    let mut ids: Vec<&usize> = engram.codebook.keys().collect();
+    ids.sort_unstable();
+    for id in ids {
+        memory.insert(format!("chunk:{}", id), engram.codebook[id].clone());
+    }
+    for (path, signature) in FileSignatures::build(engram, manifest).iter() {
+        memory.insert(format!("file:{}", path), signature.clone());
+    }
+    if let Some(schema) = &engram.schema {
+        for label in schema.roles() {
+            memory.insert(format!("role:{}", label), schema.role(label).expect("registered role"));
+        }
+        for path in schema.paths() {
+            if let Some(record) = schema.record(path) {
+                memory.insert(format!("record:{}", path), record);
+            }
+        }
+    }
+    memory
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Expr, ComputeError> {
+        let mut parser = Parser { input, pos: 0 };
+        let expr = parser.expr()?;
+        parser.skip_space();
+        if parser.pos < input.len() {
+            return Err(parser.error("unexpected input after the expression"));
+        }
+        Ok(expr)
+    }
+
+    pub fn eval(&self, bindings: &ItemMemory) -> Result<SparseVec, ComputeError> {
+        Ok(match self {
+            Expr::Name(name) => bindings.get(name).cloned().ok_or_else(|| ComputeError::UnknownName(name.clone()))?,
+            Expr::Symbol(name) => match bindings.get(name) {
+                Some(vec) => vec.clone(),
+                None => ItemMemory::new(bindings.seed()).symbol(name),
+            },
+            Expr::Bind(args) => {
+                let mut vecs = args.iter().map(|a| a.eval(bindings));
+                match vecs.next() {
+                    Some(first) => vecs.try_fold(first?, |acc, v| Ok(acc.bind(&v?)))?,
+                    None => SparseVec::new(),
+                }
+            }
+            Expr::Unbind(value, key) => value.eval(bindings)?.bind(&key.eval(bindings)?),
+            Expr::Bundle(args) => {
+                let vecs = args.iter().map(|a| a.eval(bindings)).collect::<Result<Vec<_>, _>>()?;
+                SparseVec::bundle_sum_many(&vecs)
+            }
+            Expr::Permute(value, shift) => {
+                let vec = value.eval(bindings)?;
+                let n = shift.unsigned_abs() as usize;
+                if *shift < 0 {
+                    vec.inverse_permute(n)
+                } else {
+                    vec.permute(n)
+                }
+            }
+            Expr::Cleanup(value) => {
+                let (name, _) = bindings.cleanup(&value.eval(bindings)?).ok_or(ComputeError::EmptyMemory)?;
+                bindings.get(&name).expect("cleanup returns a bound name").clone()
+            }
+        })
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ComputeError {
+        ComputeError::Syntax {
+            position: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ComputeError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ComputeError> {
+        if self.eat('(') {
+            let inner = self.expr()?;
+            self.expect(')')?;
+            return Ok(inner);
+        }
+        if self.rest().starts_with('"') {
+            return Ok(Expr::Name(self.string()?));
+        }
+        let start = self.pos;
+        let word = self.word();
+        if word.is_empty() {
+            return Err(self.error("expected a name or a function call"));
+        }
+        if !self.eat('(') {
+            return Ok(Expr::Name(word));
+        }
+        let call = match word.as_str() {
+            "symbol" => {
+                self.skip_space();
+                let name = if self.rest().starts_with('"') { self.string()? } else { self.word() };
+                if name.is_empty() {
+                    return Err(self.error("expected a symbol name"));
+                }
+                Expr::Symbol(name)
+            }
+            "bind" | "bundle" => {
+                let mut args = vec![self.expr()?];
+                while self.eat(',') {
+                    args.push(self.expr()?);
+                }
+                if args.len() < 2 {
+                    return Err(self.error(&format!("{} takes two or more arguments", word)));
+                }
+                if word == "bind" {
+                    Expr::Bind(args)
+                } else {
+                    Expr::Bundle(args)
+                }
+            }
+            "unbind" => {
+                let value = self.expr()?;
+                self.expect(',')?;
+                Expr::Unbind(Box::new(value), Box::new(self.expr()?))
+            }
+            "permute" => {
+                let value = self.expr()?;
+                self.expect(',')?;
+                self.skip_space();
+                let shift = self.word().parse().map_err(|_| self.error("expected an integer shift"))?;
+                Expr::Permute(Box::new(value), shift)
+            }
+            "cleanup" => Expr::Cleanup(Box::new(self.expr()?)),
+            _ => {
+                self.pos = start;
+                return Err(self.error(&format!("unknown function {:?}", word)));
+            }
+        };
+        self.expect(')')?;
+        Ok(call)
+    }
+
+    /// A bare name: everything up to a delimiter.
+    fn word(&mut self) -> String {
+        self.skip_space();
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | ',' | '"'))
+            .unwrap_or(rest.len());
+        let word = rest[..end].to_string();
+        self.pos += end;
+        word
+    }
+
+    /// A double-quoted name; `\"` and `\\` escape.
+    fn string(&mut self) -> Result<String, ComputeError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => out.push(escaped),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        self.pos = start;
+        Err(self.error("unterminated string"))
+    }
+}
@@ -26,7 +26,9 @@
 //!
 //! All operations preserve balanced ternary properties and are algebraically closed.
 
+use crate::vsa::SparseVec;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::{Mul, Neg};
 
 /// Single trit: balanced ternary digit {-1, 0, +1}
@@ -184,6 +186,20 @@ impl Tryte {
         -Self::max_value(num_trits)
     }
 
+    /// Create from integer value, saturating at the range of `num_trits`
+    /// instead of wrapping
+    pub fn saturating_from_i64(value: i64, num_trits: usize) -> Self {
+        Tryte::from_i64(saturate(value, num_trits), num_trits)
+    }
+
+    /// The same value with `num_trits` trits; higher trits are dropped if
+    /// `num_trits` is smaller
+    pub fn resized(&self, num_trits: usize) -> Tryte {
+        let mut trits = self.trits.clone();
+        trits.resize(num_trits, Trit::Zero);
+        Tryte { trits }
+    }
+
     /// Trit-wise multiplication (bind at tryte level)
     pub fn bind(&self, other: &Tryte) -> Tryte {
         assert_eq!(self.len(), other.len(), "Tryte sizes must match for bind");
@@ -224,7 +240,7 @@ impl Tryte {
 }
 
 /// Configuration for dimensional encoding
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DimensionalConfig {
     /// Number of dimensions in the vector space
     pub num_dimensions: usize,
@@ -240,7 +256,7 @@ pub struct DimensionalConfig {
 }
 
 /// Configuration for trit depth across dimensions
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TritDepthConfig {
     /// All dimensions have the same trit depth
     Uniform(u8),
@@ -377,6 +393,20 @@ impl HyperVec {
         vec
     }
 
+    /// Create from a sparse ternary vector; dimensions beyond
+    /// `config.num_dimensions` are dropped
+    pub fn from_sparse(config: DimensionalConfig, vector: &SparseVec) -> Self {
+        let mut vec = HyperVec::new(config);
+        let dims = vec.config.num_dimensions;
+        for &dim in vector.pos.iter().filter(|&&d| d < dims) {
+            vec.set(dim, 1);
+        }
+        for &dim in vector.neg.iter().filter(|&&d| d < dims) {
+            vec.set(dim, -1);
+        }
+        vec
+    }
+
     /// Get value at dimension (returns 0 for unset dimensions)
     pub fn get(&self, dim: usize) -> i64 {
         self.dimensions
@@ -396,6 +426,16 @@ impl HyperVec {
         }
     }
 
+    /// Set value at dimension with `depth` trits, saturating if it does
+    /// not fit
+    pub fn set_at_depth(&mut self, dim: usize, value: i64, depth: u8) {
+        if value == 0 {
+            self.dimensions.remove(&dim);
+        } else {
+            self.dimensions.insert(dim, Tryte::saturating_from_i64(value, depth as usize));
+        }
+    }
+
     /// Number of non-zero dimensions (sparsity count)
     pub fn nnz(&self) -> usize {
         self.dimensions.len()
@@ -425,13 +465,19 @@ impl HyperVec {
             .collect();
 
         for dim in all_dims {
-            let depth = self.config.depth_for_dimension(dim);
-            let zero = Tryte::zero(depth as usize);
+            // Adaptive precision may have widened either side
+            let depth = [self.dimensions.get(&dim), other.dimensions.get(&dim)]
+                .into_iter()
+                .flatten()
+                .map(Tryte::len)
+                .max()
+                .unwrap_or(0);
+            let zero = Tryte::zero(depth);
             
-            let a = self.dimensions.get(&dim).unwrap_or(&zero);
-            let b = other.dimensions.get(&dim).unwrap_or(&zero);
+            let a = self.dimensions.get(&dim).map_or_else(|| zero.clone(), |t| t.resized(depth));
+            let b = other.dimensions.get(&dim).map_or_else(|| zero.clone(), |t| t.resized(depth));
             
-            let bundled = a.bundle(b);
+            let bundled = a.bundle(&b);
             
             // Only store if non-zero
             if bundled.to_i64() != 0 {
@@ -457,7 +503,8 @@ impl HyperVec {
         // Only dimensions present in BOTH vectors contribute
         for (dim, tryte_a) in &self.dimensions {
             if let Some(tryte_b) = other.dimensions.get(dim) {
-                let bound = tryte_a.bind(tryte_b);
+                let depth = tryte_a.len().max(tryte_b.len());
+                let bound = tryte_a.resized(depth).bind(&tryte_b.resized(depth));
                 if bound.to_i64() != 0 {
                     result.dimensions.insert(*dim, bound);
                 }
@@ -674,6 +721,72 @@ fn unpack_trits(bytes: &[u8], num_trits: usize) -> Vec<Trit> {
     trits
 }
 
+/// `value` clamped to the range `num_trits` trits hold
+fn saturate(value: i64, num_trits: usize) -> i64 {
+    match 3i64.checked_pow(num_trits as u32) {
+        Some(states) => value.clamp(-(states - 1) / 2, (states - 1) / 2),
+        None => value,
+    }
+}
+
+/// Trit depths that adaptive precision raised above the configured ones
+///
+/// Residuals and decoded values are indexed by data dimension and
+/// coefficients by basis vector, so the two are kept apart. Depths are only
+/// ever raised: the merged map of several encodings decodes each of them.
+/// An engram that was encoded differentially keeps its map in
+/// `Engram::precision`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthMap {
+    /// Depth of each expanded data dimension
+    pub dimensions: BTreeMap<usize, u8>,
+
+    /// Depth of each expanded coefficient, by basis index
+    pub coefficients: BTreeMap<usize, u8>,
+}
+
+impl DepthMap {
+    /// Whether no depth was raised
+    pub fn is_empty(&self) -> bool {
+        self.dimensions.is_empty() && self.coefficients.is_empty()
+    }
+
+    /// Depth of data dimension `dim` under `config`
+    pub fn dimension_depth(&self, config: &DimensionalConfig, dim: usize) -> u8 {
+        self.dimensions
+            .get(&dim)
+            .copied()
+            .unwrap_or_else(|| config.depth_for_dimension(dim))
+    }
+
+    /// Depth of the coefficient of basis vector `basis` under `config`
+    pub fn coefficient_depth(&self, config: &DimensionalConfig, basis: usize) -> u8 {
+        self.coefficients
+            .get(&basis)
+            .copied()
+            .unwrap_or_else(|| config.depth_for_dimension(basis))
+    }
+
+    /// Raise the depths of `self` to those of `other`; returns whether any
+    /// changed
+    pub fn merge(&mut self, other: &DepthMap) -> bool {
+        let mut changed = false;
+        for (map, theirs) in [
+            (&mut self.dimensions, &other.dimensions),
+            (&mut self.coefficients, &other.coefficients),
+        ] {
+            for (&key, &depth) in theirs {
+                let ours = map.entry(key).or_insert(0);
+                if depth > *ours {
+                    *ours = depth;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
 /// Differential encoding result
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DifferentialEncoding {
@@ -683,8 +796,10 @@ pub struct DifferentialEncoding {
     /// Residual that couldn't be captured by basis
     pub residual: HyperVec,
     
-    /// Dimensions that needed precision expansion
-    pub expanded_dims: Vec<(usize, u8)>,
+    /// Depths this encoding raised beyond the encoder's
+    /// [`DifferentialEncoder::depths`]; merge them into the map kept for
+    /// decoding
+    pub expanded: DepthMap,
     
     /// Reconstruction quality (1.0 = perfect)
     pub quality: f64,
@@ -698,6 +813,10 @@ pub struct DifferentialEncoder {
     /// Basis vectors (the codebook)
     pub basis: Vec<HyperVec>,
     
+    /// Depths raised by earlier encodings; decoding needs the map the
+    /// encodings were made with
+    pub depths: DepthMap,
+    
     /// Threshold for considering a match
     pub match_threshold: f64,
     
@@ -711,58 +830,73 @@ impl DifferentialEncoder {
         DifferentialEncoder {
             config,
             basis: Vec::new(),
+            depths: DepthMap::default(),
             match_threshold: 0.3,
             precision_threshold: 0.001,
         }
     }
 
+    /// Start from the depths of earlier encodings, such as the map an
+    /// engram was saved with
+    pub fn with_depths(mut self, depths: DepthMap) -> Self {
+        self.depths = depths;
+        self
+    }
+
     /// Add a basis vector to the codebook
     pub fn add_basis(&mut self, vector: HyperVec) {
         self.basis.push(vector);
     }
 
     /// Encode data differentially against the codebook
+    ///
+    /// With [`TritDepthConfig::Adaptive`], a coefficient or residual whose
+    /// depth loses more than `precision_threshold` of it is widened a trit
+    /// at a time up to `max_depth`, and re-encoded at the new depth. A data
+    /// dimension is also widened until its decoded value fits. What still
+    /// does not fit at `max_depth` saturates and lowers `quality`.
     pub fn encode(&self, data: &HyperVec) -> DifferentialEncoding {
+        let similarities: Vec<f64> = self.basis.iter().map(|basis_vec| data.cosine(basis_vec)).collect();
+        self.encode_with_similarities(data, &similarities)
+    }
+
+    /// [`DifferentialEncoder::encode`], given the cosine of `data` with
+    /// each basis vector in basis order, such as from the batched dot
+    /// products of many chunks at once
+    pub fn encode_with_similarities(&self, data: &HyperVec, similarities: &[f64]) -> DifferentialEncoding {
         let mut coefficients = HyperVec::new(self.config.clone());
-        let mut expanded_dims = Vec::new();
+        let mut expanded = DepthMap::default();
         
         // Project data onto each basis vector
-        for (basis_idx, basis_vec) in self.basis.iter().enumerate() {
-            let similarity = data.cosine(basis_vec);
-            
+        for (basis_idx, &similarity) in similarities.iter().enumerate().take(self.basis.len()) {
             if similarity.abs() > self.match_threshold {
                 // Quantize coefficient to balanced ternary
                 let coef_value = (similarity * 100.0) as i64;
-                coefficients.set(basis_idx, coef_value);
+                let current = self.depths.coefficient_depth(&self.config, basis_idx);
+                let depth = self.fit(current, &[coef_value], coef_value);
+                if depth > current {
+                    expanded.coefficients.insert(basis_idx, depth);
+                }
+                coefficients.set_at_depth(basis_idx, coef_value, depth);
             }
         }
 
-        // Compute residual (what basis couldn't capture)
+        // Compute residual (what basis couldn't capture) against the
+        // coefficients as stored, so decoding adds up to the same values
         let reconstructed = self.reconstruct_from_coefficients(&coefficients);
         let mut residual = HyperVec::new(self.config.clone());
+        let dims: BTreeSet<usize> = data.dimensions.keys().chain(reconstructed.keys()).copied().collect();
         
-        for dim in 0..self.config.num_dimensions {
+        for dim in dims {
             let original_val = data.get(dim);
-            let reconstructed_val = reconstructed.get(dim);
-            let diff = original_val - reconstructed_val;
+            let diff = original_val - reconstructed.get(&dim).copied().unwrap_or(0);
             
-            if diff.abs() > 0 {
-                residual.set(dim, diff);
-                
-                // Check if we need more precision
-                let relative_error = if original_val != 0 {
-                    (diff as f64).abs() / (original_val as f64).abs()
-                } else {
-                    0.0
-                };
-                
-                if relative_error > self.precision_threshold {
-                    if let TritDepthConfig::Adaptive { base_depth, max_depth } = &self.config.trit_depth {
-                        let new_depth = (*base_depth + 2).min(*max_depth);
-                        expanded_dims.push((dim, new_depth));
-                    }
-                }
+            let current = self.depths.dimension_depth(&self.config, dim);
+            let depth = self.fit(current, &[diff, original_val], original_val);
+            if depth > current {
+                expanded.dimensions.insert(dim, depth);
             }
+            residual.set_at_depth(dim, diff, depth);
         }
 
         // Calculate quality
@@ -771,14 +905,36 @@ impl DifferentialEncoder {
         DifferentialEncoding {
             coefficients,
             residual,
-            expanded_dims,
+            expanded,
             quality,
         }
     }
 
-    /// Reconstruct from coefficients only
-    fn reconstruct_from_coefficients(&self, coefficients: &HyperVec) -> HyperVec {
-        let mut result = HyperVec::new(self.config.clone());
+    /// The adaptive-precision loop: the depth, starting at `depth`, at
+    /// which saturating `values` loses at most `precision_threshold`
+    /// relative to `scale`, or `max_depth` if none does
+    fn fit(&self, mut depth: u8, values: &[i64], scale: i64) -> u8 {
+        let TritDepthConfig::Adaptive { max_depth, .. } = self.config.trit_depth else {
+            return depth;
+        };
+        let scale = scale.unsigned_abs().max(1) as f64;
+        let error = |depth: u8| {
+            values
+                .iter()
+                .map(|&v| v.abs_diff(saturate(v, depth as usize)))
+                .max()
+                .unwrap_or(0) as f64
+                / scale
+        };
+        while depth < max_depth && error(depth) > self.precision_threshold {
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Reconstruct from coefficients only, at full precision
+    fn reconstruct_from_coefficients(&self, coefficients: &HyperVec) -> BTreeMap<usize, i64> {
+        let mut result = BTreeMap::new();
         
         for (&basis_idx, coef_tryte) in &coefficients.dimensions {
             if let Some(basis_vec) = self.basis.get(basis_idx) {
@@ -786,39 +942,58 @@ impl DifferentialEncoder {
                 
                 // Scale basis vector by coefficient and bundle
                 for (&dim, tryte) in &basis_vec.dimensions {
-                    let scaled = (tryte.to_i64() as f64 * coef) as i64;
-                    let current = result.get(dim);
-                    result.set(dim, current + scaled);
+                    // Round rather than truncate: a unit trit scaled by a
+                    // coefficient below 1 would otherwise always vanish
+                    let scaled = (tryte.to_i64() as f64 * coef).round() as i64;
+                    *result.entry(dim).or_insert(0) += scaled;
                 }
             }
         }
         
+        result.retain(|_, value| *value != 0);
         result
     }
 
     /// Full reconstruction from differential encoding
+    ///
+    /// Each dimension is stored at the depth [`DifferentialEncoder::depths`]
+    /// or the encoding's own expansions give it, whichever is deeper.
     pub fn decode(&self, encoding: &DifferentialEncoding) -> HyperVec {
-        let mut result = self.reconstruct_from_coefficients(&encoding.coefficients);
+        let mut values = self.reconstruct_from_coefficients(&encoding.coefficients);
         
         // Add residual corrections
         for (&dim, tryte) in &encoding.residual.dimensions {
-            let current = result.get(dim);
-            result.set(dim, current + tryte.to_i64());
+            *values.entry(dim).or_insert(0) += tryte.to_i64();
         }
         
+        let mut result = HyperVec::new(self.config.clone());
+        for (dim, value) in values {
+            let depth = self
+                .depths
+                .dimension_depth(&self.config, dim)
+                .max(encoding.expanded.dimensions.get(&dim).copied().unwrap_or(0));
+            result.set_at_depth(dim, value, depth);
+        }
         result
     }
 
     /// Calculate reconstruction quality
     fn calculate_quality(&self, original: &HyperVec, coefficients: &HyperVec, residual: &HyperVec) -> f64 {
         let reconstructed = self.reconstruct_from_coefficients(coefficients);
+        let dims: BTreeSet<usize> = original
+            .dimensions
+            .keys()
+            .chain(reconstructed.keys())
+            .chain(residual.dimensions.keys())
+            .copied()
+            .collect();
         
         let mut total_error: f64 = 0.0;
         let mut total_energy: f64 = 0.0;
         
-        for dim in 0..self.config.num_dimensions {
+        for dim in dims {
             let orig = original.get(dim) as f64;
-            let recon = reconstructed.get(dim) as f64;
+            let recon = reconstructed.get(&dim).copied().unwrap_or(0) as f64;
             let res = residual.get(dim) as f64;
             
             total_energy += orig * orig;
@@ -966,4 +1141,46 @@ mod tests {
         assert_eq!(data.get(1), decoded.get(1));
         assert_eq!(data.get(2), decoded.get(2));
     }
+
+    #[test]
+    fn test_adaptive_precision_expansion() {
+        let config = DimensionalConfig::adaptive(64, 2, 8);
+        let mut basis = HyperVec::new(config.clone());
+        basis.set(0, 1);
+        basis.set(1, 1);
+        let mut encoder = DifferentialEncoder::new(config.clone());
+        encoder.add_basis(basis);
+
+        // 30 needs 4 trits; the coefficient needs more than the base 2 too
+        let mut data = HyperVec::new(config.clone());
+        data.set(0, 1);
+        data.set(1, 1);
+        data.set_at_depth(2, 30, 4);
+
+        let encoding = encoder.encode(&data);
+        assert_eq!(encoding.expanded.dimensions.get(&2), Some(&4));
+        assert!(encoding.expanded.coefficients.get(&0).is_some_and(|&d| d > 2));
+        assert_eq!(encoding.quality, 1.0);
+        let decoded = encoder.decode(&encoding);
+        for dim in 0..3 {
+            assert_eq!(decoded.get(dim), data.get(dim));
+        }
+
+        // Once the map is kept, later encodings need no expansion of their
+        // own, and decoding relies on the kept map
+        let encoder = encoder.with_depths(encoding.expanded.clone());
+        let mut again = data.clone();
+        again.set_at_depth(2, -25, 4);
+        let encoding = encoder.encode(&again);
+        assert!(encoding.expanded.is_empty());
+        assert_eq!(encoder.decode(&encoding).get(2), -25);
+
+        // Depths never exceed max_depth; what doesn't fit saturates
+        let mut huge = HyperVec::new(config.clone());
+        huge.set_at_depth(5, 10_000, 10);
+        let encoding = DifferentialEncoder::new(config).encode(&huge);
+        assert_eq!(encoding.expanded.dimensions.get(&5), Some(&8));
+        assert_eq!(encoding.residual.get(5), Tryte::max_value(8));
+        assert!(encoding.quality < 1.0);
+    }
 }
@@ -0,0 +1,44 @@
+//! Float methods that `core` lacks without `std`, backed by `libm`.
+//!
+//! Imported only in `no_std` builds; with `std` the inherent methods on
+//! `f32`/`f64` are used instead.
+
+pub(crate) trait Float {
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn round(self) -> Self;
+}
+
+impl Float for f64 {
+    #[inline]
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+
+    #[inline]
+    fn exp(self) -> Self {
+        libm::exp(self)
+    }
+
+    #[inline]
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+}
+
+impl Float for f32 {
+    #[inline]
+    fn sqrt(self) -> Self {
+        libm::sqrtf(self)
+    }
+
+    #[inline]
+    fn exp(self) -> Self {
+        libm::expf(self)
+    }
+
+    #[inline]
+    fn round(self) -> Self {
+        libm::roundf(self)
+    }
+}
@@ -0,0 +1,238 @@
+//! Encoding labeled graphs into hypervectors.
+//!
+//! Node ids and labels become seeded bipolar symbols (every trit ±1), so
+//! [`SparseVec::bind`] of any two keeps full support and undoes itself.
+//! A graph is held as four bundles, built with
+//! [`SparseVec::bundle_sum_many`]:
+//!
+//! - nodes: `Σ id`
+//! - node labels: `Σ id ⊗ LABEL ⊗ label`
+//! - edges: `Σ from ⊗ ρ(to)`, where `ρ` is [`SparseVec::permute`] by one,
+//!   so `(a, b)` and `(b, a)` differ
+//! - edge labels: `Σ from ⊗ ρ(to) ⊗ LABEL ⊗ label`
+//!
+//! plus a bundle of paths, each `ρ⁰(n₀) ⊗ ρ¹(n₁) ⊗ … ⊗ ρᵏ(nₖ)`.
+//!
+//! Queries compare a probe against a bundle: a member of a bundle of `m`
+//! items scores a cosine near `√(2 / πm)`, anything else near zero with a
+//! spread of `1 / √DIM`. [`EncodedGraph::threshold`] sits halfway, which
+//! keeps both error rates low up to a few hundred items per bundle. Labels
+//! and neighbours are decoded by unbinding and cleaning up against the
+//! graph's node ids and labels.
+
+use crate::vsa::record::{bipolar, bundle_threshold};
+use crate::vsa::SparseVec;
+use std::collections::BTreeSet;
+
+/// The label role. Roles are hashed in their own domain, so no node id or
+/// label shares their vector.
+const LABEL_ROLE: &str = "label";
+
+/// Turns names into symbols and graphs into [`EncodedGraph`]s. Encoders
+/// with the same seed produce the same vectors.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphEncoder {
+    seed: u64,
+}
+
+/// A directed graph with optionally labeled nodes and edges, and paths
+/// through it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabeledGraph {
+    pub nodes: Vec<(String, Option<String>)>,
+    pub edges: Vec<(String, String, Option<String>)>,
+    pub paths: Vec<Vec<String>>,
+}
+
+impl LabeledGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, id: impl Into<String>, label: Option<&str>) -> &mut Self {
+        self.nodes.push((id.into(), label.map(str::to_string)));
+        self
+    }
+
+    /// Add an edge; endpoints not yet added become unlabeled nodes.
+    pub fn add_edge(&mut self, from: impl Into<String>, to: impl Into<String>, label: Option<&str>) -> &mut Self {
+        let (from, to) = (from.into(), to.into());
+        for id in [&from, &to] {
+            if !self.nodes.iter().any(|(n, _)| n == id) {
+                self.nodes.push((id.clone(), None));
+            }
+        }
+        self.edges.push((from, to, label.map(str::to_string)));
+        self
+    }
+
+    pub fn add_path<S: AsRef<str>>(&mut self, nodes: &[S]) -> &mut Self {
+        self.paths.push(nodes.iter().map(|n| n.as_ref().to_string()).collect());
+        self
+    }
+}
+
+/// The hypervector form of a [`LabeledGraph`], with the ids and labels it
+/// was built from kept as cleanup memories.
+#[derive(Clone, Debug)]
+pub struct EncodedGraph {
+    encoder: GraphEncoder,
+    pub nodes: SparseVec,
+    pub node_labels: SparseVec,
+    pub edges: SparseVec,
+    pub edge_labels: SparseVec,
+    pub paths: SparseVec,
+    node_count: usize,
+    node_label_count: usize,
+    edge_count: usize,
+    edge_label_count: usize,
+    path_count: usize,
+    node_ids: Vec<String>,
+    labels: Vec<String>,
+}
+
+impl GraphEncoder {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Bipolar vector for `name`, derived from the seed and the name.
+    pub fn symbol(&self, name: &str) -> SparseVec {
+        self.vector("symbol", name)
+    }
+
+    /// `from ⊗ ρ(to)`.
+    pub fn edge(&self, from: &str, to: &str) -> SparseVec {
+        self.symbol(from).bind(&self.symbol(to).permute(1))
+    }
+
+    /// `ρ⁰(n₀) ⊗ ρ¹(n₁) ⊗ …`; empty for no nodes.
+    pub fn path<S: AsRef<str>>(&self, nodes: &[S]) -> SparseVec {
+        let mut steps = nodes.iter().enumerate().map(|(i, n)| self.symbol(n.as_ref()).permute(i));
+        match steps.next() {
+            Some(first) => steps.fold(first, |acc, step| acc.bind(&step)),
+            None => SparseVec::new(),
+        }
+    }
+
+    pub fn encode(&self, graph: &LabeledGraph) -> EncodedGraph {
+        let label_role = self.vector("role", LABEL_ROLE);
+        let labeled = |key: SparseVec, label: &str| key.bind(&label_role).bind(&self.symbol(label));
+
+        let node_ids: BTreeSet<&str> = graph.nodes.iter().map(|(id, _)| id.as_str()).collect();
+        let node_vecs: Vec<SparseVec> = node_ids.iter().map(|id| self.symbol(id)).collect();
+        let node_label_vecs: Vec<SparseVec> = graph
+            .nodes
+            .iter()
+            .filter_map(|(id, label)| label.as_deref().map(|l| labeled(self.symbol(id), l)))
+            .collect();
+        let edge_vecs: Vec<SparseVec> = graph.edges.iter().map(|(a, b, _)| self.edge(a, b)).collect();
+        let edge_label_vecs: Vec<SparseVec> = graph
+            .edges
+            .iter()
+            .filter_map(|(a, b, label)| label.as_deref().map(|l| labeled(self.edge(a, b), l)))
+            .collect();
+        let path_vecs: Vec<SparseVec> = graph.paths.iter().map(|p| self.path(p)).collect();
+
+        let labels: BTreeSet<&str> = graph
+            .nodes
+            .iter()
+            .filter_map(|(_, l)| l.as_deref())
+            .chain(graph.edges.iter().filter_map(|(_, _, l)| l.as_deref()))
+            .collect();
+
+        EncodedGraph {
+            encoder: self.clone(),
+            nodes: SparseVec::bundle_sum_many(&node_vecs),
+            node_labels: SparseVec::bundle_sum_many(&node_label_vecs),
+            edges: SparseVec::bundle_sum_many(&edge_vecs),
+            edge_labels: SparseVec::bundle_sum_many(&edge_label_vecs),
+            paths: SparseVec::bundle_sum_many(&path_vecs),
+            node_count: node_vecs.len(),
+            node_label_count: node_label_vecs.len(),
+            edge_count: edge_vecs.len(),
+            edge_label_count: edge_label_vecs.len(),
+            path_count: path_vecs.len(),
+            node_ids: node_ids.into_iter().map(str::to_string).collect(),
+            labels: labels.into_iter().map(str::to_string).collect(),
+        }
+    }
+
+    fn vector(&self, domain: &str, name: &str) -> SparseVec {
+        bipolar(self.seed, domain, name)
+    }
+}
+
+impl EncodedGraph {
+    /// Score above which an item counts as a member of a bundle of
+    /// `members` items; see [`bundle_threshold`].
+    pub fn threshold(members: usize) -> f64 {
+        bundle_threshold(members)
+    }
+
+    pub fn node_score(&self, id: &str) -> f64 {
+        self.nodes.cosine(&self.encoder.symbol(id))
+    }
+
+    pub fn has_node(&self, id: &str) -> bool {
+        self.node_score(id) > Self::threshold(self.node_count)
+    }
+
+    /// Cosine of the edge `(from, to)` with the edge bundle.
+    pub fn edge_score(&self, from: &str, to: &str) -> f64 {
+        self.edges.cosine(&self.encoder.edge(from, to))
+    }
+
+    /// Whether the directed edge `(from, to)` is in the graph.
+    pub fn has_edge(&self, from: &str, to: &str) -> bool {
+        self.edge_score(from, to) > Self::threshold(self.edge_count)
+    }
+
+    pub fn has_path<S: AsRef<str>>(&self, nodes: &[S]) -> bool {
+        !nodes.is_empty() && self.paths.cosine(&self.encoder.path(nodes)) > Self::threshold(self.path_count)
+    }
+
+    /// Nodes with an edge from `from`, best first. Unbinds `from` from the
+    /// edge bundle and cleans up what is left against every node id.
+    pub fn successors(&self, from: &str) -> Vec<(String, f64)> {
+        let probe = self.edges.bind(&self.encoder.symbol(from)).inverse_permute(1);
+        self.cleanup(&probe, &self.node_ids, Self::threshold(self.edge_count))
+    }
+
+    /// Nodes with an edge to `to`, best first.
+    pub fn predecessors(&self, to: &str) -> Vec<(String, f64)> {
+        let probe = self.edges.bind(&self.encoder.symbol(to).permute(1));
+        self.cleanup(&probe, &self.node_ids, Self::threshold(self.edge_count))
+    }
+
+    /// The label of node `id`, if it has one the graph knows.
+    pub fn node_label(&self, id: &str) -> Option<String> {
+        let key = self.encoder.symbol(id);
+        self.decode_label(&self.node_labels, key, self.node_label_count)
+    }
+
+    /// The label of edge `(from, to)`, if it has one the graph knows.
+    pub fn edge_label(&self, from: &str, to: &str) -> Option<String> {
+        let key = self.encoder.edge(from, to);
+        self.decode_label(&self.edge_labels, key, self.edge_label_count)
+    }
+
+    fn decode_label(&self, bundle: &SparseVec, key: SparseVec, members: usize) -> Option<String> {
+        let probe = bundle.bind(&key).bind(&self.encoder.vector("role", LABEL_ROLE));
+        self.cleanup(&probe, &self.labels, Self::threshold(members))
+            .into_iter()
+            .next()
+            .map(|(label, _)| label)
+    }
+
+    /// Candidates scoring above `threshold` against `probe`, best first.
+    fn cleanup(&self, probe: &SparseVec, candidates: &[String], threshold: f64) -> Vec<(String, f64)> {
+        let mut hits: Vec<(String, f64)> = candidates
+            .iter()
+            .map(|c| (c.clone(), probe.cosine(&self.encoder.symbol(c))))
+            .filter(|(_, score)| *score > threshold)
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hits
+    }
+}
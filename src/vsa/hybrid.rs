@@ -30,11 +30,22 @@
 //! let bound = hybrid.bind(&other_hybrid, DIM);
 //! let bundled = hybrid.bundle(&other_hybrid, DIM);
 //! ```
+//!
+//! # Calibration
+//!
+//! The constants below are the built-in crossover points, and the methods
+//! without a `_with` suffix select by them, so a vector gets the same
+//! representation on every machine. `embeddenator bench --calibrate`
+//! measures the crossover points of the local CPU and saves them (see
+//! [`HybridThresholds::path`]); a caller that wants them loads them once
+//! with [`HybridThresholds::saved`] and passes them to the `_with` methods.
 
 use crate::bitsliced::BitslicedTritVec;
 use crate::block_sparse::BlockSparseTritVec;
 use crate::vsa::{SparseVec, DIM};
 use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
 
 // ============================================================================
 // CONFIGURATION
@@ -61,10 +72,122 @@ pub const MIN_BLOCK_SPARSE_DIM: usize = 100_000;
 /// At 1% density and 1M dimensions, block-sparse uses ~160KB vs ~31MB for dense.
 pub const BLOCK_SPARSE_DENSITY_THRESHOLD: f64 = 0.01; // 1%
 
+/// The crossover points [`HybridTritVec`] selects representations by.
+///
+/// `Default` gives the built-in constants; `embeddenator bench --calibrate`
+/// measures them for the local CPU and saves them to [`Self::path`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HybridThresholds {
+    /// Density below which sparse beats bitsliced ([`DENSITY_THRESHOLD`]).
+    pub density: f64,
+    /// Dimension below which vectors always stay sparse ([`MIN_BITSLICED_DIM`]).
+    pub min_bitsliced_dim: usize,
+    /// Dimension from which block-sparse is considered ([`MIN_BLOCK_SPARSE_DIM`]).
+    pub min_block_sparse_dim: usize,
+    /// Density below which block-sparse beats bitsliced at those dimensions
+    /// ([`BLOCK_SPARSE_DENSITY_THRESHOLD`]).
+    pub block_sparse_density: f64,
+}
+
+impl Default for HybridThresholds {
+    fn default() -> Self {
+        Self::BUILT_IN
+    }
+}
+
+impl HybridThresholds {
+    /// The built-in constants.
+    pub const BUILT_IN: Self = HybridThresholds {
+        density: DENSITY_THRESHOLD,
+        min_bitsliced_dim: MIN_BITSLICED_DIM,
+        min_block_sparse_dim: MIN_BLOCK_SPARSE_DIM,
+        block_sparse_density: BLOCK_SPARSE_DENSITY_THRESHOLD,
+    };
+
+    /// Where calibration results are kept: `$EMBEDDENATOR_CALIBRATION`, or
+    /// else `$XDG_CACHE_HOME/embeddenator/calibration.json` (`~/.cache/...`
+    /// when `XDG_CACHE_HOME` is unset). `None` when no location can be derived.
+    pub fn path() -> Option<PathBuf> {
+        let env = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+        if let Some(path) = env("EMBEDDENATOR_CALIBRATION") {
+            return Some(PathBuf::from(path));
+        }
+        let base = env("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(base.join("embeddenator").join("calibration.json"))
+    }
+
+    /// Read thresholds saved by [`Self::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let thresholds: Self = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        thresholds.validate()?;
+        Ok(thresholds)
+    }
+
+    /// Write the thresholds as JSON, creating the parent directory.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.validate()?;
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    /// Densities must lie in (0, 1] and `min_bitsliced_dim` must not exceed
+    /// `min_block_sparse_dim`.
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |msg: &str| Err(io::Error::new(io::ErrorKind::InvalidData, msg.to_string()));
+        if !(self.density > 0.0 && self.density <= 1.0) {
+            return invalid("density threshold must be in (0, 1]");
+        }
+        if !(self.block_sparse_density > 0.0 && self.block_sparse_density <= 1.0) {
+            return invalid("block-sparse density threshold must be in (0, 1]");
+        }
+        if self.min_bitsliced_dim > self.min_block_sparse_dim {
+            return invalid("min_bitsliced_dim exceeds min_block_sparse_dim");
+        }
+        Ok(())
+    }
+
+    /// The representation vectors of `density` at `dim` dimensions are
+    /// kept in; see [`HybridTritVec::from_sparse`].
+    pub fn select(&self, dim: usize, density: f64) -> HybridRepr {
+        if dim < self.min_bitsliced_dim {
+            HybridRepr::Sparse
+        } else if dim >= self.min_block_sparse_dim && density < self.block_sparse_density {
+            HybridRepr::BlockSparse
+        } else if density < self.density {
+            HybridRepr::Sparse
+        } else {
+            HybridRepr::Bitsliced
+        }
+    }
+
+    /// The calibration saved at [`Self::path`], or `None` if there is none.
+    pub fn saved() -> io::Result<Option<Self>> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::load(&path).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
 // ============================================================================
 // HYBRID REPRESENTATION
 // ============================================================================
 
+/// The representations a [`HybridTritVec`] can hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HybridRepr {
+    Sparse,
+    Bitsliced,
+    BlockSparse,
+}
+
 /// Hybrid ternary vector with automatic representation selection.
 ///
 /// Transparently wraps either `SparseVec` or `BitslicedTritVec`, selecting
@@ -84,29 +207,36 @@ impl HybridTritVec {
     // CONSTRUCTION
     // ========================================================================
 
-    /// Create from sparse vector, auto-selecting representation.
+    /// Create from sparse vector, auto-selecting representation by the
+    /// built-in thresholds.
     ///
     /// # Arguments
     /// * `sparse` - Source sparse vector
     /// * `dim` - Total dimension (needed for density calculation)
     ///
     /// # Selection Logic
-    /// - If `dim < MIN_BITSLICED_DIM`: Always sparse
-    /// - If `dim >= MIN_BLOCK_SPARSE_DIM` AND `density < 1%`: Block-sparse
-    /// - If `nnz/dim < DENSITY_THRESHOLD`: Sparse
+    /// - If `dim < min_bitsliced_dim`: Always sparse
+    /// - If `dim >= min_block_sparse_dim` AND `density < block_sparse_density`: Block-sparse
+    /// - If `nnz/dim < density`: Sparse
     /// - Otherwise: Convert to bitsliced
     pub fn from_sparse(sparse: SparseVec, dim: usize) -> Self {
+        Self::from_sparse_with(sparse, dim, &HybridThresholds::BUILT_IN)
+    }
+
+    /// [`Self::from_sparse`], selecting by `thresholds`.
+    pub fn from_sparse_with(sparse: SparseVec, dim: usize, thresholds: &HybridThresholds) -> Self {
         let nnz = sparse.pos.len() + sparse.neg.len();
         let density = nnz as f64 / dim as f64;
+        Self::from_sparse_as(sparse, dim, thresholds.select(dim, density))
+    }
 
-        if dim < MIN_BITSLICED_DIM {
-            HybridTritVec::Sparse(sparse)
-        } else if dim >= MIN_BLOCK_SPARSE_DIM && density < BLOCK_SPARSE_DENSITY_THRESHOLD {
-            HybridTritVec::BlockSparse(BlockSparseTritVec::from_sparse(&sparse, dim))
-        } else if density < DENSITY_THRESHOLD {
-            HybridTritVec::Sparse(sparse)
-        } else {
-            HybridTritVec::Bitsliced(BitslicedTritVec::from_sparse(&sparse, dim))
+    /// Create from sparse vector in the representation `repr`, whatever its
+    /// density.
+    pub fn from_sparse_as(sparse: SparseVec, dim: usize, repr: HybridRepr) -> Self {
+        match repr {
+            HybridRepr::Sparse => HybridTritVec::Sparse(sparse),
+            HybridRepr::Bitsliced => HybridTritVec::Bitsliced(BitslicedTritVec::from_sparse(&sparse, dim)),
+            HybridRepr::BlockSparse => HybridTritVec::BlockSparse(BlockSparseTritVec::from_sparse(&sparse, dim)),
         }
     }
 
@@ -127,9 +257,14 @@ impl HybridTritVec {
 
     /// Create zero vector with specified dimension as bitsliced.
     pub fn new_zero(dim: usize) -> Self {
-        if dim < MIN_BITSLICED_DIM {
+        Self::new_zero_with(dim, &HybridThresholds::BUILT_IN)
+    }
+
+    /// [`Self::new_zero`], selecting by `thresholds`.
+    pub fn new_zero_with(dim: usize, t: &HybridThresholds) -> Self {
+        if dim < t.min_bitsliced_dim {
             HybridTritVec::Sparse(SparseVec::new())
-        } else if dim >= MIN_BLOCK_SPARSE_DIM {
+        } else if dim >= t.min_block_sparse_dim {
             HybridTritVec::BlockSparse(BlockSparseTritVec::new(dim))
         } else {
             HybridTritVec::Bitsliced(BitslicedTritVec::new_zero(dim))
@@ -140,6 +275,27 @@ impl HybridTritVec {
     // REPRESENTATION ACCESS
     // ========================================================================
 
+    /// The representation currently held.
+    pub fn repr(&self) -> HybridRepr {
+        match self {
+            HybridTritVec::Sparse(_) => HybridRepr::Sparse,
+            HybridTritVec::Bitsliced(_) => HybridRepr::Bitsliced,
+            HybridTritVec::BlockSparse(_) => HybridRepr::BlockSparse,
+        }
+    }
+
+    /// Convert to `repr`, or return `self` unchanged if it is already there.
+    pub fn into_repr(self, repr: HybridRepr, dim: usize) -> Self {
+        if self.repr() == repr {
+            return self;
+        }
+        match repr {
+            HybridRepr::Sparse => HybridTritVec::Sparse(self.to_sparse()),
+            HybridRepr::Bitsliced => HybridTritVec::Bitsliced(self.to_bitsliced(dim)),
+            HybridRepr::BlockSparse => HybridTritVec::BlockSparse(self.to_block_sparse(dim)),
+        }
+    }
+
     /// Check if currently using sparse representation.
     #[inline]
     pub fn is_sparse(&self) -> bool {
@@ -240,25 +396,27 @@ impl HybridTritVec {
     /// - BlockSparse × Other: Convert based on dimension
     /// - Otherwise: Convert to bitsliced and use SIMD-optimized path
     pub fn bind(&self, other: &Self, dim: usize) -> Self {
+        self.bind_with(other, dim, &HybridThresholds::BUILT_IN)
+    }
+
+    /// [`Self::bind`], selecting by `thresholds`.
+    pub fn bind_with(&self, other: &Self, dim: usize, thresholds: &HybridThresholds) -> Self {
         match (self, other) {
             (HybridTritVec::Sparse(a), HybridTritVec::Sparse(b)) => {
                 let result = a.bind(b);
-                HybridTritVec::from_sparse(result, dim)
+                HybridTritVec::from_sparse_with(result, dim, thresholds)
             }
             (HybridTritVec::BlockSparse(a), HybridTritVec::BlockSparse(b)) => {
                 HybridTritVec::BlockSparse(a.bind_dispatch(b))
             }
-            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_)) => {
-                // Mixed with block-sparse: choose representation by dimension
-                if dim >= MIN_BLOCK_SPARSE_DIM {
-                    let a_bs = self.to_block_sparse(dim);
-                    let b_bs = other.to_block_sparse(dim);
-                    HybridTritVec::BlockSparse(a_bs.bind_dispatch(&b_bs))
-                } else {
-                    let a_bit = self.to_bitsliced(dim);
-                    let b_bit = other.to_bitsliced(dim);
-                    HybridTritVec::Bitsliced(a_bit.bind(&b_bit))
-                }
+            // Mixed with block-sparse: block-sparse at large dimensions,
+            // bitsliced below them.
+            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_))
+                if dim >= thresholds.min_block_sparse_dim =>
+            {
+                let a_bs = self.to_block_sparse(dim);
+                let b_bs = other.to_block_sparse(dim);
+                HybridTritVec::BlockSparse(a_bs.bind_dispatch(&b_bs))
             }
             _ => {
                 // At least one is bitsliced, use bitsliced path
@@ -276,24 +434,25 @@ impl HybridTritVec {
     ///
     /// Conflict-cancel semantics: +1 + (-1) = 0
     pub fn bundle(&self, other: &Self, dim: usize) -> Self {
+        self.bundle_with(other, dim, &HybridThresholds::BUILT_IN)
+    }
+
+    /// [`Self::bundle`], selecting by `thresholds`.
+    pub fn bundle_with(&self, other: &Self, dim: usize, thresholds: &HybridThresholds) -> Self {
         match (self, other) {
             (HybridTritVec::Sparse(a), HybridTritVec::Sparse(b)) => {
                 let result = a.bundle(b);
-                HybridTritVec::from_sparse(result, dim)
+                HybridTritVec::from_sparse_with(result, dim, thresholds)
             }
             (HybridTritVec::BlockSparse(a), HybridTritVec::BlockSparse(b)) => {
                 HybridTritVec::BlockSparse(a.bundle_dispatch(b))
             }
-            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_)) => {
-                if dim >= MIN_BLOCK_SPARSE_DIM {
-                    let a_bs = self.to_block_sparse(dim);
-                    let b_bs = other.to_block_sparse(dim);
-                    HybridTritVec::BlockSparse(a_bs.bundle_dispatch(&b_bs))
-                } else {
-                    let a_bit = self.to_bitsliced(dim);
-                    let b_bit = other.to_bitsliced(dim);
-                    HybridTritVec::Bitsliced(a_bit.bundle(&b_bit))
-                }
+            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_))
+                if dim >= thresholds.min_block_sparse_dim =>
+            {
+                let a_bs = self.to_block_sparse(dim);
+                let b_bs = other.to_block_sparse(dim);
+                HybridTritVec::BlockSparse(a_bs.bundle_dispatch(&b_bs))
             }
             _ => {
                 let a_bs = self.to_bitsliced(dim);
@@ -305,20 +464,21 @@ impl HybridTritVec {
 
     /// Dot product: $\langle a, b \rangle = \sum_i a_i \cdot b_i$
     pub fn dot(&self, other: &Self, dim: usize) -> i64 {
+        self.dot_with(other, dim, &HybridThresholds::BUILT_IN)
+    }
+
+    /// [`Self::dot`], converting by `thresholds`.
+    pub fn dot_with(&self, other: &Self, dim: usize, thresholds: &HybridThresholds) -> i64 {
         match (self, other) {
             (HybridTritVec::BlockSparse(a), HybridTritVec::BlockSparse(b)) => {
                 a.dot_dispatch(b)
             }
-            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_)) => {
-                if dim >= MIN_BLOCK_SPARSE_DIM {
-                    let a_bs = self.to_block_sparse(dim);
-                    let b_bs = other.to_block_sparse(dim);
-                    a_bs.dot_dispatch(&b_bs)
-                } else {
-                    let a_bit = self.to_bitsliced(dim);
-                    let b_bit = other.to_bitsliced(dim);
-                    a_bit.dot(&b_bit) as i64
-                }
+            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_))
+                if dim >= thresholds.min_block_sparse_dim =>
+            {
+                let a_bs = self.to_block_sparse(dim);
+                let b_bs = other.to_block_sparse(dim);
+                a_bs.dot_dispatch(&b_bs)
             }
             _ => {
                 let a_bs = self.to_bitsliced(dim);
@@ -330,21 +490,22 @@ impl HybridTritVec {
 
     /// Cosine similarity: $\cos(a, b) = \frac{\langle a, b \rangle}{\sqrt{|a|_0 \cdot |b|_0}}$
     pub fn cosine(&self, other: &Self, dim: usize) -> f64 {
+        self.cosine_with(other, dim, &HybridThresholds::BUILT_IN)
+    }
+
+    /// [`Self::cosine`], converting by `thresholds`.
+    pub fn cosine_with(&self, other: &Self, dim: usize, thresholds: &HybridThresholds) -> f64 {
         match (self, other) {
             (HybridTritVec::Sparse(a), HybridTritVec::Sparse(b)) => a.cosine(b),
             (HybridTritVec::BlockSparse(a), HybridTritVec::BlockSparse(b)) => {
                 a.cosine_dispatch(b)
             }
-            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_)) => {
-                if dim >= MIN_BLOCK_SPARSE_DIM {
-                    let a_bs = self.to_block_sparse(dim);
-                    let b_bs = other.to_block_sparse(dim);
-                    a_bs.cosine_dispatch(&b_bs)
-                } else {
-                    let a_bit = self.to_bitsliced(dim);
-                    let b_bit = other.to_bitsliced(dim);
-                    a_bit.cosine(&b_bit)
-                }
+            (HybridTritVec::BlockSparse(_), _) | (_, HybridTritVec::BlockSparse(_))
+                if dim >= thresholds.min_block_sparse_dim =>
+            {
+                let a_bs = self.to_block_sparse(dim);
+                let b_bs = other.to_block_sparse(dim);
+                a_bs.cosine_dispatch(&b_bs)
             }
             _ => {
                 let a_bs = self.to_bitsliced(dim);
@@ -409,6 +570,7 @@ impl HybridTritVec {
     /// - Result: +1 if pos > neg, -1 if neg > pos, 0 otherwise
     pub fn bundle_many<'a>(vecs: impl IntoIterator<Item = &'a Self>, dim: usize) -> Self {
         use crate::bitsliced::CarrySaveBundle;
+        let _t = crate::profile::scope("bundle_many");
 
         let mut acc = CarrySaveBundle::new(dim);
 
@@ -0,0 +1,364 @@
+//! Algebraic laws of ternary vectors, as reusable property tests.
+//!
+//! Every representation here ([`SparseVec`], [`BitslicedTritVec`],
+//! [`BlockSparseTritVec`]) implements the same algebra: bundle is the
+//! pairwise saturating add (`P ⊕ N = Z`), bind is the elementwise product
+//! and permute is a cyclic shift towards higher indices. Code that mixes
+//! representations relies on them agreeing exactly, and a new
+//! representation is only a drop-in replacement if it obeys the same laws.
+//!
+//! Implement [`TernaryVector`] for a type and run [`check_laws`] against it,
+//! and [`check_equivalence`] against one of the representations above:
+//!
+//! ```rust,ignore
+//! use embeddenator::vsa::laws::{self, TernaryVector};
+//!
+//! #[test]
+//! fn my_vec_obeys_the_laws() {
+//!     let config = laws::config(256);
+//!     laws::check_laws::<MyVec>(4096, config.clone()).unwrap();
+//!     laws::check_equivalence::<MyVec, embeddenator::BitslicedTritVec>(4096, config).unwrap();
+//! }
+//! ```
+//!
+//! Pairwise bundle is not associative in general: `(P ⊕ N) ⊕ N = N` but
+//! `P ⊕ (N ⊕ N) = Z`. Associativity is checked on operands that never
+//! disagree in sign at a position, where it does hold.
+//!
+//! Available with the `laws` feature.
+
+use crate::bitsliced::BitslicedTritVec;
+use crate::block_sparse::BlockSparseTritVec;
+use crate::vsa::{SparseVec, DIM};
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestError, TestRunner};
+use std::fmt;
+
+/// A ternary vector representation the laws can be checked against.
+///
+/// Vectors are compared through [`TernaryVector::to_sparse`], so two values
+/// are equal when they hold the same trits, however they store them.
+pub trait TernaryVector: Clone + fmt::Debug {
+    /// The vector of dimension `dim` holding the trits of `v`, whose
+    /// indices are all below `dim`.
+    fn from_sparse(v: &SparseVec, dim: usize) -> Self;
+    /// Sorted positive and negative indices.
+    fn to_sparse(&self) -> SparseVec;
+    fn dim(&self) -> usize;
+    /// Pairwise saturating add: `P ⊕ P = P`, `P ⊕ Z = P`, `P ⊕ N = Z`.
+    fn bundle(&self, other: &Self) -> Self;
+    /// Elementwise product.
+    fn bind(&self, other: &Self) -> Self;
+    /// Cyclic shift by `shift` towards higher indices:
+    /// `out[(i + shift) % dim] = self[i]`.
+    fn permute(&self, shift: usize) -> Self;
+    fn dot(&self, other: &Self) -> i64;
+}
+
+/// Uses the crate-wide [`DIM`]; `dim` must equal it.
+impl TernaryVector for SparseVec {
+    fn from_sparse(v: &SparseVec, dim: usize) -> Self {
+        assert_eq!(dim, DIM, "SparseVec is fixed at DIM");
+        v.clone()
+    }
+
+    fn to_sparse(&self) -> SparseVec {
+        self.clone()
+    }
+
+    fn dim(&self) -> usize {
+        DIM
+    }
+
+    fn bundle(&self, other: &Self) -> Self {
+        SparseVec::bundle(self, other)
+    }
+
+    fn bind(&self, other: &Self) -> Self {
+        SparseVec::bind(self, other)
+    }
+
+    fn permute(&self, shift: usize) -> Self {
+        SparseVec::permute(self, shift)
+    }
+
+    fn dot(&self, other: &Self) -> i64 {
+        BitslicedTritVec::from_sparse(self, DIM).dot(&BitslicedTritVec::from_sparse(other, DIM)) as i64
+    }
+}
+
+impl TernaryVector for BitslicedTritVec {
+    fn from_sparse(v: &SparseVec, dim: usize) -> Self {
+        BitslicedTritVec::from_sparse(v, dim)
+    }
+
+    fn to_sparse(&self) -> SparseVec {
+        BitslicedTritVec::to_sparse(self)
+    }
+
+    fn dim(&self) -> usize {
+        self.len()
+    }
+
+    fn bundle(&self, other: &Self) -> Self {
+        BitslicedTritVec::bundle(self, other)
+    }
+
+    fn bind(&self, other: &Self) -> Self {
+        BitslicedTritVec::bind(self, other)
+    }
+
+    fn permute(&self, shift: usize) -> Self {
+        BitslicedTritVec::permute(self, shift)
+    }
+
+    fn dot(&self, other: &Self) -> i64 {
+        BitslicedTritVec::dot(self, other) as i64
+    }
+}
+
+/// Has no native permute; shifts through [`SparseVec`].
+impl TernaryVector for BlockSparseTritVec {
+    fn from_sparse(v: &SparseVec, dim: usize) -> Self {
+        BlockSparseTritVec::from_sparse(v, dim)
+    }
+
+    fn to_sparse(&self) -> SparseVec {
+        BlockSparseTritVec::to_sparse(self)
+    }
+
+    fn dim(&self) -> usize {
+        BlockSparseTritVec::dim(self)
+    }
+
+    fn bundle(&self, other: &Self) -> Self {
+        BlockSparseTritVec::bundle(self, other)
+    }
+
+    fn bind(&self, other: &Self) -> Self {
+        BlockSparseTritVec::bind(self, other)
+    }
+
+    fn permute(&self, shift: usize) -> Self {
+        let dim = self.dim();
+        let sparse = self.to_sparse();
+        let shifted = |idx: &Vec<usize>| {
+            let mut out: Vec<usize> = idx.iter().map(|&i| (i + shift) % dim).collect();
+            out.sort_unstable();
+            out
+        };
+        let shifted = SparseVec { pos: shifted(&sparse.pos), neg: shifted(&sparse.neg) };
+        BlockSparseTritVec::from_sparse(&shifted, dim)
+    }
+
+    fn dot(&self, other: &Self) -> i64 {
+        BlockSparseTritVec::dot(self, other)
+    }
+}
+
+/// A law that failed, with proptest's minimal counterexample.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LawViolation {
+    pub law: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for LawViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} violated: {}", self.law, self.reason)
+    }
+}
+
+impl std::error::Error for LawViolation {}
+
+/// A proptest configuration running `cases` cases per law, without
+/// failure persistence (downstream crates have no regression file here).
+pub fn config(cases: u32) -> Config {
+    Config { cases, failure_persistence: None, ..Config::default() }
+}
+
+/// Sparse vectors of dimension `dim` with at most `max_nnz` nonzero trits.
+pub fn sparse_vectors(dim: usize, max_nnz: usize) -> impl Strategy<Value = SparseVec> {
+    prop::collection::btree_map(0..dim, any::<bool>(), 0..=max_nnz).prop_map(|trits| {
+        let (pos, neg): (Vec<_>, Vec<_>) = trits.into_iter().partition(|&(_, positive)| positive);
+        SparseVec { pos: pos.into_iter().map(|(i, _)| i).collect(), neg: neg.into_iter().map(|(i, _)| i).collect() }
+    })
+}
+
+/// Three sparse vectors that never disagree in sign at a position: each
+/// index gets one sign and appears in any subset of the three.
+pub fn conflict_free_triples(dim: usize, max_nnz: usize) -> impl Strategy<Value = [SparseVec; 3]> {
+    prop::collection::btree_map(0..dim, (any::<bool>(), 1u8..8), 0..=max_nnz).prop_map(|trits| {
+        let mut out: [SparseVec; 3] = Default::default();
+        for (i, (positive, members)) in trits {
+            for (k, v) in out.iter_mut().enumerate() {
+                if members & (1 << k) != 0 {
+                    if positive { v.pos.push(i) } else { v.neg.push(i) }
+                }
+            }
+        }
+        out
+    })
+}
+
+fn eq<V: TernaryVector>(left: &V, right: &V, what: &str) -> Result<(), TestCaseError> {
+    let (l, r) = (left.to_sparse(), right.to_sparse());
+    prop_assert!(l.pos == r.pos && l.neg == r.neg, "{}: {:?} != {:?}", what, l, r);
+    Ok(())
+}
+
+fn run<S: Strategy>(
+    law: &'static str,
+    config: &Config,
+    strategy: S,
+    test: impl Fn(S::Value) -> Result<(), TestCaseError>,
+) -> Result<(), LawViolation> {
+    TestRunner::new(config.clone()).run(&strategy, test).map_err(|e| LawViolation {
+        law,
+        reason: match e {
+            TestError::Abort(reason) => format!("aborted: {reason}"),
+            TestError::Fail(reason, value) => format!("{reason}; minimal input: {value:?}"),
+        },
+    })
+}
+
+/// Nonzero trits per generated vector: dense enough for collisions in
+/// small dimensions, sparse enough to stay fast in large ones.
+fn max_nnz(dim: usize) -> usize {
+    (dim / 4).clamp(1, 512)
+}
+
+/// `a ⊕ b = b ⊕ a`.
+pub fn bundle_commutative<V: TernaryVector>(dim: usize, config: &Config) -> Result<(), LawViolation> {
+    let v = || sparse_vectors(dim, max_nnz(dim));
+    run("bundle commutativity", config, (v(), v()), |(a, b)| {
+        let (a, b) = (V::from_sparse(&a, dim), V::from_sparse(&b, dim));
+        eq(&a.bundle(&b), &b.bundle(&a), "a ⊕ b vs b ⊕ a")
+    })
+}
+
+/// `(a ⊕ b) ⊕ c = a ⊕ (b ⊕ c)` for operands without sign conflicts.
+pub fn bundle_associative<V: TernaryVector>(dim: usize, config: &Config) -> Result<(), LawViolation> {
+    run("bundle associativity", config, conflict_free_triples(dim, max_nnz(dim)), |[a, b, c]| {
+        let (a, b, c) = (V::from_sparse(&a, dim), V::from_sparse(&b, dim), V::from_sparse(&c, dim));
+        eq(&a.bundle(&b).bundle(&c), &a.bundle(&b.bundle(&c)), "(a ⊕ b) ⊕ c vs a ⊕ (b ⊕ c)")
+    })
+}
+
+/// `a ⊙ (b ⊕ c) = (a ⊙ b) ⊕ (a ⊙ c)`.
+pub fn bind_distributes_over_bundle<V: TernaryVector>(dim: usize, config: &Config) -> Result<(), LawViolation> {
+    let v = || sparse_vectors(dim, max_nnz(dim));
+    run("bind distributivity", config, (v(), v(), v()), |(a, b, c)| {
+        let (a, b, c) = (V::from_sparse(&a, dim), V::from_sparse(&b, dim), V::from_sparse(&c, dim));
+        eq(&a.bind(&b.bundle(&c)), &a.bind(&b).bundle(&a.bind(&c)), "a ⊙ (b ⊕ c) vs (a ⊙ b) ⊕ (a ⊙ c)")
+    })
+}
+
+/// `a ⊙ a` is `+1` exactly on the support of `a`, so binding twice by `a`
+/// gives back `x` wherever `a` is nonzero: `(x ⊙ a) ⊙ a = x ⊙ (a ⊙ a)`.
+pub fn bind_self_inverse<V: TernaryVector>(dim: usize, config: &Config) -> Result<(), LawViolation> {
+    let v = || sparse_vectors(dim, max_nnz(dim));
+    run("bind self-inverse", config, (v(), v()), |(x, a)| {
+        let mut support: Vec<usize> = a.pos.iter().chain(&a.neg).copied().collect();
+        support.sort_unstable();
+        let (x, a) = (V::from_sparse(&x, dim), V::from_sparse(&a, dim));
+        let aa = a.bind(&a);
+        eq(&aa, &V::from_sparse(&SparseVec { pos: support, neg: Vec::new() }, dim), "a ⊙ a vs |a|")?;
+        eq(&x.bind(&a).bind(&a), &x.bind(&aa), "(x ⊙ a) ⊙ a vs x ⊙ |a|")
+    })
+}
+
+/// Shifting by `k` and then by `dim - k` is the identity, and shifting
+/// keeps the number of nonzero trits.
+pub fn permute_invertible<V: TernaryVector>(dim: usize, config: &Config) -> Result<(), LawViolation> {
+    run("permute invertibility", config, (sparse_vectors(dim, max_nnz(dim)), 0..dim * 2), |(a, k)| {
+        let a = V::from_sparse(&a, dim);
+        let shifted = a.permute(k);
+        let (before, after) = (a.to_sparse(), shifted.to_sparse());
+        prop_assert_eq!(before.pos.len() + before.neg.len(), after.pos.len() + after.neg.len());
+        eq(&shifted.permute(dim - k % dim), &a, "permute(permute(a, k), dim - k) vs a")
+    })
+}
+
+/// Every law above, stopping at the first violation.
+pub fn check_laws<V: TernaryVector>(dim: usize, config: Config) -> Result<(), LawViolation> {
+    bundle_commutative::<V>(dim, &config)?;
+    bundle_associative::<V>(dim, &config)?;
+    bind_distributes_over_bundle::<V>(dim, &config)?;
+    bind_self_inverse::<V>(dim, &config)?;
+    permute_invertible::<V>(dim, &config)
+}
+
+/// `A` and `B` agree on every operation: converting inputs to each, applying
+/// bundle, bind, permute or dot, and comparing the results.
+pub fn check_equivalence<A: TernaryVector, B: TernaryVector>(dim: usize, config: Config) -> Result<(), LawViolation> {
+    let v = || sparse_vectors(dim, max_nnz(dim));
+    run("representation equivalence", &config, (v(), v(), 0..dim), |(x, y, k)| {
+        let (ax, ay) = (A::from_sparse(&x, dim), A::from_sparse(&y, dim));
+        let (bx, by) = (B::from_sparse(&x, dim), B::from_sparse(&y, dim));
+        let same = |a: SparseVec, b: SparseVec, what: &str| {
+            prop_assert!(a.pos == b.pos && a.neg == b.neg, "{}: {:?} != {:?}", what, a, b);
+            Ok(())
+        };
+        prop_assert_eq!(ax.dim(), bx.dim());
+        same(ax.to_sparse(), bx.to_sparse(), "round trip")?;
+        same(ax.bundle(&ay).to_sparse(), bx.bundle(&by).to_sparse(), "bundle")?;
+        same(ax.bind(&ay).to_sparse(), bx.bind(&by).to_sparse(), "bind")?;
+        same(ax.permute(k).to_sparse(), bx.permute(k).to_sparse(), "permute")?;
+        prop_assert_eq!(ax.dot(&ay), bx.dot(&by), "dot");
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_representations_obey_the_laws() {
+        // A small dimension, where indices collide often, and a large one.
+        for dim in [67, 1000] {
+            check_laws::<BitslicedTritVec>(dim, config(64)).unwrap();
+            check_laws::<BlockSparseTritVec>(dim, config(64)).unwrap();
+            check_equivalence::<BitslicedTritVec, BlockSparseTritVec>(dim, config(64)).unwrap();
+        }
+        check_laws::<SparseVec>(DIM, config(64)).unwrap();
+        check_equivalence::<SparseVec, BitslicedTritVec>(DIM, config(64)).unwrap();
+        check_equivalence::<SparseVec, BlockSparseTritVec>(DIM, config(64)).unwrap();
+    }
+
+    #[test]
+    fn violations_name_the_law() {
+        /// Bundle that forgets the right operand.
+        #[derive(Clone, Debug)]
+        struct LeftBiased(BitslicedTritVec);
+
+        impl TernaryVector for LeftBiased {
+            fn from_sparse(v: &SparseVec, dim: usize) -> Self {
+                LeftBiased(BitslicedTritVec::from_sparse(v, dim))
+            }
+            fn to_sparse(&self) -> SparseVec {
+                self.0.to_sparse()
+            }
+            fn dim(&self) -> usize {
+                self.0.len()
+            }
+            fn bundle(&self, _: &Self) -> Self {
+                self.clone()
+            }
+            fn bind(&self, other: &Self) -> Self {
+                LeftBiased(self.0.bind(&other.0))
+            }
+            fn permute(&self, shift: usize) -> Self {
+                LeftBiased(self.0.permute(shift))
+            }
+            fn dot(&self, other: &Self) -> i64 {
+                self.0.dot(&other.0) as i64
+            }
+        }
+
+        let err = check_laws::<LeftBiased>(64, config(64)).unwrap_err();
+        assert_eq!(err.law, "bundle commutativity");
+        assert!(err.to_string().contains("minimal input"), "{err}");
+    }
+}
@@ -0,0 +1,358 @@
+//! Ternary matrices with VSA-consistent matrix-vector products.
+//!
+//! A [`TritMatrix`] stores its rows bitsliced and back to back: row `r`
+//! occupies words `r * stride .. (r + 1) * stride` of a positive and a
+//! negative plane, laid out exactly like a [`BitslicedTritVec`]. Bits past
+//! the last column are always zero, so a row can be ANDed with any vector
+//! of the matrix's width without masking.
+//!
+//! The products agree with the per-vector operations:
+//!
+//! - [`TritMatrix::matvec_dot`]`(v)[r] == row(r).dot(v)`
+//! - [`TritMatrix::matvec_bind`]`(v).row(r) == row(r).bind(v)`
+//!
+//! which lets random-projection encoders ([`TritMatrix::project`]), cleanup
+//! memories ([`TritMatrix::best_match`]) and resonator codebooks make one
+//! call per query instead of looping over rows. The `_dispatch` variants
+//! use AVX-512 kernels when the CPU has them and the [`crate::simd`]
+//! self-test passed.
+
+use crate::bitsliced::BitslicedTritVec;
+use crate::ternary::Trit;
+use crate::vsa::SparseVec;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Row-major ternary matrix with bitsliced rows.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TritMatrix {
+    rows: usize,
+    cols: usize,
+    /// Words per row in each plane.
+    stride: usize,
+    pos: Vec<u64>,
+    neg: Vec<u64>,
+}
+
+impl TritMatrix {
+    /// All-zero matrix.
+    pub fn new_zero(rows: usize, cols: usize) -> Self {
+        let stride = BitslicedTritVec::word_count(cols);
+        Self {
+            rows,
+            cols,
+            stride,
+            pos: vec![0u64; rows * stride],
+            neg: vec![0u64; rows * stride],
+        }
+    }
+
+    /// Matrix whose rows are `rows`, each `cols` trits long.
+    ///
+    /// # Panics
+    ///
+    /// If a row's length is not `cols`.
+    pub fn from_rows(rows: &[BitslicedTritVec], cols: usize) -> Self {
+        let mut m = Self::new_zero(rows.len(), cols);
+        let mask = last_word_mask(cols);
+        for (r, row) in rows.iter().enumerate() {
+            assert_eq!(row.len(), cols, "row {} has {} trits, expected {}", r, row.len(), cols);
+            let range = m.row_range(r);
+            m.pos[range.clone()].copy_from_slice(row.pos_plane());
+            m.neg[range.clone()].copy_from_slice(row.neg_plane());
+            if m.stride > 0 {
+                m.pos[range.end - 1] &= mask;
+                m.neg[range.end - 1] &= mask;
+            }
+        }
+        m
+    }
+
+    /// Matrix with one row per sparse vector; indices at or past `cols` are
+    /// dropped.
+    pub fn from_sparse_rows(rows: &[SparseVec], cols: usize) -> Self {
+        let rows: Vec<BitslicedTritVec> = rows.iter().map(|v| BitslicedTritVec::from_sparse(v, cols)).collect();
+        Self::from_rows(&rows, cols)
+    }
+
+    /// Seeded random projection: each row has `nnz_per_row` nonzero trits
+    /// (capped at `cols`) of random sign at distinct random columns.
+    pub fn random_projection(rows: usize, cols: usize, nnz_per_row: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut m = Self::new_zero(rows, cols);
+        for r in 0..rows {
+            for c in sample(&mut rng, cols, nnz_per_row.min(cols)) {
+                m.set(r, c, if rng.gen::<bool>() { Trit::P } else { Trit::N });
+            }
+        }
+        m
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Trit {
+        let (w, bit) = self.locate(row, col);
+        match ((self.pos[w] >> bit) & 1, (self.neg[w] >> bit) & 1) {
+            (1, 0) => Trit::P,
+            (0, 1) => Trit::N,
+            _ => Trit::Z,
+        }
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, t: Trit) {
+        let (w, bit) = self.locate(row, col);
+        let flag = 1u64 << bit;
+        self.pos[w] &= !flag;
+        self.neg[w] &= !flag;
+        match t {
+            Trit::P => self.pos[w] |= flag,
+            Trit::N => self.neg[w] |= flag,
+            Trit::Z => {}
+        }
+    }
+
+    /// Copy of row `r`.
+    pub fn row(&self, r: usize) -> BitslicedTritVec {
+        let range = self.row_range(r);
+        BitslicedTritVec::from_raw(self.cols, self.pos[range.clone()].to_vec(), self.neg[range].to_vec())
+    }
+
+    /// Nonzero trits in row `r`.
+    pub fn row_nnz(&self, r: usize) -> usize {
+        let range = self.row_range(r);
+        self.pos[range.clone()]
+            .iter()
+            .zip(&self.neg[range])
+            .map(|(p, n)| (p | n).count_ones() as usize)
+            .sum()
+    }
+
+    /// Dot product of every row with `v`.
+    ///
+    /// # Panics
+    ///
+    /// If `v` is not `cols` trits long.
+    pub fn matvec_dot(&self, v: &BitslicedTritVec) -> Vec<i32> {
+        self.check_width(v);
+        let (bp, bn) = (v.pos_plane(), v.neg_plane());
+        (0..self.rows)
+            .map(|r| {
+                let range = self.row_range(r);
+                dot_words(&self.pos[range.clone()], &self.neg[range], bp, bn)
+            })
+            .collect()
+    }
+
+    /// [`TritMatrix::matvec_dot`] with automatic SIMD dispatch.
+    pub fn matvec_dot_dispatch(&self, v: &BitslicedTritVec) -> Vec<i32> {
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx512f"))]
+        {
+            if crate::bitsliced::has_avx512() && self.stride >= 8 {
+                self.check_width(v);
+                let mut out = vec![0i32; self.rows];
+                // Safety: We verified AVX-512F support via runtime detection
+                unsafe { avx512::dot_rows_avx512(self, v, &mut out) };
+                return out;
+            }
+        }
+        self.matvec_dot(v)
+    }
+
+    /// Every row bound with `v`.
+    ///
+    /// # Panics
+    ///
+    /// If `v` is not `cols` trits long.
+    pub fn matvec_bind(&self, v: &BitslicedTritVec) -> TritMatrix {
+        self.check_width(v);
+        let mut out = Self::new_zero(self.rows, self.cols);
+        let (bp, bn) = (v.pos_plane(), v.neg_plane());
+        for r in 0..self.rows {
+            for (w, i) in self.row_range(r).enumerate() {
+                let (ap, an) = (self.pos[i], self.neg[i]);
+                out.pos[i] = (ap & bp[w]) | (an & bn[w]);
+                out.neg[i] = (ap & bn[w]) | (an & bp[w]);
+            }
+        }
+        out
+    }
+
+    /// [`TritMatrix::matvec_bind`] with automatic SIMD dispatch.
+    pub fn matvec_bind_dispatch(&self, v: &BitslicedTritVec) -> TritMatrix {
+        #[cfg(all(target_arch = "x86_64", target_feature = "avx512f"))]
+        {
+            if crate::bitsliced::has_avx512() && self.stride >= 8 {
+                self.check_width(v);
+                let mut out = Self::new_zero(self.rows, self.cols);
+                // Safety: We verified AVX-512F support via runtime detection
+                unsafe { avx512::bind_rows_avx512(self, v, &mut out) };
+                return out;
+            }
+        }
+        self.matvec_bind(v)
+    }
+
+    /// Sign of each row's dot with `v`: a `rows`-trit vector, zero where
+    /// the dot is zero.
+    pub fn project(&self, v: &BitslicedTritVec) -> BitslicedTritVec {
+        let mut out = BitslicedTritVec::new_zero(self.rows);
+        for (r, dot) in self.matvec_dot_dispatch(v).into_iter().enumerate() {
+            out.set(r, Trit::from_i8_clamped(dot.signum() as i8));
+        }
+        out
+    }
+
+    /// Cosine of every row with `v`; zero for empty rows or an empty `v`.
+    pub fn cosines(&self, v: &BitslicedTritVec) -> Vec<f64> {
+        let v_nnz = v.nnz() as f64;
+        self.matvec_dot_dispatch(v)
+            .into_iter()
+            .enumerate()
+            .map(|(r, dot)| {
+                let row_nnz = self.row_nnz(r) as f64;
+                if row_nnz == 0.0 || v_nnz == 0.0 {
+                    0.0
+                } else {
+                    dot as f64 / (row_nnz.sqrt() * v_nnz.sqrt())
+                }
+            })
+            .collect()
+    }
+
+    /// Row most similar to `v` by cosine, with that cosine; the lowest
+    /// index wins ties. `None` for a matrix without rows.
+    pub fn best_match(&self, v: &BitslicedTritVec) -> Option<(usize, f64)> {
+        self.cosines(v)
+            .into_iter()
+            .enumerate()
+            .fold(None, |best, (r, cos)| match best {
+                Some((_, best_cos)) if best_cos >= cos => best,
+                _ => Some((r, cos)),
+            })
+    }
+
+    fn row_range(&self, r: usize) -> std::ops::Range<usize> {
+        assert!(r < self.rows, "row {} out of range for {} rows", r, self.rows);
+        r * self.stride..(r + 1) * self.stride
+    }
+
+    fn locate(&self, row: usize, col: usize) -> (usize, usize) {
+        assert!(col < self.cols, "column {} out of range for {} columns", col, self.cols);
+        (self.row_range(row).start + col / 64, col % 64)
+    }
+
+    fn check_width(&self, v: &BitslicedTritVec) {
+        assert_eq!(v.len(), self.cols, "vector has {} trits, matrix has {} columns", v.len(), self.cols);
+    }
+}
+
+/// Dot product of a matrix row with a vector's planes. Row bits past the
+/// last column are zero, so the vector's need no masking.
+#[inline]
+fn dot_words(ap: &[u64], an: &[u64], bp: &[u64], bn: &[u64]) -> i32 {
+    let mut acc = 0i32;
+    for w in 0..ap.len() {
+        acc += ((ap[w] & bp[w]).count_ones() + (an[w] & bn[w]).count_ones()) as i32;
+        acc -= ((ap[w] & bn[w]).count_ones() + (an[w] & bp[w]).count_ones()) as i32;
+    }
+    acc
+}
+
+const fn last_word_mask(len: usize) -> u64 {
+    match len % 64 {
+        0 => !0u64,
+        bits => (1u64 << bits) - 1,
+    }
+}
+
+// ============================================================================
+// AVX-512 KERNELS
+// ============================================================================
+
+#[cfg(all(target_arch = "x86_64", target_feature = "avx512f"))]
+pub mod avx512 {
+    //! AVX-512 matrix-vector kernels: 512 trits of a row per iteration.
+
+    use super::{dot_words, TritMatrix};
+    use crate::bitsliced::BitslicedTritVec;
+    use std::arch::x86_64::*;
+
+    /// Dot product of every row of `m` with `v` into `out`.
+    ///
+    /// # Safety
+    /// Requires AVX-512F support, `v.len() == m.cols()` and
+    /// `out.len() == m.rows()`.
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn dot_rows_avx512(m: &TritMatrix, v: &BitslicedTritVec, out: &mut [i32]) {
+        let (bp, bn) = (v.pos_plane(), v.neg_plane());
+        let chunks = m.stride / 8;
+        for (r, slot) in out.iter_mut().enumerate().take(m.rows) {
+            let base = r * m.stride;
+            let mut acc: i32 = 0;
+            for chunk in 0..chunks {
+                let offset = chunk * 8;
+                let ap = _mm512_loadu_si512(m.pos.as_ptr().add(base + offset) as *const __m512i);
+                let an = _mm512_loadu_si512(m.neg.as_ptr().add(base + offset) as *const __m512i);
+                let vp = _mm512_loadu_si512(bp.as_ptr().add(offset) as *const __m512i);
+                let vn = _mm512_loadu_si512(bn.as_ptr().add(offset) as *const __m512i);
+
+                let pp: [u64; 8] = std::mem::transmute(_mm512_and_si512(ap, vp));
+                let nn: [u64; 8] = std::mem::transmute(_mm512_and_si512(an, vn));
+                let pn: [u64; 8] = std::mem::transmute(_mm512_and_si512(ap, vn));
+                let np: [u64; 8] = std::mem::transmute(_mm512_and_si512(an, vp));
+                for i in 0..8 {
+                    acc += (pp[i].count_ones() + nn[i].count_ones()) as i32;
+                    acc -= (pn[i].count_ones() + np[i].count_ones()) as i32;
+                }
+            }
+            let tail = chunks * 8..m.stride;
+            acc += dot_words(
+                &m.pos[base + tail.start..base + tail.end],
+                &m.neg[base + tail.start..base + tail.end],
+                &bp[tail.clone()],
+                &bn[tail],
+            );
+            *slot = acc;
+        }
+    }
+
+    /// Every row of `m` bound with `v`, into `out`.
+    ///
+    /// # Safety
+    /// Requires AVX-512F support, `v.len() == m.cols()` and `out` of the
+    /// same shape as `m`.
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn bind_rows_avx512(m: &TritMatrix, v: &BitslicedTritVec, out: &mut TritMatrix) {
+        let (bp, bn) = (v.pos_plane(), v.neg_plane());
+        let chunks = m.stride / 8;
+        for r in 0..m.rows {
+            let base = r * m.stride;
+            for chunk in 0..chunks {
+                let offset = chunk * 8;
+                let ap = _mm512_loadu_si512(m.pos.as_ptr().add(base + offset) as *const __m512i);
+                let an = _mm512_loadu_si512(m.neg.as_ptr().add(base + offset) as *const __m512i);
+                let vp = _mm512_loadu_si512(bp.as_ptr().add(offset) as *const __m512i);
+                let vn = _mm512_loadu_si512(bn.as_ptr().add(offset) as *const __m512i);
+
+                let pos = _mm512_or_si512(_mm512_and_si512(ap, vp), _mm512_and_si512(an, vn));
+                let neg = _mm512_or_si512(_mm512_and_si512(ap, vn), _mm512_and_si512(an, vp));
+
+                _mm512_storeu_si512(out.pos.as_mut_ptr().add(base + offset) as *mut __m512i, pos);
+                _mm512_storeu_si512(out.neg.as_mut_ptr().add(base + offset) as *mut __m512i, neg);
+            }
+            for w in chunks * 8..m.stride {
+                let (ap, an) = (m.pos[base + w], m.neg[base + w]);
+                out.pos[base + w] = (ap & bp[w]) | (an & bn[w]);
+                out.neg[base + w] = (ap & bn[w]) | (an & bp[w]);
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Random projection of dense embeddings onto sparse ternary vectors.
+//!
+//! [`ProjectionEncoder`] turns a float embedding of any width into a
+//! [`SparseVec`] in `DIM` dimensions. Each output dimension is a signed sum of
+//! `fan_in` input components picked by a seeded hash; the `nnz` outputs with
+//! the largest magnitude are kept as `+1`/`-1` by sign and the rest are zero.
+//! Sign random projections preserve angles, so cosine between projected
+//! vectors tracks cosine between the embeddings and the result can be
+//! searched with the usual codebook machinery.
+//!
+//! Taps are derived from the seed on the fly rather than stored, so an
+//! encoder is fully described by its [`ProjectionConfig`] and the same config
+//! must be used for ingest and for queries.
+
+use crate::signature::splitmix64;
+use crate::vsa::{SparseVec, DIM};
+use serde::{Deserialize, Serialize};
+
+/// Parameters of a [`ProjectionEncoder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectionConfig {
+    /// Non-zero entries in each output vector.
+    pub nnz: usize,
+    /// Input components summed into each output dimension.
+    pub fan_in: usize,
+    pub seed: u64,
+}
+
+impl Default for ProjectionConfig {
+    fn default() -> Self {
+        Self {
+            nnz: 200,
+            fan_in: 16,
+            seed: 0xED00_0000_0000_0002,
+        }
+    }
+}
+
+/// Deterministic dense-to-ternary projection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProjectionEncoder {
+    config: ProjectionConfig,
+}
+
+impl ProjectionEncoder {
+    pub fn new(config: ProjectionConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> ProjectionConfig {
+        self.config
+    }
+
+    /// Project `embedding` to a sparse ternary vector.
+    ///
+    /// An empty or all-zero embedding yields an empty vector.
+    pub fn encode(&self, embedding: &[f32]) -> SparseVec {
+        let width = embedding.len() as u64;
+        if width == 0 {
+            return SparseVec::new();
+        }
+        let fan_in = self.config.fan_in.max(1);
+        let mut projected: Vec<(usize, f32)> = (0..DIM)
+            .map(|d| {
+                let mut state = splitmix64(self.config.seed ^ (d as u64));
+                let mut sum = 0.0f32;
+                for _ in 0..fan_in {
+                    state = splitmix64(state);
+                    let x = embedding[(state % width) as usize];
+                    sum += if state >> 63 == 1 { -x } else { x };
+                }
+                (d, sum)
+            })
+            .filter(|&(_, y)| y != 0.0 && y.is_finite())
+            .collect();
+
+        let keep = self.config.nnz.min(projected.len());
+        if keep < projected.len() {
+            projected.select_nth_unstable_by(keep, |a, b| b.1.abs().total_cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+            projected.truncate(keep);
+        }
+        projected.sort_unstable_by_key(|&(d, _)| d);
+
+        let mut out = SparseVec::new();
+        for (d, y) in projected {
+            if y > 0.0 {
+                out.pos.push(d);
+            } else {
+                out.neg.push(d);
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,199 @@
+//! Key-value records held in one hypervector.
+//!
+//! A [`Record`] binds each field's role vector with the field's value and
+//! bundles the pairs: `Σ role(field) ⊗ value`. Roles are seeded bipolar
+//! vectors (every trit ±1), so binding the record with a role again leaves
+//! that field's value plus noise from the other fields, and
+//! [`Record::get`] cleans it up against an [`ItemMemory`] of the values
+//! that may appear.
+//!
+//! ```rust,ignore
+//! use embeddenator::vsa::record::{ItemMemory, Record};
+//!
+//! let mut colours = ItemMemory::new(7);
+//! let red = colours.symbol("red");
+//! let round = colours.symbol("round");
+//! let record = Record::encode(7, [("colour", &red), ("shape", &round)]);
+//! assert_eq!(record.get("colour", &colours).unwrap().0, "red");
+//! ```
+
+use crate::vsa::{SparseVec, DIM};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Named vectors that noisy query results are cleaned up against.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemMemory {
+    seed: u64,
+    names: Vec<String>,
+    vectors: Vec<SparseVec>,
+}
+
+impl ItemMemory {
+    /// Empty memory whose [`ItemMemory::symbol`]s derive from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// The seeded bipolar symbol for `name`, added to the memory if it is
+    /// not there yet. Memories with the same seed agree on every symbol.
+    pub fn symbol(&mut self, name: &str) -> SparseVec {
+        if let Some(vec) = self.get(name) {
+            return vec.clone();
+        }
+        let vec = bipolar(self.seed, "symbol", name);
+        self.insert(name, vec.clone());
+        vec
+    }
+
+    /// Add or replace the vector for `name`.
+    pub fn insert(&mut self, name: impl Into<String>, vec: SparseVec) {
+        let name = name.into();
+        match self.names.iter().position(|n| *n == name) {
+            Some(i) => self.vectors[i] = vec,
+            None => {
+                self.names.push(name);
+                self.vectors.push(vec);
+            }
+        }
+    }
+
+    /// The seed [`ItemMemory::symbol`]s derive from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SparseVec> {
+        self.names.iter().position(|n| n == name).map(|i| &self.vectors[i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// The item most similar to `probe` by cosine, with its cosine; the
+    /// earliest inserted wins ties. `None` for an empty memory.
+    pub fn cleanup(&self, probe: &SparseVec) -> Option<(String, f64)> {
+        self.scores(probe)
+            .fold(None, |best: Option<(usize, f64)>, (i, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((i, score)),
+            })
+            .map(|(i, score)| (self.names[i].clone(), score))
+    }
+
+    /// Items scoring above `threshold` against `probe`, best first.
+    pub fn matches(&self, probe: &SparseVec, threshold: f64) -> Vec<(String, f64)> {
+        let mut hits: Vec<(String, f64)> = self
+            .scores(probe)
+            .filter(|(_, score)| *score > threshold)
+            .map(|(i, score)| (self.names[i].clone(), score))
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hits
+    }
+
+    fn scores<'a>(&'a self, probe: &'a SparseVec) -> impl Iterator<Item = (usize, f64)> + 'a {
+        self.vectors.iter().enumerate().map(move |(i, v)| (i, probe.cosine(v)))
+    }
+}
+
+/// Fields bound to their values and bundled into one vector.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub vector: SparseVec,
+    fields: Vec<String>,
+    seed: u64,
+}
+
+impl Record {
+    /// Record of `fields`, with role vectors derived from `seed`. A field
+    /// given twice holds the bundle of both values.
+    pub fn encode<'a, I>(seed: u64, fields: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a SparseVec)>,
+    {
+        let mut names = Vec::new();
+        let mut bound = Vec::new();
+        for (field, value) in fields {
+            bound.push(bipolar(seed, "role", field).bind(value));
+            if !names.iter().any(|n| n == field) {
+                names.push(field.to_string());
+            }
+        }
+        Self {
+            vector: SparseVec::bundle_sum_many(&bound),
+            fields: names,
+            seed,
+        }
+    }
+
+    /// Field names, in the order first given.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// The role vector `field` is bound with.
+    pub fn role(&self, field: &str) -> SparseVec {
+        bipolar(self.seed, "role", field)
+    }
+
+    /// The value of `field` plus noise from the other fields.
+    pub fn unbind(&self, field: &str) -> SparseVec {
+        self.vector.bind(&self.role(field))
+    }
+
+    /// The item of `memory` stored in `field`, with its cosine to the
+    /// unbound value. `None` if the field is absent or nothing in `memory`
+    /// scores above [`bundle_threshold`] for this many fields.
+    pub fn get(&self, field: &str, memory: &ItemMemory) -> Option<(String, f64)> {
+        if !self.fields.iter().any(|f| f == field) {
+            return None;
+        }
+        memory
+            .cleanup(&self.unbind(field))
+            .filter(|(_, score)| *score > bundle_threshold(self.fields.len()))
+    }
+}
+
+/// Cosine above which a vector counts as one of `members` vectors bundled
+/// with [`SparseVec::bundle_sum_many`]: half the `√(2 / πm)` a bipolar
+/// member is expected to score. Sparser members score higher.
+pub fn bundle_threshold(members: usize) -> f64 {
+    match members {
+        0 => f64::INFINITY,
+        1 => 0.5,
+        m => 0.5 * (2.0 / (std::f64::consts::PI * m as f64)).sqrt(),
+    }
+}
+
+/// Seeded bipolar vector for `name` in `domain`: every trit ±1.
+pub(crate) fn bipolar(seed: u64, domain: &str, name: &str) -> SparseVec {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&seed.to_le_bytes());
+    hasher.update(domain.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(name.as_bytes());
+    let mut rng = StdRng::from_seed(*hasher.finalize().as_bytes());
+    let (mut pos, mut neg) = (Vec::with_capacity(DIM / 2), Vec::with_capacity(DIM / 2));
+    for i in 0..DIM {
+        if rng.gen::<bool>() {
+            pos.push(i);
+        } else {
+            neg.push(i);
+        }
+    }
+    SparseVec { pos, neg }
+}
@@ -0,0 +1,262 @@
+//! Start-up self-test of the SIMD kernels.
+//!
+//! The AVX-512 and AVX2 kernels are selected from CPU feature flags alone, so
+//! a miscompiled intrinsic or a CPU that reports a feature it implements
+//! wrongly would silently corrupt results. The first time a dispatcher asks
+//! whether a path may be used ([`has_avx512`], [`has_avx2`]), [`self_test`]
+//! runs every kernel of that path on seeded random inputs and compares the
+//! output with the scalar code. A path that disagrees anywhere is disabled
+//! for the rest of the process: the dispatchers fall back to scalar, a
+//! warning is logged, and the `simd_paths_disabled` metric is incremented.
+//!
+//! [`has_avx512`]: crate::bitsliced::has_avx512
+//! [`has_avx2`]: crate::bitsliced::has_avx2
+
+// Only x86_64 has kernels to check.
+#![cfg_attr(not(target_arch = "x86_64"), allow(dead_code, unused_imports))]
+
+use crate::block_sparse::Block;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Random cases run per kernel.
+const CASES: usize = 32;
+
+/// Blocks with their ids, as the block-sparse kernels take them.
+type Blocks = Vec<(u32, Block)>;
+
+/// A family of SIMD kernels that is enabled or disabled as a whole.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SimdPath {
+    Avx512,
+    Avx2,
+}
+
+impl fmt::Display for SimdPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SimdPath::Avx512 => "AVX-512",
+            SimdPath::Avx2 => "AVX2",
+        })
+    }
+}
+
+/// Outcome of [`self_test`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Paths the CPU supports whose kernels all matched the scalar code.
+    pub passed: Vec<SimdPath>,
+    /// Paths turned off, each with the first kernel that disagreed.
+    pub disabled: Vec<(SimdPath, &'static str)>,
+}
+
+static REPORT: OnceLock<SelfTestReport> = OnceLock::new();
+
+/// Cross-check the SIMD kernels against scalar code. Runs once per process;
+/// later calls return the same report.
+pub fn self_test() -> &'static SelfTestReport {
+    REPORT.get_or_init(|| {
+        let report = run();
+        for (path, kernel) in &report.disabled {
+            crate::metrics::metrics().inc_simd_path_disabled();
+            crate::logging::warn(&format!(
+                "{} kernel {} disagrees with scalar code; using scalar paths",
+                path, kernel
+            ));
+        }
+        report
+    })
+}
+
+/// Whether [`self_test`] left `path` enabled. Does not check CPU support.
+pub fn is_enabled(path: SimdPath) -> bool {
+    !self_test().disabled.iter().any(|(p, _)| *p == path)
+}
+
+fn run() -> SelfTestReport {
+    #[cfg_attr(not(target_arch = "x86_64"), allow(unused_mut))]
+    let mut report = SelfTestReport::default();
+    #[cfg(target_arch = "x86_64")]
+    {
+        let mut rng = StdRng::seed_from_u64(0x51_4d_44);
+        let mut record = |path, failed: Option<&'static str>| match failed {
+            Some(kernel) => report.disabled.push((path, kernel)),
+            None => report.passed.push(path),
+        };
+        if std::arch::is_x86_feature_detected!("avx512f") {
+            record(SimdPath::Avx512, x86::check_avx512(&mut rng));
+        }
+        if std::arch::is_x86_feature_detected!("avx2") {
+            record(SimdPath::Avx2, x86::check_avx2(&mut rng));
+        }
+    }
+    report
+}
+
+fn random_block(rng: &mut StdRng) -> Block {
+    let (a, b): (u64, u64) = (rng.gen(), rng.gen());
+    Block { pos: a & !b, neg: b & !a }
+}
+
+/// Aligned block arrays of a random length, so kernels that work in groups
+/// also see their remainder loop.
+fn random_blocks(rng: &mut StdRng) -> (Blocks, Blocks) {
+    let len = rng.gen_range(0..40);
+    (0..len as u32)
+        .map(|id| ((id, random_block(rng)), (id, random_block(rng))))
+        .unzip()
+}
+
+/// Whether a block-array kernel filtering zero blocks matches `scalar`.
+fn blockwise_agrees<K>(rng: &mut StdRng, kernel: K, scalar: fn(&Block, &Block) -> Block) -> bool
+where
+    K: Fn(&[(u32, Block)], &[(u32, Block)], &mut Blocks),
+{
+    (0..CASES).all(|_| {
+        let (a, b) = random_blocks(rng);
+        let expected: Vec<_> = a
+            .iter()
+            .zip(&b)
+            .map(|((id, x), (_, y))| (*id, scalar(x, y)))
+            .filter(|(_, block)| !block.is_zero())
+            .collect();
+        let mut out = Vec::new();
+        kernel(&a, &b, &mut out);
+        out == expected
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::*;
+    use crate::block_sparse::avx512 as blocks;
+
+    /// Name of the first AVX-512 kernel that disagrees, if any.
+    pub(super) fn check_avx512(rng: &mut StdRng) -> Option<&'static str> {
+        // SAFETY (all calls below): the caller checked for AVX-512F.
+        if !blockwise_agrees(rng, |a, b, out| unsafe { blocks::bind_blocks_avx512(a, b, out) }, Block::bind) {
+            return Some("block bind");
+        }
+        if !blockwise_agrees(rng, |a, b, out| unsafe { blocks::bundle_blocks_avx512(a, b, out) }, Block::bundle) {
+            return Some("block bundle");
+        }
+        let dot_ok = (0..CASES).all(|_| {
+            let (a, b) = random_blocks(rng);
+            let expected: i64 = a.iter().zip(&b).map(|((_, x), (_, y))| i64::from(x.dot(y))).sum();
+            let dot = unsafe { blocks::dot_blocks_avx512(&a, &b) };
+            dot == expected
+        });
+        if !dot_ok {
+            return Some("block dot");
+        }
+        #[cfg(target_feature = "avx512f")]
+        {
+            use crate::bitsliced::{avx512 as sliced, BitslicedTritVec};
+            let random_vec = |rng: &mut StdRng, len: usize| {
+                let words = len.div_ceil(64);
+                let (mut pos, mut neg): (Vec<u64>, Vec<u64>) = (0..words)
+                    .map(|_| {
+                        let block = random_block(rng);
+                        (block.pos, block.neg)
+                    })
+                    .unzip();
+                if len % 64 != 0 {
+                    let mask = (1u64 << (len % 64)) - 1;
+                    pos[words - 1] &= mask;
+                    neg[words - 1] &= mask;
+                }
+                BitslicedTritVec::from_raw(len, pos, neg)
+            };
+            for _ in 0..CASES {
+                let len = rng.gen_range(512..4096);
+                let (a, b) = (random_vec(rng, len), random_vec(rng, len));
+                let mut out = BitslicedTritVec::new_zero(len);
+                unsafe { sliced::bind_avx512(&a, &b, &mut out) };
+                if out != a.bind(&b) {
+                    return Some("bitsliced bind");
+                }
+                unsafe { sliced::bundle_avx512(&a, &b, &mut out) };
+                if out != a.bundle(&b) {
+                    return Some("bitsliced bundle");
+                }
+                let dot = unsafe { sliced::dot_avx512(&a, &b) };
+                if dot != a.dot(&b) {
+                    return Some("bitsliced dot");
+                }
+            }
+            use crate::vsa::matrix::{avx512 as matrix, TritMatrix};
+            for _ in 0..CASES {
+                let cols = rng.gen_range(512..2048);
+                let rows: Vec<BitslicedTritVec> = (0..rng.gen_range(1..8)).map(|_| random_vec(rng, cols)).collect();
+                let (m, v) = (TritMatrix::from_rows(&rows, cols), random_vec(rng, cols));
+                let mut dots = vec![0i32; m.rows()];
+                unsafe { matrix::dot_rows_avx512(&m, &v, &mut dots) };
+                if dots != m.matvec_dot(&v) {
+                    return Some("matrix dot");
+                }
+                let mut bound = TritMatrix::new_zero(m.rows(), cols);
+                unsafe { matrix::bind_rows_avx512(&m, &v, &mut bound) };
+                if bound != m.matvec_bind(&v) {
+                    return Some("matrix bind");
+                }
+            }
+        }
+        None
+    }
+
+    /// Name of the first AVX2 kernel that disagrees, if any.
+    pub(super) fn check_avx2(rng: &mut StdRng) -> Option<&'static str> {
+        #[cfg(target_feature = "avx2")]
+        {
+            use crate::simd_cosine::{cosine_avx2, cosine_scalar};
+            use crate::vsa::{SparseVec, DIM};
+            let random_vec = |rng: &mut StdRng| {
+                let pos = (0..rng.gen_range(0..300)).map(|_| rng.gen_range(0..DIM)).collect();
+                let neg = (0..rng.gen_range(0..300)).map(|_| rng.gen_range(0..DIM)).collect();
+                SparseVec::from_unsorted(pos, neg, DIM).expect("indices are drawn below DIM")
+            };
+            for _ in 0..CASES {
+                let (a, b) = (random_vec(rng), random_vec(rng));
+                if cosine_avx2(&a, &b).to_bits() != cosine_scalar(&a, &b).to_bits() {
+                    return Some("sparse cosine");
+                }
+            }
+        }
+        let _ = rng;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernels_agree_on_this_cpu() {
+        let report = self_test();
+        assert!(report.disabled.is_empty(), "{:?}", report.disabled);
+        assert!(is_enabled(SimdPath::Avx512) && is_enabled(SimdPath::Avx2));
+    }
+
+    #[test]
+    fn a_wrong_kernel_is_caught() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let scalar = |a: &[(u32, Block)], b: &[(u32, Block)], out: &mut Vec<(u32, Block)>| {
+            out.extend(a.iter().zip(b).map(|((id, x), (_, y))| (*id, x.bind(y))).filter(|(_, z)| !z.is_zero()));
+        };
+        assert!(blockwise_agrees(&mut rng, scalar, Block::bind));
+        // Drops the sign of every eighth block's lowest trit.
+        let off_by_one = |a: &[(u32, Block)], b: &[(u32, Block)], out: &mut Vec<(u32, Block)>| {
+            scalar(a, b, out);
+            for (id, block) in out.iter_mut() {
+                if *id % 8 == 7 {
+                    block.pos &= !1;
+                    block.neg &= !1;
+                }
+            }
+        };
+        assert!(!blockwise_agrees(&mut rng, off_by_one, Block::bind));
+    }
+}
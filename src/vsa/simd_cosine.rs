@@ -17,11 +17,14 @@ use crate::vsa::SparseVec;
 /// available SIMD implementation for the current platform.
 ///
 /// This function automatically dispatches to AVX2, NEON, or scalar implementation
-/// based on compile-time feature detection.
+/// based on compile-time feature detection. The AVX2 path is skipped if the
+/// [`crate::simd`] self-test disabled it.
 pub fn cosine_simd(a: &SparseVec, b: &SparseVec) -> f64 {
     #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
     {
-        return cosine_avx2(a, b);
+        if crate::bitsliced::has_avx2() {
+            return cosine_avx2(a, b);
+        }
     }
 
     #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
@@ -151,7 +154,7 @@ unsafe fn intersection_count_simd_avx2(a: &[usize], b: &[usize]) -> usize {
 
 #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
 #[inline]
-fn cosine_avx2(a: &SparseVec, b: &SparseVec) -> f64 {
+pub(crate) fn cosine_avx2(a: &SparseVec, b: &SparseVec) -> f64 {
     // Safety: We check for AVX2 support at compile time via target_feature
     unsafe { cosine_avx2_impl(a, b) }
 }
@@ -258,6 +258,32 @@ impl SoftTernaryVec {
         (new_m0, new_m1, new_m2)
     }
 
+    /// Forget one vote everywhere: every non-zero magnitude drops by 1, and
+    /// positions reaching zero lose their sign.
+    pub fn decay(&mut self) {
+        for w in 0..Self::word_count(self.len) {
+            let (m0, m1, m2) = (self.mag_lo[w], self.mag_mi[w], self.mag_hi[w]);
+            let (n0, n1, n2) = Self::saturating_decrement_3bit(m0, m1, m2, u64::MAX);
+            self.mag_lo[w] = n0;
+            self.mag_mi[w] = n1;
+            self.mag_hi[w] = n2;
+            self.sign[w] &= n0 | n1 | n2;
+        }
+    }
+
+    /// Sum of squared magnitudes, the squared Euclidean norm.
+    pub fn norm_squared(&self) -> u64 {
+        // m = m0 + 2·m1 + 4·m2 with bits bᵢ = bᵢ², so
+        // m² = m0 + 4·m1 + 16·m2 + 4·m0·m1 + 8·m0·m2 + 16·m1·m2.
+        let mut sum = 0u64;
+        for w in 0..Self::word_count(self.len) {
+            let (m0, m1, m2) = (self.mag_lo[w], self.mag_mi[w], self.mag_hi[w]);
+            let ones = |x: u64| x.count_ones() as u64;
+            sum += ones(m0) + 4 * ones(m1) + 16 * ones(m2) + 4 * ones(m0 & m1) + 8 * ones(m0 & m2) + 16 * ones(m1 & m2);
+        }
+        sum
+    }
+
     /// Soft bundle: add two soft vectors with magnitude accumulation.
     ///
     /// For each position:
@@ -712,4 +738,25 @@ mod tests {
 
         assert_eq!(soft.nnz(), 3);
     }
+
+    #[test]
+    fn test_decay_and_norm() {
+        let mut soft = SoftTernaryVec::new_zero(100);
+        soft.set(3, 7, false);
+        soft.set(50, 2, true);
+        soft.set(99, 1, true);
+        assert_eq!(soft.norm_squared(), 49 + 4 + 1);
+
+        soft.decay();
+        assert_eq!(soft.get_signed(3), 6);
+        assert_eq!(soft.get_signed(50), -1);
+        assert_eq!(soft.get(99), (0, false));
+        assert_eq!(soft.norm_squared(), 36 + 1);
+
+        // A cleared position takes the sign of its next vote.
+        let mut hard = BitslicedTritVec::new_zero(100);
+        hard.set(99, Trit::P);
+        soft.accumulate(&hard);
+        assert_eq!(soft.get_signed(99), 1);
+    }
 }
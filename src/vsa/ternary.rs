@@ -36,8 +36,9 @@
 //! 3. Semantic markers for high-entropy regions
 //! 4. Parity trits for error detection
 
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use core::fmt;
 
 /// Single balanced ternary digit: the atomic unit
 /// 
@@ -230,7 +231,7 @@ impl Trit {
     }
 }
 
-impl std::ops::Neg for Trit {
+impl core::ops::Neg for Trit {
     type Output = Trit;
     #[inline]
     fn neg(self) -> Trit {
@@ -238,7 +239,7 @@ impl std::ops::Neg for Trit {
     }
 }
 
-impl std::ops::Mul for Trit {
+impl core::ops::Mul for Trit {
     type Output = Trit;
     #[inline]
     fn mul(self, rhs: Trit) -> Trit {
@@ -246,7 +247,7 @@ impl std::ops::Mul for Trit {
     }
 }
 
-impl std::ops::MulAssign for Trit {
+impl core::ops::MulAssign for Trit {
     #[inline]
     fn mul_assign(&mut self, rhs: Trit) {
         *self = *self * rhs;
@@ -444,14 +445,14 @@ impl Tryte3 {
     }
 }
 
-impl std::ops::Neg for Tryte3 {
+impl core::ops::Neg for Tryte3 {
     type Output = Tryte3;
     fn neg(self) -> Tryte3 {
         Tryte3::neg(self)
     }
 }
 
-impl std::ops::Mul for Tryte3 {
+impl core::ops::Mul for Tryte3 {
     type Output = Tryte3;
     fn mul(self, rhs: Tryte3) -> Tryte3 {
         Tryte3::mul(self, rhs)
@@ -522,6 +523,20 @@ impl Word6 {
         self.low.to_i8() as i16 + 27 * self.high.to_i8() as i16
     }
 
+    /// Negate all trits
+    #[inline]
+    pub const fn neg(self) -> Word6 {
+        Word6 { low: self.low.neg(), high: self.high.neg() }
+    }
+
+    /// Arithmetic addition with carry out: the result plus 729 × carry is
+    /// the exact sum
+    pub const fn add_with_carry(self, other: Word6, carry_in: Trit) -> (Word6, Trit) {
+        let (low, c0) = self.low.add_with_carry(other.low, carry_in);
+        let (high, c1) = self.high.add_with_carry(other.high, c0);
+        (Word6 { low, high }, c1)
+    }
+
     /// Trit-wise multiplication (bind)
     pub fn mul(self, other: Word6) -> Word6 {
         Word6 {
@@ -554,6 +569,13 @@ impl Word6 {
     }
 }
 
+impl core::ops::Neg for Word6 {
+    type Output = Word6;
+    fn neg(self) -> Word6 {
+        Word6::neg(self)
+    }
+}
+
 /// Balanced modulo: result in range [-(n-1)/2, (n-1)/2]
 const fn balanced_mod(value: i16, n: i16) -> i8 {
     let r = value % n;
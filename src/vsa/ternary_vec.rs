@@ -12,6 +12,9 @@
 
 use crate::ternary::Trit;
 use crate::vsa::SparseVec;
+use alloc::{vec, vec::Vec};
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PackedTritVec {
@@ -9,6 +9,9 @@ use rand::seq::SliceRandom;
 use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use alloc::{vec, vec::Vec};
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
 #[cfg(feature = "bt-phase-2")]
 use crate::ternary_vec::PackedTritVec;
@@ -16,6 +19,26 @@ use crate::ternary_vec::PackedTritVec;
 #[cfg(feature = "bt-phase-2")]
 use std::cell::RefCell;
 
+/// Property tests of the algebraic laws every representation obeys.
+#[cfg(feature = "laws")]
+pub mod laws;
+
+/// Ternary matrices with bitsliced rows and matrix-vector products.
+#[cfg(feature = "std")]
+pub mod matrix;
+
+/// Encoding labeled graphs into hypervectors, and queries against them.
+#[cfg(feature = "std")]
+pub mod graph;
+
+/// Key-value records bundled into one hypervector, and item memories.
+#[cfg(feature = "std")]
+pub mod record;
+
+/// Algebra expressions over named vectors, such as those of an engram.
+#[cfg(feature = "std")]
+pub mod compute;
+
 /// Dimension of VSA vectors
 pub const DIM: usize = 10000;
 
@@ -29,7 +52,7 @@ thread_local! {
 }
 
 /// Configuration for reversible VSA encoding/decoding operations
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ReversibleVSAConfig {
     /// Block size for chunked encoding (must be power of 2 for efficiency)
     pub block_size: usize,
@@ -39,6 +62,12 @@ pub struct ReversibleVSAConfig {
     pub base_shift: usize,
     /// Target sparsity level for operations (number of non-zero elements)
     pub target_sparsity: usize,
+    /// How chunk vectors are bundled into the engram root
+    #[serde(default)]
+    pub root_strategy: RootStrategy,
+    /// How chunk vectors are derived from chunk bytes
+    #[serde(default)]
+    pub chunk_encoding: ChunkEncoding,
 }
 
 impl Default for ReversibleVSAConfig {
@@ -48,6 +77,140 @@ impl Default for ReversibleVSAConfig {
             max_path_depth: 10,
             base_shift: 1000,
             target_sparsity: 200,  // Default sparsity level
+            root_strategy: RootStrategy::Pairwise,
+            chunk_encoding: ChunkEncoding::Reversible,
+        }
+    }
+}
+
+/// How the engram root is built from chunk vectors.
+///
+/// Chunks are decoded from the codebook, not the root, so the strategy only
+/// changes what the root is good for: the pairwise default saturates after
+/// a few dozen chunks, while the others keep it informative for large
+/// ingests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RootStrategy {
+    /// Fold each chunk into the root with [`SparseVec::bundle`].
+    #[default]
+    Pairwise,
+    /// Exact majority vote over every chunk, kept as bit-sliced vote counts
+    /// (a counted root).
+    CarrySave,
+    /// Majority vote over groups of `fan_in` chunks, then over groups of
+    /// those sub-roots, and so on up to the root; see [`TreeBundle`].
+    Tree { fan_in: usize },
+    /// Pairwise, thinned back to `max_nonzero` trits whenever it grows past.
+    Thinned { max_nonzero: usize },
+}
+
+/// How ingest turns a chunk's bytes into its codebook vector.
+///
+/// Reversible vectors decode back to most of their bytes, but two chunks
+/// differing by one inserted byte share almost nothing, so the root and
+/// codebook queries only recognise exact content. Similarity signatures
+/// keep near-duplicate chunks close, at the cost of storing every chunk's
+/// bytes as a correction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkEncoding {
+    /// [`SparseVec::encode_data`].
+    #[default]
+    Reversible,
+    /// [`SparseVec::from_data_similarity`] over `shingle`-byte shingles,
+    /// keeping `nonzero` trits.
+    Similarity { shingle: usize, nonzero: usize },
+}
+
+impl ChunkEncoding {
+    /// Similarity signatures of 4-byte shingles with 200 nonzero trits.
+    pub const fn similarity() -> Self {
+        ChunkEncoding::Similarity { shingle: 4, nonzero: 200 }
+    }
+}
+
+/// Trits each shingle votes on in [`SparseVec::from_data_similarity`].
+const SIMILARITY_TRITS_PER_SHINGLE: usize = 16;
+
+/// Incremental root of a [`RootStrategy::Tree`].
+///
+/// Vectors are pushed onto level 0; whenever a level holds `fan_in` nodes
+/// they are bundled with [`SparseVec::bundle_sum_many`] into one node a
+/// level up, like the carries of a base-`fan_in` counter. The root is the
+/// majority vote of the nodes pending on every level, kept as running
+/// counts so a push costs `fan_in` bundles at most per level.
+#[derive(Clone, Debug)]
+pub struct TreeBundle {
+    fan_in: usize,
+    levels: Vec<Vec<SparseVec>>,
+    counts: Vec<i32>,
+    len: usize,
+}
+
+impl TreeBundle {
+    /// An empty tree; `fan_in` below 2 is taken as 2.
+    pub fn new(fan_in: usize) -> Self {
+        TreeBundle {
+            fan_in: fan_in.max(2),
+            levels: Vec::new(),
+            counts: vec![0; DIM],
+            len: 0,
+        }
+    }
+
+    /// Number of vectors pushed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a leaf vector.
+    pub fn push(&mut self, vec: &SparseVec) {
+        self.len += 1;
+        self.vote(vec, 1);
+        let mut node = vec.clone();
+        let mut level = 0;
+        loop {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level].push(node);
+            if self.levels[level].len() < self.fan_in {
+                return;
+            }
+            let full = core::mem::take(&mut self.levels[level]);
+            for child in &full {
+                self.vote(child, -1);
+            }
+            node = SparseVec::bundle_sum_many(&full);
+            self.vote(&node, 1);
+            level += 1;
+        }
+    }
+
+    /// Majority vote of the pending nodes.
+    pub fn root(&self) -> SparseVec {
+        let mut root = SparseVec::new();
+        for (idx, &count) in self.counts.iter().enumerate() {
+            match count.signum() {
+                1 => root.pos.push(idx),
+                -1 => root.neg.push(idx),
+                _ => {}
+            }
+        }
+        root
+    }
+
+    fn vote(&mut self, vec: &SparseVec, weight: i32) {
+        for &idx in &vec.pos {
+            self.counts[idx] += weight;
+        }
+        for &idx in &vec.neg {
+            self.counts[idx] -= weight;
         }
     }
 }
@@ -60,6 +223,8 @@ impl ReversibleVSAConfig {
             max_path_depth: 5,
             base_shift: 500,
             target_sparsity: 100,
+            root_strategy: RootStrategy::Pairwise,
+            chunk_encoding: ChunkEncoding::Reversible,
         }
     }
 
@@ -70,12 +235,34 @@ impl ReversibleVSAConfig {
             max_path_depth: 20,
             base_shift: 2000,
             target_sparsity: 400,
+            root_strategy: RootStrategy::Pairwise,
+            chunk_encoding: ChunkEncoding::Reversible,
         }
     }
+
+    /// The permutation shift [`SparseVec::encode_data`] applies to chunks
+    /// of the file at `path`.
+    pub fn path_shift(&self, path: &str) -> usize {
+        let mut hasher = Sha256::new();
+        hasher.update(path.as_bytes());
+        let hash = hasher.finalize();
+        let path_hash = u32::from_le_bytes(hash[0..4].try_into().unwrap()) as usize;
+        (path_hash % self.max_path_depth) * self.base_shift
+    }
 }
 
 /// Sparse ternary vector with positive and negative indices
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// Both index lists must be strictly ascending; every operation relies on
+/// it, and deserialization rejects vectors that are not. A plain ternary
+/// vector also lists no index with both signs, but chunk vectors from
+/// [`SparseVec::encode_data`] may: two bytes of a block can land on one
+/// index with opposite polarity, and decoding reads both back.
+/// Build vectors from index lists with [`SparseVec::from_indices`], which
+/// checks them, or [`SparseVec::from_unsorted`], which brings them into
+/// ternary form first, rather than by assembling the fields directly.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawSparseVec")]
 pub struct SparseVec {
     /// Indices with +1 value
     pub pos: Vec<usize>,
@@ -83,6 +270,55 @@ pub struct SparseVec {
     pub neg: Vec<usize>,
 }
 
+/// Unchecked serialized form of [`SparseVec`].
+#[derive(Deserialize)]
+struct RawSparseVec {
+    pos: Vec<usize>,
+    neg: Vec<usize>,
+}
+
+impl TryFrom<RawSparseVec> for SparseVec {
+    type Error = SparseVecError;
+
+    fn try_from(raw: RawSparseVec) -> Result<Self, SparseVecError> {
+        let v = SparseVec { pos: raw.pos, neg: raw.neg };
+        v.check_sorted()?;
+        Ok(v)
+    }
+}
+
+/// A [`SparseVec`] invariant that [`SparseVec::validate`] found broken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SparseVecError {
+    /// An index is not below the vector's dimension.
+    OutOfRange { index: usize, dim: usize },
+    /// Indices of one sign are not strictly ascending at `position`.
+    Unsorted { negative: bool, position: usize },
+    /// An index is listed as both +1 and -1.
+    Overlap { index: usize },
+}
+
+impl core::fmt::Display for SparseVecError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SparseVecError::OutOfRange { index, dim } => {
+                write!(f, "index {} out of range for dimension {}", index, dim)
+            }
+            SparseVecError::Unsorted { negative, position } => write!(
+                f,
+                "{} indices not strictly ascending at position {}",
+                if *negative { "negative" } else { "positive" },
+                position
+            ),
+            SparseVecError::Overlap { index } => {
+                write!(f, "index {} is both positive and negative", index)
+            }
+        }
+    }
+}
+
+impl core::error::Error for SparseVecError {}
+
 impl Default for SparseVec {
     fn default() -> Self {
         Self::new()
@@ -95,6 +331,74 @@ impl SparseVec {
         self.pos.len() + self.neg.len()
     }
 
+    /// Check that both index lists are strictly ascending and below `dim`,
+    /// as every operation here assumes. Vectors decoded from untrusted bytes
+    /// should pass this before use.
+    pub fn validate(&self, dim: usize) -> Result<(), SparseVecError> {
+        self.check_sorted()?;
+        match self.pos.last().into_iter().chain(self.neg.last()).max() {
+            Some(&index) if index >= dim => Err(SparseVecError::OutOfRange { index, dim }),
+            _ => Ok(()),
+        }
+    }
+
+    /// [`SparseVec::validate`], and additionally that no index is both +1
+    /// and -1: the form [`SparseVec::canonicalize`] produces.
+    pub fn validate_ternary(&self, dim: usize) -> Result<(), SparseVecError> {
+        self.validate(dim)?;
+        let (mut i, mut j) = (0, 0);
+        while i < self.pos.len() && j < self.neg.len() {
+            match self.pos[i].cmp(&self.neg[j]) {
+                core::cmp::Ordering::Less => i += 1,
+                core::cmp::Ordering::Greater => j += 1,
+                core::cmp::Ordering::Equal => return Err(SparseVecError::Overlap { index: self.pos[i] }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Build a ternary vector from index lists, failing unless they already
+    /// satisfy [`SparseVec::validate_ternary`] for `dim`.
+    pub fn from_indices(pos: Vec<usize>, neg: Vec<usize>, dim: usize) -> Result<Self, SparseVecError> {
+        let v = SparseVec { pos, neg };
+        v.validate_ternary(dim)?;
+        Ok(v)
+    }
+
+    /// Build a ternary vector from index lists in any order, with repeats:
+    /// they are [canonicalized](SparseVec::canonicalize), then checked
+    /// against `dim`.
+    pub fn from_unsorted(pos: Vec<usize>, neg: Vec<usize>, dim: usize) -> Result<Self, SparseVecError> {
+        let mut v = SparseVec { pos, neg };
+        v.canonicalize();
+        v.validate(dim)?;
+        Ok(v)
+    }
+
+    /// Sort and deduplicate both index lists. An index listed with both signs
+    /// sums to 0 and is dropped from both, as [`SparseVec::bundle`] would.
+    pub fn canonicalize(&mut self) {
+        for indices in [&mut self.pos, &mut self.neg] {
+            indices.sort_unstable();
+            indices.dedup();
+        }
+        if Self::intersection_count_sorted(&self.pos, &self.neg) > 0 {
+            let pos = Self::difference_sorted(&self.pos, &self.neg);
+            self.neg = Self::difference_sorted(&self.neg, &self.pos);
+            self.pos = pos;
+        }
+    }
+
+    /// The dimension-independent part of [`SparseVec::validate`].
+    fn check_sorted(&self) -> Result<(), SparseVecError> {
+        for (negative, indices) in [(false, &self.pos), (true, &self.neg)] {
+            if let Some(position) = indices.windows(2).position(|w| w[0] >= w[1]) {
+                return Err(SparseVecError::Unsorted { negative, position: position + 1 });
+            }
+        }
+        Ok(())
+    }
+
     /// Count intersecting elements between two sorted slices.
     /// Hot path: used in cosine similarity calculation.
     #[inline]
@@ -247,6 +551,7 @@ impl SparseVec {
     /// assert!(vec.pos.len() > 0);
     /// assert!(vec.neg.len() > 0);
     /// ```
+    #[cfg(feature = "std")]
     pub fn random() -> Self {
         let mut rng = rand::thread_rng();
         let sparsity = DIM / 100; // ~1% density
@@ -264,6 +569,72 @@ impl SparseVec {
         SparseVec { pos, neg }
     }
 
+    /// The codebook vector ingest stores for a chunk of the file at `path`,
+    /// as `config.chunk_encoding` selects.
+    pub fn encode_chunk(data: &[u8], config: &ReversibleVSAConfig, path: Option<&str>) -> Self {
+        match config.chunk_encoding {
+            ChunkEncoding::Reversible => Self::encode_data(data, config, path),
+            ChunkEncoding::Similarity { shingle, nonzero } => Self::from_data_similarity(data, shingle, nonzero),
+        }
+    }
+
+    /// Similarity-preserving signature of `data` (SimHash over ternary
+    /// planes).
+    ///
+    /// Every `shingle`-byte window votes +1 or -1 on a few hash-chosen
+    /// indices; the `nonzero` indices with the strongest votes keep their
+    /// sign. Chunks sharing most of their shingles share most of their
+    /// strongest votes, so their cosine tracks how much content they have
+    /// in common regardless of where it sits. Unlike
+    /// [`SparseVec::encode_data`] the result cannot be decoded, and it does
+    /// not depend on the file path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use embeddenator::SparseVec;
+    ///
+    /// let a = SparseVec::from_data_similarity(b"the quick brown fox jumps over the lazy dog", 4, 200);
+    /// let b = SparseVec::from_data_similarity(b"the quick brown fox leaps over the lazy dog", 4, 200);
+    /// let c = SparseVec::from_data_similarity(b"lorem ipsum dolor sit amet, consectetur", 4, 200);
+    /// assert!(a.cosine(&b) > a.cosine(&c));
+    /// ```
+    pub fn from_data_similarity(data: &[u8], shingle: usize, nonzero: usize) -> Self {
+        if data.is_empty() || nonzero == 0 {
+            return SparseVec::new();
+        }
+        let shingle = shingle.clamp(1, data.len());
+        let mut votes = vec![0i32; DIM];
+        for window in data.windows(shingle) {
+            let mut h = xxhash_rust::xxh3::xxh3_64(window);
+            for _ in 0..SIMILARITY_TRITS_PER_SHINGLE {
+                // splitmix64 step: one hash yields a stream of (index, sign) draws
+                h = h.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = h;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+                votes[(z >> 1) as usize % DIM] += if z & 1 == 1 { 1 } else { -1 };
+            }
+        }
+
+        let mut ranked: Vec<(usize, i32)> = votes.into_iter().enumerate().filter(|&(_, v)| v != 0).collect();
+        ranked.sort_unstable_by(|a, b| b.1.abs().cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+        ranked.truncate(nonzero);
+
+        let mut out = SparseVec::new();
+        for (idx, vote) in ranked {
+            if vote > 0 {
+                out.pos.push(idx);
+            } else {
+                out.neg.push(idx);
+            }
+        }
+        out.pos.sort_unstable();
+        out.neg.sort_unstable();
+        out
+    }
+
     /// Encode data into a reversible sparse vector using block-based mapping
     ///
     /// This method implements hierarchical encoding with path-based permutations
@@ -298,16 +669,7 @@ impl SparseVec {
         }
 
         // Calculate path-based shift for hierarchical encoding
-        let path_shift = if let Some(path_str) = path {
-            // Use path hash to determine shift, constrained to prevent overflow
-            let mut hasher = Sha256::new();
-            hasher.update(path_str.as_bytes());
-            let hash = hasher.finalize();
-            let path_hash = u32::from_le_bytes(hash[0..4].try_into().unwrap()) as usize;
-            (path_hash % config.max_path_depth) * config.base_shift
-        } else {
-            0
-        };
+        let path_shift = path.map_or(0, |p| config.path_shift(p));
 
         // Split data into blocks
         let mut blocks = Vec::new();
@@ -370,15 +732,7 @@ impl SparseVec {
         }
 
         // Calculate path-based shift (same as encoding)
-        let path_shift = if let Some(path_str) = path {
-            let mut hasher = Sha256::new();
-            hasher.update(path_str.as_bytes());
-            let hash = hasher.finalize();
-            let path_hash = u32::from_le_bytes(hash[0..4].try_into().unwrap()) as usize;
-            (path_hash % config.max_path_depth) * config.base_shift
-        } else {
-            0
-        };
+        let path_shift = path.map_or(0, |p| config.path_shift(p));
 
         // Estimate number of blocks based on expected size
         let estimated_blocks = (expected_size + config.block_size - 1) / config.block_size;
@@ -615,6 +969,7 @@ impl SparseVec {
     /// assert!(sim2 > 0.3);
     /// ```
     pub fn bundle(&self, other: &SparseVec) -> SparseVec {
+        debug_assert!(self.check_sorted().and(other.check_sorted()).is_ok(), "bundle of unsorted SparseVec indices");
         // Optional ternary-native fast path (migration gate).
         // This is primarily intended for cases where vectors become dense enough
         // that packed word-wise operations are competitive.
@@ -798,6 +1153,7 @@ impl SparseVec {
     /// assert!(sim >= -1.0 && sim <= 1.0);
     /// ```
     pub fn bind(&self, other: &SparseVec) -> SparseVec {
+        debug_assert!(self.check_sorted().and(other.check_sorted()).is_ok(), "bind of unsorted SparseVec indices");
         #[cfg(feature = "bt-phase-2")]
         {
             // Packed bind is only worthwhile when both operands are dense enough.
@@ -870,21 +1226,21 @@ impl SparseVec {
             };
 
             match idx_a.cmp(&idx_b) {
-                std::cmp::Ordering::Less => {
+                core::cmp::Ordering::Less => {
                     if a_is_pos {
                         a_pos += 1;
                     } else {
                         a_neg += 1;
                     }
                 }
-                std::cmp::Ordering::Greater => {
+                core::cmp::Ordering::Greater => {
                     if b_is_pos {
                         b_pos += 1;
                     } else {
                         b_neg += 1;
                     }
                 }
-                std::cmp::Ordering::Equal => {
+                core::cmp::Ordering::Equal => {
                     let prod = sign_a * sign_b;
                     if prod == 1 {
                         result_pos.push(idx_a);
@@ -935,6 +1291,7 @@ impl SparseVec {
     /// assert!(sim < 0.3);
     /// ```
     pub fn cosine(&self, other: &SparseVec) -> f64 {
+        debug_assert!(self.check_sorted().and(other.check_sorted()).is_ok(), "cosine of unsorted SparseVec indices");
         #[cfg(feature = "bt-phase-2")]
         {
             // Only use packed cosine when total density is high enough to amortize conversion,
@@ -0,0 +1,254 @@
+//! Dense fixed-point vectors of balanced-ternary words.
+//!
+//! [`Word6Vec`] stores one [`Word6`] (six trits, -364..=364) per element,
+//! so the balanced-ternary substrate can hold small numbers — counters,
+//! scores, weights — alongside symbols. Each vector has a number of
+//! fractional trits `f`: a word `w` represents `w / 3^f`, so `f = 0` gives
+//! integers and `f = 2` steps of 1/9 up to ±40.4.
+//!
+//! Addition runs trit by trit through [`Word6::add_with_carry`]; a carry
+//! out of the top trit is an overflow. Multiplication forms the exact
+//! product and drops its low `f` trits, which in balanced ternary rounds to
+//! the nearest representable value. Overflowing results saturate at
+//! ±364 words, or make the `checked_*` variants return `None`.
+
+use crate::ternary::{Trit, Word6};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Word6Vec {
+    frac_trits: u8,
+    words: Vec<Word6>,
+}
+
+impl Word6Vec {
+    /// Most fractional trits a vector can have: every trit of the word.
+    pub const MAX_FRAC_TRITS: u8 = 6;
+
+    /// `len` zeros with `frac_trits` fractional trits.
+    ///
+    /// # Panics
+    ///
+    /// If `frac_trits` exceeds [`Word6Vec::MAX_FRAC_TRITS`].
+    pub fn new_zero(len: usize, frac_trits: u8) -> Self {
+        Self::check_frac(frac_trits);
+        Self {
+            frac_trits,
+            words: vec![Word6::ZERO; len],
+        }
+    }
+
+    /// Raw words, each within `Word6::MIN_VALUE..=Word6::MAX_VALUE`.
+    /// `None` if any is out of range.
+    ///
+    /// # Panics
+    ///
+    /// If `frac_trits` exceeds [`Word6Vec::MAX_FRAC_TRITS`].
+    pub fn from_i16_slice(values: &[i16], frac_trits: u8) -> Option<Self> {
+        Self::check_frac(frac_trits);
+        let words = values.iter().map(|&v| Word6::from_i16(v)).collect::<Option<Vec<_>>>()?;
+        Some(Self { frac_trits, words })
+    }
+
+    /// Raw words, clamping out-of-range values to ±364.
+    ///
+    /// # Panics
+    ///
+    /// If `frac_trits` exceeds [`Word6Vec::MAX_FRAC_TRITS`].
+    pub fn from_i16_slice_saturating(values: &[i16], frac_trits: u8) -> Self {
+        Self::check_frac(frac_trits);
+        Self {
+            frac_trits,
+            words: values.iter().map(|&v| word_saturating(v as i64)).collect(),
+        }
+    }
+
+    /// Real values rounded to the nearest step of `3^-frac_trits`, clamped
+    /// to the representable range; NaN becomes zero.
+    ///
+    /// # Panics
+    ///
+    /// If `frac_trits` exceeds [`Word6Vec::MAX_FRAC_TRITS`].
+    pub fn from_f64_slice(values: &[f64], frac_trits: u8) -> Self {
+        Self::check_frac(frac_trits);
+        let scale = 3f64.powi(frac_trits as i32);
+        Self {
+            frac_trits,
+            words: values.iter().map(|&v| word_saturating((v * scale).round() as i64)).collect(),
+        }
+    }
+
+    /// Raw words as integers.
+    pub fn to_i16_vec(&self) -> Vec<i16> {
+        self.words.iter().map(|w| w.to_i16()).collect()
+    }
+
+    /// Values the words represent, `word / 3^frac_trits`.
+    pub fn to_f64_vec(&self) -> Vec<f64> {
+        let scale = 3f64.powi(self.frac_trits as i32);
+        self.words.iter().map(|w| w.to_i16() as f64 / scale).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn frac_trits(&self) -> u8 {
+        self.frac_trits
+    }
+
+    pub fn words(&self) -> &[Word6] {
+        &self.words
+    }
+
+    pub fn get(&self, i: usize) -> Word6 {
+        self.words[i]
+    }
+
+    pub fn set(&mut self, i: usize, word: Word6) {
+        self.words[i] = word;
+    }
+
+    /// Elementwise sum, saturating on overflow.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn saturating_add(&self, other: &Self) -> Self {
+        self.zip_words(other, |a, b| match a.add_with_carry(b, Trit::Z) {
+            (sum, Trit::Z) => sum,
+            (_, carry) => word_saturating(carry.to_i8() as i64 * Word6::MAX_VALUE as i64),
+        })
+    }
+
+    /// Elementwise sum, or `None` if any element overflows.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        self.try_zip_words(other, |a, b| match a.add_with_carry(b, Trit::Z) {
+            (sum, Trit::Z) => Some(sum),
+            _ => None,
+        })
+    }
+
+    /// Elementwise difference, saturating on overflow.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn saturating_sub(&self, other: &Self) -> Self {
+        self.saturating_add(&-other.clone())
+    }
+
+    /// Elementwise fixed-point product, rounded to the nearest step and
+    /// saturating on overflow.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn saturating_mul(&self, other: &Self) -> Self {
+        let frac = self.frac_trits;
+        self.zip_words(other, |a, b| word_saturating(fixed_product(a, b, frac)))
+    }
+
+    /// Elementwise fixed-point product, or `None` if any element overflows.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+        let frac = self.frac_trits;
+        self.try_zip_words(other, |a, b| {
+            i16::try_from(fixed_product(a, b, frac)).ok().and_then(Word6::from_i16)
+        })
+    }
+
+    /// Exact dot product of the raw words, in units of `3^-(2 × frac_trits)`.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn dot(&self, other: &Self) -> i64 {
+        self.check_compatible(other);
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| a.to_i16() as i64 * b.to_i16() as i64)
+            .sum()
+    }
+
+    /// Dot product of the represented values.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors differ in length or fractional trits.
+    pub fn dot_f64(&self, other: &Self) -> f64 {
+        self.dot(other) as f64 / 9f64.powi(self.frac_trits as i32)
+    }
+
+    fn check_frac(frac_trits: u8) {
+        assert!(
+            frac_trits <= Self::MAX_FRAC_TRITS,
+            "Word6Vec supports at most {} fractional trits, got {}",
+            Self::MAX_FRAC_TRITS,
+            frac_trits
+        );
+    }
+
+    fn check_compatible(&self, other: &Self) {
+        assert_eq!(self.len(), other.len(), "Word6Vec length mismatch");
+        assert_eq!(self.frac_trits, other.frac_trits, "Word6Vec fractional trit mismatch");
+    }
+
+    fn zip_words(&self, other: &Self, f: impl Fn(Word6, Word6) -> Word6) -> Self {
+        self.check_compatible(other);
+        Self {
+            frac_trits: self.frac_trits,
+            words: self.words.iter().zip(&other.words).map(|(&a, &b)| f(a, b)).collect(),
+        }
+    }
+
+    fn try_zip_words(&self, other: &Self, f: impl Fn(Word6, Word6) -> Option<Word6>) -> Option<Self> {
+        self.check_compatible(other);
+        let words = self.words.iter().zip(&other.words).map(|(&a, &b)| f(a, b)).collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            frac_trits: self.frac_trits,
+            words,
+        })
+    }
+}
+
+impl std::ops::Neg for Word6Vec {
+    type Output = Word6Vec;
+    fn neg(mut self) -> Word6Vec {
+        for w in &mut self.words {
+            *w = w.neg();
+        }
+        self
+    }
+}
+
+/// `a × b` with its low `frac_trits` trits dropped: the exact product
+/// divided by `3^frac_trits`, rounded to nearest (the divisor is odd, so
+/// there are no ties).
+fn fixed_product(a: Word6, b: Word6, frac_trits: u8) -> i64 {
+    let product = a.to_i16() as i64 * b.to_i16() as i64;
+    let divisor = 3i64.pow(frac_trits as u32);
+    let half = divisor / 2;
+    if product >= 0 {
+        (product + half) / divisor
+    } else {
+        (product - half) / divisor
+    }
+}
+
+fn word_saturating(value: i64) -> Word6 {
+    let clamped = value.clamp(Word6::MIN_VALUE as i64, Word6::MAX_VALUE as i64) as i16;
+    Word6::from_i16(clamped).expect("clamped value is in range")
+}
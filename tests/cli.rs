@@ -2,3 +2,9 @@
 
 #[path = "cli/integration_cli.rs"]
 mod integration_cli;
+
+#[path = "cli/profile.rs"]
+mod profile;
+
+#[path = "cli/shell.rs"]
+mod shell;
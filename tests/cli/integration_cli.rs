@@ -240,6 +240,34 @@ fn test_cli_bundle_hier_produces_artifacts() {
         "query-text failed: {}",
         String::from_utf8_lossy(&query_output.stderr)
     );
+
+    // A spent candidate budget stops the hierarchical search and says so.
+    let budget_output = Command::new(embeddenator_bin())
+        .args([
+            "--output-format",
+            "json",
+            "query-text",
+            "-e",
+            engram.to_str().unwrap(),
+            "--text",
+            "Hello",
+            "--hierarchical-manifest",
+            hier_manifest.to_str().unwrap(),
+            "--sub-engrams-dir",
+            sub_dir.to_str().unwrap(),
+            "--max-candidates",
+            "0",
+        ])
+        .output()
+        .expect("Failed to run query-text");
+    assert!(
+        budget_output.status.success(),
+        "query-text failed: {}",
+        String::from_utf8_lossy(&budget_output.stderr)
+    );
+    let json: serde_json::Value = serde_json::from_slice(&budget_output.stdout).unwrap();
+    assert_eq!(json["truncated"], "candidate_budget");
+    assert_eq!(json["hierarchical"], serde_json::json!([]));
 }
 
 #[test]
@@ -430,3 +458,1450 @@ fn test_large_file_chunking() {
         "Large file not reconstructed correctly"
     );
 }
+
+#[cfg(feature = "signing")]
+#[test]
+fn test_cli_signed_ingest_and_verified_extract() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    create_test_input(&temp_dir).expect("Failed to create test input");
+
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let key = temp_dir.path().join("signing.key");
+    let output = temp_dir.path().join("output");
+
+    let keygen = Command::new(embeddenator_bin())
+        .args(["keygen", "--out", key.to_str().unwrap()])
+        .output()
+        .expect("Failed to run keygen");
+    assert!(keygen.status.success());
+
+    let ingest_output = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--sign-key",
+            key.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(ingest_output.status.success());
+    assert!(temp_dir.path().join("test.engram.sig").exists());
+
+    let pub_key = temp_dir.path().join("signing.key.pub");
+    let extract = |out: &PathBuf| {
+        Command::new(embeddenator_bin())
+            .args([
+                "extract",
+                "-e",
+                engram.to_str().unwrap(),
+                "-m",
+                manifest.to_str().unwrap(),
+                "-o",
+                out.to_str().unwrap(),
+                "--verify-signature",
+                "enforce",
+                "--trusted-key",
+                pub_key.to_str().unwrap(),
+            ])
+            .output()
+            .expect("Failed to run extract")
+    };
+
+    assert!(extract(&output).status.success());
+
+    // Without a trusted key, enforcing proves nothing and is refused.
+    let untrusted = Command::new(embeddenator_bin())
+        .args(["extract", "-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .args(["-o", temp_dir.path().join("output3").to_str().unwrap(), "--verify-signature", "enforce"])
+        .output()
+        .expect("Failed to run extract");
+    assert!(!untrusted.status.success());
+    assert!(String::from_utf8_lossy(&untrusted.stderr).contains("trusted public key"));
+
+    // Any change to the manifest invalidates the signature.
+    let mut tampered = fs::read(&manifest).unwrap();
+    tampered.push(b'\n');
+    fs::write(&manifest, tampered).unwrap();
+    let rejected = extract(&temp_dir.path().join("output2"));
+    assert!(!rejected.status.success());
+}
+
+#[test]
+fn test_cli_multi_root_namespaces() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data = temp_dir.path().join("srv_data");
+    let cfg = temp_dir.path().join("etc_app");
+    fs::create_dir_all(data.join("nested")).unwrap();
+    fs::create_dir_all(&cfg).unwrap();
+    fs::write(data.join("nested/records.csv"), b"id,value\n1,42\n").unwrap();
+    fs::write(cfg.join("app.toml"), b"[server]\nport = 8080\n").unwrap();
+
+    let engram = temp_dir.path().join("ns.engram");
+    let manifest = temp_dir.path().join("ns.json");
+    let ingest_output = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "--root",
+            &format!("data={}", data.display()),
+            "--root",
+            &format!("cfg={}", cfg.display()),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(
+        ingest_output.status.success(),
+        "Ingest failed: {}",
+        String::from_utf8_lossy(&ingest_output.stderr)
+    );
+
+    let output = temp_dir.path().join("cfg_only");
+    let extract_output = Command::new(embeddenator_bin())
+        .args([
+            "extract",
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "--namespace",
+            "cfg",
+        ])
+        .output()
+        .expect("Failed to run extract");
+    assert!(extract_output.status.success());
+    assert_eq!(fs::read(output.join("app.toml")).unwrap(), fs::read(cfg.join("app.toml")).unwrap());
+    assert!(!output.join("nested").exists());
+    assert!(!output.join("data").exists());
+}
+
+#[test]
+fn test_cli_ingest_with_part_size_and_extract() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    let data: Vec<u8> = (0..30_000u32).map(|i| (i % 253) as u8).collect();
+    fs::write(input_dir.join("big.bin"), &data).unwrap();
+
+    let engram = temp_dir.path().join("split.engram");
+    let manifest = temp_dir.path().join("split.json");
+    let ingest_output = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input_dir.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--part-size",
+            "8192",
+        ])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(
+        ingest_output.status.success(),
+        "Ingest failed: {}",
+        String::from_utf8_lossy(&ingest_output.stderr)
+    );
+    assert!(temp_dir.path().join("split.engram.part0001").exists());
+
+    let output_dir = temp_dir.path().join("output");
+    let extract_output = Command::new(embeddenator_bin())
+        .args([
+            "extract",
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run extract");
+    assert!(
+        extract_output.status.success(),
+        "Extract failed: {}",
+        String::from_utf8_lossy(&extract_output.stderr)
+    );
+    assert_eq!(fs::read(output_dir.join("big.bin")).unwrap(), data);
+}
+
+#[test]
+fn test_cli_delta_and_apply_delta() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let run = |args: &[&str]| {
+        let output = Command::new(embeddenator_bin())
+            .args(args)
+            .output()
+            .expect("Failed to run embeddenator");
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+
+    let v1 = temp_dir.path().join("v1");
+    fs::create_dir_all(&v1).unwrap();
+    fs::write(v1.join("a.txt"), b"first release").unwrap();
+    run(&["ingest", "-i", &path("v1"), "-e", &path("v1.engram"), "-m", &path("v1.json")]);
+
+    let v2 = temp_dir.path().join("v2");
+    fs::create_dir_all(&v2).unwrap();
+    fs::write(v2.join("a.txt"), b"first release").unwrap();
+    fs::write(v2.join("b.txt"), b"nightly update").unwrap();
+    run(&["ingest", "-i", &path("v2"), "-e", &path("v2.engram"), "-m", &path("v2.json")]);
+
+    run(&[
+        "delta", "--base-engram", &path("v1.engram"), "--base-manifest", &path("v1.json"),
+        "-e", &path("v2.engram"), "-m", &path("v2.json"), "-o", &path("v1-v2.delta"),
+    ]);
+    run(&[
+        "apply-delta", "--base-engram", &path("v1.engram"), "--base-manifest", &path("v1.json"),
+        "--delta", &path("v1-v2.delta"), "-e", &path("out.engram"), "-m", &path("out.json"),
+    ]);
+    run(&["extract", "-e", &path("out.engram"), "-m", &path("out.json"), "-o", &path("restored")]);
+
+    assert_eq!(fs::read(temp_dir.path().join("restored/a.txt")).unwrap(), b"first release");
+    assert_eq!(fs::read(temp_dir.path().join("restored/b.txt")).unwrap(), b"nightly update");
+}
+
+#[test]
+fn test_cli_export() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"exported metadata").unwrap();
+    let engram = temp_dir.path().join("root.engram");
+    let manifest = temp_dir.path().join("manifest.json");
+    let out_dir = temp_dir.path().join("tables");
+
+    let status = Command::new(embeddenator_bin())
+        .args(["ingest", "-i"])
+        .arg(&input_dir)
+        .arg("-e")
+        .arg(&engram)
+        .arg("-m")
+        .arg(&manifest)
+        .status()
+        .expect("Failed to run embeddenator");
+    assert!(status.success());
+
+    let output = Command::new(embeddenator_bin())
+        .arg("export")
+        .arg("-e")
+        .arg(&engram)
+        .arg("-m")
+        .arg(&manifest)
+        .arg("-o")
+        .arg(&out_dir)
+        .output()
+        .expect("Failed to run embeddenator");
+    if cfg!(feature = "parquet") {
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(out_dir.join("files.parquet").is_file());
+        assert!(out_dir.join("chunks.parquet").is_file());
+    } else {
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("enable feature `parquet`"));
+    }
+}
+
+#[test]
+fn test_cli_stats_json() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"duplicated content").unwrap();
+    fs::write(input_dir.join("b.txt"), b"duplicated content").unwrap();
+
+    let engram = temp_dir.path().join("stats.engram");
+    let manifest = temp_dir.path().join("stats.json");
+    let ingest_output = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input_dir.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(ingest_output.status.success());
+
+    let stats_output = Command::new(embeddenator_bin())
+        .args([
+            "stats",
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--json",
+        ])
+        .output()
+        .expect("Failed to run stats");
+    assert!(
+        stats_output.status.success(),
+        "Stats failed: {}",
+        String::from_utf8_lossy(&stats_output.stderr)
+    );
+
+    let report: serde_json::Value = serde_json::from_slice(&stats_output.stdout).unwrap();
+    assert_eq!(report["total"]["files"], 2);
+    assert_eq!(report["total"]["dedup_ratio"], 2.0);
+    assert_eq!(report["by_extension"]["txt"]["files"], 2);
+}
+
+#[test]
+fn test_cli_json_output_and_exit_codes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    create_test_input(&temp_dir).expect("Failed to create test input");
+
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("json.engram");
+    let manifest = temp_dir.path().join("json.manifest.json");
+    let ingest_output = Command::new(embeddenator_bin())
+        .args([
+            "--output-format",
+            "json",
+            "ingest",
+            "-i",
+            input.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(
+        ingest_output.status.success(),
+        "Ingest failed: {}",
+        String::from_utf8_lossy(&ingest_output.stderr)
+    );
+    let summary: serde_json::Value = serde_json::from_slice(&ingest_output.stdout).unwrap();
+    assert_eq!(summary["files"], 4);
+    assert_eq!(summary["stats"]["total"]["files"], 4);
+
+    let query_output = Command::new(embeddenator_bin())
+        .args([
+            "query",
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "-q",
+            input.join("test.txt").to_str().unwrap(),
+            "--output-format",
+            "json",
+        ])
+        .output()
+        .expect("Failed to run query");
+    assert!(query_output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&query_output.stdout).unwrap();
+    assert_eq!(result["files"][0]["path"], "test.txt");
+    assert!(result["similarity"].is_number());
+
+    let verify = |engram: &PathBuf| {
+        Command::new(embeddenator_bin())
+            .args([
+                "--output-format",
+                "json",
+                "verify",
+                "-e",
+                engram.to_str().unwrap(),
+                "-m",
+                manifest.to_str().unwrap(),
+            ])
+            .output()
+            .expect("Failed to run verify")
+    };
+
+    let ok = verify(&engram);
+    assert!(ok.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&ok.stdout).unwrap();
+    assert_eq!(report["mismatches"].as_array().map(Vec::len), Some(0));
+
+    let missing = verify(&temp_dir.path().join("missing.engram"));
+    assert_eq!(missing.status.code(), Some(3));
+    let error: serde_json::Value = serde_json::from_slice(&missing.stderr).unwrap();
+    assert_eq!(error["error"]["kind"], "not-found");
+
+    let corrupt = temp_dir.path().join("corrupt.engram");
+    fs::write(&corrupt, b"not an engram").unwrap();
+    let bad = verify(&corrupt);
+    assert_eq!(bad.status.code(), Some(4));
+    let error: serde_json::Value = serde_json::from_slice(&bad.stderr).unwrap();
+    assert_eq!(error["error"]["code"], 4);
+}
+
+#[test]
+fn test_cli_profile_defaults_and_precedence() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    create_test_input(&temp_dir).expect("Failed to create test input");
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("profile.engram");
+    let manifest = temp_dir.path().join("profile.json");
+
+    let config = temp_dir.path().join("config.toml");
+    fs::write(
+        &config,
+        "default_profile = \"team\"\n\n[profiles.team]\nengram_checksum = \"blake3\"\n",
+    )
+    .unwrap();
+
+    let checksummed = |extra: &[&str], env: &[(&str, &str)]| {
+        let mut ingest = Command::new(embeddenator_bin());
+        ingest
+            .args([
+                "ingest",
+                "-i",
+                input.to_str().unwrap(),
+                "-e",
+                engram.to_str().unwrap(),
+                "-m",
+                manifest.to_str().unwrap(),
+            ])
+            .args(extra)
+            .env("EMBEDDENATOR_CONFIG", &config);
+        for (k, v) in env {
+            ingest.env(k, v);
+        }
+        let out = ingest.output().expect("Failed to run ingest");
+        assert!(out.status.success(), "Ingest failed: {}", String::from_utf8_lossy(&out.stderr));
+
+        let info = Command::new(embeddenator_bin())
+            .args(["info", "-e", engram.to_str().unwrap(), "--json"])
+            .env("EMBEDDENATOR_CONFIG", &config)
+            .output()
+            .expect("Failed to run info");
+        let info: serde_json::Value = serde_json::from_slice(&info.stdout).unwrap();
+        info["storage"]["checksummed"].as_bool().unwrap()
+    };
+
+    assert!(checksummed(&[], &[]));
+    assert!(!checksummed(&[], &[("EMBEDDENATOR_ENGRAM_CHECKSUM", "none")]));
+    assert!(!checksummed(&["--engram-checksum", "none"], &[]));
+    assert!(checksummed(&["--engram-checksum", "xxh3"], &[("EMBEDDENATOR_ENGRAM_CHECKSUM", "none")]));
+
+    let unknown = Command::new(embeddenator_bin())
+        .args(["--profile", "nope", "ls", "-m", manifest.to_str().unwrap()])
+        .env("EMBEDDENATOR_CONFIG", &config)
+        .output()
+        .expect("Failed to run ls");
+    assert_eq!(unknown.status.code(), Some(2));
+}
+
+#[test]
+fn test_cli_profile_chunk_size_is_recorded() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(input.join("data.bin"), &data).unwrap();
+    let engram = temp_dir.path().join("chunked.engram");
+    let manifest = temp_dir.path().join("chunked.json");
+    let config = temp_dir.path().join("config.toml");
+    fs::write(&config, "default_profile = \"team\"\n\n[profiles.team]\nchunk_size = 1000\n").unwrap();
+
+    let chunk_size = |extra: &[&str], env: &[(&str, &str)]| {
+        let mut ingest = Command::new(embeddenator_bin());
+        ingest
+            .args([
+                "ingest",
+                "-i",
+                input.to_str().unwrap(),
+                "-e",
+                engram.to_str().unwrap(),
+                "-m",
+                manifest.to_str().unwrap(),
+            ])
+            .args(extra)
+            .env("EMBEDDENATOR_CONFIG", &config);
+        for (k, v) in env {
+            ingest.env(k, v);
+        }
+        let out = ingest.output().expect("Failed to run ingest");
+        assert!(out.status.success(), "Ingest failed: {}", String::from_utf8_lossy(&out.stderr));
+
+        let out_dir = temp_dir.path().join("out");
+        let _ = fs::remove_dir_all(&out_dir);
+        let extract = Command::new(embeddenator_bin())
+            .args([
+                "extract",
+                "-e",
+                engram.to_str().unwrap(),
+                "-m",
+                manifest.to_str().unwrap(),
+                "-o",
+                out_dir.to_str().unwrap(),
+            ])
+            .output()
+            .expect("Failed to run extract");
+        assert!(extract.status.success(), "Extract failed: {}", String::from_utf8_lossy(&extract.stderr));
+        assert_eq!(fs::read(out_dir.join("data.bin")).unwrap(), data);
+
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+        json["files"][0]["chunks"].as_array().unwrap().len()
+    };
+
+    assert_eq!(chunk_size(&[], &[]), 5);
+    assert_eq!(chunk_size(&[], &[("EMBEDDENATOR_CHUNK_SIZE", "2500")]), 2);
+    assert_eq!(chunk_size(&["--chunk-size", "4096"], &[("EMBEDDENATOR_CHUNK_SIZE", "2500")]), 2);
+
+    let zero = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", input.to_str().unwrap(), "-e", engram.to_str().unwrap(), "--chunk-size", "0"])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(!zero.status.success());
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_cli_encrypted_ingest_and_extract() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("secret.txt"), b"classified notes").unwrap();
+
+    let key_file = temp_dir.path().join("engram.key");
+    fs::write(&key_file, "11".repeat(32)).unwrap();
+
+    let engram = temp_dir.path().join("enc.engram");
+    let manifest = temp_dir.path().join("enc.json");
+    let ingest_output = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input_dir.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--encrypt-key",
+            key_file.to_str().unwrap(),
+            "--key-id",
+            "4",
+        ])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(
+        ingest_output.status.success(),
+        "Ingest failed: {}",
+        String::from_utf8_lossy(&ingest_output.stderr)
+    );
+
+    let output_dir = temp_dir.path().join("out");
+    let extract = |keys: &[String]| {
+        let mut args = vec![
+            "extract".to_string(),
+            "-e".to_string(),
+            engram.to_str().unwrap().to_string(),
+            "-m".to_string(),
+            manifest.to_str().unwrap().to_string(),
+            "-o".to_string(),
+            output_dir.to_str().unwrap().to_string(),
+        ];
+        args.extend(keys.iter().cloned());
+        Command::new(embeddenator_bin())
+            .args(&args)
+            .output()
+            .expect("Failed to run extract")
+    };
+
+    assert!(!extract(&[]).status.success());
+
+    let ok = extract(&["--key".to_string(), format!("4={}", key_file.display())]);
+    assert!(ok.status.success(), "Extract failed: {}", String::from_utf8_lossy(&ok.stderr));
+    assert_eq!(fs::read(output_dir.join("secret.txt")).unwrap(), b"classified notes");
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_cli_namespace_keys_and_rekey() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(input_dir.join("hr")).unwrap();
+    fs::create_dir_all(input_dir.join("pub")).unwrap();
+    fs::write(input_dir.join("hr/pay.txt"), b"salaries").unwrap();
+    fs::write(input_dir.join("pub/readme.txt"), b"public").unwrap();
+    let key = |id: u32, byte: &str| {
+        let path = temp_dir.path().join(format!("{id}.key"));
+        fs::write(&path, byte.repeat(32)).unwrap();
+        format!("{id}={}", path.display())
+    };
+    let (old_key, new_key) = (key(2, "22"), key(5, "55"));
+
+    let engram = temp_dir.path().join("root.engram");
+    let manifest = temp_dir.path().join("manifest.json");
+    let run = |args: &[&str]| {
+        Command::new(embeddenator_bin())
+            .args(args)
+            .args(["-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+            .output()
+            .expect("Failed to run embeddenator")
+    };
+    let ingest = run(&["ingest", "-i", input_dir.to_str().unwrap(), "--key-rule", "hr=2", "--key", &old_key]);
+    assert!(ingest.status.success(), "Ingest failed: {}", String::from_utf8_lossy(&ingest.stderr));
+    let group = || {
+        let mut names: Vec<String> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|n| n.starts_with("root.engram.key"))
+            .collect();
+        names.sort();
+        names
+    };
+    assert!(group()[0].starts_with("root.engram.key0002."), "{:?}", group());
+
+    let out = temp_dir.path().join("out");
+    let out_arg = out.to_str().unwrap();
+    let locked = run(&["extract", "-o", out_arg]);
+    assert_eq!(locked.status.code(), Some(6), "{}", String::from_utf8_lossy(&locked.stderr));
+    let public = run(&["extract", "-o", out_arg, "--namespace", "pub"]);
+    assert!(public.status.success(), "{}", String::from_utf8_lossy(&public.stderr));
+    assert_eq!(fs::read(out.join("readme.txt")).unwrap(), b"public");
+
+    let rekey = run(&["rekey", "--rotate", "2:5", "--key", &old_key, "--key", &new_key]);
+    assert!(rekey.status.success(), "Rekey failed: {}", String::from_utf8_lossy(&rekey.stderr));
+    assert!(String::from_utf8_lossy(&rekey.stdout).contains("key 5: wrote 1 chunks"));
+    let groups = group();
+    assert!(groups.len() == 1 && groups[0].starts_with("root.engram.key0005."), "{groups:?}");
+
+    assert_eq!(run(&["extract", "-o", out_arg, "--key", &old_key]).status.code(), Some(6));
+    let ok = run(&["extract", "-o", out_arg, "--key", &new_key]);
+    assert!(ok.status.success(), "Extract failed: {}", String::from_utf8_lossy(&ok.stderr));
+    assert_eq!(fs::read(out.join("hr/pay.txt")).unwrap(), b"salaries");
+}
+
+#[test]
+fn test_cli_manifest_upgrade() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let manifest = temp_dir.path().join("old.json");
+    let old = r#"{"files": [{"path": "a.txt", "is_text": true, "size": 5, "chunks": [0]}], "total_chunks": 1}"#;
+    fs::write(&manifest, old).unwrap();
+    let upgrade = |extra: &[&str]| {
+        Command::new(embeddenator_bin())
+            .args(["--output-format", "json", "manifest", "upgrade", "-m", manifest.to_str().unwrap()])
+            .args(extra)
+            .output()
+            .expect("Failed to run manifest upgrade")
+    };
+
+    let dry_run = upgrade(&["--dry-run"]);
+    assert!(dry_run.status.success(), "{}", String::from_utf8_lossy(&dry_run.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&dry_run.stdout).unwrap();
+    assert_eq!(report["from"], 1);
+    assert!(report["written"].is_null());
+    assert_eq!(fs::read_to_string(&manifest).unwrap(), old);
+
+    let done = upgrade(&[]);
+    assert!(done.status.success(), "{}", String::from_utf8_lossy(&done.stderr));
+    let upgraded: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(upgraded["schema_version"], report["to"]);
+    assert_eq!(upgraded["files"][0]["path"], "a.txt");
+
+    let downgraded = temp_dir.path().join("v1.json");
+    let out = Command::new(embeddenator_bin())
+        .args(["manifest", "downgrade", "-m", manifest.to_str().unwrap(), "--to", "1", "-o"])
+        .arg(&downgraded)
+        .output()
+        .expect("Failed to run manifest downgrade");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let v1: serde_json::Value = serde_json::from_slice(&fs::read(&downgraded).unwrap()).unwrap();
+    assert_eq!(v1, serde_json::from_str::<serde_json::Value>(old).unwrap());
+}
+
+#[test]
+fn test_cli_ingest_normalizes_paths() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input = temp_dir.path().join("input");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("Cafe\u{301}.TXT"), b"coffee").unwrap();
+    let engram = temp_dir.path().join("n.engram");
+    let manifest = temp_dir.path().join("n.json");
+
+    let out = Command::new(embeddenator_bin())
+        .args(["ingest", "--normalize-paths", "nfc", "--lowercase-paths", "-i"])
+        .arg(&input)
+        .arg("-e")
+        .arg(&engram)
+        .arg("-m")
+        .arg(&manifest)
+        .output()
+        .expect("Failed to run ingest");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let written: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(written["files"][0]["path"], "caf\u{e9}.txt");
+    assert_eq!(written["path_normalization"], serde_json::json!({"unicode": "nfc", "lowercase": true}));
+
+    let output = temp_dir.path().join("out");
+    let out = Command::new(embeddenator_bin())
+        .args(["extract", "-e"])
+        .arg(&engram)
+        .arg("-m")
+        .arg(&manifest)
+        .arg("-o")
+        .arg(&output)
+        .output()
+        .expect("Failed to run extract");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(fs::read(output.join("caf\u{e9}.txt")).unwrap(), b"coffee");
+}
+
+#[test]
+fn test_cli_ingest_skip_rules() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input = temp_dir.path().join("input");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("keep.txt"), b"kept").unwrap();
+    fs::write(input.join("scratch.TMP"), b"dropped").unwrap();
+    fs::write(input.join("large.txt"), vec![b'l'; 5000]).unwrap();
+    let manifest = temp_dir.path().join("s.json");
+
+    let out = Command::new(embeddenator_bin())
+        .args(["ingest", "--skip", "extension=tmp", "--skip", "larger-than=4096", "-i"])
+        .arg(&input)
+        .arg("-e")
+        .arg(temp_dir.path().join("s.engram"))
+        .arg("-m")
+        .arg(&manifest)
+        .output()
+        .expect("Failed to run ingest");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("scratch.TMP: extension") && stderr.contains("large.txt: size-limit"), "{stderr}");
+    let written: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    let paths: Vec<&str> = written["files"].as_array().unwrap().iter().map(|f| f["path"].as_str().unwrap()).collect();
+    assert_eq!(paths, ["keep.txt"]);
+
+    let bad = Command::new(embeddenator_bin())
+        .args(["ingest", "--skip", "smaller-than=3", "-i"])
+        .arg(&input)
+        .output()
+        .expect("Failed to run ingest");
+    assert!(!bad.status.success());
+}
+
+#[test]
+fn test_cli_query_access_stats_and_heatmap() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let stats = temp_dir.path().join("access.json");
+
+    let ingest = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", input.to_str().unwrap(), "-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(ingest.status.success());
+
+    for _ in 0..2 {
+        let query = Command::new(embeddenator_bin())
+            .args([
+                "query",
+                "-e",
+                engram.to_str().unwrap(),
+                "-m",
+                manifest.to_str().unwrap(),
+                "-q",
+                input.join("test.txt").to_str().unwrap(),
+                "--k",
+                "1",
+                "--access-stats",
+                stats.to_str().unwrap(),
+            ])
+            .output()
+            .unwrap();
+        assert!(query.status.success(), "{}", String::from_utf8_lossy(&query.stderr));
+    }
+
+    let heatmap = Command::new(embeddenator_bin())
+        .args(["heatmap", "--stats", stats.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(heatmap.status.success(), "{}", String::from_utf8_lossy(&heatmap.stderr));
+    let out = String::from_utf8_lossy(&heatmap.stdout);
+    assert!(out.starts_with("test.txt"), "{out}");
+
+    let json = Command::new(embeddenator_bin())
+        .args(["heatmap", "--stats", stats.to_str().unwrap(), "-m", manifest.to_str().unwrap(), "--json"])
+        .output()
+        .unwrap();
+    let rows: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(rows.as_array().unwrap().iter().map(|r| r["cells"][0].as_u64().unwrap()).max(), Some(2));
+}
+
+#[test]
+fn test_cli_ingest_with_encode_threads() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let input = temp_dir.path().join("input");
+    fs::write(input.join("large.bin"), (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<u8>>()).unwrap();
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let output = temp_dir.path().join("output");
+
+    let ingest = Command::new(embeddenator_bin())
+        .args([
+            "--output-format",
+            "json",
+            "ingest",
+            "-i",
+            input.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--encode-threads",
+            "2",
+            "--queue-bytes",
+            "4096",
+        ])
+        .output()
+        .unwrap();
+    assert!(ingest.status.success(), "{}", String::from_utf8_lossy(&ingest.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&ingest.stdout).unwrap();
+    assert!(report["encode_queue"]["chunks"].as_u64().unwrap() > 0, "{report}");
+    assert!(report["encode_queue"]["pauses"].as_u64().unwrap() > 0, "{report}");
+
+    let extract = Command::new(embeddenator_bin())
+        .args(["extract", "-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap(), "-o", output.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(extract.status.success(), "{}", String::from_utf8_lossy(&extract.stderr));
+    assert_eq!(fs::read(output.join("large.bin")).unwrap(), fs::read(input.join("large.bin")).unwrap());
+    assert_eq!(fs::read(output.join("subdir/nested.txt")).unwrap(), b"Nested file content\n");
+
+    let budget_without_threads = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", input.to_str().unwrap(), "--memory-budget", "1000000"])
+        .output()
+        .unwrap();
+    assert!(!budget_without_threads.status.success());
+}
+
+#[test]
+fn test_cli_quantized_export_and_import() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let signatures = temp_dir.path().join("signatures");
+    let vectors = temp_dir.path().join("test.engram.vectors");
+
+    let ingest = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", input.to_str().unwrap(), "-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(ingest.status.success());
+
+    let export = Command::new(embeddenator_bin())
+        .args([
+            "--output-format",
+            "json",
+            "export",
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--format",
+            "quantized",
+            "-o",
+            signatures.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(export.status.success(), "{}", String::from_utf8_lossy(&export.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&export.stdout).unwrap();
+    assert_eq!(report["metadata"]["format"], "packed2");
+    assert_eq!(report["metadata"]["rows"], 4);
+    assert!(signatures.join("signatures.json").exists());
+
+    let import = Command::new(embeddenator_bin())
+        .args(["import-signatures", "-i", signatures.to_str().unwrap(), "-o", vectors.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(import.status.success(), "{}", String::from_utf8_lossy(&import.stderr));
+    assert!(String::from_utf8_lossy(&import.stdout).contains("Imported 4 chunk signatures"));
+    assert_eq!(embeddenator::ChunkVectors::load(&vectors).unwrap().len(), 4);
+
+    fs::write(signatures.join("signatures.json"), b"{}").unwrap();
+    let bad = Command::new(embeddenator_bin())
+        .args(["import-signatures", "-i", signatures.to_str().unwrap(), "-o", vectors.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!bad.status.success());
+}
+
+#[test]
+fn test_cli_flamegraph_writes_folded_stacks() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let folded = temp_dir.path().join("ingest.folded");
+
+    let ingest = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--flamegraph",
+            folded.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(ingest.status.success(), "{}", String::from_utf8_lossy(&ingest.stderr));
+
+    let stacks = fs::read_to_string(&folded).unwrap();
+    assert!(stacks.lines().any(|l| l.starts_with("ingest_file;encode_chunk ")), "{stacks}");
+    for line in stacks.lines() {
+        let (stack, us) = line.rsplit_once(' ').unwrap();
+        assert!(!stack.is_empty() && !stack.contains(' '), "{line}");
+        assert!(us.parse::<u64>().unwrap() > 0, "{line}");
+    }
+}
+
+#[test]
+fn test_cli_scrub_writes_status_file() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let status = temp_dir.path().join("scrub.json");
+
+    let ingest = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(ingest.status.success(), "{}", String::from_utf8_lossy(&ingest.stderr));
+
+    let scrub = Command::new(embeddenator_bin())
+        .args([
+            "scrub",
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+            "--status-file",
+            status.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(scrub.status.success(), "{}", String::from_utf8_lossy(&scrub.stderr));
+
+    let written: serde_json::Value = serde_json::from_str(&fs::read_to_string(&status).unwrap()).unwrap();
+    assert!(written["finished_unix"].as_u64().unwrap() > 0);
+    assert!(written["report"]["chunks_checked"].as_u64().unwrap() > 0);
+    assert!(written["report"]["damaged"].as_array().unwrap().is_empty());
+}
+
+#[cfg(feature = "logging")]
+#[test]
+fn test_cli_json_logs_share_one_correlation_id() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let input = temp_dir.path().join("input");
+    let engram = temp_dir.path().join("test.engram");
+    let manifest = temp_dir.path().join("test.manifest.json");
+    let config = temp_dir.path().join("config.toml");
+    fs::write(&config, "default_profile = \"ops\"\n\n[profiles.ops]\nlog_level = \"info\"\nlog_format = \"json\"\n").unwrap();
+
+    let ingest = Command::new(embeddenator_bin())
+        .args([
+            "ingest",
+            "-i",
+            input.to_str().unwrap(),
+            "-e",
+            engram.to_str().unwrap(),
+            "-m",
+            manifest.to_str().unwrap(),
+        ])
+        .env("EMBEDDENATOR_CONFIG", &config)
+        .env_remove("EMBEDDENATOR_LOG")
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap();
+    assert!(ingest.status.success(), "{}", String::from_utf8_lossy(&ingest.stderr));
+
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&ingest.stderr)
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap_or_else(|e| panic!("{l}: {e}")))
+        .collect();
+    let subsystems: Vec<&str> = events.iter().filter_map(|e| e["subsystem"].as_str()).collect();
+    assert!(subsystems.contains(&"cli") && subsystems.contains(&"fs"), "{subsystems:?}");
+    let id = events[0]["correlation_id"].as_str().unwrap();
+    assert!(events.iter().all(|e| e["correlation_id"] == id && e["operation"] == "ingest"));
+    assert!(events.iter().any(|e| e["message"].as_str().unwrap().starts_with("Ingesting ")));
+}
+
+#[test]
+fn test_cli_eval_reports_metrics_and_fails_on_a_drop() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_input(&temp_dir).unwrap();
+    let dir = temp_dir.path();
+    let engram = dir.join("test.engram");
+    let manifest = dir.join("test.manifest.json");
+    let ingest = Command::new(embeddenator_bin())
+        .args(["ingest", "-i"])
+        .arg(dir.join("input"))
+        .arg("-e")
+        .arg(&engram)
+        .arg("-m")
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    assert!(ingest.status.success(), "{}", String::from_utf8_lossy(&ingest.stderr));
+    fs::write(dir.join("q.bin"), b"Nested file content\n").unwrap();
+    fs::write(
+        dir.join("queries.jsonl"),
+        "{\"id\": \"hello\", \"text\": \"Hello, holographic world!\\n\", \"relevant\": [\"test.txt\"]}\n\
+         {\"id\": \"nested\", \"file\": \"q.bin\", \"relevant\": [\"subdir/nested.txt\"]}\n",
+    )
+    .unwrap();
+
+    let eval = |extra: &[&str]| {
+        Command::new(embeddenator_bin())
+            .arg("eval")
+            .arg("-e")
+            .arg(&engram)
+            .arg("-m")
+            .arg(&manifest)
+            .arg("-q")
+            .arg(dir.join("queries.jsonl"))
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    let baseline = dir.join("before.json");
+    let run = eval(&["-k", "1,3", "-o", baseline.to_str().unwrap()]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    assert!(stdout.contains("2 queries") && stdout.contains("recall@3"), "{stdout}");
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&baseline).unwrap()).unwrap();
+    let reranked = &report["stages"][1];
+    assert_eq!(reranked["stage"], "reranked");
+    assert_eq!(reranked["cutoffs"][0]["recall"], 1.0);
+
+    let json = eval(&["--output-format", "json", "-k", "1,3", "--baseline", baseline.to_str().unwrap()]);
+    assert!(json.status.success(), "{}", String::from_utf8_lossy(&json.stderr));
+    let out: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(out["changes"].as_array().unwrap().len(), 15);
+
+    // A baseline better than this run is a regression.
+    let mut better = report.clone();
+    better["stages"][1]["mrr"] = 2.0.into();
+    fs::write(&baseline, better.to_string()).unwrap();
+    let dropped = eval(&["-k", "1,3", "--baseline", baseline.to_str().unwrap()]);
+    assert!(!dropped.status.success());
+    assert!(String::from_utf8_lossy(&dropped.stderr).contains("1 metric(s) dropped"));
+}
+
+#[test]
+fn test_cli_generations_commit_tier_and_checkout() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let run = |args: &[&str]| {
+        let output = Command::new(embeddenator_bin())
+            .args(args)
+            .output()
+            .expect("Failed to run embeddenator");
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    for i in 1..=3 {
+        fs::write(input.join(format!("v{i}.txt")), format!("release {i}")).unwrap();
+        run(&["ingest", "-i", &path("input"), "-e", &path("root.engram"), "-m", &path("manifest.json")]);
+        run(&[
+            "generations", "commit", "--store", &path("gens"), "-e", &path("root.engram"),
+            "-m", &path("manifest.json"), "--keep-full", "1", "--keep-local", "2", "--remote", &path("remote"),
+        ]);
+    }
+
+    let listing = run(&["--output-format", "json", "generations", "list", "--store", &path("gens")]);
+    let listing: serde_json::Value = serde_json::from_str(&listing).unwrap();
+    assert_eq!(listing["policy"]["keep_full"], 1);
+    let gens = listing["generations"].as_array().unwrap();
+    assert_eq!(gens.len(), 3);
+    assert_eq!(gens[0]["location"], "remote");
+    assert_eq!(gens[1]["base"], 3);
+    assert!(gens[2]["base"].is_null());
+
+    run(&[
+        "generations", "checkout", "--store", &path("gens"), "--generation", "1", "--remote", &path("remote"),
+        "-e", &path("g1.engram"), "-m", &path("g1.json"),
+    ]);
+    run(&["extract", "-e", &path("g1.engram"), "-m", &path("g1.json"), "-o", &path("restored")]);
+    assert_eq!(fs::read(temp_dir.path().join("restored/v1.txt")).unwrap(), b"release 1");
+    assert!(!temp_dir.path().join("restored/v2.txt").exists());
+
+    let tier = run(&["generations", "tier", "--store", &path("gens"), "--remote", &path("remote")]);
+    assert!(tier.contains("Nothing to tier"), "{tier}");
+}
+
+#[test]
+fn test_cli_find_similar_reports_file_and_region() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    let words = |seed: usize| -> String {
+        (0..2_000).map(|i| format!("w{} ", (seed * 7_919 + i * 104_729) % 100_003)).collect()
+    };
+    fs::write(input.join("alpha.txt"), words(1)).unwrap();
+    fs::write(input.join("beta.txt"), words(2)).unwrap();
+    fs::write(temp_dir.path().join("local.txt"), &words(2).as_bytes()[..4096]).unwrap();
+
+    let status = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", &path("input"), "-e", &path("root.engram"), "-m", &path("manifest.json")])
+        .status()
+        .expect("Failed to run ingest");
+    assert!(status.success());
+
+    let output = Command::new(embeddenator_bin())
+        .args([
+            "--output-format", "json", "find-similar", "-e", &path("root.engram"), "-m", &path("manifest.json"),
+            &path("local.txt"),
+        ])
+        .output()
+        .expect("Failed to run find-similar");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let top = &report["files"][0];
+    assert_eq!(top["path"], "beta.txt");
+    assert_eq!(top["regions"][0]["offset"], 0);
+    assert_eq!(top["regions"][0]["len"], 4096);
+}
+
+#[test]
+fn test_cli_ingest_roles_and_query_role() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("alpha.txt"), b"first file").unwrap();
+    fs::write(input.join("beta.txt"), b"second file").unwrap();
+
+    let output = Command::new(embeddenator_bin())
+        .args(["--output-format", "json", "ingest", "-i", &path("input"), "-e", &path("root.engram"), "-m", &path("manifest.json")])
+        .args(["--role", "alpha.txt:author=ada", "--role", "beta.txt:author=grace", "--metadata-roles"])
+        .args(["--schema-seed", "5"])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["role_schema"]["seed"], 5);
+    assert_eq!(report["role_schema"]["bound_paths"], 2);
+
+    let query = |args: &[&str]| {
+        Command::new(embeddenator_bin())
+            .args(["--output-format", "json", "query-role", "-e", &path("root.engram"), "--role", "author"])
+            .args(args)
+            .output()
+            .expect("Failed to run query-role")
+    };
+    let output = query(&["--value", "ada"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let hits = report["hits"].as_array().unwrap();
+    assert_eq!(hits.len(), 1, "{report}");
+    assert_eq!(hits[0]["path"], "alpha.txt");
+
+    let output = query(&["--like", "beta.txt"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["hits"][0]["path"], "beta.txt");
+
+    let output = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", &path("input"), "-e", &path("other.engram"), "-m", &path("other.json")])
+        .args(["--role", "gamma.txt:author=ada"])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_compute_evaluates_expressions() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("alpha.txt"), b"first file").unwrap();
+    fs::write(input.join("beta.txt"), b"second file, a little longer").unwrap();
+
+    let status = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", &path("input"), "-e", &path("root.engram"), "-m", &path("manifest.json")])
+        .status()
+        .expect("Failed to run ingest");
+    assert!(status.success());
+
+    let compute = |expr: &str| {
+        Command::new(embeddenator_bin())
+            .args(["--output-format", "json", "compute", "-e", &path("root.engram"), "-m", &path("manifest.json")])
+            .arg(expr)
+            .output()
+            .expect("Failed to run compute")
+    };
+    let output = compute("unbind(bind(file:beta.txt, symbol(role)), symbol(role))");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = report["matches"].as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap()).collect();
+    assert!(names[..2].contains(&"file:beta.txt"), "{report}");
+    assert!(report["matches"][0]["cosine"].as_f64().unwrap() > 0.99);
+
+    let output = compute("bind(file:gamma.txt, root)");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("file:gamma.txt"));
+}
+
+#[test]
+fn test_cli_info_shows_ingest_provenance() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("a.txt"), b"audited content").unwrap();
+
+    let status = Command::new(embeddenator_bin())
+        .args(["ingest", "--deterministic", "-i", &path("input"), "-e", &path("root.engram"), "-m", &path("manifest.json")])
+        .env_remove("SOURCE_DATE_EPOCH")
+        .status()
+        .expect("Failed to run ingest");
+    assert!(status.success());
+
+    let info = |manifest: &str| {
+        let output = Command::new(embeddenator_bin())
+            .args(["info", "-e", &path("root.engram"), "-m", manifest, "--json"])
+            .output()
+            .expect("Failed to run info");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    let report = info(&path("manifest.json"));
+    let provenance = &report["provenance"];
+    assert_eq!(provenance["tool_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(provenance["reproducible"], true);
+    assert!(provenance["hostname"].is_null());
+    assert_eq!(provenance["sources"][0], path("input"));
+    assert_eq!(provenance["input_files"], 1);
+    assert_eq!(report["provenance_issues"], serde_json::json!([]));
+
+    // A manifest edited after ingest no longer matches its inputs digest.
+    let mut manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(path("manifest.json")).unwrap()).unwrap();
+    manifest["files"][0]["path"] = "renamed.txt".into();
+    fs::write(path("edited.json"), serde_json::to_vec(&manifest).unwrap()).unwrap();
+    assert_eq!(info(&path("edited.json"))["provenance_issues"][0]["field"], "inputs");
+}
+
+#[test]
+fn test_cli_ingest_adaptive_trit_depth() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..6u8 {
+        let data: Vec<u8> = (0..5000u32).map(|j| (j as u8).wrapping_mul(i | 1) ^ i).collect();
+        fs::write(input_dir.join(format!("f{i}.bin")), data).unwrap();
+    }
+    let engram = temp_dir.path().join("root.engram");
+    let manifest = temp_dir.path().join("manifest.json");
+    let ingest = |extra: &[&str]| {
+        Command::new(embeddenator_bin())
+            .args(["--output-format", "json", "ingest", "-i", input_dir.to_str().unwrap()])
+            .args(["-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+            .args(extra)
+            .output()
+            .expect("Failed to run ingest")
+    };
+
+    let output = ingest(&["--basis", "2", "--trit-depth", "1:6"]);
+    assert!(output.status.success(), "Ingest failed: {}", String::from_utf8_lossy(&output.stderr));
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(summary["precision"]["expanded_coefficients"].as_u64().unwrap() > 0, "{summary}");
+    let recorded: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(recorded["encoding"]["dimensional"]["trit_depth"]["Adaptive"]["max_depth"], 6);
+
+    let out = temp_dir.path().join("out");
+    let extract = Command::new(embeddenator_bin())
+        .args(["extract", "-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .args(["-o", out.to_str().unwrap()])
+        .output()
+        .expect("Failed to run extract");
+    assert!(extract.status.success(), "Extract failed: {}", String::from_utf8_lossy(&extract.stderr));
+    assert_eq!(fs::read(out.join("f3.bin")).unwrap(), fs::read(input_dir.join("f3.bin")).unwrap());
+
+    assert!(!ingest(&["--basis", "2", "--trit-depth", "4:2"]).status.success());
+    assert!(!ingest(&["--trit-depth", "1:6"]).status.success());
+}
+
+#[test]
+fn test_cli_ingest_differential() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..8u8 {
+        let mut data = vec![b'a' + i % 2; 4096];
+        data[i as usize * 7] = b'!';
+        fs::write(input_dir.join(format!("f{i}.txt")), data).unwrap();
+    }
+    let engram = temp_dir.path().join("root.engram");
+    let manifest = temp_dir.path().join("manifest.json");
+    let output = Command::new(embeddenator_bin())
+        .args(["--output-format", "json", "ingest", "-i", input_dir.to_str().unwrap()])
+        .args(["-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .args(["--basis", "2", "--differential"])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(output.status.success(), "Ingest failed: {}", String::from_utf8_lossy(&output.stderr));
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let report = &summary["differential"];
+    assert!(report["differential"].as_u64().unwrap() > 0, "{summary}");
+    assert!(report["differential_bytes"].as_u64() < report["raw_bytes"].as_u64(), "{summary}");
+
+    let out = temp_dir.path().join("out");
+    let extract = Command::new(embeddenator_bin())
+        .args(["extract", "-e", engram.to_str().unwrap(), "-m", manifest.to_str().unwrap()])
+        .args(["-o", out.to_str().unwrap()])
+        .output()
+        .expect("Failed to run extract");
+    assert!(extract.status.success(), "Extract failed: {}", String::from_utf8_lossy(&extract.stderr));
+    for i in 0..8 {
+        let name = format!("f{i}.txt");
+        assert_eq!(fs::read(out.join(&name)).unwrap(), fs::read(input_dir.join(&name)).unwrap());
+    }
+
+    let without_basis = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", input_dir.to_str().unwrap(), "-e", engram.to_str().unwrap()])
+        .args(["-m", manifest.to_str().unwrap(), "--differential"])
+        .output()
+        .expect("Failed to run ingest");
+    assert!(!without_basis.status.success());
+}
+
+#[test]
+fn test_cli_align_and_query_federated_aligned() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+    let input = temp_dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    let mut state = 0x9e37_79b9u32;
+    for i in 0..12 {
+        let data: Vec<u8> = (0..1024)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect();
+        fs::write(input.join(format!("f{i:02}.bin")), data).unwrap();
+    }
+
+    for name in ["ours", "theirs"] {
+        let status = Command::new(embeddenator_bin())
+            .args(["ingest", "-i", &path("input"), "-e", &path(&format!("{name}.engram"))])
+            .args(["-m", &path(&format!("{name}.json"))])
+            .status()
+            .expect("Failed to run ingest");
+        assert!(status.success());
+    }
+
+    let output = Command::new(embeddenator_bin())
+        .args(["--output-format", "json", "align"])
+        .args(["-t", &format!("{},{}", path("ours.engram"), path("ours.json"))])
+        .args(["-s", &format!("{},{}", path("theirs.engram"), path("theirs.json"))])
+        .output()
+        .expect("Failed to run align");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["anchors"], 12);
+    // Twelve anchors map only some dimensions, enough to find a shared file.
+    assert!(report["fit"].as_f64().unwrap() > 0.3, "{report}");
+    assert_eq!(report["alignment"], path("theirs.engram.align"));
+
+    let output = Command::new(embeddenator_bin())
+        .args(["--output-format", "json", "query-federated", "-q", &input.join("f03.bin").to_string_lossy()])
+        .args(["-s", &format!("{},{}", path("ours.engram"), path("ours.json"))])
+        .args(["--aligned", &format!("{},{},{}", path("theirs.engram"), path("theirs.json"), path("theirs.engram.align"))])
+        .output()
+        .expect("Failed to run query-federated");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let hits: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut top: Vec<&str> = hits.as_array().unwrap()[..2].iter().map(|h| h["paths"][0].as_str().unwrap()).collect();
+    top.sort();
+    assert_eq!(top, ["f03.bin", "f03.bin"], "{hits}");
+
+    let output = Command::new(embeddenator_bin())
+        .args(["align", "-t", &format!("{},{}", path("ours.engram"), path("ours.json"))])
+        .args(["-s", &path("theirs.engram")])
+        .output()
+        .expect("Failed to run align");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_info_uses_calibration_only_when_asked() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+    fs::create_dir_all(temp_dir.path().join("input")).unwrap();
+    fs::write(temp_dir.path().join("input/a.txt"), b"some text to encode").unwrap();
+    let status = Command::new(embeddenator_bin())
+        .args(["ingest", "-i", &path("input"), "-e", &path("root.engram"), "-m", &path("manifest.json")])
+        .status()
+        .expect("Failed to run ingest");
+    assert!(status.success());
+
+    // A calibration that makes every vector bitsliced.
+    fs::write(temp_dir.path().join("calibration.json"), r#"{"density": 1e-9, "min_bitsliced_dim": 1}"#).unwrap();
+    let info = |extra: &[&str]| {
+        let output = Command::new(embeddenator_bin())
+            .args(["info", "-e", &path("root.engram"), "--json"])
+            .args(extra)
+            .env("EMBEDDENATOR_CALIBRATION", path("calibration.json"))
+            .output()
+            .expect("Failed to run info");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        info["root_representation"].as_str().unwrap().to_string()
+    };
+    assert_eq!(info(&[]), "sparse");
+    assert_eq!(info(&["--calibrated"]), "bitsliced");
+}
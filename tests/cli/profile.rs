@@ -0,0 +1,64 @@
+//! Tests for CLI configuration files, profiles and environment overrides.
+
+#[test]
+fn cli_profile_layers_environment_over_file() {
+    use embeddenator::cli::config::{config_path, env_var_name, Profile};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    let file = r#"
+default_profile = "team"
+
+[profiles.team]
+engram_compression = "zstd"
+key_id = 2
+keys = ["1=/keys/old.key", "2=/keys/new.key"]
+
+[profiles.team.vector_sync]
+url = "http://qdrant:6333"
+
+[profiles.ci]
+max_chunks = 10
+chunk_size = 8192
+"#;
+    let source = Path::new("config.toml");
+    let env_of = |vars: &[(&str, &str)]| {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name: &str| vars.get(name).cloned()
+    };
+
+    let team = Profile::from_config(Some(file), source, None, env_of(&[])).unwrap();
+    assert_eq!(team.engram_compression.as_deref(), Some("zstd"));
+    assert_eq!(team.key_id, Some(2));
+    assert_eq!(team.keys.len(), 2);
+    assert_eq!(team.vector_sync.url.as_deref(), Some("http://qdrant:6333"));
+
+    let env = env_of(&[
+        ("EMBEDDENATOR_PROFILE", "ci"),
+        ("EMBEDDENATOR_KEY_ID", "7"),
+        ("EMBEDDENATOR_VECTOR_SYNC_TOKEN", "12345"),
+        ("EMBEDDENATOR_SIMILARITY_CHUNKS", "true"),
+    ]);
+    let ci = Profile::from_config(Some(file), source, None, env).unwrap();
+    assert_eq!(ci.max_chunks, Some(10));
+    assert_eq!(ci.chunk_size, Some(8192));
+    assert_eq!(ci.similarity_chunks, Some(true));
+    assert_eq!(ci.engram_compression, None);
+    assert_eq!(ci.key_id, Some(7));
+    assert_eq!(ci.vector_sync.token.as_deref(), Some("12345"));
+
+    // The flag picks the profile over the environment.
+    let flagged = Profile::from_config(Some(file), source, Some("team"), env_of(&[("EMBEDDENATOR_PROFILE", "ci")]));
+    assert_eq!(flagged.unwrap().key_id, Some(2));
+
+    assert!(Profile::from_config(Some(file), source, Some("missing"), env_of(&[])).is_err());
+    assert!(Profile::from_config(Some(file), source, None, env_of(&[("EMBEDDENATOR_MAX_CHUNKS", "many")])).is_err());
+    assert!(Profile::from_config(Some("[profiles.x]\nnum_dimensions = 1\n"), source, Some("x"), env_of(&[])).is_err());
+    assert_eq!(Profile::from_config(None, source, None, env_of(&[])).unwrap(), Profile::default());
+
+    assert_eq!(env_var_name("vector_sync.url"), "EMBEDDENATOR_VECTOR_SYNC_URL");
+    assert_eq!(
+        config_path(env_of(&[("HOME", "/home/u")])),
+        Some(Path::new("/home/u/.config/embeddenator/config.toml").to_path_buf())
+    );
+}
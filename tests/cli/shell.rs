@@ -0,0 +1,60 @@
+//! Tests for the interactive shell: commands and completion.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn shell_session_commands_and_completion() {
+    use embeddenator::cli::shell::{complete, split_words, Flow, Session};
+    use embeddenator::EmbrFS;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(src.join("docs")).unwrap();
+    fs::write(src.join("docs").join("a.txt"), "alpha\nbeta\ngamma\n").unwrap();
+    fs::write(src.join("docs").join("b.txt"), "alpha\nBETA\ngamma\n").unwrap();
+    fs::write(src.join("main.rs"), "fn main() {}\n").unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &ReversibleVSAConfig::default()).unwrap();
+    let log = tmp.path().join("src.edna");
+    embrfs.save_append_log(&log).unwrap();
+
+    let mut session = Session::new(Default::default());
+    let run = |session: &mut Session, line: &str| {
+        let mut out = Vec::new();
+        session.execute(line, &mut out).map(|flow| (flow, String::from_utf8(out).unwrap()))
+    };
+
+    assert!(run(&mut session, "ls").is_err(), "nothing open yet");
+    run(&mut session, &format!("open {}", log.display())).unwrap();
+    assert_eq!(session.paths().len(), 3);
+
+    let (_, ls) = run(&mut session, "ls '*.txt'").unwrap();
+    assert!(ls.contains("docs/a.txt") && !ls.contains("main.rs"), "{ls}");
+    let (_, cat) = run(&mut session, "cat ./docs/a.txt").unwrap();
+    assert_eq!(cat, "alpha\nbeta\ngamma\n");
+    let (_, diff) = run(&mut session, "diff docs/a.txt docs/b.txt").unwrap();
+    assert!(diff.contains("first difference at byte 6"), "{diff}");
+    assert!(diff.contains("- beta") && diff.contains("+ BETA"), "{diff}");
+    let (_, same) = run(&mut session, "diff docs/a.txt docs/a.txt").unwrap();
+    assert!(same.starts_with("identical"));
+    let (_, hits) = run(&mut session, "query fn main() {}").unwrap();
+    assert!(hits.lines().next().unwrap().ends_with("main.rs"), "{hits}");
+    assert!(run(&mut session, "cat missing.txt").is_err());
+    assert!(run(&mut session, "frobnicate").is_err());
+    assert_eq!(run(&mut session, "exit").unwrap().0, Flow::Exit);
+
+    assert_eq!(split_words(r#"cat "a b.txt" c\ d"#).unwrap(), ["cat", "a b.txt", "c d"]);
+    assert!(split_words("cat 'open").is_err());
+
+    let paths = session.paths();
+    assert_eq!(complete("ca", 2, &paths), (0, vec!["cat ".to_string()]));
+    assert_eq!(complete("cat d", 5, &paths), (4, vec!["docs/".to_string()]));
+    assert_eq!(
+        complete("diff docs/", 10, &paths),
+        (5, vec!["docs/a.txt ".to_string(), "docs/b.txt ".to_string()])
+    );
+    assert!(complete("query d", 7, &paths).1.is_empty());
+}
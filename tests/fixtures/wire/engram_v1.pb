@@ -0,0 +1,6 @@
+
+
+"Q4Q;:Fixture file for format regression tests.
+Its engram is committed in bincode and protobuf form; both must keep
+loading and reconstructing these exact bytes.
+* 
\ No newline at end of file
@@ -8,3 +8,7 @@ mod hierarchical_determinism;
 
 #[path = "hierarchical/hierarchical_unfolding.rs"]
 mod hierarchical_unfolding;
+
+#[cfg(feature = "async")]
+#[path = "hierarchical/async_store.rs"]
+mod async_store;
@@ -0,0 +1,148 @@
+//! Async sub-engram store and envelope I/O (feature `async`).
+
+use std::collections::HashMap;
+
+use embeddenator::async_io;
+use embeddenator::embrfs::{ManifestItem, ManifestLevel};
+use embeddenator::envelope::{BinaryWriteOptions, ChecksumCodec, Keyring, PayloadKind};
+use embeddenator::{
+    query_hierarchical_codebook_with_store, save_sub_engrams_dir, DirectorySubEngramStore, HierarchicalManifest,
+    HierarchicalQueryBounds, SparseVec, SubEngram, SubEngramStore,
+};
+
+fn sv(pos: &[usize]) -> SparseVec {
+    let mut v = SparseVec::new();
+    v.pos = pos.to_vec();
+    v
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("tokio runtime")
+}
+
+fn sub(id: &str, pos: &[usize], chunk_ids: Vec<usize>, children: &[&str]) -> SubEngram {
+    SubEngram {
+        id: id.to_string(),
+        root: sv(pos),
+        chunk_count: chunk_ids.len(),
+        chunk_ids,
+        children: children.iter().map(|c| c.to_string()).collect(),
+    }
+}
+
+#[test]
+fn prefetched_store_matches_directory_store() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let sub_dir = tmp.path().join("sub_engrams");
+    let mut sub_engrams = HashMap::new();
+    sub_engrams.insert("root".to_string(), sub("root", &[9], vec![1], &["child"]));
+    sub_engrams.insert("child".to_string(), sub("child", &[9, 11], vec![0], &[]));
+    for n in 0..40 {
+        let id = format!("leaf/{n}");
+        sub_engrams.insert(id.clone(), sub(&id, &[n], vec![n], &[]));
+    }
+    save_sub_engrams_dir(&sub_engrams, &sub_dir).expect("save_sub_engrams_dir");
+    let store = DirectorySubEngramStore::new(&sub_dir);
+
+    let mut ids: Vec<String> = sub_engrams.keys().cloned().collect();
+    ids.push("absent".to_string());
+    let prefetched = runtime().block_on(async {
+        assert_eq!(store.load_async("child").await.unwrap().root.pos, vec![9, 11]);
+        assert!(store.load_async("absent").await.is_none());
+        store.prefetch(ids, 4).await
+    });
+    assert_eq!(prefetched.len(), sub_engrams.len());
+    assert_eq!(prefetched.missing(), ["absent".to_string()]);
+    assert_eq!(prefetched.get("leaf/7").unwrap().chunk_ids, vec![7]);
+
+    let mut codebook = HashMap::new();
+    codebook.insert(0, sv(&[9, 11]));
+    codebook.insert(1, sv(&[9]));
+    let hierarchical = HierarchicalManifest {
+        version: 1,
+        levels: vec![ManifestLevel {
+            level: 0,
+            items: vec![ManifestItem {
+                path: "root".to_string(),
+                sub_engram_id: "root".to_string(),
+                signature: None,
+            }],
+        }],
+        sub_engrams: HashMap::new(),
+        digests: Default::default(),
+    };
+    let bounds = HierarchicalQueryBounds {
+        k: 1,
+        candidate_k: 10,
+        beam_width: 8,
+        max_depth: 2,
+        max_expansions: 8,
+        max_open_indices: 2,
+        max_open_engrams: 2,
+        min_signature_cosine: None,
+        time_budget: None,
+        max_candidates: None,
+    };
+    let query = sv(&[9, 11]);
+    let from_disk = query_hierarchical_codebook_with_store(&hierarchical, &store, &codebook, &query, &bounds);
+    let from_prefetch = query_hierarchical_codebook_with_store(&hierarchical, &prefetched, &codebook, &query, &bounds);
+    assert_eq!(from_prefetch.len(), 1);
+    assert_eq!(from_prefetch[0].sub_engram_id, from_disk[0].sub_engram_id);
+    assert_eq!(from_prefetch[0].chunk_id, 0);
+    assert_eq!(prefetched.load("root").unwrap().children, vec!["child".to_string()]);
+}
+
+#[test]
+fn async_envelope_round_trip() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let path = tmp.path().join("payload.bin");
+    let payload = b"async envelope payload ".repeat(500);
+    let opts = BinaryWriteOptions {
+        checksum: ChecksumCodec::Xxh3,
+        ..Default::default()
+    };
+
+    let decoded = runtime().block_on(async {
+        async_io::write_envelope(&path, PayloadKind::SubEngramBincode, opts, payload.clone())
+            .await
+            .unwrap();
+        async_io::read_envelope(&path, PayloadKind::SubEngramBincode, &Keyring::default())
+            .await
+            .unwrap()
+    });
+    assert_eq!(decoded, payload);
+
+    let mut corrupt = std::fs::read(&path).unwrap();
+    let last = corrupt.len() - 20;
+    corrupt[last] ^= 0xff;
+    std::fs::write(&path, corrupt).unwrap();
+    let result = runtime().block_on(async_io::read_envelope(&path, PayloadKind::SubEngramBincode, &Keyring::default()));
+    assert!(result.is_err());
+}
+
+#[test]
+fn async_engram_and_manifest_load_match_sync() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let input = tmp.path().join("in");
+    std::fs::create_dir_all(&input).unwrap();
+    std::fs::write(input.join("a.txt"), "served asynchronously ".repeat(300)).unwrap();
+    let mut fsys = embeddenator::EmbrFS::new();
+    fsys.ingest_directory(&input, false, &embeddenator::ReversibleVSAConfig::default())
+        .unwrap();
+    let engram_path = tmp.path().join("root.engram");
+    let manifest_path = tmp.path().join("manifest.json");
+    fsys.save_engram(&engram_path).unwrap();
+    fsys.save_manifest(&manifest_path).unwrap();
+
+    let keys = Keyring::default();
+    let (engram, manifest) = runtime().block_on(async {
+        (
+            async_io::load_engram(&engram_path, &keys).await.unwrap(),
+            async_io::load_manifest(&manifest_path, &keys).await.unwrap(),
+        )
+    });
+    assert_eq!(engram.codebook.len(), fsys.engram.codebook.len());
+    assert_eq!(manifest.files, fsys.manifest.files);
+}
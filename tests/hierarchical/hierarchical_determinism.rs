@@ -88,3 +88,41 @@ fn bundle_hier_is_deterministic_and_sharding_stable() {
 
     assert_eq!(list_filenames(&d1), list_filenames(&d2), "sub-engram filenames drifted");
 }
+
+#[test]
+fn directory_signatures_roll_up_and_locate_changed_subtrees() {
+    use embeddenator::diff_hierarchical_manifests;
+
+    let config = ReversibleVSAConfig::default();
+    let snapshot = |readme: &[u8]| {
+        let mut fsys = EmbrFS::new();
+        fsys.ingest_bytes(b"fn main() {}", "src/main.rs".into(), &config).expect("ingest");
+        fsys.ingest_bytes(b"pub mod fs;", "src/lib.rs".into(), &config).expect("ingest");
+        fsys.ingest_bytes(readme, "docs/guide/readme.md".into(), &config).expect("ingest");
+        fsys.bundle_hierarchically(500, false, &config).expect("bundle")
+    };
+
+    let before = snapshot(b"# Guide\n");
+    assert!(before.levels.iter().flat_map(|l| &l.items).all(|item| item.signature.is_some()));
+    assert!(diff_hierarchical_manifests(&before, &snapshot(b"# Guide\n")).is_empty());
+
+    // Only the edited file's ancestors differ, and the untouched `src` subtree is not visited.
+    let after = snapshot(b"# Guide, revised\n");
+    let diff = diff_hierarchical_manifests(&before, &after);
+    assert_eq!(diff.changed, vec!["docs/guide/readme.md".to_string()]);
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+    assert_eq!(diff.nodes_compared, 4);
+
+    // Signatures survive the JSON round trip, and manifests without them are compared in full.
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let path = tmp.path().join("hier.json");
+    embeddenator::save_hierarchical_manifest(&after, &path).expect("save");
+    let mut legacy = embeddenator::load_hierarchical_manifest(&path).expect("load");
+    assert!(diff_hierarchical_manifests(&after, &legacy).is_empty());
+    for item in legacy.levels.iter_mut().flat_map(|l| &mut l.items) {
+        item.signature = None;
+    }
+    let full = diff_hierarchical_manifests(&legacy, &after);
+    assert_eq!(full.changed.len(), 3);
+    assert_eq!(full.nodes_compared, 6);
+}
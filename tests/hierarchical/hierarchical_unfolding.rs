@@ -65,14 +65,17 @@ fn hierarchical_unfolding_respects_bounds_and_is_deterministic() {
                 ManifestItem {
                     path: "A".to_string(),
                     sub_engram_id: "A".to_string(),
+                    signature: None,
                 },
                 ManifestItem {
                     path: "B".to_string(),
                     sub_engram_id: "B".to_string(),
+                    signature: None,
                 },
             ],
         }],
         sub_engrams,
+        digests: Default::default(),
     };
 
     // Tight bounds: only 1 expansion should occur.
@@ -84,6 +87,9 @@ fn hierarchical_unfolding_respects_bounds_and_is_deterministic() {
         max_expansions: 1,
         max_open_indices: 2,
         max_open_engrams: 2,
+        min_signature_cosine: None,
+        time_budget: None,
+        max_candidates: None,
     };
 
     let r1 = query_hierarchical_codebook(&hierarchical, &codebook, &query, &bounds);
@@ -136,9 +142,11 @@ fn hierarchical_unfolding_can_descend_into_children() {
             items: vec![ManifestItem {
                 path: "root".to_string(),
                 sub_engram_id: "root".to_string(),
+                signature: None,
             }],
         }],
         sub_engrams,
+        digests: Default::default(),
     };
 
     let bounds = HierarchicalQueryBounds {
@@ -149,6 +157,9 @@ fn hierarchical_unfolding_can_descend_into_children() {
         max_expansions: 8,
         max_open_indices: 8,
         max_open_engrams: 8,
+        min_signature_cosine: None,
+        time_budget: None,
+        max_candidates: None,
     };
 
     let results = query_hierarchical_codebook(&hierarchical, &codebook, &query, &bounds);
@@ -200,9 +211,11 @@ fn hierarchical_unfolding_can_load_sub_engrams_from_directory_store() {
             items: vec![ManifestItem {
                 path: "root".to_string(),
                 sub_engram_id: "root".to_string(),
+                signature: None,
             }],
         }],
         sub_engrams: HashMap::new(),
+        digests: Default::default(),
     };
 
     // Also ensure save/load of the manifest works with empty sub_engrams.
@@ -219,6 +232,9 @@ fn hierarchical_unfolding_can_load_sub_engrams_from_directory_store() {
         max_expansions: 8,
         max_open_indices: 2,
         max_open_engrams: 2,
+        min_signature_cosine: None,
+        time_budget: None,
+        max_candidates: None,
     };
 
     let results = query_hierarchical_codebook_with_store(&loaded_hier, &store, &codebook, &query, &bounds);
@@ -226,3 +242,299 @@ fn hierarchical_unfolding_can_load_sub_engrams_from_directory_store() {
     assert_eq!(results[0].chunk_id, 0);
     assert_eq!(results[0].sub_engram_id, "child");
 }
+
+#[test]
+fn batched_sub_engram_loads_match_serial_loads() {
+    use embeddenator::SubEngramStore;
+    use std::cell::RefCell;
+
+    /// Records the size of every `load_many` batch.
+    struct Recording<'a> {
+        inner: &'a DirectorySubEngramStore,
+        batches: RefCell<Vec<usize>>,
+    }
+    impl SubEngramStore for Recording<'_> {
+        fn load(&self, id: &str) -> Option<SubEngram> {
+            self.inner.load(id)
+        }
+        fn load_many(&self, ids: &[String]) -> Vec<Option<SubEngram>> {
+            self.batches.borrow_mut().push(ids.len());
+            self.inner.load_many(ids)
+        }
+    }
+
+    // 12 directories of 3 files each; chunk 3 * d + f is file f of directory d.
+    let mut codebook: HashMap<usize, SparseVec> = HashMap::new();
+    let mut sub_engrams: HashMap<String, SubEngram> = HashMap::new();
+    let mut items = Vec::new();
+    for d in 0..12usize {
+        let dir = format!("d{d}");
+        let mut children = Vec::new();
+        for f in 0..3usize {
+            let id = 3 * d + f;
+            codebook.insert(id, sv(&[d, 100 + id], &[200 + f]));
+            let child = format!("{dir}/f{f}");
+            sub_engrams.insert(
+                child.clone(),
+                SubEngram { id: child.clone(), root: codebook[&id].clone(), chunk_ids: vec![id], chunk_count: 1, children: vec![] },
+            );
+            children.push(child);
+        }
+        sub_engrams.insert(
+            dir.clone(),
+            SubEngram { id: dir.clone(), root: sv(&[d], &[]), chunk_ids: vec![3 * d, 3 * d + 1, 3 * d + 2], chunk_count: 3, children },
+        );
+        items.push(ManifestItem { path: dir.clone(), sub_engram_id: dir, signature: None });
+    }
+    let hierarchical = HierarchicalManifest {
+        version: 1,
+        levels: vec![ManifestLevel { level: 0, items }],
+        sub_engrams: sub_engrams.clone(),
+        digests: Default::default(),
+    };
+    let tmp = tempfile::tempdir().unwrap();
+    save_sub_engrams_dir(&sub_engrams, tmp.path()).unwrap();
+
+    let bounds = HierarchicalQueryBounds { k: 5, max_open_engrams: 4, beam_width: 6, ..Default::default() };
+    let query = sv(&[4, 7, 113], &[201]);
+    let in_memory = query_hierarchical_codebook(&hierarchical, &codebook, &query, &bounds);
+    assert!(!in_memory.is_empty());
+    for threads in [1, 8] {
+        let dir_store = DirectorySubEngramStore::new(tmp.path()).with_threads(threads);
+        let store = Recording { inner: &dir_store, batches: RefCell::new(Vec::new()) };
+        let hits = query_hierarchical_codebook_with_store(&hierarchical, &store, &codebook, &query, &bounds);
+        assert_eq!(hits, in_memory, "threads = {threads}");
+        // Level 0 is fetched in windows of the cache size.
+        let batches = store.batches.into_inner();
+        assert_eq!(batches[..3], [4, 4, 4], "{batches:?}");
+    }
+
+    let ids: Vec<String> = vec!["d3".into(), "missing".into(), "d3/f1".into()];
+    let loaded = DirectorySubEngramStore::new(tmp.path()).load_many(&ids);
+    assert_eq!(loaded[0].as_ref().unwrap().id, "d3");
+    assert!(loaded[1].is_none());
+    assert_eq!(loaded[2].as_ref().unwrap().chunk_ids, vec![10]);
+}
+
+#[test]
+fn directory_signatures_prune_dissimilar_subtrees() {
+    let query = sv(&[1, 2, 3], &[]);
+    let mut codebook: HashMap<usize, SparseVec> = HashMap::new();
+    codebook.insert(0, sv(&[1, 2, 3], &[]));
+    codebook.insert(1, sv(&[1, 2], &[50]));
+
+    // Roots say nothing; signatures tell the subtrees apart.
+    let mut sub_engrams: HashMap<String, SubEngram> = HashMap::new();
+    for (id, chunk) in [("near", 0), ("far", 1)] {
+        sub_engrams.insert(
+            id.to_string(),
+            SubEngram { id: id.to_string(), root: SparseVec::new(), chunk_ids: vec![chunk], chunk_count: 1, children: vec![] },
+        );
+    }
+    let item = |id: &str, signature: SparseVec| ManifestItem {
+        path: id.to_string(),
+        sub_engram_id: id.to_string(),
+        signature: Some(signature),
+    };
+    let hierarchical = HierarchicalManifest {
+        version: 1,
+        levels: vec![ManifestLevel {
+            level: 0,
+            items: vec![item("near", sv(&[1, 2, 3], &[])), item("far", sv(&[], &[1, 2, 3]))],
+        }],
+        sub_engrams,
+        digests: Default::default(),
+    };
+
+    let unpruned = HierarchicalQueryBounds { k: 2, ..Default::default() };
+    let hits = query_hierarchical_codebook(&hierarchical, &codebook, &query, &unpruned);
+    assert_eq!(hits.len(), 2);
+
+    // Beam width 1 follows the signature, not the (empty) root.
+    let narrow = HierarchicalQueryBounds { k: 2, beam_width: 1, max_expansions: 1, ..Default::default() };
+    let hits = query_hierarchical_codebook(&hierarchical, &codebook, &query, &narrow);
+    assert_eq!(hits.iter().map(|h| h.sub_engram_id.as_str()).collect::<Vec<_>>(), ["near"]);
+
+    let pruned = HierarchicalQueryBounds { k: 2, min_signature_cosine: Some(0.5), ..Default::default() };
+    let hits = query_hierarchical_codebook(&hierarchical, &codebook, &query, &pruned);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].chunk_id, 0);
+}
+
+#[test]
+fn sub_engram_digests_catch_corrupt_files() {
+    use embeddenator::{load_hierarchical_manifest, EmbrError, SubEngramFault, SubEngramStore};
+
+    let mut sub_engrams: HashMap<String, SubEngram> = HashMap::new();
+    for (i, id) in ["a", "b", "c"].into_iter().enumerate() {
+        let chunk_ids = vec![10 * i, 10 * i + 4];
+        sub_engrams.insert(
+            id.to_string(),
+            SubEngram { id: id.to_string(), root: sv(&[i], &[]), chunk_ids, chunk_count: 2, children: vec![] },
+        );
+    }
+    let mut hierarchical = HierarchicalManifest {
+        version: 1,
+        levels: vec![],
+        sub_engrams: sub_engrams.clone(),
+        digests: Default::default(),
+    };
+    hierarchical.record_digests();
+    assert_eq!(hierarchical.digests["b"].chunk_range, Some((10, 14)));
+
+    // Digests survive the manifest round trip.
+    let tmp = tempfile::tempdir().unwrap();
+    let manifest_path = tmp.path().join("hier.json");
+    save_hierarchical_manifest(&hierarchical, &manifest_path).unwrap();
+    let hierarchical = load_hierarchical_manifest(&manifest_path).unwrap();
+    assert_eq!(hierarchical.digests.len(), 3);
+
+    let dir = tmp.path().join("subs");
+    save_sub_engrams_dir(&sub_engrams, &dir).unwrap();
+    let store = DirectorySubEngramStore::new(&dir).with_digests(&hierarchical);
+    assert_eq!(store.verify_all().unwrap(), 3);
+
+    // A file swapped for another sub-engram's decodes fine but fails its digest.
+    std::fs::copy(dir.join("a.subengram"), dir.join("b.subengram")).unwrap();
+    let fault = |err: EmbrError| match err {
+        EmbrError::CorruptSubEngram(c) => (c.id, c.chunk_range, c.fault),
+        other => panic!("expected a corrupt sub-engram, got {other}"),
+    };
+    let (id, range, reason) = fault(store.try_load("b").unwrap_err());
+    assert_eq!((id.as_str(), range), ("b", Some((10, 14))));
+    assert!(matches!(reason, SubEngramFault::DigestMismatch { .. }));
+    assert!(store.load("b").is_none());
+    assert_eq!(fault(store.verify_all().unwrap_err()).0, "b");
+    // Without digests the store trusts what it reads.
+    assert_eq!(DirectorySubEngramStore::new(&dir).load("b").unwrap().id, "a");
+
+    std::fs::write(dir.join("c.subengram"), b"garbage").unwrap();
+    assert!(matches!(fault(store.try_load("c").unwrap_err()).2, SubEngramFault::Malformed(_)));
+    std::fs::remove_file(dir.join("a.subengram")).unwrap();
+    let (id, range, reason) = fault(store.try_load("a").unwrap_err());
+    assert_eq!((id.as_str(), range, reason), ("a", Some((0, 4)), SubEngramFault::Missing));
+}
+
+#[test]
+fn explained_queries_trace_contributions_and_skipped_nodes() {
+    use embeddenator::{explain_hierarchical_query, NodeOutcome};
+
+    let query = sv(&[1, 2, 3], &[]);
+    let mut codebook: HashMap<usize, SparseVec> = HashMap::new();
+    codebook.insert(0, sv(&[1, 2, 3], &[]));
+    codebook.insert(1, sv(&[1, 2], &[60]));
+    codebook.insert(2, sv(&[1, 70], &[]));
+
+    let node = |id: &str, root: SparseVec, chunk_ids: Vec<usize>, children: &[&str]| {
+        let children = children.iter().map(|c| c.to_string()).collect();
+        (id.to_string(), SubEngram { id: id.to_string(), root, chunk_ids, chunk_count: 1, children })
+    };
+    let sub_engrams: HashMap<String, SubEngram> = [
+        node("A", sv(&[1, 2, 3], &[]), vec![0], &["A/x"]),
+        node("A/x", sv(&[1, 2, 3], &[]), vec![0, 2], &["A/x/deep"]),
+        node("A/x/deep", sv(&[1], &[]), vec![2], &[]),
+        node("B", sv(&[1, 2], &[]), vec![1], &[]),
+        node("C", sv(&[90], &[]), vec![2], &[]),
+        node("P", sv(&[1, 2, 3], &[]), vec![0], &[]),
+    ]
+    .into_iter()
+    .collect();
+    let item = |id: &str, signature: Option<SparseVec>| ManifestItem { path: id.to_string(), sub_engram_id: id.to_string(), signature };
+    let hierarchical = HierarchicalManifest {
+        version: 1,
+        levels: vec![ManifestLevel {
+            level: 0,
+            items: vec![item("A", None), item("B", None), item("C", None), item("P", Some(sv(&[], &[1, 2, 3])))],
+        }],
+        sub_engrams,
+        digests: Default::default(),
+    };
+    let bounds = HierarchicalQueryBounds {
+        k: 3,
+        beam_width: 2,
+        max_depth: 1,
+        min_signature_cosine: Some(0.0),
+        ..Default::default()
+    };
+
+    let explained = explain_hierarchical_query(&hierarchical, &codebook, &query, &bounds);
+    let hits: Vec<_> = explained.results.iter().map(|r| r.hit.clone()).collect();
+    assert_eq!(hits, query_hierarchical_codebook(&hierarchical, &codebook, &query, &bounds));
+
+    // Chunk 0 came back from A and from its child; A reported it first.
+    let best = &explained.results[0];
+    assert_eq!((best.hit.chunk_id, best.path.clone()), (0, vec!["A".to_string()]));
+    let from: Vec<&str> = best.contributions.iter().map(|c| c.sub_engram_id.as_str()).collect();
+    assert_eq!(from, ["A", "A/x"]);
+    assert!(best.contributions.iter().all(|c| c.cosine > 0.99));
+    let deep_hit = explained.results.iter().find(|r| r.hit.chunk_id == 2).unwrap();
+    assert_eq!(deep_hit.path, ["A", "A/x"]);
+
+    let outcome = |id: &str| explained.node(id).unwrap().outcome.clone();
+    assert!(matches!(outcome("A"), NodeOutcome::Expanded { hits: 1 }));
+    assert!(matches!(outcome("B"), NodeOutcome::Expanded { .. }));
+    assert_eq!(outcome("C"), NodeOutcome::OutsideBeam);
+    assert!(matches!(outcome("P"), NodeOutcome::PrunedBySignature { cosine } if cosine < -0.99));
+    assert_eq!(outcome("A/x/deep"), NodeOutcome::BeyondMaxDepth);
+    assert_eq!(explained.node("A/x/deep").unwrap().parent.as_deref(), Some("A/x"));
+}
+
+#[test]
+fn budgets_return_best_effort_hits_flagged_as_truncated() {
+    use embeddenator::{explain_hierarchical_query, query_hierarchical_budgeted, NodeOutcome, QueryTruncation};
+    use std::time::Duration;
+
+    // 8 directories of 3 files each; chunk 3 * d + f is file f of directory d.
+    let mut codebook: HashMap<usize, SparseVec> = HashMap::new();
+    let mut sub_engrams: HashMap<String, SubEngram> = HashMap::new();
+    let mut items = Vec::new();
+    for d in 0..8usize {
+        let dir = format!("d{d}");
+        let chunk_ids: Vec<usize> = (0..3).map(|f| 3 * d + f).collect();
+        for &id in &chunk_ids {
+            codebook.insert(id, sv(&[1, 100 + id], &[]));
+        }
+        sub_engrams.insert(dir.clone(), SubEngram { id: dir.clone(), root: sv(&[1, 100 + 3 * d], &[]), chunk_ids, chunk_count: 3, children: vec![] });
+        items.push(ManifestItem { path: dir.clone(), sub_engram_id: dir, signature: None });
+    }
+    let hierarchical = HierarchicalManifest {
+        version: 1,
+        levels: vec![ManifestLevel { level: 0, items }],
+        sub_engrams,
+        digests: Default::default(),
+    };
+    let query = sv(&[1, 109], &[]);
+    let unbounded = HierarchicalQueryBounds { k: 3, beam_width: 8, ..Default::default() };
+
+    let full = query_hierarchical_budgeted(&hierarchical, &codebook, &query, &unbounded);
+    assert_eq!(full.truncated, None);
+    assert_eq!(full.nodes_expanded, 8);
+    assert_eq!(full.candidates_scanned, 24);
+    assert_eq!(full.hits, query_hierarchical_codebook(&hierarchical, &codebook, &query, &unbounded));
+
+    // An exhausted time budget still expands the best node, d3.
+    let timed = HierarchicalQueryBounds { time_budget: Some(Duration::ZERO), ..unbounded.clone() };
+    let early = query_hierarchical_budgeted(&hierarchical, &codebook, &query, &timed);
+    assert_eq!(early.truncated, Some(QueryTruncation::TimeBudget));
+    assert_eq!(early.nodes_expanded, 1);
+    assert_eq!(early.hits[0].chunk_id, 9);
+    assert_eq!(early.hits[0], full.hits[0]);
+
+    let explained = explain_hierarchical_query(&hierarchical, &codebook, &query, &timed);
+    assert_eq!(explained.truncated, Some(QueryTruncation::TimeBudget));
+    assert!(matches!(explained.node("d3").unwrap().outcome, NodeOutcome::Expanded { .. }));
+    assert_eq!(explained.node("d0").unwrap().outcome, NodeOutcome::OverBudget);
+
+    // The last node scans only what is left of the candidate budget.
+    let counted = HierarchicalQueryBounds { max_candidates: Some(7), ..unbounded.clone() };
+    let early = query_hierarchical_budgeted(&hierarchical, &codebook, &query, &counted);
+    assert_eq!(early.truncated, Some(QueryTruncation::CandidateBudget));
+    assert_eq!((early.nodes_expanded, early.candidates_scanned), (3, 7));
+    assert_eq!(early.hits[0].chunk_id, 9);
+
+    // A budget that runs out with nothing left to expand is no truncation.
+    let exact = HierarchicalQueryBounds { max_candidates: Some(24), ..unbounded };
+    let done = query_hierarchical_budgeted(&hierarchical, &codebook, &query, &exact);
+    assert_eq!(done.truncated, None);
+    assert_eq!(done.hits, full.hits);
+}
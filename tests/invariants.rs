@@ -3,6 +3,9 @@
 // Keeping this as a top-level file ensures Cargo discovers and runs the suite,
 // while the actual tests live in subdirectories for navigability.
 
+#[path = "support/mod.rs"]
+mod support;
+
 #[path = "invariants/bitsliced_equivalence.rs"]
 mod bitsliced_equivalence;
 
@@ -15,6 +18,18 @@ mod bt_phase2_scratch_invariants;
 #[path = "invariants/packed_trit_vec.rs"]
 mod packed_trit_vec;
 
+#[path = "invariants/word6_vec.rs"]
+mod word6_vec;
+
+#[path = "invariants/trit_matrix.rs"]
+mod trit_matrix;
+
+#[path = "invariants/graph_encoding.rs"]
+mod graph_encoding;
+
+#[path = "invariants/record.rs"]
+mod record;
+
 #[path = "invariants/ternary_refactor_invariants.rs"]
 mod ternary_refactor_invariants;
 
@@ -27,9 +42,100 @@ mod reconstruction_guarantee;
 #[path = "invariants/ternary_signature_index.rs"]
 mod ternary_signature_index;
 
+#[path = "invariants/semantic.rs"]
+mod semantic;
+
+#[cfg(feature = "onnx")]
+#[path = "invariants/onnx_embed.rs"]
+mod onnx_embed;
+
+#[path = "invariants/stream_ingest.rs"]
+mod stream_ingest;
+
+#[path = "invariants/git_archive.rs"]
+mod git_archive;
+
+#[cfg(feature = "kafka")]
+#[path = "invariants/kafka_source.rs"]
+mod kafka_source;
+
+#[cfg(feature = "s3")]
+#[path = "invariants/object_fetch.rs"]
+mod object_fetch;
+
+#[cfg(feature = "nbd")]
+#[path = "invariants/nbd.rs"]
+mod nbd;
+
+#[cfg(feature = "sqlite")]
+#[path = "invariants/sqlite_vtab.rs"]
+mod sqlite_vtab;
+
 #[path = "invariants/envelope_edge_cases.rs"]
 mod envelope_edge_cases;
 
+#[path = "invariants/envelope_streaming.rs"]
+mod envelope_streaming;
+
+#[path = "invariants/envelope_checksums.rs"]
+mod envelope_checksums;
+
+#[path = "invariants/errors.rs"]
+mod errors;
+
+#[path = "invariants/envelope_encryption.rs"]
+mod envelope_encryption;
+
+#[path = "invariants/append_log.rs"]
+mod append_log;
+
+#[path = "invariants/lazy_envelope.rs"]
+mod lazy_envelope;
+
+#[path = "invariants/multipart.rs"]
+mod multipart;
+
+#[path = "invariants/rkyv_engram.rs"]
+mod rkyv_engram;
+
+#[path = "invariants/delta.rs"]
+mod delta;
+
+#[path = "invariants/export_parquet.rs"]
+mod export_parquet;
+
+#[path = "invariants/dense_export.rs"]
+mod dense_export;
+
+#[path = "invariants/wire.rs"]
+mod wire;
+
+#[path = "invariants/bulk_io.rs"]
+mod bulk_io;
+
+#[path = "invariants/in_memory.rs"]
+mod in_memory;
+
+#[cfg(any(feature = "lmdb", feature = "rocksdb"))]
+#[path = "invariants/vector_stores.rs"]
+mod vector_stores;
+
+#[cfg(feature = "vector-sync")]
+#[path = "invariants/vector_sync.rs"]
+mod vector_sync;
+
+#[cfg(feature = "grpc")]
+#[path = "invariants/grpc.rs"]
+mod grpc;
+
+#[cfg(feature = "http")]
+#[path = "invariants/http_api.rs"]
+mod http_api;
+
+#[cfg(feature = "ffi")]
+#[path = "invariants/ffi.rs"]
+mod ffi;
+
 #[path = "invariants/extended_dimensionality.rs"]
 mod extended_dimensionality;
 
@@ -49,3 +155,150 @@ mod register_validity;
 #[cfg(feature = "proptest")]
 #[path = "invariants/block_sparse_invariants.rs"]
 mod block_sparse_invariants;
+
+#[path = "invariants/manifest_schema.rs"]
+mod manifest_schema;
+
+#[path = "invariants/safe_path.rs"]
+mod safe_path;
+
+#[path = "invariants/path_norm.rs"]
+mod path_norm;
+
+#[path = "invariants/ingest_queue.rs"]
+mod ingest_queue;
+
+#[path = "invariants/health.rs"]
+mod health;
+
+#[path = "invariants/quantized_export.rs"]
+mod quantized_export;
+
+#[path = "invariants/trit_coding.rs"]
+mod trit_coding;
+
+#[path = "invariants/generations.rs"]
+mod generations;
+
+#[path = "invariants/block_prefilter.rs"]
+mod block_prefilter;
+
+#[path = "invariants/hybrid_backend.rs"]
+mod hybrid_backend;
+
+#[path = "invariants/find_similar.rs"]
+mod find_similar;
+
+#[path = "invariants/buffer_pool.rs"]
+mod buffer_pool;
+
+#[path = "invariants/ingest_provenance.rs"]
+mod ingest_provenance;
+
+#[path = "invariants/namespace_keys.rs"]
+mod namespace_keys;
+
+#[path = "invariants/adaptive_precision.rs"]
+mod adaptive_precision;
+#[path = "invariants/differential_ingest.rs"]
+mod differential_ingest;
+#[path = "invariants/compute.rs"]
+mod compute;
+#[path = "invariants/role_schema.rs"]
+mod role_schema;
+#[path = "invariants/alignment.rs"]
+mod alignment;
+#[path = "invariants/ingest_namespaces.rs"]
+mod ingest_namespaces;
+#[path = "invariants/ingest_stats.rs"]
+mod ingest_stats;
+#[path = "invariants/capacity.rs"]
+mod capacity;
+#[path = "invariants/bench_harness.rs"]
+mod bench_harness;
+#[path = "invariants/hybrid_calibration.rs"]
+mod hybrid_calibration;
+#[path = "invariants/memory_usage.rs"]
+mod memory_usage;
+#[path = "invariants/listing.rs"]
+mod listing;
+#[path = "invariants/engram_info.rs"]
+mod engram_info;
+#[path = "invariants/convert.rs"]
+mod convert;
+#[path = "invariants/shards.rs"]
+mod shards;
+#[path = "invariants/chunk_cache.rs"]
+mod chunk_cache;
+#[path = "invariants/lazy_engram.rs"]
+mod lazy_engram;
+#[path = "invariants/shared_codebook.rs"]
+mod shared_codebook;
+#[path = "invariants/basis_training.rs"]
+mod basis_training;
+#[path = "invariants/outlier_detectors.rs"]
+mod outlier_detectors;
+#[path = "invariants/batch_projection.rs"]
+mod batch_projection;
+#[path = "invariants/codebook_diff.rs"]
+mod codebook_diff;
+#[path = "invariants/chunk_refs.rs"]
+mod chunk_refs;
+#[path = "invariants/chunk_clusters.rs"]
+mod chunk_clusters;
+#[path = "invariants/distributed.rs"]
+mod distributed;
+#[path = "invariants/replica.rs"]
+mod replica;
+#[path = "invariants/merge.rs"]
+mod merge;
+#[path = "invariants/transactions.rs"]
+mod transactions;
+#[path = "invariants/engram_reader.rs"]
+mod engram_reader;
+#[path = "invariants/inodes.rs"]
+mod inodes;
+#[path = "invariants/vfs.rs"]
+mod vfs;
+#[path = "invariants/access_stats.rs"]
+mod access_stats;
+#[path = "invariants/access_policy.rs"]
+mod access_policy;
+#[path = "invariants/seal.rs"]
+mod seal;
+#[path = "invariants/remote_sync.rs"]
+mod remote_sync;
+#[path = "invariants/ingest_filters.rs"]
+mod ingest_filters;
+#[path = "invariants/retention.rs"]
+mod retention;
+#[path = "invariants/load_limits.rs"]
+mod load_limits;
+#[path = "invariants/reproducible_ingest.rs"]
+mod reproducible_ingest;
+#[path = "invariants/ingest_reader.rs"]
+mod ingest_reader;
+#[path = "invariants/encoding_config.rs"]
+mod encoding_config;
+#[path = "invariants/sparse_vec_canonical.rs"]
+mod sparse_vec_canonical;
+#[path = "invariants/scrub.rs"]
+mod scrub;
+#[path = "invariants/counted_root.rs"]
+mod counted_root;
+#[path = "invariants/file_versions.rs"]
+mod file_versions;
+#[path = "invariants/root_strategies.rs"]
+mod root_strategies;
+#[path = "invariants/jobs.rs"]
+mod jobs;
+#[path = "invariants/extract_provenance.rs"]
+mod extract_provenance;
+#[path = "invariants/stream_monitor.rs"]
+mod stream_monitor;
+#[path = "invariants/latency.rs"]
+mod latency;
+#[path = "invariants/profiling.rs"]
+mod profiling;
+#[path = "invariants/logging.rs"]
+mod logging;
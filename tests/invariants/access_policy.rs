@@ -0,0 +1,76 @@
+//! Tests for namespace access policies.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn access_policy_grants_namespaces() {
+    use embeddenator::access::{bearer_token, namespace_of, token_digest, Access, AccessPolicy};
+    use embeddenator::{EmbrFS, EngramFS, EngramReader};
+
+    let policy = AccessPolicy::from_toml(&format!(
+        r#"
+[anonymous]
+read = ["public"]
+
+[[principal]]
+name = "team-a"
+tokens = ["{}"]
+uids = [1001]
+read = ["shared"]
+write = ["team-a"]
+
+[[principal]]
+name = "ops"
+tokens = ["{}"]
+write = ["*"]
+"#,
+        token_digest("a-token"),
+        token_digest("ops-token")
+    ))
+    .unwrap();
+
+    let anonymous = policy.for_token(None).unwrap();
+    assert!(anonymous.allows(Access::Read, "public/index.html"));
+    assert!(!anonymous.allows(Access::Write, "public/index.html"));
+    assert!(!anonymous.allows(Access::Read, "README.md"));
+    assert!(policy.for_token(Some("wrong")).is_none());
+
+    let team_a = policy.for_token(Some("a-token")).unwrap();
+    assert_eq!(policy.for_uid(1001), Some(team_a));
+    assert_eq!(policy.for_uid(0), Some(anonymous));
+    assert!(team_a.allows(Access::Read, "team-a/src/lib.rs"));
+    assert!(team_a.allows(Access::Write, "/team-a/src/lib.rs"));
+    assert!(team_a.allows(Access::Read, "shared/notes.txt"));
+    assert!(!team_a.allows(Access::Write, "shared/notes.txt"));
+    assert!(!team_a.allows(Access::Read, "team-b/x"));
+    assert!(!team_a.is_admin());
+    let ops = policy.for_token(Some("ops-token")).unwrap();
+    assert!(ops.is_admin() && ops.allows(Access::Read, "README.md"));
+
+    assert_eq!(namespace_of("/team-a/src/lib.rs"), "team-a");
+    assert_eq!(namespace_of("README.md"), "");
+    assert_eq!(bearer_token("Bearer  abc "), Some("abc"));
+    assert_eq!(bearer_token("Basic abc"), None);
+
+    // Malformed policies are refused.
+    assert!(AccessPolicy::from_toml("[[principal]]\nname = \"x\"\ntokens = [\"plain\"]\n").is_err());
+    assert!(AccessPolicy::from_toml("[anonymous]\nread = [\"a/b\"]\n").is_err());
+    assert!(AccessPolicy::from_toml("[[principal]]\nname = \"x\"\nuids = [5]\n[[principal]]\nname = \"y\"\nuids = [5]\n").is_err());
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    for path in ["team-a/a.txt", "team-b/b.txt", "public/p.txt", "top.txt"] {
+        fs.ingest_bytes(path.as_bytes(), path.to_string(), &config).unwrap();
+    }
+    let visible: Vec<String> = team_a.readable(&fs.manifest).files.into_iter().map(|f| f.path).collect();
+    assert_eq!(visible, ["team-a/a.txt"]);
+
+    let reader = EngramReader::from_embrfs(fs, config);
+    let mount = EngramFS::from_reader(&reader, true).with_access_policy(policy);
+    let ino = |path: &str| mount.lookup_path(path).unwrap();
+    assert!(mount.permits(1001, 1));
+    assert!(mount.permits(1001, ino("/team-a")) && mount.permits(1001, ino("/team-a/a.txt")));
+    assert!(!mount.permits(1001, ino("/team-b")) && !mount.permits(1001, ino("/team-b/b.txt")));
+    assert!(!mount.permits(1001, ino("/top.txt")));
+    assert!(mount.permits(42, ino("/public/p.txt")) && !mount.permits(42, ino("/team-a/a.txt")));
+}
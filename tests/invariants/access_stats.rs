@@ -0,0 +1,71 @@
+//! Tests for per-file access statistics.
+
+use embeddenator::{ReversibleVSAConfig, SparseVec};
+
+#[test]
+fn access_stats_count_reads_extracts_and_queries() {
+    use embeddenator::embrfs::{SubEngram, SubEngramStore, DEFAULT_CHUNK_SIZE};
+    use embeddenator::{AccessKind, AccessStats, EmbrFS, EngramReader, TieredSubEngramStore};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let config = ReversibleVSAConfig::default();
+    let data: Vec<u8> = (0..3 * DEFAULT_CHUNK_SIZE as u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 9) as u8).collect();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&data, "hot.bin".into(), &config).unwrap();
+    fs.ingest_bytes(b"cold file", "cold.txt".into(), &config).unwrap();
+    let hot = fs.manifest.files[0].chunks.clone();
+
+    let out = tempfile::tempdir().unwrap();
+    EmbrFS::extract_with_access(&fs.engram, &fs.manifest, out.path(), false, &config, None, fs.access_recorder()).unwrap();
+    let stats = fs.access_stats();
+    assert_eq!(stats.files["hot.bin"].extracts, 1);
+    assert_eq!(stats.chunks.len(), 4);
+    assert!(stats.chunks.values().all(|c| c.extracts == 1 && c.reads == 0));
+
+    let recorder = Arc::clone(fs.access_recorder());
+    let reader = EngramReader::from_embrfs(fs, config);
+    reader.read_file("hot.bin").unwrap();
+    reader.read_range("hot.bin", DEFAULT_CHUNK_SIZE, DEFAULT_CHUNK_SIZE + 10).unwrap();
+    reader.prefetch("cold.txt", 0..9).unwrap();
+    let query = reader.engram().codebook[&hot[2]].clone();
+    assert_eq!(reader.query_codebook(&query, 1)[0].id, hot[2]);
+
+    let stats = recorder.snapshot();
+    assert_eq!(stats.files["hot.bin"].reads, 2);
+    assert_eq!(stats.files["cold.txt"].reads, 0, "prefetching is not a read");
+    assert_eq!(stats.chunks[&(hot[0] as u64)].reads, 1);
+    assert_eq!(stats.chunks[&(hot[1] as u64)].reads, 2);
+    assert_eq!(stats.chunks[&(hot[2] as u64)].queries, 1);
+
+    let rows = stats.heatmap(reader.manifest(), 2);
+    assert_eq!(rows.iter().map(|r| r.path.as_str()).collect::<Vec<_>>(), ["hot.bin", "cold.txt"]);
+    assert_eq!(rows[0].cells, [3, 3]);
+
+    // The memory tier keeps sub-engrams whose chunks are accessed.
+    struct Subs(HashMap<String, SubEngram>);
+    impl SubEngramStore for Subs {
+        fn load(&self, id: &str) -> Option<SubEngram> {
+            self.0.get(id).cloned()
+        }
+    }
+    let sub = |id: &str, chunk: usize| {
+        let sub = SubEngram {
+            id: id.to_string(),
+            root: SparseVec::new(),
+            chunk_ids: vec![chunk],
+            chunk_count: 1,
+            children: Vec::new(),
+        };
+        (id.to_string(), sub)
+    };
+    let access = Arc::new(AccessStats::new());
+    access.record_chunks(&[0], AccessKind::Query, Duration::ZERO);
+    let tiered = TieredSubEngramStore::new(Subs([sub("a", 0), sub("b", 1), sub("c", 2)].into()), 2)
+        .with_access_stats(access);
+    for id in ["a", "b", "c"] {
+        tiered.load(id).unwrap();
+    }
+    assert!(tiered.is_cached("a") && !tiered.is_cached("b") && tiered.is_cached("c"));
+}
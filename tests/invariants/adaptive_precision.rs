@@ -0,0 +1,89 @@
+//! Tests for adaptive trit-depth expansion and the depth map engrams keep.
+
+use embeddenator::{DepthMap, DimensionalConfig, EmbrFS, HyperVec, ReversibleVSAConfig, DIM};
+
+fn adaptive_fs(base: u8, max: u8) -> EmbrFS {
+    let mut fsys = EmbrFS::with_config(ReversibleVSAConfig::default(), DimensionalConfig::adaptive(DIM, base, max)).unwrap();
+    let config = fsys.vsa_config();
+    for i in 0..12u8 {
+        let data: Vec<u8> = (0..3000u32).map(|j| (j as u8).wrapping_mul(i | 1) ^ i).collect();
+        fsys.ingest_bytes(&data, format!("f{i}.bin"), &config).unwrap();
+    }
+    fsys
+}
+
+#[test]
+fn adaptive_ingest_keeps_depths_that_decode_exactly() {
+    let mut fsys = adaptive_fs(1, 6);
+    let (basis, _) = fsys.train_basis(3);
+    let report = fsys.adapt_precision(&basis);
+    assert_eq!(report.chunks, fsys.engram.codebook.len());
+    assert!(report.expanded_coefficients > 0, "{report:?}");
+    assert_eq!(report.mean_quality, 1.0);
+    let precision = fsys.engram.precision.clone().expect("depths were raised");
+    assert!(precision.coefficients.values().all(|&d| d > 1 && d <= 6));
+
+    // The map survives a save and load, and decodes every chunk exactly.
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("root.engram");
+    fsys.save_engram(&path).unwrap();
+    let mut loaded = EmbrFS::new();
+    loaded.engram = EmbrFS::load_engram(&path).unwrap();
+    loaded.manifest = fsys.manifest.clone();
+    assert_eq!(loaded.engram.precision.as_ref(), Some(&precision));
+
+    let encoder = loaded.differential_encoder(&basis);
+    let config = encoder.config.clone();
+    for vec in loaded.engram.codebook.values() {
+        let data = HyperVec::from_sparse(config.clone(), vec);
+        let encoding = encoder.encode(&data);
+        assert!(encoding.expanded.is_empty(), "the kept map covers every chunk");
+        let decoded = encoder.decode(&encoding);
+        assert_eq!(decoded.dimensions.keys().collect::<Vec<_>>(), data.dimensions.keys().collect::<Vec<_>>());
+        assert!(data.dimensions.keys().all(|&d| decoded.get(d) == data.get(d)));
+    }
+}
+
+#[test]
+fn fixed_depths_never_expand() {
+    let mut fsys = EmbrFS::new();
+    let config = fsys.vsa_config();
+    fsys.ingest_bytes(b"uniform depth", "a.txt".into(), &config).unwrap();
+    let (basis, _) = fsys.train_basis(1);
+    assert_eq!(fsys.adapt_precision(&basis).chunks, 0);
+    assert!(fsys.engram.precision.is_none());
+}
+
+#[test]
+fn precision_and_root_counts_round_trip_in_any_combination() {
+    let td = tempfile::tempdir().unwrap();
+    let depths = DepthMap {
+        dimensions: [(7, 4), (9000, 3)].into_iter().collect(),
+        coefficients: [(0, 5)].into_iter().collect(),
+    };
+    for (counts, precision) in [(false, false), (true, false), (false, true), (true, true)] {
+        let mut fsys = adaptive_fs(2, 6);
+        if counts {
+            fsys.engram.count_root();
+        }
+        fsys.engram.precision = precision.then(|| depths.clone());
+        let path = td.path().join(format!("{counts}-{precision}.engram"));
+        fsys.save_engram(&path).unwrap();
+        let loaded = EmbrFS::load_engram(&path).unwrap();
+        assert_eq!(loaded.root_counts.is_some(), counts);
+        assert_eq!(loaded.precision, fsys.engram.precision);
+        assert_eq!(loaded.codebook.len(), fsys.engram.codebook.len());
+    }
+}
+
+#[cfg(feature = "proto")]
+#[test]
+fn precision_survives_the_wire_format() {
+    let mut fsys = adaptive_fs(1, 6);
+    let (basis, _) = fsys.train_basis(2);
+    fsys.adapt_precision(&basis);
+    let bytes = embeddenator::wire::encode_engram(&fsys.engram).unwrap();
+    let decoded = embeddenator::wire::decode_engram(&bytes).unwrap();
+    assert!(decoded.precision.is_some());
+    assert_eq!(decoded.precision, fsys.engram.precision);
+}
@@ -0,0 +1,142 @@
+//! Tests for aligning one engram's vector space with another's.
+
+use embeddenator::retrieval::align::{anchor_pairs, AlignOptions, Alignment};
+use embeddenator::{EmbrFS, EngramReader, FederatedIndex, DIM};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+/// A seeded signed relabelling of the dimensions, as a differently seeded
+/// encoder would give.
+struct Relabel {
+    perm: Vec<usize>,
+    flip: Vec<bool>,
+}
+
+impl Relabel {
+    fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut perm: Vec<usize> = (0..DIM).collect();
+        perm.shuffle(&mut rng);
+        let flip = (0..DIM).map(|_| rng.gen()).collect();
+        Self { perm, flip }
+    }
+
+    fn apply(&self, mut fsys: EmbrFS) -> EmbrFS {
+        for vec in fsys.engram.codebook.values_mut() {
+            let mut out = embeddenator::SparseVec::new();
+            for (dims, negative) in [(&vec.pos, false), (&vec.neg, true)] {
+                for &d in dims {
+                    let target = if negative != self.flip[d] { &mut out.neg } else { &mut out.pos };
+                    target.push(self.perm[d]);
+                }
+            }
+            out.pos.sort_unstable();
+            out.neg.sort_unstable();
+            *vec = out;
+        }
+        fsys
+    }
+}
+
+fn corpus(files: usize) -> EmbrFS {
+    let mut fsys = EmbrFS::new();
+    let config = fsys.vsa_config();
+    let mut rng = StdRng::seed_from_u64(42);
+    for i in 0..files {
+        let data: Vec<u8> = (0..1024).map(|_| rng.gen()).collect();
+        fsys.ingest_bytes(&data, format!("f{i:02}.bin"), &config).unwrap();
+    }
+    fsys
+}
+
+#[test]
+fn relabelled_space_is_learned_as_a_permutation() {
+    let target = corpus(200);
+    let source = Relabel::new(7).apply(corpus(200));
+    let pairs: Vec<_> = anchor_pairs(&source.manifest, &target.manifest)
+        .into_iter()
+        .map(|(s, t)| (&source.engram.codebook[&s], &target.engram.codebook[&t]))
+        .collect();
+    assert_eq!(pairs.len(), target.engram.codebook.len());
+
+    // Learn from the first 180 chunks and check on the rest, against each
+    // chunk's cosine with itself.
+    let alignment = Alignment::learn(&pairs[..180], AlignOptions::default());
+    assert_eq!(alignment.anchors, 180);
+    assert!(alignment.coverage > 0.95, "{}", alignment.coverage);
+    assert!(alignment.fit > 0.95, "{}", alignment.fit);
+    for (s, t) in &pairs[180..] {
+        let before = s.cosine(t);
+        let after = alignment.project(s).cosine(t) / t.cosine(t);
+        assert!(before.abs() < 0.1 && after > 0.9, "{before} -> {after}");
+    }
+}
+
+#[test]
+fn alignment_needs_shared_files_and_round_trips() {
+    let target = corpus(24);
+    let source = Relabel::new(9).apply(corpus(24));
+    let alignment = Alignment::between(
+        (&source.engram, &source.manifest),
+        (&target.engram, &target.manifest),
+        AlignOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(alignment.anchors, 24);
+
+    let td = tempfile::tempdir().unwrap();
+    let path = embeddenator::retrieval::align::default_alignment_path(td.path().join("source.engram"));
+    alignment.save(&path).unwrap();
+    // JSON may round the weights in their last bit; the map is what matters.
+    let loaded = Alignment::load(&path).unwrap();
+    assert_eq!((loaded.anchors, loaded.ties()), (alignment.anchors, alignment.ties()));
+    for v in source.engram.codebook.values() {
+        assert_eq!(loaded.project(v), alignment.project(v));
+    }
+
+    let unrelated = EmbrFS::new();
+    let err = Alignment::between(
+        (&unrelated.engram, &unrelated.manifest),
+        (&target.engram, &target.manifest),
+        AlignOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn federated_search_finds_chunks_of_an_aligned_engram() {
+    let target = corpus(200);
+    let relabel = Relabel::new(11);
+    let mut source = relabel.apply(corpus(200));
+    // Only the source holds this file; its vector is in the source space.
+    let config = source.vsa_config();
+    let mut own = EmbrFS::new();
+    let mut rng = StdRng::seed_from_u64(99);
+    let data: Vec<u8> = (0..1024).map(|_| rng.gen()).collect();
+    own.ingest_bytes(&data, "own.bin".to_string(), &config).unwrap();
+    let own = relabel.apply(own);
+    let id = source.manifest.total_chunks;
+    source.engram.codebook.insert(id, own.engram.codebook[&0].clone());
+    let mut entry = own.manifest.files[0].clone();
+    entry.chunks = vec![id];
+    source.manifest.files.push(entry);
+
+    let alignment = Alignment::between(
+        (&source.engram, &source.manifest),
+        (&target.engram, &target.manifest),
+        AlignOptions::default(),
+    )
+    .unwrap();
+    let mut federation = FederatedIndex::new();
+    federation
+        .add_aligned_engram("source", Arc::new(EngramReader::from_embrfs(source, config.clone())), &alignment)
+        .unwrap();
+    let hits = federation.query(&data, 3);
+    assert_eq!((hits[0].chunk, hits[0].paths.as_slice()), (id, ["own.bin".to_string()].as_slice()));
+    // A chunk's cosine with itself is about 2/3, its trits overlapping.
+    assert!(hits[0].cosine > 0.5 && hits[1].cosine < 0.1, "{hits:?}");
+}
+
@@ -0,0 +1,160 @@
+//! Tests for the append-only engram log: delta saves, tombstones and
+//! torn-tail recovery.
+
+use embeddenator::append_log::{AppendLog, PendingRecord, RecordKind};
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use std::fs::{self, OpenOptions};
+
+fn record(kind: RecordKind, id: u64, payload: &[u8]) -> PendingRecord {
+    PendingRecord {
+        kind,
+        id,
+        payload: payload.to_vec(),
+    }
+}
+
+#[test]
+fn delta_save_writes_only_changed_records() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("a.txt"), "alpha ".repeat(2000)).unwrap();
+    let config = ReversibleVSAConfig::default();
+    let log_path = td.path().join("root.edna");
+
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_directory(&input, false, &config).unwrap();
+    let first = fsys.save_append_log(&log_path).unwrap();
+    assert_eq!(first.chunks_written, fsys.engram.codebook.len());
+    assert!(first.manifest_written && first.meta_written);
+
+    let unchanged = fsys.save_append_log(&log_path).unwrap();
+    assert_eq!(unchanged.bytes_appended, 0);
+    assert_eq!(unchanged.chunks_written, 0);
+
+    let before = fsys.engram.codebook.len();
+    fs::write(input.join("b.txt"), "bravo").unwrap();
+    fsys.ingest_file(input.join("b.txt"), "b.txt".to_string(), false, &config)
+        .unwrap();
+    let delta = fsys.save_append_log(&log_path).unwrap();
+    assert_eq!(delta.chunks_written, fsys.engram.codebook.len() - before);
+    assert!(delta.manifest_written);
+    assert!(delta.bytes_appended < first.bytes_appended);
+
+    let (engram, manifest) = EmbrFS::load_append_log(&log_path).unwrap();
+    let out = td.path().join("out");
+    EmbrFS::extract(&engram, &manifest, &out, false, &config).unwrap();
+    assert_eq!(fs::read(out.join("a.txt")).unwrap(), fs::read(input.join("a.txt")).unwrap());
+    assert_eq!(fs::read(out.join("b.txt")).unwrap(), b"bravo");
+}
+
+#[test]
+fn tombstones_hide_chunks_after_reopen() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("log.edna");
+
+    let mut log = AppendLog::open(&path).unwrap();
+    assert!(log.is_empty());
+    log.append(vec![record(RecordKind::Chunk, 1, b"one"), record(RecordKind::Chunk, 2, b"two")])
+        .unwrap();
+    log.append(vec![record(RecordKind::Tombstone, 1, b"")]).unwrap();
+    log.append(vec![record(RecordKind::Chunk, 2, b"two v2")]).unwrap();
+    drop(log);
+
+    let log = AppendLog::open(&path).unwrap();
+    assert!(log.chunk(1).is_none());
+    assert_eq!(log.read(log.chunk(2).unwrap()).unwrap(), b"two v2");
+
+    let compacted = log.compact_to(td.path().join("compact.edna")).unwrap();
+    assert!(compacted.len() < log.len());
+    assert_eq!(compacted.read(compacted.chunk(2).unwrap()).unwrap(), b"two v2");
+    assert!(compacted.chunk(1).is_none());
+}
+
+#[test]
+fn torn_tail_recovers_last_complete_commit() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("log.edna");
+
+    let mut log = AppendLog::open(&path).unwrap();
+    log.append(vec![record(RecordKind::Chunk, 7, b"committed")]).unwrap();
+    let committed_len = log.len();
+    log.append(vec![record(RecordKind::Chunk, 8, &[0xab; 512])]).unwrap();
+    drop(log);
+
+    // Simulate a crash partway through the second save.
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(committed_len + 100).unwrap();
+    drop(file);
+
+    let mut log = AppendLog::open(&path).unwrap();
+    assert_eq!(log.recovered_bytes(), 100);
+    assert_eq!(log.len(), committed_len);
+    assert_eq!(fs::metadata(&path).unwrap().len(), committed_len);
+    assert!(log.chunk(8).is_none());
+    assert_eq!(log.read(log.chunk(7).unwrap()).unwrap(), b"committed");
+
+    // The log stays appendable after recovery.
+    log.append(vec![record(RecordKind::Chunk, 9, b"after")]).unwrap();
+    drop(log);
+    let log = AppendLog::open(&path).unwrap();
+    assert_eq!(log.recovered_bytes(), 0);
+    assert_eq!(log.read(log.chunk(9).unwrap()).unwrap(), b"after");
+}
+
+#[test]
+fn corrupted_record_is_rejected_on_read() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("log.edna");
+
+    let mut log = AppendLog::open(&path).unwrap();
+    log.append(vec![record(RecordKind::Chunk, 1, b"payload bytes")]).unwrap();
+    let r = log.chunk(1).unwrap();
+    drop(log);
+
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[r.offset as usize + 16 + 3] ^= 0xff;
+    fs::write(&path, &bytes).unwrap();
+
+    let log = AppendLog::open(&path).unwrap();
+    let err = log.read(log.chunk(1).unwrap()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains(&format!("byte {}", r.offset)), "{err}");
+}
+
+#[test]
+fn compact_keeps_live_state_and_orders_chunks_by_file() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("root.edna");
+    let config = ReversibleVSAConfig::default();
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_bytes(&[7u8; 10_000], "z/last.bin".into(), &config).unwrap();
+    fsys.save_append_log(&path).unwrap();
+    fsys.ingest_bytes(b"alpha", "a/first.txt".into(), &config).unwrap();
+    fsys.save_append_log(&path).unwrap();
+    fsys.ingest_bytes(b"alpha, revised", "a/first.txt".into(), &config).unwrap();
+    fsys.save_append_log(&path).unwrap();
+    let before = fs::metadata(&path).unwrap().len();
+
+    let stats = EmbrFS::compact(&path).unwrap();
+    assert_eq!(stats.bytes_before, before);
+    assert_eq!(stats.bytes_after, fs::metadata(&path).unwrap().len());
+    assert!(stats.bytes_reclaimed() > 0, "{stats:?}");
+
+    let (engram, manifest) = EmbrFS::load_append_log(&path).unwrap();
+    assert_eq!(stats.chunks, engram.codebook.len());
+    let out = td.path().join("out");
+    EmbrFS::extract(&engram, &manifest, &out, false, &config).unwrap();
+    assert_eq!(fs::read(out.join("a/first.txt")).unwrap(), b"alpha, revised");
+    assert_eq!(fs::read(out.join("z/last.bin")).unwrap(), vec![7u8; 10_000]);
+
+    // a/first.txt sorts first, so its chunk now precedes z/last.bin's.
+    let log = AppendLog::open(&path).unwrap();
+    let offset = |file: &str| {
+        let entry = manifest.files.iter().find(|f| f.path == file).unwrap();
+        log.chunk(entry.chunks[0] as u64).unwrap().offset
+    };
+    assert!(offset("a/first.txt") < offset("z/last.bin"));
+
+    assert!(EmbrFS::compact(td.path().join("missing.edna")).is_err());
+}
@@ -0,0 +1,84 @@
+//! Tests for training a codebook basis from chunk prototypes.
+
+#[test]
+fn trained_basis_shrinks_differential_residuals() {
+    use embeddenator::codebook::{self, Codebook};
+    use embeddenator::{DimensionalConfig, HyperVec, SparseVec};
+
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    // Two clusters of noisy copies of a prototype, drawn from a fixed seed
+    // so the residual sizes below are the same on every run.
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut prototype = || {
+        let mut dims: Vec<usize> = (0..embeddenator::DIM).collect();
+        dims.shuffle(&mut rng);
+        let sparsity = embeddenator::DIM / 100;
+        let (mut pos, mut neg) = (dims[..sparsity].to_vec(), dims[sparsity..2 * sparsity].to_vec());
+        pos.sort_unstable();
+        neg.sort_unstable();
+        SparseVec { pos, neg }
+    };
+    let prototypes = [prototype(), prototype()];
+    let samples: Vec<SparseVec> = (0..40)
+        .map(|i| {
+            let proto = &prototypes[i % 2];
+            let mut v = proto.clone();
+            v.pos.retain(|&d| (d + i) % 10 != 0);
+            v.neg.retain(|&d| (d + i) % 10 != 0);
+            v
+        })
+        .collect();
+
+    let mut basis = Codebook::new(embeddenator::DIM);
+    let report = basis.train_basis(&samples, 2);
+    assert_eq!(report.samples, 40);
+    assert_eq!(report.basis_size, 2);
+    assert!(report.converged);
+    assert!(report.mean_similarity > 0.8, "{report:?}");
+    let weights: f64 = basis.basis_vectors.iter().map(|b| b.weight).sum();
+    assert!((weights - 1.0).abs() < 1e-9);
+
+    let mut again = Codebook::new(embeddenator::DIM);
+    assert_eq!(again.train_basis(&samples, 2), report);
+
+    // Residuals against the trained basis are much smaller than the data.
+    let config = DimensionalConfig::default();
+    let encoder = basis.differential_encoder(config.clone());
+    let untrained = Codebook::new(embeddenator::DIM).differential_encoder(config.clone());
+    for sample in samples.iter().take(4) {
+        let data = HyperVec::from_sparse(config.clone(), sample);
+        let trained = encoder.encode(&data);
+        assert!(trained.residual.nnz() * 4 < data.nnz(), "{} of {}", trained.residual.nnz(), data.nnz());
+        assert_eq!(untrained.encode(&data).residual.nnz(), data.nnz());
+        let decoded = encoder.decode(&trained);
+        assert_eq!(decoded.dimensions.keys().collect::<Vec<_>>(), data.dimensions.keys().collect::<Vec<_>>());
+        assert!(data.dimensions.keys().all(|&d| decoded.get(d) == data.get(d)));
+    }
+
+    // More clusters than samples is capped at the samples.
+    let mut small = Codebook::new(embeddenator::DIM);
+    assert_eq!(small.train_basis(&samples[..3], 10).basis_size, 3);
+
+    // A basis trained at ingest is saved beside the engram.
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    for i in 0..6u8 {
+        std::fs::write(src.join(format!("f{i}.bin")), vec![i; 9000]).unwrap();
+    }
+    let mut embrfs = embeddenator::EmbrFS::new();
+    embrfs
+        .ingest_directory(&src, false, &embeddenator::ReversibleVSAConfig::default())
+        .unwrap();
+    let (trained, report) = embrfs.train_basis(3);
+    assert_eq!(report.samples, embrfs.engram.codebook.len());
+    assert!(report.basis_size <= 3 && report.basis_size > 0);
+    let path = codebook::default_basis_path(tmp.path().join("x.engram"));
+    trained.save(&path, Default::default()).unwrap();
+    let loaded = Codebook::load(&path).unwrap();
+    assert_eq!(loaded.basis_vectors.len(), report.basis_size);
+    assert_eq!(loaded.basis_vectors[0].vector.pos, trained.basis_vectors[0].vector.pos);
+}
@@ -0,0 +1,55 @@
+//! Tests for batched codebook projection.
+
+#[test]
+fn codebook_project_batch_matches_project() {
+    use embeddenator::{Codebook, ProjectionBackend, SparseVec};
+
+    // A basis learned from the 64-byte chunks the inputs are made of, so
+    // projections find matches.
+    let blocks: Vec<Vec<u8>> = (0..3u8).map(|b| (0..64u8).map(|i| i.wrapping_mul(7) ^ (b * 50)).collect()).collect();
+    let samples: Vec<SparseVec> = blocks.iter().map(|b| SparseVec::from_bytes(b)).collect();
+    let mut codebook = Codebook::new(embeddenator::DIM);
+    codebook.train_basis(&samples, 3);
+
+    let inputs: Vec<Vec<u8>> = (0..6)
+        .map(|i| {
+            let mut data: Vec<u8> = (0..4).flat_map(|j| blocks[(i + j) % 3].clone()).collect();
+            data.extend((0..40u8).map(|x| x.wrapping_mul(97) ^ i as u8));
+            data
+        })
+        .collect();
+    let batch = codebook.project_batch(&inputs);
+    assert_eq!(batch.len(), inputs.len());
+    for (data, batched) in inputs.iter().zip(&batch) {
+        let single = codebook.project(data);
+        assert!(!batched.coefficients.is_empty());
+        assert_eq!(batched.coefficients, single.coefficients);
+        assert_eq!(batched.residual, single.residual);
+        let positions = |r: &embeddenator::ProjectionResult| r.outliers.iter().map(|o| o.position).collect::<Vec<_>>();
+        assert_eq!(positions(batched), positions(&single));
+    }
+
+    // Coefficients carry the sparse cosine of chunk and basis vector.
+    for (&key, word) in &batch[0].coefficients {
+        let basis = &codebook.basis_vectors[(key / 1000) as usize];
+        let chunk = &inputs[0][(key % 1000) as usize * 64..][..64];
+        let sim = SparseVec::from_bytes(chunk).cosine(&basis.vector);
+        assert_eq!(word.decode(), (sim * 1000.0) as i64);
+    }
+    assert!(codebook.project_batch::<Vec<u8>>(&[]).is_empty());
+
+    // Every backend gives the same answer, including for empty inputs and
+    // an empty basis (the GPU one falls back to the CPU without a device).
+    let mut with_empty = inputs.clone();
+    with_empty.insert(2, Vec::new());
+    for codebook in [codebook.clone(), Codebook::new(embeddenator::DIM)] {
+        let expected: Vec<_> = with_empty.iter().map(|d| codebook.project(d)).collect();
+        for backend in [ProjectionBackend::Cpu, ProjectionBackend::Auto, ProjectionBackend::Cuda] {
+            let got = codebook.project_batch_on(&with_empty, backend);
+            for (got, want) in got.iter().zip(&expected) {
+                assert_eq!(got.coefficients, want.coefficients, "{backend}");
+                assert_eq!(got.residual, want.residual, "{backend}");
+            }
+        }
+    }
+}
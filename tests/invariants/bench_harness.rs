@@ -0,0 +1,40 @@
+//! Tests for the benchmark harness: reports and comparisons.
+
+#[test]
+fn bench_harness_reports_and_compares() {
+    use embeddenator::bench::{self, BenchOptions};
+    use std::time::Duration;
+
+    let opts = BenchOptions {
+        dims: vec![256, 4096],
+        min_time: Duration::from_micros(200),
+        corpus_bytes: 64 << 10,
+        ..BenchOptions::default()
+    };
+    let report = bench::run(&opts).unwrap();
+    assert_eq!(report.micro.len(), 2 * 15);
+    for repr in ["sparse", "packed", "bitsliced", "block_sparse"] {
+        assert!(report.micro.iter().any(|m| m.repr == repr && m.op == "bind" && m.dim == 4096));
+    }
+    assert!(report.micro.iter().all(|m| m.iterations > 0 && m.ns_per_op > 0.0));
+    let names: Vec<&str> = report.macro_.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["ingest", "extract"]);
+    assert_eq!(report.macro_[0].bytes, 64 << 10);
+
+    let json = serde_json::to_string(&report).unwrap();
+    assert!(json.contains("\"macro\":["));
+    let parsed: bench::BenchReport = serde_json::from_str(&json).unwrap();
+    // JSON float parsing may be off in the last bit.
+    assert!(report.regressions(&parsed, 1e-9).is_empty());
+
+    let mut faster = parsed;
+    faster.micro[0].ns_per_op /= 2.0;
+    faster.macro_[1].mib_per_sec *= 2.0;
+    let regressions = report.regressions(&faster, 0.5);
+    assert_eq!(regressions.len(), 2);
+    assert!(regressions.iter().all(|r| (r.slowdown - 1.0).abs() < 1e-9));
+    assert_eq!(regressions[1].name, "macro/extract");
+
+    let bad = BenchOptions { density: 1.5, ..BenchOptions::quick() };
+    assert!(bench::run(&bad).is_err());
+}
@@ -0,0 +1,77 @@
+//! Block-sparse prefiltering: candidates streamed from a scannable store.
+
+use embeddenator::{
+    BlockPrefilterIndex, BlockPrefilterOptions, CandidateGenerator, PrefilteredStore,
+    ScannableVectorStore, SparseVec, DIM,
+};
+use std::collections::HashMap;
+
+/// Forty pseudo-random words, distinct per document.
+fn document(i: usize) -> String {
+    (0..40).map(|j| format!("w{} ", (i * 7_919 + j * 104_729) % 100_003)).collect()
+}
+
+fn corpus(n: usize) -> HashMap<usize, SparseVec> {
+    (0..n)
+        .map(|i| (i * 2, SparseVec::from_data_similarity(document(i).as_bytes(), 4, 200)))
+        .collect()
+}
+
+#[test]
+fn hashmap_scan_pages_in_id_order() {
+    let store = corpus(10);
+    let page: Vec<usize> = store.scan(5, 3).unwrap().into_iter().map(|(id, _)| id).collect();
+    assert_eq!(page, [6, 8, 10]);
+    assert!(store.scan(100, 3).unwrap().is_empty());
+}
+
+#[test]
+fn every_vector_is_its_own_candidate() {
+    let store = corpus(200);
+    let index = BlockPrefilterIndex::build(&store, DIM, BlockPrefilterOptions::default()).unwrap();
+    assert_eq!(index.len(), 200);
+    for (&id, vec) in store.iter().take(20) {
+        let candidates = index.candidates(vec, 10);
+        assert!(candidates.len() <= 10);
+        assert_eq!(candidates.first(), Some(&id), "{id} should share every block with itself");
+    }
+}
+
+#[test]
+fn building_in_small_batches_matches_one_pass() {
+    let store = corpus(97);
+    let opts = BlockPrefilterOptions::default();
+    let whole = BlockPrefilterIndex::build(&store, DIM, opts).unwrap();
+    let batched = BlockPrefilterIndex::build(&store, DIM, BlockPrefilterOptions { scan_batch: 7, ..opts }).unwrap();
+    assert_eq!(batched.len(), 97);
+    let query = &store[&42];
+    assert_eq!(whole.candidates(query, 50), batched.candidates(query, 50));
+}
+
+#[test]
+fn strict_intersection_narrows_the_candidates() {
+    let store = corpus(300);
+    let index = BlockPrefilterIndex::build(&store, DIM, BlockPrefilterOptions::default()).unwrap();
+    let query = &store[&10];
+    let loose = index.candidates_with_options(query, 1, usize::MAX);
+    let strict = index.candidates_with_options(query, 8, usize::MAX);
+    assert!(strict.contains(&10));
+    assert!(strict.len() < loose.len());
+    assert!(strict.iter().all(|id| loose.contains(id)));
+    assert!(index.candidates_with_options(query, 2, 0).is_empty());
+}
+
+#[test]
+fn prefiltered_store_scores_candidates_by_cosine() {
+    let mut store = corpus(150);
+    // A near-duplicate of document 7 should come right after it.
+    let near = format!("{}!", document(7));
+    store.insert(1_000, SparseVec::from_data_similarity(near.as_bytes(), 4, 200));
+    let prefiltered = PrefilteredStore::build(store).unwrap().with_oversample(20);
+    let query = prefiltered.store()[&14].clone();
+    let hits = prefiltered.candidates(&query, 3);
+    assert_eq!(hits[0].0, 14);
+    assert!((hits[0].1 - 1.0).abs() < 1e-9);
+    assert_eq!(hits[1].0, 1_000);
+    assert!(hits.windows(2).all(|w| w[0].1 >= w[1].1));
+}
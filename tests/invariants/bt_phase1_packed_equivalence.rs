@@ -1,10 +1,6 @@
 #![cfg(feature = "bt-phase-1")]
 
-// Use an explicit path to avoid ambiguity between helper modules.
-#[path = "../support/bt_migration.rs"]
-mod bt_migration;
-
-use bt_migration::{mk_random_sparsevec, sparse_dot};
+use crate::support::bt_migration::{mk_random_sparsevec, sparse_dot};
 use embeddenator::{PackedTritVec, SparseVec, DIM};
 use rand::SeedableRng;
 
@@ -0,0 +1,71 @@
+//! Tests for the size-classed buffer pool shared by the read paths.
+
+use embeddenator::buffer_pool::MAX_POOLED_BYTES;
+use embeddenator::{BufferPool, EmbrFS, ReversibleVSAConfig};
+
+#[test]
+fn returned_buffers_serve_requests_of_their_class() {
+    let pool = BufferPool::new(4);
+    let mut buf = pool.take(100);
+    assert_eq!(buf.capacity(), 4096);
+    buf.extend_from_slice(b"stale");
+    let ptr = buf.as_ptr();
+    pool.give(buf);
+
+    let again = pool.take(4096);
+    assert_eq!(again.as_ptr(), ptr);
+    assert!(again.is_empty());
+    // A page-sized buffer cannot serve a larger class.
+    pool.give(again);
+    assert!(pool.take(5000).capacity() >= 8192);
+
+    let stats = pool.stats();
+    assert_eq!((stats.hits, stats.misses, stats.recycled), (1, 2, 2));
+    assert_eq!((stats.idle_buffers, stats.idle_bytes), (1, 4096));
+}
+
+#[test]
+fn full_classes_and_odd_sizes_are_not_kept() {
+    let pool = BufferPool::new(1);
+    pool.give(Vec::with_capacity(4096));
+    pool.give(Vec::with_capacity(4096));
+    pool.give(Vec::with_capacity(100));
+    pool.give(Vec::with_capacity(4 * MAX_POOLED_BYTES));
+    let stats = pool.stats();
+    assert_eq!((stats.recycled, stats.discarded, stats.idle_buffers), (1, 3, 1));
+
+    // Past the largest class requests are allocated exactly.
+    assert_eq!(pool.take(MAX_POOLED_BYTES + 1).capacity(), MAX_POOLED_BYTES + 1);
+}
+
+#[test]
+fn leases_return_on_drop_unless_kept() {
+    let pool = BufferPool::new(4);
+    {
+        let mut lease = pool.lease(10_000);
+        lease.extend_from_slice(&[1; 10_000]);
+    }
+    assert_eq!(pool.stats().idle_buffers, 1);
+    let kept = pool.lease(10_000).into_vec();
+    assert_eq!(kept.capacity(), 16_384);
+    assert_eq!(pool.stats().idle_buffers, 0);
+    pool.clear();
+}
+
+#[test]
+fn extraction_recycles_into_the_global_pool() {
+    let td = tempfile::tempdir().unwrap();
+    let config = ReversibleVSAConfig::default();
+    let mut fsys = EmbrFS::new();
+    for i in 0..4 {
+        let data = format!("file {i} ").repeat(1_500);
+        fsys.ingest_bytes(data.as_bytes(), format!("f{i}.txt"), &config).unwrap();
+    }
+    let before = BufferPool::global().stats().recycled;
+    EmbrFS::extract(&fsys.engram, &fsys.manifest, td.path().join("out"), false, &config).unwrap();
+    assert!(BufferPool::global().stats().recycled > before);
+    for i in 0..4 {
+        let back = std::fs::read(td.path().join(format!("out/f{i}.txt"))).unwrap();
+        assert_eq!(back, format!("file {i} ").repeat(1_500).into_bytes());
+    }
+}
@@ -0,0 +1,83 @@
+//! Tests for batched whole-file I/O.
+
+use embeddenator::bulk_io::{read_files, Backend, BulkFileWriter};
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use std::fs;
+use std::path::PathBuf;
+
+fn contents(i: usize) -> Vec<u8> {
+    // Includes empty files and ones spanning several batches' worth of bytes.
+    (0..(i * 997) % 20_000).map(|j| (i + j) as u8).collect()
+}
+
+fn round_trip(writer: BulkFileWriter) {
+    let td = tempfile::tempdir().unwrap();
+    let paths: Vec<PathBuf> = (0..300).map(|i| td.path().join(format!("{i}.bin"))).collect();
+    fs::write(&paths[0], b"stale contents that must be replaced").unwrap();
+
+    let mut writer = writer.with_batch_limits(64 * 1024, 50);
+    for (i, path) in paths.iter().enumerate() {
+        writer.write_file(path.clone(), contents(i)).unwrap();
+    }
+    let stats = writer.finish().unwrap();
+    assert_eq!(stats.files, paths.len() as u64);
+    assert!(stats.batches > 1);
+
+    for (i, data) in read_files(&paths).unwrap().into_iter().enumerate() {
+        assert_eq!(data, contents(i), "file {i}");
+        assert_eq!(fs::read(&paths[i]).unwrap(), data);
+    }
+}
+
+#[test]
+fn std_backend_round_trips() {
+    let writer = BulkFileWriter::std();
+    assert_eq!(writer.backend(), Backend::Std);
+    round_trip(writer);
+}
+
+#[test]
+fn default_backend_round_trips() {
+    let writer = BulkFileWriter::new();
+    if !cfg!(all(feature = "io-uring", target_os = "linux")) {
+        assert_eq!(writer.backend(), Backend::Std);
+    }
+    round_trip(writer);
+}
+
+#[test]
+fn missing_directory_is_reported_with_path() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("missing/dir/file.bin");
+    let mut writer = BulkFileWriter::new();
+    writer.write_file(path, b"x".to_vec()).unwrap();
+    let err = writer.finish().unwrap_err();
+    assert!(err.to_string().contains("file.bin"), "unexpected error: {err}");
+
+    let err = read_files(&[td.path().join("absent.bin")]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn extract_mixes_bulk_and_streamed_files() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    fs::create_dir_all(input.join("nested")).unwrap();
+    for i in 0..20 {
+        fs::write(input.join(format!("nested/small{i}.txt")), format!("small file {i}\n").repeat(i + 1)).unwrap();
+    }
+    let big: Vec<u8> = (0..1_200_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(input.join("big.bin"), &big).unwrap();
+
+    let config = ReversibleVSAConfig::default();
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_directory(&input, false, &config).unwrap();
+    let out = td.path().join("out");
+    EmbrFS::extract(&fsys.engram, &fsys.manifest, &out, false, &config).unwrap();
+
+    assert_eq!(fs::read(out.join("big.bin")).unwrap(), big);
+    for i in 0..20 {
+        let name = format!("nested/small{i}.txt");
+        assert_eq!(fs::read(out.join(&name)).unwrap(), fs::read(input.join(&name)).unwrap());
+    }
+}
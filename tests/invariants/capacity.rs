@@ -0,0 +1,109 @@
+//! Tests for root capacity monitoring and membership checks.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn capacity_report_tracks_root_saturation() {
+    use embeddenator::capacity::{self, CapacityMonitor, CapacityReport};
+    use embeddenator::embrfs::EmbrFS;
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    embrfs.capacity_monitor = None;
+    let empty = embrfs.capacity_report();
+    assert_eq!((empty.members, empty.sampled, empty.saturation), (0, 0, 0.0));
+    assert!(empty.remaining > 1000);
+
+    let mut x = 0x9e37_79b9_7f4a_7c15u64;
+    let mut ingest = |embrfs: &mut EmbrFS, name: &str| {
+        let data: Vec<u8> = (0..4096 * 16)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        embrfs.ingest_bytes(&data, name.to_string(), &config).unwrap();
+    };
+
+    ingest(&mut embrfs, "a.bin");
+    let small = embrfs.capacity_report();
+    assert_eq!((small.members, small.sampled), (16, 16));
+    assert!((small.expected_cosine - 0.25).abs() < 1e-12);
+    assert!((small.noise_floor - 0.01).abs() < 1e-12);
+    assert!(small.observed_cosine > small.threshold);
+    assert!(small.min_cosine <= small.observed_cosine);
+    assert!(small.saturation < 1.0 && small.remaining > 0);
+
+    for i in 0..15 {
+        ingest(&mut embrfs, &format!("b{i}.bin"));
+    }
+    let large = embrfs.capacity_report();
+    assert_eq!(large.members, 256);
+    assert!(large.saturation > small.saturation);
+    assert!(large.remaining < small.remaining);
+    assert!(large.below_threshold > 0);
+    assert!(large.root_density > small.root_density);
+
+    let sampled = CapacityReport::analyze_sampled(&embrfs.engram, 10);
+    assert_eq!((sampled.members, sampled.sampled), (256, 10));
+
+    // The monitor checks once enough chunks arrived, then waits for growth.
+    let mut monitor = CapacityMonitor::new(capacity::DEFAULT_WARN_SATURATION);
+    let report = monitor.observe(&embrfs.engram).unwrap();
+    assert!(report.is_near_capacity(monitor.warn_at));
+    assert!(monitor.observe(&embrfs.engram).is_none());
+}
+
+#[test]
+fn probably_contains_checks_membership_against_root() {
+    use embeddenator::capacity::{self, MembershipResult, MembershipVerdict};
+    use embeddenator::embrfs::EmbrFS;
+
+    let mut x = 0x2545_f491_4f6c_dd1du64;
+    let mut random = |len: usize| -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    };
+    let stored = random(4096 * 4);
+    let unrelated = random(4096 * 4);
+
+    let mut embrfs = EmbrFS::new();
+    embrfs.capacity_monitor = None;
+    let config = ReversibleVSAConfig::default();
+    embrfs.ingest_bytes(&stored, "data/a.bin".to_string(), &config).unwrap();
+    embrfs.ingest_bytes(&random(4096 * 4), "data/b.bin".to_string(), &config).unwrap();
+
+    let hit = embrfs.probably_contains_file(&stored, "data/a.bin");
+    assert_eq!(hit.verdict, MembershipVerdict::Likely);
+    assert_eq!(hit.members, 8);
+    assert!(hit.score > hit.threshold && hit.false_positive_rate < 1e-12);
+
+    let chunk = embrfs.engram.probably_contains(&stored[..4096], &config, Some("data/a.bin"));
+    assert!(chunk.is_likely() && chunk.score >= hit.score);
+
+    // Unrelated data scores as noise.
+    let miss = embrfs.probably_contains_file(&unrelated, "data/a.bin");
+    assert_eq!(miss.verdict, MembershipVerdict::Unlikely);
+    assert!(miss.score < miss.threshold && miss.false_positive_rate > 1e-6);
+    assert_eq!(embrfs.probably_contains_file(&[], "x").score, 0.0);
+
+    // Calibration: the threshold follows the bundle size until the root
+    // saturates, and the false-positive rate is the normal noise tail.
+    let small = MembershipResult::from_score(0.2, 16);
+    assert!((small.threshold - 0.05).abs() < 1e-12);
+    assert!((MembershipResult::from_score(0.2, 256).threshold - 1.0 / 32.0).abs() < 1e-12);
+    assert!((capacity::false_positive_rate(0.0) - 0.5).abs() < 1e-6);
+    assert!((capacity::false_positive_rate(3.0 * capacity::noise_floor()) - 0.00135).abs() < 1e-5);
+    assert_eq!(MembershipResult::from_score(0.5, 20_000).verdict, MembershipVerdict::Saturated);
+    let file = MembershipResult::combine(&[small.clone(), MembershipResult::from_score(0.01, 16)]).unwrap();
+    assert_eq!((file.verdict, file.score), (MembershipVerdict::Unlikely, 0.01));
+    assert!(MembershipResult::combine(&[]).is_none());
+}
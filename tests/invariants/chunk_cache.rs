@@ -0,0 +1,48 @@
+//! Tests for the decoded-chunk cache.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn chunk_cache_shared_by_verify_and_extract() {
+    use embeddenator::{ChunkCache, EmbrFS};
+    use std::fs;
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("a.bin"), vec![7u8; 12000]).unwrap();
+    fs::write(src.join("b.txt"), "cached hello\n").unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &config).unwrap();
+    let chunks: usize = embrfs.manifest.files.iter().map(|f| f.chunks.len()).sum();
+
+    let cache = ChunkCache::new(1 << 20);
+    assert!(EmbrFS::verify_with_cache(&embrfs.engram, &embrfs.manifest, &config, &cache)
+        .unwrap()
+        .is_ok());
+    let after_verify = cache.stats();
+    assert_eq!(after_verify.hits + after_verify.misses, chunks as u64);
+    assert!(after_verify.entries > 0);
+
+    // Extraction finds every chunk verification already decoded.
+    let out = tmp.path().join("out");
+    EmbrFS::extract_with_cache(&embrfs.engram, &embrfs.manifest, &out, false, &config, &cache)
+        .unwrap();
+    let after_extract = cache.stats();
+    assert_eq!(after_extract.misses, after_verify.misses);
+    assert_eq!(after_extract.hits, after_verify.hits + chunks as u64);
+    for name in ["a.bin", "b.txt"] {
+        assert_eq!(fs::read(out.join(name)).unwrap(), fs::read(src.join(name)).unwrap());
+    }
+
+    // A budget too small for any chunk caches nothing.
+    let tiny = ChunkCache::new(1);
+    assert!(EmbrFS::verify_with_cache(&embrfs.engram, &embrfs.manifest, &config, &tiny)
+        .unwrap()
+        .is_ok());
+    assert!(tiny.is_empty());
+    assert_eq!(tiny.stats().hits, 0);
+}
@@ -0,0 +1,46 @@
+//! Tests for clustering near-duplicate chunks.
+
+#[test]
+fn codebook_cluster_groups_near_duplicate_chunks() {
+    use embeddenator::{Codebook, SparseVec};
+    use std::collections::HashMap;
+
+    let a = SparseVec::random();
+    let b = SparseVec::random();
+    let noisy = |proto: &SparseVec, i: usize| {
+        let mut v = proto.clone();
+        v.pos.retain(|&d| !(d + i).is_multiple_of(10));
+        v.neg.retain(|&d| !(d + i).is_multiple_of(10));
+        v
+    };
+    let mut chunks = HashMap::new();
+    chunks.insert(7, SparseVec::random());
+    chunks.insert(3, a.clone());
+    chunks.insert(4, noisy(&a, 1));
+    chunks.insert(10, b.clone());
+    chunks.insert(11, noisy(&b, 2));
+    chunks.insert(12, noisy(&b, 3));
+    chunks.insert(20, SparseVec::new());
+
+    let clusters = Codebook::cluster(&chunks, 0.8);
+    assert_eq!(clusters.chunks, 7);
+    let shape: Vec<(usize, Vec<usize>)> =
+        clusters.clusters.iter().map(|c| (c.representative, c.members.clone())).collect();
+    assert_eq!(
+        shape,
+        vec![(10, vec![10, 11, 12]), (3, vec![3, 4]), (7, vec![7]), (20, vec![20])]
+    );
+    assert!(clusters.clusters[0].min_similarity >= 0.8 && clusters.clusters[0].min_similarity < 1.0);
+    assert_eq!(clusters.singletons(), 2);
+    assert_eq!(clusters.redundant_chunks(), 3);
+    assert_eq!(clusters.size_histogram().into_iter().collect::<Vec<_>>(), vec![(1, 2), (2, 1), (3, 1)]);
+    assert_eq!(clusters.representatives().collect::<Vec<_>>(), vec![10, 3, 7, 20]);
+    assert_eq!(Codebook::cluster(&chunks, 0.8), clusters);
+
+    // Above 1 nothing joins; at -1 everything does.
+    assert_eq!(Codebook::cluster(&chunks, 1.01).singletons(), 7);
+    let all = Codebook::cluster(&chunks, -1.0);
+    assert_eq!(all.len(), 1);
+    assert_eq!(all.clusters[0].representative, 3);
+    assert!(Codebook::cluster(&HashMap::new(), 0.8).is_empty());
+}
@@ -0,0 +1,52 @@
+//! Tests for chunk reference counting.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn chunk_refs_guard_unreferenced_removal() {
+    use embeddenator::{ChunkRefs, EmbrFS, Manifest};
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_bytes(&vec![1u8; 9000], "a.bin".into(), &config).unwrap();
+    embrfs.ingest_bytes(&vec![2u8; 5000], "b.bin".into(), &config).unwrap();
+    embrfs.ingest_bytes(b"tail", "c.txt".into(), &config).unwrap();
+    let full = embrfs.manifest.clone();
+    let subset = Manifest {
+        files: full.files.iter().filter(|f| f.path != "b.bin").cloned().collect(),
+        ..full.clone()
+    };
+    let b_chunks = full.files.iter().find(|f| f.path == "b.bin").unwrap().chunks.clone();
+
+    let mut refs = ChunkRefs::from_manifests([&full, &subset]);
+    assert_eq!(refs.manifests(), 2);
+    assert_eq!(refs.refs(full.files[2].chunks[0]), 2);
+    assert_eq!(refs.refs(b_chunks[0]), 1);
+    assert!(refs.unreferenced(&embrfs.engram).is_empty());
+
+    // Dropping the full manifest leaves b.bin's chunks unreferenced.
+    assert!(refs.remove_manifest(&full));
+    assert!(!refs.remove_manifest(&full));
+    assert_eq!(refs.unreferenced(&embrfs.engram), b_chunks);
+
+    // Counts that disagree with a live manifest refuse to remove anything.
+    let chunks = embrfs.engram.codebook.len();
+    let engram = &mut embrfs.engram;
+    assert!(engram.remove_unreferenced(&refs, &[&subset, &full]).is_err());
+    let stale = ChunkRefs::from_manifests([&subset]);
+    let mut edited = subset.clone();
+    edited.files.pop();
+    assert!(engram.remove_unreferenced(&stale, &[&edited]).is_err());
+    assert_eq!(engram.codebook.len(), chunks);
+
+    let before = engram.corrections.stats();
+    let report = engram.remove_unreferenced(&refs, &[&subset]).unwrap();
+    assert_eq!(report.removed, b_chunks);
+    assert!(report.reclaimed_bytes > 0);
+    assert_eq!(report.remaining, chunks - b_chunks.len());
+    assert_eq!(engram.corrections.stats().original_bytes, before.original_bytes - 5000);
+    assert!(EmbrFS::verify(engram, &subset, &config).unwrap().is_ok());
+
+    // Nothing left to reclaim.
+    assert!(engram.remove_unreferenced(&refs, &[&subset]).unwrap().removed.is_empty());
+}
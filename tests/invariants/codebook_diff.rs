@@ -0,0 +1,59 @@
+//! Tests for codebook identity and diffs.
+
+#[test]
+fn codebook_artifact_identity_and_diff() {
+    use embeddenator::{Codebook, Keyring, OutlierRule, PayloadKind, SparseVec, WordMetadata};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let keys = Keyring::default();
+    let mut codebook = Codebook::with_salt(embeddenator::DIM, [7u8; 32]);
+    codebook.initialize_standard_basis();
+    let path = tmp.path().join("v1.codebook");
+    codebook.save(&path, Default::default()).unwrap();
+    assert_eq!(PayloadKind::sniff_file(&path).unwrap(), Some(PayloadKind::Codebook));
+
+    let loaded = Codebook::load_pinned(&path, &keys, &codebook.id()).unwrap();
+    assert_eq!(loaded.digest(), codebook.digest());
+    assert!(Codebook::load_pinned(&path, &keys, &"0".repeat(64)).is_err());
+
+    // Usage statistics don't change the identity.
+    let mut used = loaded.clone();
+    used.statistics.total_bytes_encoded = 1 << 20;
+    assert_eq!(used.id(), codebook.id());
+
+    // Projections through the loaded copy keep every word's metadata.
+    let data = b"the quick brown fox jumps over the lazy dog".repeat(3);
+    let (a, b) = (codebook.project(&data), loaded.project(&data));
+    assert_eq!(a.coefficients, b.coefficients);
+    assert_eq!(a.residual, b.residual);
+    assert!(b.residual.iter().all(|w| w.metadata() == WordMetadata::Residual));
+    assert!(b.coefficients.values().all(|w| w.metadata() == WordMetadata::Data));
+
+    let mut next = loaded.clone();
+    next.basis_vectors.retain(|b| b.id != 3);
+    next.basis_vectors[0].weight = 2.0;
+    next.basis_vectors[1].vector = SparseVec::random();
+    next.basis_vectors.push(embeddenator::codebook::BasisVector {
+        id: 42,
+        vector: SparseVec::random(),
+        label: Some("new".into()),
+        weight: 1.0,
+    });
+    next.outliers.rules.push(OutlierRule::Residual(0.9));
+    let diff = loaded.diff(&next);
+    assert!(!diff.is_empty());
+    assert_eq!(diff.basis_added, vec![42]);
+    assert_eq!(diff.basis_removed, vec![3]);
+    assert_eq!(diff.basis_changed, vec![1]);
+    assert_eq!(diff.basis_reweighted, vec![0]);
+    assert!(diff.outliers_changed && !diff.salt_changed && !diff.semantic_markers_changed);
+    assert_eq!(diff.dimensionality, None);
+    assert!(loaded.diff(&used).is_empty());
+
+    // Newer layouts are refused rather than misread.
+    let mut future = codebook.clone();
+    future.version = embeddenator::codebook::CODEBOOK_VERSION + 1;
+    let future_path = tmp.path().join("future.codebook");
+    future.save(&future_path, Default::default()).unwrap();
+    assert!(Codebook::load(&future_path).is_err());
+}
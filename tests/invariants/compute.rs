@@ -0,0 +1,75 @@
+//! Tests for algebra expressions over named vectors.
+
+use embeddenator::vsa::compute::{self, ComputeError, Expr};
+use embeddenator::vsa::record::ItemMemory;
+use embeddenator::EmbrFS;
+
+fn colours() -> ItemMemory {
+    let mut memory = ItemMemory::new(5);
+    for name in ["red", "round", "blue", "square"] {
+        memory.symbol(name);
+    }
+    memory
+}
+
+#[test]
+fn role_filler_queries_compose() {
+    let memory = colours();
+    let record = "bundle(bind(symbol(colour), red), bind(symbol(shape), round), bind(symbol(size), square))";
+    let colour = compute::eval(&format!("cleanup(unbind({record}, symbol(colour)))"), &memory).unwrap();
+    assert_eq!(&colour, memory.get("red").unwrap());
+    let shape = compute::eval(&format!("cleanup(unbind({record}, symbol(\"shape\")))"), &memory).unwrap();
+    assert_eq!(&shape, memory.get("round").unwrap());
+
+    // Symbols agree with the memory's seed; bound names win.
+    assert_eq!(compute::eval("symbol(colour)", &memory).unwrap(), ItemMemory::new(5).symbol("colour"));
+    assert_eq!(&compute::eval("symbol(red)", &memory).unwrap(), memory.get("red").unwrap());
+}
+
+#[test]
+fn permutations_invert_and_names_may_be_quoted() {
+    let mut memory = colours();
+    let red = memory.get("red").unwrap().clone();
+    memory.insert("dark red", red.clone());
+    assert_eq!(compute::eval("permute(red, 3)", &memory).unwrap(), red.permute(3));
+    assert_eq!(compute::eval(" ( permute( permute(red,3) , -3 ) ) ", &memory).unwrap(), red);
+    assert_eq!(compute::eval("\"dark red\"", &memory).unwrap(), red);
+    assert_eq!(
+        Expr::parse("bind(a, \"b\\\"c\")").unwrap(),
+        Expr::Bind(vec![Expr::Name("a".into()), Expr::Name("b\"c".into())])
+    );
+}
+
+#[test]
+fn malformed_expressions_say_where() {
+    let memory = colours();
+    assert_eq!(compute::eval("green", &memory), Err(ComputeError::UnknownName("green".into())));
+    assert_eq!(compute::eval("cleanup(red)", &ItemMemory::new(5)), Err(ComputeError::UnknownName("red".into())));
+    assert_eq!(compute::eval("cleanup(symbol(red))", &ItemMemory::new(5)), Err(ComputeError::EmptyMemory));
+    let position = |expr: &str| match Expr::parse(expr) {
+        Err(ComputeError::Syntax { position, .. }) => position,
+        other => panic!("{expr}: {other:?}"),
+    };
+    assert_eq!(position("frobnicate(red)"), 0);
+    assert_eq!(position("bind(red)"), 8);
+    assert_eq!(position("permute(red, x)"), 14);
+    assert_eq!(position("unbind(red blue)"), 11);
+    assert_eq!(position("red blue"), 4);
+    assert_eq!(position("\"red"), 0);
+    assert_eq!(position(""), 0);
+}
+
+#[test]
+fn engram_vectors_are_bound_by_name() {
+    let mut fsys = EmbrFS::new();
+    let config = fsys.vsa_config();
+    fsys.ingest_bytes(b"alpha beta gamma", "docs/a.txt".into(), &config).unwrap();
+    fsys.ingest_bytes(b"delta epsilon", "b.txt".into(), &config).unwrap();
+    let memory = compute::bindings(&fsys.engram, &fsys.manifest, 0);
+    assert_eq!(memory.names().collect::<Vec<_>>(), ["root", "chunk:0", "chunk:1", "file:docs/a.txt", "file:b.txt"]);
+
+    assert_eq!(compute::eval("root", &memory).unwrap(), fsys.engram.root);
+    // Binding with a bipolar key and unbinding again gives the file back.
+    let cleaned = compute::eval("cleanup(unbind(bind(file:docs/a.txt, symbol(key)), symbol(key)))", &memory).unwrap();
+    assert_eq!(cleaned, fsys.engram.codebook[&0]);
+}
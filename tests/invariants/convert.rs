@@ -0,0 +1,54 @@
+//! Tests for converting engrams between storage formats.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn convert_between_storage_formats_preserves_files() {
+    use embeddenator::{convert, BinaryWriteOptions, ChecksumCodec, ConvertOptions, EmbrFS, StorageFormat, TargetFormat};
+    use std::fs;
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("a.txt"), "alpha\nbeta\n").unwrap();
+    fs::write(src.join("b.bin"), (0..20_000u32).map(|i| (i * 31 % 251) as u8).collect::<Vec<_>>()).unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &config).unwrap();
+    let engram = tmp.path().join("root.engram");
+    embrfs.save_engram(&engram).unwrap();
+
+    // Split with checksums, in place.
+    let split = ConvertOptions {
+        write: BinaryWriteOptions { checksum: ChecksumCodec::Blake3, ..Default::default() },
+        part_size: Some(16 * 1024),
+        ..Default::default()
+    };
+    let report = convert::convert(&embrfs, &engram, &split, &config).unwrap();
+    assert_eq!(report.files_verified, 2);
+    assert_eq!(report.bytes_verified, 20_000 + 11);
+    assert_eq!(report.format.container, "split");
+    assert!(tmp.path().join("root.engram.part0001").exists());
+
+    // Back to a single file: the old parts go away.
+    let mut loaded = EmbrFS::new();
+    loaded.engram = EmbrFS::load_engram(&engram).unwrap();
+    loaded.manifest = embrfs.manifest.clone();
+    let report = convert::convert(&loaded, &engram, &ConvertOptions::default(), &config).unwrap();
+    assert_eq!(report.format.container, "bincode");
+    assert!(!tmp.path().join("root.engram.part0000").exists());
+    assert_eq!(StorageFormat::detect(&engram).unwrap().container, "bincode");
+
+    let log = tmp.path().join("root.edna");
+    let to_log = ConvertOptions { format: TargetFormat::AppendLog, ..Default::default() };
+    convert::convert(&loaded, &log, &to_log, &config).unwrap();
+    let (engram_data, manifest) = EmbrFS::load_append_log(&log).unwrap();
+    assert!(EmbrFS::verify(&engram_data, &manifest, &config).unwrap().is_ok());
+
+    // Write options other than the container only apply to envelopes.
+    let bad = ConvertOptions { part_size: Some(1024), ..to_log };
+    assert!(convert::convert(&loaded, &log, &bad, &config).is_err());
+    assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 3, "no staging left behind");
+}
@@ -0,0 +1,53 @@
+//! Tests for counted bundles as engram roots.
+
+#[test]
+fn counted_root_subtracts_removed_chunks() {
+    use embeddenator::{EmbrFS, ReversibleVSAConfig};
+
+    let config = ReversibleVSAConfig::default();
+    let files: [(&str, Vec<u8>); 3] = [
+        ("a.txt", b"alpha ".repeat(1500)),
+        ("b.bin", (0..9000u32).map(|i| (i * 7 % 251) as u8).collect()),
+        ("c.txt", b"gamma delta ".repeat(800)),
+    ];
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&files[0].1, files[0].0.into(), &config).unwrap();
+    fs.engram.count_root();
+    for (path, data) in &files[1..] {
+        fs.ingest_bytes(data, (*path).into(), &config).unwrap();
+    }
+    assert_eq!(fs.engram.root_counts.as_ref().unwrap().count() as usize, fs.engram.codebook.len());
+    assert!(fs.remove_file("b.bin"));
+    fs.engram.validate(&Default::default()).unwrap();
+
+    // Subtracting gives the majority a fresh count of the survivors gives.
+    let mut fresh = EmbrFS::new();
+    fresh.engram.count_root();
+    for (path, data) in [&files[0], &files[2]] {
+        fresh.ingest_bytes(data, (*path).into(), &config).unwrap();
+    }
+    assert_eq!(fs.engram.root.pos, fresh.engram.root.pos);
+    assert_eq!(fs.engram.root.neg, fresh.engram.root.neg);
+    assert_eq!(fs.engram.root_counts, fresh.engram.root_counts);
+
+    // Taking out votes the counts do not hold is refused.
+    let stranger = embeddenator::vsa::SparseVec { pos: (0..embeddenator::vsa::DIM).collect(), neg: Vec::new() };
+    let before = fs.engram.root_counts.clone();
+    assert!(!fs.engram.subtract_from_root(&stranger));
+    assert_eq!(fs.engram.root_counts, before);
+
+    // The counts survive a bincode round trip and are checked on load.
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("root.engram");
+    fs.save_engram(&path).unwrap();
+    let loaded = EmbrFS::load_engram(&path).unwrap();
+    assert_eq!(loaded.root_counts, fs.engram.root_counts);
+    #[cfg(feature = "proto")]
+    {
+        let bytes = embeddenator::wire::encode_engram(&fs.engram).unwrap();
+        assert_eq!(embeddenator::wire::decode_engram(&bytes).unwrap().root_counts, fs.engram.root_counts);
+    }
+    let mut skewed = loaded;
+    skewed.root = skewed.root.bundle(&stranger);
+    assert!(skewed.validate(&Default::default()).unwrap_err().to_string().contains("root counts"));
+}
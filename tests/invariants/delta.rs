@@ -0,0 +1,102 @@
+//! Tests for delta envelopes against a base engram.
+
+use embeddenator::delta::{engram_digest, flatten};
+use embeddenator::{BinaryWriteOptions, EmbrFS, EngramDelta, ReversibleVSAConfig};
+use std::fs;
+use std::path::Path;
+
+fn write_inputs(dir: &Path) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("a.txt"), "stable base content ".repeat(800)).unwrap();
+    fs::write(dir.join("b.bin"), (0..12_000u32).map(|i| (i % 199) as u8).collect::<Vec<_>>()).unwrap();
+}
+
+/// Reload a saved engram so the base and target can diverge independently.
+fn reload(dir: &Path, fsys: &EmbrFS, name: &str) -> EmbrFS {
+    let engram = dir.join(format!("{name}.engram"));
+    let manifest = dir.join(format!("{name}.json"));
+    fsys.save_engram(&engram).unwrap();
+    fsys.save_manifest(&manifest).unwrap();
+    let mut copy = EmbrFS::new();
+    copy.engram = EmbrFS::load_engram(&engram).unwrap();
+    copy.manifest = EmbrFS::load_manifest(&manifest).unwrap();
+    copy
+}
+
+fn add_file(fsys: &mut EmbrFS, dir: &Path, name: &str, data: &[u8]) {
+    fs::write(dir.join(name), data).unwrap();
+    fsys.ingest_file(dir.join(name), name.to_string(), false, &ReversibleVSAConfig::default())
+        .unwrap();
+}
+
+fn remove_file(fsys: &mut EmbrFS, name: &str) {
+    let pos = fsys.manifest.files.iter().position(|f| f.path == name).unwrap();
+    let entry = fsys.manifest.files.remove(pos);
+    for id in entry.chunks {
+        fsys.engram.codebook.remove(&id);
+    }
+}
+
+#[test]
+fn delta_roundtrip_rebuilds_target() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    write_inputs(&input);
+    let mut base = EmbrFS::new();
+    base.ingest_directory(&input, false, &ReversibleVSAConfig::default())
+        .unwrap();
+
+    let mut target = reload(td.path(), &base, "base");
+    let removed_ids = base.manifest.files.iter().find(|f| f.path == "b.bin").unwrap().chunks.clone();
+    add_file(&mut target, &input, "c.txt", b"nightly addition");
+    remove_file(&mut target, "b.bin");
+
+    let delta = EngramDelta::between(&base.engram, &base.manifest, &target.engram, &target.manifest).unwrap();
+    assert!(!delta.is_empty());
+    assert_eq!(delta.manifest.removed, vec!["b.bin".to_string()]);
+    assert_eq!(delta.manifest.upserted.len(), 1);
+    assert!(delta.upserted_chunks.len() < target.engram.codebook.len());
+
+    let path = td.path().join("update.delta");
+    delta.save(&path, BinaryWriteOptions::default()).unwrap();
+    assert!(fs::metadata(&path).unwrap().len() < fs::metadata(td.path().join("base.engram")).unwrap().len());
+
+    let loaded = EngramDelta::load(&path).unwrap();
+    let (engram, manifest) = loaded.apply(&base.engram, &base.manifest).unwrap();
+    assert_eq!(engram_digest(&engram).unwrap(), engram_digest(&target.engram).unwrap());
+    let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["a.txt", "c.txt"]);
+    assert!(removed_ids.iter().all(|id| !engram.codebook.contains_key(id)));
+
+    let out = td.path().join("out");
+    EmbrFS::extract(&engram, &manifest, &out, false, &ReversibleVSAConfig::default()).unwrap();
+    assert_eq!(fs::read(out.join("a.txt")).unwrap(), fs::read(input.join("a.txt")).unwrap());
+    assert_eq!(fs::read(out.join("c.txt")).unwrap(), b"nightly addition");
+    assert!(!out.join("b.bin").exists());
+}
+
+#[test]
+fn delta_rejects_the_wrong_base_and_chains_with_flatten() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    write_inputs(&input);
+    let mut v1 = EmbrFS::new();
+    v1.ingest_directory(&input, false, &ReversibleVSAConfig::default())
+        .unwrap();
+
+    let mut v2 = reload(td.path(), &v1, "v1");
+    add_file(&mut v2, &input, "two.txt", b"second release");
+    let mut v3 = reload(td.path(), &v2, "v2");
+    add_file(&mut v3, &input, "three.txt", b"third release");
+
+    let d12 = EngramDelta::between(&v1.engram, &v1.manifest, &v2.engram, &v2.manifest).unwrap();
+    let d23 = EngramDelta::between(&v2.engram, &v2.manifest, &v3.engram, &v3.manifest).unwrap();
+
+    let err = d23.apply(&v1.engram, &v1.manifest).err().expect("wrong base");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let (engram, manifest) = flatten(&v1.engram, &v1.manifest, &[d12, d23]).unwrap();
+    assert_eq!(engram_digest(&engram).unwrap(), engram_digest(&v3.engram).unwrap());
+    assert_eq!(manifest.files, v3.manifest.files);
+    assert_eq!(manifest.total_chunks, v3.manifest.total_chunks);
+}
@@ -0,0 +1,138 @@
+//! Tests for dense `.npy` and FAISS exports of codebook vectors.
+
+use crate::support::ingested;
+use embeddenator::dense_export::{to_faiss, to_npy, DenseDtype, FAISS_INDEX, IDS_NPY, VECTORS_NPY};
+use embeddenator::{EmbrFS, SparseVec, DIM};
+use std::fs;
+
+/// Split an NPY 1.0 file into its header dictionary and data.
+fn parse_npy(bytes: &[u8]) -> (String, &[u8]) {
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert_eq!((10 + len) % 64, 0, "header must be 64-byte aligned");
+    let header = String::from_utf8(bytes[10..10 + len].to_vec()).unwrap();
+    assert!(header.ends_with('\n'));
+    (header, &bytes[10 + len..])
+}
+
+/// `vec` with indices present in both `pos` and `neg` cancelled out.
+fn cancelled(vec: &SparseVec) -> SparseVec {
+    SparseVec {
+        pos: vec.pos.iter().copied().filter(|i| !vec.neg.contains(i)).collect(),
+        neg: vec.neg.iter().copied().filter(|i| !vec.pos.contains(i)).collect(),
+    }
+}
+
+fn sorted_ids(fsys: &EmbrFS) -> Vec<usize> {
+    let mut ids: Vec<usize> = fsys.engram.codebook.keys().copied().collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn npy_int8_rows_are_codebook_trits() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    let summary = to_npy(&fsys.engram, DenseDtype::Int8, td.path()).unwrap();
+    let ids = sorted_ids(&fsys);
+    assert_eq!(summary.rows, ids.len());
+    assert_eq!(summary.dim, DIM);
+
+    let raw = fs::read(td.path().join(VECTORS_NPY)).unwrap();
+    let (header, data) = parse_npy(&raw);
+    assert!(header.contains("'descr': '|i1'"), "{header}");
+    assert!(header.contains(&format!("'shape': ({}, {DIM})", ids.len())), "{header}");
+    assert_eq!(data.len(), ids.len() * DIM);
+
+    for (row, id) in data.chunks(DIM).zip(&ids) {
+        let vec = cancelled(&fsys.engram.codebook[id]);
+        let pos: Vec<usize> = (0..DIM).filter(|&d| row[d] as i8 == 1).collect();
+        let neg: Vec<usize> = (0..DIM).filter(|&d| row[d] as i8 == -1).collect();
+        assert_eq!(pos, vec.pos);
+        assert_eq!(neg, vec.neg);
+    }
+
+    let raw = fs::read(td.path().join(IDS_NPY)).unwrap();
+    let (header, data) = parse_npy(&raw);
+    assert!(header.contains("'descr': '<u8'") && header.contains(&format!("({},)", ids.len())));
+    let exported: Vec<usize> = data
+        .chunks(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+        .collect();
+    assert_eq!(exported, ids);
+}
+
+fn f32_rows(data: &[u8]) -> Vec<Vec<f32>> {
+    data.chunks(DIM * 4)
+        .map(|row| row.chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect())
+        .collect()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (*x as f64) * (*y as f64)).sum()
+}
+
+#[test]
+fn npy_float_inner_product_matches_cosine() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    to_npy(&fsys.engram, DenseDtype::Float32, td.path()).unwrap();
+    let ids = sorted_ids(&fsys);
+
+    let raw = fs::read(td.path().join(VECTORS_NPY)).unwrap();
+    let (header, data) = parse_npy(&raw);
+    assert!(header.contains("'descr': '<f4'"), "{header}");
+    let rows = f32_rows(data);
+    assert_eq!(rows.len(), ids.len());
+
+    for (i, a) in rows.iter().enumerate().take(4) {
+        assert!((dot(a, a) - 1.0).abs() < 1e-4);
+        for (j, b) in rows.iter().enumerate().skip(i + 1).take(4) {
+            let expected = cancelled(&fsys.engram.codebook[&ids[i]]).cosine(&cancelled(&fsys.engram.codebook[&ids[j]]));
+            assert!((dot(a, b) - expected).abs() < 1e-4, "rows {i},{j}");
+        }
+    }
+}
+
+#[test]
+fn faiss_index_layout() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    let summary = to_faiss(&fsys.engram, td.path()).unwrap();
+    let ids = sorted_ids(&fsys);
+    let n = ids.len();
+
+    let raw = fs::read(td.path().join(FAISS_INDEX)).unwrap();
+    // IndexIDMap header, IndexFlatIP header, float vector, id vector.
+    let header_len = 4 + 4 + 8 + 8 + 8 + 1 + 4;
+    assert_eq!(raw.len(), 2 * header_len + 8 + n * DIM * 4 + 8 + n * 8);
+    let check_header = |at: usize, fourcc: &[u8]| {
+        assert_eq!(&raw[at..at + 4], fourcc);
+        assert_eq!(i32::from_le_bytes(raw[at + 4..at + 8].try_into().unwrap()), DIM as i32);
+        assert_eq!(i64::from_le_bytes(raw[at + 8..at + 16].try_into().unwrap()), n as i64);
+        assert_eq!(raw[at + 32], 1, "is_trained");
+        assert_eq!(i32::from_le_bytes(raw[at + 33..at + 37].try_into().unwrap()), 0, "inner product");
+    };
+    check_header(0, b"IxMp");
+    check_header(header_len, b"IxFI");
+
+    let mut at = 2 * header_len;
+    assert_eq!(u64::from_le_bytes(raw[at..at + 8].try_into().unwrap()), (n * DIM) as u64);
+    at += 8;
+    let rows = f32_rows(&raw[at..at + n * DIM * 4]);
+    at += n * DIM * 4;
+    assert_eq!(u64::from_le_bytes(raw[at..at + 8].try_into().unwrap()), n as u64);
+    at += 8;
+    let exported: Vec<usize> = raw[at..]
+        .chunks(8)
+        .map(|b| i64::from_le_bytes(b.try_into().unwrap()) as usize)
+        .collect();
+    assert_eq!(exported, ids);
+    assert_eq!(summary.rows, n);
+
+    // Same rows as the float32 `.npy` export.
+    let npy = tempfile::tempdir().unwrap();
+    to_npy(&fsys.engram, DenseDtype::Float32, npy.path()).unwrap();
+    let raw = fs::read(npy.path().join(VECTORS_NPY)).unwrap();
+    assert_eq!(f32_rows(parse_npy(&raw).1), rows);
+}
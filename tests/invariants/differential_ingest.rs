@@ -0,0 +1,107 @@
+//! Tests for differential chunk storage against a trained basis.
+
+use embeddenator::codebook::Codebook;
+use embeddenator::{EmbrFS, DIM};
+use std::fs;
+use std::io;
+
+/// Chunks in two clusters: runs of `a` or of `b`, each with one byte changed.
+fn clustered_fs() -> (EmbrFS, Vec<Vec<u8>>) {
+    let mut fsys = EmbrFS::new();
+    let config = fsys.vsa_config();
+    let mut files = Vec::new();
+    for i in 0..16u8 {
+        let mut data = vec![b'a' + i % 2; 4096];
+        data[i as usize * 7] = b'!';
+        fsys.ingest_bytes(&data, format!("f{i}.txt"), &config).unwrap();
+        files.push(data);
+    }
+    (fsys, files)
+}
+
+#[test]
+fn differential_chunks_are_smaller_and_extract_bit_perfect() {
+    let td = tempfile::tempdir().unwrap();
+    let (mut fsys, files) = clustered_fs();
+    let raw_path = td.path().join("raw.engram");
+    fsys.save_engram(&raw_path).unwrap();
+
+    let (basis, _) = fsys.train_basis(2);
+    let report = fsys.encode_differential(&basis).unwrap();
+    assert_eq!((report.chunks, report.basis_vectors), (16, 2));
+    assert!(report.differential > 0, "{report:?}");
+    assert!(report.ratio() < 1.0, "{report:?}");
+    assert_eq!(report.mean_quality, 1.0);
+
+    let path = td.path().join("root.engram");
+    fsys.save_engram(&path).unwrap();
+    assert!(fs::metadata(&path).unwrap().len() < fs::metadata(&raw_path).unwrap().len());
+
+    // Loading decodes the chunks back into the codebook.
+    let engram = EmbrFS::load_engram(&path).unwrap();
+    assert_eq!(engram.differential.as_ref().unwrap().chunks.len(), report.differential);
+    assert_eq!(engram.codebook, fsys.engram.codebook);
+
+    let out = tempfile::tempdir().unwrap();
+    EmbrFS::extract(&engram, &fsys.manifest, out.path(), false, &fsys.vsa_config()).unwrap();
+    for (i, data) in files.iter().enumerate() {
+        assert_eq!(&fs::read(out.path().join(format!("f{i}.txt"))).unwrap(), data);
+    }
+}
+
+#[test]
+fn batched_projection_matches_the_scalar_encoder() {
+    use embeddenator::dimensional::HyperVec;
+    use embeddenator::DifferentialChunk;
+
+    let (mut fsys, _) = clustered_fs();
+    let (basis, _) = fsys.train_basis(2);
+    let mut encoder = fsys.differential_encoder(&basis);
+    let mut ids: Vec<usize> = fsys.engram.codebook.keys().copied().collect();
+    ids.sort_unstable();
+    let expected: Vec<(usize, DifferentialChunk)> = ids
+        .iter()
+        .map(|id| {
+            let encoding = encoder.encode(&HyperVec::from_sparse(encoder.config.clone(), &fsys.engram.codebook[id]));
+            encoder.depths.merge(&encoding.expanded);
+            (*id, DifferentialChunk::from(&encoding))
+        })
+        .collect();
+
+    fsys.encode_differential(&basis).unwrap();
+    let chunks = &fsys.engram.differential.as_ref().unwrap().chunks;
+    assert!(!chunks.is_empty());
+    for (id, chunk) in &expected {
+        if let Some(stored) = chunks.get(id) {
+            assert_eq!(stored, chunk, "chunk {id}");
+        }
+    }
+}
+
+#[test]
+fn chunks_changed_after_encoding_are_saved_raw() {
+    let td = tempfile::tempdir().unwrap();
+    let (mut fsys, _) = clustered_fs();
+    let (basis, _) = fsys.train_basis(2);
+    let report = fsys.encode_differential(&basis).unwrap();
+    let chunks = &fsys.engram.differential.as_ref().unwrap().chunks;
+    let changed = *chunks.keys().next().unwrap();
+    let replacement = fsys.engram.codebook.iter().find(|(id, _)| !chunks.contains_key(id)).unwrap().1.clone();
+    fsys.engram.codebook.insert(changed, replacement.clone());
+
+    let path = td.path().join("root.engram");
+    fsys.save_engram(&path).unwrap();
+    let engram = EmbrFS::load_engram(&path).unwrap();
+    assert_eq!(engram.codebook[&changed], replacement);
+    let kept = &engram.differential.as_ref().unwrap().chunks;
+    assert_eq!(kept.len(), report.differential - 1);
+    assert!(!kept.contains_key(&changed));
+}
+
+#[test]
+fn an_untrained_basis_is_refused() {
+    let (mut fsys, _) = clustered_fs();
+    let err = fsys.encode_differential(&Codebook::new(DIM)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(fsys.engram.differential.is_none());
+}
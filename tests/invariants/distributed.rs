@@ -0,0 +1,70 @@
+//! Tests for engrams distributed across nodes.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn distributed_engram_routes_reads_and_merges_search() {
+    use embeddenator::distributed::{self, DistributedEngram, EngramNode, LocalNode, Placement};
+    use embeddenator::{EmbrFS, DEFAULT_CHUNK_SIZE};
+
+    let config = ReversibleVSAConfig::default();
+    let files: Vec<Vec<u8>> = (0..6u8)
+        .map(|i| (0..9000u32).map(|j| (j as u8).wrapping_mul(i + 1) ^ i).collect())
+        .collect();
+    let mut embrfs = EmbrFS::new();
+    for (i, data) in files.iter().enumerate() {
+        embrfs.ingest_bytes(data, format!("f{}.bin", i), &config).unwrap();
+    }
+    let engram = &embrfs.engram;
+    let total = engram.codebook.len();
+
+    for placement in [Placement::Hash, Placement::ranges_for(engram, 3)] {
+        // Every chunk lands on exactly one node.
+        let parts = distributed::split(engram, 3, placement);
+        assert_eq!(parts.iter().map(|p| p.codebook.len()).sum::<usize>(), total);
+        for (n, part) in parts.iter().enumerate() {
+            assert!(!part.codebook.is_empty(), "{placement:?} left node {n} empty");
+            assert!(part.codebook.keys().all(|&id| placement.node_for(id, 3) == n));
+        }
+
+        let cluster = DistributedEngram::local(engram, 3, placement);
+        assert_eq!(cluster.chunk_count().unwrap(), total);
+        for (file, data) in embrfs.manifest.files.iter().zip(&files) {
+            assert_eq!(&cluster.read_file(file, &config, DEFAULT_CHUNK_SIZE).unwrap(), data);
+        }
+
+        // A chunk's own vector is its best match, wherever it lives.
+        let id = *embrfs.manifest.files[4].chunks.last().unwrap();
+        let hits = cluster.search(&engram.codebook[&id], 5).unwrap();
+        assert_eq!(hits.len(), 5);
+        assert_eq!(hits[0].chunk_id, id);
+        assert_eq!(hits[0].node, cluster.node_for(id));
+        assert_eq!(hits[0].cosine, engram.codebook[&id].cosine(&engram.codebook[&id]));
+        assert!(hits.windows(2).all(|w| w[0].cosine >= w[1].cosine));
+
+        let whole = cluster.to_engram().unwrap();
+        assert_eq!(whole.codebook.len(), total);
+        assert_eq!(whole.root.pos, engram.root.pos);
+        assert_eq!(whole.corrections.stats().total_chunks, engram.corrections.stats().total_chunks);
+    }
+
+    // Node errors surface with the node's number.
+    struct Down;
+    impl EngramNode for Down {
+        fn chunk_ids(&self) -> std::io::Result<Vec<usize>> {
+            Err(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "unreachable"))
+        }
+        fn fetch(&self, _: &[usize]) -> std::io::Result<Vec<distributed::ChunkRecord>> {
+            self.chunk_ids().map(|_| Vec::new())
+        }
+        fn search(&self, _: &embeddenator::SparseVec, _: usize) -> std::io::Result<Vec<(usize, f64)>> {
+            self.chunk_ids().map(|_| Vec::new())
+        }
+    }
+    let mut parts = distributed::split(engram, 2, Placement::Hash).into_iter();
+    let nodes: Vec<Box<dyn EngramNode>> = vec![Box::new(LocalNode::new(parts.next().unwrap())), Box::new(Down)];
+    let cluster = DistributedEngram::new(engram.root.clone(), Placement::Hash, nodes);
+    let err = cluster.chunk_count().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+    assert!(err.to_string().starts_with("node 1:"), "{err}");
+}
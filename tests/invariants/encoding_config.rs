@@ -0,0 +1,80 @@
+//! Tests for recording and checking the encoding configuration.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn encoding_config_is_recorded_and_checked() {
+    use embeddenator::dimensional::DimensionalConfig;
+    use embeddenator::{EmbrError, EmbrFS, Keyring};
+
+    let config = ReversibleVSAConfig {
+        block_size: 128,
+        max_path_depth: 4,
+        ..Default::default()
+    };
+    let mut fs = EmbrFS::with_config(config.clone(), DimensionalConfig::default()).unwrap();
+    assert_eq!(fs.vsa_config(), config);
+    let data: Vec<u8> = (0..9000u32).map(|i| (i * 31 % 251) as u8).collect();
+    fs.ingest_bytes(&data, "a.bin".into(), &config).unwrap();
+    assert!(fs
+        .ingest_bytes(&data, "b.bin".into(), &ReversibleVSAConfig::default())
+        .is_err());
+
+    // The config survives the manifest, and decoding needs it.
+    let json = serde_json::to_vec(&fs.manifest).unwrap();
+    let manifest = EmbrFS::manifest_from_bytes(&json, &Keyring::new()).unwrap();
+    assert_eq!(manifest.encoding().vsa, config);
+    assert!(EmbrFS::verify(&fs.engram, &manifest, &ReversibleVSAConfig::default()).is_err());
+    assert_eq!(EmbrFS::verify(&fs.engram, &manifest, &config).unwrap().files_verified, 1);
+
+    // Manifests written with another vector dimension are refused.
+    let other = DimensionalConfig {
+        num_dimensions: 4096,
+        ..Default::default()
+    };
+    let Err(err) = EmbrFS::with_config(config.clone(), other.clone()) else {
+        panic!("a dimension other than DIM was accepted");
+    };
+    assert!(matches!(err, EmbrError::DimensionMismatch { found: 4096, .. }));
+    assert_eq!(
+        err.to_string(),
+        format!("chunk encoding: expected dimension {}, found 4096", embeddenator::DIM)
+    );
+    let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    value["encoding"]["dimensional"]["num_dimensions"] = 4096.into();
+    let err = EmbrFS::manifest_from_bytes(value.to_string().as_bytes(), &Keyring::new()).unwrap_err();
+    assert!(matches!(err, EmbrError::DimensionMismatch { .. }));
+}
+
+#[test]
+fn chunk_size_is_recorded_and_followed() {
+    use embeddenator::{EmbrFS, EncodingConfig, Keyring, DEFAULT_CHUNK_SIZE};
+
+    let encoding = EncodingConfig {
+        chunk_size: 1000,
+        ..Default::default()
+    };
+    let mut fs = EmbrFS::with_encoding(encoding).unwrap();
+    let config = fs.vsa_config();
+    let data: Vec<u8> = (0..4500u32).map(|i| (i * 7 % 253) as u8).collect();
+    fs.ingest_bytes(&data, "a.bin".into(), &config).unwrap();
+    assert_eq!(fs.manifest.files[0].chunks.len(), 5);
+
+    let json = serde_json::to_vec(&fs.manifest).unwrap();
+    let manifest = EmbrFS::manifest_from_bytes(&json, &Keyring::new()).unwrap();
+    assert_eq!(manifest.chunk_size(), 1000);
+    let bytes = EmbrFS::reconstruct_bytes(&fs.engram, &manifest.files[0], &config, manifest.chunk_size()).unwrap();
+    assert_eq!(bytes, data);
+    assert_eq!(EmbrFS::verify(&fs.engram, &manifest, &config).unwrap().files_verified, 1);
+
+    // The default is left out, so existing manifests are unchanged.
+    let json = serde_json::to_value(EmbrFS::new().manifest).unwrap();
+    assert!(json.get("encoding").is_none_or(|e| e.get("chunk_size").is_none()));
+    assert_eq!(EmbrFS::new().manifest.chunk_size(), DEFAULT_CHUNK_SIZE);
+
+    let zero = EncodingConfig {
+        chunk_size: 0,
+        ..Default::default()
+    };
+    assert!(EmbrFS::with_encoding(zero).is_err());
+}
@@ -0,0 +1,45 @@
+//! Tests for engram info reports and storage format detection.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn engram_info_and_storage_format() {
+    use embeddenator::embrfs::EmbrFS;
+    use embeddenator::info::{EngramInfo, StorageFormat};
+    use embeddenator::vsa::DIM;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    let payload: Vec<u8> = (0..9000u32).map(|i| (i * 13 % 256) as u8).collect();
+    fs::write(src.join("a.bin"), &payload).unwrap();
+    fs::write(src.join("b.bin"), &payload).unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &ReversibleVSAConfig::default()).unwrap();
+
+    let info = EngramInfo::compute(&embrfs.engram, Some(&embrfs.manifest));
+    assert_eq!(info.dimension, DIM);
+    assert_eq!(info.chunks, embrfs.engram.codebook.len());
+    assert_eq!(info.root_nnz, embrfs.engram.root.pos.len() + embrfs.engram.root.neg.len());
+    assert!((info.root_density - info.root_nnz as f64 / DIM as f64).abs() < 1e-12);
+    assert!(info.mean_chunk_nnz > 0.0 && info.codebook_bytes > 0);
+    let totals = info.totals.as_ref().unwrap();
+    assert_eq!(totals.files, 2);
+    assert!(totals.dedup_ratio >= 2.0, "identical files dedup: {totals:?}");
+    assert!(EngramInfo::compute(&embrfs.engram, None).totals.is_none());
+
+    let plain = tmp.path().join("root.engram");
+    embrfs.save_engram(&plain).unwrap();
+    let format = StorageFormat::detect(&plain).unwrap();
+    assert_eq!(format.container, "bincode");
+    assert_eq!(format.file_bytes, fs::metadata(&plain).unwrap().len());
+    assert!(!format.encrypted && !format.checksummed);
+
+    let log = tmp.path().join("root.edna");
+    embrfs.save_append_log(&log).unwrap();
+    let format = StorageFormat::detect(&log).unwrap();
+    assert_eq!((format.container.as_str(), format.version), ("append-log", 1));
+}
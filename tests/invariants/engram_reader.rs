@@ -0,0 +1,98 @@
+//! Tests for the shared engram reader: concurrent reads and prefetching.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn engram_reader_serves_concurrent_reads_and_shares_with_mount() {
+    use embeddenator::{EmbrFS, EngramFS, EngramReader};
+    use std::sync::Arc;
+
+    let config = ReversibleVSAConfig::default();
+    let files: Vec<Vec<u8>> = (0..4u8)
+        .map(|i| (0..10_000u32).map(|j| (j as u8).wrapping_mul(i + 3) ^ i).collect())
+        .collect();
+    let mut fs = EmbrFS::new();
+    for (i, data) in files.iter().enumerate() {
+        fs.ingest_bytes(data, format!("dir/f{}.bin", i), &config).unwrap();
+    }
+    let probe = fs.engram.codebook[&fs.manifest.files[2].chunks[1]].clone();
+    let reader = Arc::new(EngramReader::from_embrfs(fs, config));
+
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let reader = Arc::clone(&reader);
+            let (files, probe) = (files.clone(), probe.clone());
+            std::thread::spawn(move || {
+                let i = t % files.len();
+                let path = format!("dir/f{}.bin", i);
+                assert_eq!(reader.read_file(&path).unwrap(), files[i]);
+                assert_eq!(reader.read_range(&path, 4000, 9000).unwrap(), files[i][4000..9000]);
+                let ids = |hits: Vec<embeddenator::RerankedResult>| hits.into_iter().map(|h| h.id).collect::<Vec<_>>();
+                assert_eq!(ids(reader.query_codebook(&probe, 3)), ids(reader.engram().query_codebook(&probe, 3)));
+                assert!(!reader.similar_chunks(&files[i][..4096], 2).is_empty());
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert!(reader.verify().unwrap().is_ok());
+    assert!(reader.read_file("missing").is_err());
+    let stats = reader.chunk_cache().stats();
+    assert!(stats.hits > 0);
+
+    // A mount over the reader decodes from the same cache.
+    let mount = EngramFS::from_reader(&reader, true);
+    assert_eq!(mount.file_count(), 4);
+    let ino = mount.lookup_path("/dir/f1.bin").unwrap();
+    assert_eq!(mount.read_data(ino, 0, 10_000).unwrap(), files[1]);
+    assert!(reader.chunk_cache().stats().hits > stats.hits);
+}
+
+#[test]
+fn reader_prefetch_warms_chunks_and_sub_engrams() {
+    use embeddenator::embrfs::{save_sub_engrams_dir, DirectorySubEngramStore, SubEngramStore, DEFAULT_CHUNK_SIZE};
+    use embeddenator::{EmbrFS, EngramReader, TieredSubEngramStore};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let data: Vec<u8> = (0..3 * DEFAULT_CHUNK_SIZE as u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 9) as u8).collect();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&data, "logs/day1.bin".into(), &config).unwrap();
+    fs.ingest_bytes(b"small", "notes.txt".into(), &config).unwrap();
+    let hierarchical = fs.bundle_hierarchically(200, false, &config).unwrap();
+    let dir = tempdir().unwrap();
+    save_sub_engrams_dir(&hierarchical.sub_engrams, dir.path()).unwrap();
+
+    let entry = fs.manifest.files.iter().find(|f| f.path == "logs/day1.bin").unwrap().clone();
+    let wanted = hierarchical.sub_engrams_holding(&entry.chunks[1..]);
+    assert!(!wanted.is_empty());
+
+    let tiered = Arc::new(TieredSubEngramStore::new(DirectorySubEngramStore::new(dir.path()), 16));
+    let store: Arc<dyn SubEngramStore + Send + Sync> = tiered.clone();
+    let reader = EngramReader::from_embrfs(fs, config).with_sub_engrams(hierarchical, store);
+
+    let report = reader
+        .prefetch("logs/day1.bin", DEFAULT_CHUNK_SIZE + 1..usize::MAX)
+        .unwrap();
+    assert_eq!(report.chunks, 2);
+    assert_eq!(report.sub_engrams, wanted.len());
+    assert!(wanted.iter().all(|id| tiered.is_cached(id)));
+    assert_eq!(reader.chunk_cache().len(), 2);
+
+    // The warmed range reads without decoding again.
+    let misses = reader.chunk_cache().stats().misses;
+    let mut tail = Vec::new();
+    reader
+        .stream_range(&entry, DEFAULT_CHUNK_SIZE, data.len(), |b| {
+            tail.extend_from_slice(b);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(tail, &data[DEFAULT_CHUNK_SIZE..]);
+    assert_eq!(reader.chunk_cache().stats().misses, misses);
+
+    assert_eq!(reader.prefetch("notes.txt", 10..20).unwrap(), Default::default());
+    assert!(reader.prefetch("missing.txt", 0..1).is_err());
+}
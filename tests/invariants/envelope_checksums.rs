@@ -0,0 +1,114 @@
+//! Tests for per-frame and root checksums in framed EDN1 envelopes.
+
+use embeddenator::envelope::{unwrap_auto, wrap_or_legacy, ChecksumCodec, EnvelopeCorruption, EnvelopeWriter};
+use embeddenator::{BinaryWriteOptions, EmbrError, EmbrFS, PayloadKind, ReversibleVSAConfig};
+use std::fs;
+use std::io::Write;
+
+const FRAME_HEADER_LEN: usize = 8;
+
+fn checksummed(checksum: ChecksumCodec, payload: &[u8], frame_size: usize) -> Vec<u8> {
+    let opts = BinaryWriteOptions {
+        checksum,
+        ..Default::default()
+    };
+    let mut writer = EnvelopeWriter::new(Vec::new(), PayloadKind::EngramBincode, opts)
+        .unwrap()
+        .with_frame_size(frame_size);
+    writer.write_all(payload).unwrap();
+    writer.finish().unwrap()
+}
+
+fn corruption_of(err: &std::io::Error) -> EnvelopeCorruption {
+    err.get_ref()
+        .and_then(|e| e.downcast_ref::<EnvelopeCorruption>())
+        .unwrap_or_else(|| panic!("expected EnvelopeCorruption, got {err}"))
+        .clone()
+}
+
+#[test]
+fn checksummed_envelopes_roundtrip() {
+    let payload: Vec<u8> = (0..5000u32).map(|i| (i % 241) as u8).collect();
+    for checksum in [ChecksumCodec::Blake3, ChecksumCodec::Xxh3] {
+        let bytes = checksummed(checksum, &payload, 1024);
+        assert_eq!(&bytes[..4], b"EDN1");
+        assert_eq!(unwrap_auto(PayloadKind::EngramBincode, &bytes).unwrap(), payload);
+
+        let opts = BinaryWriteOptions {
+            checksum,
+            ..Default::default()
+        };
+        let wrapped = wrap_or_legacy(PayloadKind::EngramBincode, opts, &payload).unwrap();
+        assert_eq!(unwrap_auto(PayloadKind::EngramBincode, &wrapped).unwrap(), payload);
+    }
+}
+
+#[test]
+fn flipped_frame_byte_reports_frame_and_offset() {
+    let payload = vec![0x5au8; 3000];
+    let bytes = checksummed(ChecksumCodec::Blake3, &payload, 1000);
+
+    // Header (16) + checksum extension (4) precede the first frame; each frame
+    // is header + 1000 stored bytes + 32-byte digest.
+    let frame_span = (FRAME_HEADER_LEN + 1000 + 32) as u64;
+    let second_frame = 20 + frame_span;
+    let mut corrupt = bytes.clone();
+    corrupt[second_frame as usize + FRAME_HEADER_LEN + 10] ^= 0xff;
+
+    let err = unwrap_auto(PayloadKind::EngramBincode, &corrupt).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let c = corruption_of(&err);
+    assert_eq!(c.checksum, ChecksumCodec::Blake3);
+    assert_eq!(c.frame, Some(1));
+    assert_eq!(c.offset, second_frame);
+    assert_eq!(c.len, frame_span);
+    assert_ne!(c.expected, c.actual);
+    assert!(err.to_string().contains("frame 1"));
+}
+
+#[test]
+fn tampered_trailer_reports_root_mismatch() {
+    let payload = vec![1u8; 100];
+    let bytes = checksummed(ChecksumCodec::Xxh3, &payload, 64);
+
+    // The trailer is `[0; 8] total:u64 root:[u8; 8]`; bump the recorded total.
+    let mut corrupt = bytes.clone();
+    let total_at = corrupt.len() - 8 - 8;
+    corrupt[total_at] ^= 0x01;
+
+    let err = unwrap_auto(PayloadKind::EngramBincode, &corrupt).unwrap_err();
+    let c = corruption_of(&err);
+    assert_eq!(c.frame, None);
+    assert_eq!(c.offset, 0);
+    assert_eq!(c.len, (bytes.len() - 8) as u64);
+}
+
+#[test]
+fn load_engram_surfaces_typed_corruption() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("a.txt"), b"checksummed engram payload").unwrap();
+
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_directory(&input, false, &ReversibleVSAConfig::default())
+        .unwrap();
+
+    let path = td.path().join("root.engram");
+    let opts = BinaryWriteOptions {
+        checksum: ChecksumCodec::Blake3,
+        ..Default::default()
+    };
+    fsys.save_engram_with_options(&path, opts).unwrap();
+    EmbrFS::load_engram(&path).unwrap();
+
+    let mut bytes = fs::read(&path).unwrap();
+    let mid = bytes.len() / 2;
+    bytes[mid] ^= 0x80;
+    fs::write(&path, &bytes).unwrap();
+
+    let err = EmbrFS::load_engram(&path)
+        .err()
+        .expect("corrupted engram should fail to load");
+    assert!(matches!(err, EmbrError::EnvelopeCorruption(_)), "{err}");
+}
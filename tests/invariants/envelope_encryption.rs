@@ -0,0 +1,162 @@
+//! Tests for AEAD-encrypted EDN1 envelopes.
+
+use embeddenator::envelope::wrap_or_legacy;
+use embeddenator::{BinaryWriteOptions, EncryptionCodec, EncryptionKey, PayloadKind};
+
+fn encrypted_opts(key: EncryptionKey) -> BinaryWriteOptions {
+    BinaryWriteOptions {
+        encryption: EncryptionCodec::XChaCha20Poly1305,
+        key: Some(key),
+        ..Default::default()
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+#[test]
+fn encryption_reports_missing_feature() {
+    let opts = encrypted_opts(EncryptionKey::new(1, [7u8; 32]));
+    let err = wrap_or_legacy(PayloadKind::EngramBincode, opts, b"secret").unwrap_err();
+    assert!(err.to_string().contains("not enabled"), "unexpected error: {err}");
+}
+
+#[test]
+fn encryption_without_key_is_rejected() {
+    let opts = BinaryWriteOptions {
+        encryption: EncryptionCodec::XChaCha20Poly1305,
+        ..Default::default()
+    };
+    assert!(wrap_or_legacy(PayloadKind::EngramBincode, opts, b"secret").is_err());
+}
+
+#[cfg(feature = "encryption")]
+mod enabled {
+    use super::*;
+    use embeddenator::envelope::{unwrap_auto, unwrap_auto_with_keys, EnvelopeWriter};
+    use embeddenator::{ChecksumCodec, EmbrFS, Keyring, ReversibleVSAConfig};
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn roundtrip_hides_plaintext() {
+        let key = EncryptionKey::new(3, [9u8; 32]);
+        let payload = b"top secret codebook contents ".repeat(50);
+        let wrapped = wrap_or_legacy(PayloadKind::EngramBincode, encrypted_opts(key), &payload).unwrap();
+
+        assert!(!wrapped.windows(10).any(|w| w == &payload[..10]));
+        let keys: Keyring = [key].into_iter().collect();
+        assert_eq!(unwrap_auto_with_keys(PayloadKind::EngramBincode, &wrapped, &keys).unwrap(), payload);
+    }
+
+    #[test]
+    fn keyring_selects_key_by_id_after_rotation() {
+        let old = EncryptionKey::new(1, [1u8; 32]);
+        let new = EncryptionKey::new(2, [2u8; 32]);
+        let before = wrap_or_legacy(PayloadKind::EngramBincode, encrypted_opts(old), b"before").unwrap();
+        let after = wrap_or_legacy(PayloadKind::EngramBincode, encrypted_opts(new), b"after").unwrap();
+
+        let keys: Keyring = [old, new].into_iter().collect();
+        assert_eq!(unwrap_auto_with_keys(PayloadKind::EngramBincode, &before, &keys).unwrap(), b"before");
+        assert_eq!(unwrap_auto_with_keys(PayloadKind::EngramBincode, &after, &keys).unwrap(), b"after");
+    }
+
+    #[test]
+    fn missing_or_wrong_key_is_permission_denied() {
+        let key = EncryptionKey::new(5, [5u8; 32]);
+        let wrapped = wrap_or_legacy(PayloadKind::EngramBincode, encrypted_opts(key), b"payload").unwrap();
+
+        let err = unwrap_auto(PayloadKind::EngramBincode, &wrapped).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("key id 5"));
+
+        let wrong: Keyring = [EncryptionKey::new(5, [6u8; 32])].into_iter().collect();
+        let err = unwrap_auto_with_keys(PayloadKind::EngramBincode, &wrapped, &wrong).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn tampered_or_reordered_frames_fail_authentication() {
+        let key = EncryptionKey::new(1, [4u8; 32]);
+        let keys: Keyring = [key].into_iter().collect();
+        let mut writer = EnvelopeWriter::new(Vec::new(), PayloadKind::EngramBincode, encrypted_opts(key))
+            .unwrap()
+            .with_frame_size(16);
+        writer.write_all(&[0xabu8; 32]).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        // Header (16) + encryption extension (16), then two frames of
+        // 8-byte header + 24-byte nonce + 16 ciphertext + 16 tag.
+        let first = 32;
+        let frame_len = 8 + 24 + 16 + 16;
+
+        let mut flipped = bytes.clone();
+        flipped[first + 8 + 30] ^= 1;
+        let err = unwrap_auto_with_keys(PayloadKind::EngramBincode, &flipped, &keys).unwrap_err();
+        assert!(err.to_string().contains("frame 0"), "unexpected error: {err}");
+
+        let mut swapped = bytes.clone();
+        let (a, b) = swapped[first..first + 2 * frame_len].split_at_mut(frame_len);
+        a.swap_with_slice(b);
+        assert!(unwrap_auto_with_keys(PayloadKind::EngramBincode, &swapped, &keys).is_err());
+    }
+
+    #[test]
+    fn engram_and_manifest_roundtrip_encrypted_with_checksums() {
+        let td = tempfile::tempdir().unwrap();
+        let input = td.path().join("in");
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("a.txt"), b"encrypted at rest").unwrap();
+
+        let config = ReversibleVSAConfig::default();
+        let mut fsys = EmbrFS::new();
+        fsys.ingest_directory(&input, false, &config).unwrap();
+
+        let key = EncryptionKey::new(7, [0x42u8; 32]);
+        let opts = BinaryWriteOptions {
+            checksum: ChecksumCodec::Blake3,
+            ..encrypted_opts(key)
+        };
+        let engram_path = td.path().join("root.engram");
+        let manifest_path = td.path().join("manifest.json");
+        fsys.save_engram_with_options(&engram_path, opts).unwrap();
+        fsys.save_manifest_with_options(&manifest_path, opts).unwrap();
+
+        assert!(!fs::read(&manifest_path).unwrap().windows(5).any(|w| w == b"a.txt"));
+        assert!(EmbrFS::load_manifest(&manifest_path).is_err());
+
+        let keys: Keyring = [key].into_iter().collect();
+        let engram = EmbrFS::load_engram_with_keys(&engram_path, &keys).unwrap();
+        let manifest = EmbrFS::load_manifest_with_keys(&manifest_path, &keys).unwrap();
+        let out = td.path().join("out");
+        EmbrFS::extract(&engram, &manifest, &out, false, &config).unwrap();
+        assert_eq!(fs::read(out.join("a.txt")).unwrap(), b"encrypted at rest");
+    }
+
+    #[test]
+    fn mount_decrypts_chunks_on_access() {
+        use embeddenator::fuse_shim::EngramFS;
+        use embeddenator::LazyEngram;
+
+        let td = tempfile::tempdir().unwrap();
+        let config = ReversibleVSAConfig::default();
+        let data: Vec<u8> = (0..9_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let mut fsys = EmbrFS::new();
+        fsys.ingest_bytes(&data, "big.bin".into(), &config).unwrap();
+        fsys.ingest_bytes(b"small", "dir/small.txt".into(), &config).unwrap();
+
+        let key = EncryptionKey::new(2, [0x17u8; 32]);
+        let engram_path = td.path().join("root.engram");
+        fsys.save_engram_with_options(&engram_path, encrypted_opts(key)).unwrap();
+
+        assert!(LazyEngram::open(&engram_path, &Keyring::default()).is_err());
+        let keys: Keyring = [key].into_iter().collect();
+        let lazy = LazyEngram::open(&engram_path, &keys).unwrap();
+        let mount = EngramFS::from_lazy(lazy, &fsys.manifest, config, true).with_cache_limits(1, 1 << 20);
+
+        let ino = mount.lookup_path("/big.bin").unwrap();
+        assert_eq!(mount.read_data(ino, 0, 10_000).unwrap(), data);
+        assert_eq!(mount.read_data(ino, 4090, 20).unwrap(), &data[4090..4110]);
+        assert_eq!(mount.chunk_cache().len(), 1);
+        let ino = mount.lookup_path("/dir/small.txt").unwrap();
+        assert_eq!(mount.read_data(ino, 0, 100).unwrap(), b"small");
+    }
+}
@@ -0,0 +1,178 @@
+//! Tests for the incremental EDN1 envelope reader/writer.
+
+use embeddenator::envelope::{unwrap_auto, EnvelopeReader, EnvelopeWriter};
+use embeddenator::{BinaryWriteOptions, CompressionCodec, PayloadKind};
+use std::io::{Read, Write};
+
+/// Build a framed envelope by hand using the uncompressed codec, so the
+/// framing logic is exercised without any compression feature enabled.
+fn make_framed(kind: u8, frames: &[&[u8]], trailer_total: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(b"EDN1");
+    out.push(kind);
+    out.push(0);
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    for frame in frames {
+        out.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        out.extend_from_slice(frame);
+    }
+    out.extend_from_slice(&[0u8; 8]);
+    out.extend_from_slice(&trailer_total.to_le_bytes());
+    out
+}
+
+fn read_all(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    EnvelopeReader::new(data, PayloadKind::EngramBincode)?.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[test]
+fn uncompressed_writer_is_legacy_passthrough() {
+    let payload: Vec<u8> = (0..10_000u32).map(|i| (i % 253) as u8).collect();
+    let mut writer = EnvelopeWriter::new(Vec::new(), PayloadKind::EngramBincode, BinaryWriteOptions::default())
+        .unwrap();
+    writer.write_all(&payload).unwrap();
+    let bytes = writer.finish().unwrap();
+    assert_eq!(bytes, payload);
+    assert_eq!(read_all(&bytes).unwrap(), payload);
+}
+
+#[test]
+fn reader_accepts_short_legacy_input() {
+    assert_eq!(read_all(b"").unwrap(), b"");
+    assert_eq!(read_all(b"EDN1xx").unwrap(), b"EDN1xx");
+}
+
+#[test]
+fn framed_envelope_decodes_across_frames() {
+    let data = make_framed(1, &[b"hello ", b"streaming ", b"world"], 21);
+    assert_eq!(read_all(&data).unwrap(), b"hello streaming world");
+    assert_eq!(unwrap_auto(PayloadKind::EngramBincode, &data).unwrap(), b"hello streaming world");
+}
+
+#[test]
+fn framed_envelope_rejects_truncation_and_bad_trailer() {
+    let data = make_framed(1, &[b"abc", b"def"], 6);
+    assert!(read_all(&data[..data.len() - 12]).is_err());
+    assert!(read_all(&make_framed(1, &[b"abc"], 4)).is_err());
+}
+
+#[test]
+fn framed_envelope_rejects_kind_mismatch() {
+    let data = make_framed(2, &[b"abc"], 3);
+    assert!(read_all(&data).is_err());
+}
+
+#[cfg(feature = "compression-zstd")]
+#[test]
+fn zstd_writer_roundtrips_with_small_frames() {
+    let payload: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let opts = BinaryWriteOptions {
+        codec: CompressionCodec::Zstd,
+        level: Some(3),
+        ..Default::default()
+    };
+    let mut writer = EnvelopeWriter::new(Vec::new(), PayloadKind::EngramBincode, opts)
+        .unwrap()
+        .with_frame_size(4096);
+    for piece in payload.chunks(1000) {
+        writer.write_all(piece).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    assert_eq!(&bytes[..4], b"EDN1");
+    assert_eq!(read_all(&bytes).unwrap(), payload);
+    assert_eq!(unwrap_auto(PayloadKind::EngramBincode, &bytes).unwrap(), payload);
+}
+
+#[cfg(not(feature = "compression-zstd"))]
+#[test]
+fn writer_reports_missing_codec_up_front() {
+    let opts = BinaryWriteOptions {
+        codec: CompressionCodec::Zstd,
+        level: None,
+        ..Default::default()
+    };
+    let mut sink = Vec::new();
+    let err = EnvelopeWriter::new(&mut sink, PayloadKind::EngramBincode, opts).err().unwrap();
+    assert!(err.to_string().contains("not enabled"));
+    assert!(sink.is_empty());
+}
+
+#[test]
+fn dictionary_sampler_caps_and_spreads_samples() {
+    use embeddenator::envelope::DictionarySampler;
+
+    let mut sampler = DictionarySampler::new(16);
+    sampler.write_all(&vec![7u8; 16 * 5000]).unwrap();
+    let count = sampler.sample_count();
+    assert!(count > 256 && count <= 1024, "unexpected sample count {count}");
+}
+
+#[cfg(feature = "compression-zstd")]
+fn source_like_corpus() -> Vec<u8> {
+    let mut out = Vec::new();
+    for i in 0..20_000 {
+        out.extend_from_slice(
+            format!(
+                "pub fn handler_{i}(ctx: &mut Context, req: Request) -> Result<Response, Error> {{\n    \
+                 let value_{i} = ctx.lookup(\"key_{}\")?;\n    Ok(Response::new(value_{i} * {}))\n}}\n\n",
+                i * 31 % 977,
+                i % 13
+            )
+            .as_bytes(),
+        );
+    }
+    out
+}
+
+#[cfg(feature = "compression-zstd")]
+#[test]
+fn zstd_dict_roundtrips_and_beats_plain_per_frame_zstd() {
+    use embeddenator::envelope::{wrap_or_legacy, DICT_FRAME_SIZE};
+
+    let payload = source_like_corpus();
+    let dict_opts = BinaryWriteOptions {
+        codec: CompressionCodec::ZstdDict,
+        level: Some(3),
+        ..Default::default()
+    };
+    let wrapped = wrap_or_legacy(PayloadKind::EngramBincode, dict_opts, &payload).unwrap();
+    assert_eq!(&wrapped[..4], b"EDN1");
+    assert_eq!(read_all(&wrapped).unwrap(), payload);
+    assert_eq!(unwrap_auto(PayloadKind::EngramBincode, &wrapped).unwrap(), payload);
+
+    let plain_opts = BinaryWriteOptions {
+        codec: CompressionCodec::Zstd,
+        level: Some(3),
+        ..Default::default()
+    };
+    let mut writer = EnvelopeWriter::new(Vec::new(), PayloadKind::EngramBincode, plain_opts)
+        .unwrap()
+        .with_frame_size(DICT_FRAME_SIZE);
+    writer.write_all(&payload).unwrap();
+    let plain = writer.finish().unwrap();
+
+    assert!(
+        wrapped.len() < plain.len(),
+        "dictionary envelope ({}) should be smaller than plain per-frame zstd ({})",
+        wrapped.len(),
+        plain.len()
+    );
+}
+
+#[cfg(feature = "compression-zstd")]
+#[test]
+fn zstd_dict_handles_payloads_too_small_to_train() {
+    use embeddenator::envelope::wrap_or_legacy;
+
+    let opts = BinaryWriteOptions {
+        codec: CompressionCodec::ZstdDict,
+        level: None,
+        ..Default::default()
+    };
+    let wrapped = wrap_or_legacy(PayloadKind::EngramBincode, opts, b"tiny").unwrap();
+    assert_eq!(read_all(&wrapped).unwrap(), b"tiny");
+}
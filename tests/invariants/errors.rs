@@ -0,0 +1,82 @@
+//! Tests for EmbrError variants and their io::Error round trip.
+
+use embeddenator::{
+    BinaryWriteOptions, ChecksumCodec, EmbrError, EmbrFS, IngestLimits, ReversibleVSAConfig, DEFAULT_CHUNK_SIZE,
+};
+use std::io;
+
+#[test]
+fn typed_errors_survive_io_round_trips() {
+    let err = EmbrError::MissingChunk {
+        path: "a.bin".into(),
+        chunk_id: 7,
+    };
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let as_io = io::Error::from(err);
+    assert_eq!(as_io.kind(), io::ErrorKind::InvalidData);
+    assert!(matches!(
+        EmbrError::find(&as_io),
+        Some(EmbrError::MissingChunk { chunk_id: 7, .. })
+    ));
+    assert!(matches!(
+        EmbrError::from(as_io),
+        EmbrError::MissingChunk { chunk_id: 7, .. }
+    ));
+
+    // Plain I/O errors pass through unchanged.
+    let err = EmbrError::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+    assert!(matches!(err, EmbrError::Io(_)));
+    let back = io::Error::from(err);
+    assert_eq!(
+        (back.kind(), back.to_string()),
+        (io::ErrorKind::NotFound, "gone".to_string())
+    );
+    assert!(EmbrError::find(&back).is_none());
+}
+
+#[test]
+fn engram_api_reports_structured_failures() {
+    let config = ReversibleVSAConfig::default();
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&data, "a.bin".into(), &config).unwrap();
+
+    // A chunk that decodes to the wrong bytes fails the file checksum.
+    let chunks = fs.manifest.files[0].chunks.clone();
+    let first = fs.engram.codebook[&chunks[0]].clone();
+    fs.engram.codebook.insert(chunks[1], first);
+    fs.engram.corrections = Default::default();
+    match EmbrFS::reconstruct_bytes(&fs.engram, &fs.manifest.files[0], &config, DEFAULT_CHUNK_SIZE).unwrap_err() {
+        EmbrError::ManifestMismatch(files) => {
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].path, "a.bin");
+        }
+        other => panic!("expected a manifest mismatch, got {other}"),
+    }
+
+    // A manifest envelope is not an engram.
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("manifest.env");
+    let opts = BinaryWriteOptions {
+        checksum: ChecksumCodec::Xxh3,
+        ..Default::default()
+    };
+    fs.save_manifest_with_options(&path, opts).unwrap();
+    let Err(err) = EmbrFS::load_engram(&path) else {
+        panic!("a manifest loaded as an engram");
+    };
+    assert!(matches!(err, EmbrError::InvalidEnvelope(_)), "{err:?}");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut limited = EmbrFS::with_limits(IngestLimits {
+        max_file_size: Some(10),
+        ..Default::default()
+    });
+    let err = limited
+        .ingest_bytes(&data, "big".into(), &config)
+        .unwrap_err();
+    assert!(
+        matches!(err, EmbrError::Quota(ref q) if q.path == "big"),
+        "{err}"
+    );
+}
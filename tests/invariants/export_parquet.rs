@@ -0,0 +1,77 @@
+//! Tests for Parquet export of manifest and chunk metadata.
+
+use crate::support::ingested;
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn parquet_reports_missing_feature() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    let out = td.path().join("export");
+    let err = embeddenator::export::to_parquet(&fsys.engram, &fsys.manifest, &out).unwrap_err();
+    assert!(err.to_string().contains("not enabled"), "unexpected error: {err}");
+    assert!(!out.exists());
+}
+
+#[cfg(feature = "parquet")]
+mod enabled {
+    use super::*;
+    use std::fs;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{UInt32Type, UInt64Type};
+    use arrow_array::RecordBatch;
+    use embeddenator::export::{to_parquet, CHUNKS_TABLE, FILES_TABLE};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn read_table(path: &std::path::Path) -> Vec<RecordBatch> {
+        ParquetRecordBatchReaderBuilder::try_new(fs::File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn tables_match_manifest() {
+        let td = tempfile::tempdir().unwrap();
+        let fsys = ingested(td.path());
+        let out = td.path().join("export");
+        let summary = to_parquet(&fsys.engram, &fsys.manifest, &out).unwrap();
+
+        let total_refs: usize = fsys.manifest.files.iter().map(|f| f.chunks.len()).sum();
+        assert_eq!(summary.files, fsys.manifest.files.len());
+        assert_eq!(summary.chunks, total_refs);
+
+        let files = read_table(&out.join(FILES_TABLE));
+        assert_eq!(files.iter().map(RecordBatch::num_rows).sum::<usize>(), summary.files);
+        let batch = &files[0];
+        let paths = batch.column_by_name("path").unwrap().as_string::<i32>();
+        let sizes = batch.column_by_name("size").unwrap().as_primitive::<UInt64Type>();
+        for (row, entry) in fsys.manifest.files.iter().enumerate() {
+            assert_eq!(paths.value(row), entry.path);
+            assert_eq!(sizes.value(row), entry.size as u64);
+        }
+
+        let chunks = read_table(&out.join(CHUNKS_TABLE));
+        assert_eq!(chunks.iter().map(RecordBatch::num_rows).sum::<usize>(), total_refs);
+        let batch = &chunks[0];
+        let ids = batch.column_by_name("chunk_id").unwrap().as_primitive::<UInt64Type>();
+        let index = batch.column_by_name("chunk_index").unwrap().as_primitive::<UInt32Type>();
+        let lens = batch.column_by_name("len").unwrap().as_primitive::<UInt64Type>();
+        let signatures = batch.column_by_name("signature").unwrap();
+        assert_eq!(signatures.null_count(), 0);
+
+        let mut row = 0;
+        for entry in &fsys.manifest.files {
+            let mut covered = 0;
+            for (idx, &id) in entry.chunks.iter().enumerate() {
+                assert_eq!(ids.value(row), id as u64);
+                assert_eq!(index.value(row), idx as u32);
+                covered += lens.value(row);
+                row += 1;
+            }
+            assert_eq!(covered, entry.size as u64);
+        }
+    }
+}
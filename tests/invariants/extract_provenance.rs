@@ -0,0 +1,47 @@
+//! Tests for chunk provenance reported by extraction.
+
+#[test]
+fn extract_reports_chunk_provenance() {
+    use embeddenator::correction::CorrectionStore;
+    use embeddenator::{ChunkProvenance, ChunkSource, EmbrFS, ReversibleVSAConfig};
+
+    let config = ReversibleVSAConfig::default();
+    let v1: Vec<u8> = (0..10_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    let mut v2 = v1.clone();
+    v2[5000..5008].copy_from_slice(b"patched!");
+
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&v1, "v1/blob.bin".into(), &config).unwrap();
+    fs.ingest_version(&v2, "v2/blob.bin".into(), "v1/blob.bin", &config).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let report =
+        EmbrFS::extract_with_provenance(&fs.engram, &fs.manifest, None, dir.path(), false, &config, None).unwrap();
+    assert_eq!(std::fs::read(dir.path().join("v2/blob.bin")).unwrap(), v2);
+    assert_eq!(report.files.len(), 2);
+    assert_eq!(report.sources.total(), 6);
+    assert_eq!(report.files[1].sources.differential, 3);
+    assert_eq!(report.files[0].sources.differential, 0);
+    assert_eq!(report.sources.missing, 0);
+    assert_eq!((report.confidence, report.min_confidence), (1.0, 1.0));
+    let last = &report.files[0].chunks[2];
+    assert_eq!((last.len, last.verified), (10_000 - 2 * 4096, Some(true)));
+
+    // The JSON form carries the source of every chunk.
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["files"][1]["chunks"][0]["source"], "differential");
+
+    // Without a recorded hash, confidence falls back to how well the bytes
+    // re-encode to the stored vector; a missing vector scores 0.
+    let legacy = &mut fs.engram;
+    legacy.corrections = CorrectionStore::new();
+    let id = fs.manifest.files[0].chunks[0];
+    let exact = ChunkProvenance::of(legacy, id, "v1/blob.bin", 4096, Some(&v1[..4096]), &config);
+    assert_eq!((exact.source, exact.verified), (ChunkSource::Codebook, None));
+    assert!(exact.confidence > 0.99);
+    let wrong = ChunkProvenance::of(legacy, id, "v1/blob.bin", 4096, Some(&v1[4096..8192]), &config);
+    assert!(wrong.confidence < 0.5);
+    legacy.codebook.remove(&id);
+    let missing = ChunkProvenance::of(legacy, id, "v1/blob.bin", 4096, None, &config);
+    assert_eq!((missing.source, missing.confidence), (ChunkSource::Missing, 0.0));
+}
@@ -0,0 +1,107 @@
+//! The C ABI, driven from Rust the way a C caller would use it.
+
+use embeddenator::ffi::*;
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+fn c(s: &str) -> CString {
+    CString::new(s).unwrap()
+}
+
+fn last_error() -> String {
+    let msg = emb_last_error();
+    assert!(!msg.is_null());
+    unsafe { CStr::from_ptr(msg) }.to_string_lossy().into_owned()
+}
+
+#[test]
+fn engram_round_trip_through_c_abi() {
+    let td = tempfile::tempdir().unwrap();
+    let text = b"embedded from another runtime\n".repeat(400);
+    let engram_path = c(td.path().join("root.engram").to_str().unwrap());
+    let manifest_path = c(td.path().join("manifest.json").to_str().unwrap());
+
+    unsafe {
+        let engram = emb_engram_new();
+        let status = emb_engram_ingest_bytes(engram, c("notes/a.txt").as_ptr(), text.as_ptr(), text.len());
+        assert_eq!(status, EmbStatus::Ok);
+        assert_eq!(emb_engram_ingest_bytes(engram, c("empty").as_ptr(), ptr::null(), 0), EmbStatus::Ok);
+        assert_eq!(emb_engram_file_count(engram), 2);
+        assert_eq!(
+            emb_engram_save(engram, engram_path.as_ptr(), manifest_path.as_ptr()),
+            EmbStatus::Ok
+        );
+        emb_engram_free(engram);
+
+        let mut loaded = ptr::null_mut();
+        assert_eq!(
+            emb_engram_load(engram_path.as_ptr(), manifest_path.as_ptr(), &mut loaded),
+            EmbStatus::Ok
+        );
+        assert!(emb_engram_chunk_count(loaded) > 0);
+
+        let (mut data, mut len) = (ptr::null_mut(), 0usize);
+        assert_eq!(emb_engram_read_file(loaded, c("notes/a.txt").as_ptr(), &mut data, &mut len), EmbStatus::Ok);
+        assert_eq!(std::slice::from_raw_parts(data, len), &text[..]);
+        emb_bytes_free(data, len);
+
+        let (mut ids, mut scores, mut count) = ([0u64; 4], [0f64; 4], 0usize);
+        let status = emb_engram_query(loaded, text.as_ptr(), 4096, 4, ids.as_mut_ptr(), scores.as_mut_ptr(), &mut count);
+        assert_eq!(status, EmbStatus::Ok);
+        assert!(count > 0 && count <= 4);
+        assert!(scores[..count].windows(2).all(|w| w[0] >= w[1]));
+
+        let out_dir = td.path().join("out");
+        let out = c(out_dir.to_str().unwrap());
+        assert_eq!(emb_engram_extract(loaded, out.as_ptr()), EmbStatus::Ok);
+        assert_eq!(std::fs::read(out_dir.join("notes/a.txt")).unwrap(), text);
+        emb_engram_free(loaded);
+    }
+}
+
+#[test]
+fn errors_are_reported_not_raised() {
+    unsafe {
+        let engram = emb_engram_new();
+        let (mut data, mut len) = (ptr::null_mut(), 0usize);
+        assert_eq!(emb_engram_read_file(engram, c("missing").as_ptr(), &mut data, &mut len), EmbStatus::NotFound);
+        assert!(last_error().contains("missing"));
+        assert_eq!(emb_engram_ingest_bytes(engram, ptr::null(), ptr::null(), 0), EmbStatus::NullArgument);
+        assert_eq!(emb_engram_ingest_bytes(engram, c("x").as_ptr(), ptr::null(), 5), EmbStatus::NullArgument);
+        let bad_utf8 = [0xffu8, 0xfe, 0];
+        assert_eq!(
+            emb_engram_ingest_bytes(engram, bad_utf8.as_ptr().cast(), ptr::null(), 0),
+            EmbStatus::InvalidUtf8
+        );
+        let mut out = ptr::null_mut();
+        let status = emb_engram_load(c("/nonexistent/e").as_ptr(), c("/nonexistent/m").as_ptr(), &mut out);
+        assert_eq!(status, EmbStatus::NotFound);
+        assert!(out.is_null());
+        assert_eq!(emb_engram_file_count(ptr::null()), 0);
+        emb_engram_free(engram);
+        emb_engram_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn vector_ops_on_handles() {
+    unsafe {
+        let a = emb_vec_encode(b"alpha".as_ptr(), 5);
+        let b = emb_vec_random();
+        assert!(!a.is_null() && emb_vec_nnz(a) > 0);
+        assert!((emb_vec_cosine(a, a) - 1.0).abs() < 1e-9);
+
+        let bundled = emb_vec_bundle(a, b);
+        assert!(emb_vec_cosine(bundled, a) > emb_vec_cosine(b, a));
+        let bound = emb_vec_bind(a, b);
+        let shifted = emb_vec_permute(a, 7);
+        assert!(!bound.is_null() && !shifted.is_null());
+        assert!(emb_vec_cosine(shifted, a) < 0.5);
+
+        assert!(emb_vec_bundle(a, ptr::null()).is_null());
+        assert!(emb_vec_cosine(a, ptr::null()).is_nan());
+        for v in [a, b, bundled, bound, shifted] {
+            emb_vec_free(v);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Tests for file versions stored as chunk deltas.
+
+#[test]
+fn file_versions_store_chunk_deltas() {
+    use embeddenator::{CorrectionType, EmbrFS, ReversibleVSAConfig, DEFAULT_CHUNK_SIZE};
+
+    let config = ReversibleVSAConfig::default();
+    let v1: Vec<u8> = (0..20_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    let mut v2 = v1.clone();
+    v2[7000..7010].copy_from_slice(b"patched!!!");
+    v2.splice(12_000..12_000, b"grown".iter().copied());
+
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&v1, "v1/blob.bin".into(), &config).unwrap();
+    let before = fs.engram.corrections.stats().correction_bytes;
+    fs.ingest_version(&v2, "v2/blob.bin".into(), "v1/blob.bin", &config).unwrap();
+    let added = fs.engram.corrections.stats().correction_bytes - before;
+    assert!(added * 10 < before, "v2 added {added} correction bytes, v1 took {before}");
+    let v2_chunks = fs.manifest.files[1].chunks.clone();
+    assert!(v2_chunks.iter().all(|&id| matches!(
+        fs.engram.corrections.get(id as u64).unwrap().correction,
+        CorrectionType::Delta { .. }
+    )));
+    assert!(fs.ingest_version(&v2, "v3/blob.bin".into(), "missing.bin", &config).is_err());
+
+    // Deltas are applied on extract, also after a round trip through disk.
+    let td = tempfile::tempdir().unwrap();
+    fs.save_engram(td.path().join("e.engram")).unwrap();
+    let loaded = EmbrFS::load_engram(td.path().join("e.engram")).unwrap();
+    for (file, data) in fs.manifest.files.iter().zip([&v1, &v2]) {
+        assert_eq!(&EmbrFS::reconstruct_bytes(&loaded, file, &config, DEFAULT_CHUNK_SIZE).unwrap(), data);
+    }
+    #[cfg(feature = "proto")]
+    {
+        let bytes = embeddenator::wire::encode_engram(&fs.engram).unwrap();
+        let decoded = embeddenator::wire::decode_engram(&bytes).unwrap();
+        assert_eq!(EmbrFS::reconstruct_bytes(&decoded, &fs.manifest.files[1], &config, DEFAULT_CHUNK_SIZE).unwrap(), v2);
+    }
+
+    // Dropping the base version keeps v2 whole.
+    assert!(fs.remove_file("v1/blob.bin"));
+    assert!(!v2_chunks.iter().any(|&id| matches!(
+        fs.engram.corrections.get(id as u64).unwrap().correction,
+        CorrectionType::Delta { .. }
+    )));
+    assert_eq!(EmbrFS::reconstruct_bytes(&fs.engram, &fs.manifest.files[0], &config, DEFAULT_CHUNK_SIZE).unwrap(), v2);
+}
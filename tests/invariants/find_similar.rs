@@ -0,0 +1,71 @@
+//! Tests for `find_similar`: ranking ingested files against a local one.
+
+use embeddenator::{find_similar, ChunkEncoding, DimensionalConfig, EmbrFS, ReversibleVSAConfig, SimilarOptions};
+
+/// `len` bytes of pseudo-random words seeded by `seed`.
+fn text(seed: usize, len: usize) -> Vec<u8> {
+    let mut out = String::new();
+    let mut i = 0;
+    while out.len() < len {
+        out += &format!("w{} ", (seed * 7_919 + i * 104_729) % 100_003);
+        i += 1;
+    }
+    out.truncate(len);
+    out.into_bytes()
+}
+
+fn ingest(files: &[(&str, Vec<u8>)], config: &ReversibleVSAConfig) -> EmbrFS {
+    let mut fsys = EmbrFS::with_config(config.clone(), DimensionalConfig::default()).unwrap();
+    for (name, data) in files {
+        fsys.ingest_bytes(data, name.to_string(), config).unwrap();
+    }
+    fsys
+}
+
+#[test]
+fn a_copied_chunk_points_at_its_file_and_offset() {
+    let files = [("a.txt", text(1, 12_000)), ("b.txt", text(2, 12_000)), ("c.txt", text(3, 12_000))];
+    let fsys = ingest(&files, &ReversibleVSAConfig::default());
+
+    let query = files[1].1[4096..8192].to_vec();
+    let hits = find_similar(&fsys.engram, &fsys.manifest, &query, &SimilarOptions::default());
+
+    let top = &hits[0];
+    assert_eq!(top.path, "b.txt");
+    assert_eq!(top.coverage, 1.0);
+    let region = &top.regions[0];
+    assert_eq!((region.query_offset, region.query_len), (0, 4096));
+    assert_eq!((region.offset, region.len), (4096, 4096));
+    assert!(region.cosine > 0.99, "identical chunk scored {}", region.cosine);
+    assert!(hits.iter().skip(1).all(|f| f.score < top.score));
+}
+
+#[test]
+fn near_duplicates_rank_first_with_similarity_encoding() {
+    let config = ReversibleVSAConfig {
+        chunk_encoding: ChunkEncoding::similarity(),
+        ..ReversibleVSAConfig::default()
+    };
+    let files = [("a.txt", text(1, 6_000)), ("b.txt", text(2, 6_000)), ("c.txt", text(3, 6_000))];
+    let fsys = ingest(&files, &config);
+
+    let mut edited = files[2].1.clone();
+    edited[100..140].copy_from_slice(&[b'x'; 40]);
+    let hits = find_similar(&fsys.engram, &fsys.manifest, &edited, &SimilarOptions::default());
+
+    assert_eq!(hits[0].path, "c.txt");
+    assert_eq!(hits[0].regions.len(), 2);
+    assert!(hits[0].regions.iter().all(|r| r.query_offset == r.offset));
+}
+
+#[test]
+fn options_limit_files_and_namespace() {
+    let files = [("a.txt", text(1, 5_000)), ("b.txt", text(2, 5_000))];
+    let fsys = ingest(&files, &ReversibleVSAConfig::default());
+
+    let opts = SimilarOptions { k: 1, ..SimilarOptions::default() };
+    assert_eq!(find_similar(&fsys.engram, &fsys.manifest, &files[0].1, &opts).len(), 1);
+    let opts = SimilarOptions { namespace: Some("elsewhere".into()), ..SimilarOptions::default() };
+    assert!(find_similar(&fsys.engram, &fsys.manifest, &files[0].1, &opts).is_empty());
+    assert!(find_similar(&fsys.engram, &fsys.manifest, &[], &SimilarOptions::default()).is_empty());
+}
@@ -0,0 +1,117 @@
+//! Tests for the generational store: tiering by policy and reads across tiers.
+
+use embeddenator::delta::engram_digest;
+use embeddenator::{
+    DirectoryTier, EmbrFS, GenerationLocation, GenerationPolicy, GenerationalStore, ReversibleVSAConfig,
+};
+use std::fs;
+use std::path::Path;
+
+fn add_file(fsys: &mut EmbrFS, dir: &Path, name: &str, data: &[u8]) {
+    fs::write(dir.join(name), data).unwrap();
+    fsys.ingest_file(dir.join(name), name.to_string(), false, &ReversibleVSAConfig::default())
+        .unwrap();
+}
+
+#[test]
+fn old_generations_become_deltas_then_move_to_the_remote() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    fs::create_dir_all(&input).unwrap();
+    let remote_dir = td.path().join("remote");
+    let mut store = GenerationalStore::open(td.path().join("gens"))
+        .unwrap()
+        .with_policy(GenerationPolicy { keep_full: 2, keep_local: 3 })
+        .with_remote(Box::new(DirectoryTier::new(&remote_dir).unwrap()));
+
+    let mut fsys = EmbrFS::new();
+    add_file(&mut fsys, &input, "base.txt", "shared base content ".repeat(600).as_bytes());
+    let mut digests = Vec::new();
+    for i in 1..=5u32 {
+        add_file(&mut fsys, &input, &format!("v{i}.txt"), format!("release {i}").as_bytes());
+        let (record, _) = store.commit(&fsys).unwrap();
+        assert_eq!(record.id, u64::from(i));
+        digests.push(engram_digest(&fsys.engram).unwrap());
+    }
+
+    let gens = store.generations();
+    let forms: Vec<(Option<u64>, GenerationLocation)> = gens.iter().map(|g| (g.base, g.location)).collect();
+    assert_eq!(
+        forms,
+        [
+            (Some(2), GenerationLocation::Remote),
+            (Some(3), GenerationLocation::Remote),
+            (Some(4), GenerationLocation::Local),
+            (None, GenerationLocation::Local),
+            (None, GenerationLocation::Local),
+        ]
+    );
+    assert!(gens[2].stored_bytes < gens[3].stored_bytes);
+    let gen_dir = td.path().join("gens");
+    assert!(!gen_dir.join("1.engram").exists() && !gen_dir.join("1.delta").exists());
+    assert!(remote_dir.join("1.delta").exists() && gen_dir.join("3.delta").exists());
+
+    // Reads are transparent, and the policy survives a reopen.
+    let reopened = GenerationalStore::open(&gen_dir)
+        .unwrap()
+        .with_remote(Box::new(DirectoryTier::new(&remote_dir).unwrap()));
+    assert_eq!(reopened.policy(), GenerationPolicy { keep_full: 2, keep_local: 3 });
+    for (i, digest) in digests.iter().enumerate() {
+        let (engram, manifest) = reopened.read(i as u64 + 1).unwrap();
+        assert_eq!(&engram_digest(&engram).unwrap(), digest);
+        assert_eq!(manifest.files.len(), i + 2);
+    }
+    let out = td.path().join("out");
+    let (engram, manifest) = reopened.read(1).unwrap();
+    EmbrFS::extract(&engram, &manifest, &out, false, &ReversibleVSAConfig::default()).unwrap();
+    assert_eq!(fs::read(out.join("v1.txt")).unwrap(), b"release 1");
+    assert!(!out.join("v2.txt").exists());
+}
+
+#[test]
+fn remote_generations_need_the_remote_to_read() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    fs::create_dir_all(&input).unwrap();
+    let gen_dir = td.path().join("gens");
+    let mut store = GenerationalStore::open(&gen_dir)
+        .unwrap()
+        .with_policy(GenerationPolicy { keep_full: 1, keep_local: 1 })
+        .with_remote(Box::new(DirectoryTier::new(td.path().join("remote")).unwrap()));
+    let mut fsys = EmbrFS::new();
+    for i in 1..=2u32 {
+        add_file(&mut fsys, &input, &format!("f{i}.txt"), format!("file {i}").as_bytes());
+        store.commit(&fsys).unwrap();
+    }
+    assert_eq!(store.generations()[0].location, GenerationLocation::Remote);
+
+    let local_only = GenerationalStore::open(&gen_dir).unwrap();
+    assert!(local_only.read(2).is_ok());
+    let Err(err) = local_only.read(1) else { panic!("read a remote generation without the remote") };
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert!(err.to_string().contains("remote"), "{err}");
+    assert!(matches!(local_only.read(9), Err(e) if e.kind() == std::io::ErrorKind::NotFound));
+}
+
+#[test]
+fn tiering_without_a_remote_keeps_deltas_local() {
+    let td = tempfile::tempdir().unwrap();
+    let input = td.path().join("in");
+    fs::create_dir_all(&input).unwrap();
+    let mut store = GenerationalStore::open(td.path().join("gens")).unwrap();
+    let mut fsys = EmbrFS::new();
+    for i in 1..=4u32 {
+        add_file(&mut fsys, &input, &format!("f{i}.txt"), format!("file {i}").as_bytes());
+        store.commit(&fsys).unwrap();
+    }
+    // Tightening the policy later compacts what the default kept full.
+    let mut store = GenerationalStore::open(td.path().join("gens"))
+        .unwrap()
+        .with_policy(GenerationPolicy { keep_full: 1, keep_local: 1 });
+    let report = store.apply_policy().unwrap();
+    assert_eq!(report.compacted, [3]);
+    assert!(report.pushed.is_empty());
+    assert!(store.generations().iter().all(|g| g.location == GenerationLocation::Local));
+    assert_eq!(store.read(1).unwrap().1.files.len(), 1);
+    assert!(store.apply_policy().unwrap().is_empty());
+}
@@ -0,0 +1,232 @@
+//! Tests for chunk dedup and the per-commit git archive.
+
+use embeddenator::git_archive::{GitArchive, GitIngestOptions, MODE_EXECUTABLE};
+use embeddenator::{ChunkIndex, EmbrFS, ReversibleVSAConfig, DEFAULT_CHUNK_SIZE};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn git(repo: &Path, args: &[&str]) -> String {
+    git_at(repo, args, "2024-01-01T00:00:00Z")
+}
+
+fn git_at(repo: &Path, args: &[&str], date: &str) -> String {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false"])
+        .args(args)
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date)
+        .output()
+        .expect("git on PATH");
+    assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap().trim().to_string()
+}
+
+fn commit(repo: &Path, message: &str) -> String {
+    git(repo, &["add", "-A"]);
+    git(repo, &["commit", "-q", "--allow-empty", "-m", message]);
+    git(repo, &["rev-parse", "HEAD"])
+}
+
+fn data(seed: u8, len: usize) -> Vec<u8> {
+    (0..len).map(|i| ((i * 31) as u8).wrapping_add(seed) ^ (i >> 9) as u8).collect()
+}
+
+fn read_tree(dir: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut files: Vec<(String, Vec<u8>)> = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .map(|e| e.unwrap())
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| {
+            let rel = e.path().strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/");
+            (rel, fs::read(e.path()).unwrap())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn chunk_dedup_shares_identical_chunks_under_the_same_path() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.chunk_index = Some(ChunkIndex::default());
+    let v1 = data(1, 12_000);
+    let mut v2 = v1.clone();
+    v2[5000] ^= 0xff;
+
+    fs.ingest_bytes(&v1, "f".into(), &config).unwrap();
+    assert_eq!((fs.manifest.total_chunks, fs.engram.codebook.len()), (3, 3));
+    fs.ingest_bytes(&v2, "f".into(), &config).unwrap();
+    assert_eq!((fs.manifest.total_chunks, fs.engram.codebook.len()), (4, 4));
+    assert_eq!(fs.manifest.files[1].chunks, [0, 3, 2]);
+    // Encoding depends on the path, so another path gets its own chunks.
+    fs.ingest_bytes(&v1, "g".into(), &config).unwrap();
+    assert_eq!(fs.engram.codebook.len(), 7);
+    assert_eq!(fs.chunk_index.as_ref().unwrap().len(), 7);
+
+    for (file, expected) in fs.manifest.files.iter().zip([&v1, &v2, &v1]) {
+        assert_eq!(&EmbrFS::reconstruct_bytes(&fs.engram, file, &config, DEFAULT_CHUNK_SIZE).unwrap(), expected);
+    }
+
+    // Removing a file drops its private chunks from the index as well.
+    fs.remove_file("g");
+    assert_eq!(fs.chunk_index.as_ref().unwrap().len(), 4);
+    fs.ingest_bytes(&v1, "g".into(), &config).unwrap();
+    assert_eq!(fs.manifest.files[2].chunks, [7, 8, 9]);
+}
+
+#[test]
+fn every_commit_extracts_and_history_shares_chunks() {
+    let td = tempfile::tempdir().unwrap();
+    let repo = td.path().join("repo");
+    fs::create_dir(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+
+    let big = data(7, 20_000);
+    fs::write(repo.join("big.bin"), &big).unwrap();
+    fs::create_dir(repo.join("src")).unwrap();
+    fs::write(repo.join("src/lib.rs"), b"pub fn one() {}\n").unwrap();
+    let c1 = commit(&repo, "first");
+
+    let mut big2 = big.clone();
+    big2.extend_from_slice(b"tail");
+    fs::write(repo.join("big.bin"), &big2).unwrap();
+    fs::write(repo.join("run.sh"), b"#!/bin/sh\necho hi\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(repo.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("src/lib.rs", repo.join("lib-link")).unwrap();
+    }
+    let c2 = commit(&repo, "second");
+    git(&repo, &["tag", "-a", "v1", "-m", "release"]);
+
+    fs::remove_file(repo.join("src/lib.rs")).unwrap();
+    let c3 = commit(&repo, "third");
+
+    let archive_dir = td.path().join("archive");
+    let config = ReversibleVSAConfig::default();
+    let options = GitIngestOptions { range: Some(format!("{c1}..{c2}")), verbose: false };
+    let first = GitArchive::ingest(&repo, &archive_dir, &options, &config).unwrap();
+    assert_eq!((first.commits_added, first.commits_present), (1, 0));
+
+    // Appending picks up c1 and c3 and skips c2.
+    let summary = GitArchive::ingest(&repo, &archive_dir, &GitIngestOptions::default(), &config).unwrap();
+    assert_eq!((summary.commits_added, summary.commits_present), (2, 1));
+    // Everything in c1 except big.bin's shorter last chunk was already
+    // stored with c2.
+    assert_eq!(summary.chunks_added, 1);
+
+    let archive = GitArchive::open(&archive_dir).unwrap();
+    let ids: Vec<&str> = archive.commits().iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, [c2.as_str(), c1.as_str(), c3.as_str()]);
+    assert_eq!(archive.commits()[0].parents, std::slice::from_ref(&c1));
+    assert_eq!(archive.commits()[0].summary, "second");
+    assert_eq!(archive.refs()["refs/tags/v1"], c2);
+    assert_eq!(archive.refs()["HEAD"], c3);
+
+    // Shared history: big.bin's first four chunks are stored once.
+    let total: usize = [&c1, &c2, &c3]
+        .iter()
+        .map(|c| archive.manifest(c).unwrap().files.iter().map(|f| f.chunks.len()).sum::<usize>())
+        .sum();
+    assert!(archive.engram().codebook.len() < total);
+    let m1 = archive.manifest(&c1).unwrap();
+    let m2 = archive.manifest("v1").unwrap();
+    let chunks = |m: &embeddenator::Manifest| m.files.iter().find(|f| f.path == "big.bin").unwrap().chunks.clone();
+    assert_eq!(chunks(&m1)[..4], chunks(&m2)[..4]);
+
+    let out = td.path().join("out-v1");
+    archive.extract("v1", &out, &config).unwrap();
+    let mut expected = vec![
+        ("big.bin".to_string(), big2.clone()),
+        ("run.sh".to_string(), b"#!/bin/sh\necho hi\n".to_vec()),
+        ("src/lib.rs".to_string(), b"pub fn one() {}\n".to_vec()),
+    ];
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(archive.resolve("v1").unwrap().modes["run.sh"], MODE_EXECUTABLE);
+        let mode = fs::metadata(out.join("run.sh")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111);
+        assert_eq!(fs::read_link(out.join("lib-link")).unwrap(), Path::new("src/lib.rs"));
+        expected.insert(1, ("lib-link".to_string(), b"pub fn one() {}\n".to_vec()));
+    }
+    assert_eq!(read_tree(&out), expected);
+
+    let out = td.path().join("out-1");
+    archive.extract(&c1[..8], &out, &config).unwrap();
+    assert_eq!(
+        read_tree(&out),
+        [("big.bin".to_string(), big), ("src/lib.rs".to_string(), b"pub fn one() {}\n".to_vec())]
+    );
+    let out = td.path().join("out-main");
+    archive.extract("main", &out, &config).unwrap();
+    assert!(!out.join("src/lib.rs").exists());
+
+    assert!(archive.resolve("nope").is_err());
+    assert!(archive.resolve("abc").is_err());
+}
+
+#[test]
+fn snapshots_resolve_by_time_and_queries_stay_in_their_snapshot() {
+    let td = tempfile::tempdir().unwrap();
+    let repo = td.path().join("repo");
+    fs::create_dir(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+    let commit_at = |message: &str, date: &str| {
+        git(&repo, &["add", "-A"]);
+        git_at(&repo, &["commit", "-q", "--allow-empty", "-m", message], date);
+        git(&repo, &["rev-parse", "HEAD"])
+    };
+
+    let old = data(3, 8_000);
+    let new = data(200, 8_000);
+    let common = data(90, 4_096);
+    fs::write(repo.join("a.bin"), &old).unwrap();
+    fs::write(repo.join("common.bin"), &common).unwrap();
+    let c1 = commit_at("first", "2024-01-01T00:00:00Z");
+    fs::remove_file(repo.join("a.bin")).unwrap();
+    fs::write(repo.join("b.bin"), &new).unwrap();
+    let c2 = commit_at("second", "2024-03-01T12:00:00Z");
+
+    let archive_dir = td.path().join("archive");
+    let config = ReversibleVSAConfig::default();
+    GitArchive::ingest(&repo, &archive_dir, &GitIngestOptions::default(), &config).unwrap();
+    let archive = GitArchive::open(&archive_dir).unwrap();
+
+    assert_eq!(archive.resolve_as_of("2024-02-15").unwrap().id, c1);
+    assert_eq!(archive.resolve_as_of("2024-03-01T12:00:00Z").unwrap().id, c2);
+    assert_eq!(archive.resolve_as_of("2024-03-01T11:59").unwrap().id, c1);
+    assert_eq!(archive.resolve_as_of("@1709294400").unwrap().id, c2);
+    assert_eq!(archive.resolve_as_of("main").unwrap().id, c2);
+    assert!(archive.resolve_as_of("2023-12-31").is_err());
+    assert!(archive.resolve_as_of("2024-13-01").is_err());
+
+    // The chunks of a.bin are still in the engram, but only the first
+    // snapshot's query can find them.
+    let hits = archive.query(&c1, &old[..4096], 3, &config).unwrap();
+    assert_eq!(hits[0].paths, ["a.bin"]);
+    let later = archive.query(&c2, &old[..4096], 3, &config).unwrap();
+    assert!(later.iter().all(|h| !h.paths.contains(&"a.bin".to_string())));
+    assert!(later.iter().all(|h| h.cosine < hits[0].cosine));
+
+    let diff = archive.query_diff(&c1, "main", &old[..4096], 2, &config).unwrap();
+    assert_eq!((diff.from.as_str(), diff.to.as_str()), (c1.as_str(), c2.as_str()));
+    assert_eq!(diff.removed[0].chunk, hits[0].chunk);
+    assert!(diff.kept.is_empty());
+    assert!(diff.added.iter().all(|h| !h.paths.contains(&"a.bin".to_string())));
+
+    // An unchanged file is matched at both points.
+    let diff = archive.query_diff(&c1, &c2, &common[..4096], 1, &config).unwrap();
+    assert_eq!(diff.kept[0].paths, ["common.bin"]);
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+
+    let out = td.path().join("out");
+    let commit = archive.resolve_as_of("2024-02-01").unwrap().id.clone();
+    archive.extract(&commit, &out, &config).unwrap();
+    assert_eq!(read_tree(&out), [("a.bin".to_string(), old), ("common.bin".to_string(), common)]);
+}
@@ -0,0 +1,77 @@
+use embeddenator::vsa::graph::{EncodedGraph, GraphEncoder, LabeledGraph};
+
+fn sample_graph() -> LabeledGraph {
+    let mut graph = LabeledGraph::new();
+    graph
+        .add_node("alice", Some("person"))
+        .add_node("bob", Some("person"))
+        .add_node("acme", Some("company"))
+        .add_edge("alice", "bob", Some("knows"))
+        .add_edge("bob", "acme", Some("works_at"))
+        .add_edge("alice", "acme", Some("works_at"))
+        .add_edge("acme", "paris", None)
+        .add_path(&["alice", "bob", "acme"]);
+    graph
+}
+
+#[test]
+fn edges_are_directed_and_detected() {
+    let encoded = GraphEncoder::new(1).encode(&sample_graph());
+    assert!(encoded.has_edge("alice", "bob"));
+    assert!(encoded.has_edge("acme", "paris"));
+    assert!(!encoded.has_edge("bob", "alice"));
+    assert!(!encoded.has_edge("paris", "alice"));
+    assert!(encoded.edge_score("alice", "bob") > encoded.edge_score("bob", "alice") + 0.2);
+
+    assert!(encoded.has_node("paris"));
+    assert!(!encoded.has_node("berlin"));
+}
+
+#[test]
+fn neighbours_and_labels_decode() {
+    let encoded = GraphEncoder::new(1).encode(&sample_graph());
+    let successors: Vec<String> = encoded.successors("alice").into_iter().map(|(n, _)| n).collect();
+    assert_eq!(successors.len(), 2);
+    assert!(successors.contains(&"bob".to_string()) && successors.contains(&"acme".to_string()));
+    let predecessors: Vec<String> = encoded.predecessors("acme").into_iter().map(|(n, _)| n).collect();
+    assert_eq!(predecessors.len(), 2);
+    assert!(predecessors.contains(&"alice".to_string()) && predecessors.contains(&"bob".to_string()));
+
+    assert_eq!(encoded.node_label("acme").as_deref(), Some("company"));
+    assert_eq!(encoded.node_label("bob").as_deref(), Some("person"));
+    assert_eq!(encoded.node_label("paris"), None);
+    assert_eq!(encoded.edge_label("bob", "acme").as_deref(), Some("works_at"));
+    assert_eq!(encoded.edge_label("alice", "bob").as_deref(), Some("knows"));
+    assert_eq!(encoded.edge_label("acme", "paris"), None);
+}
+
+#[test]
+fn paths_depend_on_order() {
+    let encoded = GraphEncoder::new(1).encode(&sample_graph());
+    assert!(encoded.has_path(&["alice", "bob", "acme"]));
+    assert!(!encoded.has_path(&["acme", "bob", "alice"]));
+    assert!(!encoded.has_path(&["alice", "acme"]));
+    assert!(!encoded.has_path::<&str>(&[]));
+}
+
+#[test]
+fn larger_graphs_stay_accurate() {
+    let mut graph = LabeledGraph::new();
+    for i in 0..150 {
+        graph.add_edge(format!("n{i}"), format!("n{}", (i * 7 + 3) % 150), None);
+    }
+    let encoded = GraphEncoder::new(9).encode(&graph);
+    for i in (0..150).step_by(5) {
+        assert!(encoded.has_edge(&format!("n{i}"), &format!("n{}", (i * 7 + 3) % 150)), "n{i}");
+        assert!(!encoded.has_edge(&format!("n{i}"), &format!("n{}", (i * 7 + 4) % 150)), "n{i}");
+    }
+    assert!(EncodedGraph::threshold(150) > 0.03);
+}
+
+#[test]
+fn symbols_are_seeded() {
+    let a = GraphEncoder::new(3);
+    assert_eq!(a.symbol("x"), GraphEncoder::new(3).symbol("x"));
+    assert!(a.symbol("x").cosine(&GraphEncoder::new(4).symbol("x")).abs() < 0.1);
+    assert!(a.symbol("x").cosine(&a.symbol("y")).abs() < 0.1);
+}
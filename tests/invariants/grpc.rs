@@ -0,0 +1,190 @@
+//! End-to-end tests for the gRPC service over a loopback socket.
+
+use embeddenator::grpc::api::embeddenator_client::EmbeddenatorClient;
+use embeddenator::grpc::api::ingest_request::Msg;
+use embeddenator::grpc::api::*;
+use embeddenator::grpc::{serve_with_listener, EngramService};
+use std::collections::BTreeMap;
+use tonic::transport::Channel;
+use tonic::Code;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .expect("tokio runtime")
+}
+
+async fn start(data_dir: &std::path::Path) -> EmbeddenatorClient<Channel> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_with_listener(listener, EngramService::new(data_dir)));
+    EmbeddenatorClient::connect(format!("http://{addr}")).await.unwrap()
+}
+
+fn ingest_messages(name: &str, files: &[(&str, Vec<u8>)], piece: usize) -> Vec<IngestRequest> {
+    let mut out = vec![IngestRequest {
+        msg: Some(Msg::Target(EngramRef { name: name.into() })),
+    }];
+    for (path, data) in files {
+        let pieces: Vec<&[u8]> = if data.is_empty() { vec![&[][..]] } else { data.chunks(piece).collect() };
+        let mut offset = 0;
+        for (i, part) in pieces.iter().enumerate() {
+            out.push(IngestRequest {
+                msg: Some(Msg::Chunk(FileChunk {
+                    path: path.to_string(),
+                    offset,
+                    data: part.to_vec(),
+                    last: i + 1 == pieces.len(),
+                })),
+            });
+            offset += part.len() as u64;
+        }
+    }
+    out
+}
+
+async fn extract_all(client: &mut EmbeddenatorClient<Channel>, name: &str, chunk_size: u32) -> BTreeMap<String, Vec<u8>> {
+    let mut stream = client
+        .extract(ExtractRequest {
+            name: name.into(),
+            paths: vec![],
+            chunk_size,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    while let Some(chunk) = stream.message().await.unwrap() {
+        let buf = files.entry(chunk.path).or_default();
+        assert_eq!(chunk.offset, buf.len() as u64);
+        buf.extend_from_slice(&chunk.data);
+    }
+    files
+}
+
+#[test]
+fn ingest_extract_query_and_persist() {
+    let td = tempfile::tempdir().unwrap();
+    let text = b"remote hosts drive engrams over grpc\n".repeat(200);
+    let binary: Vec<u8> = (0..50_000u32).map(|i| (i * 7 + 3) as u8).collect();
+    let files = [("docs/readme.txt", text.clone()), ("bin/blob.dat", binary.clone()), ("empty", Vec::new())];
+
+    runtime().block_on(async {
+        let mut client = start(td.path()).await;
+        client.create_engram(EngramRef { name: "main".into() }).await.unwrap();
+        let status = client.create_engram(EngramRef { name: "main".into() }).await.unwrap_err();
+        assert_eq!(status.code(), Code::AlreadyExists);
+
+        let reply = client
+            .ingest(tokio_stream::iter(ingest_messages("main", &files, 3000)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(reply.files_ingested, 3);
+        assert_eq!(reply.bytes_ingested, (text.len() + binary.len()) as u64);
+
+        let extracted = extract_all(&mut client, "main", 4096).await;
+        assert_eq!(extracted["docs/readme.txt"], text);
+        assert_eq!(extracted["bin/blob.dat"], binary);
+        assert!(extracted["empty"].is_empty());
+
+        let hits = client
+            .query(QueryRequest {
+                name: "main".into(),
+                data: text[..4096].to_vec(),
+                k: 3,
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .hits;
+        assert!(!hits.is_empty());
+        assert!(hits.windows(2).all(|w| w[0].cosine >= w[1].cosine));
+
+        client
+            .save_engram(EngramFiles {
+                name: "main".into(),
+                engram_path: "saved/main.engram".into(),
+                manifest_path: "saved/main.json".into(),
+            })
+            .await
+            .unwrap_err();
+        std::fs::create_dir_all(td.path().join("saved")).unwrap();
+        client
+            .save_engram(EngramFiles {
+                name: "main".into(),
+                engram_path: "saved/main.engram".into(),
+                manifest_path: "saved/main.json".into(),
+            })
+            .await
+            .unwrap();
+        let loaded = client
+            .load_engram(EngramFiles {
+                name: "copy".into(),
+                engram_path: "saved/main.engram".into(),
+                manifest_path: "saved/main.json".into(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(loaded.files, 3);
+        assert_eq!(extract_all(&mut client, "copy", 0).await["bin/blob.dat"], binary);
+
+        let names: Vec<String> = client
+            .list_engrams(ListEngramsRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .engrams
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["copy", "main"]);
+        let listed = client.list_files(EngramRef { name: "copy".into() }).await.unwrap().into_inner();
+        assert_eq!(listed.files.len(), 3);
+
+        assert!(client.drop_engram(EngramRef { name: "copy".into() }).await.unwrap().into_inner().dropped);
+        let status = client.list_files(EngramRef { name: "copy".into() }).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn rejects_escaping_paths_and_malformed_streams() {
+    let td = tempfile::tempdir().unwrap();
+    runtime().block_on(async {
+        let mut client = start(td.path()).await;
+        client.create_engram(EngramRef { name: "e".into() }).await.unwrap();
+
+        let status = client
+            .save_engram(EngramFiles {
+                name: "e".into(),
+                engram_path: "../outside.engram".into(),
+                manifest_path: "m.json".into(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let status = client
+            .ingest(tokio_stream::iter(ingest_messages("e", &[("/etc/passwd", b"x".to_vec())], 10)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let mut messages = ingest_messages("e", &[("a.txt", b"hello world".to_vec())], 4);
+        messages.remove(2);
+        let status = client.ingest(tokio_stream::iter(messages)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let status = client
+            .ingest(tokio_stream::iter(vec![IngestRequest {
+                msg: Some(Msg::Chunk(FileChunk::default())),
+            }]))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    });
+}
@@ -0,0 +1,138 @@
+//! Liveness and readiness reports for the server modes.
+
+use embeddenator::health::{spawn, CheckStatus, Health};
+use embeddenator::scrub::{RepairSource, ScrubReport, ScrubStatus, ScrubbedChunk};
+use embeddenator::{ChunkCache, DirectorySubEngramStore, TieredSubEngramStore};
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+fn status_of(health: &Health, name: &str) -> CheckStatus {
+    let report = health.report();
+    report
+        .checks
+        .iter()
+        .find(|c| c.name == name)
+        .unwrap_or_else(|| panic!("no check {:?} in {:?}", name, report))
+        .status
+}
+
+fn damaged(source: Option<RepairSource>, repaired: bool) -> ScrubStatus {
+    ScrubStatus::now(ScrubReport {
+        chunks_checked: 10,
+        damaged: vec![ScrubbedChunk {
+            chunk_id: 3,
+            path: "a.txt".into(),
+            source,
+        }],
+        repaired,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn readiness_follows_engram_load_state() {
+    let health = Health::requiring_engram();
+    assert!(!health.report().ready);
+    assert_eq!(status_of(&health, "engram"), CheckStatus::Failing);
+
+    health.engram_loading("docs");
+    assert_eq!(health.respond("/readyz").unwrap().0, 503);
+    assert_eq!(health.respond("/healthz").unwrap().0, 200);
+
+    health.engram_loaded("docs", 2, 7);
+    let report = health.report();
+    assert!(report.ready);
+    assert_eq!(report.status, CheckStatus::Ok);
+    assert_eq!(report.checks[0].detail, "2 files, 7 chunks");
+
+    health.engram_failed("other", &"no such file");
+    assert_eq!(status_of(&health, "engram:other"), CheckStatus::Failing);
+    health.engram_dropped("other");
+    assert!(health.report().ready);
+    assert_eq!(health.respond("/readyz?verbose=1").unwrap().0, 200);
+    assert!(health.respond("/metrics").is_none());
+
+    // Without an engram requirement an empty server is ready.
+    assert!(Health::new().report().ready);
+}
+
+#[test]
+fn unreachable_stores_fail_and_full_caches_degrade() {
+    let dir = tempfile::tempdir().unwrap();
+    let health = Health::new();
+    health.add_store("local", Arc::new(DirectorySubEngramStore::new(dir.path())));
+    let missing = dir.path().join("gone");
+    health.add_store("remote", Arc::new(TieredSubEngramStore::new(DirectorySubEngramStore::new(&missing), 4)));
+    assert_eq!(status_of(&health, "store:local"), CheckStatus::Ok);
+    assert_eq!(status_of(&health, "store:remote"), CheckStatus::Failing);
+    std::fs::create_dir(&missing).unwrap();
+    assert_eq!(status_of(&health, "store:remote"), CheckStatus::Ok);
+
+    let cache = Arc::new(ChunkCache::new(1000));
+    health.add_chunk_cache("chunks", Arc::clone(&cache));
+    cache.insert(1, vec![0u8; 600].into());
+    assert_eq!(status_of(&health, "cache:chunks"), CheckStatus::Ok);
+    cache.insert(2, vec![0u8; 300].into());
+    cache.insert(3, vec![0u8; 500].into());
+    assert_eq!(status_of(&health, "cache:chunks"), CheckStatus::Ok, "evicting but not full");
+    cache.insert(4, vec![0u8; 200].into());
+    let report = health.report();
+    assert_eq!(status_of(&health, "cache:chunks"), CheckStatus::Degraded);
+    assert_eq!(report.status, CheckStatus::Degraded);
+    assert!(report.ready);
+    health.set_cache_pressure(1.5);
+    assert_eq!(status_of(&health, "cache:chunks"), CheckStatus::Ok);
+}
+
+#[test]
+fn scrub_results_come_from_the_newest_record_or_status_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("scrub.json");
+    let health = Health::new();
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Ok);
+
+    health.set_scrub_status_file(&file);
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Ok, "no pass has finished yet");
+
+    damaged(Some(RepairSource::Verbatim), false).save(&file).unwrap();
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Degraded);
+    damaged(Some(RepairSource::Verbatim), true).save(&file).unwrap();
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Ok);
+    damaged(None, true).save(&file).unwrap();
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Failing);
+    assert!(!health.report().ready);
+
+    let mut newer = damaged(Some(RepairSource::Verbatim), true);
+    newer.finished_unix += 60;
+    health.record_scrub(newer);
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Ok);
+
+    std::fs::write(&file, b"{ not json").unwrap();
+    assert_eq!(status_of(&health, "scrub"), CheckStatus::Degraded);
+}
+
+#[test]
+fn spawned_listener_answers_probes_over_http() {
+    let health = Health::requiring_engram();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    spawn(listener, health.clone()).unwrap();
+
+    let get = |method: &str, path: &str| {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n", method, path).unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        reply
+    };
+    let reply = get("GET", "/readyz");
+    assert!(reply.starts_with("HTTP/1.1 503 "), "{}", reply);
+    assert!(reply.contains(r#""ready":false"#));
+
+    health.engram_loaded("default", 1, 1);
+    assert!(get("GET", "/readyz").starts_with("HTTP/1.1 200 "));
+    let head = get("HEAD", "/healthz");
+    assert!(head.starts_with("HTTP/1.1 200 ") && head.ends_with("\r\n\r\n"));
+    assert!(get("GET", "/nope").starts_with("HTTP/1.1 404 "));
+    assert!(get("POST", "/readyz").starts_with("HTTP/1.1 405 "));
+}
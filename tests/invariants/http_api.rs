@@ -0,0 +1,250 @@
+//! End-to-end tests for the read-only HTTP API over a loopback socket.
+
+use embeddenator::http_api::{serve_with_listener, HttpService};
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("json body")
+    }
+}
+
+fn request(addr: SocketAddr, method: &str, path: &str, extra: &[(&str, &str)], body: &[u8]) -> Reply {
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    let mut head = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (k, v) in extra {
+        head.push_str(&format!("{k}: {v}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(body).unwrap();
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).unwrap();
+
+    let split = raw.windows(4).position(|w| w == b"\r\n\r\n").expect("end of headers");
+    let head = String::from_utf8(raw[..split].to_vec()).unwrap();
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+    let headers = lines
+        .map(|l| {
+            let (k, v) = l.split_once(':').unwrap();
+            (k.trim().to_string(), v.trim().to_string())
+        })
+        .collect();
+    Reply {
+        status,
+        headers,
+        body: raw[split + 4..].to_vec(),
+    }
+}
+
+fn get(addr: SocketAddr, path: &str) -> Reply {
+    request(addr, "GET", path, &[], b"")
+}
+
+fn start(fs: EmbrFS) -> (tokio::runtime::Runtime, SocketAddr) {
+    start_service(HttpService::new(fs.engram, fs.manifest))
+}
+
+fn start_service(service: HttpService) -> (tokio::runtime::Runtime, SocketAddr) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+    runtime.spawn(serve_with_listener(listener, service));
+    (runtime, addr)
+}
+
+#[test]
+fn serves_files_ranges_search_and_chunks() {
+    let config = ReversibleVSAConfig::default();
+    let text = b"engrams behind a reverse proxy\n".repeat(300);
+    let binary: Vec<u8> = (0..20_000u32).map(|i| (i * 31 + 7) as u8).collect();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&text, "docs/guide.txt".into(), &config).unwrap();
+    fs.ingest_bytes(&binary, "data/blob.bin".into(), &config).unwrap();
+    let (_runtime, addr) = start(fs);
+
+    let files = get(addr, "/files").json();
+    assert_eq!(files.as_array().unwrap().len(), 2);
+    let docs = get(addr, "/files?prefix=docs/").json();
+    assert_eq!(docs[0]["path"], "docs/guide.txt");
+    assert_eq!(docs[0]["size"], text.len());
+
+    let whole = get(addr, "/files/data/blob.bin");
+    assert_eq!(whole.status, 200);
+    assert_eq!(whole.header("accept-ranges"), Some("bytes"));
+    assert_eq!(whole.header("content-type"), Some("application/octet-stream"));
+    assert!(whole.header("etag").is_some());
+    assert_eq!(whole.body, binary);
+
+    // Spans a chunk boundary, so two chunks are decoded and trimmed.
+    let part = request(addr, "GET", "/files/data/blob.bin", &[("Range", "bytes=4000-9000")], b"");
+    assert_eq!(part.status, 206);
+    assert_eq!(part.header("content-range"), Some("bytes 4000-9000/20000"));
+    assert_eq!(part.body, &binary[4000..9001]);
+
+    let tail = request(addr, "GET", "/files/docs/guide.txt", &[("Range", "bytes=-100")], b"");
+    assert_eq!(tail.status, 206);
+    assert_eq!(tail.body, &text[text.len() - 100..]);
+
+    let beyond = request(addr, "GET", "/files/data/blob.bin", &[("Range", "bytes=20000-")], b"");
+    assert_eq!(beyond.status, 416);
+    assert_eq!(beyond.header("content-range"), Some("bytes */20000"));
+
+    let head = request(addr, "HEAD", "/files/docs/guide.txt", &[], b"");
+    assert_eq!(head.status, 200);
+    assert_eq!(head.header("content-length"), Some(text.len().to_string().as_str()));
+    assert!(head.body.is_empty());
+
+    // Paths resolve as they would under a mount; directories aren't files.
+    assert_eq!(get(addr, "/files/./data//blob.bin").body, binary);
+    assert_eq!(get(addr, "/files/data").status, 404);
+
+    let missing = get(addr, "/files/nope.txt");
+    assert_eq!(missing.status, 404);
+    assert!(missing.json()["error"].as_str().unwrap().contains("nope.txt"));
+
+    let hits = request(addr, "POST", "/search?k=3", &[], &text[..4096]).json()["hits"].clone();
+    let hits = hits.as_array().unwrap();
+    assert!(!hits.is_empty() && hits.len() <= 3);
+    assert!(hits.windows(2).all(|w| w[0]["cosine"].as_f64() >= w[1]["cosine"].as_f64()));
+    assert!(hits.iter().any(|h| h["paths"][0] == "docs/guide.txt"));
+    assert_eq!(get(addr, "/search?q=reverse%20proxy&k=2").status, 200);
+    assert_eq!(get(addr, "/search").status, 400);
+
+    let page = get(addr, "/chunks?limit=2").json();
+    let ids = page["ids"].as_array().unwrap();
+    assert_eq!(ids.len(), 2);
+    assert!(page["total"].as_u64().unwrap() >= 2);
+    let after = get(addr, &format!("/chunks?after={}", ids[1])).json();
+    assert!(after["ids"].as_array().unwrap().iter().all(|id| id.as_u64() > ids[1].as_u64()));
+
+    let chunk = get(addr, &format!("/chunks/{}", ids[0])).json();
+    assert_eq!(chunk["id"], ids[0]);
+    assert!(!chunk["refs"].as_array().unwrap().is_empty());
+    assert_eq!(get(addr, "/chunks/999999999").status, 404);
+}
+
+#[test]
+fn access_policy_limits_each_token_to_its_namespaces() {
+    use embeddenator::access::{token_digest, AccessPolicy};
+
+    let config = ReversibleVSAConfig::default();
+    let secret = b"team-b secret plans\n".repeat(200);
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"welcome\n", "public/readme.txt".into(), &config).unwrap();
+    fs.ingest_bytes(&secret, "team-b/plans.txt".into(), &config).unwrap();
+    let policy = AccessPolicy::from_toml(&format!(
+        "[anonymous]\nread = [\"public\"]\n\n[[principal]]\nname = \"b\"\ntokens = [\"{}\"]\nread = [\"public\"]\nwrite = [\"team-b\"]\n",
+        token_digest("b-token")
+    ))
+    .unwrap();
+    let (_runtime, addr) = start_service(HttpService::new(fs.engram, fs.manifest).with_access_policy(policy));
+    let as_b = [("Authorization", "Bearer b-token")];
+
+    let anonymous = get(addr, "/files").json();
+    assert_eq!(anonymous.as_array().unwrap().len(), 1);
+    assert_eq!(anonymous[0]["path"], "public/readme.txt");
+    assert_eq!(get(addr, "/files/public/readme.txt").body, b"welcome\n");
+    assert_eq!(get(addr, "/files/team-b/plans.txt").status, 404);
+    let hits = request(addr, "POST", "/search?k=5", &[], &secret[..1024]).json();
+    assert!(hits["hits"].as_array().unwrap().iter().all(|h| h["paths"][0] == "public/readme.txt"));
+    assert_eq!(get(addr, "/chunks").json()["total"], 1);
+
+    assert_eq!(request(addr, "GET", "/files", &as_b, b"").json().as_array().unwrap().len(), 2);
+    assert_eq!(request(addr, "GET", "/files/team-b/plans.txt", &as_b, b"").body, secret);
+    let hits = request(addr, "POST", "/search?k=1", &as_b, &secret[..1024]).json();
+    assert_eq!(hits["hits"][0]["paths"][0], "team-b/plans.txt");
+
+    let stranger = request(addr, "GET", "/files", &[("Authorization", "Bearer nope")], b"");
+    assert_eq!(stranger.status, 401);
+    assert_eq!(stranger.header("www-authenticate"), Some("Bearer"));
+}
+
+#[test]
+fn health_probes_report_scrub_status_without_a_token() {
+    use embeddenator::access::AccessPolicy;
+    use embeddenator::scrub::{ScrubReport, ScrubStatus, ScrubbedChunk};
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"probe me\n", "a.txt".into(), &config).unwrap();
+    let service = HttpService::new(fs.engram, fs.manifest).with_access_policy(AccessPolicy::from_toml("").unwrap());
+    let dir = tempfile::tempdir().unwrap();
+    let status_file = dir.path().join("scrub.json");
+    service.health().set_scrub_status_file(&status_file);
+    let (_runtime, addr) = start_service(service);
+
+    let ready = get(addr, "/readyz");
+    assert_eq!(ready.status, 200);
+    let report = ready.json();
+    assert_eq!(report["ready"], true);
+    let names: Vec<&str> = report["checks"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["engram:default", "cache:chunks", "scrub"]);
+    assert_eq!(report["checks"][0]["detail"], "1 files, 1 chunks");
+
+    ScrubStatus::now(ScrubReport {
+        chunks_checked: 1,
+        damaged: vec![ScrubbedChunk {
+            chunk_id: 0,
+            path: "a.txt".into(),
+            source: None,
+        }],
+        repaired: true,
+        ..Default::default()
+    })
+    .save(&status_file)
+    .unwrap();
+    let ready = get(addr, "/readyz");
+    assert_eq!(ready.status, 503);
+    assert_eq!(ready.json()["checks"][2]["status"], "failing");
+    let live = get(addr, "/healthz");
+    assert_eq!(live.status, 200);
+    assert_eq!(live.json()["status"], "failing");
+}
+
+#[test]
+fn request_ids_are_echoed_or_generated() {
+    use embeddenator::http_api::REQUEST_ID_HEADER;
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"trace me\n", "a.txt".into(), &config).unwrap();
+    let (_runtime, addr) = start(fs);
+
+    let given = request(addr, "GET", "/files/a.txt", &[(REQUEST_ID_HEADER, "req-7")], b"");
+    assert_eq!(given.status, 200);
+    assert_eq!(given.header(REQUEST_ID_HEADER), Some("req-7"));
+
+    let fresh = get(addr, "/files/a.txt");
+    let other = get(addr, "/nope");
+    let (fresh, other) = (fresh.header(REQUEST_ID_HEADER).unwrap(), other.header(REQUEST_ID_HEADER).unwrap());
+    assert_eq!(fresh.len(), 16);
+    assert_ne!(fresh, other);
+
+    let oversized = "x".repeat(200);
+    let replaced = request(addr, "GET", "/files/a.txt", &[(REQUEST_ID_HEADER, &oversized)], b"");
+    assert_ne!(replaced.header(REQUEST_ID_HEADER), Some(oversized.as_str()));
+}
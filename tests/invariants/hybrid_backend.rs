@@ -0,0 +1,70 @@
+//! `HybridBackend`: one representation per backend, same results as the
+//! sparse backend.
+
+use embeddenator::{
+    rerank_top_k_by_cosine, HybridBackend, HybridRepr, HybridThresholds, ReversibleVSAConfig, SparseVec,
+    SparseVecBackend, VsaBackend, DIM,
+};
+use std::collections::HashMap;
+
+const REPRS: [HybridRepr; 3] = [HybridRepr::Sparse, HybridRepr::Bitsliced, HybridRepr::BlockSparse];
+
+#[test]
+fn thresholds_select_by_dimension_and_density() {
+    let t = HybridThresholds::default();
+    assert_eq!(t.select(100, 0.5), HybridRepr::Sparse);
+    assert_eq!(t.select(DIM, 0.001), HybridRepr::Sparse);
+    assert_eq!(t.select(DIM, 0.05), HybridRepr::Bitsliced);
+    assert_eq!(t.select(1_000_000, 0.001), HybridRepr::BlockSparse);
+    assert_eq!(t.select(1_000_000, 0.05), HybridRepr::Bitsliced);
+}
+
+#[test]
+fn every_representation_matches_the_sparse_backend() {
+    let cfg = ReversibleVSAConfig::default();
+    let sparse = SparseVecBackend;
+    let a = SparseVec::random();
+    let b = SparseVec::random();
+    for repr in REPRS {
+        let hybrid = HybridBackend::with_repr(DIM, repr);
+        let (ha, hb) = (hybrid.adopt(a.clone()), hybrid.adopt(b.clone()));
+        assert_eq!(ha.repr(), repr);
+
+        let bound = hybrid.bind(&ha, &hb);
+        assert_eq!(bound.repr(), repr);
+        assert_eq!(bound.to_sparse().pos, sparse.bind(&a, &b).pos, "{repr:?}");
+        let bundled = hybrid.bundle(&ha, &hb);
+        assert_eq!(bundled.repr(), repr);
+        assert!(bundled.to_sparse().cosine(&sparse.bundle(&a, &b)) > 0.99, "{repr:?}");
+        assert!((hybrid.cosine(&ha, &hb) - sparse.cosine(&a, &b)).abs() < 1e-9, "{repr:?}");
+        assert_eq!(hybrid.zero().repr(), repr);
+
+        // Decoding is approximate (ingest stores corrections); it must just
+        // agree with the sparse backend.
+        let data = b"hybrid interop payload";
+        let encoded = hybrid.encode_data(data, &cfg, Some("a.txt"));
+        assert_eq!(encoded.to_sparse().pos, sparse.encode_data(data, &cfg, Some("a.txt")).pos);
+        assert_eq!(
+            hybrid.decode_data(&encoded, &cfg, Some("a.txt"), data.len()),
+            sparse.decode_data(&encoded.to_sparse(), &cfg, Some("a.txt"), data.len())
+        );
+    }
+}
+
+#[test]
+fn store_declared_settings_pick_the_backend_and_rerank_through_a_view() {
+    let store: HashMap<usize, SparseVec> = (0..40).map(|i| (i, SparseVec::random())).collect();
+    let backend = HybridBackend::for_store(&store).unwrap();
+    assert_eq!(backend.dim(), DIM);
+    let density = store.values().map(|v| v.pos.len() + v.neg.len()).sum::<usize>() as f64 / (40 * DIM) as f64;
+    assert_eq!(backend.repr(), HybridThresholds::BUILT_IN.select(DIM, density));
+    assert_eq!(HybridBackend::for_store(&HashMap::new()).unwrap().dim(), DIM);
+
+    let query = store[&7].clone();
+    let expected = rerank_top_k_by_cosine(&SparseVecBackend, &store, &query, 0..40, 5).unwrap();
+    let got = rerank_top_k_by_cosine(&backend, &backend.view(&store), &backend.adopt(query), 0..40, 5).unwrap();
+    assert_eq!(got[0].0, 7);
+    for (g, e) in got.iter().zip(&expected) {
+        assert!((g.1 - e.1).abs() < 1e-9);
+    }
+}
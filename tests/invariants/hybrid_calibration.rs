@@ -0,0 +1,28 @@
+//! Tests for calibrating the hybrid representation thresholds.
+
+#[test]
+fn calibration_saves_valid_thresholds() {
+    use embeddenator::bench;
+    use embeddenator::HybridThresholds;
+    use std::time::Duration;
+
+    let thresholds = bench::calibrate(Duration::from_micros(100));
+    thresholds.validate().unwrap();
+    assert!(thresholds.density >= 0.0005 && thresholds.density <= 0.2);
+    assert!(thresholds.min_bitsliced_dim <= 4096);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache/embeddenator/calibration.json");
+    thresholds.save(&path).unwrap();
+    assert_eq!(HybridThresholds::load(&path).unwrap(), thresholds);
+
+    // Missing fields take the built-in values; nonsense is rejected.
+    std::fs::write(&path, r#"{"density": 0.02}"#).unwrap();
+    let partial = HybridThresholds::load(&path).unwrap();
+    assert_eq!(partial.density, 0.02);
+    assert_eq!(partial.min_block_sparse_dim, HybridThresholds::default().min_block_sparse_dim);
+    std::fs::write(&path, r#"{"density": 0.0}"#).unwrap();
+    assert!(HybridThresholds::load(&path).is_err());
+    let inverted = HybridThresholds { min_bitsliced_dim: 1 << 20, ..HybridThresholds::default() };
+    assert!(inverted.save(&path).is_err());
+}
@@ -0,0 +1,114 @@
+//! Reading engrams and manifests from memory, as wasm32 hosts must, and
+//! engrams that only ever live there.
+
+use embeddenator::envelope::{BinaryWriteOptions, Keyring};
+use embeddenator::{EmbrError, EmbrFS, FileSignatures, IngestLimits, QuotaKind, ReversibleVSAConfig, SparseVec, DEFAULT_CHUNK_SIZE};
+
+#[test]
+fn engram_and_manifest_decode_from_bytes() {
+    let td = tempfile::tempdir().unwrap();
+    let config = ReversibleVSAConfig::default();
+    let text = b"reconstructed client-side\n".repeat(250);
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&text, "site/index.txt".into(), &config).unwrap();
+
+    let engram_path = td.path().join("root.engram");
+    let manifest_path = td.path().join("manifest.json");
+    fs.save_engram_with_options(&engram_path, BinaryWriteOptions::default()).unwrap();
+    fs.save_manifest(&manifest_path).unwrap();
+
+    let keys = Keyring::default();
+    let engram = EmbrFS::engram_from_bytes(&std::fs::read(&engram_path).unwrap(), &keys).unwrap();
+    let manifest = EmbrFS::manifest_from_bytes(&std::fs::read(&manifest_path).unwrap(), &keys).unwrap();
+    assert_eq!(engram.codebook.len(), fs.engram.codebook.len());
+    assert_eq!(manifest.files.len(), 1);
+
+    let bytes = EmbrFS::reconstruct_bytes(&engram, &manifest.files[0], &config, DEFAULT_CHUNK_SIZE).unwrap();
+    assert_eq!(bytes, text);
+}
+
+#[test]
+fn tampered_bytes_are_rejected() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"short file", "a.txt".into(), &config).unwrap();
+
+    let mut entry = fs.manifest.files[0].clone();
+    entry.blake3 = Some("00".repeat(32));
+    let err = EmbrFS::reconstruct_bytes(&fs.engram, &entry, &config, DEFAULT_CHUNK_SIZE).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let keys = Keyring::default();
+    assert!(EmbrFS::engram_from_bytes(b"not an engram", &keys).is_err());
+    assert!(EmbrFS::manifest_from_bytes(b"{", &keys).is_err());
+}
+
+fn pseudo_random(seed: u64, len: usize) -> Vec<u8> {
+    let mut x = seed;
+    (0..len)
+        .map(|_| {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn in_memory_engram_ingests_queries_and_extracts_but_never_saves() {
+    let td = tempfile::tempdir().unwrap();
+    let config = ReversibleVSAConfig::default();
+    let target = pseudo_random(3, 9000);
+    let mut fs = EmbrFS::in_memory();
+    assert!(fs.is_in_memory());
+    fs.ingest_bytes(&pseudo_random(1, 6000), "staging/one.bin".into(), &config).unwrap();
+    fs.ingest_bytes(&target, "staging/target.bin".into(), &config).unwrap();
+
+    let signatures = FileSignatures::build(&fs.engram, &fs.manifest);
+    let query = SparseVec::encode_data(&target[..4096], &config, None);
+    assert_eq!(signatures.query_any_shift(&query, &config, 1)[0].path, "staging/target.bin");
+    let entry = fs.manifest.files.iter().find(|f| f.path == "staging/target.bin").unwrap();
+    assert_eq!(EmbrFS::reconstruct_bytes(&fs.engram, entry, &config, DEFAULT_CHUNK_SIZE).unwrap(), target);
+    EmbrFS::extract(&fs.engram, &fs.manifest, td.path().join("out"), false, &config).unwrap();
+    assert_eq!(std::fs::read(td.path().join("out/staging/target.bin")).unwrap(), target);
+
+    let engram_path = td.path().join("root.engram");
+    let err = fs.save_engram(&engram_path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    assert_eq!(
+        fs.save_manifest(td.path().join("manifest.json")).unwrap_err().kind(),
+        std::io::ErrorKind::Unsupported
+    );
+    assert!(!engram_path.exists());
+}
+
+#[test]
+fn held_memory_is_bounded_and_released_on_remove() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::in_memory();
+    fs.ingest_bytes(&pseudo_random(1, 6000), "a.bin".into(), &config).unwrap();
+    let after_one = fs.held_memory_bytes();
+    assert!(after_one > 0);
+    assert!(after_one <= fs.memory_usage().total());
+
+    fs.limits = IngestLimits {
+        max_memory_bytes: Some(after_one + 1024),
+        ..IngestLimits::default()
+    };
+    let err = fs.ingest_bytes(&pseudo_random(2, 6000), "b.bin".into(), &config).unwrap_err();
+    match err {
+        EmbrError::Quota(quota) => {
+            assert_eq!(quota.kind, QuotaKind::MemoryBytes);
+            assert_eq!(quota.path, "b.bin");
+        }
+        other => panic!("expected a quota error, got {other}"),
+    }
+    assert_eq!(fs.manifest.files.len(), 1);
+    assert_eq!(fs.engram.codebook.len(), 2);
+    assert_eq!(fs.held_memory_bytes(), after_one);
+
+    // Removing a file makes room again.
+    assert!(fs.remove_file("a.bin"));
+    assert_eq!(fs.held_memory_bytes(), 0);
+    fs.ingest_bytes(&pseudo_random(2, 6000), "b.bin".into(), &config).unwrap();
+    assert!(fs.held_memory_bytes() <= after_one + 1024);
+}
@@ -0,0 +1,44 @@
+//! Tests for ingesting directories under logical root namespaces.
+
+#[test]
+fn ingest_root_namespaces() {
+    use embeddenator::embrfs::EmbrFS;
+    use embeddenator::vsa::ReversibleVSAConfig;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let tmp = tempdir().unwrap();
+    let a = tmp.path().join("a");
+    let b = tmp.path().join("b");
+    fs::create_dir_all(&a).unwrap();
+    fs::create_dir_all(&b).unwrap();
+    fs::write(a.join("one.txt"), b"alpha").unwrap();
+    fs::write(b.join("two.txt"), b"beta").unwrap();
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_root("data", &a, false, &config).unwrap();
+    embrfs.ingest_root("cfg", &b, false, &config).unwrap();
+
+    assert!(embrfs.ingest_root("data", &b, false, &config).is_err());
+    assert!(embrfs.ingest_root("bad/name", &b, false, &config).is_err());
+
+    assert_eq!(embrfs.manifest.namespaces.len(), 2);
+    let data_files: Vec<&str> = embrfs
+        .manifest
+        .files_in_namespace("data")
+        .map(|f| f.path.as_str())
+        .collect();
+    assert_eq!(data_files, vec!["data/one.txt"]);
+    assert_eq!(embrfs.manifest.chunk_ids_in_namespace("cfg").len(), 1);
+
+    let out = tmp.path().join("out");
+    EmbrFS::extract_namespace(&embrfs.engram, &embrfs.manifest, "data", &out, false, &config).unwrap();
+    assert_eq!(fs::read(out.join("one.txt")).unwrap(), b"alpha");
+    assert!(!out.join("two.txt").exists());
+
+    assert!(
+        EmbrFS::extract_namespace(&embrfs.engram, &embrfs.manifest, "missing", &out, false, &config)
+            .is_err()
+    );
+}
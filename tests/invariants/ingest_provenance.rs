@@ -0,0 +1,73 @@
+//! Tests for the provenance ingest records in the manifest.
+
+use embeddenator::{EmbrFS, EngramInfo, IngestClock, ProvenanceIssue, ReversibleVSAConfig};
+
+fn ingest(mut fsys: EmbrFS, files: &[(&str, &str)]) -> EmbrFS {
+    let config = ReversibleVSAConfig::default();
+    for (path, data) in files {
+        fsys.ingest_bytes(data.as_bytes(), path.to_string(), &config).unwrap();
+    }
+    fsys
+}
+
+const FILES: &[(&str, &str)] = &[("b.txt", "second file"), ("a.txt", "first file, a little longer")];
+
+#[test]
+fn recorded_provenance_checks_clean_and_survives_the_manifest() {
+    let mut fsys = ingest(EmbrFS::new(), FILES);
+    fsys.record_provenance(&["input".to_string()]).unwrap();
+    let p = *fsys.manifest.provenance.clone().unwrap();
+    assert_eq!(p.tool_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(p.encoder, "reversible");
+    assert_eq!((p.input_files, p.input_bytes), (2, 38));
+    assert_eq!(p.chunking.chunk_size, 4096);
+    assert!(!p.reproducible && p.recorded_at.is_some());
+    assert!(p.check(&fsys.engram, &fsys.manifest).unwrap().is_empty());
+
+    let td = tempfile::tempdir().unwrap();
+    fsys.save_manifest(td.path().join("manifest.json")).unwrap();
+    let loaded = EmbrFS::load_manifest(td.path().join("manifest.json")).unwrap();
+    assert_eq!(loaded.provenance.as_deref(), Some(&p));
+
+    let info = EngramInfo::compute(&fsys.engram, Some(&loaded));
+    assert_eq!(info.provenance, Some(p));
+    assert_eq!(info.provenance_issues, Some(Vec::new()));
+    assert!(EngramInfo::compute(&fsys.engram, None).provenance.is_none());
+}
+
+#[test]
+fn changes_after_ingest_are_reported() {
+    let mut fsys = ingest(EmbrFS::new(), FILES);
+    fsys.record_provenance(&[]).unwrap();
+    let p = *fsys.manifest.provenance.clone().unwrap();
+
+    let fsys = ingest(fsys, &[("c.txt", "added later")]);
+    let issues = p.check(&fsys.engram, &fsys.manifest).unwrap();
+    assert!(matches!(issues.as_slice(), [ProvenanceIssue::Inputs { .. }, ProvenanceIssue::Engram { .. }]), "{issues:?}");
+
+    let mut renormalized = fsys.manifest.clone();
+    renormalized.path_normalization.lowercase = true;
+    let issues = p.check(&fsys.engram, &renormalized).unwrap();
+    assert!(matches!(issues[0], ProvenanceIssue::Config { .. }), "{issues:?}");
+}
+
+#[test]
+fn reproducible_ingests_reproduce_each_other() {
+    let run = |files: &[(&str, &str)]| {
+        let mut fsys = ingest(EmbrFS::reproducible(IngestClock::Fixed(1_700_000_000)), files);
+        fsys.canonicalize();
+        fsys.record_provenance(&["tree".to_string()]).unwrap();
+        *fsys.manifest.provenance.unwrap()
+    };
+    let first = run(FILES);
+    let second = run(&[FILES[1], FILES[0]]);
+    assert!(first.reproducible);
+    assert_eq!(first.hostname, None);
+    assert_eq!(first.recorded_at, Some(1_700_000_000));
+    assert!(first.reproduces(&second));
+    assert_eq!(first, second);
+
+    let other = run(&[("a.txt", "different")]);
+    assert!(!first.same_recipe(&other));
+    assert!(!first.reproduces(&other));
+}
@@ -0,0 +1,148 @@
+//! Parallel directory ingest through the watermark-bounded encode queue.
+
+use embeddenator::{
+    EmbrFS, ExtensionFilter, FilterAction, IngestPipeline, IngestQueue, ReversibleVSAConfig, Watermarks,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+fn pseudo_random(seed: u64, len: usize) -> Vec<u8> {
+    let mut x = seed;
+    (0..len)
+        .map(|_| {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect()
+}
+
+fn sample_tree(dir: &std::path::Path) {
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), b"line of text\n".repeat(2000)).unwrap();
+    std::fs::write(dir.join("b.bin"), pseudo_random(1, 50_000)).unwrap();
+    std::fs::write(dir.join("empty"), b"").unwrap();
+    std::fs::write(dir.join("skip.log"), pseudo_random(2, 30_000)).unwrap();
+    std::fs::write(dir.join("sub/c.bin"), pseudo_random(3, 9_000)).unwrap();
+    std::fs::write(dir.join("sub/d.txt"), b"small").unwrap();
+}
+
+fn ingest(dir: &std::path::Path, pipeline: Option<IngestPipeline>) -> EmbrFS {
+    let mut fs = EmbrFS::new();
+    fs.pipeline = pipeline;
+    fs.filters.push(ExtensionFilter::deny(["log"]), FilterAction::Skip);
+    fs.ingest_directory(dir, false, &ReversibleVSAConfig::default()).unwrap();
+    fs
+}
+
+#[test]
+fn pipelined_ingest_stores_what_serial_ingest_does() {
+    let td = tempfile::tempdir().unwrap();
+    sample_tree(td.path());
+    let serial = ingest(td.path(), None);
+
+    // Any one chunk reaches the high mark.
+    let high = 4096;
+    let pipeline = IngestPipeline {
+        watermarks: Watermarks::bounded(high),
+        ..IngestPipeline::new(3)
+    };
+    let parallel = ingest(td.path(), Some(pipeline));
+
+    let files = |fs: &EmbrFS| {
+        fs.manifest
+            .files
+            .iter()
+            .map(|f| (f.path.clone(), f.size, f.chunks.clone(), f.blake3.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(files(&parallel), files(&serial));
+    assert!(!parallel.manifest.files.iter().any(|f| f.path == "skip.log"));
+    assert_eq!(parallel.manifest.total_chunks, serial.manifest.total_chunks);
+    assert_eq!(parallel.engram.codebook, serial.engram.codebook);
+    assert_eq!(parallel.engram.root, serial.engram.root);
+    assert_eq!(parallel.held_memory_bytes(), serial.held_memory_bytes());
+
+    let stats = parallel.pipeline_stats();
+    assert!(stats.pauses > 0, "{stats:?}");
+    // Past the high mark by at most the chunk that crossed it and one the
+    // bundler was waiting for.
+    assert!(stats.peak_bytes < high + 4 * 4096 * 3, "{stats:?}");
+    assert_eq!(serial.pipeline_stats(), Default::default());
+
+    let out = td.path().join("out");
+    EmbrFS::extract(&parallel.engram, &parallel.manifest, &out, false, &ReversibleVSAConfig::default()).unwrap();
+    assert_eq!(std::fs::read(out.join("b.bin")).unwrap(), pseudo_random(1, 50_000));
+}
+
+#[test]
+fn memory_budget_narrows_the_queue_as_the_engram_grows() {
+    let td = tempfile::tempdir().unwrap();
+    sample_tree(td.path());
+    let serial = ingest(td.path(), None);
+
+    // The engram alone outgrows the budget part way, leaving the workers a
+    // chunk of lookahead from then on.
+    let pipeline = IngestPipeline {
+        memory_budget: Some(serial.held_memory_bytes() / 2),
+        ..IngestPipeline::new(4)
+    };
+    let parallel = ingest(td.path(), Some(pipeline));
+    assert_eq!(parallel.engram.codebook, serial.engram.codebook);
+    assert!(parallel.pipeline_stats().peak_bytes <= serial.held_memory_bytes() / 2 + 4 * 4096 * 3);
+}
+
+#[test]
+fn queue_pauses_at_the_high_mark_until_drained_to_the_low_one() {
+    let queue: IngestQueue<u64> = IngestQueue::new(2, Watermarks::new(100, 50), None);
+    assert!(queue.push(1, 1, 60));
+    assert!(queue.push(1, 2, 60));
+    // Paused, but file 0 is the one the consumer waits for and has nothing
+    // queued, so it may still push.
+    assert!(queue.push(0, 0, 10));
+    queue.finish(0);
+
+    let pushed = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            assert!(queue.push(1, 3, 10));
+            pushed.store(true, Ordering::SeqCst);
+        });
+        assert_eq!(queue.pop(0), Some(0));
+        assert_eq!(queue.pop(0), None);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!pushed.load(Ordering::SeqCst), "pushed above the low mark");
+        assert_eq!(queue.pop(1), Some(1));
+        assert_eq!(queue.pop(1), Some(2));
+    });
+    assert!(pushed.load(Ordering::SeqCst));
+    queue.finish(1);
+    assert_eq!(queue.pop(1), Some(3));
+    assert_eq!(queue.pop(1), None);
+    assert_eq!(queue.stats().pauses, 1);
+    assert_eq!(queue.stats().peak_bytes, 130);
+}
+
+#[test]
+fn held_bytes_lower_the_marks_within_the_budget() {
+    let queue: IngestQueue<u64> = IngestQueue::new(2, Watermarks::new(1000, 500), Some(1000));
+    queue.set_held(1000);
+    assert!(queue.push(0, 0, 10));
+    assert_eq!(queue.stats().pauses, 1);
+
+    let pushed = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            assert!(queue.push(1, 1, 10));
+            pushed.store(true, Ordering::SeqCst);
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!pushed.load(Ordering::SeqCst), "pushed past the budget");
+        // The engram shrank: the queue is under the low mark again.
+        queue.set_held(0);
+    });
+    assert_eq!(queue.queued_bytes(), 20);
+
+    queue.close();
+    assert!(!queue.push(1, 2, 10));
+    assert_eq!(queue.pop(1), None);
+}
@@ -0,0 +1,53 @@
+//! Tests for ingesting from a reader of unknown length.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn ingest_stream_chunks_short_reads_like_a_file() {
+    use embeddenator::{EmbrError, EmbrFS, IngestLimits, QuotaKind, DEFAULT_CHUNK_SIZE};
+    use std::io::Read;
+
+    /// Generates `left` bytes on the fly, at most 1000 per read.
+    struct Trickle {
+        left: usize,
+        next: u8,
+    }
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(1000).min(self.left);
+            for b in &mut buf[..n] {
+                *b = self.next;
+                self.next = self.next.wrapping_mul(7).wrapping_add(13);
+            }
+            self.left -= n;
+            Ok(n)
+        }
+    }
+    let len = 10 * 4096 + 123;
+    let mut expected = Vec::new();
+    Trickle { left: len, next: 1 }.read_to_end(&mut expected).unwrap();
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_stream(Trickle { left: len, next: 1 }, "stream.bin".into(), &config)
+        .unwrap();
+    fs.ingest_bytes(&expected, "bytes.bin".into(), &config).unwrap();
+    let (streamed, whole) = (&fs.manifest.files[0], &fs.manifest.files[1]);
+    assert_eq!(streamed.size, len);
+    assert_eq!(streamed.chunks.len(), 11);
+    assert_eq!(streamed.blake3, whole.blake3);
+    assert_eq!(EmbrFS::reconstruct_bytes(&fs.engram, streamed, &config, DEFAULT_CHUNK_SIZE).unwrap(), expected);
+
+    // Limits apply as the stream grows.
+    let mut limited = EmbrFS::with_limits(IngestLimits {
+        max_file_size: Some(8192),
+        ..Default::default()
+    });
+    let err = limited
+        .ingest_stream(Trickle { left: len, next: 1 }, "big.bin".into(), &config)
+        .unwrap_err();
+    match err {
+        EmbrError::Quota(q) => assert_eq!((q.kind, q.attempted), (QuotaKind::FileSize, 12288)),
+        other => panic!("expected a quota error, got {}", other),
+    }
+}
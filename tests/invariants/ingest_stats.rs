@@ -0,0 +1,40 @@
+//! Tests for ingest statistics: deduplication and grouping.
+
+#[test]
+fn ingest_stats_dedup_and_grouping() {
+    use embeddenator::embrfs::EmbrFS;
+    use embeddenator::vsa::ReversibleVSAConfig;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(src.join("docs")).unwrap();
+    let payload: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(src.join("a.bin"), &payload).unwrap();
+    fs::write(src.join("docs").join("b.bin"), &payload).unwrap();
+    fs::write(src.join("docs").join("note.TXT"), b"hello").unwrap();
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &config).unwrap();
+
+    let stats = embrfs.ingest_stats();
+    assert_eq!(stats.total.files, 3);
+    assert_eq!(stats.total.raw_bytes, 2 * payload.len() as u64 + 5);
+    assert_eq!(stats.total.unique_chunk_bytes, payload.len() as u64 + 5);
+    assert!(stats.total.dedup_ratio > 1.9 && stats.total.dedup_ratio < 2.0);
+    assert!(stats.total.encoded_bytes > 0);
+
+    let bin = &stats.by_extension["bin"];
+    assert_eq!(bin.files, 2);
+    assert!((bin.dedup_ratio - 2.0).abs() < 1e-9);
+    assert_eq!(stats.by_extension["txt"].files, 1);
+
+    assert_eq!(stats.by_directory["."].files, 1);
+    assert_eq!(stats.by_directory["docs"].files, 2);
+    assert!((stats.by_directory["docs"].dedup_ratio - 1.0).abs() < 1e-9);
+
+    let histogram_files: usize = stats.chunk_histogram.iter().map(|b| b.files).sum();
+    assert_eq!(histogram_files, 3);
+}
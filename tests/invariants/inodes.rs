@@ -0,0 +1,54 @@
+//! Tests for stable inode numbers.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn inode_numbers_survive_remounts() {
+    use embeddenator::{EmbrFS, EngramFS};
+
+    let config = ReversibleVSAConfig::default();
+    let dir = tempfile::tempdir().unwrap();
+    let mount = |fs: &EmbrFS| {
+        fs.save_engram(dir.path().join("e.engram")).unwrap();
+        let engram = EmbrFS::load_engram(dir.path().join("e.engram")).unwrap();
+        EngramFS::from_engram(engram, fs.manifest.clone(), config.clone(), 4096, true)
+    };
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"one", "docs/one.txt".into(), &config).unwrap();
+    fs.ingest_bytes(b"two", "src/two.rs".into(), &config).unwrap();
+    // Numbered in the order a mount of a legacy manifest used to use.
+    assert_eq!(fs.manifest.inodes.get("docs"), Some(2));
+    assert_eq!(fs.manifest.inodes.get("/docs/one.txt"), Some(3));
+    let before = mount(&fs);
+    let one = before.lookup_path("/docs/one.txt").unwrap();
+    let two = before.lookup_path("/src/two.rs").unwrap();
+    assert_eq!(one, 3);
+
+    // A file sorting first, and a removal, leave existing numbers alone.
+    fs.ingest_bytes(b"zero", "a/zero.txt".into(), &config).unwrap();
+    assert!(fs.remove_file("docs/one.txt"));
+    let after = mount(&fs);
+    assert_eq!(after.lookup_path("/src/two.rs"), Some(two));
+    assert!(after.lookup_path("/a/zero.txt").unwrap() > two);
+    // The old number is not handed to another path.
+    fs.ingest_bytes(b"again", "docs/one.txt".into(), &config).unwrap();
+    assert_eq!(mount(&fs).lookup_path("/docs/one.txt"), Some(one));
+
+    // The table survives saving and loading.
+    fs.save_manifest(dir.path().join("m.json")).unwrap();
+    let loaded = EmbrFS::load_manifest(dir.path().join("m.json")).unwrap();
+    assert_eq!(loaded.inodes, fs.manifest.inodes);
+    #[cfg(feature = "proto")]
+    {
+        let bytes = embeddenator::wire::encode_manifest(&fs.manifest).unwrap();
+        assert_eq!(embeddenator::wire::decode_manifest(&bytes).unwrap().inodes, fs.manifest.inodes);
+    }
+
+    // A legacy manifest gets its files numbered on the next ingest.
+    let mut legacy = EmbrFS::new();
+    legacy.ingest_bytes(b"x", "x/y.txt".into(), &config).unwrap();
+    legacy.manifest.inodes = Default::default();
+    legacy.ingest_bytes(b"z", "z.txt".into(), &config).unwrap();
+    assert_eq!(legacy.manifest.inodes.get("x/y.txt"), Some(3));
+    assert_eq!(legacy.manifest.inodes.get("z.txt"), Some(4));
+}
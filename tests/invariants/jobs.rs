@@ -0,0 +1,53 @@
+//! Tests for job cancellation, progress and rate limits.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn cancelled_and_rate_limited_jobs() {
+    use embeddenator::{CancellationToken, EmbrError, EmbrFS, JobControl, JobProgress};
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let src = tempdir().unwrap();
+    for i in 0..3 {
+        std::fs::write(src.path().join(format!("f{i}.txt")), vec![b'a' + i as u8; 2000]).unwrap();
+    }
+
+    // A token cancelled up front stops ingest before the first file.
+    let token = CancellationToken::new();
+    let mut fs = EmbrFS::new();
+    fs.control = JobControl::new().with_cancellation(token.clone());
+    token.cancel();
+    match fs.ingest_directory(src.path(), false, &config) {
+        Err(EmbrError::Cancelled(progress)) => assert_eq!(progress, JobProgress::default()),
+        other => panic!("expected cancellation, got {:?}", other.map(|_| ())),
+    }
+    assert!(fs.manifest.files.is_empty());
+    assert!(fs.engram.codebook.is_empty());
+
+    let mut fs = EmbrFS::new();
+    fs.ingest_directory(src.path(), false, &config).unwrap();
+
+    let out = tempdir().unwrap();
+    let cancelled = JobControl::new().with_cancellation(token.clone());
+    assert!(matches!(
+        EmbrFS::extract_with_control(&fs.engram, &fs.manifest, out.path(), false, &config, &cancelled),
+        Err(EmbrError::Cancelled(JobProgress { files: 0, bytes: 0 }))
+    ));
+    assert!(matches!(
+        EmbrFS::verify_with_control(&fs.engram, &fs.manifest, &config, &cancelled),
+        Err(EmbrError::Cancelled(_))
+    ));
+
+    // 6000 bytes at 20000 bytes/s: the last file waits for the first two.
+    let throttled = JobControl::new().with_rate_limit(20_000);
+    let start = Instant::now();
+    EmbrFS::extract_with_control(&fs.engram, &fs.manifest, out.path(), false, &config, &throttled).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(180));
+    for i in 0..3 {
+        assert_eq!(std::fs::read(out.path().join(format!("f{i}.txt"))).unwrap(), vec![b'a' + i as u8; 2000]);
+    }
+    let report = EmbrFS::verify_with_control(&fs.engram, &fs.manifest, &config, &JobControl::new()).unwrap();
+    assert!(report.mismatches.is_empty());
+}
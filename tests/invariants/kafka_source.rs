@@ -0,0 +1,45 @@
+//! Tests for decoding Kafka messages into file events (no broker needed).
+
+use embeddenator::kafka_source::KafkaFormat;
+use embeddenator::stream_ingest::{FileEvent, ObjectFetcher};
+use std::io;
+
+struct Echo;
+
+impl ObjectFetcher for Echo {
+    fn fetch(&self, bucket: &str, key: &str) -> io::Result<Vec<u8>> {
+        Ok(format!("{bucket}:{key}").into_bytes())
+    }
+}
+
+#[test]
+fn file_messages_put_and_tombstones_delete() {
+    let format = KafkaFormat::Files;
+    assert_eq!(
+        format.decode(Some(b"logs/a.txt"), Some(b"hello")).unwrap(),
+        [FileEvent::Put { path: "logs/a.txt".into(), data: b"hello".to_vec() }]
+    );
+    assert_eq!(
+        format.decode(Some(b"logs/a.txt"), None).unwrap(),
+        [FileEvent::Delete { path: "logs/a.txt".into() }]
+    );
+    assert_eq!(format.decode(None, Some(b"x")).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert!(format.decode(Some(&[0xff, 0xfe]), Some(b"x")).is_err());
+}
+
+#[test]
+fn s3_event_messages_fetch_created_objects() {
+    let format = KafkaFormat::S3Events(Box::new(Echo));
+    let message = br#"{"Records":[
+        {"eventName":"s3:ObjectCreated:Put","s3":{"bucket":{"name":"b"},"object":{"key":"k%201"}}},
+        {"eventName":"s3:ObjectRemoved:Delete","s3":{"bucket":{"name":"b"},"object":{"key":"k2"}}}
+    ]}"#;
+    assert_eq!(
+        format.decode(Some(b"ignored"), Some(message)).unwrap(),
+        [
+            FileEvent::Put { path: "k 1".into(), data: b"b:k 1".to_vec() },
+            FileEvent::Delete { path: "k2".into() },
+        ]
+    );
+    assert!(format.decode(None, None).unwrap().is_empty());
+}
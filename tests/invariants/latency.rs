@@ -0,0 +1,25 @@
+//! Tests for latency histograms.
+
+#[test]
+fn latency_histogram_quantiles() {
+    use embeddenator::LatencyHistogram;
+    use std::time::Duration;
+
+    let histogram = LatencyHistogram::new();
+    assert_eq!(histogram.snapshot().quantile(0.5), Duration::ZERO);
+
+    for _ in 0..90 {
+        histogram.record(Duration::from_micros(3));
+    }
+    for _ in 0..10 {
+        histogram.record(Duration::from_millis(5));
+    }
+    let stats = histogram.snapshot();
+    assert_eq!(stats.count, 100);
+    assert_eq!(stats.max(), Duration::from_millis(5));
+    // 3µs lands in [2, 4)µs; 5ms in [4096, 8192)µs, capped at the max seen.
+    assert_eq!(stats.quantile(0.5), Duration::from_micros(4));
+    assert_eq!(stats.quantile(0.9), Duration::from_micros(4));
+    assert_eq!(stats.quantile(0.99), Duration::from_millis(5));
+    assert_eq!(stats.mean(), Duration::from_nanos((90 * 3_000 + 10 * 5_000_000) / 100));
+}
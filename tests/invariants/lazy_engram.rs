@@ -0,0 +1,74 @@
+//! Tests for lazily read engrams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn lazy_engram_reads_chunks_from_disk() {
+    use embeddenator::{
+        BinaryWriteOptions, ChecksumCodec, EmbrFS, EnvelopeWriter, Keyring, LazyEngram, PayloadKind,
+        SeekableEnvelope, DEFAULT_CHUNK_SIZE,
+    };
+    use std::fs::{self, File};
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    for i in 0..3u8 {
+        let data: Vec<u8> = (0..9000u32).map(|n| (n as u8).wrapping_mul(i + 1)).collect();
+        fs::write(src.join(format!("f{i}.bin")), data).unwrap();
+    }
+    fs::write(src.join("a.txt"), "lazy hello\n").unwrap();
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &config).unwrap();
+
+    // Bare bincode, and a checksummed envelope cut into many small frames.
+    let bare = tmp.path().join("bare.engram");
+    embrfs.save_engram(&bare).unwrap();
+    let framed = tmp.path().join("framed.engram");
+    let opts = BinaryWriteOptions { checksum: ChecksumCodec::Blake3, ..Default::default() };
+    let mut writer = EnvelopeWriter::new(File::create(&framed).unwrap(), PayloadKind::EngramBincode, opts)
+        .unwrap()
+        .with_frame_size(512);
+    writer.write_all(&bincode::serialize(&embrfs.engram).unwrap()).unwrap();
+    writer.finish().unwrap();
+
+    let mut seekable =
+        SeekableEnvelope::open(File::open(&framed).unwrap(), PayloadKind::EngramBincode, &Keyring::default())
+            .unwrap();
+    assert!(seekable.frame_count() > 10);
+    let payload = bincode::serialize(&embrfs.engram).unwrap();
+    assert_eq!(seekable.len(), payload.len() as u64);
+    let mut window = vec![0u8; 1500];
+    seekable.seek(SeekFrom::Start(700)).unwrap();
+    seekable.read_exact(&mut window).unwrap();
+    assert_eq!(window, payload[700..2200]);
+
+    for path in [&bare, &framed] {
+        let lazy = LazyEngram::open(path, &Keyring::default()).unwrap();
+        assert_eq!(lazy.chunk_count(), embrfs.engram.codebook.len());
+        for id in lazy.chunk_ids() {
+            assert_eq!(lazy.chunk(id).unwrap().unwrap().pos, embrfs.engram.codebook[&id].pos);
+        }
+        assert!(lazy.chunk(1_000_000).unwrap().is_none());
+        for entry in &embrfs.manifest.files {
+            assert_eq!(lazy.read_file(entry, &config, DEFAULT_CHUNK_SIZE).unwrap(), fs::read(src.join(&entry.path)).unwrap());
+        }
+        let full = lazy.to_engram().unwrap();
+        assert_eq!(full.codebook.len(), embrfs.engram.codebook.len());
+        assert!(EmbrFS::verify(&full, &embrfs.manifest, &config).unwrap().is_ok());
+    }
+
+    // Flipping a byte inside a frame body fails that frame's read, not open.
+    let mut bytes = fs::read(&framed).unwrap();
+    let at = bytes.len() / 2;
+    bytes[at] ^= 0xff;
+    fs::write(&framed, bytes).unwrap();
+    let mut seekable =
+        SeekableEnvelope::open(File::open(&framed).unwrap(), PayloadKind::EngramBincode, &Keyring::default())
+            .unwrap();
+    let mut all = Vec::new();
+    assert!(seekable.read_to_end(&mut all).is_err());
+}
@@ -0,0 +1,115 @@
+//! Tests for the memory-mapped, lazily decoded append-log reader.
+
+use embeddenator::{append_log, EmbrFS, Envelope, ReversibleVSAConfig};
+use std::fs;
+
+fn saved_log(td: &tempfile::TempDir) -> (EmbrFS, std::path::PathBuf) {
+    let input = td.path().join("in");
+    fs::create_dir_all(input.join("sub")).unwrap();
+    fs::write(input.join("a.txt"), "lazy ".repeat(3000)).unwrap();
+    fs::write(input.join("sub/b.bin"), (0..9000u32).map(|i| (i * 7) as u8).collect::<Vec<_>>()).unwrap();
+
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_directory(&input, false, &ReversibleVSAConfig::default())
+        .unwrap();
+    let path = td.path().join("root.edna");
+    fsys.save_append_log(&path).unwrap();
+    (fsys, path)
+}
+
+#[test]
+fn append_logs_are_recognized() {
+    let td = tempfile::tempdir().unwrap();
+    let (fsys, path) = saved_log(&td);
+    assert!(append_log::is_append_log(&path).unwrap());
+
+    let engram = td.path().join("root.engram");
+    fsys.save_engram(&engram).unwrap();
+    assert!(!append_log::is_append_log(&engram).unwrap());
+    let empty = td.path().join("empty");
+    fs::write(&empty, b"").unwrap();
+    assert!(!append_log::is_append_log(&empty).unwrap());
+}
+
+#[cfg(not(feature = "mmap"))]
+#[test]
+fn open_mmap_reports_missing_feature() {
+    let td = tempfile::tempdir().unwrap();
+    let (_, path) = saved_log(&td);
+    let err = Envelope::open_mmap(&path).err().expect("mmap feature is disabled");
+    assert!(err.to_string().contains("not enabled"), "unexpected error: {err}");
+}
+
+#[cfg(feature = "mmap")]
+mod enabled {
+    use super::*;
+    use embeddenator::DEFAULT_CHUNK_SIZE;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    #[test]
+    fn lazy_reads_match_eager_load() {
+        let td = tempfile::tempdir().unwrap();
+        let (fsys, path) = saved_log(&td);
+        let env = Envelope::open_mmap(&path).unwrap();
+
+        assert_eq!(env.chunk_count(), fsys.engram.codebook.len());
+        assert_eq!(env.root().unwrap().pos, fsys.engram.root.pos);
+        let manifest = env.manifest().unwrap();
+        assert_eq!(manifest.files.len(), fsys.manifest.files.len());
+
+        let id = *fsys.engram.codebook.keys().next().unwrap();
+        let vec = env.chunk(id).unwrap().unwrap();
+        assert_eq!(vec.pos, fsys.engram.codebook[&id].pos);
+        assert_eq!(vec.neg, fsys.engram.codebook[&id].neg);
+        assert!(env.chunk(usize::MAX).unwrap().is_none());
+
+        let config = ReversibleVSAConfig::default();
+        for entry in &manifest.files {
+            let expected = fs::read(td.path().join("in").join(&entry.path)).unwrap();
+            assert_eq!(env.read_file(entry, &config, DEFAULT_CHUNK_SIZE).unwrap(), expected, "{}", entry.path);
+        }
+    }
+
+    #[test]
+    fn write_file_streams_and_checks_the_digest() {
+        let td = tempfile::tempdir().unwrap();
+        let (_, path) = saved_log(&td);
+        let env = Envelope::open_mmap(&path).unwrap();
+        let config = ReversibleVSAConfig::default();
+        let mut entry = env.manifest().unwrap().files.into_iter().find(|f| f.path == "sub/b.bin").unwrap();
+
+        let mut out = Vec::new();
+        env.write_file(&entry, &config, DEFAULT_CHUNK_SIZE, &mut out).unwrap();
+        assert_eq!(out, fs::read(td.path().join("in/sub/b.bin")).unwrap());
+
+        entry.blake3 = Some("00".repeat(32));
+        let err = env.write_file(&entry, &config, DEFAULT_CHUNK_SIZE, &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("sub/b.bin"), "{err}");
+    }
+
+    #[test]
+    fn torn_tail_is_ignored_without_modifying_the_file() {
+        let td = tempfile::tempdir().unwrap();
+        let (fsys, path) = saved_log(&td);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0x01, 0, 0, 0, 0xff, 0xff]).unwrap();
+        drop(file);
+        let len = fs::metadata(&path).unwrap().len();
+
+        let env = Envelope::open_mmap(&path).unwrap();
+        assert_eq!(env.ignored_bytes(), 6);
+        assert_eq!(env.chunk_count(), fsys.engram.codebook.len());
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+    }
+
+    #[test]
+    fn non_log_files_are_rejected() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("not-a-log");
+        fs::write(&path, b"EDN1 but not an append log").unwrap();
+        let err = Envelope::open_mmap(&path).err().expect("bad magic");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,61 @@
+//! Tests for manifest listings: filters and trees.
+
+#[test]
+fn listing_filters_and_tree() {
+    use embeddenator::embrfs::{FileEntry, Manifest};
+    use embeddenator::listing::{self, PathFilter};
+
+    let entry = |path: &str, size: usize, chunks: usize, is_text: bool| FileEntry {
+        path: path.to_string(),
+        is_text,
+        size,
+        chunks: (0..chunks).collect(),
+        blake3: None,
+        ingested_at: None,
+        signature: None,
+        unix: None,
+    };
+    let manifest = Manifest {
+        files: vec![
+            entry("src/main.rs", 120, 1, true),
+            entry("README.md", 40, 1, true),
+            entry("src/util/mod.rs", 300, 2, true),
+            entry("assets/logo.png", 5000, 2, false),
+        ],
+        total_chunks: 6,
+        ..Default::default()
+    };
+
+    let all = listing::list(&manifest, &PathFilter::default());
+    let paths: Vec<&str> = all.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, ["README.md", "assets/logo.png", "src/main.rs", "src/util/mod.rs"]);
+    assert_eq!((all[1].size, all[1].chunks, all[1].is_text), (5000, 2, false));
+
+    // Name patterns match at any depth; path patterns anchor at the root.
+    let by_name = listing::list(&manifest, &PathFilter::new(&["*.rs"]).unwrap());
+    assert_eq!(by_name.len(), 2);
+    let shallow = listing::list(&manifest, &PathFilter::new(&["src/*.rs"]).unwrap());
+    assert_eq!(shallow.len(), 1);
+    let deep = listing::list(&manifest, &PathFilter::new(&["src/**/*.rs", "*.md"]).unwrap());
+    assert_eq!(deep.len(), 3);
+    assert!(PathFilter::new(&["[oops"]).is_err());
+
+    let tree = listing::tree(&manifest, &PathFilter::default());
+    assert_eq!((tree.files, tree.size, tree.chunks), (4, 5460, 6));
+    let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["assets", "src", "README.md"]);
+    let src = &tree.children[1];
+    assert_eq!((src.files, src.size), (2, 420));
+    assert!(!src.is_file() && src.children[1].is_file());
+
+    let rendered = tree.render(None);
+    assert!(rendered.starts_with(". (4 files, 5460 bytes)\n"), "{rendered}");
+    assert!(rendered.contains("│   ├── util/ (1 files, 300 bytes)\n"), "{rendered}");
+    assert!(rendered.contains("│   │   └── mod.rs (300 bytes)\n"), "{rendered}");
+    assert!(rendered.ends_with("└── README.md (40 bytes)\n"), "{rendered}");
+    assert!(!tree.render(Some(1)).contains("mod.rs"));
+
+    let filtered = listing::tree(&manifest, &PathFilter::new(&["*.md"]).unwrap());
+    assert_eq!(filtered.files, 1);
+    assert_eq!(filtered.children.len(), 1, "empty directories are dropped");
+}
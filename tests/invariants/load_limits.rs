@@ -0,0 +1,82 @@
+//! Tests for loading corrupt or oversized engrams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn engram_load_rejects_corrupt_and_oversized_payloads() {
+    use embeddenator::{
+        BlockError, BlockSparseTritVec, EmbrError, EmbrFS, EngramCorruption, Keyring, LoadLimits,
+        SparseVecError, DIM,
+    };
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&[7u8; 9000], "a.bin".into(), &config).unwrap();
+    let keys = Keyring::default();
+    let limits = LoadLimits::default();
+    let load = |engram: &embeddenator::Engram, limits: &LoadLimits| {
+        EmbrFS::engram_from_bytes_with_limits(&bincode::serialize(engram).unwrap(), &keys, limits)
+    };
+    let corruption = |result: Result<_, EmbrError>| match result {
+        Err(EmbrError::CorruptEngram(c)) => c,
+        other => panic!("expected CorruptEngram, got {:?}", other.map(|_: embeddenator::Engram| ())),
+    };
+    assert!(load(&fs.engram, &limits).is_ok());
+    let copy = |engram: &embeddenator::Engram| -> embeddenator::Engram {
+        bincode::deserialize(&bincode::serialize(engram).unwrap()).unwrap()
+    };
+
+    // Record counts and decoded size are capped.
+    let few = LoadLimits { max_chunks: 1, ..limits };
+    assert_eq!(
+        corruption(load(&fs.engram, &few)),
+        EngramCorruption::TooManyChunks { count: 3, limit: 1 }
+    );
+    let small = LoadLimits { max_bytes: 64, ..limits };
+    assert_eq!(corruption(load(&fs.engram, &small)), EngramCorruption::TooLarge { limit: 64 });
+
+    // Vector indices must be in range and strictly ascending.
+    let mut bad = copy(&fs.engram);
+    bad.codebook.get_mut(&1).unwrap().pos.push(DIM + 5);
+    assert_eq!(
+        corruption(load(&bad, &limits)),
+        EngramCorruption::InvalidVector {
+            chunk: Some(1),
+            error: SparseVecError::OutOfRange { index: DIM + 5, dim: DIM },
+        }
+    );
+    let mut bad = copy(&fs.engram);
+    bad.root.neg = vec![5, 3];
+    match corruption(load(&bad, &limits)) {
+        EngramCorruption::Malformed(msg) => {
+            assert!(msg.contains("negative indices not strictly ascending at position 1"), "{}", msg)
+        }
+        other => panic!("expected a decode error, got {}", other),
+    }
+
+    // An empty root and codebook, then one correction whose type tag is 99.
+    let mut raw = Vec::new();
+    for word in [0u64, 0, 0, 1, 0, 0] {
+        raw.extend_from_slice(&word.to_le_bytes());
+    }
+    raw.extend_from_slice(&99u32.to_le_bytes());
+    assert!(matches!(
+        corruption(EmbrFS::engram_from_bytes(&raw, &keys)),
+        EngramCorruption::Malformed(_)
+    ));
+
+    // Truncated payloads and garbage fail without panicking, as corrupt.
+    let full = bincode::serialize(&fs.engram).unwrap();
+    let err = EmbrFS::engram_from_bytes(&full[..full.len() / 2], &keys).err().unwrap();
+    let err = std::io::Error::from(err);
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+    // Block-sparse vectors check their invariants when deserialized.
+    let overlap = r#"{"dim":128,"blocks":[[0,{"pos":3,"neg":1}]]}"#;
+    let err = serde_json::from_str::<BlockSparseTritVec>(overlap).unwrap_err();
+    assert!(err.to_string().contains(&BlockError::Overlap { block_id: 0, overlap: 1 }.to_string()));
+    let beyond = r#"{"dim":128,"blocks":[[2,{"pos":1,"neg":0}]]}"#;
+    assert!(serde_json::from_str::<BlockSparseTritVec>(beyond).is_err());
+    let fine = r#"{"dim":128,"blocks":[[0,{"pos":1,"neg":2}],[1,{"pos":4,"neg":0}]]}"#;
+    assert_eq!(serde_json::from_str::<BlockSparseTritVec>(fine).unwrap().nnz(), 3);
+}
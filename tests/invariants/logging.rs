@@ -0,0 +1,53 @@
+//! Tests for structured logging of operations.
+
+#[test]
+fn logging_operations_nest_and_follow_work_to_other_threads() {
+    use embeddenator::cli::config::Profile;
+    use embeddenator::logging::{self, LogFormat, Operation};
+    use std::path::Path;
+
+    assert!(logging::current().is_none());
+    let outer = Operation::new("qa-outer");
+    assert_eq!(outer.id().len(), 16);
+    assert_ne!(outer.id(), Operation::new("qa-outer").id());
+    {
+        let _outer = outer.enter();
+        assert_eq!(logging::current().unwrap().id(), outer.id());
+        {
+            let inner = Operation::with_id("qa-inner", "req-42");
+            let _inner = inner.enter();
+            assert_eq!(logging::current().unwrap().name(), "qa-inner");
+            assert_eq!(logging::current().unwrap().id(), "req-42");
+        }
+        assert_eq!(logging::current().unwrap().id(), outer.id());
+
+        // Another thread has no operation until it enters the one handed to it.
+        let handed = logging::current();
+        let seen = std::thread::spawn(move || {
+            assert!(logging::current().is_none());
+            let _op = handed.as_ref().map(Operation::enter);
+            logging::current().map(|op| op.id().to_string())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(seen.as_deref(), Some(outer.id()));
+    }
+    assert!(logging::current().is_none());
+
+    let config = Profile::from_config(
+        Some("[profiles.ops]\nlog_level = \"embeddenator=debug\"\nlog_format = \"JSON\"\n"),
+        Path::new("config.toml"),
+        Some("ops"),
+        |_| None,
+    )
+    .unwrap()
+    .log_config()
+    .unwrap();
+    assert_eq!(config.level.as_deref(), Some("embeddenator=debug"));
+    assert_eq!(config.format, LogFormat::Json);
+    let bad = Profile::from_config(None, Path::new("config.toml"), None, |name| {
+        (name == "EMBEDDENATOR_LOG_FORMAT").then(|| "xml".to_string())
+    })
+    .unwrap();
+    assert!(bad.log_config().is_err());
+}
@@ -0,0 +1,98 @@
+//! Manifest schema versions: old manifests keep loading, new ones say what
+//! they are, and downgrades drop exactly the newer fields.
+
+use embeddenator::manifest_schema::{self, Versioned, MANIFEST_SCHEMA_VERSION};
+use embeddenator::{EmbrFS, EncodingConfig, Manifest, ReversibleVSAConfig};
+use serde_json::{json, Value};
+
+/// A manifest as the first release wrote it.
+fn v1() -> Value {
+    json!({
+        "files": [{"path": "a.txt", "is_text": true, "size": 5, "chunks": [0]}],
+        "total_chunks": 1
+    })
+}
+
+#[test]
+fn unversioned_manifests_are_dated_by_their_fields() {
+    assert_eq!(manifest_schema::version_of(&v1()).unwrap(), 1);
+
+    let mut v4 = v1();
+    v4["files"][0]["blake3"] = json!("00");
+    v4["files"][0]["ingested_at"] = Value::Null;
+    assert_eq!(manifest_schema::version_of(&v4).unwrap(), 4);
+
+    let mut v8 = v4.clone();
+    v8["inodes"] = json!({"next": 3, "paths": {"a.txt": 2}});
+    assert_eq!(manifest_schema::version_of(&v8).unwrap(), 8);
+
+    let mut tagged = v1();
+    tagged["schema_version"] = json!(6);
+    assert_eq!(manifest_schema::version_of(&tagged).unwrap(), 6);
+    tagged["schema_version"] = json!("six");
+    assert!(manifest_schema::version_of(&tagged).is_err());
+    assert!(manifest_schema::version_of(&json!([])).is_err());
+}
+
+#[test]
+fn every_prior_version_loads_and_newer_ones_are_refused() {
+    let mut json = v1();
+    let applied = manifest_schema::upgrade(&mut json).unwrap();
+    assert_eq!(applied.len(), MANIFEST_SCHEMA_VERSION as usize - 1);
+    assert_eq!(json["schema_version"], json!(MANIFEST_SCHEMA_VERSION));
+    assert!(manifest_schema::upgrade(&mut json).unwrap().is_empty());
+
+    let manifest = Manifest::from_json(v1()).unwrap();
+    assert_eq!(manifest.files[0].path, "a.txt");
+    assert_eq!(manifest.files[0].blake3, None);
+    assert!(manifest.inodes.is_empty());
+
+    let mut future = v1();
+    future["schema_version"] = json!(MANIFEST_SCHEMA_VERSION + 1);
+    assert!(Manifest::from_json(future).is_err());
+}
+
+#[test]
+fn saved_manifests_record_their_version_and_downgrade_cleanly() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"hello", "docs/a.txt".into(), &config).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("manifest.json");
+    fs.save_manifest(&path).unwrap();
+
+    let written: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(written["schema_version"], json!(MANIFEST_SCHEMA_VERSION));
+    let loaded = EmbrFS::load_manifest(&path).unwrap();
+    assert_eq!(loaded.inodes, fs.manifest.inodes);
+
+    let mut old = serde_json::to_value(Versioned::new(&loaded)).unwrap();
+    manifest_schema::downgrade(&mut old, 3).unwrap();
+    let keys: Vec<&str> = old.as_object().unwrap().keys().map(String::as_str).collect();
+    assert_eq!(keys, ["files", "total_chunks"]);
+    let file: Vec<&str> = old["files"][0].as_object().unwrap().keys().map(String::as_str).collect();
+    assert_eq!(file, ["blake3", "chunks", "is_text", "path", "size"]);
+    assert_eq!(manifest_schema::version_of(&old).unwrap(), 2);
+    assert_eq!(Manifest::from_json(old).unwrap().files, {
+        let mut files = loaded.files.clone();
+        files[0].ingested_at = None;
+        files[0].signature = None;
+        files[0].unix = None;
+        files
+    });
+
+    let mut encoded = loaded.clone();
+    encoded.encoding = Some(EncodingConfig {
+        vsa: ReversibleVSAConfig { block_size: 128, ..Default::default() },
+        ..Default::default()
+    });
+    let mut json = serde_json::to_value(Versioned::new(&encoded)).unwrap();
+    assert!(manifest_schema::downgrade(&mut json.clone(), 4).is_err());
+    manifest_schema::downgrade(&mut json, 5).unwrap();
+    assert!(manifest_schema::downgrade(&mut json, 0).is_err());
+
+    encoded.encoding = Some(EncodingConfig { chunk_size: 1000, ..Default::default() });
+    let mut json = serde_json::to_value(Versioned::new(&encoded)).unwrap();
+    assert!(manifest_schema::downgrade(&mut json.clone(), 12).is_err());
+    manifest_schema::downgrade(&mut json, 13).unwrap();
+}
@@ -0,0 +1,63 @@
+//! Tests for memory accounting of engram structures.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn memory_usage_accounts_for_each_structure() {
+    use embeddenator::embrfs::{EmbrFS, DEFAULT_CHUNK_SIZE};
+    use embeddenator::fuse_shim::EngramFS;
+    use embeddenator::metrics::metrics;
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    let empty = embrfs.memory_usage();
+    assert_eq!(empty.codebook_bytes + empty.index_bytes + empty.cache_bytes, 0);
+
+    embrfs.chunk_index = Some(Default::default());
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+    embrfs.ingest_bytes(&data, "dir/a.bin".into(), &config).unwrap();
+    let usage = embrfs.memory_usage();
+    let vector_bytes: usize = embrfs
+        .engram
+        .codebook
+        .values()
+        .map(|v| (v.pos.len() + v.neg.len()) * std::mem::size_of::<usize>())
+        .sum();
+    assert!(usage.codebook_bytes >= vector_bytes as u64);
+    assert!(usage.root_bytes > 0 && usage.manifest_bytes >= "dir/a.bin".len() as u64);
+    assert!(usage.index_bytes > 0, "dedup index is counted");
+    assert_eq!(usage.cache_bytes, 0);
+    assert_eq!(
+        usage.total(),
+        usage.codebook_bytes
+            + usage.root_bytes
+            + usage.corrections_bytes
+            + usage.manifest_bytes
+            + usage.index_bytes
+    );
+
+    let index = embrfs.engram.build_codebook_index();
+    assert!(index.memory_bytes() >= vector_bytes as u64);
+
+    let engram_usage = embrfs.engram.memory_usage();
+    let mount = EngramFS::from_engram(
+        embrfs.engram,
+        embrfs.manifest,
+        config.clone(),
+        DEFAULT_CHUNK_SIZE,
+        true,
+    );
+    let before = mount.memory_usage();
+    assert_eq!(before.codebook_bytes, engram_usage.codebook_bytes);
+    let ino = mount.lookup_path("/dir/a.bin").unwrap();
+    assert_eq!(mount.read_data(ino, 0, 10_000).unwrap(), &data[..10_000]);
+    let after = mount.memory_usage();
+    assert!(after.cache_bytes >= before.cache_bytes + 8192, "{before:?} {after:?}");
+
+    usage.publish();
+    let snapshot = metrics().snapshot();
+    #[cfg(feature = "metrics")]
+    assert_eq!(snapshot.memory_codebook_bytes, usage.codebook_bytes);
+    #[cfg(not(feature = "metrics"))]
+    assert_eq!(snapshot.memory_codebook_bytes, 0);
+}
@@ -0,0 +1,89 @@
+//! Tests for merging concurrently edited engrams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn merge_concurrent_is_commutative_and_records_conflicts() {
+    use embeddenator::{delta, EmbrFS, Manifest};
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let bytes = |seed: u8, len: u32| -> Vec<u8> {
+        (0..len).map(|j| (j as u8).wrapping_mul(seed) ^ seed).collect()
+    };
+    let dir = tempdir().unwrap();
+    let mut base = EmbrFS::new();
+    base.ingest_bytes(&bytes(3, 9000), "base.bin".into(), &config).unwrap();
+    base.save_engram(dir.path().join("base.engram")).unwrap();
+    base.save_manifest(dir.path().join("base.json")).unwrap();
+    let base_chunks = base.engram.codebook.len();
+
+    // Two copies taking different files, and different content at one path.
+    let load = || {
+        let mut fs = EmbrFS::new();
+        fs.engram = EmbrFS::load_engram(dir.path().join("base.engram")).unwrap();
+        fs.manifest = EmbrFS::load_manifest(dir.path().join("base.json")).unwrap();
+        fs
+    };
+    let mut a = load();
+    a.ingest_bytes(&bytes(5, 7000), "a.bin".into(), &config).unwrap();
+    a.ingest_bytes(&bytes(7, 5000), "notes.txt".into(), &config).unwrap();
+    let mut b = load();
+    b.ingest_bytes(&bytes(11, 6000), "b.bin".into(), &config).unwrap();
+    b.ingest_bytes(&bytes(13, 4000), "notes.txt".into(), &config).unwrap();
+    let new_a = a.engram.codebook.len() - base_chunks;
+    let new_b = b.engram.codebook.len() - base_chunks;
+
+    let (ab, report) = EmbrFS::merge_concurrent(&a, &b).unwrap();
+    let (ba, report_ba) = EmbrFS::merge_concurrent(&b, &a).unwrap();
+    assert_eq!(report, report_ba);
+    assert_eq!(
+        serde_json::to_string(&ab.manifest).unwrap(),
+        serde_json::to_string(&ba.manifest).unwrap()
+    );
+    assert_eq!(
+        delta::engram_digest(&ab.engram).unwrap(),
+        delta::engram_digest(&ba.engram).unwrap()
+    );
+
+    assert_eq!(report.shared_chunks, base_chunks);
+    assert_eq!(report.renumbered_chunks, new_a.min(new_b));
+    assert_eq!(ab.engram.codebook.len(), base_chunks + new_a + new_b);
+    assert_eq!(ab.manifest.total_chunks, base_chunks + new_a + new_b);
+    assert_eq!(report.files, 4);
+    let paths: Vec<&str> = ab.manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["a.bin", "b.bin", "base.bin", "notes.txt"]);
+
+    let report_verify = EmbrFS::verify(&ab.engram, &ab.manifest, &config).unwrap();
+    assert!(report_verify.is_ok());
+    assert_eq!(report_verify.files_verified, 4);
+
+    // The losing version of notes.txt is still readable from the merge.
+    assert_eq!(report.conflicts.len(), 1);
+    let conflict = &report.conflicts[0];
+    assert_eq!(conflict.path, "notes.txt");
+    assert_eq!(conflict.discarded.len(), 1);
+    let losing = Manifest {
+        files: conflict.discarded.clone(),
+        total_chunks: ab.manifest.total_chunks,
+        namespaces: Default::default(),
+        retention: None,
+        encoding: None,
+        inodes: Default::default(),
+        path_normalization: Default::default(),
+        provenance: None,
+        namespace_keys: None,
+    };
+    assert_eq!(EmbrFS::verify(&ab.engram, &losing, &config).unwrap().files_verified, 1);
+    let sizes = [conflict.kept.size, conflict.discarded[0].size];
+    assert!(sizes.contains(&5000) && sizes.contains(&4000));
+
+    // Merging a copy with itself changes nothing.
+    let (aa, report_aa) = EmbrFS::merge_concurrent(&a, &a).unwrap();
+    assert_eq!(report_aa.renumbered_chunks, 0);
+    assert!(report_aa.conflicts.is_empty());
+    assert_eq!(
+        delta::engram_digest(&aa.engram).unwrap(),
+        delta::engram_digest(&a.engram).unwrap()
+    );
+}
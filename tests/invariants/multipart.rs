@@ -0,0 +1,79 @@
+//! Tests for engrams split across part files with a master index.
+
+use crate::support::ingested;
+use embeddenator::multipart::{is_part_index, read_part_index};
+use embeddenator::{BinaryWriteOptions, ChecksumCodec, EmbrFS, ReversibleVSAConfig};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn part_path(index: &Path, n: usize) -> PathBuf {
+    let mut s = index.as_os_str().to_os_string();
+    s.push(format!(".part{:04}", n));
+    PathBuf::from(s)
+}
+
+#[test]
+fn split_engram_loads_transparently() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    let path = td.path().join("root.engram");
+    let opts = BinaryWriteOptions {
+        checksum: ChecksumCodec::Xxh3,
+        ..Default::default()
+    };
+
+    let index = fsys.save_engram_parts(&path, 4096, opts).unwrap();
+    assert!(index.parts.len() > 1);
+    assert!(is_part_index(&path).unwrap());
+    assert_eq!(read_part_index(&path).unwrap(), index);
+    for (n, part) in index.parts.iter().enumerate() {
+        let len = fs::metadata(part_path(&path, n)).unwrap().len();
+        assert_eq!(len, part.len);
+        assert!(len <= 4096);
+    }
+
+    let engram = EmbrFS::load_engram(&path).unwrap();
+    let out = td.path().join("out");
+    EmbrFS::extract(&engram, &fsys.manifest, &out, false, &ReversibleVSAConfig::default()).unwrap();
+    for name in ["docs/a.txt", "b.bin"] {
+        assert_eq!(fs::read(out.join(name)).unwrap(), fs::read(td.path().join("in").join(name)).unwrap());
+    }
+}
+
+#[test]
+fn missing_or_altered_parts_are_named() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    let path = td.path().join("root.engram");
+    fsys.save_engram_parts(&path, 4096, BinaryWriteOptions::default())
+        .unwrap();
+
+    let second = part_path(&path, 1);
+    let mut bytes = fs::read(&second).unwrap();
+    bytes[100] ^= 0xff;
+    fs::write(&second, &bytes).unwrap();
+    let err = EmbrFS::load_engram(&path).err().expect("altered part");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("root.engram.part0001"), "{err}");
+
+    fs::remove_file(&second).unwrap();
+    let err = EmbrFS::load_engram(&path).err().expect("missing part");
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert!(err.to_string().contains("root.engram.part0001"), "{err}");
+}
+
+#[test]
+fn resaving_with_fewer_parts_removes_stale_ones() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    let path = td.path().join("root.engram");
+
+    let many = fsys.save_engram_parts(&path, 2048, BinaryWriteOptions::default()).unwrap();
+    let few = fsys.save_engram_parts(&path, 1 << 20, BinaryWriteOptions::default()).unwrap();
+    assert_eq!(few.parts.len(), 1);
+    assert!(part_path(&path, 0).exists());
+    for n in 1..many.parts.len() {
+        assert!(!part_path(&path, n).exists());
+    }
+    EmbrFS::load_engram(&path).unwrap();
+}
@@ -0,0 +1,260 @@
+//! Tests for per-namespace encryption keys.
+
+use embeddenator::namespace_keys::rekey;
+use embeddenator::{BinaryWriteOptions, EmbrFS, EncryptionKey, KeyManifest, KeyRule, Keyring, ReversibleVSAConfig};
+
+fn keyed_fs() -> EmbrFS {
+    let config = ReversibleVSAConfig::default();
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_bytes(b"salaries and reviews", "hr/pay.txt".into(), &config).unwrap();
+    fsys.ingest_bytes(b"build notes", "eng/notes.txt".into(), &config).unwrap();
+    fsys.ingest_bytes(b"public readme", "readme.txt".into(), &config).unwrap();
+    fsys
+}
+
+#[test]
+fn longest_matching_prefix_picks_the_key() {
+    let keys = KeyManifest {
+        rules: vec![KeyRule::new("hr", 2), KeyRule::new("hr/exec/", 3)],
+        groups: Vec::new(),
+    };
+    assert_eq!(keys.key_for("hr/pay.txt"), Some(2));
+    assert_eq!(keys.key_for("hr/exec/bonus.txt"), Some(3));
+    assert_eq!(keys.key_for("hr"), Some(2));
+    assert_eq!(keys.key_for("hrx/pay.txt"), None);
+    assert_eq!(keys.key_for("readme.txt"), None);
+}
+
+#[cfg(not(feature = "encryption"))]
+#[test]
+fn keyed_groups_report_missing_feature() {
+    let td = tempfile::tempdir().unwrap();
+    let mut fsys = keyed_fs();
+    let keys: Keyring = [EncryptionKey::new(2, [2u8; 32])].into_iter().collect();
+    let err = rekey(
+        &mut fsys.engram,
+        &mut fsys.manifest,
+        &td.path().join("root.engram"),
+        vec![KeyRule::new("hr", 2)],
+        &keys,
+        BinaryWriteOptions::default(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("not enabled"), "unexpected error: {err}");
+}
+
+#[cfg(feature = "encryption")]
+mod enabled {
+    use super::*;
+    use embeddenator::namespace_keys::{open, rekey_saved, remove_stale};
+    use embeddenator::EmbrError;
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    fn keyring(ids: &[u32]) -> Keyring {
+        ids.iter().map(|&id| EncryptionKey::new(id, [id as u8; 32])).collect()
+    }
+
+    fn save(fsys: &EmbrFS, dir: &Path) {
+        fsys.save_engram(dir.join("root.engram")).unwrap();
+        fsys.save_manifest(dir.join("manifest.json")).unwrap();
+    }
+
+    fn extract(dir: &Path, keys: &Keyring, namespace: Option<&str>) -> io::Result<tempfile::TempDir> {
+        let engram_path = dir.join("root.engram");
+        let mut engram = EmbrFS::load_engram(&engram_path)?;
+        let manifest = EmbrFS::load_manifest(dir.join("manifest.json"))?;
+        let report = open(&mut engram, &manifest, &engram_path, keys)?;
+        let key_manifest = manifest.namespace_keys.as_ref().unwrap();
+        match namespace {
+            Some(ns) => report.require(key_manifest, manifest.files_in_namespace(ns))?,
+            None => report.require(key_manifest, &manifest.files)?,
+        }
+        let out = tempfile::tempdir()?;
+        match namespace {
+            Some(ns) => EmbrFS::extract_namespace(&engram, &manifest, ns, out.path(), false, &manifest.encoding().vsa)?,
+            None => EmbrFS::extract(&engram, &manifest, out.path(), false, &manifest.encoding().vsa)?,
+        }
+        Ok(out)
+    }
+
+    fn group_file(fsys: &EmbrFS, dir: &Path, key_id: u32) -> std::path::PathBuf {
+        dir.join(&fsys.manifest.namespace_keys.as_ref().unwrap().group(key_id).unwrap().file)
+    }
+
+    fn keyed(dir: &Path) -> EmbrFS {
+        let mut fsys = keyed_fs();
+        let rules = vec![KeyRule::new("hr", 2), KeyRule::new("eng", 3)];
+        let report = rekey(
+            &mut fsys.engram,
+            &mut fsys.manifest,
+            &dir.join("root.engram"),
+            rules,
+            &keyring(&[2, 3]),
+            BinaryWriteOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.written.keys().copied().collect::<Vec<_>>(), vec![2, 3]);
+        assert!(report.engram_changed);
+        save(&fsys, dir);
+        fsys
+    }
+
+    #[test]
+    fn each_namespace_reads_only_with_its_key() {
+        let td = tempfile::tempdir().unwrap();
+        let fsys = keyed(td.path());
+        assert_eq!(fsys.engram.codebook.len(), 1);
+
+        let group = fs::read(group_file(&fsys, td.path(), 2)).unwrap();
+        assert!(!group.windows(8).any(|w| w == b"salaries"));
+
+        let out = extract(td.path(), &keyring(&[2]), Some("hr")).unwrap();
+        assert_eq!(fs::read(out.path().join("pay.txt")).unwrap(), b"salaries and reviews");
+
+        let err = extract(td.path(), &keyring(&[2]), None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(matches!(EmbrError::find(&err), Some(EmbrError::MissingKey(msg)) if msg.contains("key id 3")));
+
+        // Any provider works, such as a closure standing in for a KMS.
+        let kms = |id: u32| Ok((id == 3).then(|| EncryptionKey::new(3, [3u8; 32])));
+        let engram_path = td.path().join("root.engram");
+        let mut engram = EmbrFS::load_engram(&engram_path).unwrap();
+        let report = open(&mut engram, &fsys.manifest, &engram_path, &kms).unwrap();
+        assert_eq!((report.opened, report.locked), (vec![3], vec![2]));
+
+        let wrong: Keyring = [EncryptionKey::new(2, [9u8; 32]), EncryptionKey::new(3, [3u8; 32])].into_iter().collect();
+        assert!(extract(td.path(), &wrong, None).is_err());
+    }
+
+    #[test]
+    fn rotating_one_key_rewrites_only_its_group() {
+        let td = tempfile::tempdir().unwrap();
+        let mut fsys = keyed(td.path());
+        let (hr_group, eng_group) = (group_file(&fsys, td.path(), 2), group_file(&fsys, td.path(), 3));
+        let eng_before = fs::read(&eng_group).unwrap();
+
+        let report = rekey(
+            &mut fsys.engram,
+            &mut fsys.manifest,
+            &td.path().join("root.engram"),
+            vec![KeyRule::new("hr", 4), KeyRule::new("eng", 3)],
+            &keyring(&[2, 4]),
+            BinaryWriteOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.written.keys().copied().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(report.unchanged, vec![3]);
+        assert_eq!(report.removed, vec![2]);
+        assert!(!report.engram_changed);
+        // The old group stays until the manifest naming the new one is saved.
+        assert!(hr_group.exists());
+        fsys.save_manifest(td.path().join("manifest.json")).unwrap();
+        remove_stale(&td.path().join("root.engram"), &report).unwrap();
+
+        assert!(!hr_group.exists());
+        assert_eq!(group_file(&fsys, td.path(), 3), eng_group);
+        assert_eq!(fs::read(&eng_group).unwrap(), eng_before);
+        assert!(extract(td.path(), &keyring(&[2, 3]), None).is_err());
+        let out = extract(td.path(), &keyring(&[3, 4]), None).unwrap();
+        assert_eq!(fs::read(out.path().join("hr/pay.txt")).unwrap(), b"salaries and reviews");
+    }
+
+    #[test]
+    fn clearing_rules_moves_chunks_back_into_the_engram() {
+        let td = tempfile::tempdir().unwrap();
+        let mut fsys = keyed(td.path());
+        let engram_path = td.path().join("root.engram");
+        // The engram as loaded lacks the grouped chunks; rekey reads them back.
+        fsys.engram = EmbrFS::load_engram(&engram_path).unwrap();
+
+        let report = rekey(
+            &mut fsys.engram,
+            &mut fsys.manifest,
+            &engram_path,
+            Vec::new(),
+            &keyring(&[2, 3]),
+            BinaryWriteOptions::default(),
+        )
+        .unwrap();
+        assert!(report.engram_changed);
+        assert_eq!(report.removed, vec![2, 3]);
+        assert!(fsys.manifest.namespace_keys.is_none());
+        assert_eq!(fsys.engram.codebook.len(), 3);
+
+        let out = tempfile::tempdir().unwrap();
+        EmbrFS::extract(&fsys.engram, &fsys.manifest, out.path(), false, &ReversibleVSAConfig::default()).unwrap();
+        assert_eq!(fs::read(out.path().join("eng/notes.txt")).unwrap(), b"build notes");
+    }
+
+    #[test]
+    fn failed_rekey_leaves_engram_manifest_and_groups_as_they_were() {
+        let td = tempfile::tempdir().unwrap();
+        let mut fsys = keyed(td.path());
+        let before = fsys.manifest.namespace_keys.clone();
+        let files = || {
+            let mut names: Vec<_> = fs::read_dir(td.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+            names.sort();
+            names
+        };
+        let on_disk = files();
+
+        // Key 5 is missing: its group cannot be written, after key 4's was.
+        let err = rekey(
+            &mut fsys.engram,
+            &mut fsys.manifest,
+            &td.path().join("root.engram"),
+            vec![KeyRule::new("hr", 4), KeyRule::new("eng", 5)],
+            &keyring(&[2, 3, 4]),
+            BinaryWriteOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(EmbrError::find(&err), Some(EmbrError::MissingKey(msg)) if msg.contains("key id 5")));
+        assert_eq!(fsys.manifest.namespace_keys, before);
+        assert_eq!(fsys.engram.codebook.len(), 1);
+        assert_eq!(files(), on_disk);
+    }
+
+    #[test]
+    fn rekey_saved_commits_then_removes_stale_groups() {
+        let td = tempfile::tempdir().unwrap();
+        keyed(td.path());
+        let (engram_path, manifest_path) = (td.path().join("root.engram"), td.path().join("manifest.json"));
+        let mut fsys = EmbrFS::new();
+        fsys.engram = EmbrFS::load_engram(&engram_path).unwrap();
+        fsys.manifest = EmbrFS::load_manifest(&manifest_path).unwrap();
+        let old_group = group_file(&fsys, td.path(), 2);
+
+        let report = rekey_saved(
+            &mut fsys,
+            &engram_path,
+            &manifest_path,
+            vec![KeyRule::new("eng", 3)],
+            &keyring(&[2, 3]),
+            BinaryWriteOptions::default(),
+        )
+        .unwrap();
+        assert!(report.engram_changed);
+        assert_eq!(report.removed, vec![2]);
+        assert!(!old_group.exists());
+        let out = extract(td.path(), &keyring(&[3]), None).unwrap();
+        assert_eq!(fs::read(out.path().join("hr/pay.txt")).unwrap(), b"salaries and reviews");
+
+        #[cfg(feature = "signing")]
+        {
+            let (secret, _) = embeddenator::signing::generate_keypair().unwrap();
+            embeddenator::seal::seal_files(&engram_path, &manifest_path, &secret).unwrap();
+            let err = rekey(
+                &mut fsys.engram,
+                &mut fsys.manifest,
+                &engram_path,
+                Vec::new(),
+                &keyring(&[3]),
+                BinaryWriteOptions::default(),
+            )
+            .unwrap_err();
+            assert!(matches!(EmbrError::find(&err), Some(EmbrError::Immutable(_))));
+        }
+    }
+}
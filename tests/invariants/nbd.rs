@@ -0,0 +1,217 @@
+//! Tests for the read-only NBD server, driven by a minimal protocol client.
+
+use embeddenator::nbd::NbdServer;
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+const OPT_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+
+fn disk() -> Vec<u8> {
+    (0..40_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect()
+}
+
+fn start(server: NbdServer) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || server.serve_listener(listener));
+    addr
+}
+
+fn server_with(files: &[(&str, Vec<u8>)]) -> NbdServer {
+    let mut fs = EmbrFS::new();
+    for (path, data) in files {
+        fs.ingest_bytes(data, path.to_string(), &ReversibleVSAConfig::default())
+            .unwrap();
+    }
+    NbdServer::new(fs.engram, fs.manifest)
+}
+
+struct Client(TcpStream);
+
+impl Client {
+    fn connect(addr: &str, client_flags: u32) -> Self {
+        let mut c = Client(TcpStream::connect(addr).unwrap());
+        assert_eq!(&c.bytes(8), b"NBDMAGIC");
+        assert_eq!(c.u64(), IHAVEOPT);
+        assert_eq!(c.u16() & 3, 3);
+        c.0.write_all(&client_flags.to_be_bytes()).unwrap();
+        c
+    }
+
+    fn bytes(&mut self, n: usize) -> Vec<u8> {
+        let mut b = vec![0; n];
+        self.0.read_exact(&mut b).unwrap();
+        b
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_be_bytes(self.bytes(2).try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_be_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.bytes(8).try_into().unwrap())
+    }
+
+    fn option(&mut self, option: u32, data: &[u8]) {
+        let mut msg = IHAVEOPT.to_be_bytes().to_vec();
+        msg.extend_from_slice(&option.to_be_bytes());
+        msg.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        msg.extend_from_slice(data);
+        self.0.write_all(&msg).unwrap();
+    }
+
+    /// (reply type, payload) of the next option reply to `option`.
+    fn reply(&mut self, option: u32) -> (u32, Vec<u8>) {
+        assert_eq!(self.u64(), OPT_REPLY_MAGIC);
+        assert_eq!(self.u32(), option);
+        let kind = self.u32();
+        let len = self.u32() as usize;
+        (kind, self.bytes(len))
+    }
+
+    fn go(&mut self, name: &str, info_requests: &[u16]) -> (u32, Vec<(u32, Vec<u8>)>) {
+        let mut data = (name.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&(info_requests.len() as u16).to_be_bytes());
+        for r in info_requests {
+            data.extend_from_slice(&r.to_be_bytes());
+        }
+        self.option(7, &data);
+        let mut infos = Vec::new();
+        loop {
+            let (kind, payload) = self.reply(7);
+            match kind {
+                3 => infos.push((kind, payload)),
+                _ => return (kind, infos),
+            }
+        }
+    }
+
+    fn request(&mut self, command: u16, cookie: u64, offset: u64, len: u32, data: &[u8]) {
+        let mut msg = 0x2560_9513u32.to_be_bytes().to_vec();
+        msg.extend_from_slice(&0u16.to_be_bytes());
+        msg.extend_from_slice(&command.to_be_bytes());
+        msg.extend_from_slice(&cookie.to_be_bytes());
+        msg.extend_from_slice(&offset.to_be_bytes());
+        msg.extend_from_slice(&len.to_be_bytes());
+        msg.extend_from_slice(data);
+        self.0.write_all(&msg).unwrap();
+    }
+
+    /// (error, cookie) of the next simple reply.
+    fn simple_reply(&mut self) -> (u32, u64) {
+        assert_eq!(self.u32(), 0x6744_6698);
+        (self.u32(), self.u64())
+    }
+
+    fn read(&mut self, cookie: u64, offset: u64, len: u32) -> Result<Vec<u8>, u32> {
+        self.request(0, cookie, offset, len, &[]);
+        let (error, got) = self.simple_reply();
+        assert_eq!(got, cookie);
+        match error {
+            0 => Ok(self.bytes(len as usize)),
+            e => Err(e),
+        }
+    }
+}
+
+#[test]
+fn go_reads_blocks_and_refuses_writes() {
+    let image = disk();
+    let addr = start(server_with(&[("vm.img", image.clone()), ("notes.txt", b"hi".to_vec())]));
+
+    let mut c = Client::connect(&addr, 3);
+    c.option(3, &[]);
+    let mut names = Vec::new();
+    loop {
+        match c.reply(3) {
+            (2, payload) => names.push(String::from_utf8(payload[4..].to_vec()).unwrap()),
+            (1, _) => break,
+            other => panic!("unexpected list reply {other:?}"),
+        }
+    }
+    assert_eq!(names, ["vm.img", "notes.txt"]);
+
+    // Two files: no default export.
+    assert_eq!(c.go("", &[]).0, (1 << 31) | 6);
+    c.option(99, &[]);
+    assert_eq!(c.reply(99).0, (1 << 31) | 1);
+
+    let (ack, infos) = c.go("vm.img", &[3]);
+    assert_eq!(ack, 1);
+    let export = &infos[0].1;
+    assert_eq!(u16::from_be_bytes([export[0], export[1]]), 0);
+    assert_eq!(u64::from_be_bytes(export[2..10].try_into().unwrap()), image.len() as u64);
+    let flags = u16::from_be_bytes([export[10], export[11]]);
+    assert_eq!(flags & 3, 3, "has-flags and read-only");
+    let block = &infos[1].1;
+    assert_eq!(u32::from_be_bytes(block[6..10].try_into().unwrap()), 4096);
+
+    assert_eq!(c.read(1, 0, 4096).unwrap(), &image[..4096]);
+    assert_eq!(c.read(2, 5000, 10_000).unwrap(), &image[5000..15_000]);
+    assert_eq!(c.read(3, 39_990, 10).unwrap(), &image[39_990..]);
+    assert_eq!(c.read(4, 39_990, 11), Err(22));
+    assert_eq!(c.read(5, u64::MAX - 1, 4), Err(22));
+
+    c.request(1, 6, 0, 3, b"abc");
+    assert_eq!(c.simple_reply(), (1, 6));
+    c.request(3, 7, 0, 0, &[]);
+    assert_eq!(c.simple_reply(), (0, 7));
+    assert_eq!(c.read(8, 100, 1).unwrap(), &image[100..101]);
+    c.request(2, 9, 0, 0, &[]);
+    let mut rest = Vec::new();
+    c.0.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn export_name_and_default_export() {
+    let image = disk();
+    let addr = start(server_with(&[("only.img", image.clone())]));
+
+    // Legacy NBD_OPT_EXPORT_NAME with the default export and zero padding.
+    let mut c = Client::connect(&addr, 1);
+    c.option(1, b"");
+    assert_eq!(c.u64(), image.len() as u64);
+    assert_eq!(c.u16() & 2, 2);
+    assert_eq!(c.bytes(124), [0; 124]);
+    assert_eq!(c.read(1, 4096, 8192).unwrap(), &image[4096..12_288]);
+
+    // Unknown names close the connection during EXPORT_NAME.
+    let mut c = Client::connect(&addr, 3);
+    c.option(1, b"missing");
+    let mut rest = Vec::new();
+    c.0.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+
+    let both = server_with(&[("a.img", image.clone()), ("b.img", image[..5000].to_vec())]);
+    assert!(both.clone().with_default_export("nope").is_err());
+    let addr = start(both.with_default_export("b.img").unwrap());
+    let mut c = Client::connect(&addr, 3);
+    let (ack, infos) = c.go("", &[]);
+    assert_eq!((ack, infos.len()), (1, 1));
+    assert_eq!(c.read(1, 0, 5000).unwrap(), &image[..5000]);
+}
+
+#[test]
+fn corrupted_chunks_are_eio() {
+    let image = disk();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&image, "vm.img".into(), &ReversibleVSAConfig::default())
+        .unwrap();
+    let second = fs.manifest.files[0].chunks[1];
+    fs.engram.codebook.remove(&second);
+    let addr = start(NbdServer::new(fs.engram, fs.manifest));
+
+    let mut c = Client::connect(&addr, 3);
+    assert_eq!(c.go("vm.img", &[]).0, 1);
+    assert_eq!(c.read(1, 0, 4096).unwrap(), &image[..4096]);
+    assert_eq!(c.read(2, 4000, 200), Err(5));
+    assert_eq!(c.read(3, 8192, 100).unwrap(), &image[8192..8292]);
+}
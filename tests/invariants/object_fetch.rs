@@ -0,0 +1,31 @@
+//! Tests for the object_store-backed S3 object fetcher.
+
+use embeddenator::object_fetch::ObjectStoreFetcher;
+use embeddenator::stream_ingest::{resolve_object_events, FileEvent, ObjectEvent, ObjectEventKind, ObjectFetcher};
+use object_store::memory::InMemory;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use std::io;
+use std::sync::Arc;
+
+#[test]
+fn fetches_per_bucket_and_maps_missing_objects() {
+    let store = Arc::new(InMemory::new());
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    runtime
+        .block_on(store.put(&Path::from("data/one.bin"), vec![1u8, 2, 3].into()))
+        .unwrap();
+
+    let fetcher = ObjectStoreFetcher::new().unwrap().with_store("archive", store);
+    assert_eq!(fetcher.fetch("archive", "data/one.bin").unwrap(), [1, 2, 3]);
+    assert_eq!(fetcher.fetch("archive", "data/two.bin").unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(fetcher.fetch("other", "data/one.bin").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    let event = |key: &str| ObjectEvent {
+        kind: ObjectEventKind::Created,
+        bucket: "archive".into(),
+        key: key.into(),
+    };
+    let files = resolve_object_events(vec![event("data/one.bin"), event("data/two.bin")], &fetcher).unwrap();
+    assert_eq!(files, [FileEvent::Put { path: "data/one.bin".into(), data: vec![1, 2, 3] }]);
+}
@@ -0,0 +1,144 @@
+//! Tests for the ONNX chunk embedder, using a tiny hand-encoded model.
+
+use embeddenator::onnx_embed::OnnxEmbedder;
+use embeddenator::projection::ProjectionConfig;
+use embeddenator::semantic::{ChunkEmbedder, SemanticEncoder};
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+
+const HIDDEN: usize = 8;
+
+fn varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn int_field(out: &mut Vec<u8>, field: u64, v: u64) {
+    varint(out, field << 3);
+    varint(out, v);
+}
+
+fn bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, (field << 3) | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn table(token: usize, j: usize) -> f32 {
+    ((token * 31 + j * 17) % 23) as f32 - 11.0
+}
+
+/// ONNX model `hidden = Gather(table, input_ids)`: per-token states
+/// `[1, S, HIDDEN]` looked up from a 256-row embedding table.
+fn gather_model() -> Vec<u8> {
+    let mut node = Vec::new();
+    bytes_field(&mut node, 1, b"table");
+    bytes_field(&mut node, 1, b"input_ids");
+    bytes_field(&mut node, 2, b"hidden");
+    bytes_field(&mut node, 3, b"lookup");
+    bytes_field(&mut node, 4, b"Gather");
+
+    let mut init = Vec::new();
+    int_field(&mut init, 1, 256);
+    int_field(&mut init, 1, HIDDEN as u64);
+    int_field(&mut init, 2, 1); // FLOAT
+    bytes_field(&mut init, 8, b"table");
+    let raw: Vec<u8> = (0..256)
+        .flat_map(|t| (0..HIDDEN).map(move |j| table(t, j)))
+        .flat_map(f32::to_le_bytes)
+        .collect();
+    bytes_field(&mut init, 9, &raw);
+
+    let value_info = |name: &[u8], elem_type: u64, dims: &[Result<u64, &[u8]>]| {
+        let mut shape = Vec::new();
+        for dim in dims {
+            let mut d = Vec::new();
+            match dim {
+                Ok(v) => int_field(&mut d, 1, *v),
+                Err(param) => bytes_field(&mut d, 2, param),
+            }
+            bytes_field(&mut shape, 1, &d);
+        }
+        let mut tensor = Vec::new();
+        int_field(&mut tensor, 1, elem_type);
+        bytes_field(&mut tensor, 2, &shape);
+        let mut ty = Vec::new();
+        bytes_field(&mut ty, 1, &tensor);
+        let mut info = Vec::new();
+        bytes_field(&mut info, 1, name);
+        bytes_field(&mut info, 2, &ty);
+        info
+    };
+
+    let mut graph = Vec::new();
+    bytes_field(&mut graph, 1, &node);
+    bytes_field(&mut graph, 2, b"embed");
+    bytes_field(&mut graph, 5, &init);
+    bytes_field(&mut graph, 11, &value_info(b"input_ids", 7, &[Ok(1), Err(b"S")]));
+    bytes_field(&mut graph, 12, &value_info(b"hidden", 1, &[Ok(1), Err(b"S"), Ok(HIDDEN as u64)]));
+
+    let mut opset = Vec::new();
+    int_field(&mut opset, 2, 13);
+    let mut model = Vec::new();
+    int_field(&mut model, 1, 7);
+    bytes_field(&mut model, 7, &graph);
+    bytes_field(&mut model, 8, &opset);
+    model
+}
+
+/// Mean-pooled, L2-normalized table rows for byte tokens.
+fn expected(data: &[u8]) -> Vec<f32> {
+    let mut mean = [0f32; HIDDEN];
+    for &b in data {
+        for (j, m) in mean.iter_mut().enumerate() {
+            *m += table(b as usize, j) / data.len() as f32;
+        }
+    }
+    let norm = mean.iter().map(|x| x * x).sum::<f32>().sqrt();
+    mean.iter().map(|x| x / norm).collect()
+}
+
+#[test]
+fn byte_level_model_mean_pools_and_normalizes() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("embed.onnx");
+    std::fs::write(&path, gather_model()).unwrap();
+
+    let embedder = OnnxEmbedder::load(&path, None).unwrap();
+    assert!(embedder.model_id().starts_with("embed.onnx@"));
+    for data in [&b"semantic"[..], b"x", b"a longer chunk of text to pool over"] {
+        let got = embedder.embed(data).unwrap();
+        assert_eq!(got.len(), HIDDEN);
+        for (g, e) in got.iter().zip(expected(data)) {
+            assert!((g - e).abs() < 1e-5, "{got:?}");
+        }
+    }
+    assert_eq!(embedder.embed(b"same").unwrap(), embedder.embed(b"same").unwrap());
+
+    let truncated = OnnxEmbedder::load(&path, None).unwrap().with_max_tokens(3);
+    assert_eq!(truncated.embed(b"abcdef").unwrap(), embedder.embed(b"abc").unwrap());
+
+    assert!(OnnxEmbedder::load(td.path().join("missing.onnx"), None).is_err());
+    std::fs::write(td.path().join("junk.onnx"), b"not a model").unwrap();
+    assert!(OnnxEmbedder::load(td.path().join("junk.onnx"), None).is_err());
+}
+
+#[test]
+fn ingest_with_onnx_signatures() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("embed.onnx");
+    std::fs::write(&path, gather_model()).unwrap();
+    let embedder = OnnxEmbedder::load(&path, None).unwrap().with_max_tokens(64);
+    let model_id = embedder.model_id();
+
+    let mut fsys = EmbrFS::new();
+    fsys.set_semantic_encoder(SemanticEncoder::new(Box::new(embedder), ProjectionConfig::default()));
+    fsys.ingest_bytes(&b"onnx backed signatures\n".repeat(300), "a.txt".into(), &ReversibleVSAConfig::default())
+        .unwrap();
+    let signatures = fsys.semantic.as_ref().unwrap();
+    assert_eq!(signatures.model, model_id);
+    assert_eq!(signatures.vectors.len(), fsys.engram.codebook.len());
+    assert!(signatures.vectors.values().all(|v| !v.pos.is_empty() || !v.neg.is_empty()));
+}
@@ -0,0 +1,62 @@
+//! Tests for semantic outlier detectors.
+
+#[test]
+fn outlier_detectors_are_configurable() {
+    use embeddenator::{Codebook, OutlierDetector, OutlierRule, OutlierWindow};
+
+    struct ZeroRun;
+    impl OutlierDetector for ZeroRun {
+        fn name(&self) -> &str {
+            "zero-run"
+        }
+        fn detect(&self, _: &Codebook, window: &OutlierWindow<'_>) -> Option<f64> {
+            window.bytes.iter().all(|&b| b == 0).then_some(1.0)
+        }
+    }
+
+    // Text with a stretch of pseudo-random bytes in the middle.
+    let mut data = b"the quick brown fox jumps over the lazy dog. ".repeat(4);
+    let noisy_at = data.len();
+    let mut x = 0x9e37_79b9_7f4a_7c15u64;
+    for _ in 0..64 {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        data.push(x as u8);
+    }
+    data.extend(b"the quick brown fox jumps over the lazy dog. ".repeat(2));
+    data.extend([0u8; 40]);
+
+    let mut codebook = Codebook::new(embeddenator::DIM);
+    codebook.initialize_standard_basis();
+    let outliers = codebook.project(&data).outliers;
+    assert!(!outliers.is_empty());
+    for o in &outliers {
+        assert_eq!(o.detector, "entropy");
+        assert!(o.entropy_score > 4.5);
+        assert!(o.position + o.length > noisy_at && o.position < noisy_at + 64);
+    }
+
+    codebook.outliers.rules.clear();
+    assert!(codebook.project(&data).outliers.is_empty());
+
+    // No window resembles the pattern basis, so all of them are flagged.
+    codebook.outliers.rules = vec!["similarity=0.3".parse().unwrap()];
+    let flagged = codebook.project(&data).outliers;
+    assert!(flagged.len() > outliers.len());
+    assert!(flagged.iter().all(|o| o.detector == "similarity" && o.score > 0.7));
+
+    let custom = codebook.project_with(&data, &[&ZeroRun]).outliers;
+    assert!(!custom.is_empty());
+    assert!(custom.iter().all(|o| o.detector == "zero-run" && o.position >= data.len() - 40));
+
+    assert_eq!("residual=0.9".parse::<OutlierRule>(), Ok(OutlierRule::Residual(0.9)));
+    assert!("entropy".parse::<OutlierRule>().is_err());
+    assert!("novelty=1".parse::<OutlierRule>().is_err());
+
+    // Rules travel with a saved codebook.
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("x.basis");
+    codebook.save(&path, Default::default()).unwrap();
+    assert_eq!(Codebook::load(&path).unwrap().outliers, codebook.outliers);
+}
@@ -0,0 +1,80 @@
+//! Logical paths are normalized at ingest as the manifest says, and
+//! extraction reports paths the output filesystem cannot tell apart.
+
+use embeddenator::path_norm::{collisions, Folding};
+use embeddenator::{EmbrFS, PathNormalization, ReversibleVSAConfig, UnicodeForm};
+
+const COMPOSED: &str = "caf\u{e9}.txt";
+const DECOMPOSED: &str = "cafe\u{301}.txt";
+
+#[test]
+fn ingest_applies_and_records_the_policy() {
+    let policy = PathNormalization {
+        unicode: UnicodeForm::Nfc,
+        lowercase: true,
+    };
+    assert_eq!(policy.apply(format!("Docs/{}", DECOMPOSED)), format!("docs/{}", COMPOSED));
+    let nfd = PathNormalization {
+        unicode: UnicodeForm::Nfd,
+        lowercase: false,
+    };
+    assert_eq!(nfd.apply(COMPOSED.to_string()), DECOMPOSED);
+    assert_eq!(PathNormalization::default().apply(DECOMPOSED.to_string()), DECOMPOSED);
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.manifest.path_normalization = policy;
+    fs.ingest_bytes(b"one", format!("Docs/{}", DECOMPOSED), &config).unwrap();
+    fs.ingest_bytes(b"two", "docs/README".into(), &config).unwrap();
+    let paths: Vec<&str> = fs.manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, [format!("docs/{}", COMPOSED).as_str(), "docs/readme"]);
+
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("manifest.json");
+    fs.save_manifest(&path).unwrap();
+    assert_eq!(EmbrFS::load_manifest(&path).unwrap().path_normalization, policy);
+
+    // Without a policy the manifest says nothing about it.
+    let mut plain = EmbrFS::new();
+    plain.ingest_bytes(b"one", DECOMPOSED.into(), &config).unwrap();
+    plain.save_manifest(&path).unwrap();
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("path_normalization"));
+    assert_eq!(plain.manifest.files[0].path, DECOMPOSED);
+}
+
+#[test]
+fn collisions_follow_the_filesystem_folding() {
+    let paths = ["A.txt", COMPOSED, "b.txt", "a.txt", DECOMPOSED, "B.TXT"];
+    let portable = collisions(paths, Folding::PORTABLE);
+    let groups: Vec<Vec<&str>> = portable
+        .iter()
+        .map(|c| c.paths.iter().map(String::as_str).collect())
+        .collect();
+    assert_eq!(groups, [vec!["A.txt", "a.txt"], vec![COMPOSED, DECOMPOSED], vec!["b.txt", "B.TXT"]]);
+
+    let case_only = Folding {
+        case_insensitive: true,
+        normalization_insensitive: false,
+    };
+    assert_eq!(collisions(paths, case_only).len(), 2);
+    assert!(collisions(paths, Folding::default()).is_empty());
+    assert_eq!(collisions(["x", "x"], Folding::default()).len(), 1);
+}
+
+#[test]
+fn extraction_reports_what_the_output_dir_folds_together() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    for (path, data) in [("Notes.txt", b"upper"), ("notes.txt", b"lower"), (COMPOSED, b"nfc.."), (DECOMPOSED, b"nfd..")] {
+        fs.ingest_bytes(data, path.into(), &config).unwrap();
+    }
+
+    let td = tempfile::tempdir().unwrap();
+    let folding = Folding::probe(td.path()).unwrap();
+    let report = EmbrFS::extract_with_provenance(&fs.engram, &fs.manifest, None, td.path(), false, &config, None).unwrap();
+    assert_eq!(report.collisions, fs.manifest.collisions(None, folding));
+    assert_eq!(report.collisions.len(), folding.case_insensitive as usize + folding.normalization_insensitive as usize);
+    // Whatever the filesystem, the last of each group is what reads back.
+    assert_eq!(std::fs::read(td.path().join("notes.txt")).unwrap(), b"lower");
+    assert_eq!(std::fs::read(td.path().join(DECOMPOSED)).unwrap(), b"nfd..");
+}
@@ -0,0 +1,44 @@
+//! Tests for profiling scopes and folded stacks.
+
+#[test]
+fn profile_scopes_nest_into_folded_stacks() {
+    use embeddenator::profile;
+    use std::time::Duration;
+
+    // Off by default: nothing is recorded.
+    drop(profile::scope("qa_profile_never"));
+    assert!(!profile::snapshot().stacks.keys().any(|k| k.contains("qa_profile_never")));
+
+    profile::enable(1);
+    for _ in 0..3 {
+        let _outer = profile::scope("qa_profile_outer");
+        std::thread::sleep(Duration::from_millis(2));
+        {
+            let _inner = profile::scope("qa_profile_inner");
+            std::thread::sleep(Duration::from_millis(3));
+            let _again = profile::scope("qa_profile_outer");
+        }
+    }
+    // Other threads are profiled separately and merge when their outermost
+    // scope closes.
+    std::thread::spawn(|| drop(profile::scope("qa_profile_inner"))).join().unwrap();
+    profile::disable();
+
+    let snapshot = profile::snapshot();
+    let outer = snapshot.stacks["qa_profile_outer"];
+    let inner = snapshot.stacks["qa_profile_outer;qa_profile_inner"];
+    assert_eq!(outer.calls, 3);
+    assert_eq!(inner.calls, 3);
+    assert_eq!(snapshot.stacks["qa_profile_outer;qa_profile_inner;qa_profile_outer"].calls, 3);
+    assert_eq!(snapshot.stacks["qa_profile_inner"].calls, 1);
+    assert!(outer.self_ns >= 6_000_000 && outer.self_ns < outer.total_ns, "{outer:?}");
+    assert!(inner.total_ns >= 9_000_000, "{inner:?}");
+    assert!(outer.total_ns >= outer.self_ns + inner.total_ns);
+    // The nested repeat of the outer scope is not counted twice.
+    assert_eq!(snapshot.total_ns("qa_profile_outer"), outer.total_ns);
+
+    let folded = snapshot.folded();
+    let line = folded.lines().find(|l| l.starts_with("qa_profile_outer;qa_profile_inner ")).unwrap();
+    let us: u64 = line.rsplit(' ').next().unwrap().parse().unwrap();
+    assert!(us >= 9_000, "{line}");
+}
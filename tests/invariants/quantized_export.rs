@@ -0,0 +1,108 @@
+//! Tests for int8 and packed 2-bit exports of chunk signatures and their
+//! import.
+
+use crate::support::ingested;
+use embeddenator::dense_export::IDS_NPY;
+use embeddenator::quantized_export::{
+    export_engram, import, pack_trits, unpack_trits, QuantizedFormat, QuantizedMetadata, METADATA_JSON,
+    SIGNATURES_NPY,
+};
+use embeddenator::{SparseVec, DIM};
+use std::fs;
+use std::io::ErrorKind;
+
+/// `vec` with indices present in both `pos` and `neg` cancelled out.
+fn cancelled(vec: &SparseVec) -> SparseVec {
+    SparseVec {
+        pos: vec.pos.iter().copied().filter(|i| !vec.neg.contains(i)).collect(),
+        neg: vec.neg.iter().copied().filter(|i| !vec.pos.contains(i)).collect(),
+    }
+}
+
+#[test]
+fn packing_puts_four_trits_in_a_byte() {
+    let row = [1i8, -1, 0, 1, -1, 0];
+    let mut packed = Vec::new();
+    pack_trits(&row, &mut packed);
+    assert_eq!(packed, vec![0b01_00_10_01, 0b00_10]);
+
+    let mut back = [0i8; 6];
+    assert_eq!(unpack_trits(&packed, &mut back), Some(()));
+    assert_eq!(back, row);
+    // A `11` code and a set padding bit are both refused.
+    assert_eq!(unpack_trits(&[0b11, 0], &mut back), None);
+    assert_eq!(unpack_trits(&[0, 0b01_00_00], &mut back), None);
+}
+
+#[test]
+fn both_formats_round_trip_the_codebook() {
+    let root = tempfile::tempdir().unwrap();
+    let fsys = ingested(root.path());
+    for format in [QuantizedFormat::Int8, QuantizedFormat::Packed2] {
+        let td = tempfile::tempdir().unwrap();
+        let meta = export_engram(&fsys.engram, format, td.path()).unwrap();
+        assert_eq!(meta.rows, fsys.engram.codebook.len());
+        assert_eq!(meta.dim, DIM);
+        assert_eq!(meta.row_bytes, format.row_bytes(DIM));
+        assert!(meta.density > 0.0 && meta.density < 0.1, "{meta:?}");
+
+        let npy = fs::read(td.path().join(SIGNATURES_NPY)).unwrap();
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!(npy.len() - 10 - header_len, meta.rows * meta.row_bytes);
+
+        let (read, vectors) = import(td.path()).unwrap();
+        assert_eq!(read, meta);
+        assert_eq!(vectors.len(), fsys.engram.codebook.len());
+        for (id, vec) in &fsys.engram.codebook {
+            assert_eq!(vectors.get(*id), Some(&cancelled(vec)), "chunk {id}");
+        }
+    }
+}
+
+#[test]
+fn import_refuses_inconsistent_exports() {
+    let td = tempfile::tempdir().unwrap();
+    let fsys = ingested(td.path());
+    export_engram(&fsys.engram, QuantizedFormat::Packed2, td.path()).unwrap();
+    let meta_path = td.path().join(METADATA_JSON);
+    let meta: QuantizedMetadata = serde_json::from_slice(&fs::read(&meta_path).unwrap()).unwrap();
+    let original_npy = fs::read(td.path().join(SIGNATURES_NPY)).unwrap();
+
+    let refused = |what: &str| {
+        let err = import(td.path()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{what}: {err}");
+    };
+    let write_meta = |meta: &QuantizedMetadata| fs::write(&meta_path, serde_json::to_vec(meta).unwrap()).unwrap();
+
+    write_meta(&QuantizedMetadata { density: meta.density * 2.0, nnz: meta.nnz * 2, ..meta.clone() });
+    refused("density");
+    write_meta(&QuantizedMetadata { dim: DIM / 2, row_bytes: DIM / 8, ..meta.clone() });
+    refused("dimension");
+    write_meta(&QuantizedMetadata { format: QuantizedFormat::Int8, ..meta.clone() });
+    refused("row bytes");
+    write_meta(&QuantizedMetadata { format: QuantizedFormat::Int8, row_bytes: DIM, ..meta.clone() });
+    refused("dtype");
+    write_meta(&meta);
+
+    // Every code `11` in the first row's first byte.
+    let mut npy = original_npy.clone();
+    let data = npy.len() - meta.rows * meta.row_bytes;
+    npy[data] = 0xff;
+    fs::write(td.path().join(SIGNATURES_NPY), &npy).unwrap();
+    refused("trit code");
+
+    let mut npy = original_npy.clone();
+    npy.push(0);
+    fs::write(td.path().join(SIGNATURES_NPY), &npy).unwrap();
+    refused("trailing data");
+    fs::write(td.path().join(SIGNATURES_NPY), &original_npy).unwrap();
+
+    // Ids out of order.
+    let ids_path = td.path().join(IDS_NPY);
+    let mut ids = fs::read(&ids_path).unwrap();
+    let first = ids.len() - meta.rows * 8;
+    let (a, b) = ids[first..].split_at_mut(8);
+    a.swap_with_slice(&mut b[..8]);
+    fs::write(&ids_path, &ids).unwrap();
+    refused("id order");
+}
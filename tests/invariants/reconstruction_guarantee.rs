@@ -283,3 +283,76 @@ fn test_correction_stats_accuracy() {
     println!("  Corrected: {}", stats.corrected_chunks);
     println!("  Correction overhead: {:.2}%", stats.correction_ratio * 100.0);
 }
+
+#[test]
+fn test_file_checksums_recorded_and_verified() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    fs::write(input_dir.join("a.bin"), &data).unwrap();
+    fs::write(input_dir.join("b.txt"), b"checksummed text\n").unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    let config = ReversibleVSAConfig::default();
+    embrfs.ingest_directory(&input_dir, false, &config).unwrap();
+
+    let entry = embrfs.manifest.files.iter().find(|f| f.path == "a.bin").unwrap();
+    assert_eq!(
+        entry.blake3.as_deref(),
+        Some(blake3::hash(&data).to_hex().as_str())
+    );
+
+    let report = EmbrFS::verify(&embrfs.engram, &embrfs.manifest, &config).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.files_verified, 2);
+    assert_eq!(report.files_unchecked, 0);
+}
+
+#[test]
+fn test_checksum_mismatch_reports_offending_chunks() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+
+    let data: Vec<u8> = (0..9_000u32).map(|i| (i % 256) as u8).collect();
+    fs::write(input_dir.join("a.bin"), &data).unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    let config = ReversibleVSAConfig::default();
+    embrfs.ingest_directory(&input_dir, false, &config).unwrap();
+
+    // Lose the middle chunk from the codebook.
+    let chunks = embrfs.manifest.files[0].chunks.clone();
+    assert_eq!(chunks.len(), 3);
+    embrfs.engram.codebook.remove(&chunks[1]);
+
+    let report = EmbrFS::verify(&embrfs.engram, &embrfs.manifest, &config).unwrap();
+    assert_eq!(report.mismatches.len(), 1);
+    assert_eq!(report.mismatches[0].path, "a.bin");
+    assert_eq!(report.mismatches[0].chunk_ids, vec![chunks[1]]);
+
+    let err = EmbrFS::extract(&embrfs.engram, &embrfs.manifest, &output_dir, false, &config)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("a.bin"));
+}
+
+#[test]
+fn test_legacy_entries_without_checksum_are_unchecked() {
+    let mut embrfs = EmbrFS::new();
+    let config = ReversibleVSAConfig::default();
+    embrfs.manifest.files.push(embeddenator::FileEntry {
+        path: "legacy.txt".to_string(),
+        is_text: true,
+        size: 0,
+        chunks: Vec::new(),
+        ..Default::default()
+    });
+
+    let report = EmbrFS::verify(&embrfs.engram, &embrfs.manifest, &config).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.files_unchecked, 1);
+}
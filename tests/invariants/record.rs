@@ -0,0 +1,62 @@
+use embeddenator::vsa::graph::GraphEncoder;
+use embeddenator::vsa::record::{bundle_threshold, ItemMemory, Record};
+use embeddenator::{SparseVec, TritMatrix, DIM};
+
+#[test]
+fn fields_decode_to_their_values() {
+    let mut values = ItemMemory::new(5);
+    let (red, round) = (values.symbol("red"), values.symbol("round"));
+    values.symbol("blue");
+    values.symbol("square");
+    let record = Record::encode(11, [("colour", &red), ("shape", &round)]);
+
+    assert_eq!(record.fields(), ["colour", "shape"]);
+    assert_eq!(record.get("colour", &values).unwrap().0, "red");
+    assert_eq!(record.get("shape", &values).unwrap().0, "round");
+    assert_eq!(record.get("size", &values), None);
+
+    // The same names map to the same symbols in every memory and encoder
+    // with the seed.
+    assert_eq!(ItemMemory::new(5).symbol("red"), red);
+    assert_eq!(GraphEncoder::new(5).symbol("red"), red);
+}
+
+#[test]
+fn many_fields_and_sparse_values() {
+    // Random 2%-dense values.
+    let sparse = TritMatrix::random_projection(40, DIM, 200, 17);
+    let mut memory = ItemMemory::new(0);
+    for i in 0..40 {
+        memory.insert(format!("value-{i}"), sparse.row(i).to_sparse());
+    }
+    let names: Vec<String> = (0..25).map(|i| format!("field-{i}")).collect();
+    let vectors: Vec<SparseVec> = (0..25).map(|i| memory.get(&format!("value-{}", (i * 3) % 40)).unwrap().clone()).collect();
+    let record = Record::encode(3, names.iter().map(String::as_str).zip(&vectors));
+
+    for i in 0..25 {
+        let (name, score) = record.get(&format!("field-{i}"), &memory).unwrap();
+        assert_eq!(name, format!("value-{}", (i * 3) % 40));
+        assert!(score > bundle_threshold(25));
+    }
+}
+
+#[test]
+fn item_memory_cleanup_and_matches() {
+    let mut memory = ItemMemory::new(1);
+    assert!(memory.cleanup(&SparseVec::new()).is_none());
+    let a = memory.symbol("a");
+    let b = memory.symbol("b");
+    memory.symbol("c");
+    assert_eq!(memory.symbol("a"), a);
+    assert_eq!(memory.len(), 3);
+
+    let noisy = SparseVec::bundle_sum_many([&a, &b, &a]);
+    assert_eq!(memory.cleanup(&noisy).unwrap().0, "a");
+    let hits: Vec<String> = memory.matches(&a.bundle(&b), bundle_threshold(2)).into_iter().map(|(n, _)| n).collect();
+    assert_eq!(hits.len(), 2);
+    assert!(hits.contains(&"a".to_string()) && hits.contains(&"b".to_string()));
+
+    memory.insert("a", b.clone());
+    assert_eq!(memory.get("a"), Some(&b));
+    assert_eq!(memory.len(), 3);
+}
@@ -0,0 +1,158 @@
+//! Tests for delta sync with a remote engram.
+
+use embeddenator::ReversibleVSAConfig;
+
+/// Run one sync against a server thread for the files at `remote`, which
+/// reads them with `keys`.
+#[cfg(unix)]
+fn session<F>(remote: &std::path::Path, keys: embeddenator::Keyring, client: F) -> embeddenator::TransferReport
+where
+    F: FnOnce(
+        std::io::BufReader<std::os::unix::net::UnixStream>,
+        std::io::BufWriter<std::os::unix::net::UnixStream>,
+    ) -> std::io::Result<embeddenator::TransferReport>,
+{
+    use std::io::{BufReader, BufWriter};
+    use std::os::unix::net::UnixStream;
+
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let (engram, manifest) = (remote.join("root.engram"), remote.join("manifest.json"));
+    let server = std::thread::spawn(move || {
+        embeddenator::remote_sync::serve(
+            BufReader::new(theirs.try_clone().unwrap()),
+            BufWriter::new(theirs),
+            &engram,
+            &manifest,
+            &keys,
+        )
+    });
+    let report = client(BufReader::new(ours.try_clone().unwrap()), BufWriter::new(ours)).unwrap();
+    server.join().unwrap().unwrap();
+    report
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_sync_transfers_only_changed_chunks() {
+    use embeddenator::remote_sync::{self, SyncOptions};
+    use embeddenator::{EmbrFS, Keyring, DEFAULT_CHUNK_SIZE};
+
+    let keys = Keyring::default();
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote, other) = (dir.path().join("local"), dir.path().join("remote"), dir.path().join("other"));
+    for d in [&local, &remote, &other] {
+        std::fs::create_dir(d).unwrap();
+    }
+    let (engram, manifest) = (local.join("root.engram"), local.join("manifest.json"));
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    for i in 0..3u8 {
+        let data: Vec<u8> = (0..9000u32).map(|j| (j as u8).wrapping_mul(i + 5) ^ (j >> 7) as u8).collect();
+        fs.ingest_bytes(&data, format!("f{}.bin", i), &config).unwrap();
+    }
+    fs.save_engram(&engram).unwrap();
+    fs.save_manifest(&manifest).unwrap();
+    let opts = SyncOptions { batch_chunks: 2, ..Default::default() };
+
+    let first = session(&remote, Keyring::default(), |r, w| remote_sync::push(r, w, &engram, &manifest, &keys, &opts));
+    assert_eq!(first.chunks_transferred, fs.engram.codebook.len());
+    assert!(!first.up_to_date);
+
+    // After one more file only its chunks cross the link.
+    fs.ingest_bytes(&[7u8; 5000], "new.bin".into(), &config).unwrap();
+    fs.save_engram(&engram).unwrap();
+    fs.save_manifest(&manifest).unwrap();
+    let second = session(&remote, Keyring::default(), |r, w| remote_sync::push(r, w, &engram, &manifest, &keys, &opts));
+    assert_eq!((second.chunks_transferred, second.chunks_removed), (2, 0));
+    assert!(second.bytes_sent < first.bytes_sent);
+    let third = session(&remote, Keyring::default(), |r, w| remote_sync::push(r, w, &engram, &manifest, &keys, &opts));
+    assert!(third.up_to_date);
+
+    // A fresh copy pulled from the remote extracts to the same files.
+    let (pulled, pulled_manifest) = (other.join("root.engram"), other.join("manifest.json"));
+    let report = session(&remote, Keyring::default(), |r, w| remote_sync::pull(r, w, &pulled, &pulled_manifest, &keys, &opts));
+    assert_eq!(report.chunks_transferred, fs.engram.codebook.len());
+    let engram_copy = EmbrFS::load_engram(&pulled).unwrap();
+    let manifest_copy = EmbrFS::load_manifest(&pulled_manifest).unwrap();
+    for file in &fs.manifest.files {
+        let copy = manifest_copy.files.iter().find(|f| f.path == file.path).unwrap();
+        assert_eq!(
+            EmbrFS::reconstruct_bytes(&engram_copy, copy, &config, DEFAULT_CHUNK_SIZE).unwrap(),
+            EmbrFS::reconstruct_bytes(&fs.engram, file, &config, DEFAULT_CHUNK_SIZE).unwrap()
+        );
+    }
+
+    // Removing a file locally removes its chunks remotely on the next push.
+    fs.remove_file("new.bin");
+    fs.save_engram(&engram).unwrap();
+    fs.save_manifest(&manifest).unwrap();
+    let report = session(&remote, Keyring::default(), |r, w| remote_sync::push(r, w, &engram, &manifest, &keys, &opts));
+    assert_eq!((report.chunks_transferred, report.chunks_removed), (0, 2));
+    assert_eq!(EmbrFS::load_engram(remote.join("root.engram")).unwrap().codebook.len(), fs.engram.codebook.len());
+}
+
+#[test]
+fn remote_session_rejects_option_like_hosts() {
+    use embeddenator::remote_sync::RemoteSession;
+
+    // `ssh -oProxyCommand=...` would run a local command before connecting.
+    let err = RemoteSession::spawn("-oProxyCommand=touch pwned:root.engram", None, Some("false"), "embeddenator", &[])
+        .err()
+        .expect("host starting with '-' must be rejected");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("not a host name"), "{err}");
+}
+
+#[cfg(all(unix, feature = "compression-zstd", feature = "encryption"))]
+#[test]
+fn remote_sync_pull_keeps_target_compression_and_encryption() {
+    use embeddenator::remote_sync::{self, SyncOptions};
+    use embeddenator::{
+        BinaryWriteOptions, CompressionCodec, EmbrFS, EncryptionCodec, EncryptionKey, EnvelopeHeader, Keyring,
+        DEFAULT_CHUNK_SIZE,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote) = (dir.path().join("local"), dir.path().join("remote"));
+    for d in [&local, &remote] {
+        std::fs::create_dir(d).unwrap();
+    }
+    let config = ReversibleVSAConfig::default();
+
+    let mut theirs = EmbrFS::new();
+    theirs.ingest_bytes(&[3u8; 7000], "a.bin".into(), &config).unwrap();
+    theirs.ingest_bytes(&[9u8; 5000], "b.bin".into(), &config).unwrap();
+    theirs.save_engram(remote.join("root.engram")).unwrap();
+    theirs.save_manifest(remote.join("manifest.json")).unwrap();
+
+    // The pull target starts as an older, zstd-compressed and encrypted copy.
+    let key = EncryptionKey::new(1, [5u8; 32]);
+    let keys: Keyring = [key].into_iter().collect();
+    let opts = BinaryWriteOptions {
+        codec: CompressionCodec::Zstd,
+        encryption: EncryptionCodec::XChaCha20Poly1305,
+        key: Some(key),
+        ..Default::default()
+    };
+    let (engram, manifest) = (local.join("root.engram"), local.join("manifest.json"));
+    let mut ours = EmbrFS::new();
+    ours.ingest_bytes(&[3u8; 7000], "a.bin".into(), &config).unwrap();
+    ours.save_engram_with_options(&engram, opts).unwrap();
+    ours.save_manifest_with_options(&manifest, opts).unwrap();
+
+    let report = session(&remote, Keyring::default(), |r, w| {
+        remote_sync::pull(r, w, &engram, &manifest, &keys, &SyncOptions::default())
+    });
+    assert!(!report.up_to_date);
+
+    for path in [&engram, &manifest] {
+        let header = EnvelopeHeader::parse(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(header.compression, CompressionCodec::Zstd, "{}", path.display());
+        assert!(header.encrypted, "{}", path.display());
+    }
+    assert!(EmbrFS::load_engram(&engram).is_err());
+    let pulled = EmbrFS::load_engram_with_keys(&engram, &keys).unwrap();
+    let pulled_manifest = EmbrFS::load_manifest_with_keys(&manifest, &keys).unwrap();
+    let file = pulled_manifest.files.iter().find(|f| f.path == "b.bin").unwrap();
+    assert_eq!(EmbrFS::reconstruct_bytes(&pulled, file, &config, DEFAULT_CHUNK_SIZE).unwrap(), vec![9u8; 5000]);
+}
@@ -0,0 +1,71 @@
+//! Tests for Merkle-tree replica sync and repair.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn replica_merkle_diff_sync_and_repair() {
+    use embeddenator::replica::{self, MerkleTree, RepairedChunk};
+    use embeddenator::EmbrFS;
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    for i in 0..4u8 {
+        let mut data: Vec<u8> = (0..9000u32).map(|j| (j as u8).wrapping_mul(i + 3) ^ i).collect();
+        data[100..112].copy_from_slice(format!("<<marker-{}>>", i).as_bytes());
+        embrfs.ingest_bytes(&data, format!("f{}.bin", i), &config).unwrap();
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("root.engram");
+    embrfs.save_engram(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let load = || EmbrFS::load_engram(&path).unwrap();
+    // A copy with one byte of f1's first chunk flipped where it is stored.
+    let flipped = |n: usize, bit: u8| {
+        let mut bytes = bytes.clone();
+        let at = bytes.windows(12).position(|w| w == b"<<marker-1>>").unwrap();
+        bytes[at + n] ^= bit;
+        let path = dir.path().join(format!("flipped-{}-{}", n, bit));
+        std::fs::write(&path, bytes).unwrap();
+        EmbrFS::load_engram(&path).unwrap()
+    };
+    let manifest = &embrfs.manifest;
+
+    let tree = MerkleTree::build(&embrfs.engram).unwrap();
+    assert_eq!(tree.len(), embrfs.engram.codebook.len());
+    assert_eq!(tree.root(), MerkleTree::build(&load()).unwrap().root());
+    assert!(tree.diff(&MerkleTree::build(&load()).unwrap()).chunks.is_empty());
+
+    // Damage a chunk of f1 and drop one of f2.
+    let f1 = manifest.files[1].chunks[0];
+    let f2 = manifest.files[2].chunks[1];
+    let mut local = flipped(2, 1);
+    local.codebook.remove(&f2);
+    let diff = tree.diff(&MerkleTree::build(&local).unwrap());
+    assert_eq!(diff.chunks, vec![f1 as u64, f2 as u64]);
+    assert!(diff.nodes_compared < 4 * 64, "{}", diff.nodes_compared);
+
+    // The first peer is damaged at the same chunk of f1, the second isn't.
+    let mut bad_peer = flipped(5, 2);
+    let good_peer = load();
+    let report = replica::repair(&mut local, manifest, &[&bad_peer, &good_peer], &config).unwrap();
+    assert!(report.is_ok(), "{report:?}");
+    assert_eq!(report.files_checked, 4);
+    assert_eq!(report.damaged, vec!["f1.bin".to_string(), "f2.bin".to_string()]);
+    assert_eq!(
+        report.repaired,
+        vec![RepairedChunk { chunk_id: f1, peer: 1 }, RepairedChunk { chunk_id: f2, peer: 0 }]
+    );
+    assert!(EmbrFS::verify(&local, manifest, &config).unwrap().is_ok());
+    assert_eq!(MerkleTree::build(&local).unwrap().root(), tree.root());
+
+    // With only the bad peer, f1 stays damaged.
+    let mut local = flipped(2, 1);
+    let report = replica::repair(&mut local, manifest, &[&bad_peer], &config).unwrap();
+    assert_eq!(report.unrepaired, vec!["f1.bin".to_string()]);
+    assert!(report.repaired.is_empty());
+
+    // Sync copies only what differs and ends identical.
+    let sync = replica::sync_from(&mut bad_peer, &good_peer).unwrap();
+    assert_eq!((sync.copied, sync.removed), (1, 0));
+    assert_eq!(MerkleTree::build(&bad_peer).unwrap().root(), tree.root());
+}
@@ -0,0 +1,51 @@
+//! Tests for reproducible ingest.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn reproducible_ingest_is_byte_identical_across_orders() {
+    use embeddenator::{EmbrFS, IngestClock, DEFAULT_CHUNK_SIZE};
+
+    let config = ReversibleVSAConfig::default();
+    let files: Vec<(String, Vec<u8>)> = vec![
+        ("src/main.rs".into(), b"fn main() { println!(\"hi\"); }\n".repeat(300)),
+        ("README.md".into(), b"# demo\n".to_vec()),
+        ("assets/blob.bin".into(), (0..9000u32).map(|i| (i * 31 % 251) as u8).collect()),
+    ];
+    let build = |order: &[usize]| {
+        let mut fs = EmbrFS::reproducible(IngestClock::Fixed(1_700_000_000));
+        for &i in order {
+            fs.ingest_bytes(&files[i].1, files[i].0.clone(), &config).unwrap();
+        }
+        fs.canonicalize();
+        let engram = bincode::serialize(&fs.engram).unwrap();
+        let manifest = serde_json::to_vec(&fs.manifest).unwrap();
+        (fs, engram, manifest)
+    };
+
+    let (fs, engram, manifest) = build(&[0, 1, 2]);
+    let (_, engram_2, manifest_2) = build(&[2, 0, 1]);
+    assert_eq!(engram, engram_2);
+    assert_eq!(manifest, manifest_2);
+
+    // Files are in path order and chunks numbered densely in that order.
+    let paths: Vec<&str> = fs.manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["README.md", "assets/blob.bin", "src/main.rs"]);
+    assert_eq!(fs.manifest.files[0].chunks, [0]);
+    assert_eq!(fs.manifest.files[1].chunks, [1, 2, 3]);
+    assert_eq!(fs.manifest.total_chunks, fs.engram.codebook.len());
+    assert!(fs.manifest.files.iter().all(|f| f.ingested_at == Some(1_700_000_000)));
+    for file in &fs.manifest.files {
+        let data = &files.iter().find(|(p, _)| *p == file.path).unwrap().1;
+        assert_eq!(&EmbrFS::reconstruct_bytes(&fs.engram, file, &config, DEFAULT_CHUNK_SIZE).unwrap(), data);
+    }
+
+    // Canonical form is a fixed point.
+    let mut again = fs;
+    again.canonicalize();
+    assert_eq!(bincode::serialize(&again.engram).unwrap(), engram);
+
+    let mut omitted = EmbrFS::reproducible(IngestClock::Omit);
+    omitted.ingest_bytes(b"x", "x".into(), &config).unwrap();
+    assert_eq!(omitted.manifest.files[0].ingested_at, None);
+}
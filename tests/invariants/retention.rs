@@ -0,0 +1,89 @@
+//! Tests for retention policies.
+
+#[test]
+fn retention_policy_purges_expired_files_and_their_chunks() {
+    use embeddenator::retention::parse_ttl;
+    use embeddenator::{EmbrFS, ReversibleVSAConfig, RetentionAudit, RetentionPolicy, DEFAULT_CHUNK_SIZE};
+
+    let policy = RetentionPolicy::from_toml(
+        r#"
+        [classes]
+        short = { ttl = "1h" }
+        hold = {}
+
+        [[rule]]
+        path = "legal/**"
+        class = "hold"
+
+        [[rule]]
+        path = "scratch/**"
+        ttl = 60
+
+        [[rule]]
+        path = "*.log"
+        class = "short"
+        "#,
+    )
+    .unwrap();
+    assert!(RetentionPolicy::from_toml("[[rule]]\npath = \"x\"\nclass = \"nope\"").is_err());
+    assert!(RetentionPolicy::from_toml("[[rule]]\npath = \"x\"").is_err());
+    assert_eq!(parse_ttl("2w"), Ok(1_209_600));
+    assert!(parse_ttl("3y").is_err());
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.manifest.retention = Some(policy);
+    let shared = vec![9u8; 5000];
+    fs.ingest_bytes(&shared, "scratch/a.bin".into(), &config).unwrap();
+    fs.ingest_bytes(&[1u8; 5000], "scratch/b.bin".into(), &config).unwrap();
+    fs.ingest_bytes(b"audit trail", "legal/old.log".into(), &config).unwrap();
+    fs.ingest_bytes(b"service log", "app.log".into(), &config).unwrap();
+    fs.ingest_bytes(b"fn main() {}", "main.rs".into(), &config).unwrap();
+    // An undated copy of scratch/a.bin (as from an older manifest) never
+    // expires, and keeps the chunks it shares alive.
+    let legacy = embeddenator::FileEntry {
+        path: "scratch/legacy.bin".into(),
+        ingested_at: None,
+        ..fs.manifest.files[0].clone()
+    };
+    fs.manifest.files.push(legacy);
+    let t0 = fs.manifest.files[0].ingested_at.unwrap();
+    for f in &mut fs.manifest.files {
+        f.ingested_at = f.ingested_at.map(|_| t0);
+    }
+    let chunks_before = fs.engram.codebook.len();
+
+    let preview = fs.retention_preview(t0 + 60).unwrap();
+    assert!(!preview.applied);
+    let paths = |a: &RetentionAudit| a.purged.iter().map(|p| p.path.clone()).collect::<Vec<_>>();
+    assert_eq!(paths(&preview), ["scratch/a.bin", "scratch/b.bin"]);
+    assert_eq!(fs.manifest.files.len(), 6, "previews change nothing");
+
+    // Only scratch/b.bin's chunks go.
+    let audit = fs.apply_retention(t0 + 60).unwrap();
+    assert!(audit.applied);
+    assert_eq!(paths(&audit), paths(&preview));
+    assert_eq!((audit.files_kept, audit.chunks_removed, audit.bytes_purged), (4, 2, 10_000));
+    assert_eq!(audit.purged[0].rule, "scratch/**");
+    assert_eq!(fs.engram.codebook.len(), chunks_before - 2);
+    let legacy = fs.manifest.files.iter().find(|f| f.path == "scratch/legacy.bin").unwrap();
+    assert_eq!(EmbrFS::reconstruct_bytes(&fs.engram, legacy, &config, DEFAULT_CHUNK_SIZE).unwrap(), shared);
+
+    // The log class expires an hour in; the legal hold never does.
+    let later = fs.apply_retention(t0 + 3600).unwrap();
+    assert_eq!(paths(&later), ["app.log"]);
+    let left: Vec<&str> = fs.manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(left, ["legal/old.log", "main.rs", "scratch/legacy.bin"]);
+
+    // The audit log is one JSON record per line.
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("audit.jsonl");
+    audit.append_to(&log).unwrap();
+    later.append_to(&log).unwrap();
+    let records: Vec<RetentionAudit> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(records, [audit, later]);
+}
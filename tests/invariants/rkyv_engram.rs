@@ -0,0 +1,64 @@
+//! Tests for rkyv-archived engram payloads.
+
+use crate::support::ingested;
+
+#[cfg(not(feature = "rkyv"))]
+#[test]
+fn rkyv_reports_missing_feature() {
+    let td = tempfile::tempdir().unwrap();
+    let err = ingested(td.path())
+        .save_engram_rkyv(td.path().join("root.engram"))
+        .unwrap_err();
+    assert!(err.to_string().contains("not enabled"), "unexpected error: {err}");
+}
+
+#[cfg(feature = "rkyv")]
+mod enabled {
+    use super::*;
+    use embeddenator::{EmbrFS, ReversibleVSAConfig};
+    use std::fs;
+    use embeddenator::RkyvEngram;
+
+    #[test]
+    fn archive_reads_in_place_and_loads_transparently() {
+        let td = tempfile::tempdir().unwrap();
+        let fsys = ingested(td.path());
+        let path = td.path().join("root.engram");
+        fsys.save_engram_rkyv(&path).unwrap();
+
+        let archive = RkyvEngram::open(&path).unwrap();
+        assert_eq!(archive.chunk_count(), fsys.engram.codebook.len());
+        let ids: Vec<usize> = archive.chunk_ids().collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        for id in ids {
+            let expected = &fsys.engram.codebook[&id];
+            let got = archive.chunk(id).unwrap();
+            assert_eq!((got.pos, got.neg), (expected.pos.clone(), expected.neg.clone()));
+        }
+        assert!(archive.chunk(usize::MAX).is_none());
+        assert_eq!(archive.root().pos, fsys.engram.root.pos);
+
+        let engram = EmbrFS::load_engram(&path).unwrap();
+        let out = td.path().join("out");
+        EmbrFS::extract(&engram, &fsys.manifest, &out, false, &ReversibleVSAConfig::default()).unwrap();
+        for name in ["docs/a.txt", "b.bin"] {
+            assert_eq!(fs::read(out.join(name)).unwrap(), fs::read(td.path().join("in").join(name)).unwrap());
+        }
+    }
+
+    #[test]
+    fn damaged_archives_fail_validation() {
+        let td = tempfile::tempdir().unwrap();
+        let fsys = ingested(td.path());
+        let path = td.path().join("root.engram");
+        fsys.save_engram_rkyv(&path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+        let err = RkyvEngram::open(&path).err().expect("truncated archive");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        fsys.save_engram(&path).unwrap();
+        assert!(RkyvEngram::open(&path).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Tests for role schemas: files bound to values under named roles.
+
+use embeddenator::role_schema::{RoleSchema, RoleValue};
+use embeddenator::EmbrFS;
+use std::io;
+
+fn authored_fs() -> EmbrFS {
+    let mut fsys = EmbrFS::new();
+    let config = fsys.vsa_config();
+    for (path, data) in [
+        ("docs/guide.md", b"how to use it".as_slice()),
+        ("docs/faq.md", b"questions"),
+        ("src/main.rs", b"fn main() {}"),
+    ] {
+        fsys.ingest_bytes(data, path.to_string(), &config).unwrap();
+    }
+    fsys.role_schema(11);
+    fsys.bind_role("docs/guide.md", "author", RoleValue::Text("ada".into())).unwrap();
+    fsys.bind_role("docs/guide.md", "licence", RoleValue::Text("mit".into())).unwrap();
+    fsys.bind_role("docs/faq.md", "author", RoleValue::Text("grace".into())).unwrap();
+    fsys.bind_role("src/main.rs", "author", RoleValue::Text("ada".into())).unwrap();
+    fsys.bind_role("src/", "licence", RoleValue::Text("apache".into())).unwrap();
+    fsys
+}
+
+#[test]
+fn query_finds_paths_by_role_value() {
+    let fsys = authored_fs();
+    let schema = fsys.engram.schema.as_ref().unwrap();
+    assert_eq!(schema.roles().collect::<Vec<_>>(), ["author", "licence"]);
+
+    let paths = |hits: Vec<embeddenator::RoleHit>| hits.into_iter().map(|h| h.path).collect::<Vec<_>>();
+    let mut ada = paths(schema.query_text("author", "ada", 10));
+    ada.sort();
+    assert_eq!(ada, ["docs/guide.md", "src/main.rs"]);
+    assert_eq!(paths(schema.query_text("author", "grace", 10)), ["docs/faq.md"]);
+    assert_eq!(paths(schema.query_text("licence", "apache", 10)), ["src"]);
+    assert!(schema.query_text("author", "linus", 10).is_empty());
+    assert!(schema.query_text("editor", "ada", 10).is_empty());
+}
+
+#[test]
+fn schema_survives_save_and_load() {
+    let td = tempfile::tempdir().unwrap();
+    let mut fsys = authored_fs();
+    fsys.bind_metadata_roles().unwrap();
+    let path = td.path().join("root.engram");
+    fsys.save_engram(&path).unwrap();
+
+    let loaded = EmbrFS::load_engram(&path).unwrap();
+    let schema = loaded.schema.as_ref().unwrap();
+    assert_eq!(schema, fsys.engram.schema.as_ref().unwrap());
+    // Vectors are derived from the labels and seed, so they agree across loads.
+    assert_eq!(schema.role("author"), RoleSchema::new(11).define("author").into());
+    assert_eq!(schema.query_text("author", "grace", 1)[0].path, "docs/faq.md");
+    let mut text: Vec<String> = schema.query_text("kind", "text", 10).into_iter().map(|h| h.path).collect();
+    text.sort();
+    let mut expected: Vec<&str> = fsys.manifest.files.iter().filter(|f| f.is_text).map(|f| f.path.as_str()).collect();
+    expected.sort();
+    assert!(!expected.is_empty());
+    assert_eq!(text, expected);
+}
+
+#[test]
+fn bindings_follow_the_manifest() {
+    let mut fsys = authored_fs();
+    let err = fsys.bind_role("missing.txt", "author", RoleValue::Text("ada".into())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    assert!(fsys.remove_file("docs/faq.md"));
+    let schema = fsys.engram.schema.as_ref().unwrap();
+    assert!(schema.bindings("docs/faq.md").is_empty());
+    assert!(schema.query_text("author", "grace", 10).is_empty());
+
+    let mut bare = EmbrFS::new();
+    let config = bare.vsa_config();
+    bare.ingest_bytes(b"x", "x.txt".to_string(), &config).unwrap();
+    let err = bare.bind_role("x.txt", "author", RoleValue::Text("ada".into())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn merge_unions_schemas_with_one_seed() {
+    let mut a = RoleSchema::new(3);
+    a.bind("a.txt", "author", RoleValue::Text("ada".into()));
+    let mut b = RoleSchema::new(3);
+    b.bind("b.txt", "author", RoleValue::Text("ada".into()));
+    b.define("licence");
+
+    let merged = RoleSchema::merge(&a, &b);
+    assert_eq!(merged, RoleSchema::merge(&b, &a));
+    assert_eq!(merged.query_text("author", "ada", 10).len(), 2);
+    assert_eq!(merged.roles().count(), 2);
+
+    let other = RoleSchema::new(9);
+    assert_eq!(RoleSchema::merge(&other, &a), a);
+}
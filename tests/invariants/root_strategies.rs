@@ -0,0 +1,70 @@
+//! Tests for root bundling strategies.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn root_strategies_build_record_and_rebuild() {
+    use embeddenator::dimensional::DimensionalConfig;
+    use embeddenator::{EmbrFS, Keyring, RootStrategy, TreeBundle};
+
+    let files: Vec<Vec<u8>> = (0..3u32)
+        .map(|f| (0..9000u32).map(|i| (i.wrapping_mul(2_654_435_761 + f) >> 11) as u8).collect())
+        .collect();
+    let strategies = [
+        RootStrategy::Pairwise,
+        RootStrategy::CarrySave,
+        RootStrategy::Tree { fan_in: 2 },
+        RootStrategy::Thinned { max_nonzero: 300 },
+    ];
+    for strategy in strategies {
+        let config = ReversibleVSAConfig { root_strategy: strategy, ..Default::default() };
+        let mut fs = EmbrFS::with_config(config.clone(), DimensionalConfig::default()).unwrap();
+        for (i, data) in files.iter().enumerate() {
+            fs.ingest_bytes(data, format!("f{i}.bin"), &config).unwrap();
+        }
+        let mut ids: Vec<usize> = fs.engram.codebook.keys().copied().collect();
+        ids.sort_unstable();
+        match strategy {
+            RootStrategy::CarrySave => assert!(fs.engram.root_counts.is_some()),
+            RootStrategy::Tree { fan_in } => {
+                let mut tree = TreeBundle::new(fan_in);
+                ids.iter().for_each(|id| tree.push(&fs.engram.codebook[id]));
+                assert_eq!(tree.len(), ids.len());
+                assert_eq!(fs.engram.root, tree.root());
+            }
+            RootStrategy::Thinned { max_nonzero } => {
+                assert!(fs.engram.root.pos.len() + fs.engram.root.neg.len() <= max_nonzero)
+            }
+            RootStrategy::Pairwise => assert!(fs.engram.root_counts.is_none()),
+        }
+
+        // Ingest and a rebuild from the codebook agree, also after removal.
+        let ingested = fs.engram.root.clone();
+        fs.rebuild_root();
+        assert_eq!(fs.engram.root, ingested, "{strategy:?}");
+        assert!(fs.remove_file("f1.bin"));
+        let removed = fs.engram.root.clone();
+        fs.rebuild_root();
+        assert_eq!(fs.engram.root, removed, "{strategy:?}");
+        fs.ingest_bytes(&files[1], "again.bin".into(), &config).unwrap();
+        let appended = fs.engram.root.clone();
+        fs.rebuild_root();
+        assert_eq!(fs.engram.root, appended, "{strategy:?}");
+
+        // The strategy is part of the recorded encoding.
+        let json = serde_json::to_vec(&fs.manifest).unwrap();
+        let manifest = EmbrFS::manifest_from_bytes(&json, &Keyring::new()).unwrap();
+        assert_eq!(manifest.encoding().vsa.root_strategy, strategy);
+        #[cfg(feature = "proto")]
+        {
+            let bytes = embeddenator::wire::encode_manifest(&fs.manifest).unwrap();
+            let decoded = embeddenator::wire::decode_manifest(&bytes).unwrap();
+            assert_eq!(decoded.encoding().vsa.root_strategy, strategy);
+        }
+    }
+
+    for bad in [RootStrategy::Tree { fan_in: 1 }, RootStrategy::Thinned { max_nonzero: 0 }] {
+        let config = ReversibleVSAConfig { root_strategy: bad, ..Default::default() };
+        assert!(EmbrFS::with_config(config, DimensionalConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Extraction keeps manifest paths inside the output directory.
+
+use embeddenator::safe_path::relative_path;
+use embeddenator::{EmbrError, EmbrFS, PathPolicy, PathProblem, ReversibleVSAConfig};
+use std::path::PathBuf;
+
+#[test]
+fn manifest_paths_resolve_to_plain_names() {
+    assert_eq!(relative_path("docs/./a.txt").unwrap(), PathBuf::from("docs").join("a.txt"));
+    assert_eq!(relative_path("docs//a.txt/").unwrap(), PathBuf::from("docs").join("a.txt"));
+    assert_eq!(relative_path("..hidden/a..b").unwrap(), PathBuf::from("..hidden").join("a..b"));
+
+    for (path, problem) in [
+        ("../etc/passwd", PathProblem::ParentDir),
+        ("docs/../../x", PathProblem::ParentDir),
+        ("/etc/passwd", PathProblem::Absolute),
+        ("", PathProblem::Empty),
+        ("./", PathProblem::Empty),
+        ("a\0b", PathProblem::Nul),
+    ] {
+        assert_eq!(relative_path(path).unwrap_err().problem, problem, "{path:?}");
+    }
+    #[cfg(windows)]
+    {
+        assert_eq!(relative_path("C:/x").unwrap_err().problem, PathProblem::Absolute);
+        assert_eq!(relative_path("a\\..\\..\\x").unwrap_err().problem, PathProblem::Separator);
+    }
+}
+
+#[test]
+fn extract_refuses_paths_outside_the_output_dir() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"fine", "ok.txt".into(), &config).unwrap();
+    fs.ingest_bytes(b"escaped", "escaped.txt".into(), &config).unwrap();
+    fs.manifest.files[1].path = "../escaped.txt".into();
+
+    let td = tempfile::tempdir().unwrap();
+    let out = td.path().join("out");
+    let err = EmbrFS::extract(&fs.engram, &fs.manifest, &out, false, &config).unwrap_err();
+    assert!(
+        matches!(err, EmbrError::UnsafePath(ref p) if p.path == "../escaped.txt" && p.problem == PathProblem::ParentDir),
+        "{err}"
+    );
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    // Nothing is written, not even the safe file listed first.
+    assert!(!out.join("ok.txt").exists());
+    assert!(!td.path().join("escaped.txt").exists());
+
+    // The escape hatch writes where the path leads.
+    EmbrFS::extract_with_path_policy(
+        &fs.engram,
+        &fs.manifest,
+        None,
+        &out,
+        false,
+        &config,
+        None,
+        PathPolicy::AllowUnsafe,
+    )
+    .unwrap();
+    assert_eq!(std::fs::read(td.path().join("escaped.txt")).unwrap(), b"escaped");
+    assert_eq!(std::fs::read(out.join("ok.txt")).unwrap(), b"fine");
+}
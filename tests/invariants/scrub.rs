@@ -0,0 +1,58 @@
+//! Tests for scrubbing engrams for corruption.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn scrub_repairs_from_twins_and_reports_the_rest() {
+    use embeddenator::scrub::{self, RepairSource, ScrubOptions, ScrubbedChunk};
+    use embeddenator::EmbrFS;
+
+    let config = ReversibleVSAConfig::default();
+    let data = |i: u8| -> Vec<u8> {
+        let mut data: Vec<u8> = (0..9000u32).map(|j| (j as u8).wrapping_mul(i + 3) ^ i).collect();
+        data[100..112].copy_from_slice(format!("<<marker-{}>>", i).as_bytes());
+        data
+    };
+    // b.bin holds the same bytes as a.bin under its own chunk ids.
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_bytes(&data(0), "a.bin".into(), &config).unwrap();
+    embrfs.ingest_bytes(&data(0), "b.bin".into(), &config).unwrap();
+    embrfs.ingest_bytes(&data(1), "c.bin".into(), &config).unwrap();
+    let manifest = &embrfs.manifest;
+    let (a, b, c) = (manifest.files[0].chunks[0], manifest.files[1].chunks[0], manifest.files[2].chunks[0]);
+
+    let clean = scrub::scrub(&mut embrfs.engram, manifest, &config, &ScrubOptions::default()).unwrap();
+    assert!(clean.is_ok() && clean.damaged.is_empty(), "{clean:?}");
+    assert_eq!(clean.chunks_checked + clean.chunks_unchecked, embrfs.engram.codebook.len());
+
+    // Flip a stored byte of the first chunk of b and of c. Both are held
+    // verbatim in their correction records, b's after a's.
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("root.engram");
+    embrfs.save_engram(&path).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    for marker in [b"<<marker-0>>", b"<<marker-1>>"] {
+        let at = bytes.windows(12).rposition(|w| w == marker).unwrap();
+        bytes[at + 2] ^= 1;
+    }
+    std::fs::write(&path, bytes).unwrap();
+    let mut local = EmbrFS::load_engram(&path).unwrap();
+
+    let expected = vec![
+        ScrubbedChunk { chunk_id: b, path: "b.bin".into(), source: Some(RepairSource::Twin { chunk_id: a }) },
+        ScrubbedChunk { chunk_id: c, path: "c.bin".into(), source: None },
+    ];
+    let dry = scrub::scrub(&mut local, manifest, &config, &ScrubOptions { rate: None, repair: false }).unwrap();
+    assert_eq!(dry.damaged, expected);
+    assert!(!dry.is_ok());
+    assert_eq!(EmbrFS::verify(&local, manifest, &config).unwrap().mismatches.len(), 2);
+
+    let opts = ScrubOptions { rate: Some(1e6), repair: true };
+    let report = scrub::scrub(&mut local, manifest, &config, &opts).unwrap();
+    assert_eq!(report.damaged, expected);
+    assert_eq!((report.repairable(), report.irreparable()), (1, 1));
+    let again = scrub::scrub(&mut local, manifest, &config, &opts).unwrap();
+    assert_eq!(again.damaged, expected[1..]);
+    let verify = EmbrFS::verify(&local, manifest, &config).unwrap();
+    assert!(verify.mismatches.iter().all(|m| m.path == "c.bin"), "{verify:?}");
+}
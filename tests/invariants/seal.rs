@@ -0,0 +1,56 @@
+//! Tests for sealed, read-only engrams.
+
+#[cfg(feature = "signing")]
+#[test]
+fn sealed_engram_refuses_writes_and_verifies() {
+    use embeddenator::error::EmbrError;
+    use embeddenator::info::StorageFormat;
+    use embeddenator::{seal, signing, EmbrFS, ReversibleVSAConfig};
+
+    let dir = tempfile::tempdir().unwrap();
+    let engram_path = dir.path().join("archive.engram");
+    let manifest_path = dir.path().join("archive.json");
+    let config = ReversibleVSAConfig::default();
+    let data: Vec<u8> = (0..9000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&data, "records/2026.bin".into(), &config).unwrap();
+    fs.save_engram(&engram_path).unwrap();
+    fs.save_manifest(&manifest_path).unwrap();
+
+    let (secret, public) = signing::generate_keypair().unwrap();
+    assert!(!seal::is_sealed(&engram_path).unwrap());
+    let sealed = seal::seal_files(&engram_path, &manifest_path, &secret).unwrap();
+    assert!(seal::is_sealed(&engram_path).unwrap() && seal::is_sealed(&manifest_path).unwrap());
+    assert_eq!(seal::read_seal(&manifest_path).unwrap(), Some(sealed.clone()));
+    assert_eq!(seal::verify_seal(&engram_path, &manifest_path, Some(&public)).unwrap(), sealed);
+    assert!(StorageFormat::detect(&engram_path).unwrap().sealed);
+
+    // Sealed files load unchanged.
+    let engram = EmbrFS::load_engram(&engram_path).unwrap();
+    let manifest = EmbrFS::load_manifest(&manifest_path).unwrap();
+    assert!(EmbrFS::verify(&engram, &manifest, &config).unwrap().is_ok());
+
+    // Every way of replacing them is refused.
+    let immutable = |err: EmbrError| matches!(err, EmbrError::Immutable(_));
+    assert!(immutable(fs.save_engram(&engram_path).unwrap_err()));
+    assert!(immutable(fs.save_manifest(&manifest_path).unwrap_err()));
+    assert!(immutable(
+        fs.save_transactional(&engram_path, &manifest_path, Default::default(), Default::default())
+            .unwrap_err()
+    ));
+    let err = seal::seal_files(&engram_path, &manifest_path, &secret).unwrap_err();
+    assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::ReadOnlyFilesystem);
+    assert!(seal::verify_seal(&engram_path, &manifest_path, None).is_ok());
+
+    // An untrusted key or any change to the sealed bytes fails verification.
+    let (_, other) = signing::generate_keypair().unwrap();
+    assert!(seal::verify_seal(&engram_path, &manifest_path, Some(&other)).is_err());
+    let mut tampered = std::fs::read(&manifest_path).unwrap();
+    let last = tampered.len() - 2;
+    tampered[last] ^= 1;
+    std::fs::write(&manifest_path, &tampered).unwrap();
+    assert!(matches!(
+        seal::verify_seal(&engram_path, &manifest_path, None),
+        Err(EmbrError::Crypto(_))
+    ));
+}
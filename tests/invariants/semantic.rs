@@ -0,0 +1,141 @@
+//! Tests for projected semantic signatures and hybrid retrieval.
+
+use embeddenator::envelope::{BinaryWriteOptions, ChecksumCodec, EnvelopeWriter, PayloadKind};
+use embeddenator::projection::{ProjectionConfig, ProjectionEncoder};
+use embeddenator::semantic::{hybrid_query, ChunkEmbedder, SemanticEncoder, SemanticSignatures};
+use embeddenator::{EmbrFS, ReversibleVSAConfig, DIM};
+use std::io;
+
+/// Normalized byte histogram: chunks with similar byte mixes embed close together.
+struct Histogram;
+
+impl ChunkEmbedder for Histogram {
+    fn model_id(&self) -> String {
+        "histogram".into()
+    }
+
+    fn embed(&self, data: &[u8]) -> io::Result<Vec<f32>> {
+        let mut h = vec![0f32; 256];
+        for &b in data {
+            h[b as usize] += 1.0;
+        }
+        Ok(h)
+    }
+}
+
+fn encoder() -> SemanticEncoder {
+    SemanticEncoder::new(Box::new(Histogram), ProjectionConfig::default())
+}
+
+#[test]
+fn projection_is_deterministic_sparse_and_angle_preserving() {
+    let encoder = ProjectionEncoder::default();
+    let a: Vec<f32> = (0..384).map(|i| ((i * 37 % 101) as f32 - 50.0) / 50.0).collect();
+    let near: Vec<f32> = a.iter().enumerate().map(|(i, x)| x + if i % 7 == 0 { 0.1 } else { 0.0 }).collect();
+    let far: Vec<f32> = (0..384).map(|i| ((i * 91 % 89) as f32 - 44.0) / 44.0).collect();
+
+    let pa = encoder.encode(&a);
+    assert_eq!(pa.pos, encoder.encode(&a).pos);
+    assert_eq!(pa.neg, encoder.encode(&a).neg);
+    assert_eq!(pa.pos.len() + pa.neg.len(), ProjectionConfig::default().nnz);
+    assert!(pa.pos.iter().chain(&pa.neg).all(|&d| d < DIM));
+    assert!(pa.pos.windows(2).all(|w| w[0] < w[1]));
+
+    let scaled: Vec<f32> = a.iter().map(|x| x * 2.0).collect();
+    assert_eq!(encoder.encode(&scaled).pos, pa.pos);
+    assert!(pa.cosine(&encoder.encode(&near)) > pa.cosine(&encoder.encode(&far)) + 0.2);
+
+    assert!(encoder.encode(&[]).pos.is_empty());
+    let silent = encoder.encode(&[0.0; 16]);
+    assert!(silent.pos.is_empty() && silent.neg.is_empty());
+    let reseeded = ProjectionEncoder::new(ProjectionConfig {
+        seed: 7,
+        ..ProjectionConfig::default()
+    });
+    assert_ne!(reseeded.encode(&a).pos, pa.pos);
+}
+
+#[test]
+fn ingest_records_signatures_and_hybrid_query_blends() {
+    let config = ReversibleVSAConfig::default();
+    let mut fsys = EmbrFS::new();
+    fsys.set_semantic_encoder(encoder());
+    let prose = b"the quick brown fox jumps over the lazy dog. ".repeat(100);
+    let zeros = vec![0u8; 5000];
+    fsys.ingest_bytes(&prose, "prose.txt".into(), &config).unwrap();
+    fsys.ingest_bytes(&zeros, "zeros.bin".into(), &config).unwrap();
+
+    let signatures = fsys.semantic.as_ref().unwrap();
+    assert_eq!(signatures.model, "histogram");
+    let mut ids: Vec<usize> = signatures.vectors.keys().copied().collect();
+    ids.sort_unstable();
+    let mut chunk_ids: Vec<usize> = fsys.engram.codebook.keys().copied().collect();
+    chunk_ids.sort_unstable();
+    assert_eq!(ids, chunk_ids);
+
+    // Same letters, different order: no exact overlap, but a close histogram.
+    let query_text = b"lazy dogs jump over the quick brown fox";
+    let semantic_query = encoder().encode(query_text).unwrap();
+    let exact_query = embeddenator::SparseVec::encode_data(query_text, &config, None);
+    let prose_chunks = &fsys.manifest.files[0].chunks;
+
+    let semantic_only = hybrid_query(&fsys.engram, signatures, None, Some(&semantic_query), 3, 1.0);
+    assert!(prose_chunks.contains(&semantic_only[0].id));
+    assert_eq!(semantic_only[0].exact, 0.0);
+    assert!(semantic_only[0].semantic > 0.5);
+    assert_eq!(semantic_only[0].score, semantic_only[0].semantic);
+
+    let blended = hybrid_query(&fsys.engram, signatures, Some(&exact_query), Some(&semantic_query), 5, 0.5);
+    assert!(!blended.is_empty() && blended.len() <= 5);
+    assert!(blended.windows(2).all(|w| w[0].score >= w[1].score));
+    for hit in &blended {
+        assert!((hit.score - 0.5 * (hit.exact + hit.semantic)).abs() < 1e-12);
+    }
+    assert!(hybrid_query(&fsys.engram, signatures, Some(&exact_query), None, 0, 0.5).is_empty());
+}
+
+#[test]
+fn signatures_round_trip_and_reset_on_model_change() {
+    let td = tempfile::tempdir().unwrap();
+    let mut fsys = EmbrFS::new();
+    fsys.set_semantic_encoder(encoder());
+    fsys.ingest_bytes(b"hello semantic world", "a.txt".into(), &ReversibleVSAConfig::default())
+        .unwrap();
+
+    let path = embeddenator::semantic::default_signatures_path(td.path().join("root.engram"));
+    assert!(path.ends_with("root.engram.semantic"));
+    let saved = fsys.semantic.clone().unwrap();
+    saved.save(&path, BinaryWriteOptions::default()).unwrap();
+    let loaded = SemanticSignatures::load(&path).unwrap();
+    assert_eq!(loaded.model, saved.model);
+    assert_eq!(loaded.projection, saved.projection);
+    assert_eq!(loaded.vectors.len(), 1);
+    assert!(encoder().matches(&loaded));
+    assert!(SemanticSignatures::load(td.path()).is_err());
+    assert_eq!(loaded.vectors, saved.vectors);
+
+    // Files written before the vectors were coded still load.
+    let legacy = td.path().join("legacy.semantic");
+    let mut writer = EnvelopeWriter::new(
+        std::fs::File::create(&legacy).unwrap(),
+        PayloadKind::SemanticSignatures,
+        BinaryWriteOptions {
+            checksum: ChecksumCodec::Xxh3,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    bincode::serialize_into(&mut writer, &saved).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(SemanticSignatures::load(&legacy).unwrap().vectors, saved.vectors);
+
+    // Same model keeps what was collected; a different projection starts over.
+    fsys.set_semantic_encoder(encoder());
+    assert_eq!(fsys.semantic.as_ref().unwrap().vectors.len(), 1);
+    let other = ProjectionConfig {
+        nnz: 64,
+        ..ProjectionConfig::default()
+    };
+    fsys.set_semantic_encoder(SemanticEncoder::new(Box::new(Histogram), other));
+    assert!(fsys.semantic.as_ref().unwrap().vectors.is_empty());
+}
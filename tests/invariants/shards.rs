@@ -0,0 +1,55 @@
+//! Tests for sharded engrams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn sharded_engram_loads_shards_on_demand() {
+    use embeddenator::{BinaryWriteOptions, EmbrFS, Keyring, ShardedEngram, StorageFormat, DEFAULT_CHUNK_SIZE};
+    use std::fs;
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    for i in 0..4u8 {
+        fs::write(src.join(format!("f{i}.bin")), vec![i.wrapping_mul(37); 9000]).unwrap();
+    }
+    fs::write(src.join("a.txt"), "sharded hello\n").unwrap();
+
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &config).unwrap();
+    let path = tmp.path().join("root.engram");
+    let index = embrfs.save_engram_shards(&path, 4, BinaryWriteOptions::default()).unwrap();
+    assert!(index.shards.len() > 1);
+    assert_eq!(StorageFormat::detect(&path).unwrap().container, "sharded");
+
+    // A full load sees the same engram.
+    let loaded = EmbrFS::load_engram(&path).unwrap();
+    assert_eq!(loaded.codebook.len(), embrfs.engram.codebook.len());
+    assert!(EmbrFS::verify(&loaded, &embrfs.manifest, &config).unwrap().is_ok());
+
+    let sharded = ShardedEngram::open(&path, &Keyring::default()).unwrap().with_max_resident(1);
+    assert_eq!(sharded.resident_shards(), 0);
+    for entry in &embrfs.manifest.files {
+        let mut out = Vec::new();
+        sharded.write_file(entry, &config, DEFAULT_CHUNK_SIZE, &mut out).unwrap();
+        assert_eq!(out, fs::read(src.join(&entry.path)).unwrap());
+        assert_eq!(sharded.resident_shards(), 1);
+    }
+    assert!(sharded.chunk(1_000_000).unwrap().is_none());
+
+    // Fewer shards on resave: the extra files go away.
+    let before = fs::read_dir(tmp.path()).unwrap().count();
+    embrfs.save_engram_shards(&path, 1 << 20, BinaryWriteOptions::default()).unwrap();
+    assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), before - index.shards.len() + 1);
+
+    // A tampered shard is caught by its digest.
+    let shard = tmp.path().join("root.engram.shard0000");
+    let mut data = fs::read(&shard).unwrap();
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    fs::write(&shard, data).unwrap();
+    let err = EmbrFS::load_engram(&path).err().expect("tampered shard");
+    assert!(err.to_string().contains("blake3"), "{err}");
+}
@@ -0,0 +1,74 @@
+//! Tests for codebooks shared between engrams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn shared_codebook_refcounts_and_gc() {
+    use embeddenator::shared_codebook;
+    use embeddenator::{BinaryWriteOptions, EmbrFS, Keyring, SharedCodebook, StorageFormat};
+    use std::fs;
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let tmp = tempdir().unwrap();
+    let keys = Keyring::default();
+    let opts = BinaryWriteOptions::default();
+    let codebook = tmp.path().join("datasets.codebook");
+
+    // Two versions of a dataset sharing one of their two files.
+    let mut versions = Vec::new();
+    for v in 0..2u8 {
+        let src = tmp.path().join(format!("v{v}"));
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("common.bin"), vec![42u8; 12000]).unwrap();
+        fs::write(src.join("own.txt"), format!("version {v} only\n")).unwrap();
+        let mut embrfs = EmbrFS::new();
+        embrfs.ingest_directory(&src, false, &config).unwrap();
+        let engram = tmp.path().join(format!("v{v}.engram"));
+        let report = embrfs.save_engram_shared(&engram, &codebook, opts).unwrap();
+        assert_eq!(report.chunks, embrfs.engram.codebook.len());
+        if v == 1 {
+            assert!(report.records_added < report.chunks);
+        }
+        versions.push((engram, embrfs));
+    }
+    let shared = SharedCodebook::open(&codebook, &keys).unwrap();
+    let separate: usize = versions.iter().map(|(_, fs)| fs.engram.codebook.len()).sum();
+    assert!(shared.len() < separate);
+    assert_eq!(shared.referrers.len(), 2);
+    assert_eq!(StorageFormat::detect(&versions[0].0).unwrap().container, "codebook-ref");
+
+    for (engram, embrfs) in &versions {
+        let loaded = EmbrFS::load_engram(engram).unwrap();
+        assert!(EmbrFS::verify(&loaded, &embrfs.manifest, &config).unwrap().is_ok());
+    }
+
+    // Resaving an engram replaces its references rather than adding more.
+    let (v0, fs0) = &versions[0];
+    fs0.save_engram_shared(v0, &codebook, opts).unwrap();
+    let resaved = SharedCodebook::open(&codebook, &keys).unwrap();
+    assert_eq!(resaved.referrers.len(), 2);
+    assert_eq!(resaved.len(), shared.len());
+    assert_eq!(resaved.unreferenced(), 0);
+
+    // Releasing v0 leaves its own records unreferenced until gc.
+    shared_codebook::release(v0, &keys, opts).unwrap();
+    let released = SharedCodebook::open(&codebook, &keys).unwrap();
+    assert!(released.unreferenced() > 0);
+    let dry = shared_codebook::gc(&codebook, None, &keys, opts, true).unwrap();
+    assert_eq!(dry.records_removed, released.unreferenced());
+    assert_eq!(SharedCodebook::open(&codebook, &keys).unwrap().len(), released.len());
+    let report = shared_codebook::gc(&codebook, None, &keys, opts, false).unwrap();
+    assert_eq!(report.engrams_after, 1);
+    let collected = SharedCodebook::open(&codebook, &keys).unwrap();
+    assert_eq!(collected.len(), released.len() - released.unreferenced());
+    let (v1, fs1) = &versions[1];
+    let loaded = EmbrFS::load_engram(v1).unwrap();
+    assert!(EmbrFS::verify(&loaded, &fs1.manifest, &config).unwrap().is_ok());
+
+    // Recounting from the engrams on disk agrees with the stored counts.
+    let recount = shared_codebook::gc(&codebook, Some(std::slice::from_ref(v1)), &keys, opts, false).unwrap();
+    assert_eq!(recount.records_removed, 0);
+    assert_eq!(recount.engrams_after, 1);
+    assert!(shared_codebook::gc(&codebook, Some(&[]), &keys, opts, true).unwrap().records_removed > 0);
+}
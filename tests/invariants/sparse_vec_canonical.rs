@@ -0,0 +1,42 @@
+//! Tests for the canonical form of `SparseVec`.
+
+use embeddenator::SparseVec;
+
+#[test]
+fn sparse_vec_canonical_form() {
+    use embeddenator::{SparseVecError, DIM};
+
+    let mut v = SparseVec {
+        pos: vec![9, 2, 7, 2, 40],
+        neg: vec![7, 1, 1, 30],
+    };
+    assert_eq!(v.validate(DIM), Err(SparseVecError::Unsorted { negative: false, position: 1 }));
+    v.canonicalize();
+    assert_eq!((v.pos.as_slice(), v.neg.as_slice()), (&[2, 9, 40][..], &[1, 30][..]));
+    assert_eq!(v.validate_ternary(DIM), Ok(()));
+    assert_eq!(v.validate(32), Err(SparseVecError::OutOfRange { index: 40, dim: 32 }));
+
+    assert_eq!(
+        SparseVec::from_indices(vec![1, 5], vec![2, 5], DIM).err(),
+        Some(SparseVecError::Overlap { index: 5 })
+    );
+    // Chunk encodings may overlap; they still validate as sorted vectors.
+    let both = SparseVec::from_indices(vec![1, 5], vec![2, 6], DIM).unwrap();
+    let overlapping = SparseVec { pos: vec![1, 5], neg: vec![5] };
+    assert_eq!(overlapping.validate(DIM), Ok(()));
+    assert_eq!(both.bundle(&overlapping).neg, vec![2, 6]);
+
+    // Unordered lists are canonicalized, then checked against the dimension.
+    let built = SparseVec::from_unsorted(vec![9, 2, 7, 2, 40], vec![7, 1, 1, 30], DIM).unwrap();
+    assert_eq!(built, v);
+    assert_eq!(
+        SparseVec::from_unsorted(vec![3, 1], vec![64], 64).err(),
+        Some(SparseVecError::OutOfRange { index: 64, dim: 64 })
+    );
+
+    // Deserialization refuses unsorted or repeated indices.
+    let json = |pos: &str, neg: &str| format!(r#"{{"pos":{},"neg":{}}}"#, pos, neg);
+    assert!(serde_json::from_str::<SparseVec>(&json("[1,5]", "[5]")).is_ok());
+    let err = serde_json::from_str::<SparseVec>(&json("[1,5]", "[3,3]")).unwrap_err();
+    assert!(err.to_string().contains("negative indices not strictly ascending"), "{}", err);
+}
@@ -0,0 +1,100 @@
+//! Tests for the SQLite virtual tables over an engram.
+
+use embeddenator::sqlite_vtab::EngramTables;
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use rusqlite::Connection;
+
+fn tables() -> (EngramTables, Vec<u8>) {
+    let config = ReversibleVSAConfig::default();
+    let code: Vec<u8> = (0..9000u32).map(|i| b"fn main() { run(); }\n"[i as usize % 21]).collect();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&code, "src/main.rs".into(), &config).unwrap();
+    fs.ingest_bytes(b"# notes\nnothing to see", "README.md".into(), &config).unwrap();
+    fs.ingest_bytes(&[0u8; 5000], "blob.bin".into(), &config).unwrap();
+    (EngramTables::new(fs.engram, fs.manifest), code)
+}
+
+#[test]
+fn files_table_lists_the_manifest() {
+    let conn = Connection::open_in_memory().unwrap();
+    tables().0.register(&conn).unwrap();
+
+    let mut stmt = conn
+        .prepare("SELECT path, size, is_text, chunks, length(blake3) FROM embr_files ORDER BY size DESC")
+        .unwrap();
+    let rows: Vec<(String, i64, bool, i64, i64)> = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        [
+            ("src/main.rs".into(), 9000, true, 3, 64),
+            ("blob.bin".into(), 5000, false, 2, 64),
+            ("README.md".into(), 22, true, 1, 64),
+        ]
+    );
+
+    // Joins with ordinary tables.
+    conn.execute_batch(
+        "CREATE TABLE owners(path TEXT PRIMARY KEY, team TEXT);
+         INSERT INTO owners VALUES ('src/main.rs', 'core'), ('README.md', 'docs');",
+    )
+    .unwrap();
+    let total: i64 = conn
+        .query_row(
+            "SELECT sum(f.size) FROM embr_files f JOIN owners o USING (path) WHERE o.team = 'core'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(total, 9000);
+}
+
+#[test]
+fn search_is_a_table_valued_function() {
+    let conn = Connection::open_in_memory().unwrap();
+    let (tables, code) = tables();
+    tables.register(&conn).unwrap();
+    tables.register_as(&conn, "other").unwrap();
+
+    let mut stmt = conn
+        .prepare("SELECT rank, chunk_id, cosine, path FROM embr_search(?1, 2) ORDER BY rank")
+        .unwrap();
+    let hits: Vec<(i64, i64, f64, Option<String>)> = stmt
+        .query_map([&code[..4096]], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!((hits[0].0, hits[0].1, hits[0].3.as_deref()), (1, 0, Some("src/main.rs")));
+    assert!(hits[0].2 >= hits[1].2);
+
+    // Text queries, named arguments, the default k, and a second prefix.
+    let n: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM other_search WHERE query = 'fn main() { run(); }'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(n, 6);
+    let joined: String = conn
+        .query_row(
+            "SELECT f.path FROM embr_search(?1, 1) s JOIN embr_files f USING (path)",
+            [&code[4096..8192]],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(joined, "src/main.rs");
+
+    let none: i64 = conn
+        .query_row("SELECT count(*) FROM embr_search(NULL)", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(none, 0);
+    let err = conn
+        .query_row("SELECT count(*) FROM embr_search", [], |r| r.get::<_, i64>(0))
+        .unwrap_err();
+    assert!(err.to_string().contains("needs a query"), "{err}");
+}
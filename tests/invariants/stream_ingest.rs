@@ -0,0 +1,198 @@
+//! Tests for streaming ingest: event application, checkpoints and replay.
+
+use embeddenator::stream_ingest::{
+    parse_s3_notification, resolve_object_events, EventSource, FileEvent, ObjectEventKind, ObjectFetcher,
+    StreamIngestor, StreamSettings,
+};
+use embeddenator::{EmbrFS, ReversibleVSAConfig, DEFAULT_CHUNK_SIZE};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// Delivers queued events; uncommitted ones are redelivered by `restart`.
+#[derive(Default)]
+struct QueueSource {
+    log: Vec<FileEvent>,
+    committed: usize,
+    delivered: usize,
+    commits: usize,
+}
+
+impl QueueSource {
+    fn restart(&mut self) {
+        self.delivered = self.committed;
+    }
+}
+
+impl EventSource for QueueSource {
+    fn poll(&mut self, max: usize, _timeout: Duration) -> io::Result<Vec<FileEvent>> {
+        let end = (self.delivered + max).min(self.log.len());
+        let batch = self.log[self.delivered..end].to_vec();
+        self.delivered = end;
+        Ok(batch)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.committed = self.delivered;
+        self.commits += 1;
+        Ok(())
+    }
+}
+
+fn put(path: &str, data: Vec<u8>) -> FileEvent {
+    FileEvent::Put { path: path.into(), data }
+}
+
+fn data(seed: u8, len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(seed).wrapping_add(seed)).collect()
+}
+
+fn contents(fs: &EmbrFS) -> HashMap<String, Vec<u8>> {
+    let config = ReversibleVSAConfig::default();
+    fs.manifest
+        .files
+        .iter()
+        .map(|f| (f.path.clone(), EmbrFS::reconstruct_bytes(&fs.engram, f, &config, DEFAULT_CHUNK_SIZE).unwrap()))
+        .collect()
+}
+
+fn settings(checkpoint_every: usize) -> StreamSettings {
+    StreamSettings {
+        checkpoint_every,
+        checkpoint_interval: Duration::from_secs(3600),
+        batch_size: 2,
+        poll_timeout: Duration::ZERO,
+    }
+}
+
+#[test]
+fn remove_file_matches_fresh_ingest_of_the_rest() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(&data(3, 9000), "a".into(), &config).unwrap();
+    fs.ingest_bytes(&data(5, 5000), "b".into(), &config).unwrap();
+    fs.ingest_bytes(&data(7, 100), "c".into(), &config).unwrap();
+    let before = fs.engram.corrections.stats();
+
+    assert!(fs.remove_file("b"));
+    assert!(!fs.remove_file("b"));
+    assert_eq!(fs.manifest.files.len(), 2);
+    assert!(fs.manifest.total_chunks > fs.engram.codebook.len());
+    let after = fs.engram.corrections.stats();
+    assert_eq!(after.original_bytes, before.original_bytes - 5000);
+    assert_eq!(after.total_chunks as usize, fs.engram.codebook.len());
+
+    // Ids are not reused, so a fresh ingest of the remaining files only lines
+    // up after its own "b" is removed too.
+    let mut fresh = EmbrFS::new();
+    fresh.ingest_bytes(&data(3, 9000), "a".into(), &config).unwrap();
+    fresh.ingest_bytes(&data(5, 5000), "b".into(), &config).unwrap();
+    fresh.ingest_bytes(&data(7, 100), "c".into(), &config).unwrap();
+    fresh.remove_file("b");
+    assert_eq!(fs.engram.root.pos, fresh.engram.root.pos);
+
+    let mut expected_root = EmbrFS::new();
+    let mut ids: Vec<usize> = fs.engram.codebook.keys().copied().collect();
+    ids.sort_unstable();
+    for id in ids {
+        expected_root.engram.root = expected_root.engram.root.bundle(&fs.engram.codebook[&id]);
+    }
+    assert_eq!(fs.engram.root.neg, expected_root.engram.root.neg);
+
+    let files = contents(&fs);
+    assert_eq!(files["a"], data(3, 9000));
+    assert_eq!(files["c"], data(7, 100));
+}
+
+#[test]
+fn checkpoints_commit_and_replay_is_idempotent() {
+    let td = tempfile::tempdir().unwrap();
+    let checkpoint = td.path().join("root.edna");
+    let mut source = QueueSource {
+        log: vec![
+            put("logs/1", data(11, 6000)),
+            put("logs/2", data(13, 3000)),
+            put("logs/1", data(17, 2000)),
+            FileEvent::Delete { path: "logs/2".into() },
+            put("logs/3", data(19, 4500)),
+            FileEvent::Delete { path: "missing".into() },
+        ],
+        ..Default::default()
+    };
+
+    let mut ingestor = StreamIngestor::open(&checkpoint, ReversibleVSAConfig::default(), settings(3)).unwrap();
+    assert_eq!(ingestor.step(&mut source).unwrap(), 2);
+    assert_eq!(source.commits, 0);
+    assert_eq!(ingestor.step(&mut source).unwrap(), 2);
+    assert_eq!((source.commits, source.committed), (1, 4));
+    assert!(checkpoint.exists());
+    // One more batch is applied but never checkpointed: the process "dies".
+    assert_eq!(ingestor.step(&mut source).unwrap(), 2);
+    drop(ingestor);
+    source.restart();
+
+    let mut ingestor = StreamIngestor::open(&checkpoint, ReversibleVSAConfig::default(), settings(3)).unwrap();
+    let files = contents(ingestor.fs());
+    assert_eq!(files.len(), 1);
+    assert_eq!(files["logs/1"], data(17, 2000));
+
+    let stats = ingestor.run(&mut source, &AtomicBool::new(false), true).unwrap();
+    assert_eq!((stats.ingested, stats.removed, stats.skipped), (1, 0, 1));
+    assert_eq!(source.committed, source.log.len());
+    let files = contents(ingestor.fs());
+    assert_eq!(files.len(), 2);
+    assert_eq!(files["logs/3"], data(19, 4500));
+
+    // Redelivering everything from the start passes through old versions but
+    // converges on the same files.
+    let snapshot = contents(ingestor.fs());
+    source.committed = 0;
+    source.restart();
+    let before = ingestor.stats();
+    let after = ingestor.run(&mut source, &AtomicBool::new(false), true).unwrap();
+    assert_eq!(contents(ingestor.fs()), snapshot);
+    assert_eq!(after.checkpoints, before.checkpoints + 2);
+    assert!(after.skipped > before.skipped);
+    let (loaded_engram, loaded_manifest) = EmbrFS::load_append_log(&checkpoint).unwrap();
+    assert_eq!(loaded_manifest.files.len(), 2);
+    assert_eq!(loaded_engram.codebook.len(), ingestor.fs().engram.codebook.len());
+}
+
+#[test]
+fn s3_notifications_resolve_to_file_events() {
+    let message = br#"{"EventName":"s3:ObjectCreated:Put","Key":"archive/a b.txt","Records":[
+        {"eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"archive"},"object":{"key":"dir/a+b%2B%C3%A9.txt","size":3}}},
+        {"eventName":"s3:ObjectRemoved:Delete","s3":{"bucket":{"name":"archive"},"object":{"key":"old.bin"}}},
+        {"eventName":"ObjectAccessed:Get","s3":{"bucket":{"name":"archive"},"object":{"key":"x"}}},
+        {"eventName":"ObjectCreated:Copy","s3":{"bucket":{"name":"archive"},"object":{"key":"gone"}}}
+    ]}"#;
+    let events = parse_s3_notification(message).unwrap();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].kind, ObjectEventKind::Created);
+    assert_eq!(events[0].key, "dir/a b+é.txt");
+    assert_eq!(events[1].kind, ObjectEventKind::Removed);
+
+    assert!(parse_s3_notification(br#"{"Service":"Amazon S3","Event":"s3:TestEvent"}"#).unwrap().is_empty());
+    assert!(parse_s3_notification(b"not json").is_err());
+    assert!(parse_s3_notification(br#"{"Records":[{"eventName":"ObjectCreated:Put","s3":{}}]}"#).is_err());
+
+    struct Objects;
+    impl ObjectFetcher for Objects {
+        fn fetch(&self, bucket: &str, key: &str) -> io::Result<Vec<u8>> {
+            assert_eq!(bucket, "archive");
+            match key {
+                "gone" => Err(io::ErrorKind::NotFound.into()),
+                _ => Ok(key.as_bytes().to_vec()),
+            }
+        }
+    }
+    let files = resolve_object_events(events, &Objects).unwrap();
+    assert_eq!(
+        files,
+        [
+            put("dir/a b+é.txt", b"dir/a b+\xc3\xa9.txt".to_vec()),
+            FileEvent::Delete { path: "old.bin".into() },
+        ]
+    );
+}
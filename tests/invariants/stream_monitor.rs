@@ -0,0 +1,60 @@
+//! Tests for drift monitoring of ingest streams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn stream_monitor_flags_drift_from_baseline() {
+    use embeddenator::dimensional::DimensionalConfig;
+    use embeddenator::{ChunkEncoding, DriftAlert, EmbrFS, StreamMonitor};
+
+    let words = ["alpha", "beta", "gamma", "delta", "omega", "sigma", "kappa", "theta"];
+    let mut x = 0x9e37_79b9_7f4a_7c15u64;
+    let mut text = |len: usize| -> Vec<u8> {
+        let mut out = Vec::with_capacity(len + 8);
+        while out.len() < len {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            out.extend_from_slice(words[(x % 8) as usize].as_bytes());
+            out.push(b' ');
+        }
+        out.truncate(len);
+        out
+    };
+    let baseline = text(4096 * 32);
+
+    let config = ReversibleVSAConfig { chunk_encoding: ChunkEncoding::similarity(), ..Default::default() };
+    let mut fs = EmbrFS::with_config(config, DimensionalConfig::default()).unwrap();
+    fs.ingest_bytes(&baseline, "corpus.txt".into(), &fs.vsa_config()).unwrap();
+
+    let mut monitor = StreamMonitor::for_engram(&fs.engram, &fs.manifest).with_window_chunks(4);
+    // Fresh text from the same vocabulary matches the baseline.
+    let familiar = monitor.push(&text(4096 * 16));
+    assert_eq!(familiar.len(), 4);
+    assert!(familiar.iter().all(|r| r.alert.is_none() && r.window_similarity > 0.5));
+    assert_eq!((familiar[1].offset, familiar[1].len), (4096 * 4, 4096 * 4));
+
+    // Unrelated bytes: each window scores as noise at once, while the
+    // sketch takes a few decays to forget the baseline before it alerts.
+    let noise: Vec<u8> = (0..4096 * 32u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+    let drift = monitor.push(&noise);
+    assert_eq!(drift.len(), 8);
+    assert!(drift.iter().all(|r| r.window_similarity.abs() < 0.05));
+    assert!(drift[0].alert.is_none());
+    assert_eq!(drift.last().unwrap().alert, Some(DriftAlert::Below));
+    assert!(drift.windows(2).all(|w| w[1].similarity <= w[0].similarity));
+
+    // A partial window is reported on finish; an upper threshold flags
+    // streams that look too much like the baseline.
+    assert!(monitor.push(&baseline[..100]).is_empty());
+    let last = monitor.finish().unwrap();
+    assert_eq!((last.index, last.len, monitor.bytes()), (12, 100, 4096 * 48 + 100));
+    assert!(monitor.finish().is_none());
+
+    let mut replay = StreamMonitor::for_engram(&fs.engram, &fs.manifest)
+        .with_window_chunks(8)
+        .with_thresholds(0.0, Some(0.5));
+    let reports = replay.push(&baseline);
+    assert!(reports.iter().all(|r| r.alert == Some(DriftAlert::Above)));
+    assert_eq!(replay.windows(), 4);
+}
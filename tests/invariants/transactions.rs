@@ -0,0 +1,91 @@
+//! Tests for transactional saves and crash recovery.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn transactional_save_rolls_back_or_forward_after_crash() {
+    use embeddenator::txn::{self, Recovery, Transaction};
+    use embeddenator::{BinaryWriteOptions, EmbrFS};
+    use std::fs;
+    use tempfile::tempdir;
+
+    let config = ReversibleVSAConfig::default();
+    let dir = tempdir().unwrap();
+    let engram_path = dir.path().join("data.engram");
+    let manifest_path = dir.path().join("data.json");
+    let wal_path = dir.path().join("data.engram.wal");
+    let opts = BinaryWriteOptions::default();
+
+    let mut old = EmbrFS::new();
+    old.ingest_bytes(b"old contents of the first file", "a.txt".into(), &config).unwrap();
+    let report = old.save_transactional(&engram_path, &manifest_path, opts, opts).unwrap();
+    assert_eq!(report.files.len(), 2);
+    assert!(!wal_path.exists());
+    let old_engram = fs::read(&engram_path).unwrap();
+    let old_manifest = fs::read(&manifest_path).unwrap();
+
+    let mut new = EmbrFS::new();
+    new.ingest_bytes(b"new contents, a good deal longer than the old ones", "a.txt".into(), &config)
+        .unwrap();
+    new.ingest_bytes(b"and a second file", "b.txt".into(), &config).unwrap();
+    let stage = |txn: &mut Transaction| {
+        txn.stage(&engram_path, |out| {
+            bincode::serialize_into(out, &new.engram).map_err(std::io::Error::other)
+        })
+        .unwrap();
+        txn.stage(&manifest_path, |out| Ok(serde_json::to_writer(out, &new.manifest)?)).unwrap();
+    };
+
+    // A writer that dies before committing: its log and staging files are
+    // captured before the drop cleans them up, then put back.
+    let mut txn = Transaction::begin(&engram_path).unwrap();
+    stage(&mut txn);
+    let leftovers: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|x| x == "wal" || x == "txn"))
+        .map(|p| (p.clone(), fs::read(&p).unwrap()))
+        .collect();
+    assert_eq!(leftovers.len(), 3);
+    drop(txn);
+    assert!(!wal_path.exists());
+    for (path, data) in &leftovers {
+        fs::write(path, data).unwrap();
+    }
+    assert_eq!(txn::recover(&engram_path).unwrap(), Recovery::RolledBack { discarded: 2 });
+    assert!(leftovers.iter().all(|(p, _)| !p.exists()));
+    assert_eq!(fs::read(&engram_path).unwrap(), old_engram);
+    assert_eq!(fs::read(&manifest_path).unwrap(), old_manifest);
+
+    // A torn log is rolled back by the next load.
+    for (path, data) in &leftovers {
+        fs::write(path, data).unwrap();
+    }
+    let wal = fs::read(&wal_path).unwrap();
+    fs::write(&wal_path, &wal[..wal.len() - 3]).unwrap();
+    let loaded = EmbrFS::load_engram(&engram_path).unwrap();
+    assert_eq!(loaded.codebook.len(), old.engram.codebook.len());
+    assert!(!wal_path.exists());
+    assert_eq!(txn::recover(&engram_path).unwrap(), Recovery::Clean);
+
+    // A writer that dies between renames, after the commit point: the
+    // manifest can't replace a directory, so only the engram moves.
+    fs::remove_file(&manifest_path).unwrap();
+    fs::create_dir(&manifest_path).unwrap();
+    let mut txn = Transaction::begin(&engram_path).unwrap();
+    stage(&mut txn);
+    assert!(txn.commit().is_err());
+    assert!(wal_path.exists());
+    fs::remove_dir(&manifest_path).unwrap();
+    assert_eq!(txn::recover(&engram_path).unwrap(), Recovery::RolledForward { applied: 2 });
+    assert!(!wal_path.exists());
+    let engram = EmbrFS::load_engram(&engram_path).unwrap();
+    let manifest = EmbrFS::load_manifest(&manifest_path).unwrap();
+    assert_eq!(manifest.files.len(), 2);
+    assert!(EmbrFS::verify(&engram, &manifest, &config).unwrap().is_ok());
+
+    // Staging the same target twice is refused.
+    let mut txn = Transaction::begin(&engram_path).unwrap();
+    txn.stage(&engram_path, |out| out.write_all(b"x")).unwrap();
+    assert!(txn.stage(&engram_path, |out| out.write_all(b"y")).is_err());
+}
@@ -0,0 +1,77 @@
+//! Elias–Fano coding of sparse ternary vectors.
+
+use embeddenator::trit_coding::{
+    decode_indices, decode_map, decode_vector, encode_indices, encode_map, encode_vector, is_coded,
+};
+use embeddenator::{SparseVec, DIM};
+use std::collections::HashMap;
+
+fn round_trip(values: &[usize], universe: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_indices(values, universe, &mut out);
+    let mut input = out.as_slice();
+    assert_eq!(decode_indices(&mut input, universe).unwrap(), values, "universe {universe}");
+    assert!(input.is_empty());
+    out
+}
+
+#[test]
+fn index_lists_round_trip_at_every_density() {
+    assert_eq!(round_trip(&[], 10), [0]);
+    round_trip(&[0], 1);
+    round_trip(&[9], 10);
+    round_trip(&(0..64).collect::<Vec<_>>(), 64);
+    round_trip(&[0, 1, 2, 1000, DIM - 1], DIM);
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    for n in [1, 7, 100, 2500] {
+        let mut values: Vec<usize> = (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % DIM as u64) as usize
+            })
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+        let coded = round_trip(&values, DIM);
+        // Within a couple of bytes of n * (2 + log2(DIM / n)) bits.
+        let bound = values.len() as f64 * (2.0 + (DIM as f64 / values.len() as f64).log2()) / 8.0;
+        assert!((coded.len() as f64) < bound + 4.0, "{} bytes for {} indices", coded.len(), values.len());
+    }
+}
+
+#[test]
+fn corrupt_lists_are_rejected() {
+    let mut out = Vec::new();
+    encode_indices(&[3, 40, 41], 64, &mut out);
+    for cut in 0..out.len() {
+        assert!(decode_indices(&mut &out[..cut], 64).is_err(), "cut at {cut}");
+    }
+    // Counts the data cannot hold, and values past the universe.
+    assert!(decode_indices(&mut [200u8, 1, 0].as_slice(), 1000).is_err());
+    assert!(decode_indices(&mut [1u8, 0b0000_0111, 0b0000_1000].as_slice(), 8).is_err());
+    // Two values with equal low bits and both high-part ones in bucket 0.
+    assert!(decode_indices(&mut [2u8, 0b0101, 0b11].as_slice(), 8).is_err());
+}
+
+#[test]
+fn maps_round_trip_with_overlapping_trits() {
+    let mut vectors = HashMap::new();
+    vectors.insert(0, SparseVec { pos: vec![1, 5, 9], neg: vec![2] });
+    vectors.insert(7, SparseVec { pos: vec![4], neg: vec![4, DIM - 1] });
+    vectors.insert(3, SparseVec::new());
+    let coded = encode_map(&vectors, DIM);
+    assert!(is_coded(&coded));
+    assert_eq!(decode_map(&coded).unwrap(), (DIM, vectors));
+    assert!(decode_map(&coded[..coded.len() - 1]).is_err());
+    let mut trailing = coded.clone();
+    trailing.push(0);
+    assert!(decode_map(&trailing).is_err());
+    assert!(!is_coded(&coded[1..]));
+    assert_eq!(decode_map(&encode_map(&HashMap::new(), 16)).unwrap(), (16, HashMap::new()));
+
+    let mut one = Vec::new();
+    encode_vector(&SparseVec { pos: vec![3], neg: vec![3] }, 4, &mut one);
+    assert_eq!(decode_vector(&mut one.as_slice(), 4).unwrap().neg, [3]);
+}
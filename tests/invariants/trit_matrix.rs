@@ -0,0 +1,82 @@
+use embeddenator::{BitslicedTritVec, ReversibleVSAConfig, SparseVec, Trit, TritMatrix, DIM};
+
+fn codebook(n: usize) -> Vec<SparseVec> {
+    let config = ReversibleVSAConfig::default();
+    (0..n).map(|i| SparseVec::encode_data(format!("symbol-{i}").as_bytes(), &config, None)).collect()
+}
+
+/// Quasi-orthogonal symbols, unlike the encodings of similar strings.
+fn random_symbols(n: usize) -> Vec<BitslicedTritVec> {
+    let m = TritMatrix::random_projection(n, DIM, 200, 42);
+    (0..n).map(|r| m.row(r)).collect()
+}
+
+#[test]
+fn matvec_dot_and_bind_match_rowwise_ops() {
+    let symbols = codebook(6);
+    let m = TritMatrix::from_sparse_rows(&symbols, DIM);
+    assert_eq!((m.rows(), m.cols()), (6, DIM));
+    let query = BitslicedTritVec::from_sparse(&codebook(7)[6], DIM);
+
+    let dots = m.matvec_dot(&query);
+    let bound = m.matvec_bind(&query);
+    for (r, symbol) in symbols.iter().enumerate() {
+        let row = BitslicedTritVec::from_sparse(symbol, DIM);
+        assert_eq!(m.row(r), row);
+        assert_eq!(m.row_nnz(r), row.nnz());
+        assert_eq!(dots[r], row.dot(&query));
+        assert_eq!(bound.row(r), row.bind(&query));
+    }
+    assert_eq!(m.matvec_dot_dispatch(&query), dots);
+    assert_eq!(m.matvec_bind_dispatch(&query), bound);
+}
+
+#[test]
+fn odd_widths_keep_padding_clear() {
+    // 70 columns leave 58 unused bits in each row's second word.
+    let mut m = TritMatrix::new_zero(3, 70);
+    m.set(0, 69, Trit::P);
+    m.set(1, 0, Trit::N);
+    m.set(2, 64, Trit::N);
+    assert_eq!(m.get(0, 69), Trit::P);
+    assert_eq!(m.get(2, 63), Trit::Z);
+
+    let mut v = BitslicedTritVec::new_zero(70);
+    v.set(69, Trit::P);
+    v.set(0, Trit::P);
+    v.set(64, Trit::N);
+    assert_eq!(m.matvec_dot(&v), [1, -1, 1]);
+    assert_eq!(m.project(&v).to_sparse(), SparseVec { pos: vec![0, 2], neg: vec![1] });
+}
+
+#[test]
+fn cleanup_memory_recovers_noisy_symbol() {
+    let symbols = random_symbols(21);
+    let m = TritMatrix::from_rows(&symbols[..20], DIM);
+    let noisy = symbols[13].bundle(&symbols[20]);
+    let (best, cosine) = m.best_match(&noisy).unwrap();
+    assert_eq!(best, 13);
+    assert!(cosine > 0.3, "{}", cosine);
+    assert!(TritMatrix::new_zero(0, DIM).best_match(&noisy).is_none());
+}
+
+#[test]
+fn random_projection_is_seeded_and_preserves_similarity() {
+    let a = TritMatrix::random_projection(256, DIM, 64, 7);
+    assert_eq!(a, TritMatrix::random_projection(256, DIM, 64, 7));
+    assert_ne!(a, TritMatrix::random_projection(256, DIM, 64, 8));
+    assert!((0..256).all(|r| a.row_nnz(r) == 64));
+
+    let symbols = codebook(2);
+    let x = BitslicedTritVec::from_sparse(&symbols[0], DIM);
+    let near = x.bundle(&BitslicedTritVec::from_sparse(&symbols[1], DIM));
+    let far = BitslicedTritVec::from_sparse(&symbols[1], DIM).negate();
+    let (px, pnear, pfar) = (a.project(&x), a.project(&near), a.project(&far));
+    assert!(px.cosine(&pnear) > px.cosine(&pfar));
+}
+
+#[test]
+#[should_panic(expected = "matrix has")]
+fn matvec_rejects_wrong_width() {
+    TritMatrix::new_zero(2, 128).matvec_dot(&BitslicedTritVec::new_zero(64));
+}
@@ -0,0 +1,68 @@
+//! Persistent `VectorStore` backends: round trips, scans, dimension checks
+//! and reranking straight from disk.
+
+use embeddenator::{rerank_top_k_by_cosine, SparseVec, SparseVecBackend, VectorStore, DIM};
+use std::collections::HashMap;
+use std::io;
+
+fn vectors(n: usize) -> HashMap<usize, SparseVec> {
+    (0..n).map(|i| (i * 3, SparseVec::random())).collect()
+}
+
+fn same(a: &SparseVec, b: &SparseVec) -> bool {
+    a.pos == b.pos && a.neg == b.neg
+}
+
+/// Both stores share one inherent API; `$open(dim)` must reopen the same
+/// on-disk store each time.
+macro_rules! round_trip {
+    ($open:expr) => {{
+        let open = $open;
+        let data = vectors(50);
+        let store = open(DIM).unwrap();
+        assert!(store.is_empty().unwrap());
+        assert_eq!(store.put_batch(data.iter().map(|(&id, v)| (id, v))).unwrap(), 50);
+        assert_eq!(store.len().unwrap(), 50);
+        assert!(same(&store.get_vector(9).unwrap().unwrap(), &data[&9]));
+        assert!(store.get_vector(10).unwrap().is_none());
+
+        let ids: Vec<usize> = store.scan(30, 4).unwrap().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![30, 33, 36, 39]);
+
+        let query = data[&30].clone();
+        let hits = rerank_top_k_by_cosine(&SparseVecBackend, &store, &query, data.keys().copied(), 3).unwrap();
+        assert_eq!(hits[0].0, 30);
+        assert!(rerank_top_k_by_cosine(&SparseVecBackend, &store, &query, [1], 1).is_err());
+
+        let mut bad = SparseVec::new();
+        bad.pos.push(DIM);
+        assert_eq!(store.put(1000, &bad).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(store.get_vector(1000).unwrap().is_none());
+
+        assert!(store.remove(9).unwrap());
+        assert!(!store.remove(9).unwrap());
+        drop(store);
+
+        let reopened = open(DIM).unwrap();
+        assert_eq!(reopened.len().unwrap(), 49);
+        assert!(same(&VectorStore::get(&reopened, 3).unwrap(), &data[&3]));
+        drop(reopened);
+        assert_eq!(open(DIM * 2).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+    }};
+}
+
+#[cfg(feature = "lmdb")]
+#[test]
+fn lmdb_store_round_trip() {
+    use embeddenator::lmdb_store::LmdbVectorStore;
+    let td = tempfile::tempdir().unwrap();
+    round_trip!(|dim| LmdbVectorStore::open_with_map_size(td.path(), dim, 16 << 20));
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn rocksdb_store_round_trip() {
+    use embeddenator::rocksdb_store::RocksDbVectorStore;
+    let td = tempfile::tempdir().unwrap();
+    round_trip!(|dim| RocksDbVectorStore::open(td.path(), dim));
+}
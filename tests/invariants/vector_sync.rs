@@ -0,0 +1,247 @@
+//! Tests for syncing chunk vectors into Qdrant/Milvus collections.
+
+use embeddenator::delta::EngramDelta;
+use embeddenator::vector_sync::{MilvusSink, QdrantSink, SyncPoint, VectorSink, VectorSync, QDRANT_VECTOR_NAME};
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Mutex};
+
+fn engram_with(files: &[(&str, Vec<u8>)]) -> EmbrFS {
+    let config = ReversibleVSAConfig::default();
+    let mut fsys = EmbrFS::new();
+    for (path, data) in files {
+        fsys.ingest_bytes(data, path.to_string(), &config).unwrap();
+    }
+    fsys
+}
+
+fn text() -> Vec<u8> {
+    b"synced into a vector database\n".repeat(400)
+}
+
+fn binary() -> Vec<u8> {
+    (0..9000u32).map(|i| (i * 13 + 5) as u8).collect()
+}
+
+#[derive(Default)]
+struct Recorded {
+    ensured: usize,
+    upserted: Vec<SyncPoint>,
+    deleted: Vec<u64>,
+}
+
+#[derive(Default, Clone)]
+struct MemorySink(Arc<Mutex<Recorded>>);
+
+impl VectorSink for MemorySink {
+    fn ensure_collection(&self) -> io::Result<()> {
+        self.0.lock().unwrap().ensured += 1;
+        Ok(())
+    }
+
+    fn upsert(&self, points: &[SyncPoint]) -> io::Result<()> {
+        self.0.lock().unwrap().upserted.extend_from_slice(points);
+        Ok(())
+    }
+
+    fn delete(&self, ids: &[u64]) -> io::Result<()> {
+        self.0.lock().unwrap().deleted.extend_from_slice(ids);
+        Ok(())
+    }
+}
+
+#[test]
+fn sync_all_batches_every_chunk_with_payload() {
+    let fsys = engram_with(&[("docs/a.txt", text()), ("b.bin", binary())]);
+    let sink = MemorySink::default();
+    let summary = VectorSync::new(sink.clone())
+        .with_batch_size(2)
+        .sync_all(&fsys.engram, &fsys.manifest)
+        .unwrap();
+
+    let n = fsys.engram.codebook.len();
+    assert_eq!(summary.upserted, n);
+    assert_eq!(summary.requests, n.div_ceil(2));
+    let recorded = sink.0.lock().unwrap();
+    assert_eq!(recorded.ensured, 1);
+    let ids: Vec<u64> = recorded.upserted.iter().map(|p| p.id).collect();
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(ids.len(), n);
+
+    for point in &recorded.upserted {
+        assert_eq!(point.indices.len(), point.values.len());
+        assert!(point.indices.windows(2).all(|w| w[0] < w[1]));
+        let norm: f32 = point.values.iter().map(|v| v * v).sum();
+        assert!((norm - 1.0).abs() < 1e-4);
+        assert_eq!(point.payload["chunk_id"], json!(point.id));
+    }
+
+    let file = &fsys.manifest.files[0];
+    let first = recorded.upserted.iter().find(|p| p.id == file.chunks[1] as u64).unwrap();
+    assert!(first.payload["paths"].as_array().unwrap().contains(&json!("docs/a.txt")));
+    let r = first.payload["refs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["path"] == "docs/a.txt" && r["index"] == 1)
+        .expect("reference to the second chunk");
+    assert_eq!(r["offset"].as_u64(), r["len"].as_u64());
+}
+
+#[test]
+fn sync_delta_sends_only_changes() {
+    let base = engram_with(&[("a.txt", text()), ("b.bin", binary())]);
+    let grown = engram_with(&[("a.txt", text()), ("b.bin", binary()), ("c.txt", b"new file\n".repeat(900))]);
+    let delta = EngramDelta::between(&base.engram, &base.manifest, &grown.engram, &grown.manifest).unwrap();
+
+    let sink = MemorySink::default();
+    let summary = VectorSync::new(sink.clone())
+        .sync_delta(&base.manifest, &delta, &grown.engram, &grown.manifest)
+        .unwrap();
+    let added: HashSet<u64> = grown.manifest.files[2].chunks.iter().map(|&c| c as u64).collect();
+    let upserted: HashSet<u64> = sink.0.lock().unwrap().upserted.iter().map(|p| p.id).collect();
+    assert!(added.is_subset(&upserted));
+    assert!(upserted.len() < grown.engram.codebook.len());
+    assert_eq!(summary.deleted, 0);
+
+    // Dropping a file deletes chunks nobody else uses.
+    let shrunk = engram_with(&[("a.txt", text())]);
+    let delta = EngramDelta::between(&base.engram, &base.manifest, &shrunk.engram, &shrunk.manifest).unwrap();
+    assert!(!delta.removed_chunks.is_empty());
+    let sink = MemorySink::default();
+    let summary = VectorSync::new(sink.clone())
+        .with_payload(|id, refs| {
+            let mut payload = serde_json::Map::new();
+            payload.insert("n".into(), json!(id + refs.len()));
+            payload
+        })
+        .sync_delta(&base.manifest, &delta, &shrunk.engram, &shrunk.manifest)
+        .unwrap();
+    let recorded = sink.0.lock().unwrap();
+    assert_eq!(recorded.deleted, delta.removed_chunks);
+    assert_eq!(summary.deleted, delta.removed_chunks.len());
+    assert!(recorded.upserted.iter().all(|p| p.payload.contains_key("n") && !delta.removed_chunks.contains(&p.id)));
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+/// Loopback HTTP server answering each request with `reply(request)`.
+fn mock_server(reply: impl Fn(&Request) -> Value + Send + 'static) -> (String, Arc<Mutex<Vec<Request>>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let seen = log.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap().to_string();
+            let path = parts.next().unwrap().to_string();
+            let mut headers = Vec::new();
+            let mut len = 0;
+            loop {
+                let mut h = String::new();
+                reader.read_line(&mut h).unwrap();
+                let h = h.trim_end();
+                if h.is_empty() {
+                    break;
+                }
+                let (k, v) = h.split_once(':').unwrap();
+                if k.eq_ignore_ascii_case("content-length") {
+                    len = v.trim().parse().unwrap();
+                }
+                headers.push((k.to_ascii_lowercase(), v.trim().to_string()));
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            let request = Request {
+                method,
+                path,
+                headers,
+                body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+            };
+            let out = reply(&request).to_string();
+            seen.lock().unwrap().push(request);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                out.len(),
+                out
+            )
+            .unwrap();
+        }
+    });
+    (url, log)
+}
+
+#[test]
+fn qdrant_rest_calls() {
+    let fsys = engram_with(&[("a.txt", text())]);
+    let (url, log) = mock_server(|req| match req.path.as_str() {
+        "/collections/docs/exists" => json!({ "result": { "exists": false }, "status": "ok" }),
+        _ => json!({ "result": true, "status": "ok" }),
+    });
+    let sink = QdrantSink::new(format!("{url}/"), "docs").with_api_key("secret");
+    VectorSync::new(sink).sync_all(&fsys.engram, &fsys.manifest).unwrap();
+    QdrantSink::new(url, "docs").delete(&[3, 4]).unwrap();
+
+    let log = log.lock().unwrap();
+    let calls: Vec<(&str, &str)> = log.iter().map(|r| (r.method.as_str(), r.path.as_str())).collect();
+    assert_eq!(
+        calls,
+        [
+            ("GET", "/collections/docs/exists"),
+            ("PUT", "/collections/docs"),
+            ("PUT", "/collections/docs/points?wait=true"),
+            ("POST", "/collections/docs/points/delete?wait=true"),
+        ]
+    );
+    assert!(log[0].headers.contains(&("api-key".into(), "secret".into())));
+    assert!(log[1].body["sparse_vectors"][QDRANT_VECTOR_NAME].is_object());
+    let points = log[2].body["points"].as_array().unwrap();
+    assert_eq!(points.len(), fsys.engram.codebook.len());
+    let vector = &points[0]["vector"][QDRANT_VECTOR_NAME];
+    assert_eq!(vector["indices"].as_array().unwrap().len(), vector["values"].as_array().unwrap().len());
+    assert_eq!(points[0]["payload"]["paths"], json!(["a.txt"]));
+    assert_eq!(log[3].body, json!({ "points": [3, 4] }));
+}
+
+#[test]
+fn milvus_rest_calls_and_errors() {
+    let fsys = engram_with(&[("a.txt", text())]);
+    let (url, log) = mock_server(|req| match req.path.as_str() {
+        "/v2/vectordb/collections/has" => json!({ "code": 0, "data": { "has": true } }),
+        "/v2/vectordb/entities/delete" => json!({ "code": 1100, "message": "collection not loaded" }),
+        _ => json!({ "code": 0, "data": {} }),
+    });
+    let sink = MilvusSink::new(url.clone(), "docs").with_token("root:Milvus");
+    VectorSync::new(sink).sync_all(&fsys.engram, &fsys.manifest).unwrap();
+    let err = MilvusSink::new(url, "docs").delete(&[7, 9]).unwrap_err();
+    assert!(err.to_string().contains("collection not loaded"), "{err}");
+
+    let log = log.lock().unwrap();
+    let paths: Vec<&str> = log.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        ["/v2/vectordb/collections/has", "/v2/vectordb/entities/upsert", "/v2/vectordb/entities/delete"]
+    );
+    assert!(log.iter().all(|r| r.method == "POST" && r.body["collectionName"] == "docs"));
+    assert!(log[0].headers.contains(&("authorization".into(), "Bearer root:Milvus".into())));
+    let row = &log[1].body["data"][0];
+    assert!(row["id"].is_u64());
+    assert!(!row["vector"].as_object().unwrap().is_empty());
+    assert_eq!(row["paths"], json!(["a.txt"]));
+    assert_eq!(log[2].body["filter"], "id in [7,9]");
+}
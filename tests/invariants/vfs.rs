@@ -0,0 +1,37 @@
+//! Tests for the virtual filesystem layer.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn virtual_fs_resolves_the_same_over_reader_and_mount() {
+    use embeddenator::fuse_shim::ROOT_INO;
+    use embeddenator::{EmbrFS, EngramFS, EngramReader, FileKind, VirtualFs};
+    use std::io::ErrorKind;
+
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"alpha beta gamma", "src/lib.rs".into(), &config).unwrap();
+    fs.ingest_bytes(b"readme", "README.md".into(), &config).unwrap();
+    let reader = EngramReader::from_embrfs(fs, config);
+    let mount = EngramFS::from_reader(&reader, true);
+
+    let backends: [&dyn VirtualFs; 2] = [&reader, &mount];
+    for vfs in backends {
+        let ino = vfs.resolve("/src/lib.rs").unwrap();
+        assert_eq!(vfs.resolve("src//./lib.rs/"), Some(ino));
+        assert_eq!(vfs.lookup(vfs.resolve("src").unwrap(), "lib.rs"), Some(ino));
+        assert_eq!(vfs.resolve(""), Some(ROOT_INO));
+        assert!(vfs.resolve("src/missing.rs").is_none());
+        assert_eq!(vfs.getattr(ino).unwrap().size, 16);
+        assert_eq!(vfs.read(ino, 6, 4).unwrap(), b"beta");
+
+        let mut names: Vec<String> = vfs.readdir(ROOT_INO).unwrap().into_iter().map(|e| e.name).collect();
+        names.sort();
+        assert_eq!(names, ["README.md", "src"]);
+        assert_eq!(vfs.getattr(vfs.resolve("src").unwrap()).unwrap().kind, FileKind::Directory);
+        assert_eq!(vfs.readlink(ino).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(vfs.readlink(999_999).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+    assert_eq!(reader.file_at(reader.resolve("README.md").unwrap()).unwrap().path, "README.md");
+    assert!(reader.file_at(ROOT_INO).is_none());
+}
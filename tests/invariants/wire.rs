@@ -0,0 +1,101 @@
+//! Tests for the protobuf wire format.
+
+use crate::support::ingested;
+
+#[test]
+fn schema_is_embedded() {
+    assert!(embeddenator::wire::PROTO_SCHEMA.contains("package embeddenator.v1;"));
+}
+
+#[cfg(not(feature = "proto"))]
+#[test]
+fn wire_reports_missing_feature() {
+    let td = tempfile::tempdir().unwrap();
+    let err = embeddenator::wire::encode_engram(&ingested(td.path()).engram).unwrap_err();
+    assert!(err.to_string().contains("not enabled"), "unexpected error: {err}");
+}
+
+#[cfg(feature = "proto")]
+mod enabled {
+    use super::*;
+    use embeddenator::{EmbrFS, ReversibleVSAConfig};
+    use std::fs;
+    use embeddenator::wire::{self, pb};
+    use prost::Message;
+
+    #[test]
+    fn engram_and_manifest_round_trip_and_extract() {
+        let td = tempfile::tempdir().unwrap();
+        let fsys = ingested(td.path());
+
+        let engram_bytes = wire::encode_engram(&fsys.engram).unwrap();
+        let manifest_bytes = wire::encode_manifest(&fsys.manifest).unwrap();
+        assert_eq!(wire::encode_engram(&fsys.engram).unwrap(), engram_bytes, "encoding is deterministic");
+
+        let mut restored = EmbrFS::new();
+        restored.engram = wire::decode_engram(&engram_bytes).unwrap();
+        restored.manifest = wire::decode_manifest(&manifest_bytes).unwrap();
+        assert_eq!(restored.manifest.files, fsys.manifest.files);
+        assert_eq!(restored.engram.codebook.len(), fsys.engram.codebook.len());
+        assert_eq!(
+            restored.engram.corrections.stats().total_chunks,
+            fsys.engram.corrections.stats().total_chunks
+        );
+
+        let out = td.path().join("out");
+        EmbrFS::extract(&restored.engram, &restored.manifest, &out, false, &ReversibleVSAConfig::default()).unwrap();
+        assert_eq!(
+            fs::read(out.join("docs/a.txt")).unwrap(),
+            fs::read(td.path().join("in/docs/a.txt")).unwrap()
+        );
+        assert_eq!(fs::read(out.join("b.bin")).unwrap(), fs::read(td.path().join("in/b.bin")).unwrap());
+    }
+
+    #[test]
+    fn decoding_rejects_malformed_messages() {
+        let bad_order = pb::Engram {
+            version: wire::WIRE_VERSION,
+            root: Some(pb::SparseVec { pos: vec![5, 3], neg: vec![] }),
+            ..Default::default()
+        };
+        let Err(err) = wire::decode_engram(&bad_order.encode_to_vec()) else {
+            panic!("descending indices were accepted");
+        };
+        assert!(err.to_string().contains("ascending"), "unexpected error: {err}");
+
+        let future = pb::Manifest {
+            version: wire::WIRE_VERSION + 1,
+            ..Default::default()
+        };
+        let err = wire::decode_manifest(&future.encode_to_vec()).unwrap_err();
+        assert!(err.to_string().contains("version"), "unexpected error: {err}");
+
+        let reused_inode = pb::Manifest {
+            version: wire::WIRE_VERSION,
+            inodes: Some(pb::InodeTable {
+                next: 3,
+                paths: [("a".to_string(), 2), ("b".to_string(), 3)].into(),
+            }),
+            ..Default::default()
+        };
+        let err = wire::decode_manifest(&reused_inode.encode_to_vec()).unwrap_err();
+        assert!(err.to_string().contains("inode"), "unexpected error: {err}");
+
+        let short_hash = pb::Engram {
+            version: wire::WIRE_VERSION,
+            root: Some(pb::SparseVec::default()),
+            corrections: vec![pb::ChunkCorrection {
+                chunk_id: 1,
+                hash: vec![0; 4],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let Err(err) = wire::decode_engram(&short_hash.encode_to_vec()) else {
+            panic!("short correction hash was accepted");
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        assert!(wire::decode_engram(b"\xff\xff\xff").is_err());
+    }
+}
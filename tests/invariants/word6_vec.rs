@@ -0,0 +1,75 @@
+use embeddenator::ternary::{Trit, Word6};
+use embeddenator::Word6Vec;
+
+#[test]
+fn word6_add_with_carry_is_exact_for_all_pairs() {
+    for a in (Word6::MIN_VALUE..=Word6::MAX_VALUE).step_by(7) {
+        for b in Word6::MIN_VALUE..=Word6::MAX_VALUE {
+            let (sum, carry) = Word6::from_i16(a).unwrap().add_with_carry(Word6::from_i16(b).unwrap(), Trit::Z);
+            assert_eq!(sum.to_i16() + 729 * carry.to_i8() as i16, a + b, "{} + {}", a, b);
+        }
+    }
+}
+
+#[test]
+fn word6_vec_i16_roundtrip_and_range() {
+    let values = [0i16, 1, -1, 13, -27, 364, -364];
+    let v = Word6Vec::from_i16_slice(&values, 0).unwrap();
+    assert_eq!(v.to_i16_vec(), values);
+    assert!(Word6Vec::from_i16_slice(&[365], 0).is_none());
+    assert_eq!(Word6Vec::from_i16_slice_saturating(&[1000, -1000, 5], 0).to_i16_vec(), [364, -364, 5]);
+}
+
+#[test]
+fn word6_vec_add_saturates_or_fails_on_overflow() {
+    let a = Word6Vec::from_i16_slice(&[300, -300, 10], 0).unwrap();
+    let b = Word6Vec::from_i16_slice(&[100, -100, -25], 0).unwrap();
+    assert_eq!(a.saturating_add(&b).to_i16_vec(), [364, -364, -15]);
+    assert!(a.checked_add(&b).is_none());
+    assert_eq!(a.saturating_sub(&b).to_i16_vec(), [200, -200, 35]);
+
+    let small = Word6Vec::from_i16_slice(&[1, 2, 3], 0).unwrap();
+    assert_eq!(small.checked_add(&small).unwrap().to_i16_vec(), [2, 4, 6]);
+}
+
+#[test]
+fn word6_vec_fixed_point_mul_rounds_to_nearest() {
+    // Two fractional trits: steps of 1/9.
+    let a = Word6Vec::from_f64_slice(&[1.5, -2.0, 0.25, 30.0], 2);
+    let b = Word6Vec::from_f64_slice(&[2.0, 1.5, 0.5, 30.0], 2);
+    assert_eq!(a.to_i16_vec(), [14, -18, 2, 270]);
+
+    let product = a.saturating_mul(&b);
+    // 14 × 18 / 9 = 28; -18 × 14 / 9 = -28; 2 × 5 / 9 ≈ 1.11 rounds to 1.
+    assert_eq!(&product.to_i16_vec()[..3], [28, -28, 1]);
+    assert_eq!(product.get(3).to_i16(), Word6::MAX_VALUE);
+    assert!(a.checked_mul(&b).is_none());
+
+    for x in -40..=40i16 {
+        for y in -40..=40i16 {
+            let exact = x as f64 * y as f64 / 9.0;
+            let got = Word6Vec::from_i16_slice(&[x], 2)
+                .unwrap()
+                .saturating_mul(&Word6Vec::from_i16_slice(&[y], 2).unwrap())
+                .to_i16_vec()[0];
+            assert!((got as f64 - exact).abs() <= 0.5, "{} × {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn word6_vec_dot_products() {
+    let a = Word6Vec::from_i16_slice(&[3, -4, 5], 1).unwrap();
+    let b = Word6Vec::from_i16_slice(&[6, 2, -1], 1).unwrap();
+    assert_eq!(a.dot(&b), 18 - 8 - 5);
+    assert!((a.dot_f64(&b) - 5.0 / 9.0).abs() < 1e-12);
+    assert_eq!((-a.clone()).to_i16_vec(), [-3, 4, -5]);
+}
+
+#[test]
+#[should_panic(expected = "fractional trit mismatch")]
+fn word6_vec_rejects_mixed_scales() {
+    let a = Word6Vec::new_zero(2, 0);
+    let b = Word6Vec::new_zero(2, 1);
+    let _ = a.saturating_add(&b);
+}
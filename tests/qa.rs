@@ -11,3 +11,6 @@ mod test_metrics_integrity;
 
 #[path = "qa/qa_comprehensive.rs"]
 mod qa_comprehensive;
+
+#[path = "qa/ingest_limits.rs"]
+mod ingest_limits;
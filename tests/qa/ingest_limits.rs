@@ -0,0 +1,83 @@
+//! Ingest quota enforcement and dry-run estimation.
+
+use embeddenator::{EmbrError, EmbrFS, IngestLimits, QuotaExceeded, QuotaKind, ReversibleVSAConfig};
+use std::fs;
+use tempfile::TempDir;
+
+fn quota_of(err: &EmbrError) -> &QuotaExceeded {
+    match err {
+        EmbrError::Quota(quota) => quota,
+        other => panic!("expected a quota error, got {other}"),
+    }
+}
+
+fn write_inputs(dir: &TempDir) -> std::path::PathBuf {
+    let input = dir.path().join("input");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("small.txt"), b"tiny file\n").unwrap();
+    fs::write(input.join("big.bin"), vec![7u8; 3 * 4096 + 10]).unwrap();
+    input
+}
+
+#[test]
+fn max_file_size_rejects_large_file() {
+    let dir = TempDir::new().unwrap();
+    let input = write_inputs(&dir);
+    let mut fs = EmbrFS::with_limits(IngestLimits {
+        max_file_size: Some(4096),
+        ..IngestLimits::default()
+    });
+
+    let err = fs
+        .ingest_directory(&input, false, &ReversibleVSAConfig::default())
+        .unwrap_err();
+    let quota = quota_of(&err);
+    assert_eq!(quota.kind, QuotaKind::FileSize);
+    assert_eq!(quota.path, "big.bin");
+    assert_eq!(quota.attempted, 3 * 4096 + 10);
+}
+
+#[test]
+fn max_chunks_and_engram_bytes_are_enforced() {
+    let dir = TempDir::new().unwrap();
+    let input = write_inputs(&dir);
+    let config = ReversibleVSAConfig::default();
+
+    let mut fs = EmbrFS::with_limits(IngestLimits {
+        max_chunks: Some(3),
+        ..IngestLimits::default()
+    });
+    let err = fs.ingest_directory(&input, false, &config).unwrap_err();
+    assert_eq!(quota_of(&err).kind, QuotaKind::ChunkCount);
+
+    let mut fs = EmbrFS::with_limits(IngestLimits {
+        max_engram_bytes: Some(64),
+        ..IngestLimits::default()
+    });
+    let err = fs.ingest_directory(&input, false, &config).unwrap_err();
+    assert_eq!(quota_of(&err).kind, QuotaKind::EngramBytes);
+
+    // Unlimited ingest of the same tree succeeds.
+    let mut fs = EmbrFS::new();
+    fs.ingest_directory(&input, false, &config).unwrap();
+    assert_eq!(fs.manifest.total_chunks, 5);
+}
+
+#[test]
+fn dry_run_estimate_reports_violations_without_ingesting() {
+    let dir = TempDir::new().unwrap();
+    let input = write_inputs(&dir);
+    let fs = EmbrFS::with_limits(IngestLimits {
+        max_chunks: Some(2),
+        ..IngestLimits::default()
+    });
+
+    let est = fs.estimate_directory(&input, &ReversibleVSAConfig::default()).unwrap();
+    assert_eq!(est.files, 2);
+    assert_eq!(est.input_bytes, 10 + 3 * 4096 + 10);
+    assert_eq!(est.chunks, 5);
+    assert!(est.estimated_engram_bytes > 0);
+    assert_eq!(est.violations.len(), 1);
+    assert_eq!(est.violations[0].kind, QuotaKind::ChunkCount);
+    assert_eq!(fs.manifest.total_chunks, 0);
+}
@@ -8,3 +8,6 @@ mod compression_backward_compat;
 
 #[path = "regression/compression_missing_codec.rs"]
 mod compression_missing_codec;
+
+#[path = "regression/format_fixtures.rs"]
+mod format_fixtures;
@@ -278,3 +278,42 @@ fn bundle_hier_compressed_subengrams_and_query_text() {
         "expected hierarchical output, got: {stdout}"
     );
 }
+
+#[cfg(feature = "compression-zstd")]
+#[test]
+fn engram_roundtrips_with_zstd_dictionary() {
+    use embeddenator::{BinaryWriteOptions, CompressionCodec};
+
+    let td = tempfile::tempdir().expect("tempdir");
+    let input_dir = td.path().join("in");
+    let out_dir = td.path().join("out");
+    for i in 0..32 {
+        let body = format!("fn item_{i}() -> usize {{ {i} * 2 }}\n").repeat(200);
+        write_file(input_dir.join(format!("src/m{i}.rs")), body.as_bytes()).expect("write input");
+    }
+
+    let mut fsys = EmbrFS::new();
+    let config = ReversibleVSAConfig::default();
+    fsys.ingest_directory(&input_dir, false, &config).expect("ingest");
+
+    let engram_path = td.path().join("dict.engram");
+    let opts = BinaryWriteOptions {
+        codec: CompressionCodec::ZstdDict,
+        level: None,
+        ..Default::default()
+    };
+    fsys.save_engram_with_options(&engram_path, opts).expect("save engram");
+    let bytes = fs::read(&engram_path).expect("read engram");
+    assert_eq!(&bytes[..4], b"EDN1");
+    assert_eq!(bytes[5], CompressionCodec::ZstdDict as u8);
+
+    let loaded = EmbrFS::load_engram(&engram_path).expect("load engram");
+    EmbrFS::extract(&loaded, &fsys.manifest, &out_dir, false, &config).expect("extract");
+    for i in 0..32 {
+        let rel = format!("src/m{i}.rs");
+        assert_eq!(
+            fs::read(out_dir.join(&rel)).expect("read extracted"),
+            fs::read(input_dir.join(&rel)).expect("read input")
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Engrams, manifests and vectors written by earlier releases, committed under
+//! `tests/fixtures/wire`. A failure here means a format changed in a way old
+//! files cannot survive.
+//!
+//! After a deliberate format change, regenerate with
+//! `cargo test --features proto --test regression regenerate_fixtures -- --ignored`
+//! and say why in the commit.
+
+use embeddenator::{EmbrFS, IngestClock, Manifest, ReversibleVSAConfig};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FIXTURE_TEXT: &str = "Fixture file for format regression tests.\n\
+    Its engram is committed in bincode and protobuf form; both must keep\n\
+    loading and reconstructing these exact bytes.\n";
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/wire").join(name)
+}
+
+fn fixture_fs() -> EmbrFS {
+    let mut fsys = EmbrFS::reproducible(IngestClock::Omit);
+    fsys.ingest_bytes(FIXTURE_TEXT.as_bytes(), "fixture.txt".into(), &ReversibleVSAConfig::default())
+        .unwrap();
+    fsys.canonicalize();
+    fsys
+}
+
+fn extract_fixture_text(engram: &embeddenator::Engram, manifest: &Manifest) -> String {
+    let out = tempfile::tempdir().unwrap();
+    EmbrFS::extract(engram, manifest, out.path(), false, &ReversibleVSAConfig::default()).unwrap();
+    fs::read_to_string(out.path().join("fixture.txt")).unwrap()
+}
+
+#[test]
+fn bincode_engram_fixture_still_loads() {
+    let engram = EmbrFS::load_engram(fixture("engram_v1.bincode")).unwrap();
+    let manifest = EmbrFS::load_manifest(fixture("manifest_v1.json")).unwrap();
+    assert_eq!(extract_fixture_text(&engram, &manifest), FIXTURE_TEXT);
+}
+
+#[test]
+#[ignore = "rewrites the committed fixtures"]
+fn regenerate_fixtures() {
+    let fsys = fixture_fs();
+    fs::create_dir_all(fixture("")).unwrap();
+    fsys.save_engram(fixture("engram_v1.bincode")).unwrap();
+    fsys.save_manifest(fixture("manifest_v1.json")).unwrap();
+    #[cfg(feature = "proto")]
+    proto::regenerate(&fsys);
+}
+
+#[cfg(feature = "proto")]
+mod proto {
+    use super::*;
+    use embeddenator::wire;
+    use embeddenator::{BitslicedTritVec, BlockSparseTritVec, HybridTritVec, SparseVec};
+
+    fn hybrid_source() -> SparseVec {
+        SparseVec {
+            pos: vec![3, 70, 511, 999, 150_001, 199_999],
+            neg: vec![0, 64, 640, 131_072],
+        }
+    }
+
+    /// Each representation with the dimension it is stored at.
+    fn hybrids() -> Vec<(&'static str, HybridTritVec, usize)> {
+        let source = hybrid_source();
+        let small = SparseVec {
+            pos: source.pos.iter().copied().filter(|&i| i < 1000).collect(),
+            neg: source.neg.iter().copied().filter(|&i| i < 1000).collect(),
+        };
+        vec![
+            ("hybrid_sparse_v1.pb", HybridTritVec::Sparse(small.clone()), 1000),
+            (
+                "hybrid_bitsliced_v1.pb",
+                HybridTritVec::Bitsliced(BitslicedTritVec::from_sparse(&small, 1000)),
+                1000,
+            ),
+            (
+                "hybrid_block_sparse_v1.pb",
+                HybridTritVec::BlockSparse(BlockSparseTritVec::from_sparse(&source, 200_000)),
+                200_000,
+            ),
+        ]
+    }
+
+    pub(super) fn regenerate(fsys: &EmbrFS) {
+        fs::write(fixture("engram_v1.pb"), wire::encode_engram(&fsys.engram).unwrap()).unwrap();
+        fs::write(fixture("manifest_v1.pb"), wire::encode_manifest(&fsys.manifest).unwrap()).unwrap();
+        for (name, vec, dim) in hybrids() {
+            fs::write(fixture(name), wire::encode_hybrid(&vec, dim).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn proto_fixtures_decode_and_reencode_identically() {
+        let engram_bytes = fs::read(fixture("engram_v1.pb")).unwrap();
+        let manifest_bytes = fs::read(fixture("manifest_v1.pb")).unwrap();
+        let engram = wire::decode_engram(&engram_bytes).unwrap();
+        let manifest = wire::decode_manifest(&manifest_bytes).unwrap();
+        assert_eq!(extract_fixture_text(&engram, &manifest), FIXTURE_TEXT);
+        assert_eq!(wire::encode_engram(&engram).unwrap(), engram_bytes);
+        assert_eq!(wire::encode_manifest(&manifest).unwrap(), manifest_bytes);
+
+        // The bincode fixture holds the same engram.
+        let bincode = EmbrFS::load_engram(fixture("engram_v1.bincode")).unwrap();
+        assert_eq!(wire::encode_engram(&bincode).unwrap(), engram_bytes);
+    }
+
+    #[test]
+    fn hybrid_fixtures_keep_their_representation() {
+        for (name, expected, dim) in hybrids() {
+            let bytes = fs::read(fixture(name)).unwrap();
+            let (vec, decoded_dim) = wire::decode_hybrid(&bytes).unwrap();
+            assert_eq!(decoded_dim, dim, "{name}");
+            assert_eq!(std::mem::discriminant(&vec), std::mem::discriminant(&expected), "{name}");
+            let (got, want) = (vec.to_sparse(), expected.to_sparse());
+            assert_eq!((got.pos, got.neg), (want.pos, want.neg), "{name}");
+            assert_eq!(wire::encode_hybrid(&vec, dim).unwrap(), bytes, "{name}");
+        }
+    }
+
+    #[test]
+    fn fields_from_newer_writers_are_skipped() {
+        // Field 99 (varint 42) and field 100 (bytes "new"), unknown to this schema.
+        let extra = [0x98, 0x06, 42, 0xa2, 0x06, 3, b'n', b'e', b'w'];
+        let mut engram_bytes = fs::read(fixture("engram_v1.pb")).unwrap();
+        let expected = wire::decode_engram(&engram_bytes).unwrap();
+        engram_bytes.extend_from_slice(&extra);
+        let engram = wire::decode_engram(&engram_bytes).unwrap();
+        assert_eq!(wire::encode_engram(&engram).unwrap(), wire::encode_engram(&expected).unwrap());
+
+        let mut hybrid_bytes = fs::read(fixture("hybrid_block_sparse_v1.pb")).unwrap();
+        hybrid_bytes.extend_from_slice(&extra);
+        let (vec, _) = wire::decode_hybrid(&hybrid_bytes).unwrap();
+        let (got, want) = (vec.to_sparse(), hybrid_source());
+        assert_eq!((got.pos, got.neg), (want.pos, want.neg));
+    }
+
+    #[test]
+    fn proto_engram_files_load_like_any_other() {
+        let td = tempfile::tempdir().unwrap();
+        let fsys = fixture_fs();
+        let path = td.path().join("fixture.engram");
+        fsys.save_engram_proto(&path, Default::default()).unwrap();
+        let info = embeddenator::info::StorageFormat::detect(&path).unwrap();
+        assert_eq!(info.container, "proto");
+        let loaded = EmbrFS::load_engram(&path).unwrap();
+        assert_eq!(extract_fixture_text(&loaded, &fsys.manifest), FIXTURE_TEXT);
+        let from_bytes =
+            EmbrFS::engram_from_bytes(&fs::read(&path).unwrap(), &Default::default()).unwrap();
+        assert_eq!(
+            wire::encode_engram(&from_bytes).unwrap(),
+            fs::read(fixture("engram_v1.pb")).unwrap()
+        );
+
+        let framed = embeddenator::BinaryWriteOptions {
+            checksum: embeddenator::ChecksumCodec::Xxh3,
+            ..Default::default()
+        };
+        fsys.save_engram_proto(&path, framed).unwrap();
+        assert!(embeddenator::info::StorageFormat::detect(&path).unwrap().checksummed);
+        let loaded = EmbrFS::load_engram(&path).unwrap();
+        assert_eq!(extract_fixture_text(&loaded, &fsys.manifest), FIXTURE_TEXT);
+    }
+
+    #[test]
+    fn decode_hybrid_rejects_inconsistent_vectors() {
+        use prost::Message;
+        let planes = |pos: Vec<u64>| wire::pb::HybridVec {
+            version: wire::WIRE_VERSION,
+            dim: 100,
+            repr: Some(wire::pb::Repr::Bitsliced(wire::pb::BitslicedVec {
+                neg: vec![0; pos.len()],
+                pos,
+            })),
+        };
+        for (msg, needle) in [
+            (planes(vec![0]), "needs 2"),
+            (planes(vec![0, 1 << 40]), "past dimension"),
+            (
+                wire::pb::HybridVec {
+                    version: wire::WIRE_VERSION,
+                    dim: 100,
+                    repr: Some(wire::pb::Repr::Sparse(wire::pb::SparseVec { pos: vec![100], neg: vec![] })),
+                },
+                "out of range",
+            ),
+        ] {
+            let err = wire::decode_hybrid(&msg.encode_to_vec()).expect_err("accepted");
+            assert!(err.to_string().contains(needle), "{needle}: {err}");
+        }
+    }
+}
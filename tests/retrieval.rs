@@ -5,3 +5,21 @@ mod retrieval_index;
 
 #[path = "retrieval/query_shift_sweep.rs"]
 mod query_shift_sweep;
+
+#[path = "retrieval/query_cache.rs"]
+mod query_cache;
+
+#[path = "retrieval/chunk_vectors.rs"]
+mod chunk_vectors;
+
+#[path = "retrieval/eval.rs"]
+mod eval;
+
+#[path = "retrieval/file_signatures.rs"]
+mod file_signatures;
+
+#[path = "retrieval/federation.rs"]
+mod federation;
+
+#[path = "retrieval/similarity_encoding.rs"]
+mod similarity_encoding;
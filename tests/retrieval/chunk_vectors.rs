@@ -0,0 +1,51 @@
+use embeddenator::chunk_vectors::default_vectors_path;
+use embeddenator::envelope::{BinaryWriteOptions, PayloadKind};
+use embeddenator::{ChunkVectors, EmbrFS, ReversibleVSAConfig};
+
+#[test]
+fn chunk_vectors_round_trip_and_answer_queries_like_the_engram() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    for f in 0..4u32 {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i.wrapping_mul(2_654_435_761 + f) >> 9) as u8).collect();
+        fs.ingest_bytes(&data, format!("f{f}.bin"), &config).unwrap();
+    }
+    let vectors = ChunkVectors::from_engram(&fs.engram);
+    assert_eq!(vectors.len(), fs.engram.codebook.len());
+
+    // The coded form beats both bincode of the codebook and the older
+    // varint gaps, and both decode exactly.
+    let encoded = vectors.encode();
+    let varint = vectors.encode_varint();
+    let raw = bincode::serialize(&fs.engram.codebook).unwrap();
+    assert!(encoded.len() * 4 < raw.len());
+    assert!(encoded.len() < varint.len());
+    assert_eq!(ChunkVectors::decode(&encoded).unwrap(), vectors);
+    assert_eq!(ChunkVectors::decode(&varint).unwrap(), vectors);
+    assert!(ChunkVectors::decode(&encoded[..encoded.len() - 1]).is_err());
+    assert!(ChunkVectors::decode(&varint[..varint.len() - 1]).is_err());
+
+    let td = tempfile::tempdir().unwrap();
+    let path = default_vectors_path(td.path().join("root.engram"));
+    assert!(path.ends_with("root.engram.vectors"));
+    vectors.save(&path, BinaryWriteOptions::default()).unwrap();
+    assert_eq!(PayloadKind::sniff_file(&path).unwrap(), Some(PayloadKind::ChunkVectors));
+    let loaded = ChunkVectors::load(&path).unwrap();
+    assert_eq!(loaded, vectors);
+    let index = ChunkVectors::load_index(&path).unwrap();
+    let probe = fs.engram.codebook[&3].clone();
+    assert_eq!(index.query_top_k(&probe, 8), loaded.build_index().query_top_k(&probe, 8));
+
+    // Queries over the loaded vectors match queries over the engram.
+    let query = fs.engram.codebook[&5].clone();
+    let index = loaded.build_index();
+    let hits = loaded.query(&index, &query, 50, 5);
+    assert_eq!(hits[0].id, 5);
+    let expected = fs.engram.query_codebook_with_index(&fs.engram.build_codebook_index(), &query, 50, 5);
+    let ids = |h: &[embeddenator::RerankedResult]| h.iter().map(|r| r.id).collect::<Vec<_>>();
+    assert_eq!(ids(&hits), ids(&expected));
+
+    // Other envelopes are refused.
+    fs.save_engram(td.path().join("root.engram")).unwrap();
+    assert!(ChunkVectors::load(td.path().join("root.engram")).is_err());
+}
@@ -0,0 +1,133 @@
+//! Retrieval quality metrics over labeled query sets.
+
+use embeddenator::retrieval::eval::{
+    evaluate, precision_at_k, recall_at_k, reciprocal_rank, EvalOptions, QuerySet, Stage,
+};
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use std::collections::HashSet;
+
+/// Words no two files share, so each file is its own best match.
+fn text(word: &str) -> String {
+    (0..200).map(|i| format!("{word}{} ", (i * 7) % 97)).collect()
+}
+
+fn corpus() -> EmbrFS {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    for word in ["amber", "basalt", "cobalt", "dolomite", "emerald"] {
+        fs.ingest_bytes(text(word).as_bytes(), format!("{word}.txt"), &config).unwrap();
+    }
+    fs
+}
+
+#[test]
+fn metrics_follow_their_definitions() {
+    let relevant: HashSet<u32> = [2, 5, 9].into_iter().collect();
+    let ranked = [7, 2, 4, 5];
+    assert_eq!(recall_at_k(&ranked, &relevant, 1), 0.0);
+    assert!((recall_at_k(&ranked, &relevant, 4) - 2.0 / 3.0).abs() < 1e-12);
+    assert_eq!(precision_at_k(&ranked, &relevant, 2), 0.5);
+    // A short ranking leaves empty slots, which count against precision.
+    assert_eq!(precision_at_k(&ranked, &relevant, 8), 0.25);
+    assert_eq!(reciprocal_rank(&ranked, &relevant), 0.5);
+    assert_eq!(reciprocal_rank(&[1, 3], &relevant), 0.0);
+    assert_eq!(recall_at_k(&ranked, &HashSet::new(), 4), 0.0);
+}
+
+#[test]
+fn query_sets_reject_ambiguous_lines() {
+    let set = QuerySet::parse(
+        "# labeled by hand\n\n{\"text\": \"amber1\", \"relevant\": [\"amber.txt\"]}\n{\"id\": \"c\", \"file\": \"q.bin\", \"relevant_chunks\": [3]}\n",
+    )
+    .unwrap();
+    assert_eq!(set.queries.len(), 2);
+    assert_eq!(set.queries[0].id, "3", "unnamed queries take their line number");
+    assert_eq!(set.queries[1].relevant_chunks, [3]);
+
+    for bad in [
+        "{\"relevant\": [\"a\"]}",
+        "{\"text\": \"x\", \"file\": \"q\", \"relevant\": [\"a\"]}",
+        "{\"text\": \"x\"}",
+        "{\"text\": \"x\", \"relevant\": [\"a\"], \"relevant_chunks\": [1]}",
+        "{\"text\": ",
+    ] {
+        assert!(QuerySet::parse(bad).is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn stages_score_labeled_queries_and_compare_with_a_baseline() {
+    let fs = corpus();
+    let emerald = fs.manifest.files.iter().find(|f| f.path == "emerald.txt").unwrap();
+    let line = |id: &str, word: &str, label: String| {
+        format!("{{\"id\": \"{id}\", \"text\": \"{}\", {label}}}\n", &text(word)[..240])
+    };
+    let set = QuerySet::parse(&[
+        line("b", "basalt", r#""relevant": ["basalt.txt"]"#.into()),
+        line("d", "dolomite", r#""relevant": ["dolomite.txt", "amber.txt"]"#.into()),
+        line("e", "emerald", format!(r#""relevant_chunks": [{}]"#, emerald.chunks[0])),
+    ]
+    .concat())
+    .unwrap();
+
+    let options = EvalOptions {
+        cutoffs: vec![1, 3],
+        ..EvalOptions::default()
+    };
+    let report = evaluate(&fs.engram, &fs.manifest, &set, &options).unwrap();
+    assert_eq!(report.queries, 3);
+    assert_eq!(report.stages.iter().map(|s| s.stage).collect::<Vec<_>>(), Stage::ALL);
+    for stage in &report.stages {
+        assert_eq!(stage.queries.len(), 3);
+        let b = &stage.queries[0];
+        assert_eq!((b.first_relevant, b.found, b.relevant), (Some(1), 1, 1), "{:?}", stage.stage);
+        // "d" labels a file the query does not resemble, so half is found at best.
+        assert_eq!(stage.queries[1].relevant, 2);
+        let at1 = stage.at(1).unwrap();
+        assert!(at1.precision >= 2.0 / 3.0 - 1e-12, "{:?}: {:?}", stage.stage, at1);
+        assert!(at1.recall < 1.0 && stage.queries[1].found >= 1);
+        assert!(stage.mrr > 0.0 && stage.mrr <= 1.0);
+    }
+    let exhaustive = report.stage(Stage::Exhaustive).unwrap();
+    let reranked = report.stage(Stage::Reranked).unwrap();
+    assert!(reranked.mrr <= exhaustive.mrr + 1e-12);
+
+    // Comparing a report with itself changes nothing; a narrower run only
+    // compares the metrics both have.
+    let same = report.compare(&report);
+    assert_eq!(same.len(), 3 * (2 * 2 + 1));
+    assert!(same.iter().all(|c| c.delta == 0.0));
+    let narrow = evaluate(
+        &fs.engram,
+        &fs.manifest,
+        &set,
+        &EvalOptions {
+            cutoffs: vec![1],
+            stages: vec![Stage::Index],
+            candidate_k: Some(8),
+        },
+    )
+    .unwrap();
+    let names: Vec<String> = narrow.compare(&report).into_iter().map(|c| c.name).collect();
+    assert_eq!(names, ["index/recall@1", "index/precision@1"]);
+
+    // Timings may not survive the JSON round trip to the last bit; metrics do.
+    let json = serde_json::to_string(&report).unwrap();
+    let back = serde_json::from_str::<embeddenator::EvalReport>(&json).unwrap();
+    assert_eq!(back.queries, report.queries);
+    assert_eq!(back.compare(&report).len(), same.len());
+    assert!(back.compare(&report).iter().all(|c| c.delta == 0.0));
+}
+
+#[test]
+fn labels_naming_unknown_files_are_errors() {
+    let fs = corpus();
+    let set = QuerySet::parse("{\"id\": \"typo\", \"text\": \"amber1\", \"relevant\": [\"ambr.txt\"]}").unwrap();
+    let err = evaluate(&fs.engram, &fs.manifest, &set, &EvalOptions::default()).unwrap_err();
+    assert!(err.to_string().contains("ambr.txt"), "{err}");
+    let none = EvalOptions {
+        cutoffs: vec![0],
+        ..EvalOptions::default()
+    };
+    assert!(evaluate(&fs.engram, &fs.manifest, &QuerySet::default(), &none).is_err());
+}
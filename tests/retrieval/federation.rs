@@ -0,0 +1,48 @@
+//! Tests for federated search across engrams.
+
+use embeddenator::ReversibleVSAConfig;
+
+#[test]
+fn federated_index_ranks_across_engrams_with_provenance() {
+    use embeddenator::{EmbrFS, EngramReader, FederatedIndex, ScoreNormalization};
+    use std::sync::Arc;
+
+    let config = ReversibleVSAConfig::default();
+    let engram = |seed: u8, names: &[&str]| {
+        let mut fs = EmbrFS::new();
+        for (i, name) in names.iter().enumerate() {
+            let data: Vec<u8> = (0..8192u32).map(|j| (j as u8).wrapping_mul(seed + i as u8) ^ (j >> 8) as u8 ^ seed).collect();
+            fs.ingest_bytes(&data, name.to_string(), &config).unwrap();
+        }
+        fs
+    };
+    let a = engram(3, &["a/one.bin", "a/two.bin", "a/three.bin"]);
+    let b = engram(101, &["b/only.bin", "b/other.bin", "b/more.bin"]);
+    let probe = b.engram.codebook[&b.manifest.files[0].chunks[1]].clone();
+    let probe_id = b.manifest.files[0].chunks[1];
+    let vectors = a.engram.codebook.clone();
+    let index = a.engram.build_codebook_index();
+
+    let mut federation = FederatedIndex::new();
+    federation.add_engram("a", Arc::new(EngramReader::from_embrfs(a, config.clone()))).unwrap();
+    federation.add_engram("b", Arc::new(EngramReader::from_embrfs(b, config.clone()))).unwrap();
+    federation.add_index("a-index", index, vectors).unwrap();
+    assert!(federation.add_index("a", Default::default(), Default::default()).is_err());
+    assert_eq!(federation.sources().collect::<Vec<_>>(), ["a", "b", "a-index"]);
+
+    let hits = federation.query_vector(&probe, 4);
+    assert_eq!(hits.len(), 4);
+    assert_eq!((hits[0].source.as_str(), hits[0].chunk), ("b", probe_id));
+    assert_eq!(hits[0].paths, ["b/only.bin"]);
+    assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+    assert!(hits.iter().filter(|h| h.source == "a-index").all(|h| h.paths.is_empty()));
+
+    // Min-max puts every source's best hit at 1; weights then decide.
+    let mut federation = federation.with_normalization(ScoreNormalization::MinMax);
+    federation.set_weight("b", 0.5).unwrap();
+    let hits = federation.query_vector(&probe, 2);
+    assert!(hits.iter().all(|h| h.source != "b" && (h.score - 1.0).abs() < 1e-9));
+    assert!(federation.set_weight("missing", 1.0).is_err());
+    assert!(federation.remove("a-index"));
+    assert_eq!(federation.query_vector(&probe, 0), []);
+}
@@ -0,0 +1,101 @@
+//! Tests for per-file signatures: ranking and near-duplicate search.
+
+use embeddenator::{ReversibleVSAConfig, SparseVec};
+
+#[test]
+fn file_signatures_rank_the_source_file_first() {
+    use embeddenator::embrfs::{EmbrFS, FileSignatures};
+    use std::fs;
+    use tempfile::tempdir;
+
+    let tmp = tempdir().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    let file = |seed: u64, len: usize| -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (x >> 56) as u8
+            })
+            .collect()
+    };
+    let target = file(3, 9000);
+    fs::write(src.join("one.bin"), file(1, 6000)).unwrap();
+    fs::write(src.join("two.bin"), file(2, 6000)).unwrap();
+    fs::write(src.join("sub").join("target.bin"), &target).unwrap();
+    fs::write(src.join("empty.bin"), b"").unwrap();
+
+    let config = ReversibleVSAConfig::default();
+    let mut embrfs = EmbrFS::new();
+    embrfs.ingest_directory(&src, false, &config).unwrap();
+
+    let signatures = FileSignatures::build(&embrfs.engram, &embrfs.manifest);
+    assert_eq!(signatures.len(), 3, "empty files have no signature");
+
+    let query = SparseVec::encode_data(&target[..4096], &config, None);
+    let hits = signatures.query_any_shift(&query, &config, 2);
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].path, "sub/target.bin");
+    assert_eq!(hits[0].chunks, 3);
+    assert!(hits[0].cosine > hits[1].cosine, "{hits:?}");
+
+    let only_top = FileSignatures::build_filtered(&embrfs.engram, &embrfs.manifest, |f| !f.path.contains('/'));
+    assert_eq!(only_top.len(), 2);
+    assert!(only_top
+        .query_any_shift(&query, &config, 5)
+        .iter()
+        .all(|h| h.path != "sub/target.bin"));
+}
+
+#[test]
+fn file_signatures_find_near_duplicates() {
+    use embeddenator::{EmbrFS, ReversibleVSAConfig};
+
+    let config = ReversibleVSAConfig::default();
+    let report: Vec<u8> = (0..12_000u32).map(|i| b"quarterly numbers, "[(i % 19) as usize]).collect();
+    let mut revised = report.clone();
+    revised[5000..5040].copy_from_slice(&[b'#'; 40]);
+    let other: Vec<u8> = (0..12_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+
+    let mut fs = EmbrFS::new();
+    fs.file_signatures = true;
+    fs.ingest_bytes(&report, "docs/report.txt".into(), &config).unwrap();
+    fs.ingest_bytes(&revised, "archive/2024/report-v2.txt".into(), &config).unwrap();
+    fs.ingest_bytes(&other, "noise.bin".into(), &config).unwrap();
+    assert!(fs.manifest.files.iter().all(|f| f.signature.is_some()));
+
+    // Signatures do not depend on the path: a near copy elsewhere ranks first.
+    let hits = fs.similar_files("docs/report.txt", 2);
+    assert_eq!(hits[0].0, "archive/2024/report-v2.txt");
+    assert!(hits[0].1 > 0.9 && hits[1].1 < 0.5, "{hits:?}");
+    let pairs = fs.near_duplicate_files(0.9);
+    assert_eq!(pairs.len(), 1);
+    assert_eq!((pairs[0].0.as_str(), pairs[0].1.as_str()), ("docs/report.txt", "archive/2024/report-v2.txt"));
+    assert!(fs.similar_files("missing.txt", 3).is_empty());
+
+    // Recorded signatures match the ones bundled on demand for old manifests,
+    // and file ranking uses them without touching the codebook.
+    for file in &fs.manifest.files {
+        let legacy = embeddenator::FileEntry { signature: None, ..file.clone() };
+        assert_eq!(EmbrFS::file_signature(&fs.engram, &legacy), file.signature);
+    }
+    let recorded = embeddenator::FileSignatures::build_with(&fs.manifest, |_| panic!("chunk fetched")).unwrap();
+    let rebuilt = embeddenator::FileSignatures::build(&fs.engram, &embeddenator::Manifest {
+        files: fs.manifest.files.iter().map(|f| embeddenator::FileEntry { signature: None, ..f.clone() }).collect(),
+        ..fs.manifest.clone()
+    });
+    let query = fs.engram.codebook[&0].clone();
+    assert_eq!(recorded.query(&query, 10, 3), rebuilt.query(&query, 10, 3));
+
+    // And they survive the manifest round trip.
+    let td = tempfile::tempdir().unwrap();
+    fs.save_manifest(td.path().join("manifest.json")).unwrap();
+    let loaded = EmbrFS::load_manifest(td.path().join("manifest.json")).unwrap();
+    assert_eq!(loaded.files, fs.manifest.files);
+    #[cfg(feature = "proto")]
+    {
+        let bytes = embeddenator::wire::encode_manifest(&fs.manifest).unwrap();
+        assert_eq!(embeddenator::wire::decode_manifest(&bytes).unwrap().files, fs.manifest.files);
+    }
+}
@@ -0,0 +1,83 @@
+use embeddenator::{EmbrFS, EngramReader, QueryCache, ReversibleVSAConfig, SparseVec, TernaryInvertedIndex};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn vectors() -> HashMap<usize, SparseVec> {
+    let config = ReversibleVSAConfig::default();
+    (0..20)
+        .map(|i| (i, SparseVec::encode_data(format!("item-{i}").as_bytes(), &config, None)))
+        .collect()
+}
+
+#[test]
+fn repeated_queries_are_served_from_the_cache() {
+    let vectors = vectors();
+    let index = TernaryInvertedIndex::build_from_map(&vectors);
+    let cache = QueryCache::new(16);
+    let query = &vectors[&3];
+
+    let first = cache.top_k_reranked(&index, query, &vectors, 10, 5);
+    assert_eq!(first.to_vec(), index.query_top_k_reranked(query, &vectors, 10, 5));
+    let misses = cache.stats().misses;
+    let again = cache.top_k_reranked(&index, query, &vectors, 10, 5);
+    assert!(Arc::ptr_eq(&first, &again));
+    assert_eq!(cache.stats().misses, misses);
+
+    // Another k over the same candidate budget reuses the candidate set.
+    let hits = cache.stats().hits;
+    let top3 = cache.top_k_reranked(&index, query, &vectors, 10, 3);
+    assert_eq!(top3[..], first[..3]);
+    assert_eq!(cache.stats().hits, hits + 1);
+}
+
+#[test]
+fn index_updates_and_invalidate_drop_cached_answers() {
+    let mut vectors = vectors();
+    let mut index = TernaryInvertedIndex::build_from_map(&vectors);
+    let cache = QueryCache::new(16);
+    let query = vectors[&3].clone();
+    assert_eq!(cache.top_k(&index, &query, 5)[0].id, 3);
+
+    // A copy of the query added under id 100 ties with id 3.
+    vectors.insert(100, query.clone());
+    index.add(100, &query);
+    index.finalize();
+    let hits = cache.top_k(&index, &query, 5);
+    assert!(hits.iter().any(|h| h.id == 100), "{hits:?}");
+    assert_eq!(cache.stats().invalidations, 1);
+
+    cache.invalidate();
+    assert!(cache.is_empty());
+    assert_eq!(cache.stats().invalidations, 2);
+}
+
+#[test]
+fn cache_evicts_least_recently_used() {
+    let vectors = vectors();
+    let index = TernaryInvertedIndex::build_from_map(&vectors);
+    let cache = QueryCache::new(2);
+    cache.top_k(&index, &vectors[&0], 3);
+    cache.top_k(&index, &vectors[&1], 3);
+    cache.top_k(&index, &vectors[&0], 3);
+    cache.top_k(&index, &vectors[&2], 3);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.stats().evictions, 1);
+    let hits = cache.stats().hits;
+    cache.top_k(&index, &vectors[&0], 3);
+    assert_eq!(cache.stats().hits, hits + 1);
+}
+
+#[test]
+fn reader_caches_similar_chunk_queries() {
+    let config = ReversibleVSAConfig::default();
+    let mut fs = EmbrFS::new();
+    fs.ingest_bytes(b"the quick brown fox jumps over the lazy dog", "a.txt".into(), &config).unwrap();
+    fs.ingest_bytes(b"pack my box with five dozen liquor jugs", "b.txt".into(), &config).unwrap();
+    let reader = EngramReader::from_embrfs(fs, config);
+
+    let first = reader.similar_chunks(b"the quick brown fox", 2);
+    assert_eq!(reader.query_cache().stats().hits, 0);
+    assert_eq!(reader.similar_chunks(b"the quick brown fox", 2), first);
+    assert_eq!(reader.query_cache().stats().hits, 1);
+    assert_eq!(first[0].2, vec!["a.txt".to_string()]);
+}
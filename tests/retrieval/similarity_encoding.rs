@@ -0,0 +1,58 @@
+//! Tests for similarity-preserving chunk encoding.
+
+use embeddenator::{ReversibleVSAConfig, SparseVec};
+
+#[test]
+fn similarity_chunk_encoding_tracks_near_duplicates() {
+    use embeddenator::dimensional::DimensionalConfig;
+    use embeddenator::{ChunkEncoding, EmbrFS, DEFAULT_CHUNK_SIZE};
+
+    let text: Vec<u8> = (0..3000u32)
+        .map(|i| b"abcdefghijklmnopqrstuvwxyz ,."[(i.wrapping_mul(2_654_435_761) >> 27) as usize % 29])
+        .collect();
+    let mut edited = text.clone();
+    edited.insert(1500, b'!');
+    edited[200] = b'#';
+    let other: Vec<u8> = text.iter().rev().map(|b| b.wrapping_add(1)).collect();
+
+    // One inserted byte leaves the signatures close; reversible vectors of
+    // the same pair share almost nothing after the insertion point.
+    let sig = |data: &[u8]| SparseVec::from_data_similarity(data, 4, 200);
+    assert!(sig(&text).cosine(&sig(&edited)) > 0.7);
+    assert!(sig(&text).cosine(&sig(&other)) < 0.2);
+    let reversible = ReversibleVSAConfig::default();
+    let (a, b) = (
+        SparseVec::encode_data(&text[1024..2048], &reversible, None),
+        SparseVec::encode_data(&edited[1024..2048], &reversible, None),
+    );
+    assert!(a.cosine(&b) < sig(&text[1024..2048]).cosine(&sig(&edited[1024..2048])));
+
+    let config = ReversibleVSAConfig { chunk_encoding: ChunkEncoding::similarity(), ..Default::default() };
+    let mut fs = EmbrFS::with_config(config.clone(), DimensionalConfig::default()).unwrap();
+    fs.ingest_bytes(&text, "text.txt".into(), &config).unwrap();
+    fs.ingest_bytes(&other, "other.txt".into(), &config).unwrap();
+    assert_eq!(fs.vsa_config().chunk_encoding, ChunkEncoding::similarity());
+
+    // Extraction stays exact: the bytes come from corrections.
+    for file in &fs.manifest.files {
+        let expected = if file.path == "text.txt" { &text } else { &other };
+        assert_eq!(&EmbrFS::reconstruct_bytes(&fs.engram, file, &config, DEFAULT_CHUNK_SIZE).unwrap(), expected);
+    }
+
+    // The edited first chunk finds the original chunk in the codebook.
+    let probe = SparseVec::encode_chunk(&edited[..1024], &config, None);
+    let best = fs
+        .engram
+        .codebook
+        .iter()
+        .max_by(|a, b| probe.cosine(a.1).total_cmp(&probe.cosine(b.1)))
+        .map(|(&id, _)| id)
+        .unwrap();
+    assert_eq!(best, fs.manifest.files[0].chunks[0]);
+
+    let bad = ReversibleVSAConfig {
+        chunk_encoding: ChunkEncoding::Similarity { shingle: 0, nonzero: 10 },
+        ..Default::default()
+    };
+    assert!(EmbrFS::with_config(bad, DimensionalConfig::default()).is_err());
+}
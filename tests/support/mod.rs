@@ -1 +1,21 @@
+//! Fixtures shared by the integration test crates.
+
+use embeddenator::{EmbrFS, ReversibleVSAConfig};
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "bt-phase-1")]
 pub mod bt_migration;
+
+/// Ingest a small tree written under `root/in`: a text file `docs/a.txt`
+/// and a binary file `b.bin`, each spanning several chunks.
+pub fn ingested(root: &Path) -> EmbrFS {
+    let input = root.join("in");
+    fs::create_dir_all(input.join("docs")).unwrap();
+    fs::write(input.join("docs/a.txt"), "ingested text ".repeat(1000)).unwrap();
+    fs::write(input.join("b.bin"), (0..20_000u32).map(|i| (i * 31 % 251) as u8).collect::<Vec<_>>()).unwrap();
+    let mut fsys = EmbrFS::new();
+    fsys.ingest_directory(&input, false, &ReversibleVSAConfig::default())
+        .unwrap();
+    fsys
+}